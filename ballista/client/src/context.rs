@@ -17,18 +17,27 @@
 
 //! Distributed execution context.
 
-use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::datatypes::{DataType, Fields, Schema, SchemaRef};
 use datafusion::execution::context::DataFilePaths;
 use log::info;
 use parking_lot::Mutex;
 use sqlparser::ast::Statement;
 use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::{Arc, Weak};
 
-use ballista_core::config::BallistaConfig;
+use ballista_core::admin_statement::{AdminStatement, AdminStatementNode};
+use ballista_core::config::{
+    BallistaConfig, BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, DATAFUSION_CONFIG_PREFIX,
+};
+use ballista_core::error::BallistaError;
+use ballista_core::protocol::handshake;
 use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
-use ballista_core::serde::protobuf::{CreateSessionParams, KeyValuePair};
+use ballista_core::serde::protobuf::{
+    CreateSessionParams, KeyValuePair, RemoveSessionParams,
+};
+use ballista_core::temporary_table::TemporaryTable;
 use ballista_core::utils::{
     create_df_ctx_with_ballista_query_planner, create_grpc_client_connection,
 };
@@ -39,13 +48,18 @@ use datafusion::dataframe::DataFrame;
 use datafusion::datasource::{source_as_provider, TableProvider};
 use datafusion::error::{DataFusionError, Result};
 use datafusion::logical_expr::{
-    CreateExternalTable, DdlStatement, LogicalPlan, TableScan,
+    CreateExternalTable, CreateMemoryTable, DdlStatement, Extension, LogicalPlan,
+    LogicalPlanBuilder, SetVariable, Statement as LogicalStatement, TableScan,
 };
 use datafusion::prelude::{
     AvroReadOptions, CsvReadOptions, NdJsonReadOptions, ParquetReadOptions,
     SessionConfig, SessionContext,
 };
 use datafusion::sql::parser::{DFParser, Statement as DFStatement};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::parser::ParserError;
+use datafusion::sql::sqlparser::tokenizer::{Token, Tokenizer};
+use tonic::transport::Channel;
 
 struct BallistaContextState {
     /// Ballista configuration
@@ -77,9 +91,21 @@ impl BallistaContextState {
     }
 }
 
+/// Named sessions created over the connection of a context, owned by the context. Its
+/// sessions hold a weak reference to them, as they would otherwise keep themselves alive.
+type SessionPool = Mutex<HashMap<String, Arc<BallistaContext>>>;
+
 pub struct BallistaContext {
     state: Arc<Mutex<BallistaContextState>>,
     context: Arc<SessionContext>,
+    /// Client of the scheduler, whose channel is shared with the sessions of the context
+    scheduler: SchedulerGrpcClient<Channel>,
+    /// Id of the scheduler side session of this context
+    session_id: String,
+    /// The named sessions of this context, or of the context this session was created by
+    sessions: Weak<SessionPool>,
+    /// The named sessions of this context, `None` for a named session of another context
+    _owned_sessions: Option<Arc<SessionPool>>,
 }
 
 impl BallistaContext {
@@ -104,42 +130,57 @@ impl BallistaContext {
         let mut scheduler = SchedulerGrpcClient::new(connection)
             .max_encoding_message_size(limit)
             .max_decoding_message_size(limit);
-
-        let remote_session_id = scheduler
-            .create_session(CreateSessionParams {
-                settings: config
-                    .settings()
-                    .iter()
-                    .map(|(k, v)| KeyValuePair {
-                        key: k.to_owned(),
-                        value: v.to_owned(),
-                    })
-                    .collect::<Vec<_>>(),
-            })
+        handshake(&mut scheduler)
             .await
-            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
-            .into_inner()
-            .session_id;
+            .map_err(|e| DataFusionError::Execution(format!("{e}")))?;
 
-        info!(
-            "Server side SessionContext created with session id: {}",
-            remote_session_id
-        );
+        let remote_session_id = create_remote_session(&mut scheduler, config).await?;
 
         let ctx = {
             create_df_ctx_with_ballista_query_planner::<LogicalPlanNode>(
                 scheduler_url,
-                remote_session_id,
+                remote_session_id.clone(),
                 state.config(),
             )
         };
 
+        let sessions = Arc::new(SessionPool::default());
         Ok(Self {
             state: Arc::new(Mutex::new(state)),
             context: Arc::new(ctx),
+            scheduler,
+            session_id: remote_session_id,
+            sessions: Arc::downgrade(&sessions),
+            _owned_sessions: Some(sessions),
         })
     }
 
+    /// Create a context for executing queries against the Ballista scheduler whose
+    /// `host:port` address was written to a discovery file, e.g. by a standalone context
+    /// configured with `ballista.standalone.discovery_file`
+    pub async fn remote_from_discovery_file(
+        path: impl AsRef<Path>,
+        config: &BallistaConfig,
+    ) -> ballista_core::error::Result<Self> {
+        let path = path.as_ref();
+        let address = std::fs::read_to_string(path)?;
+        let (host, port) = address
+            .trim()
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+            .ok_or_else(|| {
+                BallistaError::General(format!(
+                    "Invalid scheduler address in discovery file {}: {address}",
+                    path.display()
+                ))
+            })?;
+        Self::remote(host, port, config).await
+    }
+
+    /// Create a context for executing queries against an in-process scheduler and
+    /// executor. The scheduler listens on a port assigned by the OS, see
+    /// [`BallistaContext::scheduler_port`], so that several standalone contexts can run on the
+    /// same machine.
     #[cfg(feature = "standalone")]
     pub async fn standalone(
         config: &BallistaConfig,
@@ -162,31 +203,12 @@ impl BallistaContext {
             }
         };
 
-        let remote_session_id = scheduler
-            .create_session(CreateSessionParams {
-                settings: config
-                    .settings()
-                    .iter()
-                    .map(|(k, v)| KeyValuePair {
-                        key: k.to_owned(),
-                        value: v.to_owned(),
-                    })
-                    .collect::<Vec<_>>(),
-            })
-            .await
-            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
-            .into_inner()
-            .session_id;
-
-        info!(
-            "Server side SessionContext created with session id: {}",
-            remote_session_id
-        );
+        let remote_session_id = create_remote_session(&mut scheduler, config).await?;
 
         let ctx = {
             create_df_ctx_with_ballista_query_planner::<LogicalPlanNode>(
                 scheduler_url,
-                remote_session_id,
+                remote_session_id.clone(),
                 config,
             )
         };
@@ -195,21 +217,148 @@ impl BallistaContext {
             BallistaCodec::default();
 
         ballista_executor::new_standalone_executor(
-            scheduler,
+            scheduler.clone(),
             concurrent_tasks,
             default_codec,
         )
         .await?;
 
+        if let Some(path) = config.standalone_discovery_file() {
+            std::fs::write(&path, format!("localhost:{}\n", addr.port()))?;
+            info!("Wrote the address of the in-proc scheduler to {path}");
+        }
+
         let state =
             BallistaContextState::new("localhost".to_string(), addr.port(), config);
 
+        let sessions = Arc::new(SessionPool::default());
         Ok(Self {
             state: Arc::new(Mutex::new(state)),
             context: Arc::new(ctx),
+            scheduler,
+            session_id: remote_session_id,
+            sessions: Arc::downgrade(&sessions),
+            _owned_sessions: Some(sessions),
+        })
+    }
+
+    /// The named sessions of the context, unless it was dropped
+    fn sessions(&self) -> ballista_core::error::Result<Arc<SessionPool>> {
+        self.sessions.upgrade().ok_or_else(|| {
+            BallistaError::General(
+                "The context of the session was dropped along with its sessions"
+                    .to_string(),
+            )
         })
     }
 
+    /// Create a named session with its own configuration and tables, over the connection
+    /// of this context to the scheduler. The session can be retrieved by its name from this
+    /// context or any of its sessions, until it is closed.
+    pub async fn create_session(
+        &self,
+        name: &str,
+        config: &BallistaConfig,
+    ) -> ballista_core::error::Result<Arc<BallistaContext>> {
+        let sessions = self.sessions()?;
+        if sessions.lock().contains_key(name) {
+            return Err(BallistaError::General(format!(
+                "Session {name} already exists"
+            )));
+        }
+
+        let mut scheduler = self.scheduler.clone();
+        let remote_session_id = create_remote_session(&mut scheduler, config).await?;
+
+        let (scheduler_host, scheduler_port) = {
+            let state = self.state.lock();
+            (state.scheduler_host.clone(), state.scheduler_port)
+        };
+        let ctx = create_df_ctx_with_ballista_query_planner::<LogicalPlanNode>(
+            format!("http://{scheduler_host}:{scheduler_port}"),
+            remote_session_id.clone(),
+            config,
+        );
+        let session = Arc::new(Self {
+            state: Arc::new(Mutex::new(BallistaContextState::new(
+                scheduler_host,
+                scheduler_port,
+                config,
+            ))),
+            context: Arc::new(ctx),
+            scheduler,
+            session_id: remote_session_id,
+            _owned_sessions: None,
+            sessions: self.sessions.clone(),
+        });
+
+        let exists = {
+            let mut sessions = sessions.lock();
+            let exists = sessions.contains_key(name);
+            if !exists {
+                sessions.insert(name.to_owned(), session.clone());
+            }
+            exists
+        };
+        if exists {
+            // a session with the same name was created concurrently
+            remove_remote_session(&mut self.scheduler.clone(), &session.session_id)
+                .await?;
+            return Err(BallistaError::General(format!(
+                "Session {name} already exists"
+            )));
+        }
+        Ok(session)
+    }
+
+    /// Get a named session created with [`BallistaContext::create_session`]
+    pub fn session(&self, name: &str) -> Option<Arc<BallistaContext>> {
+        self.sessions.upgrade()?.lock().get(name).cloned()
+    }
+
+    /// Names of the open sessions created with [`BallistaContext::create_session`]
+    pub fn session_names(&self) -> Vec<String> {
+        let Some(sessions) = self.sessions.upgrade() else {
+            return vec![];
+        };
+        let mut names: Vec<String> = sessions.lock().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Close a named session, removing it and its temporary tables from the scheduler.
+    /// Returns whether the session was open.
+    pub async fn close_session(&self, name: &str) -> ballista_core::error::Result<bool> {
+        let Some(session) = self
+            .sessions
+            .upgrade()
+            .and_then(|sessions| sessions.lock().remove(name))
+        else {
+            return Ok(false);
+        };
+        remove_remote_session(&mut self.scheduler.clone(), &session.session_id).await?;
+        info!(
+            "Closed session {} with session id {}",
+            name, session.session_id
+        );
+        Ok(true)
+    }
+
+    /// Id of the scheduler side session of this context
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Host of the scheduler of this context
+    pub fn scheduler_host(&self) -> String {
+        self.state.lock().scheduler_host.clone()
+    }
+
+    /// Port of the scheduler of this context, assigned by the OS for standalone contexts
+    pub fn scheduler_port(&self) -> u16 {
+        self.state.lock().scheduler_port
+    }
+
     /// Create a DataFrame representing an Json table scan
     pub async fn read_json<P: DataFilePaths>(
         &self,
@@ -345,6 +494,22 @@ impl BallistaContext {
         Ok(is_show_variable)
     }
 
+    /// is a `CREATE TEMPORARY TABLE ... AS SELECT` sql
+    fn is_create_temporary_table(sql: &str) -> Result<bool> {
+        let statements = DFParser::parse_sql(sql)?;
+        Ok(match statements.front() {
+            Some(DFStatement::Statement(st)) => matches!(
+                **st,
+                Statement::CreateTable {
+                    temporary: true,
+                    query: Some(_),
+                    ..
+                }
+            ),
+            _ => false,
+        })
+    }
+
     pub fn context(&self) -> &SessionContext {
         &self.context
     }
@@ -353,9 +518,41 @@ impl BallistaContext {
     ///
     /// This method is `async` because queries of type `CREATE EXTERNAL TABLE`
     /// might require the schema to be inferred.
+    ///
+    /// The administrative statements `SHOW JOBS`, `SHOW EXECUTORS` and
+    /// `KILL JOB '<id>'` are executed by the scheduler.
+    ///
+    /// The `SET` statements, e.g. `SET ballista.shuffle.partitions = 32`, change the
+    /// settings of the scheduler side session for the next queries of the context.
+    ///
+    /// Several statements separated by semicolons are executed in order, and the
+    /// DataFrame of the last one is returned. The result of
+    /// `CREATE TEMPORARY TABLE ... AS SELECT` stays on the executors, and the table
+    /// can be queried until the session is removed.
     pub async fn sql(&self, sql: &str) -> Result<DataFrame> {
+        let mut statements = split_statements(sql)?;
+        if statements.len() <= 1 {
+            return self.sql_statement(sql).await;
+        }
+
+        let last = statements.pop().unwrap();
+        for statement in statements {
+            self.sql_statement(&statement).await?.collect().await?;
+        }
+        self.sql_statement(&last).await
+    }
+
+    async fn sql_statement(&self, sql: &str) -> Result<DataFrame> {
         let mut ctx = self.context.clone();
 
+        if let Some(statement) = AdminStatement::parse(sql) {
+            let node = Arc::new(AdminStatementNode::try_new(statement)?);
+            return Ok(DataFrame::new(
+                ctx.state(),
+                LogicalPlan::Extension(Extension { node }),
+            ));
+        }
+
         let is_show = self.is_show_statement(sql).await?;
         // the show tables、 show columns sql can not run at scheduler because the tables is store at client
         if is_show {
@@ -404,15 +601,34 @@ impl BallistaContext {
             )) => {
                 let table_exists = ctx.table_exist(name)?;
                 let schema: SchemaRef = Arc::new(schema.as_ref().to_owned().into());
+                // the partition columns have the type declared in the schema, strings when
+                // the schema is inferred from the files
                 let table_partition_cols = table_partition_cols
                     .iter()
                     .map(|col| {
+                        if schema.fields().is_empty() {
+                            return Ok((col.to_owned(), DataType::Utf8));
+                        }
                         schema
                             .field_with_name(col)
                             .map(|f| (f.name().to_owned(), f.data_type().to_owned()))
                             .map_err(|e| DataFusionError::ArrowError(e, None))
                     })
                     .collect::<Result<Vec<_>>>()?;
+                // the values of the partition columns come from the paths of the files
+                // rather than from their content
+                let file_schema = Schema::new(
+                    schema
+                        .fields()
+                        .iter()
+                        .filter(|field| {
+                            !table_partition_cols
+                                .iter()
+                                .any(|(col, _)| col == field.name())
+                        })
+                        .cloned()
+                        .collect::<Fields>(),
+                );
 
                 match (if_not_exists, table_exists) {
                     (_, false) => match file_type.to_lowercase().as_str() {
@@ -421,20 +637,20 @@ impl BallistaContext {
                                 .has_header(*has_header)
                                 .delimiter(*delimiter as u8)
                                 .table_partition_cols(table_partition_cols.to_vec());
-                            if !schema.fields().is_empty() {
-                                options = options.schema(&schema);
+                            if !file_schema.fields().is_empty() {
+                                options = options.schema(&file_schema);
                             }
                             self.register_csv(name.table(), location, options).await?;
                             Ok(DataFrame::new(ctx.state(), plan))
                         }
                         "parquet" => {
-                            self.register_parquet(
-                                name.table(),
-                                location,
-                                ParquetReadOptions::default()
-                                    .table_partition_cols(table_partition_cols),
-                            )
-                            .await?;
+                            let mut options = ParquetReadOptions::default()
+                                .table_partition_cols(table_partition_cols);
+                            if !file_schema.fields().is_empty() {
+                                options = options.schema(&file_schema);
+                            }
+                            self.register_parquet(name.table(), location, options)
+                                .await?;
                             Ok(DataFrame::new(ctx.state(), plan))
                         }
                         "avro" => {
@@ -457,6 +673,66 @@ impl BallistaContext {
                     ))),
                 }
             }
+            LogicalPlan::Ddl(DdlStatement::CreateMemoryTable(CreateMemoryTable {
+                ref name,
+                ref input,
+                if_not_exists,
+                or_replace,
+                ..
+            })) if Self::is_create_temporary_table(sql)? => {
+                let table_exists = ctx.table_exist(name.clone())?;
+                match (if_not_exists, or_replace, table_exists) {
+                    (true, _, true) => ctx.execute_logical_plan(plan).await,
+                    (false, false, true) => Err(DataFusionError::Execution(format!(
+                        "Table '{name}' already exists"
+                    ))),
+                    _ => {
+                        // the table is planned by the scheduler, which reads the data
+                        // from the executors
+                        let table: Arc<dyn TableProvider> =
+                            Arc::new(TemporaryTable::new(
+                                name.table(),
+                                Arc::new(input.schema().as_ref().into()),
+                            ));
+                        self.register_table(name.table(), table.clone())?;
+                        ctx.register_table(name.clone(), table)?;
+                        Ok(DataFrame::new(ctx.state(), plan))
+                    }
+                }
+            }
+            LogicalPlan::Statement(LogicalStatement::SetVariable(SetVariable {
+                ref variable,
+                ref value,
+                ..
+            })) => {
+                // the scheduler saves the setting in the session, and rejects the invalid
+                // ones before the local context is changed
+                DataFrame::new(ctx.state(), plan.clone()).collect().await?;
+
+                let config = {
+                    let mut state = self.state.lock();
+                    let mut settings = state.config.settings().clone();
+                    settings.insert(variable.to_owned(), value.to_owned());
+                    state.config = BallistaConfig::with_settings(settings)
+                        .map_err(|e| DataFusionError::Execution(format!("{e}")))?;
+                    state.config.clone()
+                };
+                // the DataFusion options of the local context are sent along with the
+                // queries, and take precedence over the ones of the session
+                if variable.starts_with(DATAFUSION_CONFIG_PREFIX) {
+                    ctx.execute_logical_plan(plan).await?;
+                } else if variable == BALLISTA_DEFAULT_SHUFFLE_PARTITIONS {
+                    let sql = format!(
+                        "SET datafusion.execution.target_partitions = {}",
+                        config.default_shuffle_partitions()
+                    );
+                    ctx.sql(&sql).await?;
+                }
+                Ok(DataFrame::new(
+                    ctx.state(),
+                    LogicalPlanBuilder::empty(false).build()?,
+                ))
+            }
             _ => ctx.execute_logical_plan(plan).await,
         }
     }
@@ -474,6 +750,82 @@ impl BallistaContext {
     }
 }
 
+/// Create a session on the scheduler with the settings of the config, returning its id
+async fn create_remote_session(
+    scheduler: &mut SchedulerGrpcClient<Channel>,
+    config: &BallistaConfig,
+) -> ballista_core::error::Result<String> {
+    let remote_session_id = scheduler
+        .create_session(CreateSessionParams {
+            settings: config
+                .settings()
+                .iter()
+                .map(|(k, v)| KeyValuePair {
+                    key: k.to_owned(),
+                    value: v.to_owned(),
+                })
+                .collect::<Vec<_>>(),
+        })
+        .await
+        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+        .into_inner()
+        .session_id;
+
+    info!(
+        "Server side SessionContext created with session id: {}",
+        remote_session_id
+    );
+    Ok(remote_session_id)
+}
+
+/// Remove a session from the scheduler
+async fn remove_remote_session(
+    scheduler: &mut SchedulerGrpcClient<Channel>,
+    session_id: &str,
+) -> ballista_core::error::Result<()> {
+    scheduler
+        .remove_session(RemoveSessionParams {
+            session_id: session_id.to_owned(),
+        })
+        .await
+        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+    Ok(())
+}
+
+/// Split SQL text into its statements, keeping the text of each statement as written
+fn split_statements(sql: &str) -> Result<Vec<String>> {
+    let tokens = Tokenizer::new(&GenericDialect {}, sql)
+        .with_unescape(false)
+        .tokenize()
+        .map_err(|e| {
+            DataFusionError::SQL(ParserError::TokenizerError(e.to_string()), None)
+        })?;
+
+    let mut statements = vec![];
+    let mut statement = String::new();
+    let mut is_empty = true;
+    for token in tokens {
+        match token {
+            Token::SemiColon => {
+                if !is_empty {
+                    statements.push(statement.clone());
+                }
+                statement.clear();
+                is_empty = true;
+            }
+            Token::Whitespace(_) => statement.push_str(&token.to_string()),
+            token => {
+                statement.push_str(&token.to_string());
+                is_empty = false;
+            }
+        }
+    }
+    if !is_empty {
+        statements.push(statement);
+    }
+    Ok(statements)
+}
+
 #[cfg(test)]
 #[cfg(feature = "standalone")]
 mod standalone_tests {
@@ -493,6 +845,32 @@ mod standalone_tests {
         df.collect().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_standalone_discovery_file() -> Result<()> {
+        use super::*;
+        use ballista_core::config::BALLISTA_STANDALONE_DISCOVERY_FILE;
+        let tmp_dir = TempDir::new().unwrap();
+        let discovery_file = tmp_dir.path().join("scheduler");
+        let config = BallistaConfig::builder()
+            .set(
+                BALLISTA_STANDALONE_DISCOVERY_FILE,
+                &discovery_file.display().to_string(),
+            )
+            .build()?;
+
+        // several standalone contexts run side by side
+        let context = BallistaContext::standalone(&config, 1).await?;
+        let other_context =
+            BallistaContext::standalone(&BallistaConfig::new()?, 1).await?;
+        assert_ne!(context.scheduler_port(), other_context.scheduler_port());
+
+        let remote_context =
+            BallistaContext::remote_from_discovery_file(&discovery_file, &config).await?;
+        assert_eq!(context.scheduler_port(), remote_context.scheduler_port());
+        remote_context.sql("SELECT 1;").await?.collect().await?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_write_parquet() -> Result<()> {
         use super::*;
@@ -513,6 +891,282 @@ mod standalone_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_write_parquet_directory() -> Result<()> {
+        use super::*;
+        use ballista_core::execution_plans::SUCCESS_MARKER;
+        use datafusion::arrow::array::UInt64Array;
+        use std::fs::File;
+        use std::io::Write;
+        let context =
+            BallistaContext::standalone(&BallistaConfig::new().unwrap(), 2).await?;
+
+        let tmp_dir = TempDir::new().unwrap();
+        let input_path = tmp_dir.path().join("input");
+        std::fs::create_dir(&input_path).unwrap();
+        for (file_name, data) in [("1.csv", "a\n1\n2\n"), ("2.csv", "a\n3\n")] {
+            File::create(input_path.join(file_name))
+                .expect("creating temp file")
+                .write_all(data.as_bytes())
+                .expect("writing data");
+        }
+        let df = context
+            .read_csv(input_path.to_str().unwrap(), CsvReadOptions::new())
+            .await?;
+
+        let dir_path = format!("{}/", tmp_dir.path().join("output").display());
+        let count = df
+            .write_parquet(&dir_path, DataFrameWriteOptions::default(), None)
+            .await?;
+        let count = count[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(count, 3);
+        assert!(tmp_dir.path().join("output").join(SUCCESS_MARKER).exists());
+
+        let rows = context
+            .read_parquet(&dir_path, ParquetReadOptions::default())
+            .await?
+            .count()
+            .await?;
+        assert_eq!(rows, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_admin_statements() -> Result<()> {
+        use super::*;
+        use datafusion::arrow::array::{Array, BooleanArray, StringArray};
+        let context =
+            BallistaContext::standalone(&BallistaConfig::new().unwrap(), 1).await?;
+        context.sql("SELECT 1").await?.collect().await?;
+
+        // pull-based executors like the standalone one send no heartbeats, so they
+        // are not listed, as in the REST API
+        let executors = context.sql("SHOW EXECUTORS").await?;
+        assert_eq!(executors.schema().field(0).name(), "executor_id");
+        executors.collect().await?;
+
+        let jobs = context.sql("show jobs;").await?.collect().await?;
+        let job_ids = jobs[0]
+            .column_by_name("job_id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let status = jobs[0]
+            .column_by_name("status")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        // the SELECT and SHOW EXECUTORS jobs, ordered by start time
+        assert_eq!(job_ids.len(), 2);
+        assert_eq!(status.value(0), "Successful");
+
+        let sql = format!("KILL JOB '{}'", job_ids.value(0));
+        let killed = context.sql(&sql).await?.collect().await?;
+        let cancelled = killed[0]
+            .column_by_name("cancelled")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<BooleanArray>()
+            .unwrap();
+        assert!(cancelled.value(0));
+
+        let result = context.sql("KILL JOB 'missing'").await?.collect().await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_statement() -> Result<()> {
+        use super::*;
+        let context =
+            BallistaContext::standalone(&BallistaConfig::new().unwrap(), 1).await?;
+        context
+            .sql("SET ballista.shuffle.partitions = 3")
+            .await?
+            .collect()
+            .await?;
+        context
+            .sql("SET datafusion.execution.batch_size = 1024")
+            .await?
+            .collect()
+            .await?;
+
+        let config = context.state.lock().config.clone();
+        assert_eq!(config.default_shuffle_partitions(), 3);
+        let options = context.context().state().config().clone();
+        assert_eq!(options.target_partitions(), 3);
+        assert_eq!(options.batch_size(), 1024);
+
+        // invalid settings are rejected by the scheduler
+        assert!(context
+            .sql("SET ballista.shuffle.partitions = 'many'")
+            .await
+            .is_err());
+        assert!(context
+            .sql("SET ballista.no_such_option = 1")
+            .await
+            .is_err());
+        assert_eq!(context.state.lock().config.default_shuffle_partitions(), 3);
+        context.sql("SELECT 1").await?.collect().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_external_table() -> Result<()> {
+        use super::*;
+        use datafusion::arrow::array::Int64Array;
+        let context =
+            BallistaContext::standalone(&BallistaConfig::new().unwrap(), 1).await?;
+
+        let tmp_dir = TempDir::new().unwrap();
+        for (year, amounts) in [("2023", "1\n2\n"), ("2024", "10\n20\n")] {
+            let dir = tmp_dir.path().join(format!("year={year}"));
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(dir.join("data.csv"), format!("amount\n{amounts}"))?;
+        }
+        let location = tmp_dir.path().to_str().unwrap();
+
+        // with the schema declared, and inferred from the files
+        let sql = format!(
+            "CREATE EXTERNAL TABLE declared (amount BIGINT, year VARCHAR) \
+            STORED AS CSV WITH HEADER ROW PARTITIONED BY (year) LOCATION '{location}'"
+        );
+        context.sql(&sql).await?;
+        let sql = format!(
+            "CREATE EXTERNAL TABLE inferred \
+            STORED AS CSV WITH HEADER ROW PARTITIONED BY (year) LOCATION '{location}'"
+        );
+        context.sql(&sql).await?;
+
+        for table in ["declared", "inferred"] {
+            let sql =
+                format!("SELECT sum(amount) AS total FROM {table} WHERE year = '2024'");
+            let batches = context.sql(&sql).await?.collect().await?;
+            let total = batches[0]
+                .column_by_name("total")
+                .unwrap()
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap();
+            assert_eq!(total.value(0), 30);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_temporary_table() -> Result<()> {
+        use super::*;
+        use datafusion::arrow::array::Int64Array;
+        let context =
+            BallistaContext::standalone(&BallistaConfig::new().unwrap(), 1).await?;
+        let sql = "CREATE TEMPORARY TABLE t AS SELECT 1 AS a UNION ALL SELECT 2 AS a; \
+            SELECT sum(a) AS total FROM t;";
+        let batches = context.sql(sql).await?.collect().await?;
+        let total = batches[0]
+            .column_by_name("total")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(total.value(0), 3);
+
+        let result = context
+            .sql("CREATE TEMPORARY TABLE t AS SELECT 3 AS a")
+            .await;
+        assert!(result.is_err());
+
+        context
+            .sql("CREATE OR REPLACE TEMPORARY TABLE t AS SELECT 3 AS a")
+            .await?
+            .collect()
+            .await?;
+        let batches = context.sql("SELECT a FROM t").await?.collect().await?;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_named_sessions() -> Result<()> {
+        use super::*;
+        let context =
+            BallistaContext::standalone(&BallistaConfig::new().unwrap(), 1).await?;
+        let config = BallistaConfig::builder()
+            .set("ballista.shuffle.partitions", "2")
+            .build()?;
+        let session = context.create_session("etl", &config).await?;
+        assert_ne!(context.session_id(), session.session_id());
+        assert!(context.create_session("etl", &config).await.is_err());
+        assert_eq!(vec!["etl".to_owned()], context.session_names());
+        assert!(session.session("etl").is_some());
+
+        // tables are registered per session
+        session
+            .sql("CREATE TEMPORARY TABLE t AS SELECT 1 AS a")
+            .await?
+            .collect()
+            .await?;
+        session.sql("SELECT a FROM t").await?.collect().await?;
+        assert!(context.sql("SELECT a FROM t").await.is_err());
+
+        assert!(context.close_session("etl").await?);
+        assert!(!context.close_session("etl").await?);
+        assert!(context.session_names().is_empty());
+        context.sql("SELECT 1").await?.collect().await?;
+
+        // the sessions left open are dropped along with their context
+        let session = context.create_session("adhoc", &config).await?;
+        let weak_session = Arc::downgrade(&session);
+        drop(context);
+        assert!(session.session_names().is_empty());
+        assert!(session.create_session("etl", &config).await.is_err());
+        drop(session);
+        assert!(weak_session.upgrade().is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_table() -> Result<()> {
+        use super::*;
+        use ballista_core::config::BALLISTA_MEMORY_TABLE_MAX_SIZE;
+        use datafusion::arrow::array::Int32Array;
+        use datafusion::arrow::datatypes::{DataType, Field, Schema};
+        use datafusion::arrow::record_batch::RecordBatch;
+        use datafusion::datasource::MemTable;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )?;
+        let table = Arc::new(MemTable::try_new(schema, vec![vec![batch]])?);
+
+        let context =
+            BallistaContext::standalone(&BallistaConfig::new().unwrap(), 1).await?;
+        context.register_table("lookup", table.clone())?;
+        let batches = context
+            .sql("SELECT count(*) AS n FROM lookup WHERE a > 1")
+            .await?
+            .collect()
+            .await?;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_MEMORY_TABLE_MAX_SIZE, "1")
+            .build()?;
+        let context = BallistaContext::standalone(&config, 1).await?;
+        context.register_table("lookup", table)?;
+        let result = context.sql("SELECT a FROM lookup").await?.collect().await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_write_csv() -> Result<()> {
         use super::*;
@@ -0,0 +1,92 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Hash partitioning throughput of the shuffle writers, see
+//! [`ballista_core::execution_plans::HashPartitioner`], compared to the batch partitioner
+//! of DataFusion
+
+use std::sync::Arc;
+
+use ballista_core::execution_plans::HashPartitioner;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use datafusion::arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::physical_plan::expressions::Column;
+use datafusion::physical_plan::metrics;
+use datafusion::physical_plan::repartition::BatchPartitioner;
+use datafusion::physical_plan::Partitioning;
+
+const NUM_ROWS: usize = 8192;
+
+fn create_batch() -> RecordBatch {
+    let key: ArrayRef = Arc::new(Int64Array::from_iter_values(
+        (0..NUM_ROWS as i64).map(|i| i * 7919 % 100_003),
+    ));
+    let name: ArrayRef = Arc::new(StringArray::from_iter_values(
+        (0..NUM_ROWS).map(|i| format!("name-{}", i % 1000)),
+    ));
+    let value: ArrayRef = Arc::new(Float64Array::from_iter_values(
+        (0..NUM_ROWS).map(|i| i as f64 / 3.0),
+    ));
+    RecordBatch::try_from_iter(vec![("key", key), ("name", name), ("value", value)])
+        .unwrap()
+}
+
+fn partitioning(num_partitions: usize) -> Partitioning {
+    Partitioning::Hash(vec![Arc::new(Column::new("key", 0))], num_partitions)
+}
+
+/// Rows partitioned per second, with more and more output partitions
+fn partition(c: &mut Criterion) {
+    let batch = create_batch();
+    let mut group = c.benchmark_group("hash_partition");
+    group.throughput(Throughput::Elements(NUM_ROWS as u64));
+    for num_partitions in [16, 200, 1000] {
+        let mut partitioner =
+            HashPartitioner::try_new(partitioning(num_partitions), metrics::Time::new())
+                .unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("ballista", num_partitions),
+            &batch,
+            |b, batch| b.iter(|| partitioner.partition(batch.clone()).unwrap()),
+        );
+
+        let mut partitioner =
+            BatchPartitioner::try_new(partitioning(num_partitions), metrics::Time::new())
+                .unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("datafusion", num_partitions),
+            &batch,
+            |b, batch| {
+                b.iter(|| {
+                    let mut output = Vec::with_capacity(num_partitions);
+                    partitioner
+                        .partition(batch.clone(), |partition, batch| {
+                            output.push((partition, batch));
+                            Ok(())
+                        })
+                        .unwrap();
+                    output
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, partition);
+criterion_main!(benches);
@@ -28,6 +28,10 @@ fn main() -> Result<(), String> {
     let version = rustc_version::version().unwrap();
     println!("cargo:rustc-env=RUSTC_VERSION={version}");
 
+    // the version of arrow resolved for the build, advertised by the executors, empty when
+    // the lock file of the build cannot be found
+    println!("cargo:rustc-env=ARROW_VERSION={}", locked_version("arrow"));
+
     // TODO: undo when resolved: https://github.com/intellij-rust/intellij-rust/issues/9402
     #[cfg(feature = "docsrs")]
     let path = out.join("ballista.rs");
@@ -56,3 +60,34 @@ fn main() -> Result<(), String> {
 
     Ok(())
 }
+
+/// Find the version of a package in the lock file of the workspace being built
+fn locked_version(package: &str) -> String {
+    let manifest_dir =
+        std::path::PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let Some(lock_file) = manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.exists())
+    else {
+        return String::new();
+    };
+    println!("cargo:rerun-if-changed={}", lock_file.display());
+    let Ok(lock) = std::fs::read_to_string(lock_file) else {
+        return String::new();
+    };
+    let name = format!("name = \"{package}\"");
+    let mut lines = lock.lines();
+    while let Some(line) = lines.next() {
+        if line == name {
+            if let Some(version) = lines
+                .next()
+                .and_then(|line| line.strip_prefix("version = \""))
+                .and_then(|version| version.strip_suffix('"'))
+            {
+                return version.to_owned();
+            }
+        }
+    }
+    String::new()
+}
@@ -0,0 +1,204 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Administrative SQL statements (`SHOW JOBS`, `SHOW EXECUTORS` and `KILL JOB '<id>'`),
+//! which are answered by the scheduler from its own state rather than planned by DataFusion
+
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use datafusion::common::{DFSchema, DFSchemaRef};
+use datafusion::logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNodeCore};
+use datafusion::sql::sqlparser::dialect::GenericDialect;
+use datafusion::sql::sqlparser::tokenizer::{Token, Tokenizer};
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AdminStatement {
+    /// List the jobs known to the scheduler
+    ShowJobs,
+    /// List the executors registered with the scheduler
+    ShowExecutors,
+    /// Cancel the job with the given ID
+    KillJob(String),
+}
+
+impl AdminStatement {
+    /// Parse an administrative statement, returning `None` for any other SQL
+    pub fn parse(sql: &str) -> Option<Self> {
+        let tokens = Tokenizer::new(&GenericDialect {}, sql).tokenize().ok()?;
+        let mut tokens = tokens
+            .into_iter()
+            .filter(|token| !matches!(token, Token::Whitespace(_)));
+        let keyword = |token: Option<Token>, expected: &str| match token {
+            Some(Token::Word(word)) if word.quote_style.is_none() => {
+                word.value.eq_ignore_ascii_case(expected)
+            }
+            _ => false,
+        };
+
+        let first = tokens.next();
+        let second = tokens.next();
+        let statement = if keyword(first.clone(), "SHOW")
+            && keyword(second.clone(), "JOBS")
+        {
+            AdminStatement::ShowJobs
+        } else if keyword(first.clone(), "SHOW") && keyword(second.clone(), "EXECUTORS") {
+            AdminStatement::ShowExecutors
+        } else if keyword(first, "KILL") && keyword(second, "JOB") {
+            match tokens.next() {
+                Some(Token::SingleQuotedString(job_id)) => {
+                    AdminStatement::KillJob(job_id)
+                }
+                _ => return None,
+            }
+        } else {
+            return None;
+        };
+
+        // allow a single trailing semicolon
+        match (tokens.next(), tokens.next()) {
+            (None, _) | (Some(Token::SemiColon), None) => Some(statement),
+            _ => None,
+        }
+    }
+
+    /// The schema of the result of the statement
+    pub fn schema(&self) -> SchemaRef {
+        let timestamp = DataType::Timestamp(TimeUnit::Millisecond, None);
+        let fields = match self {
+            AdminStatement::ShowJobs => vec![
+                Field::new("job_id", DataType::Utf8, false),
+                Field::new("job_name", DataType::Utf8, false),
+                Field::new("status", DataType::Utf8, false),
+                Field::new("error", DataType::Utf8, true),
+                Field::new("num_stages", DataType::UInt32, false),
+                Field::new("completed_stages", DataType::UInt32, false),
+                Field::new("start_time", timestamp.clone(), false),
+                // null until the job has finished
+                Field::new("end_time", timestamp, true),
+            ],
+            AdminStatement::ShowExecutors => vec![
+                Field::new("executor_id", DataType::Utf8, false),
+                Field::new("host", DataType::Utf8, false),
+                Field::new("port", DataType::UInt32, false),
+                Field::new("grpc_port", DataType::UInt32, false),
+                Field::new("task_slots", DataType::UInt32, false),
+                Field::new("last_seen", timestamp, false),
+            ],
+            AdminStatement::KillJob(_) => vec![
+                Field::new("job_id", DataType::Utf8, false),
+                Field::new("cancelled", DataType::Boolean, false),
+            ],
+        };
+        Arc::new(Schema::new(fields))
+    }
+}
+
+impl fmt::Display for AdminStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminStatement::ShowJobs => write!(f, "SHOW JOBS"),
+            AdminStatement::ShowExecutors => write!(f, "SHOW EXECUTORS"),
+            AdminStatement::KillJob(job_id) => {
+                write!(f, "KILL JOB '{}'", job_id.replace('\'', "''"))
+            }
+        }
+    }
+}
+
+/// Logical plan node standing for an [`AdminStatement`] in a client side plan. The
+/// `BallistaQueryPlanner` sends the statement to the scheduler as SQL.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AdminStatementNode {
+    statement: AdminStatement,
+    schema: DFSchemaRef,
+}
+
+impl AdminStatementNode {
+    pub fn try_new(statement: AdminStatement) -> datafusion::error::Result<Self> {
+        let schema = Arc::new(DFSchema::try_from(statement.schema().as_ref().clone())?);
+        Ok(Self { statement, schema })
+    }
+
+    pub fn statement(&self) -> &AdminStatement {
+        &self.statement
+    }
+}
+
+impl UserDefinedLogicalNodeCore for AdminStatementNode {
+    fn name(&self) -> &str {
+        "AdminStatement"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AdminStatement: {}", self.statement)
+    }
+
+    fn from_template(&self, _exprs: &[Expr], _inputs: &[LogicalPlan]) -> Self {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_admin_statements() {
+        assert_eq!(
+            AdminStatement::parse("SHOW JOBS"),
+            Some(AdminStatement::ShowJobs)
+        );
+        assert_eq!(
+            AdminStatement::parse("  show executors;"),
+            Some(AdminStatement::ShowExecutors)
+        );
+        assert_eq!(
+            AdminStatement::parse("kill job 'abc1234'"),
+            Some(AdminStatement::KillJob("abc1234".to_string()))
+        );
+
+        assert_eq!(AdminStatement::parse("SHOW TABLES"), None);
+        assert_eq!(AdminStatement::parse("SELECT * FROM jobs"), None);
+        assert_eq!(AdminStatement::parse("KILL JOB abc1234"), None);
+        assert_eq!(AdminStatement::parse("KILL JOB 'a' 'b'"), None);
+        assert_eq!(AdminStatement::parse("SHOW JOBS; SELECT 1"), None);
+        assert_eq!(AdminStatement::parse("SHOW \"JOBS\""), None);
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let statement = AdminStatement::KillJob("it's".to_string());
+        assert_eq!(statement.to_string(), "KILL JOB 'it''s'");
+        assert_eq!(
+            AdminStatement::parse(&statement.to_string()),
+            Some(statement)
+        );
+    }
+}
@@ -32,6 +32,7 @@ use log::{error, info, warn};
 use object_store::path::Path;
 use object_store::{ObjectMeta, ObjectStore};
 use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::runtime::Runtime;
 
@@ -48,6 +49,8 @@ where
     loading_cache: DefaultFileLoadingCache<M>,
     io_runtime: Runtime,
     metrics: Arc<FileCacheMetrics>,
+    /// The maximum data size to be cached, shared with the cache counter
+    capacity: Arc<AtomicUsize>,
 }
 
 impl<M> FileCacheLayer<M>
@@ -58,6 +61,7 @@ where
         let cache_store = cache_medium.get_object_store();
 
         let cache_counter = FileCacheCounter::new(capacity);
+        let capacity = cache_counter.capacity.clone();
         let lru_cache = LruCache::with_resource_counter(cache_counter);
         let file_cache_loader = Arc::new(FileCacheLoader::new(cache_medium));
         let cache_with_removal_listener =
@@ -78,9 +82,21 @@ where
             loading_cache,
             io_runtime,
             metrics,
+            capacity,
         }
     }
 
+    /// The maximum data size to be cached
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Change the maximum data size to be cached. When shrinking the capacity, the least
+    /// recently used entries are evicted as soon as new entries are cached.
+    pub fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::Relaxed);
+    }
+
     pub fn cache_store(&self) -> Arc<dyn ObjectStore> {
         self.cache_store.clone()
     }
@@ -256,10 +272,10 @@ where
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct FileCacheCounter {
     /// The maximum data size to be cached
-    capacity: usize,
+    capacity: Arc<AtomicUsize>,
     /// The data size already be cached
     cached_size: usize,
 }
@@ -267,13 +283,13 @@ pub struct FileCacheCounter {
 impl FileCacheCounter {
     pub fn new(capacity: usize) -> Self {
         FileCacheCounter {
-            capacity,
+            capacity: Arc::new(AtomicUsize::new(capacity)),
             cached_size: 0,
         }
     }
 
     pub fn capacity(&self) -> usize {
-        self.capacity
+        self.capacity.load(Ordering::Relaxed)
     }
 
     pub fn cached_size(&self) -> usize {
@@ -294,6 +310,6 @@ impl ResourceCounter for FileCacheCounter {
     }
 
     fn exceed_capacity(&self) -> bool {
-        self.cached_size > self.capacity
+        self.cached_size > self.capacity()
     }
 }
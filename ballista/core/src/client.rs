@@ -29,6 +29,7 @@ use crate::error::{BallistaError, Result};
 use crate::serde::scheduler::{Action, PartitionId};
 
 use arrow_flight;
+use arrow_flight::sql::ProstMessageExt;
 use arrow_flight::utils::flight_data_to_arrow_batch;
 use arrow_flight::Ticket;
 use arrow_flight::{flight_service_client::FlightServiceClient, FlightData};
@@ -42,11 +43,16 @@ use datafusion::error::DataFusionError;
 
 use crate::serde::protobuf;
 use crate::utils::create_grpc_client_connection;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::{RecordBatchStream, SendableRecordBatchStream};
-use futures::{Stream, StreamExt};
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt, TryStreamExt};
 use log::{debug, warn};
 use prost::Message;
-use tonic::{Code, Streaming};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status, Streaming};
 
 /// Client for interacting with Ballista executors.
 #[derive(Clone)]
@@ -54,6 +60,10 @@ pub struct BallistaClient {
     flight_client: FlightServiceClient<tonic::transport::channel::Channel>,
 }
 
+/// Metadata key of the session ID sent with the fetches proxied by the scheduler, which
+/// only relays the fetches of the outputs of the jobs of this session
+pub const SESSION_ID_METADATA_KEY: &str = "ballista-session-id";
+
 //TODO make this configurable
 const IO_RETRIES_TIMES: u8 = 3;
 const IO_RETRY_WAIT_TIME_MS: u64 = 3000;
@@ -62,7 +72,11 @@ impl BallistaClient {
     /// Create a new BallistaClient to connect to the executor listening on the specified
     /// host and port
     pub async fn try_new(host: &str, port: u16) -> Result<Self> {
-        let addr = format!("http://{host}:{port}");
+        Self::try_new_with_url(format!("http://{host}:{port}")).await
+    }
+
+    /// Create a new BallistaClient to connect to the flight service at the specified URL
+    pub async fn try_new_with_url(addr: String) -> Result<Self> {
         debug!("BallistaClient connecting to {}", addr);
         let connection =
             create_grpc_client_connection(addr.clone())
@@ -97,16 +111,187 @@ impl BallistaClient {
         };
         self.execute_action(&action)
             .await
-            .map_err(|error| match error {
-                // map grpc connection error to partition fetch error.
-                BallistaError::GrpcActionError(msg) => BallistaError::FetchFailed(
-                    executor_id.to_owned(),
-                    partition_id.stage_id,
-                    partition_id.partition_id,
-                    msg,
-                ),
-                other => other,
+            .map_err(|error| fetch_error(error, executor_id, partition_id))
+    }
+
+    /// Fetch several shuffle partitions from an executor, which merges them into a single
+    /// stream. `partition_id` identifies the partition read by the reducer.
+    pub async fn fetch_partitions(
+        &mut self,
+        executor_id: &str,
+        partition_id: &PartitionId,
+        paths: Vec<String>,
+        host: &str,
+        port: u16,
+    ) -> Result<SendableRecordBatchStream> {
+        let action = Action::FetchPartitions {
+            job_id: partition_id.job_id.clone(),
+            stage_id: partition_id.stage_id,
+            partition_id: partition_id.partition_id,
+            paths,
+            host: host.to_owned(),
+            port,
+        };
+        self.execute_action(&action)
+            .await
+            .map_err(|error| fetch_error(error, executor_id, partition_id))
+    }
+
+    /// Fetch several partitions of an executor over a single stream, rather than with a
+    /// request per partition. The partitions must share their schema, e.g. the output
+    /// partitions of a job, and are streamed one after the other. The executor sends at
+    /// most `credits` batches ahead of the batches consumed from the returned stream.
+    pub async fn exchange_partitions(
+        &mut self,
+        executor_id: &str,
+        partitions: Vec<(PartitionId, String)>,
+        host: &str,
+        port: u16,
+        credits: usize,
+    ) -> Result<SendableRecordBatchStream> {
+        let Some((first_partition_id, _)) = partitions.first() else {
+            return Err(BallistaError::General(
+                "No partition to exchange".to_owned(),
+            ));
+        };
+        let first_partition_id = first_partition_id.clone();
+        let num_partitions = partitions.len();
+        let credits = credits.clamp(1, u32::MAX as usize);
+        let request = protobuf::PartitionExchangeRequest {
+            partitions: partitions
+                .iter()
+                .map(|(partition_id, path)| protobuf::FetchPartition {
+                    job_id: partition_id.job_id.clone(),
+                    stage_id: partition_id.stage_id as u32,
+                    partition_id: partition_id.partition_id as u32,
+                    path: path.clone(),
+                    host: host.to_owned(),
+                    port: port as u32,
+                })
+                .collect(),
+            credits: credits as u32,
+        };
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        let _ = request_tx.send(exchange_request(request));
+
+        let exchange = self
+            .start_exchange(request_rx)
+            .await
+            .map_err(|error| fetch_error(error, executor_id, &first_partition_id))?;
+        let Some((stream, schema)) = exchange else {
+            debug!(
+                "Executor {executor_id} does not support partition exchanges, fetching \
+                its partitions one at a time"
+            );
+            return self
+                .fetch_partitions_in_turn(executor_id, partitions, host, port)
+                .await;
+        };
+        Ok(Box::pin(PartitionExchangeStream {
+            inner: FlightDataStream::new(stream, schema),
+            request_tx: Some(request_tx),
+            remaining_partitions: num_partitions,
+            credits,
+            consumed: 0,
+        }))
+    }
+
+    /// Start a partition exchange with the requests sent to `request_rx`, returning the
+    /// stream of the exchange and its schema, or `None` if the executor does not
+    /// implement partition exchanges, e.g. an executor of an older version
+    async fn start_exchange(
+        &mut self,
+        request_rx: mpsc::UnboundedReceiver<FlightData>,
+    ) -> Result<Option<(Streaming<FlightData>, SchemaRef)>> {
+        let result = self
+            .flight_client
+            .do_exchange(UnboundedReceiverStream::new(request_rx))
+            .await;
+        let mut stream = match result {
+            Ok(response) => response.into_inner(),
+            Err(e) if e.code() == Code::Unimplemented => return Ok(None),
+            Err(e) => return Err(BallistaError::GrpcActionError(format!("{e:?}"))),
+        };
+        match stream.message().await {
+            Ok(Some(flight_data)) => {
+                let schema = Arc::new(Schema::try_from(&flight_data)?);
+                Ok(Some((stream, schema)))
+            }
+            Ok(None) => Err(BallistaError::GrpcActionError(
+                "Did not receive schema batch from flight server".to_string(),
+            )),
+            Err(e) if e.code() == Code::Unimplemented => Ok(None),
+            Err(e) => Err(BallistaError::GrpcActionError(format!("{e:?}"))),
+        }
+    }
+
+    /// Fetch the partitions of an executor one after the other with a request per
+    /// partition, for the executors which do not implement partition exchanges
+    async fn fetch_partitions_in_turn(
+        &mut self,
+        executor_id: &str,
+        partitions: Vec<(PartitionId, String)>,
+        host: &str,
+        port: u16,
+    ) -> Result<SendableRecordBatchStream> {
+        let mut partitions = partitions.into_iter();
+        let Some((partition_id, path)) = partitions.next() else {
+            return Err(BallistaError::General(
+                "No partition to exchange".to_owned(),
+            ));
+        };
+        let first = self
+            .fetch_partition(executor_id, &partition_id, &path, host, port)
+            .await?;
+        let schema = first.schema();
+        let client = self.clone();
+        let executor_id = executor_id.to_owned();
+        let host = host.to_owned();
+        let rest = futures::stream::iter(partitions)
+            .then(move |(partition_id, path)| {
+                let mut client = client.clone();
+                let executor_id = executor_id.clone();
+                let host = host.clone();
+                async move {
+                    client
+                        .fetch_partition(&executor_id, &partition_id, &path, &host, port)
+                        .await
+                        .map_err(|e| DataFusionError::External(Box::new(e)))
+                }
             })
+            .try_flatten();
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            first.chain(rest),
+        )))
+    }
+
+    /// Fetch a partition of an executor through the scheduler, which proxies the request
+    /// to the executor. The connected flight service must be the one of the scheduler.
+    /// The session of the job is sent along, the scheduler only relaying the fetches of
+    /// the outputs of the jobs of this session.
+    pub async fn fetch_partition_via_scheduler(
+        &mut self,
+        session_id: &str,
+        executor_id: &str,
+        partition_id: &PartitionId,
+        path: &str,
+        host: &str,
+        port: u16,
+    ) -> Result<SendableRecordBatchStream> {
+        let action: protobuf::Action = Action::FetchPartition {
+            job_id: partition_id.job_id.clone(),
+            stage_id: partition_id.stage_id,
+            partition_id: partition_id.partition_id,
+            path: path.to_owned(),
+            host: host.to_owned(),
+            port,
+        }
+        .try_into()?;
+        // the flight service of the scheduler expects the action as a protobuf Any
+        self.do_get(action.as_any().encode_to_vec(), Some(session_id))
+            .await
+            .map_err(|error| fetch_error(error, executor_id, partition_id))
     }
 
     /// Execute an action and retrieve the results
@@ -122,6 +307,19 @@ impl BallistaClient {
             .encode(&mut buf)
             .map_err(|e| BallistaError::GrpcActionError(format!("{e:?}")))?;
 
+        self.do_get(buf, None).await
+    }
+
+    /// Send a ticket to the flight service and retrieve the results, retrying on IO errors
+    async fn do_get(
+        &mut self,
+        buf: Vec<u8>,
+        session_id: Option<&str>,
+    ) -> Result<SendableRecordBatchStream> {
+        let session_id = session_id
+            .map(MetadataValue::try_from)
+            .transpose()
+            .map_err(|e| BallistaError::General(format!("Invalid session ID: {e}")))?;
         for i in 0..IO_RETRIES_TIMES {
             if i > 0 {
                 warn!(
@@ -134,9 +332,14 @@ impl BallistaClient {
                 .await;
             }
 
-            let request = tonic::Request::new(Ticket {
+            let mut request = tonic::Request::new(Ticket {
                 ticket: buf.clone().into(),
             });
+            if let Some(session_id) = &session_id {
+                request
+                    .metadata_mut()
+                    .insert(SESSION_ID_METADATA_KEY, session_id.clone());
+            }
             let result = self.flight_client.do_get(request).await;
             let res = match result {
                 Ok(res) => res,
@@ -187,16 +390,36 @@ impl BallistaClient {
     }
 }
 
+/// Map a gRPC error fetching a partition to a partition fetch error
+fn fetch_error(
+    error: BallistaError,
+    executor_id: &str,
+    partition_id: &PartitionId,
+) -> BallistaError {
+    match error {
+        BallistaError::GrpcActionError(msg) => BallistaError::FetchFailed(
+            executor_id.to_owned(),
+            partition_id.stage_id,
+            partition_id.partition_id,
+            msg,
+        ),
+        other => other,
+    }
+}
+
 struct FlightDataStream {
-    stream: Streaming<FlightData>,
+    stream: BoxStream<'static, std::result::Result<FlightData, Status>>,
     schema: SchemaRef,
     dictionaries_by_id: HashMap<i64, ArrayRef>,
 }
 
 impl FlightDataStream {
-    pub fn new(stream: Streaming<FlightData>, schema: SchemaRef) -> Self {
+    pub fn new<S>(stream: S, schema: SchemaRef) -> Self
+    where
+        S: Stream<Item = std::result::Result<FlightData, Status>> + Send + 'static,
+    {
         Self {
-            stream,
+            stream: stream.boxed(),
             schema,
             dictionaries_by_id: HashMap::new(),
         }
@@ -234,3 +457,193 @@ impl RecordBatchStream for FlightDataStream {
         self.schema.clone()
     }
 }
+
+/// A request of a partition exchange, in the app metadata of a message without data
+fn exchange_request(request: protobuf::PartitionExchangeRequest) -> FlightData {
+    FlightData::new().with_app_metadata(request.encode_to_vec())
+}
+
+/// The batches of the partitions of a partition exchange, granting credits to the
+/// executor as they are consumed
+struct PartitionExchangeStream {
+    inner: FlightDataStream,
+    /// Closed once all the partitions are received, which ends the exchange
+    request_tx: Option<mpsc::UnboundedSender<FlightData>>,
+    remaining_partitions: usize,
+    /// The credits granted to the executor when the exchange started
+    credits: usize,
+    /// The batches consumed since credits were last granted
+    consumed: usize,
+}
+
+impl PartitionExchangeStream {
+    /// Grant the credits of the consumed batches once they reach half of the initial
+    /// credits, so that the executor streams the next batches while these are processed
+    fn consume_batch(&mut self) {
+        self.consumed += 1;
+        if self.consumed < (self.credits / 2).max(1) {
+            return;
+        }
+        if let Some(request_tx) = &self.request_tx {
+            let _ =
+                request_tx.send(exchange_request(protobuf::PartitionExchangeRequest {
+                    partitions: vec![],
+                    credits: self.consumed as u32,
+                }));
+        }
+        self.consumed = 0;
+    }
+}
+
+impl Stream for PartitionExchangeStream {
+    type Item = datafusion::error::Result<RecordBatch>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        loop {
+            let flight_data = match self.inner.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(flight_data))) => flight_data,
+                Poll::Ready(Some(Err(e))) => {
+                    return Poll::Ready(Some(Err(ArrowError::from_external_error(
+                        Box::new(e),
+                    )
+                    .into())))
+                }
+                Poll::Ready(None) if self.remaining_partitions > 0 => {
+                    // the missing partitions are reported once, the stream then ends
+                    let remaining_partitions =
+                        std::mem::take(&mut self.remaining_partitions);
+                    return Poll::Ready(Some(Err(DataFusionError::Execution(format!(
+                        "Partition exchange ended with {remaining_partitions} partitions \
+                        not received"
+                    )))));
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            // the messages without data mark the end of the partitions
+            if flight_data.data_header.is_empty() {
+                self.remaining_partitions = self.remaining_partitions.saturating_sub(1);
+                if self.remaining_partitions == 0 {
+                    self.request_tx = None;
+                }
+                continue;
+            }
+            let batch = flight_data_to_arrow_batch(
+                &flight_data,
+                self.inner.schema.clone(),
+                &self.inner.dictionaries_by_id,
+            )
+            .map_err(|e| DataFusionError::ArrowError(e, None));
+            self.consume_batch();
+            return Poll::Ready(Some(batch));
+        }
+    }
+}
+
+impl RecordBatchStream for PartitionExchangeStream {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use arrow_flight::utils::batches_to_flight_data;
+    use arrow_flight::FlightData;
+    use datafusion::arrow::array::Int64Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use futures::StreamExt;
+    use prost::Message;
+    use tokio::sync::mpsc;
+
+    use super::{FlightDataStream, PartitionExchangeStream};
+    use crate::serde::protobuf;
+
+    fn partition_end(partition_index: u32) -> FlightData {
+        let end = protobuf::PartitionExchangeEnd { partition_index };
+        FlightData::new().with_app_metadata(end.encode_to_vec())
+    }
+
+    /// A partition exchange of two partitions whose stream ends with the given messages,
+    /// along with the requests of the exchange
+    fn exchange_stream(
+        messages: Vec<FlightData>,
+    ) -> (PartitionExchangeStream, mpsc::UnboundedReceiver<FlightData>) {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        let stream = PartitionExchangeStream {
+            inner: FlightDataStream::new(
+                futures::stream::iter(messages.into_iter().map(Ok)),
+                schema,
+            ),
+            request_tx: Some(request_tx),
+            remaining_partitions: 2,
+            credits: 4,
+            consumed: 0,
+        };
+        (stream, request_rx)
+    }
+
+    /// The messages of the batches of a partition, without the schema of the exchange
+    fn batch_messages(num_batches: usize) -> Vec<FlightData> {
+        let schema = Schema::new(vec![Field::new("a", DataType::Int64, false)]);
+        let batches = (0..num_batches)
+            .map(|i| {
+                RecordBatch::try_new(
+                    Arc::new(schema.clone()),
+                    vec![Arc::new(Int64Array::from(vec![i as i64]))],
+                )
+                .unwrap()
+            })
+            .collect();
+        batches_to_flight_data(&schema, batches)
+            .unwrap()
+            .into_iter()
+            .skip(1)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_partition_exchange_stream() {
+        let mut messages = batch_messages(3);
+        messages.push(partition_end(0));
+        messages.extend(batch_messages(1));
+        messages.push(partition_end(1));
+        let (stream, mut request_rx) = exchange_stream(messages);
+
+        let batches = stream.collect::<Vec<_>>().await;
+        assert_eq!(batches.len(), 4);
+        assert!(batches.iter().all(|batch| batch.is_ok()));
+        // the credits of every other batch are granted again, half of the initial credits
+        let mut credits = vec![];
+        while let Some(request) = request_rx.recv().await {
+            let request =
+                protobuf::PartitionExchangeRequest::decode(request.app_metadata.as_ref())
+                    .unwrap();
+            assert!(request.partitions.is_empty());
+            credits.push(request.credits);
+        }
+        assert_eq!(credits, vec![2, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_partition_exchange_stream_ended_early() {
+        let mut messages = batch_messages(1);
+        messages.push(partition_end(0));
+        let (stream, mut request_rx) = exchange_stream(messages);
+
+        let results = stream.collect::<Vec<_>>().await;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        let error = results[1].as_ref().unwrap_err().to_string();
+        assert!(error.contains("1 partitions not received"), "{error}");
+        // no credits are granted for a single batch out of four credits
+        assert!(request_rx.try_recv().is_err());
+    }
+}
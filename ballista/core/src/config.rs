@@ -26,6 +26,8 @@ use std::result;
 use crate::error::{BallistaError, Result};
 
 use datafusion::arrow::datatypes::DataType;
+use datafusion::config::ConfigOptions;
+use datafusion::prelude::SessionConfig;
 
 pub const BALLISTA_JOB_NAME: &str = "ballista.job.name";
 pub const BALLISTA_DEFAULT_SHUFFLE_PARTITIONS: &str = "ballista.shuffle.partitions";
@@ -36,6 +38,9 @@ pub const BALLISTA_REPARTITION_JOINS: &str = "ballista.repartition.joins";
 pub const BALLISTA_REPARTITION_AGGREGATIONS: &str = "ballista.repartition.aggregations";
 pub const BALLISTA_REPARTITION_WINDOWS: &str = "ballista.repartition.windows";
 pub const BALLISTA_PARQUET_PRUNING: &str = "ballista.parquet.pruning";
+/// whether the scheduler reads the metadata of the parquet files scanned by a job to prune
+/// their row groups with the statistics, so that no task reads only row groups filtered out
+pub const BALLISTA_PARQUET_PRUNE_ROW_GROUPS: &str = "ballista.parquet.prune_row_groups";
 pub const BALLISTA_COLLECT_STATISTICS: &str = "ballista.collect_statistics";
 /// Indicate whether to enable to data cache for a task
 pub const BALLISTA_DATA_CACHE_ENABLED: &str = "ballista.data_cache.enabled";
@@ -46,6 +51,89 @@ pub const BALLISTA_PLUGIN_DIR: &str = "ballista.plugin_dir";
 /// max message size for gRPC clients
 pub const BALLISTA_GRPC_CLIENT_MAX_MESSAGE_SIZE: &str =
     "ballista.grpc_client_max_message_size";
+/// max size in bytes of the in-memory tables of the client which are sent to the cluster
+/// along with the plan
+pub const BALLISTA_MEMORY_TABLE_MAX_SIZE: &str = "ballista.memory_table.max_size";
+/// whether the client fetches the output partitions of a job in partition order and merges
+/// sorted output partitions, so that the results keep the order of the query
+pub const BALLISTA_CLIENT_ORDERED_FETCH: &str = "ballista.client.ordered_fetch";
+/// whether subqueries referenced several times in a query, such as WITH clause CTEs, are
+/// computed once as a stage shared by all references rather than inlined at every reference
+pub const BALLISTA_CTE_MATERIALIZE: &str = "ballista.cte.materialize";
+/// zone of the client, the scheduler binds the final stage tasks of the jobs of the session to
+/// executors of this zone when there are any, so that the results are fetched within the zone
+pub const BALLISTA_CLIENT_ZONE: &str = "ballista.client.zone";
+/// whether the client fetches the results of a job through the scheduler, which proxies the
+/// requests to the executors, rather than from the executors directly
+pub const BALLISTA_CLIENT_FETCH_VIA_SCHEDULER: &str =
+    "ballista.client.fetch_via_scheduler";
+/// number of batches the executors stream ahead of the client when it fetches the output
+/// partitions of an executor over a single partition exchange stream, 0 means the client
+/// fetches every output partition with its own request
+pub const BALLISTA_CLIENT_EXCHANGE_CREDITS: &str = "ballista.client.exchange_credits";
+/// whether the final stage of a job is run by the client on the fetched output partitions of
+/// the stage it reads, when this stage outputs a single partition, e.g. a final sort or limit
+pub const BALLISTA_CLIENT_COLLECT_STAGE: &str = "ballista.client.collect_stage";
+/// max number of tasks running at once of each stage of a job, e.g. for jobs scanning
+/// rate limited sources. It applies to the stages without a limit of their own, 0 means
+/// the stages run with the parallelism of the cluster.
+pub const BALLISTA_JOB_MAX_CONCURRENT_STAGE_TASKS: &str =
+    "ballista.job.max_concurrent_stage_tasks";
+/// max number of tasks running at once of some stages of a job, as comma separated
+/// `stage_id:max_tasks` pairs, e.g. `1:4,3:2` for the stages scanning rate limited
+/// sources, overriding `ballista.job.max_concurrent_stage_tasks`, 0 means no limit
+pub const BALLISTA_JOB_MAX_CONCURRENT_TASKS_BY_STAGE: &str =
+    "ballista.job.max_concurrent_tasks_by_stage";
+/// max number of tasks of a stage running or holding their outputs on the same executor,
+/// so that losing an executor only loses a bounded part of the work of the stage, 0 means
+/// no limit
+pub const BALLISTA_STAGE_MAX_TASKS_PER_EXECUTOR: &str =
+    "ballista.stage.max_tasks_per_executor";
+/// experimental, whether a stage starts once the tasks of its input stages are all running and
+/// some of them finished, its tasks consuming the shuffle partitions as they are written
+pub const BALLISTA_STAGE_PIPELINED: &str = "ballista.stage.pipelined";
+/// path of the file which a standalone context writes the address of its scheduler to, so
+/// that other processes can connect to it
+pub const BALLISTA_STANDALONE_DISCOVERY_FILE: &str = "ballista.standalone.discovery_file";
+/// min number of shuffle partitions a task reads from the same executor for this executor to
+/// merge them into a single stream, 0 means the partitions are always fetched one by one
+pub const BALLISTA_SHUFFLE_MERGE_FETCH_THRESHOLD: &str =
+    "ballista.shuffle.merge_fetch_threshold";
+/// number of batches a task fetches from its shuffle partitions ahead of the batches it
+/// computes, 0 means the batches are fetched on demand
+pub const BALLISTA_SHUFFLE_PREFETCH_BATCHES: &str = "ballista.shuffle.prefetch_batches";
+/// whether the consecutive batches read by a task from its shuffle partitions which are
+/// smaller than the batch size are concatenated
+pub const BALLISTA_SHUFFLE_COALESCE_BATCHES: &str = "ballista.shuffle.coalesce_batches";
+/// memory budget in bytes of the batches computed by a task which wait to be written to its
+/// shuffle files, the task waiting for the writes once it is exhausted
+pub const BALLISTA_SHUFFLE_WRITE_BUFFER_SIZE: &str = "ballista.shuffle.write_buffer_size";
+/// target number of bytes of the files read by every task of a file scan, the scheduler
+/// regroups the files of the scans by size accordingly, 0 keeps the file groups planned by
+/// DataFusion
+pub const BALLISTA_SCAN_TARGET_BYTES_PER_TASK: &str =
+    "ballista.scan.target_bytes_per_task";
+/// max number of rows of the results of a job, the job fails or its results are truncated
+/// when its final stage outputs more rows, 0 means no limit
+pub const BALLISTA_RESULTS_MAX_ROWS: &str = "ballista.results.max_rows";
+/// max number of bytes of the results of a job, the job fails or its results are truncated
+/// when its final stage outputs more bytes, 0 means no limit
+pub const BALLISTA_RESULTS_MAX_BYTES: &str = "ballista.results.max_bytes";
+/// whether the results of a job exceeding the max rows or bytes are truncated to the limits
+/// rather than failing the job
+pub const BALLISTA_RESULTS_TRUNCATE: &str = "ballista.results.truncate";
+/// policy of distributing the tasks of the jobs of the session to the executor slots, which
+/// overrides the policy of the scheduler: bias, round-robin or weighted, empty for the
+/// scheduler policy
+pub const BALLISTA_TASK_DISTRIBUTION: &str = "ballista.task.distribution";
+/// whether the probe side scans of the partitioned hash joins wait for the build side stage,
+/// whose min and max join key values the scheduler then pushes to the scans as filters
+pub const BALLISTA_JOIN_RUNTIME_FILTERS: &str = "ballista.join.runtime_filters";
+/// size in bytes of the bloom filters of the build side join keys, which the scheduler
+/// pushes to the probe side scans of the partitioned hash joins, 0 means no bloom filters
+pub const BALLISTA_JOIN_BLOOM_FILTER_BYTES: &str = "ballista.join.bloom_filter_bytes";
+/// DataFusion settings, i.e. keys with this prefix, are passed through to the session and task configurations
+pub const DATAFUSION_CONFIG_PREFIX: &str = "datafusion.";
 
 pub type ParseResult<T> = result::Result<T, String>;
 
@@ -137,6 +225,17 @@ impl BallistaConfig {
             }
         }
 
+        if let Some(v) = settings.get(BALLISTA_JOB_MAX_CONCURRENT_TASKS_BY_STAGE) {
+            Self::parse_tasks_by_stage(v).map_err(|e| BallistaError::General(format!("Failed to parse user-supplied value '{BALLISTA_JOB_MAX_CONCURRENT_TASKS_BY_STAGE}' for configuration setting '{v}': {e}")))?;
+        }
+
+        let mut datafusion_config = ConfigOptions::new();
+        for (name, v) in &settings {
+            if name.starts_with(DATAFUSION_CONFIG_PREFIX) {
+                datafusion_config.set(name, v).map_err(|e| BallistaError::General(format!("Failed to parse user-supplied value '{v}' for DataFusion configuration setting '{name}': {e}")))?;
+            }
+        }
+
         Ok(Self { settings })
     }
 
@@ -173,6 +272,22 @@ impl BallistaConfig {
         Ok(())
     }
 
+    /// Parse comma separated `stage_id:max_tasks` pairs
+    fn parse_tasks_by_stage(val: &str) -> ParseResult<HashMap<usize, usize>> {
+        val.split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| -> ParseResult<(usize, usize)> {
+                let (stage_id, max_tasks) = pair
+                    .split_once(':')
+                    .ok_or_else(|| format!("expected stage_id:max_tasks, got {pair}"))?;
+                let stage_id = stage_id.trim().parse().map_err(|e| format!("{e:?}"))?;
+                let max_tasks = max_tasks.trim().parse().map_err(|e| format!("{e:?}"))?;
+                Ok((stage_id, max_tasks))
+            })
+            .collect()
+    }
+
     /// All available configuration options
     pub fn valid_entries() -> HashMap<String, ConfigEntry> {
         let entries = vec![
@@ -214,6 +329,79 @@ impl BallistaConfig {
                              "Configuration for max message size in gRPC clients".to_string(),
                              DataType::UInt64,
                              Some((16 * 1024 * 1024).to_string())),
+            ConfigEntry::new(BALLISTA_MEMORY_TABLE_MAX_SIZE.to_string(),
+                             "Sets the max size in bytes of the in-memory tables of the client which are sent to the cluster along with the plan".to_string(),
+                             DataType::UInt64,
+                             Some((4 * 1024 * 1024).to_string())),
+            ConfigEntry::new(BALLISTA_CLIENT_ORDERED_FETCH.to_string(),
+                             "Sets whether the client fetches the output partitions of a job in partition order and merges them when they are sorted, so that ORDER BY queries return ordered results".to_string(),
+                             DataType::Boolean, Some("true".to_string())),
+            ConfigEntry::new(BALLISTA_CTE_MATERIALIZE.to_string(),
+                             "Sets whether CTEs and other subqueries referenced several times in a query are computed once by a stage shared by all references".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_CLIENT_ZONE.to_string(),
+                             "Sets the zone of the client, so that the final stage tasks of jobs run on executors registered in the same zone".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_CLIENT_FETCH_VIA_SCHEDULER.to_string(),
+                             "Sets whether the client fetches the results of jobs through the scheduler rather than from the executors, e.g. when the executors are not reachable from the client. Requires the flight-sql feature of the scheduler".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_CLIENT_EXCHANGE_CREDITS.to_string(),
+                             "Sets the number of batches the executors stream ahead of the client when it fetches all the output partitions of an executor over a single stream, 0 to fetch every output partition with its own request".to_string(),
+                             DataType::UInt64, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_CLIENT_COLLECT_STAGE.to_string(),
+                             "Sets whether the client runs the final single partition stage of jobs, e.g. a final sort, limit or merge, on the fetched partitions of the stage it reads, saving a round trip to the cluster for small results".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_MAX_CONCURRENT_STAGE_TASKS.to_string(),
+                             "Sets the max number of tasks which run at once for each stage of a job without a limit of its own, 0 for no limit".to_string(),
+                             DataType::UInt64, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_MAX_CONCURRENT_TASKS_BY_STAGE.to_string(),
+                             "Sets the max number of tasks which run at once for some stages of a job, as comma separated stage_id:max_tasks pairs, e.g. 1:4,3:2, overriding ballista.job.max_concurrent_stage_tasks for these stages, 0 for no limit. The stage ids are the ones shown by the scheduler UI and REST API".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_STAGE_MAX_TASKS_PER_EXECUTOR.to_string(),
+                             "Sets the max number of tasks of every stage of a job which run or hold their outputs on the same executor, bounding the work lost with an executor, 0 for no limit. A stage with more tasks than the executors can hold under the limit waits for more executors".to_string(),
+                             DataType::UInt64, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_STAGE_PIPELINED.to_string(),
+                             "Experimental, sets whether the stages start before their input stages complete, once all the input tasks run and some of them finished, to lower the latency of selective queries. Only supported with the push-based task scheduling policy".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_STANDALONE_DISCOVERY_FILE.to_string(),
+                             "Sets the path of the file which a standalone context writes the host:port address of its scheduler to, empty for none".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_SHUFFLE_MERGE_FETCH_THRESHOLD.to_string(),
+                             "Sets the min number of shuffle partitions a task reads from the same executor for the executor to merge them into a single stream, 0 to fetch the partitions one by one. Executors of older versions can not serve merged streams".to_string(),
+                             DataType::UInt64, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_SHUFFLE_PREFETCH_BATCHES.to_string(),
+                             "Sets the number of batches a task fetches from its shuffle partitions ahead of the batches it computes, 0 to fetch them on demand".to_string(),
+                             DataType::UInt64, Some("2".to_string())),
+            ConfigEntry::new(BALLISTA_SHUFFLE_COALESCE_BATCHES.to_string(),
+                             "Sets whether the consecutive batches read by a task from its shuffle partitions which are smaller than the batch size are concatenated".to_string(),
+                             DataType::Boolean, Some("true".to_string())),
+            ConfigEntry::new(BALLISTA_SHUFFLE_WRITE_BUFFER_SIZE.to_string(),
+                             "Sets the memory budget in bytes of the batches computed by a task which wait to be written to its shuffle files, so that the computation overlaps the disk writes".to_string(),
+                             DataType::UInt64, Some((64 * 1024 * 1024).to_string())),
+            ConfigEntry::new(BALLISTA_SCAN_TARGET_BYTES_PER_TASK.to_string(),
+                             "Sets the target number of bytes of the files read by every task of a file scan, the files being grouped by size into tasks, 0 to keep the file groups planned by DataFusion".to_string(),
+                             DataType::UInt64, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_PARQUET_PRUNE_ROW_GROUPS.to_string(),
+                             "Sets whether the scheduler prunes the row groups of the scanned parquet files with their statistics when planning the jobs, so that the tasks only read the row groups which may match the filters".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_RESULTS_MAX_ROWS.to_string(),
+                             "Sets the max number of rows of the results of a job, which fails or whose results are truncated beyond, 0 for no limit".to_string(),
+                             DataType::UInt64, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_RESULTS_MAX_BYTES.to_string(),
+                             "Sets the max number of bytes of the results of a job, which fails or whose results are truncated beyond, 0 for no limit".to_string(),
+                             DataType::UInt64, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_RESULTS_TRUNCATE.to_string(),
+                             "Sets whether the results of a job exceeding the max rows or bytes are truncated to the limits, rather than failing the job".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_TASK_DISTRIBUTION.to_string(),
+                             "Sets the policy of distributing the tasks of the jobs to the executor slots, possible values: bias, round-robin, weighted, empty for the policy of the scheduler".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOIN_RUNTIME_FILTERS.to_string(),
+                             "Sets whether the probe side stages of the partitioned hash joins start once their build side stage completed, filtering the scanned rows with the min and max join keys of the build side, which cuts the data scanned by selective joins such as star schema queries".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_JOIN_BLOOM_FILTER_BYTES.to_string(),
+                             "Sets the size in bytes of the bloom filters of the join keys which the build side stages of the partitioned hash joins collect, the probe side stages starting once their build side stage completed and only shuffling the rows whose join key may be in the filters, 0 for no bloom filters".to_string(),
+                             DataType::UInt64, Some("0".to_string())),
         ];
         entries
             .iter()
@@ -225,6 +413,17 @@ impl BallistaConfig {
         &self.settings
     }
 
+    /// Override the options of `config` with the DataFusion settings of this configuration
+    pub fn apply_datafusion_settings(&self, mut config: SessionConfig) -> SessionConfig {
+        for (name, v) in &self.settings {
+            if name.starts_with(DATAFUSION_CONFIG_PREFIX) {
+                // infallible because we validate all configs in the constructor
+                config.options_mut().set(name, v).unwrap();
+            }
+        }
+        config
+    }
+
     pub fn default_shuffle_partitions(&self) -> usize {
         self.get_usize_setting(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS)
     }
@@ -245,6 +444,115 @@ impl BallistaConfig {
         self.get_usize_setting(BALLISTA_GRPC_CLIENT_MAX_MESSAGE_SIZE)
     }
 
+    pub fn memory_table_max_size(&self) -> usize {
+        self.get_usize_setting(BALLISTA_MEMORY_TABLE_MAX_SIZE)
+    }
+
+    pub fn client_ordered_fetch(&self) -> bool {
+        self.get_bool_setting(BALLISTA_CLIENT_ORDERED_FETCH)
+    }
+
+    pub fn cte_materialize(&self) -> bool {
+        self.get_bool_setting(BALLISTA_CTE_MATERIALIZE)
+    }
+
+    /// The zone of the client, if any
+    pub fn client_zone(&self) -> Option<String> {
+        Some(self.get_string_setting(BALLISTA_CLIENT_ZONE))
+            .filter(|zone| !zone.is_empty())
+    }
+
+    pub fn client_fetch_via_scheduler(&self) -> bool {
+        self.get_bool_setting(BALLISTA_CLIENT_FETCH_VIA_SCHEDULER)
+    }
+
+    pub fn client_exchange_credits(&self) -> Option<usize> {
+        Some(self.get_usize_setting(BALLISTA_CLIENT_EXCHANGE_CREDITS))
+            .filter(|credits| *credits > 0)
+    }
+
+    pub fn client_collect_stage(&self) -> bool {
+        self.get_bool_setting(BALLISTA_CLIENT_COLLECT_STAGE)
+    }
+
+    pub fn job_max_concurrent_stage_tasks(&self) -> Option<usize> {
+        Some(self.get_usize_setting(BALLISTA_JOB_MAX_CONCURRENT_STAGE_TASKS))
+            .filter(|max_tasks| *max_tasks > 0)
+    }
+
+    /// The max number of running tasks by stage id of the stages with a limit of their own
+    pub fn job_max_concurrent_tasks_by_stage(&self) -> HashMap<usize, usize> {
+        // infallible because we validate all configs in the constructor
+        Self::parse_tasks_by_stage(
+            &self.get_string_setting(BALLISTA_JOB_MAX_CONCURRENT_TASKS_BY_STAGE),
+        )
+        .unwrap()
+    }
+
+    pub fn stage_max_tasks_per_executor(&self) -> Option<usize> {
+        Some(self.get_usize_setting(BALLISTA_STAGE_MAX_TASKS_PER_EXECUTOR))
+            .filter(|max_tasks| *max_tasks > 0)
+    }
+
+    pub fn stage_pipelined(&self) -> bool {
+        self.get_bool_setting(BALLISTA_STAGE_PIPELINED)
+    }
+
+    pub fn standalone_discovery_file(&self) -> Option<String> {
+        Some(self.get_string_setting(BALLISTA_STANDALONE_DISCOVERY_FILE))
+            .filter(|path| !path.is_empty())
+    }
+
+    pub fn shuffle_merge_fetch_threshold(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SHUFFLE_MERGE_FETCH_THRESHOLD)
+    }
+
+    pub fn shuffle_prefetch_batches(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SHUFFLE_PREFETCH_BATCHES)
+    }
+
+    pub fn shuffle_coalesce_batches(&self) -> bool {
+        self.get_bool_setting(BALLISTA_SHUFFLE_COALESCE_BATCHES)
+    }
+
+    pub fn shuffle_write_buffer_size(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SHUFFLE_WRITE_BUFFER_SIZE)
+    }
+
+    pub fn scan_target_bytes_per_task(&self) -> Option<usize> {
+        Some(self.get_usize_setting(BALLISTA_SCAN_TARGET_BYTES_PER_TASK))
+            .filter(|target_bytes| *target_bytes > 0)
+    }
+
+    pub fn results_max_rows(&self) -> Option<usize> {
+        Some(self.get_usize_setting(BALLISTA_RESULTS_MAX_ROWS))
+            .filter(|max_rows| *max_rows > 0)
+    }
+
+    pub fn results_max_bytes(&self) -> Option<usize> {
+        Some(self.get_usize_setting(BALLISTA_RESULTS_MAX_BYTES))
+            .filter(|max_bytes| *max_bytes > 0)
+    }
+
+    pub fn results_truncate(&self) -> bool {
+        self.get_bool_setting(BALLISTA_RESULTS_TRUNCATE)
+    }
+
+    /// The task distribution policy of the jobs, if it overrides the one of the scheduler
+    pub fn task_distribution(&self) -> Option<String> {
+        Some(self.get_string_setting(BALLISTA_TASK_DISTRIBUTION))
+            .filter(|policy| !policy.is_empty())
+    }
+
+    pub fn join_runtime_filters(&self) -> bool {
+        self.get_bool_setting(BALLISTA_JOIN_RUNTIME_FILTERS)
+    }
+
+    pub fn join_bloom_filter_bytes(&self) -> Option<usize> {
+        Some(self.get_usize_setting(BALLISTA_JOIN_BLOOM_FILTER_BYTES))
+            .filter(|num_bytes| *num_bytes > 0)
+    }
+
     pub fn repartition_joins(&self) -> bool {
         self.get_bool_setting(BALLISTA_REPARTITION_JOINS)
     }
@@ -261,6 +569,10 @@ impl BallistaConfig {
         self.get_bool_setting(BALLISTA_PARQUET_PRUNING)
     }
 
+    pub fn parquet_prune_row_groups(&self) -> bool {
+        self.get_bool_setting(BALLISTA_PARQUET_PRUNE_ROW_GROUPS)
+    }
+
     pub fn collect_statistics(&self) -> bool {
         self.get_bool_setting(BALLISTA_COLLECT_STATISTICS)
     }
@@ -327,6 +639,33 @@ impl parse_arg::ParseArgFromStr for TaskSchedulingPolicy {
     }
 }
 
+// an enum used to configure how the executors spread the shuffle files over their work dirs
+// needs to be visible to code generated by configure_me
+#[derive(Clone, ArgEnum, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum WorkDirPlacement {
+    /// The tasks write to the work dirs in turn
+    RoundRobin,
+    /// The tasks write to the work dirs in proportion to the free space of their disks
+    FreeSpace,
+}
+
+impl std::str::FromStr for WorkDirPlacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ArgEnum::from_str(s, true)
+    }
+}
+
+impl parse_arg::ParseArgFromStr for WorkDirPlacement {
+    fn describe_type<W: fmt::Write>(mut writer: W) -> fmt::Result {
+        write!(
+            writer,
+            "The placement of the shuffle files over the work dirs"
+        )
+    }
+}
+
 // an enum used to configure the log rolling policy
 // needs to be visible to code generated by configure_me
 #[derive(Clone, ArgEnum, Copy, Debug, serde::Deserialize)]
@@ -382,6 +721,25 @@ mod tests {
         assert_eq!(16, config.default_shuffle_partitions());
         assert!(!config.default_with_information_schema());
         assert_eq!(16777216, config.default_grpc_client_max_message_size());
+        assert_eq!(4194304, config.memory_table_max_size());
+        assert!(config.client_ordered_fetch());
+        assert!(!config.cte_materialize());
+        assert_eq!(None, config.client_zone());
+        assert!(!config.client_fetch_via_scheduler());
+        assert_eq!(None, config.client_exchange_credits());
+        assert!(!config.client_collect_stage());
+        assert_eq!(None, config.job_max_concurrent_stage_tasks());
+        assert!(config.job_max_concurrent_tasks_by_stage().is_empty());
+        assert_eq!(None, config.stage_max_tasks_per_executor());
+        assert_eq!(None, config.standalone_discovery_file());
+        assert_eq!(None, config.scan_target_bytes_per_task());
+        assert!(!config.parquet_prune_row_groups());
+        assert_eq!(None, config.results_max_rows());
+        assert_eq!(None, config.results_max_bytes());
+        assert!(!config.results_truncate());
+        assert_eq!(None, config.task_distribution());
+        assert!(!config.join_runtime_filters());
+        assert_eq!(None, config.join_bloom_filter_bytes());
         Ok(())
     }
 
@@ -401,6 +759,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn max_concurrent_tasks_by_stage() -> Result<()> {
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_JOB_MAX_CONCURRENT_STAGE_TASKS, "8")
+            .set(BALLISTA_JOB_MAX_CONCURRENT_TASKS_BY_STAGE, "1:4, 3:0")
+            .build()?;
+        assert_eq!(Some(8), config.job_max_concurrent_stage_tasks());
+        assert_eq!(
+            HashMap::from([(1, 4), (3, 0)]),
+            config.job_max_concurrent_tasks_by_stage()
+        );
+
+        for invalid in ["1", "1:x", "a:2"] {
+            let config = BallistaConfig::builder()
+                .set(BALLISTA_JOB_MAX_CONCURRENT_TASKS_BY_STAGE, invalid)
+                .build();
+            assert!(config.is_err(), "{invalid} should be rejected");
+        }
+        Ok(())
+    }
+
     #[test]
     fn custom_config_invalid() -> Result<()> {
         let config = BallistaConfig::builder()
@@ -417,4 +796,29 @@ mod tests {
         assert_eq!("General(\"Failed to parse user-supplied value 'ballista.with_information_schema' for configuration setting '123': ParseBoolError\")", format!("{:?}", config.unwrap_err()));
         Ok(())
     }
+
+    #[test]
+    fn datafusion_settings() -> Result<()> {
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_BATCH_SIZE, "1024")
+            .set("datafusion.execution.batch_size", "2048")
+            .set("datafusion.execution.parquet.pruning", "false")
+            .build()?;
+        let session_config = config.apply_datafusion_settings(
+            SessionConfig::new().with_batch_size(config.default_batch_size()),
+        );
+        assert_eq!(2048, session_config.batch_size());
+        assert!(!session_config.parquet_pruning());
+
+        let config = BallistaConfig::builder()
+            .set("datafusion.execution.batch_size", "true")
+            .build();
+        assert!(config.is_err());
+
+        let config = BallistaConfig::builder()
+            .set("datafusion.no_such_option", "1")
+            .build();
+        assert!(config.is_err());
+        Ok(())
+    }
 }
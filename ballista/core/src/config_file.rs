@@ -0,0 +1,179 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Configuration files of the scheduler and executor binaries.
+//!
+//! The binaries layer their configuration: the defaults are overridden by the default
+//! configuration file, then by the `BALLISTA_SCHEDULER_*` or `BALLISTA_EXECUTOR_*`
+//! environment variables, then by the configuration file given on the command line, then by
+//! the command line flags. The configuration file given with `--config` may be a TOML or a
+//! YAML file with the same keys as the flags.
+
+use crate::error::{BallistaError, Result};
+use std::ffi::OsString;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempPath;
+
+/// Command line option of the binaries giving a TOML or YAML configuration file
+pub const CONFIG_OPTION: &str = "--config";
+/// Command line option of the configuration file understood by the argument parser of the
+/// binaries, which only reads TOML files
+pub const CONFIG_FILE_OPTION: &str = "--config-file";
+/// Subcommand of the binaries printing their effective configuration instead of starting
+pub const PRINT_CONFIG_COMMAND: &str = "print-config";
+
+/// Command line arguments rewritten by [config_file_args]
+pub struct ConfigFileArgs {
+    /// The arguments to give to the argument parser of the binaries
+    pub args: Vec<OsString>,
+    /// The TOML files converted from YAML files, removed when dropped
+    _toml_files: Vec<TempPath>,
+}
+
+/// Rewrite the `--config <file>` option of the command line arguments into the
+/// `--config-file` option of the argument parser of the binaries. YAML files, recognized
+/// by their `.yaml` or `.yml` extension, are converted to a TOML file in the temporary
+/// directory, only readable by the current user and removed when the returned arguments
+/// are dropped, so they should be kept until the arguments are parsed.
+pub fn config_file_args(
+    args: impl IntoIterator<Item = OsString>,
+) -> Result<ConfigFileArgs> {
+    let mut args = args.into_iter();
+    let mut rewritten = vec![];
+    let mut toml_files = vec![];
+    while let Some(arg) = args.next() {
+        let path = match arg.to_str() {
+            Some(CONFIG_OPTION) => args.next().ok_or_else(|| {
+                BallistaError::General(format!("Missing value of {CONFIG_OPTION}"))
+            })?,
+            Some(arg) if arg.starts_with(&format!("{CONFIG_OPTION}=")) => {
+                OsString::from(&arg[CONFIG_OPTION.len() + 1..])
+            }
+            _ => {
+                rewritten.push(arg);
+                continue;
+            }
+        };
+        rewritten.push(OsString::from(CONFIG_FILE_OPTION));
+        match toml_config_file(Path::new(&path))? {
+            Some(toml_file) => {
+                rewritten.push(toml_file.as_os_str().to_owned());
+                toml_files.push(toml_file);
+            }
+            None => rewritten.push(path),
+        }
+    }
+    Ok(ConfigFileArgs {
+        args: rewritten,
+        _toml_files: toml_files,
+    })
+}
+
+/// Whether the remaining command line arguments ask for the effective configuration
+pub fn is_print_config(remaining_args: impl IntoIterator<Item = OsString>) -> bool {
+    remaining_args
+        .into_iter()
+        .any(|arg| arg.to_str() == Some(PRINT_CONFIG_COMMAND))
+}
+
+/// Convert a YAML configuration file to a temporary TOML file, or return `None` for the
+/// other files
+fn toml_config_file(path: &Path) -> Result<Option<TempPath>> {
+    let is_yaml = matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("yaml" | "yml")
+    );
+    if !is_yaml {
+        return Ok(None);
+    }
+
+    let yaml = fs::read_to_string(path)?;
+    let toml = yaml_to_toml(&yaml).map_err(|e| {
+        BallistaError::General(format!(
+            "Invalid YAML configuration file {}: {e}",
+            path.display()
+        ))
+    })?;
+    // the file is created with mode 0600, the configuration may hold secrets
+    let mut toml_file = tempfile::Builder::new()
+        .prefix("ballista-config-")
+        .suffix(".toml")
+        .tempfile()?;
+    toml_file.write_all(toml.as_bytes())?;
+    Ok(Some(toml_file.into_temp_path()))
+}
+
+fn yaml_to_toml(yaml: &str) -> std::result::Result<String, String> {
+    let value: toml::Value = serde_yaml::from_str(yaml).map_err(|e| e.to_string())?;
+    toml::to_string(&value).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_file_args() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let yaml_path = dir.path().join("scheduler.yaml");
+        fs::write(&yaml_path, "bind_port: 50051\nexternal_host: scheduler\n")?;
+
+        let config_args = config_file_args(vec![
+            OsString::from("ballista-scheduler"),
+            OsString::from(format!("--config={}", yaml_path.display())),
+            OsString::from("--bind-port"),
+            OsString::from("50052"),
+        ])?;
+        let args = &config_args.args;
+        assert_eq!(5, args.len());
+        assert_eq!(OsString::from(CONFIG_FILE_OPTION), args[1]);
+        let toml = fs::read_to_string(&args[2])?;
+        let value: toml::Value =
+            toml::from_str(&toml).map_err(|e| BallistaError::General(e.to_string()))?;
+        assert_eq!(Some(50051), value["bind_port"].as_integer());
+        assert_eq!(Some("scheduler"), value["external_host"].as_str());
+        assert_eq!(OsString::from("--bind-port"), args[3]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&args[2])?.permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+        let toml_path = args[2].clone();
+        drop(config_args);
+        assert!(!Path::new(&toml_path).exists());
+
+        // TOML files are passed through
+        let args = config_file_args(vec![
+            OsString::from(CONFIG_OPTION),
+            OsString::from("scheduler.toml"),
+        ])?
+        .args;
+        assert_eq!(
+            vec![
+                OsString::from(CONFIG_FILE_OPTION),
+                OsString::from("scheduler.toml")
+            ],
+            args
+        );
+
+        assert!(is_print_config(vec![OsString::from(PRINT_CONFIG_COMMAND)]));
+        Ok(())
+    }
+}
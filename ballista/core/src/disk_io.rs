@@ -0,0 +1,264 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Scheduling of the blocking disk IO of an executor.
+//!
+//! The shuffle files written by the tasks and the shuffle files read to serve the Flight
+//! requests of the other executors share the same disks. Each kind of IO runs in a pool of
+//! its own, bounding the number of concurrent operations, so that a burst of writes cannot
+//! stall the reads until the fetches of the other executors time out.
+
+use std::fmt::{Debug, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use tokio::sync::Semaphore;
+
+use crate::error::{BallistaError, Result};
+use crate::serde::protobuf;
+
+/// The default maximum number of concurrent shuffle write operations of an executor
+pub const DEFAULT_DISK_WRITE_IO_CONCURRENCY: usize = 8;
+
+/// The default maximum number of concurrent shuffle read operations of an executor
+pub const DEFAULT_DISK_READ_IO_CONCURRENCY: usize = 16;
+
+/// A pool running blocking disk IO operations on the blocking threads of the runtime, at
+/// most `max_concurrency` at a time
+pub struct DiskIoPool {
+    name: String,
+    permits: Arc<Semaphore>,
+    max_concurrency: usize,
+    counters: Arc<DiskIoCounters>,
+}
+
+#[derive(Default)]
+struct DiskIoCounters {
+    running: AtomicU64,
+    queued: AtomicU64,
+    completed: AtomicU64,
+    wait_time_nanos: AtomicU64,
+}
+
+/// Counts an operation in `counter` until dropped, so that the operations whose caller
+/// stopped waiting, e.g. as its future was dropped, are no longer counted
+struct CountGuard<'a>(&'a AtomicU64);
+
+impl<'a> CountGuard<'a> {
+    fn new(counter: &'a AtomicU64) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for CountGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Debug for DiskIoPool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskIoPool")
+            .field("name", &self.name)
+            .field("max_concurrency", &self.max_concurrency)
+            .finish()
+    }
+}
+
+impl DiskIoPool {
+    /// Create a pool running at most `max_concurrency` operations at a time, 0 for no limit
+    pub fn new(name: impl Into<String>, max_concurrency: usize) -> Self {
+        let max_concurrency = if max_concurrency == 0 {
+            Semaphore::MAX_PERMITS
+        } else {
+            max_concurrency.min(Semaphore::MAX_PERMITS)
+        };
+        Self {
+            name: name.into(),
+            permits: Arc::new(Semaphore::new(max_concurrency)),
+            max_concurrency,
+            counters: Arc::new(DiskIoCounters::default()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run a blocking operation once the pool has room for it. The operation runs to
+    /// completion even if the returned future is dropped once it started.
+    pub async fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T> {
+        let queued_at = Instant::now();
+        let queued = CountGuard::new(&self.counters.queued);
+        let permit = self.permits.clone().acquire_owned().await;
+        drop(queued);
+        let permit = permit.map_err(|e| {
+            BallistaError::Internal(format!("Disk IO pool {} is closed: {e}", self.name))
+        })?;
+        self.counters
+            .wait_time_nanos
+            .fetch_add(queued_at.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+        let counters = self.counters.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let running = CountGuard::new(&counters.running);
+            let result = f();
+            drop(running);
+            counters.completed.fetch_add(1, Ordering::Relaxed);
+            drop(permit);
+            result
+        })
+        .await;
+        Ok(result?)
+    }
+
+    /// Snapshot of the metrics of the pool, reported by the executor with its heartbeats
+    pub fn metrics(&self) -> protobuf::DiskIoMetrics {
+        protobuf::DiskIoMetrics {
+            pool: self.name.clone(),
+            max_concurrency: self.max_concurrency as u64,
+            running: self.counters.running.load(Ordering::Relaxed),
+            queued: self.counters.queued.load(Ordering::Relaxed),
+            completed: self.counters.completed.load(Ordering::Relaxed),
+            wait_time_nanos: self.counters.wait_time_nanos.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The disk IO pools of an executor, see the [module](self) documentation
+#[derive(Debug, Clone)]
+pub struct DiskIoScheduler {
+    /// The pool writing the shuffle files of the tasks
+    pub write: Arc<DiskIoPool>,
+    /// The pool reading the shuffle files served to the other executors
+    pub read: Arc<DiskIoPool>,
+}
+
+impl DiskIoScheduler {
+    pub fn new(write_concurrency: usize, read_concurrency: usize) -> Self {
+        Self {
+            write: Arc::new(DiskIoPool::new("write", write_concurrency)),
+            read: Arc::new(DiskIoPool::new("read", read_concurrency)),
+        }
+    }
+}
+
+impl Default for DiskIoScheduler {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_DISK_WRITE_IO_CONCURRENCY,
+            DEFAULT_DISK_READ_IO_CONCURRENCY,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::DiskIoPool;
+    use crate::error::Result;
+
+    #[tokio::test]
+    async fn test_disk_io_pool() -> Result<()> {
+        let pool = Arc::new(DiskIoPool::new("write", 2));
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+
+        let operations = (0..8).map(|i| {
+            let pool = pool.clone();
+            let running = running.clone();
+            let max_running = max_running.clone();
+            tokio::spawn(async move {
+                pool.run(move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(10));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    i
+                })
+                .await
+            })
+        });
+        let mut results = vec![];
+        for operation in operations.collect::<Vec<_>>() {
+            results.push(operation.await??);
+        }
+
+        assert_eq!(results, (0..8).collect::<Vec<_>>());
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+        let metrics = pool.metrics();
+        assert_eq!(metrics.pool, "write");
+        assert_eq!(metrics.max_concurrency, 2);
+        assert_eq!(metrics.completed, 8);
+        assert_eq!(metrics.running, 0);
+        assert_eq!(metrics.queued, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disk_io_pool_dropped_callers() -> Result<()> {
+        let pool = Arc::new(DiskIoPool::new("read", 1));
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (finish_tx, finish_rx) = std::sync::mpsc::channel::<()>();
+
+        // the first operation holds the pool while the second one waits for it
+        let running = tokio::spawn({
+            let pool = pool.clone();
+            async move {
+                pool.run(move || {
+                    started_tx.send(()).unwrap();
+                    finish_rx.recv().unwrap();
+                })
+                .await
+            }
+        });
+        tokio::task::spawn_blocking(move || started_rx.recv().unwrap()).await?;
+        let queued = tokio::spawn({
+            let pool = pool.clone();
+            async move { pool.run(|| ()).await }
+        });
+        while pool.metrics().queued == 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(pool.metrics().running, 1);
+
+        // the callers stop waiting, the running operation still runs to completion
+        queued.abort();
+        running.abort();
+        assert!(queued.await.unwrap_err().is_cancelled());
+        assert!(running.await.unwrap_err().is_cancelled());
+        assert_eq!(pool.metrics().queued, 0);
+        assert_eq!(pool.metrics().running, 1);
+
+        finish_tx.send(()).unwrap();
+        while pool.metrics().running > 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        assert_eq!(pool.metrics().completed, 1);
+        pool.run(|| ()).await?;
+        assert_eq!(pool.metrics().completed, 2);
+        Ok(())
+    }
+}
@@ -15,12 +15,15 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use log::{error, info};
-use tokio::sync::mpsc;
+use parking_lot::Mutex;
+use tokio::sync::{mpsc, oneshot, Notify, Semaphore};
 
 use crate::error::{BallistaError, Result};
 
@@ -30,23 +33,43 @@ pub trait EventAction<E>: Send + Sync {
 
     fn on_stop(&self);
 
-    async fn on_receive(
-        &self,
-        event: E,
-        tx_event: &mpsc::Sender<E>,
-        rx_event: &mpsc::Receiver<E>,
-    ) -> Result<()>;
+    async fn on_receive(&self, event: E, tx_event: &mpsc::Sender<E>) -> Result<()>;
 
     fn on_error(&self, error: BallistaError);
+
+    /// The key of the event, e.g. its job. The events of a key are processed in the order
+    /// they are received, while the events of different keys may be processed
+    /// concurrently if the event loop has a concurrency greater than one. The events of
+    /// the empty key, which may touch the state of any key, are processed alone, once the
+    /// events received before them are processed and before the events received after
+    /// them.
+    fn event_key(&self, _event: &E) -> String {
+        String::new()
+    }
+
+    /// Whether the event is dropped when an event of the same non-empty key is waiting to
+    /// be processed, as the waiting event does the same, e.g. a request to revive the
+    /// offers of the executors
+    fn is_coalesced(&self, _event: &E) -> bool {
+        false
+    }
+
+    /// Called when an event of `event_key` was processed, `latency` after it was received
+    fn on_processed(&self, _event_key: &str, _latency: Duration) {}
 }
 
 #[derive(Clone)]
 pub struct EventLoop<E> {
     pub name: String,
     pub buffer_size: usize,
+    /// The maximum number of events of different keys processed at once
+    pub concurrency: usize,
     stopped: Arc<AtomicBool>,
     action: Arc<dyn EventAction<E>>,
     tx_event: Option<mpsc::Sender<E>>,
+    /// Requests to flush the event loop, answered with the number of events received so
+    /// far once they are processed
+    tx_flush: Option<mpsc::UnboundedSender<oneshot::Sender<usize>>>,
 }
 
 impl<E: Send + 'static> EventLoop<E> {
@@ -58,13 +81,26 @@ impl<E: Send + 'static> EventLoop<E> {
         Self {
             name,
             buffer_size,
+            concurrency: 1,
             stopped: Arc::new(AtomicBool::new(false)),
             action,
             tx_event: None,
+            tx_flush: None,
         }
     }
 
-    fn run(&self, mut rx_event: mpsc::Receiver<E>) {
+    /// Process up to `concurrency` events of different keys at once, see
+    /// [EventAction::event_key]
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    fn run(
+        &self,
+        mut rx_event: mpsc::Receiver<E>,
+        mut rx_flush: mpsc::UnboundedReceiver<oneshot::Sender<usize>>,
+    ) {
         assert!(
             self.tx_event.is_some(),
             "The event sender should be initialized first!"
@@ -73,17 +109,50 @@ impl<E: Send + 'static> EventLoop<E> {
         let name = self.name.clone();
         let stopped = self.stopped.clone();
         let action = self.action.clone();
+        let concurrency = self.concurrency;
         tokio::spawn(async move {
             info!("Starting the event loop {}", name);
+            let lanes = Arc::new(EventLanes::new(concurrency));
+            let mut received = 0;
             while !stopped.load(Ordering::SeqCst) {
-                if let Some(event) = rx_event.recv().await {
-                    if let Err(e) = action.on_receive(event, &tx_event, &rx_event).await {
-                        error!("Fail to process event due to {}", e);
-                        action.on_error(e);
+                tokio::select! {
+                    // the events sent before a flush request are received before it
+                    biased;
+                    event = rx_event.recv() => {
+                        let Some(event) = event else {
+                            info!("Event Channel closed, shutting down");
+                            break;
+                        };
+                        received += 1;
+                        let received_at = Instant::now();
+                        let key = action.event_key(&event);
+                        if concurrency <= 1 || key.is_empty() {
+                            // no event is received while the event of the empty key waits
+                            lanes.wait_idle().await;
+                            process_event(action.as_ref(), event, &tx_event).await;
+                            action.on_processed(&key, received_at.elapsed());
+                        } else if action.is_coalesced(&event) && lanes.is_waiting(&key) {
+                            // the waiting event of the key is processed after this one
+                            // was received, doing the same
+                        } else if let Some(event) =
+                            lanes.enqueue(&key, event, received_at)
+                        {
+                            tokio::spawn(run_lane(
+                                lanes.clone(),
+                                key,
+                                event,
+                                received_at,
+                                action.clone(),
+                                tx_event.clone(),
+                            ));
+                        }
+                    }
+                    Some(tx_reply) = rx_flush.recv() => {
+                        // like an event of the empty key, the flush request waits for
+                        // the events received before it
+                        lanes.wait_idle().await;
+                        let _ = tx_reply.send(received);
                     }
-                } else {
-                    info!("Event Channel closed, shutting down");
-                    break;
                 }
             }
             info!("The event loop {} has been stopped", name);
@@ -100,8 +169,10 @@ impl<E: Send + 'static> EventLoop<E> {
         self.action.on_start();
 
         let (tx_event, rx_event) = mpsc::channel::<E>(self.buffer_size);
+        let (tx_flush, rx_flush) = mpsc::unbounded_channel();
         self.tx_event = Some(tx_event);
-        self.run(rx_event);
+        self.tx_flush = Some(tx_flush);
+        self.run(rx_event, rx_flush);
 
         Ok(())
     }
@@ -114,6 +185,34 @@ impl<E: Send + 'static> EventLoop<E> {
         }
     }
 
+    /// Wait until all the events posted so far, and the events posted while processing
+    /// them, have been processed. Returns false if events are still pending after `timeout`.
+    pub async fn flush(&self, timeout: Duration) -> bool {
+        let Some(tx_flush) = self.tx_flush.as_ref() else {
+            return true;
+        };
+        let flushed = async {
+            let mut last_received = None;
+            loop {
+                let (tx_reply, rx_reply) = oneshot::channel();
+                if tx_flush.send(tx_reply).is_err() {
+                    // the event loop is stopped
+                    return;
+                }
+                let Ok(received) = rx_reply.await else {
+                    return;
+                };
+                // the events received before the previous request were processed, and
+                // none was posted while processing them if none was received since
+                if last_received == Some(received) {
+                    return;
+                }
+                last_received = Some(received);
+            }
+        };
+        tokio::time::timeout(timeout, flushed).await.is_ok()
+    }
+
     pub fn get_sender(&self) -> Result<EventSender<E>> {
         Ok(EventSender {
             tx_event: self.tx_event.as_ref().cloned().ok_or_else(|| {
@@ -123,6 +222,102 @@ impl<E: Send + 'static> EventLoop<E> {
     }
 }
 
+async fn process_event<E: Send>(
+    action: &dyn EventAction<E>,
+    event: E,
+    tx_event: &mpsc::Sender<E>,
+) {
+    if let Err(e) = action.on_receive(event, tx_event).await {
+        error!("Fail to process event due to {}", e);
+        action.on_error(e);
+    }
+}
+
+/// The events waiting for the event of the same key being processed, when the events of
+/// different keys are processed concurrently
+struct EventLanes<E> {
+    /// The events waiting of the keys with an event being processed
+    waiting: Mutex<HashMap<String, VecDeque<(E, Instant)>>>,
+    /// The permits of processing an event, bounding the concurrency
+    permits: Semaphore,
+    /// Notified when the last event of a key was processed
+    lane_done: Notify,
+}
+
+impl<E> EventLanes<E> {
+    fn new(concurrency: usize) -> Self {
+        Self {
+            waiting: Mutex::new(HashMap::new()),
+            permits: Semaphore::new(concurrency),
+            lane_done: Notify::new(),
+        }
+    }
+
+    /// Wait until no event of any key is being processed or waiting
+    async fn wait_idle(&self) {
+        while !self.waiting.lock().is_empty() {
+            self.lane_done.notified().await;
+        }
+    }
+
+    /// Whether an event of the key is waiting for the event being processed
+    fn is_waiting(&self, key: &str) -> bool {
+        self.waiting
+            .lock()
+            .get(key)
+            .map(|events| !events.is_empty())
+            .unwrap_or(false)
+    }
+
+    /// Queue the event behind the event of the same key being processed, if any, or else
+    /// return it to be processed right away
+    fn enqueue(&self, key: &str, event: E, received_at: Instant) -> Option<E> {
+        let mut waiting = self.waiting.lock();
+        match waiting.get_mut(key) {
+            Some(events) => {
+                events.push_back((event, received_at));
+                None
+            }
+            None => {
+                waiting.insert(key.to_owned(), VecDeque::new());
+                Some(event)
+            }
+        }
+    }
+
+    /// The next event of the key to process, if any
+    fn next(&self, key: &str) -> Option<(E, Instant)> {
+        let mut waiting = self.waiting.lock();
+        let next = waiting.get_mut(key).and_then(VecDeque::pop_front);
+        if next.is_none() {
+            waiting.remove(key);
+            self.lane_done.notify_one();
+        }
+        next
+    }
+}
+
+/// Process the events of a key in order until none is waiting
+async fn run_lane<E: Send + 'static>(
+    lanes: Arc<EventLanes<E>>,
+    key: String,
+    event: E,
+    received_at: Instant,
+    action: Arc<dyn EventAction<E>>,
+    tx_event: mpsc::Sender<E>,
+) {
+    let mut next = Some((event, received_at));
+    while let Some((event, received_at)) = next.take() {
+        {
+            // a permit is taken for every event so that the other keys get their turn
+            let _permit = lanes.permits.acquire().await.expect("never closed");
+            process_event(action.as_ref(), event, &tx_event).await;
+        }
+        action.on_processed(&key, received_at.elapsed());
+        next = lanes.next(&key);
+    }
+}
+
 #[derive(Clone)]
 pub struct EventSender<E> {
     tx_event: mpsc::Sender<E>,
@@ -140,3 +335,172 @@ impl<E> EventSender<E> {
             .map_err(|e| BallistaError::General(format!("Fail to send event due to {e}")))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct CountingAction {
+        processed: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl EventAction<usize> for CountingAction {
+        fn on_start(&self) {}
+
+        fn on_stop(&self) {}
+
+        async fn on_receive(
+            &self,
+            event: usize,
+            tx_event: &mpsc::Sender<usize>,
+        ) -> Result<()> {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            self.processed.fetch_add(1, Ordering::SeqCst);
+            // events posted while processing are flushed too
+            if event > 0 {
+                tx_event.send(event - 1).await.unwrap();
+            }
+            Ok(())
+        }
+
+        fn on_error(&self, _error: BallistaError) {}
+    }
+
+    #[tokio::test]
+    async fn test_flush() -> Result<()> {
+        let action = Arc::new(CountingAction {
+            processed: AtomicUsize::new(0),
+        });
+        let mut event_loop = EventLoop::new("test".to_owned(), 16, action.clone());
+        event_loop.start()?;
+
+        let sender = event_loop.get_sender()?;
+        sender.post_event(3).await?;
+        sender.post_event(0).await?;
+        assert!(event_loop.flush(Duration::from_secs(5)).await);
+        assert_eq!(5, action.processed.load(Ordering::SeqCst));
+
+        sender.post_event(1000).await?;
+        assert!(!event_loop.flush(Duration::from_millis(50)).await);
+        event_loop.stop();
+        Ok(())
+    }
+
+    /// Sleeps for the given milliseconds, recording the events in the order they
+    /// finished, the events of the `revive` key being coalesced
+    struct KeyedAction {
+        finished: Mutex<Vec<(String, u64)>>,
+    }
+
+    #[async_trait]
+    impl EventAction<(String, u64)> for KeyedAction {
+        fn on_start(&self) {}
+
+        fn on_stop(&self) {}
+
+        async fn on_receive(
+            &self,
+            event: (String, u64),
+            _tx_event: &mpsc::Sender<(String, u64)>,
+        ) -> Result<()> {
+            tokio::time::sleep(Duration::from_millis(event.1)).await;
+            self.finished.lock().push(event);
+            Ok(())
+        }
+
+        fn on_error(&self, _error: BallistaError) {}
+
+        fn event_key(&self, event: &(String, u64)) -> String {
+            event.0.clone()
+        }
+
+        fn is_coalesced(&self, event: &(String, u64)) -> bool {
+            event.0 == "revive"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_keys() -> Result<()> {
+        let action = Arc::new(KeyedAction {
+            finished: Mutex::new(vec![]),
+        });
+        let mut event_loop =
+            EventLoop::new("test".to_owned(), 16, action.clone()).with_concurrency(2);
+        event_loop.start()?;
+
+        let sender = event_loop.get_sender()?;
+        for event in [("a", 200), ("a", 0), ("b", 0), ("b", 50)] {
+            sender.post_event((event.0.to_owned(), event.1)).await?;
+        }
+        assert!(event_loop.flush(Duration::from_secs(5)).await);
+
+        // the events of a key are processed in order, without waiting for the other keys
+        let finished: Vec<(&str, u64)> = action
+            .finished
+            .lock()
+            .iter()
+            .map(|(key, millis)| (key.as_str(), *millis))
+            .collect();
+        assert_eq!(finished, vec![("b", 0), ("b", 50), ("a", 200), ("a", 0)]);
+        event_loop.stop();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_empty_key_barrier() -> Result<()> {
+        let action = Arc::new(KeyedAction {
+            finished: Mutex::new(vec![]),
+        });
+        let mut event_loop =
+            EventLoop::new("test".to_owned(), 16, action.clone()).with_concurrency(2);
+        event_loop.start()?;
+
+        let sender = event_loop.get_sender()?;
+        for event in [("a", 100), ("", 50), ("b", 0)] {
+            sender.post_event((event.0.to_owned(), event.1)).await?;
+        }
+        assert!(event_loop.flush(Duration::from_secs(5)).await);
+
+        // the event of the empty key waits for the events received before it, and the
+        // events received after it wait for it
+        let finished: Vec<(&str, u64)> = action
+            .finished
+            .lock()
+            .iter()
+            .map(|(key, millis)| (key.as_str(), *millis))
+            .collect();
+        assert_eq!(finished, vec![("a", 100), ("", 50), ("b", 0)]);
+        event_loop.stop();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_coalesced_events() -> Result<()> {
+        let action = Arc::new(KeyedAction {
+            finished: Mutex::new(vec![]),
+        });
+        let mut event_loop =
+            EventLoop::new("test".to_owned(), 16, action.clone()).with_concurrency(2);
+        event_loop.start()?;
+
+        let sender = event_loop.get_sender()?;
+        for event in [("a", 300), ("revive", 100), ("revive", 0), ("revive", 0)] {
+            sender.post_event((event.0.to_owned(), event.1)).await?;
+        }
+        assert!(event_loop.flush(Duration::from_secs(5)).await);
+
+        // the events of the key do not wait for the other keys, and an event received
+        // while another one waits is dropped
+        let finished: Vec<(&str, u64)> = action
+            .finished
+            .lock()
+            .iter()
+            .map(|(key, millis)| (key.as_str(), *millis))
+            .collect();
+        assert_eq!(finished, vec![("revive", 100), ("revive", 0), ("a", 300)]);
+        event_loop.stop();
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Bloom filters of the join keys, for the semi-join reduction of the partitioned hash
+//! joins. The build side stage of a join collects a bloom filter of its join key for every
+//! map task, which the scheduler merges into a single filter of all the build side
+//! keys. The probe side stage then only shuffles the rows whose join key may be in it.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use ahash::RandomState;
+use datafusion::arrow::array::{Array, ArrayRef, BooleanArray};
+use datafusion::arrow::compute::filter_record_batch;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::common::hash_utils::create_hashes;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_plan::metrics::{
+    BaselineMetrics, ExecutionPlanMetricsSet, MetricsSet,
+};
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
+    SendableRecordBatchStream, Statistics,
+};
+use futures::StreamExt;
+
+/// Number of bits set in a bloom filter for every value
+const NUM_HASHES: u64 = 3;
+
+/// A bloom filter of the values of a column, whose false positive rate depends on its size
+/// and on the number of distinct values added to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloomFilter {
+    bits: Vec<u8>,
+}
+
+impl BloomFilter {
+    /// Create an empty filter of `num_bytes` bytes
+    pub fn new(num_bytes: usize) -> Self {
+        Self {
+            bits: vec![0; num_bytes.max(1)],
+        }
+    }
+
+    /// The filter of the given bits, `None` if there are none
+    pub fn from_bytes(bits: Vec<u8>) -> Option<Self> {
+        (!bits.is_empty()).then_some(Self { bits })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bits
+    }
+
+    /// Add the values of the other filter to this filter. Returns `false`, leaving this
+    /// filter unchanged, if the filters are not of the same size.
+    pub fn union(&mut self, other: &BloomFilter) -> bool {
+        if self.bits.len() != other.bits.len() {
+            return false;
+        }
+        for (bits, other_bits) in self.bits.iter_mut().zip(&other.bits) {
+            *bits |= other_bits;
+        }
+        true
+    }
+
+    /// Add the non null values of the array to the filter
+    pub fn insert(&mut self, values: &ArrayRef) -> Result<()> {
+        for (i, hash) in hash_values(values)?.into_iter().enumerate() {
+            if values.is_valid(i) {
+                for position in self.positions(hash) {
+                    self.bits[position / 8] |= 1 << (position % 8);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the values of the array may be in the filter, `false` for the null values
+    pub fn contains(&self, values: &ArrayRef) -> Result<BooleanArray> {
+        let contained: Vec<bool> = hash_values(values)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, hash)| {
+                values.is_valid(i)
+                    && self.positions(hash).all(|position| {
+                        self.bits[position / 8] & (1 << (position % 8)) != 0
+                    })
+            })
+            .collect();
+        Ok(BooleanArray::from(contained))
+    }
+
+    /// The bits of a value, derived from its hash by double hashing
+    fn positions(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let num_bits = self.bits.len() as u64 * 8;
+        let (h1, h2) = (hash & u32::MAX as u64, (hash >> 32) | 1);
+        (0..NUM_HASHES).map(move |i| (h1.wrapping_add(i * h2) % num_bits) as usize)
+    }
+}
+
+/// The hashes of the values, which must be the same on every executor
+fn hash_values(values: &ArrayRef) -> Result<Vec<u64>> {
+    // fixed seeds, distinct from the ones of the hash partitioning as the hashes of the
+    // values of a shuffle partition are all equal modulo the number of partitions
+    let random_state = RandomState::with_seeds(7, 11, 13, 17);
+    let mut hashes = vec![0; values.len()];
+    create_hashes(&[values.clone()], &random_state, &mut hashes)?;
+    Ok(hashes)
+}
+
+/// BloomFilterExec only outputs the rows of its input whose value of a column may be in a
+/// [BloomFilter], which the scheduler creates from the build side of a join to filter its
+/// probe side.
+#[derive(Debug)]
+pub struct BloomFilterExec {
+    input: Arc<dyn ExecutionPlan>,
+    /// The filtered column of the input
+    pub column: Column,
+    pub filter: Arc<BloomFilter>,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl BloomFilterExec {
+    /// Create a new BloomFilterExec filtering the `column` of the input
+    pub fn try_new(
+        input: Arc<dyn ExecutionPlan>,
+        column: Column,
+        filter: Arc<BloomFilter>,
+    ) -> Result<Self> {
+        let schema = input.schema();
+        if schema.fields().len() <= column.index()
+            || schema.field(column.index()).name() != column.name()
+        {
+            return Err(DataFusionError::Plan(format!(
+                "BloomFilterExec expects the column {column} in its input"
+            )));
+        }
+        Ok(Self {
+            input,
+            column,
+            filter,
+            metrics: ExecutionPlanMetricsSet::new(),
+        })
+    }
+
+    pub fn input(&self) -> &Arc<dyn ExecutionPlan> {
+        &self.input
+    }
+}
+
+impl DisplayAs for BloomFilterExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "BloomFilterExec: column={}, bytes={}",
+                    self.column,
+                    self.filter.as_bytes().len()
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for BloomFilterExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        match children.as_slice() {
+            [input] => Ok(Arc::new(Self::try_new(
+                input.clone(),
+                self.column.clone(),
+                self.filter.clone(),
+            )?)),
+            _ => Err(DataFusionError::Plan(
+                "BloomFilterExec expects exactly one child".to_owned(),
+            )),
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.input.execute(partition, context)?;
+        let index = self.column.index();
+        let filter = self.filter.clone();
+        let baseline_metrics = BaselineMetrics::new(&self.metrics, partition);
+        let stream = input.map(move |batch| {
+            let _timer = baseline_metrics.elapsed_compute().timer();
+            let batch = batch?;
+            let contained = filter.contains(batch.column(index))?;
+            let batch = filter_record_batch(&batch, &contained)?;
+            baseline_metrics.record_output(batch.num_rows());
+            Ok(batch)
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            stream,
+        )))
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int64Array, StringArray};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::physical_plan::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+
+    #[test]
+    fn test_bloom_filter() -> Result<()> {
+        let mut filter = BloomFilter::new(1024);
+        let values: ArrayRef = Arc::new(Int64Array::from_iter_values(0..500));
+        filter.insert(&values)?;
+        assert!(filter.contains(&values)?.iter().all(|c| c == Some(true)));
+
+        // few of the other values are false positives
+        let others: ArrayRef = Arc::new(Int64Array::from_iter_values(1000..2000));
+        assert!(filter.contains(&others)?.true_count() < 100);
+
+        // null values are neither added nor contained
+        let nulls: ArrayRef = Arc::new(Int64Array::from(vec![None, Some(1)]));
+        let mut other = BloomFilter::new(1024);
+        other.insert(&nulls)?;
+        assert_eq!(
+            other.contains(&nulls)?,
+            BooleanArray::from(vec![false, true])
+        );
+
+        // the union contains the values of both filters, of the same size only
+        let more: ArrayRef = Arc::new(Int64Array::from(vec![5000]));
+        other.insert(&more)?;
+        assert!(filter.union(&other));
+        assert!(filter.contains(&more)?.value(0));
+        assert!(!filter.union(&BloomFilter::new(512)));
+        assert_eq!(BloomFilter::from_bytes(vec![]), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bloom_filter_exec() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Utf8, true),
+            Field::new("b", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![
+                    Some("x"),
+                    Some("y"),
+                    None,
+                    Some("x"),
+                ])),
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+            ],
+        )?;
+        let mut filter = BloomFilter::new(64);
+        filter.insert(&(Arc::new(StringArray::from(vec!["x"])) as ArrayRef))?;
+
+        let input = Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?);
+        let exec = Arc::new(BloomFilterExec::try_new(
+            input,
+            Column::new("a", 0),
+            Arc::new(filter),
+        )?);
+        let batches = collect(exec, Arc::new(TaskContext::default())).await?;
+        let values: Vec<i64> = batches
+            .iter()
+            .flat_map(|batch| {
+                let column = batch.column(1).as_any().downcast_ref::<Int64Array>();
+                column.unwrap().values().to_vec()
+            })
+            .collect();
+        assert_eq!(values, vec![1, 4]);
+        Ok(())
+    }
+}
@@ -17,35 +17,53 @@
 
 use crate::client::BallistaClient;
 use crate::config::BallistaConfig;
+use crate::execution_plans::{ShuffleReaderExec, UnresolvedShuffleExec};
+use crate::protocol::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
 use crate::serde::protobuf::execute_query_params::OptionalSessionId;
 use crate::serde::protobuf::{
     execute_query_params::Query, execute_query_result, job_status,
-    scheduler_grpc_client::SchedulerGrpcClient, ExecuteQueryParams, GetJobStatusParams,
-    GetJobStatusResult, PartitionLocation,
+    scheduler_grpc_client::SchedulerGrpcClient, CreateTemporaryTable, ExecuteQueryParams,
+    FailedJob, GetJobStatusParams, GetJobStatusResult, OutputSortColumn,
+    PartitionLocation,
 };
-use crate::utils::create_grpc_client_connection;
+use crate::serde::scheduler::PartitionLocation as ShufflePartitionLocation;
+use crate::serde::{BallistaLogicalExtensionCodec, BallistaPhysicalExtensionCodec};
+use crate::utils::{
+    backoff_with_jitter, batch_memory_size, create_grpc_client_connection,
+    session_config_props,
+};
+use datafusion::arrow::compute::SortOptions;
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::common::tree_node::{Transformed, TreeNode};
 use datafusion::error::{DataFusionError, Result};
 use datafusion::execution::context::TaskContext;
+use datafusion::execution::memory_pool::MemoryConsumer;
 use datafusion::logical_expr::LogicalPlan;
-use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_expr::{EquivalenceProperties, PhysicalSortExpr};
+use datafusion::physical_plan::metrics::{
+    BaselineMetrics, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::sorts::streaming_merge::streaming_merge;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use datafusion::physical_plan::{
     DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning,
     PlanProperties, SendableRecordBatchStream, Statistics,
 };
-use datafusion_proto::logical_plan::{
-    AsLogicalPlan, DefaultLogicalExtensionCodec, LogicalExtensionCodec,
-};
-use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt};
-use log::{error, info};
+use datafusion_proto::logical_plan::{AsLogicalPlan, LogicalExtensionCodec};
+use datafusion_proto::physical_plan::{AsExecutionPlan, PhysicalExtensionCodec};
+use datafusion_proto::protobuf::PhysicalPlanNode;
+use futures::{StreamExt, TryFutureExt, TryStreamExt};
+use log::{error, info, warn};
 use std::any::Any;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::time::Duration;
+use tonic::Code;
+use uuid::Uuid;
 
 /// This operator sends a logical plan to a Ballista scheduler for execution and
 /// polls the scheduler until the query is complete and then fetches the resulting
@@ -59,13 +77,22 @@ pub struct DistributedQueryExec<T: 'static + AsLogicalPlan> {
     config: BallistaConfig,
     /// Logical plan to execute
     plan: LogicalPlan,
+    /// SQL sent instead of the logical plan, for statements which only the scheduler
+    /// can plan
+    sql: Option<String>,
+    /// Temporary table of the session to keep the query output in, on the executors
+    temporary_table: Option<CreateTemporaryTable>,
     /// Codec for LogicalPlan extensions
     extension_codec: Arc<dyn LogicalExtensionCodec>,
+    /// Codec for the extensions of the final stage detached by the scheduler
+    physical_extension_codec: Arc<dyn PhysicalExtensionCodec>,
     /// Phantom data for serializable plan message
     plan_repr: PhantomData<T>,
     /// Session id
     session_id: String,
     properties: PlanProperties,
+    /// Statistics of the job output reported by the scheduler
+    metrics: ExecutionPlanMetricsSet,
 }
 
 impl<T: 'static + AsLogicalPlan> DistributedQueryExec<T> {
@@ -80,10 +107,14 @@ impl<T: 'static + AsLogicalPlan> DistributedQueryExec<T> {
             scheduler_url,
             config,
             plan,
-            extension_codec: Arc::new(DefaultLogicalExtensionCodec {}),
+            sql: None,
+            temporary_table: None,
+            extension_codec: Arc::new(BallistaLogicalExtensionCodec {}),
+            physical_extension_codec: Arc::new(BallistaPhysicalExtensionCodec {}),
             plan_repr: PhantomData,
             session_id,
             properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         }
     }
 
@@ -99,10 +130,14 @@ impl<T: 'static + AsLogicalPlan> DistributedQueryExec<T> {
             scheduler_url,
             config,
             plan,
+            sql: None,
+            temporary_table: None,
             extension_codec,
+            physical_extension_codec: Arc::new(BallistaPhysicalExtensionCodec {}),
             plan_repr: PhantomData,
             session_id,
             properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         }
     }
 
@@ -119,13 +154,41 @@ impl<T: 'static + AsLogicalPlan> DistributedQueryExec<T> {
             scheduler_url,
             config,
             plan,
+            sql: None,
+            temporary_table: None,
             extension_codec,
+            physical_extension_codec: Arc::new(BallistaPhysicalExtensionCodec {}),
             plan_repr,
             session_id,
             properties,
+            metrics: ExecutionPlanMetricsSet::new(),
         }
     }
 
+    /// Send the given SQL to the scheduler rather than the logical plan, which then
+    /// only describes the schema of the result
+    pub fn with_sql(mut self, sql: String) -> Self {
+        self.sql = Some(sql);
+        self
+    }
+
+    /// Keep the output of the query on the executors as a temporary table of the session
+    /// rather than fetching it. Executing the plan then produces no rows.
+    pub fn with_temporary_table(mut self, name: String, or_replace: bool) -> Self {
+        self.temporary_table = Some(CreateTemporaryTable { name, or_replace });
+        self
+    }
+
+    /// Codec for the extensions of the final stage of the job, which the scheduler
+    /// detaches for the client to run with `ballista.client.collect_stage`
+    pub fn with_physical_extension_codec(
+        mut self,
+        physical_extension_codec: Arc<dyn PhysicalExtensionCodec>,
+    ) -> Self {
+        self.physical_extension_codec = physical_extension_codec;
+        self
+    }
+
     fn compute_properties(schema: SchemaRef) -> PlanProperties {
         PlanProperties::new(
             EquivalenceProperties::new(schema),
@@ -178,40 +241,62 @@ impl<T: 'static + AsLogicalPlan> ExecutionPlan for DistributedQueryExec<T> {
             scheduler_url: self.scheduler_url.clone(),
             config: self.config.clone(),
             plan: self.plan.clone(),
+            sql: self.sql.clone(),
+            temporary_table: self.temporary_table.clone(),
             extension_codec: self.extension_codec.clone(),
+            physical_extension_codec: self.physical_extension_codec.clone(),
             plan_repr: self.plan_repr,
             session_id: self.session_id.clone(),
             properties: Self::compute_properties(
                 self.plan.schema().as_ref().clone().into(),
             ),
+            metrics: ExecutionPlanMetricsSet::new(),
         }))
     }
 
     fn execute(
         &self,
         partition: usize,
-        _context: Arc<TaskContext>,
+        context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
         assert_eq!(0, partition);
 
-        let mut buf: Vec<u8> = vec![];
-        let plan_message = T::try_from_logical_plan(
-            &self.plan,
-            self.extension_codec.as_ref(),
-        )
-        .map_err(|e| {
-            DataFusionError::Internal(format!("failed to serialize logical plan: {e:?}"))
-        })?;
-        plan_message.try_encode(&mut buf).map_err(|e| {
-            DataFusionError::Execution(format!("failed to encode logical plan: {e:?}"))
-        })?;
+        let query = match &self.sql {
+            Some(sql) => Query::Sql(sql.clone()),
+            None => {
+                let mut buf: Vec<u8> = vec![];
+                let plan_message =
+                    T::try_from_logical_plan(&self.plan, self.extension_codec.as_ref())
+                        .map_err(|e| {
+                        DataFusionError::Internal(format!(
+                            "failed to serialize logical plan: {e:?}"
+                        ))
+                    })?;
+                plan_message.try_encode(&mut buf).map_err(|e| {
+                    DataFusionError::Execution(format!(
+                        "failed to encode logical plan: {e:?}"
+                    ))
+                })?;
+                Query::LogicalPlan(buf)
+            }
+        };
 
+        // the scheduler and executors run the query with the options of the client session
         let query = ExecuteQueryParams {
-            query: Some(Query::LogicalPlan(buf)),
-            settings: vec![],
+            query: Some(query),
+            settings: session_config_props(context.session_config()),
+            temporary_table: self.temporary_table.clone(),
             optional_session_id: Some(OptionalSessionId::SessionId(
                 self.session_id.clone(),
             )),
+            // the submission is retried with the same key on transient errors
+            idempotency_key: Uuid::new_v4().to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            // the final stage is run by the client, which always fetches the partitions
+            // it reads from the executors
+            collect_stage: self.config.client_collect_stage()
+                && !self.config.client_fetch_via_scheduler(),
         };
 
         let stream = futures::stream::once(
@@ -220,6 +305,20 @@ impl<T: 'static + AsLogicalPlan> ExecutionPlan for DistributedQueryExec<T> {
                 self.session_id.clone(),
                 query,
                 self.config.default_grpc_client_max_message_size(),
+                self.config.client_ordered_fetch(),
+                self.config
+                    .client_fetch_via_scheduler()
+                    .then(|| self.scheduler_url.clone()),
+                self.config.client_exchange_credits(),
+                (
+                    self.config.results_max_rows(),
+                    self.config.results_max_bytes(),
+                ),
+                self.schema(),
+                context,
+                self.physical_extension_codec.clone(),
+                self.metrics.clone(),
+                partition,
             )
             .map_err(|e| ArrowError::ExternalError(Box::new(e))),
         )
@@ -229,6 +328,10 @@ impl<T: 'static + AsLogicalPlan> ExecutionPlan for DistributedQueryExec<T> {
         Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
     }
 
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
     fn statistics(&self) -> Result<Statistics> {
         // This execution plan sends the logical plan to the scheduler without
         // performing the node by node conversion to a full physical plan.
@@ -237,12 +340,28 @@ impl<T: 'static + AsLogicalPlan> ExecutionPlan for DistributedQueryExec<T> {
     }
 }
 
+/// Number of attempts to submit a query to the scheduler when it is unavailable. The
+/// attempts are deduplicated by the scheduler thanks to the idempotency key of the query.
+const EXECUTE_QUERY_ATTEMPTS: u32 = 3;
+const EXECUTE_QUERY_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+const EXECUTE_QUERY_MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+#[allow(clippy::too_many_arguments)]
 async fn execute_query(
     scheduler_url: String,
     session_id: String,
     query: ExecuteQueryParams,
     max_message_size: usize,
-) -> Result<impl Stream<Item = Result<RecordBatch>> + Send> {
+    ordered_fetch: bool,
+    proxy_url: Option<String>,
+    exchange_credits: Option<usize>,
+    (max_rows, max_bytes): (Option<usize>, Option<usize>),
+    schema: SchemaRef,
+    context: Arc<TaskContext>,
+    physical_extension_codec: Arc<dyn PhysicalExtensionCodec>,
+    metrics: ExecutionPlanMetricsSet,
+    partition: usize,
+) -> Result<SendableRecordBatchStream> {
     info!("Connecting to Ballista scheduler at {}", scheduler_url);
     // TODO reuse the scheduler to avoid connecting to the Ballista scheduler again and again
     let connection = create_grpc_client_connection(scheduler_url)
@@ -253,11 +372,36 @@ async fn execute_query(
         .max_encoding_message_size(max_message_size)
         .max_decoding_message_size(max_message_size);
 
-    let query_result = scheduler
-        .execute_query(query)
-        .await
-        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
-        .into_inner();
+    // the output of queries creating temporary tables stays on the executors
+    let fetch_output = query.temporary_table.is_none();
+    let proxy = proxy_url.map(|url| FetchProxy {
+        url,
+        session_id: session_id.clone(),
+    });
+
+    let mut attempt = 0;
+    let query_result = loop {
+        match scheduler.execute_query(query.clone()).await {
+            Ok(response) => break response.into_inner(),
+            Err(e)
+                if attempt + 1 < EXECUTE_QUERY_ATTEMPTS
+                    && matches!(e.code(), Code::Unavailable | Code::DeadlineExceeded) =>
+            {
+                let backoff = backoff_with_jitter(
+                    attempt,
+                    EXECUTE_QUERY_INITIAL_BACKOFF,
+                    EXECUTE_QUERY_MAX_BACKOFF,
+                );
+                warn!(
+                    "Failed to submit query to the scheduler, retrying in {:?}: {:?}",
+                    backoff, e
+                );
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(DataFusionError::Execution(format!("{e:?}"))),
+        }
+    };
 
     let query_result = match query_result.result.unwrap() {
         execute_query_result::Result::Success(success_result) => success_result,
@@ -274,6 +418,12 @@ async fn execute_query(
     );
 
     let job_id = query_result.job_id;
+    if query_result.estimated_start_delay_ms > 0 {
+        info!(
+            "Job {} submitted, estimated to start in {} ms",
+            job_id, query_result.estimated_start_delay_ms
+        );
+    }
     let mut prev_status: Option<job_status::Status> = None;
 
     loop {
@@ -295,9 +445,24 @@ async fn execute_query(
                 wait_future.await;
                 prev_status = status;
             }
-            Some(job_status::Status::Queued(_)) => {
+            Some(job_status::Status::Queued(ref queued)) => {
+                // the estimated start delay of a queued job changes while it waits
+                if !matches!(prev_status, Some(job_status::Status::Queued(_))) {
+                    if queued.estimated_start_delay_ms > 0 {
+                        info!(
+                            "Job {} still queued, estimated to start in {} ms...",
+                            job_id, queued.estimated_start_delay_ms
+                        );
+                    } else {
+                        info!("Job {} still queued...", job_id);
+                    }
+                }
+                wait_future.await;
+                prev_status = status;
+            }
+            Some(job_status::Status::Planning(_)) => {
                 if has_status_change {
-                    info!("Job {} still queued...", job_id);
+                    info!("Job {} is being planned...", job_id);
                 }
                 wait_future.await;
                 prev_status = status;
@@ -310,26 +475,324 @@ async fn execute_query(
                 prev_status = status;
             }
             Some(job_status::Status::Failed(err)) => {
-                let msg = format!("Job {} failed: {}", job_id, err.error);
+                let msg = failed_job_message(&job_id, &err);
                 error!("{}", msg);
                 break Err(DataFusionError::Execution(msg));
             }
             Some(job_status::Status::Successful(successful)) => {
-                let streams = successful.partition_location.into_iter().map(|p| {
-                    let f = fetch_partition(p)
-                        .map_err(|e| ArrowError::ExternalError(Box::new(e)));
+                if let Some(stats) = &successful.output_stats {
+                    info!(
+                        "Job {} produced {} rows in {} batches ({} bytes)",
+                        job_id, stats.num_rows, stats.num_batches, stats.num_bytes
+                    );
+                    MetricBuilder::new(&metrics)
+                        .output_rows(partition)
+                        .add(stats.num_rows as usize);
+                    MetricBuilder::new(&metrics)
+                        .counter("output_batches", partition)
+                        .add(stats.num_batches as usize);
+                    MetricBuilder::new(&metrics)
+                        .counter("output_bytes", partition)
+                        .add(stats.num_bytes as usize);
+                }
+
+                if successful.truncated {
+                    warn!(
+                        "Results of job {} truncated to the result limits of the session, \
+                        max rows {:?}, max bytes {:?}",
+                        job_id, max_rows, max_bytes
+                    );
+                    MetricBuilder::new(&metrics)
+                        .counter("truncated_results", partition)
+                        .add(1);
+                }
+                let truncate = |stream: Result<SendableRecordBatchStream>| {
+                    if successful.truncated {
+                        stream.map(|stream| truncate_results(stream, max_rows, max_bytes))
+                    } else {
+                        stream
+                    }
+                };
+
+                // the scheduler detached the final stage of the job for the client to run
+                if fetch_output && !successful.collect_plan.is_empty() {
+                    info!(
+                        "Running the final stage of job {} on its {} output partitions",
+                        job_id,
+                        successful.partition_location.len()
+                    );
+                    break collect_partitions(
+                        &successful.collect_plan,
+                        successful.partition_location.clone(),
+                        context,
+                        physical_extension_codec.as_ref(),
+                    );
+                }
 
-                    futures::stream::once(f).try_flatten()
-                });
+                let mut locations = if fetch_output {
+                    successful.partition_location.clone()
+                } else {
+                    vec![]
+                };
 
-                break Ok(futures::stream::iter(streams).flatten());
+                if ordered_fetch {
+                    locations.sort_by_key(|location| {
+                        location
+                            .partition_id
+                            .as_ref()
+                            .map(|partition_id| partition_id.partition_id)
+                    });
+                    if locations.len() > 1 && !successful.output_ordering.is_empty() {
+                        info!(
+                            "Merging {} sorted output partitions of job {}",
+                            locations.len(),
+                            job_id
+                        );
+                        break truncate(
+                            merge_partitions(
+                                locations,
+                                proxy,
+                                &successful.output_ordering,
+                                schema,
+                                context,
+                                partition,
+                            )
+                            .await,
+                        );
+                    }
+                }
+
+                let streams: Vec<_> = match exchange_credits.filter(|_| proxy.is_none()) {
+                    Some(credits) => {
+                        if !ordered_fetch {
+                            locations.sort_by_key(|location| {
+                                location
+                                    .executor_meta
+                                    .as_ref()
+                                    .map(|meta| meta.id.clone())
+                            });
+                        }
+                        group_by_executor(locations)
+                            .into_iter()
+                            .map(|locations| {
+                                let f = exchange_partitions(locations, credits)
+                                    .map_err(|e| ArrowError::ExternalError(Box::new(e)));
+
+                                futures::stream::once(f).try_flatten().boxed()
+                            })
+                            .collect()
+                    }
+                    None => locations
+                        .into_iter()
+                        .map(|p| {
+                            let f = fetch_partition(p, proxy.clone())
+                                .map_err(|e| ArrowError::ExternalError(Box::new(e)));
+
+                            futures::stream::once(f).try_flatten().boxed()
+                        })
+                        .collect(),
+                };
+
+                break truncate(Ok(Box::pin(RecordBatchStreamAdapter::new(
+                    schema,
+                    futures::stream::iter(streams).flatten(),
+                ))));
             }
         };
     }
 }
 
+/// Describe the failure of a job, including the task which failed it and the error chain
+fn failed_job_message(job_id: &str, failed: &FailedJob) -> String {
+    let mut msg = format!("Job {} failed: {}", job_id, failed.error);
+    if let Some(task) = &failed.failed_task {
+        msg.push_str(&format!(
+            "\nFailed task: TID {} {}/{}.{}/{} on executor {}",
+            task.task_id,
+            job_id,
+            task.stage_id,
+            task.stage_attempt_num,
+            task.partition_id,
+            task.executor_id
+        ));
+    }
+    for (i, cause) in failed.error_chain.iter().enumerate() {
+        msg.push_str(&format!("\n  {i}: {cause}"));
+    }
+    msg
+}
+
+/// Truncate the results of a job to the max rows and bytes of the session, the scheduler
+/// only dropping the output partitions beyond the limits
+fn truncate_results(
+    stream: SendableRecordBatchStream,
+    max_rows: Option<usize>,
+    max_bytes: Option<usize>,
+) -> SendableRecordBatchStream {
+    let schema = stream.schema();
+    let remaining = (
+        max_rows.unwrap_or(usize::MAX),
+        max_bytes.unwrap_or(usize::MAX),
+    );
+    let stream = stream.scan(remaining, |(remaining_rows, remaining_bytes), batch| {
+        futures::future::ready(match batch {
+            Ok(batch) => truncate_batch(batch, remaining_rows, remaining_bytes)
+                .map_err(DataFusionError::from)
+                .transpose(),
+            Err(e) => Some(Err(e)),
+        })
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, stream))
+}
+
+/// The first rows of the batch within the remaining rows and bytes, the rows of a batch
+/// being assumed of the same size. `None` once the remaining rows or bytes are exhausted.
+/// The bytes are measured like the scheduler measures the output partitions of the jobs.
+fn truncate_batch(
+    batch: RecordBatch,
+    remaining_rows: &mut usize,
+    remaining_bytes: &mut usize,
+) -> std::result::Result<Option<RecordBatch>, ArrowError> {
+    if *remaining_rows == 0 || *remaining_bytes == 0 {
+        return Ok(None);
+    }
+    if batch.num_rows() == 0 {
+        return Ok(Some(batch));
+    }
+    let size = batch_memory_size(&batch)?;
+    let mut num_rows = batch.num_rows().min(*remaining_rows);
+    if size > *remaining_bytes {
+        let rows_within_bytes =
+            *remaining_bytes as u128 * batch.num_rows() as u128 / size as u128;
+        num_rows = num_rows.min(rows_within_bytes as usize);
+    }
+    if num_rows == 0 {
+        *remaining_bytes = 0;
+        return Ok(None);
+    }
+    if num_rows < batch.num_rows() {
+        *remaining_rows = 0;
+        return Ok(Some(batch.slice(0, num_rows)));
+    }
+    *remaining_rows -= num_rows;
+    *remaining_bytes = remaining_bytes.saturating_sub(size);
+    Ok(Some(batch))
+}
+
+/// Merge the sorted output partitions of a job into a single sorted stream
+async fn merge_partitions(
+    locations: Vec<PartitionLocation>,
+    proxy: Option<FetchProxy>,
+    output_ordering: &[OutputSortColumn],
+    schema: SchemaRef,
+    context: Arc<TaskContext>,
+    partition: usize,
+) -> Result<SendableRecordBatchStream> {
+    let expressions = output_ordering
+        .iter()
+        .map(|sort_column| {
+            let index = sort_column.column_index as usize;
+            if index >= schema.fields().len() {
+                return Err(DataFusionError::Internal(format!(
+                    "Sort column {index} is out of bounds for the job output schema"
+                )));
+            }
+            Ok(PhysicalSortExpr {
+                expr: Arc::new(Column::new(schema.field(index).name(), index)),
+                options: SortOptions {
+                    descending: sort_column.descending,
+                    nulls_first: sort_column.nulls_first,
+                },
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let streams = futures::future::try_join_all(
+        locations
+            .into_iter()
+            .map(|location| fetch_partition(location, proxy.clone())),
+    )
+    .await?;
+
+    let reservation = MemoryConsumer::new(format!("DistributedQueryExec[{partition}]"))
+        .register(context.memory_pool());
+
+    streaming_merge(
+        streams,
+        schema,
+        &expressions,
+        BaselineMetrics::new(&ExecutionPlanMetricsSet::new(), partition),
+        context.session_config().batch_size(),
+        None,
+        reservation,
+    )
+}
+
+/// Run the final stage of a job detached by the scheduler, reading the output partitions
+/// of the job with a [ShuffleReaderExec] in place of its [UnresolvedShuffleExec]
+fn collect_partitions(
+    collect_plan: &[u8],
+    locations: Vec<PartitionLocation>,
+    context: Arc<TaskContext>,
+    codec: &dyn PhysicalExtensionCodec,
+) -> Result<SendableRecordBatchStream> {
+    let plan = PhysicalPlanNode::try_decode(collect_plan).and_then(|proto| {
+        proto.try_into_physical_plan(
+            context.as_ref(),
+            context.runtime_env().as_ref(),
+            codec,
+        )
+    })?;
+    let locations = locations
+        .into_iter()
+        .map(|location| location.try_into())
+        .collect::<std::result::Result<Vec<ShufflePartitionLocation>, _>>()
+        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+
+    let plan = plan.transform_up(&|plan: Arc<dyn ExecutionPlan>| {
+        let Some(unresolved_shuffle) =
+            plan.as_any().downcast_ref::<UnresolvedShuffleExec>()
+        else {
+            return Ok(Transformed::no(plan));
+        };
+        let mut partitions = vec![vec![]; unresolved_shuffle.output_partition_count];
+        for location in &locations {
+            let partition_id = location.partition_id.partition_id;
+            partitions
+                .get_mut(partition_id)
+                .ok_or_else(|| {
+                    DataFusionError::Internal(format!(
+                        "Output partition {partition_id} is out of bounds for the final \
+                         stage reading {} partitions",
+                        unresolved_shuffle.output_partition_count
+                    ))
+                })?
+                .push(location.clone());
+        }
+        let shuffle_reader = ShuffleReaderExec::try_new(
+            unresolved_shuffle.stage_id,
+            partitions,
+            unresolved_shuffle.schema(),
+        )
+        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        Ok(Transformed::yes(Arc::new(shuffle_reader)))
+    })?;
+    plan.data.execute(0, context)
+}
+
+/// The flight service of the scheduler relaying the fetches of the outputs of the jobs of
+/// a session
+#[derive(Clone)]
+struct FetchProxy {
+    url: String,
+    session_id: String,
+}
+
+/// Fetch an output partition from its executor, or through the flight service of the
+/// scheduler when there is a proxy
 async fn fetch_partition(
     location: PartitionLocation,
+    proxy: Option<FetchProxy>,
 ) -> Result<SendableRecordBatchStream> {
     let metadata = location.executor_meta.ok_or_else(|| {
         DataFusionError::Internal("Received empty executor metadata".to_owned())
@@ -339,17 +802,221 @@ async fn fetch_partition(
     })?;
     let host = metadata.host.as_str();
     let port = metadata.port as u16;
+    let stream = match proxy {
+        Some(proxy) => {
+            let mut ballista_client =
+                BallistaClient::try_new_with_url(proxy.url)
+                    .await
+                    .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+            ballista_client
+                .fetch_partition_via_scheduler(
+                    &proxy.session_id,
+                    &metadata.id,
+                    &partition_id.into(),
+                    &location.path,
+                    host,
+                    port,
+                )
+                .await
+        }
+        None => {
+            let mut ballista_client = BallistaClient::try_new(host, port)
+                .await
+                .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+            ballista_client
+                .fetch_partition(
+                    &metadata.id,
+                    &partition_id.into(),
+                    &location.path,
+                    host,
+                    port,
+                )
+                .await
+        }
+    };
+    stream.map_err(|e| DataFusionError::External(Box::new(e)))
+}
+
+/// Split the output partitions into runs of consecutive partitions of the same executor
+fn group_by_executor(locations: Vec<PartitionLocation>) -> Vec<Vec<PartitionLocation>> {
+    let mut groups: Vec<Vec<PartitionLocation>> = vec![];
+    for location in locations {
+        match groups.last_mut() {
+            Some(group) if group[0].executor_meta == location.executor_meta => {
+                group.push(location)
+            }
+            _ => groups.push(vec![location]),
+        }
+    }
+    groups
+}
+
+/// Fetch output partitions of the same executor over a single partition exchange stream
+async fn exchange_partitions(
+    locations: Vec<PartitionLocation>,
+    credits: usize,
+) -> Result<SendableRecordBatchStream> {
+    let metadata = locations
+        .first()
+        .and_then(|location| location.executor_meta.clone())
+        .ok_or_else(|| {
+            DataFusionError::Internal("Received empty executor metadata".to_owned())
+        })?;
+    let partitions = locations
+        .into_iter()
+        .map(|location| {
+            let partition_id = location.partition_id.ok_or_else(|| {
+                DataFusionError::Internal("Received empty partition id".to_owned())
+            })?;
+            Ok((partition_id.into(), location.path))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let host = metadata.host.as_str();
+    let port = metadata.port as u16;
     let mut ballista_client = BallistaClient::try_new(host, port)
         .await
         .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
     ballista_client
-        .fetch_partition(
-            &metadata.id,
-            &partition_id.into(),
-            &location.path,
-            host,
-            port,
-        )
+        .exchange_partitions(&metadata.id, partitions, host, port, credits)
         .await
         .map_err(|e| DataFusionError::External(Box::new(e)))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{ArrayRef, Int64Array};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::error::Result;
+    use datafusion::execution::context::TaskContext;
+    use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+    use datafusion::physical_plan::{common, ExecutionPlan};
+    use datafusion_proto::physical_plan::{AsExecutionPlan, PhysicalExtensionCodec};
+    use datafusion_proto::protobuf::PhysicalPlanNode;
+
+    use super::{collect_partitions, group_by_executor, truncate_batch};
+    use crate::execution_plans::UnresolvedShuffleExec;
+    use crate::serde::protobuf::{ExecutorMetadata, PartitionLocation};
+    use crate::serde::BallistaPhysicalExtensionCodec;
+    use crate::utils::batch_memory_size;
+
+    fn batch(num_rows: i64) -> RecordBatch {
+        let array: ArrayRef = Arc::new(Int64Array::from_iter_values(0..num_rows));
+        RecordBatch::try_from_iter(vec![("a", array)]).unwrap()
+    }
+
+    #[test]
+    fn test_truncate_batch() -> Result<()> {
+        let (mut remaining_rows, mut remaining_bytes) = (150, usize::MAX);
+        let truncated =
+            truncate_batch(batch(100), &mut remaining_rows, &mut remaining_bytes)?;
+        assert_eq!(truncated.unwrap().num_rows(), 100);
+        let truncated =
+            truncate_batch(batch(100), &mut remaining_rows, &mut remaining_bytes)?;
+        assert_eq!(truncated.unwrap().num_rows(), 50);
+        assert!(
+            truncate_batch(batch(100), &mut remaining_rows, &mut remaining_bytes)?
+                .is_none()
+        );
+
+        let size = batch_memory_size(&batch(100))?;
+        let (mut remaining_rows, mut remaining_bytes) = (usize::MAX, size / 2);
+        let truncated =
+            truncate_batch(batch(100), &mut remaining_rows, &mut remaining_bytes)?;
+        assert_eq!(truncated.unwrap().num_rows(), size / 2 * 100 / size);
+        assert!(
+            truncate_batch(batch(100), &mut remaining_rows, &mut remaining_bytes)?
+                .is_none()
+        );
+
+        // a slice is measured by the rows it holds rather than by the whole batch
+        let (mut remaining_rows, mut remaining_bytes) = (usize::MAX, size / 2);
+        let truncated = truncate_batch(
+            batch(200).slice(0, 50),
+            &mut remaining_rows,
+            &mut remaining_bytes,
+        )?;
+        assert_eq!(truncated.unwrap().num_rows(), 50);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_executor() {
+        let location = |executor_id: &str| PartitionLocation {
+            executor_meta: Some(ExecutorMetadata {
+                id: executor_id.to_owned(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let groups = group_by_executor(vec![
+            location("a"),
+            location("a"),
+            location("b"),
+            location("a"),
+        ]);
+        let executors: Vec<(String, usize)> = groups
+            .iter()
+            .map(|group| {
+                let executor_id = group[0].executor_meta.as_ref().unwrap().id.clone();
+                (executor_id, group.len())
+            })
+            .collect();
+        // only consecutive partitions are grouped, to keep the order of the partitions
+        assert_eq!(
+            executors,
+            vec![
+                ("a".to_owned(), 2),
+                ("b".to_owned(), 1),
+                ("a".to_owned(), 1)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_partitions() -> datafusion::error::Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, true)]));
+        let plan: Arc<dyn ExecutionPlan> = Arc::new(CoalescePartitionsExec::new(
+            Arc::new(UnresolvedShuffleExec::new(1, schema.clone(), 2)),
+        ));
+        let mut collect_plan = vec![];
+        PhysicalPlanNode::try_from_physical_plan(
+            plan,
+            &BallistaPhysicalExtensionCodec {},
+        )?
+        .try_encode(&mut collect_plan)?;
+
+        // the output partitions of the job are read in place of the unresolved shuffle
+        let stream = collect_partitions(
+            &collect_plan,
+            vec![],
+            Arc::new(TaskContext::default()),
+            &BallistaPhysicalExtensionCodec {},
+        )?;
+        assert_eq!(stream.schema(), schema);
+        let batches = common::collect(stream).await?;
+        assert!(batches.is_empty());
+
+        // the locations must be partitions of the stage read by the final stage
+        let location = PartitionLocation {
+            partition_id: Some(crate::serde::protobuf::PartitionId {
+                partition_id: 2,
+                ..Default::default()
+            }),
+            executor_meta: Some(ExecutorMetadata::default()),
+            partition_stats: Some(Default::default()),
+            ..Default::default()
+        };
+        assert!(collect_partitions(
+            &collect_plan,
+            vec![location],
+            Arc::new(TaskContext::default()),
+            &BallistaPhysicalExtensionCodec {},
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use datafusion::arrow::array::UInt64Array;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::execution::object_store::ObjectStoreUrl;
+use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning,
+    PlanProperties, SendableRecordBatchStream, Statistics,
+};
+use futures::TryStreamExt;
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+/// Name of the marker written to the output directory of a distributed write once all its
+/// partitions have been written successfully
+pub const SUCCESS_MARKER: &str = "_SUCCESS";
+
+/// Name of the subdirectory of the output directory of a distributed write within which
+/// every job stages the files it writes until they are committed
+pub const STAGING_DIR: &str = "_temporary";
+
+/// FileSinkCommitExec completes a distributed write, once all the partitions written by a
+/// [crate::execution_plans::ParallelFileSinkExec] have been successfully written.
+///
+/// The partitions are written to a staging directory, so that a failed or running write
+/// leaves no file in the output directory. FileSinkCommitExec sums the row counts of its
+/// single input partition, moves the files of the staging directory to the output
+/// directory and then writes an empty [SUCCESS_MARKER] file to the output directory, so
+/// that readers can tell a complete output apart from an output being committed.
+#[derive(Debug)]
+pub struct FileSinkCommitExec {
+    input: Arc<dyn ExecutionPlan>,
+    object_store_url: ObjectStoreUrl,
+    /// The directory the files are moved to and the marker is written to
+    output_path: Path,
+    /// The directory the partitions were written to
+    staging_path: Path,
+    properties: PlanProperties,
+}
+
+impl FileSinkCommitExec {
+    /// Create a new FileSinkCommitExec, summing the row counts of `input`
+    pub fn new(
+        input: Arc<dyn ExecutionPlan>,
+        object_store_url: ObjectStoreUrl,
+        output_path: Path,
+        staging_path: Path,
+    ) -> Self {
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(input.schema()),
+            Partitioning::UnknownPartitioning(1),
+            ExecutionMode::Bounded,
+        );
+        Self {
+            input,
+            object_store_url,
+            output_path,
+            staging_path,
+            properties,
+        }
+    }
+
+    /// The object store written to
+    pub fn object_store_url(&self) -> &ObjectStoreUrl {
+        &self.object_store_url
+    }
+
+    /// The directory written to
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+
+    /// The directory the partitions were written to
+    pub fn staging_path(&self) -> &Path {
+        &self.staging_path
+    }
+}
+
+/// Move the files of the directory `from` to the directory `to`, keeping their paths
+/// relative to the directory
+async fn move_files(
+    object_store: &dyn ObjectStore,
+    from: &Path,
+    to: &Path,
+) -> Result<()> {
+    let files: Vec<Path> = object_store
+        .list(Some(from))
+        .map_ok(|meta| meta.location)
+        .try_collect()
+        .await?;
+    for file in files {
+        let Some(parts) = file.prefix_match(from) else {
+            continue;
+        };
+        let target = parts.fold(to.clone(), |path, part| path.child(part));
+        object_store.rename(&file, &target).await?;
+    }
+    Ok(())
+}
+
+impl DisplayAs for FileSinkCommitExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "FileSinkCommitExec: output={}{}, staging={}",
+                    self.object_store_url.as_str(),
+                    self.output_path,
+                    self.staging_path
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for FileSinkCommitExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        match children.as_slice() {
+            [input] => Ok(Arc::new(Self::new(
+                input.clone(),
+                self.object_store_url.clone(),
+                self.output_path.clone(),
+                self.staging_path.clone(),
+            ))),
+            _ => Err(DataFusionError::Plan(
+                "FileSinkCommitExec expects exactly one child".to_owned(),
+            )),
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        if partition != 0 {
+            return Err(DataFusionError::Internal(format!(
+                "FileSinkCommitExec invalid partition {partition}"
+            )));
+        }
+        let input = self.input.execute(0, context.clone())?;
+        let object_store = context.runtime_env().object_store(&self.object_store_url)?;
+        let marker_path = self.output_path.child(SUCCESS_MARKER);
+        let output_path = self.output_path.clone();
+        let staging_path = self.staging_path.clone();
+        let schema = self.schema();
+
+        let output = futures::stream::once(async move {
+            let batches: Vec<RecordBatch> = input.try_collect().await?;
+            let mut count = 0;
+            for batch in &batches {
+                let counts = batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<UInt64Array>()
+                    .ok_or_else(|| {
+                        DataFusionError::Internal(
+                            "FileSinkCommitExec expects row counts as input".to_owned(),
+                        )
+                    })?;
+                count += counts.iter().flatten().sum::<u64>();
+            }
+            move_files(object_store.as_ref(), &staging_path, &output_path).await?;
+            object_store.put(&marker_path, Bytes::new()).await?;
+            Ok(RecordBatch::try_new(
+                schema,
+                vec![Arc::new(UInt64Array::from(vec![count]))],
+            )?)
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema(),
+            output,
+        )))
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::common::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_commit_writes_success_marker() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "count",
+            DataType::UInt64,
+            false,
+        )]));
+        let batch = |count: u64| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(UInt64Array::from(vec![count]))],
+            )
+        };
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![batch(3)?, batch(2)?]],
+            schema.clone(),
+            None,
+        )?);
+        let tmp_dir = TempDir::new()?;
+        let staging_dir = tmp_dir.path().join("_temporary").join("job");
+        std::fs::create_dir_all(staging_dir.join("part"))?;
+        std::fs::write(staging_dir.join("1.csv"), "a\n1\n")?;
+        std::fs::write(staging_dir.join("part").join("2.csv"), "a\n2\n")?;
+        let output_path = Path::from_filesystem_path(tmp_dir.path())?;
+        let staging_path = Path::from_filesystem_path(&staging_dir)?;
+
+        let exec = FileSinkCommitExec::new(
+            input,
+            ObjectStoreUrl::local_filesystem(),
+            output_path,
+            staging_path,
+        );
+        let batches = collect(exec.execute(0, Arc::new(TaskContext::default()))?).await?;
+        let count = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap()
+            .value(0);
+        assert_eq!(count, 5);
+        assert!(tmp_dir.path().join(SUCCESS_MARKER).exists());
+        // the staged files are moved to the output directory
+        assert!(tmp_dir.path().join("1.csv").exists());
+        assert!(tmp_dir.path().join("part").join("2.csv").exists());
+        assert!(!staging_dir.join("1.csv").exists());
+        Ok(())
+    }
+}
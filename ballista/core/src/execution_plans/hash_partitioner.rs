@@ -0,0 +1,230 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Vectorized hash partitioning of the batches written by the shuffle writers.
+//!
+//! The rows of a batch are sorted by partition with a counting sort of their hashes, the
+//! columns are reordered with a single `take` each, and the batch of every partition is a
+//! zero-copy slice of the reordered batch. The rows are assigned to the same partitions as
+//! with the hash repartitioning of DataFusion, except for the custom partitionings of
+//! [super::custom_partitioning] which give the partitions of the rows directly.
+
+use std::sync::Arc;
+
+use ahash::RandomState;
+use datafusion::arrow::array::{AsArray, UInt32Array};
+use datafusion::arrow::compute::take;
+use datafusion::arrow::datatypes::UInt64Type;
+use datafusion::arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use datafusion::common::hash_utils::create_hashes;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_plan::metrics;
+use datafusion::physical_plan::{Partitioning, PhysicalExpr};
+
+use super::PartitionIdExpr;
+
+/// Splits batches into their hash partitions
+pub struct HashPartitioner {
+    exprs: Vec<Arc<dyn PhysicalExpr>>,
+    num_partitions: usize,
+    /// Whether the single expression gives the partitions of the rows, see [PartitionIdExpr]
+    custom: bool,
+    /// Same seeds as the hash repartitioning of DataFusion
+    random_state: RandomState,
+    /// Buffers reused across batches
+    hashes: Vec<u64>,
+    partition_ids: Vec<u32>,
+    timer: metrics::Time,
+}
+
+impl HashPartitioner {
+    /// Create a partitioner for a hash partitioning, the time spent partitioning being
+    /// added to `timer`
+    pub fn try_new(partitioning: Partitioning, timer: metrics::Time) -> Result<Self> {
+        let custom = PartitionIdExpr::from_partitioning(&partitioning).is_some();
+        match partitioning {
+            Partitioning::Hash(exprs, num_partitions) if num_partitions > 0 => Ok(Self {
+                exprs,
+                num_partitions,
+                custom,
+                random_state: RandomState::with_seeds(0, 0, 0, 0),
+                hashes: vec![],
+                partition_ids: vec![],
+                timer,
+            }),
+            other => Err(DataFusionError::Internal(format!(
+                "Unsupported partitioning for hash partitioner: {other:?}"
+            ))),
+        }
+    }
+
+    /// Split a batch into the non empty batches of its partitions, by partition number
+    pub fn partition(&mut self, batch: RecordBatch) -> Result<Vec<(usize, RecordBatch)>> {
+        let num_rows = batch.num_rows();
+        if num_rows == 0 {
+            return Ok(vec![]);
+        }
+        if self.num_partitions == 1 {
+            return Ok(vec![(0, batch)]);
+        }
+        let _timer = self.timer.timer();
+
+        let arrays = self
+            .exprs
+            .iter()
+            .map(|expr| expr.evaluate(&batch)?.into_array(num_rows))
+            .collect::<Result<Vec<_>>>()?;
+        let num_partitions = self.num_partitions as u64;
+        self.partition_ids.clear();
+        if self.custom {
+            for partition in arrays[0].as_primitive::<UInt64Type>() {
+                match partition {
+                    Some(partition) if partition < num_partitions => {
+                        self.partition_ids.push(partition as u32)
+                    }
+                    partition => {
+                        return Err(DataFusionError::Execution(format!(
+                            "Invalid partition {partition:?} of a custom partitioning into {num_partitions} partitions"
+                        )))
+                    }
+                }
+            }
+        } else {
+            self.hashes.clear();
+            self.hashes.resize(num_rows, 0);
+            create_hashes(&arrays, &self.random_state, &mut self.hashes)?;
+            self.partition_ids.extend(
+                self.hashes
+                    .iter()
+                    .map(|hash| (*hash % num_partitions) as u32),
+            );
+        }
+
+        // counting sort of the rows by partition, keeping the order of the rows within
+        // every partition
+        let mut offsets = vec![0usize; self.num_partitions + 1];
+        for partition in &self.partition_ids {
+            offsets[*partition as usize + 1] += 1;
+        }
+        for partition in 0..self.num_partitions {
+            offsets[partition + 1] += offsets[partition];
+        }
+        let mut next = offsets.clone();
+        let mut indices = vec![0u32; num_rows];
+        for (row, partition) in self.partition_ids.iter().enumerate() {
+            let position = &mut next[*partition as usize];
+            indices[*position] = row as u32;
+            *position += 1;
+        }
+
+        let indices = UInt32Array::from(indices);
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|column| take(column.as_ref(), &indices, None))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let options = RecordBatchOptions::new().with_row_count(Some(num_rows));
+        let sorted =
+            RecordBatch::try_new_with_options(batch.schema(), columns, &options)?;
+
+        Ok(offsets
+            .windows(2)
+            .enumerate()
+            .filter(|(_, range)| range[1] > range[0])
+            .map(|(partition, range)| {
+                (partition, sorted.slice(range[0], range[1] - range[0]))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution_plans::custom_partitioning;
+    use datafusion::arrow::array::{ArrayRef, Int64Array, StringArray};
+    use datafusion::arrow::compute::concat_batches;
+    use datafusion::logical_expr::Operator;
+    use datafusion::physical_plan::expressions::Column;
+    use datafusion::physical_plan::expressions::{col, lit, BinaryExpr};
+    use datafusion::physical_plan::repartition::BatchPartitioner;
+
+    #[test]
+    fn test_same_partitions_as_datafusion() -> Result<()> {
+        let a: ArrayRef = Arc::new(Int64Array::from_iter_values(0..1000));
+        let b: ArrayRef = Arc::new(StringArray::from_iter_values(
+            (0..1000).map(|i| format!("value-{}", i % 37)),
+        ));
+        let batch = RecordBatch::try_from_iter(vec![("a", a), ("b", b)])?;
+        let partitioning = Partitioning::Hash(
+            vec![Arc::new(Column::new("b", 1)), Arc::new(Column::new("a", 0))],
+            7,
+        );
+
+        let mut expected = vec![vec![]; 7];
+        BatchPartitioner::try_new(partitioning.clone(), metrics::Time::new())?
+            .partition(batch.clone(), |partition, batch| {
+                expected[partition].push(batch);
+                Ok(())
+            })?;
+
+        let partitions = HashPartitioner::try_new(partitioning, metrics::Time::new())?
+            .partition(batch.clone())?;
+        assert_eq!(
+            partitions
+                .iter()
+                .map(|(_, batch)| batch.num_rows())
+                .sum::<usize>(),
+            batch.num_rows()
+        );
+        for (partition, batch) in partitions {
+            let expected = concat_batches(&batch.schema(), &expected[partition])?;
+            assert_eq!(expected, batch);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_partitioning() -> Result<()> {
+        let a: ArrayRef = Arc::new(Int64Array::from_iter_values(0..100));
+        let batch = RecordBatch::try_from_iter(vec![("a", a)])?;
+        let schema = batch.schema();
+
+        // ranges of 25 values
+        let range = Arc::new(BinaryExpr::new(
+            col("a", &schema)?,
+            Operator::Divide,
+            lit(25i64),
+        ));
+        let mut partitioner = HashPartitioner::try_new(
+            custom_partitioning(range, 4),
+            metrics::Time::new(),
+        )?;
+        let partitions = partitioner.partition(batch.clone())?;
+        assert_eq!(4, partitions.len());
+        for (partition, output) in partitions {
+            assert_eq!(batch.slice(partition * 25, 25), output);
+        }
+
+        let mut partitioner = HashPartitioner::try_new(
+            custom_partitioning(col("a", &schema)?, 4),
+            metrics::Time::new(),
+        )?;
+        assert!(partitioner.partition(batch).is_err());
+        Ok(())
+    }
+}
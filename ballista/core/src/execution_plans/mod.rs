@@ -18,12 +18,35 @@
 //! This module contains execution plans that are needed to distribute DataFusion's execution plans into
 //! several Ballista executors.
 
+mod bloom_filter;
 mod distributed_query;
+mod file_sink_commit;
+mod hash_partitioner;
+mod materialized_cte;
+mod parallel_file_sink;
+mod partition_id_expr;
+mod partition_placement;
+mod runtime_filter;
 mod shuffle_reader;
+mod shuffle_spiller;
 mod shuffle_writer;
 mod unresolved_shuffle;
 
+pub use bloom_filter::{BloomFilter, BloomFilterExec};
 pub use distributed_query::DistributedQueryExec;
-pub use shuffle_reader::ShuffleReaderExec;
-pub use shuffle_writer::ShuffleWriterExec;
+pub use file_sink_commit::{FileSinkCommitExec, STAGING_DIR, SUCCESS_MARKER};
+pub use hash_partitioner::HashPartitioner;
+pub use materialized_cte::MaterializedCteExec;
+pub use parallel_file_sink::ParallelFileSinkExec;
+pub use partition_id_expr::{custom_partitioning, PartitionIdExpr};
+pub use partition_placement::PartitionPlacementExec;
+pub use runtime_filter::{supports_column_bounds, RuntimeFilterExec};
+pub use shuffle_reader::{
+    ShuffleReaderExec, ShuffleReaderOptions, DEFAULT_SHUFFLE_READER_MAX_REQUESTS,
+    DEFAULT_SHUFFLE_READER_MERGE_FETCH_THRESHOLD,
+    DEFAULT_SHUFFLE_READER_PREFETCH_BATCHES,
+};
+pub use shuffle_writer::{
+    ShuffleWriterExec, ShuffleWriterOptions, DEFAULT_SHUFFLE_WRITER_BUFFER_SIZE,
+};
 pub use unresolved_shuffle::UnresolvedShuffleExec;
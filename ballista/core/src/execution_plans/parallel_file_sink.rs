@@ -0,0 +1,389 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::datasource::file_format::csv::CsvSink;
+use datafusion::datasource::file_format::json::JsonSink;
+use datafusion::datasource::file_format::parquet::ParquetSink;
+use datafusion::datasource::listing::ListingTableUrl;
+use datafusion::datasource::physical_plan::FileSinkConfig;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::execution::object_store::ObjectStoreUrl;
+use datafusion::physical_expr::EquivalenceProperties;
+use datafusion::physical_plan::insert::{DataSink, FileSinkExec};
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionMode, ExecutionPlan, Partitioning,
+    PlanProperties, SendableRecordBatchStream, Statistics,
+};
+use object_store::path::Path;
+
+/// ParallelFileSinkExec writes every partition of the input of a [FileSinkExec] with its
+/// own writer, so that the partitions of a distributed write can be written by different
+/// tasks directly to the output directory.
+///
+/// A [FileSinkExec] requires a single input partition and writes all of them from a single
+/// task. Every partition of a ParallelFileSinkExec executes the [FileSinkExec] over the
+/// matching input partition only, and outputs the number of rows it has written. Only
+/// writes to a directory are supported, since every writer creates its own files.
+#[derive(Debug)]
+pub struct ParallelFileSinkExec {
+    /// The [FileSinkExec] to run for every partition of its input
+    sink: Arc<dyn ExecutionPlan>,
+    object_store_url: ObjectStoreUrl,
+    /// The output directory
+    output_path: Path,
+    properties: PlanProperties,
+}
+
+impl ParallelFileSinkExec {
+    /// Create a new ParallelFileSinkExec, writing the partitions of the input of `sink`
+    pub fn try_new(sink: Arc<dyn ExecutionPlan>) -> Result<Self> {
+        let file_sink =
+            sink.as_any()
+                .downcast_ref::<FileSinkExec>()
+                .ok_or_else(|| {
+                    DataFusionError::Plan(
+                        "ParallelFileSinkExec expects a FileSinkExec as input".to_owned(),
+                    )
+                })?;
+        let config = Self::output_config(file_sink).ok_or_else(|| {
+            DataFusionError::Plan(
+                "ParallelFileSinkExec only supports writes to a directory".to_owned(),
+            )
+        })?;
+        let partition_count = file_sink
+            .input()
+            .properties()
+            .partitioning
+            .partition_count();
+        let properties = PlanProperties::new(
+            EquivalenceProperties::new(sink.schema()),
+            Partitioning::UnknownPartitioning(partition_count),
+            ExecutionMode::Bounded,
+        );
+        Ok(Self {
+            object_store_url: config.object_store_url.clone(),
+            output_path: config.table_paths[0].prefix().clone(),
+            sink,
+            properties,
+        })
+    }
+
+    /// Whether the input partitions of `sink` can be written by different writers
+    pub fn supports(sink: &FileSinkExec) -> bool {
+        Self::output_config(sink).is_some()
+    }
+
+    /// Returns a copy of `sink` writing to the subdirectory `dir` of its output
+    /// directory, e.g. to stage the files of a write until it is committed
+    pub fn with_subdirectory(sink: &FileSinkExec, dir: &str) -> Result<FileSinkExec> {
+        let config = Self::output_config(sink).ok_or_else(|| {
+            DataFusionError::Plan(
+                "ParallelFileSinkExec only supports writes to a directory".to_owned(),
+            )
+        })?;
+        let output_dir =
+            ListingTableUrl::parse(format!("{}{dir}/", config.table_paths[0].as_str()))?;
+        let config = FileSinkConfig {
+            table_paths: vec![output_dir],
+            ..config.clone()
+        };
+        let output_schema = config.output_schema.clone();
+        let data_sink = sink.sink().as_any();
+        let data_sink: Arc<dyn DataSink> =
+            if let Some(parquet) = data_sink.downcast_ref::<ParquetSink>() {
+                Arc::new(ParquetSink::new(config, parquet.parquet_options().clone()))
+            } else if let Some(csv) = data_sink.downcast_ref::<CsvSink>() {
+                Arc::new(CsvSink::new(config, csv.writer_options().clone()))
+            } else if let Some(json) = data_sink.downcast_ref::<JsonSink>() {
+                Arc::new(JsonSink::new(config, json.writer_options().clone()))
+            } else {
+                return Err(DataFusionError::Plan(
+                    "ParallelFileSinkExec only supports writes to a directory".to_owned(),
+                ));
+            };
+        Ok(FileSinkExec::new(
+            sink.input().clone(),
+            data_sink,
+            output_schema,
+            sink.sort_order().clone(),
+        ))
+    }
+
+    /// The object store written to
+    pub fn object_store_url(&self) -> &ObjectStoreUrl {
+        &self.object_store_url
+    }
+
+    /// The directory written to
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+
+    /// Returns the configuration of the file sink, if it writes to a single directory
+    fn output_config(sink: &FileSinkExec) -> Option<&FileSinkConfig> {
+        let data_sink = sink.sink().as_any();
+        let config = if let Some(parquet) = data_sink.downcast_ref::<ParquetSink>() {
+            parquet.config()
+        } else if let Some(csv) = data_sink.downcast_ref::<CsvSink>() {
+            csv.config()
+        } else if let Some(json) = data_sink.downcast_ref::<JsonSink>() {
+            json.config()
+        } else {
+            return None;
+        };
+        match config.table_paths.as_slice() {
+            [path] if path.is_collection() => Some(config),
+            _ => None,
+        }
+    }
+}
+
+impl DisplayAs for ParallelFileSinkExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "ParallelFileSinkExec: output={}{}",
+                    self.object_store_url.as_str(),
+                    self.output_path
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for ParallelFileSinkExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.sink.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.sink.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        match children.as_slice() {
+            [sink] => Ok(Arc::new(Self::try_new(sink.clone())?)),
+            _ => Err(DataFusionError::Plan(
+                "ParallelFileSinkExec expects exactly one child".to_owned(),
+            )),
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let input = self.sink.children()[0].clone();
+        let input_partition: Arc<dyn ExecutionPlan> =
+            Arc::new(InputPartitionExec::new(input, partition));
+        self.sink
+            .clone()
+            .with_new_children(vec![input_partition])?
+            .execute(0, context)
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+/// Exposes a single partition of its input as its only partition
+#[derive(Debug)]
+struct InputPartitionExec {
+    input: Arc<dyn ExecutionPlan>,
+    partition: usize,
+    properties: PlanProperties,
+}
+
+impl InputPartitionExec {
+    fn new(input: Arc<dyn ExecutionPlan>, partition: usize) -> Self {
+        let properties = PlanProperties::new(
+            input.properties().equivalence_properties().clone(),
+            Partitioning::UnknownPartitioning(1),
+            ExecutionMode::Bounded,
+        );
+        Self {
+            input,
+            partition,
+            properties,
+        }
+    }
+}
+
+impl DisplayAs for InputPartitionExec {
+    fn fmt_as(
+        &self,
+        _t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        write!(f, "InputPartitionExec: partition={}", self.partition)
+    }
+}
+
+impl ExecutionPlan for InputPartitionExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        &self.properties
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        match children.as_slice() {
+            [input] => Ok(Arc::new(Self::new(input.clone(), self.partition))),
+            _ => Err(DataFusionError::Plan(
+                "InputPartitionExec expects exactly one child".to_owned(),
+            )),
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        if partition != 0 {
+            return Err(DataFusionError::Internal(format!(
+                "InputPartitionExec invalid partition {partition}"
+            )));
+        }
+        self.input.execute(self.partition, context)
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        Ok(Statistics::new_unknown(&self.schema()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Int32Array, UInt64Array};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::common::config::CsvOptions;
+    use datafusion::common::file_options::csv_writer::CsvWriterOptions;
+    use datafusion::datasource::listing::ListingTableUrl;
+    use datafusion::physical_plan::common::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+    use tempfile::TempDir;
+
+    fn csv_sink(input: Arc<dyn ExecutionPlan>, path: &str) -> Result<FileSinkExec> {
+        let config = FileSinkConfig {
+            object_store_url: ObjectStoreUrl::local_filesystem(),
+            file_groups: vec![],
+            table_paths: vec![ListingTableUrl::parse(path)?],
+            output_schema: input.schema(),
+            table_partition_cols: vec![],
+            overwrite: false,
+        };
+        let sink =
+            CsvSink::new(config, CsvWriterOptions::try_from(&CsvOptions::default())?);
+        Ok(FileSinkExec::new(
+            input.clone(),
+            Arc::new(sink),
+            input.schema(),
+            None,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_write_partitions_in_parallel() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let batch = |values: Vec<i32>| {
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(values))])
+        };
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![batch(vec![1, 2, 3])?], vec![batch(vec![4, 5])?]],
+            schema.clone(),
+            None,
+        )?);
+        let tmp_dir = TempDir::new()?;
+        let output = format!("{}/", tmp_dir.path().display());
+
+        let exec = ParallelFileSinkExec::try_new(Arc::new(csv_sink(input, &output)?))?;
+        assert_eq!(exec.properties().partitioning.partition_count(), 2);
+
+        let context = Arc::new(TaskContext::default());
+        let mut counts = vec![];
+        for partition in 0..2 {
+            let batches = collect(exec.execute(partition, context.clone())?).await?;
+            let count = batches[0]
+                .column(0)
+                .as_any()
+                .downcast_ref::<UInt64Array>()
+                .unwrap()
+                .value(0);
+            counts.push(count);
+        }
+        assert_eq!(counts, vec![3, 2]);
+        assert_eq!(std::fs::read_dir(tmp_dir.path())?.count(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_single_file_output_unsupported() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let input = Arc::new(MemoryExec::try_new(&[vec![]], schema, None)?);
+        let tmp_dir = TempDir::new()?;
+        let output = format!("{}", tmp_dir.path().join("output.csv").display());
+
+        let sink = csv_sink(input, &output)?;
+        assert!(!ParallelFileSinkExec::supports(&sink));
+        assert!(ParallelFileSinkExec::try_new(Arc::new(sink)).is_err());
+        Ok(())
+    }
+}
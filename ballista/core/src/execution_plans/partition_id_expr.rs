@@ -0,0 +1,136 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::fmt::{self, Display};
+use std::hash::Hasher;
+use std::sync::Arc;
+
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Schema};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::logical_expr::ColumnarValue;
+use datafusion::physical_plan::{Partitioning, PhysicalExpr};
+
+/// Create a custom partitioning, in which `expr` computes the output partition of every row
+/// directly, e.g. with a UDF bucketing the rows by range to match the layout of the table
+/// written by the job, rather than from the hash of partition keys.
+///
+/// The partitioning is a hash partitioning on a single [PartitionIdExpr], so that a
+/// [datafusion::physical_plan::repartition::RepartitionExec] with this partitioning, added
+/// to the physical plan by an extension planner or a physical optimizer rule, is planned as
+/// a shuffle like any other repartition. The shuffle writers then send every row to the
+/// partition computed by `expr`, which must be lower than `num_partitions`. DataFusion
+/// itself hashes the partitions computed by `expr` when running the repartition locally.
+pub fn custom_partitioning(
+    expr: Arc<dyn PhysicalExpr>,
+    num_partitions: usize,
+) -> Partitioning {
+    Partitioning::Hash(vec![Arc::new(PartitionIdExpr::new(expr))], num_partitions)
+}
+
+/// Expression computing the output partition of the rows of a custom partitioning, see
+/// [custom_partitioning]. Evaluates to the `UInt64` value of the wrapped expression.
+#[derive(Debug)]
+pub struct PartitionIdExpr {
+    expr: Arc<dyn PhysicalExpr>,
+}
+
+impl PartitionIdExpr {
+    pub fn new(expr: Arc<dyn PhysicalExpr>) -> Self {
+        Self { expr }
+    }
+
+    /// The expression computing the partitions
+    pub fn expr(&self) -> &Arc<dyn PhysicalExpr> {
+        &self.expr
+    }
+
+    /// The expression computing the partitions of a custom partitioning, `None` for the
+    /// other partitionings
+    pub fn from_partitioning(partitioning: &Partitioning) -> Option<&Self> {
+        match partitioning {
+            Partitioning::Hash(exprs, _) if exprs.len() == 1 => {
+                exprs[0].as_any().downcast_ref::<Self>()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Display for PartitionIdExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "partition_id({})", self.expr)
+    }
+}
+
+impl PartialEq<dyn Any> for PartitionIdExpr {
+    fn eq(&self, other: &dyn Any) -> bool {
+        let other = if let Some(expr) = other.downcast_ref::<Arc<dyn PhysicalExpr>>() {
+            expr.as_any()
+        } else if let Some(expr) = other.downcast_ref::<Box<dyn PhysicalExpr>>() {
+            expr.as_any()
+        } else {
+            other
+        };
+        other
+            .downcast_ref::<Self>()
+            .map(|other| self.expr.as_ref().eq(other.expr.as_any()))
+            .unwrap_or(false)
+    }
+}
+
+impl PhysicalExpr for PartitionIdExpr {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn data_type(&self, _input_schema: &Schema) -> Result<DataType> {
+        Ok(DataType::UInt64)
+    }
+
+    fn nullable(&self, input_schema: &Schema) -> Result<bool> {
+        self.expr.nullable(input_schema)
+    }
+
+    fn evaluate(&self, batch: &RecordBatch) -> Result<ColumnarValue> {
+        let partitions = self.expr.evaluate(batch)?.into_array(batch.num_rows())?;
+        Ok(ColumnarValue::Array(cast(&partitions, &DataType::UInt64)?))
+    }
+
+    fn children(&self) -> Vec<Arc<dyn PhysicalExpr>> {
+        vec![self.expr.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn PhysicalExpr>>,
+    ) -> Result<Arc<dyn PhysicalExpr>> {
+        match children.as_slice() {
+            [expr] => Ok(Arc::new(Self::new(expr.clone()))),
+            _ => Err(DataFusionError::Internal(format!(
+                "PartitionIdExpr expects a single child, got {}",
+                children.len()
+            ))),
+        }
+    }
+
+    fn dyn_hash(&self, state: &mut dyn Hasher) {
+        self.expr.dyn_hash(state);
+    }
+}
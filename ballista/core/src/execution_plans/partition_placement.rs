@@ -0,0 +1,163 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
+    SendableRecordBatchStream, Statistics,
+};
+
+/// PartitionPlacementExec declares the executor hosts on which each partition of its input
+/// is preferably executed, e.g. the hosts storing a shard of a custom data source.
+///
+/// The scheduler binds the tasks of a stage containing a PartitionPlacementExec to executors
+/// running on one of the preferred hosts of their partition, when such executors have available
+/// task slots, and falls back to its task distribution policy otherwise. Hosts are matched
+/// against the host the executors registered with. The execution is delegated to the input.
+#[derive(Debug, Clone)]
+pub struct PartitionPlacementExec {
+    input: Arc<dyn ExecutionPlan>,
+    /// The preferred executor hosts, in order of preference, for every input partition
+    preferred_hosts: Vec<Vec<String>>,
+}
+
+impl PartitionPlacementExec {
+    /// Create a new PartitionPlacementExec, with preferred hosts for every input partition
+    pub fn try_new(
+        input: Arc<dyn ExecutionPlan>,
+        preferred_hosts: Vec<Vec<String>>,
+    ) -> Result<Self> {
+        let partition_count = input.properties().partitioning.partition_count();
+        if preferred_hosts.len() != partition_count {
+            return Err(DataFusionError::Plan(format!(
+                "PartitionPlacementExec expects preferred hosts for {} partitions, got {}",
+                partition_count,
+                preferred_hosts.len()
+            )));
+        }
+        Ok(Self {
+            input,
+            preferred_hosts,
+        })
+    }
+
+    /// The preferred executor hosts for every partition
+    pub fn preferred_hosts(&self) -> &[Vec<String>] {
+        &self.preferred_hosts
+    }
+}
+
+impl DisplayAs for PartitionPlacementExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                write!(f, "PartitionPlacementExec")
+            }
+            DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "PartitionPlacementExec: preferred_hosts={:?}",
+                    self.preferred_hosts
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for PartitionPlacementExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn benefits_from_input_partitioning(&self) -> Vec<bool> {
+        vec![false]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        match children.as_slice() {
+            [input] => Ok(Arc::new(Self::try_new(
+                input.clone(),
+                self.preferred_hosts.clone(),
+            )?)),
+            _ => Err(DataFusionError::Plan(
+                "PartitionPlacementExec expects exactly one child".to_owned(),
+            )),
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        self.input.execute(partition, context)
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::empty::EmptyExec;
+
+    #[test]
+    fn test_preferred_hosts_per_partition() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let input = Arc::new(EmptyExec::new(schema).with_partitions(2));
+
+        let exec = PartitionPlacementExec::try_new(
+            input.clone(),
+            vec![vec!["host1".to_owned()], vec![]],
+        )?;
+        assert_eq!(exec.properties().partitioning.partition_count(), 2);
+        assert_eq!(exec.preferred_hosts()[0], vec!["host1".to_owned()]);
+
+        assert!(PartitionPlacementExec::try_new(input, vec![]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::{DataType, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::logical_expr::Accumulator;
+use datafusion::physical_expr::expressions::{Column, MaxAccumulator, MinAccumulator};
+use datafusion::physical_plan::{
+    DisplayAs, DisplayFormatType, ExecutionPlan, PlanProperties,
+    SendableRecordBatchStream, Statistics,
+};
+
+use crate::serde::scheduler::ColumnBounds;
+
+/// RuntimeFilterExec marks the probe side of a partitioned hash join, at the scan of its
+/// join key, as filtered by the min and max join key values of the build side of the join.
+///
+/// The stage of a RuntimeFilterExec depends on the stage computing the build side, whose
+/// outputs report the bounds of their columns. Once the build side stage completes, the
+/// scheduler replaces the RuntimeFilterExec with a filter of the probe join key between
+/// these bounds, which is also pushed to the parquet scans to prune their row groups. The
+/// execution is delegated to the input when the bounds are unknown.
+#[derive(Debug, Clone)]
+pub struct RuntimeFilterExec {
+    input: Arc<dyn ExecutionPlan>,
+    /// The stage computing the build side of the join
+    pub build_stage_id: usize,
+    /// Index of the join key in the output of the build side stage
+    pub build_column: usize,
+    /// The join key of the probe side, in the output of the input
+    pub probe_column: Column,
+}
+
+impl RuntimeFilterExec {
+    /// Create a new RuntimeFilterExec filtering the `probe_column` of the input with the
+    /// bounds of the `build_column` of the outputs of stage `build_stage_id`
+    pub fn try_new(
+        input: Arc<dyn ExecutionPlan>,
+        build_stage_id: usize,
+        build_column: usize,
+        probe_column: Column,
+    ) -> Result<Self> {
+        let schema = input.schema();
+        if schema.fields().len() <= probe_column.index()
+            || schema.field(probe_column.index()).name() != probe_column.name()
+        {
+            return Err(DataFusionError::Plan(format!(
+                "RuntimeFilterExec expects the column {probe_column} in its input"
+            )));
+        }
+        Ok(Self {
+            input,
+            build_stage_id,
+            build_column,
+            probe_column,
+        })
+    }
+
+    pub fn input(&self) -> &Arc<dyn ExecutionPlan> {
+        &self.input
+    }
+}
+
+impl DisplayAs for RuntimeFilterExec {
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default | DisplayFormatType::Verbose => {
+                write!(
+                    f,
+                    "RuntimeFilterExec: probe={}, build_stage={}, build_column={}",
+                    self.probe_column, self.build_stage_id, self.build_column
+                )
+            }
+        }
+    }
+}
+
+impl ExecutionPlan for RuntimeFilterExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn properties(&self) -> &PlanProperties {
+        self.input.properties()
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn benefits_from_input_partitioning(&self) -> Vec<bool> {
+        vec![false]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        match children.as_slice() {
+            [input] => Ok(Arc::new(Self::try_new(
+                input.clone(),
+                self.build_stage_id,
+                self.build_column,
+                self.probe_column.clone(),
+            )?)),
+            _ => Err(DataFusionError::Plan(
+                "RuntimeFilterExec expects exactly one child".to_owned(),
+            )),
+        }
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        self.input.execute(partition, context)
+    }
+
+    fn statistics(&self) -> Result<Statistics> {
+        self.input.statistics()
+    }
+}
+
+/// Whether the min and max values of the columns of the type are collected for the
+/// runtime filters, i.e. for the types of the usual join keys whose order is total
+pub fn supports_column_bounds(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Date32
+            | DataType::Date64
+            | DataType::Timestamp(_, _)
+            | DataType::Decimal128(_, _)
+            | DataType::Utf8
+            | DataType::LargeUtf8
+    )
+}
+
+/// Collector of the min and max values of the columns of the batches of a shuffle partition
+pub(crate) struct ColumnBoundsCollector {
+    /// The accumulators of the columns whose bounds are collected
+    accumulators: Vec<Option<(MinAccumulator, MaxAccumulator)>>,
+}
+
+impl ColumnBoundsCollector {
+    pub fn new(schema: &Schema) -> Self {
+        let accumulators = schema
+            .fields()
+            .iter()
+            .map(|field| {
+                let data_type = field.data_type();
+                if !supports_column_bounds(data_type) {
+                    return None;
+                }
+                Some((
+                    MinAccumulator::try_new(data_type).ok()?,
+                    MaxAccumulator::try_new(data_type).ok()?,
+                ))
+            })
+            .collect();
+        Self { accumulators }
+    }
+
+    pub fn update(&mut self, batch: &RecordBatch) -> Result<()> {
+        for (accumulators, column) in self.accumulators.iter_mut().zip(batch.columns()) {
+            if let Some((min, max)) = accumulators {
+                let values = [column.clone()];
+                min.update_batch(&values)?;
+                max.update_batch(&values)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Vec<ColumnBounds> {
+        self.accumulators
+            .into_iter()
+            .map(|accumulators| match accumulators {
+                Some((mut min, mut max)) => ColumnBounds {
+                    min: min.evaluate().ok().filter(|value| !value.is_null()),
+                    max: max.evaluate().ok().filter(|value| !value.is_null()),
+                },
+                None => ColumnBounds::default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Float64Array, Int32Array, StringArray};
+    use datafusion::arrow::datatypes::Field;
+    use datafusion::common::ScalarValue;
+
+    #[test]
+    fn test_column_bounds() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, true),
+            Field::new("b", DataType::Utf8, true),
+            Field::new("c", DataType::Float64, true),
+        ]));
+        let mut collector = ColumnBoundsCollector::new(&schema);
+        for (a, b) in [
+            (vec![Some(3), None, Some(7)], None),
+            (vec![Some(5)], Some("x")),
+        ] {
+            let num_rows = a.len();
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(a)),
+                    Arc::new(StringArray::from(vec![b; num_rows])),
+                    Arc::new(Float64Array::from(vec![1.0; num_rows])),
+                ],
+            )?;
+            collector.update(&batch)?;
+        }
+
+        let bounds = collector.finish();
+        assert_eq!(
+            bounds,
+            vec![
+                ColumnBounds {
+                    min: Some(ScalarValue::Int32(Some(3))),
+                    max: Some(ScalarValue::Int32(Some(7))),
+                },
+                ColumnBounds {
+                    min: Some(ScalarValue::Utf8(Some("x".to_owned()))),
+                    max: Some(ScalarValue::Utf8(Some("x".to_owned()))),
+                },
+                // the bounds of floats are not collected
+                ColumnBounds::default(),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -19,7 +19,7 @@ use async_trait::async_trait;
 use datafusion::arrow::ipc::reader::StreamReader;
 use datafusion::common::stats::Precision;
 use std::any::Any;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs::File;
 use std::io::BufReader;
@@ -27,32 +27,117 @@ use std::pin::Pin;
 use std::result;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 
 use crate::client::BallistaClient;
+use crate::config::{
+    BALLISTA_SHUFFLE_COALESCE_BATCHES, BALLISTA_SHUFFLE_MERGE_FETCH_THRESHOLD,
+    BALLISTA_SHUFFLE_PREFETCH_BATCHES,
+};
+use crate::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use crate::serde::protobuf::GetShuffleLocationsParams;
 use crate::serde::scheduler::{PartitionLocation, PartitionStats};
 
+use datafusion::arrow::compute::concat_batches;
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::common::runtime::SpawnedTask;
 
-use datafusion::error::Result;
+use datafusion::error::{DataFusionError, Result};
 use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricsSet};
 use datafusion::physical_plan::{
     ColumnStatistics, DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning,
     PlanProperties, RecordBatchStream, SendableRecordBatchStream, Statistics,
 };
-use futures::{Stream, StreamExt, TryStreamExt};
+use futures::{ready, Stream, StreamExt, TryStreamExt};
 
 use crate::error::BallistaError;
 use datafusion::execution::context::TaskContext;
-use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion::physical_plan::stream::{
+    RecordBatchReceiverStream, RecordBatchStreamAdapter,
+};
 use itertools::Itertools;
 use log::{error, info};
 use rand::prelude::SliceRandom;
 use rand::thread_rng;
 use tokio::sync::{mpsc, Semaphore};
 use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
+
+/// The default maximum number of concurrent requests a task sends to fetch shuffle partitions
+pub const DEFAULT_SHUFFLE_READER_MAX_REQUESTS: usize = 50;
+
+/// The default min number of partitions a task reads from the same executor for them to be
+/// fetched as a single merged stream, 0 to fetch them one by one as executors of older
+/// versions can not serve merged fetches
+pub const DEFAULT_SHUFFLE_READER_MERGE_FETCH_THRESHOLD: usize = 0;
+
+/// The default number of batches a task fetches ahead of the batches it computes
+pub const DEFAULT_SHUFFLE_READER_PREFETCH_BATCHES: usize = 2;
+
+/// Interval at which the tasks of pipelined stages poll the scheduler for the locations of
+/// the shuffle partitions written since they started
+const PIPELINED_LOCATIONS_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Options of the [ShuffleReaderExec], which executors can set as an extension of the
+/// [datafusion::prelude::SessionConfig] of their tasks
+#[derive(Debug, Clone)]
+pub struct ShuffleReaderOptions {
+    /// The maximum number of concurrent requests sent to fetch shuffle partitions
+    pub max_requests: usize,
+    /// The min number of partitions read from the same executor for this executor to merge
+    /// them into a single stream, 0 to fetch the partitions one by one
+    pub merge_fetch_threshold: usize,
+    /// The number of batches fetched ahead of the batches consumed by the task, 0 to fetch
+    /// them on demand
+    pub prefetch_batches: usize,
+    /// Whether the consecutive batches smaller than the batch size of the task are
+    /// concatenated
+    pub coalesce_batches: bool,
+    /// Client of the scheduler of the task, from which the shuffle readers of pipelined
+    /// stages get the locations of the partitions written after the task started
+    pub scheduler: Option<SchedulerGrpcClient<Channel>>,
+}
+
+impl Default for ShuffleReaderOptions {
+    fn default() -> Self {
+        Self {
+            max_requests: DEFAULT_SHUFFLE_READER_MAX_REQUESTS,
+            merge_fetch_threshold: DEFAULT_SHUFFLE_READER_MERGE_FETCH_THRESHOLD,
+            prefetch_batches: DEFAULT_SHUFFLE_READER_PREFETCH_BATCHES,
+            coalesce_batches: true,
+            scheduler: None,
+        }
+    }
+}
+
+impl ShuffleReaderOptions {
+    /// The options set by the props of a task, sending at most `max_requests` concurrent
+    /// requests and polling the `scheduler` of the task
+    pub fn from_props(
+        props: &HashMap<String, String>,
+        max_requests: usize,
+        scheduler: Option<SchedulerGrpcClient<Channel>>,
+    ) -> Self {
+        Self {
+            max_requests,
+            merge_fetch_threshold: props
+                .get(BALLISTA_SHUFFLE_MERGE_FETCH_THRESHOLD)
+                .and_then(|threshold| threshold.parse().ok())
+                .unwrap_or(DEFAULT_SHUFFLE_READER_MERGE_FETCH_THRESHOLD),
+            prefetch_batches: props
+                .get(BALLISTA_SHUFFLE_PREFETCH_BATCHES)
+                .and_then(|batches| batches.parse().ok())
+                .unwrap_or(DEFAULT_SHUFFLE_READER_PREFETCH_BATCHES),
+            coalesce_batches: props
+                .get(BALLISTA_SHUFFLE_COALESCE_BATCHES)
+                .and_then(|coalesce| coalesce.parse().ok())
+                .unwrap_or(true),
+            scheduler,
+        }
+    }
+}
 
 /// ShuffleReaderExec reads partitions that have already been materialized by a ShuffleWriterExec
 /// being executed by an executor
@@ -63,6 +148,10 @@ pub struct ShuffleReaderExec {
     pub(crate) schema: SchemaRef,
     /// Each partition of a shuffle can read data from multiple locations
     pub partition: Vec<Vec<PartitionLocation>>,
+    /// The job of the stage to read from if this stage is pipelined, i.e. started before
+    /// the stage to read from completed. The locations of the partitions written since are
+    /// then polled from the scheduler.
+    pub pipelined_job_id: Option<String>,
     /// Execution metrics
     metrics: ExecutionPlanMetricsSet,
     properties: PlanProperties,
@@ -86,10 +175,17 @@ impl ShuffleReaderExec {
             stage_id,
             schema,
             partition,
+            pipelined_job_id: None,
             metrics: ExecutionPlanMetricsSet::new(),
             properties,
         })
     }
+
+    /// Read from a stage of the given job which may not be complete yet
+    pub fn with_pipelined_job_id(mut self, job_id: Option<String>) -> Self {
+        self.pipelined_job_id = job_id;
+        self
+    }
 }
 
 impl DisplayAs for ShuffleReaderExec {
@@ -100,7 +196,11 @@ impl DisplayAs for ShuffleReaderExec {
     ) -> std::fmt::Result {
         match t {
             DisplayFormatType::Default | DisplayFormatType::Verbose => {
-                write!(f, "ShuffleReaderExec: partitions={}", self.partition.len())
+                write!(f, "ShuffleReaderExec: partitions={}", self.partition.len())?;
+                if self.pipelined_job_id.is_some() {
+                    write!(f, ", pipelined")?;
+                }
+                Ok(())
             }
         }
     }
@@ -126,11 +226,14 @@ impl ExecutionPlan for ShuffleReaderExec {
         self: Arc<Self>,
         _children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(ShuffleReaderExec::try_new(
-            self.stage_id,
-            self.partition.clone(),
-            self.schema.clone(),
-        )?))
+        Ok(Arc::new(
+            ShuffleReaderExec::try_new(
+                self.stage_id,
+                self.partition.clone(),
+                self.schema.clone(),
+            )?
+            .with_pipelined_job_id(self.pipelined_job_id.clone()),
+        ))
     }
 
     fn execute(
@@ -141,8 +244,12 @@ impl ExecutionPlan for ShuffleReaderExec {
         let task_id = context.task_id().unwrap_or_else(|| partition.to_string());
         info!("ShuffleReaderExec::execute({})", task_id);
 
-        // TODO make the maximum size configurable, or make it depends on global memory control
-        let max_request_num = 50usize;
+        // TODO make the maximum size depend on global memory control
+        let options = context
+            .session_config()
+            .get_extension::<ShuffleReaderOptions>()
+            .map(|options| options.as_ref().clone())
+            .unwrap_or_default();
         let mut partition_locations = HashMap::new();
         for p in &self.partition[partition] {
             partition_locations
@@ -160,14 +267,42 @@ impl ExecutionPlan for ShuffleReaderExec {
         // Shuffle partitions for evenly send fetching partition requests to avoid hot executors within multiple tasks
         partition_locations.shuffle(&mut thread_rng());
 
-        let response_receiver =
-            send_fetch_partitions(partition_locations, max_request_num);
+        let prefetch_batches = options.prefetch_batches;
+        let coalesce_batches = options.coalesce_batches;
+        let response_receiver = match &self.pipelined_job_id {
+            Some(job_id) => {
+                let scheduler = options.scheduler.clone().ok_or_else(|| {
+                    DataFusionError::Execution(
+                        "A pipelined shuffle read requires a scheduler client".to_owned(),
+                    )
+                })?;
+                send_pipelined_fetch_partitions(
+                    job_id.clone(),
+                    self.stage_id,
+                    partition,
+                    partition_locations,
+                    scheduler,
+                    options,
+                )
+            }
+            None => send_fetch_partitions(
+                partition_locations,
+                options.max_requests,
+                options.merge_fetch_threshold,
+            ),
+        };
 
         let result = RecordBatchStreamAdapter::new(
             Arc::new(self.schema.as_ref().clone()),
             response_receiver.try_flatten(),
         );
-        Ok(Box::pin(result))
+        let target_batch_size =
+            coalesce_batches.then(|| context.session_config().batch_size());
+        Ok(read_ahead(
+            Box::pin(result),
+            target_batch_size,
+            prefetch_batches,
+        ))
     }
 
     fn metrics(&self) -> Option<MetricsSet> {
@@ -175,37 +310,58 @@ impl ExecutionPlan for ShuffleReaderExec {
     }
 
     fn statistics(&self) -> Result<Statistics> {
+        if self.pipelined_job_id.is_some() {
+            // the partitions written after the stage started are unknown
+            return Ok(Statistics::new_unknown(&self.schema));
+        }
         Ok(stats_for_partitions(
             self.schema.fields().len(),
             self.partition
                 .iter()
                 .flatten()
-                .map(|loc| loc.partition_stats),
+                .map(|loc| &loc.partition_stats),
         ))
     }
 }
 
-fn stats_for_partitions(
+fn stats_for_partitions<'a>(
     num_fields: usize,
-    partition_stats: impl Iterator<Item = PartitionStats>,
+    partition_stats: impl Iterator<Item = &'a PartitionStats>,
 ) -> Statistics {
-    // TODO stats: add column statistics to PartitionStats
-    let (num_rows, total_byte_size) =
-        partition_stats.fold((Some(0), Some(0)), |(num_rows, total_byte_size), part| {
+    // TODO stats: add min, max and distinct count column statistics to PartitionStats
+    let (num_rows, total_byte_size, null_counts) = partition_stats.fold(
+        (Some(0), Some(0), Some(vec![0; num_fields])),
+        |(num_rows, total_byte_size, null_counts), part| {
             // if any statistic is unkown it makes the entire statistic unkown
             let num_rows = num_rows.zip(part.num_rows).map(|(a, b)| a + b as usize);
             let total_byte_size = total_byte_size
                 .zip(part.num_bytes)
                 .map(|(a, b)| a + b as usize);
-            (num_rows, total_byte_size)
-        });
+            let null_counts = null_counts
+                .zip(part.null_counts())
+                .filter(|(a, b)| a.len() == b.len())
+                .map(|(a, b)| a.iter().zip(b).map(|(a, b)| a + *b as usize).collect());
+            (num_rows, total_byte_size, null_counts)
+        },
+    );
+
+    let column_statistics = match null_counts {
+        Some(null_counts) => null_counts
+            .into_iter()
+            .map(|null_count| ColumnStatistics {
+                null_count: Precision::Exact(null_count),
+                ..ColumnStatistics::new_unknown()
+            })
+            .collect(),
+        None => vec![ColumnStatistics::new_unknown(); num_fields],
+    };
 
     Statistics {
         num_rows: num_rows.map(Precision::Exact).unwrap_or(Precision::Absent),
         total_byte_size: total_byte_size
             .map(Precision::Exact)
             .unwrap_or(Precision::Absent),
-        column_statistics: vec![ColumnStatistics::new_unknown(); num_fields],
+        column_statistics,
     }
 }
 
@@ -239,6 +395,111 @@ impl RecordBatchStream for LocalShuffleStream {
     }
 }
 
+/// Coalesce the batches of a shuffle read to `target_batch_size` rows if set, and fetch
+/// `prefetch_batches` batches ahead of the consumer of the stream in a background task, so
+/// that fetching the shuffle partitions overlaps computing the batches already fetched
+fn read_ahead(
+    stream: SendableRecordBatchStream,
+    target_batch_size: Option<usize>,
+    prefetch_batches: usize,
+) -> SendableRecordBatchStream {
+    let mut stream = match target_batch_size {
+        Some(target_batch_size) => {
+            Box::pin(CoalesceBatchesStream::new(stream, target_batch_size))
+        }
+        None => stream,
+    };
+    if prefetch_batches == 0 {
+        return stream;
+    }
+    let mut builder =
+        RecordBatchReceiverStream::builder(stream.schema(), prefetch_batches);
+    let sender = builder.tx();
+    builder.spawn(async move {
+        while let Some(batch) = stream.next().await {
+            if sender.send(batch).await.is_err() {
+                // the consumer of the stream is gone
+                break;
+            }
+        }
+        Ok(())
+    });
+    builder.build()
+}
+
+/// Concatenates the consecutive batches of a stream smaller than a target number of rows,
+/// as the shuffle partitions of the reduce tasks often hold tiny batches
+struct CoalesceBatchesStream {
+    input: SendableRecordBatchStream,
+    target_batch_size: usize,
+    buffer: Vec<RecordBatch>,
+    buffered_rows: usize,
+    finished: bool,
+}
+
+impl CoalesceBatchesStream {
+    fn new(input: SendableRecordBatchStream, target_batch_size: usize) -> Self {
+        Self {
+            input,
+            target_batch_size,
+            buffer: vec![],
+            buffered_rows: 0,
+            finished: false,
+        }
+    }
+
+    fn flush(&mut self) -> Result<RecordBatch> {
+        let batches = std::mem::take(&mut self.buffer);
+        self.buffered_rows = 0;
+        Ok(concat_batches(&batches[0].schema(), &batches)?)
+    }
+}
+
+impl Stream for CoalesceBatchesStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.finished {
+                return Poll::Ready(None);
+            }
+            match ready!(self.input.poll_next_unpin(cx)) {
+                Some(Ok(batch)) => {
+                    if batch.num_rows() == 0 {
+                        continue;
+                    }
+                    if self.buffer.is_empty()
+                        && batch.num_rows() >= self.target_batch_size
+                    {
+                        return Poll::Ready(Some(Ok(batch)));
+                    }
+                    self.buffered_rows += batch.num_rows();
+                    self.buffer.push(batch);
+                    if self.buffered_rows >= self.target_batch_size {
+                        return Poll::Ready(Some(self.flush()));
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    self.finished = true;
+                    if !self.buffer.is_empty() {
+                        return Poll::Ready(Some(self.flush()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl RecordBatchStream for CoalesceBatchesStream {
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+}
+
 /// Adapter for a tokio ReceiverStream that implements the SendableRecordBatchStream interface
 struct AbortableReceiverStream {
     inner: ReceiverStream<result::Result<SendableRecordBatchStream, BallistaError>>,
@@ -279,9 +540,108 @@ impl Stream for AbortableReceiverStream {
 fn send_fetch_partitions(
     partition_locations: Vec<PartitionLocation>,
     max_request_num: usize,
+    merge_fetch_threshold: usize,
 ) -> AbortableReceiverStream {
     let (response_sender, response_receiver) = mpsc::channel(max_request_num);
     let semaphore = Arc::new(Semaphore::new(max_request_num));
+    let spawned_tasks = spawn_fetch_partitions(
+        partition_locations,
+        merge_fetch_threshold,
+        &semaphore,
+        &response_sender,
+    );
+    AbortableReceiverStream::create(response_receiver, spawned_tasks)
+}
+
+/// Fetch the partitions of a shuffle whose writing stage may still be running, polling the
+/// scheduler for the locations written since the task started until the stage completes.
+/// Each map partition is fetched once, from the first location reported for it.
+fn send_pipelined_fetch_partitions(
+    job_id: String,
+    stage_id: usize,
+    partition_id: usize,
+    partition_locations: Vec<PartitionLocation>,
+    mut scheduler: SchedulerGrpcClient<Channel>,
+    options: ShuffleReaderOptions,
+) -> AbortableReceiverStream {
+    let (response_sender, response_receiver) = mpsc::channel(options.max_requests);
+    let semaphore = Arc::new(Semaphore::new(options.max_requests));
+    let poll_task = SpawnedTask::spawn(async move {
+        let mut fetched_map_partitions = HashSet::new();
+        let mut spawned_tasks = vec![];
+        let mut locations = partition_locations;
+        let mut complete = false;
+        loop {
+            let new_locations = locations
+                .into_iter()
+                .filter(|p| fetched_map_partitions.insert(p.map_partition_id))
+                .collect::<Vec<_>>();
+            if !new_locations.is_empty() {
+                spawned_tasks.extend(spawn_fetch_partitions(
+                    new_locations,
+                    options.merge_fetch_threshold,
+                    &semaphore,
+                    &response_sender,
+                ));
+            }
+            if complete {
+                break;
+            }
+
+            tokio::time::sleep(PIPELINED_LOCATIONS_POLL_INTERVAL).await;
+            let result = scheduler
+                .get_shuffle_locations(GetShuffleLocationsParams {
+                    job_id: job_id.clone(),
+                    stage_id: stage_id as u32,
+                    partition_id: partition_id as u32,
+                })
+                .await
+                .map_err(|e| {
+                    BallistaError::General(format!(
+                        "Failed to get the locations of shuffle partition {job_id}/{stage_id}/{partition_id}: {e}"
+                    ))
+                })
+                .and_then(|result| {
+                    let result = result.into_inner();
+                    let locations: Vec<PartitionLocation> = result
+                        .partition_location
+                        .into_iter()
+                        .map(|location| location.try_into())
+                        .collect::<result::Result<_, BallistaError>>()?;
+                    Ok((locations, result.complete))
+                });
+            match result {
+                Ok((new_locations, is_complete)) => {
+                    locations = new_locations;
+                    complete = is_complete;
+                }
+                Err(e) => {
+                    if let Err(e) = response_sender.send(Err(e)).await {
+                        error!("Fail to send response event to the channel due to {}", e);
+                    }
+                    break;
+                }
+            }
+        }
+        // the fetching tasks are aborted when dropped
+        for task in spawned_tasks {
+            let _ = task.join().await;
+        }
+    });
+
+    AbortableReceiverStream::create(response_receiver, vec![poll_task])
+}
+
+/// Spawn the tasks fetching the partitions, which send the partition streams to
+/// `response_sender` while holding at most as many remote requests as permits of `semaphore`
+fn spawn_fetch_partitions(
+    partition_locations: Vec<PartitionLocation>,
+    merge_fetch_threshold: usize,
+    semaphore: &Arc<Semaphore>,
+    response_sender: &mpsc::Sender<
+        result::Result<SendableRecordBatchStream, BallistaError>,
+    >,
+) -> Vec<SpawnedTask<()>> {
     let mut spawned_tasks: Vec<SpawnedTask<()>> = vec![];
     let (local_locations, remote_locations): (Vec<_>, Vec<_>) = partition_locations
         .into_iter()
@@ -304,6 +664,32 @@ fn send_fetch_partitions(
         }
     }));
 
+    let (merged_locations, remote_locations) =
+        split_merged_fetches(remote_locations, merge_fetch_threshold);
+    if !merged_locations.is_empty() {
+        info!(
+            "merging the shuffle files of {} executors, remaining remote shuffle file count:{}.",
+            merged_locations.len(),
+            remote_locations.len()
+        );
+    }
+
+    for ps in merged_locations.into_iter() {
+        let semaphore = semaphore.clone();
+        let response_sender = response_sender.clone();
+        spawned_tasks.push(SpawnedTask::spawn(async move {
+            // Block if exceeds max request number.
+            let permit = semaphore.acquire_owned().await.unwrap();
+            let r = fetch_partitions_remote(ps).await;
+            // Block if the channel buffer is full.
+            if let Err(e) = response_sender.send(r).await {
+                error!("Fail to send response event to the channel due to {}", e);
+            }
+            // Increase semaphore by dropping existing permits.
+            drop(permit);
+        }));
+    }
+
     for p in remote_locations.into_iter() {
         let semaphore = semaphore.clone();
         let response_sender = response_sender.clone();
@@ -320,7 +706,39 @@ fn send_fetch_partitions(
         }));
     }
 
-    AbortableReceiverStream::create(response_receiver, spawned_tasks)
+    spawned_tasks
+}
+
+/// Split the remote locations into the groups of locations of the executors holding at least
+/// `merge_fetch_threshold` of them, which are fetched as a single merged stream, and the
+/// locations fetched one by one
+fn split_merged_fetches(
+    remote_locations: Vec<PartitionLocation>,
+    merge_fetch_threshold: usize,
+) -> (Vec<Vec<PartitionLocation>>, Vec<PartitionLocation>) {
+    if merge_fetch_threshold == 0 {
+        return (vec![], remote_locations);
+    }
+    let mut locations_by_executor: HashMap<String, Vec<PartitionLocation>> =
+        HashMap::new();
+    for p in remote_locations {
+        locations_by_executor
+            .entry(p.executor_meta.id.clone())
+            .or_default()
+            .push(p);
+    }
+    let mut merged = vec![];
+    let mut single = vec![];
+    for ps in locations_by_executor.into_values() {
+        if ps.len() >= merge_fetch_threshold {
+            merged.push(ps);
+        } else {
+            single.extend(ps);
+        }
+    }
+    // keep the requests evenly spread over the executors
+    single.shuffle(&mut thread_rng());
+    (merged, single)
 }
 
 fn check_is_local_location(location: &PartitionLocation) -> bool {
@@ -390,6 +808,37 @@ async fn fetch_partition_remote(
         .await
 }
 
+/// Fetch the partitions held by the same executor as a single stream merged by the executor
+async fn fetch_partitions_remote(
+    locations: Vec<PartitionLocation>,
+) -> result::Result<SendableRecordBatchStream, BallistaError> {
+    let first = locations.first().ok_or_else(|| {
+        BallistaError::Internal("No partition location to fetch".to_owned())
+    })?;
+    let metadata = first.executor_meta.clone();
+    let partition_id = first.partition_id.clone();
+    let host = metadata.host.as_str();
+    let port = metadata.port;
+    let mut ballista_client =
+        BallistaClient::try_new(host, port)
+            .await
+            .map_err(|error| match error {
+                // map grpc connection error to partition fetch error.
+                BallistaError::GrpcConnectionError(msg) => BallistaError::FetchFailed(
+                    metadata.id.clone(),
+                    partition_id.stage_id,
+                    partition_id.partition_id,
+                    msg,
+                ),
+                other => other,
+            })?;
+
+    let paths = locations.into_iter().map(|p| p.path).collect();
+    ballista_client
+        .fetch_partitions(&metadata.id, &partition_id, paths, host, port)
+        .await
+}
+
 async fn fetch_partition_local(
     location: &PartitionLocation,
 ) -> result::Result<SendableRecordBatchStream, BallistaError> {
@@ -433,6 +882,7 @@ async fn fetch_partition_object_store(
 mod tests {
     use super::*;
     use crate::execution_plans::ShuffleWriterExec;
+    use crate::protocol::PROTOCOL_VERSION;
     use crate::serde::scheduler::{ExecutorMetadata, ExecutorSpecification, PartitionId};
     use crate::utils;
     use datafusion::arrow::array::{Int32Array, StringArray, UInt32Array};
@@ -442,7 +892,7 @@ mod tests {
     use datafusion::common::DataFusionError;
     use datafusion::physical_expr::expressions::Column;
     use datafusion::physical_plan::common;
-    use datafusion::physical_plan::memory::MemoryExec;
+    use datafusion::physical_plan::memory::{MemoryExec, MemoryStream};
     use datafusion::prelude::SessionContext;
     use tempfile::{tempdir, TempDir};
 
@@ -466,20 +916,28 @@ mod tests {
                 num_rows: Some(10),
                 num_bytes: Some(84),
                 num_batches: Some(1),
+                null_counts: Some(vec![1, 0]),
+                column_bounds: None,
             },
             PartitionStats {
                 num_rows: Some(4),
                 num_bytes: Some(65),
                 num_batches: None,
+                null_counts: Some(vec![2, 3]),
+                column_bounds: None,
             },
         ];
 
-        let result = stats_for_partitions(0, part_stats.into_iter());
+        let result = stats_for_partitions(2, part_stats.iter());
 
+        let column_stats = |null_count| ColumnStatistics {
+            null_count: Precision::Exact(null_count),
+            ..ColumnStatistics::new_unknown()
+        };
         let exptected = Statistics {
             num_rows: Precision::Exact(14),
             total_byte_size: Precision::Exact(149),
-            column_statistics: vec![],
+            column_statistics: vec![column_stats(3), column_stats(3)],
         };
 
         assert_eq!(result, exptected);
@@ -492,20 +950,24 @@ mod tests {
                 num_rows: Some(10),
                 num_bytes: Some(84),
                 num_batches: Some(1),
+                null_counts: Some(vec![1]),
+                column_bounds: None,
             },
             PartitionStats {
                 num_rows: None,
                 num_bytes: None,
                 num_batches: None,
+                null_counts: None,
+                column_bounds: None,
             },
         ];
 
-        let result = stats_for_partitions(0, part_stats.into_iter());
+        let result = stats_for_partitions(1, part_stats.iter());
 
         let exptected = Statistics {
             num_rows: Precision::Absent,
             total_byte_size: Precision::Absent,
-            column_statistics: vec![],
+            column_statistics: vec![ColumnStatistics::new_unknown()],
         };
 
         assert_eq!(result, exptected);
@@ -538,6 +1000,11 @@ mod tests {
                     port: 7070,
                     grpc_port: 8080,
                     specification: ExecutorSpecification { task_slots: 1 },
+                    zone: String::new(),
+                    rack: String::new(),
+                    instance_type: String::new(),
+                    capabilities: Default::default(),
+                    protocol_version: PROTOCOL_VERSION,
                 },
                 partition_stats: Default::default(),
                 path: "test_path".to_string(),
@@ -636,8 +1103,11 @@ mod tests {
             file_path.to_str().unwrap().to_string(),
         );
 
-        let response_receiver =
-            send_fetch_partitions(partition_locations, max_request_num);
+        let response_receiver = send_fetch_partitions(
+            partition_locations,
+            max_request_num,
+            DEFAULT_SHUFFLE_READER_MERGE_FETCH_THRESHOLD,
+        );
 
         let stream = RecordBatchStreamAdapter::new(
             Arc::new(schema),
@@ -648,6 +1118,58 @@ mod tests {
         assert_eq!(partition_num, result.len());
     }
 
+    #[tokio::test]
+    async fn test_read_ahead() -> Result<()> {
+        let batch = create_test_batch();
+        let batches = vec![
+            batch.slice(0, 1),
+            batch.slice(1, 1),
+            batch.slice(2, 0),
+            batch.slice(2, 1),
+            batch.clone(),
+            batch.slice(0, 2),
+        ];
+        let stream = || -> Result<SendableRecordBatchStream> {
+            Ok(Box::pin(MemoryStream::try_new(
+                batches.clone(),
+                create_test_schema(),
+                None,
+            )?))
+        };
+
+        let result = common::collect(read_ahead(stream()?, Some(3), 2)).await?;
+        let num_rows: Vec<usize> = result.iter().map(|b| b.num_rows()).collect();
+        assert_eq!(vec![3, 3, 2], num_rows);
+        assert_eq!(batch, result[0]);
+
+        let result = common::collect(read_ahead(stream()?, None, 0)).await?;
+        assert_eq!(batches.len(), result.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_merged_fetches() {
+        let mut locations = get_test_partition_locations(4, "path".to_string());
+        for p in locations.iter_mut().take(3) {
+            p.executor_meta.id = "exec0".to_string();
+        }
+
+        let (merged, single) = split_merged_fetches(locations.clone(), 3);
+        assert_eq!(1, merged.len());
+        assert_eq!(3, merged[0].len());
+        assert!(merged[0].iter().all(|p| p.executor_meta.id == "exec0"));
+        assert_eq!(1, single.len());
+        assert_eq!("exec3", single[0].executor_meta.id);
+
+        let (merged, single) = split_merged_fetches(locations.clone(), 4);
+        assert!(merged.is_empty());
+        assert_eq!(4, single.len());
+
+        let (merged, single) = split_merged_fetches(locations, 0);
+        assert!(merged.is_empty());
+        assert_eq!(4, single.len());
+    }
+
     fn get_test_partition_locations(n: usize, path: String) -> Vec<PartitionLocation> {
         (0..n)
             .map(|partition_id| PartitionLocation {
@@ -663,6 +1185,11 @@ mod tests {
                     port: 50051,
                     grpc_port: 50052,
                     specification: ExecutorSpecification { task_slots: 12 },
+                    zone: String::new(),
+                    rack: String::new(),
+                    instance_type: String::new(),
+                    capabilities: Default::default(),
+                    protocol_version: PROTOCOL_VERSION,
                 },
                 partition_stats: Default::default(),
                 path: path.clone(),
@@ -0,0 +1,311 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Asynchronous writes of the shuffle partitions of a task.
+//!
+//! The batches of every output partition are sent to an IO task of their own, which writes
+//! them to the partition file while the task computes the next batches. The batches waiting
+//! to be written by all the partitions of the task are bounded by a memory budget, the
+//! computation waiting for the IO tasks whenever the budget is exhausted.
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::ipc::writer::{IpcWriteOptions, StreamWriter};
+use datafusion::arrow::ipc::CompressionType;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_plan::metrics;
+use log::debug;
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+
+use crate::disk_io::DiskIoPool;
+use crate::execution_plans::runtime_filter::ColumnBoundsCollector;
+use crate::serde::scheduler::ColumnBounds;
+use crate::utils::batch_memory_size;
+
+/// A shuffle partition written to disk
+#[derive(Debug)]
+pub(crate) struct SpilledPartition {
+    pub path: PathBuf,
+    pub num_batches: u64,
+    pub num_rows: u64,
+    /// Size of the partition file
+    pub num_bytes: u64,
+    /// Number of null values of each column
+    pub null_counts: Vec<u64>,
+    /// Min and max values of each column, empty if not collected
+    pub column_bounds: Vec<ColumnBounds>,
+}
+
+type PartitionPath = Box<dyn Fn(usize) -> PathBuf + Send>;
+
+/// Writer of the output partitions of a task, see the module documentation
+pub(crate) struct ShuffleSpiller {
+    schema: SchemaRef,
+    /// Permits of the memory budget, one per byte
+    budget: Arc<Semaphore>,
+    budget_bytes: usize,
+    /// The IO tasks of the output partitions, started with their first batch
+    writers: Vec<Option<PartitionWriter>>,
+    partition_path: PartitionPath,
+    write_time: metrics::Time,
+    io_pool: Option<Arc<DiskIoPool>>,
+    column_bounds: bool,
+}
+
+struct PartitionWriter {
+    tx: mpsc::UnboundedSender<(RecordBatch, OwnedSemaphorePermit)>,
+    handle: JoinHandle<Result<SpilledPartition>>,
+}
+
+impl ShuffleSpiller {
+    /// Create a writer of `num_partitions` output partitions, written to the files given by
+    /// `partition_path`. The time spent writing is added to `write_time`.
+    pub fn new(
+        schema: SchemaRef,
+        num_partitions: usize,
+        write_buffer_size: usize,
+        write_time: metrics::Time,
+        partition_path: impl Fn(usize) -> PathBuf + Send + 'static,
+    ) -> Self {
+        // a single batch larger than the budget takes all of it
+        let budget_bytes = write_buffer_size.clamp(1, Semaphore::MAX_PERMITS);
+        Self {
+            schema,
+            budget: Arc::new(Semaphore::new(budget_bytes)),
+            budget_bytes,
+            writers: (0..num_partitions).map(|_| None).collect(),
+            partition_path: Box::new(partition_path),
+            write_time,
+            io_pool: None,
+            column_bounds: false,
+        }
+    }
+
+    /// Run the writes in a disk IO pool rather than on any blocking thread of the runtime
+    pub fn with_io_pool(mut self, io_pool: Option<Arc<DiskIoPool>>) -> Self {
+        self.io_pool = io_pool;
+        self
+    }
+
+    /// Collect the min and max values of the columns of every partition
+    pub fn with_column_bounds(mut self, column_bounds: bool) -> Self {
+        self.column_bounds = column_bounds;
+        self
+    }
+
+    /// Queue a batch of an output partition to be written, waiting for earlier batches to be
+    /// written first if the memory budget is exhausted. The batches are slices of the
+    /// same partitioned batch, each counted by the parts of the buffers it uses.
+    pub async fn write(&mut self, partition: usize, batch: RecordBatch) -> Result<()> {
+        let size = batch_memory_size(&batch)?.clamp(1, self.budget_bytes);
+        let permit = self
+            .budget
+            .clone()
+            .acquire_many_owned(size as u32)
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+
+        if self.writers[partition].is_none() {
+            self.writers[partition] = Some(self.start_writer(partition));
+        }
+        let writer = self.writers[partition].as_ref().unwrap();
+        if writer.tx.send((batch, permit)).is_err() {
+            // the IO task stopped after failing to write, its error is returned
+            let writer = self.writers[partition].take().unwrap();
+            return match writer.handle.await {
+                Ok(Err(e)) => Err(e),
+                Ok(Ok(_)) => Err(DataFusionError::Internal(format!(
+                    "Writer of shuffle partition {partition} stopped early"
+                ))),
+                Err(e) => Err(DataFusionError::Execution(format!("{e:?}"))),
+            };
+        }
+        Ok(())
+    }
+
+    /// Wait for all the queued batches to be written and close the partition files. Returns
+    /// the written partitions, by partition number.
+    pub async fn finish(self) -> Result<Vec<(usize, SpilledPartition)>> {
+        let mut partitions = vec![];
+        for (partition, writer) in self.writers.into_iter().enumerate() {
+            if let Some(PartitionWriter { tx, handle }) = writer {
+                drop(tx);
+                let spilled = handle
+                    .await
+                    .map_err(|e| DataFusionError::Execution(format!("{e:?}")))??;
+                partitions.push((partition, spilled));
+            }
+        }
+        Ok(partitions)
+    }
+
+    fn start_writer(&self, partition: usize) -> PartitionWriter {
+        let (tx, mut rx) =
+            mpsc::unbounded_channel::<(RecordBatch, OwnedSemaphorePermit)>();
+        let path = (self.partition_path)(partition);
+        let schema = self.schema.clone();
+        let num_fields = schema.fields().len();
+        let write_time = self.write_time.clone();
+        let io_pool = self.io_pool.clone();
+        let mut column_bounds = self
+            .column_bounds
+            .then(|| ColumnBoundsCollector::new(&schema));
+
+        let handle = tokio::spawn(async move {
+            let mut writer = {
+                let path = path.clone();
+                let write_time = write_time.clone();
+                blocking(io_pool.as_deref(), move || {
+                    let _timer = write_time.timer();
+                    if let Some(dir) = path.parent() {
+                        fs::create_dir_all(dir)?;
+                    }
+                    debug!("Writing results to {:?}", path);
+                    let options = IpcWriteOptions::default()
+                        .try_with_compression(Some(CompressionType::LZ4_FRAME))?;
+                    let file = File::create(&path)?;
+                    Ok(StreamWriter::try_new_with_options(
+                        file,
+                        schema.as_ref(),
+                        options,
+                    )?)
+                })
+                .await?
+            };
+
+            let mut num_batches = 0;
+            let mut num_rows = 0;
+            let mut null_counts = vec![0; num_fields];
+            // a blocking thread is only taken while writing a batch, so that the partitions
+            // waiting for batches do not hold any
+            while let Some((batch, permit)) = rx.recv().await {
+                num_batches += 1;
+                num_rows += batch.num_rows() as u64;
+                for (null_count, column) in null_counts.iter_mut().zip(batch.columns()) {
+                    *null_count += column.null_count() as u64;
+                }
+                if let Some(column_bounds) = column_bounds.as_mut() {
+                    column_bounds.update(&batch)?;
+                }
+                let write_time = write_time.clone();
+                writer = blocking(io_pool.as_deref(), move || {
+                    let _timer = write_time.timer();
+                    writer.write(&batch)?;
+                    drop(permit);
+                    Ok(writer)
+                })
+                .await?;
+            }
+
+            let column_bounds = column_bounds
+                .map(|column_bounds| column_bounds.finish())
+                .unwrap_or_default();
+            blocking(io_pool.as_deref(), move || {
+                let _timer = write_time.timer();
+                writer.finish()?;
+                let num_bytes = fs::metadata(&path)?.len();
+                Ok(SpilledPartition {
+                    path,
+                    num_batches,
+                    num_rows,
+                    num_bytes,
+                    null_counts,
+                    column_bounds,
+                })
+            })
+            .await
+        });
+
+        PartitionWriter { tx, handle }
+    }
+}
+
+/// Run blocking IO in the disk IO pool if any, on the blocking threads of the runtime
+/// otherwise
+async fn blocking<T: Send + 'static>(
+    io_pool: Option<&DiskIoPool>,
+    f: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    match io_pool {
+        Some(io_pool) => io_pool
+            .run(f)
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?,
+        None => tokio::task::spawn_blocking(f)
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::UInt32Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::ipc::reader::StreamReader;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_shuffle_spiller() -> Result<()> {
+        let schema =
+            Arc::new(Schema::new(vec![Field::new("a", DataType::UInt32, false)]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(UInt32Array::from(vec![1, 2, 3]))],
+        )?;
+        let work_dir = TempDir::new()?;
+        let dir = work_dir.path().to_owned();
+
+        // a budget smaller than a batch lets a single batch wait to be written
+        let mut spiller =
+            ShuffleSpiller::new(schema, 3, 1, metrics::Time::new(), move |partition| {
+                dir.join(format!("{partition}")).join("data-0.arrow")
+            });
+        for _ in 0..10 {
+            spiller.write(0, batch.clone()).await?;
+            spiller.write(2, batch.clone()).await?;
+        }
+        spiller.write(2, batch.clone()).await?;
+
+        let partitions = spiller.finish().await?;
+        assert_eq!(
+            vec![(0, 10, 30), (2, 11, 33)],
+            partitions
+                .iter()
+                .map(|(partition, spilled)| (
+                    *partition,
+                    spilled.num_batches,
+                    spilled.num_rows
+                ))
+                .collect::<Vec<_>>()
+        );
+
+        let (_, spilled) = &partitions[1];
+        assert_eq!(fs::metadata(&spilled.path)?.len(), spilled.num_bytes);
+        let reader = StreamReader::try_new(File::open(&spilled.path)?, None)?;
+        let num_rows: usize = reader
+            .map(|batch| batch.map(|batch| batch.num_rows()))
+            .sum::<std::result::Result<_, _>>()?;
+        assert_eq!(33, num_rows);
+        Ok(())
+    }
+}
@@ -20,22 +20,26 @@
 //! partition is re-partitioned and streamed to disk in Arrow IPC format. Future stages of the query
 //! will use the ShuffleReaderExec to read these results.
 
-use datafusion::arrow::ipc::writer::IpcWriteOptions;
-use datafusion::arrow::ipc::CompressionType;
-
-use datafusion::arrow::ipc::writer::StreamWriter;
 use std::any::Any;
-use std::fs;
-use std::fs::File;
+use std::collections::HashMap;
 use std::future::Future;
 use std::iter::Iterator;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Instant;
 
+use super::hash_partitioner::HashPartitioner;
+use super::shuffle_spiller::ShuffleSpiller;
+use crate::config::{
+    BALLISTA_JOIN_BLOOM_FILTER_BYTES, BALLISTA_JOIN_RUNTIME_FILTERS,
+    BALLISTA_SHUFFLE_WRITE_BUFFER_SIZE,
+};
+use crate::disk_io::DiskIoPool;
 use crate::utils;
 
+use crate::execution_plans::BloomFilter;
 use crate::serde::protobuf::ShuffleWritePartition;
+use crate::serde::scheduler::to_proto::{bloom_filters_to_proto, column_bounds_to_proto};
 use crate::serde::scheduler::PartitionStats;
 use datafusion::arrow::array::{
     ArrayBuilder, ArrayRef, StringBuilder, StructBuilder, UInt32Builder, UInt64Builder,
@@ -44,9 +48,10 @@ use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_plan::aggregates::{AggregateExec, AggregateMode};
 use datafusion::physical_plan::memory::MemoryStream;
 use datafusion::physical_plan::metrics::{
-    self, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+    self, ExecutionPlanMetricsSet, MetricBuilder, MetricValue, MetricsSet,
 };
 
 use datafusion::physical_plan::{
@@ -57,7 +62,6 @@ use futures::{StreamExt, TryFutureExt, TryStreamExt};
 
 use datafusion::arrow::error::ArrowError;
 use datafusion::execution::context::TaskContext;
-use datafusion::physical_plan::repartition::BatchPartitioner;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use log::{debug, info};
 
@@ -78,16 +82,70 @@ pub struct ShuffleWriterExec {
     /// Optional shuffle output partitioning.
     /// If it's none, it means there's no need to do repartitioning.
     shuffle_output_partitioning: Option<Partitioning>,
+    /// Columns of the output whose bloom filters are collected, as the stage is the build
+    /// side of joins with runtime filters
+    bloom_filter_columns: Vec<usize>,
     /// Execution metrics
     metrics: ExecutionPlanMetricsSet,
     properties: PlanProperties,
 }
 
-pub struct WriteTracker {
-    pub num_batches: usize,
-    pub num_rows: usize,
-    pub writer: StreamWriter<File>,
-    pub path: PathBuf,
+/// The default memory budget in bytes of the batches computed by a task which wait to be
+/// written to its shuffle files
+pub const DEFAULT_SHUFFLE_WRITER_BUFFER_SIZE: usize = 64 * 1024 * 1024;
+
+/// Options of the [ShuffleWriterExec], which executors can set as an extension of the
+/// [datafusion::prelude::SessionConfig] of their tasks
+#[derive(Debug, Clone)]
+pub struct ShuffleWriterOptions {
+    /// The memory budget in bytes of the batches computed by a task which wait to be written
+    /// to its shuffle files, the task waiting for earlier batches to be written once it is
+    /// exhausted
+    pub buffer_size: usize,
+    /// The pool running the writes of the shuffle files, which run on the blocking threads
+    /// of the runtime without bound if not set
+    pub io_pool: Option<Arc<DiskIoPool>>,
+    /// Whether the min and max values of the columns of the hash partitioned outputs, which
+    /// the build sides of the partitioned joins are, are collected for the runtime filters
+    pub column_bounds: bool,
+    /// The size in bytes of the bloom filters of the columns requested by the writer, no
+    /// bloom filters being collected if 0
+    pub bloom_filter_bytes: usize,
+}
+
+impl Default for ShuffleWriterOptions {
+    fn default() -> Self {
+        Self {
+            buffer_size: DEFAULT_SHUFFLE_WRITER_BUFFER_SIZE,
+            io_pool: None,
+            column_bounds: false,
+            bloom_filter_bytes: 0,
+        }
+    }
+}
+
+impl ShuffleWriterOptions {
+    /// The options set by the props of a task, writing its shuffle files with the pool
+    pub fn from_props(
+        props: &HashMap<String, String>,
+        io_pool: Option<Arc<DiskIoPool>>,
+    ) -> Self {
+        Self {
+            buffer_size: props
+                .get(BALLISTA_SHUFFLE_WRITE_BUFFER_SIZE)
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(DEFAULT_SHUFFLE_WRITER_BUFFER_SIZE),
+            io_pool,
+            column_bounds: props
+                .get(BALLISTA_JOIN_RUNTIME_FILTERS)
+                .and_then(|enabled| enabled.parse().ok())
+                .unwrap_or(false),
+            bloom_filter_bytes: props
+                .get(BALLISTA_JOIN_BLOOM_FILTER_BYTES)
+                .and_then(|num_bytes| num_bytes.parse().ok())
+                .unwrap_or(0),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -97,6 +155,8 @@ struct ShuffleWriteMetrics {
     repart_time: metrics::Time,
     input_rows: metrics::Count,
     output_rows: metrics::Count,
+    /// Estimated bytes which the partial aggregates of the stage kept out of the shuffle
+    bytes_saved: metrics::Count,
 }
 
 impl ShuffleWriteMetrics {
@@ -109,11 +169,15 @@ impl ShuffleWriteMetrics {
 
         let output_rows = MetricBuilder::new(metrics).output_rows(partition);
 
+        let bytes_saved =
+            MetricBuilder::new(metrics).counter("shuffle_bytes_saved", partition);
+
         Self {
             write_time,
             repart_time,
             input_rows,
             output_rows,
+            bytes_saved,
         }
     }
 }
@@ -143,6 +207,7 @@ impl ShuffleWriterExec {
             plan,
             work_dir,
             shuffle_output_partitioning,
+            bloom_filter_columns: vec![],
             metrics: ExecutionPlanMetricsSet::new(),
             properties,
         })
@@ -171,6 +236,18 @@ impl ShuffleWriterExec {
         self.shuffle_output_partitioning.as_ref()
     }
 
+    /// Collect bloom filters of the columns of the hash partitioned outputs, if enabled by
+    /// the [ShuffleWriterOptions] of the tasks
+    pub fn with_bloom_filter_columns(mut self, bloom_filter_columns: Vec<usize>) -> Self {
+        self.bloom_filter_columns = bloom_filter_columns;
+        self
+    }
+
+    /// The columns of the output whose bloom filters are collected
+    pub fn bloom_filter_columns(&self) -> &[usize] {
+        &self.bloom_filter_columns
+    }
+
     pub fn execute_shuffle_write(
         &self,
         input_partition: usize,
@@ -183,6 +260,12 @@ impl ShuffleWriterExec {
         let write_metrics = ShuffleWriteMetrics::new(input_partition, &self.metrics);
         let output_partitioning = self.shuffle_output_partitioning.clone();
         let plan = self.plan.clone();
+        let bloom_filter_columns = self.bloom_filter_columns.clone();
+        let options = context
+            .session_config()
+            .get_extension::<ShuffleWriterOptions>()
+            .map(|options| options.as_ref().clone())
+            .unwrap_or_default();
 
         async move {
             let now = Instant::now();
@@ -212,6 +295,12 @@ impl ShuffleWriterExec {
                     write_metrics
                         .output_rows
                         .add(stats.num_rows.unwrap_or(0) as usize);
+                    write_metrics.bytes_saved.add(shuffle_bytes_saved(
+                        &plan,
+                        input_partition,
+                        stats.num_rows.unwrap_or(0),
+                        stats.num_bytes.unwrap_or(0),
+                    ));
                     timer.done();
 
                     info!(
@@ -227,18 +316,48 @@ impl ShuffleWriterExec {
                         num_batches: stats.num_batches.unwrap_or(0),
                         num_rows: stats.num_rows.unwrap_or(0),
                         num_bytes: stats.num_bytes.unwrap_or(0),
+                        null_counts: stats.null_counts.unwrap_or_default(),
+                        column_bounds: vec![],
+                        bloom_filters: vec![],
                     }])
                 }
 
                 Some(Partitioning::Hash(exprs, num_output_partitions)) => {
-                    // we won't necessary produce output for every possible partition, so we
-                    // create writers on demand
-                    let mut writers: Vec<Option<WriteTracker>> = vec![];
-                    for _ in 0..num_output_partitions {
-                        writers.push(None);
-                    }
-
-                    let mut partitioner = BatchPartitioner::try_new(
+                    // the output partitions are written by IO tasks while the next batches
+                    // are computed, the files being created on demand as we won't
+                    // necessarily produce output for every possible partition
+                    let mut spiller = ShuffleSpiller::new(
+                        stream.schema(),
+                        num_output_partitions,
+                        options.buffer_size,
+                        write_metrics.write_time.clone(),
+                        move |output_partition| {
+                            let mut path = path.clone();
+                            path.push(format!("{output_partition}"));
+                            path.push(format!("data-{input_partition}.arrow"));
+                            path
+                        },
+                    )
+                    .with_io_pool(options.io_pool.clone())
+                    .with_column_bounds(options.column_bounds);
+
+                    // a single filter of every column for all the output partitions, as
+                    // the scheduler unions the filters of the map tasks anyway
+                    let num_fields = stream.schema().fields().len();
+                    let mut bloom_filters: Vec<(usize, BloomFilter)> =
+                        if options.bloom_filter_bytes > 0 {
+                            bloom_filter_columns
+                                .into_iter()
+                                .filter(|column| *column < num_fields)
+                                .map(|column| {
+                                    (column, BloomFilter::new(options.bloom_filter_bytes))
+                                })
+                                .collect()
+                        } else {
+                            vec![]
+                        };
+
+                    let mut partitioner = HashPartitioner::try_new(
                         Partitioning::Hash(exprs, num_output_partitions),
                         write_metrics.repart_time.clone(),
                     )?;
@@ -247,84 +366,55 @@ impl ShuffleWriterExec {
                         let input_batch = result?;
 
                         write_metrics.input_rows.add(input_batch.num_rows());
+                        for (column, bloom_filter) in bloom_filters.iter_mut() {
+                            bloom_filter.insert(input_batch.column(*column))?;
+                        }
 
-                        partitioner.partition(
-                            input_batch,
-                            |output_partition, output_batch| {
-                                // partition func in datafusion make sure not write empty output_batch.
-                                let timer = write_metrics.write_time.timer();
-                                match &mut writers[output_partition] {
-                                    Some(w) => {
-                                        w.num_batches += 1;
-                                        w.num_rows += output_batch.num_rows();
-                                        w.writer.write(&output_batch)?;
-                                    }
-                                    None => {
-                                        let mut path = path.clone();
-                                        path.push(&format!("{output_partition}"));
-                                        std::fs::create_dir_all(&path)?;
-
-                                        path.push(format!(
-                                            "data-{input_partition}.arrow"
-                                        ));
-                                        debug!("Writing results to {:?}", path);
-
-                                        let options = IpcWriteOptions::default()
-                                            .try_with_compression(Some(
-                                                CompressionType::LZ4_FRAME,
-                                            ))?;
-
-                                        let file = File::create(path.clone())?;
-                                        let mut writer =
-                                            StreamWriter::try_new_with_options(
-                                                file,
-                                                stream.schema().as_ref(),
-                                                options,
-                                            )?;
-
-                                        writer.write(&output_batch)?;
-                                        writers[output_partition] = Some(WriteTracker {
-                                            num_batches: 1,
-                                            num_rows: output_batch.num_rows(),
-                                            writer,
-                                            path,
-                                        });
-                                    }
-                                }
-                                write_metrics.output_rows.add(output_batch.num_rows());
-                                timer.done();
-                                Ok(())
-                            },
-                        )?;
+                        // the partitioner does not return empty output batches
+                        for (output_partition, output_batch) in
+                            partitioner.partition(input_batch)?
+                        {
+                            write_metrics.output_rows.add(output_batch.num_rows());
+                            spiller.write(output_partition, output_batch).await?;
+                        }
                     }
 
                     let mut part_locs = vec![];
 
-                    for (i, w) in writers.iter_mut().enumerate() {
-                        match w {
-                            Some(w) => {
-                                let num_bytes = fs::metadata(&w.path)?.len();
-                                w.writer.finish()?;
-                                debug!(
-                                    "Finished writing shuffle partition {} at {:?}. Batches: {}. Rows: {}. Bytes: {}.",
-                                    i,
-                                    w.path,
-                                    w.num_batches,
-                                    w.num_rows,
-                                    num_bytes
-                                );
-
-                                part_locs.push(ShuffleWritePartition {
-                                    partition_id: i as u64,
-                                    path: w.path.to_string_lossy().to_string(),
-                                    num_batches: w.num_batches as u64,
-                                    num_rows: w.num_rows as u64,
-                                    num_bytes,
-                                });
-                            }
-                            None => {}
-                        }
+                    for (i, spilled) in spiller.finish().await? {
+                        debug!(
+                            "Finished writing shuffle partition {} at {:?}. Batches: {}. Rows: {}. Bytes: {}.",
+                            i,
+                            spilled.path,
+                            spilled.num_batches,
+                            spilled.num_rows,
+                            spilled.num_bytes
+                        );
+
+                        part_locs.push(ShuffleWritePartition {
+                            partition_id: i as u64,
+                            path: spilled.path.to_string_lossy().to_string(),
+                            num_batches: spilled.num_batches,
+                            num_rows: spilled.num_rows,
+                            num_bytes: spilled.num_bytes,
+                            null_counts: spilled.null_counts,
+                            column_bounds: column_bounds_to_proto(&spilled.column_bounds),
+                            bloom_filters: vec![],
+                        });
                     }
+                    if let Some(part_loc) = part_locs.first_mut() {
+                        part_loc.bloom_filters = bloom_filters_to_proto(
+                            bloom_filters
+                                .iter()
+                                .map(|(column, bloom_filter)| (*column, bloom_filter)),
+                        );
+                    }
+                    write_metrics.bytes_saved.add(shuffle_bytes_saved(
+                        &plan,
+                        input_partition,
+                        part_locs.iter().map(|loc| loc.num_rows).sum(),
+                        part_locs.iter().map(|loc| loc.num_bytes).sum(),
+                    ));
                     Ok(part_locs)
                 }
 
@@ -375,13 +465,16 @@ impl ExecutionPlan for ShuffleWriterExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(ShuffleWriterExec::try_new(
-            self.job_id.clone(),
-            self.stage_id,
-            children[0].clone(),
-            self.work_dir.clone(),
-            self.shuffle_output_partitioning.clone(),
-        )?))
+        Ok(Arc::new(
+            ShuffleWriterExec::try_new(
+                self.job_id.clone(),
+                self.stage_id,
+                children[0].clone(),
+                self.work_dir.clone(),
+                self.shuffle_output_partitioning.clone(),
+            )?
+            .with_bloom_filter_columns(self.bloom_filter_columns.clone()),
+        ))
     }
 
     fn execute(
@@ -456,6 +549,57 @@ impl ExecutionPlan for ShuffleWriterExec {
     }
 }
 
+/// Estimate the bytes which the partial aggregates of the stage kept out of the shuffle when
+/// computing a partition, as the rows they merged times the average size of a shuffled row
+fn shuffle_bytes_saved(
+    plan: &Arc<dyn ExecutionPlan>,
+    partition: usize,
+    num_rows: u64,
+    num_bytes: u64,
+) -> usize {
+    if num_rows == 0 {
+        return 0;
+    }
+    let merged_rows = partial_aggregate_merged_rows(plan, partition) as u64;
+    (merged_rows * num_bytes / num_rows) as usize
+}
+
+/// Number of input rows of a partition which the partial aggregates of the plan merged with
+/// other rows. Partial aggregates whose input does not report its output rows are ignored.
+fn partial_aggregate_merged_rows(
+    plan: &Arc<dyn ExecutionPlan>,
+    partition: usize,
+) -> usize {
+    if let Some(aggregate) = plan.as_any().downcast_ref::<AggregateExec>() {
+        if *aggregate.mode() == AggregateMode::Partial {
+            let input_rows = partition_output_rows(aggregate.input(), partition);
+            let output_rows = partition_output_rows(plan, partition);
+            return match (input_rows, output_rows) {
+                (Some(input_rows), Some(output_rows)) => {
+                    input_rows.saturating_sub(output_rows)
+                }
+                _ => 0,
+            };
+        }
+    }
+    plan.children()
+        .iter()
+        .map(|child| partial_aggregate_merged_rows(child, partition))
+        .sum()
+}
+
+fn partition_output_rows(
+    plan: &Arc<dyn ExecutionPlan>,
+    partition: usize,
+) -> Option<usize> {
+    plan.metrics()?
+        .sum(|metric| {
+            matches!(metric.value(), MetricValue::OutputRows(_))
+                && metric.partition() == Some(partition)
+        })
+        .map(|rows| rows.as_usize())
+}
+
 fn result_schema() -> SchemaRef {
     let stats = PartitionStats::default();
     Arc::new(Schema::new(vec![
@@ -469,11 +613,13 @@ fn result_schema() -> SchemaRef {
 mod tests {
     use super::*;
     use datafusion::arrow::array::{StringArray, StructArray, UInt32Array, UInt64Array};
+    use datafusion::physical_plan::aggregates::PhysicalGroupBy;
+    use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
     use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
     use datafusion::physical_plan::expressions::Column;
 
     use datafusion::physical_plan::memory::MemoryExec;
-    use datafusion::prelude::SessionContext;
+    use datafusion::prelude::{SessionConfig, SessionContext};
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -573,6 +719,85 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    // the rows of each partition depend on the hash output, so don't test here
+    #[cfg(not(feature = "force_hash_collisions"))]
+    async fn test_bloom_filters() -> Result<()> {
+        let session_config =
+            SessionConfig::new().with_extension(Arc::new(ShuffleWriterOptions {
+                bloom_filter_bytes: 64,
+                ..Default::default()
+            }));
+        let session_ctx = SessionContext::new_with_config(session_config);
+        let work_dir = TempDir::new()?;
+        let query_stage = ShuffleWriterExec::try_new(
+            "jobOne".to_owned(),
+            1,
+            create_input_plan()?,
+            work_dir.into_path().to_str().unwrap().to_owned(),
+            Some(Partitioning::Hash(vec![Arc::new(Column::new("a", 0))], 2)),
+        )?
+        .with_bloom_filter_columns(vec![0]);
+        let part_locs = query_stage
+            .execute_shuffle_write(0, session_ctx.task_ctx())
+            .await?;
+
+        // a single filter of both output partitions, sent with the first one
+        assert_eq!(2, part_locs.len());
+        assert!(part_locs[1].bloom_filters.is_empty());
+        let [bloom_filter] = part_locs[0].bloom_filters.as_slice() else {
+            panic!("The first output partition should have one bloom filter");
+        };
+        assert_eq!(0, bloom_filter.column);
+        let bloom_filter = BloomFilter::from_bytes(bloom_filter.bits.clone()).unwrap();
+        let values: ArrayRef = Arc::new(UInt32Array::from(vec![1, 3]));
+        assert_eq!(2, bloom_filter.contains(&values)?.true_count());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shuffle_bytes_saved() -> Result<()> {
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        // each input partition has 4 rows with 2 distinct values of a
+        let input_plan = Arc::new(CoalesceBatchesExec::new(create_input_plan()?, 8192));
+        let partial_aggregate = Arc::new(AggregateExec::try_new(
+            AggregateMode::Partial,
+            PhysicalGroupBy::new_single(vec![(
+                Arc::new(Column::new("a", 0)),
+                "a".to_string(),
+            )]),
+            vec![],
+            vec![],
+            input_plan.clone(),
+            input_plan.schema(),
+        )?);
+        let work_dir = TempDir::new()?;
+        let query_stage = ShuffleWriterExec::try_new(
+            "jobOne".to_owned(),
+            1,
+            partial_aggregate,
+            work_dir.into_path().to_str().unwrap().to_owned(),
+            Some(Partitioning::Hash(vec![Arc::new(Column::new("a", 0))], 2)),
+        )?;
+        let mut stream = query_stage.execute(0, task_ctx)?;
+        utils::collect_stream(&mut stream)
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+
+        let bytes_saved = query_stage
+            .metrics()
+            .unwrap()
+            .sum_by_name("shuffle_bytes_saved")
+            .map(|bytes| bytes.as_usize())
+            .unwrap_or(0);
+        assert!(bytes_saved > 0);
+
+        Ok(())
+    }
+
     fn create_input_plan() -> Result<Arc<dyn ExecutionPlan>> {
         let schema = Arc::new(Schema::new(vec![
             Field::new("a", DataType::UInt32, true),
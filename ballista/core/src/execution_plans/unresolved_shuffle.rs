@@ -51,11 +51,25 @@ impl UnresolvedShuffleExec {
         schema: SchemaRef,
         output_partition_count: usize,
     ) -> Self {
+        Self::new_with_partitioning(
+            stage_id,
+            schema,
+            Partitioning::UnknownPartitioning(output_partition_count),
+        )
+    }
+
+    /// Create a new UnresolvedShuffleExec which reports the output partitioning of the
+    /// ShuffleWriterExec it depends on, so that the distributed planner can tell whether
+    /// its consumers are already correctly partitioned
+    pub fn new_with_partitioning(
+        stage_id: usize,
+        schema: SchemaRef,
+        partitioning: Partitioning,
+    ) -> Self {
+        let output_partition_count = partitioning.partition_count();
         let properties = PlanProperties::new(
             datafusion::physical_expr::EquivalenceProperties::new(schema.clone()),
-            // TODO the output partition is known and should be populated here!
-            // see https://github.com/apache/arrow-datafusion/issues/758
-            Partitioning::UnknownPartitioning(output_partition_count),
+            partitioning,
             datafusion::physical_plan::ExecutionMode::Bounded,
         );
         Self {
@@ -0,0 +1,101 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! In-memory tables of the client, whose record batches are sent to the cluster along
+//! with the plan
+
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::Expr;
+use datafusion::physical_plan::empty::EmptyExec;
+use datafusion::physical_plan::values::ValuesExec;
+use datafusion::physical_plan::ExecutionPlan;
+use std::any::Any;
+use std::sync::Arc;
+
+/// Table holding the record batches of an in-memory table of the client, such as a
+/// [`MemTable`](datafusion::datasource::MemTable).
+///
+/// The client replaces the in-memory tables of a plan with this table, which is
+/// serialized with its data, so that the executors can read it.
+#[derive(Debug)]
+pub struct InlineTable {
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+}
+
+impl InlineTable {
+    pub fn new(schema: SchemaRef, batches: Vec<RecordBatch>) -> Self {
+        Self { schema, batches }
+    }
+
+    pub fn batches(&self) -> &[RecordBatch] {
+        &self.batches
+    }
+
+    /// The size in bytes of the record batches of the table
+    pub fn size(&self) -> usize {
+        self.batches
+            .iter()
+            .map(|batch| batch.get_array_memory_size())
+            .sum()
+    }
+}
+
+#[async_trait]
+impl TableProvider for InlineTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Temporary
+    }
+
+    async fn scan(
+        &self,
+        _state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let (schema, batches) = match projection {
+            Some(projection) => (
+                Arc::new(self.schema.project(projection)?),
+                self.batches
+                    .iter()
+                    .map(|batch| batch.project(projection))
+                    .collect::<std::result::Result<Vec<_>, _>>()?,
+            ),
+            None => (self.schema.clone(), self.batches.clone()),
+        };
+
+        if batches.is_empty() {
+            Ok(Arc::new(EmptyExec::new(schema)))
+        } else {
+            Ok(Arc::new(ValuesExec::try_new_from_batches(schema, batches)?))
+        }
+    }
+}
@@ -17,23 +17,37 @@
 
 #![doc = include_str!("../README.md")]
 pub const BALLISTA_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Version of arrow of the build, empty if unknown
+pub const ARROW_VERSION: &str = env!("ARROW_VERSION");
 
 pub fn print_version() {
     println!("Ballista version: {BALLISTA_VERSION}")
 }
 
+pub mod admin_statement;
 #[cfg(not(windows))]
 pub mod cache_layer;
 pub mod client;
 pub mod config;
+pub mod config_file;
 pub mod consistent_hash;
+pub mod disk_io;
 pub mod error;
 pub mod event_loop;
 pub mod execution_plans;
+pub mod inline_table;
+pub mod materialized_cte;
 pub mod object_store_registry;
+pub mod plan_protection;
 /// some plugins
 pub mod plugin;
+pub mod protocol;
+#[cfg(feature = "python-udf")]
+pub mod python_udf;
+pub mod temporary_table;
 pub mod utils;
+#[cfg(feature = "wasm-udf")]
+pub mod wasm_udf;
 
 #[macro_use]
 pub mod serde;
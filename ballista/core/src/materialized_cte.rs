@@ -0,0 +1,245 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Materialization of the subqueries which are referenced several times in a query, such
+//! as WITH clause CTEs, so that the cluster computes them only once
+
+use crate::execution_plans::MaterializedCteExec;
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::common::config::ConfigOptions;
+use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
+use datafusion::datasource::{provider_as_source, TableProvider, TableType};
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{Expr, LogicalPlan, TableScan};
+use datafusion::optimizer::analyzer::AnalyzerRule;
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::ExecutionPlan;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Table standing for a subquery which is referenced several times in a query.
+///
+/// It is serialized with the logical plan of the subquery. When the scheduler plans the
+/// query, every scan of the table plans the subquery under a [`MaterializedCteExec`] with
+/// the id of the subquery, which the distributed planner turns into a single stage read by
+/// all the scans.
+#[derive(Debug)]
+pub struct MaterializedCte {
+    id: usize,
+    name: String,
+    plan: LogicalPlan,
+    schema: SchemaRef,
+}
+
+impl MaterializedCte {
+    pub fn new(id: usize, name: impl Into<String>, plan: LogicalPlan) -> Self {
+        let schema = Arc::new(plan.schema().as_ref().clone().into());
+        Self {
+            id,
+            name: name.into(),
+            plan,
+            schema,
+        }
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn plan(&self) -> &LogicalPlan {
+        &self.plan
+    }
+}
+
+#[async_trait]
+impl TableProvider for MaterializedCte {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Temporary
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let input = state.create_physical_plan(&self.plan).await?;
+        let cte: Arc<dyn ExecutionPlan> =
+            Arc::new(MaterializedCteExec::new(input, self.id, self.name.clone()));
+
+        match projection {
+            Some(projection) => {
+                let schema = cte.schema();
+                let exprs = projection
+                    .iter()
+                    .map(|index| {
+                        let name = schema.field(*index).name();
+                        let column: Arc<dyn PhysicalExpr> =
+                            Arc::new(Column::new(name, *index));
+                        (column, name.to_string())
+                    })
+                    .collect();
+                Ok(Arc::new(ProjectionExec::try_new(exprs, cte)?))
+            }
+            None => Ok(cte),
+        }
+    }
+}
+
+/// Analyzer rule of the client replacing the subqueries which are referenced several times
+/// in a plan with scans of a [`MaterializedCte`].
+///
+/// The scans keep the optimizer from pushing different filters and projections into every
+/// reference, so that all references compute the same output.
+#[derive(Debug, Default)]
+pub struct MaterializeCtes {}
+
+impl MaterializeCtes {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl AnalyzerRule for MaterializeCtes {
+    fn analyze(&self, plan: LogicalPlan, _config: &ConfigOptions) -> Result<LogicalPlan> {
+        let mut references: HashMap<LogicalPlan, usize> = HashMap::new();
+        plan.apply(&mut |plan| {
+            if let LogicalPlan::SubqueryAlias(alias) = plan {
+                // materializing a plain table scan would only add a shuffle
+                if !matches!(alias.input.as_ref(), LogicalPlan::TableScan(_)) {
+                    *references.entry(plan.clone()).or_default() += 1;
+                }
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+
+        let ctes: HashMap<LogicalPlan, Arc<dyn TableProvider>> = references
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .enumerate()
+            .map(|(id, (plan, _))| {
+                let name = match &plan {
+                    LogicalPlan::SubqueryAlias(alias) => alias.alias.to_string(),
+                    _ => unreachable!("only subquery aliases are counted"),
+                };
+                let cte: Arc<dyn TableProvider> =
+                    Arc::new(MaterializedCte::new(id, name, plan.clone()));
+                (plan, cte)
+            })
+            .collect();
+        if ctes.is_empty() {
+            return Ok(plan);
+        }
+
+        // the outermost references are replaced first, so that the subqueries nested in a
+        // materialized subquery are left as they are
+        let plan = plan.transform_down(&|plan| match ctes.get(&plan) {
+            Some(cte) => {
+                let LogicalPlan::SubqueryAlias(alias) = &plan else {
+                    unreachable!("only subquery aliases are materialized")
+                };
+                let scan = TableScan::try_new(
+                    alias.alias.clone(),
+                    provider_as_source(cte.clone()),
+                    None,
+                    vec![],
+                    None,
+                )?;
+                Ok(Transformed::yes(LogicalPlan::TableScan(scan)))
+            }
+            None => Ok(Transformed::no(plan)),
+        })?;
+        Ok(plan.data)
+    }
+
+    fn name(&self) -> &str {
+        "materialize_ctes"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::empty::EmptyTable;
+    use datafusion::datasource::source_as_provider;
+    use datafusion::prelude::SessionContext;
+
+    fn materialized_scans(plan: &LogicalPlan) -> Result<Vec<(String, usize)>> {
+        let mut scans = vec![];
+        plan.apply(&mut |plan| {
+            if let LogicalPlan::TableScan(scan) = plan {
+                let provider = source_as_provider(&scan.source)?;
+                if let Some(cte) = provider.as_any().downcast_ref::<MaterializedCte>() {
+                    scans.push((cte.name().to_string(), cte.id()));
+                }
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+        Ok(scans)
+    }
+
+    #[tokio::test]
+    async fn test_materialize_ctes() -> Result<()> {
+        let ctx = SessionContext::new();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+        ]));
+        ctx.register_table("t", Arc::new(EmptyTable::new(schema)))?;
+
+        // a CTE referenced twice is materialized
+        let plan = ctx
+            .sql(
+                "WITH c AS (SELECT a, count(*) AS n FROM t GROUP BY a) \
+                SELECT x.a, y.n FROM c x JOIN c y ON x.a = y.a",
+            )
+            .await?
+            .into_unoptimized_plan();
+        let analyzed = MaterializeCtes::new().analyze(plan, &ConfigOptions::new())?;
+        let scans = materialized_scans(&analyzed)?;
+        assert_eq!(scans, vec![("c".to_string(), 0), ("c".to_string(), 0)]);
+
+        // a CTE referenced once is inlined
+        let plan = ctx
+            .sql("WITH c AS (SELECT a, count(*) AS n FROM t GROUP BY a) SELECT * FROM c")
+            .await?
+            .into_unoptimized_plan();
+        let analyzed = MaterializeCtes::new().analyze(plan, &ConfigOptions::new())?;
+        assert!(materialized_scans(&analyzed)?.is_empty());
+
+        Ok(())
+    }
+}
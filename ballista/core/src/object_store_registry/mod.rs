@@ -52,6 +52,22 @@ impl BallistaObjectStoreRegistry {
         Default::default()
     }
 
+    /// Schemes of the URLs of the object stores available, i.e. the local file system and the
+    /// object stores of the enabled features
+    pub fn supported_schemes() -> Vec<&'static str> {
+        #[allow(unused_mut)]
+        let mut schemes = vec!["file"];
+        #[cfg(any(feature = "hdfs", feature = "hdfs3"))]
+        schemes.extend(["hdfs", "viewfs"]);
+        #[cfg(feature = "s3")]
+        schemes.extend(["s3", "oss"]);
+        #[cfg(feature = "azure")]
+        schemes.push("azure");
+        #[cfg(feature = "gcs")]
+        schemes.extend(["gs", "gcs"]);
+        schemes
+    }
+
     /// Find a suitable object store based on its url and enabled features if possible
     fn get_feature_store(
         &self,
@@ -0,0 +1,196 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Protection of the serialized plans sent to the executors and stored by the schedulers.
+//!
+//! The serialized plans are optionally encrypted with AES-256-GCM, then signed with
+//! HMAC-SHA256, using keys shared by the schedulers and the executors of a cluster. Plans
+//! whose signature does not match are rejected, so that a compromised state store cannot
+//! make the executors run arbitrary plans.
+
+use std::fmt;
+use std::sync::Arc;
+
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::{aead, digest, hmac};
+
+use crate::error::{BallistaError, Result};
+
+/// Keys protecting the serialized plans, the plans are left as is without keys
+#[derive(Clone, Default)]
+pub struct PlanProtection {
+    signing_key: Option<hmac::Key>,
+    encryption_key: Option<Arc<aead::LessSafeKey>>,
+}
+
+impl PlanProtection {
+    /// Create the protection from hex encoded keys. The encryption key must be 32 bytes
+    /// long, the signing key should be at least as long.
+    pub fn new(signing_key: Option<&str>, encryption_key: Option<&str>) -> Result<Self> {
+        let signing_key = signing_key
+            .map(|key| {
+                let key = decode_key(key, "signing")?;
+                Ok::<_, BallistaError>(hmac::Key::new(hmac::HMAC_SHA256, &key))
+            })
+            .transpose()?;
+        let encryption_key = encryption_key
+            .map(|key| {
+                let key = decode_key(key, "encryption")?;
+                let key =
+                    aead::UnboundKey::new(&aead::AES_256_GCM, &key).map_err(|_| {
+                        BallistaError::General(format!(
+                            "The plan encryption key must be {} bytes long",
+                            aead::AES_256_GCM.key_len()
+                        ))
+                    })?;
+                Ok::<_, BallistaError>(Arc::new(aead::LessSafeKey::new(key)))
+            })
+            .transpose()?;
+        Ok(Self {
+            signing_key,
+            encryption_key,
+        })
+    }
+
+    /// Whether the serialized plans are signed or encrypted
+    pub fn is_enabled(&self) -> bool {
+        self.signing_key.is_some() || self.encryption_key.is_some()
+    }
+
+    /// Encrypt then sign a serialized plan
+    pub fn protect(&self, mut plan: Vec<u8>) -> Result<Vec<u8>> {
+        if let Some(key) = &self.encryption_key {
+            let mut nonce = [0u8; aead::NONCE_LEN];
+            SystemRandom::new().fill(&mut nonce).map_err(|_| {
+                BallistaError::Internal("Failed to generate a nonce".to_owned())
+            })?;
+            key.seal_in_place_append_tag(
+                aead::Nonce::assume_unique_for_key(nonce),
+                aead::Aad::empty(),
+                &mut plan,
+            )
+            .map_err(|_| {
+                BallistaError::Internal("Failed to encrypt serialized plan".to_owned())
+            })?;
+            let mut sealed = Vec::with_capacity(nonce.len() + plan.len());
+            sealed.extend_from_slice(&nonce);
+            sealed.append(&mut plan);
+            plan = sealed;
+        }
+        if let Some(key) = &self.signing_key {
+            let tag = hmac::sign(key, &plan);
+            plan.extend_from_slice(tag.as_ref());
+        }
+        Ok(plan)
+    }
+
+    /// Verify the signature of a protected plan, then decrypt it
+    pub fn unprotect(&self, mut data: Vec<u8>) -> Result<Vec<u8>> {
+        if let Some(key) = &self.signing_key {
+            let invalid_signature = || {
+                BallistaError::General("Invalid signature of serialized plan".to_owned())
+            };
+            let plan_len = data
+                .len()
+                .checked_sub(digest::SHA256_OUTPUT_LEN)
+                .ok_or_else(invalid_signature)?;
+            let (plan, tag) = data.split_at(plan_len);
+            hmac::verify(key, plan, tag).map_err(|_| invalid_signature())?;
+            data.truncate(plan_len);
+        }
+        if let Some(key) = &self.encryption_key {
+            let failed_decryption =
+                || BallistaError::General("Failed to decrypt serialized plan".to_owned());
+            if data.len() < aead::NONCE_LEN {
+                return Err(failed_decryption());
+            }
+            let mut sealed = data.split_off(aead::NONCE_LEN);
+            let nonce = aead::Nonce::try_assume_unique_for_key(&data)
+                .map_err(|_| failed_decryption())?;
+            let plan_len = key
+                .open_in_place(nonce, aead::Aad::empty(), &mut sealed)
+                .map_err(|_| failed_decryption())?
+                .len();
+            sealed.truncate(plan_len);
+            data = sealed;
+        }
+        Ok(data)
+    }
+}
+
+impl fmt::Debug for PlanProtection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PlanProtection")
+            .field("signed", &self.signing_key.is_some())
+            .field("encrypted", &self.encryption_key.is_some())
+            .finish()
+    }
+}
+
+fn decode_key(key: &str, name: &str) -> Result<Vec<u8>> {
+    let key = hex::decode(key.trim()).map_err(|e| {
+        BallistaError::General(format!("Invalid hex encoded plan {name} key: {e}"))
+    })?;
+    if key.is_empty() {
+        return Err(BallistaError::General(format!(
+            "The plan {name} key must not be empty"
+        )));
+    }
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNING_KEY: &str =
+        "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    const ENCRYPTION_KEY: &str =
+        "1f1e1d1c1b1a191817161514131211100f0e0d0c0b0a09080706050403020100";
+
+    #[test]
+    fn test_plan_protection() -> Result<()> {
+        let plan = b"serialized plan".to_vec();
+
+        let none = PlanProtection::default();
+        assert!(!none.is_enabled());
+        assert_eq!(plan, none.protect(plan.clone())?);
+
+        let protection = PlanProtection::new(Some(SIGNING_KEY), Some(ENCRYPTION_KEY))?;
+        let protected = protection.protect(plan.clone())?;
+        assert!(!protected
+            .windows(plan.len())
+            .any(|window| window == plan.as_slice()));
+        assert_eq!(plan, protection.unprotect(protected.clone())?);
+
+        // tampered plans and unprotected plans are rejected
+        let mut tampered = protected.clone();
+        tampered[0] ^= 1;
+        assert!(protection.unprotect(tampered).is_err());
+        assert!(protection.unprotect(plan.clone()).is_err());
+
+        // a plan signed with another key is rejected
+        let other = PlanProtection::new(Some(ENCRYPTION_KEY), None)?;
+        assert!(other.unprotect(protected).is_err());
+        let signed = other.protect(plan.clone())?;
+        assert_eq!(plan, other.unprotect(signed)?);
+
+        assert!(PlanProtection::new(None, Some("00ff")).is_err());
+        assert!(PlanProtection::new(Some("not hex"), None).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Version of the protocol spoken by the clients, schedulers and executors, negotiated when
+//! they connect to the scheduler, so that the processes of a cluster can be upgraded one at a
+//! time.
+//!
+//! Version 1 is the protocol of the releases predating the negotiation, whose processes send
+//! no version, i.e. version 0 on the wire. Version 2 adds the negotiation and the
+//! capabilities advertised by the executors, see
+//! [crate::serde::scheduler::ExecutorCapabilities]. The executors speaking version 1 do not
+//! advertise them and are assumed to support all the features of their protocol.
+//!
+//! The scheduler keeps the version negotiated with every executor in its metadata, see
+//! [crate::serde::scheduler::ExecutorMetadata::protocol_version], to only use the
+//! features of this version with the executor.
+
+use log::info;
+use tonic::transport::Channel;
+use tonic::Code;
+
+use crate::error::{BallistaError, Result};
+use crate::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use crate::serde::protobuf::HandshakeParams;
+use crate::BALLISTA_VERSION;
+
+/// Most recent version of the protocol spoken by this build
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// Oldest version of the protocol still spoken by this build
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// First version of the protocol whose executors advertise their capabilities
+pub const CAPABILITIES_PROTOCOL_VERSION: u32 = 2;
+
+/// Negotiate the version of the protocol with a peer speaking the versions `min_version` to
+/// `version`, as sent by the peer. Returns the most recent version spoken by both sides, or
+/// an error describing the mismatch when they do not speak a common version.
+pub fn negotiate_protocol_version(
+    peer: &str,
+    version: u32,
+    min_version: u32,
+) -> Result<u32> {
+    // the peers predating the negotiation send no version and speak only version 1
+    let (version, min_version) = match (version, min_version) {
+        (0, _) => (1, 1),
+        (version, 0) => (version, version),
+        versions => versions,
+    };
+    let negotiated = version.min(PROTOCOL_VERSION);
+    if negotiated < min_version.max(MIN_PROTOCOL_VERSION) {
+        let upgraded = if version < MIN_PROTOCOL_VERSION {
+            peer.to_owned()
+        } else {
+            "this process".to_owned()
+        };
+        return Err(BallistaError::General(format!(
+            "{peer} speaks protocol versions {min_version} to {version} but this process \
+            speaks versions {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}, {upgraded} must \
+            be upgraded"
+        )));
+    }
+    Ok(negotiated)
+}
+
+/// Negotiate the version of the protocol with the scheduler, when a client or an executor
+/// connects to it. The schedulers predating the negotiation are assumed to speak version 1.
+pub async fn handshake(scheduler: &mut SchedulerGrpcClient<Channel>) -> Result<u32> {
+    let result = scheduler
+        .handshake(HandshakeParams {
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            ballista_version: BALLISTA_VERSION.to_owned(),
+        })
+        .await;
+    match result {
+        Ok(result) => {
+            let result = result.into_inner();
+            info!(
+                "Speaking protocol version {} with scheduler of Ballista {}",
+                result.protocol_version, result.ballista_version
+            );
+            // also checked by the scheduler, unless it speaks a more recent version
+            negotiate_protocol_version("Scheduler", result.protocol_version, 0)
+        }
+        Err(status) if status.code() == Code::Unimplemented => {
+            negotiate_protocol_version("Scheduler", 0, 0)
+        }
+        Err(status) if status.code() == Code::FailedPrecondition => {
+            Err(BallistaError::General(status.message().to_owned()))
+        }
+        Err(status) => Err(BallistaError::GrpcActionError(format!(
+            "Handshake with the scheduler failed: {status:?}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{negotiate_protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+    #[test]
+    fn test_negotiate_protocol_version() {
+        // same version
+        assert_eq!(
+            PROTOCOL_VERSION,
+            negotiate_protocol_version("Client", PROTOCOL_VERSION, MIN_PROTOCOL_VERSION)
+                .unwrap()
+        );
+        // the peers predating the negotiation speak version 1
+        assert_eq!(1, negotiate_protocol_version("Client", 0, 0).unwrap());
+        // a more recent peer still speaking this version
+        assert_eq!(
+            PROTOCOL_VERSION,
+            negotiate_protocol_version("Client", PROTOCOL_VERSION + 1, PROTOCOL_VERSION)
+                .unwrap()
+        );
+        // a more recent peer no longer speaking this version
+        let error = negotiate_protocol_version(
+            "Client",
+            PROTOCOL_VERSION + 2,
+            PROTOCOL_VERSION + 1,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("this process must be upgraded"));
+    }
+}
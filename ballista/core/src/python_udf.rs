@@ -0,0 +1,573 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Scalar UDFs implemented by Python functions, which are pickled by the clients with
+//! cloudpickle and sent along with the plans to the scheduler and the executors.
+//!
+//! The executors run the functions in a pool of Python worker processes, started on demand,
+//! exchanging the batches with them in the Arrow IPC stream format over their standard
+//! input and output. Like the UDFs of DataFusion Python, a function takes one pyarrow array
+//! per argument and returns a pyarrow array of the same length. The worker processes need
+//! the `pyarrow` and `cloudpickle` packages.
+//!
+//! A call waiting for a worker or for its response past [PythonUdfConfig::call_timeout]
+//! fails, and the worker which did not respond in time is killed and replaced.
+
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+use std::io::{BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use datafusion::arrow::array::{Array, ArrayRef};
+use datafusion::arrow::compute::cast;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use datafusion::common::DataFusionError;
+use datafusion::error::Result;
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+};
+use datafusion_proto::protobuf::ArrowType;
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use parking_lot::{Condvar, Mutex};
+use tokio::runtime::RuntimeFlavor;
+
+use crate::serde::protobuf;
+
+/// The source of the Python worker processes
+const WORKER: &str = include_str!("python_udf_worker.py");
+
+static CONFIG: OnceCell<PythonUdfConfig> = OnceCell::new();
+
+static POOL: OnceCell<WorkerPool> = OnceCell::new();
+
+/// Configuration of the Python worker processes of an executor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PythonUdfConfig {
+    /// The Python interpreter running the workers
+    pub python: String,
+    /// The maximum number of workers, which is the maximum number of batches processed by
+    /// the Python UDFs concurrently
+    pub max_workers: usize,
+    /// The `cgroup.procs` file of the cgroup in which the workers are placed, e.g. to
+    /// bound their memory, if any
+    pub cgroup_procs: Option<PathBuf>,
+    /// The maximum time a batch waits for a worker and its response, if any
+    pub call_timeout: Option<Duration>,
+}
+
+impl Default for PythonUdfConfig {
+    fn default() -> Self {
+        Self {
+            python: "python3".to_owned(),
+            max_workers: std::thread::available_parallelism()
+                .map(|parallelism| parallelism.get())
+                .unwrap_or(1),
+            cgroup_procs: None,
+            call_timeout: Some(Duration::from_secs(300)),
+        }
+    }
+}
+
+/// Set the configuration of the Python worker processes of this process. It must be called
+/// before the first Python UDF is invoked to take effect.
+pub fn set_python_udf_config(config: PythonUdfConfig) -> Result<()> {
+    CONFIG.set(config).map_err(|_| {
+        DataFusionError::Internal(
+            "The Python UDF configuration is already set".to_owned(),
+        )
+    })
+}
+
+fn pool() -> &'static WorkerPool {
+    POOL.get_or_init(|| WorkerPool::new(CONFIG.get().cloned().unwrap_or_default()))
+}
+
+/// The response of a worker, see [PythonWorker::call]
+type WorkerResponse = std::io::Result<std::result::Result<Vec<u8>, String>>;
+
+/// A Python worker process. Its standard input and output are written and read by a
+/// thread of its own, so that a call is abandoned once its deadline is reached.
+struct PythonWorker {
+    child: Child,
+    requests: Sender<(Vec<u8>, Vec<u8>)>,
+    responses: Receiver<WorkerResponse>,
+}
+
+impl PythonWorker {
+    fn spawn(config: &PythonUdfConfig) -> std::io::Result<Self> {
+        let mut child = Command::new(&config.python)
+            .arg("-c")
+            .arg(WORKER)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("the standard input is piped");
+        let stdout = child.stdout.take().expect("the standard output is piped");
+        info!("Started Python UDF worker {}", child.id());
+        if let Some(cgroup_procs) = &config.cgroup_procs {
+            // the worker still runs outside of the cgroup rather than failing the task
+            if let Err(e) = std::fs::write(cgroup_procs, child.id().to_string()) {
+                warn!(
+                    "Failed to place Python UDF worker {} in {cgroup_procs:?}: {e}",
+                    child.id()
+                );
+            }
+        }
+
+        let (requests, pending_requests) = mpsc::channel::<(Vec<u8>, Vec<u8>)>();
+        let (send_response, responses) = mpsc::channel();
+        // the thread exits once the worker is dropped, which kills the process
+        let thread = std::thread::Builder::new()
+            .name(format!("python-udf-{}", child.id()))
+            .spawn(move || {
+                let mut stdout = BufReader::new(stdout);
+                for (pickled_function, arguments) in pending_requests {
+                    let response =
+                        exchange(&mut stdin, &mut stdout, &pickled_function, &arguments);
+                    let failed = response.is_err();
+                    if send_response.send(response).is_err() || failed {
+                        break;
+                    }
+                }
+            });
+        if let Err(e) = thread {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        Ok(Self {
+            child,
+            requests,
+            responses,
+        })
+    }
+
+    /// Call a pickled function with the arguments encoded in the Arrow IPC stream format.
+    /// The outer error means that the worker is unusable, e.g. it did not respond before
+    /// the `deadline`, the inner one that the function failed.
+    fn call(
+        &mut self,
+        pickled_function: &[u8],
+        arguments: &[u8],
+        deadline: Option<Instant>,
+    ) -> WorkerResponse {
+        let exited = || {
+            std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "the worker stopped responding",
+            )
+        };
+        self.requests
+            .send((pickled_function.to_vec(), arguments.to_vec()))
+            .map_err(|_| exited())?;
+        match deadline {
+            Some(deadline) => self
+                .responses
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .map_err(|e| match e {
+                    RecvTimeoutError::Timeout => std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "the worker did not respond in time",
+                    ),
+                    RecvTimeoutError::Disconnected => exited(),
+                })?,
+            None => self.responses.recv().map_err(|_| exited())?,
+        }
+    }
+}
+
+/// Send a call to a worker and read its response
+fn exchange(
+    stdin: &mut ChildStdin,
+    stdout: &mut BufReader<ChildStdout>,
+    pickled_function: &[u8],
+    arguments: &[u8],
+) -> WorkerResponse {
+    for frame in [pickled_function, arguments] {
+        stdin.write_all(&(frame.len() as u32).to_le_bytes())?;
+        stdin.write_all(frame)?;
+    }
+    stdin.flush()?;
+
+    let mut header = [0u8; 5];
+    stdout.read_exact(&mut header)?;
+    let len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+    let mut response = vec![0u8; len as usize];
+    stdout.read_exact(&mut response)?;
+    Ok(match header[0] {
+        0 => Ok(response),
+        _ => Err(String::from_utf8_lossy(&response).into_owned()),
+    })
+}
+
+impl Drop for PythonWorker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct WorkerPoolState {
+    idle: Vec<PythonWorker>,
+    /// The number of workers, idle or not
+    workers: usize,
+}
+
+/// The pool of the Python worker processes, bounded by [PythonUdfConfig::max_workers]
+struct WorkerPool {
+    config: PythonUdfConfig,
+    state: Mutex<WorkerPoolState>,
+    released: Condvar,
+}
+
+impl WorkerPool {
+    fn new(config: PythonUdfConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(WorkerPoolState {
+                idle: vec![],
+                workers: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self, deadline: Option<Instant>) -> Result<PythonWorker> {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(worker) = state.idle.pop() {
+                return Ok(worker);
+            }
+            if state.workers < self.config.max_workers.max(1) {
+                state.workers += 1;
+                drop(state);
+                return PythonWorker::spawn(&self.config).map_err(|e| {
+                    self.discard();
+                    DataFusionError::Execution(format!(
+                        "Failed to start Python UDF worker {}: {e}",
+                        self.config.python
+                    ))
+                });
+            }
+            match deadline {
+                Some(deadline) => {
+                    if self.released.wait_until(&mut state, deadline).timed_out() {
+                        return Err(DataFusionError::Execution(format!(
+                            "Timed out waiting for one of the {} Python UDF workers",
+                            state.workers
+                        )));
+                    }
+                }
+                None => self.released.wait(&mut state),
+            }
+        }
+    }
+
+    fn release(&self, worker: PythonWorker) {
+        self.state.lock().idle.push(worker);
+        self.released.notify_one();
+    }
+
+    /// Forget a worker which is not usable anymore, so that another one may be started
+    fn discard(&self) {
+        self.state.lock().workers -= 1;
+        self.released.notify_one();
+    }
+
+    fn call(&self, pickled_function: &[u8], arguments: &[u8]) -> Result<Vec<u8>> {
+        let deadline = self
+            .config
+            .call_timeout
+            .map(|call_timeout| Instant::now() + call_timeout);
+        let mut worker = self.acquire(deadline)?;
+        match worker.call(pickled_function, arguments, deadline) {
+            Ok(result) => {
+                self.release(worker);
+                result.map_err(DataFusionError::Execution)
+            }
+            Err(e) => {
+                // the worker is killed, another one is started by the next call
+                warn!("Python UDF worker {} failed: {e}", worker.child.id());
+                drop(worker);
+                self.discard();
+                Err(DataFusionError::Execution(format!(
+                    "Python UDF worker failed: {e}"
+                )))
+            }
+        }
+    }
+}
+
+/// A scalar UDF calling a pickled Python function, see the [module](self) documentation
+pub struct PythonUdf {
+    name: String,
+    pickled_function: Vec<u8>,
+    signature: Signature,
+    arg_types: Vec<DataType>,
+    return_type: DataType,
+}
+
+impl Debug for PythonUdf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PythonUdf")
+            .field("name", &self.name)
+            .field("arg_types", &self.arg_types)
+            .field("return_type", &self.return_type)
+            .finish()
+    }
+}
+
+impl PythonUdf {
+    /// Create the UDF `name` from a Python function pickled by cloudpickle, taking pyarrow
+    /// arrays of the given argument types and returning a pyarrow array of the return type
+    pub fn new(
+        name: impl Into<String>,
+        pickled_function: Vec<u8>,
+        arg_types: Vec<DataType>,
+        return_type: DataType,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            pickled_function,
+            signature: Signature::exact(arg_types.clone(), Volatility::Volatile),
+            arg_types,
+            return_type,
+        }
+    }
+
+    /// Convert to a [ScalarUDF] to register in a session
+    pub fn into_udf(self) -> ScalarUDF {
+        ScalarUDF::new_from_impl(self)
+    }
+
+    fn call(&self, args: Vec<ArrayRef>, num_rows: usize) -> Result<ArrayRef> {
+        let fields: Vec<Field> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| Field::new(format!("arg{i}"), arg.data_type().clone(), true))
+            .collect();
+        let batch = RecordBatch::try_new_with_options(
+            Arc::new(Schema::new(fields)),
+            args,
+            &RecordBatchOptions::new().with_row_count(Some(num_rows)),
+        )?;
+        let mut arguments = vec![];
+        {
+            let mut writer = StreamWriter::try_new(&mut arguments, &batch.schema())?;
+            writer.write(&batch)?;
+            writer.finish()?;
+        }
+
+        let response = block_in_place(|| pool().call(&self.pickled_function, &arguments))
+            .map_err(|e| match e {
+                DataFusionError::Execution(e) => DataFusionError::Execution(format!(
+                    "Python UDF {} failed: {e}",
+                    self.name
+                )),
+                e => e,
+            })?;
+        let result = StreamReader::try_new(response.as_slice(), None)?
+            .next()
+            .transpose()?
+            .ok_or_else(|| {
+                DataFusionError::Execution(format!(
+                    "Python UDF {} returned no result",
+                    self.name
+                ))
+            })?;
+        let result = result.column(0);
+        if result.len() != num_rows {
+            return Err(DataFusionError::Execution(format!(
+                "Python UDF {} returned {} values for {num_rows} rows",
+                self.name,
+                result.len()
+            )));
+        }
+        if result.data_type() == &self.return_type {
+            Ok(result.clone())
+        } else {
+            Ok(cast(result, &self.return_type)?)
+        }
+    }
+}
+
+impl ScalarUDFImpl for PythonUdf {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let num_rows = args
+            .iter()
+            .find_map(|arg| match arg {
+                ColumnarValue::Array(array) => Some(array.len()),
+                ColumnarValue::Scalar(_) => None,
+            })
+            .unwrap_or(1);
+        let arrays = args
+            .iter()
+            .map(|arg| arg.clone().into_array(num_rows))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ColumnarValue::Array(self.call(arrays, num_rows)?))
+    }
+}
+
+/// Wait for the Python workers without holding up the other tasks of the thread of the
+/// Tokio runtime, if any
+fn block_in_place<R>(f: impl FnOnce() -> R) -> R {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+/// Convert a UDF to the node sent along with the plan if it is a Python UDF, the other UDFs
+/// being looked up by name when the plan is decoded
+pub(crate) fn to_node(udf: &ScalarUDF) -> Result<Option<protobuf::PythonUdfNode>> {
+    let inner = udf.inner();
+    let Some(udf) = inner.as_any().downcast_ref::<PythonUdf>() else {
+        return Ok(None);
+    };
+    let arg_types = udf
+        .arg_types
+        .iter()
+        .map(ArrowType::try_from)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| DataFusionError::Internal(format!("{e:?}")))?;
+    let return_type = ArrowType::try_from(&udf.return_type)
+        .map_err(|e| DataFusionError::Internal(format!("{e:?}")))?;
+    Ok(Some(protobuf::PythonUdfNode {
+        name: udf.name.clone(),
+        pickled_function: udf.pickled_function.clone(),
+        arg_types,
+        return_type: Some(return_type),
+    }))
+}
+
+/// Create a Python UDF sent along with a plan. The function is only unpickled by the
+/// workers, when the UDF is invoked.
+pub(crate) fn from_node(node: protobuf::PythonUdfNode) -> Result<Arc<ScalarUDF>> {
+    let name = &node.name;
+    let arg_types = node
+        .arg_types
+        .iter()
+        .map(DataType::try_from)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| DataFusionError::Internal(format!("{e:?}")))?;
+    let return_type = node
+        .return_type
+        .as_ref()
+        .ok_or_else(|| {
+            DataFusionError::Internal(format!("Missing return type of Python UDF {name}"))
+        })
+        .and_then(|return_type| {
+            DataType::try_from(return_type)
+                .map_err(|e| DataFusionError::Internal(format!("{e:?}")))
+        })?;
+    let udf = PythonUdf::new(node.name, node.pickled_function, arg_types, return_type);
+    Ok(Arc::new(udf.into_udf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::datatypes::DataType;
+    use datafusion::error::Result;
+
+    use super::{from_node, to_node, PythonUdf, PythonUdfConfig, WorkerPool};
+
+    #[test]
+    fn test_python_udf_node() -> Result<()> {
+        let udf = PythonUdf::new(
+            "add_one",
+            b"pickled".to_vec(),
+            vec![DataType::Int64],
+            DataType::Int64,
+        );
+        let node = to_node(&udf.into_udf())?.unwrap();
+        assert_eq!(node.pickled_function, b"pickled");
+
+        let decoded = from_node(node)?;
+        assert_eq!(decoded.name(), "add_one");
+        assert_eq!(decoded.return_type(&[DataType::Int64])?, DataType::Int64);
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_python_interpreter() {
+        let pool = WorkerPool::new(PythonUdfConfig {
+            python: "missing-python-interpreter".to_owned(),
+            max_workers: 1,
+            cgroup_procs: None,
+            call_timeout: None,
+        });
+        assert!(pool.call(b"pickled", b"arguments").is_err());
+        // the failed worker does not hold the only slot of the pool
+        assert!(pool.call(b"pickled", b"arguments").is_err());
+        assert_eq!(pool.state.lock().workers, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unresponsive_python_worker() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, Instant};
+
+        // a worker which never responds
+        let dir = tempfile::tempdir()?;
+        let python = dir.path().join("python");
+        std::fs::write(&python, "#!/bin/sh\nexec sleep 60\n")?;
+        std::fs::set_permissions(&python, std::fs::Permissions::from_mode(0o755))?;
+        let pool = WorkerPool::new(PythonUdfConfig {
+            python: python.to_string_lossy().into_owned(),
+            max_workers: 1,
+            cgroup_procs: None,
+            call_timeout: Some(Duration::from_millis(200)),
+        });
+
+        let start = Instant::now();
+        assert!(pool.call(b"pickled", b"arguments").is_err());
+        assert!(start.elapsed() < Duration::from_secs(30));
+        // the worker was killed rather than holding the only slot of the pool
+        assert_eq!(pool.state.lock().workers, 0);
+        assert!(pool.call(b"pickled", b"arguments").is_err());
+        assert_eq!(pool.state.lock().workers, 0);
+        Ok(())
+    }
+}
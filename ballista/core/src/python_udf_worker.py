@@ -0,0 +1,91 @@
+# Licensed to the Apache Software Foundation (ASF) under one
+# or more contributor license agreements.  See the NOTICE file
+# distributed with this work for additional information
+# regarding copyright ownership.  The ASF licenses this file
+# to you under the Apache License, Version 2.0 (the
+# "License"); you may not use this file except in compliance
+# with the License.  You may obtain a copy of the License at
+#
+#   http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing,
+# software distributed under the License is distributed on an
+# "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+# KIND, either express or implied.  See the License for the
+# specific language governing permissions and limitations
+# under the License.
+
+# A worker of the Python UDFs of an executor, calling the pickled functions on the batches
+# written to its standard input and writing the results to its standard output.
+#
+# A request is the function pickled by cloudpickle, followed by a batch of the arguments in
+# the Arrow IPC stream format, both prefixed by their length as a little-endian u32. The
+# response is a status byte, 0 when the call succeeded and 1 otherwise, followed by the
+# result in the Arrow IPC stream format or by the error message, prefixed by its length.
+
+import hashlib
+import struct
+import sys
+import traceback
+
+import cloudpickle
+import pyarrow as pa
+
+
+def read_exact(stream, size):
+    data = stream.read(size)
+    if len(data) != size:
+        raise EOFError("the executor closed the standard input of the worker")
+    return data
+
+
+def read_frame(stream):
+    (size,) = struct.unpack("<I", read_exact(stream, 4))
+    return read_exact(stream, size)
+
+
+def write_frame(stream, status, data):
+    stream.write(struct.pack("<BI", status, len(data)))
+    stream.write(data)
+    stream.flush()
+
+
+def call(functions, pickled_function, arguments):
+    key = hashlib.sha256(pickled_function).digest()
+    function = functions.get(key)
+    if function is None:
+        function = cloudpickle.loads(pickled_function)
+        functions[key] = function
+    batch = pa.ipc.open_stream(arguments).read_next_batch()
+    result = function(*batch.columns)
+    if isinstance(result, pa.ChunkedArray):
+        result = result.combine_chunks()
+    elif not isinstance(result, pa.Array):
+        result = pa.array(result)
+    sink = pa.BufferOutputStream()
+    result_batch = pa.record_batch([result], names=["result"])
+    with pa.ipc.new_stream(sink, result_batch.schema) as writer:
+        writer.write_batch(result_batch)
+    return sink.getvalue().to_pybytes()
+
+
+def main():
+    stdin = sys.stdin.buffer
+    stdout = sys.stdout.buffer
+    # what the functions print must not corrupt the responses
+    sys.stdout = sys.stderr
+    functions = {}
+    while True:
+        try:
+            pickled_function = read_frame(stdin)
+        except EOFError:
+            return
+        arguments = read_frame(stdin)
+        try:
+            write_frame(stdout, 0, call(functions, pickled_function, arguments))
+        except Exception:
+            write_frame(stdout, 1, traceback.format_exc().encode("utf-8"))
+
+
+if __name__ == "__main__":
+    main()
@@ -1,11 +1,138 @@
 // This file is @generated by prost-build.
+/// a table provider of the client, sent along with the logical plan
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BallistaTableProviderNode {
+    #[prost(
+        oneof = "ballista_table_provider_node::TableProviderType",
+        tags = "1, 2, 3"
+    )]
+    pub table_provider_type: ::core::option::Option<
+        ballista_table_provider_node::TableProviderType,
+    >,
+}
+/// Nested message and enum types in `BallistaTableProviderNode`.
+pub mod ballista_table_provider_node {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum TableProviderType {
+        #[prost(message, tag = "1")]
+        TemporaryTable(super::TemporaryTableNode),
+        #[prost(message, tag = "2")]
+        InlineTable(super::InlineTableNode),
+        #[prost(message, tag = "3")]
+        MaterializedCte(super::MaterializedCteNode),
+    }
+}
+/// a reference to a temporary table of the session, which is resolved by the scheduler
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TemporaryTableNode {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+}
+/// an in-memory table of the client
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InlineTableNode {
+    /// the record batches, encoded as an Arrow IPC stream
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RuntimeFilterExecNode {
+    /// the stage computing the build side of the join
+    #[prost(uint32, tag = "1")]
+    pub build_stage_id: u32,
+    /// index of the join key in the output of the build stage
+    #[prost(uint32, tag = "2")]
+    pub build_column: u32,
+    /// the join key of the probe side, in the output of the input
+    #[prost(string, tag = "3")]
+    pub probe_column_name: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "4")]
+    pub probe_column_index: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BloomFilterExecNode {
+    #[prost(string, tag = "1")]
+    pub column_name: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub column_index: u32,
+    #[prost(bytes = "vec", tag = "3")]
+    pub bits: ::prost::alloc::vec::Vec<u8>,
+}
+/// a scalar UDF sent along with the plans, rather than registered in the sessions by name
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ScalarUdfNode {
+    #[prost(oneof = "scalar_udf_node::UdfType", tags = "1, 2")]
+    pub udf_type: ::core::option::Option<scalar_udf_node::UdfType>,
+}
+/// Nested message and enum types in `ScalarUdfNode`.
+pub mod scalar_udf_node {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum UdfType {
+        #[prost(message, tag = "1")]
+        WasmUdf(super::WasmUdfNode),
+        #[prost(message, tag = "2")]
+        PythonUdf(super::PythonUdfNode),
+    }
+}
+/// a scalar UDF implemented by a WebAssembly module, run by the wasm-udf feature
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WasmUdfNode {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// the WebAssembly module, exporting a function named after the UDF
+    #[prost(bytes = "vec", tag = "2")]
+    pub module: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, repeated, tag = "3")]
+    pub arg_types: ::prost::alloc::vec::Vec<::datafusion_proto::protobuf::ArrowType>,
+    #[prost(message, optional, tag = "4")]
+    pub return_type: ::core::option::Option<::datafusion_proto::protobuf::ArrowType>,
+}
+/// a scalar UDF implemented by a Python function, run by the python-udf feature
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PythonUdfNode {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// the function pickled by cloudpickle, taking and returning pyarrow arrays
+    #[prost(bytes = "vec", tag = "2")]
+    pub pickled_function: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, repeated, tag = "3")]
+    pub arg_types: ::prost::alloc::vec::Vec<::datafusion_proto::protobuf::ArrowType>,
+    #[prost(message, optional, tag = "4")]
+    pub return_type: ::core::option::Option<::datafusion_proto::protobuf::ArrowType>,
+}
+/// a subquery referenced several times in the plan, which is computed once by the cluster
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MaterializedCteNode {
+    /// identifies the subquery among the materialized subqueries of the plan
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(string, tag = "2")]
+    pub name: ::prost::alloc::string::String,
+    /// the logical plan of the subquery, encoded as a datafusion LogicalPlanNode
+    #[prost(bytes = "vec", tag = "3")]
+    pub plan: ::prost::alloc::vec::Vec<u8>,
+}
 /// /////////////////////////////////////////////////////////////////////////////////////////////////
 /// Ballista Physical Plan
 /// /////////////////////////////////////////////////////////////////////////////////////////////////
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BallistaPhysicalPlanNode {
-    #[prost(oneof = "ballista_physical_plan_node::PhysicalPlanType", tags = "1, 2, 3")]
+    #[prost(
+        oneof = "ballista_physical_plan_node::PhysicalPlanType",
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9"
+    )]
     pub physical_plan_type: ::core::option::Option<
         ballista_physical_plan_node::PhysicalPlanType,
     >,
@@ -21,6 +148,18 @@ pub mod ballista_physical_plan_node {
         ShuffleReader(super::ShuffleReaderExecNode),
         #[prost(message, tag = "3")]
         UnresolvedShuffle(super::UnresolvedShuffleExecNode),
+        #[prost(message, tag = "4")]
+        PartitionPlacement(super::PartitionPlacementExecNode),
+        #[prost(message, tag = "5")]
+        ParallelFileSink(super::ParallelFileSinkExecNode),
+        #[prost(message, tag = "6")]
+        FileSinkCommit(super::FileSinkCommitExecNode),
+        #[prost(message, tag = "7")]
+        Values(super::ValuesExecNode),
+        #[prost(message, tag = "8")]
+        RuntimeFilter(super::RuntimeFilterExecNode),
+        #[prost(message, tag = "9")]
+        BloomFilter(super::BloomFilterExecNode),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -38,6 +177,14 @@ pub struct ShuffleWriterExecNode {
     pub output_partitioning: ::core::option::Option<
         ::datafusion_proto::protobuf::PhysicalHashRepartition,
     >,
+    /// whether the single expression of the output partitioning gives the partition of the
+    /// rows directly rather than being hashed, see PartitionIdExpr
+    #[prost(bool, tag = "5")]
+    pub custom_partitioning: bool,
+    /// the columns of the output whose bloom filters are collected, for the runtime filters of
+    /// the joins whose build side the stage is
+    #[prost(uint32, repeated, tag = "6")]
+    pub bloom_filter_columns: ::prost::alloc::vec::Vec<u32>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -59,6 +206,10 @@ pub struct ShuffleReaderExecNode {
     /// The stage to read from
     #[prost(uint32, tag = "3")]
     pub stage_id: u32,
+    /// The job of the stage to read from when the reading stage is pipelined, i.e. started
+    /// before the stage to read from completed, empty otherwise
+    #[prost(string, tag = "4")]
+    pub pipelined_job_id: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -67,6 +218,41 @@ pub struct ShuffleReaderPartition {
     #[prost(message, repeated, tag = "1")]
     pub location: ::prost::alloc::vec::Vec<PartitionLocation>,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PartitionPlacementExecNode {
+    /// the preferred executor hosts for each partition of the input
+    #[prost(message, repeated, tag = "1")]
+    pub preferred_hosts: ::prost::alloc::vec::Vec<PreferredHosts>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PreferredHosts {
+    #[prost(string, repeated, tag = "1")]
+    pub host: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ParallelFileSinkExecNode {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FileSinkCommitExecNode {
+    #[prost(string, tag = "1")]
+    pub object_store_url: ::prost::alloc::string::String,
+    /// the output directory, to move the written files and write the success marker to
+    #[prost(string, tag = "2")]
+    pub output_path: ::prost::alloc::string::String,
+    /// the directory the partitions were written to
+    #[prost(string, tag = "3")]
+    pub staging_path: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ValuesExecNode {
+    /// the values, encoded as an Arrow IPC stream
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+}
 /// /////////////////////////////////////////////////////////////////////////////////////////////////
 /// Ballista Scheduling
 /// /////////////////////////////////////////////////////////////////////////////////////////////////
@@ -99,6 +285,70 @@ pub struct ExecutionGraph {
     pub end_time: u64,
     #[prost(uint64, tag = "13")]
     pub queued_at: u64,
+    /// max number of running tasks of every stage, 0 means no limit
+    #[prost(uint32, tag = "14")]
+    pub max_running_stage_tasks: u32,
+    /// whether stages may start before their input stages complete
+    #[prost(bool, tag = "15")]
+    pub pipelined_stages: bool,
+    #[prost(message, optional, tag = "18")]
+    pub result_limits: ::core::option::Option<ResultLimits>,
+    /// max number of running tasks of every stage on the same executor, 0 means no limit
+    #[prost(uint32, tag = "19")]
+    pub max_stage_tasks_per_executor: u32,
+    /// final single partition stage of the job run by the client, encoded with the
+    /// physical codec of the scheduler, empty if the scheduler runs all the stages
+    #[prost(bytes = "vec", tag = "20")]
+    pub collect_plan: ::prost::alloc::vec::Vec<u8>,
+    /// DataFusion options of the job session which differ from the defaults, sent along
+    /// with every task of the job
+    #[prost(message, repeated, tag = "21")]
+    pub session_props: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// max number of running tasks of some stages by stage id, overriding
+    /// max_running_stage_tasks, 0 means no limit
+    #[prost(map = "uint32, uint32", tag = "22")]
+    pub max_running_tasks_by_stage: ::std::collections::HashMap<u32, u32>,
+}
+/// Limits of the results of a job, 0 means no limit
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResultLimits {
+    #[prost(uint64, tag = "1")]
+    pub max_rows: u64,
+    #[prost(uint64, tag = "2")]
+    pub max_bytes: u64,
+    /// whether the results exceeding the limits are truncated rather than failing the job
+    #[prost(bool, tag = "3")]
+    pub truncate: bool,
+}
+/// Optimized logical plan of a job, stored once when the job is submitted rather than with
+/// every snapshot of its execution graph
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobLogicalPlan {
+    /// as displayed by DataFusion
+    #[prost(string, tag = "1")]
+    pub logical_plan: ::prost::alloc::string::String,
+    /// encoded with the logical codec of the scheduler, empty if it could not be encoded
+    #[prost(bytes = "vec", tag = "2")]
+    pub encoded_logical_plan: ::prost::alloc::vec::Vec<u8>,
+}
+/// Task status updates of an execution graph, saved since its last snapshot
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecutionGraphDelta {
+    #[prost(message, optional, tag = "1")]
+    pub executor: ::core::option::Option<ExecutorMetadata>,
+    #[prost(message, repeated, tag = "2")]
+    pub task_status: ::prost::alloc::vec::Vec<TaskStatus>,
+    /// ownership epoch of the job when the delta was saved, the deltas saved by a scheduler
+    /// which lost the ownership of the job are not replayed
+    #[prost(uint64, tag = "3")]
+    pub epoch: u64,
+    /// id of the next task of the graph when the delta was saved, the ids of the tasks
+    /// launched since the snapshot are not reused once the deltas are replayed
+    #[prost(uint32, tag = "4")]
+    pub task_id_gen: u32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -277,7 +527,7 @@ pub struct Action {
     /// configuration settings
     #[prost(message, repeated, tag = "100")]
     pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
-    #[prost(oneof = "action::ActionType", tags = "3")]
+    #[prost(oneof = "action::ActionType", tags = "3, 4")]
     pub action_type: ::core::option::Option<action::ActionType>,
 }
 /// Nested message and enum types in `Action`.
@@ -288,6 +538,9 @@ pub mod action {
         /// Fetch a partition from an executor
         #[prost(message, tag = "3")]
         FetchPartition(super::FetchPartition),
+        /// Fetch several partitions from an executor as a single stream
+        #[prost(message, tag = "4")]
+        FetchPartitions(super::FetchPartitions),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -326,6 +579,47 @@ pub struct FetchPartition {
     #[prost(uint32, tag = "6")]
     pub port: u32,
 }
+/// Shuffle partitions of the same job stage and executor, which the executor merges into a
+/// single stream so that a reducer reads many small map outputs with one request
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FetchPartitions {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+    /// Output partition of the shuffle read by the reducer
+    #[prost(uint32, tag = "3")]
+    pub partition_id: u32,
+    #[prost(string, repeated, tag = "4")]
+    pub paths: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, tag = "5")]
+    pub host: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "6")]
+    pub port: u32,
+}
+/// Request of a client streaming shuffle partitions of an executor over a single
+/// DoExchange stream, sent in the app_metadata of the FlightData messages of the client.
+/// The partitions share the schema of the stream and are streamed in request order.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PartitionExchangeRequest {
+    /// Partitions to stream after the ones previously requested
+    #[prost(message, repeated, tag = "1")]
+    pub partitions: ::prost::alloc::vec::Vec<FetchPartition>,
+    /// Number of additional record batches the client is ready to receive
+    #[prost(uint32, tag = "2")]
+    pub credits: u32,
+}
+/// Sent by the executor in the app_metadata of a FlightData message without data, once the
+/// batches of a requested partition were streamed
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PartitionExchangeEnd {
+    /// Index of the partition among the requested partitions of the stream
+    #[prost(uint32, tag = "1")]
+    pub partition_index: u32,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PartitionLocation {
@@ -362,6 +656,10 @@ pub struct TaskId {
     pub task_attempt_num: u32,
     #[prost(uint32, tag = "3")]
     pub partition_id: u32,
+    /// Token identifying the launch of the task attempt, for the executors to ignore the
+    /// launches retried by the schedulers
+    #[prost(string, tag = "4")]
+    pub launch_token: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -382,8 +680,8 @@ pub struct ColumnStats {
     pub min_value: ::core::option::Option<::datafusion_proto::protobuf::ScalarValue>,
     #[prost(message, optional, tag = "2")]
     pub max_value: ::core::option::Option<::datafusion_proto::protobuf::ScalarValue>,
-    #[prost(uint32, tag = "3")]
-    pub null_count: u32,
+    #[prost(uint64, tag = "3")]
+    pub null_count: u64,
     #[prost(uint32, tag = "4")]
     pub distinct_count: u32,
 }
@@ -392,6 +690,12 @@ pub struct ColumnStats {
 pub struct OperatorMetricsSet {
     #[prost(message, repeated, tag = "1")]
     pub metrics: ::prost::alloc::vec::Vec<OperatorMetric>,
+    /// Position of the operator among the operators of the stage plan which have metrics, depth first
+    #[prost(uint32, tag = "2")]
+    pub operator_id: u32,
+    /// Version of the metrics schema of the writer, see OPERATOR_METRICS_VERSION
+    #[prost(uint32, tag = "3")]
+    pub version: u32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -420,6 +724,19 @@ pub struct NamedTime {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct OperatorMetric {
+    /// Labels distinguishing metrics with the same name, e.g. the output partition of a repartition
+    #[prost(message, repeated, tag = "11")]
+    pub labels: ::prost::alloc::vec::Vec<MetricLabel>,
+    /// Generic form of the metric, set along with the typed metric so that the readers which do not
+    /// know its type, e.g. schedulers and UIs of older versions, can still interpret and aggregate it
+    #[prost(string, tag = "12")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(int64, tag = "13")]
+    pub value: i64,
+    #[prost(enumeration = "MetricType", tag = "14")]
+    pub metric_type: i32,
+    #[prost(enumeration = "MetricAggregation", tag = "15")]
+    pub aggregation: i32,
     #[prost(oneof = "operator_metric::Metric", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10")]
     pub metric: ::core::option::Option<operator_metric::Metric>,
 }
@@ -450,6 +767,14 @@ pub mod operator_metric {
         EndTimestamp(i64),
     }
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MetricLabel {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub value: ::prost::alloc::string::String,
+}
 /// Used by scheduler
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -464,6 +789,44 @@ pub struct ExecutorMetadata {
     pub grpc_port: u32,
     #[prost(message, optional, tag = "5")]
     pub specification: ::core::option::Option<ExecutorSpecification>,
+    /// Zone the executor runs in, empty if unknown
+    #[prost(string, tag = "6")]
+    pub zone: ::prost::alloc::string::String,
+    /// Rack the executor runs in, empty if unknown
+    #[prost(string, tag = "7")]
+    pub rack: ::prost::alloc::string::String,
+    /// Instance type of the host of the executor, empty if unknown
+    #[prost(string, tag = "8")]
+    pub instance_type: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "9")]
+    pub capabilities: ::core::option::Option<ExecutorCapabilities>,
+    /// version of the protocol negotiated with the executor, 0 for the metadata saved by
+    /// schedulers predating the negotiation
+    #[prost(uint32, tag = "10")]
+    pub protocol_version: u32,
+}
+/// Capabilities advertised by an executor when it registers, empty for executors of
+/// versions which do not advertise them
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExecutorCapabilities {
+    /// Schemes of the URLs of the object stores the executor can access, e.g. s3
+    #[prost(string, repeated, tag = "1")]
+    pub object_store_schemes: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Compression codecs of the shuffle files the executor can read and write
+    #[prost(string, repeated, tag = "2")]
+    pub shuffle_compression_codecs: ::prost::alloc::vec::Vec<
+        ::prost::alloc::string::String,
+    >,
+    /// Free space in bytes of the disk holding the work dir of the executor when it started
+    #[prost(uint64, tag = "3")]
+    pub available_disk: u64,
+    #[prost(string, tag = "4")]
+    pub ballista_version: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub datafusion_version: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub arrow_version: ::prost::alloc::string::String,
 }
 /// Used by grpc
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -477,6 +840,23 @@ pub struct ExecutorRegistration {
     pub grpc_port: u32,
     #[prost(message, optional, tag = "5")]
     pub specification: ::core::option::Option<ExecutorSpecification>,
+    /// Zone the executor runs in, empty if unknown
+    #[prost(string, tag = "6")]
+    pub zone: ::prost::alloc::string::String,
+    /// Rack the executor runs in, empty if unknown
+    #[prost(string, tag = "7")]
+    pub rack: ::prost::alloc::string::String,
+    /// Instance type of the host of the executor, empty if unknown
+    #[prost(string, tag = "8")]
+    pub instance_type: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "9")]
+    pub capabilities: ::core::option::Option<ExecutorCapabilities>,
+    /// versions of the protocol spoken by the executor, 0 for executors predating the
+    /// negotiation
+    #[prost(uint32, tag = "10")]
+    pub protocol_version: u32,
+    #[prost(uint32, tag = "11")]
+    pub min_protocol_version: u32,
     /// "optional" keyword is stable in protoc 3.15 but prost is still on 3.14 (see <https://github.com/tokio-rs/prost/issues/430> and <https://github.com/tokio-rs/prost/pull/455>)
     /// this syntax is ugly but is binary compatible with the "optional" keyword (see <https://stackoverflow.com/questions/42622015/how-to-define-an-optional-field-in-protobuf-3>)
     #[prost(oneof = "executor_registration::OptionalHost", tags = "2")]
@@ -505,12 +885,15 @@ pub struct ExecutorHeartbeat {
     pub metrics: ::prost::alloc::vec::Vec<ExecutorMetric>,
     #[prost(message, optional, tag = "4")]
     pub status: ::core::option::Option<ExecutorStatus>,
+    /// IDs of the jobs running tasks on the executor, for all the schedulers
+    #[prost(string, repeated, tag = "5")]
+    pub running_jobs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorMetric {
     /// TODO add more metrics
-    #[prost(oneof = "executor_metric::Metric", tags = "1")]
+    #[prost(oneof = "executor_metric::Metric", tags = "1, 2, 3")]
     pub metric: ::core::option::Option<executor_metric::Metric>,
 }
 /// Nested message and enum types in `ExecutorMetric`.
@@ -521,8 +904,49 @@ pub mod executor_metric {
     pub enum Metric {
         #[prost(uint64, tag = "1")]
         AvailableMemory(u64),
+        #[prost(message, tag = "2")]
+        DiskIo(super::DiskIoMetrics),
+        #[prost(message, tag = "3")]
+        Cgroup(super::CgroupMetrics),
     }
 }
+/// the metrics of a disk IO pool of an executor
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DiskIoMetrics {
+    /// the kind of IO of the pool, i.e. read or write
+    #[prost(string, tag = "1")]
+    pub pool: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub max_concurrency: u64,
+    /// the number of operations running and waiting for the pool
+    #[prost(uint64, tag = "3")]
+    pub running: u64,
+    #[prost(uint64, tag = "4")]
+    pub queued: u64,
+    #[prost(uint64, tag = "5")]
+    pub completed: u64,
+    /// the total time the operations waited for the pool
+    #[prost(uint64, tag = "6")]
+    pub wait_time_nanos: u64,
+}
+/// the usage and limits of the cgroup v2 of an executor
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CgroupMetrics {
+    /// the memory used by the processes of the cgroup, in bytes
+    #[prost(uint64, tag = "1")]
+    pub memory_current: u64,
+    /// the memory limit of the cgroup in bytes, 0 if unlimited
+    #[prost(uint64, tag = "2")]
+    pub memory_max: u64,
+    /// the total CPU time used by the processes of the cgroup
+    #[prost(uint64, tag = "3")]
+    pub cpu_usage_usec: u64,
+    /// the CPU limit of the cgroup in thousandths of CPUs, 0 if unlimited
+    #[prost(uint64, tag = "4")]
+    pub cpu_max_millis: u64,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorStatus {
@@ -574,6 +998,10 @@ pub struct AvailableTaskSlots {
     pub executor_id: ::prost::alloc::string::String,
     #[prost(uint32, tag = "2")]
     pub slots: u32,
+    /// slots held by running tasks in excess of the resized task slots of the executor, which
+    /// are not returned to the available slots when the tasks complete
+    #[prost(uint32, tag = "3")]
+    pub excess_slots: u32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -581,6 +1009,24 @@ pub struct ExecutorTaskSlots {
     #[prost(message, repeated, tag = "1")]
     pub task_slots: ::prost::alloc::vec::Vec<AvailableTaskSlots>,
 }
+/// Lease of an active scheduler, renewed while it runs
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SchedulerLease {
+    /// Unix timestamp in milliseconds after which the scheduler is considered failed, unless renewed
+    #[prost(uint64, tag = "1")]
+    pub expires_at: u64,
+}
+/// Executor slots held by a scheduler for tasks it launched
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SlotReservation {
+    /// Unix timestamp in milliseconds after which the slots are considered leaked, unless renewed
+    #[prost(uint64, tag = "1")]
+    pub expires_at: u64,
+    #[prost(message, repeated, tag = "2")]
+    pub task_slots: ::prost::alloc::vec::Vec<AvailableTaskSlots>,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorData {
@@ -684,6 +1130,25 @@ pub struct ShuffleWritePartition {
     pub num_rows: u64,
     #[prost(uint64, tag = "5")]
     pub num_bytes: u64,
+    /// Number of null values of each column, empty if unknown
+    #[prost(uint64, repeated, tag = "6")]
+    pub null_counts: ::prost::alloc::vec::Vec<u64>,
+    /// Min and max values of each column, collected for the runtime filters of joins, empty
+    /// if not collected
+    #[prost(message, repeated, tag = "7")]
+    pub column_bounds: ::prost::alloc::vec::Vec<ColumnStats>,
+    /// Bloom filters of the columns requested by the shuffle writer, of all the output
+    /// partitions of the map task, only set on its first output partition
+    #[prost(message, repeated, tag = "8")]
+    pub bloom_filters: ::prost::alloc::vec::Vec<ColumnBloomFilter>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ColumnBloomFilter {
+    #[prost(uint32, tag = "1")]
+    pub column: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub bits: ::prost::alloc::vec::Vec<u8>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -732,6 +1197,9 @@ pub struct PollWorkParams {
     /// All tasks must be reported until they reach the failed or completed state
     #[prost(message, repeated, tag = "3")]
     pub task_status: ::prost::alloc::vec::Vec<TaskStatus>,
+    /// IDs of the jobs running tasks on the executor
+    #[prost(string, repeated, tag = "4")]
+    pub running_jobs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -756,6 +1224,10 @@ pub struct TaskDefinition {
     pub launch_time: u64,
     #[prost(message, repeated, tag = "11")]
     pub props: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// Token identifying the launch of the task attempt, for the executors to ignore the
+    /// launches retried by the schedulers
+    #[prost(string, tag = "12")]
+    pub launch_token: ::prost::alloc::string::String,
 }
 /// A set of tasks in the same stage
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -778,6 +1250,21 @@ pub struct MultiTaskDefinition {
     #[prost(message, repeated, tag = "9")]
     pub props: ::prost::alloc::vec::Vec<KeyValuePair>,
 }
+/// Inputs of a failed task, saved by the executor so that the task can be replayed
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskDump {
+    #[prost(message, optional, tag = "1")]
+    pub task: ::core::option::Option<TaskDefinition>,
+    /// the shuffle partitions read by the task
+    #[prost(message, repeated, tag = "2")]
+    pub input_partitions: ::prost::alloc::vec::Vec<PartitionLocation>,
+    #[prost(string, tag = "3")]
+    pub executor_id: ::prost::alloc::string::String,
+    /// the error which failed the task
+    #[prost(string, tag = "4")]
+    pub error: ::prost::alloc::string::String,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SessionSettings {
@@ -821,6 +1308,13 @@ pub struct HeartBeatParams {
     pub status: ::core::option::Option<ExecutorStatus>,
     #[prost(message, optional, tag = "4")]
     pub metadata: ::core::option::Option<ExecutorRegistration>,
+    /// Status of the tasks finished since the last report, sent along with the heartbeat rather
+    /// than with UpdateTaskStatus
+    #[prost(message, repeated, tag = "5")]
+    pub task_status: ::prost::alloc::vec::Vec<TaskStatus>,
+    /// IDs of the jobs running tasks on the executor
+    #[prost(string, repeated, tag = "6")]
+    pub running_jobs: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -828,6 +1322,10 @@ pub struct HeartBeatResult {
     /// TODO it's from Spark for BlockManager
     #[prost(bool, tag = "1")]
     pub reregister: bool,
+    /// Task slots of the executor available for new tasks, as known in the memory of the
+    /// scheduler, 0 if unknown
+    #[prost(uint32, tag = "2")]
+    pub available_task_slots: u32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -852,6 +1350,10 @@ pub struct ExecutorStoppedParams {
     /// stop reason
     #[prost(string, tag = "2")]
     pub reason: ::prost::alloc::string::String,
+    /// The executor received a preemption notice of its instance and is about to be terminated,
+    /// so the shuffle outputs it holds are recomputed on other executors right away
+    #[prost(bool, tag = "3")]
+    pub preempted: bool,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -876,6 +1378,23 @@ pub struct UpdateTaskStatusResult {
 pub struct ExecuteQueryParams {
     #[prost(message, repeated, tag = "4")]
     pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// keep the output of the query on the executors as a temporary table of the session,
+    /// rather than returning it to the client
+    #[prost(message, optional, tag = "5")]
+    pub temporary_table: ::core::option::Option<CreateTemporaryTable>,
+    /// submissions to the same session with the same non empty key return the job of the
+    /// first submission, so that clients can safely retry
+    #[prost(string, tag = "6")]
+    pub idempotency_key: ::prost::alloc::string::String,
+    /// versions of the protocol spoken by the client, 0 for clients predating the negotiation
+    #[prost(uint32, tag = "7")]
+    pub protocol_version: u32,
+    #[prost(uint32, tag = "8")]
+    pub min_protocol_version: u32,
+    /// the client runs the single partition final stage of the job itself when the
+    /// scheduler detaches it, see SuccessfulJob.collect_plan
+    #[prost(bool, tag = "9")]
+    pub collect_stage: bool,
     #[prost(oneof = "execute_query_params::Query", tags = "1, 2")]
     pub query: ::core::option::Option<execute_query_params::Query>,
     #[prost(oneof = "execute_query_params::OptionalSessionId", tags = "3")]
@@ -902,6 +1421,14 @@ pub mod execute_query_params {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreateTemporaryTable {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(bool, tag = "2")]
+    pub or_replace: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CreateSessionParams {
     #[prost(message, repeated, tag = "1")]
     pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
@@ -968,6 +1495,13 @@ pub struct ExecuteQuerySuccessResult {
     pub job_id: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub session_id: ::prost::alloc::string::String,
+    /// Schema of the output of the job
+    #[prost(message, optional, tag = "3")]
+    pub schema: ::core::option::Option<::datafusion_proto::protobuf::Schema>,
+    /// Estimated delay in milliseconds before the job starts, from the free planning slots
+    /// and the recent throughput of the job queue, 0 if the job starts right away or unknown
+    #[prost(uint64, tag = "4")]
+    pub estimated_start_delay_ms: u64,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -997,6 +1531,7 @@ pub struct GetJobStatusParams {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SuccessfulJob {
+    /// Output partitions of the final stage, including their row, batch and byte counts
     #[prost(message, repeated, tag = "1")]
     pub partition_location: ::prost::alloc::vec::Vec<PartitionLocation>,
     #[prost(uint64, tag = "2")]
@@ -1005,12 +1540,60 @@ pub struct SuccessfulJob {
     pub started_at: u64,
     #[prost(uint64, tag = "4")]
     pub ended_at: u64,
+    /// Totals over the partition_stats of all output partitions
+    #[prost(message, optional, tag = "5")]
+    pub output_stats: ::core::option::Option<JobOutputStats>,
+    /// Sort order of each output partition, empty if the output partitions are not sorted
+    #[prost(message, repeated, tag = "6")]
+    pub output_ordering: ::prost::alloc::vec::Vec<OutputSortColumn>,
+    /// Whether the output partitions exceeded the result limits of the job and were truncated,
+    /// the client truncating the rows it fetches to the limits as well
+    #[prost(bool, tag = "7")]
+    pub truncated: bool,
+    /// Final single partition stage run by the client on the output partitions, whose
+    /// UnresolvedShuffleExec reads them, empty if the output partitions are the results
+    #[prost(bytes = "vec", tag = "8")]
+    pub collect_plan: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct OutputSortColumn {
+    /// Index of the column in the output schema of the job
+    #[prost(uint32, tag = "1")]
+    pub column_index: u32,
+    #[prost(bool, tag = "2")]
+    pub descending: bool,
+    #[prost(bool, tag = "3")]
+    pub nulls_first: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobOutputStats {
+    #[prost(uint64, tag = "1")]
+    pub num_rows: u64,
+    #[prost(uint64, tag = "2")]
+    pub num_batches: u64,
+    #[prost(uint64, tag = "3")]
+    pub num_bytes: u64,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct QueuedJob {
     #[prost(uint64, tag = "1")]
     pub queued_at: u64,
+    /// Estimated delay in milliseconds before the job starts, from its position in the job
+    /// queue, the free planning slots and the recent throughput of the queue, 0 if the job
+    /// starts right away or unknown
+    #[prost(uint64, tag = "2")]
+    pub estimated_start_delay_ms: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PlanningJob {
+    #[prost(uint64, tag = "1")]
+    pub queued_at: u64,
+    #[prost(uint64, tag = "2")]
+    pub planning_started_at: u64,
 }
 /// TODO: add progress report
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -1022,6 +1605,10 @@ pub struct RunningJob {
     pub started_at: u64,
     #[prost(string, tag = "3")]
     pub scheduler: ::prost::alloc::string::String,
+    /// incremented whenever a scheduler takes the ownership of the job, which tells the
+    /// scheduler handing off the job whether the other scheduler acquired it
+    #[prost(uint64, tag = "4")]
+    pub epoch: u64,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -1034,15 +1621,35 @@ pub struct FailedJob {
     pub started_at: u64,
     #[prost(uint64, tag = "4")]
     pub ended_at: u64,
+    /// The task whose failure failed the job, not set if the job failed during planning or was cancelled
+    #[prost(message, optional, tag = "5")]
+    pub failed_task: ::core::option::Option<FailedJobTask>,
+    /// Error messages from the job failure down to the task error, each truncated
+    #[prost(string, repeated, tag = "6")]
+    pub error_chain: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct JobStatus {
+pub struct FailedJobTask {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub stage_attempt_num: u32,
+    #[prost(uint32, tag = "3")]
+    pub partition_id: u32,
+    #[prost(uint32, tag = "4")]
+    pub task_id: u32,
     #[prost(string, tag = "5")]
-    pub job_id: ::prost::alloc::string::String,
+    pub executor_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobStatus {
+    #[prost(string, tag = "5")]
+    pub job_id: ::prost::alloc::string::String,
     #[prost(string, tag = "6")]
     pub job_name: ::prost::alloc::string::String,
-    #[prost(oneof = "job_status::Status", tags = "1, 2, 3, 4")]
+    #[prost(oneof = "job_status::Status", tags = "1, 2, 3, 4, 7")]
     pub status: ::core::option::Option<job_status::Status>,
 }
 /// Nested message and enum types in `JobStatus`.
@@ -1058,6 +1665,8 @@ pub mod job_status {
         Failed(super::FailedJob),
         #[prost(message, tag = "4")]
         Successful(super::SuccessfulJob),
+        #[prost(message, tag = "7")]
+        Planning(super::PlanningJob),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -1068,6 +1677,37 @@ pub struct GetJobStatusResult {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchJobStatusParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchJobStatusResult {
+    #[prost(oneof = "watch_job_status_result::Event", tags = "1, 2")]
+    pub event: ::core::option::Option<watch_job_status_result::Event>,
+}
+/// Nested message and enum types in `WatchJobStatusResult`.
+pub mod watch_job_status_result {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        /// the status of the job, sent first and then on every status transition
+        #[prost(message, tag = "1")]
+        Status(super::JobStatus),
+        /// a stage of the job completed successfully
+        #[prost(message, tag = "2")]
+        StageCompleted(super::StageCompleted),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StageCompleted {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetFileMetadataParams {
     #[prost(string, tag = "1")]
     pub path: ::prost::alloc::string::String,
@@ -1109,6 +1749,20 @@ pub struct CleanJobDataParams {
 pub struct CleanJobDataResult {}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportExecutionGraphParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "ExecutionGraphFormat", tag = "2")]
+    pub format: i32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportExecutionGraphResult {
+    #[prost(string, tag = "1")]
+    pub content: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct LaunchTaskParams {
     /// Allow to launch a task set to an executor at once
     #[prost(message, repeated, tag = "1")]
@@ -1162,6 +1816,325 @@ pub struct RemoveJobDataParams {
 pub struct RemoveJobDataResult {}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateExecutorConfigParams {
+    /// the settings to change, keyed by the name of the executor configuration parameter
+    #[prost(message, repeated, tag = "1")]
+    pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateExecutorConfigResult {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateJobSchedulerParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// the scheduler now owning the job, to send the status of its tasks to
+    #[prost(string, tag = "2")]
+    pub scheduler_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateJobSchedulerResult {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReplicateShuffleDataParams {
+    /// the shuffle partitions to copy, held by another executor
+    #[prost(message, repeated, tag = "1")]
+    pub partitions: ::prost::alloc::vec::Vec<PartitionLocation>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReplicateShuffleDataResult {
+    /// the paths of the copies of the partitions on this executor, in the order of the
+    /// partitions
+    #[prost(string, repeated, tag = "1")]
+    pub paths: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTaskLogsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "3")]
+    pub task_id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskLogLine {
+    /// milliseconds since the epoch
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    #[prost(string, tag = "2")]
+    pub level: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub target: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub message: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTaskLogsResult {
+    /// whether an executor kept the log lines of the task
+    #[prost(bool, tag = "1")]
+    pub found: bool,
+    /// the executor which ran the task
+    #[prost(string, tag = "2")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub lines: ::prost::alloc::vec::Vec<TaskLogLine>,
+    /// the number of older lines dropped because the buffer of the task was full
+    #[prost(uint64, tag = "4")]
+    pub dropped_lines: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetShuffleLocationsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// the stage writing the shuffle
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+    /// the output partition of the shuffle
+    #[prost(uint32, tag = "3")]
+    pub partition_id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetShuffleLocationsResult {
+    #[prost(message, repeated, tag = "1")]
+    pub partition_location: ::prost::alloc::vec::Vec<PartitionLocation>,
+    /// whether the stage writing the shuffle completed, so that all the locations are known
+    #[prost(bool, tag = "2")]
+    pub complete: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResizeExecutorTaskSlotsParams {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    /// the new number of task slots of the executor, at least one
+    #[prost(uint32, tag = "2")]
+    pub task_slots: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResizeExecutorTaskSlotsResult {
+    /// the task slots of the executor before the resize
+    #[prost(uint32, tag = "1")]
+    pub previous_task_slots: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HandOffJobsParams {
+    /// the active jobs to hand off, all the active jobs of the scheduler when empty
+    #[prost(string, repeated, tag = "1")]
+    pub job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// the endpoint of the scheduler taking over the jobs, as host:port
+    #[prost(string, tag = "2")]
+    pub scheduler_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HandOffJobsResult {
+    /// the jobs acquired by the other scheduler
+    #[prost(string, repeated, tag = "1")]
+    pub job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AcquireJobsParams {
+    #[prost(string, repeated, tag = "1")]
+    pub job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AcquireJobsResult {
+    /// the jobs acquired by the scheduler, the others stay with their current scheduler
+    #[prost(string, repeated, tag = "1")]
+    pub job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PromoteSchedulerParams {
+    /// the schedulers which failed, whose running jobs are taken over along with the jobs
+    /// released by their scheduler
+    #[prost(string, repeated, tag = "1")]
+    pub failed_schedulers: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PromoteSchedulerResult {
+    /// the jobs taken over by the promoted scheduler
+    #[prost(string, repeated, tag = "1")]
+    pub job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// The shuffle files held by an executor for a stage of a job
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ShuffleInventoryStage {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    /// the map partitions whose shuffle output is held
+    #[prost(uint32, repeated, tag = "2")]
+    pub map_partitions: ::prost::alloc::vec::Vec<u32>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ShuffleInventoryJob {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub stages: ::prost::alloc::vec::Vec<ShuffleInventoryStage>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReportShuffleInventoryParams {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub jobs: ::prost::alloc::vec::Vec<ShuffleInventoryJob>,
+    /// the time the shuffle files were listed, in milliseconds since the epoch
+    #[prost(uint64, tag = "3")]
+    pub listed_at: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReportShuffleInventoryResult {
+    /// the active jobs missing shuffle outputs of the executor, whose lost outputs are recomputed
+    #[prost(string, repeated, tag = "1")]
+    pub recomputed_job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// the jobs unknown to the scheduler, whose shuffle files the executor should remove
+    #[prost(string, repeated, tag = "2")]
+    pub stale_job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HandshakeParams {
+    /// versions of the protocol spoken by the client or executor connecting to the scheduler
+    #[prost(uint32, tag = "1")]
+    pub protocol_version: u32,
+    #[prost(uint32, tag = "2")]
+    pub min_protocol_version: u32,
+    #[prost(string, tag = "3")]
+    pub ballista_version: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HandshakeResult {
+    /// most recent version of the protocol spoken by both sides
+    #[prost(uint32, tag = "1")]
+    pub protocol_version: u32,
+    #[prost(string, tag = "2")]
+    pub ballista_version: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetJobPlanParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// session which submitted the job, the plans of the jobs of other sessions are not
+    /// returned
+    #[prost(string, tag = "2")]
+    pub session_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StagePlan {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    /// physical plan of the stage, as displayed by DataFusion
+    #[prost(string, tag = "2")]
+    pub plan: ::prost::alloc::string::String,
+    /// physical plan of the stage encoded with the physical codec of the scheduler
+    #[prost(bytes = "vec", tag = "3")]
+    pub encoded_plan: ::prost::alloc::vec::Vec<u8>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetJobPlanResult {
+    /// optimized logical plan of the job, as displayed by DataFusion
+    #[prost(string, tag = "1")]
+    pub logical_plan: ::prost::alloc::string::String,
+    /// optimized logical plan of the job encoded with the logical codec of the scheduler
+    #[prost(bytes = "vec", tag = "2")]
+    pub encoded_logical_plan: ::prost::alloc::vec::Vec<u8>,
+    /// physical plans of the stages of the job, ordered by stage id
+    #[prost(message, repeated, tag = "3")]
+    pub stages: ::prost::alloc::vec::Vec<StagePlan>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetJobSchedulingTraceParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// whether the stream follows the scheduling decisions of the job until it completes, rather
+    /// than ending after the decisions made so far
+    #[prost(bool, tag = "2")]
+    pub follow: bool,
+}
+/// A scheduling decision of the scheduler about a job
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SchedulingTraceEvent {
+    /// sequence number of the event in the trace of the job, starting at 0
+    #[prost(uint64, tag = "1")]
+    pub seq: u64,
+    /// time of the decision in milliseconds since the epoch
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(oneof = "scheduling_trace_event::Event", tags = "3, 4")]
+    pub event: ::core::option::Option<scheduling_trace_event::Event>,
+}
+/// Nested message and enum types in `SchedulingTraceEvent`.
+pub mod scheduling_trace_event {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "3")]
+        TaskBound(super::TaskBound),
+        #[prost(message, tag = "4")]
+        TaskLaunchFailed(super::TaskLaunchFailed),
+    }
+}
+/// A task bound to a task slot of an executor
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskBound {
+    #[prost(uint32, tag = "1")]
+    pub task_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "3")]
+    pub stage_attempt_num: u32,
+    #[prost(uint32, tag = "4")]
+    pub partition_id: u32,
+    #[prost(uint32, tag = "5")]
+    pub task_attempt: u32,
+    #[prost(string, tag = "6")]
+    pub executor_id: ::prost::alloc::string::String,
+    /// why the task was bound to the executor, e.g. input_locality or round_robin
+    #[prost(string, tag = "7")]
+    pub reason: ::prost::alloc::string::String,
+    /// time in milliseconds the task waited for a task slot since its stage started running
+    #[prost(uint64, tag = "8")]
+    pub queue_wait_ms: u64,
+}
+/// Tasks bound to an executor which could not be launched on it, whose slots are released
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskLaunchFailed {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(uint32, repeated, tag = "2")]
+    pub task_ids: ::prost::alloc::vec::Vec<u32>,
+    #[prost(string, tag = "3")]
+    pub error: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RunningTaskInfo {
     #[prost(uint32, tag = "1")]
     pub task_id: u32,
@@ -1172,6 +2145,98 @@ pub struct RunningTaskInfo {
     #[prost(uint32, tag = "4")]
     pub partition_id: u32,
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ExecutionGraphFormat {
+    Dot = 0,
+    Json = 1,
+}
+impl ExecutionGraphFormat {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ExecutionGraphFormat::Dot => "DOT",
+            ExecutionGraphFormat::Json => "JSON",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "DOT" => Some(Self::Dot),
+            "JSON" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MetricType {
+    /// A number of events or items, e.g. rows
+    Count = 0,
+    /// A value at a point in time, e.g. memory usage
+    Gauge = 1,
+    /// A duration in nanoseconds
+    Time = 2,
+    /// A UTC timestamp in nanoseconds
+    Timestamp = 3,
+}
+impl MetricType {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            MetricType::Count => "COUNT",
+            MetricType::Gauge => "GAUGE",
+            MetricType::Time => "TIME",
+            MetricType::Timestamp => "TIMESTAMP",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "COUNT" => Some(Self::Count),
+            "GAUGE" => Some(Self::Gauge),
+            "TIME" => Some(Self::Time),
+            "TIMESTAMP" => Some(Self::Timestamp),
+            _ => None,
+        }
+    }
+}
+/// How the values of a metric reported by several tasks are combined
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MetricAggregation {
+    Sum = 0,
+    Min = 1,
+    Max = 2,
+}
+impl MetricAggregation {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            MetricAggregation::Sum => "SUM",
+            MetricAggregation::Min => "MIN",
+            MetricAggregation::Max => "MAX",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "SUM" => Some(Self::Sum),
+            "MIN" => Some(Self::Min),
+            "MAX" => Some(Self::Max),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod scheduler_grpc_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -1534,12 +2599,12 @@ pub mod scheduler_grpc_client {
                 );
             self.inner.unary(req, path, codec).await
         }
-        /// Used by Executor to tell Scheduler it is stopped.
-        pub async fn executor_stopped(
+        /// Push the status transitions and the stage completions of a job until it completes
+        pub async fn watch_job_status(
             &mut self,
-            request: impl tonic::IntoRequest<super::ExecutorStoppedParams>,
+            request: impl tonic::IntoRequest<super::WatchJobStatusParams>,
         ) -> std::result::Result<
-            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Response<tonic::codec::Streaming<super::WatchJobStatusResult>>,
             tonic::Status,
         > {
             self.inner
@@ -1553,12 +2618,40 @@ pub mod scheduler_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped",
+                "/ballista.protobuf.SchedulerGrpc/WatchJobStatus",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "ExecutorStopped"),
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "WatchJobStatus"),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// Used by Executor to tell Scheduler it is stopped.
+        pub async fn executor_stopped(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExecutorStoppedParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "ExecutorStopped"),
                 );
             self.inner.unary(req, path, codec).await
         }
@@ -1614,98 +2707,12 @@ pub mod scheduler_grpc_client {
                 );
             self.inner.unary(req, path, codec).await
         }
-    }
-}
-/// Generated client implementations.
-pub mod executor_grpc_client {
-    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
-    #[derive(Debug, Clone)]
-    pub struct ExecutorGrpcClient<T> {
-        inner: tonic::client::Grpc<T>,
-    }
-    impl ExecutorGrpcClient<tonic::transport::Channel> {
-        /// Attempt to create a new client by connecting to a given endpoint.
-        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
-        where
-            D: TryInto<tonic::transport::Endpoint>,
-            D::Error: Into<StdError>,
-        {
-            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
-            Ok(Self::new(conn))
-        }
-    }
-    impl<T> ExecutorGrpcClient<T>
-    where
-        T: tonic::client::GrpcService<tonic::body::BoxBody>,
-        T::Error: Into<StdError>,
-        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
-        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
-    {
-        pub fn new(inner: T) -> Self {
-            let inner = tonic::client::Grpc::new(inner);
-            Self { inner }
-        }
-        pub fn with_origin(inner: T, origin: Uri) -> Self {
-            let inner = tonic::client::Grpc::with_origin(inner, origin);
-            Self { inner }
-        }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> ExecutorGrpcClient<InterceptedService<T, F>>
-        where
-            F: tonic::service::Interceptor,
-            T::ResponseBody: Default,
-            T: tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
-                >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-            >>::Error: Into<StdError> + Send + Sync,
-        {
-            ExecutorGrpcClient::new(InterceptedService::new(inner, interceptor))
-        }
-        /// Compress requests with the given encoding.
-        ///
-        /// This requires the server to support it otherwise it might respond with an
-        /// error.
-        #[must_use]
-        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.inner = self.inner.send_compressed(encoding);
-            self
-        }
-        /// Enable decompressing responses.
-        #[must_use]
-        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.inner = self.inner.accept_compressed(encoding);
-            self
-        }
-        /// Limits the maximum size of a decoded message.
-        ///
-        /// Default: `4MB`
-        #[must_use]
-        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_decoding_message_size(limit);
-            self
-        }
-        /// Limits the maximum size of an encoded message.
-        ///
-        /// Default: `usize::MAX`
-        #[must_use]
-        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_encoding_message_size(limit);
-            self
-        }
-        pub async fn launch_task(
+        /// Export the execution graph of a job, to be consumed by visualization tools
+        pub async fn export_execution_graph(
             &mut self,
-            request: impl tonic::IntoRequest<super::LaunchTaskParams>,
+            request: impl tonic::IntoRequest<super::ExportExecutionGraphParams>,
         ) -> std::result::Result<
-            tonic::Response<super::LaunchTaskResult>,
+            tonic::Response<super::ExportExecutionGraphResult>,
             tonic::Status,
         > {
             self.inner
@@ -1719,18 +2726,24 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/LaunchTask",
+                "/ballista.protobuf.SchedulerGrpc/ExportExecutionGraph",
             );
             let mut req = request.into_request();
             req.extensions_mut()
-                .insert(GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchTask"));
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "ExportExecutionGraph",
+                    ),
+                );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn launch_multi_task(
+        /// Get the log lines buffered by the executor which ran a task
+        pub async fn get_task_logs(
             &mut self,
-            request: impl tonic::IntoRequest<super::LaunchMultiTaskParams>,
+            request: impl tonic::IntoRequest<super::GetTaskLogsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::LaunchMultiTaskResult>,
+            tonic::Response<super::GetTaskLogsResult>,
             tonic::Status,
         > {
             self.inner
@@ -1744,20 +2757,25 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/LaunchMultiTask",
+                "/ballista.protobuf.SchedulerGrpc/GetTaskLogs",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchMultiTask"),
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "GetTaskLogs",
+                    ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn stop_executor(
+        /// Get the locations of a shuffle partition known so far, polled by the tasks of
+        /// pipelined stages
+        pub async fn get_shuffle_locations(
             &mut self,
-            request: impl tonic::IntoRequest<super::StopExecutorParams>,
+            request: impl tonic::IntoRequest<super::GetShuffleLocationsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::StopExecutorResult>,
+            tonic::Response<super::GetShuffleLocationsResult>,
             tonic::Status,
         > {
             self.inner
@@ -1771,20 +2789,25 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/StopExecutor",
+                "/ballista.protobuf.SchedulerGrpc/GetShuffleLocations",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "StopExecutor"),
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "GetShuffleLocations",
+                    ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn cancel_tasks(
+        /// Change the task slots of a registered executor at runtime, e.g. to leave CPU to the
+        /// services colocated with the executor
+        pub async fn resize_executor_task_slots(
             &mut self,
-            request: impl tonic::IntoRequest<super::CancelTasksParams>,
+            request: impl tonic::IntoRequest<super::ResizeExecutorTaskSlotsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CancelTasksResult>,
+            tonic::Response<super::ResizeExecutorTaskSlotsResult>,
             tonic::Status,
         > {
             self.inner
@@ -1798,20 +2821,24 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/CancelTasks",
+                "/ballista.protobuf.SchedulerGrpc/ResizeExecutorTaskSlots",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "CancelTasks"),
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "ResizeExecutorTaskSlots",
+                    ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn remove_job_data(
+        /// Transfer the ownership of active jobs to another scheduler, e.g. before draining this one
+        pub async fn hand_off_jobs(
             &mut self,
-            request: impl tonic::IntoRequest<super::RemoveJobDataParams>,
+            request: impl tonic::IntoRequest<super::HandOffJobsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::RemoveJobDataResult>,
+            tonic::Response<super::HandOffJobsResult>,
             tonic::Status,
         > {
             self.inner
@@ -1825,153 +2852,280 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/RemoveJobData",
+                "/ballista.protobuf.SchedulerGrpc/HandOffJobs",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "RemoveJobData"),
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "HandOffJobs",
+                    ),
                 );
             self.inner.unary(req, path, codec).await
         }
-    }
-}
-/// Generated server implementations.
-pub mod scheduler_grpc_server {
-    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with SchedulerGrpcServer.
-    #[async_trait]
-    pub trait SchedulerGrpc: Send + Sync + 'static {
-        /// Executors must poll the scheduler for heartbeat and to receive tasks
-        async fn poll_work(
-            &self,
-            request: tonic::Request<super::PollWorkParams>,
-        ) -> std::result::Result<tonic::Response<super::PollWorkResult>, tonic::Status>;
-        async fn register_executor(
-            &self,
-            request: tonic::Request<super::RegisterExecutorParams>,
-        ) -> std::result::Result<
-            tonic::Response<super::RegisterExecutorResult>,
-            tonic::Status,
-        >;
-        /// Push-based task scheduler will only leverage this interface
-        /// rather than the PollWork interface to report executor states
-        async fn heart_beat_from_executor(
-            &self,
-            request: tonic::Request<super::HeartBeatParams>,
-        ) -> std::result::Result<tonic::Response<super::HeartBeatResult>, tonic::Status>;
-        async fn update_task_status(
-            &self,
-            request: tonic::Request<super::UpdateTaskStatusParams>,
-        ) -> std::result::Result<
-            tonic::Response<super::UpdateTaskStatusResult>,
-            tonic::Status,
-        >;
-        async fn get_file_metadata(
-            &self,
-            request: tonic::Request<super::GetFileMetadataParams>,
+        /// Take over the ownership of active jobs released by another scheduler
+        pub async fn acquire_jobs(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AcquireJobsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::GetFileMetadataResult>,
+            tonic::Response<super::AcquireJobsResult>,
             tonic::Status,
-        >;
-        async fn create_session(
-            &self,
-            request: tonic::Request<super::CreateSessionParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/AcquireJobs",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "AcquireJobs",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Turn a standby scheduler into an active one, taking over the running jobs of the
+        /// failed schedulers
+        pub async fn promote_scheduler(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PromoteSchedulerParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CreateSessionResult>,
+            tonic::Response<super::PromoteSchedulerResult>,
             tonic::Status,
-        >;
-        async fn update_session(
-            &self,
-            request: tonic::Request<super::UpdateSessionParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/PromoteScheduler",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "PromoteScheduler",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Reconcile the shuffle files held by an executor with the active jobs, to recompute
+        /// lost shuffle outputs before they are fetched and to clean up leftover files
+        pub async fn report_shuffle_inventory(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ReportShuffleInventoryParams>,
         ) -> std::result::Result<
-            tonic::Response<super::UpdateSessionResult>,
+            tonic::Response<super::ReportShuffleInventoryResult>,
             tonic::Status,
-        >;
-        async fn remove_session(
-            &self,
-            request: tonic::Request<super::RemoveSessionParams>,
-        ) -> std::result::Result<
-            tonic::Response<super::RemoveSessionResult>,
-            tonic::Status,
-        >;
-        async fn execute_query(
-            &self,
-            request: tonic::Request<super::ExecuteQueryParams>,
-        ) -> std::result::Result<
-            tonic::Response<super::ExecuteQueryResult>,
-            tonic::Status,
-        >;
-        async fn get_job_status(
-            &self,
-            request: tonic::Request<super::GetJobStatusParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/ReportShuffleInventory",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "ReportShuffleInventory",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Negotiate the version of the protocol spoken by a client or an executor connecting to
+        /// the scheduler, failing when they do not speak a common version
+        pub async fn handshake(
+            &mut self,
+            request: impl tonic::IntoRequest<super::HandshakeParams>,
         ) -> std::result::Result<
-            tonic::Response<super::GetJobStatusResult>,
+            tonic::Response<super::HandshakeResult>,
             tonic::Status,
-        >;
-        /// Used by Executor to tell Scheduler it is stopped.
-        async fn executor_stopped(
-            &self,
-            request: tonic::Request<super::ExecutorStoppedParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/Handshake",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "Handshake",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Get the optimized logical plan and the physical plans of the stages of a job, as text
+        /// and encoded, e.g. to display and diff the plans of several runs
+        pub async fn get_job_plan(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetJobPlanParams>,
         ) -> std::result::Result<
-            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Response<super::GetJobPlanResult>,
             tonic::Status,
-        >;
-        async fn cancel_job(
-            &self,
-            request: tonic::Request<super::CancelJobParams>,
-        ) -> std::result::Result<tonic::Response<super::CancelJobResult>, tonic::Status>;
-        async fn clean_job_data(
-            &self,
-            request: tonic::Request<super::CleanJobDataParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/GetJobPlan",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "GetJobPlan",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Stream the scheduling decisions of a job, which executor each task was bound to, why and
+        /// how long it waited for a task slot, to debug locality and starvation issues. The decisions
+        /// are kept in memory by the scheduler curating the job, the other schedulers forward the
+        /// request to it, and are lost when it restarts or hands the job off.
+        pub async fn get_job_scheduling_trace(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetJobSchedulingTraceParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CleanJobDataResult>,
+            tonic::Response<tonic::codec::Streaming<super::SchedulingTraceEvent>>,
             tonic::Status,
-        >;
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/GetJobSchedulingTrace",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "GetJobSchedulingTrace",
+                    ),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
     }
-    #[derive(Debug)]
-    pub struct SchedulerGrpcServer<T: SchedulerGrpc> {
-        inner: _Inner<T>,
-        accept_compression_encodings: EnabledCompressionEncodings,
-        send_compression_encodings: EnabledCompressionEncodings,
-        max_decoding_message_size: Option<usize>,
-        max_encoding_message_size: Option<usize>,
+}
+/// Generated client implementations.
+pub mod executor_grpc_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    #[derive(Debug, Clone)]
+    pub struct ExecutorGrpcClient<T> {
+        inner: tonic::client::Grpc<T>,
     }
-    struct _Inner<T>(Arc<T>);
-    impl<T: SchedulerGrpc> SchedulerGrpcServer<T> {
+    impl ExecutorGrpcClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> ExecutorGrpcClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
         pub fn new(inner: T) -> Self {
-            Self::from_arc(Arc::new(inner))
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
         }
-        pub fn from_arc(inner: Arc<T>) -> Self {
-            let inner = _Inner(inner);
-            Self {
-                inner,
-                accept_compression_encodings: Default::default(),
-                send_compression_encodings: Default::default(),
-                max_decoding_message_size: None,
-                max_encoding_message_size: None,
-            }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
         }
         pub fn with_interceptor<F>(
             inner: T,
             interceptor: F,
-        ) -> InterceptedService<Self, F>
+        ) -> ExecutorGrpcClient<InterceptedService<T, F>>
         where
             F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
         {
-            InterceptedService::new(Self::new(inner), interceptor)
+            ExecutorGrpcClient::new(InterceptedService::new(inner, interceptor))
         }
-        /// Enable decompressing requests with the given encoding.
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
         #[must_use]
-        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.accept_compression_encodings.enable(encoding);
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
             self
         }
-        /// Compress responses with the given encoding, if the client supports it.
+        /// Enable decompressing responses.
         #[must_use]
-        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.send_compression_encodings.enable(encoding);
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
             self
         }
         /// Limits the maximum size of a decoded message.
@@ -1979,7 +3133,7 @@ pub mod scheduler_grpc_server {
         /// Default: `4MB`
         #[must_use]
         pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
-            self.max_decoding_message_size = Some(limit);
+            self.inner = self.inner.max_decoding_message_size(limit);
             self
         }
         /// Limits the maximum size of an encoded message.
@@ -1987,47 +3141,952 @@ pub mod scheduler_grpc_server {
         /// Default: `usize::MAX`
         #[must_use]
         pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
-            self.max_encoding_message_size = Some(limit);
+            self.inner = self.inner.max_encoding_message_size(limit);
             self
         }
-    }
-    impl<T, B> tonic::codegen::Service<http::Request<B>> for SchedulerGrpcServer<T>
-    where
-        T: SchedulerGrpc,
-        B: Body + Send + 'static,
-        B::Error: Into<StdError> + Send + 'static,
-    {
-        type Response = http::Response<tonic::body::BoxBody>;
-        type Error = std::convert::Infallible;
-        type Future = BoxFuture<Self::Response, Self::Error>;
-        fn poll_ready(
+        pub async fn launch_task(
             &mut self,
-            _cx: &mut Context<'_>,
-        ) -> Poll<std::result::Result<(), Self::Error>> {
-            Poll::Ready(Ok(()))
+            request: impl tonic::IntoRequest<super::LaunchTaskParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::LaunchTaskResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/LaunchTask",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchTask"));
+            self.inner.unary(req, path, codec).await
         }
-        fn call(&mut self, req: http::Request<B>) -> Self::Future {
-            let inner = self.inner.clone();
-            match req.uri().path() {
-                "/ballista.protobuf.SchedulerGrpc/PollWork" => {
+        pub async fn launch_multi_task(
+            &mut self,
+            request: impl tonic::IntoRequest<super::LaunchMultiTaskParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::LaunchMultiTaskResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/LaunchMultiTask",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchMultiTask"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn stop_executor(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StopExecutorParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::StopExecutorResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/StopExecutor",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "StopExecutor"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn cancel_tasks(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CancelTasksParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CancelTasksResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/CancelTasks",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "CancelTasks"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn remove_job_data(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RemoveJobDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveJobDataResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/RemoveJobData",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "RemoveJobData"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Change the reloadable settings of the executor without restarting it
+        pub async fn update_executor_config(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateExecutorConfigParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateExecutorConfigResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/UpdateExecutorConfig",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.ExecutorGrpc",
+                        "UpdateExecutorConfig",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Get the log lines buffered while running a task
+        pub async fn get_task_logs(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetTaskLogsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetTaskLogsResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/GetTaskLogs",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.ExecutorGrpc",
+                        "GetTaskLogs",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Send the status of the tasks of a job to another scheduler, after a handoff of the job
+        pub async fn update_job_scheduler(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateJobSchedulerParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateJobSchedulerResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/UpdateJobScheduler",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.ExecutorGrpc",
+                        "UpdateJobScheduler",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Copy shuffle partitions held by another executor, e.g. a preempted one, to this executor
+        pub async fn replicate_shuffle_data(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ReplicateShuffleDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ReplicateShuffleDataResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/ReplicateShuffleData",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.ExecutorGrpc",
+                        "ReplicateShuffleData",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod scheduler_grpc_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with SchedulerGrpcServer.
+    #[async_trait]
+    pub trait SchedulerGrpc: Send + Sync + 'static {
+        /// Executors must poll the scheduler for heartbeat and to receive tasks
+        async fn poll_work(
+            &self,
+            request: tonic::Request<super::PollWorkParams>,
+        ) -> std::result::Result<tonic::Response<super::PollWorkResult>, tonic::Status>;
+        async fn register_executor(
+            &self,
+            request: tonic::Request<super::RegisterExecutorParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RegisterExecutorResult>,
+            tonic::Status,
+        >;
+        /// Push-based task scheduler will only leverage this interface
+        /// rather than the PollWork interface to report executor states
+        async fn heart_beat_from_executor(
+            &self,
+            request: tonic::Request<super::HeartBeatParams>,
+        ) -> std::result::Result<tonic::Response<super::HeartBeatResult>, tonic::Status>;
+        async fn update_task_status(
+            &self,
+            request: tonic::Request<super::UpdateTaskStatusParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateTaskStatusResult>,
+            tonic::Status,
+        >;
+        async fn get_file_metadata(
+            &self,
+            request: tonic::Request<super::GetFileMetadataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetFileMetadataResult>,
+            tonic::Status,
+        >;
+        async fn create_session(
+            &self,
+            request: tonic::Request<super::CreateSessionParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CreateSessionResult>,
+            tonic::Status,
+        >;
+        async fn update_session(
+            &self,
+            request: tonic::Request<super::UpdateSessionParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateSessionResult>,
+            tonic::Status,
+        >;
+        async fn remove_session(
+            &self,
+            request: tonic::Request<super::RemoveSessionParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveSessionResult>,
+            tonic::Status,
+        >;
+        async fn execute_query(
+            &self,
+            request: tonic::Request<super::ExecuteQueryParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExecuteQueryResult>,
+            tonic::Status,
+        >;
+        async fn get_job_status(
+            &self,
+            request: tonic::Request<super::GetJobStatusParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetJobStatusResult>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the WatchJobStatus method.
+        type WatchJobStatusStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::WatchJobStatusResult, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// Push the status transitions and the stage completions of a job until it completes
+        async fn watch_job_status(
+            &self,
+            request: tonic::Request<super::WatchJobStatusParams>,
+        ) -> std::result::Result<
+            tonic::Response<Self::WatchJobStatusStream>,
+            tonic::Status,
+        >;
+        /// Used by Executor to tell Scheduler it is stopped.
+        async fn executor_stopped(
+            &self,
+            request: tonic::Request<super::ExecutorStoppedParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Status,
+        >;
+        async fn cancel_job(
+            &self,
+            request: tonic::Request<super::CancelJobParams>,
+        ) -> std::result::Result<tonic::Response<super::CancelJobResult>, tonic::Status>;
+        async fn clean_job_data(
+            &self,
+            request: tonic::Request<super::CleanJobDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CleanJobDataResult>,
+            tonic::Status,
+        >;
+        /// Export the execution graph of a job, to be consumed by visualization tools
+        async fn export_execution_graph(
+            &self,
+            request: tonic::Request<super::ExportExecutionGraphParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExportExecutionGraphResult>,
+            tonic::Status,
+        >;
+        /// Get the log lines buffered by the executor which ran a task
+        async fn get_task_logs(
+            &self,
+            request: tonic::Request<super::GetTaskLogsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetTaskLogsResult>,
+            tonic::Status,
+        >;
+        /// Get the locations of a shuffle partition known so far, polled by the tasks of
+        /// pipelined stages
+        async fn get_shuffle_locations(
+            &self,
+            request: tonic::Request<super::GetShuffleLocationsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetShuffleLocationsResult>,
+            tonic::Status,
+        >;
+        /// Change the task slots of a registered executor at runtime, e.g. to leave CPU to the
+        /// services colocated with the executor
+        async fn resize_executor_task_slots(
+            &self,
+            request: tonic::Request<super::ResizeExecutorTaskSlotsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ResizeExecutorTaskSlotsResult>,
+            tonic::Status,
+        >;
+        /// Transfer the ownership of active jobs to another scheduler, e.g. before draining this one
+        async fn hand_off_jobs(
+            &self,
+            request: tonic::Request<super::HandOffJobsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::HandOffJobsResult>,
+            tonic::Status,
+        >;
+        /// Take over the ownership of active jobs released by another scheduler
+        async fn acquire_jobs(
+            &self,
+            request: tonic::Request<super::AcquireJobsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::AcquireJobsResult>,
+            tonic::Status,
+        >;
+        /// Turn a standby scheduler into an active one, taking over the running jobs of the
+        /// failed schedulers
+        async fn promote_scheduler(
+            &self,
+            request: tonic::Request<super::PromoteSchedulerParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::PromoteSchedulerResult>,
+            tonic::Status,
+        >;
+        /// Reconcile the shuffle files held by an executor with the active jobs, to recompute
+        /// lost shuffle outputs before they are fetched and to clean up leftover files
+        async fn report_shuffle_inventory(
+            &self,
+            request: tonic::Request<super::ReportShuffleInventoryParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ReportShuffleInventoryResult>,
+            tonic::Status,
+        >;
+        /// Negotiate the version of the protocol spoken by a client or an executor connecting to
+        /// the scheduler, failing when they do not speak a common version
+        async fn handshake(
+            &self,
+            request: tonic::Request<super::HandshakeParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::HandshakeResult>,
+            tonic::Status,
+        >;
+        /// Get the optimized logical plan and the physical plans of the stages of a job, as text
+        /// and encoded, e.g. to display and diff the plans of several runs
+        async fn get_job_plan(
+            &self,
+            request: tonic::Request<super::GetJobPlanParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetJobPlanResult>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the GetJobSchedulingTrace method.
+        type GetJobSchedulingTraceStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::SchedulingTraceEvent, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// Stream the scheduling decisions of a job, which executor each task was bound to, why and
+        /// how long it waited for a task slot, to debug locality and starvation issues. The decisions
+        /// are kept in memory by the scheduler curating the job, the other schedulers forward the
+        /// request to it, and are lost when it restarts or hands the job off.
+        async fn get_job_scheduling_trace(
+            &self,
+            request: tonic::Request<super::GetJobSchedulingTraceParams>,
+        ) -> std::result::Result<
+            tonic::Response<Self::GetJobSchedulingTraceStream>,
+            tonic::Status,
+        >;
+    }
+    #[derive(Debug)]
+    pub struct SchedulerGrpcServer<T: SchedulerGrpc> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: SchedulerGrpc> SchedulerGrpcServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for SchedulerGrpcServer<T>
+    where
+        T: SchedulerGrpc,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/ballista.protobuf.SchedulerGrpc/PollWork" => {
+                    #[allow(non_camel_case_types)]
+                    struct PollWorkSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::PollWorkParams>
+                    for PollWorkSvc<T> {
+                        type Response = super::PollWorkResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PollWorkParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::poll_work(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = PollWorkSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/RegisterExecutor" => {
+                    #[allow(non_camel_case_types)]
+                    struct RegisterExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::RegisterExecutorParams>
+                    for RegisterExecutorSvc<T> {
+                        type Response = super::RegisterExecutorResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RegisterExecutorParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::register_executor(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = RegisterExecutorSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/HeartBeatFromExecutor" => {
+                    #[allow(non_camel_case_types)]
+                    struct HeartBeatFromExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::HeartBeatParams>
+                    for HeartBeatFromExecutorSvc<T> {
+                        type Response = super::HeartBeatResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::HeartBeatParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::heart_beat_from_executor(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = HeartBeatFromExecutorSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/UpdateTaskStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateTaskStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::UpdateTaskStatusParams>
+                    for UpdateTaskStatusSvc<T> {
+                        type Response = super::UpdateTaskStatusResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateTaskStatusParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::update_task_status(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdateTaskStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/GetFileMetadata" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetFileMetadataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::GetFileMetadataParams>
+                    for GetFileMetadataSvc<T> {
+                        type Response = super::GetFileMetadataResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetFileMetadataParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::get_file_metadata(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetFileMetadataSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/CreateSession" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::CreateSessionParams>
+                    for CreateSessionSvc<T> {
+                        type Response = super::CreateSessionResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateSessionParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::create_session(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CreateSessionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/UpdateSession" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::UpdateSessionParams>
+                    for UpdateSessionSvc<T> {
+                        type Response = super::UpdateSessionResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateSessionParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::update_session(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdateSessionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/RemoveSession" => {
+                    #[allow(non_camel_case_types)]
+                    struct RemoveSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::RemoveSessionParams>
+                    for RemoveSessionSvc<T> {
+                        type Response = super::RemoveSessionResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RemoveSessionParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::remove_session(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = RemoveSessionSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/ExecuteQuery" => {
                     #[allow(non_camel_case_types)]
-                    struct PollWorkSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct ExecuteQuerySvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::PollWorkParams>
-                    for PollWorkSvc<T> {
-                        type Response = super::PollWorkResult;
+                    > tonic::server::UnaryService<super::ExecuteQueryParams>
+                    for ExecuteQuerySvc<T> {
+                        type Response = super::ExecuteQueryResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::PollWorkParams>,
+                            request: tonic::Request<super::ExecuteQueryParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::poll_work(&inner, request).await
+                                <T as SchedulerGrpc>::execute_query(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -2039,7 +4098,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = PollWorkSvc(inner);
+                        let method = ExecuteQuerySvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2055,25 +4114,72 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/RegisterExecutor" => {
+                "/ballista.protobuf.SchedulerGrpc/GetJobStatus" => {
                     #[allow(non_camel_case_types)]
-                    struct RegisterExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetJobStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::RegisterExecutorParams>
-                    for RegisterExecutorSvc<T> {
-                        type Response = super::RegisterExecutorResult;
+                    > tonic::server::UnaryService<super::GetJobStatusParams>
+                    for GetJobStatusSvc<T> {
+                        type Response = super::GetJobStatusResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RegisterExecutorParams>,
+                            request: tonic::Request<super::GetJobStatusParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::register_executor(&inner, request)
+                                <T as SchedulerGrpc>::get_job_status(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetJobStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/WatchJobStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct WatchJobStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::ServerStreamingService<super::WatchJobStatusParams>
+                    for WatchJobStatusSvc<T> {
+                        type Response = super::WatchJobStatusResult;
+                        type ResponseStream = T::WatchJobStatusStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::WatchJobStatusParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::watch_job_status(&inner, request)
                                     .await
                             };
                             Box::pin(fut)
@@ -2086,7 +4192,54 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = RegisterExecutorSvc(inner);
+                        let method = WatchJobStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExecutorStoppedSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::ExecutorStoppedParams>
+                    for ExecutorStoppedSvc<T> {
+                        type Response = super::ExecutorStoppedResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExecutorStoppedParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::executor_stopped(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ExecutorStoppedSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2102,25 +4255,117 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/HeartBeatFromExecutor" => {
+                "/ballista.protobuf.SchedulerGrpc/CancelJob" => {
                     #[allow(non_camel_case_types)]
-                    struct HeartBeatFromExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct CancelJobSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::HeartBeatParams>
-                    for HeartBeatFromExecutorSvc<T> {
-                        type Response = super::HeartBeatResult;
+                    > tonic::server::UnaryService<super::CancelJobParams>
+                    for CancelJobSvc<T> {
+                        type Response = super::CancelJobResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::HeartBeatParams>,
+                            request: tonic::Request<super::CancelJobParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::heart_beat_from_executor(
+                                <T as SchedulerGrpc>::cancel_job(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CancelJobSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/CleanJobData" => {
+                    #[allow(non_camel_case_types)]
+                    struct CleanJobDataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::CleanJobDataParams>
+                    for CleanJobDataSvc<T> {
+                        type Response = super::CleanJobDataResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CleanJobDataParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::clean_job_data(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CleanJobDataSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/ExportExecutionGraph" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExportExecutionGraphSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::ExportExecutionGraphParams>
+                    for ExportExecutionGraphSvc<T> {
+                        type Response = super::ExportExecutionGraphResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExportExecutionGraphParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SchedulerGrpc>::export_execution_graph(
                                         &inner,
                                         request,
                                     )
@@ -2136,7 +4381,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = HeartBeatFromExecutorSvc(inner);
+                        let method = ExportExecutionGraphSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2152,25 +4397,28 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/UpdateTaskStatus" => {
+                "/ballista.protobuf.SchedulerGrpc/GetTaskLogs" => {
                     #[allow(non_camel_case_types)]
-                    struct UpdateTaskStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetTaskLogsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::UpdateTaskStatusParams>
-                    for UpdateTaskStatusSvc<T> {
-                        type Response = super::UpdateTaskStatusResult;
+                    > tonic::server::UnaryService<super::GetTaskLogsParams>
+                    for GetTaskLogsSvc<T> {
+                        type Response = super::GetTaskLogsResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::UpdateTaskStatusParams>,
+                            request: tonic::Request<super::GetTaskLogsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::update_task_status(&inner, request)
+                                <T as SchedulerGrpc>::get_task_logs(
+                                        &inner,
+                                        request,
+                                    )
                                     .await
                             };
                             Box::pin(fut)
@@ -2183,7 +4431,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = UpdateTaskStatusSvc(inner);
+                        let method = GetTaskLogsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2199,25 +4447,28 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/GetFileMetadata" => {
+                "/ballista.protobuf.SchedulerGrpc/GetShuffleLocations" => {
                     #[allow(non_camel_case_types)]
-                    struct GetFileMetadataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetShuffleLocationsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::GetFileMetadataParams>
-                    for GetFileMetadataSvc<T> {
-                        type Response = super::GetFileMetadataResult;
+                    > tonic::server::UnaryService<super::GetShuffleLocationsParams>
+                    for GetShuffleLocationsSvc<T> {
+                        type Response = super::GetShuffleLocationsResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::GetFileMetadataParams>,
+                            request: tonic::Request<super::GetShuffleLocationsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::get_file_metadata(&inner, request)
+                                <T as SchedulerGrpc>::get_shuffle_locations(
+                                        &inner,
+                                        request,
+                                    )
                                     .await
                             };
                             Box::pin(fut)
@@ -2230,7 +4481,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = GetFileMetadataSvc(inner);
+                        let method = GetShuffleLocationsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2246,25 +4497,29 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/CreateSession" => {
+                "/ballista.protobuf.SchedulerGrpc/ResizeExecutorTaskSlots" => {
                     #[allow(non_camel_case_types)]
-                    struct CreateSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct ResizeExecutorTaskSlotsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::CreateSessionParams>
-                    for CreateSessionSvc<T> {
-                        type Response = super::CreateSessionResult;
+                    > tonic::server::UnaryService<super::ResizeExecutorTaskSlotsParams>
+                    for ResizeExecutorTaskSlotsSvc<T> {
+                        type Response = super::ResizeExecutorTaskSlotsResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CreateSessionParams>,
+                            request: tonic::Request<super::ResizeExecutorTaskSlotsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::create_session(&inner, request).await
+                                <T as SchedulerGrpc>::resize_executor_task_slots(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2276,7 +4531,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = CreateSessionSvc(inner);
+                        let method = ResizeExecutorTaskSlotsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2292,25 +4547,29 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/UpdateSession" => {
+                "/ballista.protobuf.SchedulerGrpc/HandOffJobs" => {
                     #[allow(non_camel_case_types)]
-                    struct UpdateSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct HandOffJobsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::UpdateSessionParams>
-                    for UpdateSessionSvc<T> {
-                        type Response = super::UpdateSessionResult;
+                    > tonic::server::UnaryService<super::HandOffJobsParams>
+                    for HandOffJobsSvc<T> {
+                        type Response = super::HandOffJobsResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::UpdateSessionParams>,
+                            request: tonic::Request<super::HandOffJobsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::update_session(&inner, request).await
+                                <T as SchedulerGrpc>::hand_off_jobs(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2322,7 +4581,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = UpdateSessionSvc(inner);
+                        let method = HandOffJobsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2338,25 +4597,29 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/RemoveSession" => {
+                "/ballista.protobuf.SchedulerGrpc/AcquireJobs" => {
                     #[allow(non_camel_case_types)]
-                    struct RemoveSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct AcquireJobsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::RemoveSessionParams>
-                    for RemoveSessionSvc<T> {
-                        type Response = super::RemoveSessionResult;
+                    > tonic::server::UnaryService<super::AcquireJobsParams>
+                    for AcquireJobsSvc<T> {
+                        type Response = super::AcquireJobsResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RemoveSessionParams>,
+                            request: tonic::Request<super::AcquireJobsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::remove_session(&inner, request).await
+                                <T as SchedulerGrpc>::acquire_jobs(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2368,7 +4631,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = RemoveSessionSvc(inner);
+                        let method = AcquireJobsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2384,25 +4647,29 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/ExecuteQuery" => {
+                "/ballista.protobuf.SchedulerGrpc/PromoteScheduler" => {
                     #[allow(non_camel_case_types)]
-                    struct ExecuteQuerySvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct PromoteSchedulerSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::ExecuteQueryParams>
-                    for ExecuteQuerySvc<T> {
-                        type Response = super::ExecuteQueryResult;
+                    > tonic::server::UnaryService<super::PromoteSchedulerParams>
+                    for PromoteSchedulerSvc<T> {
+                        type Response = super::PromoteSchedulerResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ExecuteQueryParams>,
+                            request: tonic::Request<super::PromoteSchedulerParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::execute_query(&inner, request).await
+                                <T as SchedulerGrpc>::promote_scheduler(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2414,7 +4681,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = ExecuteQuerySvc(inner);
+                        let method = PromoteSchedulerSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2430,25 +4697,29 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/GetJobStatus" => {
+                "/ballista.protobuf.SchedulerGrpc/ReportShuffleInventory" => {
                     #[allow(non_camel_case_types)]
-                    struct GetJobStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct ReportShuffleInventorySvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::GetJobStatusParams>
-                    for GetJobStatusSvc<T> {
-                        type Response = super::GetJobStatusResult;
+                    > tonic::server::UnaryService<super::ReportShuffleInventoryParams>
+                    for ReportShuffleInventorySvc<T> {
+                        type Response = super::ReportShuffleInventoryResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::GetJobStatusParams>,
+                            request: tonic::Request<super::ReportShuffleInventoryParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::get_job_status(&inner, request).await
+                                <T as SchedulerGrpc>::report_shuffle_inventory(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2460,7 +4731,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = GetJobStatusSvc(inner);
+                        let method = ReportShuffleInventorySvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2476,25 +4747,28 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped" => {
+                "/ballista.protobuf.SchedulerGrpc/Handshake" => {
                     #[allow(non_camel_case_types)]
-                    struct ExecutorStoppedSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct HandshakeSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::ExecutorStoppedParams>
-                    for ExecutorStoppedSvc<T> {
-                        type Response = super::ExecutorStoppedResult;
+                    > tonic::server::UnaryService<super::HandshakeParams>
+                    for HandshakeSvc<T> {
+                        type Response = super::HandshakeResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ExecutorStoppedParams>,
+                            request: tonic::Request<super::HandshakeParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::executor_stopped(&inner, request)
+                                <T as SchedulerGrpc>::handshake(
+                                        &inner,
+                                        request,
+                                    )
                                     .await
                             };
                             Box::pin(fut)
@@ -2507,7 +4781,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = ExecutorStoppedSvc(inner);
+                        let method = HandshakeSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2523,25 +4797,29 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/CancelJob" => {
+                "/ballista.protobuf.SchedulerGrpc/GetJobPlan" => {
                     #[allow(non_camel_case_types)]
-                    struct CancelJobSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetJobPlanSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::CancelJobParams>
-                    for CancelJobSvc<T> {
-                        type Response = super::CancelJobResult;
+                    > tonic::server::UnaryService<super::GetJobPlanParams>
+                    for GetJobPlanSvc<T> {
+                        type Response = super::GetJobPlanResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CancelJobParams>,
+                            request: tonic::Request<super::GetJobPlanParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::cancel_job(&inner, request).await
+                                <T as SchedulerGrpc>::get_job_plan(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2553,7 +4831,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = CancelJobSvc(inner);
+                        let method = GetJobPlanSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2569,25 +4847,31 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/CleanJobData" => {
+                "/ballista.protobuf.SchedulerGrpc/GetJobSchedulingTrace" => {
                     #[allow(non_camel_case_types)]
-                    struct CleanJobDataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetJobSchedulingTraceSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::CleanJobDataParams>
-                    for CleanJobDataSvc<T> {
-                        type Response = super::CleanJobDataResult;
+                    > tonic::server::ServerStreamingService<
+                        super::GetJobSchedulingTraceParams,
+                    > for GetJobSchedulingTraceSvc<T> {
+                        type Response = super::SchedulingTraceEvent;
+                        type ResponseStream = T::GetJobSchedulingTraceStream;
                         type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
+                            tonic::Response<Self::ResponseStream>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CleanJobDataParams>,
+                            request: tonic::Request<super::GetJobSchedulingTraceParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as SchedulerGrpc>::clean_job_data(&inner, request).await
+                                <T as SchedulerGrpc>::get_job_scheduling_trace(
+                                        &inner,
+                                        request,
+                                    )
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -2599,7 +4883,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = CleanJobDataSvc(inner);
+                        let method = GetJobSchedulingTraceSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2610,7 +4894,7 @@ pub mod scheduler_grpc_server {
                                 max_decoding_message_size,
                                 max_encoding_message_size,
                             );
-                        let res = grpc.unary(method, req).await;
+                        let res = grpc.server_streaming(method, req).await;
                         Ok(res)
                     };
                     Box::pin(fut)
@@ -2698,6 +4982,38 @@ pub mod executor_grpc_server {
             tonic::Response<super::RemoveJobDataResult>,
             tonic::Status,
         >;
+        /// Change the reloadable settings of the executor without restarting it
+        async fn update_executor_config(
+            &self,
+            request: tonic::Request<super::UpdateExecutorConfigParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateExecutorConfigResult>,
+            tonic::Status,
+        >;
+        /// Get the log lines buffered while running a task
+        async fn get_task_logs(
+            &self,
+            request: tonic::Request<super::GetTaskLogsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetTaskLogsResult>,
+            tonic::Status,
+        >;
+        /// Send the status of the tasks of a job to another scheduler, after a handoff of the job
+        async fn update_job_scheduler(
+            &self,
+            request: tonic::Request<super::UpdateJobSchedulerParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateJobSchedulerResult>,
+            tonic::Status,
+        >;
+        /// Copy shuffle partitions held by another executor, e.g. a preempted one, to this executor
+        async fn replicate_shuffle_data(
+            &self,
+            request: tonic::Request<super::ReplicateShuffleDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ReplicateShuffleDataResult>,
+            tonic::Status,
+        >;
     }
     #[derive(Debug)]
     pub struct ExecutorGrpcServer<T: ExecutorGrpc> {
@@ -3009,6 +5325,194 @@ pub mod executor_grpc_server {
                     };
                     Box::pin(fut)
                 }
+                "/ballista.protobuf.ExecutorGrpc/UpdateExecutorConfig" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateExecutorConfigSvc<T: ExecutorGrpc>(pub Arc<T>);
+                    impl<
+                        T: ExecutorGrpc,
+                    > tonic::server::UnaryService<super::UpdateExecutorConfigParams>
+                    for UpdateExecutorConfigSvc<T> {
+                        type Response = super::UpdateExecutorConfigResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateExecutorConfigParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ExecutorGrpc>::update_executor_config(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdateExecutorConfigSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.ExecutorGrpc/GetTaskLogs" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetTaskLogsSvc<T: ExecutorGrpc>(pub Arc<T>);
+                    impl<
+                        T: ExecutorGrpc,
+                    > tonic::server::UnaryService<super::GetTaskLogsParams>
+                    for GetTaskLogsSvc<T> {
+                        type Response = super::GetTaskLogsResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetTaskLogsParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ExecutorGrpc>::get_task_logs(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetTaskLogsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.ExecutorGrpc/UpdateJobScheduler" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateJobSchedulerSvc<T: ExecutorGrpc>(pub Arc<T>);
+                    impl<
+                        T: ExecutorGrpc,
+                    > tonic::server::UnaryService<super::UpdateJobSchedulerParams>
+                    for UpdateJobSchedulerSvc<T> {
+                        type Response = super::UpdateJobSchedulerResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateJobSchedulerParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ExecutorGrpc>::update_job_scheduler(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdateJobSchedulerSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.ExecutorGrpc/ReplicateShuffleData" => {
+                    #[allow(non_camel_case_types)]
+                    struct ReplicateShuffleDataSvc<T: ExecutorGrpc>(pub Arc<T>);
+                    impl<
+                        T: ExecutorGrpc,
+                    > tonic::server::UnaryService<super::ReplicateShuffleDataParams>
+                    for ReplicateShuffleDataSvc<T> {
+                        type Response = super::ReplicateShuffleDataResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ReplicateShuffleDataParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ExecutorGrpc>::replicate_shuffle_data(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ReplicateShuffleDataSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
@@ -21,9 +21,20 @@
 use crate::{error::BallistaError, serde::scheduler::Action as BallistaAction};
 
 use arrow_flight::sql::ProstMessageExt;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::ipc::reader::StreamReader;
+use datafusion::arrow::ipc::writer::StreamWriter;
 use datafusion::common::DataFusionError;
+use datafusion::datasource::TableProvider;
+use datafusion::execution::object_store::ObjectStoreUrl;
 use datafusion::execution::FunctionRegistry;
+#[cfg(any(feature = "wasm-udf", feature = "python-udf"))]
+use datafusion::logical_expr::ScalarUDF;
+use datafusion::logical_expr::{Extension, LogicalPlan};
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_plan::values::ValuesExec;
 use datafusion::physical_plan::{ExecutionPlan, Partitioning};
+use datafusion::prelude::SessionContext;
 use datafusion_proto::common::proto_error;
 use datafusion_proto::physical_plan::from_proto::parse_protobuf_hash_partitioning;
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
@@ -33,6 +44,7 @@ use datafusion_proto::{
     physical_plan::{AsExecutionPlan, PhysicalExtensionCodec},
 };
 
+use object_store::path::Path;
 use prost::Message;
 use std::fmt::Debug;
 use std::marker::PhantomData;
@@ -40,10 +52,17 @@ use std::sync::Arc;
 use std::{convert::TryInto, io::Cursor};
 
 use crate::execution_plans::{
+    custom_partitioning, BloomFilter, BloomFilterExec, FileSinkCommitExec,
+    ParallelFileSinkExec, PartitionIdExpr, PartitionPlacementExec, RuntimeFilterExec,
     ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec,
 };
+use crate::inline_table::InlineTable;
+use crate::materialized_cte::MaterializedCte;
+use crate::plan_protection::PlanProtection;
 use crate::serde::protobuf::ballista_physical_plan_node::PhysicalPlanType;
+use crate::serde::protobuf::ballista_table_provider_node::TableProviderType;
 use crate::serde::scheduler::PartitionLocation;
+use crate::temporary_table::TemporaryTable;
 pub use generated::ballista as protobuf;
 
 pub mod generated;
@@ -70,6 +89,51 @@ pub fn decode_protobuf(bytes: &[u8]) -> Result<BallistaAction, BallistaError> {
         .and_then(|node| node.try_into())
 }
 
+/// Encode a UDF along with the plan if it is a WASM or a Python UDF, the other UDFs being
+/// looked up by name when the plan is decoded
+#[cfg(any(feature = "wasm-udf", feature = "python-udf"))]
+fn encode_udf(udf: &ScalarUDF, buf: &mut Vec<u8>) -> Result<(), DataFusionError> {
+    use protobuf::scalar_udf_node::UdfType;
+
+    let mut udf_type = None;
+    #[cfg(feature = "wasm-udf")]
+    if udf_type.is_none() {
+        udf_type = crate::wasm_udf::to_node(udf)?.map(UdfType::WasmUdf);
+    }
+    #[cfg(feature = "python-udf")]
+    if udf_type.is_none() {
+        udf_type = crate::python_udf::to_node(udf)?.map(UdfType::PythonUdf);
+    }
+    if udf_type.is_none() {
+        return Ok(());
+    }
+    protobuf::ScalarUdfNode { udf_type }
+        .encode(buf)
+        .map_err(|e| {
+            DataFusionError::Internal(format!("Failed to encode UDF {}: {e}", udf.name()))
+        })
+}
+
+/// Decode a WASM or a Python UDF encoded along with a plan
+#[cfg(any(feature = "wasm-udf", feature = "python-udf"))]
+fn decode_udf(name: &str, buf: &[u8]) -> Result<Arc<ScalarUDF>, DataFusionError> {
+    use protobuf::scalar_udf_node::UdfType;
+
+    let node = protobuf::ScalarUdfNode::decode(buf).map_err(|e| {
+        DataFusionError::Internal(format!("Could not deserialize UDF {name}: {e}"))
+    })?;
+    match node.udf_type {
+        #[cfg(feature = "wasm-udf")]
+        Some(UdfType::WasmUdf(node)) => crate::wasm_udf::from_node(node),
+        #[cfg(feature = "python-udf")]
+        Some(UdfType::PythonUdf(node)) => crate::python_udf::from_node(node),
+        _ => Err(DataFusionError::NotImplemented(format!(
+            "UDF {name} is not supported by this process, check the wasm-udf and \
+            python-udf features"
+        ))),
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BallistaCodec<
     T: 'static + AsLogicalPlan = LogicalPlanNode,
@@ -77,6 +141,8 @@ pub struct BallistaCodec<
 > {
     logical_extension_codec: Arc<dyn LogicalExtensionCodec>,
     physical_extension_codec: Arc<dyn PhysicalExtensionCodec>,
+    /// Protection of the serialized plans sent to executors and stored by schedulers
+    plan_protection: PlanProtection,
     logical_plan_repr: PhantomData<T>,
     physical_plan_repr: PhantomData<U>,
 }
@@ -84,8 +150,9 @@ pub struct BallistaCodec<
 impl Default for BallistaCodec {
     fn default() -> Self {
         Self {
-            logical_extension_codec: Arc::new(DefaultLogicalExtensionCodec {}),
+            logical_extension_codec: Arc::new(BallistaLogicalExtensionCodec {}),
             physical_extension_codec: Arc::new(BallistaPhysicalExtensionCodec {}),
+            plan_protection: PlanProtection::default(),
             logical_plan_repr: PhantomData,
             physical_plan_repr: PhantomData,
         }
@@ -100,6 +167,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> BallistaCodec<T,
         Self {
             logical_extension_codec,
             physical_extension_codec,
+            plan_protection: PlanProtection::default(),
             logical_plan_repr: PhantomData,
             physical_plan_repr: PhantomData,
         }
@@ -112,6 +180,141 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> BallistaCodec<T,
     pub fn physical_extension_codec(&self) -> &dyn PhysicalExtensionCodec {
         self.physical_extension_codec.as_ref()
     }
+
+    /// Sign or encrypt the serialized plans sent to executors and stored by schedulers
+    pub fn with_plan_protection(mut self, plan_protection: PlanProtection) -> Self {
+        self.plan_protection = plan_protection;
+        self
+    }
+
+    pub fn plan_protection(&self) -> &PlanProtection {
+        &self.plan_protection
+    }
+}
+
+/// Logical extension codec for the table providers which Ballista clients hand over to
+/// the scheduler, which are references to the temporary tables of the session,
+/// in-memory tables sent along with the plan and materialized subqueries
+#[derive(Debug)]
+pub struct BallistaLogicalExtensionCodec {}
+
+impl LogicalExtensionCodec for BallistaLogicalExtensionCodec {
+    fn try_decode(
+        &self,
+        buf: &[u8],
+        inputs: &[LogicalPlan],
+        ctx: &SessionContext,
+    ) -> Result<Extension, DataFusionError> {
+        DefaultLogicalExtensionCodec {}.try_decode(buf, inputs, ctx)
+    }
+
+    fn try_encode(
+        &self,
+        node: &Extension,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), DataFusionError> {
+        DefaultLogicalExtensionCodec {}.try_encode(node, buf)
+    }
+
+    #[cfg(any(feature = "wasm-udf", feature = "python-udf"))]
+    fn try_decode_udf(
+        &self,
+        name: &str,
+        buf: &[u8],
+    ) -> Result<Arc<ScalarUDF>, DataFusionError> {
+        decode_udf(name, buf)
+    }
+
+    #[cfg(any(feature = "wasm-udf", feature = "python-udf"))]
+    fn try_encode_udf(
+        &self,
+        node: &ScalarUDF,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), DataFusionError> {
+        encode_udf(node, buf)
+    }
+
+    fn try_decode_table_provider(
+        &self,
+        buf: &[u8],
+        schema: SchemaRef,
+        ctx: &SessionContext,
+    ) -> Result<Arc<dyn TableProvider>, DataFusionError> {
+        let node = protobuf::BallistaTableProviderNode::decode(buf).map_err(|e| {
+            DataFusionError::Internal(format!(
+                "Could not deserialize BallistaTableProviderNode: {e}"
+            ))
+        })?;
+
+        match node.table_provider_type {
+            Some(TableProviderType::TemporaryTable(table)) => {
+                // the table is resolved by name when the scheduler plans the query
+                Ok(Arc::new(TemporaryTable::new(table.name, schema)))
+            }
+            Some(TableProviderType::InlineTable(table)) => {
+                let reader = StreamReader::try_new(table.data.as_slice(), None)?;
+                let batches = reader.collect::<Result<Vec<_>, _>>()?;
+                Ok(Arc::new(InlineTable::new(schema, batches)))
+            }
+            Some(TableProviderType::MaterializedCte(cte)) => {
+                let plan = LogicalPlanNode::decode(cte.plan.as_slice())
+                    .map_err(|e| {
+                        DataFusionError::Internal(format!(
+                            "Could not deserialize the plan of materialized CTE {}: {e}",
+                            cte.name
+                        ))
+                    })?
+                    .try_into_logical_plan(ctx, self)?;
+                Ok(Arc::new(MaterializedCte::new(cte.id as usize, cte.name, plan)))
+            }
+            None => Err(DataFusionError::Internal(
+                "Could not deserialize BallistaTableProviderNode because it's table_provider_type is none".to_string(),
+            )),
+        }
+    }
+
+    fn try_encode_table_provider(
+        &self,
+        node: Arc<dyn TableProvider>,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), DataFusionError> {
+        let table_provider_type = if let Some(table) =
+            node.as_any().downcast_ref::<TemporaryTable>()
+        {
+            TableProviderType::TemporaryTable(protobuf::TemporaryTableNode {
+                name: table.name().to_string(),
+            })
+        } else if let Some(table) = node.as_any().downcast_ref::<InlineTable>() {
+            let mut data = vec![];
+            {
+                let mut writer =
+                    StreamWriter::try_new(&mut data, table.schema().as_ref())?;
+                for batch in table.batches() {
+                    writer.write(batch)?;
+                }
+                writer.finish()?;
+            }
+            TableProviderType::InlineTable(protobuf::InlineTableNode { data })
+        } else if let Some(cte) = node.as_any().downcast_ref::<MaterializedCte>() {
+            let mut plan = vec![];
+            LogicalPlanNode::try_from_logical_plan(cte.plan(), self)?
+                .try_encode(&mut plan)?;
+            TableProviderType::MaterializedCte(protobuf::MaterializedCteNode {
+                id: cte.id() as u32,
+                name: cte.name().to_string(),
+                plan,
+            })
+        } else {
+            return DefaultLogicalExtensionCodec {}.try_encode_table_provider(node, buf);
+        };
+
+        let proto = protobuf::BallistaTableProviderNode {
+            table_provider_type: Some(table_provider_type),
+        };
+        proto.encode(buf).map_err(|e| {
+            DataFusionError::Internal(format!("failed to encode table provider: {e:?}"))
+        })
+    }
 }
 
 #[derive(Debug)]
@@ -142,19 +345,42 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
             PhysicalPlanType::ShuffleWriter(shuffle_writer) => {
                 let input = inputs[0].clone();
 
-                let shuffle_output_partitioning = parse_protobuf_hash_partitioning(
+                let mut shuffle_output_partitioning = parse_protobuf_hash_partitioning(
                     shuffle_writer.output_partitioning.as_ref(),
                     registry,
                     input.schema().as_ref(),
                 )?;
+                if shuffle_writer.custom_partitioning {
+                    shuffle_output_partitioning = match shuffle_output_partitioning {
+                        Some(Partitioning::Hash(mut exprs, partition_count))
+                            if exprs.len() == 1 =>
+                        {
+                            Some(custom_partitioning(exprs.remove(0), partition_count))
+                        }
+                        other => {
+                            return Err(DataFusionError::Internal(format!(
+                                "Invalid custom partitioning for ShuffleWriterExec: {other:?}"
+                            )));
+                        }
+                    };
+                }
 
-                Ok(Arc::new(ShuffleWriterExec::try_new(
-                    shuffle_writer.job_id.clone(),
-                    shuffle_writer.stage_id as usize,
-                    input,
-                    "".to_string(), // this is intentional but hacky - the executor will fill this in
-                    shuffle_output_partitioning,
-                )?))
+                Ok(Arc::new(
+                    ShuffleWriterExec::try_new(
+                        shuffle_writer.job_id.clone(),
+                        shuffle_writer.stage_id as usize,
+                        input,
+                        "".to_string(), // this is intentional but hacky - the executor will fill this in
+                        shuffle_output_partitioning,
+                    )?
+                    .with_bloom_filter_columns(
+                        shuffle_writer
+                            .bloom_filter_columns
+                            .iter()
+                            .map(|column| *column as usize)
+                            .collect(),
+                    ),
+                ))
             }
             PhysicalPlanType::ShuffleReader(shuffle_reader) => {
                 let stage_id = shuffle_reader.stage_id as usize;
@@ -176,7 +402,11 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                     })
                     .collect::<Result<Vec<_>, DataFusionError>>()?;
                 let shuffle_reader =
-                    ShuffleReaderExec::try_new(stage_id, partition_location, schema)?;
+                    ShuffleReaderExec::try_new(stage_id, partition_location, schema)?
+                        .with_pipelined_job_id(
+                            (!shuffle_reader.pipelined_job_id.is_empty())
+                                .then(|| shuffle_reader.pipelined_job_id.clone()),
+                        );
                 Ok(Arc::new(shuffle_reader))
             }
             PhysicalPlanType::UnresolvedShuffle(unresolved_shuffle) => {
@@ -187,6 +417,68 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                     unresolved_shuffle.output_partition_count as usize,
                 )))
             }
+            PhysicalPlanType::PartitionPlacement(partition_placement) => {
+                let preferred_hosts = partition_placement
+                    .preferred_hosts
+                    .iter()
+                    .map(|hosts| hosts.host.clone())
+                    .collect();
+                Ok(Arc::new(PartitionPlacementExec::try_new(
+                    inputs[0].clone(),
+                    preferred_hosts,
+                )?))
+            }
+            PhysicalPlanType::ParallelFileSink(_) => {
+                Ok(Arc::new(ParallelFileSinkExec::try_new(inputs[0].clone())?))
+            }
+            PhysicalPlanType::FileSinkCommit(file_sink_commit) => {
+                let object_store_url =
+                    ObjectStoreUrl::parse(&file_sink_commit.object_store_url)?;
+                let output_path = Path::parse(&file_sink_commit.output_path)
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                let staging_path = Path::parse(&file_sink_commit.staging_path)
+                    .map_err(|e| DataFusionError::External(Box::new(e)))?;
+                Ok(Arc::new(FileSinkCommitExec::new(
+                    inputs[0].clone(),
+                    object_store_url,
+                    output_path,
+                    staging_path,
+                )))
+            }
+            PhysicalPlanType::Values(values) => {
+                let reader = StreamReader::try_new(values.data.as_slice(), None)?;
+                let schema = reader.schema();
+                let batches = reader.collect::<Result<Vec<_>, _>>()?;
+                Ok(Arc::new(ValuesExec::try_new_from_batches(schema, batches)?))
+            }
+            PhysicalPlanType::RuntimeFilter(runtime_filter) => {
+                Ok(Arc::new(RuntimeFilterExec::try_new(
+                    inputs[0].clone(),
+                    runtime_filter.build_stage_id as usize,
+                    runtime_filter.build_column as usize,
+                    Column::new(
+                        &runtime_filter.probe_column_name,
+                        runtime_filter.probe_column_index as usize,
+                    ),
+                )?))
+            }
+            PhysicalPlanType::BloomFilter(bloom_filter) => {
+                let filter = BloomFilter::from_bytes(bloom_filter.bits.clone())
+                    .ok_or_else(|| {
+                        DataFusionError::Internal(
+                            "Could not deserialize BloomFilterExec without bits"
+                                .to_owned(),
+                        )
+                    })?;
+                Ok(Arc::new(BloomFilterExec::try_new(
+                    inputs[0].clone(),
+                    Column::new(
+                        &bloom_filter.column_name,
+                        bloom_filter.column_index as usize,
+                    ),
+                    Arc::new(filter),
+                )?))
+            }
         }
     }
 
@@ -198,8 +490,17 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
         if let Some(exec) = node.as_any().downcast_ref::<ShuffleWriterExec>() {
             // note that we use shuffle_output_partitioning() rather than output_partitioning()
             // to get the true output partitioning
+            let partition_id_expr = exec
+                .shuffle_output_partitioning()
+                .and_then(PartitionIdExpr::from_partitioning);
             let output_partitioning = match exec.shuffle_output_partitioning() {
                 Some(Partitioning::Hash(exprs, partition_count)) => {
+                    // the expression computing the partitions of a custom partitioning is
+                    // serialized in place of its wrapper
+                    let exprs = match partition_id_expr {
+                        Some(partition_id_expr) => vec![partition_id_expr.expr().clone()],
+                        None => exprs.clone(),
+                    };
                     let default_codec =
                         datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec {};
                     Some(datafusion_proto::protobuf::PhysicalHashRepartition {
@@ -225,6 +526,12 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                         stage_id: exec.stage_id() as u32,
                         input: None,
                         output_partitioning,
+                        custom_partitioning: partition_id_expr.is_some(),
+                        bloom_filter_columns: exec
+                            .bloom_filter_columns()
+                            .iter()
+                            .map(|column| *column as u32)
+                            .collect(),
                     },
                 )),
             };
@@ -259,6 +566,10 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                         stage_id,
                         partition,
                         schema: Some(exec.schema().as_ref().try_into()?),
+                        pipelined_job_id: exec
+                            .pipelined_job_id
+                            .clone()
+                            .unwrap_or_default(),
                     },
                 )),
             };
@@ -285,6 +596,115 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                 ))
             })?;
 
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<PartitionPlacementExec>()
+        {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::PartitionPlacement(
+                    protobuf::PartitionPlacementExecNode {
+                        preferred_hosts: exec
+                            .preferred_hosts()
+                            .iter()
+                            .map(|hosts| protobuf::PreferredHosts {
+                                host: hosts.clone(),
+                            })
+                            .collect(),
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode partition placement execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if node.as_any().is::<ParallelFileSinkExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::ParallelFileSink(
+                    protobuf::ParallelFileSinkExecNode {},
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode parallel file sink execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<FileSinkCommitExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::FileSinkCommit(
+                    protobuf::FileSinkCommitExecNode {
+                        object_store_url: exec.object_store_url().to_string(),
+                        output_path: exec.output_path().to_string(),
+                        staging_path: exec.staging_path().to_string(),
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode file sink commit execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<ValuesExec>() {
+            let mut data = vec![];
+            {
+                let mut writer =
+                    StreamWriter::try_new(&mut data, exec.schema().as_ref())?;
+                for batch in exec.data() {
+                    writer.write(&batch)?;
+                }
+                writer.finish()?;
+            }
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::Values(
+                    protobuf::ValuesExecNode { data },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode values execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<RuntimeFilterExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::RuntimeFilter(
+                    protobuf::RuntimeFilterExecNode {
+                        build_stage_id: exec.build_stage_id as u32,
+                        build_column: exec.build_column as u32,
+                        probe_column_name: exec.probe_column.name().to_owned(),
+                        probe_column_index: exec.probe_column.index() as u32,
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode runtime filter execution plan: {e:?}"
+                ))
+            })?;
+
+            Ok(())
+        } else if let Some(exec) = node.as_any().downcast_ref::<BloomFilterExec>() {
+            let proto = protobuf::BallistaPhysicalPlanNode {
+                physical_plan_type: Some(PhysicalPlanType::BloomFilter(
+                    protobuf::BloomFilterExecNode {
+                        column_name: exec.column.name().to_owned(),
+                        column_index: exec.column.index() as u32,
+                        bits: exec.filter.as_bytes().to_vec(),
+                    },
+                )),
+            };
+            proto.encode(buf).map_err(|e| {
+                DataFusionError::Internal(format!(
+                    "failed to encode bloom filter execution plan: {e:?}"
+                ))
+            })?;
+
             Ok(())
         } else {
             Err(DataFusionError::Internal(format!(
@@ -292,4 +712,22 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
             )))
         }
     }
+
+    #[cfg(any(feature = "wasm-udf", feature = "python-udf"))]
+    fn try_decode_udf(
+        &self,
+        name: &str,
+        buf: &[u8],
+    ) -> Result<Arc<ScalarUDF>, DataFusionError> {
+        decode_udf(name, buf)
+    }
+
+    #[cfg(any(feature = "wasm-udf", feature = "python-udf"))]
+    fn try_encode_udf(
+        &self,
+        node: &ScalarUDF,
+        buf: &mut Vec<u8>,
+    ) -> Result<(), DataFusionError> {
+        encode_udf(node, buf)
+    }
 }
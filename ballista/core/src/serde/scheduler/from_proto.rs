@@ -17,10 +17,11 @@
 
 use chrono::{TimeZone, Utc};
 use datafusion::common::tree_node::{Transformed, TransformedResult, TreeNode};
+use datafusion::common::ScalarValue;
 use datafusion::execution::runtime_env::RuntimeEnv;
 use datafusion::logical_expr::{AggregateUDF, ScalarUDF, WindowUDF};
 use datafusion::physical_plan::metrics::{
-    Count, Gauge, MetricValue, MetricsSet, Time, Timestamp,
+    Count, Gauge, Label, MetricValue, MetricsSet, Time, Timestamp,
 };
 use datafusion::physical_plan::{ExecutionPlan, Metric};
 use datafusion_proto::logical_plan::AsLogicalPlan;
@@ -31,13 +32,18 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use crate::error::BallistaError;
+use crate::execution_plans::BloomFilter;
 use crate::serde::scheduler::{
-    Action, ExecutorData, ExecutorMetadata, ExecutorSpecification, PartitionId,
-    PartitionLocation, PartitionStats, SimpleFunctionRegistry, TaskDefinition,
+    Action, ColumnBounds, ExecutorCapabilities, ExecutorData, ExecutorMetadata,
+    ExecutorSpecification, PartitionId, PartitionLocation, PartitionStats,
+    SimpleFunctionRegistry, TaskDefinition,
 };
 
 use crate::serde::{protobuf, BallistaCodec};
-use protobuf::{operator_metric, NamedCount, NamedGauge, NamedTime};
+use log::debug;
+use protobuf::{
+    operator_metric, MetricAggregation, MetricType, NamedCount, NamedGauge, NamedTime,
+};
 
 impl TryInto<Action> for protobuf::Action {
     type Error = BallistaError;
@@ -54,6 +60,16 @@ impl TryInto<Action> for protobuf::Action {
                     port: fetch.port as u16,
                 })
             }
+            Some(protobuf::action::ActionType::FetchPartitions(fetch)) => {
+                Ok(Action::FetchPartitions {
+                    job_id: fetch.job_id,
+                    stage_id: fetch.stage_id as usize,
+                    partition_id: fetch.partition_id as usize,
+                    paths: fetch.paths,
+                    host: fetch.host,
+                    port: fetch.port as u16,
+                })
+            }
             _ => Err(BallistaError::General(
                 "scheduler::from_proto(Action) invalid or missing action".to_owned(),
             )),
@@ -75,14 +91,58 @@ impl Into<PartitionId> for protobuf::PartitionId {
 #[allow(clippy::from_over_into)]
 impl Into<PartitionStats> for protobuf::PartitionStats {
     fn into(self) -> PartitionStats {
-        PartitionStats::new(
+        let stats = PartitionStats::new(
             foo(self.num_rows),
             foo(self.num_batches),
             foo(self.num_bytes),
-        )
+        );
+        if self.column_stats.is_empty() {
+            return stats;
+        }
+        let stats = stats
+            .with_null_counts(self.column_stats.iter().map(|c| c.null_count).collect());
+        if self
+            .column_stats
+            .iter()
+            .any(|c| c.min_value.is_some() || c.max_value.is_some())
+        {
+            stats.with_column_bounds(column_bounds_from_proto(&self.column_stats))
+        } else {
+            stats
+        }
     }
 }
 
+/// The min and max values of the columns of a partition, the values which cannot be decoded
+/// being unknown
+pub fn column_bounds_from_proto(
+    column_stats: &[protobuf::ColumnStats],
+) -> Vec<ColumnBounds> {
+    let decode = |value: Option<&datafusion_proto::protobuf::ScalarValue>| {
+        value.and_then(|value| ScalarValue::try_from(value).ok())
+    };
+    column_stats
+        .iter()
+        .map(|stats| ColumnBounds {
+            min: decode(stats.min_value.as_ref()),
+            max: decode(stats.max_value.as_ref()),
+        })
+        .collect()
+}
+
+/// The bloom filters of the columns of a partition, by column index
+pub fn bloom_filters_from_proto(
+    bloom_filters: &[protobuf::ColumnBloomFilter],
+) -> Vec<(usize, Arc<BloomFilter>)> {
+    bloom_filters
+        .iter()
+        .filter_map(|bloom_filter| {
+            let filter = BloomFilter::from_bytes(bloom_filter.bits.clone())?;
+            Some((bloom_filter.column as usize, Arc::new(filter)))
+        })
+        .collect()
+}
+
 fn foo(n: i64) -> Option<u64> {
     if n < 0 {
         None
@@ -190,13 +250,65 @@ impl TryInto<MetricValue> for protobuf::OperatorMetric {
                 timestamp.set(Utc.timestamp_nanos(value));
                 Ok(MetricValue::EndTimestamp(timestamp))
             }
-            None => Err(BallistaError::General(
-                "scheduler::from_proto(OperatorMetric) metric is None.".to_owned(),
-            )),
+            // a metric of a type unknown to this version, interpreted with its generic form
+            None => generic_metric_value(&self),
         }
     }
 }
 
+/// The value of a metric from its generic name, value, type and aggregation, e.g. for the
+/// metric types added to the schema after this version
+fn generic_metric_value(
+    metric: &protobuf::OperatorMetric,
+) -> Result<MetricValue, BallistaError> {
+    if metric.name.is_empty() {
+        return Err(BallistaError::General(
+            "scheduler::from_proto(OperatorMetric) metric is None.".to_owned(),
+        ));
+    }
+    let metric_type = MetricType::try_from(metric.metric_type).map_err(|_| {
+        BallistaError::General(format!(
+            "scheduler::from_proto(OperatorMetric) unknown type {} of metric {}",
+            metric.metric_type, metric.name
+        ))
+    })?;
+    // only the timestamps may be before the epoch
+    if metric.value < 0 && metric_type != MetricType::Timestamp {
+        return Err(BallistaError::General(format!(
+            "scheduler::from_proto(OperatorMetric) negative value {} of metric {}",
+            metric.value, metric.name
+        )));
+    }
+    let name = metric.name.clone().into();
+    Ok(match metric_type {
+        MetricType::Count => {
+            let count = Count::new();
+            count.add(metric.value as usize);
+            MetricValue::Count { name, count }
+        }
+        MetricType::Gauge => {
+            let gauge = Gauge::new();
+            gauge.add(metric.value as usize);
+            MetricValue::Gauge { name, gauge }
+        }
+        MetricType::Time => {
+            let time = Time::new();
+            time.add_duration(Duration::from_nanos(metric.value as u64));
+            MetricValue::Time { name, time }
+        }
+        MetricType::Timestamp => {
+            let timestamp = Timestamp::new();
+            timestamp.set(Utc.timestamp_nanos(metric.value));
+            // the earliest of the timestamps of the tasks is kept for the start timestamps
+            if metric.aggregation() == MetricAggregation::Min {
+                MetricValue::StartTimestamp(timestamp)
+            } else {
+                MetricValue::EndTimestamp(timestamp)
+            }
+        }
+    })
+}
+
 impl TryInto<MetricsSet> for protobuf::OperatorMetricsSet {
     type Error = BallistaError;
 
@@ -205,11 +317,27 @@ impl TryInto<MetricsSet> for protobuf::OperatorMetricsSet {
         let metrics = self
             .metrics
             .into_iter()
-            .map(|m| m.try_into())
-            .collect::<Result<Vec<_>, BallistaError>>()?;
+            .filter_map(|m| {
+                let labels = m
+                    .labels
+                    .iter()
+                    .map(|label| Label::new(label.name.clone(), label.value.clone()))
+                    .collect::<Vec<_>>();
+                // the metrics which can't be interpreted are skipped rather than failing
+                // the whole set, e.g. if written by a newer version
+                let value: Result<MetricValue, BallistaError> = m.try_into();
+                match value {
+                    Ok(value) => Some((value, labels)),
+                    Err(e) => {
+                        debug!("Skipping operator metric: {e}");
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
 
-        for value in metrics {
-            let new_metric = Arc::new(Metric::new(value, None));
+        for (value, labels) in metrics {
+            let new_metric = Arc::new(Metric::new_with_labels(value, None, labels));
             ms.push(new_metric)
         }
         Ok(ms)
@@ -225,6 +353,26 @@ impl Into<ExecutorMetadata> for protobuf::ExecutorMetadata {
             port: self.port as u16,
             grpc_port: self.grpc_port as u16,
             specification: self.specification.unwrap().into(),
+            zone: self.zone,
+            rack: self.rack,
+            instance_type: self.instance_type,
+            capabilities: self.capabilities.map(Into::into).unwrap_or_default(),
+            // the metadata saved before the negotiation are of version 1 executors
+            protocol_version: self.protocol_version.max(1),
+        }
+    }
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<ExecutorCapabilities> for protobuf::ExecutorCapabilities {
+    fn into(self) -> ExecutorCapabilities {
+        ExecutorCapabilities {
+            object_store_schemes: self.object_store_schemes,
+            shuffle_compression_codecs: self.shuffle_compression_codecs,
+            available_disk: self.available_disk,
+            ballista_version: self.ballista_version,
+            datafusion_version: self.datafusion_version,
+            arrow_version: self.arrow_version,
         }
     }
 }
@@ -307,14 +455,15 @@ pub fn get_task_definition<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
         window_functions: task_window_functions,
     });
 
-    let encoded_plan = task.plan.as_slice();
-    let plan: Arc<dyn ExecutionPlan> = U::try_decode(encoded_plan).and_then(|proto| {
-        proto.try_into_physical_plan(
-            function_registry.as_ref(),
-            runtime.as_ref(),
-            codec.physical_extension_codec(),
-        )
-    })?;
+    let encoded_plan = codec.plan_protection().unprotect(task.plan)?;
+    let plan: Arc<dyn ExecutionPlan> =
+        U::try_decode(&encoded_plan).and_then(|proto| {
+            proto.try_into_physical_plan(
+                function_registry.as_ref(),
+                runtime.as_ref(),
+                codec.physical_extension_codec(),
+            )
+        })?;
 
     let job_id = task.job_id;
     let stage_id = task.stage_id as usize;
@@ -324,6 +473,7 @@ pub fn get_task_definition<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     let launch_time = task.launch_time;
     let task_id = task.task_id as usize;
     let session_id = task.session_id;
+    let launch_token = task.launch_token;
 
     Ok(TaskDefinition {
         task_id,
@@ -337,6 +487,7 @@ pub fn get_task_definition<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
         session_id,
         props,
         function_registry,
+        launch_token,
     })
 }
 
@@ -376,14 +527,15 @@ pub fn get_task_definition_vec<
         window_functions: task_window_functions,
     });
 
-    let encoded_plan = multi_task.plan.as_slice();
-    let plan: Arc<dyn ExecutionPlan> = U::try_decode(encoded_plan).and_then(|proto| {
-        proto.try_into_physical_plan(
-            function_registry.as_ref(),
-            runtime.as_ref(),
-            codec.physical_extension_codec(),
-        )
-    })?;
+    let encoded_plan = codec.plan_protection().unprotect(multi_task.plan)?;
+    let plan: Arc<dyn ExecutionPlan> =
+        U::try_decode(&encoded_plan).and_then(|proto| {
+            proto.try_into_physical_plan(
+                function_registry.as_ref(),
+                runtime.as_ref(),
+                codec.physical_extension_codec(),
+            )
+        })?;
 
     let job_id = multi_task.job_id;
     let stage_id = multi_task.stage_id as usize;
@@ -407,6 +559,7 @@ pub fn get_task_definition_vec<
                 session_id: session_id.clone(),
                 props: props.clone(),
                 function_registry: function_registry.clone(),
+                launch_token: task_id.launch_token.clone(),
             })
         })
         .collect()
@@ -422,3 +575,148 @@ fn reset_metrics_for_execution_plan(
     .data()
     .map_err(BallistaError::DataFusionError)
 }
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+    use std::sync::Arc;
+
+    use chrono::{TimeZone, Utc};
+    use datafusion::physical_plan::metrics::{
+        Count, Label, Metric, MetricValue, MetricsSet,
+    };
+
+    use crate::serde::protobuf::{
+        self, MetricAggregation, MetricType, OperatorMetric, OperatorMetricsSet,
+    };
+    use crate::serde::scheduler::to_proto::operator_metrics_to_proto;
+    use crate::serde::scheduler::OPERATOR_METRICS_VERSION;
+
+    fn count_metrics(name: &'static str, value: usize) -> MetricsSet {
+        let count = Count::new();
+        count.add(value);
+        let mut metrics = MetricsSet::new();
+        metrics.push(Arc::new(Metric::new_with_labels(
+            MetricValue::Count {
+                name: name.into(),
+                count,
+            },
+            None,
+            vec![Label::new("output_partition", "1")],
+        )));
+        metrics
+    }
+
+    /// A metric of a type added after this version, only known by its generic form
+    fn generic_metric(
+        name: &str,
+        value: i64,
+        metric_type: MetricType,
+        aggregation: MetricAggregation,
+    ) -> OperatorMetric {
+        OperatorMetric {
+            metric: None,
+            labels: vec![],
+            name: name.to_owned(),
+            value,
+            metric_type: metric_type as i32,
+            aggregation: aggregation as i32,
+        }
+    }
+
+    #[test]
+    fn test_operator_metrics_round_trip() {
+        let metrics = operator_metrics_to_proto(vec![
+            count_metrics("rows_scanned", 3),
+            count_metrics("bytes_spilled", 5),
+        ])
+        .unwrap();
+        assert_eq!(
+            metrics
+                .iter()
+                .map(|ms| (ms.operator_id, ms.version))
+                .collect::<Vec<_>>(),
+            vec![(0, OPERATOR_METRICS_VERSION), (1, OPERATOR_METRICS_VERSION)]
+        );
+        let metric = &metrics[1].metrics[0];
+        assert_eq!(
+            (metric.name.as_str(), metric.value, metric.metric_type()),
+            ("bytes_spilled", 5, MetricType::Count)
+        );
+
+        let metrics: MetricsSet = metrics[1].clone().try_into().unwrap();
+        assert_eq!(
+            metrics.sum_by_name("bytes_spilled").map(|v| v.as_usize()),
+            Some(5)
+        );
+        let labels = metrics
+            .iter()
+            .flat_map(|metric| metric.labels().to_vec())
+            .map(|label| (label.name().to_owned(), label.value().to_owned()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![("output_partition".to_owned(), "1".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_generic_operator_metric() {
+        let value: MetricValue =
+            generic_metric("rows_pruned", 7, MetricType::Count, MetricAggregation::Sum)
+                .try_into()
+                .unwrap();
+        assert_eq!(value.name(), "rows_pruned");
+        assert_eq!(value.as_usize(), 7);
+
+        // the earliest of the timestamps is the start timestamp
+        let value: MetricValue = generic_metric(
+            "first_batch",
+            1_000,
+            MetricType::Timestamp,
+            MetricAggregation::Min,
+        )
+        .try_into()
+        .unwrap();
+        assert!(matches!(
+            value,
+            MetricValue::StartTimestamp(timestamp)
+                if timestamp.value() == Some(Utc.timestamp_nanos(1_000))
+        ));
+
+        // a negative count is rejected rather than wrapped
+        let value: Result<MetricValue, _> =
+            generic_metric("rows_pruned", -1, MetricType::Count, MetricAggregation::Sum)
+                .try_into();
+        assert!(value.is_err());
+    }
+
+    #[test]
+    fn test_unknown_operator_metric_skipped() {
+        let mut unknown_type =
+            generic_metric("rows_pruned", 7, MetricType::Count, MetricAggregation::Sum);
+        unknown_type.metric_type = 42;
+        let metrics = OperatorMetricsSet {
+            metrics: vec![
+                unknown_type,
+                // without a typed metric nor a name
+                OperatorMetric::default(),
+                generic_metric(
+                    "rows_pruned",
+                    -1,
+                    MetricType::Gauge,
+                    MetricAggregation::Max,
+                ),
+                protobuf::OperatorMetric {
+                    metric: Some(protobuf::operator_metric::Metric::OutputRows(2)),
+                    ..Default::default()
+                },
+            ],
+            operator_id: 0,
+            version: OPERATOR_METRICS_VERSION + 1,
+        };
+        let metrics: MetricsSet = metrics.try_into().unwrap();
+        assert_eq!(metrics.iter().count(), 1);
+        assert_eq!(metrics.output_rows(), Some(2));
+    }
+}
@@ -23,14 +23,18 @@ use datafusion::arrow::array::{
     ArrayBuilder, StructArray, StructBuilder, UInt64Array, UInt64Builder,
 };
 use datafusion::arrow::datatypes::{DataType, Field};
-use datafusion::common::DataFusionError;
+use datafusion::common::{DataFusionError, ScalarValue};
 use datafusion::execution::FunctionRegistry;
 use datafusion::logical_expr::{AggregateUDF, ScalarUDF, WindowUDF};
 use datafusion::physical_plan::ExecutionPlan;
 use datafusion::physical_plan::Partitioning;
+use datafusion::DATAFUSION_VERSION;
 use serde::Serialize;
 
 use crate::error::BallistaError;
+use crate::object_store_registry::BallistaObjectStoreRegistry;
+use crate::protocol::CAPABILITIES_PROTOCOL_VERSION;
+use crate::{ARROW_VERSION, BALLISTA_VERSION};
 
 pub mod from_proto;
 pub mod to_proto;
@@ -47,6 +51,15 @@ pub enum Action {
         host: String,
         port: u16,
     },
+    /// Collect several shuffle partitions of an executor, merged into a single stream
+    FetchPartitions {
+        job_id: String,
+        stage_id: usize,
+        partition_id: usize,
+        paths: Vec<String>,
+        host: String,
+        port: u16,
+    },
 }
 
 /// Unique identifier for the output partition of an operator.
@@ -84,6 +97,150 @@ pub struct ExecutorMetadata {
     pub port: u16,
     pub grpc_port: u16,
     pub specification: ExecutorSpecification,
+    /// Zone the executor runs in, empty if unknown
+    pub zone: String,
+    /// Rack the executor runs in, empty if unknown
+    pub rack: String,
+    /// Instance type of the host of the executor, empty if unknown
+    pub instance_type: String,
+    pub capabilities: ExecutorCapabilities,
+    /// Version of the protocol negotiated with the executor, see [crate::protocol]
+    pub protocol_version: u32,
+}
+
+/// Names of the topology labels of executors
+pub const TOPOLOGY_LABELS: [&str; 3] = ["zone", "rack", "instance_type"];
+
+/// Version of the schema of the operator metrics reported in the task status. Version 1 adds
+/// the generic name, value, type and aggregation of every metric and the operator ID of every
+/// metrics set.
+pub const OPERATOR_METRICS_VERSION: u32 = 1;
+
+impl ExecutorMetadata {
+    /// Whether the executor speaks a version of the protocol advertising its
+    /// capabilities. The executors speaking an older version are assumed to support all
+    /// the features of their protocol, and their capabilities are not checked.
+    pub fn advertises_capabilities(&self) -> bool {
+        self.protocol_version >= CAPABILITIES_PROTOCOL_VERSION
+    }
+
+    /// Check that the executor can run the plans and read the shuffle files of this
+    /// build, see [ExecutorCapabilities::check_compatibility]
+    pub fn check_compatibility(&self) -> Result<(), BallistaError> {
+        if !self.advertises_capabilities() {
+            return Ok(());
+        }
+        self.capabilities.check_compatibility()
+    }
+
+    /// Whether the executor can access the object stores of the given URL scheme
+    pub fn supports_object_store(&self, scheme: &str) -> bool {
+        !self.advertises_capabilities() || self.capabilities.supports_object_store(scheme)
+    }
+
+    /// Get the value of a topology label of the executor, see [`TOPOLOGY_LABELS`].
+    /// Returns `None` for unknown labels and for labels the executor did not register.
+    pub fn topology_label(&self, name: &str) -> Option<&str> {
+        let value = match name {
+            "zone" => &self.zone,
+            "rack" => &self.rack,
+            "instance_type" => &self.instance_type,
+            _ => return None,
+        };
+        (!value.is_empty()).then_some(value.as_str())
+    }
+}
+
+/// Compression codec of the shuffle files written by the executors
+const SHUFFLE_COMPRESSION_CODEC: &str = "lz4_frame";
+
+/// Capabilities advertised by an executor when it registers, empty for executors of
+/// versions which do not advertise them
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExecutorCapabilities {
+    /// Schemes of the URLs of the object stores the executor can access, e.g. s3
+    pub object_store_schemes: Vec<String>,
+    /// Compression codecs of the shuffle files the executor can read and write
+    pub shuffle_compression_codecs: Vec<String>,
+    /// Free space in bytes of the disk holding the work dir of the executor when it started
+    pub available_disk: u64,
+    pub ballista_version: String,
+    pub datafusion_version: String,
+    pub arrow_version: String,
+}
+
+impl ExecutorCapabilities {
+    /// Capabilities of an executor of this build, whose work dir has the given free space
+    pub fn local(available_disk: u64) -> Self {
+        Self {
+            object_store_schemes: BallistaObjectStoreRegistry::supported_schemes()
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+            shuffle_compression_codecs: vec![
+                SHUFFLE_COMPRESSION_CODEC.to_owned(),
+                "zstd".to_owned(),
+            ],
+            available_disk,
+            ballista_version: BALLISTA_VERSION.to_owned(),
+            datafusion_version: DATAFUSION_VERSION.to_owned(),
+            arrow_version: ARROW_VERSION.to_owned(),
+        }
+    }
+
+    /// Check that the executor can run the plans and read the shuffle files of this build,
+    /// i.e. that its versions of Ballista, DataFusion and Arrow are compatible with those of
+    /// this build and that it supports the compression codec of the shuffle files. The
+    /// capabilities the executor does not advertise are not checked.
+    pub fn check_compatibility(&self) -> Result<(), BallistaError> {
+        for (name, version, local) in [
+            ("Ballista", &self.ballista_version, BALLISTA_VERSION),
+            ("DataFusion", &self.datafusion_version, DATAFUSION_VERSION),
+            ("Arrow", &self.arrow_version, ARROW_VERSION),
+        ] {
+            if !version.is_empty()
+                && !local.is_empty()
+                && !compatible_versions(version, local)
+            {
+                return Err(BallistaError::General(format!(
+                    "{name} version {version} of the executor is not compatible with \
+                    version {local}"
+                )));
+            }
+        }
+        if !self.shuffle_compression_codecs.is_empty()
+            && !self
+                .shuffle_compression_codecs
+                .iter()
+                .any(|codec| codec == SHUFFLE_COMPRESSION_CODEC)
+        {
+            return Err(BallistaError::General(format!(
+                "The executor does not support the {SHUFFLE_COMPRESSION_CODEC} compression \
+                of the shuffle files"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the executor can access the object stores of the given URL scheme, which is
+    /// assumed for the executors not advertising their object stores
+    pub fn supports_object_store(&self, scheme: &str) -> bool {
+        self.object_store_schemes.is_empty()
+            || self.object_store_schemes.iter().any(|s| s == scheme)
+    }
+}
+
+/// Whether two semantic versions are compatible, i.e. have the same major version, or the
+/// same minor version for the 0.x versions
+fn compatible_versions(version: &str, other: &str) -> bool {
+    let key = |version: &str| {
+        let mut parts = version.split('.');
+        match (parts.next(), parts.next()) {
+            (Some("0"), minor) => ("0".to_owned(), minor.map(str::to_owned)),
+            (major, _) => (major.unwrap_or_default().to_owned(), None),
+        }
+    };
+    key(version) == key(other)
 }
 
 /// Specification of an executor, indicting executor resources, like total task slots
@@ -106,11 +263,23 @@ pub struct ExecutorDataChange {
 }
 
 /// Summary of executed partition
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Clone, Default)]
 pub struct PartitionStats {
     pub(crate) num_rows: Option<u64>,
     pub(crate) num_batches: Option<u64>,
     pub(crate) num_bytes: Option<u64>,
+    /// Number of null values of each column
+    pub(crate) null_counts: Option<Vec<u64>>,
+    /// Min and max values of each column
+    pub(crate) column_bounds: Option<Vec<ColumnBounds>>,
+}
+
+/// Min and max values of a column of a partition, which are unknown for the columns of
+/// types whose bounds are not collected and for partitions without non null values
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnBounds {
+    pub min: Option<ScalarValue>,
+    pub max: Option<ScalarValue>,
 }
 
 impl fmt::Display for PartitionStats {
@@ -133,10 +302,40 @@ impl PartitionStats {
             num_rows,
             num_batches,
             num_bytes,
+            null_counts: None,
+            column_bounds: None,
         }
     }
 
-    pub fn arrow_struct_repr(self) -> Field {
+    /// Set the number of null values of each column of the partition
+    pub fn with_null_counts(mut self, null_counts: Vec<u64>) -> Self {
+        self.null_counts = Some(null_counts);
+        self
+    }
+
+    /// Set the min and max values of each column of the partition
+    pub fn with_column_bounds(mut self, column_bounds: Vec<ColumnBounds>) -> Self {
+        self.column_bounds = Some(column_bounds);
+        self
+    }
+
+    pub fn num_rows(&self) -> Option<u64> {
+        self.num_rows
+    }
+
+    pub fn num_bytes(&self) -> Option<u64> {
+        self.num_bytes
+    }
+
+    pub fn null_counts(&self) -> Option<&[u64]> {
+        self.null_counts.as_deref()
+    }
+
+    pub fn column_bounds(&self) -> Option<&[ColumnBounds]> {
+        self.column_bounds.as_deref()
+    }
+
+    pub fn arrow_struct_repr(&self) -> Field {
         Field::new(
             "partition_stats",
             DataType::Struct(self.arrow_struct_fields().into()),
@@ -144,7 +343,7 @@ impl PartitionStats {
         )
     }
 
-    pub fn arrow_struct_fields(self) -> Vec<Field> {
+    pub fn arrow_struct_fields(&self) -> Vec<Field> {
         vec![
             Field::new("num_rows", DataType::UInt64, false),
             Field::new("num_batches", DataType::UInt64, false),
@@ -152,7 +351,7 @@ impl PartitionStats {
         ]
     }
 
-    pub fn to_arrow_arrayref(self) -> Result<Arc<StructArray>, BallistaError> {
+    pub fn to_arrow_arrayref(&self) -> Result<Arc<StructArray>, BallistaError> {
         let mut field_builders = Vec::new();
 
         let mut num_rows_builder = UInt64Builder::with_capacity(1);
@@ -205,6 +404,8 @@ impl PartitionStats {
             num_rows: Some(num_rows.value(0).to_owned()),
             num_batches: Some(num_batches.value(0).to_owned()),
             num_bytes: Some(num_bytes.value(0).to_owned()),
+            null_counts: None,
+            column_bounds: None,
         }
     }
 }
@@ -289,6 +490,8 @@ pub struct TaskDefinition {
     pub session_id: String,
     pub props: Arc<HashMap<String, String>>,
     pub function_registry: Arc<SimpleFunctionRegistry>,
+    /// Token identifying the launch of the task attempt, empty if not given by the scheduler
+    pub launch_token: String,
 }
 
 #[derive(Debug)]
@@ -15,21 +15,27 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use datafusion::common::ScalarValue;
 use datafusion::error::DataFusionError;
-use datafusion::physical_plan::metrics::{MetricValue, MetricsSet};
+use datafusion::physical_plan::metrics::{MetricValue, MetricsSet, Timestamp};
 use std::convert::TryInto;
 
 use crate::error::BallistaError;
+use crate::execution_plans::BloomFilter;
 
 use crate::serde::protobuf;
 use datafusion_proto::protobuf as datafusion_protobuf;
 
 use crate::serde::scheduler::{
-    Action, ExecutorData, ExecutorMetadata, ExecutorSpecification, PartitionId,
-    PartitionLocation, PartitionStats,
+    Action, ColumnBounds, ExecutorCapabilities, ExecutorData, ExecutorMetadata,
+    ExecutorSpecification, PartitionId, PartitionLocation, PartitionStats,
+    OPERATOR_METRICS_VERSION,
 };
 use datafusion::physical_plan::Partitioning;
-use protobuf::{action::ActionType, operator_metric, NamedCount, NamedGauge, NamedTime};
+use protobuf::{
+    action::ActionType, operator_metric, MetricAggregation, MetricLabel, MetricType,
+    NamedCount, NamedGauge, NamedTime,
+};
 
 impl TryInto<protobuf::Action> for Action {
     type Error = BallistaError;
@@ -54,6 +60,26 @@ impl TryInto<protobuf::Action> for Action {
                 })),
                 settings: vec![],
             }),
+            Action::FetchPartitions {
+                job_id,
+                stage_id,
+                partition_id,
+                paths,
+                host,
+                port,
+            } => Ok(protobuf::Action {
+                action_type: Some(ActionType::FetchPartitions(
+                    protobuf::FetchPartitions {
+                        job_id,
+                        stage_id: stage_id as u32,
+                        partition_id: partition_id as u32,
+                        paths,
+                        host,
+                        port: port as u32,
+                    },
+                )),
+                settings: vec![],
+            }),
         }
     }
 }
@@ -87,15 +113,57 @@ impl TryInto<protobuf::PartitionLocation> for PartitionLocation {
 impl Into<protobuf::PartitionStats> for PartitionStats {
     fn into(self) -> protobuf::PartitionStats {
         let none_value = -1_i64;
+        let null_counts = self.null_counts.unwrap_or_default();
+        let mut column_stats = self
+            .column_bounds
+            .as_deref()
+            .map(column_bounds_to_proto)
+            .unwrap_or_default();
+        column_stats
+            .resize_with(null_counts.len().max(column_stats.len()), Default::default);
+        for (stats, null_count) in column_stats.iter_mut().zip(null_counts) {
+            stats.null_count = null_count;
+        }
         protobuf::PartitionStats {
             num_rows: self.num_rows.map(|n| n as i64).unwrap_or(none_value),
             num_batches: self.num_batches.map(|n| n as i64).unwrap_or(none_value),
             num_bytes: self.num_bytes.map(|n| n as i64).unwrap_or(none_value),
-            column_stats: vec![],
+            column_stats,
         }
     }
 }
 
+/// The bloom filters of the columns of a partition, by column index
+pub fn bloom_filters_to_proto<'a>(
+    bloom_filters: impl IntoIterator<Item = (usize, &'a BloomFilter)>,
+) -> Vec<protobuf::ColumnBloomFilter> {
+    bloom_filters
+        .into_iter()
+        .map(|(column, bloom_filter)| protobuf::ColumnBloomFilter {
+            column: column as u32,
+            bits: bloom_filter.as_bytes().to_vec(),
+        })
+        .collect()
+}
+
+/// The min and max values of the columns of a partition as column statistics, the values
+/// which cannot be encoded being left unknown
+pub fn column_bounds_to_proto(
+    column_bounds: &[ColumnBounds],
+) -> Vec<protobuf::ColumnStats> {
+    let encode = |value: Option<&ScalarValue>| {
+        value.and_then(|value| datafusion_protobuf::ScalarValue::try_from(value).ok())
+    };
+    column_bounds
+        .iter()
+        .map(|bounds| protobuf::ColumnStats {
+            min_value: encode(bounds.min.as_ref()),
+            max_value: encode(bounds.max.as_ref()),
+            ..Default::default()
+        })
+        .collect()
+}
+
 pub fn hash_partitioning_to_proto(
     output_partitioning: Option<&Partitioning>,
 ) -> Result<Option<datafusion_protobuf::PhysicalHashRepartition>, BallistaError> {
@@ -122,59 +190,91 @@ impl TryInto<protobuf::OperatorMetric> for &MetricValue {
     type Error = BallistaError;
 
     fn try_into(self) -> Result<protobuf::OperatorMetric, Self::Error> {
-        match self {
-            MetricValue::OutputRows(count) => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::OutputRows(count.value() as u64)),
-            }),
-            MetricValue::ElapsedCompute(time) => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::ElapseTime(time.value() as u64)),
-            }),
-            MetricValue::SpillCount(count) => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::SpillCount(count.value() as u64)),
-            }),
-            MetricValue::SpilledBytes(count) => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::SpilledBytes(count.value() as u64)),
-            }),
-            MetricValue::CurrentMemoryUsage(gauge) => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::CurrentMemoryUsage(
-                    gauge.value() as u64
-                )),
-            }),
-            MetricValue::Count { name, count } => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::Count(NamedCount {
+        let timestamp_nanos = |timestamp: &Timestamp| {
+            timestamp
+                .value()
+                .and_then(|m| m.timestamp_nanos_opt())
+                .unwrap_or(0)
+        };
+        let (metric, metric_type, aggregation, value) = match self {
+            MetricValue::OutputRows(count) => (
+                operator_metric::Metric::OutputRows(count.value() as u64),
+                MetricType::Count,
+                MetricAggregation::Sum,
+                count.value() as i64,
+            ),
+            MetricValue::ElapsedCompute(time) => (
+                operator_metric::Metric::ElapseTime(time.value() as u64),
+                MetricType::Time,
+                MetricAggregation::Sum,
+                time.value() as i64,
+            ),
+            MetricValue::SpillCount(count) => (
+                operator_metric::Metric::SpillCount(count.value() as u64),
+                MetricType::Count,
+                MetricAggregation::Sum,
+                count.value() as i64,
+            ),
+            MetricValue::SpilledBytes(count) => (
+                operator_metric::Metric::SpilledBytes(count.value() as u64),
+                MetricType::Count,
+                MetricAggregation::Sum,
+                count.value() as i64,
+            ),
+            MetricValue::CurrentMemoryUsage(gauge) => (
+                operator_metric::Metric::CurrentMemoryUsage(gauge.value() as u64),
+                MetricType::Gauge,
+                MetricAggregation::Sum,
+                gauge.value() as i64,
+            ),
+            MetricValue::Count { name, count } => (
+                operator_metric::Metric::Count(NamedCount {
                     name: name.to_string(),
                     value: count.value() as u64,
-                })),
-            }),
-            MetricValue::Gauge { name, gauge } => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::Gauge(NamedGauge {
+                }),
+                MetricType::Count,
+                MetricAggregation::Sum,
+                count.value() as i64,
+            ),
+            MetricValue::Gauge { name, gauge } => (
+                operator_metric::Metric::Gauge(NamedGauge {
                     name: name.to_string(),
                     value: gauge.value() as u64,
-                })),
-            }),
-            MetricValue::Time { name, time } => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::Time(NamedTime {
+                }),
+                MetricType::Gauge,
+                MetricAggregation::Sum,
+                gauge.value() as i64,
+            ),
+            MetricValue::Time { name, time } => (
+                operator_metric::Metric::Time(NamedTime {
                     name: name.to_string(),
                     value: time.value() as u64,
-                })),
-            }),
-            MetricValue::StartTimestamp(timestamp) => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::StartTimestamp(
-                    timestamp
-                        .value()
-                        .and_then(|m| m.timestamp_nanos_opt())
-                        .unwrap_or(0),
-                )),
-            }),
-            MetricValue::EndTimestamp(timestamp) => Ok(protobuf::OperatorMetric {
-                metric: Some(operator_metric::Metric::EndTimestamp(
-                    timestamp
-                        .value()
-                        .and_then(|m| m.timestamp_nanos_opt())
-                        .unwrap_or(0),
-                )),
-            }),
-        }
+                }),
+                MetricType::Time,
+                MetricAggregation::Sum,
+                time.value() as i64,
+            ),
+            MetricValue::StartTimestamp(timestamp) => (
+                operator_metric::Metric::StartTimestamp(timestamp_nanos(timestamp)),
+                MetricType::Timestamp,
+                MetricAggregation::Min,
+                timestamp_nanos(timestamp),
+            ),
+            MetricValue::EndTimestamp(timestamp) => (
+                operator_metric::Metric::EndTimestamp(timestamp_nanos(timestamp)),
+                MetricType::Timestamp,
+                MetricAggregation::Max,
+                timestamp_nanos(timestamp),
+            ),
+        };
+        Ok(protobuf::OperatorMetric {
+            labels: vec![],
+            name: self.name().to_string(),
+            value,
+            metric_type: metric_type as i32,
+            aggregation: aggregation as i32,
+            metric: Some(metric),
+        })
     }
 }
 
@@ -184,12 +284,43 @@ impl TryInto<protobuf::OperatorMetricsSet> for MetricsSet {
     fn try_into(self) -> Result<protobuf::OperatorMetricsSet, Self::Error> {
         let metrics = self
             .iter()
-            .map(|m| m.value().try_into())
+            .map(|m| {
+                let mut metric: protobuf::OperatorMetric = m.value().try_into()?;
+                metric.labels = m
+                    .labels()
+                    .iter()
+                    .map(|label| MetricLabel {
+                        name: label.name().to_string(),
+                        value: label.value().to_string(),
+                    })
+                    .collect();
+                Ok(metric)
+            })
             .collect::<Result<Vec<_>, BallistaError>>()?;
-        Ok(protobuf::OperatorMetricsSet { metrics })
+        Ok(protobuf::OperatorMetricsSet {
+            metrics,
+            operator_id: 0,
+            version: OPERATOR_METRICS_VERSION,
+        })
     }
 }
 
+/// Serialize the metrics of the operators of a stage plan which have metrics, depth first,
+/// identifying every operator by its position
+pub fn operator_metrics_to_proto(
+    metrics: Vec<MetricsSet>,
+) -> Result<Vec<protobuf::OperatorMetricsSet>, BallistaError> {
+    metrics
+        .into_iter()
+        .enumerate()
+        .map(|(operator_id, metrics)| {
+            let mut metrics: protobuf::OperatorMetricsSet = metrics.try_into()?;
+            metrics.operator_id = operator_id as u32;
+            Ok(metrics)
+        })
+        .collect()
+}
+
 #[allow(clippy::from_over_into)]
 impl Into<protobuf::ExecutorMetadata> for ExecutorMetadata {
     fn into(self) -> protobuf::ExecutorMetadata {
@@ -199,6 +330,25 @@ impl Into<protobuf::ExecutorMetadata> for ExecutorMetadata {
             port: self.port as u32,
             grpc_port: self.grpc_port as u32,
             specification: Some(self.specification.into()),
+            zone: self.zone,
+            rack: self.rack,
+            instance_type: self.instance_type,
+            capabilities: Some(self.capabilities.into()),
+            protocol_version: self.protocol_version,
+        }
+    }
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<protobuf::ExecutorCapabilities> for ExecutorCapabilities {
+    fn into(self) -> protobuf::ExecutorCapabilities {
+        protobuf::ExecutorCapabilities {
+            object_store_schemes: self.object_store_schemes,
+            shuffle_compression_codecs: self.shuffle_compression_codecs,
+            available_disk: self.available_disk,
+            ballista_version: self.ballista_version,
+            datafusion_version: self.datafusion_version,
+            arrow_version: self.arrow_version,
         }
     }
 }
@@ -0,0 +1,98 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Temporary tables created with `CREATE TEMPORARY TABLE ... AS SELECT`, whose data is
+//! kept on the executors for the lifetime of the session
+
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::common::{plan_err, TableReference};
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::Expr;
+use datafusion::physical_plan::ExecutionPlan;
+use std::any::Any;
+use std::sync::Arc;
+
+/// Reference to a temporary table of the session.
+///
+/// Clients register it to be able to plan queries over the table, and it is serialized by
+/// name only. When the scheduler plans the query, the reference resolves to the table the
+/// scheduler registered in the session, which reads the data from the executors.
+#[derive(Debug)]
+pub struct TemporaryTable {
+    name: String,
+    schema: SchemaRef,
+}
+
+impl TemporaryTable {
+    pub fn new(name: impl Into<String>, schema: SchemaRef) -> Self {
+        Self {
+            name: name.into(),
+            schema,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[async_trait]
+impl TableProvider for TemporaryTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Temporary
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let options = &state.config_options().catalog;
+        let table_ref = TableReference::from(self.name.as_str())
+            .resolve(&options.default_catalog, &options.default_schema);
+        let table = match state
+            .catalog_list()
+            .catalog(&table_ref.catalog)
+            .and_then(|catalog| catalog.schema(&table_ref.schema))
+        {
+            Some(schema) => schema.table(&table_ref.table).await?,
+            None => None,
+        };
+
+        match table {
+            Some(table) if table.as_any().is::<TemporaryTable>() => plan_err!(
+                "Temporary table {} can only be read by the Ballista scheduler",
+                self.name
+            ),
+            Some(table) => table.scan(state, projection, filters, limit).await,
+            None => plan_err!("Temporary table {} not found in the session", self.name),
+        }
+    }
+}
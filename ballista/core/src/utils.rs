@@ -15,27 +15,40 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::config::BallistaConfig;
+use crate::admin_statement::AdminStatementNode;
+use crate::config::{BallistaConfig, BALLISTA_MEMORY_TABLE_MAX_SIZE};
 use crate::error::{BallistaError, Result};
 use crate::execution_plans::{
     DistributedQueryExec, ShuffleWriterExec, UnresolvedShuffleExec,
 };
+use crate::inline_table::InlineTable;
+use crate::materialized_cte::MaterializeCtes;
 use crate::object_store_registry::with_object_store_registry;
+use crate::serde::protobuf::KeyValuePair;
 use crate::serde::scheduler::PartitionStats;
+use crate::serde::{BallistaLogicalExtensionCodec, BallistaPhysicalExtensionCodec};
 
 use async_trait::async_trait;
 use datafusion::arrow::datatypes::Schema;
+use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::ipc::writer::IpcWriteOptions;
 use datafusion::arrow::ipc::writer::StreamWriter;
 use datafusion::arrow::ipc::CompressionType;
 use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::common::config::ConfigOptions;
+use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
 use datafusion::datasource::physical_plan::{CsvExec, ParquetExec};
+use datafusion::datasource::{
+    provider_as_source, source_as_provider, MemTable, TableProvider,
+};
 use datafusion::error::DataFusionError;
 use datafusion::execution::context::{
     QueryPlanner, SessionConfig, SessionContext, SessionState,
 };
 use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
-use datafusion::logical_expr::{DdlStatement, LogicalPlan};
+use datafusion::logical_expr::{
+    CreateMemoryTable, DdlStatement, Extension, LogicalPlan, SetVariable, Statement,
+};
 use datafusion::physical_plan::aggregates::AggregateExec;
 use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
 use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
@@ -45,12 +58,13 @@ use datafusion::physical_plan::joins::HashJoinExec;
 use datafusion::physical_plan::metrics::MetricsSet;
 use datafusion::physical_plan::projection::ProjectionExec;
 use datafusion::physical_plan::sorts::sort::SortExec;
-use datafusion::physical_plan::{metrics, ExecutionPlan, RecordBatchStream};
-use datafusion_proto::logical_plan::{
-    AsLogicalPlan, DefaultLogicalExtensionCodec, LogicalExtensionCodec,
-};
+use datafusion::physical_plan::{collect, metrics, ExecutionPlan, RecordBatchStream};
+use datafusion_proto::logical_plan::{AsLogicalPlan, LogicalExtensionCodec};
+use datafusion_proto::physical_plan::PhysicalExtensionCodec;
 use futures::StreamExt;
 use log::error;
+use rand::Rng;
+use std::collections::HashMap;
 use std::io::{BufWriter, Write};
 use std::marker::PhantomData;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -85,6 +99,7 @@ pub async fn write_stream_to_disk(
     let mut num_rows = 0;
     let mut num_batches = 0;
     let mut num_bytes = 0;
+    let mut null_counts = vec![0; stream.schema().fields().len()];
 
     let options = IpcWriteOptions::default()
         .try_with_compression(Some(CompressionType::LZ4_FRAME))?;
@@ -95,10 +110,13 @@ pub async fn write_stream_to_disk(
     while let Some(result) = stream.next().await {
         let batch = result?;
 
-        let batch_size_bytes: usize = batch.get_array_memory_size();
+        let batch_size_bytes = batch_memory_size(&batch)?;
         num_batches += 1;
         num_rows += batch.num_rows();
         num_bytes += batch_size_bytes;
+        for (null_count, column) in null_counts.iter_mut().zip(batch.columns()) {
+            *null_count += column.null_count() as u64;
+        }
 
         let timer = disk_write_metric.timer();
         writer.write(&batch)?;
@@ -111,7 +129,19 @@ pub async fn write_stream_to_disk(
         Some(num_rows as u64),
         Some(num_batches),
         Some(num_bytes as u64),
-    ))
+    )
+    .with_null_counts(null_counts))
+}
+
+/// Memory held by a batch, only counting the parts of the buffers it uses, so that the
+/// slices of a batch are not counted as the whole batch. The bytes of the results of the
+/// jobs are measured so both by the scheduler and by the clients.
+pub fn batch_memory_size(batch: &RecordBatch) -> std::result::Result<usize, ArrowError> {
+    let mut size = 0;
+    for column in batch.columns() {
+        size += column.to_data().get_slice_memory_size()?;
+    }
+    Ok(size)
 }
 
 pub async fn collect_stream(
@@ -252,6 +282,7 @@ pub fn create_df_ctx_with_ballista_query_planner<T: 'static + AsLogicalPlan>(
     let session_config = SessionConfig::new()
         .with_target_partitions(config.default_shuffle_partitions())
         .with_information_schema(true);
+    let session_config = config.apply_datafusion_settings(session_config);
     let mut session_state = SessionState::new_with_config_rt(
         session_config,
         Arc::new(
@@ -260,6 +291,9 @@ pub fn create_df_ctx_with_ballista_query_planner<T: 'static + AsLogicalPlan>(
         ),
     )
     .with_query_planner(planner);
+    if config.cte_materialize() {
+        session_state = session_state.add_analyzer_rule(Arc::new(MaterializeCtes::new()));
+    }
     session_state = session_state.with_session_id(session_id);
     // the SessionContext created here is the client side context, but the session_id is from server side.
     SessionContext::new_with_state(session_state)
@@ -269,6 +303,7 @@ pub struct BallistaQueryPlanner<T: AsLogicalPlan> {
     scheduler_url: String,
     config: BallistaConfig,
     extension_codec: Arc<dyn LogicalExtensionCodec>,
+    physical_extension_codec: Arc<dyn PhysicalExtensionCodec>,
     plan_repr: PhantomData<T>,
 }
 
@@ -277,7 +312,8 @@ impl<T: 'static + AsLogicalPlan> BallistaQueryPlanner<T> {
         Self {
             scheduler_url,
             config,
-            extension_codec: Arc::new(DefaultLogicalExtensionCodec {}),
+            extension_codec: Arc::new(BallistaLogicalExtensionCodec {}),
+            physical_extension_codec: Arc::new(BallistaPhysicalExtensionCodec {}),
             plan_repr: PhantomData,
         }
     }
@@ -291,6 +327,7 @@ impl<T: 'static + AsLogicalPlan> BallistaQueryPlanner<T> {
             scheduler_url,
             config,
             extension_codec,
+            physical_extension_codec: Arc::new(BallistaPhysicalExtensionCodec {}),
             plan_repr: PhantomData,
         }
     }
@@ -305,9 +342,20 @@ impl<T: 'static + AsLogicalPlan> BallistaQueryPlanner<T> {
             scheduler_url,
             config,
             extension_codec,
+            physical_extension_codec: Arc::new(BallistaPhysicalExtensionCodec {}),
             plan_repr,
         }
     }
+
+    /// Codec for the extensions of the final stages which the scheduler detaches for the
+    /// client to run, see `ballista.client.collect_stage`
+    pub fn with_physical_extension_codec(
+        mut self,
+        physical_extension_codec: Arc<dyn PhysicalExtensionCodec>,
+    ) -> Self {
+        self.physical_extension_codec = physical_extension_codec;
+        self
+    }
 }
 
 #[async_trait]
@@ -322,18 +370,160 @@ impl<T: 'static + AsLogicalPlan> QueryPlanner for BallistaQueryPlanner<T> {
                 // table state is managed locally in the BallistaContext, not in the scheduler
                 Ok(Arc::new(EmptyExec::new(Arc::new(Schema::empty()))))
             }
-            _ => Ok(Arc::new(DistributedQueryExec::with_repr(
-                self.scheduler_url.clone(),
-                self.config.clone(),
-                logical_plan.clone(),
-                self.extension_codec.clone(),
-                self.plan_repr,
-                session_state.session_id().to_string(),
-            ))),
+            LogicalPlan::Ddl(DdlStatement::CreateMemoryTable(CreateMemoryTable {
+                name,
+                input,
+                or_replace,
+                ..
+            })) => {
+                // only `CREATE TEMPORARY TABLE ... AS SELECT` reaches the planner, its
+                // output is kept on the executors as a temporary table of the session
+                let input = inline_memory_tables(
+                    input,
+                    session_state,
+                    self.config.memory_table_max_size(),
+                )
+                .await?;
+                Ok(Arc::new(
+                    DistributedQueryExec::with_repr(
+                        self.scheduler_url.clone(),
+                        self.config.clone(),
+                        input,
+                        self.extension_codec.clone(),
+                        self.plan_repr,
+                        session_state.session_id().to_string(),
+                    )
+                    .with_temporary_table(name.to_string(), *or_replace),
+                ))
+            }
+            LogicalPlan::Extension(Extension { node })
+                if node.as_any().is::<AdminStatementNode>() =>
+            {
+                // administrative statements are answered by the scheduler from its own state
+                let statement = node
+                    .as_any()
+                    .downcast_ref::<AdminStatementNode>()
+                    .unwrap()
+                    .statement();
+                Ok(Arc::new(
+                    DistributedQueryExec::with_repr(
+                        self.scheduler_url.clone(),
+                        self.config.clone(),
+                        logical_plan.clone(),
+                        self.extension_codec.clone(),
+                        self.plan_repr,
+                        session_state.session_id().to_string(),
+                    )
+                    .with_sql(statement.to_string()),
+                ))
+            }
+            LogicalPlan::Statement(Statement::SetVariable(SetVariable {
+                variable,
+                value,
+                ..
+            })) => {
+                // the settings of the session are changed by the scheduler
+                let sql = format!("SET {variable} = '{}'", value.replace('\'', "''"));
+                Ok(Arc::new(
+                    DistributedQueryExec::with_repr(
+                        self.scheduler_url.clone(),
+                        self.config.clone(),
+                        logical_plan.clone(),
+                        self.extension_codec.clone(),
+                        self.plan_repr,
+                        session_state.session_id().to_string(),
+                    )
+                    .with_sql(sql),
+                ))
+            }
+            _ => {
+                let plan = inline_memory_tables(
+                    logical_plan,
+                    session_state,
+                    self.config.memory_table_max_size(),
+                )
+                .await?;
+                Ok(Arc::new(
+                    DistributedQueryExec::with_repr(
+                        self.scheduler_url.clone(),
+                        self.config.clone(),
+                        plan,
+                        self.extension_codec.clone(),
+                        self.plan_repr,
+                        session_state.session_id().to_string(),
+                    )
+                    .with_physical_extension_codec(self.physical_extension_codec.clone()),
+                ))
+            }
         }
     }
 }
 
+/// Replace the in-memory tables of the plan, which the cluster cannot read, with
+/// [`InlineTable`]s holding their record batches, so that the data is sent along with
+/// the plan. Fails if the data exceeds `max_size` bytes.
+///
+/// The tables are told apart by their provider rather than their name, as scans of
+/// different tables can share the same name, e.g. the `?table?` of the scans of
+/// [`DataFrame`](datafusion::dataframe::DataFrame)s read from record batches.
+async fn inline_memory_tables(
+    plan: &LogicalPlan,
+    session_state: &SessionState,
+    max_size: usize,
+) -> std::result::Result<LogicalPlan, DataFusionError> {
+    let mut memory_tables = HashMap::new();
+    plan.apply(&mut |plan| {
+        if let LogicalPlan::TableScan(scan) = plan {
+            let provider = source_as_provider(&scan.source)?;
+            if provider.as_any().is::<MemTable>() {
+                memory_tables
+                    .entry(provider_key(&provider))
+                    .or_insert_with(|| (scan.table_name.clone(), provider));
+            }
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    if memory_tables.is_empty() {
+        return Ok(plan.clone());
+    }
+
+    let mut size = 0;
+    let mut inline_tables = HashMap::new();
+    for (key, (name, provider)) in memory_tables {
+        let exec = provider.scan(session_state, None, &[], None).await?;
+        let batches = collect(exec, session_state.task_ctx()).await?;
+        let table = InlineTable::new(provider.schema(), batches);
+        size += table.size();
+        if size > max_size {
+            return Err(DataFusionError::Plan(format!(
+                "In-memory tables of more than {max_size} bytes cannot be sent to the cluster, \
+                table {name} exceeds the limit set by {BALLISTA_MEMORY_TABLE_MAX_SIZE}"
+            )));
+        }
+        inline_tables.insert(key, provider_as_source(Arc::new(table)));
+    }
+
+    let plan = plan.clone().transform_up(&|plan| match plan {
+        LogicalPlan::TableScan(scan) => {
+            let key = provider_key(&source_as_provider(&scan.source)?);
+            match inline_tables.get(&key) {
+                Some(source) => Ok(Transformed::yes(LogicalPlan::TableScan(TableScan {
+                    source: source.clone(),
+                    ..scan
+                }))),
+                None => Ok(Transformed::no(LogicalPlan::TableScan(scan))),
+            }
+        }
+        plan => Ok(Transformed::no(plan)),
+    })?;
+    Ok(plan.data)
+}
+
+/// Identifies a table provider by the address of its data
+fn provider_key(provider: &Arc<dyn TableProvider>) -> usize {
+    Arc::as_ptr(provider) as *const () as usize
+}
+
 pub async fn create_grpc_client_connection<D>(
     dst: D,
 ) -> std::result::Result<Channel, Error>
@@ -386,3 +576,130 @@ pub fn get_time_before(interval_seconds: u64) -> u64 {
         .unwrap_or_else(|| Duration::from_secs(0))
         .as_secs()
 }
+
+/// Collect the DataFusion options of the session config which differ from the defaults, such
+/// as the time zone, batch size and parquet options of a client session, to send them along
+/// with queries and tasks
+pub fn session_config_props(session_config: &SessionConfig) -> Vec<KeyValuePair> {
+    let defaults: HashMap<String, Option<String>> = ConfigOptions::default()
+        .entries()
+        .into_iter()
+        .map(|entry| (entry.key, entry.value))
+        .collect();
+    session_config
+        .options()
+        .entries()
+        .into_iter()
+        .filter_map(|entry| match (entry.value, defaults.get(&entry.key)) {
+            (Some(value), Some(Some(default))) if &value == default => None,
+            (Some(value), _) => Some(KeyValuePair {
+                key: entry.key,
+                value,
+            }),
+            (None, _) => None,
+        })
+        .collect()
+}
+
+/// Delay before the retry following `attempt` failed attempts, starting from 0. The delay
+/// doubles with every attempt from `initial_backoff` up to `max_backoff`, and is randomly
+/// picked in its upper half so that retrying clients don't hit a server at the same time.
+pub fn backoff_with_jitter(
+    attempt: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+) -> Duration {
+    let backoff = initial_backoff
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(max_backoff);
+    backoff / 2 + (backoff / 2).mul_f64(rand::thread_rng().gen::<f64>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_with_jitter() {
+        let initial = Duration::from_millis(100);
+        let max = Duration::from_secs(1);
+        for attempt in 0..64 {
+            let backoff = backoff_with_jitter(attempt, initial, max);
+            let expected = (initial * 2u32.saturating_pow(attempt.min(16))).min(max);
+            assert!(backoff >= expected / 2, "{backoff:?} < {expected:?} / 2");
+            assert!(backoff <= expected, "{backoff:?} > {expected:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inline_memory_tables_with_same_name() -> Result<()> {
+        use datafusion::arrow::array::Int32Array;
+        use datafusion::arrow::datatypes::{DataType, Field};
+        use datafusion::logical_expr::LogicalPlanBuilder;
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let table = |values: Vec<i32>| -> Result<Arc<dyn TableProvider>> {
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from(values))],
+            )?;
+            Ok(Arc::new(MemTable::try_new(
+                schema.clone(),
+                vec![vec![batch]],
+            )?))
+        };
+        let left = provider_as_source(table(vec![1, 2])?);
+        let right = provider_as_source(table(vec![3])?);
+        let plan = LogicalPlanBuilder::scan("?table?", left, None)?
+            .union(LogicalPlanBuilder::scan("?table?", right, None)?.build()?)?
+            .build()?;
+
+        let session_state = default_session_builder(SessionConfig::new());
+        let plan = inline_memory_tables(&plan, &session_state, usize::MAX).await?;
+        let mut num_rows = vec![];
+        plan.apply(&mut |plan| {
+            if let LogicalPlan::TableScan(scan) = plan {
+                let provider = source_as_provider(&scan.source)?;
+                let table = provider.as_any().downcast_ref::<InlineTable>().unwrap();
+                num_rows.push(table.batches().iter().map(|b| b.num_rows()).sum());
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+        assert_eq!(num_rows, vec![2usize, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_config_props() {
+        let mut session_config = SessionConfig::new().with_batch_size(1024);
+        session_config.options_mut().execution.time_zone = Some("+08:00".to_string());
+        session_config
+            .options_mut()
+            .execution
+            .parquet
+            .pushdown_filters = true;
+
+        let mut props = session_config_props(&session_config)
+            .into_iter()
+            .map(|kv| (kv.key, kv.value))
+            .collect::<Vec<_>>();
+        props.sort();
+        assert_eq!(
+            props,
+            vec![
+                (
+                    "datafusion.execution.batch_size".to_string(),
+                    "1024".to_string()
+                ),
+                (
+                    "datafusion.execution.parquet.pushdown_filters".to_string(),
+                    "true".to_string()
+                ),
+                (
+                    "datafusion.execution.time_zone".to_string(),
+                    "+08:00".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,454 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Scalar UDFs implemented by user-provided WebAssembly modules, which are sent along with
+//! the plans to the scheduler and the executors, and run in a sandbox with limited memory
+//! and instructions. The modules may not import anything, so that the UDFs cannot reach
+//! the files, the network or the memory of the process running them.
+//!
+//! The module of a UDF exports a function named after the UDF, taking one WebAssembly value
+//! per argument and returning one value, with `i32`, `i64`, `f32` and `f64` standing for
+//! the `Int32`, `Int64`, `Float32` and `Float64` Arrow types. The function is called once
+//! per row, the rows with a null argument being null.
+
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use ballista_cache::backend::policy::lru::lru_cache::LruCache;
+use ballista_cache::backend::policy::lru::DefaultResourceCounter;
+use ballista_cache::backend::policy::CachePolicy;
+use datafusion::arrow::array::{
+    Array, ArrayRef, AsArray, Float32Array, Float64Array, Int32Array, Int64Array,
+};
+use datafusion::arrow::datatypes::{
+    DataType, Float32Type, Float64Type, Int32Type, Int64Type,
+};
+use datafusion::common::DataFusionError;
+use datafusion::error::Result;
+use datafusion::logical_expr::{
+    ColumnarValue, ScalarUDF, ScalarUDFImpl, Signature, Volatility,
+};
+use datafusion_proto::protobuf::ArrowType;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use ring::digest;
+use wasmtime::{
+    Config, Engine, ExternType, Instance, Module, Store, StoreLimits, StoreLimitsBuilder,
+    Val, ValType,
+};
+
+use crate::serde::protobuf;
+
+static ENGINE: OnceCell<Engine> = OnceCell::new();
+
+/// Number of the most recently used compiled modules kept in [`MODULES`]
+const MAX_CACHED_MODULES: usize = 64;
+
+/// The most recently used compiled modules, by SHA-256 digest of their bytes, so that a
+/// module is compiled once per process rather than for every plan using it
+static MODULES: OnceCell<Mutex<LruCache<Vec<u8>, Module>>> = OnceCell::new();
+
+static LIMITS: OnceCell<WasmUdfLimits> = OnceCell::new();
+
+/// Limits of the resources used by a WASM UDF to process a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmUdfLimits {
+    /// The maximum size in bytes of the linear memory of the module
+    pub max_memory_bytes: usize,
+    /// The fuel of the module, roughly the number of WebAssembly instructions it may run
+    pub fuel: u64,
+}
+
+impl Default for WasmUdfLimits {
+    fn default() -> Self {
+        Self {
+            max_memory_bytes: 64 * 1024 * 1024,
+            fuel: 1_000_000_000,
+        }
+    }
+}
+
+/// Set the limits of the WASM UDFs decoded by this process. It must be called before the
+/// first WASM UDF is decoded to take effect.
+pub fn set_wasm_udf_limits(limits: WasmUdfLimits) -> Result<()> {
+    LIMITS.set(limits).map_err(|_| {
+        DataFusionError::Internal("The WASM UDF limits are already set".to_owned())
+    })
+}
+
+fn engine() -> Result<&'static Engine> {
+    ENGINE.get_or_try_init(|| {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        Engine::new(&config).map_err(|e| {
+            DataFusionError::Internal(format!("Failed to create WASM engine: {e}"))
+        })
+    })
+}
+
+fn compile(bytes: &[u8]) -> Result<Module> {
+    let key = digest::digest(&digest::SHA256, bytes).as_ref().to_vec();
+    let modules = MODULES.get_or_init(|| {
+        Mutex::new(LruCache::with_resource_counter(
+            DefaultResourceCounter::new(MAX_CACHED_MODULES),
+        ))
+    });
+    if let Some(module) = modules.lock().get(&key) {
+        return Ok(module);
+    }
+    let module = Module::new(engine()?, bytes)
+        .map_err(|e| DataFusionError::Plan(format!("Invalid WASM UDF module: {e}")))?;
+    modules.lock().put(key, module.clone());
+    Ok(module)
+}
+
+fn val_type_matches(val_type: &ValType, data_type: &DataType) -> bool {
+    matches!(
+        (val_type, data_type),
+        (ValType::I32, DataType::Int32)
+            | (ValType::I64, DataType::Int64)
+            | (ValType::F32, DataType::Float32)
+            | (ValType::F64, DataType::Float64)
+    )
+}
+
+/// A scalar UDF calling a function of a WebAssembly module, see the [module](self)
+/// documentation for the ABI of the function
+pub struct WasmUdf {
+    name: String,
+    bytes: Vec<u8>,
+    module: Module,
+    signature: Signature,
+    arg_types: Vec<DataType>,
+    return_type: DataType,
+    limits: WasmUdfLimits,
+}
+
+impl Debug for WasmUdf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmUdf")
+            .field("name", &self.name)
+            .field("arg_types", &self.arg_types)
+            .field("return_type", &self.return_type)
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+impl WasmUdf {
+    /// Create the UDF `name` from the bytes of a WebAssembly module, in binary or text
+    /// format, exporting a function `name` of the given argument and return types
+    pub fn try_new(
+        name: impl Into<String>,
+        bytes: Vec<u8>,
+        arg_types: Vec<DataType>,
+        return_type: DataType,
+    ) -> Result<Self> {
+        let name = name.into();
+        let module = compile(&bytes)?;
+        if module.imports().next().is_some() {
+            return Err(DataFusionError::Plan(format!(
+                "The module of WASM UDF {name} may not import anything"
+            )));
+        }
+        let Some(ExternType::Func(func_type)) = module.get_export(&name) else {
+            return Err(DataFusionError::Plan(format!(
+                "The module of WASM UDF {name} does not export a function {name}"
+            )));
+        };
+        let params: Vec<ValType> = func_type.params().collect();
+        let results: Vec<ValType> = func_type.results().collect();
+        let valid = params.len() == arg_types.len()
+            && params
+                .iter()
+                .zip(&arg_types)
+                .all(|(param, arg_type)| val_type_matches(param, arg_type))
+            && results.len() == 1
+            && val_type_matches(&results[0], &return_type);
+        if !valid {
+            return Err(DataFusionError::Plan(format!(
+                "The function of WASM UDF {name} does not take {arg_types:?} and return \
+                {return_type}, the supported types are Int32, Int64, Float32 and Float64"
+            )));
+        }
+        Ok(Self {
+            signature: Signature::exact(arg_types.clone(), Volatility::Immutable),
+            name,
+            bytes,
+            module,
+            arg_types,
+            return_type,
+            limits: LIMITS.get().copied().unwrap_or_default(),
+        })
+    }
+
+    /// Override the limits of the resources used by the UDF to process a batch
+    pub fn with_limits(mut self, limits: WasmUdfLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Convert to a [ScalarUDF] to register in a session
+    pub fn into_udf(self) -> ScalarUDF {
+        ScalarUDF::new_from_impl(self)
+    }
+
+    fn call(&self, args: &[ArrayRef], num_rows: usize) -> Result<ArrayRef> {
+        let error = |e: wasmtime::Error| {
+            DataFusionError::Execution(format!("WASM UDF {} failed: {e}", self.name))
+        };
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.max_memory_bytes)
+            .instances(1)
+            .build();
+        let mut store = Store::new(engine()?, limits);
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store.set_fuel(self.limits.fuel).map_err(error)?;
+        let instance = Instance::new(&mut store, &self.module, &[]).map_err(error)?;
+        let func = instance.get_func(&mut store, &self.name).ok_or_else(|| {
+            DataFusionError::Execution(format!(
+                "The module of WASM UDF {} does not export a function {}",
+                self.name, self.name
+            ))
+        })?;
+
+        let mut params = Vec::with_capacity(args.len());
+        let mut result = [Val::I32(0)];
+        let mut values = Vec::with_capacity(num_rows);
+        for row in 0..num_rows {
+            params.clear();
+            params.extend(args.iter().map_while(|arg| value_at(arg, row)));
+            if params.len() < args.len() {
+                values.push(None);
+                continue;
+            }
+            func.call(&mut store, &params, &mut result).map_err(error)?;
+            values.push(Some(result[0].clone()));
+        }
+        Ok(to_array(&self.return_type, &values))
+    }
+}
+
+impl ScalarUDFImpl for WasmUdf {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn return_type(&self, _arg_types: &[DataType]) -> Result<DataType> {
+        Ok(self.return_type.clone())
+    }
+
+    fn invoke(&self, args: &[ColumnarValue]) -> Result<ColumnarValue> {
+        let num_rows = args
+            .iter()
+            .find_map(|arg| match arg {
+                ColumnarValue::Array(array) => Some(array.len()),
+                ColumnarValue::Scalar(_) => None,
+            })
+            .unwrap_or(1);
+        let arrays = args
+            .iter()
+            .map(|arg| arg.clone().into_array(num_rows))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ColumnarValue::Array(self.call(&arrays, num_rows)?))
+    }
+}
+
+fn value_at(array: &ArrayRef, row: usize) -> Option<Val> {
+    if array.is_null(row) {
+        return None;
+    }
+    match array.data_type() {
+        DataType::Int32 => Some(Val::I32(array.as_primitive::<Int32Type>().value(row))),
+        DataType::Int64 => Some(Val::I64(array.as_primitive::<Int64Type>().value(row))),
+        DataType::Float32 => Some(Val::F32(
+            array.as_primitive::<Float32Type>().value(row).to_bits(),
+        )),
+        DataType::Float64 => Some(Val::F64(
+            array.as_primitive::<Float64Type>().value(row).to_bits(),
+        )),
+        _ => None,
+    }
+}
+
+fn to_array(data_type: &DataType, values: &[Option<Val>]) -> ArrayRef {
+    let values = values.iter().map(Option::as_ref);
+    match data_type {
+        DataType::Int32 => Arc::new(
+            values
+                .map(|value| value.and_then(Val::i32))
+                .collect::<Int32Array>(),
+        ),
+        DataType::Int64 => Arc::new(
+            values
+                .map(|value| value.and_then(Val::i64))
+                .collect::<Int64Array>(),
+        ),
+        DataType::Float32 => Arc::new(
+            values
+                .map(|value| value.and_then(Val::f32))
+                .collect::<Float32Array>(),
+        ),
+        _ => Arc::new(
+            values
+                .map(|value| value.and_then(Val::f64))
+                .collect::<Float64Array>(),
+        ),
+    }
+}
+
+/// Convert a UDF to the node sent along with the plan if it is a WASM UDF, the other UDFs
+/// being looked up by name when the plan is decoded
+pub(crate) fn to_node(udf: &ScalarUDF) -> Result<Option<protobuf::WasmUdfNode>> {
+    let inner = udf.inner();
+    let Some(udf) = inner.as_any().downcast_ref::<WasmUdf>() else {
+        return Ok(None);
+    };
+    let arg_types = udf
+        .arg_types
+        .iter()
+        .map(ArrowType::try_from)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| DataFusionError::Internal(format!("{e:?}")))?;
+    let return_type = ArrowType::try_from(&udf.return_type)
+        .map_err(|e| DataFusionError::Internal(format!("{e:?}")))?;
+    Ok(Some(protobuf::WasmUdfNode {
+        name: udf.name.clone(),
+        module: udf.bytes.clone(),
+        arg_types,
+        return_type: Some(return_type),
+    }))
+}
+
+/// Create a WASM UDF sent along with a plan
+pub(crate) fn from_node(node: protobuf::WasmUdfNode) -> Result<Arc<ScalarUDF>> {
+    let name = &node.name;
+    let arg_types = node
+        .arg_types
+        .iter()
+        .map(DataType::try_from)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| DataFusionError::Internal(format!("{e:?}")))?;
+    let return_type = node
+        .return_type
+        .as_ref()
+        .ok_or_else(|| {
+            DataFusionError::Internal(format!("Missing return type of WASM UDF {name}"))
+        })
+        .and_then(|return_type| {
+            DataType::try_from(return_type)
+                .map_err(|e| DataFusionError::Internal(format!("{e:?}")))
+        })?;
+    let udf = WasmUdf::try_new(node.name, node.module, arg_types, return_type)?;
+    Ok(Arc::new(udf.into_udf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::array::{ArrayRef, Int64Array};
+    use datafusion::arrow::datatypes::DataType;
+    use datafusion::error::Result;
+    use datafusion::logical_expr::{ColumnarValue, ScalarUDFImpl};
+
+    use super::{from_node, to_node, WasmUdf, WasmUdfLimits};
+
+    const ADD_ONE: &str = r#"(module
+        (func (export "add_one") (param i64) (result i64)
+            local.get 0
+            i64.const 1
+            i64.add))"#;
+
+    const SPIN: &str = r#"(module
+        (func (export "spin") (param i64) (result i64)
+            (loop $forever (br $forever))
+            local.get 0))"#;
+
+    fn invoke(udf: &WasmUdf, values: Vec<Option<i64>>) -> Result<ArrayRef> {
+        let array: ArrayRef = Arc::new(Int64Array::from(values));
+        match udf.invoke(&[ColumnarValue::Array(array)])? {
+            ColumnarValue::Array(array) => Ok(array),
+            ColumnarValue::Scalar(scalar) => scalar.to_array(),
+        }
+    }
+
+    #[test]
+    fn test_wasm_udf() -> Result<()> {
+        let udf = WasmUdf::try_new(
+            "add_one",
+            ADD_ONE.as_bytes().to_vec(),
+            vec![DataType::Int64],
+            DataType::Int64,
+        )?;
+        let result = invoke(&udf, vec![Some(1), None, Some(41)])?;
+        let expected: ArrayRef =
+            Arc::new(Int64Array::from(vec![Some(2), None, Some(42)]));
+        assert_eq!(&result, &expected);
+
+        // the UDF is sent along with the plans
+        let node = to_node(&udf.into_udf())?.unwrap();
+        let decoded = from_node(node)?;
+        assert_eq!(decoded.name(), "add_one");
+
+        // the signature must match the exported function
+        assert!(WasmUdf::try_new(
+            "add_one",
+            ADD_ONE.as_bytes().to_vec(),
+            vec![DataType::Int32],
+            DataType::Int64,
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wasm_udf_limits() -> Result<()> {
+        let udf = WasmUdf::try_new(
+            "spin",
+            SPIN.as_bytes().to_vec(),
+            vec![DataType::Int64],
+            DataType::Int64,
+        )?
+        .with_limits(WasmUdfLimits {
+            max_memory_bytes: 1024 * 1024,
+            fuel: 10_000,
+        });
+        assert!(invoke(&udf, vec![Some(1)]).is_err());
+
+        let importing = r#"(module
+            (import "env" "read" (func $read (result i64)))
+            (func (export "read_env") (param i64) (result i64) call $read))"#;
+        assert!(WasmUdf::try_new(
+            "read_env",
+            importing.as_bytes().to_vec(),
+            vec![DataType::Int64],
+            DataType::Int64,
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,406 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Restriction of the locations which the plans of tasks may read or write.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::{
+    FileSinkCommitExec, ParallelFileSinkExec, ShuffleReaderExec,
+};
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::datasource::file_format::csv::CsvSink;
+use datafusion::datasource::file_format::json::JsonSink;
+use datafusion::datasource::file_format::parquet::ParquetSink;
+use datafusion::datasource::listing::ListingTableUrl;
+use datafusion::datasource::physical_plan::{
+    AvroExec, CsvExec, FileScanConfig, NdJsonExec, ParquetExec,
+};
+use datafusion::physical_plan::insert::FileSinkExec;
+use datafusion::physical_plan::ExecutionPlan;
+
+/// Object store URLs and local directories, e.g. `s3://bucket/prefix` or `/data`, under
+/// which the plans of tasks may read and write files. The tasks whose plans scan or write
+/// files elsewhere are rejected.
+#[derive(Debug, Clone)]
+pub struct AllowedLocations {
+    /// Normalized URLs of the allowed locations, without trailing slash
+    prefixes: Vec<String>,
+}
+
+impl AllowedLocations {
+    /// Parse a comma separated list of locations, `None` if the list is empty
+    pub fn parse(locations: &str) -> Result<Option<Self>> {
+        let prefixes = locations
+            .split(',')
+            .map(str::trim)
+            .filter(|location| !location.is_empty())
+            .map(|location| {
+                let url = ListingTableUrl::parse(location).map_err(|e| {
+                    BallistaError::General(format!(
+                        "Invalid allowed location {location}: {e}"
+                    ))
+                })?;
+                Ok(url.as_str().trim_end_matches('/').to_owned())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((!prefixes.is_empty()).then_some(Self { prefixes }))
+    }
+
+    /// Whether the URL of a file or directory is under one of the allowed locations
+    pub fn is_allowed(&self, location: &str) -> bool {
+        self.prefixes.iter().any(|prefix| {
+            location
+                .strip_prefix(prefix.as_str())
+                .map(|rest| rest.is_empty() || rest.starts_with('/'))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Check that all the files scanned or written by the plan are under the allowed
+    /// locations, and that the shuffle files it reads from the local disks are under the
+    /// `work_dirs` of the executor
+    pub fn check_plan(
+        &self,
+        plan: &Arc<dyn ExecutionPlan>,
+        work_dirs: &[String],
+    ) -> Result<()> {
+        let mut denied = None;
+        plan.apply(&mut |plan| {
+            let location = plan_locations(plan).map(|locations| {
+                locations
+                    .into_iter()
+                    .find(|location| !self.is_allowed(location))
+                    .or_else(|| {
+                        shuffle_files(plan)
+                            .into_iter()
+                            .find(|path| !is_under_work_dirs(path, work_dirs))
+                    })
+            });
+            match location {
+                Ok(None) => Ok(TreeNodeRecursion::Continue),
+                Ok(Some(location)) => {
+                    denied = Some(BallistaError::General(format!(
+                        "Access to {location} is not allowed on this executor"
+                    )));
+                    Ok(TreeNodeRecursion::Stop)
+                }
+                Err(e) => {
+                    denied = Some(e);
+                    Ok(TreeNodeRecursion::Stop)
+                }
+            }
+        })?;
+        match denied {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+/// URLs of the files scanned or written by a node of a plan. Fails for the writes whose
+/// location is unknown, which cannot be checked.
+fn plan_locations(plan: &Arc<dyn ExecutionPlan>) -> Result<Vec<String>> {
+    let plan_any = plan.as_any();
+    let scan_config = if let Some(exec) = plan_any.downcast_ref::<ParquetExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = plan_any.downcast_ref::<AvroExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = plan_any.downcast_ref::<NdJsonExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = plan_any.downcast_ref::<CsvExec>() {
+        Some(exec.base_config())
+    } else {
+        None
+    };
+    if let Some(config) = scan_config {
+        return Ok(scanned_files(config));
+    }
+
+    let locations = if let Some(exec) = plan_any.downcast_ref::<ParallelFileSinkExec>() {
+        vec![format!(
+            "{}{}",
+            exec.object_store_url().as_str(),
+            exec.output_path()
+        )]
+    } else if let Some(exec) = plan_any.downcast_ref::<FileSinkCommitExec>() {
+        vec![format!(
+            "{}{}",
+            exec.object_store_url().as_str(),
+            exec.output_path()
+        )]
+    } else if let Some(exec) = plan_any.downcast_ref::<FileSinkExec>() {
+        let sink = exec.sink().as_any();
+        let config = if let Some(parquet) = sink.downcast_ref::<ParquetSink>() {
+            parquet.config()
+        } else if let Some(csv) = sink.downcast_ref::<CsvSink>() {
+            csv.config()
+        } else if let Some(json) = sink.downcast_ref::<JsonSink>() {
+            json.config()
+        } else {
+            return Err(BallistaError::General(format!(
+                "The location written by {exec:?} cannot be checked against the allowed \
+                locations of this executor"
+            )));
+        };
+        config
+            .table_paths
+            .iter()
+            .map(|path| path.as_str().to_owned())
+            .collect()
+    } else {
+        vec![]
+    };
+    Ok(locations)
+}
+
+/// Paths of the shuffle files read by a node of a plan
+fn shuffle_files(plan: &Arc<dyn ExecutionPlan>) -> Vec<String> {
+    match plan.as_any().downcast_ref::<ShuffleReaderExec>() {
+        Some(exec) => exec
+            .partition
+            .iter()
+            .flatten()
+            .map(|location| location.path.clone())
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Whether a shuffle file is under one of the work directories of the executor. The
+/// shuffle files read from the local disks are the ones which exist, the others are
+/// fetched from the executors which wrote them.
+fn is_under_work_dirs(path: &str, work_dirs: &[String]) -> bool {
+    let Ok(path) = Path::new(path).canonicalize() else {
+        return true;
+    };
+    work_dirs.iter().any(|work_dir| {
+        Path::new(work_dir)
+            .canonicalize()
+            .map(|work_dir| path.starts_with(work_dir))
+            .unwrap_or(false)
+    })
+}
+
+fn scanned_files(config: &FileScanConfig) -> Vec<String> {
+    config
+        .file_groups
+        .iter()
+        .flatten()
+        .map(|file| {
+            format!(
+                "{}{}",
+                config.object_store_url.as_str(),
+                file.object_meta.location
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::protocol::PROTOCOL_VERSION;
+    use ballista_core::serde::scheduler::{
+        ExecutorMetadata, ExecutorSpecification, PartitionId, PartitionLocation,
+    };
+    use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+    use datafusion::common::config::CsvOptions;
+    use datafusion::common::file_options::csv_writer::CsvWriterOptions;
+    use datafusion::common::Statistics;
+    use datafusion::datasource::listing::PartitionedFile;
+    use datafusion::datasource::physical_plan::FileSinkConfig;
+    use datafusion::execution::object_store::ObjectStoreUrl;
+    use datafusion::execution::{SendableRecordBatchStream, TaskContext};
+    use datafusion::physical_plan::empty::EmptyExec;
+    use datafusion::physical_plan::insert::DataSink;
+    use datafusion::physical_plan::{DisplayAs, DisplayFormatType};
+    use std::any::Any;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]))
+    }
+
+    fn avro_scan(path: &str) -> Arc<dyn ExecutionPlan> {
+        Arc::new(AvroExec::new(FileScanConfig {
+            object_store_url: ObjectStoreUrl::local_filesystem(),
+            file_schema: schema(),
+            file_groups: vec![vec![PartitionedFile::new(path, 10)]],
+            statistics: Statistics::new_unknown(&schema()),
+            projection: None,
+            limit: None,
+            table_partition_cols: vec![],
+            output_ordering: vec![],
+        }))
+    }
+
+    fn shuffle_reader(path: &str) -> Result<Arc<dyn ExecutionPlan>> {
+        let location = PartitionLocation {
+            map_partition_id: 0,
+            partition_id: PartitionId {
+                job_id: "job".to_string(),
+                stage_id: 1,
+                partition_id: 0,
+            },
+            executor_meta: ExecutorMetadata {
+                id: "executor".to_string(),
+                host: "localhost".to_string(),
+                port: 50051,
+                grpc_port: 50052,
+                specification: ExecutorSpecification { task_slots: 1 },
+                zone: String::new(),
+                rack: String::new(),
+                instance_type: String::new(),
+                capabilities: Default::default(),
+                protocol_version: PROTOCOL_VERSION,
+            },
+            partition_stats: Default::default(),
+            path: path.to_owned(),
+        };
+        Ok(Arc::new(ShuffleReaderExec::try_new(
+            1,
+            vec![vec![location]],
+            schema(),
+        )?))
+    }
+
+    /// A sink of a kind unknown to the executor
+    #[derive(Debug)]
+    struct UnknownSink;
+
+    impl DisplayAs for UnknownSink {
+        fn fmt_as(
+            &self,
+            _t: DisplayFormatType,
+            f: &mut std::fmt::Formatter,
+        ) -> std::fmt::Result {
+            write!(f, "UnknownSink")
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl DataSink for UnknownSink {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn metrics(&self) -> Option<datafusion::physical_plan::metrics::MetricsSet> {
+            None
+        }
+
+        async fn write_all(
+            &self,
+            _data: SendableRecordBatchStream,
+            _context: &Arc<TaskContext>,
+        ) -> datafusion::error::Result<u64> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn test_allowed_locations() -> Result<()> {
+        assert!(AllowedLocations::parse(" ")?.is_none());
+
+        let allowed = AllowedLocations::parse("s3://bucket/data/, /mnt/shared")?.unwrap();
+        assert!(allowed.is_allowed("s3://bucket/data"));
+        assert!(allowed.is_allowed("s3://bucket/data/year=2024/part-0.parquet"));
+        assert!(!allowed.is_allowed("s3://bucket/database/part-0.parquet"));
+        assert!(!allowed.is_allowed("s3://other/data/part-0.parquet"));
+        assert!(allowed.is_allowed("file:///mnt/shared/table/part-0.csv"));
+        assert!(!allowed.is_allowed("file:///etc/passwd"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_plan_scans_and_sinks() -> Result<()> {
+        let allowed = AllowedLocations::parse("/mnt/shared")?.unwrap();
+        allowed.check_plan(&avro_scan("/mnt/shared/table/part-0.avro"), &[])?;
+        assert!(allowed.check_plan(&avro_scan("/etc/passwd"), &[]).is_err());
+
+        let input: Arc<dyn ExecutionPlan> = Arc::new(EmptyExec::new(schema()));
+        let csv_sink = |path: &str| -> Result<Arc<dyn ExecutionPlan>> {
+            let config = FileSinkConfig {
+                object_store_url: ObjectStoreUrl::local_filesystem(),
+                file_groups: vec![],
+                table_paths: vec![ListingTableUrl::parse(path)?],
+                output_schema: schema(),
+                table_partition_cols: vec![],
+                overwrite: false,
+            };
+            Ok(Arc::new(FileSinkExec::new(
+                input.clone(),
+                Arc::new(CsvSink::new(
+                    config,
+                    CsvWriterOptions::try_from(&CsvOptions::default())?,
+                )),
+                schema(),
+                None,
+            )))
+        };
+        allowed.check_plan(&csv_sink("/mnt/shared/output/")?, &[])?;
+        assert!(allowed.check_plan(&csv_sink("/tmp/output/")?, &[]).is_err());
+
+        // the location written by an unknown sink cannot be checked
+        let unknown: Arc<dyn ExecutionPlan> = Arc::new(FileSinkExec::new(
+            input.clone(),
+            Arc::new(UnknownSink),
+            schema(),
+            None,
+        ));
+        assert!(allowed.check_plan(&unknown, &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_plan_shuffle_files() -> Result<()> {
+        let allowed = AllowedLocations::parse("/mnt/shared")?.unwrap();
+        let work_dir = tempfile::tempdir()?;
+        let other_dir = tempfile::tempdir()?;
+        let work_dirs = vec![work_dir.path().to_string_lossy().into_owned()];
+
+        let shuffle_file = work_dir.path().join("job/1/0/data-0.arrow");
+        std::fs::create_dir_all(shuffle_file.parent().unwrap())?;
+        std::fs::write(&shuffle_file, b"")?;
+        allowed.check_plan(
+            &shuffle_reader(&shuffle_file.to_string_lossy())?,
+            &work_dirs,
+        )?;
+
+        // the local files outside of the work directories are not read
+        let other_file = other_dir.path().join("secret");
+        std::fs::write(&other_file, b"")?;
+        assert!(allowed
+            .check_plan(&shuffle_reader(&other_file.to_string_lossy())?, &work_dirs)
+            .is_err());
+        let escaping = work_dir
+            .path()
+            .join("..")
+            .join(other_dir.path().file_name().unwrap())
+            .join("secret");
+        assert!(allowed
+            .check_plan(&shuffle_reader(&escaping.to_string_lossy())?, &work_dirs)
+            .is_err());
+
+        // the files written by other executors are fetched from them
+        let remote_file = work_dir.path().join("job/1/0/data-1.arrow");
+        allowed
+            .check_plan(&shuffle_reader(&remote_file.to_string_lossy())?, &work_dirs)?;
+        Ok(())
+    }
+}
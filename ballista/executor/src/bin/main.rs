@@ -17,13 +17,24 @@
 
 //! Ballista Rust executor binary.
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use ballista_core::config_file::{config_file_args, is_print_config};
+use ballista_core::plan_protection::PlanProtection;
 use ballista_core::print_version;
+use ballista_executor::allowed_locations::AllowedLocations;
 use ballista_executor::executor_process::{
     start_executor_process, ExecutorProcessConfig,
 };
+use ballista_executor::reloadable_config::{
+    CACHE_CAPACITY, LOG_LEVEL_SETTING, SHUFFLE_READER_MAX_REQUESTS,
+};
+use ballista_executor::task_dump::{read_task_dump, replay_task, replay_task_path};
 use config::prelude::*;
 
 #[macro_use]
@@ -40,18 +51,88 @@ mod config {
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
+const CONFIG_FILES: &[&str] = &["/etc/ballista/executor.toml"];
+
+/// Parse the configuration again, to get the reloadable settings from the configuration
+/// files
+fn load_reloadable_settings() -> Result<HashMap<String, String>> {
+    let config_args = config_file_args(env::args_os())?;
+    let (opt, _remaining_args) =
+        Config::custom_args_and_optional_files(config_args.args.clone(), CONFIG_FILES)
+            .map_err(|e| anyhow!("{e}"))?;
+    drop(config_args);
+    let mut settings = HashMap::from([
+        (
+            SHUFFLE_READER_MAX_REQUESTS.to_owned(),
+            opt.shuffle_reader_max_requests.to_string(),
+        ),
+        (CACHE_CAPACITY.to_owned(), opt.cache_capacity.to_string()),
+    ]);
+    // the log level set by the RUST_LOG environment variable takes precedence
+    if env::var("RUST_LOG").is_err() {
+        settings.insert(LOG_LEVEL_SETTING.to_owned(), opt.log_level_setting);
+    }
+    Ok(settings)
+}
+
+/// Execute the task of a dump saved by an executor, see [ballista_executor::task_dump]
+async fn replay_task_dump(path: PathBuf, work_dir: Option<String>) -> Result<()> {
+    tracing_subscriber::fmt().with_ansi(false).init();
+
+    let dump = read_task_dump(&path)?;
+    if let Some(task) = &dump.task {
+        println!(
+            "Replaying task {} of job {}, stage {}, partition {}",
+            task.task_id, task.job_id, task.stage_id, task.partition_id
+        );
+        println!("Failed on executor {}: {}", dump.executor_id, dump.error);
+    }
+    for location in &dump.input_partitions {
+        let executor = location.executor_meta.as_ref();
+        println!(
+            "Input shuffle partition {:?} at {} on {}:{}",
+            location.partition_id,
+            location.path,
+            executor.map(|e| e.host.as_str()).unwrap_or_default(),
+            executor.map(|e| e.port).unwrap_or_default(),
+        );
+    }
+
+    let temp_dir = tempfile::TempDir::new()?;
+    let work_dir =
+        work_dir.unwrap_or_else(|| temp_dir.path().to_string_lossy().into_owned());
+    let partitions = replay_task(dump, &work_dir).await?;
+    println!(
+        "Task succeeded, wrote {} shuffle partitions to {work_dir}",
+        partitions.len()
+    );
+    for partition in partitions {
+        println!("{partition:?}");
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    // parse command-line arguments
-    let (opt, _remaining_args) =
-        Config::including_optional_config_files(&["/etc/ballista/executor.toml"])
+    // parse options, from the configuration files, environment variables and flags
+    let config_args = config_file_args(env::args_os())?;
+    let (opt, remaining_args) =
+        Config::custom_args_and_optional_files(config_args.args.clone(), CONFIG_FILES)
             .unwrap_or_exit();
+    // remove the TOML files converted from YAML files
+    drop(config_args);
+    let remaining_args: Vec<OsString> = remaining_args.collect();
+    let print_config = is_print_config(remaining_args.clone());
 
     if opt.version {
         print_version();
         std::process::exit(0);
     }
 
+    if let Some(path) = replay_task_path(&remaining_args) {
+        return replay_task_dump(path?, opt.work_dir).await;
+    }
+
     let log_file_name_prefix = format!(
         "executor_{}_{}",
         opt.external_host
@@ -60,27 +141,69 @@ async fn main() -> Result<()> {
         opt.bind_port
     );
 
+    let plan_protection = PlanProtection::new(
+        opt.plan_signing_key.as_deref(),
+        opt.plan_encryption_key.as_deref(),
+    )?;
+
+    let allowed_locations =
+        AllowedLocations::parse(&opt.allowed_locations)?.map(Arc::new);
+
     let config = ExecutorProcessConfig {
         special_mod_log_level: opt.log_level_setting,
         external_host: opt.external_host,
+        zone: opt.zone,
+        rack: opt.rack,
+        instance_type: opt.instance_type,
         bind_host: opt.bind_host,
         port: opt.bind_port,
         grpc_port: opt.bind_grpc_port,
         scheduler_host: opt.scheduler_host,
         scheduler_port: opt.scheduler_port,
         scheduler_connect_timeout_seconds: opt.scheduler_connect_timeout_seconds,
+        scheduler_retry_max_backoff_ms: opt.scheduler_retry_max_backoff_ms,
         concurrent_tasks: opt.concurrent_tasks,
+        task_runtime_threads: opt.task_runtime_threads,
+        task_runtime_cpus: opt.task_runtime_cpus,
         task_scheduling_policy: opt.task_scheduling_policy,
         work_dir: opt.work_dir,
+        work_dir_placement: opt.work_dir_placement,
         log_dir: opt.log_dir,
         log_file_name_prefix,
         log_rotation_policy: opt.log_rotation_policy,
         print_thread_info: opt.print_thread_info,
         job_data_ttl_seconds: opt.job_data_ttl_seconds,
         job_data_clean_up_interval_seconds: opt.job_data_clean_up_interval_seconds,
+        shuffle_inventory_report_interval_seconds: opt
+            .shuffle_inventory_report_interval_seconds,
         grpc_max_decoding_message_size: opt.grpc_server_max_decoding_message_size,
         grpc_max_encoding_message_size: opt.grpc_server_max_encoding_message_size,
         executor_heartbeat_interval_seconds: opt.executor_heartbeat_interval_seconds,
+        heartbeat_task_status: opt.heartbeat_task_status,
+        max_task_metrics_per_operator: opt.max_task_metrics_per_operator,
+        task_log_max_lines: opt.task_log_max_lines,
+        task_log_max_tasks: opt.task_log_max_tasks,
+        task_dump_dir: opt.task_dump_dir,
+        task_dump_max_files: opt.task_dump_max_files,
+        preemption_notice_command: opt.preemption_notice_command,
+        preemption_notice_url: opt.preemption_notice_url,
+        preemption_notice_poll_interval_seconds: opt
+            .preemption_notice_poll_interval_seconds,
+        allowed_locations,
+        plan_protection,
+        shuffle_reader_max_requests: opt.shuffle_reader_max_requests,
+        wasm_udf_max_memory_bytes: opt.wasm_udf_max_memory_bytes as usize,
+        wasm_udf_fuel: opt.wasm_udf_fuel,
+        python_udf_executable: opt.python_udf_executable,
+        python_udf_workers: opt.python_udf_workers,
+        python_udf_timeout_seconds: opt.python_udf_timeout_seconds,
+        disk_write_io_concurrency: opt.disk_write_io_concurrency,
+        disk_read_io_concurrency: opt.disk_read_io_concurrency,
+        cgroup: opt.cgroup,
+        cgroup_memory_max: opt.cgroup_memory_max,
+        cgroup_cpu_max_millis: opt.cgroup_cpu_max_millis,
+        cgroup_workers_only: opt.cgroup_workers_only,
+        settings_loader: Some(Arc::new(load_reloadable_settings)),
         data_cache_policy: opt.data_cache_policy,
         cache_dir: opt.cache_dir,
         cache_capacity: opt.cache_capacity,
@@ -88,5 +211,10 @@ async fn main() -> Result<()> {
         execution_engine: None,
     };
 
+    if print_config {
+        println!("{config:#?}");
+        return Ok(());
+    }
+
     start_executor_process(Arc::new(config)).await
 }
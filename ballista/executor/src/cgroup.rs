@@ -0,0 +1,176 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The cgroup v2 of an executor on Linux, which bounds the memory and the CPU time of the
+//! executor or of the child processes running its tasks, such as the Python UDF workers.
+//! The usage of the cgroup is reported to the scheduler in the heartbeats of the executor.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::CgroupMetrics;
+use log::{info, warn};
+
+/// The root of the cgroup v2 hierarchy, under which the relative cgroup paths are
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// The period of the CPU limit of the cgroup in microseconds
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+/// The limits applied to a cgroup, unlimited if zero
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CgroupLimits {
+    /// The memory limit in bytes
+    pub memory_max: u64,
+    /// The CPU limit in thousandths of CPUs
+    pub cpu_max_millis: u64,
+}
+
+/// A cgroup v2 of the executor
+#[derive(Debug, Clone)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Create the cgroup if missing, relative to the root of the cgroup hierarchy unless
+    /// the path is absolute, and apply the limits to it
+    pub fn try_new(path: &str, limits: CgroupLimits) -> Result<Self> {
+        if !cfg!(target_os = "linux") {
+            return Err(BallistaError::General(format!(
+                "Cannot use cgroup {path}, cgroups are not supported on this platform"
+            )));
+        }
+        let path = Path::new(CGROUP_ROOT).join(path);
+        if !path.exists() {
+            // the controllers must be enabled in the parent to limit the new cgroup
+            if let Some(parent) = path.parent() {
+                if let Err(e) =
+                    fs::write(parent.join("cgroup.subtree_control"), "+memory +cpu")
+                {
+                    warn!("Failed to enable the controllers of {parent:?}: {e}");
+                }
+            }
+            fs::create_dir_all(&path).map_err(|e| {
+                BallistaError::General(format!("Failed to create cgroup {path:?}: {e}"))
+            })?;
+        }
+        let cgroup = Self { path };
+        if limits.memory_max > 0 {
+            cgroup.write("memory.max", &limits.memory_max.to_string())?;
+        }
+        if limits.cpu_max_millis > 0 {
+            cgroup.write("cpu.max", &format_cpu_max(limits.cpu_max_millis))?;
+        }
+        info!("Using cgroup {:?} with limits {:?}", cgroup.path, limits);
+        Ok(cgroup)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The file listing the processes of the cgroup, to which a process is moved by
+    /// writing its pid
+    pub fn procs_file(&self) -> PathBuf {
+        self.path.join("cgroup.procs")
+    }
+
+    /// Move a process, with all its threads, to the cgroup
+    pub fn add_process(&self, pid: u32) -> Result<()> {
+        self.write("cgroup.procs", &pid.to_string())
+    }
+
+    /// The current usage and limits of the cgroup, unlimited if they cannot be read
+    pub fn metrics(&self) -> Result<CgroupMetrics> {
+        let memory_current = self.read("memory.current")?;
+        let cpu_stat = self.read("cpu.stat")?;
+        Ok(CgroupMetrics {
+            memory_current: memory_current.trim().parse().unwrap_or_default(),
+            memory_max: self
+                .read("memory.max")
+                .map(|max| parse_memory_max(&max))
+                .unwrap_or_default(),
+            cpu_usage_usec: parse_cpu_usage(&cpu_stat),
+            cpu_max_millis: self
+                .read("cpu.max")
+                .map(|max| parse_cpu_max(&max))
+                .unwrap_or_default(),
+        })
+    }
+
+    fn read(&self, file: &str) -> Result<String> {
+        let path = self.path.join(file);
+        fs::read_to_string(&path)
+            .map_err(|e| BallistaError::General(format!("Failed to read {path:?}: {e}")))
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<()> {
+        let path = self.path.join(file);
+        fs::write(&path, value).map_err(|e| {
+            BallistaError::General(format!("Failed to write {value} to {path:?}: {e}"))
+        })
+    }
+}
+
+/// The value of `cpu.max` limiting the cgroup to thousandths of CPUs
+fn format_cpu_max(millis: u64) -> String {
+    format!("{} {CPU_PERIOD_USEC}", millis * CPU_PERIOD_USEC / 1000)
+}
+
+/// The limit of `memory.max` in bytes, 0 if unlimited
+fn parse_memory_max(max: &str) -> u64 {
+    max.trim().parse().unwrap_or_default()
+}
+
+/// The limit of `cpu.max` in thousandths of CPUs, 0 if unlimited
+fn parse_cpu_max(max: &str) -> u64 {
+    let mut fields = max.split_whitespace();
+    let quota: Option<u64> = fields.next().and_then(|quota| quota.parse().ok());
+    let period: Option<u64> = fields.next().and_then(|period| period.parse().ok());
+    match (quota, period) {
+        (Some(quota), Some(period)) if period > 0 => (quota * 1000 / period).max(1),
+        _ => 0,
+    }
+}
+
+/// The total CPU time of `cpu.stat` in microseconds
+fn parse_cpu_usage(stat: &str) -> u64 {
+    stat.lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|usage| usage.trim().parse().ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cgroup_files() {
+        assert_eq!(format_cpu_max(1500), "150000 100000");
+        assert_eq!(parse_cpu_max("150000 100000\n"), 1500);
+        assert_eq!(parse_cpu_max("max 100000\n"), 0);
+        assert_eq!(parse_memory_max("1073741824\n"), 1 << 30);
+        assert_eq!(parse_memory_max("max\n"), 0);
+        assert_eq!(
+            parse_cpu_usage("usage_usec 42000\nuser_usec 40000\nsystem_usec 2000\n"),
+            42000
+        );
+    }
+}
@@ -20,6 +20,7 @@
 //! This module contains a dedicated thread pool for running "cpu
 //! intensive" workloads as query plans
 
+use ballista_core::error::{BallistaError, Result};
 use log::warn;
 use parking_lot::Mutex;
 use std::{pin::Pin, sync::Arc};
@@ -47,6 +48,9 @@ struct State {
     /// The name of the threads for this executor
     thread_name: String,
 
+    /// The CPUs to which the threads are pinned, any CPU if empty
+    cpus: Vec<usize>,
+
     /// Channel for requests -- the dedicated executor takes requests
     /// from here and runs them.
     requests: Option<std::sync::mpsc::Sender<Task>>,
@@ -65,7 +69,8 @@ impl std::fmt::Debug for DedicatedExecutor {
         let mut d = f.debug_struct("DedicatedExecutor");
 
         d.field("num_threads", &state.num_threads)
-            .field("thread_name", &state.thread_name);
+            .field("thread_name", &state.thread_name)
+            .field("cpus", &state.cpus);
 
         if state.requests.is_some() {
             d.field("requests", &"Some(...)")
@@ -92,8 +97,20 @@ impl DedicatedExecutor {
     /// not starve other more important tasks (such as answering health checks)
     ///
     pub fn new(thread_name: impl Into<String>, num_threads: usize) -> Self {
+        Self::new_with_cpus(thread_name, num_threads, vec![])
+    }
+
+    /// Creates a new `DedicatedExecutor` whose threads are pinned to the given CPUs, e.g.
+    /// the cores of a NUMA node, so that the other CPUs are left to the main runtime. The
+    /// threads may run on any CPU if `cpus` is empty.
+    pub fn new_with_cpus(
+        thread_name: impl Into<String>,
+        num_threads: usize,
+        cpus: Vec<usize>,
+    ) -> Self {
         let thread_name = thread_name.into();
         let name_copy = thread_name.to_string();
+        let thread_cpus = cpus.clone();
 
         let (tx, rx) = std::sync::mpsc::channel();
 
@@ -104,7 +121,12 @@ impl DedicatedExecutor {
                 .enable_all()
                 .thread_name(&name_copy)
                 .worker_threads(num_threads)
-                .on_thread_start(move || set_current_thread_priority(WORKER_PRIORITY))
+                .on_thread_start(move || {
+                    set_current_thread_priority(WORKER_PRIORITY);
+                    if !thread_cpus.is_empty() {
+                        set_current_thread_affinity(&thread_cpus);
+                    }
+                })
                 .build()
                 .expect("Creating tokio runtime");
 
@@ -121,6 +143,7 @@ impl DedicatedExecutor {
         let state = State {
             num_threads,
             thread_name,
+            cpus,
             requests: Some(tx),
             thread: Some(thread),
         };
@@ -207,6 +230,55 @@ fn set_current_thread_priority(_prio: i32) {
     warn!("Setting worker thread priority not supported on this platform");
 }
 
+/// Parse a list of CPUs such as `0-3,8,10-11`, as in the `cpuset` of Linux
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>> {
+    let invalid = |item: &str| {
+        BallistaError::General(format!("Invalid CPU {item:?} in CPU list {list:?}"))
+    };
+    let mut cpus = vec![];
+    for item in list
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+    {
+        let (first, last) = item.split_once('-').unwrap_or((item, item));
+        let first: usize = first.trim().parse().map_err(|_| invalid(item))?;
+        let last: usize = last.trim().parse().map_err(|_| invalid(item))?;
+        if first > last {
+            return Err(invalid(item));
+        }
+        cpus.extend(first..=last);
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+#[cfg(target_os = "linux")]
+fn set_current_thread_affinity(cpus: &[usize]) {
+    // sched_setaffinity with a pid of zero sets the affinity of the current thread
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for cpu in cpus.iter().filter(|cpu| **cpu < libc::CPU_SETSIZE as usize) {
+            libc::CPU_SET(*cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result != 0 {
+        warn!(
+            "Failed to pin worker thread to CPUs {:?}: {}",
+            cpus,
+            std::io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_current_thread_affinity(_cpus: &[usize]) {
+    warn!("Pinning worker threads to CPUs not supported on this platform");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -370,6 +442,40 @@ mod tests {
         exec.join();
     }
 
+    #[test]
+    fn test_parse_cpu_list() -> Result<()> {
+        assert!(parse_cpu_list("")?.is_empty());
+        assert_eq!(vec![0, 1, 2, 3, 8], parse_cpu_list("8, 0-3,2")?);
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("0-a").is_err());
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn executor_pinned_to_cpus() {
+        // the CPUs the tests may run on depend on the environment
+        let cpu = *current_thread_cpus().last().unwrap();
+        let exec =
+            DedicatedExecutor::new_with_cpus("Test DedicatedExecutor", 1, vec![cpu]);
+        let cpus = exec.spawn(async { current_thread_cpus() });
+        assert_eq!(cpus.await.unwrap(), vec![cpu]);
+        exec.join();
+    }
+
+    /// The CPUs the current thread may run on
+    #[cfg(target_os = "linux")]
+    fn current_thread_cpus() -> Vec<usize> {
+        unsafe {
+            let mut set: libc::cpu_set_t = std::mem::zeroed();
+            let size = std::mem::size_of::<libc::cpu_set_t>();
+            assert_eq!(libc::sched_getaffinity(0, size, &mut set), 0);
+            (0..libc::CPU_SETSIZE as usize)
+                .filter(|cpu| libc::CPU_ISSET(*cpu, &set))
+                .collect()
+        }
+    }
+
     /// Wait for the barrier and then return `result`
     async fn do_work(result: usize, barrier: Arc<Barrier>) -> usize {
         barrier.wait();
@@ -75,6 +75,11 @@ impl ExecutionEngine for DefaultExecutionEngine {
                 work_dir.to_string(),
                 shuffle_writer.shuffle_output_partitioning().cloned(),
             )
+            .map(|exec| {
+                exec.with_bloom_filter_columns(
+                    shuffle_writer.bloom_filter_columns().to_vec(),
+                )
+            })
         } else {
             Err(DataFusionError::Internal(
                 "Plan passed to new_query_stage_exec is not a ShuffleWriterExec"
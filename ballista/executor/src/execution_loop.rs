@@ -27,8 +27,11 @@ use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
 use crate::cpu_bound_executor::DedicatedExecutor;
 use crate::executor::Executor;
-use crate::{as_task_status, TaskExecutionTimes};
+use crate::launched_tasks::LaunchedTasks;
+use crate::task_dump::dump_failed_task;
+use crate::{as_task_status, task_config_options, TaskExecutionTimes};
 use ballista_core::error::BallistaError;
+use ballista_core::execution_plans::{ShuffleReaderOptions, ShuffleWriterOptions};
 use ballista_core::serde::scheduler::{ExecutorSpecification, PartitionId};
 use ballista_core::serde::BallistaCodec;
 use datafusion::execution::context::TaskContext;
@@ -36,9 +39,9 @@ use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 use futures::FutureExt;
 use log::{debug, error, info, warn};
+use parking_lot::Mutex;
 use std::any::Any;
 use std::collections::HashMap;
-use std::convert::TryInto;
 use std::error::Error;
 use std::ops::Deref;
 use std::sync::mpsc::{Receiver, Sender, TryRecvError};
@@ -65,8 +68,8 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         std::sync::mpsc::channel::<TaskStatus>();
     info!("Starting poll work loop with scheduler");
 
-    let dedicated_executor =
-        DedicatedExecutor::new("task_runner", executor_specification.task_slots as usize);
+    let dedicated_executor = executor.new_task_runtime();
+    let launched_tasks = Arc::new(Mutex::new(LaunchedTasks::default()));
 
     loop {
         // Wait for task slots to be available before asking for new work
@@ -89,6 +92,7 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 metadata: Some(executor.metadata.clone()),
                 num_free_slots: available_task_slots.available_permits() as u32,
                 task_status,
+                running_jobs: executor.running_jobs(),
             })
             .await;
 
@@ -99,6 +103,13 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
 
                 for task in tasks {
                     let task_status_sender = task_status_sender.clone();
+                    if is_launched(
+                        &launched_tasks,
+                        &task_status_sender,
+                        &task.launch_token,
+                    ) {
+                        continue;
+                    }
 
                     // Acquire a permit/slot for the task
                     let permit =
@@ -109,8 +120,10 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                         permit,
                         task_status_sender,
                         task,
+                        &scheduler,
                         &codec,
                         &dedicated_executor,
+                        &launched_tasks,
                     )
                     .await
                     {
@@ -132,6 +145,28 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     }
 }
 
+/// Whether the task attempt of the launch token was already launched, e.g. by the
+/// scheduler taking over the job, in which case the original status of the task is
+/// reported again if it has finished
+fn is_launched(
+    launched_tasks: &Mutex<LaunchedTasks>,
+    task_status_sender: &Sender<TaskStatus>,
+    launch_token: &str,
+) -> bool {
+    // tasks launched by older schedulers have no launch token
+    if launch_token.is_empty() {
+        return false;
+    }
+    let Some(task_status) = launched_tasks.lock().get(launch_token) else {
+        return false;
+    };
+    info!("Ignoring duplicate launch of task {launch_token}");
+    if let Some(task_status) = task_status {
+        let _ = task_status_sender.send(task_status);
+    }
+    true
+}
+
 /// Tries to get meaningful description from panic-error.
 pub(crate) fn any_to_string(any: &Box<dyn Any + Send>) -> String {
     if let Some(s) = any.downcast_ref::<&str>() {
@@ -145,14 +180,18 @@ pub(crate) fn any_to_string(any: &Box<dyn Any + Send>) -> String {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
     executor: Arc<Executor>,
     permit: OwnedSemaphorePermit,
     task_status_sender: Sender<TaskStatus>,
-    task: TaskDefinition,
+    mut task: TaskDefinition,
+    scheduler: &SchedulerGrpcClient<Channel>,
     codec: &BallistaCodec<T, U>,
     dedicated_executor: &DedicatedExecutor,
+    launched_tasks: &Arc<Mutex<LaunchedTasks>>,
 ) -> Result<(), BallistaError> {
+    task.plan = codec.plan_protection().unprotect(task.plan)?;
     let task_id = task.task_id;
     let task_attempt_num = task.task_attempt_num;
     let job_id = task.job_id;
@@ -160,6 +199,7 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     let stage_attempt_num = task.stage_attempt_num;
     let task_launch_time = task.launch_time;
     let partition_id = task.partition_id;
+    let launch_token = task.launch_token.clone();
     let start_exec_time = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
@@ -168,16 +208,30 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
         "TID {task_id} {job_id}/{stage_id}.{stage_attempt_num}/{partition_id}.{task_attempt_num}"
     );
     info!("Received task {}", task_identity);
+    // the definition of the task is kept to dump it if the task fails
+    let task_to_dump = executor.task_dump_dir.as_ref().map(|_| task.clone());
 
     let mut task_props = HashMap::new();
     for kv_pair in task.props {
         task_props.insert(kv_pair.key, kv_pair.value);
     }
-    let mut config = ConfigOptions::new();
-    for (k, v) in task_props {
-        config.set(&k, &v)?;
-    }
-    let session_config = SessionConfig::from(config);
+    let reader_options = ShuffleReaderOptions::from_props(
+        &task_props,
+        executor.reloadable_config.shuffle_reader_max_requests(),
+        Some(scheduler.clone()),
+    );
+    let writer_options = ShuffleWriterOptions::from_props(
+        &task_props,
+        Some(executor.disk_io.write.clone()),
+    );
+    // a task with invalid options is decoded with the default ones to report it failed
+    let (config, invalid_config) = match task_config_options(&task_props) {
+        Ok(config) => (config, Ok(())),
+        Err(e) => (ConfigOptions::new(), Err(e)),
+    };
+    let session_config = SessionConfig::from(config)
+        .with_extension(Arc::new(reader_options))
+        .with_extension(Arc::new(writer_options));
 
     let mut task_scalar_functions = HashMap::new();
     let mut task_aggregate_functions = HashMap::new();
@@ -216,9 +270,15 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     let query_stage_exec = executor.execution_engine.create_query_stage_exec(
         job_id.clone(),
         stage_id as usize,
-        plan,
-        &executor.work_dir,
+        plan.clone(),
+        executor.work_dirs.next_dir(),
     )?;
+    let allowed_locations = invalid_config.and(executor.check_allowed_locations(&plan));
+    // recorded once the task is about to run, for a launch failing before to be retried
+    if !launch_token.is_empty() {
+        launched_tasks.lock().launch(&launch_token);
+    }
+    let launched_tasks = launched_tasks.clone();
     dedicated_executor.spawn(async move {
         use std::panic::AssertUnwindSafe;
         let part = PartitionId {
@@ -227,32 +287,48 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
             partition_id: partition_id as usize,
         };
 
-        let execution_result = match AssertUnwindSafe(executor.execute_query_stage(
-            task_id as usize,
-            part.clone(),
-            query_stage_exec.clone(),
-            task_context,
-        ))
-        .catch_unwind()
-        .await
-        {
-            Ok(Ok(r)) => Ok(r),
-            Ok(Err(r)) => Err(r),
-            Err(r) => {
-                error!("Error executing task: {:?}", any_to_string(&r));
-                Err(BallistaError::Internal(format!("{:#?}", any_to_string(&r))))
+        let execution_result = if let Err(e) = allowed_locations {
+            warn!("Rejected task {}: {}", task_identity, e);
+            Err(e)
+        } else {
+            match AssertUnwindSafe(executor.execute_query_stage(
+                task_id as usize,
+                part.clone(),
+                query_stage_exec.clone(),
+                task_context,
+            ))
+            .catch_unwind()
+            .await
+            {
+                Ok(Ok(r)) => Ok(r),
+                Ok(Err(r)) => Err(r),
+                Err(r) => {
+                    error!("Error executing task: {:?}", any_to_string(&r));
+                    Err(BallistaError::Internal(format!("{:#?}", any_to_string(&r))))
+                }
             }
         };
 
         info!("Done with task {}", task_identity);
         debug!("Statistics: {:?}", execution_result);
 
-        let plan_metrics = query_stage_exec.collect_plan_metrics();
-        let operator_metrics = plan_metrics
-            .into_iter()
-            .map(|m| m.try_into())
-            .collect::<Result<Vec<_>, BallistaError>>()
-            .ok();
+        if let (Err(e), Some(task_to_dump)) = (&execution_result, task_to_dump) {
+            dump_failed_task(&executor, task_to_dump, &plan, e);
+        }
+
+        let operator_metrics = match executor
+            .metrics_collector
+            .operator_metrics(query_stage_exec.as_ref())
+        {
+            Ok(metrics) => Some(metrics),
+            Err(e) => {
+                warn!(
+                    "Fail to collect metrics for task {}: {:?}",
+                    task_identity, e
+                );
+                None
+            }
+        };
 
         let end_exec_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -265,7 +341,7 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
             end_exec_time,
         };
 
-        let _ = task_status_sender.send(as_task_status(
+        let task_status = as_task_status(
             execution_result,
             executor.metadata.id.clone(),
             task_id as usize,
@@ -273,7 +349,11 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
             part,
             operator_metrics,
             task_execution_times,
-        ));
+        );
+        if !launch_token.is_empty() {
+            launched_tasks.lock().finish(&launch_token, &task_status);
+        }
+        let _ = task_status_sender.send(task_status);
 
         // Release the permit after the work is done
         drop(permit);
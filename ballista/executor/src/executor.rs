@@ -17,10 +17,17 @@
 
 //! Ballista executor logic
 
+use crate::allowed_locations::AllowedLocations;
+use crate::cgroup::Cgroup;
+use crate::cpu_bound_executor::DedicatedExecutor;
 use crate::execution_engine::DefaultExecutionEngine;
 use crate::execution_engine::ExecutionEngine;
 use crate::execution_engine::QueryStageExecutor;
 use crate::metrics::ExecutorMetricsCollector;
+use crate::reloadable_config::ReloadableConfig;
+use crate::task_logs::{task_span, TaskLogs};
+use crate::work_dirs::WorkDirs;
+use ballista_core::disk_io::DiskIoScheduler;
 use ballista_core::error::BallistaError;
 use ballista_core::serde::protobuf;
 use ballista_core::serde::protobuf::ExecutorRegistration;
@@ -31,12 +38,15 @@ use datafusion::execution::runtime_env::RuntimeEnv;
 use datafusion::logical_expr::WindowUDF;
 use datafusion::physical_plan::udaf::AggregateUDF;
 use datafusion::physical_plan::udf::ScalarUDF;
+use datafusion::physical_plan::ExecutionPlan;
 use futures::future::AbortHandle;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use tracing::Instrument;
 
 pub struct TasksDrainedFuture(pub Arc<Executor>);
 
@@ -60,9 +70,12 @@ pub struct Executor {
     /// Metadata
     pub metadata: ExecutorRegistration,
 
-    /// Directory for storing partial results
+    /// Directory for storing partial results, the first of the work dirs
     pub work_dir: String,
 
+    /// Directories over which the tasks spread their shuffle files
+    pub work_dirs: Arc<WorkDirs>,
+
     /// Scalar functions that are registered in the Executor
     pub scalar_functions: HashMap<String, Arc<ScalarUDF>>,
 
@@ -92,6 +105,35 @@ pub struct Executor {
     /// Execution engine that the executor will delegate to
     /// for executing query stages
     pub(crate) execution_engine: Arc<dyn ExecutionEngine>,
+
+    /// Settings which can be changed while the executor is running
+    pub reloadable_config: Arc<ReloadableConfig>,
+
+    /// Log lines of the tasks executed by the executor
+    pub task_logs: Arc<TaskLogs>,
+
+    /// Directory where the dumps of failed tasks are saved, if any
+    pub task_dump_dir: Option<PathBuf>,
+
+    /// Maximum number of dumps kept in the dump directory, no limit if zero
+    pub task_dump_max_files: usize,
+
+    /// Locations under which the plans of tasks may read and write files, if restricted
+    pub allowed_locations: Option<Arc<AllowedLocations>>,
+
+    /// Pools running the writes of the shuffle files and the reads serving them
+    pub disk_io: DiskIoScheduler,
+
+    /// The cgroup of the executor or of its Python UDF workers, whose usage is reported in
+    /// the heartbeats, if any
+    pub cgroup: Option<Arc<Cgroup>>,
+
+    /// Number of worker threads of the runtime running the tasks, the concurrent tasks if zero
+    task_runtime_threads: usize,
+
+    /// CPUs to which the worker threads of the runtime running the tasks are pinned, any CPU
+    /// if empty
+    task_runtime_cpus: Vec<usize>,
 }
 
 impl Executor {
@@ -108,6 +150,7 @@ impl Executor {
         Self {
             metadata,
             work_dir: work_dir.to_owned(),
+            work_dirs: Arc::new(WorkDirs::single(work_dir)),
             // TODO add logic to dynamically load UDF/UDAFs libs from files
             scalar_functions: HashMap::new(),
             aggregate_functions: HashMap::new(),
@@ -119,8 +162,96 @@ impl Executor {
             abort_handles: Default::default(),
             execution_engine: execution_engine
                 .unwrap_or_else(|| Arc::new(DefaultExecutionEngine {})),
+            reloadable_config: Arc::new(ReloadableConfig::default()),
+            task_logs: Arc::new(TaskLogs::default()),
+            task_dump_dir: None,
+            task_dump_max_files: 100,
+            allowed_locations: None,
+            disk_io: DiskIoScheduler::default(),
+            cgroup: None,
+            task_runtime_threads: 0,
+            task_runtime_cpus: vec![],
         }
     }
+
+    /// Set the settings which can be changed while the executor is running
+    pub fn with_reloadable_config(
+        mut self,
+        reloadable_config: Arc<ReloadableConfig>,
+    ) -> Self {
+        self.reloadable_config = reloadable_config;
+        self
+    }
+
+    /// Set the store of the log lines of the tasks executed by the executor
+    pub fn with_task_logs(mut self, task_logs: Arc<TaskLogs>) -> Self {
+        self.task_logs = task_logs;
+        self
+    }
+
+    /// Set the directory where the dumps of failed tasks are saved, see [crate::task_dump]
+    pub fn with_task_dump_dir(mut self, task_dump_dir: Option<PathBuf>) -> Self {
+        self.task_dump_dir = task_dump_dir;
+        self
+    }
+
+    /// Set the maximum number of dumps kept in the dump directory, the oldest dumps being
+    /// removed first. There is no limit if zero.
+    pub fn with_task_dump_max_files(mut self, task_dump_max_files: usize) -> Self {
+        self.task_dump_max_files = task_dump_max_files;
+        self
+    }
+
+    /// Restrict the locations under which the plans of tasks may read and write files
+    pub fn with_allowed_locations(
+        mut self,
+        allowed_locations: Option<Arc<AllowedLocations>>,
+    ) -> Self {
+        self.allowed_locations = allowed_locations;
+        self
+    }
+
+    /// Set the pools running the writes of the shuffle files and the reads serving them
+    pub fn with_disk_io(mut self, disk_io: DiskIoScheduler) -> Self {
+        self.disk_io = disk_io;
+        self
+    }
+
+    /// Set the cgroup whose usage is reported in the heartbeats, see [crate::cgroup]
+    pub fn with_cgroup(mut self, cgroup: Option<Arc<Cgroup>>) -> Self {
+        self.cgroup = cgroup;
+        self
+    }
+
+    /// Spread the shuffle files of the tasks over several work dirs
+    pub fn with_work_dirs(mut self, work_dirs: WorkDirs) -> Self {
+        self.work_dir = work_dirs.primary().to_owned();
+        self.work_dirs = Arc::new(work_dirs);
+        self
+    }
+
+    /// Set the number of worker threads of the runtime running the tasks, and the CPUs to
+    /// which they are pinned
+    pub fn with_task_runtime(mut self, threads: usize, cpus: Vec<usize>) -> Self {
+        self.task_runtime_threads = threads;
+        self.task_runtime_cpus = cpus;
+        self
+    }
+
+    /// Create the runtime running the tasks, separate from the runtime serving the gRPC and
+    /// Flight requests so that heavy tasks do not delay the shuffle fetches
+    pub(crate) fn new_task_runtime(&self) -> DedicatedExecutor {
+        let threads = if self.task_runtime_threads > 0 {
+            self.task_runtime_threads
+        } else {
+            self.concurrent_tasks
+        };
+        DedicatedExecutor::new_with_cpus(
+            "task_runner",
+            threads,
+            self.task_runtime_cpus.clone(),
+        )
+    }
 }
 
 impl Executor {
@@ -136,6 +267,20 @@ impl Executor {
         }
     }
 
+    /// Check that the plan of a task only reads and writes files under the allowed
+    /// locations of the executor, if restricted
+    pub fn check_allowed_locations(
+        &self,
+        plan: &Arc<dyn ExecutionPlan>,
+    ) -> Result<(), BallistaError> {
+        match &self.allowed_locations {
+            Some(allowed_locations) => {
+                allowed_locations.check_plan(plan, self.work_dirs.dirs())
+            }
+            None => Ok(()),
+        }
+    }
+
     /// Execute one partition of a query stage and persist the result to disk in IPC format. On
     /// success, return a RecordBatch containing metadata about the results, including path
     /// and statistics.
@@ -153,7 +298,9 @@ impl Executor {
         self.abort_handles
             .insert((task_id, partition.clone()), abort_handle);
 
-        let partitions = task.await??;
+        let partitions = task
+            .instrument(task_span(&partition.job_id, partition.stage_id, task_id))
+            .await??;
 
         self.abort_handles.remove(&(task_id, partition.clone()));
 
@@ -196,6 +343,16 @@ impl Executor {
     pub fn active_task_count(&self) -> usize {
         self.abort_handles.len()
     }
+
+    /// The IDs of the jobs with tasks running on the executor
+    pub fn running_jobs(&self) -> Vec<String> {
+        self.abort_handles
+            .iter()
+            .map(|entry| entry.key().1.job_id.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -341,6 +498,12 @@ mod test {
             grpc_port: 0,
             specification: None,
             optional_host: None,
+            zone: String::new(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: None,
+            protocol_version: 0,
+            min_protocol_version: 0,
         };
 
         let ctx = SessionContext::new();
@@ -350,7 +513,7 @@ mod test {
             &work_dir,
             ctx.runtime_env(),
             None,
-            Arc::new(LoggingMetricsCollector {}),
+            Arc::new(LoggingMetricsCollector::default()),
             2,
             None,
         );
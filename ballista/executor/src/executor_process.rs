@@ -17,11 +17,13 @@
 
 //! Ballista Executor Process
 
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::{Duration, Instant, UNIX_EPOCH};
-use std::{env, io};
+use std::{env, fmt, io};
 
 use anyhow::{Context, Result};
 use arrow_flight::flight_service_server::FlightServiceServer;
@@ -34,9 +36,12 @@ use tokio::signal;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tokio::{fs, time};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 use uuid::Uuid;
 
+use datafusion::execution::disk_manager::DiskManagerConfig;
 use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
 
@@ -44,11 +49,16 @@ use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
 use ballista_core::cache_layer::{
     medium::local_disk::LocalDiskMedium, policy::file::FileCacheLayer, CacheLayer,
 };
-use ballista_core::config::{DataCachePolicy, LogRotationPolicy, TaskSchedulingPolicy};
+use ballista_core::config::{
+    DataCachePolicy, LogRotationPolicy, TaskSchedulingPolicy, WorkDirPlacement,
+};
+use ballista_core::disk_io::{DiskIoPool, DiskIoScheduler};
 use ballista_core::error::BallistaError;
 #[cfg(not(windows))]
 use ballista_core::object_store_registry::cache::CachedBasedObjectStoreRegistry;
 use ballista_core::object_store_registry::with_object_store_registry;
+use ballista_core::plan_protection::PlanProtection;
+use ballista_core::protocol::{self, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
 use ballista_core::serde::protobuf::executor_resource::Resource;
 use ballista_core::serde::protobuf::executor_status::Status;
 use ballista_core::serde::protobuf::{
@@ -58,38 +68,71 @@ use ballista_core::serde::protobuf::{
 };
 use ballista_core::serde::BallistaCodec;
 use ballista_core::utils::{
-    create_grpc_client_connection, create_grpc_server, get_time_before,
+    backoff_with_jitter, create_grpc_client_connection, create_grpc_server,
+    get_time_before,
 };
 use ballista_core::BALLISTA_VERSION;
 
+use crate::allowed_locations::AllowedLocations;
+use crate::cgroup::{Cgroup, CgroupLimits};
+use crate::cpu_bound_executor::parse_cpu_list;
 use crate::execution_engine::ExecutionEngine;
 use crate::executor::{Executor, TasksDrainedFuture};
-use crate::executor_server::TERMINATING;
+use crate::executor_server::{SCHEDULER_RETRY_INITIAL_BACKOFF, TERMINATING};
 use crate::flight_service::BallistaFlightService;
 use crate::metrics::LoggingMetricsCollector;
+use crate::preemption::wait_for_preemption_notice;
+use crate::reloadable_config::{LogFilterReloader, ReloadableConfig};
+use crate::self_check::self_check;
+use crate::shuffle_inventory::report_shuffle_inventory;
 use crate::shutdown::Shutdown;
 use crate::shutdown::ShutdownNotifier;
+use crate::task_logs::{TaskLogLayer, TaskLogs};
 use crate::terminate;
+use crate::work_dirs::WorkDirs;
 use crate::{execution_loop, executor_server};
 
+/// Loads the reloadable settings of the executor, see [ReloadableConfig::update]
+pub type SettingsLoader = dyn Fn() -> Result<HashMap<String, String>> + Send + Sync;
+
 pub struct ExecutorProcessConfig {
     pub bind_host: String,
     pub external_host: Option<String>,
+    /// Zone the executor runs in, e.g. the availability zone of a cloud deployment
+    pub zone: Option<String>,
+    /// Rack the executor runs in
+    pub rack: Option<String>,
+    /// Instance type of the host of the executor
+    pub instance_type: Option<String>,
     pub port: u16,
     pub grpc_port: u16,
     pub scheduler_host: String,
     pub scheduler_port: u16,
     pub scheduler_connect_timeout_seconds: u16,
+    /// The maximum delay between attempts to connect or register to the scheduler
+    pub scheduler_retry_max_backoff_ms: u64,
     pub concurrent_tasks: usize,
+    /// The number of worker threads of the runtime running the tasks, the concurrent tasks
+    /// if zero
+    pub task_runtime_threads: usize,
+    /// Comma separated list of CPUs, e.g. `0-7,16-23`, to which the worker threads of the
+    /// runtime running the tasks are pinned, any CPU if empty
+    pub task_runtime_cpus: String,
     pub task_scheduling_policy: TaskSchedulingPolicy,
     pub log_dir: Option<String>,
+    /// Comma separated work dirs, a temporary directory if none
     pub work_dir: Option<String>,
+    /// The placement of the shuffle files of the tasks over the work dirs
+    pub work_dir_placement: WorkDirPlacement,
     pub special_mod_log_level: String,
     pub print_thread_info: bool,
     pub log_file_name_prefix: String,
     pub log_rotation_policy: LogRotationPolicy,
     pub job_data_ttl_seconds: u64,
     pub job_data_clean_up_interval_seconds: u64,
+    /// The interval in seconds of the reports of the shuffle files held by the executor to
+    /// the scheduler, see [crate::shuffle_inventory], disabled if zero
+    pub shuffle_inventory_report_interval_seconds: u64,
     pub data_cache_policy: Option<DataCachePolicy>,
     pub cache_dir: Option<String>,
     pub cache_capacity: u64,
@@ -99,17 +142,179 @@ pub struct ExecutorProcessConfig {
     /// The maximum size of an encoded message
     pub grpc_max_encoding_message_size: u32,
     pub executor_heartbeat_interval_seconds: u64,
+    /// Whether the status of the finished tasks is sent along with heartbeats rather than
+    /// with separate requests
+    pub heartbeat_task_status: bool,
+    /// The maximum number of metrics reported for each operator of a task, no limit if zero
+    pub max_task_metrics_per_operator: usize,
+    /// The maximum number of log lines kept for each task, task logs are not kept if zero
+    pub task_log_max_lines: usize,
+    /// The maximum number of tasks whose log lines are kept
+    pub task_log_max_tasks: usize,
+    /// Directory where the dumps of failed tasks are saved, if any
+    pub task_dump_dir: Option<String>,
+    pub task_dump_max_files: usize,
+    /// Command polled to check whether the instance received a preemption notice, see
+    /// [crate::preemption]
+    pub preemption_notice_command: Option<String>,
+    /// Instance metadata URL polled to check whether the instance received a preemption
+    /// notice
+    pub preemption_notice_url: Option<String>,
+    pub preemption_notice_poll_interval_seconds: u64,
+    /// Locations under which the plans of tasks may read and write files, no restriction if
+    /// `None`
+    pub allowed_locations: Option<Arc<AllowedLocations>>,
+    /// Keys verifying or decrypting the serialized plans received from the scheduler
+    pub plan_protection: PlanProtection,
+    /// The maximum number of concurrent requests a task sends to fetch shuffle partitions
+    pub shuffle_reader_max_requests: usize,
+    /// The maximum size in bytes of the memory of a WASM UDF processing a batch, with the
+    /// `wasm-udf` feature
+    pub wasm_udf_max_memory_bytes: usize,
+    /// The fuel of a WASM UDF processing a batch, roughly the number of WebAssembly
+    /// instructions it may run, with the `wasm-udf` feature
+    pub wasm_udf_fuel: u64,
+    /// The Python interpreter running the workers of the Python UDFs, with the `python-udf`
+    /// feature
+    pub python_udf_executable: String,
+    /// The maximum number of Python UDF worker processes, with the `python-udf` feature,
+    /// or 0 for the number of CPU cores
+    pub python_udf_workers: usize,
+    /// The maximum time in seconds a batch waits for a Python UDF worker and its
+    /// response, with the `python-udf` feature, or 0 for no limit
+    pub python_udf_timeout_seconds: u64,
+    /// The maximum number of concurrent writes of shuffle files, 0 for no limit
+    pub disk_write_io_concurrency: usize,
+    /// The maximum number of concurrent reads of shuffle files served to the other
+    /// executors, 0 for no limit
+    pub disk_read_io_concurrency: usize,
+    /// The cgroup v2 in which the executor runs on Linux, relative to `/sys/fs/cgroup`,
+    /// see [crate::cgroup]
+    pub cgroup: Option<String>,
+    /// The memory limit in bytes of the cgroup, 0 for no limit
+    pub cgroup_memory_max: u64,
+    /// The CPU limit of the cgroup in thousandths of CPUs, 0 for no limit
+    pub cgroup_cpu_max_millis: u64,
+    /// Whether only the Python UDF workers are placed in the cgroup, not the executor
+    pub cgroup_workers_only: bool,
+    /// Optional loader of the reloadable settings of the executor, keyed by parameter name,
+    /// e.g. from its configuration files. The settings are loaded and applied whenever the
+    /// executor receives a SIGHUP signal.
+    pub settings_loader: Option<Arc<SettingsLoader>>,
     /// Optional execution engine to use to execute physical plans, will default to
     /// DataFusion if none is provided.
     pub execution_engine: Option<Arc<dyn ExecutionEngine>>,
 }
 
+impl fmt::Debug for ExecutorProcessConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExecutorProcessConfig")
+            .field("bind_host", &self.bind_host)
+            .field("external_host", &self.external_host)
+            .field("zone", &self.zone)
+            .field("rack", &self.rack)
+            .field("instance_type", &self.instance_type)
+            .field("port", &self.port)
+            .field("grpc_port", &self.grpc_port)
+            .field("scheduler_host", &self.scheduler_host)
+            .field("scheduler_port", &self.scheduler_port)
+            .field(
+                "scheduler_connect_timeout_seconds",
+                &self.scheduler_connect_timeout_seconds,
+            )
+            .field(
+                "scheduler_retry_max_backoff_ms",
+                &self.scheduler_retry_max_backoff_ms,
+            )
+            .field("concurrent_tasks", &self.concurrent_tasks)
+            .field("task_runtime_threads", &self.task_runtime_threads)
+            .field("task_runtime_cpus", &self.task_runtime_cpus)
+            .field("task_scheduling_policy", &self.task_scheduling_policy)
+            .field("log_dir", &self.log_dir)
+            .field("work_dir", &self.work_dir)
+            .field("work_dir_placement", &self.work_dir_placement)
+            .field("special_mod_log_level", &self.special_mod_log_level)
+            .field("print_thread_info", &self.print_thread_info)
+            .field("log_file_name_prefix", &self.log_file_name_prefix)
+            .field("log_rotation_policy", &self.log_rotation_policy)
+            .field("job_data_ttl_seconds", &self.job_data_ttl_seconds)
+            .field(
+                "job_data_clean_up_interval_seconds",
+                &self.job_data_clean_up_interval_seconds,
+            )
+            .field(
+                "shuffle_inventory_report_interval_seconds",
+                &self.shuffle_inventory_report_interval_seconds,
+            )
+            .field("data_cache_policy", &self.data_cache_policy)
+            .field("cache_dir", &self.cache_dir)
+            .field("cache_capacity", &self.cache_capacity)
+            .field("cache_io_concurrency", &self.cache_io_concurrency)
+            .field(
+                "grpc_max_decoding_message_size",
+                &self.grpc_max_decoding_message_size,
+            )
+            .field(
+                "grpc_max_encoding_message_size",
+                &self.grpc_max_encoding_message_size,
+            )
+            .field(
+                "executor_heartbeat_interval_seconds",
+                &self.executor_heartbeat_interval_seconds,
+            )
+            .field("heartbeat_task_status", &self.heartbeat_task_status)
+            .field(
+                "max_task_metrics_per_operator",
+                &self.max_task_metrics_per_operator,
+            )
+            .field("task_log_max_lines", &self.task_log_max_lines)
+            .field("task_log_max_tasks", &self.task_log_max_tasks)
+            .field("task_dump_dir", &self.task_dump_dir)
+            .field("task_dump_max_files", &self.task_dump_max_files)
+            .field("preemption_notice_command", &self.preemption_notice_command)
+            .field("preemption_notice_url", &self.preemption_notice_url)
+            .field(
+                "preemption_notice_poll_interval_seconds",
+                &self.preemption_notice_poll_interval_seconds,
+            )
+            .field("allowed_locations", &self.allowed_locations)
+            .field("plan_protection", &self.plan_protection)
+            .field(
+                "shuffle_reader_max_requests",
+                &self.shuffle_reader_max_requests,
+            )
+            .field("wasm_udf_max_memory_bytes", &self.wasm_udf_max_memory_bytes)
+            .field("wasm_udf_fuel", &self.wasm_udf_fuel)
+            .field("python_udf_executable", &self.python_udf_executable)
+            .field("python_udf_workers", &self.python_udf_workers)
+            .field(
+                "python_udf_timeout_seconds",
+                &self.python_udf_timeout_seconds,
+            )
+            .field("disk_write_io_concurrency", &self.disk_write_io_concurrency)
+            .field("disk_read_io_concurrency", &self.disk_read_io_concurrency)
+            .field("cgroup", &self.cgroup)
+            .field("cgroup_memory_max", &self.cgroup_memory_max)
+            .field("cgroup_cpu_max_millis", &self.cgroup_cpu_max_millis)
+            .field("cgroup_workers_only", &self.cgroup_workers_only)
+            .field("settings_loader", &self.settings_loader.is_some())
+            .field("execution_engine", &self.execution_engine.is_some())
+            .finish()
+    }
+}
+
 pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<()> {
     let rust_log = env::var(EnvFilter::DEFAULT_ENV);
     let log_filter =
         EnvFilter::new(rust_log.unwrap_or(opt.special_mod_log_level.clone()));
+    let task_logs = Arc::new(TaskLogs::new(
+        opt.task_log_max_lines,
+        opt.task_log_max_tasks,
+    ));
     // File layer
-    if let Some(log_dir) = opt.log_dir.clone() {
+    let log_filter_reloader: LogFilterReloader = if let Some(log_dir) =
+        opt.log_dir.clone()
+    {
         let log_file = match opt.log_rotation_policy {
             LogRotationPolicy::Minutely => {
                 tracing_appender::rolling::minutely(log_dir, &opt.log_file_name_prefix)
@@ -124,23 +329,78 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
                 tracing_appender::rolling::never(log_dir, &opt.log_file_name_prefix)
             }
         };
-        tracing_subscriber::fmt()
+        let subscriber = tracing_subscriber::fmt()
             .with_ansi(false)
             .with_thread_names(opt.print_thread_info)
             .with_thread_ids(opt.print_thread_info)
             .with_writer(log_file)
             .with_env_filter(log_filter)
+            .with_filter_reloading();
+        let handle = subscriber.reload_handle();
+        subscriber
+            .finish()
+            .with(TaskLogLayer::new(task_logs.clone()))
             .init();
+        Box::new(move |filter| handle.reload(filter).map_err(log_reload_error))
     } else {
         // Console layer
-        tracing_subscriber::fmt()
+        let subscriber = tracing_subscriber::fmt()
             .with_ansi(false)
             .with_thread_names(opt.print_thread_info)
             .with_thread_ids(opt.print_thread_info)
             .with_writer(io::stdout)
             .with_env_filter(log_filter)
+            .with_filter_reloading();
+        let handle = subscriber.reload_handle();
+        subscriber
+            .finish()
+            .with(TaskLogLayer::new(task_logs.clone()))
             .init();
-    }
+        Box::new(move |filter| handle.reload(filter).map_err(log_reload_error))
+    };
+
+    #[cfg(feature = "wasm-udf")]
+    ballista_core::wasm_udf::set_wasm_udf_limits(
+        ballista_core::wasm_udf::WasmUdfLimits {
+            max_memory_bytes: opt.wasm_udf_max_memory_bytes,
+            fuel: opt.wasm_udf_fuel,
+        },
+    )?;
+
+    let cgroup = match &opt.cgroup {
+        Some(path) => {
+            let cgroup = Cgroup::try_new(
+                path,
+                CgroupLimits {
+                    memory_max: opt.cgroup_memory_max,
+                    cpu_max_millis: opt.cgroup_cpu_max_millis,
+                },
+            )?;
+            // the child processes of the executor inherit its cgroup
+            if !opt.cgroup_workers_only {
+                cgroup.add_process(std::process::id())?;
+            }
+            Some(Arc::new(cgroup))
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "python-udf")]
+    ballista_core::python_udf::set_python_udf_config(
+        ballista_core::python_udf::PythonUdfConfig {
+            python: opt.python_udf_executable.clone(),
+            max_workers: match opt.python_udf_workers {
+                0 => ballista_core::python_udf::PythonUdfConfig::default().max_workers,
+                workers => workers,
+            },
+            cgroup_procs: cgroup
+                .as_ref()
+                .filter(|_| opt.cgroup_workers_only)
+                .map(|cgroup| cgroup.procs_file()),
+            call_timeout: (opt.python_udf_timeout_seconds > 0)
+                .then(|| Duration::from_secs(opt.python_udf_timeout_seconds)),
+        },
+    )?;
 
     let addr = format!("{}:{}", opt.bind_host, opt.port);
     let addr = addr
@@ -151,13 +411,17 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     let scheduler_port = opt.scheduler_port;
     let scheduler_url = format!("http://{scheduler_host}:{scheduler_port}");
 
-    let work_dir = opt.work_dir.clone().unwrap_or(
-        TempDir::new()?
-            .into_path()
-            .into_os_string()
-            .into_string()
-            .unwrap(),
-    );
+    let work_dirs = match &opt.work_dir {
+        Some(work_dir) => WorkDirs::parse(work_dir, opt.work_dir_placement)?,
+        None => WorkDirs::single(
+            TempDir::new()?
+                .into_path()
+                .into_os_string()
+                .into_string()
+                .unwrap(),
+        ),
+    };
+    let work_dir = work_dirs.primary().to_owned();
 
     let concurrent_tasks = if opt.concurrent_tasks == 0 {
         // use all available cores if no concurrency level is specified
@@ -167,9 +431,17 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     };
 
     info!("Running with config:");
-    info!("work_dir: {}", work_dir);
+    info!("work_dirs: {:?}", work_dirs.dirs());
+    info!("work_dir_placement: {:?}", opt.work_dir_placement);
     info!("concurrent_tasks: {}", concurrent_tasks);
 
+    let capabilities = self_check(work_dirs.dirs())?;
+
+    let task_runtime_cpus = parse_cpu_list(&opt.task_runtime_cpus)?;
+    if !task_runtime_cpus.is_empty() {
+        info!("task_runtime_cpus: {:?}", task_runtime_cpus);
+    }
+
     // assign this executor an unique ID
     let executor_id = Uuid::new_v4().to_string();
     let executor_meta = ExecutorRegistration {
@@ -185,9 +457,18 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
                 resource: Some(Resource::TaskSlots(concurrent_tasks as u32)),
             }],
         }),
+        zone: opt.zone.clone().unwrap_or_default(),
+        rack: opt.rack.clone().unwrap_or_default(),
+        instance_type: opt.instance_type.clone().unwrap_or_default(),
+        capabilities: Some(capabilities.clone().into()),
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: MIN_PROTOCOL_VERSION,
     };
 
-    let config = RuntimeConfig::new().with_temp_file_path(work_dir.clone());
+    // the spill files of the operators are spread over the work dirs as well
+    let config = RuntimeConfig::new().with_disk_manager(DiskManagerConfig::NewSpecified(
+        work_dirs.dirs().iter().map(PathBuf::from).collect(),
+    ));
     let runtime = {
         let config = with_object_store_registry(config.clone());
         Arc::new(RuntimeEnv::new(config).map_err(|_| {
@@ -195,6 +476,10 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
         })?)
     };
 
+    #[allow(unused_mut)]
+    let mut reloadable_config = ReloadableConfig::new(opt.shuffle_reader_max_requests)
+        .with_log_filter_reloader(log_filter_reloader);
+
     // Set the object store registry
     #[cfg(not(windows))]
     let runtime_with_data_cache = {
@@ -215,6 +500,7 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
                     }
                 });
         if let Some(cache_layer) = cache_layer {
+            reloadable_config = reloadable_config.with_data_cache(cache_layer.clone());
             let registry = Arc::new(CachedBasedObjectStoreRegistry::new(
                 runtime.object_store_registry.clone(),
                 cache_layer,
@@ -232,20 +518,46 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     #[cfg(windows)]
     let runtime_with_data_cache = { None };
 
-    let metrics_collector = Arc::new(LoggingMetricsCollector::default());
+    let metrics_collector = Arc::new(
+        LoggingMetricsCollector::default()
+            .with_max_metrics_per_operator(opt.max_task_metrics_per_operator),
+    );
 
-    let executor = Arc::new(Executor::new(
-        executor_meta,
-        &work_dir,
-        runtime,
-        runtime_with_data_cache,
-        metrics_collector,
-        concurrent_tasks,
-        opt.execution_engine.clone(),
-    ));
+    let reloadable_config = Arc::new(reloadable_config);
+    let executor = Arc::new(
+        Executor::new(
+            executor_meta,
+            &work_dir,
+            runtime,
+            runtime_with_data_cache,
+            metrics_collector,
+            concurrent_tasks,
+            opt.execution_engine.clone(),
+        )
+        .with_reloadable_config(reloadable_config.clone())
+        .with_task_logs(task_logs)
+        .with_task_dump_dir(opt.task_dump_dir.clone().map(PathBuf::from))
+        .with_task_dump_max_files(opt.task_dump_max_files)
+        .with_allowed_locations(opt.allowed_locations.clone())
+        .with_disk_io(DiskIoScheduler::new(
+            opt.disk_write_io_concurrency,
+            opt.disk_read_io_concurrency,
+        ))
+        .with_task_runtime(opt.task_runtime_threads, task_runtime_cpus)
+        .with_cgroup(cgroup)
+        .with_work_dirs(work_dirs),
+    );
 
-    let connect_timeout = opt.scheduler_connect_timeout_seconds as u64;
-    let connection = if connect_timeout == 0 {
+    if let Some(settings_loader) = opt.settings_loader.clone() {
+        tokio::spawn(reload_settings_on_hangup(
+            settings_loader,
+            reloadable_config,
+        ));
+    }
+
+    let connect_timeout =
+        Duration::from_secs(opt.scheduler_connect_timeout_seconds as u64);
+    let connection = if connect_timeout.is_zero() {
         create_grpc_client_connection(scheduler_url)
             .await
             .context("Could not connect to scheduler")
@@ -253,43 +565,49 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
         // this feature was added to support docker-compose so that we can have the executor
         // wait for the scheduler to start, or at least run for 10 seconds before failing so
         // that docker-compose's restart policy will restart the container.
-        let start_time = Instant::now().elapsed().as_secs();
-        let mut x = None;
-        while x.is_none()
-            && Instant::now().elapsed().as_secs() - start_time < connect_timeout
-        {
+        let max_backoff = Duration::from_millis(opt.scheduler_retry_max_backoff_ms);
+        let start_time = Instant::now();
+        let mut attempt = 0;
+        loop {
             match create_grpc_client_connection(scheduler_url.clone())
                 .await
                 .context("Could not connect to scheduler")
             {
                 Ok(conn) => {
                     info!("Connected to scheduler at {}", scheduler_url);
-                    x = Some(conn);
+                    break Ok(conn);
                 }
                 Err(e) => {
+                    let backoff = backoff_with_jitter(
+                        attempt,
+                        SCHEDULER_RETRY_INITIAL_BACKOFF,
+                        max_backoff,
+                    );
+                    if start_time.elapsed() + backoff > connect_timeout {
+                        break Err(BallistaError::General(format!(
+                            "Timed out attempting to connect to scheduler at {scheduler_url} ({e})"
+                        ))
+                        .into());
+                    }
                     warn!(
-                        "Failed to connect to scheduler at {} ({}); retrying ...",
-                        scheduler_url, e
+                        "Failed to connect to scheduler at {} ({}); retrying in {:?} ...",
+                        scheduler_url, e, backoff
                     );
-                    std::thread::sleep(time::Duration::from_millis(500));
+                    time::sleep(backoff).await;
+                    attempt += 1;
                 }
             }
         }
-        match x {
-            Some(conn) => Ok(conn),
-            _ => Err(BallistaError::General(format!(
-                "Timed out attempting to connect to scheduler at {scheduler_url}"
-            ))
-            .into()),
-        }
     }?;
 
     let mut scheduler = SchedulerGrpcClient::new(connection)
         .max_encoding_message_size(opt.grpc_max_encoding_message_size as usize)
         .max_decoding_message_size(opt.grpc_max_decoding_message_size as usize);
+    // fail fast when the scheduler speaks no protocol version of this executor
+    protocol::handshake(&mut scheduler).await?;
 
     let default_codec: BallistaCodec<LogicalPlanNode, PhysicalPlanNode> =
-        BallistaCodec::default();
+        BallistaCodec::default().with_plan_protection(opt.plan_protection.clone());
 
     let scheduler_policy = opt.task_scheduling_policy;
     let job_data_ttl_seconds = opt.job_data_ttl_seconds;
@@ -297,27 +615,55 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     // Graceful shutdown notification
     let shutdown_noti = ShutdownNotifier::new();
 
+    if opt.shuffle_inventory_report_interval_seconds > 0 {
+        let mut interval_time = time::interval(Duration::from_secs(
+            opt.shuffle_inventory_report_interval_seconds,
+        ));
+        let mut inventory_shutdown = shutdown_noti.subscribe_for_shutdown();
+        let mut scheduler = scheduler.clone();
+        let executor_id = executor.metadata.id.clone();
+        let work_dirs = executor.work_dirs.clone();
+        tokio::spawn(async move {
+            // As long as the shutdown notification has not been received
+            while !inventory_shutdown.is_shutdown() {
+                tokio::select! {
+                    _ = interval_time.tick() => {
+                        if let Err(e) = report_shuffle_inventory(&mut scheduler, &executor_id, work_dirs.dirs()).await {
+                            warn!("Ballista executor fail to report shuffle inventory {:?}", e)
+                        }
+                    },
+                    _ = inventory_shutdown.recv() => return,
+                };
+            }
+        });
+    }
+
     if opt.job_data_clean_up_interval_seconds > 0 {
         let mut interval_time =
             time::interval(Duration::from_secs(opt.job_data_clean_up_interval_seconds));
         let mut shuffle_cleaner_shutdown = shutdown_noti.subscribe_for_shutdown();
         let shuffle_cleaner_complete = shutdown_noti.shutdown_complete_tx.clone();
+        let work_dirs = executor.work_dirs.clone();
         tokio::spawn(async move {
             // As long as the shutdown notification has not been received
             while !shuffle_cleaner_shutdown.is_shutdown() {
                 tokio::select! {
                     _ = interval_time.tick() => {
-                            if let Err(e) = clean_shuffle_data_loop(&work_dir, job_data_ttl_seconds).await
-                        {
-                            error!("Ballista executor fail to clean_shuffle_data {:?}", e)
+                        for work_dir in work_dirs.dirs() {
+                            if let Err(e) = clean_shuffle_data_loop(work_dir, job_data_ttl_seconds).await
+                            {
+                                error!("Ballista executor fail to clean_shuffle_data {:?}", e)
+                            }
                         }
                         },
                     _ = shuffle_cleaner_shutdown.recv() => {
-                        if let Err(e) = clean_all_shuffle_data(&work_dir).await
-                        {
-                            error!("Ballista executor fail to clean_shuffle_data {:?}", e)
-                        } else {
-                            info!("Shuffle data cleaned.");
+                        for work_dir in work_dirs.dirs() {
+                            if let Err(e) = clean_all_shuffle_data(work_dir).await
+                            {
+                                error!("Ballista executor fail to clean_shuffle_data {:?}", e)
+                            } else {
+                                info!("Shuffle data of {} cleaned.", work_dir);
+                            }
                         }
                         drop(shuffle_cleaner_complete);
                         return;
@@ -358,6 +704,7 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     };
     service_handlers.push(tokio::spawn(flight_server_run(
         addr,
+        executor.disk_io.read.clone(),
         shutdown_noti.subscribe_for_shutdown(),
     )));
 
@@ -366,6 +713,12 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     // Concurrently run the service checking and listen for the `shutdown` signal and wait for the stop request coming.
     // The check_services runs until an error is encountered, so under normal circumstances, this `select!` statement runs
     // until the `shutdown` signal is received or a stop request is coming.
+    let preemption_notice = wait_for_preemption_notice(
+        opt.preemption_notice_command.clone(),
+        opt.preemption_notice_url.clone(),
+        Duration::from_secs(opt.preemption_notice_poll_interval_seconds.max(1)),
+    );
+    let mut preempted = false;
     let (notify_scheduler, stop_reason) = tokio::select! {
         service_val = check_services(&mut service_handlers) => {
             let msg = format!("executor services stopped with reason {service_val:?}");
@@ -382,6 +735,12 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
              info!("{:?}", msg);
             (true, msg)
         },
+        notice = preemption_notice => {
+            let msg = format!("executor received a {notice}");
+            info!("{:?}", msg);
+            preempted = true;
+            (true, msg)
+        },
         _ = stop_recv.recv() => {
             (false, "".to_string())
         },
@@ -414,7 +773,15 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
                             resource: Some(Resource::TaskSlots(concurrent_tasks as u32)),
                         }],
                     }),
+                    zone: opt.zone.clone().unwrap_or_default(),
+                    rack: opt.rack.clone().unwrap_or_default(),
+                    instance_type: opt.instance_type.clone().unwrap_or_default(),
+                    capabilities: Some(capabilities.into()),
+                    protocol_version: PROTOCOL_VERSION,
+                    min_protocol_version: MIN_PROTOCOL_VERSION,
                 }),
+                task_status: vec![],
+                running_jobs: vec![],
             })
             .await
         {
@@ -426,6 +793,7 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
             .executor_stopped(ExecutorStoppedParams {
                 executor_id,
                 reason: stop_reason,
+                preempted,
             })
             .await
         {
@@ -458,12 +826,36 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     Ok(())
 }
 
+fn log_reload_error(e: tracing_subscriber::reload::Error) -> BallistaError {
+    BallistaError::Internal(format!("Failed to reload the log filter: {e}"))
+}
+
+/// Load and apply the reloadable settings whenever a SIGHUP signal is received
+async fn reload_settings_on_hangup(
+    settings_loader: Arc<SettingsLoader>,
+    reloadable_config: Arc<ReloadableConfig>,
+) {
+    loop {
+        if let Err(e) = terminate::sig_hup().await {
+            error!("Failed to listen for SIGHUP signals: {:?}", e);
+            return;
+        }
+        info!("Reloading executor settings");
+        let result = settings_loader()
+            .and_then(|settings| reloadable_config.update(&settings).map_err(Into::into));
+        if let Err(e) = result {
+            error!("Failed to reload executor settings: {:?}", e);
+        }
+    }
+}
+
 // Arrow flight service
 async fn flight_server_run(
     addr: SocketAddr,
+    io_pool: Arc<DiskIoPool>,
     mut grpc_shutdown: Shutdown,
 ) -> Result<(), BallistaError> {
-    let service = BallistaFlightService::new();
+    let service = BallistaFlightService::new().with_io_pool(io_pool);
     let server = FlightServiceServer::new(service);
     info!(
         "Ballista v{} Rust Executor Flight Server listening on {:?}",
@@ -17,27 +17,31 @@
 
 use ballista_core::BALLISTA_VERSION;
 use std::collections::HashMap;
-use std::convert::TryInto;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
 
 use log::{debug, error, info, warn};
+use parking_lot::Mutex;
 use tonic::transport::Channel;
 use tonic::{Request, Response, Status};
 
 use ballista_core::config::BALLISTA_DATA_CACHE_ENABLED;
 use ballista_core::error::BallistaError;
+use ballista_core::execution_plans::{ShuffleReaderOptions, ShuffleWriterOptions};
 use ballista_core::serde::protobuf::{
     executor_grpc_server::{ExecutorGrpc, ExecutorGrpcServer},
     executor_metric, executor_status,
     scheduler_grpc_client::SchedulerGrpcClient,
     CancelTasksParams, CancelTasksResult, ExecutorMetric, ExecutorStatus,
-    HeartBeatParams, LaunchMultiTaskParams, LaunchMultiTaskResult, LaunchTaskParams,
-    LaunchTaskResult, RegisterExecutorParams, RemoveJobDataParams, RemoveJobDataResult,
-    StopExecutorParams, StopExecutorResult, TaskStatus, UpdateTaskStatusParams,
+    GetTaskLogsParams, GetTaskLogsResult, HeartBeatParams, LaunchMultiTaskParams,
+    LaunchMultiTaskResult, LaunchTaskParams, LaunchTaskResult, RegisterExecutorParams,
+    RemoveJobDataParams, RemoveJobDataResult, ReplicateShuffleDataParams,
+    ReplicateShuffleDataResult, StopExecutorParams, StopExecutorResult, TaskStatus,
+    UpdateExecutorConfigParams, UpdateExecutorConfigResult, UpdateJobSchedulerParams,
+    UpdateJobSchedulerResult, UpdateTaskStatusParams,
 };
 use ballista_core::serde::scheduler::from_proto::{
     get_task_definition, get_task_definition_vec,
@@ -45,7 +49,9 @@ use ballista_core::serde::scheduler::from_proto::{
 use ballista_core::serde::scheduler::PartitionId;
 use ballista_core::serde::scheduler::TaskDefinition;
 use ballista_core::serde::BallistaCodec;
-use ballista_core::utils::{create_grpc_client_connection, create_grpc_server};
+use ballista_core::utils::{
+    backoff_with_jitter, create_grpc_client_connection, create_grpc_server,
+};
 use dashmap::DashMap;
 use datafusion::config::ConfigOptions;
 use datafusion::execution::TaskContext;
@@ -54,15 +60,20 @@ use datafusion_proto::{logical_plan::AsLogicalPlan, physical_plan::AsExecutionPl
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::task::JoinHandle;
 
-use crate::cpu_bound_executor::DedicatedExecutor;
 use crate::executor::Executor;
 use crate::executor_process::ExecutorProcessConfig;
+use crate::launched_tasks::LaunchedTasks;
+use crate::shuffle_replication::replicate_partition;
 use crate::shutdown::ShutdownNotifier;
-use crate::{as_task_status, TaskExecutionTimes};
+use crate::task_dump::{dump_failed_task, encode_task_definition};
+use crate::{as_task_status, task_config_options, TaskExecutionTimes};
 
 type ServerHandle = JoinHandle<Result<(), BallistaError>>;
 type SchedulerClients = Arc<DashMap<String, SchedulerGrpcClient<Channel>>>;
 
+/// Delay before the first retry to connect or register to the scheduler
+pub(crate) const SCHEDULER_RETRY_INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+
 /// Wrap TaskDefinition with its curator scheduler id for task update to its specific curator scheduler later
 #[derive(Debug)]
 struct CuratorTaskDefinition {
@@ -101,6 +112,7 @@ pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
         codec,
         config.grpc_max_encoding_message_size as usize,
         config.grpc_max_decoding_message_size as usize,
+        config.heartbeat_task_status,
     );
 
     // 1. Start executor grpc service
@@ -132,7 +144,14 @@ pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
     // 2. Do executor registration
     // TODO the executor registration should happen only after the executor grpc server started.
     let executor_server = Arc::new(executor_server);
-    match register_executor(&mut scheduler, executor.clone()).await {
+    match register_executor_with_retry(
+        &mut scheduler,
+        executor.clone(),
+        Duration::from_secs(config.scheduler_connect_timeout_seconds as u64),
+        Duration::from_millis(config.scheduler_retry_max_backoff_ms),
+    )
+    .await
+    {
         Ok(_) => {
             info!("Executor registration succeed");
         }
@@ -159,6 +178,39 @@ pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
     Ok(server)
 }
 
+/// Register the executor to the scheduler, retrying with exponential backoff until `timeout`
+/// elapsed, so that the executor survives a scheduler being briefly unavailable
+async fn register_executor_with_retry(
+    scheduler: &mut SchedulerGrpcClient<Channel>,
+    executor: Arc<Executor>,
+    timeout: Duration,
+    max_backoff: Duration,
+) -> Result<(), BallistaError> {
+    let start_time = Instant::now();
+    let mut attempt = 0;
+    loop {
+        match register_executor(scheduler, executor.clone()).await {
+            Ok(_) => return Ok(()),
+            Err(error) => {
+                let backoff = backoff_with_jitter(
+                    attempt,
+                    SCHEDULER_RETRY_INITIAL_BACKOFF,
+                    max_backoff,
+                );
+                if start_time.elapsed() + backoff > timeout {
+                    return Err(error);
+                }
+                warn!(
+                    "Executor registration failed due to: {}; retrying in {:?} ...",
+                    error, backoff
+                );
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
 #[allow(clippy::clone_on_copy)]
 async fn register_executor(
     scheduler: &mut SchedulerGrpcClient<Channel>,
@@ -186,8 +238,16 @@ pub struct ExecutorServer<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPl
     codec: BallistaCodec<T, U>,
     scheduler_to_register: SchedulerGrpcClient<Channel>,
     schedulers: SchedulerClients,
+    /// The schedulers which took over jobs from the schedulers which launched their tasks,
+    /// keyed by job
+    job_schedulers: Arc<DashMap<String, String>>,
     grpc_max_encoding_message_size: usize,
     grpc_max_decoding_message_size: usize,
+    /// Whether the task status is sent along with heartbeats rather than with UpdateTaskStatus
+    heartbeat_task_status: bool,
+    /// When the last heartbeat carrying task status was sent
+    last_task_status_heartbeat: Arc<Mutex<Option<Instant>>>,
+    launched_tasks: Arc<Mutex<LaunchedTasks>>,
 }
 
 #[derive(Clone)]
@@ -214,6 +274,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         codec: BallistaCodec<T, U>,
         grpc_max_encoding_message_size: usize,
         grpc_max_decoding_message_size: usize,
+        heartbeat_task_status: bool,
     ) -> Self {
         Self {
             _start_time: SystemTime::now()
@@ -225,8 +286,12 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             codec,
             scheduler_to_register,
             schedulers: Default::default(),
+            job_schedulers: Default::default(),
             grpc_max_encoding_message_size,
             grpc_max_decoding_message_size,
+            heartbeat_task_status,
+            last_task_status_heartbeat: Default::default(),
+            launched_tasks: Default::default(),
         }
     }
 
@@ -254,29 +319,28 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         }
     }
 
+    /// The scheduler to send the status of the tasks of a job to, the scheduler which
+    /// launched the task unless the job was handed off to another scheduler since
+    fn job_scheduler(&self, job_id: &str, scheduler_id: String) -> String {
+        self.job_schedulers
+            .get(job_id)
+            .map(|scheduler_id| scheduler_id.clone())
+            .unwrap_or(scheduler_id)
+    }
+
     /// 1. First Heartbeat to its registration scheduler, if successful then return; else go next.
     /// 2. Heartbeat to schedulers which has launching tasks to this executor until one succeeds
     async fn heartbeat(&self) {
-        let status = if TERMINATING.load(Ordering::Acquire) {
-            executor_status::Status::Terminating(String::default())
-        } else {
-            executor_status::Status::Active(String::default())
-        };
-
-        let heartbeat_params = HeartBeatParams {
-            executor_id: self.executor.metadata.id.clone(),
-            metrics: self.get_executor_metrics(),
-            status: Some(ExecutorStatus {
-                status: Some(status),
-            }),
-            metadata: Some(self.executor.metadata.clone()),
-        };
+        let heartbeat_params = self.heartbeat_params(vec![]);
         let mut scheduler = self.scheduler_to_register.clone();
         match scheduler
             .heart_beat_from_executor(heartbeat_params.clone())
             .await
         {
-            Ok(_) => {
+            Ok(result) => {
+                if result.into_inner().reregister {
+                    self.reregister(&mut scheduler).await;
+                }
                 return;
             }
             Err(e) => {
@@ -295,7 +359,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
                 .heart_beat_from_executor(heartbeat_params.clone())
                 .await
             {
-                Ok(_) => {
+                Ok(result) => {
+                    if result.into_inner().reregister {
+                        self.reregister(scheduler).await;
+                    }
                     break;
                 }
                 Err(e) => {
@@ -308,6 +375,132 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         }
     }
 
+    fn heartbeat_params(&self, task_status: Vec<TaskStatus>) -> HeartBeatParams {
+        let status = if TERMINATING.load(Ordering::Acquire) {
+            executor_status::Status::Terminating(String::default())
+        } else {
+            executor_status::Status::Active(String::default())
+        };
+
+        HeartBeatParams {
+            executor_id: self.executor.metadata.id.clone(),
+            metrics: self.get_executor_metrics(),
+            status: Some(ExecutorStatus {
+                status: Some(status),
+            }),
+            metadata: Some(self.executor.metadata.clone()),
+            task_status,
+            running_jobs: self.executor.running_jobs(),
+        }
+    }
+
+    /// Whether a heartbeat carrying task status was sent within `interval`, in which case
+    /// the periodic heartbeat is not needed
+    fn sent_task_status_heartbeat_within(&self, interval: Duration) -> bool {
+        let sent_at = *self.last_task_status_heartbeat.lock();
+        sent_at.map_or(false, |sent_at| sent_at.elapsed() < interval)
+    }
+
+    /// Send the status of tasks to a scheduler, along with a heartbeat if enabled
+    async fn report_task_status(
+        &self,
+        scheduler: &mut SchedulerGrpcClient<Channel>,
+        tasks_status: Vec<TaskStatus>,
+    ) -> Result<(), BallistaError> {
+        if !self.heartbeat_task_status {
+            scheduler
+                .update_task_status(UpdateTaskStatusParams {
+                    executor_id: self.executor.metadata.id.clone(),
+                    task_status: tasks_status,
+                })
+                .await?;
+            return Ok(());
+        }
+        let result = scheduler
+            .heart_beat_from_executor(self.heartbeat_params(tasks_status))
+            .await?
+            .into_inner();
+        *self.last_task_status_heartbeat.lock() = Some(Instant::now());
+        debug!(
+            "Sent task status with heartbeat, {} task slots available",
+            result.available_task_slots
+        );
+        if result.reregister {
+            self.reregister(scheduler).await;
+        }
+        Ok(())
+    }
+
+    /// Register again to a scheduler which asked for it in a heartbeat result, e.g. because
+    /// it has declared this executor dead. Failures are retried on the next heartbeat.
+    async fn reregister(&self, scheduler: &mut SchedulerGrpcClient<Channel>) {
+        // A terminating executor should not be offered new tasks
+        if TERMINATING.load(Ordering::Acquire) {
+            return;
+        }
+        info!("Scheduler requested executor re-registration");
+        match register_executor(scheduler, self.executor.clone()).await {
+            Ok(_) => {
+                info!("Executor re-registration succeed");
+            }
+            Err(error) => {
+                warn!("Executor re-registration failed due to: {}", error);
+            }
+        }
+    }
+
+    /// Reserve the launch token of a task attempt to launch it, unless it was already
+    /// launched, e.g. by a launch request retried by the scheduler or by the scheduler
+    /// taking over the job, in which case the original status of the task is reported
+    /// again if it has finished. Returns whether the task attempt is to be launched.
+    async fn reserve_launch(&self, scheduler_id: &str, launch_token: &str) -> bool {
+        // tasks launched by older schedulers have no launch token
+        if launch_token.is_empty() {
+            return true;
+        }
+        let launched = self.launched_tasks.lock().reserve(launch_token);
+        let Some(task_status) = launched else {
+            return true;
+        };
+        info!("Ignoring duplicate launch of task {launch_token}");
+        if let Some(task_status) = task_status {
+            self.executor_env
+                .tx_task_status
+                .send(CuratorTaskStatus {
+                    scheduler_id: scheduler_id.to_owned(),
+                    task_status,
+                })
+                .await
+                .unwrap();
+        }
+        false
+    }
+
+    /// Release the launch token of a task attempt which failed to be queued to run
+    fn release_launch(&self, launch_token: &str) {
+        if !launch_token.is_empty() {
+            self.launched_tasks.lock().release(launch_token);
+        }
+    }
+
+    /// Queue a task to run, releasing its launch token if the task queue is closed
+    async fn queue_task(
+        &self,
+        scheduler_id: &str,
+        task: TaskDefinition,
+    ) -> Result<(), Status> {
+        let launch_token = task.launch_token.clone();
+        let task = CuratorTaskDefinition {
+            scheduler_id: scheduler_id.to_owned(),
+            task,
+        };
+        if self.executor_env.tx_task.send(task).await.is_err() {
+            self.release_launch(&launch_token);
+            return Err(Status::unavailable("The executor no longer runs tasks"));
+        }
+        Ok(())
+    }
+
     /// This method should not return Err. If task fails, a failure task status should be sent
     /// to the channel to notify the scheduler.
     async fn run_task(&self, task_identity: String, curator_task: CuratorTaskDefinition) {
@@ -317,6 +510,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             .as_millis() as u64;
         info!("Start to run task {}", task_identity);
         let task = curator_task.task;
+        // the definition of the task is kept to dump it if the task fails
+        let task_to_dump = self.executor.task_dump_dir.as_ref().map(|_| task.clone());
 
         let task_id = task.task_id;
         let job_id = task.job_id;
@@ -324,6 +519,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         let stage_attempt_num = task.stage_attempt_num;
         let partition_id = task.partition_id;
         let plan = task.plan;
+        let launch_token = task.launch_token;
+        let allowed_locations = self.executor.check_allowed_locations(&plan);
 
         let part = PartitionId {
             job_id: job_id.clone(),
@@ -338,23 +535,37 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
                 job_id.clone(),
                 stage_id,
                 plan,
-                &self.executor.work_dir,
+                self.executor.work_dirs.next_dir(),
             )
             .unwrap();
 
-        let task_context = {
+        let (task_context, invalid_config) = {
             let task_props = task.props;
             let data_cache = task_props
                 .get(BALLISTA_DATA_CACHE_ENABLED)
                 .map(|data_cache| data_cache.parse().unwrap_or(false))
                 .unwrap_or(false);
-            let mut config = ConfigOptions::new();
-            for (k, v) in task_props.iter() {
-                if let Err(e) = config.set(k, v) {
-                    debug!("Fail to set session config for ({},{}): {:?}", k, v, e);
-                }
-            }
-            let session_config = SessionConfig::from(config);
+            let reader_options = ShuffleReaderOptions::from_props(
+                &task_props,
+                self.executor
+                    .reloadable_config
+                    .shuffle_reader_max_requests(),
+                self.get_scheduler_client(&curator_task.scheduler_id)
+                    .await
+                    .ok(),
+            );
+            let writer_options = ShuffleWriterOptions::from_props(
+                &task_props,
+                Some(self.executor.disk_io.write.clone()),
+            );
+            // a task with invalid options is reported failed instead of running
+            let (config, invalid_config) = match task_config_options(&task_props) {
+                Ok(config) => (config, Ok(())),
+                Err(e) => (ConfigOptions::new(), Err(e)),
+            };
+            let session_config = SessionConfig::from(config)
+                .with_extension(Arc::new(reader_options))
+                .with_extension(Arc::new(writer_options));
 
             let function_registry = task.function_registry;
             if data_cache {
@@ -362,7 +573,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             }
             let runtime = self.executor.get_runtime(data_cache);
 
-            Arc::new(TaskContext::new(
+            let task_context = Arc::new(TaskContext::new(
                 Some(task_identity.clone()),
                 task.session_id,
                 session_config,
@@ -370,31 +581,56 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
                 function_registry.aggregate_functions.clone(),
                 function_registry.window_functions.clone(),
                 runtime,
-            ))
+            ));
+            (task_context, invalid_config)
         };
 
         info!("Start to execute shuffle write for task {}", task_identity);
 
-        let execution_result = self
-            .executor
-            .execute_query_stage(
-                task_id,
-                part.clone(),
-                query_stage_exec.clone(),
-                task_context,
-            )
-            .await;
+        let execution_result = match invalid_config.and(allowed_locations) {
+            Ok(()) => {
+                self.executor
+                    .execute_query_stage(
+                        task_id,
+                        part.clone(),
+                        query_stage_exec.clone(),
+                        task_context,
+                    )
+                    .await
+            }
+            Err(e) => {
+                warn!("Rejected task {}: {}", task_identity, e);
+                Err(e)
+            }
+        };
         info!("Done with task {}", task_identity);
         debug!("Statistics: {:?}", execution_result);
 
-        let plan_metrics = query_stage_exec.collect_plan_metrics();
-        let operator_metrics = match plan_metrics
-            .into_iter()
-            .map(|m| m.try_into())
-            .collect::<Result<Vec<_>, BallistaError>>()
+        if let (Err(e), Some(task_to_dump)) = (&execution_result, task_to_dump) {
+            match encode_task_definition(&task_to_dump, &self.codec) {
+                Ok(encoded) => {
+                    dump_failed_task(&self.executor, encoded, &task_to_dump.plan, e)
+                }
+                Err(encode_error) => warn!(
+                    "Failed to encode failed task {} to dump it: {:?}",
+                    task_identity, encode_error
+                ),
+            }
+        }
+
+        let operator_metrics = match self
+            .executor
+            .metrics_collector
+            .operator_metrics(query_stage_exec.as_ref())
         {
             Ok(metrics) => Some(metrics),
-            Err(_) => None,
+            Err(e) => {
+                warn!(
+                    "Fail to collect metrics for task {}: {:?}",
+                    task_identity, e
+                );
+                None
+            }
         };
         let executor_id = &self.executor.metadata.id;
 
@@ -417,6 +653,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             operator_metrics,
             task_execution_times,
         );
+        if !launch_token.is_empty() {
+            self.launched_tasks
+                .lock()
+                .finish(&launch_token, &task_status);
+        }
 
         let scheduler_id = curator_task.scheduler_id;
         let task_status_sender = self.executor_env.tx_task_status.clone();
@@ -429,12 +670,32 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             .unwrap();
     }
 
-    // TODO populate with real metrics
     fn get_executor_metrics(&self) -> Vec<ExecutorMetric> {
-        let available_memory = ExecutorMetric {
-            metric: Some(executor_metric::Metric::AvailableMemory(u64::MAX)),
+        let cgroup_metrics = self.executor.cgroup.as_ref().and_then(|cgroup| {
+            cgroup
+                .metrics()
+                .map_err(|e| warn!("Failed to get the metrics of the cgroup: {e}"))
+                .ok()
+        });
+        // the memory is only known to be bounded by the limit of the cgroup
+        let available_memory = match &cgroup_metrics {
+            Some(cgroup) if cgroup.memory_max > 0 => {
+                cgroup.memory_max.saturating_sub(cgroup.memory_current)
+            }
+            _ => u64::MAX,
         };
-        let executor_metrics = vec![available_memory];
+        let disk_io = &self.executor.disk_io;
+        let mut executor_metrics = vec![ExecutorMetric {
+            metric: Some(executor_metric::Metric::AvailableMemory(available_memory)),
+        }];
+        executor_metrics.extend(cgroup_metrics.map(|cgroup| ExecutorMetric {
+            metric: Some(executor_metric::Metric::Cgroup(cgroup)),
+        }));
+        executor_metrics.extend([&disk_io.write, &disk_io.read].map(|pool| {
+            ExecutorMetric {
+                metric: Some(executor_metric::Metric::DiskIo(pool.metrics())),
+            }
+        }));
         executor_metrics
     }
 }
@@ -459,11 +720,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> Heartbeater<T, U>
         let heartbeat_complete = shutdown_noti.shutdown_complete_tx.clone();
         tokio::spawn(async move {
             info!("Starting heartbeater to send heartbeat the scheduler periodically");
+            let interval = Duration::from_secs(executor_heartbeat_interval_seconds);
             // As long as the shutdown notification has not been received
             while !heartbeat_shutdown.is_shutdown() {
-                executor_server.heartbeat().await;
+                // the heartbeats carrying task status, if any, stand for periodic heartbeats
+                if !executor_server.sent_task_status_heartbeat_within(interval) {
+                    executor_server.heartbeat().await;
+                }
                 tokio::select! {
-                    _ = tokio::time::sleep(Duration::from_secs(executor_heartbeat_interval_seconds)) => {},
+                    _ = tokio::time::sleep(interval) => {},
                     _ = heartbeat_shutdown.recv() => {
                         info!("Stop heartbeater");
                         drop(heartbeat_complete);
@@ -517,7 +782,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
                 let mut fetched_task_num = 0usize;
                 if let Some(task_status) = maybe_task_status {
                     let task_status_vec = curator_task_status_map
-                        .entry(task_status.scheduler_id)
+                        .entry(executor_server.job_scheduler(
+                            &task_status.task_status.job_id,
+                            task_status.scheduler_id,
+                        ))
                         .or_default();
                     task_status_vec.push(task_status.task_status);
                     fetched_task_num += 1;
@@ -532,7 +800,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
                     match rx_task_status.try_recv() {
                         Ok(task_status) => {
                             let task_status_vec = curator_task_status_map
-                                .entry(task_status.scheduler_id)
+                                .entry(executor_server.job_scheduler(
+                                    &task_status.task_status.job_id,
+                                    task_status.scheduler_id,
+                                ))
                                 .or_default();
                             task_status_vec.push(task_status.task_status);
                             fetched_task_num += 1;
@@ -552,15 +823,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
                 for (scheduler_id, tasks_status) in curator_task_status_map.into_iter() {
                     match executor_server.get_scheduler_client(&scheduler_id).await {
                         Ok(mut scheduler) => {
-                            if let Err(e) = scheduler
-                                .update_task_status(UpdateTaskStatusParams {
-                                    executor_id: executor_server
-                                        .executor
-                                        .metadata
-                                        .id
-                                        .clone(),
-                                    task_status: tasks_status.clone(),
-                                })
+                            if let Err(e) = executor_server
+                                .report_task_status(&mut scheduler, tasks_status.clone())
                                 .await
                             {
                                 error!(
@@ -589,10 +853,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
 
             // Use a dedicated executor for CPU bound tasks so that the main tokio
             // executor can still answer requests even when under load
-            let dedicated_executor = DedicatedExecutor::new(
-                "task_runner",
-                executor_server.executor.concurrent_tasks,
-            );
+            let dedicated_executor = executor_server.executor.new_task_runtime();
 
             // As long as the shutdown notification has not been received
             while !task_runner_shutdown.is_shutdown() {
@@ -642,23 +903,25 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
             tasks,
             scheduler_id,
         } = request.into_inner();
-        let task_sender = self.executor_env.tx_task.clone();
         for task in tasks {
-            task_sender
-                .send(CuratorTaskDefinition {
-                    scheduler_id: scheduler_id.clone(),
-                    task: get_task_definition(
-                        task,
-                        self.executor.get_runtime(false),
-                        self.executor.scalar_functions.clone(),
-                        self.executor.aggregate_functions.clone(),
-                        self.executor.window_functions.clone(),
-                        self.codec.clone(),
-                    )
-                    .map_err(|e| Status::invalid_argument(format!("{e}")))?,
-                })
-                .await
-                .unwrap();
+            let launch_token = task.launch_token.clone();
+            if !self.reserve_launch(&scheduler_id, &launch_token).await {
+                continue;
+            }
+            // released if the launch fails, for the scheduler to retry it
+            let task = get_task_definition(
+                task,
+                self.executor.get_runtime(false),
+                self.executor.scalar_functions.clone(),
+                self.executor.aggregate_functions.clone(),
+                self.executor.window_functions.clone(),
+                self.codec.clone(),
+            )
+            .map_err(|e| {
+                self.release_launch(&launch_token);
+                Status::invalid_argument(format!("{e}"))
+            })?;
+            self.queue_task(&scheduler_id, task).await?;
         }
         Ok(Response::new(LaunchTaskResult { success: true }))
     }
@@ -673,8 +936,24 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
             multi_tasks,
             scheduler_id,
         } = request.into_inner();
-        let task_sender = self.executor_env.tx_task.clone();
-        for multi_task in multi_tasks {
+        for mut multi_task in multi_tasks {
+            let mut task_ids = Vec::with_capacity(multi_task.task_ids.len());
+            for task_id in std::mem::take(&mut multi_task.task_ids) {
+                if self
+                    .reserve_launch(&scheduler_id, &task_id.launch_token)
+                    .await
+                {
+                    task_ids.push(task_id);
+                }
+            }
+            if task_ids.is_empty() {
+                continue;
+            }
+            let launch_tokens: Vec<String> = task_ids
+                .iter()
+                .map(|task_id| task_id.launch_token.clone())
+                .collect();
+            multi_task.task_ids = task_ids;
             let multi_task: Vec<TaskDefinition> = get_task_definition_vec(
                 multi_task,
                 self.executor.get_runtime(false),
@@ -683,15 +962,18 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
                 self.executor.window_functions.clone(),
                 self.codec.clone(),
             )
-            .map_err(|e| Status::invalid_argument(format!("{e}")))?;
-            for task in multi_task {
-                task_sender
-                    .send(CuratorTaskDefinition {
-                        scheduler_id: scheduler_id.clone(),
-                        task,
-                    })
-                    .await
-                    .unwrap();
+            .map_err(|e| {
+                launch_tokens
+                    .iter()
+                    .for_each(|launch_token| self.release_launch(launch_token));
+                Status::invalid_argument(format!("{e}"))
+            })?;
+            let mut tasks = multi_task.into_iter();
+            while let Some(task) = tasks.next() {
+                if let Err(status) = self.queue_task(&scheduler_id, task).await {
+                    tasks.for_each(|task| self.release_launch(&task.launch_token));
+                    return Err(status);
+                }
             }
         }
         Ok(Response::new(LaunchMultiTaskResult { success: true }))
@@ -754,33 +1036,113 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
     ) -> Result<Response<RemoveJobDataResult>, Status> {
         let job_id = request.into_inner().job_id;
 
-        let work_dir = PathBuf::from(&self.executor.work_dir);
-        let mut path = work_dir.clone();
-        path.push(&job_id);
+        self.executor.task_logs.remove_job(&job_id);
+        self.job_schedulers.remove(&job_id);
+
+        for work_dir in self.executor.work_dirs.dirs() {
+            let work_dir = PathBuf::from(work_dir);
+            let mut path = work_dir.clone();
+            path.push(&job_id);
 
-        // Verify it's an existing directory
-        if !path.is_dir() {
-            return if !path.exists() {
-                Ok(Response::new(RemoveJobDataResult {}))
-            } else {
-                Err(Status::invalid_argument(format!(
+            // Verify it's an existing directory
+            if !path.is_dir() {
+                if !path.exists() {
+                    continue;
+                }
+                return Err(Status::invalid_argument(format!(
                     "Path {path:?} is not for a directory!!!"
-                )))
-            };
-        }
+                )));
+            }
 
-        if !is_subdirectory(path.as_path(), work_dir.as_path()) {
-            return Err(Status::invalid_argument(format!(
-                "Path {path:?} is not a subdirectory of {work_dir:?}!!!"
-            )));
-        }
+            if !is_subdirectory(path.as_path(), work_dir.as_path()) {
+                return Err(Status::invalid_argument(format!(
+                    "Path {path:?} is not a subdirectory of {work_dir:?}!!!"
+                )));
+            }
 
-        info!("Remove data for job {:?}", job_id);
+            info!("Remove data for job {:?} in {:?}", job_id, work_dir);
 
-        std::fs::remove_dir_all(&path)?;
+            std::fs::remove_dir_all(&path)?;
+        }
 
         Ok(Response::new(RemoveJobDataResult {}))
     }
+
+    async fn update_executor_config(
+        &self,
+        request: Request<UpdateExecutorConfigParams>,
+    ) -> Result<Response<UpdateExecutorConfigResult>, Status> {
+        let settings = request
+            .into_inner()
+            .settings
+            .into_iter()
+            .map(|kv| (kv.key, kv.value))
+            .collect();
+        self.executor
+            .reloadable_config
+            .update(&settings)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        Ok(Response::new(UpdateExecutorConfigResult {}))
+    }
+
+    async fn get_task_logs(
+        &self,
+        request: Request<GetTaskLogsParams>,
+    ) -> Result<Response<GetTaskLogsResult>, Status> {
+        let GetTaskLogsParams {
+            job_id,
+            stage_id,
+            task_id,
+        } = request.into_inner();
+        let result = match self.executor.task_logs.get(
+            &job_id,
+            stage_id as usize,
+            task_id as usize,
+        ) {
+            Some((lines, dropped_lines)) => GetTaskLogsResult {
+                found: true,
+                executor_id: self.executor.metadata.id.clone(),
+                lines,
+                dropped_lines,
+            },
+            None => GetTaskLogsResult::default(),
+        };
+        Ok(Response::new(result))
+    }
+
+    async fn update_job_scheduler(
+        &self,
+        request: Request<UpdateJobSchedulerParams>,
+    ) -> Result<Response<UpdateJobSchedulerResult>, Status> {
+        let UpdateJobSchedulerParams {
+            job_id,
+            scheduler_id,
+        } = request.into_inner();
+        info!(
+            "Sending the status of the tasks of job {job_id} to scheduler {scheduler_id}"
+        );
+        self.job_schedulers.insert(job_id, scheduler_id);
+        Ok(Response::new(UpdateJobSchedulerResult {}))
+    }
+
+    async fn replicate_shuffle_data(
+        &self,
+        request: Request<ReplicateShuffleDataParams>,
+    ) -> Result<Response<ReplicateShuffleDataResult>, Status> {
+        let partitions = request.into_inner().partitions;
+        let mut paths = Vec::with_capacity(partitions.len());
+        for location in &partitions {
+            let work_dir = self.executor.work_dirs.next_dir();
+            let path = replicate_partition(work_dir, location).await.map_err(|e| {
+                Status::internal(format!(
+                    "Failed to replicate shuffle partition {}: {e}",
+                    location.path
+                ))
+            })?;
+            paths.push(path);
+        }
+        Ok(Response::new(ReplicateShuffleDataResult { paths }))
+    }
 }
 
 // Check whether the path is the subdirectory of the base directory
@@ -798,10 +1160,29 @@ fn is_subdirectory(path: &Path, base_path: &Path) -> bool {
 
 #[cfg(test)]
 mod test {
-    use crate::executor_server::is_subdirectory;
+    use crate::executor::Executor;
+    use crate::executor_server::{is_subdirectory, ExecutorEnv, ExecutorServer};
+    use crate::metrics::LoggingMetricsCollector;
+    use ballista_core::execution_plans::ShuffleWriterExec;
+    use ballista_core::serde::protobuf::{
+        self, executor_grpc_server::ExecutorGrpc,
+        scheduler_grpc_client::SchedulerGrpcClient, ExecutorRegistration,
+        LaunchTaskParams,
+    };
+    use ballista_core::serde::BallistaCodec;
+    use datafusion::arrow::datatypes::Schema;
+    use datafusion::physical_plan::empty::EmptyExec;
+    use datafusion::prelude::SessionContext;
+    use datafusion_proto::physical_plan::AsExecutionPlan;
+    use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
     use std::fs;
     use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    use std::time::Duration;
     use tempfile::TempDir;
+    use tokio::sync::mpsc;
+    use tonic::transport::Channel;
+    use tonic::Request;
 
     #[tokio::test]
     async fn test_is_subdirectory() {
@@ -837,4 +1218,104 @@ mod test {
         }
         path
     }
+
+    fn task_definition(
+        codec: &BallistaCodec,
+        work_dir: &str,
+        partition_id: u32,
+    ) -> protobuf::TaskDefinition {
+        let plan = ShuffleWriterExec::try_new(
+            "job".to_owned(),
+            1,
+            Arc::new(EmptyExec::new(Arc::new(Schema::empty()))),
+            work_dir.to_owned(),
+            None,
+        )
+        .unwrap();
+        let mut buf = vec![];
+        PhysicalPlanNode::try_from_physical_plan(
+            Arc::new(plan),
+            codec.physical_extension_codec(),
+        )
+        .unwrap()
+        .try_encode(&mut buf)
+        .unwrap();
+        protobuf::TaskDefinition {
+            task_id: partition_id,
+            job_id: "job".to_owned(),
+            stage_id: 1,
+            partition_id,
+            plan: codec.plan_protection().protect(buf).unwrap(),
+            launch_token: format!("job/1.0/{partition_id}.0/{partition_id}"),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_duplicate_launches() {
+        let work_dir = TempDir::new()
+            .unwrap()
+            .into_path()
+            .into_os_string()
+            .into_string()
+            .unwrap();
+        let executor_registration = ExecutorRegistration {
+            id: "executor".to_owned(),
+            ..Default::default()
+        };
+        let ctx = SessionContext::new();
+        let executor = Executor::new(
+            executor_registration,
+            &work_dir,
+            ctx.runtime_env(),
+            None,
+            Arc::new(LoggingMetricsCollector::default()),
+            2,
+            None,
+        );
+        // the queue of the tasks to run holds a single task
+        let (tx_task, mut rx_task) = mpsc::channel(1);
+        let (tx_task_status, _rx_task_status) = mpsc::channel(1);
+        let (tx_stop, _rx_stop) = mpsc::channel(1);
+        let scheduler = SchedulerGrpcClient::new(
+            Channel::from_static("http://localhost:50050").connect_lazy(),
+        );
+        let codec = BallistaCodec::default();
+        let server: ExecutorServer<LogicalPlanNode, PhysicalPlanNode> =
+            ExecutorServer::new(
+                scheduler,
+                Arc::new(executor),
+                ExecutorEnv {
+                    tx_task,
+                    tx_task_status,
+                    tx_stop,
+                },
+                codec.clone(),
+                16 * 1024 * 1024,
+                16 * 1024 * 1024,
+                false,
+            );
+        let launch = |partition_id| {
+            server.launch_task(Request::new(LaunchTaskParams {
+                tasks: vec![task_definition(&codec, &work_dir, partition_id)],
+                scheduler_id: "scheduler".to_owned(),
+            }))
+        };
+
+        // with the queue full, both launches of the next task wait for it to be queued
+        launch(0).await.unwrap();
+        let queued = async {
+            let mut queued = vec![];
+            while let Ok(Some(task)) =
+                tokio::time::timeout(Duration::from_millis(500), rx_task.recv()).await
+            {
+                queued.push(task.task.partition_id);
+            }
+            queued
+        };
+        let (first, second, queued) = tokio::join!(launch(1), launch(1), queued);
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(queued, vec![0, 1]);
+    }
 }
@@ -25,8 +25,10 @@ use std::pin::Pin;
 use arrow::ipc::CompressionType;
 use arrow_flight::encode::FlightDataEncoderBuilder;
 use arrow_flight::error::FlightError;
+use ballista_core::disk_io::{DiskIoPool, DiskIoScheduler};
 use ballista_core::error::BallistaError;
 use ballista_core::serde::decode_protobuf;
+use ballista_core::serde::protobuf;
 use ballista_core::serde::scheduler::Action as BallistaAction;
 
 use arrow::ipc::writer::IpcWriteOptions;
@@ -38,10 +40,14 @@ use arrow_flight::{
 use datafusion::arrow::{error::ArrowError, record_batch::RecordBatch};
 use futures::{Stream, StreamExt, TryStreamExt};
 use log::{debug, info};
-use std::io::{Read, Seek};
-use tokio::sync::mpsc::channel;
+use prost::Message;
+use std::io::BufReader;
+use std::sync::Arc;
 use tokio::sync::mpsc::error::SendError;
-use tokio::{sync::mpsc::Sender, task};
+use tokio::sync::mpsc::{
+    channel, unbounded_channel, Sender, UnboundedReceiver, UnboundedSender,
+};
+use tokio::sync::Semaphore;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::metadata::MetadataValue;
 use tonic::{Request, Response, Status, Streaming};
@@ -49,11 +55,22 @@ use tracing::warn;
 
 /// Service implementing the Apache Arrow Flight Protocol
 #[derive(Clone)]
-pub struct BallistaFlightService {}
+pub struct BallistaFlightService {
+    /// The pool reading the shuffle files
+    io_pool: Arc<DiskIoPool>,
+}
 
 impl BallistaFlightService {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            io_pool: DiskIoScheduler::default().read,
+        }
+    }
+
+    /// Read the shuffle files in the given disk IO pool
+    pub fn with_io_pool(mut self, io_pool: Arc<DiskIoPool>) -> Self {
+        self.io_pool = io_pool;
+        self
     }
 }
 
@@ -85,41 +102,68 @@ impl FlightService for BallistaFlightService {
         let action =
             decode_protobuf(&ticket.ticket).map_err(|e| from_ballista_err(&e))?;
 
-        match &action {
+        let paths = match action {
             BallistaAction::FetchPartition { path, .. } => {
                 debug!("FetchPartition reading {}", path);
-                let file = File::open(path)
-                    .map_err(|e| {
-                        BallistaError::General(format!(
-                            "Failed to open partition file at {path}: {e:?}"
-                        ))
-                    })
-                    .map_err(|e| from_ballista_err(&e))?;
-                let reader =
-                    StreamReader::try_new(file, None).map_err(|e| from_arrow_err(&e))?;
-
-                let (tx, rx) = channel(2);
-                let schema = reader.schema();
-                task::spawn_blocking(move || {
-                    if let Err(e) = read_partition(reader, tx) {
-                        warn!(error = %e, "error streaming shuffle partition");
+                vec![path]
+            }
+            BallistaAction::FetchPartitions { paths, .. } => {
+                debug!("FetchPartitions reading {} partitions", paths.len());
+                paths
+            }
+        };
+        let mut paths = paths.into_iter();
+        let first_path = paths
+            .next()
+            .ok_or_else(|| Status::invalid_argument("No partition to fetch"))?;
+        let io_pool = self.io_pool.clone();
+        let reader = io_pool
+            .run(move || open_partition(&first_path))
+            .await
+            .map_err(|e| from_ballista_err(&e))??;
+        let schema = reader.schema();
+
+        let (tx, rx) = channel(2);
+        tokio::spawn(async move {
+            // the partitions are the outputs of the same shuffle, so that they share the
+            // schema and are streamed one after the other. They are opened one at a time to
+            // bound the number of open files.
+            let mut reader = reader;
+            loop {
+                if let Err(e) = read_partition(&io_pool, reader, &tx).await {
+                    warn!(error = %e, "error streaming shuffle partition");
+                    return;
+                }
+                let Some(path) = paths.next() else {
+                    return;
+                };
+                let opened = io_pool
+                    .run(move || open_partition(&path))
+                    .await
+                    .map_err(|e| from_ballista_err(&e))
+                    .and_then(|reader| reader);
+                reader = match opened {
+                    Ok(reader) => reader,
+                    Err(status) => {
+                        let _ = tx.send(Err(FlightError::Tonic(status))).await;
+                        return;
                     }
-                });
-
-                let write_options: IpcWriteOptions = IpcWriteOptions::default()
-                    .try_with_compression(Some(CompressionType::LZ4_FRAME))
-                    .map_err(|e| from_arrow_err(&e))?;
-                let flight_data_stream = FlightDataEncoderBuilder::new()
-                    .with_schema(schema)
-                    .with_options(write_options)
-                    .build(ReceiverStream::new(rx))
-                    .map_err(|err| Status::from_error(Box::new(err)));
-
-                Ok(Response::new(
-                    Box::pin(flight_data_stream) as Self::DoGetStream
-                ))
+                };
             }
-        }
+        });
+
+        let write_options: IpcWriteOptions = IpcWriteOptions::default()
+            .try_with_compression(Some(CompressionType::LZ4_FRAME))
+            .map_err(|e| from_arrow_err(&e))?;
+        let flight_data_stream = FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .with_options(write_options)
+            .build(ReceiverStream::new(rx))
+            .map_err(|err| Status::from_error(Box::new(err)));
+
+        Ok(Response::new(
+            Box::pin(flight_data_stream) as Self::DoGetStream
+        ))
     }
 
     async fn get_schema(
@@ -197,11 +241,68 @@ impl FlightService for BallistaFlightService {
         Err(Status::unimplemented("list_actions"))
     }
 
+    /// Stream the shuffle partitions requested by the client over a single stream. The
+    /// client sends [`protobuf::PartitionExchangeRequest`]s in the app metadata of its
+    /// messages to request partitions and grant credits, a batch being sent for every
+    /// credit. Every partition is followed by a [`protobuf::PartitionExchangeEnd`]
+    /// message.
     async fn do_exchange(
         &self,
-        _request: Request<Streaming<FlightData>>,
+        request: Request<Streaming<FlightData>>,
     ) -> Result<Response<Self::DoExchangeStream>, Status> {
-        Err(Status::unimplemented("do_exchange"))
+        let mut requests = request.into_inner();
+        let (partition_tx, partition_rx) = unbounded_channel();
+        let credits = Arc::new(Semaphore::new(0));
+
+        // the first request is awaited so that invalid requests fail the exchange at once
+        let first_request = requests
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("No partition exchange request"))?;
+        add_exchange_request(&first_request, &partition_tx, &credits)?;
+        let request_credits = credits.clone();
+        tokio::spawn(async move {
+            loop {
+                match requests.message().await {
+                    Ok(Some(request)) => {
+                        if let Err(status) = add_exchange_request(
+                            &request,
+                            &partition_tx,
+                            &request_credits,
+                        ) {
+                            warn!(error = %status, "invalid partition exchange request");
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(status) => {
+                        warn!(error = %status, "error receiving exchange requests");
+                        break;
+                    }
+                }
+            }
+            // the client no longer grants credits, the requested partitions are streamed
+            // without flow control
+            request_credits.close();
+        });
+
+        let write_options: IpcWriteOptions = IpcWriteOptions::default()
+            .try_with_compression(Some(CompressionType::LZ4_FRAME))
+            .map_err(|e| from_arrow_err(&e))?;
+        let io_pool = self.io_pool.clone();
+        let (tx, rx) = channel(2);
+        tokio::spawn(async move {
+            if let Err(status) =
+                exchange_partitions(io_pool, partition_rx, credits, write_options, &tx)
+                    .await
+            {
+                let _ = tx.send(Err(status)).await;
+            }
+        });
+
+        Ok(Response::new(
+            Box::pin(ReceiverStream::new(rx)) as Self::DoExchangeStream
+        ))
     }
 
     async fn poll_flight_info(
@@ -212,21 +313,45 @@ impl FlightService for BallistaFlightService {
     }
 }
 
-fn read_partition<T>(
-    reader: StreamReader<std::io::BufReader<T>>,
-    tx: Sender<Result<RecordBatch, FlightError>>,
-) -> Result<(), FlightError>
-where
-    T: Read + Seek,
-{
-    if tx.is_closed() {
-        return Err(FlightError::Tonic(Status::internal(
-            "Can't send a batch, channel is closed",
-        )));
-    }
-
-    for batch in reader {
-        tx.blocking_send(batch.map_err(|err| err.into()))
+/// Open the IPC file of a shuffle partition
+fn open_partition(path: &str) -> Result<StreamReader<BufReader<File>>, Status> {
+    let file = File::open(path)
+        .map_err(|e| {
+            BallistaError::General(format!(
+                "Failed to open partition file at {path}: {e:?}"
+            ))
+        })
+        .map_err(|e| from_ballista_err(&e))?;
+    StreamReader::try_new(file, None).map_err(|e| from_arrow_err(&e))
+}
+
+/// Stream the batches of a shuffle partition to `tx`. The batches are read one at a time in
+/// the disk IO pool, so that the pool is not held while the client is slow to consume them.
+async fn read_partition(
+    io_pool: &DiskIoPool,
+    mut reader: StreamReader<BufReader<File>>,
+    tx: &Sender<Result<RecordBatch, FlightError>>,
+) -> Result<(), FlightError> {
+    loop {
+        if tx.is_closed() {
+            return Err(FlightError::Tonic(Status::internal(
+                "Can't send a batch, channel is closed",
+            )));
+        }
+        let (next_reader, batch) = io_pool
+            .run(move || {
+                let batch = reader.next();
+                (reader, batch)
+            })
+            .await
+            .map_err(|e| FlightError::Tonic(from_ballista_err(&e)))?;
+        reader = next_reader;
+        let Some(batch) = batch else {
+            return Ok(());
+        };
+        let failed = batch.is_err();
+        tx.send(batch.map_err(|err| err.into()))
+            .await
             .map_err(|err| {
                 if let SendError(Err(err)) = err {
                     err
@@ -235,7 +360,93 @@ where
                         "Can't send a batch, something went wrong",
                     ))
                 }
-            })?
+            })?;
+        if failed {
+            // the error is sent to the client, the partition is not read further
+            return Err(FlightError::Tonic(Status::internal(
+                "Failed to read a batch of the shuffle partition",
+            )));
+        }
+    }
+}
+
+/// Queue the partitions and add the credits of a partition exchange request
+fn add_exchange_request(
+    request: &FlightData,
+    partitions: &UnboundedSender<String>,
+    credits: &Semaphore,
+) -> Result<(), Status> {
+    let request =
+        protobuf::PartitionExchangeRequest::decode(request.app_metadata.as_ref())
+            .map_err(|e| {
+                Status::invalid_argument(format!(
+                    "Invalid partition exchange request: {e:?}"
+                ))
+            })?;
+    for partition in request.partitions {
+        debug!("PartitionExchange reading {}", partition.path);
+        // the partitions are only dropped once the exchange failed
+        let _ = partitions.send(partition.path);
+    }
+    credits.add_permits(request.credits as usize);
+    Ok(())
+}
+
+/// Stream the requested partitions one after the other, with the schema of the first one.
+/// The batches are sent as the client grants credits, and every partition is followed
+/// by a [`protobuf::PartitionExchangeEnd`] message.
+async fn exchange_partitions(
+    io_pool: Arc<DiskIoPool>,
+    mut partitions: UnboundedReceiver<String>,
+    credits: Arc<Semaphore>,
+    write_options: IpcWriteOptions,
+    tx: &Sender<Result<FlightData, Status>>,
+) -> Result<(), Status> {
+    let send = |data: FlightData| async move {
+        tx.send(Ok(data))
+            .await
+            .map_err(|_| Status::cancelled("The partition exchange was closed"))
+    };
+    let mut partition_index = 0;
+    while let Some(path) = partitions.recv().await {
+        let reader = io_pool
+            .run(move || open_partition(&path))
+            .await
+            .map_err(|e| from_ballista_err(&e))??;
+        let schema = reader.schema();
+        let (batch_tx, batch_rx) = channel(2);
+        let read_pool = io_pool.clone();
+        tokio::spawn(async move {
+            if let Err(e) = read_partition(&read_pool, reader, &batch_tx).await {
+                warn!(error = %e, "error streaming shuffle partition");
+            }
+        });
+
+        let mut encoded = FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .with_options(write_options.clone())
+            .build(ReceiverStream::new(batch_rx));
+        // the first message of the encoder is the schema, shared by all the partitions
+        let schema = encoded
+            .next()
+            .await
+            .transpose()
+            .map_err(|e| Status::from_error(Box::new(e)))?;
+        if let (Some(schema), 0) = (schema, partition_index) {
+            send(schema).await?;
+        }
+        while let Some(data) = encoded.next().await {
+            let data = data.map_err(|e| Status::from_error(Box::new(e)))?;
+            // a closed semaphore means that the client no longer grants credits
+            if let Ok(credit) = credits.acquire().await {
+                credit.forget();
+            }
+            send(data).await?;
+        }
+
+        let end = protobuf::PartitionExchangeEnd { partition_index };
+        send(FlightData::new().with_app_metadata(end.encode_to_vec())).await?;
+        partition_index += 1;
     }
     Ok(())
 }
@@ -247,3 +458,162 @@ fn from_arrow_err(e: &ArrowError) -> Status {
 fn from_ballista_err(e: &ballista_core::error::BallistaError) -> Status {
     Status::internal(format!("Ballista Error: {e:?}"))
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::fs::File;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use arrow::ipc::writer::StreamWriter;
+    use arrow_flight::flight_service_client::FlightServiceClient;
+    use arrow_flight::flight_service_server::FlightServiceServer;
+    use arrow_flight::FlightData;
+    use ballista_core::client::BallistaClient;
+    use ballista_core::serde::protobuf;
+    use ballista_core::serde::scheduler::PartitionId;
+    use datafusion::arrow::array::Int64Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use futures::StreamExt;
+    use prost::Message;
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::{TcpListenerStream, UnboundedReceiverStream};
+    use tonic::transport::Server;
+
+    use super::BallistaFlightService;
+
+    /// Write a shuffle partition with a batch of the given number of rows per element
+    pub(crate) fn write_partition(path: &Path, batch_rows: &[i64]) -> String {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        let mut writer =
+            StreamWriter::try_new(File::create(path).unwrap(), &schema).unwrap();
+        for num_rows in batch_rows {
+            let array = Int64Array::from_iter_values(0..*num_rows);
+            let batch =
+                RecordBatch::try_new(schema.clone(), vec![Arc::new(array)]).unwrap();
+            writer.write(&batch).unwrap();
+        }
+        writer.finish().unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    /// Start a flight service on a local port, returning the port
+    pub(crate) async fn start_flight_service() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(
+            Server::builder()
+                .add_service(FlightServiceServer::new(BallistaFlightService::new()))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        port
+    }
+
+    fn exchange_request(paths: &[String], credits: u32) -> FlightData {
+        let request = protobuf::PartitionExchangeRequest {
+            partitions: paths
+                .iter()
+                .map(|path| protobuf::FetchPartition {
+                    path: path.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            credits,
+        };
+        FlightData::new().with_app_metadata(request.encode_to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_do_exchange() {
+        let work_dir = TempDir::new().unwrap();
+        let paths = vec![
+            write_partition(&work_dir.path().join("0.arrow"), &[1, 2]),
+            write_partition(&work_dir.path().join("1.arrow"), &[3]),
+        ];
+        let port = start_flight_service().await;
+        let mut client = FlightServiceClient::connect(format!("http://127.0.0.1:{port}"))
+            .await
+            .unwrap();
+
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        request_tx.send(exchange_request(&paths, 1)).unwrap();
+        let mut stream = client
+            .do_exchange(UnboundedReceiverStream::new(request_rx))
+            .await
+            .unwrap()
+            .into_inner();
+        // the schema, then a single batch for the single credit
+        let schema = stream.message().await.unwrap().unwrap();
+        assert!(!schema.data_header.is_empty() && schema.data_body.is_empty());
+        let batch = stream.message().await.unwrap().unwrap();
+        assert!(!batch.data_body.is_empty());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), stream.message())
+                .await
+                .is_err()
+        );
+
+        // the other batches once credits are granted, every partition followed by its end
+        request_tx.send(exchange_request(&[], 10)).unwrap();
+        drop(request_tx);
+        let mut messages = vec![];
+        while let Some(message) = stream.message().await.unwrap() {
+            messages.push(message);
+        }
+        let kinds: Vec<String> = messages
+            .iter()
+            .map(|message| {
+                if message.data_header.is_empty() {
+                    let end = protobuf::PartitionExchangeEnd::decode(
+                        message.app_metadata.as_ref(),
+                    )
+                    .unwrap();
+                    format!("end {}", end.partition_index)
+                } else if message.data_body.is_empty() {
+                    "schema".to_owned()
+                } else {
+                    "batch".to_owned()
+                }
+            })
+            .collect();
+        // the schema is only sent once for all the partitions
+        assert_eq!(kinds, vec!["batch", "end 0", "batch", "end 1"]);
+    }
+
+    #[tokio::test]
+    async fn test_exchange_partitions() {
+        let work_dir = TempDir::new().unwrap();
+        let port = start_flight_service().await;
+        let partitions = [vec![1, 2], vec![], vec![3]]
+            .iter()
+            .enumerate()
+            .map(|(partition_id, batch_rows)| {
+                let path = work_dir.path().join(format!("{partition_id}.arrow"));
+                (
+                    PartitionId::new("job", 1, partition_id),
+                    write_partition(&path, batch_rows),
+                )
+            })
+            .collect();
+
+        let mut client = BallistaClient::try_new("127.0.0.1", port).await.unwrap();
+        let stream = client
+            .exchange_partitions("executor", partitions, "127.0.0.1", port, 1)
+            .await
+            .unwrap();
+        let batches: Vec<RecordBatch> =
+            stream.map(|batch| batch.unwrap()).collect::<Vec<_>>().await;
+        // the batches of the partitions in order, the credits granted batch after batch
+        assert_eq!(
+            batches
+                .iter()
+                .map(|batch| batch.num_rows())
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The task attempts launched on the executor, by launch token.
+//!
+//! A scheduler retrying a launch, or taking over a job after a failover, launches a task
+//! attempt again with the same launch token. Both the push and the pull modes look the
+//! token up in [`LaunchedTasks`] to run the task attempt only once, and to report the
+//! status of the first run again if it has finished.
+
+use std::collections::{HashMap, VecDeque};
+
+use ballista_core::serde::protobuf::TaskStatus;
+
+/// Number of the last launched tasks whose launch tokens are kept to ignore duplicates
+const MAX_LAUNCHED_TASKS: usize = 100_000;
+
+/// The last launched tasks by launch token, along with their status once finished
+#[derive(Debug, Default)]
+pub(crate) struct LaunchedTasks {
+    tasks: HashMap<String, Option<TaskStatus>>,
+    /// The launch tokens from the oldest to the most recent launch
+    tokens: VecDeque<String>,
+}
+
+impl LaunchedTasks {
+    /// The previous launch of a task attempt if any, with its status if it has finished
+    pub(crate) fn get(&self, launch_token: &str) -> Option<Option<TaskStatus>> {
+        self.tasks.get(launch_token).cloned()
+    }
+
+    /// Record the launch of a task attempt, once it is queued to run
+    pub(crate) fn launch(&mut self, launch_token: &str) {
+        if self.tasks.contains_key(launch_token) {
+            return;
+        }
+        self.tasks.insert(launch_token.to_owned(), None);
+        self.tokens.push_back(launch_token.to_owned());
+        if self.tokens.len() > MAX_LAUNCHED_TASKS {
+            if let Some(oldest) = self.tokens.pop_front() {
+                self.tasks.remove(&oldest);
+            }
+        }
+    }
+
+    /// Reserve the launch token of a task attempt about to be launched, unless the task
+    /// attempt was already launched, in which case its previous launch is returned. The
+    /// token is checked and recorded at once, for concurrent launches of the same task
+    /// attempt to run it only once.
+    pub(crate) fn reserve(&mut self, launch_token: &str) -> Option<Option<TaskStatus>> {
+        if let Some(task_status) = self.tasks.get(launch_token) {
+            return Some(task_status.clone());
+        }
+        self.launch(launch_token);
+        None
+    }
+
+    /// Release the launch token of a task attempt whose launch failed before it was
+    /// queued to run, for the launch to be retried
+    pub(crate) fn release(&mut self, launch_token: &str) {
+        if self.tasks.remove(launch_token).is_some() {
+            self.tokens.retain(|token| token != launch_token);
+        }
+    }
+
+    /// Record the status of a finished task attempt, reported again on duplicate launches.
+    /// The operator metrics are not kept, they were already reported with the first status.
+    pub(crate) fn finish(&mut self, launch_token: &str, task_status: &TaskStatus) {
+        if let Some(status) = self.tasks.get_mut(launch_token) {
+            *status = Some(TaskStatus {
+                metrics: vec![],
+                ..task_status.clone()
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LaunchedTasks;
+    use ballista_core::serde::protobuf::{OperatorMetricsSet, TaskStatus};
+
+    #[test]
+    fn test_launched_tasks() {
+        let mut launched_tasks = LaunchedTasks::default();
+        assert_eq!(launched_tasks.get("job/1.0/0.0/1"), None);
+        launched_tasks.launch("job/1.0/0.0/1");
+        launched_tasks.launch("job/1.0/1.0/2");
+        // the task is still running
+        assert_eq!(launched_tasks.get("job/1.0/0.0/1"), Some(None));
+
+        let task_status = TaskStatus {
+            task_id: 1,
+            job_id: "job".to_owned(),
+            stage_id: 1,
+            ..Default::default()
+        };
+        launched_tasks.finish(
+            "job/1.0/0.0/1",
+            &TaskStatus {
+                metrics: vec![OperatorMetricsSet::default()],
+                ..task_status.clone()
+            },
+        );
+        // the status is kept without its operator metrics
+        assert_eq!(launched_tasks.get("job/1.0/0.0/1"), Some(Some(task_status)));
+        // another attempt of the task is launched
+        assert_eq!(launched_tasks.get("job/1.0/0.1/3"), None);
+        launched_tasks.launch("job/1.0/0.1/3");
+        launched_tasks.launch("job/1.0/0.1/3");
+        assert_eq!(launched_tasks.tokens.len(), 3);
+
+        // a reserved launch token is released if the launch fails
+        assert_eq!(launched_tasks.reserve("job/1.0/2.0/4"), None);
+        assert_eq!(launched_tasks.reserve("job/1.0/2.0/4"), Some(None));
+        launched_tasks.release("job/1.0/2.0/4");
+        assert_eq!(launched_tasks.get("job/1.0/2.0/4"), None);
+        assert_eq!(launched_tasks.tokens.len(), 3);
+    }
+}
@@ -17,6 +17,8 @@
 
 #![doc = include_str!("../README.md")]
 
+pub mod allowed_locations;
+pub mod cgroup;
 pub mod collect;
 pub mod execution_engine;
 pub mod execution_loop;
@@ -25,16 +27,30 @@ pub mod executor_process;
 pub mod executor_server;
 pub mod flight_service;
 pub mod metrics;
+pub mod preemption;
+pub mod reloadable_config;
+pub mod self_check;
+pub mod shuffle_inventory;
+pub mod shuffle_replication;
 pub mod shutdown;
+pub mod task_dump;
+pub mod task_logs;
 pub mod terminate;
+pub mod work_dirs;
 
 mod cpu_bound_executor;
+mod launched_tasks;
 mod standalone;
 
 pub use standalone::new_standalone_executor;
 
+use std::collections::HashMap;
+
+use datafusion::config::ConfigOptions;
 use log::info;
 
+use ballista_core::config::DATAFUSION_CONFIG_PREFIX;
+use ballista_core::error::Result;
 use ballista_core::serde::protobuf::{
     task_status, FailedTask, OperatorMetricsSet, ShuffleWritePartition, SuccessfulTask,
     TaskStatus,
@@ -48,8 +64,23 @@ pub struct TaskExecutionTimes {
     end_exec_time: u64,
 }
 
+/// The DataFusion options of a task, failing on an option the executor can't set for
+/// the task not to run with different options than its session
+pub(crate) fn task_config_options(
+    task_props: &HashMap<String, String>,
+) -> Result<ConfigOptions> {
+    let mut config = ConfigOptions::new();
+    for (k, v) in task_props {
+        // the Ballista props are read by the executor itself
+        if k.starts_with(DATAFUSION_CONFIG_PREFIX) {
+            config.set(k, v)?;
+        }
+    }
+    Ok(config)
+}
+
 pub fn as_task_status(
-    execution_result: ballista_core::error::Result<Vec<ShuffleWritePartition>>,
+    execution_result: Result<Vec<ShuffleWritePartition>>,
     executor_id: String,
     task_id: usize,
     stage_attempt_num: usize,
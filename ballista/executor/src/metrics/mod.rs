@@ -16,6 +16,10 @@
 // under the License.
 
 use crate::execution_engine::QueryStageExecutor;
+use ballista_core::error::BallistaError;
+use ballista_core::serde::protobuf::OperatorMetricsSet;
+use ballista_core::serde::scheduler::to_proto::operator_metrics_to_proto;
+use datafusion::physical_plan::metrics::MetricsSet;
 use log::info;
 use std::sync::Arc;
 
@@ -33,12 +37,37 @@ pub trait ExecutorMetricsCollector: Send + Sync {
         partition: usize,
         plan: Arc<dyn QueryStageExecutor>,
     );
+
+    /// Serialize the metrics of every operator of the stage after it is executed, which
+    /// are reported to the scheduler in `TaskStatus.metrics`. All metrics are reported
+    /// by default.
+    fn operator_metrics(
+        &self,
+        plan: &dyn QueryStageExecutor,
+    ) -> Result<Vec<OperatorMetricsSet>, BallistaError> {
+        operator_metrics_to_proto(plan.collect_plan_metrics())
+    }
 }
 
 /// Implementation of `ExecutorMetricsCollector` which logs the completed
 /// plan to stdout.
 #[derive(Default)]
-pub struct LoggingMetricsCollector {}
+pub struct LoggingMetricsCollector {
+    /// Maximum number of metrics reported for each operator, no limit if zero
+    max_metrics_per_operator: usize,
+}
+
+impl LoggingMetricsCollector {
+    /// Bound the number of metrics reported to the scheduler for each operator, see
+    /// [`limit_operator_metrics`]
+    pub fn with_max_metrics_per_operator(
+        mut self,
+        max_metrics_per_operator: usize,
+    ) -> Self {
+        self.max_metrics_per_operator = max_metrics_per_operator;
+        self
+    }
+}
 
 impl ExecutorMetricsCollector for LoggingMetricsCollector {
     fn record_stage(
@@ -53,4 +82,97 @@ impl ExecutorMetricsCollector for LoggingMetricsCollector {
             job_id, stage_id, partition, plan
         );
     }
+
+    fn operator_metrics(
+        &self,
+        plan: &dyn QueryStageExecutor,
+    ) -> Result<Vec<OperatorMetricsSet>, BallistaError> {
+        operator_metrics_to_proto(limit_operator_metrics(
+            plan.collect_plan_metrics(),
+            self.max_metrics_per_operator,
+        ))
+    }
+}
+
+/// Bound the size of every operator metrics set to `max_metrics_per_operator` metrics,
+/// so that operators with many labeled metrics don't blow up the task status messages.
+/// Larger sets are first aggregated by name, e.g. merging the metrics of every output
+/// partition of a repartition, then truncated if still too large. No limit if zero.
+pub fn limit_operator_metrics(
+    metrics: Vec<MetricsSet>,
+    max_metrics_per_operator: usize,
+) -> Vec<MetricsSet> {
+    if max_metrics_per_operator == 0 {
+        return metrics;
+    }
+    metrics
+        .into_iter()
+        .map(|metrics_set| {
+            if metrics_set.iter().count() <= max_metrics_per_operator {
+                return metrics_set;
+            }
+            let aggregated = metrics_set.aggregate_by_name();
+            if aggregated.iter().count() <= max_metrics_per_operator {
+                return aggregated;
+            }
+            let mut truncated = MetricsSet::new();
+            for metric in aggregated.iter().take(max_metrics_per_operator) {
+                truncated.push(metric.clone());
+            }
+            truncated
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::physical_plan::metrics::{Count, Label, Metric, MetricValue};
+
+    fn labeled_count(name: &'static str, label: &str, value: usize) -> Arc<Metric> {
+        let count = Count::new();
+        count.add(value);
+        Arc::new(Metric::new_with_labels(
+            MetricValue::Count {
+                name: name.into(),
+                count,
+            },
+            None,
+            vec![Label::new("partition", label.to_string())],
+        ))
+    }
+
+    fn metrics_set(metrics: Vec<Arc<Metric>>) -> MetricsSet {
+        let mut metrics_set = MetricsSet::new();
+        metrics.into_iter().for_each(|m| metrics_set.push(m));
+        metrics_set
+    }
+
+    #[test]
+    fn test_limit_operator_metrics() {
+        let metrics = vec![
+            metrics_set(vec![labeled_count("a", "0", 1)]),
+            metrics_set(vec![
+                labeled_count("a", "0", 1),
+                labeled_count("a", "1", 2),
+                labeled_count("b", "0", 3),
+            ]),
+            metrics_set(vec![
+                labeled_count("a", "0", 1),
+                labeled_count("b", "0", 2),
+                labeled_count("c", "0", 3),
+            ]),
+        ];
+
+        let unlimited = limit_operator_metrics(metrics.clone(), 0);
+        let counts: Vec<_> = unlimited.iter().map(|m| m.iter().count()).collect();
+        assert_eq!(counts, vec![1, 3, 3]);
+
+        let limited = limit_operator_metrics(metrics, 2);
+        let counts: Vec<_> = limited.iter().map(|m| m.iter().count()).collect();
+        assert_eq!(counts, vec![1, 2, 2]);
+        // labeled metrics are aggregated before being truncated
+        assert_eq!(limited[1].sum_by_name("a").map(|v| v.as_usize()), Some(3));
+        assert_eq!(limited[1].sum_by_name("b").map(|v| v.as_usize()), Some(3));
+    }
 }
@@ -0,0 +1,142 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Watch for the preemption notices of spot or preemptible instances, so that the executor
+//! stops accepting tasks and the scheduler replicates its shuffle outputs to other
+//! executors before the instance is reclaimed.
+//!
+//! A notice is received when the configured command exits successfully, or when the
+//! configured metadata endpoint answers with a success status and a body other than
+//! `FALSE`. This matches the spot instance action endpoint of AWS,
+//! `http://169.254.169.254/latest/meta-data/spot/instance-action`, answering 404 until a
+//! notice, and the preempted endpoint of GCE,
+//! `http://metadata.google.internal/computeMetadata/v1/instance/preempted`, answering
+//! `TRUE` once preempted. The notices of the other providers can be checked by a command.
+
+use std::time::Duration;
+
+use hyper::{Body, Client, Request};
+use log::{info, warn};
+use tokio::process::Command;
+
+/// Header required by the metadata server of GCE, ignored by the others
+const METADATA_FLAVOR: (&str, &str) = ("Metadata-Flavor", "Google");
+
+/// Wait until a preemption notice is received by `command` or `url`, polled every
+/// `interval`, and return its description. Never returns if neither is configured.
+pub async fn wait_for_preemption_notice(
+    command: Option<String>,
+    url: Option<String>,
+    interval: Duration,
+) -> String {
+    if command.is_none() && url.is_none() {
+        return futures::future::pending().await;
+    }
+    info!(
+        "Watching for preemption notices every {interval:?} with command {command:?} \
+        and url {url:?}"
+    );
+    let client = Client::new();
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if let Some(command) = &command {
+            if let Some(notice) = check_command(command).await {
+                return notice;
+            }
+        }
+        if let Some(url) = &url {
+            if let Some(notice) = check_url(&client, url).await {
+                return notice;
+            }
+        }
+    }
+}
+
+async fn check_command(command: &str) -> Option<String> {
+    match Command::new("sh").arg("-c").arg(command).output().await {
+        Ok(output) if output.status.success() => Some(format!(
+            "preemption notice from command {command}: {}",
+            String::from_utf8_lossy(&output.stdout).trim()
+        )),
+        Ok(_) => None,
+        Err(e) => {
+            warn!("Failed to run preemption notice command {command}: {e}");
+            None
+        }
+    }
+}
+
+async fn check_url(
+    client: &Client<hyper::client::HttpConnector>,
+    url: &str,
+) -> Option<String> {
+    let request = Request::get(url)
+        .header(METADATA_FLAVOR.0, METADATA_FLAVOR.1)
+        .body(Body::empty());
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Invalid preemption notice url {url}: {e}");
+            return None;
+        }
+    };
+    let response = match client.request(request).await {
+        Ok(response) => response,
+        Err(e) => {
+            warn!("Failed to poll preemption notice url {url}: {e}");
+            return None;
+        }
+    };
+    if !response.status().is_success() {
+        return None;
+    }
+    let body = hyper::body::to_bytes(response.into_body()).await.ok()?;
+    let body = String::from_utf8_lossy(&body);
+    let body = body.trim();
+    (!body.eq_ignore_ascii_case("false"))
+        .then(|| format!("preemption notice from {url}: {body}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::wait_for_preemption_notice;
+
+    #[tokio::test]
+    async fn test_command_preemption_notice() {
+        let notice = wait_for_preemption_notice(
+            Some("echo terminate".to_owned()),
+            None,
+            Duration::from_millis(10),
+        )
+        .await;
+        assert!(notice.ends_with("terminate"));
+
+        let no_notice = tokio::time::timeout(
+            Duration::from_millis(100),
+            wait_for_preemption_notice(
+                Some("exit 1".to_owned()),
+                None,
+                Duration::from_millis(10),
+            ),
+        )
+        .await;
+        assert!(no_notice.is_err());
+    }
+}
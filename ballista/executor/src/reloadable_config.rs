@@ -0,0 +1,201 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Executor settings which can be changed without restarting the executor
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::DEFAULT_SHUFFLE_READER_MAX_REQUESTS;
+use log::info;
+use tracing_subscriber::EnvFilter;
+
+#[cfg(not(windows))]
+use ballista_core::cache_layer::CacheLayer;
+
+/// The log level of the executor, see the `log_level_setting` executor parameter
+pub const LOG_LEVEL_SETTING: &str = "log_level_setting";
+/// The maximum number of concurrent requests a task sends to fetch shuffle partitions
+pub const SHUFFLE_READER_MAX_REQUESTS: &str = "shuffle_reader_max_requests";
+/// The maximum data size of the data cache, see the `cache_capacity` executor parameter
+pub const CACHE_CAPACITY: &str = "cache_capacity";
+
+/// Replaces the log filter of the executor
+pub type LogFilterReloader = Box<dyn Fn(EnvFilter) -> Result<()> + Send + Sync>;
+
+/// The settings of an executor which can be changed while it is running, through the
+/// `UpdateExecutorConfig` gRPC call or by reloading its configuration files. Changes apply
+/// to running and new tasks, without losing the shuffle data of the executor.
+pub struct ReloadableConfig {
+    log_filter_reloader: Option<LogFilterReloader>,
+    shuffle_reader_max_requests: AtomicUsize,
+    #[cfg(not(windows))]
+    data_cache: Option<CacheLayer>,
+}
+
+impl Default for ReloadableConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_SHUFFLE_READER_MAX_REQUESTS)
+    }
+}
+
+impl ReloadableConfig {
+    pub fn new(shuffle_reader_max_requests: usize) -> Self {
+        Self {
+            log_filter_reloader: None,
+            shuffle_reader_max_requests: AtomicUsize::new(shuffle_reader_max_requests),
+            #[cfg(not(windows))]
+            data_cache: None,
+        }
+    }
+
+    /// Allow the log level to be changed with the given reloader
+    pub fn with_log_filter_reloader(mut self, reloader: LogFilterReloader) -> Self {
+        self.log_filter_reloader = Some(reloader);
+        self
+    }
+
+    /// Allow the capacity of the given data cache to be changed
+    #[cfg(not(windows))]
+    pub fn with_data_cache(mut self, data_cache: CacheLayer) -> Self {
+        self.data_cache = Some(data_cache);
+        self
+    }
+
+    /// The maximum number of concurrent requests a task sends to fetch shuffle partitions
+    pub fn shuffle_reader_max_requests(&self) -> usize {
+        self.shuffle_reader_max_requests.load(Ordering::Relaxed)
+    }
+
+    /// Apply the given settings, keyed by the names of the executor parameters. Either all
+    /// the settings are applied, or none of them if any is unknown or invalid.
+    pub fn update(&self, settings: &HashMap<String, String>) -> Result<()> {
+        let mut log_filter = None;
+        let mut shuffle_reader_max_requests = None;
+        let mut cache_capacity = None;
+        for (key, value) in settings {
+            match key.as_str() {
+                LOG_LEVEL_SETTING => {
+                    if self.log_filter_reloader.is_none() {
+                        return Err(BallistaError::General(
+                            "The log level of this executor cannot be changed".to_owned(),
+                        ));
+                    }
+                    log_filter = Some(EnvFilter::try_new(value).map_err(|e| {
+                        BallistaError::General(format!(
+                            "Invalid value '{value}' for {key}: {e}"
+                        ))
+                    })?);
+                }
+                SHUFFLE_READER_MAX_REQUESTS => {
+                    shuffle_reader_max_requests = match value.parse::<usize>() {
+                        Ok(max_requests) if max_requests > 0 => Some(max_requests),
+                        _ => {
+                            return Err(BallistaError::General(format!(
+                                "Invalid value '{value}' for {key}, expected a positive integer"
+                            )))
+                        }
+                    };
+                }
+                CACHE_CAPACITY => {
+                    cache_capacity = Some(value.parse::<usize>().map_err(|e| {
+                        BallistaError::General(format!(
+                            "Invalid value '{value}' for {key}: {e}"
+                        ))
+                    })?);
+                }
+                _ => {
+                    return Err(BallistaError::General(format!(
+                        "Executor setting '{key}' cannot be changed without restarting the executor"
+                    )))
+                }
+            }
+        }
+
+        if let (Some(log_filter), Some(reloader)) =
+            (log_filter, self.log_filter_reloader.as_ref())
+        {
+            reloader(log_filter)?;
+        }
+        if let Some(max_requests) = shuffle_reader_max_requests {
+            self.shuffle_reader_max_requests
+                .store(max_requests, Ordering::Relaxed);
+        }
+        if let Some(capacity) = cache_capacity {
+            self.set_cache_capacity(capacity);
+        }
+        info!("Updated executor settings {:?}", settings);
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    fn set_cache_capacity(&self, capacity: usize) {
+        match &self.data_cache {
+            Some(CacheLayer::LocalDiskFile(cache)) => cache.set_capacity(capacity),
+            Some(CacheLayer::LocalMemoryFile(cache)) => cache.set_capacity(capacity),
+            None => {}
+        }
+    }
+
+    #[cfg(windows)]
+    fn set_cache_capacity(&self, _capacity: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_update_settings() -> Result<()> {
+        let log_filters = Arc::new(parking_lot::Mutex::new(vec![]));
+        let reloaded = log_filters.clone();
+        let config =
+            ReloadableConfig::new(10).with_log_filter_reloader(Box::new(move |filter| {
+                reloaded.lock().push(filter.to_string());
+                Ok(())
+            }));
+        assert_eq!(config.shuffle_reader_max_requests(), 10);
+
+        let settings = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+        config.update(&settings(&[
+            (SHUFFLE_READER_MAX_REQUESTS, "20"),
+            (LOG_LEVEL_SETTING, "debug"),
+        ]))?;
+        assert_eq!(config.shuffle_reader_max_requests(), 20);
+        assert_eq!(log_filters.lock().as_slice(), &["debug".to_owned()]);
+
+        // nothing is applied if any setting is invalid
+        assert!(config
+            .update(&settings(&[
+                (SHUFFLE_READER_MAX_REQUESTS, "30"),
+                ("concurrent_tasks", "4"),
+            ]))
+            .is_err());
+        assert!(config
+            .update(&settings(&[(SHUFFLE_READER_MAX_REQUESTS, "0")]))
+            .is_err());
+        assert_eq!(config.shuffle_reader_max_requests(), 20);
+        Ok(())
+    }
+}
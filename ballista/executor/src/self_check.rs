@@ -0,0 +1,111 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Checks of the environment of the executor when it starts, and the capabilities it
+//! advertises to the scheduler when it registers
+
+use std::fs;
+use std::path::Path;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::scheduler::ExecutorCapabilities;
+use log::info;
+
+/// Name of the file written to the work dir to check that it is writable
+const PROBE_FILE: &str = ".ballista-self-check";
+
+/// Check that the executor can write shuffle files to each of its work dirs, then return
+/// the capabilities of the executor, with the free space of the disks holding the work dirs
+pub fn self_check(work_dirs: &[String]) -> Result<ExecutorCapabilities> {
+    let mut available = 0;
+    for work_dir in work_dirs {
+        let probe = Path::new(work_dir).join(PROBE_FILE);
+        fs::write(&probe, b"ballista")
+            .and_then(|_| fs::remove_file(&probe))
+            .map_err(|e| {
+                BallistaError::General(format!(
+                    "Work dir {work_dir} is not writable: {e}"
+                ))
+            })?;
+        available += available_disk(work_dir)?;
+    }
+
+    let capabilities = ExecutorCapabilities::local(available);
+    info!(
+        "Executor capabilities: object stores {:?}, shuffle compression codecs {:?}, \
+        {} bytes of free disk, Ballista {}, DataFusion {}, Arrow {}",
+        capabilities.object_store_schemes,
+        capabilities.shuffle_compression_codecs,
+        capabilities.available_disk,
+        capabilities.ballista_version,
+        capabilities.datafusion_version,
+        capabilities.arrow_version
+    );
+    Ok(capabilities)
+}
+
+/// Free space in bytes of the file system holding the given dir, available to unprivileged
+/// users
+#[cfg(unix)]
+pub(crate) fn available_disk(dir: &str) -> Result<u64> {
+    use std::ffi::CString;
+
+    let path = CString::new(dir)
+        .map_err(|e| BallistaError::General(format!("Invalid work dir {dir}: {e}")))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(BallistaError::General(format!(
+            "Failed to get the free space of work dir {dir}: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// The free disk space is not reported on the other platforms
+#[cfg(not(unix))]
+pub(crate) fn available_disk(_dir: &str) -> Result<u64> {
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use ballista_core::BALLISTA_VERSION;
+    use tempfile::TempDir;
+
+    use super::self_check;
+
+    #[test]
+    fn test_self_check() {
+        let work_dir = TempDir::new().unwrap();
+        let other_work_dir = TempDir::new().unwrap();
+        let work_dirs = vec![
+            work_dir.path().to_str().unwrap().to_owned(),
+            other_work_dir.path().to_str().unwrap().to_owned(),
+        ];
+        let capabilities = self_check(&work_dirs).unwrap();
+        assert_eq!(BALLISTA_VERSION, capabilities.ballista_version);
+        assert!(capabilities.supports_object_store("file"));
+        assert!(capabilities.check_compatibility().is_ok());
+        // the probe file is removed
+        assert_eq!(0, std::fs::read_dir(work_dir.path()).unwrap().count());
+
+        let missing = work_dir.path().join("missing");
+        assert!(self_check(&[missing.to_str().unwrap().to_owned()]).is_err());
+    }
+}
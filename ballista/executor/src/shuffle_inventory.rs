@@ -0,0 +1,219 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Inventory of the shuffle files held by the executor, reported to the scheduler to
+//! reconcile them with the active jobs
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ballista_core::error::Result;
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::serde::protobuf::{
+    ReportShuffleInventoryParams, ShuffleInventoryJob, ShuffleInventoryStage,
+};
+use log::{info, warn};
+use tokio::fs;
+use tonic::transport::Channel;
+
+/// List the shuffle files under the work dirs, laid out by the shuffle writers as
+/// `job_id/stage_id/map_partition/data.arrow` without output partitioning, and as
+/// `job_id/stage_id/output_partition/data-{map_partition}.arrow` with hash partitioning.
+/// The files of a job spread over several work dirs are merged, the other files and
+/// directories are ignored.
+pub async fn list_shuffle_inventory(
+    work_dirs: &[String],
+) -> Result<Vec<ShuffleInventoryJob>> {
+    let mut jobs: BTreeMap<String, BTreeMap<u32, BTreeSet<u32>>> = BTreeMap::new();
+    for work_dir in work_dirs {
+        let mut job_dirs = fs::read_dir(work_dir).await?;
+        while let Some(job_dir) = job_dirs.next_entry().await? {
+            if !job_dir.file_type().await?.is_dir() {
+                continue;
+            }
+            let Ok(job_id) = job_dir.file_name().into_string() else {
+                continue;
+            };
+            let stages = list_job_stages(&job_dir.path()).await?;
+            if stages.is_empty() {
+                continue;
+            }
+            let job = jobs.entry(job_id).or_default();
+            for (stage_id, map_partitions) in stages {
+                job.entry(stage_id).or_default().extend(map_partitions);
+            }
+        }
+    }
+    Ok(jobs
+        .into_iter()
+        .map(|(job_id, stages)| ShuffleInventoryJob {
+            job_id,
+            stages: stages
+                .into_iter()
+                .map(|(stage_id, map_partitions)| ShuffleInventoryStage {
+                    stage_id,
+                    map_partitions: map_partitions.into_iter().collect(),
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+async fn list_job_stages(job_dir: &Path) -> Result<BTreeMap<u32, BTreeSet<u32>>> {
+    let mut stages = BTreeMap::new();
+    let mut stage_dirs = fs::read_dir(job_dir).await?;
+    while let Some(stage_dir) = stage_dirs.next_entry().await? {
+        let Some(stage_id) = parse_number(&stage_dir.file_name()) else {
+            continue;
+        };
+        if !stage_dir.file_type().await?.is_dir() {
+            continue;
+        }
+        let mut map_partitions = BTreeSet::new();
+        let mut partition_dirs = fs::read_dir(stage_dir.path()).await?;
+        while let Some(partition_dir) = partition_dirs.next_entry().await? {
+            let Some(partition) = parse_number(&partition_dir.file_name()) else {
+                continue;
+            };
+            if !partition_dir.file_type().await?.is_dir() {
+                continue;
+            }
+            let mut files = fs::read_dir(partition_dir.path()).await?;
+            while let Some(file) = files.next_entry().await? {
+                let name = file.file_name();
+                let Some(name) = name.to_str() else {
+                    continue;
+                };
+                if name == "data.arrow" {
+                    map_partitions.insert(partition);
+                } else if let Some(map_partition) = name
+                    .strip_prefix("data-")
+                    .and_then(|name| name.strip_suffix(".arrow"))
+                    .and_then(|name| name.parse().ok())
+                {
+                    map_partitions.insert(map_partition);
+                }
+            }
+        }
+        if !map_partitions.is_empty() {
+            stages.insert(stage_id, map_partitions);
+        }
+    }
+    Ok(stages)
+}
+
+fn parse_number(name: &std::ffi::OsStr) -> Option<u32> {
+    name.to_str().and_then(|name| name.parse().ok())
+}
+
+/// Report the shuffle files held by the executor to the scheduler, which recomputes the
+/// shuffle outputs missing from them, then remove the files of the jobs unknown to the
+/// scheduler
+pub async fn report_shuffle_inventory(
+    scheduler: &mut SchedulerGrpcClient<Channel>,
+    executor_id: &str,
+    work_dirs: &[String],
+) -> Result<()> {
+    let listed_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64;
+    let jobs = list_shuffle_inventory(work_dirs).await?;
+    let reported: HashSet<String> = jobs.iter().map(|job| job.job_id.clone()).collect();
+
+    let result = scheduler
+        .report_shuffle_inventory(ReportShuffleInventoryParams {
+            executor_id: executor_id.to_owned(),
+            jobs,
+            listed_at,
+        })
+        .await?
+        .into_inner();
+    if !result.recomputed_job_ids.is_empty() {
+        warn!(
+            "Shuffle outputs of jobs {:?} missing from the executor, recomputed by the scheduler",
+            result.recomputed_job_ids
+        );
+    }
+
+    for job_id in result.stale_job_ids {
+        // only the directories of the reported jobs are removed
+        if !reported.contains(&job_id) {
+            continue;
+        }
+        info!("Removing shuffle files of unknown job {job_id}");
+        for work_dir in work_dirs {
+            let path = Path::new(work_dir).join(&job_id);
+            if !path.exists() {
+                continue;
+            }
+            if let Err(e) = fs::remove_dir_all(&path).await {
+                warn!("Failed to remove the directory {:?} due to {}", path, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::list_shuffle_inventory;
+
+    #[tokio::test]
+    async fn test_list_shuffle_inventory() {
+        let work_dir = TempDir::new().unwrap();
+        let other_work_dir = TempDir::new().unwrap();
+        let touch = |path: &str| {
+            let path = work_dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        };
+        // hash partitioned stage
+        touch("job/1/0/data-0.arrow");
+        touch("job/1/1/data-0.arrow");
+        // map partition written to another work dir
+        let path = other_work_dir.path().join("job/1/1/data-2.arrow");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+        // stage without output partitioning
+        touch("job/2/3/data.arrow");
+        // unrelated files
+        touch("job/2/4/index.json");
+        touch("datafusion-spill/0/0/data.arrow.tmp");
+        touch("job/stats/0/data.arrow");
+
+        let work_dirs = vec![
+            work_dir.path().to_str().unwrap().to_owned(),
+            other_work_dir.path().to_str().unwrap().to_owned(),
+        ];
+        let jobs = list_shuffle_inventory(&work_dirs).await.unwrap();
+        assert_eq!(jobs.len(), 1);
+        let job = &jobs[0];
+        assert_eq!(job.job_id, "job");
+        let stages: Vec<(u32, Vec<u32>)> = job
+            .stages
+            .iter()
+            .map(|stage| (stage.stage_id, stage.map_partitions.clone()))
+            .collect();
+        assert_eq!(stages, vec![(1, vec![0, 2]), (2, vec![3])]);
+    }
+}
@@ -0,0 +1,128 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Replication of the shuffle partitions held by other executors, e.g. by an executor
+//! about to be preempted, so that the stages reading them do not recompute them once the
+//! other executor is gone.
+
+use std::path::PathBuf;
+
+use ballista_core::client::BallistaClient;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf;
+use ballista_core::serde::scheduler::PartitionId;
+use ballista_core::utils::write_stream_to_disk;
+use datafusion::physical_plan::metrics::Time;
+use log::info;
+
+/// Copy a shuffle partition held by another executor to the work dir, laid out as
+/// `job_id/stage_id/output_partition/data-{map_partition}.arrow` like the files of the
+/// shuffle writers, so that the copy is listed in the shuffle inventory of this executor.
+/// Returns the path of the copy.
+pub async fn replicate_partition(
+    work_dir: &str,
+    location: &protobuf::PartitionLocation,
+) -> Result<String> {
+    let (Some(partition_id), Some(executor)) =
+        (&location.partition_id, &location.executor_meta)
+    else {
+        return Err(BallistaError::General(format!(
+            "Incomplete location of shuffle partition {}",
+            location.path
+        )));
+    };
+    let partition_id = PartitionId::new(
+        &partition_id.job_id,
+        partition_id.stage_id as usize,
+        partition_id.partition_id as usize,
+    );
+
+    let mut path = PathBuf::from(work_dir);
+    path.push(&partition_id.job_id);
+    path.push(format!("{}", partition_id.stage_id));
+    path.push(format!("{}", partition_id.partition_id));
+    tokio::fs::create_dir_all(&path).await?;
+    path.push(format!("data-{}.arrow", location.map_partition_id));
+    let path = path.to_str().ok_or_else(|| {
+        BallistaError::General(format!("Invalid shuffle partition path {path:?}"))
+    })?;
+
+    let host = executor.host.as_str();
+    let port = executor.port as u16;
+    let mut client = BallistaClient::try_new(host, port).await?;
+    let mut stream = client
+        .fetch_partition(&executor.id, &partition_id, &location.path, host, port)
+        .await?;
+    let stats = write_stream_to_disk(&mut stream, path, &Time::new()).await?;
+    info!(
+        "Replicated shuffle partition {} of executor {} to {path}: {stats}",
+        location.path, executor.id
+    );
+    Ok(path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use ballista_core::serde::protobuf;
+    use datafusion::arrow::ipc::reader::StreamReader;
+    use tempfile::TempDir;
+
+    use super::replicate_partition;
+    use crate::flight_service::test::{start_flight_service, write_partition};
+
+    #[tokio::test]
+    async fn test_replicate_partition() {
+        let work_dir = TempDir::new().unwrap();
+        let other_work_dir = TempDir::new().unwrap();
+        let port = start_flight_service().await;
+        let location = protobuf::PartitionLocation {
+            map_partition_id: 2,
+            partition_id: Some(protobuf::PartitionId {
+                job_id: "job".to_owned(),
+                stage_id: 1,
+                partition_id: 3,
+            }),
+            executor_meta: Some(protobuf::ExecutorMetadata {
+                id: "preempted".to_owned(),
+                host: "127.0.0.1".to_owned(),
+                port: port as u32,
+                ..Default::default()
+            }),
+            partition_stats: None,
+            path: write_partition(&other_work_dir.path().join("data.arrow"), &[1, 2]),
+        };
+
+        let path = replicate_partition(work_dir.path().to_str().unwrap(), &location)
+            .await
+            .unwrap();
+        assert_eq!(
+            path,
+            work_dir
+                .path()
+                .join("job/1/3/data-2.arrow")
+                .to_str()
+                .unwrap()
+        );
+        let num_rows: usize = StreamReader::try_new(File::open(path).unwrap(), None)
+            .unwrap()
+            .map(|batch| batch.unwrap().num_rows())
+            .sum();
+        assert_eq!(num_rows, 3);
+    }
+}
@@ -16,11 +16,13 @@
 // under the License.
 
 use crate::metrics::LoggingMetricsCollector;
+use crate::self_check::self_check;
 use crate::{execution_loop, executor::Executor, flight_service::BallistaFlightService};
 use arrow_flight::flight_service_server::FlightServiceServer;
 use ballista_core::{
     error::Result,
     object_store_registry::with_object_store_registry,
+    protocol::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
     serde::protobuf::executor_registration::OptionalHost,
     serde::protobuf::{scheduler_grpc_client::SchedulerGrpcClient, ExecutorRegistration},
     serde::scheduler::ExecutorSpecification,
@@ -54,6 +56,14 @@ pub async fn new_standalone_executor<
         BALLISTA_VERSION, addr
     );
 
+    let work_dir = TempDir::new()?
+        .into_path()
+        .into_os_string()
+        .into_string()
+        .unwrap();
+    info!("work_dir: {}", work_dir);
+    let capabilities = self_check(&[work_dir.clone()])?;
+
     let executor_meta = ExecutorRegistration {
         id: Uuid::new_v4().to_string(), // assign this executor a unique ID
         optional_host: Some(OptionalHost::Host("localhost".to_string())),
@@ -66,13 +76,13 @@ pub async fn new_standalone_executor<
             }
             .into(),
         ),
+        zone: String::new(),
+        rack: String::new(),
+        instance_type: String::new(),
+        capabilities: Some(capabilities.into()),
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: MIN_PROTOCOL_VERSION,
     };
-    let work_dir = TempDir::new()?
-        .into_path()
-        .into_os_string()
-        .into_string()
-        .unwrap();
-    info!("work_dir: {}", work_dir);
 
     let config = with_object_store_registry(
         RuntimeConfig::new().with_temp_file_path(work_dir.clone()),
@@ -88,7 +98,8 @@ pub async fn new_standalone_executor<
         None,
     ));
 
-    let service = BallistaFlightService::new();
+    let service =
+        BallistaFlightService::new().with_io_pool(executor.disk_io.read.clone());
     let server = FlightServiceServer::new(service);
     tokio::spawn(
         create_grpc_server()
@@ -0,0 +1,382 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Dumps of the inputs of failed tasks.
+//!
+//! When a dump directory is configured, the executor saves the definition of each failed
+//! task, including its serialized plan, together with the locations of the shuffle
+//! partitions it read. The dump can be replayed locally, e.g. under a debugger, with the
+//! `replay-task` command of the executor binary, as long as the input shuffle partitions
+//! are still available. Only the latest dumps are kept, up to the configured maximum
+//! number of dumps.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::{ShuffleReaderExec, ShuffleReaderOptions};
+use ballista_core::object_store_registry::with_object_store_registry;
+use ballista_core::serde::protobuf::{self, KeyValuePair, ShuffleWritePartition};
+use ballista_core::serde::scheduler::from_proto::get_task_definition;
+use ballista_core::serde::scheduler::{PartitionLocation, TaskDefinition};
+use ballista_core::serde::BallistaCodec;
+use datafusion::config::ConfigOptions;
+use datafusion::execution::context::TaskContext;
+use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::SessionConfig;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+use log::{info, warn};
+use prost::Message;
+
+use crate::execution_engine::{DefaultExecutionEngine, ExecutionEngine};
+use crate::executor::Executor;
+
+/// Command of the executor binary replaying a task from its dump
+pub const REPLAY_TASK_COMMAND: &str = "replay-task";
+
+/// Extension of the files of the task dumps
+const TASK_DUMP_EXTENSION: &str = "taskdump";
+
+/// Get the path of the dump given to the `replay-task` command, if the remaining command
+/// line arguments hold this command
+pub fn replay_task_path(remaining_args: &[OsString]) -> Option<Result<PathBuf>> {
+    let position = remaining_args
+        .iter()
+        .position(|arg| arg.to_str() == Some(REPLAY_TASK_COMMAND))?;
+    Some(
+        remaining_args
+            .get(position + 1)
+            .map(PathBuf::from)
+            .ok_or_else(|| {
+                BallistaError::General(format!(
+                    "Missing task dump path, usage: {REPLAY_TASK_COMMAND} <dump>"
+                ))
+            }),
+    )
+}
+
+/// Encode the definition of a task received by the executor
+pub(crate) fn encode_task_definition<T: AsLogicalPlan, U: AsExecutionPlan>(
+    task: &TaskDefinition,
+    codec: &BallistaCodec<T, U>,
+) -> Result<protobuf::TaskDefinition> {
+    let mut plan = vec![];
+    U::try_from_physical_plan(task.plan.clone(), codec.physical_extension_codec())?
+        .try_encode(&mut plan)?;
+    Ok(protobuf::TaskDefinition {
+        task_id: task.task_id as u32,
+        task_attempt_num: task.task_attempt_num as u32,
+        job_id: task.job_id.clone(),
+        stage_id: task.stage_id as u32,
+        stage_attempt_num: task.stage_attempt_num as u32,
+        partition_id: task.partition_id as u32,
+        plan,
+        session_id: task.session_id.clone(),
+        launch_time: task.launch_time,
+        props: task
+            .props
+            .iter()
+            .map(|(key, value)| KeyValuePair {
+                key: key.clone(),
+                value: value.clone(),
+            })
+            .collect(),
+        launch_token: task.launch_token.clone(),
+    })
+}
+
+/// Save the dump of a failed task in the dump directory of the executor, if any. Cancelled
+/// tasks are not dumped.
+pub(crate) fn dump_failed_task(
+    executor: &Executor,
+    task: protobuf::TaskDefinition,
+    plan: &Arc<dyn ExecutionPlan>,
+    error: &BallistaError,
+) {
+    let Some(dump_dir) = executor.task_dump_dir.as_ref() else {
+        return;
+    };
+    if matches!(error, BallistaError::Cancelled) {
+        return;
+    }
+    let task_identity = format!(
+        "{}/{}/{}.{}",
+        task.job_id, task.stage_id, task.partition_id, task.task_attempt_num
+    );
+    match write_task_dump(dump_dir, &executor.metadata.id, task, plan, error) {
+        Ok(path) => info!("Saved dump of failed task {} to {:?}", task_identity, path),
+        Err(e) => warn!(
+            "Failed to save dump of failed task {}: {:?}",
+            task_identity, e
+        ),
+    }
+    if let Err(e) = remove_old_task_dumps(dump_dir, executor.task_dump_max_files) {
+        warn!(
+            "Failed to remove old task dumps from {:?}: {:?}",
+            dump_dir, e
+        );
+    }
+}
+
+fn write_task_dump(
+    dump_dir: &Path,
+    executor_id: &str,
+    task: protobuf::TaskDefinition,
+    plan: &Arc<dyn ExecutionPlan>,
+    error: &BallistaError,
+) -> Result<PathBuf> {
+    let mut input_partitions = vec![];
+    collect_input_partitions(plan, task.partition_id as usize, &mut input_partitions);
+    let input_partitions = input_partitions
+        .into_iter()
+        .map(|location| location.try_into())
+        .collect::<Result<Vec<_>>>()?;
+
+    let path = dump_dir.join(format!(
+        "{}-{}-{}-{}.{TASK_DUMP_EXTENSION}",
+        task.job_id, task.stage_id, task.partition_id, task.task_attempt_num
+    ));
+    let dump = protobuf::TaskDump {
+        task: Some(task),
+        input_partitions,
+        executor_id: executor_id.to_owned(),
+        error: error.to_string(),
+    };
+    std::fs::create_dir_all(dump_dir)?;
+    std::fs::write(&path, dump.encode_to_vec())?;
+    Ok(path)
+}
+
+/// Remove the oldest dumps of the dump directory until at most `max_files` dumps are
+/// left, if `max_files` is not zero
+fn remove_old_task_dumps(dump_dir: &Path, max_files: usize) -> Result<()> {
+    if max_files == 0 {
+        return Ok(());
+    }
+    let mut dumps = vec![];
+    for entry in std::fs::read_dir(dump_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some(TASK_DUMP_EXTENSION) {
+            // the dump may have been removed concurrently by another failed task
+            if let Ok(modified) = path.metadata().and_then(|meta| meta.modified()) {
+                dumps.push((modified, path));
+            }
+        }
+    }
+    if dumps.len() <= max_files {
+        return Ok(());
+    }
+    dumps.sort();
+    for (_, path) in &dumps[..dumps.len() - max_files] {
+        if let Err(e) = std::fs::remove_file(path) {
+            warn!("Failed to remove task dump {:?}: {:?}", path, e);
+        }
+    }
+    Ok(())
+}
+
+/// Collect the locations of the shuffle partitions read by a partition of the plan
+fn collect_input_partitions(
+    plan: &Arc<dyn ExecutionPlan>,
+    partition_id: usize,
+    locations: &mut Vec<PartitionLocation>,
+) {
+    if let Some(shuffle_reader) = plan.as_any().downcast_ref::<ShuffleReaderExec>() {
+        if let Some(partition) = shuffle_reader.partition.get(partition_id) {
+            locations.extend(partition.iter().cloned());
+        }
+    }
+    for child in plan.children() {
+        collect_input_partitions(&child, partition_id, locations);
+    }
+}
+
+/// Read a task dump
+pub fn read_task_dump(path: &Path) -> Result<protobuf::TaskDump> {
+    let bytes = std::fs::read(path)?;
+    protobuf::TaskDump::decode(bytes.as_slice()).map_err(|e| {
+        BallistaError::General(format!("Invalid task dump {}: {e}", path.display()))
+    })
+}
+
+/// Execute the task of a dump again, writing its output to `work_dir`. Only the built-in
+/// functions of DataFusion are available to the task.
+pub async fn replay_task(
+    dump: protobuf::TaskDump,
+    work_dir: &str,
+) -> Result<Vec<ShuffleWritePartition>> {
+    let task = dump
+        .task
+        .ok_or_else(|| BallistaError::General("Task dump without task".to_owned()))?;
+    let runtime = Arc::new(RuntimeEnv::new(with_object_store_registry(
+        RuntimeConfig::new().with_temp_file_path(work_dir),
+    ))?);
+    let codec: BallistaCodec<LogicalPlanNode, PhysicalPlanNode> =
+        BallistaCodec::default();
+    let task = get_task_definition(
+        task,
+        runtime.clone(),
+        HashMap::new(),
+        HashMap::new(),
+        HashMap::new(),
+        codec,
+    )?;
+
+    let mut config = ConfigOptions::new();
+    for (k, v) in task.props.iter() {
+        if let Err(e) = config.set(k, v) {
+            warn!("Fail to set session config for ({},{}): {:?}", k, v, e);
+        }
+    }
+    let session_config = SessionConfig::from(config)
+        .with_extension(Arc::new(ShuffleReaderOptions::default()));
+    let task_context = Arc::new(TaskContext::new(
+        Some(format!("replay of task {}", task.task_id)),
+        task.session_id.clone(),
+        session_config,
+        task.function_registry.scalar_functions.clone(),
+        task.function_registry.aggregate_functions.clone(),
+        task.function_registry.window_functions.clone(),
+        runtime,
+    ));
+
+    let query_stage_exec = DefaultExecutionEngine {}.create_query_stage_exec(
+        task.job_id.clone(),
+        task.stage_id,
+        task.plan.clone(),
+        work_dir,
+    )?;
+    query_stage_exec
+        .execute_query_stage(task.partition_id, task_context)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::execution_plans::ShuffleWriterExec;
+    use ballista_core::serde::scheduler::SimpleFunctionRegistry;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::placeholder_row::PlaceholderRowExec;
+    use tempfile::TempDir;
+
+    fn failed_task(
+        partition_id: u32,
+    ) -> (protobuf::TaskDefinition, Arc<dyn ExecutionPlan>) {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let plan: Arc<dyn ExecutionPlan> = Arc::new(
+            ShuffleWriterExec::try_new(
+                "job".to_owned(),
+                1,
+                Arc::new(PlaceholderRowExec::new(schema)),
+                "/executor/work_dir".to_owned(),
+                None,
+            )
+            .unwrap(),
+        );
+        let task = TaskDefinition {
+            task_id: partition_id as usize,
+            task_attempt_num: 0,
+            job_id: "job".to_owned(),
+            stage_id: 1,
+            stage_attempt_num: 0,
+            partition_id: partition_id as usize,
+            plan: plan.clone(),
+            launch_time: 0,
+            session_id: "session".to_owned(),
+            props: Arc::new(HashMap::new()),
+            function_registry: Arc::new(SimpleFunctionRegistry {
+                scalar_functions: HashMap::new(),
+                aggregate_functions: HashMap::new(),
+                window_functions: HashMap::new(),
+            }),
+            launch_token: String::new(),
+        };
+        let codec: BallistaCodec<LogicalPlanNode, PhysicalPlanNode> =
+            BallistaCodec::default();
+        (encode_task_definition(&task, &codec).unwrap(), plan)
+    }
+
+    #[test]
+    fn test_replay_task_path() {
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        assert!(replay_task_path(&args(&["print-config"])).is_none());
+        assert!(matches!(
+            replay_task_path(&args(&[REPLAY_TASK_COMMAND])),
+            Some(Err(_))
+        ));
+        assert_eq!(
+            PathBuf::from("/tmp/job-1-2-0.taskdump"),
+            replay_task_path(&args(&[REPLAY_TASK_COMMAND, "/tmp/job-1-2-0.taskdump"]))
+                .unwrap()
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_and_replay_task_dump() {
+        let dump_dir = TempDir::new().unwrap();
+        let work_dir = TempDir::new().unwrap();
+        let (task, plan) = failed_task(0);
+        let error = BallistaError::General("task failed".to_owned());
+        let path =
+            write_task_dump(dump_dir.path(), "executor", task.clone(), &plan, &error)
+                .unwrap();
+
+        let dump = read_task_dump(&path).unwrap();
+        assert_eq!(Some(task), dump.task);
+        assert_eq!("executor", dump.executor_id);
+        assert_eq!(error.to_string(), dump.error);
+        assert!(dump.input_partitions.is_empty());
+
+        let partitions = replay_task(dump, work_dir.path().to_str().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(1, partitions.len());
+        assert_eq!(1, partitions[0].num_rows);
+        // the output of the replay is written to the given work directory
+        assert!(partitions[0]
+            .path
+            .starts_with(work_dir.path().to_str().unwrap()));
+    }
+
+    #[test]
+    fn test_remove_old_task_dumps() {
+        let dump_dir = TempDir::new().unwrap();
+        let error = BallistaError::General("task failed".to_owned());
+        let paths = (0..3)
+            .map(|partition_id| {
+                let (task, plan) = failed_task(partition_id);
+                write_task_dump(dump_dir.path(), "executor", task, &plan, &error).unwrap()
+            })
+            .collect::<Vec<_>>();
+        std::fs::write(dump_dir.path().join("notes.txt"), "not a dump").unwrap();
+
+        remove_old_task_dumps(dump_dir.path(), 0).unwrap();
+        assert!(paths.iter().all(|path| path.exists()));
+
+        remove_old_task_dumps(dump_dir.path(), 2).unwrap();
+        assert!(!paths[0].exists());
+        assert!(paths[1].exists() && paths[2].exists());
+        assert!(dump_dir.path().join("notes.txt").exists());
+    }
+}
@@ -0,0 +1,309 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Capture of the log lines emitted while executing tasks.
+//!
+//! Tasks are executed within a [`task_span`]. The [`TaskLogLayer`] keeps the events emitted
+//! within such a span, which pass the log filter of the executor, in a bounded [`TaskLogs`]
+//! store from which they are served to the scheduler.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Write};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ballista_core::serde::protobuf::TaskLogLine;
+use parking_lot::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Span, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Name of the span of a task
+const TASK_SPAN_NAME: &str = "ballista_task";
+
+/// Create the span within which a task is executed
+pub fn task_span(job_id: &str, stage_id: usize, task_id: usize) -> Span {
+    tracing::info_span!(
+        TASK_SPAN_NAME,
+        job_id = job_id,
+        stage_id = stage_id as u64,
+        task_id = task_id as u64
+    )
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TaskKey {
+    job_id: String,
+    stage_id: usize,
+    task_id: usize,
+}
+
+#[derive(Default)]
+struct TaskLog {
+    lines: VecDeque<TaskLogLine>,
+    /// Number of the oldest lines dropped to respect the limit of lines per task
+    dropped_lines: u64,
+}
+
+#[derive(Default)]
+struct TaskLogsInner {
+    logs: HashMap<TaskKey, TaskLog>,
+    /// Keys of the logs, oldest first
+    order: VecDeque<TaskKey>,
+}
+
+/// Log lines of the latest tasks executed by the executor. At most `max_lines` lines are
+/// kept per task, the oldest ones are dropped first, for at most `max_tasks` tasks.
+pub struct TaskLogs {
+    max_lines: usize,
+    max_tasks: usize,
+    inner: Mutex<TaskLogsInner>,
+}
+
+impl TaskLogs {
+    pub fn new(max_lines: usize, max_tasks: usize) -> Self {
+        Self {
+            max_lines,
+            max_tasks,
+            inner: Mutex::new(TaskLogsInner::default()),
+        }
+    }
+
+    /// Whether log lines are kept at all
+    pub fn is_enabled(&self) -> bool {
+        self.max_lines > 0 && self.max_tasks > 0
+    }
+
+    fn append(&self, key: &TaskKey, line: TaskLogLine) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut inner = self.inner.lock();
+        if !inner.logs.contains_key(key) {
+            while inner.order.len() >= self.max_tasks {
+                if let Some(oldest) = inner.order.pop_front() {
+                    inner.logs.remove(&oldest);
+                }
+            }
+            inner.order.push_back(key.clone());
+        }
+        let log = inner.logs.entry(key.clone()).or_default();
+        if log.lines.len() >= self.max_lines {
+            log.lines.pop_front();
+            log.dropped_lines += 1;
+        }
+        log.lines.push_back(line);
+    }
+
+    /// Get the lines kept for a task and the number of its dropped lines, `None` if no line
+    /// of the task is kept
+    pub fn get(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        task_id: usize,
+    ) -> Option<(Vec<TaskLogLine>, u64)> {
+        let key = TaskKey {
+            job_id: job_id.to_owned(),
+            stage_id,
+            task_id,
+        };
+        let inner = self.inner.lock();
+        inner
+            .logs
+            .get(&key)
+            .map(|log| (log.lines.iter().cloned().collect(), log.dropped_lines))
+    }
+
+    /// Remove the lines of all the tasks of a job
+    pub fn remove_job(&self, job_id: &str) {
+        let mut inner = self.inner.lock();
+        inner.logs.retain(|key, _| key.job_id != job_id);
+        inner.order.retain(|key| key.job_id != job_id);
+    }
+}
+
+impl Default for TaskLogs {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+/// Layer keeping the events emitted within task spans in a [`TaskLogs`] store
+pub struct TaskLogLayer {
+    task_logs: Arc<TaskLogs>,
+}
+
+impl TaskLogLayer {
+    pub fn new(task_logs: Arc<TaskLogs>) -> Self {
+        Self { task_logs }
+    }
+}
+
+impl<S> Layer<S> for TaskLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != TASK_SPAN_NAME || !self.task_logs.is_enabled() {
+            return;
+        }
+        let mut visitor = TaskKeyVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(job_id), Some(stage_id), Some(task_id)) =
+            (visitor.job_id, visitor.stage_id, visitor.task_id)
+        {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(TaskKey {
+                    job_id,
+                    stage_id: stage_id as usize,
+                    task_id: task_id as usize,
+                });
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if !self.task_logs.is_enabled() {
+            return;
+        }
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+        let Some(key) = scope
+            .into_iter()
+            .find_map(|span| span.extensions().get::<TaskKey>().cloned())
+        else {
+            return;
+        };
+
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        self.task_logs.append(
+            &key,
+            TaskLogLine {
+                timestamp,
+                level: metadata.level().to_string(),
+                target: metadata.target().to_owned(),
+                message: visitor.message,
+            },
+        );
+    }
+}
+
+#[derive(Default)]
+struct TaskKeyVisitor {
+    job_id: Option<String>,
+    stage_id: Option<u64>,
+    task_id: Option<u64>,
+}
+
+impl Visit for TaskKeyVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "stage_id" => self.stage_id = Some(value),
+            "task_id" => self.task_id = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "job_id" {
+            self.job_id = Some(value.to_owned());
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Format the message of an event followed by its other fields
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.insert_str(0, value);
+        } else {
+            self.record_debug(field, &value)
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message.insert_str(0, &format!("{value:?}")),
+            // fields of the events converted from log records
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.message, " {name}={value:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_task_logs() {
+        let task_logs = Arc::new(TaskLogs::new(2, 2));
+        let subscriber =
+            tracing_subscriber::registry().with(TaskLogLayer::new(task_logs.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("not within a task");
+            task_span("job1", 1, 0).in_scope(|| {
+                tracing::info!("first");
+                tracing::warn!(partition = 3, "second");
+                tracing::debug_span!("inner").in_scope(|| tracing::info!("third"));
+            });
+            task_span("job1", 1, 1).in_scope(|| tracing::info!("other task"));
+        });
+
+        let (lines, dropped_lines) = task_logs.get("job1", 1, 0).unwrap();
+        assert_eq!(1, dropped_lines);
+        let messages: Vec<&str> = lines.iter().map(|l| l.message.as_str()).collect();
+        assert_eq!(vec!["second partition=3", "third"], messages);
+        assert_eq!("WARN", lines[0].level);
+        assert_eq!(1, task_logs.get("job1", 1, 1).unwrap().0.len());
+
+        // the oldest task is evicted
+        let key = TaskKey {
+            job_id: "job2".to_owned(),
+            stage_id: 1,
+            task_id: 0,
+        };
+        task_logs.append(&key, TaskLogLine::default());
+        assert!(task_logs.get("job1", 1, 0).is_none());
+        assert!(task_logs.get("job2", 1, 0).is_some());
+
+        task_logs.remove_job("job1");
+        assert!(task_logs.get("job1", 1, 1).is_none());
+        assert!(task_logs.get("job2", 1, 0).is_some());
+    }
+}
@@ -32,3 +32,12 @@ pub async fn sig_term() -> io::Result<()> {
     os_impl::ctrl_break()?.recv().await;
     Ok(())
 }
+
+/// Waits for a SIGHUP signal, which never comes on Windows
+pub async fn sig_hup() -> io::Result<()> {
+    #[cfg(unix)]
+    os_impl::signal(SignalKind::hangup())?.recv().await;
+    #[cfg(windows)]
+    std::future::pending::<()>().await;
+    Ok(())
+}
@@ -0,0 +1,165 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The work dirs of an executor, typically one per disk, over which the tasks spread the
+//! shuffle files they write
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ballista_core::config::WorkDirPlacement;
+use ballista_core::error::{BallistaError, Result};
+use log::warn;
+use parking_lot::Mutex;
+
+use crate::self_check::available_disk;
+
+/// The work dirs of an executor and the placement of the shuffle files of the tasks over
+/// them
+#[derive(Debug)]
+pub struct WorkDirs {
+    dirs: Vec<String>,
+    placement: WorkDirPlacement,
+    next: AtomicUsize,
+    /// The current weights of the smooth weighted round-robin of the free space placement
+    current_weights: Mutex<Vec<i128>>,
+}
+
+impl WorkDirs {
+    pub fn try_new(dirs: Vec<String>, placement: WorkDirPlacement) -> Result<Self> {
+        if dirs.is_empty() {
+            return Err(BallistaError::General(
+                "An executor needs at least one work dir".to_owned(),
+            ));
+        }
+        Ok(Self {
+            current_weights: Mutex::new(vec![0; dirs.len()]),
+            dirs,
+            placement,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    /// A single work dir
+    pub fn single(dir: impl Into<String>) -> Self {
+        Self::try_new(vec![dir.into()], WorkDirPlacement::RoundRobin)
+            .expect("one work dir")
+    }
+
+    /// Parse a comma separated list of work dirs
+    pub fn parse(dirs: &str, placement: WorkDirPlacement) -> Result<Self> {
+        Self::try_new(
+            dirs.split(',')
+                .map(str::trim)
+                .filter(|dir| !dir.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            placement,
+        )
+    }
+
+    pub fn dirs(&self) -> &[String] {
+        &self.dirs
+    }
+
+    /// The first work dir, which holds the files of the executor other than the shuffle
+    /// files
+    pub fn primary(&self) -> &str {
+        &self.dirs[0]
+    }
+
+    /// The work dir to which the next task writes its shuffle files
+    pub fn next_dir(&self) -> &str {
+        if self.dirs.len() == 1 {
+            return &self.dirs[0];
+        }
+        let index = match self.placement {
+            WorkDirPlacement::RoundRobin => {
+                self.next.fetch_add(1, Ordering::Relaxed) % self.dirs.len()
+            }
+            WorkDirPlacement::FreeSpace => {
+                let free_space: Vec<u64> = self
+                    .dirs
+                    .iter()
+                    .map(|dir| {
+                        available_disk(dir).unwrap_or_else(|e| {
+                            warn!("Failed to get the free space of work dir {dir}: {e}");
+                            0
+                        })
+                    })
+                    .collect();
+                smooth_weighted_pick(&mut self.current_weights.lock(), &free_space)
+            }
+        };
+        &self.dirs[index]
+    }
+}
+
+/// Pick an index in proportion to its weight with the smooth weighted round-robin of nginx,
+/// which interleaves the picks rather than picking the heaviest index in a row. The indexes
+/// are picked evenly if all the weights are 0.
+fn smooth_weighted_pick(current_weights: &mut [i128], weights: &[u64]) -> usize {
+    let weights: Vec<i128> = if weights.iter().all(|weight| *weight == 0) {
+        vec![1; weights.len()]
+    } else {
+        weights.iter().map(|weight| *weight as i128).collect()
+    };
+    let total: i128 = weights.iter().sum();
+    let mut picked = 0;
+    for (index, weight) in weights.iter().enumerate() {
+        current_weights[index] += weight;
+        if current_weights[index] > current_weights[picked] {
+            picked = index;
+        }
+    }
+    current_weights[picked] -= total;
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use ballista_core::config::WorkDirPlacement;
+
+    use super::{smooth_weighted_pick, WorkDirs};
+
+    #[test]
+    fn test_round_robin_placement() {
+        let work_dirs =
+            WorkDirs::parse("/disk1, /disk2,,/disk3", WorkDirPlacement::RoundRobin)
+                .unwrap();
+        assert_eq!(work_dirs.dirs(), &["/disk1", "/disk2", "/disk3"]);
+        assert_eq!(work_dirs.primary(), "/disk1");
+        let picked: Vec<&str> = (0..4).map(|_| work_dirs.next_dir()).collect();
+        assert_eq!(picked, vec!["/disk1", "/disk2", "/disk3", "/disk1"]);
+
+        assert!(WorkDirs::parse(" , ", WorkDirPlacement::RoundRobin).is_err());
+    }
+
+    #[test]
+    fn test_smooth_weighted_pick() {
+        let mut current_weights = vec![0; 3];
+        let picked: Vec<usize> = (0..6)
+            .map(|_| smooth_weighted_pick(&mut current_weights, &[300, 200, 100]))
+            .collect();
+        assert_eq!(picked, vec![0, 1, 0, 2, 1, 0]);
+
+        let mut current_weights = vec![0; 2];
+        let picked: Vec<usize> = (0..4)
+            .map(|_| smooth_weighted_pick(&mut current_weights, &[0, 0]))
+            .collect();
+        assert_eq!(picked, vec![0, 1, 0, 1]);
+    }
+}
@@ -0,0 +1,92 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Scheduling throughput of the scheduler, see [`ballista_scheduler::benchmark`]
+
+use ballista_scheduler::benchmark::{BenchmarkConfig, SchedulerBenchmark};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokio::runtime::Runtime;
+
+/// Jobs per second, with more and more jobs submitted at once
+fn jobs(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("jobs");
+    for num_jobs in [1, 10, 100] {
+        let config = BenchmarkConfig {
+            num_jobs,
+            ..BenchmarkConfig::default()
+        };
+        let benchmark = runtime
+            .block_on(SchedulerBenchmark::try_new(config))
+            .unwrap();
+        group.throughput(Throughput::Elements(num_jobs as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_jobs),
+            &benchmark,
+            |b, benchmark| {
+                b.to_async(&runtime)
+                    .iter(|| async { benchmark.run().await.unwrap() })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Task assignments per second, with more and more tasks per job
+fn tasks(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("tasks");
+    for partitions in [4, 16, 64] {
+        let config = BenchmarkConfig {
+            num_jobs: 10,
+            partitions,
+            ..BenchmarkConfig::default()
+        };
+        let benchmark = runtime
+            .block_on(SchedulerBenchmark::try_new(config))
+            .unwrap();
+        // the number of tasks of a run only depends on the plan
+        let num_tasks = runtime.block_on(benchmark.run()).unwrap().num_tasks;
+        group.throughput(Throughput::Elements(num_tasks as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(partitions),
+            &benchmark,
+            |b, benchmark| {
+                b.to_async(&runtime)
+                    .iter(|| async { benchmark.run().await.unwrap() })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Prints the scheduling throughput and event loop latency of a single run, as measured
+/// by the driver, next to the criterion measurements
+fn summary(_c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let result = runtime
+        .block_on(async {
+            let benchmark =
+                SchedulerBenchmark::try_new(BenchmarkConfig::default()).await?;
+            benchmark.run().await
+        })
+        .unwrap();
+    println!("scheduler: {result}");
+}
+
+criterion_group!(benches, jobs, tasks, summary);
+criterion_main!(benches);
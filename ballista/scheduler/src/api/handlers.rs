@@ -11,10 +11,13 @@
 // limitations under the License.
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::readiness::{ReadinessStage, SchedulerReadiness};
 use crate::scheduler_server::SchedulerServer;
-use crate::state::execution_graph::ExecutionStage;
+use crate::state::execution_graph::{ExecutionStage, RunningTaskInfo};
 use crate::state::execution_graph_dot::ExecutionGraphDot;
+use crate::state::execution_graph_json::ExecutionGraphDescription;
 use ballista_core::serde::protobuf::job_status::Status;
+use ballista_core::serde::scheduler::ExecutorCapabilities;
 use ballista_core::BALLISTA_VERSION;
 use datafusion::physical_plan::metrics::{MetricValue, MetricsSet, Time};
 use datafusion_proto::logical_plan::AsLogicalPlan;
@@ -25,19 +28,44 @@ use graphviz_rust::printer::PrinterContext;
 use http::header::CONTENT_TYPE;
 
 use std::time::Duration;
+use warp::http::StatusCode;
 use warp::Rejection;
 
 #[derive(Debug, serde::Serialize)]
 struct SchedulerStateResponse {
     started: u128,
     version: &'static str,
+    /// Whether the scheduler is a standby which was not promoted yet
+    standby: bool,
 }
+
+#[derive(Debug, serde::Serialize)]
+struct ReadinessResponse {
+    ready: bool,
+    stage: ReadinessStage,
+    /// Whether the scheduler is a standby which was not promoted yet
+    standby: bool,
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct ExecutorMetaResponse {
     pub id: String,
     pub host: String,
     pub port: u16,
     pub last_seen: u128,
+    pub capabilities: ExecutorCapabilities,
+    /// The disk IO pools of the executor, as of its last heartbeat
+    pub disk_io: Vec<DiskIoPoolResponse>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct DiskIoPoolResponse {
+    pub pool: String,
+    pub max_concurrency: u64,
+    pub running: u64,
+    pub queued: u64,
+    pub completed: u64,
+    pub wait_time_ms: u64,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -55,6 +83,28 @@ struct CancelJobResponse {
     pub cancelled: bool,
 }
 
+#[derive(Debug, serde::Serialize)]
+struct CancelAttemptResponse {
+    pub cancelled: bool,
+    pub cancelled_tasks: usize,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TaskLogLineResponse {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TaskLogsResponse {
+    pub executor_id: String,
+    pub lines: Vec<TaskLogLineResponse>,
+    pub dropped_lines: u64,
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct QueryStageSummary {
     pub stage_id: String,
@@ -71,10 +121,41 @@ pub(crate) async fn get_scheduler_state<T: AsLogicalPlan, U: AsExecutionPlan>(
     let response = SchedulerStateResponse {
         started: data_server.start_time,
         version: BALLISTA_VERSION,
+        standby: data_server.is_standby(),
     };
     Ok(warp::reply::json(&response))
 }
 
+/// Return the readiness of the scheduler, with the status 503 until it is fully
+/// initialized, while it is a standby and once it starts shutting down
+pub(crate) async fn get_readiness<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+) -> Result<impl warp::Reply, Rejection> {
+    Ok(readiness_reply(
+        &data_server.readiness,
+        data_server.is_standby(),
+    ))
+}
+
+/// Reply with the readiness of a scheduler, with the status 503 unless it is ready and
+/// not a standby
+pub(crate) fn readiness_reply(
+    readiness: &SchedulerReadiness,
+    standby: bool,
+) -> impl warp::Reply {
+    let response = ReadinessResponse {
+        ready: readiness.is_ready() && !standby,
+        stage: readiness.stage(),
+        standby,
+    };
+    let status = if response.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    warp::reply::with_status(warp::reply::json(&response), status)
+}
+
 /// Return list of executors
 pub(crate) async fn get_executors<T: AsLogicalPlan, U: AsExecutionPlan>(
     data_server: SchedulerServer<T, U>,
@@ -87,10 +168,24 @@ pub(crate) async fn get_executors<T: AsLogicalPlan, U: AsExecutionPlan>(
         .unwrap_or_default()
         .into_iter()
         .map(|(metadata, duration)| ExecutorMetaResponse {
+            disk_io: state
+                .executor_manager
+                .disk_io_metrics(&metadata.id)
+                .into_iter()
+                .map(|metrics| DiskIoPoolResponse {
+                    pool: metrics.pool,
+                    max_concurrency: metrics.max_concurrency,
+                    running: metrics.running,
+                    queued: metrics.queued,
+                    completed: metrics.completed,
+                    wait_time_ms: metrics.wait_time_nanos / 1_000_000,
+                })
+                .collect(),
             id: metadata.id,
             host: metadata.host,
             port: metadata.port,
             last_seen: duration.as_millis(),
+            capabilities: metadata.capabilities,
         })
         .collect();
 
@@ -116,6 +211,7 @@ pub(crate) async fn get_jobs<T: AsLogicalPlan, U: AsExecutionPlan>(
             let status = &job.status;
             let job_status = match &status.status {
                 Some(Status::Queued(_)) => "Queued".to_string(),
+                Some(Status::Planning(_)) => "Planning".to_string(),
                 Some(Status::Running(_)) => "Running".to_string(),
                 Some(Status::Failed(error)) => format!("Failed: {}", error.error),
                 Some(Status::Successful(completed)) => {
@@ -184,6 +280,101 @@ pub(crate) async fn cancel_job<T: AsLogicalPlan, U: AsExecutionPlan>(
     Ok(warp::reply::json(&CancelJobResponse { cancelled: true }))
 }
 
+/// Cancel the running attempt of a stage of a job, which is retried in a new attempt
+pub(crate) async fn cancel_stage<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    job_id: String,
+    stage_id: usize,
+) -> Result<impl warp::Reply, Rejection> {
+    check_job_exists(&data_server, &job_id).await?;
+
+    let running_tasks = data_server
+        .state
+        .task_manager
+        .cancel_stage_attempt(&job_id, stage_id)
+        .await
+        .map_err(|_| warp::reject())?;
+    let Some(running_tasks) = running_tasks else {
+        return Ok(warp::reply::json(&CancelAttemptResponse {
+            cancelled: false,
+            cancelled_tasks: 0,
+        }));
+    };
+    let cancelled_tasks = running_tasks.len();
+    cancel_and_revive(&data_server, running_tasks).await?;
+
+    Ok(warp::reply::json(&CancelAttemptResponse {
+        cancelled: true,
+        cancelled_tasks,
+    }))
+}
+
+/// Cancel the running attempt of a task of a job, which is scheduled again
+pub(crate) async fn cancel_task<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    job_id: String,
+    stage_id: usize,
+    partition_id: usize,
+) -> Result<impl warp::Reply, Rejection> {
+    check_job_exists(&data_server, &job_id).await?;
+
+    let cancelled = data_server
+        .state
+        .task_manager
+        .cancel_task_attempt(&job_id, stage_id, partition_id)
+        .await
+        .map_err(|_| warp::reject())?;
+    let cancelled_tasks = match cancelled {
+        Some(task) => {
+            cancel_and_revive(&data_server, vec![task]).await?;
+            1
+        }
+        None => 0,
+    };
+
+    Ok(warp::reply::json(&CancelAttemptResponse {
+        cancelled: cancelled_tasks > 0,
+        cancelled_tasks,
+    }))
+}
+
+/// 404 if the job doesn't exist
+async fn check_job_exists<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: &SchedulerServer<T, U>,
+    job_id: &str,
+) -> Result<(), Rejection> {
+    data_server
+        .state
+        .task_manager
+        .get_job_status(job_id)
+        .await
+        .map_err(|_| warp::reject())?
+        .ok_or_else(warp::reject)?;
+    Ok(())
+}
+
+/// Cancel the cancelled task attempts on their executors, and schedule their retries
+async fn cancel_and_revive<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: &SchedulerServer<T, U>,
+    running_tasks: Vec<RunningTaskInfo>,
+) -> Result<(), Rejection> {
+    let event_sender = data_server
+        .query_stage_event_loop
+        .get_sender()
+        .map_err(|_| warp::reject())?;
+    if !running_tasks.is_empty() {
+        event_sender
+            .post_event(QueryStageSchedulerEvent::CancelTasks(running_tasks))
+            .await
+            .map_err(|_| warp::reject())?;
+    }
+    event_sender
+        .post_event(QueryStageSchedulerEvent::ReviveOffers)
+        .await
+        .map_err(|_| warp::reject())?;
+    Ok(())
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct QueryStagesResponse {
     pub stages: Vec<QueryStageSummary>,
@@ -304,6 +495,55 @@ pub(crate) async fn get_job_dot_graph<T: AsLogicalPlan, U: AsExecutionPlan>(
     }
 }
 
+/// Return the log lines of a task, as kept by the executor which ran it
+pub(crate) async fn get_task_logs<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    job_id: String,
+    stage_id: usize,
+    task_id: usize,
+) -> Result<impl warp::Reply, Rejection> {
+    let result = data_server
+        .state
+        .get_task_logs(&job_id, stage_id, task_id)
+        .await
+        .ok_or_else(warp::reject::not_found)?;
+    Ok(warp::reply::json(&TaskLogsResponse {
+        executor_id: result.executor_id,
+        lines: result
+            .lines
+            .into_iter()
+            .map(|line| TaskLogLineResponse {
+                timestamp: line.timestamp,
+                level: line.level,
+                target: line.target,
+                message: line.message,
+            })
+            .collect(),
+        dropped_lines: result.dropped_lines,
+    }))
+}
+
+/// Describe the execution graph of the specified job id, including its stages, the
+/// dependencies between them, task states and metrics, and return as JSON
+pub(crate) async fn get_job_graph<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    job_id: String,
+) -> Result<impl warp::Reply, Rejection> {
+    if let Some(graph) = data_server
+        .state
+        .task_manager
+        .get_job_execution_graph(&job_id)
+        .await
+        .map_err(|_| warp::reject())?
+    {
+        Ok(warp::reply::json(&ExecutionGraphDescription::from(
+            graph.as_ref(),
+        )))
+    } else {
+        Err(warp::reject::not_found())
+    }
+}
+
 /// Generate a dot graph for the specified job id and query stage and return as plain text
 pub(crate) async fn get_query_stage_dot_graph<T: AsLogicalPlan, U: AsExecutionPlan>(
     data_server: SchedulerServer<T, U>,
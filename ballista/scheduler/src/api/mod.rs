@@ -12,16 +12,23 @@
 
 mod handlers;
 
+use crate::scheduler_server::readiness::SchedulerReadiness;
 use crate::scheduler_server::SchedulerServer;
 use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 use std::{
     pin::Pin,
+    sync::Arc,
     task::{Context as TaskContext, Poll},
 };
 use warp::filters::BoxedFilter;
-use warp::{Buf, Filter, Reply};
+use warp::http::StatusCode;
+use warp::path::FullPath;
+use warp::reject::Reject;
+use warp::{Buf, Filter, Rejection, Reply};
 
 pub enum EitherBody<A, B> {
     Left(A),
@@ -89,6 +96,10 @@ pub fn get_routes<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
         .and(with_data_server(scheduler_server.clone()))
         .and_then(handlers::get_scheduler_state);
 
+    let route_readiness = warp::path!("ready")
+        .and(with_data_server(scheduler_server.clone()))
+        .and_then(handlers::get_readiness);
+
     let route_executors = warp::path!("api" / "executors")
         .and(with_data_server(scheduler_server.clone()))
         .and_then(handlers::get_executors);
@@ -102,6 +113,21 @@ pub fn get_routes<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
         .and(with_data_server(scheduler_server.clone()))
         .and_then(|job_id, data_server| handlers::cancel_job(data_server, job_id));
 
+    let route_cancel_stage = warp::path!("api" / "job" / String / "stage" / usize)
+        .and(warp::patch())
+        .and(with_data_server(scheduler_server.clone()))
+        .and_then(|job_id, stage_id, data_server| {
+            handlers::cancel_stage(data_server, job_id, stage_id)
+        });
+
+    let route_cancel_task =
+        warp::path!("api" / "job" / String / "stage" / usize / "task" / usize)
+            .and(warp::patch())
+            .and(with_data_server(scheduler_server.clone()))
+            .and_then(|job_id, stage_id, partition_id, data_server| {
+                handlers::cancel_task(data_server, job_id, stage_id, partition_id)
+            });
+
     let route_query_stages = warp::path!("api" / "job" / String / "stages")
         .and(with_data_server(scheduler_server.clone()))
         .and_then(|job_id, data_server| handlers::get_query_stages(data_server, job_id));
@@ -110,6 +136,10 @@ pub fn get_routes<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
         .and(with_data_server(scheduler_server.clone()))
         .and_then(|job_id, data_server| handlers::get_job_dot_graph(data_server, job_id));
 
+    let route_job_graph = warp::path!("api" / "job" / String / "graph")
+        .and(with_data_server(scheduler_server.clone()))
+        .and_then(|job_id, data_server| handlers::get_job_graph(data_server, job_id));
+
     let route_query_stage_dot =
         warp::path!("api" / "job" / String / "stage" / usize / "dot")
             .and(with_data_server(scheduler_server.clone()))
@@ -117,6 +147,13 @@ pub fn get_routes<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
                 handlers::get_query_stage_dot_graph(data_server, job_id, stage_id)
             });
 
+    let route_task_logs =
+        warp::path!("api" / "job" / String / "stage" / usize / "task" / usize / "logs")
+            .and(with_data_server(scheduler_server.clone()))
+            .and_then(|job_id, stage_id, task_id, data_server| {
+                handlers::get_task_logs(data_server, job_id, stage_id, task_id)
+            });
+
     let route_job_dot_svg = warp::path!("api" / "job" / String / "dot_svg")
         .and(with_data_server(scheduler_server.clone()))
         .and_then(|job_id, data_server| handlers::get_job_svg_graph(data_server, job_id));
@@ -126,13 +163,183 @@ pub fn get_routes<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
         .and_then(|data_server| handlers::get_scheduler_metrics(data_server));
 
     let routes = route_scheduler_state
+        .or(route_readiness)
         .or(route_executors)
         .or(route_jobs)
         .or(route_cancel_job)
+        .or(route_cancel_stage)
+        .or(route_cancel_task)
         .or(route_query_stages)
         .or(route_job_dot)
+        .or(route_job_graph)
         .or(route_query_stage_dot)
+        .or(route_task_logs)
         .or(route_job_dot_svg)
         .or(route_scheduler_metrics);
     routes.boxed()
 }
+
+/// Get the routes served while the scheduler initializes, which only answer the readiness
+/// endpoint
+pub fn get_startup_routes(
+    readiness: Arc<SchedulerReadiness>,
+    standby: bool,
+) -> BoxedFilter<(impl Reply,)> {
+    warp::path!("ready")
+        .map(move || handlers::readiness_reply(&readiness, standby))
+        .boxed()
+}
+
+/// Rejection of the requests without the expected basic authentication
+#[derive(Debug)]
+struct Unauthorized;
+
+impl Reject for Unauthorized {}
+
+/// Get the routes of the dedicated HTTP server, which require the basic authentication of
+/// `user` with `password` if set, except on the readiness endpoint
+pub fn get_http_routes<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
+    scheduler_server: SchedulerServer<T, U>,
+    basic_auth: Option<(String, String)>,
+) -> BoxedFilter<(impl Reply,)> {
+    let expected = basic_auth.map(|(user, password)| {
+        format!("Basic {}", STANDARD.encode(format!("{user}:{password}")))
+    });
+    let authorized = warp::path::full()
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(move |path: FullPath, authorization: Option<String>| {
+            let authorized = match &expected {
+                Some(expected) => {
+                    path.as_str() == "/ready"
+                        || authorization
+                            .map(|authorization| {
+                                constant_time_eq(
+                                    authorization.as_bytes(),
+                                    expected.as_bytes(),
+                                )
+                            })
+                            .unwrap_or(false)
+                }
+                None => true,
+            };
+            async move {
+                if authorized {
+                    Ok(())
+                } else {
+                    Err(warp::reject::custom(Unauthorized))
+                }
+            }
+        })
+        .untuple_one();
+
+    authorized
+        .and(get_routes(scheduler_server))
+        .recover(|rejection: Rejection| async move {
+            if rejection.find::<Unauthorized>().is_some() {
+                Ok(warp::reply::with_header(
+                    StatusCode::UNAUTHORIZED,
+                    "WWW-Authenticate",
+                    "Basic realm=\"ballista\"",
+                ))
+            } else {
+                Err(rejection)
+            }
+        })
+        .boxed()
+}
+
+/// Compare secrets in a time independent of the position of their first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::SchedulerConfig;
+    use crate::metrics::default_metrics_collector;
+    use crate::scheduler_server::readiness::ReadinessStage;
+    use crate::test_utils::test_cluster_context;
+    use ballista_core::serde::BallistaCodec;
+    use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+    use warp::http::header::WWW_AUTHENTICATE;
+
+    fn basic_auth(user: &str, password: &str) -> String {
+        format!("Basic {}", STANDARD.encode(format!("{user}:{password}")))
+    }
+
+    fn scheduler_server() -> SchedulerServer<LogicalPlanNode, PhysicalPlanNode> {
+        let scheduler_server = SchedulerServer::new(
+            "localhost:50050".to_owned(),
+            test_cluster_context(),
+            BallistaCodec::default(),
+            Arc::new(SchedulerConfig::default()),
+            default_metrics_collector().unwrap(),
+        );
+        scheduler_server
+            .readiness
+            .advance(ReadinessStage::GrpcBound);
+        scheduler_server
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth() {
+        let routes = get_http_routes(
+            scheduler_server(),
+            Some(("admin".to_owned(), "secret".to_owned())),
+        );
+
+        let response = warp::test::request()
+            .path("/api/state")
+            .reply(&routes)
+            .await;
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+        assert_eq!(
+            "Basic realm=\"ballista\"",
+            response.headers()[WWW_AUTHENTICATE]
+        );
+
+        for wrong_credentials in [
+            basic_auth("admin", "wrong"),
+            basic_auth("other", "secret"),
+            "Bearer secret".to_owned(),
+        ] {
+            let response = warp::test::request()
+                .path("/api/state")
+                .header("authorization", wrong_credentials)
+                .reply(&routes)
+                .await;
+            assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+            assert!(response.headers().contains_key(WWW_AUTHENTICATE));
+        }
+
+        let response = warp::test::request()
+            .path("/api/state")
+            .header("authorization", basic_auth("admin", "secret"))
+            .reply(&routes)
+            .await;
+        assert_eq!(StatusCode::OK, response.status());
+
+        // the readiness endpoint is probed without credentials
+        let response = warp::test::request().path("/ready").reply(&routes).await;
+        assert_eq!(StatusCode::OK, response.status());
+    }
+
+    #[tokio::test]
+    async fn test_no_basic_auth() {
+        let routes = get_http_routes(scheduler_server(), None);
+        let response = warp::test::request()
+            .path("/api/state")
+            .reply(&routes)
+            .await;
+        assert_eq!(StatusCode::OK, response.status());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secrets"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+}
@@ -0,0 +1,427 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Synthetic driver measuring the scheduling throughput of the scheduler.
+//!
+//! Jobs run against the in-memory state backend on virtual executors which complete
+//! every task as soon as it is launched, so that only the time spent in the scheduler,
+//! mostly in its event loop and the `TaskManager`, is measured.
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ballista_core::config::{
+    BallistaConfig, TaskSchedulingPolicy, BALLISTA_DEFAULT_SHUFFLE_PARTITIONS,
+};
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::protocol::PROTOCOL_VERSION;
+use ballista_core::serde::protobuf::{
+    task_status, MultiTaskDefinition, ShuffleWritePartition, SuccessfulTask, TaskId,
+    TaskStatus,
+};
+use ballista_core::serde::scheduler::{
+    ExecutorData, ExecutorMetadata, ExecutorSpecification,
+};
+use ballista_core::serde::BallistaCodec;
+use ballista_core::utils::default_session_builder;
+use datafusion::arrow::datatypes::{DataType, Field, Schema};
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::prelude::{col, sum, SessionContext};
+use datafusion::test_util::scan_empty_with_partitions;
+use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+use log::error;
+use parking_lot::Mutex;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Notify;
+
+use crate::cluster::BallistaCluster;
+use crate::config::SchedulerConfig;
+use crate::metrics::SchedulerMetricsCollector;
+use crate::scheduler_server::{timestamp_millis, SchedulerServer};
+use crate::state::executor_manager::ExecutorManager;
+use crate::state::task_manager::TaskLauncher;
+
+const BENCHMARK_SCHEDULER_NAME: &str = "localhost:50050";
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkConfig {
+    /// Number of virtual executors
+    pub num_executors: usize,
+    /// Number of task slots of each virtual executor
+    pub task_slots_per_executor: usize,
+    /// Number of jobs submitted at once in each run
+    pub num_jobs: usize,
+    /// Number of partitions of both stages of each job
+    pub partitions: usize,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            num_executors: 4,
+            task_slots_per_executor: 8,
+            num_jobs: 100,
+            partitions: 16,
+        }
+    }
+}
+
+/// Measurements of a benchmark run
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    pub num_jobs: usize,
+    /// Number of tasks launched on the executors
+    pub num_tasks: usize,
+    pub elapsed: Duration,
+    /// Time between queueing each job and its submission by the event loop
+    pub event_loop_latencies: Vec<Duration>,
+}
+
+impl BenchmarkResult {
+    pub fn jobs_per_sec(&self) -> f64 {
+        self.num_jobs as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn tasks_per_sec(&self) -> f64 {
+        self.num_tasks as f64 / self.elapsed.as_secs_f64()
+    }
+
+    pub fn mean_event_loop_latency(&self) -> Duration {
+        if self.event_loop_latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        self.event_loop_latencies.iter().sum::<Duration>()
+            / self.event_loop_latencies.len() as u32
+    }
+
+    pub fn max_event_loop_latency(&self) -> Duration {
+        self.event_loop_latencies
+            .iter()
+            .max()
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for BenchmarkResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} jobs and {} tasks in {:?}: {:.1} jobs/sec, {:.1} tasks/sec, event loop latency mean {:?} max {:?}",
+            self.num_jobs,
+            self.num_tasks,
+            self.elapsed,
+            self.jobs_per_sec(),
+            self.tasks_per_sec(),
+            self.mean_event_loop_latency(),
+            self.max_event_loop_latency()
+        )
+    }
+}
+
+/// Scheduler with virtual executors, on which jobs can be run repeatedly
+pub struct SchedulerBenchmark {
+    config: BenchmarkConfig,
+    scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode>,
+    session_ctx: Arc<SessionContext>,
+    plan: LogicalPlan,
+    launched_tasks: Arc<AtomicUsize>,
+    metrics: Arc<BenchmarkMetricsCollector>,
+}
+
+impl SchedulerBenchmark {
+    pub async fn try_new(config: BenchmarkConfig) -> Result<Self> {
+        let (status_sender, mut status_receiver) = unbounded_channel();
+        let launched_tasks = Arc::new(AtomicUsize::new(0));
+        let launcher = SyntheticTaskLauncher {
+            sender: status_sender,
+            launched_tasks: launched_tasks.clone(),
+        };
+        let metrics = Arc::new(BenchmarkMetricsCollector::default());
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new_with_task_launcher(
+                BENCHMARK_SCHEDULER_NAME.to_owned(),
+                BallistaCluster::new_memory(
+                    BENCHMARK_SCHEDULER_NAME,
+                    default_session_builder,
+                ),
+                BallistaCodec::default(),
+                Arc::new(
+                    SchedulerConfig::default()
+                        .with_scheduler_policy(TaskSchedulingPolicy::PushStaged),
+                ),
+                metrics.clone(),
+                Arc::new(launcher),
+            );
+        scheduler.init().await?;
+
+        for i in 0..config.num_executors {
+            let executor_id = format!("virtual-executor-{i}");
+            let metadata = ExecutorMetadata {
+                id: executor_id.clone(),
+                host: String::default(),
+                port: 0,
+                grpc_port: 0,
+                specification: ExecutorSpecification {
+                    task_slots: config.task_slots_per_executor as u32,
+                },
+                zone: String::new(),
+                rack: String::new(),
+                instance_type: String::new(),
+                capabilities: Default::default(),
+                protocol_version: PROTOCOL_VERSION,
+            };
+            let executor_data = ExecutorData {
+                executor_id,
+                total_task_slots: config.task_slots_per_executor as u32,
+                available_task_slots: config.task_slots_per_executor as u32,
+            };
+            scheduler
+                .state
+                .executor_manager
+                .register_executor(metadata, executor_data)
+                .await?;
+        }
+
+        // the virtual executors report the status of their tasks right away
+        let scheduler_clone = scheduler.clone();
+        tokio::spawn(async move {
+            while let Some((executor_id, status)) = status_receiver.recv().await {
+                if let Err(e) = scheduler_clone
+                    .update_task_status(&executor_id, status)
+                    .await
+                {
+                    error!("Failed to update task status: {e}");
+                }
+            }
+        });
+
+        let ballista_config = BallistaConfig::builder()
+            .set(
+                BALLISTA_DEFAULT_SHUFFLE_PARTITIONS,
+                config.partitions.to_string().as_str(),
+            )
+            .build()?;
+        let session_ctx = scheduler
+            .state
+            .session_manager
+            .create_session(&ballista_config)
+            .await?;
+
+        // an aggregation, which runs in two stages
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("gmv", DataType::UInt64, false),
+        ]);
+        let plan = scan_empty_with_partitions(None, &schema, None, config.partitions)?
+            .aggregate(vec![col("id")], vec![sum(col("gmv"))])?
+            .build()?;
+
+        Ok(Self {
+            config,
+            scheduler,
+            session_ctx,
+            plan,
+            launched_tasks,
+            metrics,
+        })
+    }
+
+    /// Submit the configured number of jobs at once and wait until all of them finish
+    pub async fn run(&self) -> Result<BenchmarkResult> {
+        let launched_tasks = self.launched_tasks.load(Ordering::SeqCst);
+        let finished_jobs = self.metrics.finished_jobs.load(Ordering::SeqCst);
+        let failed_jobs = self.metrics.failed_jobs.load(Ordering::SeqCst);
+        self.metrics.event_loop_latencies.lock().clear();
+
+        let start = Instant::now();
+        for _ in 0..self.config.num_jobs {
+            let job_id = self.scheduler.state.task_manager.generate_job_id();
+            self.scheduler
+                .submit_job(&job_id, "benchmark", self.session_ctx.clone(), &self.plan)
+                .await?;
+        }
+        loop {
+            // created before checking the count so that no notification is missed
+            let notified = self.metrics.notify.notified();
+            let finished = self.metrics.finished_jobs.load(Ordering::SeqCst);
+            if finished - finished_jobs >= self.config.num_jobs {
+                break;
+            }
+            notified.await;
+        }
+        let elapsed = start.elapsed();
+
+        let failed = self.metrics.failed_jobs.load(Ordering::SeqCst) - failed_jobs;
+        if failed > 0 {
+            return Err(BallistaError::General(format!(
+                "{failed} of the benchmark jobs failed"
+            )));
+        }
+
+        Ok(BenchmarkResult {
+            num_jobs: self.config.num_jobs,
+            num_tasks: self.launched_tasks.load(Ordering::SeqCst) - launched_tasks,
+            elapsed,
+            event_loop_latencies: self.metrics.event_loop_latencies.lock().clone(),
+        })
+    }
+}
+
+/// Launcher for virtual executors, which complete every task as soon as it is launched
+struct SyntheticTaskLauncher {
+    sender: UnboundedSender<(String, Vec<TaskStatus>)>,
+    launched_tasks: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl TaskLauncher for SyntheticTaskLauncher {
+    async fn launch_tasks(
+        &self,
+        executor: &ExecutorMetadata,
+        tasks: Vec<MultiTaskDefinition>,
+        _executor_manager: &ExecutorManager,
+    ) -> Result<()> {
+        let timestamp = timestamp_millis();
+        let mut statuses = vec![];
+        for task in tasks {
+            for TaskId {
+                task_id,
+                partition_id,
+                ..
+            } in task.task_ids
+            {
+                statuses.push(TaskStatus {
+                    task_id,
+                    job_id: task.job_id.clone(),
+                    stage_id: task.stage_id,
+                    stage_attempt_num: task.stage_attempt_num,
+                    partition_id,
+                    launch_time: timestamp,
+                    start_exec_time: timestamp,
+                    end_exec_time: timestamp,
+                    metrics: vec![],
+                    status: Some(task_status::Status::Successful(SuccessfulTask {
+                        executor_id: executor.id.clone(),
+                        partitions: vec![ShuffleWritePartition {
+                            partition_id: 0,
+                            path: String::default(),
+                            num_batches: 1,
+                            num_rows: 1,
+                            num_bytes: 1,
+                            null_counts: vec![],
+                            column_bounds: vec![],
+                            bloom_filters: vec![],
+                        }],
+                    })),
+                });
+            }
+        }
+        self.launched_tasks
+            .fetch_add(statuses.len(), Ordering::SeqCst);
+
+        self.sender
+            .send((executor.id.clone(), statuses))
+            .map_err(|e| {
+                BallistaError::Internal(format!("Error sending task status: {e:?}"))
+            })
+    }
+}
+
+/// Metrics collector counting the finished jobs and recording the event loop latency
+#[derive(Default)]
+struct BenchmarkMetricsCollector {
+    finished_jobs: AtomicUsize,
+    failed_jobs: AtomicUsize,
+    event_loop_latencies: Mutex<Vec<Duration>>,
+    notify: Notify,
+}
+
+impl BenchmarkMetricsCollector {
+    fn job_finished(&self) {
+        self.finished_jobs.fetch_add(1, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+}
+
+impl SchedulerMetricsCollector for BenchmarkMetricsCollector {
+    fn record_submitted(&self, _job_id: &str, queued_at: u64, submitted_at: u64) {
+        self.event_loop_latencies.lock().push(Duration::from_millis(
+            submitted_at.saturating_sub(queued_at),
+        ));
+    }
+
+    fn record_completed(&self, _job_id: &str, _queued_at: u64, _completed_at: u64) {
+        self.job_finished();
+    }
+
+    fn record_failed(&self, _job_id: &str, _queued_at: u64, _failed_at: u64) {
+        self.failed_jobs.fetch_add(1, Ordering::SeqCst);
+        self.job_finished();
+    }
+
+    fn record_cancelled(&self, _job_id: &str) {
+        self.failed_jobs.fetch_add(1, Ordering::SeqCst);
+        self.job_finished();
+    }
+
+    fn set_pending_tasks_queue_size(&self, _value: u64) {}
+
+    fn record_state_operation(
+        &self,
+        _keyspace: &str,
+        _operation: &str,
+        _latency: Duration,
+        _failed: bool,
+    ) {
+    }
+
+    fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_benchmark_run() -> Result<()> {
+        let benchmark = SchedulerBenchmark::try_new(BenchmarkConfig {
+            num_executors: 2,
+            task_slots_per_executor: 2,
+            num_jobs: 4,
+            partitions: 4,
+        })
+        .await?;
+
+        let first = benchmark.run().await?;
+        assert_eq!(first.num_jobs, 4);
+        assert!(first.num_tasks > 0);
+        assert_eq!(first.event_loop_latencies.len(), 4);
+
+        // the scheduler can be reused across runs
+        let second = benchmark.run().await?;
+        assert_eq!(second.num_tasks, first.num_tasks);
+        assert_eq!(second.event_loop_latencies.len(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Ballista plan compiler binary, printing the distributed plan of a SQL query without a
+//! running cluster.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+
+use anyhow::{anyhow, Result};
+use ballista_core::config::BallistaConfig;
+use ballista_core::utils::default_session_builder;
+use ballista_scheduler::plan_compiler::{
+    compile_plan, describe_plan, encode_plan, register_tables,
+};
+use ballista_scheduler::state::session_manager::create_datafusion_context;
+use clap::{ArgEnum, Parser};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum PlanFormat {
+    /// The stages, their estimated task counts and the shuffles between them
+    Json,
+    /// The execution graph encoded in protobuf, as persisted by the scheduler
+    Proto,
+}
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(
+        short,
+        long,
+        multiple_occurrences = true,
+        help = "File of DDL statements creating the tables read by the query, e.g. CREATE EXTERNAL TABLE"
+    )]
+    ddl: Vec<String>,
+
+    #[clap(
+        short,
+        long,
+        help = "File of the SQL query to compile",
+        conflicts_with = "query"
+    )]
+    file: Option<String>,
+
+    #[clap(help = "SQL query to compile")]
+    query: Option<String>,
+
+    #[clap(
+        short = 'c',
+        long = "config",
+        multiple_occurrences = true,
+        help = "Setting of the session as key=value, e.g. ballista.shuffle.partitions=64"
+    )]
+    settings: Vec<String>,
+
+    #[clap(long, arg_enum, default_value = "json")]
+    format: PlanFormat,
+
+    #[clap(
+        short,
+        long,
+        help = "File to write the plan to, the standard output if none"
+    )]
+    output: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_writer(io::stderr)
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+    let args = Args::parse();
+
+    let sql = match (&args.file, &args.query) {
+        (Some(file), _) => fs::read_to_string(file)?,
+        (None, Some(query)) => query.clone(),
+        (None, None) => {
+            return Err(anyhow!("Either a query or a query file is required"))
+        }
+    };
+    let settings = args
+        .settings
+        .iter()
+        .map(|setting| {
+            setting
+                .split_once('=')
+                .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                .ok_or_else(|| anyhow!("Invalid setting {setting}, expected key=value"))
+        })
+        .collect::<Result<HashMap<_, _>>>()?;
+    let config = BallistaConfig::with_settings(settings)?;
+    let ctx = create_datafusion_context(&config, default_session_builder);
+
+    for ddl in &args.ddl {
+        register_tables(&ctx, &fs::read_to_string(ddl)?).await?;
+    }
+    let graph = compile_plan(&ctx, &sql).await?;
+    let plan = match args.format {
+        PlanFormat::Json => {
+            let mut json = serde_json::to_vec_pretty(&describe_plan(&graph))?;
+            json.push(b'\n');
+            json
+        }
+        PlanFormat::Proto => encode_plan(graph)?,
+    };
+
+    match &args.output {
+        Some(output) => fs::write(output, plan)?,
+        None => io::stdout().write_all(&plan)?,
+    }
+    Ok(())
+}
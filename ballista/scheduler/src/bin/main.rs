@@ -24,13 +24,19 @@ use anyhow::Result;
 
 use crate::config::{Config, ResultExt};
 use ballista_core::config::LogRotationPolicy;
+use ballista_core::config_file::{config_file_args, is_print_config};
+use ballista_core::plan_protection::PlanProtection;
 use ballista_core::print_version;
 use ballista_scheduler::cluster::BallistaCluster;
 use ballista_scheduler::cluster::ClusterStorage;
 use ballista_scheduler::config::{
-    ClusterStorageConfig, SchedulerConfig, TaskDistribution, TaskDistributionPolicy,
+    parse_executor_labels, parse_keyspaces, parse_topology_label, ClusterStorageConfig,
+    HttpServerConfig, SchedulerConfig, TaskDistribution, TaskDistributionPolicy,
 };
+use ballista_scheduler::metrics::{set_job_metrics_labels, JobMetricsLabels};
+use ballista_scheduler::planner::DefaultStagePlanner;
 use ballista_scheduler::scheduler_process::start_server;
+use ballista_scheduler::state::stage_alerts::parse_stage_alert_rules;
 use tracing_subscriber::EnvFilter;
 
 #[macro_use]
@@ -48,10 +54,16 @@ mod config {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // parse options
-    let (opt, _remaining_args) =
-        Config::including_optional_config_files(&["/etc/ballista/scheduler.toml"])
-            .unwrap_or_exit();
+    // parse options, from the configuration files, environment variables and flags
+    let config_args = config_file_args(env::args_os())?;
+    let (opt, remaining_args) = Config::custom_args_and_optional_files(
+        config_args.args.clone(),
+        &["/etc/ballista/scheduler.toml"],
+    )
+    .unwrap_or_exit();
+    // remove the TOML files converted from YAML files
+    drop(config_args);
+    let print_config = is_print_config(remaining_args);
 
     if opt.version {
         print_version();
@@ -126,6 +138,7 @@ async fn main() -> Result<()> {
     let task_distribution = match opt.task_distribution {
         TaskDistribution::Bias => TaskDistributionPolicy::Bias,
         TaskDistribution::RoundRobin => TaskDistributionPolicy::RoundRobin,
+        TaskDistribution::Weighted => TaskDistributionPolicy::Weighted,
         TaskDistribution::ConsistentHash => {
             let num_replicas = opt.consistent_hash_num_replicas as usize;
             let tolerance = opt.consistent_hash_tolerance as usize;
@@ -136,12 +149,65 @@ async fn main() -> Result<()> {
         }
     };
 
+    let executor_labels =
+        parse_executor_labels(&opt.executor_labels).map_err(anyhow::Error::msg)?;
+    let compressed_keyspaces =
+        parse_keyspaces(&opt.compressed_keyspaces).map_err(anyhow::Error::msg)?;
+    let task_locality_label = if opt.task_locality_label.is_empty() {
+        None
+    } else {
+        Some(parse_topology_label(&opt.task_locality_label).map_err(anyhow::Error::msg)?)
+    };
+    let plan_protection = PlanProtection::new(
+        opt.plan_signing_key.as_deref(),
+        opt.plan_encryption_key.as_deref(),
+    )?;
+
+    let http_server = match opt.http_bind_address {
+        Some(address) => {
+            let tls = match (opt.http_tls_cert_path, opt.http_tls_key_path) {
+                (Some(cert), Some(key)) => Some((cert, key)),
+                (None, None) => None,
+                _ => anyhow::bail!(
+                    "Both http_tls_cert_path and http_tls_key_path must be set to serve HTTPS"
+                ),
+            };
+            let basic_auth =
+                match (opt.http_basic_auth_user, opt.http_basic_auth_password) {
+                    (Some(user), Some(password)) => Some((user, password)),
+                    (None, None) => None,
+                    _ => anyhow::bail!(
+                    "Both http_basic_auth_user and http_basic_auth_password must be set"
+                ),
+                };
+            Some(HttpServerConfig {
+                bind_address: address.parse()?,
+                tls,
+                basic_auth,
+            })
+        }
+        None => None,
+    };
+
+    let job_metrics_labels = opt.job_metrics_labels.then(|| JobMetricsLabels {
+        allowed_job_names: opt
+            .job_metrics_allowed_job_names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        max_job_names: opt.job_metrics_max_job_names as usize,
+    });
+
     let config = SchedulerConfig {
         namespace: opt.namespace,
         external_host: opt.external_host,
         bind_port: opt.bind_port,
         scheduling_policy: opt.scheduler_policy,
         event_loop_buffer_size: opt.event_loop_buffer_size,
+        event_loop_concurrency: opt.event_loop_concurrency,
+        job_cache_shards: opt.job_cache_shards,
         task_distribution,
         finished_job_data_clean_up_interval_seconds: opt
             .finished_job_data_clean_up_interval_seconds,
@@ -158,8 +224,51 @@ async fn main() -> Result<()> {
         grpc_server_max_encoding_message_size: opt.grpc_server_max_encoding_message_size,
         executor_timeout_seconds: opt.executor_timeout_seconds,
         expire_dead_executor_interval_seconds: opt.expire_dead_executor_interval_seconds,
+        executor_heartbeat_flush_interval_ms: opt.executor_heartbeat_flush_interval_ms,
+        executor_liveness_leases: opt.executor_liveness_leases,
+        slot_reservation_timeout_seconds: opt.slot_reservation_timeout_seconds,
+        shutdown_timeout_seconds: opt.shutdown_timeout_seconds,
+        scheduler_lease_timeout_seconds: opt.scheduler_lease_timeout_seconds,
+        standby: opt.standby,
+        cluster_state_cache_ttl_ms: opt.cluster_state_cache_ttl_ms,
+        execution_graph_compression: opt.execution_graph_compression,
+        execution_graph_chunk_size: opt.execution_graph_chunk_size,
+        execution_graph_compaction_interval: opt.execution_graph_compaction_interval,
+        compressed_keyspaces,
+        state_slow_operation_ms: opt.state_slow_operation_ms,
+        job_planning_concurrency: opt.job_planning_concurrency,
+        job_idempotency_key_ttl_seconds: opt.job_idempotency_key_ttl_seconds,
+        executor_labels,
+        task_locality_label,
+        max_jobs_per_executor: opt.max_jobs_per_executor,
+        slot_deadlock_timeout_seconds: opt.slot_deadlock_timeout_seconds,
+        executor_max_memory_percent: opt.executor_max_memory_percent,
+        job_admission_policy: opt.job_admission_policy,
+        plan_protection,
+        max_job_plan_nodes: opt.max_job_plan_nodes,
+        max_job_plan_depth: opt.max_job_plan_depth,
+        max_job_stages: opt.max_job_stages,
+        max_job_tasks: opt.max_job_tasks,
+        job_metrics_labels,
+        http_server,
+        openlineage_url: opt.openlineage_url,
+        openlineage_namespace: opt.openlineage_namespace,
+        stage_alert_rules: parse_stage_alert_rules(&opt.stage_alert_rules)
+            .map_err(anyhow::Error::msg)?,
+        stage_planner: Arc::new(DefaultStagePlanner),
+        job_archive_url: opt.job_archive_url,
     };
 
+    if print_config {
+        println!("bind_address: {addr}");
+        println!("{config:#?}");
+        return Ok(());
+    }
+
+    if let Some(labels) = config.job_metrics_labels.clone() {
+        set_job_metrics_labels(labels)?;
+    }
+
     let cluster = BallistaCluster::new_from_config(&config).await?;
 
     start_server(cluster, addr, Arc::new(config)).await?;
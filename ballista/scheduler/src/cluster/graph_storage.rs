@@ -0,0 +1,218 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Layout of the execution graphs stored in a `KeyValueStore` whose values are limited in
+//! size, such as etcd.
+//!
+//! The encoded graph is optionally compressed with zstd and split in chunks. The value of the
+//! `ExecutionGraph` keyspace starts with a header followed by the first chunk, the other
+//! chunks are stored in the `ExecutionGraphChunks` keyspace under keys which change whenever
+//! the graph is saved, so that a graph is never read with chunks of another version. Values
+//! without the header are plain encoded graphs, as stored by older schedulers, which are
+//! rejected when the stored graphs are signed or encrypted with a [`PlanProtection`].
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::plan_protection::PlanProtection;
+
+/// Marks the values stored with a header
+const HEADER_MAGIC: &[u8; 4] = b"BXG1";
+/// Magic, flags, version and number of additional chunks
+const HEADER_LEN: usize = 4 + 1 + 8 + 4;
+const FLAG_COMPRESSED: u8 = 1;
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Layout of a stored execution graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GraphLayout {
+    compressed: bool,
+    version: u64,
+    /// Number of chunks stored besides the first one
+    num_chunks: u32,
+}
+
+impl GraphLayout {
+    /// Keys of the chunks of the graph of `job_id` stored in the `ExecutionGraphChunks`
+    /// keyspace, in order
+    pub(crate) fn chunk_keys(&self, job_id: &str) -> Vec<String> {
+        (1..=self.num_chunks)
+            .map(|index| format!("{job_id}/{:016x}/{index}", self.version))
+            .collect()
+    }
+
+    /// Read the layout from the header of a stored value, `None` if the value has no header
+    pub(crate) fn from_value(value: &[u8]) -> Result<Option<Self>> {
+        if !value.starts_with(HEADER_MAGIC) {
+            return Ok(None);
+        }
+        if value.len() < HEADER_LEN {
+            return Err(BallistaError::Internal(
+                "Truncated header of stored execution graph".to_string(),
+            ));
+        }
+        let flags = value[4];
+        let version = u64::from_be_bytes(value[5..13].try_into().unwrap());
+        let num_chunks = u32::from_be_bytes(value[13..HEADER_LEN].try_into().unwrap());
+        Ok(Some(Self {
+            compressed: flags & FLAG_COMPRESSED != 0,
+            version,
+            num_chunks,
+        }))
+    }
+}
+
+/// Execution graph ready to be stored
+pub(crate) struct StoredGraph {
+    /// Value of the `ExecutionGraph` keyspace
+    pub head: Vec<u8>,
+    /// Keys and values of the `ExecutionGraphChunks` keyspace
+    pub chunks: Vec<(String, Vec<u8>)>,
+    pub layout: GraphLayout,
+    /// Total size of the stored values
+    pub size: usize,
+}
+
+/// Compress the encoded graph of `job_id` if `compression` is set, protect it and split it
+/// in values of at most `chunk_size` bytes, not counting the header
+pub(crate) fn store_graph(
+    job_id: &str,
+    encoded: Vec<u8>,
+    compression: bool,
+    chunk_size: Option<usize>,
+    protection: &PlanProtection,
+) -> Result<StoredGraph> {
+    let data = if compression {
+        zstd::bulk::compress(&encoded, COMPRESSION_LEVEL)?
+    } else {
+        encoded
+    };
+    let data = protection.protect(data)?;
+    let chunk_size = chunk_size.unwrap_or(usize::MAX).max(1);
+    let mut chunks = data.chunks(chunk_size);
+    let first = chunks.next().unwrap_or_default();
+    let rest: Vec<&[u8]> = chunks.collect();
+
+    let layout = GraphLayout {
+        compressed: compression,
+        version: if rest.is_empty() { 0 } else { rand::random() },
+        num_chunks: rest.len() as u32,
+    };
+    let mut head = Vec::with_capacity(HEADER_LEN + first.len());
+    head.extend_from_slice(HEADER_MAGIC);
+    head.push(if compression { FLAG_COMPRESSED } else { 0 });
+    head.extend_from_slice(&layout.version.to_be_bytes());
+    head.extend_from_slice(&layout.num_chunks.to_be_bytes());
+    head.extend_from_slice(first);
+
+    let chunks: Vec<(String, Vec<u8>)> = layout
+        .chunk_keys(job_id)
+        .into_iter()
+        .zip(rest.into_iter().map(|chunk| chunk.to_vec()))
+        .collect();
+    let size = head.len() + chunks.iter().map(|(_, chunk)| chunk.len()).sum::<usize>();
+    Ok(StoredGraph {
+        head,
+        chunks,
+        layout,
+        size,
+    })
+}
+
+/// Reassemble the encoded graph from the stored head value and the values of the chunks
+/// listed by its layout
+pub(crate) fn load_graph(
+    head: Vec<u8>,
+    chunks: Vec<Vec<u8>>,
+    protection: &PlanProtection,
+) -> Result<Vec<u8>> {
+    let Some(layout) = GraphLayout::from_value(&head)? else {
+        if protection.is_enabled() {
+            return Err(BallistaError::Internal(
+                "Stored execution graph is not protected".to_string(),
+            ));
+        }
+        return Ok(head);
+    };
+    if chunks.len() != layout.num_chunks as usize {
+        return Err(BallistaError::Internal(format!(
+            "Expected {} chunks of stored execution graph, got {}",
+            layout.num_chunks,
+            chunks.len()
+        )));
+    }
+    let mut data = head;
+    data.drain(..HEADER_LEN);
+    for chunk in chunks {
+        data.extend_from_slice(&chunk);
+    }
+    let data = protection.unprotect(data)?;
+    if layout.compressed {
+        Ok(zstd::stream::decode_all(data.as_slice())?)
+    } else {
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_graph() -> Result<()> {
+        let none = PlanProtection::default();
+        let encoded: Vec<u8> = (0..10_000u32)
+            .flat_map(|i| (i % 251).to_be_bytes())
+            .collect();
+
+        // a single compressed value
+        let stored = store_graph("job", encoded.clone(), true, None, &none)?;
+        assert!(stored.chunks.is_empty());
+        assert!(stored.size < encoded.len());
+        assert_eq!(load_graph(stored.head, vec![], &none)?, encoded);
+
+        // chunks of at most 1000 bytes
+        let stored = store_graph("job", encoded.clone(), false, Some(1000), &none)?;
+        assert_eq!(stored.chunks.len(), 39);
+        assert_eq!(stored.head.len(), HEADER_LEN + 1000);
+        assert!(stored
+            .chunks
+            .iter()
+            .all(|(key, chunk)| key.starts_with("job/") && chunk.len() <= 1000));
+        assert_eq!(
+            stored.layout.chunk_keys("job"),
+            stored
+                .chunks
+                .iter()
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>()
+        );
+        let chunks = stored.chunks.into_iter().map(|(_, chunk)| chunk).collect();
+        assert_eq!(load_graph(stored.head, chunks, &none)?, encoded);
+
+        // values without header are plain encoded graphs
+        assert_eq!(GraphLayout::from_value(&encoded)?, None);
+        assert_eq!(load_graph(encoded.clone(), vec![], &none)?, encoded);
+
+        // protected graphs
+        let protection = PlanProtection::new(Some("0123456789abcdef"), None)?;
+        let stored = store_graph("job", encoded.clone(), true, None, &protection)?;
+        assert!(load_graph(stored.head.clone(), vec![], &none).is_err());
+        assert_eq!(load_graph(stored.head, vec![], &protection)?, encoded);
+        assert!(load_graph(encoded, vec![], &protection).is_err());
+
+        Ok(())
+    }
+}
@@ -15,17 +15,23 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::cluster::event::ClusterEventSender;
+use crate::cluster::graph_storage::{load_graph, store_graph, GraphLayout};
 use crate::cluster::storage::{KeyValueStore, Keyspace, Lock, Operation, WatchEvent};
 use crate::cluster::{
-    bind_task_bias, bind_task_consistent_hash, bind_task_round_robin, get_scan_files,
-    is_skip_consistent_hash, BoundTask, ClusterState, ExecutorHeartbeatStream,
-    ExecutorSlot, JobState, JobStateEvent, JobStateEventStream, JobStatus,
-    TaskDistributionPolicy, TopologyNode,
+    apply_resized_task_slots, bind_task_bias, bind_task_by_input_locality,
+    bind_task_consistent_hash, bind_task_round_robin, bind_task_to_result_zone,
+    bind_task_weighted, bind_task_with_placement_hints, get_scan_files,
+    is_skip_consistent_hash, queued_job_status, resize_task_slots, return_task_slots,
+    BoundTask, CapableExecutors, ClusterState, ExecutorExpirationStream,
+    ExecutorHeartbeatStream, ExecutorJobLimit, ExecutorSlot, JobState, JobStateEvent,
+    JobStateEventStream, JobStatus, TaskDistributionPolicy, TopologyNode,
 };
-use crate::scheduler_server::{timestamp_secs, SessionBuilder};
+use crate::metrics::{NoopMetricsCollector, SchedulerMetricsCollector};
+use crate::scheduler_server::{timestamp_millis, timestamp_secs, SessionBuilder};
 use crate::state::execution_graph::ExecutionGraph;
 use crate::state::session_manager::create_datafusion_context;
-use crate::state::task_manager::JobInfoCache;
+use crate::state::task_manager::{JobInfoCache, STAGE_MAX_FAILURES, TASK_MAX_FAILURES};
 use crate::state::{decode_into, decode_protobuf};
 use async_trait::async_trait;
 use ballista_core::config::BallistaConfig;
@@ -34,7 +40,7 @@ use ballista_core::error::{BallistaError, Result};
 use ballista_core::serde::protobuf::job_status::Status;
 use ballista_core::serde::protobuf::{
     self, AvailableTaskSlots, ExecutorHeartbeat, ExecutorTaskSlots, FailedJob,
-    KeyValuePair, QueuedJob,
+    JobLogicalPlan, KeyValuePair, SchedulerLease, SlotReservation, TaskStatus,
 };
 use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
 use ballista_core::serde::BallistaCodec;
@@ -46,11 +52,13 @@ use datafusion_proto::physical_plan::AsExecutionPlan;
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
 use futures::StreamExt;
 use itertools::Itertools;
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
+use parking_lot::Mutex;
 use prost::Message;
 use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// State implementation based on underlying `KeyValueStore`
 pub struct KeyValueState<
@@ -64,15 +72,59 @@ pub struct KeyValueState<
     executors: Arc<DashMap<String, ExecutorMetadata>>,
     /// ExecutorHeartbeat cache, executor_id -> ExecutorHeartbeat
     executor_heartbeats: Arc<DashMap<String, ExecutorHeartbeat>>,
+    /// Heartbeats received since the last flush which have not been persisted yet,
+    /// executor_id -> ExecutorHeartbeat
+    pending_heartbeats: Arc<DashMap<String, ExecutorHeartbeat>>,
+    /// Interval for flushing pending heartbeats to the `KeyValueStore` in a single batch.
+    /// If `None`, every heartbeat is written through to the store.
+    heartbeat_flush_interval: Option<Duration>,
+    /// Time-to-live of the lease attached to persisted heartbeats of active and terminating
+    /// executors. If `None`, heartbeats are persisted without a lease.
+    executor_lease: Option<(Duration, Duration)>,
+    /// Sender of IDs of executors whose lease expired
+    executor_expiration_sender: Arc<ClusterEventSender<String>>,
+    /// Time after which the slots held by this scheduler are reclaimed by other schedulers,
+    /// unless the reservation is renewed. If `None`, held slots are not tracked.
+    slot_reservation_timeout: Option<Duration>,
+    /// Slots bound to tasks launched by this scheduler, executor_id -> number of slots
+    reserved_slots: DashMap<String, u32>,
+    /// Time during which the task slots read from the store are served from memory. If
+    /// `None`, the task slots are read from the store on every access.
+    state_cache_ttl: Option<Duration>,
+    /// Task slots last read from or written to the store and when, invalidated by the
+    /// watch stream of the store
+    cached_slots: Arc<Mutex<Option<(ExecutorTaskSlots, Instant)>>>,
     /// Codec used to serialize/deserialize execution plan
     codec: BallistaCodec<T, U>,
     /// Name of current scheduler. Should be `{host}:{port}`
-    #[allow(dead_code)]
     scheduler: String,
-    /// In-memory store of queued jobs. Map from Job ID -> (Job Name, queued_at timestamp)
-    queued_jobs: DashMap<String, (String, u64)>,
+    /// In-memory store of queued jobs. Map from Job ID -> (Job Name, queued_at timestamp,
+    /// planning started_at timestamp)
+    queued_jobs: DashMap<String, (String, u64, Option<u64>)>,
     //// `SessionBuilder` for constructing `SessionContext` from stored `BallistaConfig`
     session_builder: SessionBuilder,
+    /// Compress the stored execution graphs
+    graph_compression: bool,
+    /// The maximum size of the values storing an execution graph, no limit if `None`
+    graph_chunk_size: Option<usize>,
+    /// Records the size of the saved execution graphs
+    metrics_collector: Arc<dyn SchedulerMetricsCollector>,
+    /// The number of task status updates saved as deltas of an execution graph before the
+    /// whole graph is saved again. If `None`, task status updates are not saved.
+    graph_compaction_interval: Option<u64>,
+    /// Number of deltas saved since the last saved execution graph, job_id -> count
+    graph_deltas: DashMap<String, u64>,
+    /// Time-to-live of the status and the execution graph of the finished jobs, after which
+    /// they are deleted by the store. If `None`, they are kept until removed by the scheduler.
+    finished_job_ttl: Option<Duration>,
+    /// Time after which a scheduler which stopped renewing its lease is considered
+    /// failed. If `None`, the jobs of failed schedulers are taken over without checking
+    /// their lease and the jobs are saved without checking their owner.
+    scheduler_lease: Option<Duration>,
+    /// Execution graphs of the running jobs of other schedulers kept decoded in memory,
+    /// e.g. on a standby scheduler, along with the stored value they were decoded from,
+    /// job_id -> (value, graph). The deltas saved since are not applied.
+    warm_graphs: DashMap<String, (Vec<u8>, ExecutionGraph)>,
 }
 
 impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
@@ -88,13 +140,688 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             store,
             executors: Arc::new(DashMap::new()),
             executor_heartbeats: Arc::new(DashMap::new()),
+            pending_heartbeats: Arc::new(DashMap::new()),
+            heartbeat_flush_interval: None,
+            executor_lease: None,
+            executor_expiration_sender: Arc::new(ClusterEventSender::default()),
+            slot_reservation_timeout: None,
+            reserved_slots: DashMap::new(),
+            state_cache_ttl: None,
+            cached_slots: Arc::new(Mutex::new(None)),
             scheduler: scheduler.into(),
             codec,
             queued_jobs: DashMap::new(),
             session_builder,
+            graph_compression: false,
+            graph_chunk_size: None,
+            metrics_collector: Arc::new(NoopMetricsCollector::default()),
+            graph_compaction_interval: None,
+            graph_deltas: DashMap::new(),
+            finished_job_ttl: None,
+            scheduler_lease: None,
+            warm_graphs: DashMap::new(),
         }
     }
 
+    /// Compress the stored execution graphs with zstd, and split the graphs larger than
+    /// `chunk_size` bytes in several values if set
+    pub fn with_graph_storage(
+        mut self,
+        compression: bool,
+        chunk_size: Option<usize>,
+    ) -> Self {
+        self.graph_compression = compression;
+        self.graph_chunk_size = chunk_size;
+        self
+    }
+
+    /// Save the task status updates of running jobs as deltas of their last saved execution
+    /// graph, and save the whole graph instead once `interval` deltas were saved
+    pub fn with_graph_compaction_interval(mut self, interval: u64) -> Self {
+        self.graph_compaction_interval = Some(interval);
+        self
+    }
+
+    /// Write the status and the execution graph of the finished jobs with a time-to-live of
+    /// `ttl`, so that they are deleted by the store instead of by the scheduler
+    pub fn with_finished_job_ttl(mut self, ttl: Duration) -> Self {
+        self.finished_job_ttl = Some(ttl);
+        self
+    }
+
+    /// Renew the lease of this scheduler so that it expires after `timeout` unless
+    /// renewed, take over the jobs of failed schedulers only once their lease expired,
+    /// and save the jobs only while this scheduler owns them
+    pub fn with_scheduler_lease(mut self, timeout: Duration) -> Self {
+        self.scheduler_lease = Some(timeout);
+        self
+    }
+
+    /// The time-to-live of the values of a job with `status`, if it is finished
+    fn job_ttl(&self, status: &JobStatus) -> Option<Duration> {
+        self.finished_job_ttl.filter(|_| {
+            matches!(
+                status.status,
+                Some(Status::Successful(_)) | Some(Status::Failed(_))
+            )
+        })
+    }
+
+    pub fn with_metrics_collector(
+        mut self,
+        metrics_collector: Arc<dyn SchedulerMetricsCollector>,
+    ) -> Self {
+        self.metrics_collector = metrics_collector;
+        self
+    }
+
+    /// Save the status and the execution graph of a job, replacing the `deltas` saved
+    /// since the last saved graph, and return the `released_slots` in the same
+    /// transaction. The chunks of the graph are written beforehand under the keys of
+    /// their version, each on its own so that a large graph does not exceed the size of
+    /// a transaction, and the transaction writing the first value switches the graph to
+    /// that version. The chunks of the `previous` layout of the graph are removed once
+    /// the graph switched.
+    async fn put_execution_graph(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        previous: Option<GraphLayout>,
+        deltas: Vec<String>,
+        released_slots: Vec<ExecutorSlot>,
+    ) -> Result<()> {
+        let status = graph.status();
+        let encoded_graph =
+            ExecutionGraph::encode_execution_graph(graph.clone(), &self.codec)?
+                .encode_to_vec();
+        let encoded_size = encoded_graph.len();
+        let stored = store_graph(
+            job_id,
+            encoded_graph,
+            self.graph_compression,
+            self.graph_chunk_size,
+            self.codec.plan_protection(),
+        )?;
+        debug!(
+            "Saving execution graph of job {job_id}: {encoded_size} bytes encoded, {} bytes stored in {} values",
+            stored.size,
+            stored.chunks.len() + 1
+        );
+        self.metrics_collector
+            .record_execution_graph_size(stored.size as u64);
+
+        let ttl = self.job_ttl(&status);
+        let chunk_keys: Vec<String> =
+            stored.chunks.iter().map(|(key, _)| key.clone()).collect();
+        self.apply_ops_separately(stored.chunks.into_iter().map(|(key, chunk)| {
+            (
+                put_operation(chunk, ttl, job_id),
+                Keyspace::ExecutionGraphChunks,
+                key,
+            )
+        }))
+        .await?;
+
+        let mut ops = vec![(
+            put_operation(status.encode_to_vec(), ttl, job_id),
+            Keyspace::JobStatus,
+            job_id.to_string(),
+        )];
+        ops.push((
+            put_operation(stored.head, ttl, job_id),
+            Keyspace::ExecutionGraph,
+            job_id.to_string(),
+        ));
+        ops.extend(
+            deltas
+                .into_iter()
+                .map(|key| (Operation::Delete, Keyspace::ExecutionGraphDeltas, key)),
+        );
+        if ttl.is_some() {
+            // the plan of the finished job is deleted by the store along with its graph
+            let plan = self.store.get(Keyspace::JobPlans, job_id).await?;
+            if !plan.is_empty() {
+                ops.push((
+                    put_operation(plan, ttl, job_id),
+                    Keyspace::JobPlans,
+                    job_id.to_string(),
+                ));
+            }
+        }
+        if let Err(e) = self.apply_job_ops(ops, released_slots).await {
+            // the graph did not switch to the new chunks
+            let deletions = chunk_keys
+                .into_iter()
+                .map(|key| (Operation::Delete, Keyspace::ExecutionGraphChunks, key));
+            if let Err(e) = self.apply_ops_separately(deletions).await {
+                warn!("Failed to remove the unused graph chunks of job {job_id}: {e:?}");
+            }
+            return Err(e);
+        }
+        if let Some(previous) = previous.filter(|previous| *previous != stored.layout) {
+            let deletions = graph_chunk_deletions(job_id, previous);
+            if let Err(e) = self.apply_ops_separately(deletions).await {
+                warn!(
+                    "Failed to remove the previous graph chunks of job {job_id}: {e:?}"
+                );
+            }
+        }
+        if ttl.is_some() {
+            // the finished job is deleted by the store
+            self.graph_deltas.remove(job_id);
+        } else if self.graph_compaction_interval.is_some() {
+            self.graph_deltas.insert(job_id.to_string(), 0);
+        }
+        Ok(())
+    }
+
+    /// Save the status and the execution graph of a job, returning the `released_slots`
+    /// in the same transaction. With scheduler leases, the job is only saved while this
+    /// scheduler owns it at the epoch of `graph`, so that a scheduler whose jobs were
+    /// taken over while it was unresponsive does not overwrite them.
+    async fn save_job_with(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        released_slots: Vec<ExecutorSlot>,
+    ) -> Result<()> {
+        if self.scheduler_lease.is_none() {
+            return self.put_job(job_id, graph, released_slots).await;
+        }
+        let lock = self.store.lock(Keyspace::JobStatus, job_id).await?;
+
+        with_lock(lock, async {
+            self.check_job_owner(job_id, graph).await?;
+            self.put_job(job_id, graph, released_slots).await
+        })
+        .await
+    }
+
+    /// Save the status and the execution graph of a job, returning the `released_slots`
+    /// in the same transaction, without checking its owner
+    async fn put_job(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        released_slots: Vec<ExecutorSlot>,
+    ) -> Result<()> {
+        let previous = self.stored_graph_layout(job_id).await?;
+        let deltas = self.saved_graph_deltas(job_id).await?;
+        self.put_execution_graph(job_id, graph, previous, deltas, released_slots)
+            .await
+    }
+
+    /// Fail if a running job is owned by another scheduler in the store, or was acquired
+    /// again since `graph` was, to be called under the lock of the status of the job
+    async fn check_job_owner(&self, job_id: &str, graph: &ExecutionGraph) -> Result<()> {
+        let value = self.store.get(Keyspace::JobStatus, job_id).await?;
+        if value.is_empty() {
+            return Ok(());
+        }
+        let stored: JobStatus = decode_protobuf(value.as_slice())?;
+        let Some(Status::Running(stored)) = stored.status else {
+            return Ok(());
+        };
+        let stale = match &graph.status().status {
+            Some(Status::Running(running)) => stored.epoch > running.epoch,
+            _ => false,
+        };
+        if stored.scheduler != self.scheduler || stale {
+            return Err(BallistaError::General(format!(
+                "Job {job_id} was taken over by scheduler {} at epoch {}",
+                stored.scheduler, stored.epoch
+            )));
+        }
+        Ok(())
+    }
+
+    /// Save task status updates as a delta of the execution graph of a job, or save the
+    /// whole graph once the compaction interval is reached, returning the
+    /// `released_slots` in the same transaction
+    async fn save_task_statuses_with(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        executor: &ExecutorMetadata,
+        task_statuses: &[TaskStatus],
+        released_slots: Vec<ExecutorSlot>,
+    ) -> Result<()> {
+        let Some(interval) = self.graph_compaction_interval else {
+            if !released_slots.is_empty() {
+                self.release_task_slots(released_slots, vec![]).await?;
+            }
+            return Ok(());
+        };
+        // the graph is saved whole when it was not saved by this scheduler, as the number of
+        // its deltas is unknown
+        let seq = self.graph_deltas.get(job_id).map(|count| *count);
+        let seq = match seq {
+            Some(seq) if seq < interval => seq,
+            _ => return self.save_job_with(job_id, graph, released_slots).await,
+        };
+        // the deltas are tagged with the ownership epoch of the job, the finished jobs
+        // are saved whole
+        let Some(Status::Running(running)) = &graph.status().status else {
+            return self.save_job_with(job_id, graph, released_slots).await;
+        };
+
+        let delta = protobuf::ExecutionGraphDelta {
+            executor: Some(executor.clone().into()),
+            task_status: task_statuses.to_vec(),
+            epoch: running.epoch,
+            task_id_gen: graph.task_id_gen() as u32,
+        };
+        // the deltas are replayed into the graphs, so they are protected like the graphs
+        let value = self
+            .codec
+            .plan_protection()
+            .protect(delta.encode_to_vec())?;
+        let ops = vec![(
+            Operation::Put(value),
+            Keyspace::ExecutionGraphDeltas,
+            graph_delta_key(job_id, seq),
+        )];
+        self.apply_job_ops(ops, released_slots).await?;
+        self.graph_deltas.insert(job_id.to_string(), seq + 1);
+        Ok(())
+    }
+
+    /// Apply each of the operations in a transaction of its own, concurrently, for the
+    /// operations which may be too many for a single transaction
+    async fn apply_ops_separately(
+        &self,
+        ops: impl IntoIterator<Item = (Operation, Keyspace, String)>,
+    ) -> Result<()> {
+        futures::future::try_join_all(
+            ops.into_iter().map(|op| self.store.apply_txn(vec![op])),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Apply the operations saving a job, returning the `released_slots` to their
+    /// executors in the same transaction
+    async fn apply_job_ops(
+        &self,
+        ops: Vec<(Operation, Keyspace, String)>,
+        released_slots: Vec<ExecutorSlot>,
+    ) -> Result<()> {
+        if released_slots.is_empty() {
+            self.store.apply_txn(ops).await
+        } else {
+            self.release_task_slots(released_slots, ops).await
+        }
+    }
+
+    /// Return the task slots of finished tasks to their executors, applying the `job_ops`
+    /// saving their statuses in the same transaction. The job operations are prepared
+    /// beforehand, so that only the task slots are read and written under the lock of the
+    /// slots.
+    async fn release_task_slots(
+        &self,
+        executor_slots: Vec<ExecutorSlot>,
+        job_ops: Vec<(Operation, Keyspace, String)>,
+    ) -> Result<()> {
+        let mut increments = HashMap::new();
+        for (executor_id, num_slots) in executor_slots {
+            let v = increments.entry(executor_id).or_insert_with(|| 0);
+            *v += num_slots;
+        }
+
+        let lock = self.store.lock(Keyspace::Slots, "all").await?;
+
+        with_lock(lock, async {
+            let mut slots = self.load_task_slots().await?;
+
+            for executor_slots in slots.task_slots.iter_mut() {
+                if let Some(slots) = increments.get(&executor_slots.executor_id) {
+                    return_task_slots(executor_slots, *slots);
+                }
+            }
+
+            let mut ops = vec![(
+                Operation::Put(slots.encode_to_vec()),
+                Keyspace::Slots,
+                "all".to_string(),
+            )];
+            let mut reserved_slots = self.reserved_slots();
+            if self.slot_reservation_timeout.is_some() {
+                for (executor_id, num_slots) in &increments {
+                    if let Some(reserved) = reserved_slots.get_mut(executor_id) {
+                        *reserved = reserved.saturating_sub(*num_slots);
+                    }
+                }
+                reserved_slots.retain(|_, reserved| *reserved > 0);
+                ops.extend(self.slot_reservation_operation(&reserved_slots));
+            }
+
+            ops.extend(job_ops);
+            self.store.apply_txn(ops).await?;
+            self.cache_task_slots(&slots);
+            self.set_reserved_slots(reserved_slots);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Layout of the stored execution graph of a job, `None` if there is no graph or if
+    /// the graph has no header. It is always read from the store, as the graph may have
+    /// been saved by another scheduler since this scheduler last saved it, e.g. when it
+    /// took the job over.
+    async fn stored_graph_layout(&self, job_id: &str) -> Result<Option<GraphLayout>> {
+        let value = self.store.get(Keyspace::ExecutionGraph, job_id).await?;
+        GraphLayout::from_value(&value)
+    }
+
+    /// Keys of the deltas saved since the last saved execution graph of a job, in order
+    async fn saved_graph_deltas(&self, job_id: &str) -> Result<Vec<String>> {
+        if let Some((_, count)) = self.graph_deltas.remove(job_id) {
+            return Ok((0..count).map(|seq| graph_delta_key(job_id, seq)).collect());
+        }
+        if self.graph_compaction_interval.is_none() {
+            return Ok(vec![]);
+        }
+        // the job was not saved by this scheduler
+        let deltas = self
+            .store
+            .get_from_prefix(Keyspace::ExecutionGraphDeltas, &format!("{job_id}/"))
+            .await?;
+        Ok(deltas
+            .into_iter()
+            .filter_map(|(key, _)| {
+                let (_, seq) = key.rsplit_once('/')?;
+                Some(format!("{job_id}/{seq}"))
+            })
+            .sorted()
+            .collect())
+    }
+
+    /// Apply the task status updates saved since the last saved execution graph of a job
+    async fn apply_graph_deltas(&self, graph: &mut ExecutionGraph) -> Result<()> {
+        let deltas = self
+            .store
+            .get_from_prefix(
+                Keyspace::ExecutionGraphDeltas,
+                &format!("{}/", graph.job_id()),
+            )
+            .await?;
+        if deltas.is_empty() {
+            return Ok(());
+        }
+
+        let epoch = match &graph.status().status {
+            Some(Status::Running(running)) => running.epoch,
+            _ => return Ok(()),
+        };
+        // the keys of the deltas are ordered by sequence number, the stages resolved by a
+        // delta are running for the next ones
+        for (_, value) in deltas.into_iter().sorted_by(|(a, _), (b, _)| a.cmp(b)) {
+            let value = self.codec.plan_protection().unprotect(value)?;
+            let delta: protobuf::ExecutionGraphDelta = decode_protobuf(&value)?;
+            if delta.epoch != epoch {
+                // saved by a scheduler which no longer owned the job
+                warn!(
+                    "Skipping a delta of job {} saved at epoch {} rather than {epoch}",
+                    graph.job_id(),
+                    delta.epoch
+                );
+                continue;
+            }
+            // the tasks launched since the snapshot may not have reported their status
+            let task_id_gen = delta
+                .task_status
+                .iter()
+                .map(|status| status.task_id as usize + 1)
+                .max()
+                .unwrap_or_default()
+                .max(delta.task_id_gen as usize);
+            graph.advance_task_id_gen(task_id_gen);
+            graph.revive();
+            let executor: ExecutorMetadata = delta
+                .executor
+                .ok_or_else(|| {
+                    BallistaError::Internal(
+                        "Execution graph delta without executor".to_string(),
+                    )
+                })?
+                .into();
+            graph.update_task_status(
+                &executor,
+                delta.task_status,
+                TASK_MAX_FAILURES,
+                STAGE_MAX_FAILURES,
+            )?;
+        }
+        graph.revive();
+        Ok(())
+    }
+
+    /// Aggregate heartbeats in memory and persist them in batches every `interval`.
+    /// Heartbeats which change the executor status are always written through.
+    pub fn with_heartbeat_flush_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_flush_interval = Some(interval);
+        self
+    }
+
+    /// Persist heartbeats with a lease, so that executors which stop sending heartbeats are
+    /// removed by the `KeyValueStore` once the lease expires. The lease of an active executor
+    /// expires after `ttl`, the one of a terminating executor after `terminating_ttl`.
+    pub fn with_executor_lease(
+        mut self,
+        ttl: Duration,
+        terminating_ttl: Duration,
+    ) -> Self {
+        self.executor_lease = Some((ttl, terminating_ttl));
+        self
+    }
+
+    /// Track the slots bound by this scheduler in the `KeyValueStore`. If the reservation is not
+    /// renewed within `timeout`, e.g. because the scheduler crashed, other schedulers return the
+    /// slots to the available task slots.
+    pub fn with_slot_reservation_timeout(mut self, timeout: Duration) -> Self {
+        self.slot_reservation_timeout = Some(timeout);
+        self
+    }
+
+    /// Serve the task slots read from the store from memory for `ttl`, and keep the cached
+    /// executor metadata up to date with the watch stream of the store
+    pub fn with_state_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.state_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Read the available task slots, from memory if they were cached within the TTL
+    async fn get_task_slots(&self) -> Result<ExecutorTaskSlots> {
+        if let Some(ttl) = self.state_cache_ttl {
+            if let Some((slots, cached_at)) = self.cached_slots.lock().as_ref() {
+                if cached_at.elapsed() < ttl {
+                    return Ok(slots.clone());
+                }
+            }
+        }
+        self.load_task_slots().await
+    }
+
+    /// Read the task slots from the store, bypassing the cache, to update them under the
+    /// slots lock without losing the updates of other schedulers since they were cached
+    async fn load_task_slots(&self) -> Result<ExecutorTaskSlots> {
+        let value = self.store.get(Keyspace::Slots, "all").await?;
+        let slots: ExecutorTaskSlots = decode_protobuf(&value)?;
+        self.cache_task_slots(&slots);
+        Ok(slots)
+    }
+
+    /// The task slots the executor was resized to, if it was resized
+    async fn get_resized_task_slots(&self, executor_id: &str) -> Result<Option<u32>> {
+        let value = self
+            .store
+            .get(Keyspace::ResizedTaskSlots, executor_id)
+            .await?;
+        if value.is_empty() {
+            return Ok(None);
+        }
+        let task_slots = String::from_utf8_lossy(&value).parse().map_err(|e| {
+            BallistaError::Internal(format!(
+                "Unexpected task slots of resized executor {executor_id}: {e}"
+            ))
+        })?;
+        Ok(Some(task_slots))
+    }
+
+    /// Cache the task slots read from or written to the store, if caching is enabled
+    fn cache_task_slots(&self, slots: &ExecutorTaskSlots) {
+        if self.state_cache_ttl.is_some() {
+            *self.cached_slots.lock() = Some((slots.clone(), Instant::now()));
+        }
+    }
+
+    /// Invalidate the cached task slots when they are updated in the store, and update the
+    /// cached executor metadata, including the updates of other schedulers
+    async fn init_state_cache(&self) -> Result<()> {
+        let mut slot_events = self.store.watch(Keyspace::Slots, "all".to_owned()).await?;
+        let mut executor_events = self
+            .store
+            .watch(Keyspace::Executors, String::default())
+            .await?;
+
+        info!("Initializing cluster state cache listener");
+
+        let cached_slots = self.cached_slots.clone();
+        tokio::task::spawn(async move {
+            while slot_events.next().await.is_some() {
+                *cached_slots.lock() = None;
+            }
+        });
+
+        let executors = self.executors.clone();
+        tokio::task::spawn(async move {
+            while let Some(event) = executor_events.next().await {
+                match event {
+                    WatchEvent::Put(_, value) => {
+                        match decode_into::<protobuf::ExecutorMetadata, ExecutorMetadata>(
+                            &value,
+                        ) {
+                            Ok(metadata) => {
+                                executors.insert(metadata.id.clone(), metadata);
+                            }
+                            Err(e) => warn!("Invalid executor metadata in store: {e:?}"),
+                        }
+                    }
+                    WatchEvent::Delete(key) => {
+                        if let Some(executor_id) = key.rsplit('/').next() {
+                            executors.remove(executor_id);
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// The slots currently held by this scheduler, executor_id -> number of slots
+    fn reserved_slots(&self) -> HashMap<String, u32> {
+        self.reserved_slots
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect()
+    }
+
+    /// Replace the slots held by this scheduler, once the new reservation is saved
+    fn set_reserved_slots(&self, reserved_slots: HashMap<String, u32>) {
+        self.reserved_slots
+            .retain(|executor_id, _| reserved_slots.contains_key(executor_id));
+        for (executor_id, slots) in reserved_slots {
+            self.reserved_slots.insert(executor_id, slots);
+        }
+    }
+
+    /// The operation persisting the slots held by this scheduler, if slot reservations
+    /// are enabled
+    fn slot_reservation_operation(
+        &self,
+        reserved_slots: &HashMap<String, u32>,
+    ) -> Option<(Operation, Keyspace, String)> {
+        let timeout = self.slot_reservation_timeout?;
+        let reservation = SlotReservation {
+            expires_at: timestamp_millis() + timeout.as_millis() as u64,
+            task_slots: reserved_slots
+                .iter()
+                .map(|(executor_id, slots)| AvailableTaskSlots {
+                    executor_id: executor_id.clone(),
+                    slots: *slots,
+                    excess_slots: 0,
+                })
+                .collect(),
+        };
+        Some((
+            Operation::Put(reservation.encode_to_vec()),
+            Keyspace::SlotReservations,
+            self.scheduler.clone(),
+        ))
+    }
+
+    /// Return the slots of the reservation held by `scheduler` to the available task slots.
+    /// Unless `force` is set, the reservation is only reclaimed if it expired.
+    async fn reclaim_slot_reservation(
+        &self,
+        scheduler: &str,
+        force: bool,
+    ) -> Result<u32> {
+        let lock = self.store.lock(Keyspace::Slots, "all").await?;
+
+        with_lock(lock, async {
+            let value = self
+                .store
+                .get(Keyspace::SlotReservations, scheduler)
+                .await?;
+            if value.is_empty() {
+                return Ok(0);
+            }
+            let reservation: SlotReservation = decode_protobuf(&value)?;
+            if !force && reservation.expires_at > timestamp_millis() {
+                return Ok(0);
+            }
+
+            let mut slots = self.load_task_slots().await?;
+
+            let mut reclaimed = 0;
+            for executor_slots in slots.task_slots.iter_mut() {
+                if let Some(reserved) = reservation
+                    .task_slots
+                    .iter()
+                    .find(|reserved| reserved.executor_id == executor_slots.executor_id)
+                {
+                    return_task_slots(executor_slots, reserved.slots);
+                    reclaimed += reserved.slots;
+                }
+            }
+
+            self.store
+                .apply_txn(vec![
+                    (
+                        Operation::Put(slots.encode_to_vec()),
+                        Keyspace::Slots,
+                        "all".to_string(),
+                    ),
+                    (
+                        Operation::Delete,
+                        Keyspace::SlotReservations,
+                        scheduler.to_string(),
+                    ),
+                ])
+                .await?;
+            self.cache_task_slots(&slots);
+
+            if reclaimed > 0 {
+                warn!("Reclaimed {reclaimed} slots held by scheduler {scheduler}");
+            }
+
+            Ok(reclaimed)
+        })
+        .await
+    }
+
     /// Initialize the set of active executor heartbeats from storage
     async fn init_active_executor_heartbeats(&self) -> Result<()> {
         let heartbeats = self.store.scan(Keyspace::Heartbeats, None).await?;
@@ -139,6 +866,197 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             .boxed())
     }
 
+    /// Return the stream of IDs of executors whose heartbeat was deleted from the store,
+    /// which happens when the lease of the heartbeat expires
+    async fn executor_lease_expiration_stream(&self) -> Result<ExecutorExpirationStream> {
+        let events = self
+            .store
+            .watch(Keyspace::Heartbeats, String::default())
+            .await?;
+
+        Ok(events
+            .filter_map(|event| {
+                futures::future::ready(match event {
+                    WatchEvent::Delete(key) => key
+                        .rsplit('/')
+                        .next()
+                        .map(|executor_id| executor_id.to_owned()),
+                    WatchEvent::Put(_, _) => None,
+                })
+            })
+            .boxed())
+    }
+
+    /// Persist the heartbeat, with a lease if enabled
+    async fn put_heartbeat(&self, heartbeat: &ExecutorHeartbeat) -> Result<()> {
+        self.store
+            .apply_txn(vec![(
+                heartbeat_operation(heartbeat, self.executor_lease),
+                Keyspace::Heartbeats,
+                heartbeat.executor_id.clone(),
+            )])
+            .await
+    }
+
+    /// Bind the ready to running tasks from [`active_jobs`] with the available task
+    /// slots, loaded under the lock of the slots
+    async fn bind_tasks(
+        &self,
+        slots: &mut ExecutorTaskSlots,
+        distribution: TaskDistributionPolicy,
+        active_jobs: Arc<HashMap<String, JobInfoCache>>,
+        executors: Option<HashSet<String>>,
+        locality_label: Option<&str>,
+    ) -> Result<Vec<BoundTask>> {
+        let mut available_slots: Vec<&mut AvailableTaskSlots> = slots
+            .task_slots
+            .iter_mut()
+            .filter_map(|data| {
+                (data.slots > 0
+                    && executors
+                        .as_ref()
+                        .map(|executors| executors.contains(&data.executor_id))
+                        .unwrap_or(true))
+                .then_some(data)
+            })
+            .collect();
+
+        let capable_executors = CapableExecutors::new(
+            self.executors
+                .iter()
+                .map(|executor| executor.value().clone()),
+        );
+        let executor_zones: HashMap<String, String> = self
+            .executors
+            .iter()
+            .filter(|executor| !executor.zone.is_empty())
+            .map(|executor| (executor.id.clone(), executor.zone.clone()))
+            .collect();
+        let (mut bound_tasks, active_jobs) = bind_task_to_result_zone(
+            &mut available_slots,
+            &executor_zones,
+            &capable_executors,
+            active_jobs,
+        )
+        .await;
+
+        let executor_hosts: HashMap<String, String> = available_slots
+            .iter()
+            .filter_map(|slot| {
+                self.executors
+                    .get(&slot.executor_id)
+                    .map(|executor| (slot.executor_id.clone(), executor.host.clone()))
+            })
+            .collect();
+        bound_tasks.extend(
+            bind_task_with_placement_hints(
+                &mut available_slots,
+                &executor_hosts,
+                &capable_executors,
+                active_jobs.clone(),
+            )
+            .await,
+        );
+
+        if let Some(locality_label) = locality_label {
+            let executor_labels: HashMap<String, String> = available_slots
+                .iter()
+                .filter_map(|slot| {
+                    let executor = self.executors.get(&slot.executor_id)?;
+                    let value = executor.topology_label(locality_label)?;
+                    Some((slot.executor_id.clone(), value.to_string()))
+                })
+                .collect();
+            bound_tasks.extend(
+                bind_task_by_input_locality(
+                    &mut available_slots,
+                    &executor_labels,
+                    locality_label,
+                    &capable_executors,
+                    active_jobs.clone(),
+                )
+                .await,
+            );
+        }
+
+        let policy_bound_tasks = match distribution {
+            TaskDistributionPolicy::Bias => {
+                bind_task_bias(available_slots, &capable_executors, active_jobs, |_| {
+                    false
+                })
+                .await
+            }
+            TaskDistributionPolicy::RoundRobin => {
+                bind_task_round_robin(
+                    available_slots,
+                    &capable_executors,
+                    active_jobs,
+                    |_| false,
+                )
+                .await
+            }
+            TaskDistributionPolicy::Weighted => {
+                bind_task_weighted(available_slots, &capable_executors, active_jobs).await
+            }
+            TaskDistributionPolicy::ConsistentHash {
+                num_replicas,
+                tolerance,
+            } => {
+                let mut bound_tasks = bind_task_round_robin(
+                    available_slots,
+                    &capable_executors,
+                    active_jobs.clone(),
+                    |stage_plan: Arc<dyn ExecutionPlan>| {
+                        if let Ok(scan_files) = get_scan_files(stage_plan) {
+                            // Should be opposite to consistent hash ones.
+                            !is_skip_consistent_hash(&scan_files)
+                        } else {
+                            false
+                        }
+                    },
+                )
+                .await;
+                info!("{} tasks bound by round robin policy", bound_tasks.len());
+                let (bound_tasks_consistent_hash, ch_topology) =
+                    bind_task_consistent_hash(
+                        self.get_topology_nodes(&slots.task_slots, executors),
+                        &capable_executors,
+                        num_replicas,
+                        tolerance,
+                        active_jobs,
+                        |_, plan| get_scan_files(plan),
+                    )
+                    .await?;
+                info!(
+                    "{} tasks bound by consistent hashing policy",
+                    bound_tasks_consistent_hash.len()
+                );
+                if !bound_tasks_consistent_hash.is_empty() {
+                    bound_tasks.extend(bound_tasks_consistent_hash);
+                    // Update the available slots
+                    let mut executor_data: HashMap<String, AvailableTaskSlots> =
+                        std::mem::take(&mut slots.task_slots)
+                            .into_iter()
+                            .map(|slots| (slots.executor_id.clone(), slots))
+                            .collect();
+                    let ch_topology = ch_topology.unwrap();
+                    for node in ch_topology.nodes() {
+                        if let Some(data) = executor_data.get_mut(&node.id) {
+                            data.slots = node.available_slots;
+                        } else {
+                            error!("Fail to find executor data for {}", &node.id);
+                        }
+                    }
+                    slots.task_slots = executor_data.into_values().collect();
+                }
+                bound_tasks
+            }
+        };
+        bound_tasks.extend(policy_bound_tasks);
+
+        Ok(bound_tasks)
+    }
+
     /// Get the topology nodes of the cluster for consistent hashing
     fn get_topology_nodes(
         &self,
@@ -174,6 +1092,101 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         }
         nodes
     }
+
+    /// Acquire a running job released by its scheduler to any scheduler or to this one,
+    /// see `release_job`, or owned by this scheduler or by `failed_scheduler` if given
+    async fn acquire_running_job(
+        &self,
+        job_id: &str,
+        failed_scheduler: Option<&str>,
+    ) -> Result<Option<ExecutionGraph>> {
+        let lock = self.store.lock(Keyspace::JobStatus, job_id).await?;
+
+        with_lock(lock, async {
+            let value = self.store.get(Keyspace::JobStatus, job_id).await?;
+            if value.is_empty() {
+                return Ok(None);
+            }
+            let status: JobStatus = decode_protobuf(value.as_slice())?;
+            let owner = match status.status {
+                Some(Status::Running(running))
+                    if running.scheduler.is_empty()
+                        || running.scheduler == self.scheduler
+                        || Some(running.scheduler.as_str()) == failed_scheduler =>
+                {
+                    running.scheduler
+                }
+                _ => return Ok(None),
+            };
+            if !owner.is_empty()
+                && owner != self.scheduler
+                && !self.is_scheduler_lease_expired(&owner).await?
+            {
+                warn!("Job {job_id} not taken over, scheduler {owner} renewed its lease");
+                return Ok(None);
+            }
+
+            let Some(mut graph) = self.get_execution_graph(job_id).await? else {
+                return Ok(None);
+            };
+            self.warm_graphs.remove(job_id);
+            graph.set_scheduler(&self.scheduler);
+            self.put_job(job_id, &graph, vec![]).await?;
+            Ok(Some(graph))
+        })
+        .await
+    }
+
+    /// Whether `scheduler` stopped renewing its lease, always when leases are not enabled
+    async fn is_scheduler_lease_expired(&self, scheduler: &str) -> Result<bool> {
+        if self.scheduler_lease.is_none() {
+            return Ok(true);
+        }
+        let value = self.store.get(Keyspace::SchedulerLeases, scheduler).await?;
+        if value.is_empty() {
+            return Ok(true);
+        }
+        let lease: SchedulerLease = decode_protobuf(&value)?;
+        Ok(lease.expires_at < timestamp_millis())
+    }
+
+    /// Read and decode the stored execution graph of a job from its `value` in the
+    /// `ExecutionGraph` keyspace, without the deltas saved since. Returns the value the
+    /// graph was decoded from, which is read again if the graph was saved in the
+    /// meantime.
+    async fn load_graph_snapshot(
+        &self,
+        job_id: &str,
+        mut value: Vec<u8>,
+    ) -> Result<(Vec<u8>, ExecutionGraph)> {
+        // the chunks are replaced when the graph is saved again while it is read, in
+        // which case the graph is read again
+        let mut retried = false;
+        let encoded = loop {
+            let mut chunks = vec![];
+            if let Some(layout) = GraphLayout::from_value(&value)? {
+                for key in layout.chunk_keys(job_id) {
+                    chunks.push(
+                        self.store.get(Keyspace::ExecutionGraphChunks, &key).await?,
+                    );
+                }
+            }
+            if retried || chunks.iter().all(|chunk| !chunk.is_empty()) {
+                break load_graph(value.clone(), chunks, self.codec.plan_protection())?;
+            }
+            retried = true;
+            value = self.store.get(Keyspace::ExecutionGraph, job_id).await?;
+        };
+
+        let proto: protobuf::ExecutionGraph = decode_protobuf(encoded.as_slice())?;
+
+        let session = self.get_session(&proto.session_id).await?;
+
+        let graph =
+            ExecutionGraph::decode_execution_graph(proto, &self.codec, session.as_ref())
+                .await?;
+        Ok((value, graph))
+    }
 }
 
 #[async_trait]
@@ -185,6 +1198,15 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     async fn init(&self) -> Result<()> {
         self.init_active_executor_heartbeats().await?;
 
+        if self.state_cache_ttl.is_some() {
+            self.init_state_cache().await?;
+        }
+
+        if self.slot_reservation_timeout.is_some() {
+            // Slots reserved by a previous instance of this scheduler can not be renewed anymore
+            self.reclaim_slot_reservation(&self.scheduler, true).await?;
+        }
+
         let mut heartbeat_stream = self.executor_heartbeat_stream().await?;
 
         info!("Initializing heartbeat listener");
@@ -211,6 +1233,50 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             }
         });
 
+        if self.executor_lease.is_some() {
+            let mut expiration_stream = self.executor_lease_expiration_stream().await?;
+
+            info!("Initializing executor lease expiration listener");
+
+            let heartbeats = self.executor_heartbeats.clone();
+            let executors = self.executors.clone();
+            let pending = self.pending_heartbeats.clone();
+            let sender = self.executor_expiration_sender.clone();
+            tokio::task::spawn(async move {
+                while let Some(executor_id) = expiration_stream.next().await {
+                    // Executors which were removed explicitly are not active anymore
+                    if heartbeats.remove(&executor_id).is_some() {
+                        info!("Lease of executor {executor_id} expired");
+                        executors.remove(&executor_id);
+                        pending.remove(&executor_id);
+                        sender.send(&executor_id);
+                    }
+                }
+            });
+        }
+
+        if let Some(interval) = self.heartbeat_flush_interval {
+            info!(
+                "Initializing heartbeat flusher with interval {:?}",
+                interval
+            );
+
+            let store = self.store.clone();
+            let pending = self.pending_heartbeats.clone();
+            let heartbeats = self.executor_heartbeats.clone();
+            let lease = self.executor_lease;
+            tokio::task::spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    if let Err(e) =
+                        flush_heartbeats(&store, &pending, &heartbeats, lease).await
+                    {
+                        error!("Failed to flush executor heartbeats: {e:?}");
+                    }
+                }
+            });
+        }
+
         Ok(())
     }
 
@@ -219,97 +1285,78 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         distribution: TaskDistributionPolicy,
         active_jobs: Arc<HashMap<String, JobInfoCache>>,
         executors: Option<HashSet<String>>,
+        locality_label: Option<&str>,
+        job_limit: Option<ExecutorJobLimit>,
     ) -> Result<Vec<BoundTask>> {
-        let lock = self.store.lock(Keyspace::Slots, "global").await?;
+        let lock = self.store.lock(Keyspace::Slots, "all").await?;
 
         with_lock(lock, async {
-            let resources = self.store.get(Keyspace::Slots, "all").await?;
-
-            let mut slots =
-                ExecutorTaskSlots::decode(resources.as_slice()).map_err(|err| {
-                    BallistaError::Internal(format!(
-                        "Unexpected value in executor slots state: {err:?}"
-                    ))
-                })?;
-
-            let available_slots: Vec<&mut AvailableTaskSlots> = slots
-                .task_slots
-                .iter_mut()
-                .filter_map(|data| {
-                    (data.slots > 0
-                        && executors
-                            .as_ref()
-                            .map(|executors| executors.contains(&data.executor_id))
-                            .unwrap_or(true))
-                    .then_some(data)
-                })
-                .collect();
-
-            let bound_tasks = match distribution {
-                TaskDistributionPolicy::Bias => {
-                    bind_task_bias(available_slots, active_jobs, |_| false).await
-                }
-                TaskDistributionPolicy::RoundRobin => {
-                    bind_task_round_robin(available_slots, active_jobs, |_| false).await
-                }
-                TaskDistributionPolicy::ConsistentHash {
-                    num_replicas,
-                    tolerance,
-                } => {
-                    let mut bound_tasks = bind_task_round_robin(
-                        available_slots,
-                        active_jobs.clone(),
-                        |stage_plan: Arc<dyn ExecutionPlan>| {
-                            if let Ok(scan_files) = get_scan_files(stage_plan) {
-                                // Should be opposite to consistent hash ones.
-                                !is_skip_consistent_hash(&scan_files)
-                            } else {
-                                false
-                            }
-                        },
+            let mut slots = self.load_task_slots().await?;
+
+            let bound_tasks = match job_limit {
+                None => {
+                    self.bind_tasks(
+                        &mut slots,
+                        distribution,
+                        active_jobs,
+                        executors,
+                        locality_label,
                     )
-                    .await;
-                    info!("{} tasks bound by round robin policy", bound_tasks.len());
-                    let (bound_tasks_consistent_hash, ch_topology) =
-                        bind_task_consistent_hash(
-                            self.get_topology_nodes(&slots.task_slots, executors),
-                            num_replicas,
-                            tolerance,
-                            active_jobs,
-                            |_, plan| get_scan_files(plan),
-                        )
-                        .await?;
-                    info!(
-                        "{} tasks bound by consistent hashing policy",
-                        bound_tasks_consistent_hash.len()
-                    );
-                    if !bound_tasks_consistent_hash.is_empty() {
-                        bound_tasks.extend(bound_tasks_consistent_hash);
-                        // Update the available slots
-                        let mut executor_data: HashMap<String, AvailableTaskSlots> =
+                    .await?
+                }
+                Some(mut job_limit) => {
+                    let mut bound_tasks = vec![];
+                    for job_id in job_limit.jobs() {
+                        let job_executors = job_limit.allowed_executors(
+                            &job_id,
                             slots
                                 .task_slots
-                                .into_iter()
-                                .map(|slots| (slots.executor_id.clone(), slots))
-                                .collect();
-                        let ch_topology = ch_topology.unwrap();
-                        for node in ch_topology.nodes() {
-                            if let Some(data) = executor_data.get_mut(&node.id) {
-                                data.slots = node.available_slots;
-                            } else {
-                                error!("Fail to find executor data for {}", &node.id);
-                            }
+                                .iter()
+                                .filter(|data| data.slots > 0)
+                                .map(|data| &data.executor_id),
+                            executors.as_ref(),
+                        );
+                        let Some(job) = active_jobs.get(&job_id) else {
+                            continue;
+                        };
+                        if job_executors.is_empty() {
+                            continue;
                         }
-                        slots.task_slots = executor_data.into_values().collect();
+                        let job_tasks = self
+                            .bind_tasks(
+                                &mut slots,
+                                job.task_distribution.unwrap_or(distribution),
+                                Arc::new(HashMap::from([(job_id.clone(), job.clone())])),
+                                Some(job_executors),
+                                locality_label,
+                            )
+                            .await?;
+                        for (executor_id, _) in &job_tasks {
+                            job_limit.bind(executor_id, &job_id);
+                        }
+                        bound_tasks.extend(job_tasks);
                     }
                     bound_tasks
                 }
             };
 
             if !bound_tasks.is_empty() {
-                self.store
-                    .put(Keyspace::Slots, "all".to_owned(), slots.encode_to_vec())
-                    .await?
+                let mut ops = vec![(
+                    Operation::Put(slots.encode_to_vec()),
+                    Keyspace::Slots,
+                    "all".to_owned(),
+                )];
+                let mut reserved_slots = self.reserved_slots();
+                if self.slot_reservation_timeout.is_some() {
+                    for (executor_id, _) in &bound_tasks {
+                        *reserved_slots.entry(executor_id.clone()).or_insert(0) += 1;
+                    }
+                    ops.extend(self.slot_reservation_operation(&reserved_slots));
+                }
+                self.store.apply_txn(ops).await?;
+                self.cache_task_slots(&slots);
+                // the reserved slots are only updated once they are saved
+                self.set_reserved_slots(reserved_slots);
             }
 
             Ok(bound_tasks)
@@ -318,43 +1365,52 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     }
 
     async fn unbind_tasks(&self, executor_slots: Vec<ExecutorSlot>) -> Result<()> {
-        let mut increments = HashMap::new();
-        for (executor_id, num_slots) in executor_slots {
-            let v = increments.entry(executor_id).or_insert_with(|| 0);
-            *v += num_slots;
-        }
-
-        let lock = self.store.lock(Keyspace::Slots, "all").await?;
+        self.release_task_slots(executor_slots, vec![]).await
+    }
 
-        with_lock(lock, async {
-            let resources = self.store.get(Keyspace::Slots, "all").await?;
+    async fn renew_slot_reservation(&self) -> Result<()> {
+        if let Some(op) = self.slot_reservation_operation(&self.reserved_slots()) {
+            self.store.apply_txn(vec![op]).await?;
+        }
+        Ok(())
+    }
 
-            let mut slots =
-                ExecutorTaskSlots::decode(resources.as_slice()).map_err(|err| {
-                    BallistaError::Internal(format!(
-                        "Unexpected value in executor slots state: {err:?}"
-                    ))
-                })?;
+    async fn reclaim_expired_slot_reservations(&self) -> Result<u32> {
+        if self.slot_reservation_timeout.is_none() {
+            return Ok(0);
+        }
 
-            for executor_slots in slots.task_slots.iter_mut() {
-                if let Some(slots) = increments.get(&executor_slots.executor_id) {
-                    executor_slots.slots += *slots;
-                }
+        let mut reclaimed = 0;
+        for scheduler in self.store.scan_keys(Keyspace::SlotReservations).await? {
+            if scheduler != self.scheduler {
+                reclaimed += self.reclaim_slot_reservation(&scheduler, false).await?;
             }
+        }
+        Ok(reclaimed)
+    }
 
-            self.store
-                .put(Keyspace::Slots, "all".to_string(), slots.encode_to_vec())
-                .await
-        })
-        .await
+    async fn shutdown(&self) -> Result<()> {
+        flush_heartbeats(
+            &self.store,
+            &self.pending_heartbeats,
+            &self.executor_heartbeats,
+            self.executor_lease,
+        )
+        .await?;
+        // the reservation is renewed rather than released, as the tasks launched by this
+        // scheduler still hold their slots until they complete, it is reclaimed once expired
+        self.renew_slot_reservation().await
     }
 
     async fn register_executor(
         &self,
-        metadata: ExecutorMetadata,
-        spec: ExecutorData,
+        mut metadata: ExecutorMetadata,
+        mut spec: ExecutorData,
     ) -> Result<()> {
         let executor_id = metadata.id.clone();
+        if let Some(task_slots) = self.get_resized_task_slots(&executor_id).await? {
+            apply_resized_task_slots(&mut metadata, &mut spec, task_slots);
+        }
 
         //TODO this should be in a transaction
         // Now that we know we can connect, save the metadata and slots
@@ -368,21 +1424,20 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     protobuf::executor_status::Status::Active(String::default()),
                 ),
             }),
+            running_jobs: vec![],
         })
         .await?;
 
         let available_slots = AvailableTaskSlots {
             executor_id,
             slots: spec.available_task_slots,
+            excess_slots: 0,
         };
 
         let lock = self.store.lock(Keyspace::Slots, "all").await?;
 
         with_lock(lock, async {
-            let current_slots = self.store.get(Keyspace::Slots, "all").await?;
-
-            let mut current_slots: ExecutorTaskSlots =
-                decode_protobuf(current_slots.as_slice())?;
+            let mut current_slots = self.load_task_slots().await?;
 
             if let Some((idx, _)) = current_slots
                 .task_slots
@@ -400,11 +1455,85 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     "all".to_string(),
                     current_slots.encode_to_vec(),
                 )
-                .await
+                .await?;
+            self.cache_task_slots(&current_slots);
+            Ok(())
         })
-        .await?;
+        .await
+    }
 
-        Ok(())
+    async fn resize_executor_task_slots(
+        &self,
+        executor_id: &str,
+        task_slots: u32,
+    ) -> Result<u32> {
+        let lock = self.store.lock(Keyspace::Slots, "all").await?;
+
+        with_lock(lock, async {
+            let mut metadata = self.get_executor_metadata(executor_id).await?;
+            let previous_task_slots = metadata.specification.task_slots;
+            metadata.specification.task_slots = task_slots;
+
+            let mut slots = self.load_task_slots().await?;
+            if let Some(executor_slots) = slots
+                .task_slots
+                .iter_mut()
+                .find(|slots| slots.executor_id == executor_id)
+            {
+                resize_task_slots(executor_slots, previous_task_slots, task_slots);
+            }
+
+            let proto: protobuf::ExecutorMetadata = metadata.clone().into();
+            self.store
+                .apply_txn(vec![
+                    (
+                        Operation::Put(slots.encode_to_vec()),
+                        Keyspace::Slots,
+                        "all".to_string(),
+                    ),
+                    (
+                        Operation::Put(proto.encode_to_vec()),
+                        Keyspace::Executors,
+                        executor_id.to_string(),
+                    ),
+                    (
+                        Operation::Put(task_slots.to_string().into_bytes()),
+                        Keyspace::ResizedTaskSlots,
+                        executor_id.to_string(),
+                    ),
+                ])
+                .await?;
+            self.cache_task_slots(&slots);
+            self.executors.insert(executor_id.to_string(), metadata);
+            Ok(previous_task_slots)
+        })
+        .await
+    }
+
+    async fn get_available_task_slots(&self, executor_id: &str) -> Result<Option<u32>> {
+        Ok(self
+            .get_task_slots()
+            .await?
+            .task_slots
+            .iter()
+            .find(|slots| slots.executor_id == executor_id)
+            .map(|slots| slots.slots))
+    }
+
+    /// The available task slots of the executor in the cached task slots, if they are
+    /// cached and not stale
+    async fn get_cached_available_task_slots(&self, executor_id: &str) -> Option<u32> {
+        let ttl = self.state_cache_ttl?;
+        let cached_slots = self.cached_slots.lock();
+        let (slots, cached_at) = cached_slots.as_ref()?;
+        if cached_at.elapsed() >= ttl {
+            return None;
+        }
+        slots
+            .task_slots
+            .iter()
+            .find(|slots| slots.executor_id == executor_id)
+            .map(|slots| slots.slots)
     }
 
     async fn save_executor_metadata(&self, metadata: ExecutorMetadata) -> Result<()> {
@@ -448,31 +1577,34 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
 
     async fn save_executor_heartbeat(&self, heartbeat: ExecutorHeartbeat) -> Result<()> {
         let executor_id = heartbeat.executor_id.clone();
-        self.store
-            .put(
-                Keyspace::Heartbeats,
-                executor_id.clone(),
-                heartbeat.clone().encode_to_vec(),
-            )
-            .await?;
+        let transition = is_status_transition(
+            self.executor_heartbeats.get(&executor_id).as_deref(),
+            &heartbeat,
+        );
+        if self.heartbeat_flush_interval.is_some() && !transition {
+            self.pending_heartbeats
+                .insert(executor_id.clone(), heartbeat.clone());
+        } else {
+            self.pending_heartbeats.remove(&executor_id);
+            self.put_heartbeat(&heartbeat).await?;
+        }
         self.executor_heartbeats.insert(executor_id, heartbeat);
         Ok(())
     }
 
     async fn remove_executor(&self, executor_id: &str) -> Result<()> {
-        let value = ExecutorHeartbeat {
+        let heartbeat = ExecutorHeartbeat {
             executor_id: executor_id.to_owned(),
             timestamp: timestamp_secs(),
             metrics: vec![],
             status: Some(protobuf::ExecutorStatus {
                 status: Some(protobuf::executor_status::Status::Dead("".to_string())),
             }),
-        }
-        .encode_to_vec();
+            running_jobs: vec![],
+        };
 
-        self.store
-            .put(Keyspace::Heartbeats, executor_id.to_owned(), value)
-            .await?;
+        self.pending_heartbeats.remove(executor_id);
+        self.put_heartbeat(&heartbeat).await?;
         self.executor_heartbeats.remove(executor_id);
 
         // TODO Check the Executor reservation logic for push-based scheduling
@@ -492,42 +1624,83 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             .get(executor_id)
             .map(|r| r.value().clone())
     }
+
+    async fn executor_expirations(&self) -> Result<Option<ExecutorExpirationStream>> {
+        Ok(self
+            .executor_lease
+            .map(|_| self.executor_expiration_sender.subscribe().boxed()))
+    }
 }
 
 #[async_trait]
 impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> JobState
     for KeyValueState<S, T, U>
 {
+    async fn get_idempotency_key(&self, key: &str) -> Result<Option<String>> {
+        // the store deletes the key once its time-to-live has elapsed
+        let value = self.store.get(Keyspace::IdempotencyKeys, key).await?;
+        if value.is_empty() {
+            return Ok(None);
+        }
+        let job_id = String::from_utf8(value).map_err(|e| {
+            BallistaError::Internal(format!(
+                "Unexpected value of idempotency key {key}: {e}"
+            ))
+        })?;
+        Ok(Some(job_id))
+    }
+
+    async fn register_idempotency_key(
+        &self,
+        key: &str,
+        job_id: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>> {
+        let lock = self.store.lock(Keyspace::IdempotencyKeys, key).await?;
+
+        with_lock(lock, async {
+            if let Some(job_id) = self.get_idempotency_key(key).await? {
+                return Ok(Some(job_id));
+            }
+            self.store
+                .put_with_ttl(
+                    Keyspace::IdempotencyKeys,
+                    key.to_string(),
+                    job_id.as_bytes().to_vec(),
+                    ttl,
+                )
+                .await?;
+            Ok(None)
+        })
+        .await
+    }
+
     fn accept_job(&self, job_id: &str, job_name: &str, queued_at: u64) -> Result<()> {
         self.queued_jobs
-            .insert(job_id.to_string(), (job_name.to_string(), queued_at));
+            .insert(job_id.to_string(), (job_name.to_string(), queued_at, None));
 
         Ok(())
     }
 
+    fn start_planning(&self, job_id: &str, started_at: u64) -> Result<()> {
+        if let Some(mut queued) = self.queued_jobs.get_mut(job_id) {
+            queued.2 = Some(started_at);
+
+            Ok(())
+        } else {
+            Err(BallistaError::Internal(format!(
+                "Could not start planning job {job_id}, not found in queued jobs"
+            )))
+        }
+    }
+
     fn pending_job_number(&self) -> usize {
         self.queued_jobs.len()
     }
 
     async fn submit_job(&self, job_id: String, graph: &ExecutionGraph) -> Result<()> {
         if self.queued_jobs.get(&job_id).is_some() {
-            let status = graph.status();
-            let encoded_graph =
-                ExecutionGraph::encode_execution_graph(graph.clone(), &self.codec)?;
-
-            self.store
-                .apply_txn(vec![
-                    (
-                        Operation::Put(status.encode_to_vec()),
-                        Keyspace::JobStatus,
-                        job_id.clone(),
-                    ),
-                    (
-                        Operation::Put(encoded_graph.encode_to_vec()),
-                        Keyspace::ExecutionGraph,
-                        job_id.clone(),
-                    ),
-                ])
+            self.put_execution_graph(&job_id, graph, None, vec![], vec![])
                 .await?;
 
             self.queued_jobs.remove(&job_id);
@@ -540,19 +1713,34 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         }
     }
 
+    async fn save_job_plan(&self, job_id: &str, plan: &JobLogicalPlan) -> Result<()> {
+        self.store
+            .put(Keyspace::JobPlans, job_id.to_string(), plan.encode_to_vec())
+            .await
+    }
+
+    async fn get_job_plan(&self, job_id: &str) -> Result<Option<JobLogicalPlan>> {
+        let value = self.store.get(Keyspace::JobPlans, job_id).await?;
+        if value.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(decode_protobuf(&value)?))
+    }
+
     async fn get_jobs(&self) -> Result<HashSet<String>> {
         self.store.scan_keys(Keyspace::JobStatus).await
     }
 
     async fn get_job_status(&self, job_id: &str) -> Result<Option<JobStatus>> {
-        if let Some((job_name, queued_at)) = self.queued_jobs.get(job_id).as_deref() {
-            Ok(Some(JobStatus {
-                job_id: job_id.to_string(),
-                job_name: job_name.clone(),
-                status: Some(Status::Queued(QueuedJob {
-                    queued_at: *queued_at,
-                })),
-            }))
+        if let Some((job_name, queued_at, planning_started_at)) =
+            self.queued_jobs.get(job_id).as_deref()
+        {
+            Ok(Some(queued_job_status(
+                job_id,
+                job_name,
+                *queued_at,
+                *planning_started_at,
+            )))
         } else {
             let value = self.store.get(Keyspace::JobStatus, job_id).await?;
 
@@ -569,52 +1757,120 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             return Ok(None);
         }
 
-        let proto: protobuf::ExecutionGraph = decode_protobuf(value.as_slice())?;
+        let warm = self
+            .warm_graphs
+            .get(job_id)
+            .filter(|warm| warm.0 == value)
+            .map(|warm| warm.1.clone());
+        let mut graph = match warm {
+            Some(graph) => graph,
+            None => self.load_graph_snapshot(job_id, value).await?.1,
+        };
+        self.apply_graph_deltas(&mut graph).await?;
+        Ok(Some(graph))
+    }
 
-        let session = self.get_session(&proto.session_id).await?;
+    async fn warm_execution_graph(&self, job_id: &str) -> Result<()> {
+        let value = self.store.get(Keyspace::ExecutionGraph, job_id).await?;
+        if value.is_empty() {
+            self.warm_graphs.remove(job_id);
+            return Ok(());
+        }
+        if self
+            .warm_graphs
+            .get(job_id)
+            .is_some_and(|warm| warm.0 == value)
+        {
+            return Ok(());
+        }
 
-        Ok(Some(
-            ExecutionGraph::decode_execution_graph(proto, &self.codec, session.as_ref())
-                .await?,
-        ))
+        let (value, graph) = self.load_graph_snapshot(job_id, value).await?;
+        if matches!(graph.status().status, Some(Status::Running(_))) {
+            self.warm_graphs.insert(job_id.to_string(), (value, graph));
+        } else {
+            self.warm_graphs.remove(job_id);
+        }
+        Ok(())
     }
 
-    async fn save_job(&self, job_id: &str, graph: &ExecutionGraph) -> Result<()> {
-        let status = graph.status();
-        let encoded_graph =
-            ExecutionGraph::encode_execution_graph(graph.clone(), &self.codec)?;
+    fn forget_warm_execution_graphs(&self) {
+        self.warm_graphs.clear();
+    }
 
+    async fn renew_scheduler_lease(&self) -> Result<()> {
+        let Some(timeout) = self.scheduler_lease else {
+            return Ok(());
+        };
+        let lease = SchedulerLease {
+            expires_at: timestamp_millis() + timeout.as_millis() as u64,
+        };
         self.store
-            .apply_txn(vec![
-                (
-                    Operation::Put(status.encode_to_vec()),
-                    Keyspace::JobStatus,
-                    job_id.to_string(),
-                ),
-                (
-                    Operation::Put(encoded_graph.encode_to_vec()),
-                    Keyspace::ExecutionGraph,
-                    job_id.to_string(),
-                ),
-            ])
+            .apply_txn(vec![(
+                Operation::PutWithTtl(lease.encode_to_vec(), timeout),
+                Keyspace::SchedulerLeases,
+                self.scheduler.clone(),
+            )])
+            .await
+    }
+
+    async fn save_job(&self, job_id: &str, graph: &ExecutionGraph) -> Result<()> {
+        self.save_job_with(job_id, graph, vec![]).await
+    }
+
+    async fn save_task_statuses(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        executor: &ExecutorMetadata,
+        task_statuses: &[TaskStatus],
+    ) -> Result<()> {
+        self.save_task_statuses_with(job_id, graph, executor, task_statuses, vec![])
             .await
     }
 
+    async fn save_task_statuses_and_release_slots(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        executor: &ExecutorMetadata,
+        task_statuses: &[TaskStatus],
+        released_slots: u32,
+    ) -> Result<bool> {
+        self.save_task_statuses_with(
+            job_id,
+            graph,
+            executor,
+            task_statuses,
+            vec![(executor.id.clone(), released_slots)],
+        )
+        .await?;
+        Ok(true)
+    }
+
     async fn fail_unscheduled_job(&self, job_id: &str, reason: String) -> Result<()> {
-        if let Some((job_id, (job_name, queued_at))) = self.queued_jobs.remove(job_id) {
+        if let Some((job_id, (job_name, queued_at, planning_started_at))) =
+            self.queued_jobs.remove(job_id)
+        {
             let status = JobStatus {
                 job_id: job_id.clone(),
                 job_name,
                 status: Some(Status::Failed(FailedJob {
                     error: reason,
                     queued_at,
-                    started_at: 0,
-                    ended_at: 0,
+                    started_at: planning_started_at.unwrap_or_default(),
+                    ended_at: timestamp_millis(),
+                    failed_task: None,
+                    error_chain: vec![],
                 })),
             };
 
+            let ttl = self.job_ttl(&status);
             self.store
-                .put(Keyspace::JobStatus, job_id, status.encode_to_vec())
+                .apply_txn(vec![(
+                    put_operation(status.encode_to_vec(), ttl, &job_id),
+                    Keyspace::JobStatus,
+                    job_id,
+                )])
                 .await
         } else {
             Err(BallistaError::Internal(format!(
@@ -623,27 +1879,118 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         }
     }
 
+    fn expires_finished_jobs(&self) -> bool {
+        self.finished_job_ttl.is_some()
+    }
+
     async fn remove_job(&self, job_id: &str) -> Result<()> {
         if self.queued_jobs.remove(job_id).is_none() {
-            self.store
-                .apply_txn(vec![
-                    (Operation::Delete, Keyspace::JobStatus, job_id.to_string()),
-                    (
-                        Operation::Delete,
-                        Keyspace::ExecutionGraph,
-                        job_id.to_string(),
-                    ),
-                ])
-                .await
+            let layout = self.stored_graph_layout(job_id).await?;
+            let deltas = self.saved_graph_deltas(job_id).await?;
+            let mut ops = vec![
+                (Operation::Delete, Keyspace::JobStatus, job_id.to_string()),
+                (
+                    Operation::Delete,
+                    Keyspace::ExecutionGraph,
+                    job_id.to_string(),
+                ),
+                (Operation::Delete, Keyspace::JobPlans, job_id.to_string()),
+            ];
+            ops.extend(
+                deltas
+                    .into_iter()
+                    .map(|key| (Operation::Delete, Keyspace::ExecutionGraphDeltas, key)),
+            );
+            self.store.apply_txn(ops).await?;
+            match layout {
+                Some(layout) => {
+                    self.apply_ops_separately(graph_chunk_deletions(job_id, layout))
+                        .await
+                }
+                None => Ok(()),
+            }
         } else {
             Ok(())
         }
     }
 
-    async fn try_acquire_job(&self, _job_id: &str) -> Result<Option<ExecutionGraph>> {
-        Err(BallistaError::NotImplemented(
-            "Work stealing is not currently implemented".to_string(),
-        ))
+    async fn try_acquire_job(&self, job_id: &str) -> Result<Option<ExecutionGraph>> {
+        self.acquire_running_job(job_id, None).await
+    }
+
+    async fn try_take_over_job(
+        &self,
+        job_id: &str,
+        failed_scheduler: &str,
+    ) -> Result<Option<ExecutionGraph>> {
+        self.acquire_running_job(job_id, Some(failed_scheduler))
+            .await
+    }
+
+    async fn release_job(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        target: Option<&str>,
+    ) -> Result<()> {
+        let lock = self.store.lock(Keyspace::JobStatus, job_id).await?;
+
+        with_lock(lock, async {
+            // the job may have been taken over since this scheduler last saved it
+            let value = self.store.get(Keyspace::JobStatus, job_id).await?;
+            if !value.is_empty() {
+                let stored: JobStatus = decode_protobuf(value.as_slice())?;
+                if let Some(Status::Running(running)) = stored.status {
+                    if running.scheduler != self.scheduler {
+                        return Err(BallistaError::General(format!(
+                            "Job {job_id} is owned by scheduler {}, not {}",
+                            running.scheduler, self.scheduler
+                        )));
+                    }
+                }
+            }
+
+            self.put_job(job_id, graph, vec![]).await?;
+            let mut status = graph.status().clone();
+            if let Some(Status::Running(running)) = status.status.as_mut() {
+                running.scheduler = target.unwrap_or_default().to_string();
+                self.store
+                    .put(
+                        Keyspace::JobStatus,
+                        job_id.to_string(),
+                        status.encode_to_vec(),
+                    )
+                    .await?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    async fn reclaim_job(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        target: &str,
+        epoch: u64,
+    ) -> Result<bool> {
+        let lock = self.store.lock(Keyspace::JobStatus, job_id).await?;
+
+        with_lock(lock, async {
+            let value = self.store.get(Keyspace::JobStatus, job_id).await?;
+            if value.is_empty() {
+                return Ok(false);
+            }
+            let status: JobStatus = decode_protobuf(value.as_slice())?;
+            match status.status {
+                Some(Status::Running(running))
+                    if running.scheduler == target && running.epoch == epoch => {}
+                _ => return Ok(false),
+            }
+            self.put_job(job_id, graph, vec![]).await?;
+            Ok(true)
+        })
+        .await
     }
 
     async fn job_state_events(&self) -> Result<JobStateEventStream> {
@@ -761,24 +2108,136 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     }
 }
 
+/// Key of a delta of the execution graph of a job, ordered by sequence number
+fn graph_delta_key(job_id: &str, seq: u64) -> String {
+    format!("{job_id}/{seq:020}")
+}
+
 async fn with_lock<Out, F: Future<Output = Out>>(mut lock: Box<dyn Lock>, op: F) -> Out {
     let result = op.await;
     lock.unlock().await;
     result
 }
 
+/// Whether `heartbeat` changes the executor status compared to the last seen heartbeat
+fn is_status_transition(
+    last: Option<&ExecutorHeartbeat>,
+    heartbeat: &ExecutorHeartbeat,
+) -> bool {
+    let status = |heartbeat: &ExecutorHeartbeat| {
+        heartbeat
+            .status
+            .as_ref()
+            .and_then(|status| status.status.as_ref())
+            .map(std::mem::discriminant)
+    };
+    last.map(|last| status(last) != status(heartbeat))
+        .unwrap_or(true)
+}
+
+/// The operation writing `value` of `job_id`, with a time-to-live shared by the values of
+/// the job if `ttl` is set
+fn put_operation(value: Vec<u8>, ttl: Option<Duration>, job_id: &str) -> Operation {
+    match ttl {
+        Some(ttl) => Operation::PutWithSharedTtl(value, ttl, job_id.to_owned()),
+        None => Operation::Put(value),
+    }
+}
+
+/// The operations removing the chunks of the stored execution graph of `job_id`
+fn graph_chunk_deletions(
+    job_id: &str,
+    layout: GraphLayout,
+) -> impl Iterator<Item = (Operation, Keyspace, String)> {
+    layout
+        .chunk_keys(job_id)
+        .into_iter()
+        .map(|key| (Operation::Delete, Keyspace::ExecutionGraphChunks, key))
+}
+
+/// The operation persisting `heartbeat`. If `lease` is provided, the heartbeat is written with
+/// the time-to-live matching the executor status, so that it expires unless renewed.
+fn heartbeat_operation(
+    heartbeat: &ExecutorHeartbeat,
+    lease: Option<(Duration, Duration)>,
+) -> Operation {
+    let value = heartbeat.encode_to_vec();
+    match lease {
+        Some((ttl, terminating_ttl)) => {
+            let terminating = matches!(
+                heartbeat
+                    .status
+                    .as_ref()
+                    .and_then(|status| status.status.as_ref()),
+                Some(protobuf::executor_status::Status::Terminating(_))
+            );
+            Operation::PutWithTtl(value, if terminating { terminating_ttl } else { ttl })
+        }
+        None => Operation::Put(value),
+    }
+}
+
+/// Persist all pending heartbeats in a single transaction. Heartbeats of executors which
+/// have been removed since they were received are dropped.
+async fn flush_heartbeats<S: KeyValueStore>(
+    store: &S,
+    pending: &DashMap<String, ExecutorHeartbeat>,
+    heartbeats: &DashMap<String, ExecutorHeartbeat>,
+    lease: Option<(Duration, Duration)>,
+) -> Result<()> {
+    let executor_ids: Vec<String> =
+        pending.iter().map(|entry| entry.key().clone()).collect();
+
+    let ops: Vec<(Operation, Keyspace, String)> = executor_ids
+        .into_iter()
+        .filter_map(|executor_id| pending.remove(&executor_id))
+        .filter(|(executor_id, _)| heartbeats.contains_key(executor_id))
+        .map(|(executor_id, heartbeat)| {
+            (
+                heartbeat_operation(&heartbeat, lease),
+                Keyspace::Heartbeats,
+                executor_id,
+            )
+        })
+        .collect();
+
+    if !ops.is_empty() {
+        debug!("Flushing {} executor heartbeats", ops.len());
+        store.apply_txn(ops).await?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
 
-    use crate::cluster::kv::KeyValueState;
+    use crate::cluster::kv::{flush_heartbeats, graph_delta_key, KeyValueState};
     use crate::cluster::storage::sled::SledClient;
+    use crate::cluster::storage::{KeyValueStore, Keyspace};
     use crate::cluster::test_util::{test_job_lifecycle, test_job_planning_failure};
+    use crate::cluster::{ClusterState, JobState};
+    use crate::scheduler_server::timestamp_millis;
+    use crate::state::decode_protobuf;
+    use crate::state::execution_graph::ExecutionGraph;
     use crate::test_utils::{
-        test_aggregation_plan, test_join_plan, test_two_aggregations_plan,
+        await_condition, mock_completed_task, mock_executor, test_aggregation_plan,
+        test_join_plan, test_two_aggregations_plan,
     };
     use ballista_core::error::Result;
+    use ballista_core::plan_protection::PlanProtection;
+    use ballista_core::serde::protobuf::{
+        self, AvailableTaskSlots, ExecutorHeartbeat, ExecutorTaskSlots, KeyValuePair,
+        SlotReservation,
+    };
+    use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
     use ballista_core::serde::BallistaCodec;
     use ballista_core::utils::default_session_builder;
+    use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+    use futures::StreamExt;
+    use prost::Message;
+    use std::collections::HashSet;
+    use std::time::Duration;
 
     #[cfg(feature = "sled")]
     #[tokio::test]
@@ -805,6 +2264,573 @@ mod test {
         Ok(())
     }
 
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_chunked_execution_graph() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_graph_storage(true, Some(64));
+        let mut graph = test_aggregation_plan(4).await;
+        graph.set_session_props(vec![KeyValuePair {
+            key: "datafusion.execution.time_zone".to_string(),
+            value: "+08:00".to_string(),
+        }]);
+        let job_id = graph.job_id().to_string();
+
+        state.accept_job(&job_id, "", timestamp_millis())?;
+        state.submit_job(job_id.clone(), &graph).await?;
+        let chunks = store.scan_keys(Keyspace::ExecutionGraphChunks).await?;
+        assert!(!chunks.is_empty());
+
+        let stored_graph = state
+            .get_execution_graph(&job_id)
+            .await?
+            .expect("execution graph not found");
+        assert_eq!(stored_graph.job_id(), job_id);
+        assert_eq!(stored_graph.stage_count(), graph.stage_count());
+        // the options of the job session are kept for the tasks of a job taken over
+        assert_eq!(stored_graph.session_props(), graph.session_props());
+
+        // saving the graph again replaces its chunks
+        state.save_job(&job_id, &graph).await?;
+        let new_chunks = store.scan_keys(Keyspace::ExecutionGraphChunks).await?;
+        assert_eq!(new_chunks.len(), chunks.len());
+        assert!(new_chunks.is_disjoint(&chunks));
+        assert!(state.get_execution_graph(&job_id).await?.is_some());
+
+        // the chunks saved by another scheduler are replaced and removed as well
+        let other_state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_graph_storage(true, Some(64));
+        other_state.save_job(&job_id, &graph).await?;
+        let other_chunks = store.scan_keys(Keyspace::ExecutionGraphChunks).await?;
+        assert_eq!(other_chunks.len(), chunks.len());
+        assert!(other_chunks.is_disjoint(&new_chunks));
+
+        state.remove_job(&job_id).await?;
+        assert!(store
+            .scan_keys(Keyspace::ExecutionGraphChunks)
+            .await?
+            .is_empty());
+        assert!(state.get_execution_graph(&job_id).await?.is_none());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_execution_graph_deltas() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_graph_compaction_interval(2);
+        let mut graph = test_aggregation_plan(4).await;
+        let job_id = graph.job_id().to_string();
+        let executor = mock_executor("executor-1".to_string());
+
+        state.accept_job(&job_id, "", timestamp_millis())?;
+        state.submit_job(job_id.clone(), &graph).await?;
+        graph.revive();
+
+        // the status updates are saved as deltas and replayed on the stored graph
+        complete_next_task(&state, &mut graph, &executor).await?;
+        complete_next_task(&state, &mut graph, &executor).await?;
+        let deltas = store.scan_keys(Keyspace::ExecutionGraphDeltas).await?;
+        assert_eq!(deltas.len(), 2);
+        let stored_graph = state
+            .get_execution_graph(&job_id)
+            .await?
+            .expect("execution graph not found");
+        assert_eq!(stored_graph.available_tasks(), graph.available_tasks());
+        // the ids of the tasks launched since the snapshot are not reused
+        assert_eq!(stored_graph.task_id_gen(), graph.task_id_gen());
+
+        // the graph is saved whole once the compaction interval is reached
+        complete_next_task(&state, &mut graph, &executor).await?;
+        assert!(store
+            .scan_keys(Keyspace::ExecutionGraphDeltas)
+            .await?
+            .is_empty());
+        let stored_graph = state
+            .get_execution_graph(&job_id)
+            .await?
+            .expect("execution graph not found");
+        assert_eq!(stored_graph.available_tasks(), graph.available_tasks());
+
+        complete_next_task(&state, &mut graph, &executor).await?;
+        state.remove_job(&job_id).await?;
+        assert!(store
+            .scan_keys(Keyspace::ExecutionGraphDeltas)
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_signed_execution_graph_deltas() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let protection = PlanProtection::new(Some(&"01".repeat(32)), None)?;
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "",
+            store.clone(),
+            BallistaCodec::default().with_plan_protection(protection),
+            default_session_builder,
+        )
+        .with_graph_compaction_interval(2);
+        let mut graph = test_aggregation_plan(4).await;
+        let job_id = graph.job_id().to_string();
+        let executor = mock_executor("executor-1".to_string());
+
+        state.accept_job(&job_id, "", timestamp_millis())?;
+        state.submit_job(job_id.clone(), &graph).await?;
+        graph.revive();
+        complete_next_task(&state, &mut graph, &executor).await?;
+        assert!(state.get_execution_graph(&job_id).await?.is_some());
+
+        // a delta altered in the store is not replayed into the graph
+        let key = graph_delta_key(&job_id, 0);
+        let mut value = store.get(Keyspace::ExecutionGraphDeltas, &key).await?;
+        value[0] ^= 1;
+        store
+            .put(Keyspace::ExecutionGraphDeltas, key, value)
+            .await?;
+        assert!(state.get_execution_graph(&job_id).await.is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_release_job_to_scheduler() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let state = |name: &str| {
+            KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+                name,
+                store.clone(),
+                BallistaCodec::default(),
+                default_session_builder,
+            )
+        };
+        let owner = state("localhost:50050");
+        let target = state("localhost:50051");
+        let other = state("localhost:50052");
+        let mut graph = test_aggregation_plan(4).await;
+        let job_id = graph.job_id().to_string();
+        owner.accept_job(&job_id, "", timestamp_millis())?;
+        owner.submit_job(job_id.clone(), &graph).await?;
+
+        // the job released to a scheduler is only acquired by this scheduler, and is
+        // taken back while it is not acquired
+        owner
+            .release_job(&job_id, &graph, Some("localhost:50051"))
+            .await?;
+        assert!(other.try_acquire_job(&job_id).await?.is_none());
+        graph.set_scheduler("localhost:50050");
+        assert!(
+            owner
+                .reclaim_job(&job_id, &graph, "localhost:50051", 0)
+                .await?
+        );
+
+        // the job acquired by the other scheduler can neither be taken back nor released
+        owner
+            .release_job(&job_id, &graph, Some("localhost:50051"))
+            .await?;
+        assert!(target.try_acquire_job(&job_id).await?.is_some());
+        graph.set_scheduler("localhost:50050");
+        assert!(
+            !owner
+                .reclaim_job(&job_id, &graph, "localhost:50051", 1)
+                .await?
+        );
+        assert!(owner.release_job(&job_id, &graph, None).await.is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_save_task_statuses_and_release_slots() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_graph_compaction_interval(10);
+        let executor = mock_executor("executor-1".to_string());
+        state
+            .register_executor(
+                executor.clone(),
+                ExecutorData {
+                    executor_id: "executor-1".to_string(),
+                    total_task_slots: 4,
+                    available_task_slots: 3,
+                },
+            )
+            .await?;
+
+        let mut graph = test_aggregation_plan(4).await;
+        let job_id = graph.job_id().to_string();
+        state.accept_job(&job_id, "", timestamp_millis())?;
+        state.submit_job(job_id.clone(), &graph).await?;
+        graph.revive();
+
+        let task = graph.pop_next_task(&executor.id)?.expect("no task to run");
+        let status = mock_completed_task(task, &executor.id);
+        graph.update_task_status(&executor, vec![status.clone()], 4, 4)?;
+        assert!(
+            state
+                .save_task_statuses_and_release_slots(
+                    &job_id,
+                    &graph,
+                    &executor,
+                    &[status],
+                    1
+                )
+                .await?
+        );
+
+        // the delta and the released slot are saved together
+        let deltas = store.scan_keys(Keyspace::ExecutionGraphDeltas).await?;
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(4, state.get_task_slots().await?.task_slots[0].slots);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_finished_job_ttl() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_finished_job_ttl(Duration::from_millis(300));
+        assert!(state.expires_finished_jobs());
+        let mut graph = test_aggregation_plan(4).await;
+        let job_id = graph.job_id().to_string();
+
+        state.accept_job(&job_id, "", timestamp_millis())?;
+        state.submit_job(job_id.clone(), &graph).await?;
+        // a running job does not expire
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert!(state.get_job_status(&job_id).await?.is_some());
+
+        graph.fail_job("failed".to_string(), None, vec![]);
+        state.save_job(&job_id, &graph).await?;
+        assert!(state.get_execution_graph(&job_id).await?.is_some());
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert!(state.get_job_status(&job_id).await?.is_none());
+        assert!(state.get_execution_graph(&job_id).await?.is_none());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_batched_heartbeats() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_heartbeat_flush_interval(Duration::from_secs(3600));
+
+        // The first heartbeat of an executor is a status transition and written through
+        state.save_executor_heartbeat(test_heartbeat(1)).await?;
+        assert_eq!(1, stored_heartbeat(&store).await?.timestamp);
+
+        // Subsequent heartbeats with the same status are only persisted on flush
+        state.save_executor_heartbeat(test_heartbeat(2)).await?;
+        assert_eq!(1, stored_heartbeat(&store).await?.timestamp);
+        assert_eq!(
+            Some(2),
+            state
+                .get_executor_heartbeat("executor-1")
+                .map(|heartbeat| heartbeat.timestamp)
+        );
+
+        flush_heartbeats(
+            &store,
+            &state.pending_heartbeats,
+            &state.executor_heartbeats,
+            None,
+        )
+        .await?;
+        assert_eq!(2, stored_heartbeat(&store).await?.timestamp);
+        assert!(state.pending_heartbeats.is_empty());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_executor_lease_expiration() -> Result<()> {
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "",
+            SledClient::try_new_temporary()?,
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_executor_lease(Duration::from_millis(100), Duration::from_millis(100));
+        state.init().await?;
+
+        let mut expirations = state
+            .executor_expirations()
+            .await?
+            .expect("lease expirations should be supported");
+
+        state.save_executor_heartbeat(test_heartbeat(1)).await?;
+        assert!(state.get_executor_heartbeat("executor-1").is_some());
+
+        let expired = tokio::time::timeout(Duration::from_secs(10), expirations.next())
+            .await
+            .expect("executor lease should expire");
+        assert_eq!(Some("executor-1".to_string()), expired);
+        assert!(state.get_executor_heartbeat("executor-1").is_none());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_reclaim_expired_slot_reservations() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "scheduler-1",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_slot_reservation_timeout(Duration::from_secs(60));
+
+        state
+            .register_executor(
+                mock_executor("executor-1".to_string()),
+                ExecutorData {
+                    executor_id: "executor-1".to_string(),
+                    total_task_slots: 4,
+                    available_task_slots: 1,
+                },
+            )
+            .await?;
+
+        // Slots held by a scheduler which crashed and one which is still alive
+        let reservation = |expires_at: u64| SlotReservation {
+            expires_at,
+            task_slots: vec![AvailableTaskSlots {
+                executor_id: "executor-1".to_string(),
+                slots: 2,
+                excess_slots: 0,
+            }],
+        };
+        store
+            .put(
+                Keyspace::SlotReservations,
+                "scheduler-2".to_string(),
+                reservation(0).encode_to_vec(),
+            )
+            .await?;
+        store
+            .put(
+                Keyspace::SlotReservations,
+                "scheduler-3".to_string(),
+                reservation(u64::MAX).encode_to_vec(),
+            )
+            .await?;
+
+        assert_eq!(2, state.reclaim_expired_slot_reservations().await?);
+        assert_eq!(0, state.reclaim_expired_slot_reservations().await?);
+
+        let slots: ExecutorTaskSlots =
+            decode_protobuf(&store.get(Keyspace::Slots, "all").await?)?;
+        assert_eq!(3, slots.task_slots[0].slots);
+        assert_eq!(
+            HashSet::from(["scheduler-3".to_string()]),
+            store.scan_keys(Keyspace::SlotReservations).await?
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_state_cache() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "scheduler-1",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_state_cache_ttl(Duration::from_secs(3600));
+        state.init().await?;
+
+        let executor = mock_executor("executor-1".to_string());
+        state
+            .register_executor(
+                executor.clone(),
+                ExecutorData {
+                    executor_id: "executor-1".to_string(),
+                    total_task_slots: 4,
+                    available_task_slots: 4,
+                },
+            )
+            .await?;
+        assert_eq!(4, state.get_task_slots().await?.task_slots[0].slots);
+        // the heartbeats are answered from the cached slots
+        assert_eq!(
+            Some(4),
+            state.get_cached_available_task_slots("executor-1").await
+        );
+        assert_eq!(
+            None,
+            state.get_cached_available_task_slots("executor-2").await
+        );
+
+        // The slots and the executor metadata updated by another scheduler are seen once
+        // their watch events are received
+        let slots = ExecutorTaskSlots {
+            task_slots: vec![AvailableTaskSlots {
+                executor_id: "executor-1".to_string(),
+                slots: 2,
+                excess_slots: 0,
+            }],
+        };
+        store
+            .put(Keyspace::Slots, "all".to_string(), slots.encode_to_vec())
+            .await?;
+        let updated = ExecutorMetadata {
+            host: "other-host".to_string(),
+            ..executor
+        };
+        let proto: protobuf::ExecutorMetadata = updated.into();
+        store
+            .put(
+                Keyspace::Executors,
+                "executor-1".to_string(),
+                proto.encode_to_vec(),
+            )
+            .await?;
+
+        assert!(
+            await_condition(Duration::from_millis(10), 100, || async {
+                Ok(state.get_task_slots().await?.task_slots[0].slots == 2
+                    && state.get_executor_metadata("executor-1").await?.host
+                        == "other-host")
+            })
+            .await?
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_slot_updates_bypass_cache() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        // Without the cache listener, the cached slots are not invalidated by the updates
+        // of other schedulers
+        let state = KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+            "scheduler-1",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        )
+        .with_state_cache_ttl(Duration::from_secs(3600));
+
+        state
+            .register_executor(
+                mock_executor("executor-1".to_string()),
+                ExecutorData {
+                    executor_id: "executor-1".to_string(),
+                    total_task_slots: 4,
+                    available_task_slots: 4,
+                },
+            )
+            .await?;
+
+        // Another scheduler binds two slots
+        let slots = ExecutorTaskSlots {
+            task_slots: vec![AvailableTaskSlots {
+                executor_id: "executor-1".to_string(),
+                slots: 2,
+                excess_slots: 0,
+            }],
+        };
+        store
+            .put(Keyspace::Slots, "all".to_string(), slots.encode_to_vec())
+            .await?;
+
+        // Releasing a slot updates the stored slots rather than the cached ones
+        state
+            .unbind_tasks(vec![("executor-1".to_string(), 1)])
+            .await?;
+        let value = store.get(Keyspace::Slots, "all").await?;
+        let stored: ExecutorTaskSlots = decode_protobuf(&value)?;
+        assert_eq!(3, stored.task_slots[0].slots);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    fn test_heartbeat(timestamp: u64) -> ExecutorHeartbeat {
+        ExecutorHeartbeat {
+            executor_id: "executor-1".to_string(),
+            timestamp,
+            metrics: vec![],
+            status: Some(protobuf::ExecutorStatus {
+                status: Some(
+                    protobuf::executor_status::Status::Active(String::default()),
+                ),
+            }),
+            running_jobs: vec![],
+        }
+    }
+
+    #[cfg(feature = "sled")]
+    async fn complete_next_task(
+        state: &KeyValueState<SledClient, LogicalPlanNode, PhysicalPlanNode>,
+        graph: &mut ExecutionGraph,
+        executor: &ExecutorMetadata,
+    ) -> Result<()> {
+        let task = graph.pop_next_task(&executor.id)?.expect("no task to run");
+        let status = mock_completed_task(task, &executor.id);
+        graph.update_task_status(executor, vec![status.clone()], 4, 4)?;
+        graph.revive();
+        state
+            .save_task_statuses(graph.job_id(), graph, executor, &[status])
+            .await
+    }
+
+    #[cfg(feature = "sled")]
+    async fn stored_heartbeat(store: &SledClient) -> Result<ExecutorHeartbeat> {
+        let value = store.get(Keyspace::Heartbeats, "executor-1").await?;
+        decode_protobuf(&value)
+    }
+
     #[cfg(feature = "sled")]
     fn make_sled_state() -> Result<KeyValueState<SledClient>> {
         Ok(KeyValueState::new(
@@ -16,8 +16,11 @@
 // under the License.
 
 use crate::cluster::{
-    bind_task_bias, bind_task_consistent_hash, bind_task_round_robin, get_scan_files,
-    is_skip_consistent_hash, BoundTask, ClusterState, ExecutorSlot, JobState,
+    apply_resized_task_slots, bind_task_bias, bind_task_by_input_locality,
+    bind_task_consistent_hash, bind_task_round_robin, bind_task_to_result_zone,
+    bind_task_weighted, bind_task_with_placement_hints, get_scan_files,
+    is_skip_consistent_hash, queued_job_status, resize_task_slots, return_task_slots,
+    BoundTask, CapableExecutors, ClusterState, ExecutorJobLimit, ExecutorSlot, JobState,
     JobStateEvent, JobStateEventStream, JobStatus, TaskDistributionPolicy, TopologyNode,
 };
 use crate::state::execution_graph::ExecutionGraph;
@@ -26,9 +29,10 @@ use ballista_core::config::BallistaConfig;
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::serde::protobuf::{
     executor_status, AvailableTaskSlots, ExecutorHeartbeat, ExecutorStatus, FailedJob,
-    QueuedJob,
+    JobLogicalPlan, TaskStatus,
 };
 use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
 use datafusion::prelude::SessionContext;
 
@@ -44,7 +48,8 @@ use std::ops::DerefMut;
 use ballista_core::consistent_hash::node::Node;
 use datafusion::physical_plan::ExecutionPlan;
 use std::sync::Arc;
-use tokio::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
 use tracing::debug;
 
 #[derive(Default)]
@@ -55,57 +60,22 @@ pub struct InMemoryClusterState {
     executors: DashMap<String, ExecutorMetadata>,
     /// Last heartbeat received for each executor
     heartbeats: DashMap<String, ExecutorHeartbeat>,
+    /// Task slots of the resized executors, kept when they register again
+    resized_task_slots: DashMap<String, u32>,
 }
 
 impl InMemoryClusterState {
-    /// Get the topology nodes of the cluster for consistent hashing
-    fn get_topology_nodes(
-        &self,
-        guard: &MutexGuard<HashMap<String, AvailableTaskSlots>>,
-        executors: Option<HashSet<String>>,
-    ) -> HashMap<String, TopologyNode> {
-        let mut nodes: HashMap<String, TopologyNode> = HashMap::new();
-        for (executor_id, slots) in guard.iter() {
-            if let Some(executors) = executors.as_ref() {
-                if !executors.contains(executor_id) {
-                    continue;
-                }
-            }
-            if let Some(executor) = self.executors.get(&slots.executor_id) {
-                let node = TopologyNode::new(
-                    &executor.host,
-                    executor.port,
-                    &slots.executor_id,
-                    self.heartbeats
-                        .get(&executor.id)
-                        .map(|heartbeat| heartbeat.timestamp)
-                        .unwrap_or(0),
-                    slots.slots,
-                );
-                if let Some(existing_node) = nodes.get(node.name()) {
-                    if existing_node.last_seen_ts < node.last_seen_ts {
-                        nodes.insert(node.name().to_string(), node);
-                    }
-                } else {
-                    nodes.insert(node.name().to_string(), node);
-                }
-            }
-        }
-        nodes
-    }
-}
-
-#[async_trait]
-impl ClusterState for InMemoryClusterState {
-    async fn bind_schedulable_tasks(
+    /// Bind the ready to running tasks from [`active_jobs`] with the available task
+    /// slots, locked by the caller
+    async fn bind_tasks(
         &self,
+        slots: &mut HashMap<String, AvailableTaskSlots>,
         distribution: TaskDistributionPolicy,
         active_jobs: Arc<HashMap<String, JobInfoCache>>,
         executors: Option<HashSet<String>>,
+        locality_label: Option<&str>,
     ) -> Result<Vec<BoundTask>> {
-        let mut guard = self.task_slots.lock().await;
-
-        let available_slots: Vec<&mut AvailableTaskSlots> = guard
+        let mut available_slots: Vec<&mut AvailableTaskSlots> = slots
             .values_mut()
             .filter_map(|data| {
                 (data.slots > 0
@@ -117,12 +87,82 @@ impl ClusterState for InMemoryClusterState {
             })
             .collect();
 
-        let bound_tasks = match distribution {
+        let capable_executors = CapableExecutors::new(
+            self.executors
+                .iter()
+                .map(|executor| executor.value().clone()),
+        );
+        let executor_zones: HashMap<String, String> = self
+            .executors
+            .iter()
+            .filter(|executor| !executor.zone.is_empty())
+            .map(|executor| (executor.id.clone(), executor.zone.clone()))
+            .collect();
+        let (mut bound_tasks, active_jobs) = bind_task_to_result_zone(
+            &mut available_slots,
+            &executor_zones,
+            &capable_executors,
+            active_jobs,
+        )
+        .await;
+
+        let executor_hosts: HashMap<String, String> = available_slots
+            .iter()
+            .filter_map(|slot| {
+                self.executors
+                    .get(&slot.executor_id)
+                    .map(|executor| (slot.executor_id.clone(), executor.host.clone()))
+            })
+            .collect();
+        bound_tasks.extend(
+            bind_task_with_placement_hints(
+                &mut available_slots,
+                &executor_hosts,
+                &capable_executors,
+                active_jobs.clone(),
+            )
+            .await,
+        );
+
+        if let Some(locality_label) = locality_label {
+            let executor_labels: HashMap<String, String> = available_slots
+                .iter()
+                .filter_map(|slot| {
+                    let executor = self.executors.get(&slot.executor_id)?;
+                    let value = executor.topology_label(locality_label)?;
+                    Some((slot.executor_id.clone(), value.to_string()))
+                })
+                .collect();
+            bound_tasks.extend(
+                bind_task_by_input_locality(
+                    &mut available_slots,
+                    &executor_labels,
+                    locality_label,
+                    &capable_executors,
+                    active_jobs.clone(),
+                )
+                .await,
+            );
+        }
+
+        let policy_bound_tasks = match distribution {
             TaskDistributionPolicy::Bias => {
-                bind_task_bias(available_slots, active_jobs, |_| false).await
+                bind_task_bias(available_slots, &capable_executors, active_jobs, |_| {
+                    false
+                })
+                .await
             }
             TaskDistributionPolicy::RoundRobin => {
-                bind_task_round_robin(available_slots, active_jobs, |_| false).await
+                bind_task_round_robin(
+                    available_slots,
+                    &capable_executors,
+                    active_jobs,
+                    |_| false,
+                )
+                .await
+            }
+            TaskDistributionPolicy::Weighted => {
+                bind_task_weighted(available_slots, &capable_executors, active_jobs).await
             }
             TaskDistributionPolicy::ConsistentHash {
                 num_replicas,
@@ -130,6 +170,7 @@ impl ClusterState for InMemoryClusterState {
             } => {
                 let mut bound_tasks = bind_task_round_robin(
                     available_slots,
+                    &capable_executors,
                     active_jobs.clone(),
                     |stage_plan: Arc<dyn ExecutionPlan>| {
                         if let Ok(scan_files) = get_scan_files(stage_plan) {
@@ -144,7 +185,8 @@ impl ClusterState for InMemoryClusterState {
                 info!("{} tasks bound by round robin policy", bound_tasks.len());
                 let (bound_tasks_consistent_hash, ch_topology) =
                     bind_task_consistent_hash(
-                        self.get_topology_nodes(&guard, executors),
+                        self.get_topology_nodes(slots, executors),
+                        &capable_executors,
                         num_replicas,
                         tolerance,
                         active_jobs,
@@ -160,7 +202,7 @@ impl ClusterState for InMemoryClusterState {
                     // Update the available slots
                     let ch_topology = ch_topology.unwrap();
                     for node in ch_topology.nodes() {
-                        if let Some(data) = guard.get_mut(&node.id) {
+                        if let Some(data) = slots.get_mut(&node.id) {
                             data.slots = node.available_slots;
                         } else {
                             error!("Fail to find executor data for {}", &node.id);
@@ -170,7 +212,101 @@ impl ClusterState for InMemoryClusterState {
                 bound_tasks
             }
         };
+        bound_tasks.extend(policy_bound_tasks);
+
+        Ok(bound_tasks)
+    }
+
+    /// Get the topology nodes of the cluster for consistent hashing
+    fn get_topology_nodes(
+        &self,
+        task_slots: &HashMap<String, AvailableTaskSlots>,
+        executors: Option<HashSet<String>>,
+    ) -> HashMap<String, TopologyNode> {
+        let mut nodes: HashMap<String, TopologyNode> = HashMap::new();
+        for (executor_id, slots) in task_slots.iter() {
+            if let Some(executors) = executors.as_ref() {
+                if !executors.contains(executor_id) {
+                    continue;
+                }
+            }
+            if let Some(executor) = self.executors.get(&slots.executor_id) {
+                let node = TopologyNode::new(
+                    &executor.host,
+                    executor.port,
+                    &slots.executor_id,
+                    self.heartbeats
+                        .get(&executor.id)
+                        .map(|heartbeat| heartbeat.timestamp)
+                        .unwrap_or(0),
+                    slots.slots,
+                );
+                if let Some(existing_node) = nodes.get(node.name()) {
+                    if existing_node.last_seen_ts < node.last_seen_ts {
+                        nodes.insert(node.name().to_string(), node);
+                    }
+                } else {
+                    nodes.insert(node.name().to_string(), node);
+                }
+            }
+        }
+        nodes
+    }
+}
+
+#[async_trait]
+impl ClusterState for InMemoryClusterState {
+    async fn bind_schedulable_tasks(
+        &self,
+        distribution: TaskDistributionPolicy,
+        active_jobs: Arc<HashMap<String, JobInfoCache>>,
+        executors: Option<HashSet<String>>,
+        locality_label: Option<&str>,
+        job_limit: Option<ExecutorJobLimit>,
+    ) -> Result<Vec<BoundTask>> {
+        let mut guard = self.task_slots.lock().await;
 
+        let Some(mut job_limit) = job_limit else {
+            return self
+                .bind_tasks(
+                    &mut guard,
+                    distribution,
+                    active_jobs,
+                    executors,
+                    locality_label,
+                )
+                .await;
+        };
+        let mut bound_tasks = vec![];
+        for job_id in job_limit.jobs() {
+            let job_executors = job_limit.allowed_executors(
+                &job_id,
+                guard
+                    .values()
+                    .filter(|data| data.slots > 0)
+                    .map(|data| &data.executor_id),
+                executors.as_ref(),
+            );
+            let Some(job) = active_jobs.get(&job_id) else {
+                continue;
+            };
+            if job_executors.is_empty() {
+                continue;
+            }
+            let job_tasks = self
+                .bind_tasks(
+                    &mut guard,
+                    job.task_distribution.unwrap_or(distribution),
+                    Arc::new(HashMap::from([(job_id.clone(), job.clone())])),
+                    Some(job_executors),
+                    locality_label,
+                )
+                .await?;
+            for (executor_id, _) in &job_tasks {
+                job_limit.bind(executor_id, &job_id);
+            }
+            bound_tasks.extend(job_tasks);
+        }
         Ok(bound_tasks)
     }
 
@@ -185,7 +321,7 @@ impl ClusterState for InMemoryClusterState {
 
         for (executor_id, num_slots) in increments {
             if let Some(data) = guard.get_mut(&executor_id) {
-                data.slots += num_slots;
+                return_task_slots(data, num_slots);
             }
         }
 
@@ -194,10 +330,13 @@ impl ClusterState for InMemoryClusterState {
 
     async fn register_executor(
         &self,
-        metadata: ExecutorMetadata,
-        spec: ExecutorData,
+        mut metadata: ExecutorMetadata,
+        mut spec: ExecutorData,
     ) -> Result<()> {
         let executor_id = metadata.id.clone();
+        if let Some(task_slots) = self.resized_task_slots.get(&executor_id) {
+            apply_resized_task_slots(&mut metadata, &mut spec, *task_slots);
+        }
 
         self.save_executor_metadata(metadata).await?;
         self.save_executor_heartbeat(ExecutorHeartbeat {
@@ -207,6 +346,7 @@ impl ClusterState for InMemoryClusterState {
             status: Some(ExecutorStatus {
                 status: Some(executor_status::Status::Active(String::default())),
             }),
+            running_jobs: vec![],
         })
         .await?;
 
@@ -217,12 +357,43 @@ impl ClusterState for InMemoryClusterState {
             AvailableTaskSlots {
                 executor_id,
                 slots: spec.available_task_slots,
+                excess_slots: 0,
             },
         );
 
         Ok(())
     }
 
+    async fn resize_executor_task_slots(
+        &self,
+        executor_id: &str,
+        task_slots: u32,
+    ) -> Result<u32> {
+        let mut guard = self.task_slots.lock().await;
+
+        let mut metadata = self.get_executor_metadata(executor_id).await?;
+        let previous_task_slots = metadata.specification.task_slots;
+        if let Some(data) = guard.get_mut(executor_id) {
+            resize_task_slots(data, previous_task_slots, task_slots);
+        }
+        metadata.specification.task_slots = task_slots;
+        self.save_executor_metadata(metadata).await?;
+        self.resized_task_slots
+            .insert(executor_id.to_string(), task_slots);
+
+        Ok(previous_task_slots)
+    }
+
+    async fn get_available_task_slots(&self, executor_id: &str) -> Result<Option<u32>> {
+        let guard = self.task_slots.lock().await;
+        Ok(guard.get(executor_id).map(|data| data.slots))
+    }
+
+    async fn get_cached_available_task_slots(&self, executor_id: &str) -> Option<u32> {
+        let guard = self.task_slots.lock().await;
+        guard.get(executor_id).map(|data| data.slots)
+    }
+
     async fn save_executor_metadata(&self, metadata: ExecutorMetadata) -> Result<()> {
         self.executors.insert(metadata.id.clone(), metadata);
         Ok(())
@@ -280,8 +451,9 @@ pub struct InMemoryJobState {
     scheduler: String,
     /// Jobs which have either completed successfully or failed
     completed_jobs: DashMap<String, (JobStatus, Option<ExecutionGraph>)>,
-    /// In-memory store of queued jobs. Map from Job ID -> (Job Name, queued_at timestamp)
-    queued_jobs: DashMap<String, (String, u64)>,
+    /// In-memory store of queued jobs. Map from Job ID -> (Job Name, queued_at timestamp,
+    /// planning started_at timestamp)
+    queued_jobs: DashMap<String, (String, u64, Option<u64>)>,
     /// In-memory store of running job statuses. Map from Job ID -> JobStatus
     running_jobs: DashMap<String, JobStatus>,
     /// Active ballista sessions
@@ -290,6 +462,10 @@ pub struct InMemoryJobState {
     session_builder: SessionBuilder,
     /// Sender of job events
     job_event_sender: ClusterEventSender<JobStateEvent>,
+    /// Jobs submitted with an idempotency key. Map from key -> (Job ID, expiration)
+    idempotency_keys: DashMap<String, (String, Instant)>,
+    /// Optimized logical plans of the jobs. Map from Job ID -> plan
+    job_plans: DashMap<String, JobLogicalPlan>,
 }
 
 impl InMemoryJobState {
@@ -302,12 +478,40 @@ impl InMemoryJobState {
             sessions: Default::default(),
             session_builder,
             job_event_sender: ClusterEventSender::new(100),
+            idempotency_keys: Default::default(),
+            job_plans: Default::default(),
         }
     }
 }
 
 #[async_trait]
 impl JobState for InMemoryJobState {
+    async fn get_idempotency_key(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .idempotency_keys
+            .get(key)
+            .filter(|entry| entry.1 > Instant::now())
+            .map(|entry| entry.0.clone()))
+    }
+
+    async fn register_idempotency_key(
+        &self,
+        key: &str,
+        job_id: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>> {
+        let now = Instant::now();
+        self.idempotency_keys
+            .retain(|_, (_, expiration)| *expiration > now);
+        match self.idempotency_keys.entry(key.to_string()) {
+            Entry::Occupied(entry) => Ok(Some(entry.get().0.clone())),
+            Entry::Vacant(entry) => {
+                entry.insert((job_id.to_string(), now + ttl));
+                Ok(None)
+            }
+        }
+    }
+
     async fn submit_job(&self, job_id: String, graph: &ExecutionGraph) -> Result<()> {
         if self.queued_jobs.get(&job_id).is_some() {
             self.running_jobs
@@ -328,14 +532,15 @@ impl JobState for InMemoryJobState {
     }
 
     async fn get_job_status(&self, job_id: &str) -> Result<Option<JobStatus>> {
-        if let Some((job_name, queued_at)) = self.queued_jobs.get(job_id).as_deref() {
-            return Ok(Some(JobStatus {
-                job_id: job_id.to_string(),
-                job_name: job_name.clone(),
-                status: Some(Status::Queued(QueuedJob {
-                    queued_at: *queued_at,
-                })),
-            }));
+        if let Some((job_name, queued_at, planning_started_at)) =
+            self.queued_jobs.get(job_id).as_deref()
+        {
+            return Ok(Some(queued_job_status(
+                job_id,
+                job_name,
+                *queued_at,
+                *planning_started_at,
+            )));
         }
 
         if let Some(status) = self.running_jobs.get(job_id).as_deref().cloned() {
@@ -388,6 +593,17 @@ impl JobState for InMemoryJobState {
         Ok(())
     }
 
+    async fn save_task_statuses(
+        &self,
+        _job_id: &str,
+        _graph: &ExecutionGraph,
+        _executor: &ExecutorMetadata,
+        _task_statuses: &[TaskStatus],
+    ) -> Result<()> {
+        // the graphs of running jobs are only kept by the task manager
+        Ok(())
+    }
+
     async fn get_session(&self, session_id: &str) -> Result<Arc<SessionContext>> {
         self.sessions
             .get(session_id)
@@ -430,7 +646,17 @@ impl JobState for InMemoryJobState {
         Ok(Box::pin(self.job_event_sender.subscribe()))
     }
 
+    async fn save_job_plan(&self, job_id: &str, plan: &JobLogicalPlan) -> Result<()> {
+        self.job_plans.insert(job_id.to_string(), plan.clone());
+        Ok(())
+    }
+
+    async fn get_job_plan(&self, job_id: &str) -> Result<Option<JobLogicalPlan>> {
+        Ok(self.job_plans.get(job_id).map(|plan| plan.clone()))
+    }
+
     async fn remove_job(&self, job_id: &str) -> Result<()> {
+        self.job_plans.remove(job_id);
         if self.completed_jobs.remove(job_id).is_none() {
             warn!("Tried to delete non-existent job {job_id} from state");
         }
@@ -439,25 +665,40 @@ impl JobState for InMemoryJobState {
 
     async fn get_jobs(&self) -> Result<HashSet<String>> {
         Ok(self
-            .completed_jobs
+            .running_jobs
             .iter()
             .map(|pair| pair.key().clone())
+            .chain(self.completed_jobs.iter().map(|pair| pair.key().clone()))
             .collect())
     }
 
     fn accept_job(&self, job_id: &str, job_name: &str, queued_at: u64) -> Result<()> {
         self.queued_jobs
-            .insert(job_id.to_string(), (job_name.to_string(), queued_at));
+            .insert(job_id.to_string(), (job_name.to_string(), queued_at, None));
 
         Ok(())
     }
 
+    fn start_planning(&self, job_id: &str, started_at: u64) -> Result<()> {
+        if let Some(mut queued) = self.queued_jobs.get_mut(job_id) {
+            queued.2 = Some(started_at);
+
+            Ok(())
+        } else {
+            Err(BallistaError::Internal(format!(
+                "Could not start planning job {job_id}, not found in queued jobs"
+            )))
+        }
+    }
+
     fn pending_job_number(&self) -> usize {
         self.queued_jobs.len()
     }
 
     async fn fail_unscheduled_job(&self, job_id: &str, reason: String) -> Result<()> {
-        if let Some((job_id, (job_name, queued_at))) = self.queued_jobs.remove(job_id) {
+        if let Some((job_id, (job_name, queued_at, planning_started_at))) =
+            self.queued_jobs.remove(job_id)
+        {
             self.completed_jobs.insert(
                 job_id.clone(),
                 (
@@ -467,8 +708,10 @@ impl JobState for InMemoryJobState {
                         status: Some(Status::Failed(FailedJob {
                             error: reason,
                             queued_at,
-                            started_at: 0,
+                            started_at: planning_started_at.unwrap_or_default(),
                             ended_at: timestamp_millis(),
+                            failed_task: None,
+                            error_chain: vec![],
                         })),
                     },
                     None,
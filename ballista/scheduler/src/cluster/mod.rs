@@ -19,6 +19,7 @@ use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 use clap::ArgEnum;
 use datafusion::common::tree_node::TreeNode;
@@ -37,8 +38,10 @@ use ballista_core::config::BallistaConfig;
 use ballista_core::consistent_hash;
 use ballista_core::consistent_hash::ConsistentHash;
 use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::PartitionPlacementExec;
 use ballista_core::serde::protobuf::{
-    job_status, AvailableTaskSlots, ExecutorHeartbeat, JobStatus,
+    job_status, AvailableTaskSlots, ExecutorHeartbeat, JobLogicalPlan, JobStatus,
+    PlanningJob, QueuedJob, TaskStatus,
 };
 use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata, PartitionId};
 use ballista_core::serde::BallistaCodec;
@@ -46,15 +49,22 @@ use ballista_core::utils::default_session_builder;
 
 use crate::cluster::kv::KeyValueState;
 use crate::cluster::memory::{InMemoryClusterState, InMemoryJobState};
+use crate::cluster::storage::compressed::CompressedStore;
 use crate::cluster::storage::etcd::EtcdClient;
+use crate::cluster::storage::instrumented::InstrumentedStore;
 use crate::cluster::storage::sled::SledClient;
 use crate::cluster::storage::KeyValueStore;
 use crate::config::{ClusterStorageConfig, SchedulerConfig, TaskDistributionPolicy};
-use crate::scheduler_server::SessionBuilder;
-use crate::state::execution_graph::{create_task_info, ExecutionGraph, TaskDescription};
+use crate::metrics::default_metrics_collector;
+use crate::scheduler_server::{timestamp_millis, SessionBuilder};
+use crate::state::execution_graph::{
+    create_task_info, ExecutionGraph, TaskBinding, TaskDescription,
+};
+use crate::state::required_object_store_schemes;
 use crate::state::task_manager::JobInfoCache;
 
 pub mod event;
+mod graph_storage;
 pub mod kv;
 pub mod memory;
 pub mod storage;
@@ -123,14 +133,85 @@ impl BallistaCluster {
         session_builder: SessionBuilder,
         codec: BallistaCodec<T, U>,
     ) -> Self {
-        let kv_state =
-            Arc::new(KeyValueState::new(scheduler, store, codec, session_builder));
+        Self::from_kv_state(KeyValueState::new(scheduler, store, codec, session_builder))
+    }
+
+    fn from_kv_state<
+        S: KeyValueStore,
+        T: 'static + AsLogicalPlan,
+        U: 'static + AsExecutionPlan,
+    >(
+        kv_state: KeyValueState<S, T, U>,
+    ) -> Self {
+        let kv_state = Arc::new(kv_state);
         Self {
             cluster_state: kv_state.clone(),
             job_state: kv_state,
         }
     }
 
+    fn new_kv_from_config<S: KeyValueStore>(
+        store: S,
+        config: &SchedulerConfig,
+    ) -> Result<Self> {
+        let metrics_collector = default_metrics_collector()?;
+        let slow_operation_threshold = (config.state_slow_operation_ms > 0)
+            .then(|| Duration::from_millis(config.state_slow_operation_ms));
+        let store = InstrumentedStore::new(
+            store,
+            metrics_collector.clone(),
+            slow_operation_threshold,
+        );
+        let mut kv_state = KeyValueState::new(
+            config.scheduler_name(),
+            CompressedStore::new(store, config.compressed_keyspaces.clone()),
+            BallistaCodec::default().with_plan_protection(config.plan_protection.clone()),
+            default_session_builder,
+        );
+        if config.executor_heartbeat_flush_interval_ms > 0 {
+            kv_state = kv_state.with_heartbeat_flush_interval(Duration::from_millis(
+                config.executor_heartbeat_flush_interval_ms,
+            ));
+        }
+        if config.slot_reservation_timeout_seconds > 0 {
+            kv_state = kv_state.with_slot_reservation_timeout(Duration::from_secs(
+                config.slot_reservation_timeout_seconds,
+            ));
+        }
+        if config.scheduler_lease_timeout_seconds > 0 {
+            kv_state = kv_state.with_scheduler_lease(Duration::from_secs(
+                config.scheduler_lease_timeout_seconds,
+            ));
+        }
+        if config.cluster_state_cache_ttl_ms > 0 {
+            kv_state = kv_state.with_state_cache_ttl(Duration::from_millis(
+                config.cluster_state_cache_ttl_ms,
+            ));
+        }
+        if config.executor_liveness_leases {
+            kv_state = kv_state.with_executor_lease(
+                Duration::from_secs(config.executor_timeout_seconds),
+                Duration::from_secs(config.executor_termination_grace_period),
+            );
+        }
+        if config.execution_graph_compaction_interval > 0 {
+            kv_state = kv_state.with_graph_compaction_interval(
+                config.execution_graph_compaction_interval,
+            );
+        }
+        let chunk_size = (config.execution_graph_chunk_size > 0)
+            .then_some(config.execution_graph_chunk_size as usize);
+        if config.finished_job_state_clean_up_interval_seconds > 0 {
+            kv_state = kv_state.with_finished_job_ttl(Duration::from_secs(
+                config.finished_job_state_clean_up_interval_seconds,
+            ));
+        }
+        kv_state = kv_state
+            .with_graph_storage(config.execution_graph_compression, chunk_size)
+            .with_metrics_collector(metrics_collector);
+        Ok(Self::from_kv_state(kv_state))
+    }
+
     pub async fn new_from_config(config: &SchedulerConfig) -> Result<Self> {
         let scheduler = config.scheduler_name();
 
@@ -145,12 +226,10 @@ impl BallistaCluster {
                         ))
                     })?;
 
-                Ok(Self::new_kv(
+                Self::new_kv_from_config(
                     EtcdClient::new(config.namespace.clone(), etcd),
-                    scheduler,
-                    default_session_builder,
-                    BallistaCodec::default(),
-                ))
+                    config,
+                )
             }
             #[cfg(not(feature = "etcd"))]
             StateBackend::Etcd => {
@@ -164,22 +243,12 @@ impl BallistaCluster {
                     info!("Initializing Sled database in directory {}", dir);
                     let sled = SledClient::try_new(dir)?;
 
-                    Ok(Self::new_kv(
-                        sled,
-                        scheduler,
-                        default_session_builder,
-                        BallistaCodec::default(),
-                    ))
+                    Self::new_kv_from_config(sled, config)
                 } else {
                     info!("Initializing Sled database in temp directory");
                     let sled = SledClient::try_new_temporary()?;
 
-                    Ok(Self::new_kv(
-                        sled,
-                        scheduler,
-                        default_session_builder,
-                        BallistaCodec::default(),
-                    ))
+                    Self::new_kv_from_config(sled, config)
                 }
             }
             #[cfg(not(feature = "sled"))]
@@ -208,6 +277,9 @@ impl BallistaCluster {
 /// by any schedulers with a shared `ClusterState`
 pub type ExecutorHeartbeatStream = Pin<Box<dyn Stream<Item = ExecutorHeartbeat> + Send>>;
 
+/// Stream of IDs of executors whose liveness lease expired
+pub type ExecutorExpirationStream = Pin<Box<dyn Stream<Item = String> + Send>>;
+
 /// A task bound with an executor to execute.
 /// BoundTask.0 is the executor id; While BoundTask.1 is the task description.
 pub type BoundTask = (String, TaskDescription);
@@ -215,6 +287,68 @@ pub type BoundTask = (String, TaskDescription);
 /// ExecutorSlot.0 is the executor id; While ExecutorSlot.1 is for slot number.
 pub type ExecutorSlot = (String, u32);
 
+/// The limit of the jobs running tasks on every executor, see `max_jobs_per_executor`
+#[derive(Debug, Clone)]
+pub struct ExecutorJobLimit {
+    max_jobs: usize,
+    /// The IDs of the jobs running tasks on every executor
+    executor_jobs: HashMap<String, HashSet<String>>,
+    /// The IDs of the active jobs from the job running for the longest
+    jobs: Vec<String>,
+}
+
+impl ExecutorJobLimit {
+    pub fn new(
+        max_jobs: usize,
+        executor_jobs: HashMap<String, HashSet<String>>,
+        jobs: Vec<String>,
+    ) -> Self {
+        Self {
+            max_jobs,
+            executor_jobs,
+            jobs,
+        }
+    }
+
+    /// The IDs of the active jobs, in the order their tasks are bound
+    pub fn jobs(&self) -> Vec<String> {
+        self.jobs.clone()
+    }
+
+    /// Whether the tasks of a job may be bound to an executor, which either already runs
+    /// the job or runs fewer jobs than the limit
+    fn allows(&self, executor_id: &str, job_id: &str) -> bool {
+        self.executor_jobs.get(executor_id).map_or(true, |jobs| {
+            jobs.contains(job_id) || jobs.len() < self.max_jobs
+        })
+    }
+
+    /// The executors with available task slots, only among `executors` if provided, to
+    /// which the tasks of a job may be bound
+    pub fn allowed_executors<'a>(
+        &self,
+        job_id: &str,
+        available_executors: impl Iterator<Item = &'a String>,
+        executors: Option<&HashSet<String>>,
+    ) -> HashSet<String> {
+        available_executors
+            .filter(|executor_id| {
+                executors.map_or(true, |executors| executors.contains(*executor_id))
+                    && self.allows(executor_id, job_id)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Record a task of a job bound to an executor
+    pub fn bind(&mut self, executor_id: &str, job_id: &str) {
+        self.executor_jobs
+            .entry(executor_id.to_owned())
+            .or_default()
+            .insert(job_id.to_owned());
+    }
+}
+
 /// A trait that contains the necessary method to maintain a globally consistent view of cluster resources
 #[tonic::async_trait]
 pub trait ClusterState: Send + Sync + 'static {
@@ -225,12 +359,20 @@ pub trait ClusterState: Send + Sync + 'static {
 
     /// Bind the ready to running tasks from [`active_jobs`] with available executors.
     ///
-    /// If `executors` is provided, only bind slots from the specified executor IDs
+    /// If `executors` is provided, only bind slots from the specified executor IDs. If
+    /// `locality_label` is provided, the tasks reading shuffled data are first bound to
+    /// executors with the same value of this topology label as the executors holding most
+    /// of their input, see [`bind_task_by_input_locality`]. If `job_limit` is provided,
+    /// the tasks of the jobs are bound one job at a time in its order, with the task
+    /// distribution policy of the job if it has one, only to the executors allowed by the
+    /// limit. All the jobs are bound under the same lock of the task slots.
     async fn bind_schedulable_tasks(
         &self,
         distribution: TaskDistributionPolicy,
         active_jobs: Arc<HashMap<String, JobInfoCache>>,
         executors: Option<HashSet<String>>,
+        locality_label: Option<&str>,
+        job_limit: Option<ExecutorJobLimit>,
     ) -> Result<Vec<BoundTask>>;
 
     /// Unbind executor and task when a task finishes or fails. It will increase the executor
@@ -239,6 +381,25 @@ pub trait ClusterState: Send + Sync + 'static {
     /// This operations should be atomic. Either all reservations are cancelled or none are
     async fn unbind_tasks(&self, executor_slots: Vec<ExecutorSlot>) -> Result<()>;
 
+    /// Extend the expiry of the slots held by this scheduler. Implementations shared by
+    /// multiple schedulers should track the slots bound by each scheduler, so that the slots of a
+    /// scheduler which stopped renewing its reservation can be reclaimed.
+    async fn renew_slot_reservation(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Return the slots held by schedulers whose reservation expired to the available
+    /// task slots. Returns the number of reclaimed slots.
+    async fn reclaim_expired_slot_reservations(&self) -> Result<u32> {
+        Ok(0)
+    }
+
+    /// Persist the state buffered in memory, e.g. the pending executor heartbeats and the
+    /// slots reserved by this scheduler, before the scheduler exits
+    async fn shutdown(&self) -> Result<()> {
+        Ok(())
+    }
+
     /// Register a new executor in the cluster.
     async fn register_executor(
         &self,
@@ -246,6 +407,35 @@ pub trait ClusterState: Send + Sync + 'static {
         spec: ExecutorData,
     ) -> Result<()>;
 
+    /// Change the task slots of a registered executor to `task_slots` and return its previous
+    /// task slots. The available slots of the executor change by the same amount, the slots
+    /// held by running tasks in excess of the new task slots are dropped when the tasks
+    /// complete, see [`resize_task_slots`]. The new task slots are kept when the executor
+    /// registers again. Not supported by default.
+    async fn resize_executor_task_slots(
+        &self,
+        executor_id: &str,
+        _task_slots: u32,
+    ) -> Result<u32> {
+        Err(BallistaError::General(format!(
+            "Cannot resize the task slots of executor {executor_id}, the cluster state \
+            does not support it"
+        )))
+    }
+
+    /// Get the number of available task slots of an executor. Returns None if the executor
+    /// has no task slots registered, or by default if the state cannot tell
+    async fn get_available_task_slots(&self, _executor_id: &str) -> Result<Option<u32>> {
+        Ok(None)
+    }
+
+    /// Get the number of available task slots of an executor as known in the memory of
+    /// the scheduler, without reading the store, e.g. to answer every heartbeat. Returns
+    /// None if they are not known, or by default.
+    async fn get_cached_available_task_slots(&self, _executor_id: &str) -> Option<u32> {
+        None
+    }
+
     /// Save the executor metadata. This will overwrite existing metadata for the executor ID
     async fn save_executor_metadata(&self, metadata: ExecutorMetadata) -> Result<()>;
 
@@ -263,6 +453,13 @@ pub trait ClusterState: Send + Sync + 'static {
 
     /// Get executor heartbeat for the provided executor ID. Return None if the executor does not exist
     fn get_executor_heartbeat(&self, executor_id: &str) -> Option<ExecutorHeartbeat>;
+
+    /// Return a stream of IDs of executors which have been removed from the cluster because
+    /// their liveness lease expired. Return None if the implementation does not support leases,
+    /// in which case dead executors are detected by checking heartbeat timestamps.
+    async fn executor_expirations(&self) -> Result<Option<ExecutorExpirationStream>> {
+        Ok(None)
+    }
 }
 
 /// Events related to the state of jobs. Implementations may or may not support all event types.
@@ -314,15 +511,50 @@ pub trait JobState: Send + Sync {
     /// in global state
     fn accept_job(&self, job_id: &str, job_name: &str, queued_at: u64) -> Result<()>;
 
+    /// Mark a queued job as being planned. The job stays in the job queue, and is reported as
+    /// `JobStatus::Planning`, until it is submitted or failed
+    fn start_planning(&self, job_id: &str, started_at: u64) -> Result<()>;
+
     /// Get the number of queued jobs. If it's big, then it means the scheduler is too busy.
     /// In normal case, it's better to be 0.
     fn pending_job_number(&self) -> usize;
 
+    /// Get the job registered with the client supplied idempotency `key`, if its
+    /// time-to-live has not elapsed. The idempotency keys are not kept by default.
+    async fn get_idempotency_key(&self, _key: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Register `job_id` as the job submitted with the client supplied idempotency `key`
+    /// for `ttl`. If a job is already registered with the key and its time-to-live has
+    /// not elapsed, the key is left unchanged and the ID of that job is returned instead.
+    /// The idempotency keys are not kept by default, so that submissions are not
+    /// deduplicated.
+    async fn register_idempotency_key(
+        &self,
+        _key: &str,
+        _job_id: &str,
+        _ttl: Duration,
+    ) -> Result<Option<String>> {
+        Ok(None)
+    }
+
     /// Submit a new job to the `JobState`. It is assumed that the submitter owns the job.
     /// In local state the job should be save as `JobStatus::Active` and in shared state
     /// it should be saved as `JobStatus::Running` with `scheduler` set to the current scheduler
     async fn submit_job(&self, job_id: String, graph: &ExecutionGraph) -> Result<()>;
 
+    /// Save the optimized logical plan of a job when it is submitted, once per job rather
+    /// than with every snapshot of its execution graph. Not kept by default.
+    async fn save_job_plan(&self, _job_id: &str, _plan: &JobLogicalPlan) -> Result<()> {
+        Ok(())
+    }
+
+    /// Get the optimized logical plan of a job, `None` if it was not kept
+    async fn get_job_plan(&self, _job_id: &str) -> Result<Option<JobLogicalPlan>> {
+        Ok(None)
+    }
+
     /// Return a `Vec` of all active job IDs in the `JobState`
     async fn get_jobs(&self) -> Result<HashSet<String>>;
 
@@ -339,10 +571,45 @@ pub trait JobState: Send + Sync {
     /// if the job is not owned by the caller.
     async fn save_job(&self, job_id: &str, graph: &ExecutionGraph) -> Result<()>;
 
+    /// Persist task status updates reported by `executor` which were just applied to the
+    /// `graph` of an owned job. Implementations may save the updates as a delta of the last
+    /// saved graph, or save the whole graph, as done by default.
+    async fn save_task_statuses(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        _executor: &ExecutorMetadata,
+        _task_statuses: &[TaskStatus],
+    ) -> Result<()> {
+        self.save_job(job_id, graph).await
+    }
+
+    /// Persist task status updates like [`JobState::save_task_statuses`], and return
+    /// `released_slots` task slots to `executor` in the same transaction, so that a crash never
+    /// leaves the slots of finished tasks bound or returns the slots of tasks whose status was
+    /// not saved. Returns `false` without saving anything if the task slots are not stored with
+    /// the jobs, in which case the caller saves the updates and releases the slots separately.
+    async fn save_task_statuses_and_release_slots(
+        &self,
+        _job_id: &str,
+        _graph: &ExecutionGraph,
+        _executor: &ExecutorMetadata,
+        _task_statuses: &[TaskStatus],
+        _released_slots: u32,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
     /// Mark a job which has not been submitted as failed. This should be called if a job fails
     /// during planning (and does not yet have an `ExecutionGraph`)
     async fn fail_unscheduled_job(&self, job_id: &str, reason: String) -> Result<()>;
 
+    /// Whether the finished jobs are deleted from the global state once their retention
+    /// elapsed, so that they do not need to be removed by the scheduler
+    fn expires_finished_jobs(&self) -> bool {
+        false
+    }
+
     /// Delete a job from the global state
     async fn remove_job(&self, job_id: &str) -> Result<()>;
 
@@ -351,6 +618,62 @@ pub trait JobState: Send + Sync {
     /// otherwise return `None`
     async fn try_acquire_job(&self, job_id: &str) -> Result<Option<ExecutionGraph>>;
 
+    /// Attempt to take over a running job owned by `failed_scheduler`, which stopped
+    /// without releasing it, e.g. when a standby scheduler is promoted. The job is only
+    /// taken over once the lease of `failed_scheduler` expired, see
+    /// `renew_scheduler_lease`. The jobs which may be acquired with `try_acquire_job` are
+    /// also taken over.
+    async fn try_take_over_job(
+        &self,
+        job_id: &str,
+        _failed_scheduler: &str,
+    ) -> Result<Option<ExecutionGraph>> {
+        self.try_acquire_job(job_id).await
+    }
+
+    /// Save the current `ExecutionGraph` of a job owned by this scheduler and release its
+    /// ownership, so that it may be acquired by another scheduler, only the scheduler
+    /// `target` if given. Called when the scheduler shuts down or hands off the job.
+    /// Fails if the job is no longer owned by this scheduler.
+    async fn release_job(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        _target: Option<&str>,
+    ) -> Result<()> {
+        self.save_job(job_id, graph).await
+    }
+
+    /// Take back the ownership of a job released to the scheduler `target` with
+    /// `release_job` at the ownership `epoch`, saving its `graph`, unless `target`
+    /// acquired the job since. Returns whether the job was taken back.
+    async fn reclaim_job(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        _target: &str,
+        _epoch: u64,
+    ) -> Result<bool> {
+        self.save_job(job_id, graph).await?;
+        Ok(true)
+    }
+
+    /// Renew the lease of this scheduler, which tells the other schedulers that it still
+    /// owns its jobs
+    async fn renew_scheduler_lease(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Keep the decoded execution graph of a running job of another scheduler in memory,
+    /// so that taking the job over only reads the changes saved since, e.g. on a standby
+    /// scheduler. The graph is forgotten once the job is no longer running.
+    async fn warm_execution_graph(&self, _job_id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Forget the execution graphs kept in memory with `warm_execution_graph`
+    fn forget_warm_execution_graphs(&self) {}
+
     /// Get a stream of all `JobState` events. An event should be published any time that status
     /// of a job changes in state
     async fn job_state_events(&self) -> Result<JobStateEventStream>;
@@ -378,8 +701,493 @@ pub trait JobState: Send + Sync {
     ) -> Result<Option<Arc<SessionContext>>>;
 }
 
+/// The executors advertising their capabilities, to bind the tasks of a stage only to the
+/// executors able to access the object stores the stage reads from. The executors which
+/// do not advertise their capabilities are assumed to be able to run any task.
+#[derive(Debug, Default)]
+pub(crate) struct CapableExecutors {
+    executors: Vec<ExecutorMetadata>,
+}
+
+impl CapableExecutors {
+    pub(crate) fn new(executors: impl IntoIterator<Item = ExecutorMetadata>) -> Self {
+        Self {
+            executors: executors
+                .into_iter()
+                .filter(ExecutorMetadata::advertises_capabilities)
+                .collect(),
+        }
+    }
+
+    /// The IDs of the executors unable to run the tasks of a stage with the given plan
+    pub(crate) fn unable_to_run(&self, plan: &dyn ExecutionPlan) -> HashSet<String> {
+        if self.executors.is_empty() {
+            return HashSet::new();
+        }
+        let schemes = required_object_store_schemes(plan);
+        self.executors
+            .iter()
+            .filter(|executor| {
+                !schemes
+                    .iter()
+                    .all(|scheme| executor.supports_object_store(scheme))
+            })
+            .map(|executor| executor.id.clone())
+            .collect()
+    }
+}
+
+/// Max time in milliseconds the tasks of a final stage wait for a slot on the executors
+/// of the zone of the client, before falling back to the executors of any zone
+pub(crate) const RESULT_ZONE_MAX_WAIT_MS: u64 = 30_000;
+
+/// Bind the tasks of the final stage of jobs whose session sets a client zone to executors
+/// of that zone, so that the client fetches the job results within its zone.
+/// `executor_zones` maps the IDs of all the registered executors to their zone.
+///
+/// Jobs whose final stage is running are held back from the other task binding functions
+/// while the cluster has executors in their zone, even if those have no available slots,
+/// for at most [RESULT_ZONE_MAX_WAIT_MS] after the final stage started running. The tasks
+/// left then run on the executors of any zone.
+/// Returns the bound tasks and the active jobs left to the other task binding functions.
+pub(crate) async fn bind_task_to_result_zone(
+    slots: &mut [&mut AvailableTaskSlots],
+    executor_zones: &HashMap<String, String>,
+    capable_executors: &CapableExecutors,
+    active_jobs: Arc<HashMap<String, JobInfoCache>>,
+) -> (Vec<BoundTask>, Arc<HashMap<String, JobInfoCache>>) {
+    let mut schedulable_tasks: Vec<BoundTask> = vec![];
+    let mut held_back_jobs: HashSet<String> = HashSet::new();
+
+    for (job_id, job_info) in active_jobs.iter() {
+        if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
+            continue;
+        }
+        let zone = match &job_info.result_zone {
+            Some(zone) if executor_zones.values().any(|z| z == zone) => zone,
+            _ => continue,
+        };
+        let mut graph = job_info.execution_graph.write().await;
+        let session_id = graph.session_id().to_string();
+        let max_running_tasks = graph.max_running_stage_tasks();
+        let max_tasks_per_executor = graph.max_stage_tasks_per_executor();
+        let mut black_list = vec![];
+        while let Some((running_stage, task_id_gen)) =
+            graph.fetch_running_stage(&black_list)
+        {
+            black_list.push(running_stage.stage_id);
+            if !running_stage.output_links.is_empty()
+                || timestamp_millis().saturating_sub(running_stage.running_since)
+                    > RESULT_ZONE_MAX_WAIT_MS
+            {
+                continue;
+            }
+            held_back_jobs.insert(job_id.clone());
+            let mut executor_limit = running_stage
+                .executor_task_limit(max_tasks_per_executor)
+                .excluding(capable_executors.unable_to_run(running_stage.plan.as_ref()));
+            let max_tasks = running_stage
+                .schedulable_tasks(max_running_tasks.of(running_stage.stage_id));
+            let runnable_tasks = running_stage
+                .task_infos
+                .iter_mut()
+                .enumerate()
+                .filter(|(_partition, info)| info.is_none())
+                .take(max_tasks);
+            for (partition_id, task_info) in runnable_tasks {
+                // Choose the executor of the zone with the most available slots
+                let slot = match slots
+                    .iter_mut()
+                    .filter(|slot| {
+                        slot.slots > 0
+                            && executor_zones.get(&slot.executor_id) == Some(zone)
+                            && executor_limit.allows(&slot.executor_id)
+                    })
+                    .max_by_key(|slot| slot.slots)
+                {
+                    Some(slot) => slot,
+                    None => break,
+                };
+                let executor_id = slot.executor_id.clone();
+                executor_limit.bind(&executor_id);
+                let task_id = *task_id_gen;
+                *task_id_gen += 1;
+                *task_info = Some(create_task_info(executor_id.clone(), task_id));
+
+                let partition = PartitionId {
+                    job_id: job_id.clone(),
+                    stage_id: running_stage.stage_id,
+                    partition_id,
+                };
+                let task_desc = TaskDescription {
+                    session_id: session_id.clone(),
+                    partition,
+                    stage_attempt_num: running_stage.stage_attempt_num,
+                    task_id,
+                    task_attempt: running_stage.task_failure_numbers[partition_id],
+                    data_cache: false,
+                    plan: running_stage.plan.clone(),
+                    binding: TaskBinding::ResultZone,
+                    queue_wait_ms: running_stage.queue_wait_ms(partition_id),
+                };
+                schedulable_tasks.push((executor_id, task_desc));
+                slot.slots -= 1;
+            }
+        }
+    }
+
+    if !schedulable_tasks.is_empty() {
+        info!(
+            "{} final stage tasks bound to the zone of their client",
+            schedulable_tasks.len()
+        );
+    }
+    let remaining_jobs = if held_back_jobs.is_empty() {
+        active_jobs
+    } else {
+        Arc::new(
+            active_jobs
+                .iter()
+                .filter(|(job_id, _)| !held_back_jobs.contains(*job_id))
+                .map(|(job_id, job_info)| (job_id.clone(), job_info.clone()))
+                .collect(),
+        )
+    };
+    (schedulable_tasks, remaining_jobs)
+}
+
+/// Bind the tasks of stages with a [`PartitionPlacementExec`] to executors running on one of
+/// the preferred hosts of their partition. `executor_hosts` maps executor IDs to their host.
+///
+/// Tasks whose preferred hosts have no available slots are left to the task distribution policy.
+pub(crate) async fn bind_task_with_placement_hints(
+    slots: &mut [&mut AvailableTaskSlots],
+    executor_hosts: &HashMap<String, String>,
+    capable_executors: &CapableExecutors,
+    active_jobs: Arc<HashMap<String, JobInfoCache>>,
+) -> Vec<BoundTask> {
+    let mut schedulable_tasks: Vec<BoundTask> = vec![];
+
+    let mut total_slots = slots.iter().fold(0, |acc, s| acc + s.slots);
+    if total_slots == 0 {
+        return schedulable_tasks;
+    }
+
+    for (job_id, job_info) in active_jobs.iter() {
+        if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
+            continue;
+        }
+        let mut graph = job_info.execution_graph.write().await;
+        let session_id = graph.session_id().to_string();
+        let max_running_tasks = graph.max_running_stage_tasks();
+        let max_tasks_per_executor = graph.max_stage_tasks_per_executor();
+        let mut black_list = vec![];
+        while let Some((running_stage, task_id_gen)) =
+            graph.fetch_running_stage(&black_list)
+        {
+            // Each stage is visited once, whether or not all its tasks could be bound
+            black_list.push(running_stage.stage_id);
+            let preferred_hosts = if let Some(preferred_hosts) =
+                get_preferred_hosts(running_stage.plan.clone(), running_stage.partitions)
+            {
+                preferred_hosts
+            } else {
+                continue;
+            };
+            let mut executor_limit = running_stage
+                .executor_task_limit(max_tasks_per_executor)
+                .excluding(capable_executors.unable_to_run(running_stage.plan.as_ref()));
+            let max_tasks = running_stage
+                .schedulable_tasks(max_running_tasks.of(running_stage.stage_id));
+            let runnable_tasks = running_stage
+                .task_infos
+                .iter_mut()
+                .enumerate()
+                .filter(|(_partition, info)| info.is_none())
+                .take(max_tasks);
+            for (partition_id, task_info) in runnable_tasks {
+                // Choose the executor with the most available slots on the first preferred
+                // host which has any
+                let idx_slot = preferred_hosts[partition_id].iter().find_map(|host| {
+                    slots
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, slot)| {
+                            slot.slots > 0
+                                && executor_hosts.get(&slot.executor_id) == Some(host)
+                                && executor_limit.allows(&slot.executor_id)
+                        })
+                        .max_by_key(|(_, slot)| slot.slots)
+                        .map(|(idx_slot, _)| idx_slot)
+                });
+                let slot = if let Some(idx_slot) = idx_slot {
+                    &mut slots[idx_slot]
+                } else {
+                    continue;
+                };
+                let executor_id = slot.executor_id.clone();
+                executor_limit.bind(&executor_id);
+                let task_id = *task_id_gen;
+                *task_id_gen += 1;
+                *task_info = Some(create_task_info(executor_id.clone(), task_id));
+
+                let partition = PartitionId {
+                    job_id: job_id.clone(),
+                    stage_id: running_stage.stage_id,
+                    partition_id,
+                };
+                let task_desc = TaskDescription {
+                    session_id: session_id.clone(),
+                    partition,
+                    stage_attempt_num: running_stage.stage_attempt_num,
+                    task_id,
+                    task_attempt: running_stage.task_failure_numbers[partition_id],
+                    data_cache: false,
+                    plan: running_stage.plan.clone(),
+                    binding: TaskBinding::PlacementHint,
+                    queue_wait_ms: running_stage.queue_wait_ms(partition_id),
+                };
+                schedulable_tasks.push((executor_id, task_desc));
+
+                slot.slots -= 1;
+                total_slots -= 1;
+                if total_slots == 0 {
+                    return schedulable_tasks;
+                }
+            }
+        }
+    }
+
+    if !schedulable_tasks.is_empty() {
+        info!(
+            "{} tasks bound by partition placement hints",
+            schedulable_tasks.len()
+        );
+    }
+    schedulable_tasks
+}
+
+/// Bind the tasks of stages reading shuffled data to executors with the same value of the
+/// topology label `locality_label`, e.g. the same zone, as the executors holding most of the
+/// input partitions of the stage, so that shuffles stay local. `executor_labels` maps
+/// executor IDs to their value of the label.
+///
+/// Tasks for which no such executor has available slots are left to the task distribution
+/// policy.
+pub(crate) async fn bind_task_by_input_locality(
+    slots: &mut [&mut AvailableTaskSlots],
+    executor_labels: &HashMap<String, String>,
+    locality_label: &str,
+    capable_executors: &CapableExecutors,
+    active_jobs: Arc<HashMap<String, JobInfoCache>>,
+) -> Vec<BoundTask> {
+    let mut schedulable_tasks: Vec<BoundTask> = vec![];
+
+    let mut total_slots = slots.iter().fold(0, |acc, s| acc + s.slots);
+    if total_slots == 0 {
+        return schedulable_tasks;
+    }
+
+    for (job_id, job_info) in active_jobs.iter() {
+        if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
+            continue;
+        }
+        let mut graph = job_info.execution_graph.write().await;
+        let session_id = graph.session_id().to_string();
+        let max_running_tasks = graph.max_running_stage_tasks();
+        let max_tasks_per_executor = graph.max_stage_tasks_per_executor();
+        let mut black_list = vec![];
+        while let Some((running_stage, task_id_gen)) =
+            graph.fetch_running_stage(&black_list)
+        {
+            black_list.push(running_stage.stage_id);
+            let mut input_labels: HashMap<&str, usize> = HashMap::new();
+            for location in running_stage
+                .inputs
+                .values()
+                .flat_map(|output| output.partition_locations.values())
+                .flatten()
+            {
+                if let Some(value) = location.executor_meta.topology_label(locality_label)
+                {
+                    *input_labels.entry(value).or_default() += 1;
+                }
+            }
+            let preferred = match input_labels
+                .into_iter()
+                .max_by_key(|(value, locations)| (*locations, *value))
+            {
+                Some((value, _)) => value.to_string(),
+                None => continue,
+            };
+            let mut executor_limit = running_stage
+                .executor_task_limit(max_tasks_per_executor)
+                .excluding(capable_executors.unable_to_run(running_stage.plan.as_ref()));
+            let max_tasks = running_stage
+                .schedulable_tasks(max_running_tasks.of(running_stage.stage_id));
+            let runnable_tasks = running_stage
+                .task_infos
+                .iter_mut()
+                .enumerate()
+                .filter(|(_partition, info)| info.is_none())
+                .take(max_tasks);
+            for (partition_id, task_info) in runnable_tasks {
+                // Choose the executor with the preferred label with the most available slots
+                let slot = match slots
+                    .iter_mut()
+                    .filter(|slot| {
+                        slot.slots > 0
+                            && executor_labels.get(&slot.executor_id) == Some(&preferred)
+                            && executor_limit.allows(&slot.executor_id)
+                    })
+                    .max_by_key(|slot| slot.slots)
+                {
+                    Some(slot) => slot,
+                    None => break,
+                };
+                let executor_id = slot.executor_id.clone();
+                executor_limit.bind(&executor_id);
+                let task_id = *task_id_gen;
+                *task_id_gen += 1;
+                *task_info = Some(create_task_info(executor_id.clone(), task_id));
+
+                let partition = PartitionId {
+                    job_id: job_id.clone(),
+                    stage_id: running_stage.stage_id,
+                    partition_id,
+                };
+                let task_desc = TaskDescription {
+                    session_id: session_id.clone(),
+                    partition,
+                    stage_attempt_num: running_stage.stage_attempt_num,
+                    task_id,
+                    task_attempt: running_stage.task_failure_numbers[partition_id],
+                    data_cache: false,
+                    plan: running_stage.plan.clone(),
+                    binding: TaskBinding::InputLocality,
+                    queue_wait_ms: running_stage.queue_wait_ms(partition_id),
+                };
+                schedulable_tasks.push((executor_id, task_desc));
+
+                slot.slots -= 1;
+                total_slots -= 1;
+                if total_slots == 0 {
+                    return schedulable_tasks;
+                }
+            }
+        }
+    }
+
+    if !schedulable_tasks.is_empty() {
+        info!(
+            "{} tasks bound to executors close to their input by {}",
+            schedulable_tasks.len(),
+            locality_label
+        );
+    }
+    schedulable_tasks
+}
+
+/// Return the slots freed by completed tasks to the available slots of an executor. The slots
+/// in excess of the resized task slots of the executor are dropped instead.
+pub(crate) fn return_task_slots(slots: &mut AvailableTaskSlots, num_slots: u32) {
+    let dropped = num_slots.min(slots.excess_slots);
+    slots.excess_slots -= dropped;
+    slots.slots += num_slots - dropped;
+}
+
+/// Change the available slots of an executor whose task slots are resized from
+/// `previous_task_slots` to `task_slots`. The removed slots are taken from the available
+/// slots first, the rest being held by running tasks is recorded as excess slots.
+pub(crate) fn resize_task_slots(
+    slots: &mut AvailableTaskSlots,
+    previous_task_slots: u32,
+    task_slots: u32,
+) {
+    if task_slots >= previous_task_slots {
+        let added = task_slots - previous_task_slots;
+        // the slots still held in excess by running tasks are kept by them
+        let kept = added.min(slots.excess_slots);
+        slots.excess_slots -= kept;
+        slots.slots += added - kept;
+    } else {
+        let removed = previous_task_slots - task_slots;
+        let from_available = removed.min(slots.slots);
+        slots.slots -= from_available;
+        slots.excess_slots += removed - from_available;
+    }
+}
+
+/// Apply the task slots an executor was resized to when it registers again. The slots
+/// held by the tasks still running on the executor are not available.
+pub(crate) fn apply_resized_task_slots(
+    metadata: &mut ExecutorMetadata,
+    spec: &mut ExecutorData,
+    task_slots: u32,
+) {
+    let used_slots = spec
+        .total_task_slots
+        .saturating_sub(spec.available_task_slots);
+    metadata.specification.task_slots = task_slots;
+    spec.total_task_slots = task_slots;
+    spec.available_task_slots = task_slots.saturating_sub(used_slots);
+}
+
+/// Status of a job which is in the job queue, either waiting to be planned or being planned
+pub(crate) fn queued_job_status(
+    job_id: &str,
+    job_name: &str,
+    queued_at: u64,
+    planning_started_at: Option<u64>,
+) -> JobStatus {
+    let status = match planning_started_at {
+        Some(planning_started_at) => job_status::Status::Planning(PlanningJob {
+            queued_at,
+            planning_started_at,
+        }),
+        None => job_status::Status::Queued(QueuedJob {
+            queued_at,
+            estimated_start_delay_ms: 0,
+        }),
+    };
+
+    JobStatus {
+        job_id: job_id.to_string(),
+        job_name: job_name.to_string(),
+        status: Some(status),
+    }
+}
+
+/// Get the preferred executor hosts for every task of a stage with `partitions` partitions.
+///
+/// Only a stage with a single [`PartitionPlacementExec`], whose partitions are the ones
+/// of the stage, has placement hints.
+pub(crate) fn get_preferred_hosts(
+    plan: Arc<dyn ExecutionPlan>,
+    partitions: usize,
+) -> Option<Vec<Vec<String>>> {
+    let mut collector: Vec<Vec<Vec<String>>> = vec![];
+    plan.apply(&mut |plan| {
+        if let Some(exec) = plan.as_any().downcast_ref::<PartitionPlacementExec>() {
+            collector.push(exec.preferred_hosts().to_vec());
+            return Ok(TreeNodeRecursion::Jump);
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })
+    .ok()?;
+    match collector.pop() {
+        Some(preferred_hosts)
+            if collector.is_empty() && preferred_hosts.len() == partitions =>
+        {
+            Some(preferred_hosts)
+        }
+        _ => None,
+    }
+}
+
 pub(crate) async fn bind_task_bias(
     mut slots: Vec<&mut AvailableTaskSlots>,
+    capable_executors: &CapableExecutors,
     active_jobs: Arc<HashMap<String, JobInfoCache>>,
     if_skip: fn(Arc<dyn ExecutionPlan>) -> bool,
 ) -> Vec<BoundTask> {
@@ -395,7 +1203,6 @@ pub(crate) async fn bind_task_bias(
     slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
 
     let mut idx_slot = 0usize;
-    let mut slot = &mut slots[idx_slot];
     for (job_id, job_info) in active_jobs.iter() {
         if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
             debug!(
@@ -406,6 +1213,8 @@ pub(crate) async fn bind_task_bias(
         }
         let mut graph = job_info.execution_graph.write().await;
         let session_id = graph.session_id().to_string();
+        let max_running_tasks = graph.max_running_stage_tasks();
+        let max_tasks_per_executor = graph.max_stage_tasks_per_executor();
         let mut black_list = vec![];
         while let Some((running_stage, task_id_gen)) =
             graph.fetch_running_stage(&black_list)
@@ -418,25 +1227,42 @@ pub(crate) async fn bind_task_bias(
                 black_list.push(running_stage.stage_id);
                 continue;
             }
+            // The tasks left unbound by the limit of tasks per executor are not retried
+            if max_tasks_per_executor.is_some() {
+                black_list.push(running_stage.stage_id);
+            }
+            let mut executor_limit = running_stage
+                .executor_task_limit(max_tasks_per_executor)
+                .excluding(capable_executors.unable_to_run(running_stage.plan.as_ref()));
             // We are sure that it will at least bind one task by going through the following logic.
             // It will not go into a dead loop.
+            let max_tasks = running_stage
+                .schedulable_tasks(max_running_tasks.of(running_stage.stage_id));
             let runnable_tasks = running_stage
                 .task_infos
                 .iter_mut()
                 .enumerate()
                 .filter(|(_partition, info)| info.is_none())
+                .take(max_tasks)
                 .take(total_slots as usize)
                 .collect::<Vec<_>>();
             for (partition_id, task_info) in runnable_tasks {
-                // Assign [`slot`] with a slot available slot number larger than 0
-                while slot.slots == 0 {
+                // Assign [`idx_slot`] with a slot available slot number larger than 0
+                while slots[idx_slot].slots == 0 {
                     idx_slot += 1;
                     if idx_slot >= slots.len() {
                         return schedulable_tasks;
                     }
-                    slot = &mut slots[idx_slot];
                 }
+                // Skip the executors already running the max number of tasks of the stage
+                let slot = match slots[idx_slot..].iter_mut().find(|slot| {
+                    slot.slots > 0 && executor_limit.allows(&slot.executor_id)
+                }) {
+                    Some(slot) => slot,
+                    None => break,
+                };
                 let executor_id = slot.executor_id.clone();
+                executor_limit.bind(&executor_id);
                 let task_id = *task_id_gen;
                 *task_id_gen += 1;
                 *task_info = Some(create_task_info(executor_id.clone(), task_id));
@@ -454,6 +1280,8 @@ pub(crate) async fn bind_task_bias(
                     task_attempt: running_stage.task_failure_numbers[partition_id],
                     data_cache: false,
                     plan: running_stage.plan.clone(),
+                    binding: TaskBinding::Bias,
+                    queue_wait_ms: running_stage.queue_wait_ms(partition_id),
                 };
                 schedulable_tasks.push((executor_id, task_desc));
 
@@ -467,6 +1295,7 @@ pub(crate) async fn bind_task_bias(
 
 pub(crate) async fn bind_task_round_robin(
     mut slots: Vec<&mut AvailableTaskSlots>,
+    capable_executors: &CapableExecutors,
     active_jobs: Arc<HashMap<String, JobInfoCache>>,
     if_skip: fn(Arc<dyn ExecutionPlan>) -> bool,
 ) -> Vec<BoundTask> {
@@ -493,6 +1322,8 @@ pub(crate) async fn bind_task_round_robin(
         }
         let mut graph = job_info.execution_graph.write().await;
         let session_id = graph.session_id().to_string();
+        let max_running_tasks = graph.max_running_stage_tasks();
+        let max_tasks_per_executor = graph.max_stage_tasks_per_executor();
         let mut black_list = vec![];
         while let Some((running_stage, task_id_gen)) =
             graph.fetch_running_stage(&black_list)
@@ -505,13 +1336,23 @@ pub(crate) async fn bind_task_round_robin(
                 black_list.push(running_stage.stage_id);
                 continue;
             }
+            // The tasks left unbound by the limit of tasks per executor are not retried
+            if max_tasks_per_executor.is_some() {
+                black_list.push(running_stage.stage_id);
+            }
+            let mut executor_limit = running_stage
+                .executor_task_limit(max_tasks_per_executor)
+                .excluding(capable_executors.unable_to_run(running_stage.plan.as_ref()));
             // We are sure that it will at least bind one task by going through the following logic.
             // It will not go into a dead loop.
+            let max_tasks = running_stage
+                .schedulable_tasks(max_running_tasks.of(running_stage.stage_id));
             let runnable_tasks = running_stage
                 .task_infos
                 .iter_mut()
                 .enumerate()
                 .filter(|(_partition, info)| info.is_none())
+                .take(max_tasks)
                 .take(total_slots as usize)
                 .collect::<Vec<_>>();
             for (partition_id, task_info) in runnable_tasks {
@@ -522,10 +1363,21 @@ pub(crate) async fn bind_task_round_robin(
                 if slots[idx_slot].slots == 0 {
                     idx_slot = 0;
                 }
+                // Skip the executors already running the max number of tasks of the stage
+                if !executor_limit.allows(&slots[idx_slot].executor_id) {
+                    match (0..slots.len()).find(|idx| {
+                        slots[*idx].slots > 0
+                            && executor_limit.allows(&slots[*idx].executor_id)
+                    }) {
+                        Some(idx) => idx_slot = idx,
+                        None => break,
+                    }
+                }
                 // Since the slots is a vector with descending order, and the total available slots is larger than 0,
                 // we are sure the available slot number at idx_slot is larger than 1
                 let slot = &mut slots[idx_slot];
                 let executor_id = slot.executor_id.clone();
+                executor_limit.bind(&executor_id);
                 let task_id = *task_id_gen;
                 *task_id_gen += 1;
                 *task_info = Some(create_task_info(executor_id.clone(), task_id));
@@ -543,6 +1395,8 @@ pub(crate) async fn bind_task_round_robin(
                     task_attempt: running_stage.task_failure_numbers[partition_id],
                     data_cache: false,
                     plan: running_stage.plan.clone(),
+                    binding: TaskBinding::RoundRobin,
+                    queue_wait_ms: running_stage.queue_wait_ms(partition_id),
                 };
                 schedulable_tasks.push((executor_id, task_desc));
 
@@ -559,6 +1413,109 @@ pub(crate) async fn bind_task_round_robin(
     schedulable_tasks
 }
 
+/// Distribute the tasks over the executors in proportion to their available slots, every
+/// task going to the executor with the largest share of its available slots still free
+pub(crate) async fn bind_task_weighted(
+    slots: Vec<&mut AvailableTaskSlots>,
+    capable_executors: &CapableExecutors,
+    active_jobs: Arc<HashMap<String, JobInfoCache>>,
+) -> Vec<BoundTask> {
+    let mut schedulable_tasks: Vec<BoundTask> = vec![];
+
+    let mut total_slots = slots.iter().fold(0, |acc, s| acc + s.slots);
+    if total_slots == 0 {
+        warn!("Not enough available executor slots for task running!!!");
+        return schedulable_tasks;
+    }
+    info!("Total slot number is {}", total_slots);
+
+    // The available slots of every executor before binding, its weight
+    let mut slots: Vec<(u32, &mut AvailableTaskSlots)> =
+        slots.into_iter().map(|slot| (slot.slots, slot)).collect();
+
+    for (job_id, job_info) in active_jobs.iter() {
+        if !matches!(job_info.status, Some(job_status::Status::Running(_))) {
+            debug!(
+                "Job {} is not in running status and will be skipped",
+                job_id
+            );
+            continue;
+        }
+        let mut graph = job_info.execution_graph.write().await;
+        let session_id = graph.session_id().to_string();
+        let max_running_tasks = graph.max_running_stage_tasks();
+        let max_tasks_per_executor = graph.max_stage_tasks_per_executor();
+        let mut black_list = vec![];
+        while let Some((running_stage, task_id_gen)) =
+            graph.fetch_running_stage(&black_list)
+        {
+            // The tasks left unbound are bound in the next round
+            black_list.push(running_stage.stage_id);
+            let mut executor_limit = running_stage
+                .executor_task_limit(max_tasks_per_executor)
+                .excluding(capable_executors.unable_to_run(running_stage.plan.as_ref()));
+            let max_tasks = running_stage
+                .schedulable_tasks(max_running_tasks.of(running_stage.stage_id));
+            let runnable_tasks = running_stage
+                .task_infos
+                .iter_mut()
+                .enumerate()
+                .filter(|(_partition, info)| info.is_none())
+                .take(max_tasks)
+                .take(total_slots as usize)
+                .collect::<Vec<_>>();
+            for (partition_id, task_info) in runnable_tasks {
+                // Compare the free shares a.slots / a_weight and b.slots / b_weight, the
+                // executor with the most free slots first on a tie
+                let slot = slots
+                    .iter_mut()
+                    .filter(|(_, slot)| {
+                        slot.slots > 0 && executor_limit.allows(&slot.executor_id)
+                    })
+                    .max_by(|(a_weight, a), (b_weight, b)| {
+                        (a.slots as u64 * *b_weight as u64)
+                            .cmp(&(b.slots as u64 * *a_weight as u64))
+                            .then(a.slots.cmp(&b.slots))
+                    });
+                let Some((_, slot)) = slot else {
+                    break;
+                };
+                let executor_id = slot.executor_id.clone();
+                executor_limit.bind(&executor_id);
+                let task_id = *task_id_gen;
+                *task_id_gen += 1;
+                *task_info = Some(create_task_info(executor_id.clone(), task_id));
+
+                let partition = PartitionId {
+                    job_id: job_id.clone(),
+                    stage_id: running_stage.stage_id,
+                    partition_id,
+                };
+                let task_desc = TaskDescription {
+                    session_id: session_id.clone(),
+                    partition,
+                    stage_attempt_num: running_stage.stage_attempt_num,
+                    task_id,
+                    task_attempt: running_stage.task_failure_numbers[partition_id],
+                    data_cache: false,
+                    plan: running_stage.plan.clone(),
+                    binding: TaskBinding::Weighted,
+                    queue_wait_ms: running_stage.queue_wait_ms(partition_id),
+                };
+                schedulable_tasks.push((executor_id, task_desc));
+
+                slot.slots -= 1;
+                total_slots -= 1;
+                if total_slots == 0 {
+                    return schedulable_tasks;
+                }
+            }
+        }
+    }
+
+    schedulable_tasks
+}
+
 type GetScanFilesFunc = fn(
     &str,
     Arc<dyn ExecutionPlan>,
@@ -566,6 +1523,7 @@ type GetScanFilesFunc = fn(
 
 pub(crate) async fn bind_task_consistent_hash(
     topology_nodes: HashMap<String, TopologyNode>,
+    capable_executors: &CapableExecutors,
     num_replicas: usize,
     tolerance: usize,
     active_jobs: Arc<HashMap<String, JobInfoCache>>,
@@ -599,6 +1557,8 @@ pub(crate) async fn bind_task_consistent_hash(
         }
         let mut graph = job_info.execution_graph.write().await;
         let session_id = graph.session_id().to_string();
+        let max_running_tasks = graph.max_running_stage_tasks();
+        let max_tasks_per_executor = graph.max_stage_tasks_per_executor();
         let mut black_list = vec![];
         while let Some((running_stage, task_id_gen)) =
             graph.fetch_running_stage(&black_list)
@@ -613,16 +1573,22 @@ pub(crate) async fn bind_task_consistent_hash(
                 continue;
             }
             let pre_total_slots = total_slots;
+            let mut executor_limit = running_stage
+                .executor_task_limit(max_tasks_per_executor)
+                .excluding(capable_executors.unable_to_run(running_stage.plan.as_ref()));
             let scan_files = &scan_files[0];
             let tolerance_list = vec![0, tolerance];
             // First round with 0 tolerance consistent hashing policy
             // Second round with [`tolerance`] tolerance consistent hashing policy
             for tolerance in tolerance_list {
+                let max_tasks = running_stage
+                    .schedulable_tasks(max_running_tasks.of(running_stage.stage_id));
                 let runnable_tasks = running_stage
                     .task_infos
                     .iter_mut()
                     .enumerate()
                     .filter(|(_partition, info)| info.is_none())
+                    .take(max_tasks)
                     .take(total_slots)
                     .collect::<Vec<_>>();
                 for (partition_id, task_info) in runnable_tasks {
@@ -636,7 +1602,12 @@ pub(crate) async fn bind_task_consistent_hash(
                         file_for_hash.object_meta.location.as_ref().as_bytes(),
                         tolerance,
                     ) {
+                        // Leave the task unbound rather than moving it off its executor
+                        if !executor_limit.allows(&node.id) {
+                            continue;
+                        }
                         let executor_id = node.id.clone();
+                        executor_limit.bind(&executor_id);
                         let task_id = *task_id_gen;
                         *task_id_gen += 1;
                         *task_info = Some(create_task_info(executor_id.clone(), task_id));
@@ -656,6 +1627,8 @@ pub(crate) async fn bind_task_consistent_hash(
                                 [partition_id],
                             data_cache,
                             plan: running_stage.plan.clone(),
+                            binding: TaskBinding::ConsistentHash,
+                            queue_wait_ms: running_stage.queue_wait_ms(partition_id),
                         };
                         schedulable_tasks.push((executor_id, task_desc));
 
@@ -747,25 +1720,152 @@ impl consistent_hash::node::Node for TopologyNode {
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
     use std::sync::Arc;
 
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::common::Statistics;
+    use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
     use datafusion::datasource::listing::PartitionedFile;
+    use datafusion::datasource::object_store::ObjectStoreUrl;
+    use datafusion::datasource::physical_plan::{FileScanConfig, NdJsonExec};
+    use datafusion::physical_plan::empty::EmptyExec;
+    use datafusion::prelude::SessionConfig;
     use object_store::path::Path;
     use object_store::ObjectMeta;
 
+    use ballista_core::config::{BallistaConfig, BALLISTA_CLIENT_ZONE};
     use ballista_core::error::Result;
+    use ballista_core::execution_plans::PartitionPlacementExec;
+    use ballista_core::protocol::PROTOCOL_VERSION;
     use ballista_core::serde::protobuf::AvailableTaskSlots;
-    use ballista_core::serde::scheduler::{ExecutorMetadata, ExecutorSpecification};
+    use ballista_core::serde::scheduler::{
+        ExecutorCapabilities, ExecutorData, ExecutorMetadata, ExecutorSpecification,
+    };
 
+    use crate::cluster::memory::InMemoryClusterState;
     use crate::cluster::{
-        bind_task_bias, bind_task_consistent_hash, bind_task_round_robin, BoundTask,
-        TopologyNode,
+        bind_task_bias, bind_task_by_input_locality, bind_task_consistent_hash,
+        bind_task_round_robin, bind_task_to_result_zone, bind_task_weighted,
+        bind_task_with_placement_hints, resize_task_slots, return_task_slots, BoundTask,
+        CapableExecutors, ClusterState, TopologyNode,
     };
     use crate::state::execution_graph::ExecutionGraph;
     use crate::state::task_manager::JobInfoCache;
     use crate::test_utils::{mock_completed_task, test_aggregation_plan_with_job_id};
 
+    #[test]
+    fn test_resize_task_slots() {
+        // 3 of the 4 slots of the executor are held by running tasks
+        let mut slots = AvailableTaskSlots {
+            executor_id: "executor_1".to_string(),
+            slots: 1,
+            excess_slots: 0,
+        };
+
+        resize_task_slots(&mut slots, 4, 2);
+        assert_eq!((0, 1), (slots.slots, slots.excess_slots));
+        // the first completed task drops its slot, the others return theirs
+        return_task_slots(&mut slots, 1);
+        assert_eq!((0, 0), (slots.slots, slots.excess_slots));
+        return_task_slots(&mut slots, 2);
+        assert_eq!((2, 0), (slots.slots, slots.excess_slots));
+
+        // growing again keeps the excess slots held by the running tasks
+        let mut slots = AvailableTaskSlots {
+            executor_id: "executor_1".to_string(),
+            slots: 0,
+            excess_slots: 2,
+        };
+        resize_task_slots(&mut slots, 1, 4);
+        assert_eq!((1, 0), (slots.slots, slots.excess_slots));
+    }
+
+    #[tokio::test]
+    async fn test_resized_executor_registers_again() -> Result<()> {
+        let state = InMemoryClusterState::default();
+        let metadata = ExecutorMetadata {
+            id: "executor_1".to_string(),
+            host: "localhost".to_string(),
+            port: 50051,
+            grpc_port: 50052,
+            specification: ExecutorSpecification { task_slots: 4 },
+            zone: String::new(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: ExecutorCapabilities::default(),
+            protocol_version: PROTOCOL_VERSION,
+        };
+        let spec = ExecutorData {
+            executor_id: "executor_1".to_string(),
+            total_task_slots: 4,
+            available_task_slots: 4,
+        };
+        state
+            .register_executor(metadata.clone(), spec.clone())
+            .await?;
+        assert_eq!(4, state.resize_executor_task_slots("executor_1", 2).await?);
+
+        // the executor registers again with the task slots it was started with
+        state.register_executor(metadata, spec).await?;
+        let metadata = state.get_executor_metadata("executor_1").await?;
+        assert_eq!(2, metadata.specification.task_slots);
+        assert_eq!(Some(2), state.get_available_task_slots("executor_1").await?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_capable_executors() {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        let scan = NdJsonExec::new(
+            FileScanConfig {
+                object_store_url: ObjectStoreUrl::parse("s3://bucket").unwrap(),
+                statistics: Statistics::new_unknown(&schema),
+                file_schema: schema,
+                file_groups: vec![],
+                projection: None,
+                limit: None,
+                table_partition_cols: vec![],
+                output_ordering: vec![],
+            },
+            FileCompressionType::UNCOMPRESSED,
+        );
+        let executor =
+            |id: &str, schemes: &[&str], protocol_version: u32| ExecutorMetadata {
+                id: id.to_string(),
+                host: "localhost".to_string(),
+                port: 50051,
+                grpc_port: 50052,
+                specification: ExecutorSpecification { task_slots: 4 },
+                zone: String::new(),
+                rack: String::new(),
+                instance_type: String::new(),
+                capabilities: ExecutorCapabilities {
+                    object_store_schemes: schemes.iter().map(|s| s.to_string()).collect(),
+                    ..Default::default()
+                },
+                protocol_version,
+            };
+        let capable_executors = CapableExecutors::new([
+            executor("executor_1", &["file"], PROTOCOL_VERSION),
+            executor("executor_2", &["file", "s3"], PROTOCOL_VERSION),
+            // executors which do not advertise their capabilities can run any task
+            executor("executor_3", &["file"], 1),
+        ]);
+
+        let unable_to_run = capable_executors.unable_to_run(&scan);
+        assert_eq!(
+            ["executor_1".to_string()]
+                .into_iter()
+                .collect::<HashSet<_>>(),
+            unable_to_run
+        );
+        // the stages which read no object store run anywhere
+        let empty = EmptyExec::new(Arc::new(Schema::empty()));
+        assert!(capable_executors.unable_to_run(&empty).is_empty());
+    }
+
     #[tokio::test]
     async fn test_bind_task_bias() -> Result<()> {
         let num_partition = 8usize;
@@ -774,8 +1874,13 @@ mod test {
         let available_slots_ref: Vec<&mut AvailableTaskSlots> =
             available_slots.iter_mut().collect();
 
-        let bound_tasks =
-            bind_task_bias(available_slots_ref, Arc::new(active_jobs), |_| false).await;
+        let bound_tasks = bind_task_bias(
+            available_slots_ref,
+            &CapableExecutors::default(),
+            Arc::new(active_jobs),
+            |_| false,
+        )
+        .await;
         assert_eq!(9, bound_tasks.len());
 
         let result = get_result(bound_tasks);
@@ -819,6 +1924,49 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_bind_task_with_max_tasks_per_executor() -> Result<()> {
+        for round_robin in [false, true] {
+            let mut graph = mock_graph("job_b", 8, 7).await?;
+            graph.set_max_stage_tasks_per_executor(Some(2));
+            let active_jobs = Arc::new(HashMap::from([(
+                "job_b".to_string(),
+                JobInfoCache::new(graph, &SessionConfig::new()),
+            )]));
+            let mut available_slots = mock_available_slots();
+            let available_slots_ref: Vec<&mut AvailableTaskSlots> =
+                available_slots.iter_mut().collect();
+
+            let bound_tasks = if round_robin {
+                bind_task_round_robin(
+                    available_slots_ref,
+                    &CapableExecutors::default(),
+                    active_jobs,
+                    |_| false,
+                )
+                .await
+            } else {
+                bind_task_bias(
+                    available_slots_ref,
+                    &CapableExecutors::default(),
+                    active_jobs,
+                    |_| false,
+                )
+                .await
+            };
+
+            // Only 2 of the 7 pending tasks are bound to each of the 3 executors
+            assert_eq!(6, bound_tasks.len());
+            let mut executor_tasks: HashMap<String, usize> = HashMap::new();
+            for (executor_id, _) in bound_tasks {
+                *executor_tasks.entry(executor_id).or_default() += 1;
+            }
+            assert!(executor_tasks.values().all(|tasks| *tasks == 2));
+        }
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_bind_task_round_robin() -> Result<()> {
         let num_partition = 8usize;
@@ -827,9 +1975,13 @@ mod test {
         let available_slots_ref: Vec<&mut AvailableTaskSlots> =
             available_slots.iter_mut().collect();
 
-        let bound_tasks =
-            bind_task_round_robin(available_slots_ref, Arc::new(active_jobs), |_| false)
-                .await;
+        let bound_tasks = bind_task_round_robin(
+            available_slots_ref,
+            &CapableExecutors::default(),
+            Arc::new(active_jobs),
+            |_| false,
+        )
+        .await;
         assert_eq!(9, bound_tasks.len());
 
         let result = get_result(bound_tasks);
@@ -878,6 +2030,43 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_bind_task_weighted() -> Result<()> {
+        let num_partition = 8usize;
+        let active_jobs = mock_active_jobs(num_partition).await?;
+        let mut available_slots = mock_available_slots();
+        let available_slots_ref: Vec<&mut AvailableTaskSlots> =
+            available_slots.iter_mut().collect();
+
+        let bound_tasks = bind_task_weighted(
+            available_slots_ref,
+            &CapableExecutors::default(),
+            Arc::new(active_jobs),
+        )
+        .await;
+        assert_eq!(9, bound_tasks.len());
+
+        // The 9 tasks are spread in proportion to the 3, 5 and 7 available slots
+        let mut executor_tasks: HashMap<String, usize> = HashMap::new();
+        for (executor_id, _) in bound_tasks {
+            *executor_tasks.entry(executor_id).or_default() += 1;
+        }
+        let expected: HashMap<String, usize> = [
+            ("executor_1".to_string(), 2),
+            ("executor_2".to_string(), 3),
+            ("executor_3".to_string(), 4),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(expected, executor_tasks);
+        assert_eq!(
+            vec![1, 2, 3],
+            available_slots.iter().map(|s| s.slots).collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_bind_task_consistent_hash() -> Result<()> {
         let num_partition = 8usize;
@@ -891,6 +2080,7 @@ mod test {
         {
             let (bound_tasks, _) = bind_task_consistent_hash(
                 topology_nodes.clone(),
+                &CapableExecutors::default(),
                 num_replicas,
                 tolerance,
                 active_jobs.clone(),
@@ -904,6 +2094,7 @@ mod test {
         {
             let (bound_tasks, _) = bind_task_consistent_hash(
                 topology_nodes,
+                &CapableExecutors::default(),
                 num_replicas,
                 tolerance,
                 active_jobs,
@@ -946,6 +2137,7 @@ mod test {
         {
             let (bound_tasks, _) = bind_task_consistent_hash(
                 topology_nodes,
+                &CapableExecutors::default(),
                 num_replicas,
                 tolerance,
                 active_jobs,
@@ -992,6 +2184,210 @@ mod test {
         result
     }
 
+    #[tokio::test]
+    async fn test_bind_task_with_placement_hints() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let input = Arc::new(EmptyExec::new(schema).with_partitions(3));
+        let plan = Arc::new(PartitionPlacementExec::try_new(
+            input,
+            vec![
+                vec!["host_1".to_string()],
+                vec!["host_2".to_string(), "host_1".to_string()],
+                vec!["host_3".to_string()],
+            ],
+        )?);
+        let mut graph =
+            ExecutionGraph::new("localhost:50050", "job_a", "", "session", plan, 0)?;
+        graph.revive();
+        let mut active_jobs = HashMap::new();
+        active_jobs.insert(
+            "job_a".to_string(),
+            JobInfoCache::new(graph, &SessionConfig::new()),
+        );
+
+        let mut available_slots = mock_available_slots();
+        available_slots[1].slots = 0;
+        let executor_hosts: HashMap<String, String> = [
+            ("executor_1", "host_1"),
+            ("executor_2", "host_2"),
+            ("executor_3", "host_4"),
+        ]
+        .into_iter()
+        .map(|(executor_id, host)| (executor_id.to_string(), host.to_string()))
+        .collect();
+        let mut available_slots_ref: Vec<&mut AvailableTaskSlots> =
+            available_slots.iter_mut().collect();
+
+        let bound_tasks = bind_task_with_placement_hints(
+            &mut available_slots_ref,
+            &executor_hosts,
+            &CapableExecutors::default(),
+            Arc::new(active_jobs),
+        )
+        .await;
+
+        // The second partition falls back to its second preferred host, since the executor
+        // on host_2 has no available slots, while no executor runs on host_3
+        let bound_partitions: Vec<(String, usize)> = bound_tasks
+            .iter()
+            .map(|(executor_id, task)| (executor_id.clone(), task.partition.partition_id))
+            .collect();
+        assert_eq!(
+            bound_partitions,
+            vec![("executor_1".to_string(), 0), ("executor_1".to_string(), 1)]
+        );
+        assert_eq!(1, available_slots[0].slots);
+        assert_eq!(7, available_slots[2].slots);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_task_to_result_zone() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let plan = Arc::new(EmptyExec::new(schema).with_partitions(3));
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_CLIENT_ZONE, "zone_a")
+            .build()?;
+        let session_config = SessionConfig::new().with_extension(Arc::new(config));
+        let mut active_jobs = HashMap::new();
+        for job_id in ["job_a", "job_b"] {
+            let mut graph = ExecutionGraph::new(
+                "localhost:50050",
+                job_id,
+                "",
+                "session",
+                plan.clone(),
+                0,
+            )?;
+            graph.revive();
+            let session_config = if job_id == "job_a" {
+                session_config.clone()
+            } else {
+                SessionConfig::new()
+            };
+            active_jobs.insert(
+                job_id.to_string(),
+                JobInfoCache::new(graph, &session_config),
+            );
+        }
+
+        let mut available_slots = mock_available_slots();
+        available_slots[0].slots = 2;
+        let executor_zones: HashMap<String, String> = [
+            ("executor_1", "zone_a"),
+            ("executor_2", "zone_b"),
+            ("executor_3", "zone_b"),
+        ]
+        .into_iter()
+        .map(|(executor_id, zone)| (executor_id.to_string(), zone.to_string()))
+        .collect();
+        let mut available_slots_ref: Vec<&mut AvailableTaskSlots> =
+            available_slots.iter_mut().collect();
+
+        let job_a = active_jobs["job_a"].clone();
+        let (bound_tasks, remaining_jobs) = bind_task_to_result_zone(
+            &mut available_slots_ref,
+            &executor_zones,
+            &CapableExecutors::default(),
+            Arc::new(active_jobs),
+        )
+        .await;
+
+        // The third task of job_a waits for a slot in zone_a, and only job_b, which has no
+        // client zone, is left to the other task binding functions
+        assert_eq!(2, bound_tasks.len());
+        assert!(bound_tasks.iter().all(|(executor_id, task)| {
+            executor_id == "executor_1" && task.partition.job_id == "job_a"
+        }));
+        assert_eq!(0, available_slots[0].slots);
+        let remaining_job_ids: Vec<&String> = remaining_jobs.keys().collect();
+        assert_eq!(vec!["job_b"], remaining_job_ids);
+
+        // Past the max wait, the task left falls back to the executors of any zone
+        let active_jobs = Arc::new(
+            [("job_a".to_string(), job_a.clone())]
+                .into_iter()
+                .collect::<HashMap<_, _>>(),
+        );
+        {
+            let mut graph = job_a.execution_graph.write().await;
+            let (running_stage, _) = graph.fetch_running_stage(&[]).unwrap();
+            running_stage.running_since = 0;
+        }
+        let mut available_slots = mock_available_slots();
+        available_slots[0].slots = 0;
+        let mut available_slots_ref: Vec<&mut AvailableTaskSlots> =
+            available_slots.iter_mut().collect();
+        let (bound_tasks, remaining_jobs) = bind_task_to_result_zone(
+            &mut available_slots_ref,
+            &executor_zones,
+            &CapableExecutors::default(),
+            active_jobs,
+        )
+        .await;
+        assert!(bound_tasks.is_empty());
+        let remaining_job_ids: Vec<&String> = remaining_jobs.keys().collect();
+        assert_eq!(vec!["job_a"], remaining_job_ids);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bind_task_by_input_locality() -> Result<()> {
+        let mut graph = test_aggregation_plan_with_job_id(4, "job_a").await;
+        let executor = ExecutorMetadata {
+            id: "executor_0".to_string(),
+            host: "localhost".to_string(),
+            port: 50051,
+            grpc_port: 50052,
+            specification: ExecutorSpecification { task_slots: 32 },
+            zone: "zone_b".to_string(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: Default::default(),
+            protocol_version: PROTOCOL_VERSION,
+        };
+        // The first stage runs on an executor of zone_b
+        if let Some(task) = graph.pop_next_task(&executor.id)? {
+            let task_status = mock_completed_task(task, &executor.id);
+            graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+        graph.revive();
+        let mut active_jobs = HashMap::new();
+        active_jobs.insert(
+            "job_a".to_string(),
+            JobInfoCache::new(graph, &SessionConfig::new()),
+        );
+
+        let mut available_slots = mock_available_slots();
+        let executor_labels: HashMap<String, String> =
+            [("executor_1", "zone_a"), ("executor_2", "zone_b")]
+                .into_iter()
+                .map(|(executor_id, zone)| (executor_id.to_string(), zone.to_string()))
+                .collect();
+        let mut available_slots_ref: Vec<&mut AvailableTaskSlots> =
+            available_slots.iter_mut().collect();
+
+        let bound_tasks = bind_task_by_input_locality(
+            &mut available_slots_ref,
+            &executor_labels,
+            "zone",
+            &CapableExecutors::default(),
+            Arc::new(active_jobs),
+        )
+        .await;
+
+        // All the tasks of the second stage go to the executor of zone_b
+        assert_eq!(4, bound_tasks.len());
+        assert!(bound_tasks
+            .iter()
+            .all(|(executor_id, _)| executor_id == "executor_2"));
+        assert_eq!(1, available_slots[1].slots);
+
+        Ok(())
+    }
+
     async fn mock_active_jobs(
         num_partition: usize,
     ) -> Result<HashMap<String, JobInfoCache>> {
@@ -1000,8 +2396,14 @@ mod test {
         let graph_b = mock_graph("job_b", num_partition, 7).await?;
 
         let mut active_jobs = HashMap::new();
-        active_jobs.insert(graph_a.job_id().to_string(), JobInfoCache::new(graph_a));
-        active_jobs.insert(graph_b.job_id().to_string(), JobInfoCache::new(graph_b));
+        active_jobs.insert(
+            graph_a.job_id().to_string(),
+            JobInfoCache::new(graph_a, &SessionConfig::new()),
+        );
+        active_jobs.insert(
+            graph_b.job_id().to_string(),
+            JobInfoCache::new(graph_b, &SessionConfig::new()),
+        );
 
         Ok(active_jobs)
     }
@@ -1018,6 +2420,11 @@ mod test {
             port: 50051,
             grpc_port: 50052,
             specification: ExecutorSpecification { task_slots: 32 },
+            zone: String::new(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: Default::default(),
+            protocol_version: PROTOCOL_VERSION,
         };
 
         if let Some(task) = graph.pop_next_task(&executor.id)? {
@@ -1042,14 +2449,17 @@ mod test {
             AvailableTaskSlots {
                 executor_id: "executor_1".to_string(),
                 slots: 3,
+                excess_slots: 0,
             },
             AvailableTaskSlots {
                 executor_id: "executor_2".to_string(),
                 slots: 5,
+                excess_slots: 0,
             },
             AvailableTaskSlots {
                 executor_id: "executor_3".to_string(),
                 slots: 7,
+                excess_slots: 0,
             },
         ]
     }
@@ -0,0 +1,295 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Transparent compression of the values of a [`KeyValueStore`].
+//!
+//! The compressed values start with a marker followed by a zstd frame. Values without the
+//! marker, e.g. written before the compression of their keyspace was enabled or by older
+//! schedulers, are returned as they are, so that the compression can be enabled or disabled
+//! on a running cluster. Older schedulers cannot read the compressed values though.
+
+use std::collections::HashSet;
+use std::task::Poll;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use ballista_core::error::Result;
+use futures::{Stream, StreamExt};
+use log::error;
+
+use crate::cluster::storage::{
+    KeyValueStore, Keyspace, Lock, Operation, Watch, WatchEvent,
+};
+
+/// Marks the compressed values. Values encoded with protobuf never start with a zero byte.
+const MARKER: &[u8; 4] = b"\0zst";
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// A [`KeyValueStore`] compressing the values of some keyspaces with zstd, and decompressing
+/// the compressed values of all keyspaces
+#[derive(Clone)]
+pub struct CompressedStore<S: KeyValueStore> {
+    inner: S,
+    keyspaces: HashSet<Keyspace>,
+}
+
+impl<S: KeyValueStore> CompressedStore<S> {
+    /// Compress the values written to `keyspaces` of the `inner` store
+    pub fn new(inner: S, keyspaces: HashSet<Keyspace>) -> Self {
+        Self { inner, keyspaces }
+    }
+
+    /// Compress a value of `keyspace` if the compression of the keyspace is enabled and
+    /// makes it smaller
+    fn encode(&self, keyspace: &Keyspace, value: Vec<u8>) -> Result<Vec<u8>> {
+        if value.is_empty() || !self.keyspaces.contains(keyspace) {
+            return Ok(value);
+        }
+        let compressed = zstd::bulk::compress(&value, COMPRESSION_LEVEL)?;
+        if MARKER.len() + compressed.len() >= value.len() {
+            return Ok(value);
+        }
+        let mut encoded = Vec::with_capacity(MARKER.len() + compressed.len());
+        encoded.extend_from_slice(MARKER);
+        encoded.extend_from_slice(&compressed);
+        Ok(encoded)
+    }
+}
+
+/// Decompress a stored value, returning the values which were not compressed as they are
+fn decode(value: Vec<u8>) -> Result<Vec<u8>> {
+    match value.strip_prefix(MARKER) {
+        Some(compressed) => Ok(zstd::stream::decode_all(compressed)?),
+        None => Ok(value),
+    }
+}
+
+fn decode_pairs(pairs: Vec<(String, Vec<u8>)>) -> Result<Vec<(String, Vec<u8>)>> {
+    pairs
+        .into_iter()
+        .map(|(key, value)| Ok((key, decode(value)?)))
+        .collect()
+}
+
+#[async_trait]
+impl<S: KeyValueStore> KeyValueStore for CompressedStore<S> {
+    async fn get(&self, keyspace: Keyspace, key: &str) -> Result<Vec<u8>> {
+        decode(self.inner.get(keyspace, key).await?)
+    }
+
+    async fn get_from_prefix(
+        &self,
+        keyspace: Keyspace,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        decode_pairs(self.inner.get_from_prefix(keyspace, prefix).await?)
+    }
+
+    async fn scan(
+        &self,
+        keyspace: Keyspace,
+        limit: Option<usize>,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        decode_pairs(self.inner.scan(keyspace, limit).await?)
+    }
+
+    async fn scan_keys(&self, keyspace: Keyspace) -> Result<HashSet<String>> {
+        self.inner.scan_keys(keyspace).await
+    }
+
+    async fn put(&self, keyspace: Keyspace, key: String, value: Vec<u8>) -> Result<()> {
+        let value = self.encode(&keyspace, value)?;
+        self.inner.put(keyspace, key, value).await
+    }
+
+    async fn put_with_ttl(
+        &self,
+        keyspace: Keyspace,
+        key: String,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()> {
+        let value = self.encode(&keyspace, value)?;
+        self.inner.put_with_ttl(keyspace, key, value, ttl).await
+    }
+
+    async fn apply_txn(&self, ops: Vec<(Operation, Keyspace, String)>) -> Result<()> {
+        let ops = ops
+            .into_iter()
+            .map(|(op, keyspace, key)| {
+                let op = match op {
+                    Operation::Put(value) => {
+                        Operation::Put(self.encode(&keyspace, value)?)
+                    }
+                    Operation::PutWithTtl(value, ttl) => {
+                        Operation::PutWithTtl(self.encode(&keyspace, value)?, ttl)
+                    }
+                    Operation::PutWithSharedTtl(value, ttl, group) => {
+                        Operation::PutWithSharedTtl(
+                            self.encode(&keyspace, value)?,
+                            ttl,
+                            group,
+                        )
+                    }
+                    Operation::Delete => Operation::Delete,
+                };
+                Ok((op, keyspace, key))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.inner.apply_txn(ops).await
+    }
+
+    async fn acquire_locks(
+        &self,
+        ids: Vec<(Keyspace, &str)>,
+    ) -> Result<Vec<Box<dyn Lock>>> {
+        self.inner.acquire_locks(ids).await
+    }
+
+    /// The moved value is not recompressed, it stays readable whatever the compression of
+    /// the keyspaces
+    async fn mv(
+        &self,
+        from_keyspace: Keyspace,
+        to_keyspace: Keyspace,
+        key: &str,
+    ) -> Result<()> {
+        self.inner.mv(from_keyspace, to_keyspace, key).await
+    }
+
+    async fn lock(&self, keyspace: Keyspace, key: &str) -> Result<Box<dyn Lock>> {
+        self.inner.lock(keyspace, key).await
+    }
+
+    async fn watch(
+        &self,
+        keyspace: Keyspace,
+        prefix: String,
+    ) -> Result<Box<dyn Watch<Item = WatchEvent>>> {
+        let inner = self.inner.watch(keyspace, prefix).await?;
+        Ok(Box::new(CompressedWatch { inner }))
+    }
+
+    async fn delete(&self, keyspace: Keyspace, key: &str) -> Result<()> {
+        self.inner.delete(keyspace, key).await
+    }
+}
+
+/// Decompresses the values of the events of a watch
+struct CompressedWatch {
+    inner: Box<dyn Watch<Item = WatchEvent>>,
+}
+
+#[async_trait]
+impl Watch for CompressedWatch {
+    async fn cancel(&mut self) -> Result<()> {
+        self.inner.cancel().await
+    }
+}
+
+impl Stream for CompressedWatch {
+    type Item = WatchEvent;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let inner = &mut self.get_mut().inner;
+        loop {
+            match inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(WatchEvent::Put(key, value))) => match decode(value) {
+                    Ok(value) => return Poll::Ready(Some(WatchEvent::Put(key, value))),
+                    Err(e) => {
+                        error!("Failed to decompress the watched value of {key}: {e}")
+                    }
+                },
+                poll => return poll,
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sled"))]
+mod tests {
+    use std::collections::HashSet;
+
+    use futures::StreamExt;
+
+    use super::{decode, CompressedStore, MARKER};
+    use crate::cluster::storage::sled::SledClient;
+    use crate::cluster::storage::{KeyValueStore, Keyspace, Operation, WatchEvent};
+
+    #[tokio::test]
+    async fn test_compressed_store() -> Result<(), Box<dyn std::error::Error>> {
+        let sled = SledClient::try_new_temporary()?;
+        let store =
+            CompressedStore::new(sled.clone(), HashSet::from([Keyspace::Sessions]));
+        let value: Vec<u8> = (0..10_000u32).flat_map(|i| (i % 7).to_be_bytes()).collect();
+
+        let mut watch = store.watch(Keyspace::Sessions, "".to_owned()).await?;
+        store
+            .put(Keyspace::Sessions, "compressed".to_owned(), value.clone())
+            .await?;
+        store
+            .apply_txn(vec![
+                (
+                    Operation::Put(value.clone()),
+                    Keyspace::Slots,
+                    "plain".to_owned(),
+                ),
+                (
+                    Operation::Put(b"tiny".to_vec()),
+                    Keyspace::Sessions,
+                    "tiny".to_owned(),
+                ),
+            ])
+            .await?;
+
+        // only the values of the compressed keyspaces which shrink are compressed
+        let stored = sled.get(Keyspace::Sessions, "compressed").await?;
+        assert!(stored.starts_with(MARKER));
+        assert!(stored.len() < value.len());
+        assert_eq!(sled.get(Keyspace::Slots, "plain").await?, value);
+        assert_eq!(sled.get(Keyspace::Sessions, "tiny").await?, b"tiny");
+
+        assert_eq!(store.get(Keyspace::Sessions, "compressed").await?, value);
+        assert_eq!(store.get(Keyspace::Slots, "plain").await?, value);
+        assert_eq!(
+            store.scan(Keyspace::Sessions, None).await?,
+            vec![
+                ("/Sessions/compressed".to_owned(), value.clone()),
+                ("/Sessions/tiny".to_owned(), b"tiny".to_vec())
+            ]
+        );
+        assert_eq!(
+            watch.next().await,
+            Some(WatchEvent::Put(
+                "/Sessions/compressed".to_owned(),
+                value.clone()
+            ))
+        );
+
+        // the values compressed by a store are read once the compression is disabled
+        let uncompressed = CompressedStore::new(sled, HashSet::new());
+        assert_eq!(
+            uncompressed.get(Keyspace::Sessions, "compressed").await?,
+            value
+        );
+        assert_eq!(decode(b"plain".to_vec())?, b"plain");
+        Ok(())
+    }
+}
@@ -15,20 +15,23 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use std::sync::Arc;
 use std::task::Poll;
 
 use async_trait::async_trait;
 use ballista_core::error::{ballista_error, Result};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::cluster::storage::KeyValueStore;
 use etcd_client::{
-    GetOptions, LockOptions, LockResponse, Txn, TxnOp, WatchOptions, WatchStream, Watcher,
+    GetOptions, LockOptions, LockResponse, PutOptions, Txn, TxnOp, WatchOptions,
+    WatchStream, Watcher,
 };
 use futures::{Stream, StreamExt};
 use log::{debug, error, warn};
+use parking_lot::Mutex;
 
 use crate::cluster::storage::{Keyspace, Lock, Operation, Watch, WatchEvent};
 
@@ -37,11 +40,116 @@ use crate::cluster::storage::{Keyspace, Lock, Operation, Watch, WatchEvent};
 pub struct EtcdClient {
     namespace: String,
     etcd: etcd_client::Client,
+    /// The lease of every group of keys written with a time-to-live, kept alive when the
+    /// group is written again rather than granting a lease per write
+    leases: Arc<Mutex<LeaseCache>>,
+}
+
+/// A lease shared by the keys of a group written with the same time-to-live
+#[derive(Clone, Copy, Debug)]
+struct EtcdLease {
+    id: i64,
+    ttl: Duration,
+    /// When the lease expires unless kept alive, as seen by the scheduler
+    expires_at: Instant,
+}
+
+/// The leases of the groups of keys written with a time-to-live, forgotten once expired
+#[derive(Default)]
+struct LeaseCache {
+    leases: HashMap<String, EtcdLease>,
+}
+
+impl LeaseCache {
+    /// The lease of `group` with the time-to-live `ttl` which has not expired at `now`,
+    /// if any. The leases which expired are forgotten.
+    fn get(&mut self, group: &str, ttl: Duration, now: Instant) -> Option<i64> {
+        self.leases.retain(|_, lease| lease.expires_at > now);
+        self.leases
+            .get(group)
+            .filter(|lease| lease.ttl == ttl)
+            .map(|lease| lease.id)
+    }
+
+    /// Record that the lease of `group` was granted or kept alive at `now`
+    fn renewed(&mut self, group: &str, id: i64, ttl: Duration, now: Instant) {
+        let expires_at = now + ttl;
+        self.leases.insert(
+            group.to_owned(),
+            EtcdLease {
+                id,
+                ttl,
+                expires_at,
+            },
+        );
+    }
 }
 
 impl EtcdClient {
     pub fn new(namespace: String, etcd: etcd_client::Client) -> Self {
-        Self { namespace, etcd }
+        Self {
+            namespace,
+            etcd,
+            leases: Arc::new(Mutex::new(LeaseCache::default())),
+        }
+    }
+
+    /// The lease of the keys of `group` expiring after `ttl`, which is the lease of the
+    /// previous write of the group kept alive if it has not expired yet, or a new lease
+    /// otherwise
+    async fn lease(&self, group: &str, ttl: Duration) -> Result<i64> {
+        let now = Instant::now();
+        let current = self.leases.lock().get(group, ttl, now);
+        if let Some(lease_id) = current {
+            match self.keep_alive(lease_id).await {
+                Ok(true) => {
+                    self.leases.lock().renewed(group, lease_id, ttl, now);
+                    return Ok(lease_id);
+                }
+                Ok(false) => debug!("etcd lease {} of {} expired", lease_id, group),
+                Err(e) => {
+                    debug!("etcd lease {} of {} not kept alive: {}", lease_id, group, e)
+                }
+            }
+        }
+        let lease_id = self.grant_lease(ttl).await?;
+        self.leases.lock().renewed(group, lease_id, ttl, now);
+        Ok(lease_id)
+    }
+
+    /// Renew a lease for its time-to-live, returns false if it expired already
+    async fn keep_alive(
+        &self,
+        lease_id: i64,
+    ) -> std::result::Result<bool, etcd_client::Error> {
+        let (mut keeper, mut responses) = self
+            .etcd
+            .clone()
+            .lease_client()
+            .keep_alive(lease_id)
+            .await?;
+        keeper.keep_alive().await?;
+        Ok(responses
+            .message()
+            .await?
+            .map(|response| response.ttl() > 0)
+            .unwrap_or(false))
+    }
+
+    /// Grant a lease expiring after `ttl`, rounded up to whole seconds
+    async fn grant_lease(&self, ttl: Duration) -> Result<i64> {
+        let ttl_secs = ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0);
+        Ok(self
+            .etcd
+            .clone()
+            .lease_client()
+            .grant(ttl_secs.max(1) as i64, None)
+            .await
+            .map_err(|e| {
+                warn!("etcd lease failed: {}", e);
+                ballista_error("etcd lease failed")
+            })?
+            .id())
     }
 }
 
@@ -146,16 +254,39 @@ impl KeyValueStore for EtcdClient {
     async fn apply_txn(&self, ops: Vec<(Operation, Keyspace, String)>) -> Result<()> {
         let mut etcd = self.etcd.clone();
 
-        let txn_ops: Vec<TxnOp> = ops
-            .into_iter()
-            .map(|(operation, ks, key)| {
-                let key = format!("/{}/{:?}/{}", self.namespace, ks, key);
-                match operation {
-                    Operation::Put(value) => TxnOp::put(key, value, None),
-                    Operation::Delete => TxnOp::delete(key, None),
+        let mut txn_ops: Vec<TxnOp> = Vec::with_capacity(ops.len());
+        // the lease of every group, kept alive once per transaction
+        let mut txn_leases: HashMap<String, i64> = HashMap::new();
+        for (operation, ks, key) in ops {
+            let key = format!("/{}/{:?}/{}", self.namespace, ks, key);
+            let (value, ttl, group) = match operation {
+                Operation::Put(value) => {
+                    txn_ops.push(TxnOp::put(key, value, None));
+                    continue;
                 }
-            })
-            .collect();
+                Operation::Delete => {
+                    txn_ops.push(TxnOp::delete(key, None));
+                    continue;
+                }
+                Operation::PutWithTtl(value, ttl) => (value, ttl, key.clone()),
+                Operation::PutWithSharedTtl(value, ttl, group) => {
+                    (value, ttl, format!("/{}/{}", self.namespace, group))
+                }
+            };
+            let lease_id = match txn_leases.get(&group) {
+                Some(lease_id) => *lease_id,
+                None => {
+                    let lease_id = self.lease(&group, ttl).await?;
+                    txn_leases.insert(group, lease_id);
+                    lease_id
+                }
+            };
+            txn_ops.push(TxnOp::put(
+                key,
+                value,
+                Some(PutOptions::new().with_lease(lease_id)),
+            ));
+        }
 
         etcd.txn(Txn::new().and_then(txn_ops))
             .await
@@ -344,3 +475,33 @@ impl Lock for EtcdLockGuard {
         self.etcd.unlock(self.lock.key()).await.unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::LeaseCache;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_lease_cache() {
+        let mut cache = LeaseCache::default();
+        let ttl = Duration::from_secs(60);
+        let start = Instant::now();
+        assert_eq!(None, cache.get("job1", ttl, start));
+
+        // the keys of a group share its lease until it expires
+        cache.renewed("job1", 1, ttl, start);
+        cache.renewed("job2", 2, ttl, start + Duration::from_secs(30));
+        assert_eq!(
+            Some(1),
+            cache.get("job1", ttl, start + Duration::from_secs(10))
+        );
+        assert_eq!(None, cache.get("job1", Duration::from_secs(10), start));
+
+        // the expired leases are forgotten
+        assert_eq!(None, cache.get("job1", ttl, start + ttl));
+        assert_eq!(1, cache.leases.len());
+        assert_eq!(Some(2), cache.get("job2", ttl, start + ttl));
+        assert_eq!(None, cache.get("job2", ttl, start + 2 * ttl));
+        assert!(cache.leases.is_empty());
+    }
+}
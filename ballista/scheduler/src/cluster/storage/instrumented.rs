@@ -0,0 +1,283 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Latency and error metrics of the operations of a [`KeyValueStore`].
+//!
+//! Every operation is timed and recorded per keyspace with the metrics collector of the
+//! scheduler, and the operations slower than a threshold are logged with their key, to
+//! tell the slowness of the storage backend apart from the slowness of the scheduling.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ballista_core::error::Result;
+use log::warn;
+
+use crate::cluster::storage::{
+    KeyValueStore, Keyspace, Lock, Operation, Watch, WatchEvent,
+};
+use crate::metrics::SchedulerMetricsCollector;
+
+/// The keyspace label of the transactions spanning several keyspaces
+const MULTIPLE_KEYSPACES: &str = "Multiple";
+
+/// A [`KeyValueStore`] recording the latency and the failures of the operations of the
+/// `inner` store
+#[derive(Clone)]
+pub struct InstrumentedStore<S: KeyValueStore> {
+    inner: S,
+    metrics_collector: Arc<dyn SchedulerMetricsCollector>,
+    slow_operation_threshold: Option<Duration>,
+}
+
+impl<S: KeyValueStore> InstrumentedStore<S> {
+    /// Record the operations of the `inner` store with `metrics_collector`, logging a
+    /// warning for the operations taking longer than `slow_operation_threshold` if set
+    pub fn new(
+        inner: S,
+        metrics_collector: Arc<dyn SchedulerMetricsCollector>,
+        slow_operation_threshold: Option<Duration>,
+    ) -> Self {
+        Self {
+            inner,
+            metrics_collector,
+            slow_operation_threshold,
+        }
+    }
+
+    async fn instrument<T>(
+        &self,
+        keyspace: &str,
+        operation: &str,
+        key: &str,
+        future: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let start = Instant::now();
+        let result = future.await;
+        let latency = start.elapsed();
+        self.metrics_collector.record_state_operation(
+            keyspace,
+            operation,
+            latency,
+            result.is_err(),
+        );
+        match self.slow_operation_threshold {
+            Some(threshold) if latency > threshold => {
+                warn!("Slow {operation} of {keyspace} key {key:?} took {latency:?}");
+            }
+            _ => {}
+        }
+        result
+    }
+}
+
+/// The keyspace label of a transaction, [`MULTIPLE_KEYSPACES`] if it spans several
+fn txn_keyspace(ops: &[(Operation, Keyspace, String)]) -> String {
+    let keyspaces: HashSet<&Keyspace> =
+        ops.iter().map(|(_, keyspace, _)| keyspace).collect();
+    match keyspaces.into_iter().collect::<Vec<_>>().as_slice() {
+        [keyspace] => format!("{keyspace:?}"),
+        _ => MULTIPLE_KEYSPACES.to_owned(),
+    }
+}
+
+#[async_trait]
+impl<S: KeyValueStore> KeyValueStore for InstrumentedStore<S> {
+    async fn get(&self, keyspace: Keyspace, key: &str) -> Result<Vec<u8>> {
+        let label = format!("{keyspace:?}");
+        self.instrument(&label, "get", key, self.inner.get(keyspace, key))
+            .await
+    }
+
+    async fn get_from_prefix(
+        &self,
+        keyspace: Keyspace,
+        prefix: &str,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let label = format!("{keyspace:?}");
+        self.instrument(
+            &label,
+            "get_from_prefix",
+            prefix,
+            self.inner.get_from_prefix(keyspace, prefix),
+        )
+        .await
+    }
+
+    async fn scan(
+        &self,
+        keyspace: Keyspace,
+        limit: Option<usize>,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let label = format!("{keyspace:?}");
+        self.instrument(&label, "scan", "", self.inner.scan(keyspace, limit))
+            .await
+    }
+
+    async fn scan_keys(&self, keyspace: Keyspace) -> Result<HashSet<String>> {
+        let label = format!("{keyspace:?}");
+        self.instrument(&label, "scan_keys", "", self.inner.scan_keys(keyspace))
+            .await
+    }
+
+    async fn put(&self, keyspace: Keyspace, key: String, value: Vec<u8>) -> Result<()> {
+        let label = format!("{keyspace:?}");
+        let key_label = key.clone();
+        self.instrument(
+            &label,
+            "put",
+            &key_label,
+            self.inner.put(keyspace, key, value),
+        )
+        .await
+    }
+
+    async fn put_with_ttl(
+        &self,
+        keyspace: Keyspace,
+        key: String,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()> {
+        let label = format!("{keyspace:?}");
+        let key_label = key.clone();
+        self.instrument(
+            &label,
+            "put_with_ttl",
+            &key_label,
+            self.inner.put_with_ttl(keyspace, key, value, ttl),
+        )
+        .await
+    }
+
+    async fn apply_txn(&self, ops: Vec<(Operation, Keyspace, String)>) -> Result<()> {
+        let label = txn_keyspace(&ops);
+        let key = match ops.as_slice() {
+            [(_, _, key)] => key.clone(),
+            _ => format!("<{} keys>", ops.len()),
+        };
+        self.instrument(&label, "apply_txn", &key, self.inner.apply_txn(ops))
+            .await
+    }
+
+    // the locks are acquired one by one with `lock`, which records them
+
+    async fn mv(
+        &self,
+        from_keyspace: Keyspace,
+        to_keyspace: Keyspace,
+        key: &str,
+    ) -> Result<()> {
+        let label = format!("{from_keyspace:?}");
+        self.instrument(
+            &label,
+            "mv",
+            key,
+            self.inner.mv(from_keyspace, to_keyspace, key),
+        )
+        .await
+    }
+
+    async fn lock(&self, keyspace: Keyspace, key: &str) -> Result<Box<dyn Lock>> {
+        let label = format!("{keyspace:?}");
+        self.instrument(&label, "lock", key, self.inner.lock(keyspace, key))
+            .await
+    }
+
+    /// Only the creation of the watch is recorded, not the delivery of its events
+    async fn watch(
+        &self,
+        keyspace: Keyspace,
+        prefix: String,
+    ) -> Result<Box<dyn Watch<Item = WatchEvent>>> {
+        let label = format!("{keyspace:?}");
+        let prefix_label = prefix.clone();
+        self.instrument(
+            &label,
+            "watch",
+            &prefix_label,
+            self.inner.watch(keyspace, prefix),
+        )
+        .await
+    }
+
+    async fn delete(&self, keyspace: Keyspace, key: &str) -> Result<()> {
+        let label = format!("{keyspace:?}");
+        self.instrument(&label, "delete", key, self.inner.delete(keyspace, key))
+            .await
+    }
+}
+
+#[cfg(all(test, feature = "sled", feature = "prometheus"))]
+mod tests {
+    use std::sync::Arc;
+
+    use prometheus::Registry;
+
+    use super::InstrumentedStore;
+    use crate::cluster::storage::sled::SledClient;
+    use crate::cluster::storage::{KeyValueStore, Keyspace, Operation};
+    use crate::metrics::prometheus::PrometheusMetricsCollector;
+
+    #[tokio::test]
+    async fn test_instrumented_store() -> Result<(), Box<dyn std::error::Error>> {
+        let registry = Registry::new();
+        let collector = Arc::new(PrometheusMetricsCollector::new(&registry)?);
+        let store =
+            InstrumentedStore::new(SledClient::try_new_temporary()?, collector, None);
+
+        store
+            .put(Keyspace::Sessions, "session".to_owned(), b"value".to_vec())
+            .await?;
+        assert_eq!(store.get(Keyspace::Sessions, "session").await?, b"value");
+        store
+            .apply_txn(vec![
+                (Operation::Delete, Keyspace::Sessions, "session".to_owned()),
+                (Operation::Delete, Keyspace::Slots, "slots".to_owned()),
+            ])
+            .await?;
+
+        let latencies = registry
+            .gather()
+            .into_iter()
+            .find(|family| family.get_name() == "state_operation_latency_ms")
+            .unwrap();
+        let mut samples = latencies
+            .get_metric()
+            .iter()
+            .map(|metric| {
+                let mut labels: Vec<_> = metric
+                    .get_label()
+                    .iter()
+                    .map(|label| label.get_value().to_owned())
+                    .collect();
+                labels.push(metric.get_histogram().get_sample_count().to_string());
+                labels.join(",")
+            })
+            .collect::<Vec<_>>();
+        samples.sort();
+        // the labels are sorted by name, keyspace then operation
+        assert_eq!(
+            samples,
+            vec!["Multiple,apply_txn,1", "Sessions,get,1", "Sessions,put,1"]
+        );
+        Ok(())
+    }
+}
@@ -15,8 +15,10 @@
 // specific language governing permissions and limitations
 // under the License.
 
+pub mod compressed;
 #[cfg(feature = "etcd")]
 pub mod etcd;
+pub mod instrumented;
 #[cfg(feature = "sled")]
 pub mod sled;
 
@@ -24,6 +26,8 @@ use async_trait::async_trait;
 use ballista_core::error::Result;
 use futures::{future, Stream};
 use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Duration;
 use tokio::sync::OwnedMutexGuard;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -31,9 +35,22 @@ pub enum Keyspace {
     Executors,
     JobStatus,
     ExecutionGraph,
+    /// Chunks of the execution graphs too large to be stored in a single value
+    ExecutionGraphChunks,
+    /// Task status updates of the execution graphs since their last snapshot
+    ExecutionGraphDeltas,
     Slots,
     Sessions,
     Heartbeats,
+    SlotReservations,
+    /// Leases of the active schedulers
+    SchedulerLeases,
+    /// Jobs submitted with a client supplied idempotency key
+    IdempotencyKeys,
+    /// Optimized logical plans of the jobs, written once when they are submitted
+    JobPlans,
+    /// Task slots of the resized executors, kept when they register again
+    ResizedTaskSlots,
 }
 
 impl Keyspace {
@@ -42,9 +59,39 @@ impl Keyspace {
     }
 }
 
+impl FromStr for Keyspace {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "Executors" => Ok(Keyspace::Executors),
+            "JobStatus" => Ok(Keyspace::JobStatus),
+            "ExecutionGraph" => Ok(Keyspace::ExecutionGraph),
+            "ExecutionGraphChunks" => Ok(Keyspace::ExecutionGraphChunks),
+            "ExecutionGraphDeltas" => Ok(Keyspace::ExecutionGraphDeltas),
+            "Slots" => Ok(Keyspace::Slots),
+            "Sessions" => Ok(Keyspace::Sessions),
+            "Heartbeats" => Ok(Keyspace::Heartbeats),
+            "SlotReservations" => Ok(Keyspace::SlotReservations),
+            "SchedulerLeases" => Ok(Keyspace::SchedulerLeases),
+            "IdempotencyKeys" => Ok(Keyspace::IdempotencyKeys),
+            "JobPlans" => Ok(Keyspace::JobPlans),
+            "ResizedTaskSlots" => Ok(Keyspace::ResizedTaskSlots),
+            _ => Err(format!("Unknown keyspace {s}")),
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Hash)]
 pub enum Operation {
     Put(Vec<u8>),
+    /// Put a value which is deleted by the store once the time-to-live has elapsed
+    /// without the key being written again
+    PutWithTtl(Vec<u8>, Duration),
+    /// Put a value with a time-to-live shared by the values of the same group, e.g. the
+    /// values of a finished job, which the store may delete together once the
+    /// time-to-live has elapsed without any of them being written again
+    PutWithSharedTtl(Vec<u8>, Duration, String),
     Delete,
 }
 
@@ -78,6 +125,19 @@ pub trait KeyValueStore: Send + Sync + Clone + 'static {
     /// Saves the value into the provided key, overriding any previous data that might have been associated to that key.
     async fn put(&self, keyspace: Keyspace, key: String, value: Vec<u8>) -> Result<()>;
 
+    /// Saves the value into the provided key with a time-to-live. If the key is not written again
+    /// within `ttl`, it is deleted and watchers of the keyspace observe a [`WatchEvent::Delete`].
+    async fn put_with_ttl(
+        &self,
+        keyspace: Keyspace,
+        key: String,
+        value: Vec<u8>,
+        ttl: Duration,
+    ) -> Result<()> {
+        self.apply_txn(vec![(Operation::PutWithTtl(value, ttl), keyspace, key)])
+            .await
+    }
+
     /// Bundle multiple operation in a single transaction. Either all values should be saved, or all should fail.
     /// It can support multiple types of operations and keyspaces. If the count of the unique keyspace is more than one,
     /// more than one locks has to be acquired.
@@ -16,6 +16,7 @@
 // under the License.
 
 use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{sync::Arc, task::Poll};
 
 use ballista_core::error::{ballista_error, BallistaError, Result};
@@ -25,38 +26,148 @@ use async_trait::async_trait;
 use futures::{FutureExt, Stream};
 use log::warn;
 use sled_package as sled;
+use sled_package::transaction::TransactionResult;
+use sled_package::Transactional;
 use tokio::sync::Mutex;
 
 use crate::cluster::storage::{Keyspace, Lock, Operation, Watch, WatchEvent};
 
+/// Interval at which keys written with a time-to-live are checked for expiration
+const TTL_SWEEP_INTERVAL: Duration = Duration::from_millis(200);
+/// Name of the tree persisting the deadlines of the keys written with a time-to-live
+const DEADLINES_TREE: &str = "ttl_deadlines";
+
 /// A [`StateBackendClient`] implementation that uses file-based storage to save cluster state.
 #[derive(Clone)]
 pub struct SledClient {
     db: sled::Db,
+    /// Deadline of each key written with a time-to-live, in milliseconds since the epoch, so
+    /// that the keys still expire once the scheduler restarted
+    deadlines: sled::Tree,
     locks: Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>,
+    /// Sled has no native support for expiring keys, so time-to-live is emulated in memory
+    expirations: Arc<parking_lot::Mutex<Expirations>>,
+}
+
+#[derive(Default)]
+struct Expirations {
+    /// Deadline of each key written with a time-to-live
+    deadlines: HashMap<String, Instant>,
+    /// Whether a background task is currently sweeping expired keys
+    sweeping: bool,
 }
 
 impl SledClient {
     /// Creates a SledClient that saves data to the specified file.
     pub fn try_new<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
-        Ok(Self {
-            db: sled::open(path).map_err(sled_to_ballista_error)?,
-            locks: Arc::new(Mutex::new(HashMap::new())),
-        })
+        Self::from_db(sled::open(path).map_err(sled_to_ballista_error)?)
     }
 
     /// Creates a SledClient that saves data to a temp file.
     pub fn try_new_temporary() -> Result<Self> {
-        Ok(Self {
-            db: sled::Config::new()
+        Self::from_db(
+            sled::Config::new()
                 .temporary(true)
                 .open()
                 .map_err(sled_to_ballista_error)?,
+        )
+    }
+
+    /// Restore the deadlines of the keys written with a time-to-live before the database was
+    /// last closed, and sweep the expired keys if running within a tokio runtime
+    fn from_db(db: sled::Db) -> Result<Self> {
+        let deadlines = db
+            .open_tree(DEADLINES_TREE)
+            .map_err(sled_to_ballista_error)?;
+        let now = Instant::now();
+        let now_millis = epoch_millis();
+        let mut expirations = Expirations::default();
+        for entry in deadlines.iter() {
+            let (key, deadline) = entry.map_err(sled_to_ballista_error)?;
+            let (Ok(key), Ok(deadline)) = (
+                String::from_utf8(key.to_vec()),
+                <[u8; 8]>::try_from(deadline.as_ref()),
+            ) else {
+                continue;
+            };
+            let remaining = u64::from_be_bytes(deadline).saturating_sub(now_millis);
+            expirations
+                .deadlines
+                .insert(key, now + Duration::from_millis(remaining));
+        }
+
+        let client = Self {
+            db,
+            deadlines,
             locks: Arc::new(Mutex::new(HashMap::new())),
-        })
+            expirations: Arc::new(parking_lot::Mutex::new(expirations)),
+        };
+        if tokio::runtime::Handle::try_current().is_ok() {
+            client.ensure_sweeping(&mut client.expirations.lock());
+        }
+        Ok(client)
+    }
+
+    /// Clear the time-to-live of a key, if it has one
+    fn clear_deadline(&self, expirations: &mut Expirations, key: &str) {
+        if expirations.deadlines.remove(key).is_some() {
+            if let Err(e) = self.deadlines.remove(key) {
+                warn!("sled delete of the deadline of key {} failed: {:?}", key, e);
+            }
+        }
+    }
+
+    /// Start a background task deleting expired keys, unless one is already running.
+    /// The task exits once there are no more keys with a time-to-live.
+    fn ensure_sweeping(&self, expirations: &mut Expirations) {
+        if expirations.sweeping || expirations.deadlines.is_empty() {
+            return;
+        }
+        expirations.sweeping = true;
+
+        let db = self.db.clone();
+        let deadlines = self.deadlines.clone();
+        let shared = self.expirations.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(TTL_SWEEP_INTERVAL).await;
+
+                // Keep the lock while deleting so that a concurrent write of the same key
+                // can not be removed after it refreshed the deadline
+                let mut expirations = shared.lock();
+                let now = Instant::now();
+                let expired: Vec<String> = expirations
+                    .deadlines
+                    .iter()
+                    .filter(|(_, deadline)| **deadline <= now)
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                for key in expired {
+                    expirations.deadlines.remove(&key);
+                    if let Err(e) = db
+                        .remove(key.as_str())
+                        .and_then(|_| deadlines.remove(key.as_str()))
+                    {
+                        warn!("sled delete of expired key {} failed: {:?}", key, e);
+                    }
+                }
+
+                if expirations.deadlines.is_empty() {
+                    expirations.sweeping = false;
+                    return;
+                }
+            }
+        });
     }
 }
 
+fn epoch_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_millis() as u64
+}
+
 fn sled_to_ballista_error(e: sled::Error) -> BallistaError {
     match e {
         sled::Error::Io(io) => BallistaError::IoError(io),
@@ -155,6 +266,8 @@ impl KeyValueStore for SledClient {
 
     async fn put(&self, keyspace: Keyspace, key: String, value: Vec<u8>) -> Result<()> {
         let key = format!("/{keyspace:?}/{key}");
+        let mut expirations = self.expirations.lock();
+        self.clear_deadline(&mut expirations, &key);
         self.db
             .insert(key, value)
             .map_err(|e| {
@@ -166,19 +279,72 @@ impl KeyValueStore for SledClient {
 
     async fn apply_txn(&self, ops: Vec<(Operation, Keyspace, String)>) -> Result<()> {
         let mut batch = sled::Batch::default();
+        let mut deadlines: Vec<(String, Option<Duration>)> =
+            Vec::with_capacity(ops.len());
 
         for (op, keyspace, key_str) in ops {
             let key = format!("/{:?}/{}", &keyspace, key_str);
             match op {
-                Operation::Put(value) => batch.insert(key.as_str(), value),
-                Operation::Delete => batch.remove(key.as_str()),
+                Operation::Put(value) => {
+                    batch.insert(key.as_str(), value);
+                    deadlines.push((key, None));
+                }
+                // the values of a group are written at about the same time, so they
+                // expire together with a deadline each
+                Operation::PutWithTtl(value, ttl)
+                | Operation::PutWithSharedTtl(value, ttl, _) => {
+                    batch.insert(key.as_str(), value);
+                    deadlines.push((key, Some(ttl)));
+                }
+                Operation::Delete => {
+                    batch.remove(key.as_str());
+                    deadlines.push((key, None));
+                }
             }
         }
 
-        self.db.apply_batch(batch).map_err(|e| {
-            warn!("sled transaction insert failed: {}", e);
+        let mut expirations = self.expirations.lock();
+        let now = Instant::now();
+        let now_millis = epoch_millis();
+        let mut persisted = sled::Batch::default();
+        for (key, ttl) in &deadlines {
+            match ttl {
+                Some(ttl) => {
+                    let deadline = now_millis + ttl.as_millis() as u64;
+                    persisted.insert(key.as_str(), deadline.to_be_bytes().to_vec());
+                }
+                None if expirations.deadlines.contains_key(key) => {
+                    persisted.remove(key.as_str());
+                }
+                None => {}
+            }
+        }
+        // the values and their deadlines are written in the same transaction, so that a
+        // value never outlives its time-to-live once the database is opened again
+        let result: TransactionResult<()> =
+            (&*self.db, &self.deadlines).transaction(|(db, deadlines)| {
+                db.apply_batch(&batch)?;
+                deadlines.apply_batch(&persisted)?;
+                Ok(())
+            });
+        result.map_err(|e| {
+            warn!("sled transaction failed: {:?}", e);
             ballista_error("sled operations failed")
-        })
+        })?;
+
+        for (key, ttl) in deadlines {
+            match ttl {
+                Some(ttl) => {
+                    expirations.deadlines.insert(key, now + ttl);
+                }
+                None => {
+                    expirations.deadlines.remove(&key);
+                }
+            }
+        }
+        self.ensure_sweeping(&mut expirations);
+
+        Ok(())
     }
 
     async fn mv(
@@ -202,6 +368,10 @@ impl KeyValueStore for SledClient {
             batch.remove(from_key.as_str());
             batch.insert(to_key.as_str(), value);
 
+            let mut expirations = self.expirations.lock();
+            self.clear_deadline(&mut expirations, &from_key);
+            self.clear_deadline(&mut expirations, &to_key);
+
             self.db.apply_batch(batch).map_err(|e| {
                 warn!("sled transaction insert failed: {}", e);
                 ballista_error("sled insert failed")
@@ -235,6 +405,8 @@ impl KeyValueStore for SledClient {
 
     async fn delete(&self, keyspace: Keyspace, key: &str) -> Result<()> {
         let key = format!("/{keyspace:?}/{key}");
+        let mut expirations = self.expirations.lock();
+        self.clear_deadline(&mut expirations, &key);
         self.db.remove(key).map_err(|e| {
             warn!("sled delete failed: {:?}", e);
             ballista_error("sled delete failed")
@@ -288,6 +460,7 @@ mod tests {
 
     use futures::StreamExt;
     use std::result::Result;
+    use std::time::{Duration, Instant};
 
     fn create_instance() -> Result<SledClient, Box<dyn std::error::Error>> {
         Ok(SledClient::try_new_temporary()?)
@@ -392,4 +565,85 @@ mod tests {
         watch.cancel().await?;
         Ok(())
     }
+
+    #[tokio::test]
+    async fn put_with_ttl() -> Result<(), Box<dyn std::error::Error>> {
+        let client = create_instance()?;
+        let value = "value".as_bytes();
+        let mut watch: Box<dyn Watch<Item = WatchEvent>> =
+            client.watch(Keyspace::Heartbeats, "".to_owned()).await?;
+
+        client
+            .put_with_ttl(
+                Keyspace::Heartbeats,
+                "expiring".to_owned(),
+                value.to_vec(),
+                Duration::from_millis(100),
+            )
+            .await?;
+        client
+            .put_with_ttl(
+                Keyspace::Heartbeats,
+                "refreshed".to_owned(),
+                value.to_vec(),
+                Duration::from_millis(100),
+            )
+            .await?;
+        // A plain put clears the time-to-live
+        let value2 = "value2".as_bytes();
+        client
+            .put(
+                Keyspace::Heartbeats,
+                "refreshed".to_owned(),
+                value2.to_vec(),
+            )
+            .await?;
+
+        for _ in 0..3 {
+            assert!(matches!(watch.next().await, Some(WatchEvent::Put(_, _))));
+        }
+        assert_eq!(
+            watch.next().await,
+            Some(WatchEvent::Delete("/Heartbeats/expiring".to_owned()))
+        );
+        assert!(client
+            .get(Keyspace::Heartbeats, "expiring")
+            .await?
+            .is_empty());
+        assert_eq!(client.get(Keyspace::Heartbeats, "refreshed").await?, value2);
+        watch.cancel().await?;
+        Ok(())
+    }
+    #[tokio::test]
+    async fn restore_ttl() -> Result<(), Box<dyn std::error::Error>> {
+        let client = create_instance()?;
+        let value = "value".as_bytes();
+        for key in ["expiring", "cleared"] {
+            client
+                .put_with_ttl(
+                    Keyspace::JobStatus,
+                    key.to_owned(),
+                    value.to_vec(),
+                    Duration::from_secs(3600),
+                )
+                .await?;
+        }
+        client
+            .put(Keyspace::JobStatus, "cleared".to_owned(), value.to_vec())
+            .await?;
+        assert_eq!(client.deadlines.len(), 1);
+
+        // the deadlines are restored when the database is opened again
+        let restored = SledClient::from_db(client.db.clone())?;
+        let expirations = restored.expirations.lock();
+        let deadlines = &expirations.deadlines;
+        assert_eq!(
+            deadlines.keys().collect::<Vec<_>>(),
+            vec!["/JobStatus/expiring"]
+        );
+        assert!(
+            deadlines["/JobStatus/expiring"] > Instant::now() + Duration::from_secs(3500)
+        );
+        Ok(())
+    }
 }
@@ -18,9 +18,19 @@
 
 //! Ballista scheduler specific configuration
 
-use ballista_core::config::TaskSchedulingPolicy;
+use crate::cluster::storage::Keyspace;
+use crate::metrics::JobMetricsLabels;
+use crate::planner::{DefaultStagePlanner, StagePlanner};
+use crate::state::stage_alerts::StageAlertRule;
+use ballista_core::config::{BallistaConfig, TaskSchedulingPolicy};
+use ballista_core::plan_protection::PlanProtection;
+use ballista_core::serde::scheduler::TOPOLOGY_LABELS;
 use clap::ArgEnum;
+use datafusion::prelude::SessionConfig;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
 /// Configurations for the ballista scheduler of scheduling jobs and tasks
 #[derive(Debug, Clone)]
@@ -36,11 +46,18 @@ pub struct SchedulerConfig {
     pub scheduling_policy: TaskSchedulingPolicy,
     /// The event loop buffer size. for a system of high throughput, a larger value like 1000000 is recommended
     pub event_loop_buffer_size: u32,
+    /// The maximum number of events processed at once by the event loop. The events of a job are processed in
+    /// order, while the events of different jobs are processed concurrently.
+    pub event_loop_concurrency: u32,
+    /// The number of locks over which the state of the active jobs is sharded by job ID. Zero means four times
+    /// the number of cores.
+    pub job_cache_shards: u32,
     /// Policy of distributing tasks to available executor slots. For a cluster with single scheduler, round-robin is recommended
     pub task_distribution: TaskDistributionPolicy,
     /// The delayed interval for cleaning up finished job data, mainly the shuffle data, 0 means the cleaning up is disabled
     pub finished_job_data_clean_up_interval_seconds: u64,
     /// The delayed interval for cleaning up finished job state stored in the backend, 0 means the cleaning up is disabled.
+    /// The key-value backends write the finished job state with this time-to-live instead.
     pub finished_job_state_clean_up_interval_seconds: u64,
     /// The route endpoint for proxying flight sql results via scheduler
     pub advertise_flight_sql_endpoint: Option<String>,
@@ -62,6 +79,98 @@ pub struct SchedulerConfig {
     pub executor_timeout_seconds: u64,
     /// The interval to check expired or dead executors
     pub expire_dead_executor_interval_seconds: u64,
+    /// The interval (milliseconds) for flushing batched executor heartbeats to the cluster storage.
+    /// Heartbeats changing the executor status are always written through. Zero means disable batching.
+    pub executor_heartbeat_flush_interval_ms: u64,
+    /// Persist executor heartbeats with a lease of `executor_timeout_seconds` in the cluster storage, so that
+    /// dead executors are removed by the storage backend instead of by polling heartbeat timestamps.
+    pub executor_liveness_leases: bool,
+    /// The time in seconds after which task slots held by a scheduler which stopped renewing its reservation,
+    /// e.g. because it crashed, are reclaimed by other schedulers. Zero means disable.
+    pub slot_reservation_timeout_seconds: u64,
+    /// The maximum time in seconds spent processing the pending scheduler events when the scheduler
+    /// shuts down, before its active jobs are saved and released
+    pub shutdown_timeout_seconds: u64,
+    /// The time in seconds after which a scheduler which stopped renewing its lease is
+    /// considered failed, so that a promoted standby scheduler may take over its jobs.
+    /// Zero means disable.
+    pub scheduler_lease_timeout_seconds: u64,
+    /// Whether the scheduler starts as a standby, which rejects jobs and schedules no tasks but keeps
+    /// the state of the running jobs and the executors warm until it is promoted
+    pub standby: bool,
+    /// The time in milliseconds during which the task slots read from the cluster storage are served from
+    /// memory, the cache being invalidated by the watch stream of the storage, which also keeps the cached
+    /// executor metadata up to date. Zero means disable.
+    pub cluster_state_cache_ttl_ms: u64,
+    /// Compress the execution graphs saved in the cluster storage with zstd
+    pub execution_graph_compression: bool,
+    /// The maximum size in bytes of the values storing an execution graph in the cluster storage, larger
+    /// graphs are split in several values. Zero means no limit.
+    pub execution_graph_chunk_size: u64,
+    /// The number of task status updates of a job saved in the cluster storage as deltas of its execution
+    /// graph, after which the whole graph is saved again. Zero means the task status updates are not saved.
+    pub execution_graph_compaction_interval: u64,
+    /// The keyspaces of the cluster storage whose values are compressed with zstd. The compressed
+    /// values of all keyspaces are read, whether their keyspace is compressed or not.
+    pub compressed_keyspaces: HashSet<Keyspace>,
+    /// The latency in milliseconds above which the operations of the cluster storage are logged as
+    /// slow. Zero means slow operations are not logged.
+    pub state_slow_operation_ms: u64,
+    /// The number of threads planning the execution graphs of submitted jobs, which is also the maximum
+    /// number of jobs planned at once. Jobs waiting to be planned stay queued.
+    pub job_planning_concurrency: u32,
+    /// The number of seconds during which a job submitted with an idempotency key is returned to the
+    /// submissions with the same key. Zero means the idempotency keys are ignored.
+    pub job_idempotency_key_ttl_seconds: u64,
+    /// Topology labels, e.g. zone and rack, which executors must have to be bound tasks by this scheduler.
+    /// Executors which did not register one of the labels are not bound tasks. Empty means all executors.
+    pub executor_labels: HashMap<String, String>,
+    /// Topology label, e.g. zone, by which the tasks reading shuffled data prefer the executors with
+    /// the same label value as the executors which produced most of their input, if any
+    pub task_locality_label: Option<String>,
+    /// The maximum number of distinct jobs running tasks at once on an executor. Zero means no limit.
+    pub max_jobs_per_executor: u32,
+    /// The time in seconds after which the jobs are considered deadlocked when all the task slots are
+    /// held by tasks waiting for their inputs while other tasks wait for slots. Zero means disable.
+    pub slot_deadlock_timeout_seconds: u64,
+    /// The percentage of the cgroup memory limit of an executor, as reported in its heartbeats,
+    /// above which the executor is not bound new tasks. Zero means no limit.
+    pub executor_max_memory_percent: u32,
+    /// Policy of admitting submitted jobs by comparing their estimated peak task parallelism with
+    /// the task slots of the cluster
+    pub job_admission_policy: JobAdmissionPolicy,
+    /// Keys signing or encrypting the serialized plans sent to executors and stored in the cluster
+    /// storage. The executors must be configured with the same keys.
+    pub plan_protection: PlanProtection,
+    /// The maximum number of nodes of the logical plan of a submitted job. Zero means no limit.
+    pub max_job_plan_nodes: u64,
+    /// The maximum nesting depth of the messages of the encoded logical plan of a
+    /// submitted job, checked before the plan is decoded. Zero means no limit.
+    pub max_job_plan_depth: u64,
+    /// The maximum number of stages of a submitted job. Zero means no limit.
+    pub max_job_stages: u64,
+    /// The maximum number of tasks of a submitted job, summed over all its stages. Zero means no limit.
+    pub max_job_tasks: u64,
+    /// Labelling of the job metrics by job name. If not set, the job metrics are not labelled.
+    pub job_metrics_labels: Option<JobMetricsLabels>,
+    /// The dedicated HTTP server of the REST API, the metrics and the readiness endpoint. If not set,
+    /// they are served on the gRPC port.
+    pub http_server: Option<HttpServerConfig>,
+    /// The endpoint to which the OpenLineage run events of the jobs are posted, e.g.
+    /// `http://marquez:5000/api/v1/lineage`. If not set, the lineage of the jobs is not exported.
+    pub openlineage_url: Option<String>,
+    /// The OpenLineage namespace of the jobs, and of the tables read or written by the jobs which
+    /// are not files
+    pub openlineage_namespace: String,
+    /// Threshold rules on the metrics of the stages, raising a stage alert event and
+    /// incrementing the `stage_alert_total` counter when a stage exceeds them
+    pub stage_alert_rules: Vec<StageAlertRule>,
+    /// Splits the physical plans of the jobs into query stages
+    pub stage_planner: Arc<dyn StagePlanner>,
+    /// The object store prefix to which the finished jobs are archived before their state is
+    /// cleaned up from the backend, e.g. `s3://bucket/ballista/jobs`. The REST API serves the
+    /// archived jobs. If not set, the jobs are not archived.
+    pub job_archive_url: Option<String>,
 }
 
 impl Default for SchedulerConfig {
@@ -72,6 +181,8 @@ impl Default for SchedulerConfig {
             bind_port: 50050,
             scheduling_policy: TaskSchedulingPolicy::PullStaged,
             event_loop_buffer_size: 10000,
+            event_loop_concurrency: 1,
+            job_cache_shards: 0,
             task_distribution: TaskDistributionPolicy::Bias,
             finished_job_data_clean_up_interval_seconds: 300,
             finished_job_state_clean_up_interval_seconds: 3600,
@@ -84,6 +195,38 @@ impl Default for SchedulerConfig {
             grpc_server_max_encoding_message_size: 16777216,
             executor_timeout_seconds: 180,
             expire_dead_executor_interval_seconds: 15,
+            executor_heartbeat_flush_interval_ms: 0,
+            executor_liveness_leases: false,
+            slot_reservation_timeout_seconds: 0,
+            shutdown_timeout_seconds: 30,
+            scheduler_lease_timeout_seconds: 30,
+            standby: false,
+            cluster_state_cache_ttl_ms: 0,
+            execution_graph_compression: false,
+            execution_graph_chunk_size: 1048576,
+            execution_graph_compaction_interval: 100,
+            compressed_keyspaces: HashSet::new(),
+            state_slow_operation_ms: 1000,
+            job_planning_concurrency: 4,
+            job_idempotency_key_ttl_seconds: 600,
+            executor_labels: HashMap::new(),
+            task_locality_label: None,
+            max_jobs_per_executor: 0,
+            slot_deadlock_timeout_seconds: 60,
+            executor_max_memory_percent: 0,
+            job_admission_policy: JobAdmissionPolicy::Accept,
+            plan_protection: PlanProtection::default(),
+            max_job_plan_nodes: 0,
+            max_job_plan_depth: 0,
+            max_job_stages: 0,
+            max_job_tasks: 0,
+            job_metrics_labels: None,
+            http_server: None,
+            openlineage_url: None,
+            openlineage_namespace: "ballista".to_owned(),
+            stage_alert_rules: vec![],
+            stage_planner: Arc::new(DefaultStagePlanner),
+            job_archive_url: None,
         }
     }
 }
@@ -122,6 +265,16 @@ impl SchedulerConfig {
         self
     }
 
+    pub fn with_event_loop_concurrency(mut self, concurrency: u32) -> Self {
+        self.event_loop_concurrency = concurrency;
+        self
+    }
+
+    pub fn with_job_cache_shards(mut self, shards: u32) -> Self {
+        self.job_cache_shards = shards;
+        self
+    }
+
     pub fn with_finished_job_data_clean_up_interval_seconds(
         mut self,
         interval_seconds: u64,
@@ -156,6 +309,11 @@ impl SchedulerConfig {
         self
     }
 
+    pub fn with_job_planning_concurrency(mut self, concurrency: u32) -> Self {
+        self.job_planning_concurrency = concurrency;
+        self
+    }
+
     pub fn with_job_resubmit_interval_ms(mut self, interval_ms: u64) -> Self {
         self.job_resubmit_interval_ms = Some(interval_ms);
         self
@@ -175,6 +333,261 @@ impl SchedulerConfig {
         self.grpc_server_max_encoding_message_size = value;
         self
     }
+
+    pub fn with_executor_heartbeat_flush_interval_ms(mut self, interval_ms: u64) -> Self {
+        self.executor_heartbeat_flush_interval_ms = interval_ms;
+        self
+    }
+
+    pub fn with_executor_liveness_leases(mut self, enabled: bool) -> Self {
+        self.executor_liveness_leases = enabled;
+        self
+    }
+
+    pub fn with_slot_reservation_timeout_seconds(mut self, timeout_seconds: u64) -> Self {
+        self.slot_reservation_timeout_seconds = timeout_seconds;
+        self
+    }
+
+    pub fn with_shutdown_timeout_seconds(mut self, timeout_seconds: u64) -> Self {
+        self.shutdown_timeout_seconds = timeout_seconds;
+        self
+    }
+
+    pub fn with_scheduler_lease_timeout_seconds(mut self, timeout_seconds: u64) -> Self {
+        self.scheduler_lease_timeout_seconds = timeout_seconds;
+        self
+    }
+
+    pub fn with_standby(mut self, standby: bool) -> Self {
+        self.standby = standby;
+        self
+    }
+
+    pub fn with_cluster_state_cache_ttl_ms(mut self, ttl_ms: u64) -> Self {
+        self.cluster_state_cache_ttl_ms = ttl_ms;
+        self
+    }
+
+    pub fn with_execution_graph_compression(mut self, enabled: bool) -> Self {
+        self.execution_graph_compression = enabled;
+        self
+    }
+
+    pub fn with_execution_graph_chunk_size(mut self, chunk_size: u64) -> Self {
+        self.execution_graph_chunk_size = chunk_size;
+        self
+    }
+
+    pub fn with_execution_graph_compaction_interval(mut self, interval: u64) -> Self {
+        self.execution_graph_compaction_interval = interval;
+        self
+    }
+
+    pub fn with_compressed_keyspaces(mut self, keyspaces: HashSet<Keyspace>) -> Self {
+        self.compressed_keyspaces = keyspaces;
+        self
+    }
+
+    pub fn with_state_slow_operation_ms(mut self, threshold_ms: u64) -> Self {
+        self.state_slow_operation_ms = threshold_ms;
+        self
+    }
+
+    pub fn with_job_idempotency_key_ttl_seconds(mut self, ttl_seconds: u64) -> Self {
+        self.job_idempotency_key_ttl_seconds = ttl_seconds;
+        self
+    }
+
+    pub fn with_executor_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.executor_labels = labels;
+        self
+    }
+
+    pub fn with_task_locality_label(mut self, label: impl Into<String>) -> Self {
+        self.task_locality_label = Some(label.into());
+        self
+    }
+
+    pub fn with_max_jobs_per_executor(mut self, max_jobs: u32) -> Self {
+        self.max_jobs_per_executor = max_jobs;
+        self
+    }
+
+    pub fn with_slot_deadlock_timeout_seconds(mut self, timeout_seconds: u64) -> Self {
+        self.slot_deadlock_timeout_seconds = timeout_seconds;
+        self
+    }
+
+    pub fn with_executor_max_memory_percent(mut self, percent: u32) -> Self {
+        self.executor_max_memory_percent = percent;
+        self
+    }
+
+    pub fn with_job_admission_policy(mut self, policy: JobAdmissionPolicy) -> Self {
+        self.job_admission_policy = policy;
+        self
+    }
+
+    pub fn with_plan_protection(mut self, plan_protection: PlanProtection) -> Self {
+        self.plan_protection = plan_protection;
+        self
+    }
+
+    pub fn with_max_job_plan_nodes(mut self, max_plan_nodes: u64) -> Self {
+        self.max_job_plan_nodes = max_plan_nodes;
+        self
+    }
+
+    pub fn with_max_job_plan_depth(mut self, max_plan_depth: u64) -> Self {
+        self.max_job_plan_depth = max_plan_depth;
+        self
+    }
+
+    pub fn with_max_job_stages(mut self, max_stages: u64) -> Self {
+        self.max_job_stages = max_stages;
+        self
+    }
+
+    pub fn with_max_job_tasks(mut self, max_tasks: u64) -> Self {
+        self.max_job_tasks = max_tasks;
+        self
+    }
+
+    pub fn with_job_metrics_labels(mut self, labels: Option<JobMetricsLabels>) -> Self {
+        self.job_metrics_labels = labels;
+        self
+    }
+
+    pub fn with_http_server(mut self, http_server: Option<HttpServerConfig>) -> Self {
+        self.http_server = http_server;
+        self
+    }
+
+    pub fn with_openlineage(
+        mut self,
+        url: Option<String>,
+        namespace: impl Into<String>,
+    ) -> Self {
+        self.openlineage_url = url;
+        self.openlineage_namespace = namespace.into();
+        self
+    }
+
+    pub fn with_stage_alert_rules(mut self, rules: Vec<StageAlertRule>) -> Self {
+        self.stage_alert_rules = rules;
+        self
+    }
+
+    /// Split the physical plans of the jobs into query stages with a custom planner, e.g. to
+    /// fuse pairs of custom operators into a single stage
+    pub fn with_stage_planner(mut self, stage_planner: Arc<dyn StagePlanner>) -> Self {
+        self.stage_planner = stage_planner;
+        self
+    }
+
+    pub fn with_job_archive_url(mut self, url: Option<String>) -> Self {
+        self.job_archive_url = url;
+        self
+    }
+}
+
+/// Configuration of the dedicated HTTP server of the scheduler
+#[derive(Clone)]
+pub struct HttpServerConfig {
+    /// The address the server binds to
+    pub bind_address: SocketAddr,
+    /// Paths of the PEM encoded certificate and private key of the server, which uses plain
+    /// HTTP if not set
+    pub tls: Option<(String, String)>,
+    /// User and password which the requests must provide through HTTP basic authentication,
+    /// except on the readiness endpoint
+    pub basic_auth: Option<(String, String)>,
+}
+
+impl fmt::Debug for HttpServerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpServerConfig")
+            .field("bind_address", &self.bind_address)
+            .field("tls", &self.tls)
+            .field(
+                "basic_auth_user",
+                &self.basic_auth.as_ref().map(|(user, _)| user),
+            )
+            .finish()
+    }
+}
+
+/// Parse a comma separated list of topology labels of executors, e.g. `zone=us-east-1a,rack=r1`
+pub fn parse_executor_labels(
+    labels: &str,
+) -> std::result::Result<HashMap<String, String>, String> {
+    labels
+        .split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(|label| {
+            let (name, value) = label
+                .split_once('=')
+                .ok_or_else(|| format!("Expected name=value executor label: {label}"))?;
+            let name = parse_topology_label(name.trim())?;
+            Ok((name, value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Parse a comma separated list of keyspaces of the cluster storage, e.g. `Sessions,ExecutionGraphDeltas`
+pub fn parse_keyspaces(
+    keyspaces: &str,
+) -> std::result::Result<HashSet<Keyspace>, String> {
+    keyspaces
+        .split(',')
+        .map(str::trim)
+        .filter(|keyspace| !keyspace.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// Check that `name` is the name of a topology label of executors
+pub fn parse_topology_label(name: &str) -> std::result::Result<String, String> {
+    if TOPOLOGY_LABELS.contains(&name) {
+        Ok(name.to_string())
+    } else {
+        Err(format!(
+            "Unknown executor topology label {name}, expected one of {}",
+            TOPOLOGY_LABELS.join(", ")
+        ))
+    }
+}
+
+/// Policy of admitting submitted jobs, based on their estimated peak task parallelism and
+/// the total task slots of the executors which can be bound tasks
+///
+/// It needs to be visible to code generated by configure_me
+#[derive(Clone, ArgEnum, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum JobAdmissionPolicy {
+    /// Accept every job without checking the cluster capacity
+    Accept,
+    /// Accept every job, but log a warning when the cluster has no task slots or fewer task
+    /// slots than the peak parallelism of the job
+    Warn,
+    /// Fail the jobs which cannot make progress because the cluster has no task slots, instead
+    /// of leaving them queued, and warn like [`JobAdmissionPolicy::Warn`] otherwise
+    Reject,
+}
+
+impl std::str::FromStr for JobAdmissionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ArgEnum::from_str(s, true)
+    }
+}
+
+impl parse_arg::ParseArgFromStr for JobAdmissionPolicy {
+    fn describe_type<W: fmt::Write>(mut writer: W) -> fmt::Result {
+        write!(writer, "The job admission policy for the scheduler")
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -197,6 +610,9 @@ pub enum TaskDistribution {
     /// Distribute tasks evenly across executors. This will try and iterate through available executors
     /// and assign one task to each executor until all tasks are assigned.
     RoundRobin,
+    /// Distribute tasks across executors in proportion to their available slots, so that
+    /// the executors with more free slots get more of the tasks.
+    Weighted,
     /// 1. Firstly, try to bind tasks without scanning source files by [`RoundRobin`] policy.
     /// 2. Then for a task for scanning source files, firstly calculate a hash value based on input files.
     /// And then bind it with an execute according to consistent hashing policy.
@@ -218,7 +634,7 @@ impl parse_arg::ParseArgFromStr for TaskDistribution {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TaskDistributionPolicy {
     /// Eagerly assign tasks to executor slots. This will assign as many task slots per executor
     /// as are currently available
@@ -226,6 +642,9 @@ pub enum TaskDistributionPolicy {
     /// Distribute tasks evenly across executors. This will try and iterate through available executors
     /// and assign one task to each executor until all tasks are assigned.
     RoundRobin,
+    /// Distribute tasks across executors in proportion to their available slots, so that
+    /// the executors with more free slots get more of the tasks.
+    Weighted,
     /// 1. Firstly, try to bind tasks without scanning source files by [`RoundRobin`] policy.
     /// 2. Then for a task for scanning source files, firstly calculate a hash value based on input files.
     /// And then bind it with an execute according to consistent hashing policy.
@@ -235,3 +654,26 @@ pub enum TaskDistributionPolicy {
         tolerance: usize,
     },
 }
+
+impl TaskDistributionPolicy {
+    /// The policy requested for a job by its session with `ballista.task.distribution`,
+    /// which overrides the policy of the scheduler
+    pub fn from_session_config(
+        session_config: &SessionConfig,
+    ) -> std::result::Result<Option<Self>, String> {
+        let Some(policy) = session_config
+            .get_extension::<BallistaConfig>()
+            .and_then(|config| config.task_distribution())
+        else {
+            return Ok(None);
+        };
+        match policy.parse::<TaskDistribution>()? {
+            TaskDistribution::Bias => Ok(Some(Self::Bias)),
+            TaskDistribution::RoundRobin => Ok(Some(Self::RoundRobin)),
+            TaskDistribution::Weighted => Ok(Some(Self::Weighted)),
+            TaskDistribution::ConsistentHash => Err(format!(
+                "The {policy} task distribution can only be the policy of the scheduler"
+            )),
+        }
+    }
+}
@@ -46,14 +46,20 @@ use std::sync::Arc;
 use std::time::Duration;
 use tonic::{Request, Response, Status, Streaming};
 
+use crate::scheduler_server::admin_statement::admin_statement_plan;
 use crate::scheduler_server::SchedulerServer;
+use crate::state::session_manager::with_collect_stage;
 use arrow_flight::flight_service_client::FlightServiceClient;
 use arrow_flight::sql::ProstMessageExt;
 use arrow_flight::utils::batches_to_flight_data;
 use arrow_flight::SchemaAsIpc;
+use ballista_core::admin_statement::AdminStatement;
+use ballista_core::client::SESSION_ID_METADATA_KEY;
 use ballista_core::config::BallistaConfig;
 use ballista_core::serde::protobuf;
-use ballista_core::serde::protobuf::action::ActionType::FetchPartition;
+use ballista_core::serde::protobuf::action::ActionType::{
+    FetchPartition, FetchPartitions,
+};
 use ballista_core::serde::protobuf::job_status;
 use ballista_core::serde::protobuf::JobStatus;
 use ballista_core::serde::protobuf::SuccessfulJob;
@@ -85,6 +91,23 @@ pub struct FlightSqlServiceImpl {
 
 const TABLE_TYPES: [&str; 2] = ["TABLE", "VIEW"];
 
+/// Whether a fetch reads the output partition at `location`
+fn is_output_location(
+    location: &protobuf::PartitionLocation,
+    fetch: &protobuf::FetchPartition,
+) -> bool {
+    let same_partition = location.partition_id.as_ref().is_some_and(|id| {
+        id.job_id == fetch.job_id
+            && id.stage_id == fetch.stage_id
+            && id.partition_id == fetch.partition_id
+    });
+    let same_executor = location
+        .executor_meta
+        .as_ref()
+        .is_some_and(|meta| meta.host == fetch.host && meta.port == fetch.port);
+    same_partition && same_executor && location.path == fetch.path
+}
+
 impl FlightSqlServiceImpl {
     pub fn new(server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode>) -> Self {
         Self {
@@ -189,9 +212,15 @@ impl FlightSqlServiceImpl {
     }
 
     async fn prepare_statement(
+        &self,
         query: &str,
         ctx: &Arc<SessionContext>,
     ) -> Result<LogicalPlan, Status> {
+        if let Some(statement) = AdminStatement::parse(query) {
+            // the statement is executed along with the plan, see `execute_plan`
+            return admin_statement_plan(statement)
+                .map_err(|e| Status::internal(format!("Error building plan: {e}")));
+        }
         let plan = ctx
             .sql(query)
             .await
@@ -230,6 +259,7 @@ impl FlightSqlServiceImpl {
         };
         match status {
             job_status::Status::Queued(_) => Ok(None),
+            job_status::Status::Planning(_) => Ok(None),
             job_status::Status::Running(_) => Ok(None),
             job_status::Status::Failed(e) => {
                 warn!("Error executing plan: {:?}", e);
@@ -248,6 +278,13 @@ impl FlightSqlServiceImpl {
         num_rows: &mut i64,
         num_bytes: &mut i64,
     ) -> Result<Vec<FlightEndpoint>, Status> {
+        if !completed.collect_plan.is_empty() {
+            Err(Status::internal(
+                "The final stage of the job was detached for the client to run, which \
+                Flight SQL clients cannot do"
+                    .to_string(),
+            ))?
+        }
         let mut fieps: Vec<_> = vec![];
         for loc in completed.partition_location.iter() {
             let (exec_host, exec_port) = if let Some(ref md) = loc.executor_meta {
@@ -312,6 +349,100 @@ impl FlightSqlServiceImpl {
         Ok(fieps)
     }
 
+    /// The session of the caller of a proxied fetch: the session of the FlightSQL context
+    /// of the request, or the session sent by Ballista clients without such a context. A
+    /// caller with a FlightSQL context can not claim the session of another context.
+    fn fetch_session<T>(&self, request: &Request<T>) -> Result<String, Status> {
+        let session_id = request
+            .metadata()
+            .get(SESSION_ID_METADATA_KEY)
+            .map(|session_id| {
+                session_id
+                    .to_str()
+                    .map(|session_id| session_id.to_owned())
+                    .map_err(|e| {
+                        Status::invalid_argument(format!("Invalid session ID: {e}"))
+                    })
+            })
+            .transpose()?;
+        if !request.metadata().contains_key("authorization") {
+            return session_id.ok_or_else(|| {
+                Status::unauthenticated("No session ID nor authorization header")
+            });
+        }
+        let context_session_id = self.get_ctx(request)?.session_id();
+        match session_id {
+            Some(session_id) if session_id != context_session_id => {
+                Err(Status::permission_denied(format!(
+                    "The session {session_id} is not the session of the FlightSQL context"
+                )))
+            }
+            _ => Ok(context_session_id),
+        }
+    }
+
+    /// Check that a proxied fetch reads an output partition of a successful job of the
+    /// session of the caller, at the location reported by the job on an executor which
+    /// is registered and alive, so that the scheduler does not relay the requests to
+    /// arbitrary hosts or files, nor the results of the jobs of other sessions
+    async fn authorize_fetch(
+        &self,
+        fetch: &protobuf::FetchPartition,
+        session_id: &str,
+    ) -> Result<(), Status> {
+        let denied = |reason: String| {
+            warn!(
+                "Denied proxying the fetch of {} for job {} from {}:{}: {reason}",
+                fetch.path, fetch.job_id, fetch.host, fetch.port
+            );
+            Status::permission_denied(format!(
+                "The partition {} of job {} can not be fetched: {reason}",
+                fetch.partition_id, fetch.job_id
+            ))
+        };
+        let graph = self
+            .server
+            .state
+            .task_manager
+            .get_job_execution_graph(&fetch.job_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "Error getting the execution graph of job {}: {e:?}",
+                    fetch.job_id
+                ))
+            })?
+            .ok_or_else(|| denied("unknown job".to_owned()))?;
+        if graph.session_id() != session_id {
+            return Err(denied(format!(
+                "the job is not a job of session {session_id}"
+            )));
+        }
+        let executor_id = match &graph.status().status {
+            Some(job_status::Status::Successful(successful)) => successful
+                .partition_location
+                .iter()
+                .find(|location| is_output_location(location, fetch))
+                .and_then(|location| location.executor_meta.as_ref())
+                .map(|meta| meta.id.clone()),
+            _ => None,
+        }
+        .ok_or_else(|| denied("not an output partition of the job".to_owned()))?;
+
+        let executor_manager = &self.server.state.executor_manager;
+        let registered = executor_manager
+            .get_executor_metadata(&executor_id)
+            .await
+            .is_ok_and(|meta| meta.host == fetch.host && meta.port as u32 == fetch.port);
+        if !registered || executor_manager.is_dead_executor(&executor_id) {
+            return Err(denied(format!(
+                "the executor {executor_id} is not registered at {}:{}",
+                fetch.host, fetch.port
+            )));
+        }
+        Ok(())
+    }
+
     fn make_local_fieps(&self, job_id: &str) -> Result<Vec<FlightEndpoint>, Status> {
         let (host, port) = ("127.0.0.1".to_string(), 50050); // TODO: use advertise host
         let fetch = protobuf::FetchPartition {
@@ -381,6 +512,8 @@ impl FlightSqlServiceImpl {
     ) -> Result<String, Status> {
         let job_id = self.server.state.task_manager.generate_job_id();
         let job_name = format!("Flight SQL job {job_id}");
+        // Flight SQL clients only fetch the output partitions of the jobs
+        let ctx = with_collect_stage(ctx, false);
         self.server
             .submit_job(&job_id, &job_name, ctx, plan)
             .await
@@ -420,7 +553,12 @@ impl FlightSqlServiceImpl {
         ctx: Arc<SessionContext>,
         plan: &LogicalPlan,
     ) -> Result<Response<FlightInfo>, Status> {
-        let job_id = self.enqueue_job(ctx, plan).await?;
+        let admin_plan = self
+            .server
+            .execute_admin_statement(plan.clone())
+            .await
+            .map_err(|e| Status::internal(format!("Error executing statement: {e}")))?;
+        let job_id = self.enqueue_job(ctx, &admin_plan).await?;
 
         // poll for job completion
         let mut num_rows = 0;
@@ -547,7 +685,6 @@ impl FlightSqlService for FlightSqlServiceImpl {
         message: arrow_flight::sql::Any,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
         debug!("do_get_fallback type_url: {}", message.type_url);
-        let ctx = self.get_ctx(&request)?;
         if !message.is::<protobuf::Action>() {
             Err(Status::unimplemented(format!(
                 "do_get: The defined request is invalid: {}",
@@ -561,6 +698,9 @@ impl FlightSqlService for FlightSqlServiceImpl {
             .ok_or_else(|| Status::internal("Expected an Action but got None!"))?;
         let fp = match &action.action_type {
             Some(FetchPartition(fp)) => fp.clone(),
+            Some(FetchPartitions(_)) => Err(Status::unimplemented(
+                "do_get: Merged partition fetches are not proxied by the scheduler",
+            ))?,
             None => Err(Status::internal("Expected an ActionType but got None!"))?,
         };
 
@@ -576,6 +716,7 @@ impl FlightSqlService for FlightSqlServiceImpl {
             }
             "get_flight_info_tables" => {
                 debug!("Responding with tables");
+                let ctx = self.get_ctx(&request)?;
                 let rb = self
                     .tables(ctx)
                     .map_err(|_| Status::internal("Error getting tables".to_string()))?;
@@ -585,7 +726,10 @@ impl FlightSqlService for FlightSqlServiceImpl {
             _ => {}
         }
 
-        // Proxy the flight
+        // Proxy the flight, which needs no FlightSQL session so that Ballista clients can
+        // fetch job results through the scheduler
+        let session_id = self.fetch_session(&request)?;
+        self.authorize_fetch(&fp, &session_id).await?;
         let addr = format!("http://{}:{}", fp.host, fp.port);
         debug!("Scheduler proxying flight for to {}", addr);
         let connection =
@@ -626,7 +770,7 @@ impl FlightSqlService for FlightSqlServiceImpl {
         debug!("get_flight_info_statement query:\n{}", query.query);
 
         let ctx = self.get_ctx(&request)?;
-        let plan = Self::prepare_statement(&query.query, &ctx).await?;
+        let plan = self.prepare_statement(&query.query, &ctx).await?;
         let resp = self.execute_plan(ctx, &plan).await?;
 
         debug!("Returning flight info...");
@@ -889,7 +1033,7 @@ impl FlightSqlService for FlightSqlServiceImpl {
     ) -> Result<ActionCreatePreparedStatementResult, Status> {
         debug!("do_action_create_prepared_statement");
         let ctx = self.get_ctx(&request)?;
-        let plan = Self::prepare_statement(&query.query, &ctx).await?;
+        let plan = self.prepare_statement(&query.query, &ctx).await?;
         let schema_bytes = self.df_schema_to_arrow(plan.schema())?;
         let handle = self.cache_plan(plan)?;
         debug!("Prepared statement {}:\n{}", handle, query.query);
@@ -1006,3 +1150,111 @@ impl FlightSqlService for FlightSqlServiceImpl {
     /// Register a new SqlInfo result, making it available when calling GetSqlInfo.
     async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SchedulerConfig;
+    use crate::metrics::default_metrics_collector;
+    use crate::test_utils::test_cluster_context;
+    use ballista_core::serde::BallistaCodec;
+
+    #[test]
+    fn test_is_output_location() {
+        let location = protobuf::PartitionLocation {
+            partition_id: Some(protobuf::PartitionId {
+                job_id: "job".to_owned(),
+                stage_id: 2,
+                partition_id: 3,
+            }),
+            executor_meta: Some(protobuf::ExecutorMetadata {
+                host: "executor".to_owned(),
+                port: 50051,
+                ..Default::default()
+            }),
+            path: "/shuffle/job/2/3/data.arrow".to_owned(),
+            ..Default::default()
+        };
+        let fetch = protobuf::FetchPartition {
+            job_id: "job".to_owned(),
+            stage_id: 2,
+            partition_id: 3,
+            path: "/shuffle/job/2/3/data.arrow".to_owned(),
+            host: "executor".to_owned(),
+            port: 50051,
+        };
+        assert!(is_output_location(&location, &fetch));
+
+        let other_path = protobuf::FetchPartition {
+            path: "/etc/passwd".to_owned(),
+            ..fetch.clone()
+        };
+        assert!(!is_output_location(&location, &other_path));
+        let other_host = protobuf::FetchPartition {
+            host: "internal-service".to_owned(),
+            ..fetch.clone()
+        };
+        assert!(!is_output_location(&location, &other_host));
+        let other_job = protobuf::FetchPartition {
+            job_id: "other".to_owned(),
+            ..fetch
+        };
+        assert!(!is_output_location(&location, &other_job));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_session() -> Result<(), Status> {
+        let scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                test_cluster_context(),
+                BallistaCodec::default(),
+                Arc::new(SchedulerConfig::default()),
+                default_metrics_collector().unwrap(),
+            );
+        let service = FlightSqlServiceImpl::new(scheduler);
+        let handle = service.create_ctx().await?;
+        let context_session_id = service.contexts.get(&handle).unwrap().session_id();
+        let request = |bearer: Option<Uuid>, session_id: Option<&str>| {
+            let mut request = Request::new(());
+            if let Some(bearer) = bearer {
+                let bearer = format!("Bearer {bearer}");
+                request
+                    .metadata_mut()
+                    .insert("authorization", bearer.parse().unwrap());
+            }
+            if let Some(session_id) = session_id {
+                request
+                    .metadata_mut()
+                    .insert(SESSION_ID_METADATA_KEY, session_id.parse().unwrap());
+            }
+            request
+        };
+
+        // Ballista clients send their session
+        assert_eq!(
+            service.fetch_session(&request(None, Some("session")))?,
+            "session"
+        );
+        // FlightSQL clients fetch for the session of their context
+        assert_eq!(
+            service.fetch_session(&request(Some(handle), None))?,
+            context_session_id
+        );
+        assert_eq!(
+            service.fetch_session(&request(Some(handle), Some(&context_session_id)))?,
+            context_session_id
+        );
+        // and can not claim another session
+        let denied = service
+            .fetch_session(&request(Some(handle), Some("session")))
+            .unwrap_err();
+        assert_eq!(denied.code(), tonic::Code::PermissionDenied);
+        let unknown_context = service
+            .fetch_session(&request(Some(Uuid::new_v4()), Some("session")))
+            .unwrap_err();
+        assert_eq!(unknown_context.code(), tonic::Code::Internal);
+        assert!(service.fetch_session(&request(None, None)).is_err());
+        Ok(())
+    }
+}
@@ -18,13 +18,18 @@
 #![doc = include_str ! ("../README.md")]
 
 pub mod api;
+pub mod benchmark;
 pub mod cluster;
 pub mod config;
 pub mod display;
 pub mod metrics;
+pub mod plan_compiler;
 pub mod planner;
+pub mod row_group_pruning;
+pub mod runtime_filter;
 pub mod scheduler_process;
 pub mod scheduler_server;
+pub mod simulation;
 #[cfg(feature = "sled")]
 pub mod standalone;
 pub mod state;
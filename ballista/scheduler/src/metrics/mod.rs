@@ -21,12 +21,18 @@ pub mod prometheus;
 #[cfg(feature = "prometheus")]
 use crate::metrics::prometheus::PrometheusMetricsCollector;
 use ballista_core::error::Result;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Interface for recording metrics events in the scheduler. An instance of `Arc<dyn SchedulerMetricsCollector>`
 /// will be passed when constructing the `QueryStageScheduler` which is the core event loop of the scheduler.
 /// The event loop will then record metric events through this trait.
 pub trait SchedulerMetricsCollector: Send + Sync {
+    /// Record that job with `job_id` named `job_name` was queued, before it is planned. The
+    /// collectors labelling metrics by job name keep the name for the later events of the job.
+    fn record_queued(&self, _job_id: &str, _job_name: &str) {}
+
     /// Record that job with `job_id` was submitted. This will be invoked
     /// after the job's `ExecutionGraph` is created and it is ready to be scheduled
     /// on executors.
@@ -52,6 +58,40 @@ pub trait SchedulerMetricsCollector: Send + Sync {
     /// to schedule on an executor but cannot be scheduled because no resources are available.
     fn set_pending_tasks_queue_size(&self, value: u64);
 
+    /// Record that job with `job_id` was handed off to another scheduler, which records
+    /// its later events. The collectors labelling metrics by job name drop its name.
+    fn record_handed_off(&self, _job_id: &str) {}
+
+    /// Record that `slots` task slots held by a scheduler which stopped renewing its slot
+    /// reservation were reclaimed.
+    fn record_reclaimed_slots(&self, _slots: u64) {}
+
+    /// Record the size in bytes of an execution graph saved in the cluster state, after
+    /// compression.
+    fn record_execution_graph_size(&self, _bytes: u64) {}
+
+    /// Record that a stage exceeded the threshold of a stage alert rule on `metric`.
+    fn record_stage_alert(&self, _metric: &str) {}
+
+    /// Record that a scheduler event of job `job_id`, or of no job if empty, was processed
+    /// `latency` after it was received by the event loop, including the time it waited for
+    /// the previous events of the job.
+    fn record_event_latency(&self, _job_id: &str, _latency: Duration) {}
+
+    /// Record that an `operation` of the cluster storage, e.g. `get` or `apply_txn`, on
+    /// `keyspace` took `latency`, and whether it `failed`.
+    fn record_state_operation(
+        &self,
+        keyspace: &str,
+        operation: &str,
+        latency: Duration,
+        failed: bool,
+    );
+
+    /// Record that the active jobs were deadlocked waiting for task slots, and that the
+    /// waiting stages of a job were rewound to resolve the deadlock.
+    fn record_slot_deadlock(&self) {}
+
     /// Gather current metric set that should be returned when calling the scheduler's metrics API
     /// Should return a tuple containing the content of the metric set and the content type (e.g. `application/json`, `text/plain`, etc)
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>>;
@@ -68,12 +108,43 @@ impl SchedulerMetricsCollector for NoopMetricsCollector {
     fn record_failed(&self, _job_id: &str, _queued_at: u64, _failed_at: u64) {}
     fn record_cancelled(&self, _job_id: &str) {}
     fn set_pending_tasks_queue_size(&self, _value: u64) {}
+    fn record_state_operation(
+        &self,
+        _keyspace: &str,
+        _operation: &str,
+        _latency: Duration,
+        _failed: bool,
+    ) {
+    }
 
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
         Ok(None)
     }
 }
 
+/// Labelling of the job metrics of the default metrics collector by job name, so that they
+/// can be broken down per workload. The job names which are not allowed, or which exceed
+/// the cardinality cap, share the `other` label.
+#[derive(Debug, Clone)]
+pub struct JobMetricsLabels {
+    /// The job names used as label, any name if empty
+    pub allowed_job_names: HashSet<String>,
+    /// The maximum number of distinct job names used as label
+    pub max_job_names: usize,
+}
+
+/// Label the job metrics of the default metrics collector by job name. It must be called
+/// before the default metrics collector is first used to take effect.
+#[cfg(feature = "prometheus")]
+pub fn set_job_metrics_labels(labels: JobMetricsLabels) -> Result<()> {
+    prometheus::set_job_metrics_labels(labels)
+}
+
+#[cfg(not(feature = "prometheus"))]
+pub fn set_job_metrics_labels(_labels: JobMetricsLabels) -> Result<()> {
+    Ok(())
+}
+
 /// Return a reference to the systems default metrics collector.
 #[cfg(feature = "prometheus")]
 pub fn default_metrics_collector() -> Result<Arc<dyn SchedulerMetricsCollector>> {
@@ -15,21 +15,38 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::metrics::SchedulerMetricsCollector;
+use crate::metrics::{JobMetricsLabels, SchedulerMetricsCollector};
 use ballista_core::error::{BallistaError, Result};
 
+use dashmap::DashMap;
 use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
 use prometheus::{
-    register_counter_with_registry, register_gauge_with_registry,
-    register_histogram_with_registry, Counter, Gauge, Histogram, Registry,
+    register_counter_vec_with_registry, register_counter_with_registry,
+    register_gauge_with_registry, register_histogram_vec_with_registry,
+    register_histogram_with_registry, Counter, CounterVec, Gauge, Histogram,
+    HistogramVec, Registry,
 };
 use prometheus::{Encoder, TextEncoder};
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 static COLLECTOR: OnceCell<Arc<dyn SchedulerMetricsCollector>> = OnceCell::new();
 
+static JOB_METRICS_LABELS: OnceCell<JobMetricsLabels> = OnceCell::new();
+
+/// Label of the job metrics of the jobs whose name is not used as label
+const OTHER_JOB_NAME: &str = "other";
+
+pub(crate) fn set_job_metrics_labels(labels: JobMetricsLabels) -> Result<()> {
+    JOB_METRICS_LABELS.set(labels).map_err(|_| {
+        BallistaError::Internal("The job metrics labels are already set".to_owned())
+    })
+}
+
 /// SchedulerMetricsCollector implementation based on Prometheus. By default this will track
-/// 7 metrics:
+/// 9 metrics:
 /// *job_exec_time_seconds* - Histogram of successful job execution time in seconds
 /// *planning_time_ms* - Histogram of job planning time in milliseconds
 /// *failed* - Counter of failed jobs
@@ -38,21 +55,93 @@ static COLLECTOR: OnceCell<Arc<dyn SchedulerMetricsCollector>> = OnceCell::new()
 /// *job_completed_total* - Counter of completed jobs
 /// *job_submitted_total* - Counter of submitted jobs
 /// *pending_task_queue_size* - Number of pending tasks
+/// *reclaimed_slots_total* - Counter of leaked task slots reclaimed from expired slot reservations
+/// *execution_graph_size_bytes* - Histogram of the size in bytes of the execution graphs saved in the cluster state
+/// *stage_alert_total* - Counter of the stage alerts raised, labelled with the `metric` of their rule
+/// *event_latency_ms* - Histogram of the time in milliseconds from the receipt of the scheduler events to the end of their processing
+/// *state_operation_latency_ms* - Histogram of the latency in milliseconds of the cluster storage operations, labelled with their `keyspace` and `operation`
+/// *state_operation_errors_total* - Counter of the failed cluster storage operations, labelled with their `keyspace` and `operation`
+/// *slot_deadlocks_total* - Counter of the deadlocks of the jobs waiting for task slots, resolved by rewinding a job
+///
+/// If job metrics labels are set, the job metrics are labelled with the name of the jobs,
+/// `job_name`.
 pub struct PrometheusMetricsCollector {
-    execution_time: Histogram,
-    planning_time: Histogram,
-    failed: Counter,
-    cancelled: Counter,
-    completed: Counter,
-    submitted: Counter,
+    execution_time: HistogramVec,
+    planning_time: HistogramVec,
+    failed: CounterVec,
+    cancelled: CounterVec,
+    completed: CounterVec,
+    submitted: CounterVec,
     pending_queue_size: Gauge,
+    reclaimed_slots: Counter,
+    execution_graph_size: Histogram,
+    stage_alerts: CounterVec,
+    event_latency: HistogramVec,
+    state_operation_latency: HistogramVec,
+    state_operation_errors: CounterVec,
+    slot_deadlocks: Counter,
+    job_names: Option<JobNameLabels>,
+}
+
+/// Label values of the job metrics of the jobs
+struct JobNameLabels {
+    config: JobMetricsLabels,
+    /// The job names used as label so far
+    used_names: Mutex<HashSet<String>>,
+    /// Label of the jobs queued and not finished yet, job_id -> label
+    job_labels: DashMap<String, String>,
+}
+
+impl JobNameLabels {
+    fn new(config: JobMetricsLabels) -> Self {
+        Self {
+            config,
+            used_names: Mutex::new(HashSet::new()),
+            job_labels: DashMap::new(),
+        }
+    }
+
+    /// The label of the jobs named `job_name`
+    fn label(&self, job_name: &str) -> String {
+        if job_name.is_empty()
+            || (!self.config.allowed_job_names.is_empty()
+                && !self.config.allowed_job_names.contains(job_name))
+        {
+            return OTHER_JOB_NAME.to_owned();
+        }
+        let mut used_names = self.used_names.lock();
+        if used_names.contains(job_name) {
+            job_name.to_owned()
+        } else if used_names.len() < self.config.max_job_names {
+            used_names.insert(job_name.to_owned());
+            job_name.to_owned()
+        } else {
+            OTHER_JOB_NAME.to_owned()
+        }
+    }
 }
 
 impl PrometheusMetricsCollector {
     pub fn new(registry: &Registry) -> Result<Self> {
-        let execution_time = register_histogram_with_registry!(
+        Self::try_new(registry, None)
+    }
+
+    /// Create the collector, labelling the job metrics by job name if `job_metrics_labels`
+    /// is set
+    pub fn try_new(
+        registry: &Registry,
+        job_metrics_labels: Option<JobMetricsLabels>,
+    ) -> Result<Self> {
+        let job_label_names: &[&str] = if job_metrics_labels.is_some() {
+            &["job_name"]
+        } else {
+            &[]
+        };
+
+        let execution_time = register_histogram_vec_with_registry!(
             "job_exec_time_seconds",
             "Histogram of successful job execution time in seconds",
+            job_label_names,
             vec![0.5_f64, 1_f64, 5_f64, 30_f64, 60_f64],
             registry
         )
@@ -60,9 +149,10 @@ impl PrometheusMetricsCollector {
             BallistaError::Internal(format!("Error registering metric: {e:?}"))
         })?;
 
-        let planning_time = register_histogram_with_registry!(
+        let planning_time = register_histogram_vec_with_registry!(
             "planning_time_ms",
             "Histogram of job planning time in milliseconds",
+            job_label_names,
             vec![1.0_f64, 5.0_f64, 25.0_f64, 100.0_f64, 500.0_f64],
             registry
         )
@@ -70,36 +160,40 @@ impl PrometheusMetricsCollector {
             BallistaError::Internal(format!("Error registering metric: {e:?}"))
         })?;
 
-        let failed = register_counter_with_registry!(
+        let failed = register_counter_vec_with_registry!(
             "job_failed_total",
             "Counter of failed jobs",
+            job_label_names,
             registry
         )
         .map_err(|e| {
             BallistaError::Internal(format!("Error registering metric: {e:?}"))
         })?;
 
-        let cancelled = register_counter_with_registry!(
+        let cancelled = register_counter_vec_with_registry!(
             "job_cancelled_total",
             "Counter of cancelled jobs",
+            job_label_names,
             registry
         )
         .map_err(|e| {
             BallistaError::Internal(format!("Error registering metric: {e:?}"))
         })?;
 
-        let completed = register_counter_with_registry!(
+        let completed = register_counter_vec_with_registry!(
             "job_completed_total",
             "Counter of completed jobs",
+            job_label_names,
             registry
         )
         .map_err(|e| {
             BallistaError::Internal(format!("Error registering metric: {e:?}"))
         })?;
 
-        let submitted = register_counter_with_registry!(
+        let submitted = register_counter_vec_with_registry!(
             "job_submitted_total",
             "Counter of submitted jobs",
+            job_label_names,
             registry
         )
         .map_err(|e| {
@@ -115,6 +209,76 @@ impl PrometheusMetricsCollector {
             BallistaError::Internal(format!("Error registering metric: {e:?}"))
         })?;
 
+        let reclaimed_slots = register_counter_with_registry!(
+            "reclaimed_slots_total",
+            "Counter of leaked task slots reclaimed from expired slot reservations",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let execution_graph_size = register_histogram_with_registry!(
+            "execution_graph_size_bytes",
+            "Histogram of the size in bytes of the execution graphs saved in the cluster state",
+            vec![1e4_f64, 1e5_f64, 1e6_f64, 1e7_f64, 1e8_f64],
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let stage_alerts = register_counter_vec_with_registry!(
+            "stage_alert_total",
+            "Counter of the stage alerts raised",
+            &["metric"],
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let event_latency = register_histogram_vec_with_registry!(
+            "event_latency_ms",
+            "Histogram of the time in milliseconds from the receipt of the scheduler events to the end of their processing",
+            job_label_names,
+            vec![1.0_f64, 10.0_f64, 100.0_f64, 1000.0_f64, 10000.0_f64],
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let state_operation_latency = register_histogram_vec_with_registry!(
+            "state_operation_latency_ms",
+            "Histogram of the latency in milliseconds of the cluster storage operations",
+            &["keyspace", "operation"],
+            vec![1.0_f64, 5.0_f64, 25.0_f64, 100.0_f64, 500.0_f64, 2500.0_f64],
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let state_operation_errors = register_counter_vec_with_registry!(
+            "state_operation_errors_total",
+            "Counter of the failed cluster storage operations",
+            &["keyspace", "operation"],
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let slot_deadlocks = register_counter_with_registry!(
+            "slot_deadlocks_total",
+            "Counter of the deadlocks of the jobs waiting for task slots",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
         Ok(Self {
             execution_time,
             planning_time,
@@ -123,13 +287,24 @@ impl PrometheusMetricsCollector {
             completed,
             submitted,
             pending_queue_size,
+            reclaimed_slots,
+            execution_graph_size,
+            stage_alerts,
+            event_latency,
+            state_operation_latency,
+            state_operation_errors,
+            slot_deadlocks,
+            job_names: job_metrics_labels.map(JobNameLabels::new),
         })
     }
 
     pub fn current() -> Result<Arc<dyn SchedulerMetricsCollector>> {
         COLLECTOR
             .get_or_try_init(|| {
-                let collector = Self::new(::prometheus::default_registry())?;
+                let collector = Self::try_new(
+                    ::prometheus::default_registry(),
+                    JOB_METRICS_LABELS.get().cloned(),
+                )?;
 
                 Ok(Arc::new(collector) as Arc<dyn SchedulerMetricsCollector>)
             })
@@ -137,31 +312,117 @@ impl PrometheusMetricsCollector {
     }
 }
 
+impl PrometheusMetricsCollector {
+    /// The label values of the job metrics of job `job_id`, removing the job from the
+    /// labelled jobs if `finished`
+    fn job_label_values(&self, job_id: &str, finished: bool) -> Vec<String> {
+        match &self.job_names {
+            Some(job_names) => {
+                let label = if finished {
+                    job_names.job_labels.remove(job_id).map(|(_, label)| label)
+                } else {
+                    job_names
+                        .job_labels
+                        .get(job_id)
+                        .map(|label| label.value().clone())
+                };
+                vec![label.unwrap_or_else(|| OTHER_JOB_NAME.to_owned())]
+            }
+            None => vec![],
+        }
+    }
+}
+
 impl SchedulerMetricsCollector for PrometheusMetricsCollector {
-    fn record_submitted(&self, _job_id: &str, queued_at: u64, submitted_at: u64) {
-        self.submitted.inc();
+    fn record_queued(&self, job_id: &str, job_name: &str) {
+        if let Some(job_names) = &self.job_names {
+            job_names
+                .job_labels
+                .insert(job_id.to_owned(), job_names.label(job_name));
+        }
+    }
+
+    fn record_submitted(&self, job_id: &str, queued_at: u64, submitted_at: u64) {
+        let labels = self.job_label_values(job_id, false);
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        self.submitted.with_label_values(&labels).inc();
         self.planning_time
+            .with_label_values(&labels)
             .observe((submitted_at - queued_at) as f64);
     }
 
-    fn record_completed(&self, _job_id: &str, queued_at: u64, completed_at: u64) {
-        self.completed.inc();
+    fn record_completed(&self, job_id: &str, queued_at: u64, completed_at: u64) {
+        let labels = self.job_label_values(job_id, true);
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        self.completed.with_label_values(&labels).inc();
         self.execution_time
+            .with_label_values(&labels)
             .observe((completed_at - queued_at) as f64 / 1000_f64)
     }
 
-    fn record_failed(&self, _job_id: &str, _queued_at: u64, _failed_at: u64) {
-        self.failed.inc()
+    fn record_failed(&self, job_id: &str, _queued_at: u64, _failed_at: u64) {
+        let labels = self.job_label_values(job_id, true);
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        self.failed.with_label_values(&labels).inc()
     }
 
-    fn record_cancelled(&self, _job_id: &str) {
-        self.cancelled.inc();
+    fn record_cancelled(&self, job_id: &str) {
+        let labels = self.job_label_values(job_id, true);
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        self.cancelled.with_label_values(&labels).inc();
+    }
+
+    fn record_handed_off(&self, job_id: &str) {
+        if let Some(job_names) = &self.job_names {
+            job_names.job_labels.remove(job_id);
+        }
     }
 
     fn set_pending_tasks_queue_size(&self, value: u64) {
         self.pending_queue_size.set(value as f64);
     }
 
+    fn record_reclaimed_slots(&self, slots: u64) {
+        self.reclaimed_slots.inc_by(slots as f64);
+    }
+
+    fn record_execution_graph_size(&self, bytes: u64) {
+        self.execution_graph_size.observe(bytes as f64);
+    }
+
+    fn record_stage_alert(&self, metric: &str) {
+        self.stage_alerts.with_label_values(&[metric]).inc();
+    }
+
+    fn record_event_latency(&self, job_id: &str, latency: Duration) {
+        let labels = self.job_label_values(job_id, false);
+        let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+        self.event_latency
+            .with_label_values(&labels)
+            .observe(latency.as_secs_f64() * 1000_f64);
+    }
+
+    fn record_state_operation(
+        &self,
+        keyspace: &str,
+        operation: &str,
+        latency: Duration,
+        failed: bool,
+    ) {
+        self.state_operation_latency
+            .with_label_values(&[keyspace, operation])
+            .observe(latency.as_secs_f64() * 1000_f64);
+        if failed {
+            self.state_operation_errors
+                .with_label_values(&[keyspace, operation])
+                .inc();
+        }
+    }
+
+    fn record_slot_deadlock(&self) {
+        self.slot_deadlocks.inc();
+    }
+
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
         let encoder = TextEncoder::new();
 
@@ -174,3 +435,52 @@ impl SchedulerMetricsCollector for PrometheusMetricsCollector {
         Ok(Some((buffer, encoder.format_type().to_owned())))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_job_metrics_labels() -> Result<()> {
+        let collector = PrometheusMetricsCollector::try_new(
+            &Registry::new(),
+            Some(JobMetricsLabels {
+                allowed_job_names: HashSet::new(),
+                max_job_names: 2,
+            }),
+        )?;
+        for (job_id, job_name) in [
+            ("1", "etl"),
+            ("2", "report"),
+            ("3", "adhoc"),
+            ("4", "etl"),
+            ("5", ""),
+        ] {
+            collector.record_queued(job_id, job_name);
+            collector.record_submitted(job_id, 0, 1);
+            collector.record_completed(job_id, 0, 2);
+        }
+
+        // the names beyond the cardinality cap and the empty names share a label
+        let submitted =
+            |label: &str| collector.submitted.with_label_values(&[label]).get();
+        assert_eq!(2.0, submitted("etl"));
+        assert_eq!(1.0, submitted("report"));
+        assert_eq!(2.0, submitted(OTHER_JOB_NAME));
+        assert_eq!(2.0, collector.completed.with_label_values(&["etl"]).get());
+        assert!(collector.job_names.as_ref().unwrap().job_labels.is_empty());
+
+        // the jobs handed off to another scheduler end there
+        collector.record_queued("6", "etl");
+        collector.record_handed_off("6");
+        assert!(collector.job_names.as_ref().unwrap().job_labels.is_empty());
+
+        let allowed = JobNameLabels::new(JobMetricsLabels {
+            allowed_job_names: HashSet::from(["etl".to_owned()]),
+            max_job_names: 10,
+        });
+        assert_eq!("etl", allowed.label("etl"));
+        assert_eq!(OTHER_JOB_NAME, allowed.label("report"));
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Compilation of SQL queries into the distributed plans that the scheduler would run,
+//! without a cluster, e.g. to check the plans of queries in CI or for support tickets
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::execution_plans::ShuffleWriterExec;
+use ballista_core::serde::{protobuf, BallistaCodec};
+use datafusion::prelude::SessionContext;
+use datafusion::sql::parser::DFParser;
+use prost::Message;
+use serde::Serialize;
+
+use crate::planner::{DefaultStagePlanner, StagePlanner};
+use crate::runtime_filter::RuntimeFilterStagePlanner;
+use crate::state::create_job_physical_plan;
+use crate::state::execution_graph::ExecutionGraph;
+use crate::state::execution_graph_json::ExecutionGraphDescription;
+
+/// ID of the scheduler and of the job of the compiled plans
+const COMPILED_PLAN_ID: &str = "ballista-plan";
+
+/// Description of the distributed plan of a query: its stages and their shuffles
+#[derive(Debug, Serialize)]
+pub struct DistributedPlanDescription {
+    /// Number of partitions of the output of the query
+    pub output_partitions: usize,
+    /// Estimated peak number of tasks running at once
+    pub peak_parallelism: usize,
+    /// Stages sorted by stage ID
+    pub stages: Vec<CompiledStageDescription>,
+    pub shuffle_edges: Vec<ShuffleEdge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CompiledStageDescription {
+    pub stage_id: usize,
+    /// Number of tasks, one per input partition, estimated from the files known when the
+    /// query is compiled
+    pub estimated_tasks: usize,
+    /// Number of partitions written by the stage
+    pub output_partitions: usize,
+    /// Stages whose outputs are the inputs of this stage
+    pub input_stages: Vec<usize>,
+    /// Stages taking the outputs of this stage as inputs, empty for the final stage
+    pub output_links: Vec<usize>,
+    pub plan: String,
+}
+
+/// The outputs of a stage shuffled to a stage reading them
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ShuffleEdge {
+    pub from_stage: usize,
+    pub to_stage: usize,
+    /// Number of shuffle partitions written by the tasks of `from_stage`
+    pub partitions: usize,
+}
+
+/// Execute the DDL statements, e.g. `CREATE EXTERNAL TABLE`, registering the tables read
+/// by the compiled queries
+pub async fn register_tables(ctx: &SessionContext, ddl: &str) -> Result<()> {
+    for statement in DFParser::parse_sql(ddl)? {
+        let plan = ctx.state().statement_to_plan(statement).await?;
+        ctx.execute_logical_plan(plan).await?;
+    }
+    Ok(())
+}
+
+/// Compile a query into the execution graph the scheduler would create for it, planned
+/// with the settings of the session
+pub async fn compile_plan(ctx: &SessionContext, sql: &str) -> Result<ExecutionGraph> {
+    let mut statements = DFParser::parse_sql(sql)?;
+    let statement = match (statements.pop_front(), statements.is_empty()) {
+        (Some(statement), true) => statement,
+        _ => {
+            return Err(BallistaError::General(
+                "Expected a single SQL statement to compile".to_string(),
+            ))
+        }
+    };
+    let logical_plan = ctx.state().statement_to_plan(statement).await?;
+    let optimized_plan = ctx.state().optimize(&logical_plan)?;
+    let plan = create_job_physical_plan(ctx, &optimized_plan).await?;
+
+    let session_config = ctx.state().config().clone();
+    let runtime_filter_planner =
+        RuntimeFilterStagePlanner::for_session(&DefaultStagePlanner, &session_config);
+    let stage_planner: &dyn StagePlanner = match &runtime_filter_planner {
+        Some(runtime_filter_planner) => runtime_filter_planner,
+        None => &DefaultStagePlanner,
+    };
+    ExecutionGraph::new_with_stage_planner(
+        COMPILED_PLAN_ID,
+        COMPILED_PLAN_ID,
+        COMPILED_PLAN_ID,
+        &ctx.session_id(),
+        plan,
+        0,
+        stage_planner,
+    )
+}
+
+/// Describe the stages of a compiled execution graph and the shuffles between them
+pub fn describe_plan(graph: &ExecutionGraph) -> DistributedPlanDescription {
+    let mut stages = vec![];
+    let mut shuffle_edges = vec![];
+    let mut peak_parallelism = 0;
+    for stage in ExecutionGraphDescription::from(graph).stages {
+        let shuffle_writer = graph
+            .stages()
+            .get(&stage.stage_id)
+            .and_then(|stage| stage.plan().as_any().downcast_ref::<ShuffleWriterExec>());
+        let (estimated_tasks, output_partitions) = match shuffle_writer {
+            Some(shuffle_writer) => {
+                let tasks = shuffle_writer.input_partition_count();
+                let partitions = shuffle_writer
+                    .shuffle_output_partitioning()
+                    .map(|partitioning| partitioning.partition_count())
+                    .unwrap_or(tasks);
+                (tasks, partitions)
+            }
+            None => (stage.partitions.unwrap_or_default(), 0),
+        };
+        peak_parallelism = peak_parallelism.max(estimated_tasks);
+        shuffle_edges.extend(stage.output_links.iter().map(|to_stage| ShuffleEdge {
+            from_stage: stage.stage_id,
+            to_stage: *to_stage,
+            partitions: output_partitions,
+        }));
+        stages.push(CompiledStageDescription {
+            stage_id: stage.stage_id,
+            estimated_tasks,
+            output_partitions,
+            input_stages: stage.input_stages,
+            output_links: stage.output_links,
+            plan: stage.plan,
+        });
+    }
+    DistributedPlanDescription {
+        output_partitions: graph.output_partitions(),
+        peak_parallelism,
+        stages,
+        shuffle_edges,
+    }
+}
+
+/// Encode a compiled execution graph as the scheduler persists it, with the default codec
+pub fn encode_plan(graph: ExecutionGraph) -> Result<Vec<u8>> {
+    let codec: BallistaCodec = BallistaCodec::default();
+    let proto: protobuf::ExecutionGraph =
+        ExecutionGraph::encode_execution_graph(graph, &codec)?;
+    Ok(proto.encode_to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::config::BallistaConfig;
+    use datafusion::prelude::SessionConfig;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_compile_plan() -> Result<()> {
+        let config = SessionConfig::new()
+            .with_target_partitions(4)
+            .with_extension(Arc::new(BallistaConfig::new()?));
+        let ctx = SessionContext::new_with_config(config);
+        register_tables(
+            &ctx,
+            "CREATE TABLE t1 (a INT, b VARCHAR); CREATE TABLE t2 (a INT, c DOUBLE);",
+        )
+        .await?;
+
+        let graph = compile_plan(
+            &ctx,
+            "SELECT t1.b, SUM(t2.c) FROM t1 JOIN t2 ON t1.a = t2.a GROUP BY t1.b",
+        )
+        .await?;
+        let description = describe_plan(&graph);
+
+        assert!(description.stages.len() > 1);
+        let final_stage = description.stages.last().unwrap();
+        assert!(final_stage.output_links.is_empty());
+        // every stage but the final one is shuffled to the stages reading it
+        for stage in &description.stages[..description.stages.len() - 1] {
+            assert!(!stage.output_links.is_empty());
+            assert!(description
+                .shuffle_edges
+                .iter()
+                .any(|edge| edge.from_stage == stage.stage_id));
+        }
+        assert!(!encode_plan(graph)?.is_empty());
+
+        assert!(compile_plan(&ctx, "SELECT 1; SELECT 2").await.is_err());
+        Ok(())
+    }
+}
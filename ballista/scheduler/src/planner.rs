@@ -17,15 +17,29 @@
 
 //! Distributed query execution
 
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt::Debug;
 use std::sync::Arc;
 
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::{
-    execution_plans::{ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec},
+    execution_plans::{
+        FileSinkCommitExec, MaterializedCteExec, ParallelFileSinkExec, RuntimeFilterExec,
+        ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec, STAGING_DIR,
+    },
     serde::scheduler::PartitionLocation,
 };
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::datasource::file_format::file_compression_type::FileCompressionType;
+use datafusion::datasource::listing::PartitionedFile;
+use datafusion::datasource::physical_plan::{
+    ArrowExec, AvroExec, CsvExec, FileScanConfig, NdJsonExec, ParquetExec,
+};
+use datafusion::physical_plan::aggregates::{AggregateExec, AggregateMode};
 use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::insert::FileSinkExec;
 use datafusion::physical_plan::repartition::RepartitionExec;
 use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
 use datafusion::physical_plan::windows::WindowAggExec;
@@ -37,13 +51,47 @@ use log::{debug, info};
 
 type PartialQueryStageResult = (Arc<dyn ExecutionPlan>, Vec<Arc<ShuffleWriterExec>>);
 
+/// Splits the physical plan of a job into the query stages of its execution graph, i.e.
+/// decides where the exchange boundaries between stages are inserted. Custom planners can
+/// e.g. fuse pairs of custom operators into a single stage.
+pub trait StagePlanner: Debug + Send + Sync {
+    /// Returns the query stages of the plan, with a [ShuffleWriterExec] at the root of every
+    /// stage and [UnresolvedShuffleExec] leaves reading the outputs of other stages. Every
+    /// stage comes after the stages it reads from, the last stage computing the output of
+    /// the job.
+    fn plan_query_stages(
+        &self,
+        job_id: &str,
+        execution_plan: Arc<dyn ExecutionPlan>,
+    ) -> Result<Vec<Arc<ShuffleWriterExec>>>;
+}
+
+/// The default [StagePlanner], splitting the plan with a [DistributedPlanner]
+#[derive(Debug, Default)]
+pub struct DefaultStagePlanner;
+
+impl StagePlanner for DefaultStagePlanner {
+    fn plan_query_stages(
+        &self,
+        job_id: &str,
+        execution_plan: Arc<dyn ExecutionPlan>,
+    ) -> Result<Vec<Arc<ShuffleWriterExec>>> {
+        DistributedPlanner::new().plan_query_stages(job_id, execution_plan)
+    }
+}
+
 pub struct DistributedPlanner {
     next_stage_id: usize,
+    /// Stages computing the materialized subqueries planned so far, by subquery id
+    materialized_ctes: HashMap<usize, Arc<ShuffleWriterExec>>,
 }
 
 impl DistributedPlanner {
     pub fn new() -> Self {
-        Self { next_stage_id: 0 }
+        Self {
+            next_stage_id: 0,
+            materialized_ctes: HashMap::new(),
+        }
     }
 }
 
@@ -56,13 +104,21 @@ impl Default for DistributedPlanner {
 impl DistributedPlanner {
     /// Returns a vector of ExecutionPlans, where the root node is a [ShuffleWriterExec].
     /// Plans that depend on the input of other plans will have leaf nodes of type [UnresolvedShuffleExec].
-    /// A [ShuffleWriterExec] is created whenever the partitioning changes.
+    /// A [ShuffleWriterExec] is created whenever the partitioning changes. Exchanges whose input
+    /// is already partitioned as required are removed, identical exchanges are left to
+    /// [deduplicate_stages]. Every materialized subquery, see [MaterializedCteExec], is
+    /// computed by a stage of its own read by all its references.
+    /// Aggregates computed in a single step over merged partitions are split so that their
+    /// partial aggregation runs in the stage computing the partitions.
     pub fn plan_query_stages<'a>(
         &'a mut self,
         job_id: &'a str,
         execution_plan: Arc<dyn ExecutionPlan>,
     ) -> Result<Vec<Arc<ShuffleWriterExec>>> {
         info!("planning query stages for job {}", job_id);
+        let execution_plan =
+            distribute_file_sink(job_id, &execution_plan)?.unwrap_or(execution_plan);
+        let execution_plan = split_single_aggregates(execution_plan)?;
         let (new_plan, mut stages) =
             self.plan_query_stages_internal(job_id, execution_plan)?;
         stages.push(create_shuffle_writer(
@@ -87,6 +143,22 @@ impl DistributedPlanner {
             return Ok((execution_plan, vec![]));
         }
 
+        if let Some(cte) = execution_plan
+            .as_any()
+            .downcast_ref::<MaterializedCteExec>()
+        {
+            // the subquery is computed by the stage planned for its first reference
+            if let Some(shuffle_writer) = self.materialized_ctes.get(&cte.id()) {
+                debug!(
+                    "Reusing stage {} for materialized CTE {} of job {}",
+                    shuffle_writer.stage_id(),
+                    cte.name(),
+                    job_id
+                );
+                return Ok((create_unresolved_shuffle(shuffle_writer), vec![]));
+            }
+        }
+
         let mut stages = vec![];
         let mut children = vec![];
         for child in execution_plan.children() {
@@ -96,7 +168,42 @@ impl DistributedPlanner {
             stages.append(&mut child_stages);
         }
 
-        if let Some(_coalesce) = execution_plan
+        let single_input_partition = children.len() == 1
+            && children[0]
+                .properties()
+                .output_partitioning()
+                .partition_count()
+                == 1;
+
+        if let Some(cte) = execution_plan
+            .as_any()
+            .downcast_ref::<MaterializedCteExec>()
+        {
+            let shuffle_writer = create_shuffle_writer(
+                job_id,
+                self.next_stage_id(),
+                children[0].clone(),
+                None,
+            )?;
+            self.materialized_ctes
+                .insert(cte.id(), shuffle_writer.clone());
+            let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
+            stages.push(shuffle_writer);
+            Ok((unresolved_shuffle, stages))
+        } else if (execution_plan.as_any().is::<CoalescePartitionsExec>()
+            || execution_plan.as_any().is::<SortPreservingMergeExec>())
+            && single_input_partition
+        {
+            // merging a single partition does not need a new stage
+            debug!(
+                "Skipping the exchange of a single partition for job {}",
+                job_id
+            );
+            Ok((
+                with_new_children_if_necessary(execution_plan, children)?,
+                stages,
+            ))
+        } else if let Some(_coalesce) = execution_plan
             .as_any()
             .downcast_ref::<CoalescePartitionsExec>()
         {
@@ -132,6 +239,18 @@ impl DistributedPlanner {
             execution_plan.as_any().downcast_ref::<RepartitionExec>()
         {
             match repart.properties().output_partitioning() {
+                Partitioning::Hash(_, _)
+                    if children[0].properties().output_partitioning()
+                        == repart.partitioning() =>
+                {
+                    // the input is already partitioned as required, e.g. by the output
+                    // partitioning of a previous stage
+                    debug!(
+                        "Skipping the exchange of already partitioned input: {:?}",
+                        repart.partitioning()
+                    );
+                    Ok((children[0].clone(), stages))
+                }
                 Partitioning::Hash(_, _) => {
                     let shuffle_writer = create_shuffle_writer(
                         job_id,
@@ -169,19 +288,372 @@ impl DistributedPlanner {
     }
 }
 
-fn create_unresolved_shuffle(
+/// Rewrites a plan writing to a directory with a [FileSinkExec] at its root, so that every partition of its input
+/// is written by its own task to a staging directory of the job, and the write is then
+/// committed by a final task moving the files to the output directory. Returns `None` for
+/// plans which are not such writes.
+fn distribute_file_sink(
+    job_id: &str,
+    execution_plan: &Arc<dyn ExecutionPlan>,
+) -> Result<Option<Arc<dyn ExecutionPlan>>> {
+    let Some(file_sink) = execution_plan.as_any().downcast_ref::<FileSinkExec>() else {
+        return Ok(None);
+    };
+    if !ParallelFileSinkExec::supports(file_sink) {
+        return Ok(None);
+    }
+
+    // the partitions are coalesced only to satisfy the single input partition requirement
+    // of the FileSinkExec
+    let input = match file_sink
+        .input()
+        .as_any()
+        .downcast_ref::<CoalescePartitionsExec>()
+    {
+        Some(coalesce) => coalesce.input().clone(),
+        None => file_sink.input().clone(),
+    };
+    let output_path = ParallelFileSinkExec::try_new(execution_plan.clone())?
+        .output_path()
+        .clone();
+    let staged_sink = ParallelFileSinkExec::with_subdirectory(
+        file_sink,
+        &format!("{STAGING_DIR}/{job_id}"),
+    )?;
+    let sink = Arc::new(staged_sink).with_new_children(vec![input])?;
+    let writers = ParallelFileSinkExec::try_new(sink)?;
+    let object_store_url = writers.object_store_url().clone();
+    let staging_path = writers.output_path().clone();
+    Ok(Some(Arc::new(FileSinkCommitExec::new(
+        Arc::new(CoalescePartitionsExec::new(Arc::new(writers))),
+        object_store_url,
+        output_path,
+        staging_path,
+    ))))
+}
+
+/// Splits the aggregates computed in a single step over the merged partitions of their input
+/// into a partial aggregate of every partition and a final aggregate of the merged partial
+/// results. The partial aggregate then runs in the stage computing the partitions, so that
+/// only its results are shuffled, as for the aggregates split by the DataFusion planner.
+fn split_single_aggregates(
+    execution_plan: Arc<dyn ExecutionPlan>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let transformed = execution_plan.transform_up(&|plan: Arc<dyn ExecutionPlan>| {
+        let Some(aggregate) = plan.as_any().downcast_ref::<AggregateExec>() else {
+            return Ok(Transformed::no(plan));
+        };
+        let Some(coalesce) = aggregate
+            .input()
+            .as_any()
+            .downcast_ref::<CoalescePartitionsExec>()
+        else {
+            return Ok(Transformed::no(plan));
+        };
+        // ordered aggregates rely on the order of their whole input
+        if *aggregate.mode() != AggregateMode::Single
+            || coalesce
+                .input()
+                .properties()
+                .output_partitioning()
+                .partition_count()
+                == 1
+            || aggregate
+                .aggr_expr()
+                .iter()
+                .any(|expr| expr.order_bys().is_some())
+        {
+            return Ok(Transformed::no(plan));
+        }
+
+        let partial = Arc::new(AggregateExec::try_new(
+            AggregateMode::Partial,
+            aggregate.group_expr().clone(),
+            aggregate.aggr_expr().to_vec(),
+            aggregate.filter_expr().to_vec(),
+            coalesce.input().clone(),
+            aggregate.input_schema(),
+        )?);
+        let final_aggregate = AggregateExec::try_new(
+            AggregateMode::Final,
+            partial.group_expr().as_final(),
+            partial.aggr_expr().to_vec(),
+            partial.filter_expr().to_vec(),
+            Arc::new(CoalescePartitionsExec::new(partial.clone())),
+            aggregate.input_schema(),
+        )?
+        .with_limit(aggregate.limit());
+        Ok(Transformed::yes(
+            Arc::new(final_aggregate) as Arc<dyn ExecutionPlan>
+        ))
+    })?;
+    Ok(transformed.data)
+}
+
+/// Regroups the files read by the file scans of the plan, so that every task of a scan reads
+/// about `target_bytes` bytes of files rather than the number of files which DataFusion
+/// planned per partition, using the sizes listed from the object store at planning time.
+/// Scans with an output ordering keep their file groups, as it relies on the files order.
+pub fn balance_scan_file_groups(
+    execution_plan: Arc<dyn ExecutionPlan>,
+    target_bytes: usize,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let transformed = execution_plan.transform_up(&|plan: Arc<dyn ExecutionPlan>| {
+        let any = plan.as_any();
+        let base_config = if let Some(exec) = any.downcast_ref::<ParquetExec>() {
+            exec.base_config()
+        } else if let Some(exec) = any.downcast_ref::<CsvExec>() {
+            exec.base_config()
+        } else if let Some(exec) = any.downcast_ref::<NdJsonExec>() {
+            exec.base_config()
+        } else if let Some(exec) = any.downcast_ref::<AvroExec>() {
+            exec.base_config()
+        } else {
+            return Ok(Transformed::no(plan));
+        };
+        if !base_config.output_ordering.is_empty() {
+            return Ok(Transformed::no(plan));
+        }
+        let file_groups = bin_pack_files(&base_config.file_groups, target_bytes);
+        if file_groups.len() == base_config.file_groups.len() {
+            return Ok(Transformed::no(plan));
+        }
+        debug!(
+            "Regrouping {} file groups of scan into {} groups of about {} bytes",
+            base_config.file_groups.len(),
+            file_groups.len(),
+            target_bytes
+        );
+
+        let base_config = FileScanConfig {
+            file_groups,
+            ..base_config.clone()
+        };
+        // the compression of the files is not part of the serialized scans which the
+        // executors run, the regrouped scans read uncompressed files likewise
+        let balanced: Arc<dyn ExecutionPlan> =
+            if let Some(exec) = any.downcast_ref::<ParquetExec>() {
+                let options = exec.table_parquet_options().clone();
+                Arc::new(ParquetExec::new(
+                    base_config,
+                    exec.predicate().cloned(),
+                    options.global.metadata_size_hint,
+                    options,
+                ))
+            } else if let Some(exec) = any.downcast_ref::<CsvExec>() {
+                Arc::new(CsvExec::new(
+                    base_config,
+                    exec.has_header(),
+                    exec.delimiter(),
+                    exec.quote(),
+                    exec.escape(),
+                    FileCompressionType::UNCOMPRESSED,
+                ))
+            } else if any.is::<NdJsonExec>() {
+                Arc::new(NdJsonExec::new(
+                    base_config,
+                    FileCompressionType::UNCOMPRESSED,
+                ))
+            } else {
+                Arc::new(AvroExec::new(base_config))
+            };
+        Ok(Transformed::yes(balanced))
+    })?;
+    Ok(transformed.data)
+}
+
+/// Packs the files of the given groups into as few groups as needed for every group to hold
+/// about `target_bytes` bytes, placing the largest files first into the smallest group so far.
+/// The files of every group keep their original order.
+fn bin_pack_files(
+    file_groups: &[Vec<PartitionedFile>],
+    target_bytes: usize,
+) -> Vec<Vec<PartitionedFile>> {
+    let file_size = |file: &PartitionedFile| match &file.range {
+        Some(range) => (range.end - range.start) as usize,
+        None => file.object_meta.size,
+    };
+    let mut files: Vec<(usize, &PartitionedFile)> =
+        file_groups.iter().flatten().enumerate().collect();
+    if files.is_empty() {
+        return file_groups.to_vec();
+    }
+    let total_bytes: usize = files.iter().map(|(_, file)| file_size(file)).sum();
+    let group_count = total_bytes
+        .div_ceil(target_bytes.max(1))
+        .clamp(1, files.len());
+
+    files.sort_by_key(|(index, file)| (Reverse(file_size(file)), *index));
+    let mut groups: Vec<Vec<(usize, &PartitionedFile)>> = vec![vec![]; group_count];
+    let mut smallest_groups: BinaryHeap<Reverse<(usize, usize)>> =
+        (0..group_count).map(|group| Reverse((0, group))).collect();
+    for (index, file) in files {
+        let Reverse((bytes, group)) = smallest_groups.pop().expect("no file group");
+        groups[group].push((index, file));
+        smallest_groups.push(Reverse((bytes + file_size(file), group)));
+    }
+    groups
+        .into_iter()
+        .map(|mut group| {
+            group.sort_by_key(|(index, _)| *index);
+            group.into_iter().map(|(_, file)| file.clone()).collect()
+        })
+        .collect()
+}
+
+/// Create the leaf reading the output of the stage computed by the given shuffle writer
+pub fn create_unresolved_shuffle(
     shuffle_writer: &ShuffleWriterExec,
 ) -> Arc<UnresolvedShuffleExec> {
-    Arc::new(UnresolvedShuffleExec::new(
+    Arc::new(UnresolvedShuffleExec::new_with_partitioning(
         shuffle_writer.stage_id(),
         shuffle_writer.schema(),
-        shuffle_writer
-            .properties()
-            .output_partitioning()
-            .partition_count(),
+        shuffle_writer.properties().output_partitioning().clone(),
+    ))
+}
+
+/// Describes an exchange of the given input with the given partitioning, so that identical
+/// exchanges can be detected. Returns `None` if the input reads from sources whose display
+/// does not identify the data they produce, as such exchanges cannot be safely reused.
+fn exchange_key(
+    input: &Arc<dyn ExecutionPlan>,
+    partitioning: Option<&Partitioning>,
+) -> Option<String> {
+    if !has_reusable_sources(input) {
+        return None;
+    }
+    // the display of UnresolvedShuffleExec does not include the stage it reads from
+    let input_stages = find_unresolved_shuffles(input)
+        .ok()?
+        .iter()
+        .map(|shuffle| shuffle.stage_id.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    Some(format!(
+        "{partitioning:?}\n{input_stages}\n{}",
+        DisplayableExecutionPlan::new(input.as_ref()).indent(true)
     ))
 }
 
+/// Whether all leaves of the plan are either file scans, whose verbose display lists all
+/// the files they read, or reads of other stages
+fn has_reusable_sources(plan: &Arc<dyn ExecutionPlan>) -> bool {
+    let children = plan.children();
+    if children.is_empty() {
+        let any = plan.as_any();
+        any.is::<UnresolvedShuffleExec>()
+            || any.is::<ParquetExec>()
+            || any.is::<CsvExec>()
+            || any.is::<NdJsonExec>()
+            || any.is::<ArrowExec>()
+    } else {
+        children.iter().all(has_reusable_sources)
+    }
+}
+
+/// Removes the stages which compute the same output as an earlier stage, e.g. for a
+/// subquery which appears several times in the plan or for both sides of a self join, so
+/// that the consumers of such stages share the output of a single stage. The stages are
+/// expected in the order returned by [DistributedPlanner::plan_query_stages], i.e. every
+/// stage after the stages it reads from.
+pub fn deduplicate_stages(
+    job_id: &str,
+    stages: Vec<Arc<ShuffleWriterExec>>,
+) -> Result<Vec<Arc<ShuffleWriterExec>>> {
+    let final_stage_id = stages.last().map(|stage| stage.stage_id());
+    // Map from stage key -> ID of the first stage with that key
+    let mut stage_keys: HashMap<String, usize> = HashMap::new();
+    // Map from ID of a removed stage -> ID of the stage replacing it
+    let mut replaced_stages: HashMap<usize, usize> = HashMap::new();
+    let mut deduplicated = Vec::with_capacity(stages.len());
+
+    for stage in stages {
+        let stage = if replaced_stages.is_empty() {
+            stage
+        } else {
+            let input =
+                replace_stage_inputs(stage.children()[0].clone(), &replaced_stages)?;
+            create_shuffle_writer(
+                job_id,
+                stage.stage_id(),
+                input,
+                stage.shuffle_output_partitioning().cloned(),
+            )?
+        };
+
+        if Some(stage.stage_id()) != final_stage_id {
+            if let Some(key) =
+                exchange_key(&stage.children()[0], stage.shuffle_output_partitioning())
+            {
+                if let Some(existing_stage_id) = stage_keys.get(&key) {
+                    debug!(
+                        "Stage {} of job {} is replaced by identical stage {}",
+                        stage.stage_id(),
+                        job_id,
+                        existing_stage_id
+                    );
+                    replaced_stages.insert(stage.stage_id(), *existing_stage_id);
+                    continue;
+                }
+                stage_keys.insert(key, stage.stage_id());
+            }
+        }
+        deduplicated.push(stage);
+    }
+
+    if !replaced_stages.is_empty() {
+        info!(
+            "Removed {} duplicate stages of job {}",
+            replaced_stages.len(),
+            job_id
+        );
+    }
+    Ok(deduplicated)
+}
+
+/// Points the unresolved shuffles and the runtime filters of the plan which depend on a
+/// replaced stage to the stage replacing it
+fn replace_stage_inputs(
+    plan: Arc<dyn ExecutionPlan>,
+    replaced_stages: &HashMap<usize, usize>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    if let Some(unresolved_shuffle) =
+        plan.as_any().downcast_ref::<UnresolvedShuffleExec>()
+    {
+        return Ok(match replaced_stages.get(&unresolved_shuffle.stage_id) {
+            Some(stage_id) => Arc::new(UnresolvedShuffleExec::new_with_partitioning(
+                *stage_id,
+                unresolved_shuffle.schema(),
+                unresolved_shuffle
+                    .properties()
+                    .output_partitioning()
+                    .clone(),
+            )),
+            None => plan,
+        });
+    }
+
+    let children = plan.children();
+    if children.is_empty() {
+        return Ok(plan);
+    }
+    let children = children
+        .into_iter()
+        .map(|child| replace_stage_inputs(child, replaced_stages))
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(runtime_filter) = plan.as_any().downcast_ref::<RuntimeFilterExec>() {
+        if let Some(stage_id) = replaced_stages.get(&runtime_filter.build_stage_id) {
+            return Ok(Arc::new(RuntimeFilterExec::try_new(
+                children[0].clone(),
+                *stage_id,
+                runtime_filter.build_column,
+                runtime_filter.probe_column.clone(),
+            )?));
+        }
+    }
+    Ok(with_new_children_if_necessary(plan, children)?)
+}
+
 /// Returns the unresolved shuffles in the execution plan
 pub fn find_unresolved_shuffles(
     plan: &Arc<dyn ExecutionPlan>,
@@ -202,9 +674,14 @@ pub fn find_unresolved_shuffles(
     }
 }
 
+/// Replace the UnresolvedShuffleExec by ShuffleReaderExec reading the given partition
+/// locations. The readers of the `incomplete_inputs` stages of job `job_id`, which did not
+/// complete yet, get the remaining locations from the scheduler.
 pub fn remove_unresolved_shuffles(
     stage: Arc<dyn ExecutionPlan>,
     partition_locations: &HashMap<usize, HashMap<usize, Vec<PartitionLocation>>>,
+    job_id: &str,
+    incomplete_inputs: &HashSet<usize>,
 ) -> Result<Arc<dyn ExecutionPlan>> {
     let mut new_children: Vec<Arc<dyn ExecutionPlan>> = vec![];
     for child in stage.children() {
@@ -242,13 +719,24 @@ pub fn remove_unresolved_shuffles(
                     .collect::<Vec<_>>()
                     .join("\n")
             );
-            new_children.push(Arc::new(ShuffleReaderExec::try_new(
-                unresolved_shuffle.stage_id,
-                relevant_locations,
-                unresolved_shuffle.schema().clone(),
-            )?))
+            let pipelined_job_id = incomplete_inputs
+                .contains(&unresolved_shuffle.stage_id)
+                .then(|| job_id.to_owned());
+            new_children.push(Arc::new(
+                ShuffleReaderExec::try_new(
+                    unresolved_shuffle.stage_id,
+                    relevant_locations,
+                    unresolved_shuffle.schema().clone(),
+                )?
+                .with_pipelined_job_id(pipelined_job_id),
+            ))
         } else {
-            new_children.push(remove_unresolved_shuffles(child, partition_locations)?);
+            new_children.push(remove_unresolved_shuffles(
+                child,
+                partition_locations,
+                job_id,
+                incomplete_inputs,
+            )?);
         }
     }
     Ok(with_new_children_if_necessary(stage, new_children)?)
@@ -282,7 +770,9 @@ pub fn rollback_resolved_shuffles(
     Ok(with_new_children_if_necessary(stage, new_children)?)
 }
 
-fn create_shuffle_writer(
+/// Create the root of a stage computing the given plan, whose output is partitioned with
+/// `partitioning` if set, or keeps the partitioning of the plan
+pub fn create_shuffle_writer(
     job_id: &str,
     stage_id: usize,
     plan: Arc<dyn ExecutionPlan>,
@@ -299,19 +789,37 @@ fn create_shuffle_writer(
 
 #[cfg(test)]
 mod test {
-    use crate::planner::DistributedPlanner;
+    use crate::cluster::get_scan_files;
+    use crate::planner::{
+        balance_scan_file_groups, bin_pack_files, create_shuffle_writer,
+        deduplicate_stages, find_unresolved_shuffles, DistributedPlanner, StagePlanner,
+    };
+    use crate::state::execution_graph::ExecutionGraph;
     use crate::test_utils::datafusion_test_context;
     use ballista_core::error::BallistaError;
-    use ballista_core::execution_plans::UnresolvedShuffleExec;
+    use ballista_core::execution_plans::{
+        FileSinkCommitExec, MaterializedCteExec, ParallelFileSinkExec, ShuffleWriterExec,
+        UnresolvedShuffleExec,
+    };
     use ballista_core::serde::BallistaCodec;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+    use datafusion::common::ScalarValue;
+    use datafusion::datasource::listing::PartitionedFile;
+    use datafusion::datasource::physical_plan::CsvExec;
+    use datafusion::physical_expr::expressions::Column;
     use datafusion::physical_plan::aggregates::{AggregateExec, AggregateMode};
     use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+    use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+    use datafusion::physical_plan::insert::FileSinkExec;
     use datafusion::physical_plan::joins::HashJoinExec;
     use datafusion::physical_plan::projection::ProjectionExec;
+    use datafusion::physical_plan::repartition::RepartitionExec;
     use datafusion::physical_plan::sorts::sort::SortExec;
     use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
-    use datafusion::physical_plan::{displayable, ExecutionPlan};
-    use datafusion::prelude::SessionContext;
+    use datafusion::physical_plan::union::UnionExec;
+    use datafusion::physical_plan::{displayable, ExecutionPlan, Partitioning};
+    use datafusion::prelude::{CsvReadOptions, SessionContext};
     use datafusion_proto::physical_plan::AsExecutionPlan;
     use datafusion_proto::protobuf::LogicalPlanNode;
     use datafusion_proto::protobuf::PhysicalPlanNode;
@@ -626,6 +1134,514 @@ order by
         Ok(())
     }
 
+    #[tokio::test]
+    async fn distributed_write_plan() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let plan = session_state
+            .create_logical_plan(
+                "COPY (select l_returnflag, l_quantity from lineitem) \
+                TO '/tmp/ballista/output/' STORED AS PARQUET",
+            )
+            .await?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new();
+        let job_uuid = Uuid::new_v4();
+        let stages = planner.plan_query_stages(&job_uuid.to_string(), plan)?;
+        for (i, stage) in stages.iter().enumerate() {
+            println!("Stage {i}:\n{}", displayable(stage.as_ref()).indent(false));
+        }
+
+        /* Expected result:
+
+        ShuffleWriterExec: None
+          ParallelFileSinkExec: output=file:///tmp/ballista/output/_temporary/<job_id>
+            FileSinkExec: sink=ParquetSink(file_groups=[])
+              CsvExec: file_groups={2 groups: [[ballista/scheduler/testdata/lineitem/partition0.tbl], [ballista/scheduler/testdata/lineitem/partition1.tbl]]}, projection=[l_returnflag, l_quantity], has_header=false
+
+        ShuffleWriterExec: None
+          FileSinkCommitExec: output=file:///tmp/ballista/output, staging=tmp/ballista/output/_temporary/<job_id>
+            CoalescePartitionsExec
+              UnresolvedShuffleExec
+        */
+
+        assert_eq!(2, stages.len());
+
+        // verify stage 0
+        let stage0 = stages[0].children()[0].clone();
+        let writers = downcast_exec!(stage0, ParallelFileSinkExec);
+        assert_eq!(writers.properties().partitioning.partition_count(), 2);
+        downcast_exec!(writers.children()[0], FileSinkExec);
+
+        // verify stage 1
+        let stage1 = stages[1].children()[0].clone();
+        let commit = downcast_exec!(stage1, FileSinkCommitExec);
+        // the partitions are written to the staging directory of the job
+        assert_eq!(commit.output_path().as_ref(), "tmp/ballista/output");
+        assert_eq!(
+            commit.staging_path().as_ref(),
+            format!("tmp/ballista/output/_temporary/{job_uuid}")
+        );
+        assert_eq!(writers.output_path(), commit.staging_path());
+        let coalesce = commit.children()[0].clone();
+        let coalesce = downcast_exec!(coalesce, CoalescePartitionsExec);
+        let unresolved_shuffle = coalesce.children()[0].clone();
+        let unresolved_shuffle =
+            downcast_exec!(unresolved_shuffle, UnresolvedShuffleExec);
+        assert_eq!(unresolved_shuffle.stage_id, 1);
+        assert_eq!(unresolved_shuffle.output_partition_count, 2);
+
+        for stage in stages {
+            let roundtrip = roundtrip_operator(&ctx, stage.clone())?;
+            assert_eq!(
+                displayable(stage.as_ref()).indent(false).to_string(),
+                displayable(roundtrip.as_ref()).indent(false).to_string()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_self_join_plan_reuses_exchange() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "select a.l_orderkey, a.l_quantity, b.l_quantity
+            from lineitem a
+            join lineitem b on a.l_orderkey = b.l_orderkey",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new();
+        let job_id = Uuid::new_v4().to_string();
+        let stages = planner.plan_query_stages(&job_id, plan)?;
+        // both sides of the join are exchanged by a stage of their own
+        assert_eq!(3, stages.len());
+        let stages = deduplicate_stages(&job_id, stages)?;
+        for (i, stage) in stages.iter().enumerate() {
+            println!("Stage {i}:\n{}", displayable(stage.as_ref()).indent(false));
+        }
+
+        /* Expected result:
+
+        ShuffleWriterExec: Some(Hash([Column { name: "l_orderkey", index: 0 }], 2))
+          CsvExec: file_groups={2 groups: [[testdata/lineitem/partition0.tbl], [testdata/lineitem/partition1.tbl]]}, projection=[l_orderkey, l_quantity], has_header=false
+
+        ShuffleWriterExec: None
+          CoalesceBatchesExec: target_batch_size=8192
+            HashJoinExec: mode=Partitioned, join_type=Inner, on=[(l_orderkey@0, l_orderkey@0)], projection=[l_orderkey@0, l_quantity@1, l_quantity@3]
+              CoalesceBatchesExec: target_batch_size=8192
+                UnresolvedShuffleExec
+              CoalesceBatchesExec: target_batch_size=8192
+                UnresolvedShuffleExec
+        */
+
+        // both sides of the join read the output of the same stage
+        assert_eq!(2, stages.len());
+        let unresolved_shuffles =
+            find_unresolved_shuffles(&(stages[1].clone() as Arc<dyn ExecutionPlan>))?;
+        assert_eq!(2, unresolved_shuffles.len());
+        for unresolved_shuffle in unresolved_shuffles {
+            assert_eq!(stages[0].stage_id(), unresolved_shuffle.stage_id);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_plan_prunes_table_partitions() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let schema = Schema::new(vec![
+            Field::new("item", DataType::Utf8, false),
+            Field::new("amount", DataType::Int64, false),
+        ]);
+        let options = CsvReadOptions::new()
+            .schema(&schema)
+            .table_partition_cols(vec![("year".to_owned(), DataType::Utf8)]);
+        ctx.register_csv("sales", "testdata/sales", options).await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql("select item, sum(amount) from sales where year = '2024' group by item")
+            .await?;
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new();
+        let job_uuid = Uuid::new_v4();
+        let stages = planner.plan_query_stages(&job_uuid.to_string(), plan)?;
+
+        // only the files of the selected partition are scanned, by one task each
+        let scan_files = get_scan_files(stages[0].clone())?;
+        assert_eq!(1, scan_files.len());
+        assert_eq!(2, scan_files[0].len());
+        assert_eq!(
+            2,
+            stages[0].children()[0]
+                .output_partitioning()
+                .partition_count()
+        );
+        for file in scan_files[0].iter().flatten() {
+            assert_eq!(vec![ScalarValue::from("2024")], file.partition_values);
+        }
+
+        // the partition columns and values survive the serialization of the stage
+        let files = |plan: Arc<dyn ExecutionPlan>| -> Result<Vec<_>, BallistaError> {
+            Ok(get_scan_files(plan)?
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|file| (file.object_meta.location, file.partition_values))
+                .collect())
+        };
+        let stage0 = roundtrip_operator(&ctx, stages[0].clone())?;
+        assert_eq!(files(stages[0].clone())?, files(stage0.clone())?);
+        assert_eq!(stages[0].schema(), stage0.schema());
+
+        Ok(())
+    }
+
+    #[test]
+    fn bin_pack_files_by_size() {
+        let files = vec![
+            vec![
+                PartitionedFile::new("a", 100),
+                PartitionedFile::new("b", 10),
+            ],
+            vec![PartitionedFile::new("c", 60)],
+            vec![PartitionedFile::new("d", 50)],
+        ];
+        let paths = |groups: Vec<Vec<PartitionedFile>>| -> Vec<Vec<String>> {
+            groups
+                .iter()
+                .map(|group| {
+                    group
+                        .iter()
+                        .map(|file| file.object_meta.location.to_string())
+                        .collect()
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            vec![vec!["a", "b"], vec!["c", "d"]],
+            paths(bin_pack_files(&files, 110))
+        );
+        assert_eq!(
+            vec![vec!["a", "b", "c", "d"]],
+            paths(bin_pack_files(&files, 1000))
+        );
+        // a task reads at least one file
+        assert_eq!(
+            vec![vec!["a"], vec!["c"], vec!["d"], vec!["b"]],
+            paths(bin_pack_files(&files, 1))
+        );
+    }
+
+    #[tokio::test]
+    async fn distributed_plan_balances_scan_file_groups() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let schema = Schema::new(vec![
+            Field::new("item", DataType::Utf8, false),
+            Field::new("amount", DataType::Int64, false),
+        ]);
+        let options = CsvReadOptions::new()
+            .schema(&schema)
+            .table_partition_cols(vec![("year".to_owned(), DataType::Utf8)]);
+        ctx.register_csv("sales", "testdata/sales", options).await?;
+        let session_state = ctx.state();
+
+        let df = ctx.sql("select item, amount from sales").await?;
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+        assert_eq!(2, get_scan_files(plan.clone())?[0].len());
+
+        // the three files hold 68 bytes
+        let balanced = balance_scan_file_groups(plan.clone(), 1)?;
+        let scan_files = get_scan_files(balanced.clone())?;
+        assert_eq!(3, scan_files[0].len());
+        assert_eq!(3, balanced.output_partitioning().partition_count());
+        assert_eq!(plan.schema(), balanced.schema());
+
+        let balanced = balance_scan_file_groups(plan, 1024)?;
+        let scan_files = get_scan_files(balanced.clone())?;
+        assert_eq!(1, scan_files[0].len());
+        assert_eq!(3, scan_files[0][0].len());
+
+        // the regrouped scan is planned and serialized as any other scan
+        let mut planner = DistributedPlanner::new();
+        let job_uuid = Uuid::new_v4();
+        let stages = planner.plan_query_stages(&job_uuid.to_string(), balanced)?;
+        let stage = roundtrip_operator(&ctx, stages[0].clone())?;
+        let scan_files = get_scan_files(stage.clone())?;
+        assert_eq!(3, scan_files[0][0].len());
+        stage.apply(&mut |plan| {
+            if let Some(scan) = plan.as_any().downcast_ref::<CsvExec>() {
+                assert!(scan.has_header());
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_plan_skips_redundant_exchange() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx.sql("select l_orderkey from lineitem").await?;
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let partitioning =
+            Partitioning::Hash(vec![Arc::new(Column::new("l_orderkey", 0))], 2);
+        let plan = Arc::new(RepartitionExec::try_new(plan, partitioning.clone())?);
+        let plan = Arc::new(RepartitionExec::try_new(plan, partitioning)?);
+
+        let mut planner = DistributedPlanner::new();
+        let job_uuid = Uuid::new_v4();
+        let stages = planner.plan_query_stages(&job_uuid.to_string(), plan)?;
+
+        // the second exchange reads the output of the first one, which is already
+        // partitioned as required
+        assert_eq!(2, stages.len());
+        let unresolved_shuffle = stages[1].children()[0].clone();
+        let unresolved_shuffle =
+            downcast_exec!(unresolved_shuffle, UnresolvedShuffleExec);
+        assert_eq!(stages[0].stage_id(), unresolved_shuffle.stage_id);
+        assert!(stages[1].shuffle_output_partitioning().is_none());
+
+        Ok(())
+    }
+
+    /// Plans the whole plan of a job as a single stage
+    #[derive(Debug)]
+    struct SingleStagePlanner;
+
+    impl StagePlanner for SingleStagePlanner {
+        fn plan_query_stages(
+            &self,
+            job_id: &str,
+            execution_plan: Arc<dyn ExecutionPlan>,
+        ) -> Result<Vec<Arc<ShuffleWriterExec>>, BallistaError> {
+            Ok(vec![create_shuffle_writer(
+                job_id,
+                1,
+                execution_plan,
+                None,
+            )?])
+        }
+    }
+
+    #[tokio::test]
+    async fn execution_graph_with_custom_stage_planner() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx.sql("select l_orderkey from lineitem").await?;
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+        let partitioning =
+            Partitioning::Hash(vec![Arc::new(Column::new("l_orderkey", 0))], 2);
+        let plan: Arc<dyn ExecutionPlan> =
+            Arc::new(RepartitionExec::try_new(plan, partitioning)?);
+
+        let graph =
+            ExecutionGraph::new("scheduler", "job", "", "session", plan.clone(), 0)?;
+        assert_eq!(2, graph.stage_count());
+
+        let graph = ExecutionGraph::new_with_stage_planner(
+            "scheduler",
+            "job",
+            "",
+            "session",
+            plan,
+            0,
+            &SingleStagePlanner,
+        )?;
+        assert_eq!(1, graph.stage_count());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_plan_splits_single_aggregate() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql("select sum(l_extendedprice) as total from lineitem")
+            .await?;
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        // aggregate the merged partitions of the scan in a single step
+        let partial = find_partial_aggregate(&plan).expect("partial aggregate");
+        let partial = downcast_exec!(partial, AggregateExec);
+        let plan = Arc::new(AggregateExec::try_new(
+            AggregateMode::Single,
+            partial.group_expr().clone(),
+            partial.aggr_expr().to_vec(),
+            partial.filter_expr().to_vec(),
+            Arc::new(CoalescePartitionsExec::new(partial.input().clone())),
+            partial.input_schema(),
+        )?);
+
+        let mut planner = DistributedPlanner::new();
+        let job_uuid = Uuid::new_v4();
+        let stages = planner.plan_query_stages(&job_uuid.to_string(), plan)?;
+
+        // the partial aggregate runs in the scan stage and only its results are merged
+        assert_eq!(2, stages.len());
+        let partial = stages[0].children()[0].clone();
+        let partial = downcast_exec!(partial, AggregateExec);
+        assert_eq!(*partial.mode(), AggregateMode::Partial);
+        assert_eq!(
+            2,
+            partial.properties().output_partitioning().partition_count()
+        );
+
+        let final_aggregate = stages[1].children()[0].clone();
+        let final_aggregate = downcast_exec!(final_aggregate, AggregateExec);
+        assert_eq!(*final_aggregate.mode(), AggregateMode::Final);
+        let coalesce = final_aggregate.children()[0].clone();
+        let coalesce = downcast_exec!(coalesce, CoalescePartitionsExec);
+        let unresolved_shuffle = coalesce.children()[0].clone();
+        let unresolved_shuffle =
+            downcast_exec!(unresolved_shuffle, UnresolvedShuffleExec);
+        assert_eq!(stages[0].stage_id(), unresolved_shuffle.stage_id);
+
+        Ok(())
+    }
+
+    fn find_partial_aggregate(
+        plan: &Arc<dyn ExecutionPlan>,
+    ) -> Option<Arc<dyn ExecutionPlan>> {
+        match plan.as_any().downcast_ref::<AggregateExec>() {
+            Some(aggregate) if *aggregate.mode() == AggregateMode::Partial => {
+                Some(plan.clone())
+            }
+            _ => plan.children().iter().find_map(find_partial_aggregate),
+        }
+    }
+
+    #[tokio::test]
+    async fn deduplicate_repeated_subquery_stages() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "with t as (select l_orderkey, l_quantity from lineitem order by l_quantity limit 10)
+            select a.l_orderkey, a.l_quantity, b.l_quantity
+            from t a
+            join t b on a.l_orderkey = b.l_orderkey",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new();
+        let job_uuid = Uuid::new_v4();
+        let job_id = job_uuid.to_string();
+        let stages = planner.plan_query_stages(&job_id, plan)?;
+        let deduplicated = deduplicate_stages(&job_id, stages.clone())?;
+        for (i, stage) in deduplicated.iter().enumerate() {
+            println!("Stage {i}:\n{}", displayable(stage.as_ref()).indent(false));
+        }
+
+        // the subquery is computed by a single set of stages, read by both sides of the join
+        assert!(deduplicated.len() < stages.len());
+        let final_stage = deduplicated.last().unwrap().clone() as Arc<dyn ExecutionPlan>;
+        let unresolved_shuffles = find_unresolved_shuffles(&final_stage)?;
+        assert_eq!(2, unresolved_shuffles.len());
+        assert_eq!(
+            unresolved_shuffles[0].stage_id,
+            unresolved_shuffles[1].stage_id
+        );
+
+        // every stage reads stages which were kept
+        let stage_ids = deduplicated
+            .iter()
+            .map(|stage| stage.stage_id())
+            .collect::<Vec<_>>();
+        for stage in &deduplicated {
+            let stage = stage.clone() as Arc<dyn ExecutionPlan>;
+            for unresolved_shuffle in find_unresolved_shuffles(&stage)? {
+                assert!(stage_ids.contains(&unresolved_shuffle.stage_id));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_plan_materialized_cte() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql("select l_orderkey, l_quantity from lineitem")
+            .await?;
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        // two references of the same subquery
+        let references: Vec<Arc<dyn ExecutionPlan>> = vec![
+            Arc::new(MaterializedCteExec::new(plan.clone(), 0, "cte")),
+            Arc::new(MaterializedCteExec::new(plan, 0, "cte")),
+        ];
+        let plan = Arc::new(UnionExec::new(references));
+
+        let mut planner = DistributedPlanner::new();
+        let job_uuid = Uuid::new_v4();
+        let stages = planner.plan_query_stages(&job_uuid.to_string(), plan)?;
+        for (i, stage) in stages.iter().enumerate() {
+            println!("Stage {i}:\n{}", displayable(stage.as_ref()).indent(false));
+        }
+
+        /* Expected result:
+
+        ShuffleWriterExec: None
+          CsvExec: file_groups={2 groups: [[testdata/lineitem/partition0.tbl], [testdata/lineitem/partition1.tbl]]}, projection=[l_orderkey, l_quantity], has_header=false
+
+        ShuffleWriterExec: None
+          UnionExec
+            UnresolvedShuffleExec
+            UnresolvedShuffleExec
+        */
+
+        assert_eq!(2, stages.len());
+        let union = stages[1].children()[0].clone();
+        let union = downcast_exec!(union, UnionExec);
+        for input in union.children() {
+            let unresolved_shuffle = downcast_exec!(input, UnresolvedShuffleExec);
+            assert_eq!(stages[0].stage_id(), unresolved_shuffle.stage_id);
+            assert_eq!(2, unresolved_shuffle.output_partition_count);
+        }
+
+        Ok(())
+    }
+
     fn roundtrip_operator(
         ctx: &SessionContext,
         plan: Arc<dyn ExecutionPlan>,
@@ -0,0 +1,464 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Pruning of the row groups of the parquet files scanned by a job when planning the job,
+//! with the statistics of the row groups, so that the tasks of the scans only read the row
+//! groups which may match the filters of the scans. The pages of the row groups are pruned
+//! by the executors with the page index, as the scans do without planning time pruning.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use ballista_core::error::Result;
+use datafusion::arrow::array::{ArrayRef, BooleanArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, SchemaRef};
+use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
+use datafusion::common::{Column, ScalarValue};
+use datafusion::datasource::listing::{FileRange, PartitionedFile};
+use datafusion::datasource::physical_plan::{FileScanConfig, ParquetExec};
+use datafusion::error::DataFusionError;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::parquet::arrow::async_reader::{AsyncFileReader, ParquetObjectReader};
+use datafusion::parquet::file::metadata::{ParquetMetaData, RowGroupMetaData};
+use datafusion::parquet::file::statistics::Statistics;
+use datafusion::physical_optimizer::pruning::{PruningPredicate, PruningStatistics};
+use datafusion::physical_plan::ExecutionPlan;
+use futures::{StreamExt, TryStreamExt};
+use log::{debug, warn};
+use object_store::path::Path;
+
+/// Max number of parquet footers read at once
+const METADATA_FETCH_CONCURRENCY: usize = 32;
+
+/// Prunes the row groups of the files of the parquet scans of the plan which have a pruning
+/// predicate. The files whose row groups are all pruned are removed from the scans, and the
+/// other files are split into byte ranges covering the runs of row groups kept, which the
+/// scans read as the ranges of the files split by DataFusion. The file groups left empty are
+/// removed, so that no task is planned for them.
+pub async fn prune_row_groups(
+    plan: Arc<dyn ExecutionPlan>,
+    runtime_env: &RuntimeEnv,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let mut files = HashMap::new();
+    plan.apply(&mut |plan| {
+        if let Some(exec) = plan.as_any().downcast_ref::<ParquetExec>() {
+            if exec.pruning_predicate().is_some() {
+                let base_config = exec.base_config();
+                for file in base_config.file_groups.iter().flatten() {
+                    files.insert(
+                        file.object_meta.location.clone(),
+                        (
+                            base_config.object_store_url.clone(),
+                            file.object_meta.clone(),
+                        ),
+                    );
+                }
+            }
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    if files.is_empty() {
+        return Ok(plan);
+    }
+
+    let metadata: HashMap<Path, Arc<ParquetMetaData>> =
+        futures::stream::iter(files.into_values())
+            .map(|(object_store_url, object_meta)| async move {
+                let store = runtime_env.object_store(&object_store_url)?;
+                let location = object_meta.location.clone();
+                let mut reader = ParquetObjectReader::new(store, object_meta);
+                match reader.get_metadata().await {
+                    Ok(metadata) => Ok::<_, DataFusionError>(Some((location, metadata))),
+                    Err(e) => {
+                        // the row groups of the file are all read by the scan
+                        warn!(
+                            "Failed to read the metadata of parquet file {location}: {e}"
+                        );
+                        Ok(None)
+                    }
+                }
+            })
+            .buffer_unordered(METADATA_FETCH_CONCURRENCY)
+            .try_filter_map(|metadata| async move { Ok(metadata) })
+            .try_collect()
+            .await?;
+
+    let transformed = plan.transform_up(&|plan: Arc<dyn ExecutionPlan>| {
+        let Some(exec) = plan.as_any().downcast_ref::<ParquetExec>() else {
+            return Ok(Transformed::no(plan));
+        };
+        let Some(predicate) = exec.pruning_predicate() else {
+            return Ok(Transformed::no(plan));
+        };
+        let base_config = exec.base_config();
+        let mut pruned = false;
+        let mut file_groups = vec![];
+        for group in &base_config.file_groups {
+            let mut files = vec![];
+            for file in group {
+                match metadata.get(&file.object_meta.location) {
+                    Some(metadata) => {
+                        let kept = prune_file(file, metadata, predicate)?;
+                        pruned |= kept.len() != 1 || kept[0].range != file.range;
+                        files.extend(kept);
+                    }
+                    None => files.push(file.clone()),
+                }
+            }
+            if !files.is_empty() {
+                file_groups.push(files);
+            }
+        }
+        if !pruned {
+            return Ok(Transformed::no(plan));
+        }
+        debug!(
+            "Pruned the row groups of parquet scan of {} file groups into {} file groups",
+            base_config.file_groups.len(),
+            file_groups.len()
+        );
+        if file_groups.is_empty() {
+            // the scan still runs a task, which reads nothing
+            file_groups.push(vec![]);
+        }
+
+        let options = exec.table_parquet_options().clone();
+        let pruned_exec = ParquetExec::new(
+            FileScanConfig {
+                file_groups,
+                ..base_config.clone()
+            },
+            exec.predicate().cloned(),
+            options.global.metadata_size_hint,
+            options,
+        );
+        Ok(Transformed::yes(
+            Arc::new(pruned_exec) as Arc<dyn ExecutionPlan>
+        ))
+    })?;
+    Ok(transformed.data)
+}
+
+/// Returns the parts of the file holding the row groups which may match the predicate,
+/// among the row groups the file, or its range, is scanned for. The file is returned as is
+/// when none of its row groups are pruned.
+fn prune_file(
+    file: &PartitionedFile,
+    metadata: &ParquetMetaData,
+    predicate: &PruningPredicate,
+) -> datafusion::error::Result<Vec<PartitionedFile>> {
+    let row_groups = metadata.row_groups();
+    if row_groups
+        .iter()
+        .any(|row_group| row_group.num_columns() == 0)
+    {
+        return Ok(vec![file.clone()]);
+    }
+    // as for the ranges of files split by DataFusion, a row group is read for the range
+    // holding the first page of its first column
+    let offsets: Vec<i64> = row_groups
+        .iter()
+        .map(|row_group| {
+            let column = row_group.column(0);
+            column
+                .dictionary_page_offset()
+                .unwrap_or_else(|| column.data_page_offset())
+        })
+        .collect();
+    let scanned = |index: usize| match &file.range {
+        Some(range) => range.start <= offsets[index] && offsets[index] < range.end,
+        None => true,
+    };
+
+    let statistics = RowGroupStatistics {
+        schema: predicate.schema().clone(),
+        metadata,
+    };
+    let matched = predicate.prune(&statistics)?;
+    if (0..row_groups.len()).all(|index| !scanned(index) || matched[index]) {
+        return Ok(vec![file.clone()]);
+    }
+
+    let mut files = vec![];
+    let mut run_start = None;
+    for index in 0..=row_groups.len() {
+        let kept = index < row_groups.len() && scanned(index) && matched[index];
+        match run_start {
+            None if kept => run_start = Some(offsets[index]),
+            Some(start) if !kept => {
+                let end = offsets
+                    .get(index)
+                    .copied()
+                    .unwrap_or(file.object_meta.size as i64);
+                files.push(PartitionedFile {
+                    range: Some(FileRange { start, end }),
+                    ..file.clone()
+                });
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    Ok(files)
+}
+
+/// The statistics of the row groups of a parquet file, for the columns of the schema of the
+/// pruning predicate stored as top level columns of the file
+struct RowGroupStatistics<'a> {
+    schema: SchemaRef,
+    metadata: &'a ParquetMetaData,
+}
+
+impl RowGroupStatistics<'_> {
+    fn column_index(&self, column: &Column) -> Option<usize> {
+        self.metadata
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .position(|descr| descr.path().parts() == [column.name.as_str()])
+    }
+
+    fn values(
+        &self,
+        column: &Column,
+        value: impl Fn(&Statistics, &DataType) -> Option<ScalarValue>,
+    ) -> Option<ArrayRef> {
+        let data_type = self.schema.field_with_name(&column.name).ok()?.data_type();
+        let index = self.column_index(column)?;
+        let null = ScalarValue::try_from(data_type).ok()?;
+        let values = self.row_groups().map(|row_group| {
+            row_group
+                .column(index)
+                .statistics()
+                .filter(|statistics| statistics.has_min_max_set())
+                .and_then(|statistics| value(statistics, data_type))
+                .unwrap_or_else(|| null.clone())
+        });
+        ScalarValue::iter_to_array(values).ok()
+    }
+
+    fn row_groups(&self) -> impl Iterator<Item = &RowGroupMetaData> {
+        self.metadata.row_groups().iter()
+    }
+}
+
+impl PruningStatistics for RowGroupStatistics<'_> {
+    fn min_values(&self, column: &Column) -> Option<ArrayRef> {
+        self.values(column, |statistics, data_type| {
+            statistics_value(statistics, data_type, true)
+        })
+    }
+
+    fn max_values(&self, column: &Column) -> Option<ArrayRef> {
+        self.values(column, |statistics, data_type| {
+            statistics_value(statistics, data_type, false)
+        })
+    }
+
+    fn num_containers(&self) -> usize {
+        self.metadata.num_row_groups()
+    }
+
+    fn null_counts(&self, column: &Column) -> Option<ArrayRef> {
+        let index = self.column_index(column)?;
+        let null_counts: UInt64Array = self
+            .row_groups()
+            .map(|row_group| {
+                row_group
+                    .column(index)
+                    .statistics()
+                    .map(|statistics| statistics.null_count())
+            })
+            .collect();
+        Some(Arc::new(null_counts))
+    }
+
+    fn row_counts(&self, _column: &Column) -> Option<ArrayRef> {
+        let row_counts: UInt64Array = self
+            .row_groups()
+            .map(|row_group| Some(row_group.num_rows() as u64))
+            .collect();
+        Some(Arc::new(row_counts))
+    }
+
+    fn contained(
+        &self,
+        _column: &Column,
+        _values: &HashSet<ScalarValue>,
+    ) -> Option<BooleanArray> {
+        None
+    }
+}
+
+/// The min or max value of the statistics of a column chunk as a value of the arrow type of
+/// the column, for the types whose values are stored as is by parquet. `None` for the other
+/// types, whose row groups are not pruned by the column.
+fn statistics_value(
+    statistics: &Statistics,
+    data_type: &DataType,
+    min: bool,
+) -> Option<ScalarValue> {
+    macro_rules! pick {
+        ($statistics: expr) => {
+            if min {
+                $statistics.min()
+            } else {
+                $statistics.max()
+            }
+        };
+    }
+    match (statistics, data_type) {
+        (Statistics::Boolean(s), DataType::Boolean) => {
+            Some(ScalarValue::Boolean(Some(*pick!(s))))
+        }
+        (Statistics::Int32(s), DataType::Int8) => i8::try_from(*pick!(s))
+            .ok()
+            .map(|v| ScalarValue::Int8(Some(v))),
+        (Statistics::Int32(s), DataType::Int16) => i16::try_from(*pick!(s))
+            .ok()
+            .map(|v| ScalarValue::Int16(Some(v))),
+        (Statistics::Int32(s), DataType::Int32) => {
+            Some(ScalarValue::Int32(Some(*pick!(s))))
+        }
+        (Statistics::Int32(s), DataType::Date32) => {
+            Some(ScalarValue::Date32(Some(*pick!(s))))
+        }
+        (Statistics::Int64(s), DataType::Int64) => {
+            Some(ScalarValue::Int64(Some(*pick!(s))))
+        }
+        (Statistics::Float(s), DataType::Float32) => {
+            Some(ScalarValue::Float32(Some(*pick!(s))))
+        }
+        (Statistics::Double(s), DataType::Float64) => {
+            Some(ScalarValue::Float64(Some(*pick!(s))))
+        }
+        (Statistics::ByteArray(s), DataType::Utf8) => pick!(s)
+            .as_utf8()
+            .ok()
+            .map(|v| ScalarValue::Utf8(Some(v.to_owned()))),
+        (Statistics::ByteArray(s), DataType::LargeUtf8) => pick!(s)
+            .as_utf8()
+            .ok()
+            .map(|v| ScalarValue::LargeUtf8(Some(v.to_owned()))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::sync::Arc;
+
+    use ballista_core::error::Result;
+    use datafusion::arrow::array::Int64Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use datafusion::datasource::listing::FileRange;
+    use datafusion::parquet::arrow::ArrowWriter;
+    use datafusion::parquet::file::properties::WriterProperties;
+    use datafusion::physical_plan::{collect, ExecutionPlan};
+    use datafusion::prelude::{ParquetReadOptions, SessionContext};
+    use tempfile::TempDir;
+
+    use crate::cluster::get_scan_files;
+
+    use super::prune_row_groups;
+
+    /// Writes a parquet file of 4 row groups of 10 rows, with the values 0 to 39
+    fn write_parquet(dir: &TempDir) -> String {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int64, false)]));
+        let path = dir.path().join("data.parquet");
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(10)
+            .build();
+        let mut writer = ArrowWriter::try_new(
+            File::create(&path).unwrap(),
+            schema.clone(),
+            Some(properties),
+        )
+        .unwrap();
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![Arc::new(Int64Array::from_iter_values(0..40))],
+        )
+        .unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    async fn plan_and_prune(
+        ctx: &SessionContext,
+        sql: &str,
+    ) -> Result<(Arc<dyn ExecutionPlan>, Vec<Option<FileRange>>)> {
+        let plan = ctx.sql(sql).await?.create_physical_plan().await?;
+        let plan = prune_row_groups(plan, &ctx.runtime_env()).await?;
+        let ranges = get_scan_files(plan.clone())?
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|file| file.range)
+            .collect();
+        Ok((plan, ranges))
+    }
+
+    #[tokio::test]
+    async fn test_prune_row_groups() -> Result<()> {
+        let dir = TempDir::new().unwrap();
+        let path = write_parquet(&dir);
+        let ctx = SessionContext::new();
+        ctx.register_parquet("t", &path, ParquetReadOptions::default())
+            .await?;
+
+        // the last two row groups are read as a single range of the file
+        let (plan, ranges) =
+            plan_and_prune(&ctx, "select a from t where a >= 25").await?;
+        assert_eq!(1, ranges.len());
+        let range = ranges[0].clone().unwrap();
+        let rows: usize = collect(plan, ctx.task_ctx())
+            .await?
+            .iter()
+            .map(|batch| batch.num_rows())
+            .sum();
+        assert_eq!(15, rows);
+
+        // the first and last row groups are read as two ranges
+        let (plan, ranges) =
+            plan_and_prune(&ctx, "select a from t where a < 5 or a >= 35").await?;
+        assert_eq!(2, ranges.len());
+        assert_eq!(range.end, ranges[1].clone().unwrap().end);
+        let rows: usize = collect(plan, ctx.task_ctx())
+            .await?
+            .iter()
+            .map(|batch| batch.num_rows())
+            .sum();
+        assert_eq!(10, rows);
+
+        // all row groups are pruned
+        let (plan, ranges) =
+            plan_and_prune(&ctx, "select a from t where a > 100").await?;
+        assert!(ranges.is_empty());
+        assert!(collect(plan, ctx.task_ctx()).await?.is_empty());
+
+        // no row group is pruned
+        let (_, ranges) = plan_and_prune(&ctx, "select a from t where a >= 0").await?;
+        assert_eq!(vec![None], ranges);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,795 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Runtime filters of the partitioned hash joins. The stage scanning the probe side of such
+//! a join waits for the stage computing its build side, whose outputs report the min and max
+//! values of their columns. The scheduler then filters the probe side join key between the
+//! bounds of the build side join key, at the scan of the probe side, so that the rows which
+//! cannot match are neither shuffled nor, for parquet scans whose row groups are pruned by
+//! the filter, read.
+//!
+//! With bloom filters enabled, the build side stage also collects a bloom filter of its join
+//! key for every map task, whose union the scheduler pushes to the probe side stage
+//! along with the bounds. Only the probe side rows whose join key may be in the build side
+//! are then shuffled, i.e. the probe side is reduced by a semi-join with the build side.
+//! Unlike the bounds, the bloom filters are not part of the partition locations: the
+//! scheduler only keeps them in memory, in the inputs of the probe side stage, until the
+//! stage is resolved.
+//!
+//! The probe side stage being an output of the build side stage, it is also rolled back
+//! when the build side outputs are lost, e.g. with their executor.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use ballista_core::config::BallistaConfig;
+use ballista_core::error::Result;
+use ballista_core::execution_plans::{
+    supports_column_bounds, BloomFilter, BloomFilterExec, RuntimeFilterExec,
+    ShuffleWriterExec, UnresolvedShuffleExec,
+};
+use ballista_core::serde::scheduler::PartitionLocation;
+use datafusion::common::tree_node::{Transformed, TreeNode, TreeNodeRecursion};
+use datafusion::common::ScalarValue;
+use datafusion::datasource::physical_plan::ParquetExec;
+use datafusion::logical_expr::{JoinType, Operator};
+use datafusion::physical_expr::expressions::{BinaryExpr, Column, Literal};
+use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+use datafusion::physical_plan::filter::FilterExec;
+use datafusion::physical_plan::joins::{HashJoinExec, PartitionMode};
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::{
+    with_new_children_if_necessary, ExecutionPlan, PhysicalExpr,
+};
+use datafusion::prelude::SessionConfig;
+use log::debug;
+
+use crate::planner::{create_shuffle_writer, find_unresolved_shuffles, StagePlanner};
+
+/// The bloom filters of the outputs of the map tasks of a stage, by map partition id,
+/// each of them a list of the bloom filters of some columns by column index
+pub type MapBloomFilters = HashMap<usize, Vec<(usize, Arc<BloomFilter>)>>;
+
+/// A [StagePlanner] adding runtime filters to the joins of the stages planned by another
+/// planner
+#[derive(Debug)]
+pub struct RuntimeFilterStagePlanner<'a> {
+    inner: &'a dyn StagePlanner,
+    bloom_filters: bool,
+}
+
+impl<'a> RuntimeFilterStagePlanner<'a> {
+    pub fn new(inner: &'a dyn StagePlanner) -> Self {
+        Self {
+            inner,
+            bloom_filters: false,
+        }
+    }
+
+    /// Collect bloom filters of the build side join keys, for the runtime filters to also
+    /// filter the probe side join keys with
+    pub fn with_bloom_filters(mut self, bloom_filters: bool) -> Self {
+        self.bloom_filters = bloom_filters;
+        self
+    }
+
+    /// The planner adding the runtime filters enabled by the Ballista settings of a
+    /// session, `None` if the session enables none of them
+    pub fn for_session(
+        inner: &'a dyn StagePlanner,
+        session_config: &SessionConfig,
+    ) -> Option<Self> {
+        let (runtime_filters, bloom_filters) = session_config
+            .get_extension::<BallistaConfig>()
+            .map(|config| {
+                (
+                    config.join_runtime_filters(),
+                    config.join_bloom_filter_bytes().is_some(),
+                )
+            })
+            .unwrap_or_default();
+        (runtime_filters || bloom_filters)
+            .then(|| Self::new(inner).with_bloom_filters(bloom_filters))
+    }
+}
+
+impl StagePlanner for RuntimeFilterStagePlanner<'_> {
+    fn plan_query_stages(
+        &self,
+        job_id: &str,
+        execution_plan: Arc<dyn ExecutionPlan>,
+    ) -> Result<Vec<Arc<ShuffleWriterExec>>> {
+        let stages = self.inner.plan_query_stages(job_id, execution_plan)?;
+        add_runtime_filters(job_id, stages, self.bloom_filters)
+    }
+}
+
+/// A runtime filter of the probe side stage of a join
+struct RuntimeFilter {
+    probe_stage_id: usize,
+    probe_column: Column,
+    build_stage_id: usize,
+    build_column: usize,
+}
+
+/// Adds a [RuntimeFilterExec] to the probe side stage of every partitioned hash join whose
+/// probe side rows without a match are not part of its output, and whose first join keys
+/// are columns of a type whose bounds are collected. The probe side stage must only be read
+/// by the join, and must not be an input of the build side stage. With `bloom_filters`, the
+/// build side stage also collects a bloom filter of its join key.
+pub fn add_runtime_filters(
+    job_id: &str,
+    stages: Vec<Arc<ShuffleWriterExec>>,
+    bloom_filters: bool,
+) -> Result<Vec<Arc<ShuffleWriterExec>>> {
+    // Map from stage ID -> IDs of the stages it depends on
+    let mut dependencies: HashMap<usize, Vec<usize>> = HashMap::new();
+    // Map from stage ID -> number of readers of its output
+    let mut readers: HashMap<usize, usize> = HashMap::new();
+    let mut filters = vec![];
+    for stage in &stages {
+        let plan = stage.children()[0].clone();
+        for shuffle in find_unresolved_shuffles(&plan)? {
+            *readers.entry(shuffle.stage_id).or_default() += 1;
+        }
+        dependencies.insert(stage.stage_id(), stage_inputs(&plan)?);
+        plan.apply(&mut |plan| {
+            if let Some(filter) = plan
+                .as_any()
+                .downcast_ref::<HashJoinExec>()
+                .and_then(runtime_filter)
+            {
+                filters.push(filter);
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+    }
+
+    let mut filters_by_stage: HashMap<usize, RuntimeFilter> = HashMap::new();
+    // Map from stage ID -> columns of its output whose bloom filters are collected
+    let mut bloom_filter_columns: HashMap<usize, Vec<usize>> = HashMap::new();
+    for filter in filters {
+        if readers.get(&filter.probe_stage_id) != Some(&1)
+            || depends_on(&dependencies, filter.build_stage_id, filter.probe_stage_id)
+        {
+            continue;
+        }
+        dependencies
+            .entry(filter.probe_stage_id)
+            .or_default()
+            .push(filter.build_stage_id);
+        if bloom_filters {
+            bloom_filter_columns
+                .entry(filter.build_stage_id)
+                .or_default()
+                .push(filter.build_column);
+        }
+        filters_by_stage.insert(filter.probe_stage_id, filter);
+    }
+    if filters_by_stage.is_empty() {
+        return Ok(stages);
+    }
+
+    stages
+        .into_iter()
+        .map(|stage| {
+            let stage = match filters_by_stage.remove(&stage.stage_id()) {
+                Some(filter) => filter_probe_side(job_id, stage, &filter)?,
+                None => stage,
+            };
+            Ok(match bloom_filter_columns.remove(&stage.stage_id()) {
+                Some(columns) => {
+                    Arc::new(stage.as_ref().clone().with_bloom_filter_columns(columns))
+                }
+                None => stage,
+            })
+        })
+        .collect()
+}
+
+/// The probe side stage with its runtime filter
+fn filter_probe_side(
+    job_id: &str,
+    stage: Arc<ShuffleWriterExec>,
+    filter: &RuntimeFilter,
+) -> Result<Arc<ShuffleWriterExec>> {
+    debug!(
+        "Filtering stage {} of job {} with column {} of stage {}",
+        stage.stage_id(),
+        job_id,
+        filter.build_column,
+        filter.build_stage_id
+    );
+    let input = push_down_runtime_filter(stage.children()[0].clone(), filter)?;
+    create_shuffle_writer(
+        job_id,
+        stage.stage_id(),
+        input,
+        stage.shuffle_output_partitioning().cloned(),
+    )
+}
+
+/// The runtime filter of a join reading its build side and its probe side from stages
+fn runtime_filter(join: &HashJoinExec) -> Option<RuntimeFilter> {
+    if *join.partition_mode() != PartitionMode::Partitioned || join.null_equals_null() {
+        return None;
+    }
+    // the probe side rows without a match must not be part of the output
+    if !matches!(
+        join.join_type(),
+        JoinType::Inner
+            | JoinType::Left
+            | JoinType::LeftSemi
+            | JoinType::LeftAnti
+            | JoinType::RightSemi
+    ) {
+        return None;
+    }
+    let build_stage_id = read_stage(join.left())?;
+    let probe_stage_id = read_stage(join.right())?;
+    let (build_key, probe_key) = join.on().first()?;
+    let build_column = build_key.as_any().downcast_ref::<Column>()?;
+    let probe_column = probe_key.as_any().downcast_ref::<Column>()?;
+    let data_type = join
+        .left()
+        .schema()
+        .field(build_column.index())
+        .data_type()
+        .clone();
+    if !supports_column_bounds(&data_type) {
+        return None;
+    }
+    Some(RuntimeFilter {
+        probe_stage_id,
+        probe_column: probe_column.clone(),
+        build_stage_id,
+        build_column: build_column.index(),
+    })
+}
+
+/// The stage whose output is read by the plan as is
+fn read_stage(plan: &Arc<dyn ExecutionPlan>) -> Option<usize> {
+    if let Some(coalesce) = plan.as_any().downcast_ref::<CoalesceBatchesExec>() {
+        read_stage(coalesce.input())
+    } else {
+        plan.as_any()
+            .downcast_ref::<UnresolvedShuffleExec>()
+            .map(|shuffle| shuffle.stage_id)
+    }
+}
+
+/// The stages the plan depends on, i.e. reads from or is filtered by
+fn stage_inputs(plan: &Arc<dyn ExecutionPlan>) -> Result<Vec<usize>> {
+    let mut inputs: Vec<usize> = find_unresolved_shuffles(plan)?
+        .iter()
+        .map(|shuffle| shuffle.stage_id)
+        .collect();
+    plan.apply(&mut |plan| {
+        if let Some(filter) = plan.as_any().downcast_ref::<RuntimeFilterExec>() {
+            inputs.push(filter.build_stage_id);
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    Ok(inputs)
+}
+
+/// Whether the stage depends on the other stage, directly or through other stages
+fn depends_on(
+    dependencies: &HashMap<usize, Vec<usize>>,
+    stage_id: usize,
+    other_stage_id: usize,
+) -> bool {
+    let mut visited = HashSet::new();
+    let mut to_visit = vec![stage_id];
+    while let Some(stage_id) = to_visit.pop() {
+        if stage_id == other_stage_id {
+            return true;
+        }
+        if visited.insert(stage_id) {
+            to_visit.extend(dependencies.get(&stage_id).into_iter().flatten());
+        }
+    }
+    false
+}
+
+/// Adds the runtime filter to the plan of the probe side stage, as close to the scan of the
+/// probe join key as the projections, filters and batch coalescing of the plan allow
+fn push_down_runtime_filter(
+    plan: Arc<dyn ExecutionPlan>,
+    filter: &RuntimeFilter,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    push_down(plan, filter.probe_column.clone(), filter)
+}
+
+fn push_down(
+    plan: Arc<dyn ExecutionPlan>,
+    column: Column,
+    filter: &RuntimeFilter,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let any = plan.as_any();
+    let input_column = if let Some(projection) = any.downcast_ref::<ProjectionExec>() {
+        projection.expr()[column.index()]
+            .0
+            .as_any()
+            .downcast_ref::<Column>()
+            .cloned()
+    } else if any.is::<FilterExec>() || any.is::<CoalesceBatchesExec>() {
+        Some(column.clone())
+    } else {
+        None
+    };
+    match input_column {
+        Some(input_column) => {
+            let input = push_down(plan.children()[0].clone(), input_column, filter)?;
+            Ok(with_new_children_if_necessary(plan, vec![input])?)
+        }
+        None => Ok(Arc::new(RuntimeFilterExec::try_new(
+            plan,
+            filter.build_stage_id,
+            filter.build_column,
+            column,
+        )?)),
+    }
+}
+
+/// Replaces the [RuntimeFilterExec] of a stage plan, once the inputs of the stage are
+/// known, with a filter of its probe join key between the bounds of the build side join
+/// key. The filter is also pushed to the parquet scan below. If the build side join key
+/// has a bloom filter in `bloom_filters`, by stage id, a [BloomFilterExec] filters the
+/// probe join key with it above. The [RuntimeFilterExec] is removed if neither the bounds
+/// nor the bloom filter are known, e.g. as the build side stage did not complete yet or
+/// as the build side is empty.
+pub fn resolve_runtime_filters(
+    plan: Arc<dyn ExecutionPlan>,
+    partition_locations: &HashMap<usize, HashMap<usize, Vec<PartitionLocation>>>,
+    bloom_filters: &HashMap<usize, MapBloomFilters>,
+    incomplete_inputs: &HashSet<usize>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let transformed = plan.transform_up(&|plan: Arc<dyn ExecutionPlan>| {
+        let Some(filter) = plan.as_any().downcast_ref::<RuntimeFilterExec>() else {
+            return Ok(Transformed::no(plan));
+        };
+        let input = filter.input().clone();
+        let locations = partition_locations
+            .get(&filter.build_stage_id)
+            .filter(|_| !incomplete_inputs.contains(&filter.build_stage_id));
+        let bloom_filter = locations.and_then(|locations| {
+            build_side_bloom_filter(
+                locations,
+                bloom_filters.get(&filter.build_stage_id)?,
+                filter.build_column,
+            )
+        });
+        let bounds = locations
+            .and_then(|locations| build_side_bounds(locations, filter.build_column))
+            .filter(|(min, _)| {
+                input
+                    .schema()
+                    .field(filter.probe_column.index())
+                    .data_type()
+                    == &min.data_type()
+            });
+        if bounds.is_none() && bloom_filter.is_none() {
+            debug!(
+                "Removing the runtime filter of column {} without bounds nor bloom filter",
+                filter.probe_column
+            );
+            return Ok(Transformed::yes(input));
+        }
+
+        let mut plan = input;
+        if let Some((min, max)) = bounds {
+            debug!(
+                "Filtering column {} between {} and {}",
+                filter.probe_column, min, max
+            );
+            if let Some(exec) = plan.as_any().downcast_ref::<ParquetExec>() {
+                if let Some(exec) =
+                    filter_parquet_scan(exec, &filter.probe_column, &min, &max)
+                {
+                    plan = Arc::new(exec);
+                }
+            }
+            let predicate = between(Arc::new(filter.probe_column.clone()), min, max);
+            plan = Arc::new(FilterExec::try_new(predicate, plan)?);
+        }
+        if let Some(bloom_filter) = bloom_filter {
+            debug!(
+                "Filtering column {} with a bloom filter of {} bytes",
+                filter.probe_column,
+                bloom_filter.as_bytes().len()
+            );
+            plan = Arc::new(BloomFilterExec::try_new(
+                plan,
+                filter.probe_column.clone(),
+                Arc::new(bloom_filter),
+            )?);
+        }
+        Ok(Transformed::yes(plan))
+    })?;
+    Ok(transformed.data)
+}
+
+/// The min and max values of a column of the outputs of a stage, unknown if they are not
+/// known for every output holding non null values of the column
+fn build_side_bounds(
+    locations: &HashMap<usize, Vec<PartitionLocation>>,
+    column: usize,
+) -> Option<(ScalarValue, ScalarValue)> {
+    let mut bounds: Option<(ScalarValue, ScalarValue)> = None;
+    for location in locations.values().flatten() {
+        let stats = &location.partition_stats;
+        let num_rows = stats.num_rows();
+        let null_count = stats
+            .null_counts()
+            .and_then(|null_counts| null_counts.get(column).copied());
+        // the outputs without non null join keys match no probe side rows
+        if num_rows == Some(0) || (num_rows.is_some() && null_count == num_rows) {
+            continue;
+        }
+        let column_bounds = stats.column_bounds()?.get(column)?;
+        let (min, max) = (column_bounds.min.clone()?, column_bounds.max.clone()?);
+        bounds = Some(match bounds {
+            Some((lower, upper)) => (
+                if min < lower { min } else { lower },
+                if max > upper { max } else { upper },
+            ),
+            None => (min, max),
+        });
+    }
+    bounds
+}
+
+/// The union of the bloom filters of a column of the outputs of a stage, unknown if it is
+/// not known for every map task with non empty outputs
+fn build_side_bloom_filter(
+    locations: &HashMap<usize, Vec<PartitionLocation>>,
+    bloom_filters: &MapBloomFilters,
+    column: usize,
+) -> Option<BloomFilter> {
+    let map_partitions: HashSet<usize> = locations
+        .values()
+        .flatten()
+        .filter(|location| location.partition_stats.num_rows() != Some(0))
+        .map(|location| location.map_partition_id)
+        .collect();
+    let mut union: Option<BloomFilter> = None;
+    for map_partition in map_partitions {
+        let (_, bloom_filter) = bloom_filters
+            .get(&map_partition)?
+            .iter()
+            .find(|(index, _)| *index == column)?;
+        match union.as_mut() {
+            Some(union) => {
+                if !union.union(bloom_filter) {
+                    return None;
+                }
+            }
+            None => union = Some(bloom_filter.as_ref().clone()),
+        }
+    }
+    union
+}
+
+/// Whether the plan has a runtime filter with the outputs of the stage, whose bloom
+/// filters it then needs to be resolved
+pub fn is_filtered_by(plan: &Arc<dyn ExecutionPlan>, stage_id: usize) -> bool {
+    let mut filtered = false;
+    let _ = plan.apply(&mut |plan| {
+        if let Some(filter) = plan.as_any().downcast_ref::<RuntimeFilterExec>() {
+            if filter.build_stage_id == stage_id {
+                filtered = true;
+                return Ok(TreeNodeRecursion::Stop);
+            }
+        }
+        Ok(TreeNodeRecursion::Continue)
+    });
+    filtered
+}
+
+/// The parquet scan with the bounds of the column added to its predicate, so that its row
+/// groups are pruned with them. `None` if the column is not a column of the files.
+fn filter_parquet_scan(
+    exec: &ParquetExec,
+    column: &Column,
+    min: &ScalarValue,
+    max: &ScalarValue,
+) -> Option<ParquetExec> {
+    // the predicate of the scan refers to the columns of the files
+    let file_schema = &exec.base_config().file_schema;
+    let index = file_schema.index_of(column.name()).ok()?;
+    if file_schema.field(index).data_type() != &min.data_type() {
+        return None;
+    }
+    let predicate = between(
+        Arc::new(Column::new(column.name(), index)),
+        min.clone(),
+        max.clone(),
+    );
+    let predicate = match exec.predicate() {
+        Some(existing) => {
+            Arc::new(BinaryExpr::new(existing.clone(), Operator::And, predicate))
+        }
+        None => predicate,
+    };
+    let options = exec.table_parquet_options().clone();
+    Some(ParquetExec::new(
+        exec.base_config().clone(),
+        Some(predicate),
+        options.global.metadata_size_hint,
+        options,
+    ))
+}
+
+/// `expr >= min AND expr <= max`
+fn between(
+    expr: Arc<dyn PhysicalExpr>,
+    min: ScalarValue,
+    max: ScalarValue,
+) -> Arc<dyn PhysicalExpr> {
+    Arc::new(BinaryExpr::new(
+        Arc::new(BinaryExpr::new(
+            expr.clone(),
+            Operator::GtEq,
+            Arc::new(Literal::new(min)),
+        )),
+        Operator::And,
+        Arc::new(BinaryExpr::new(
+            expr,
+            Operator::LtEq,
+            Arc::new(Literal::new(max)),
+        )),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+
+    use ballista_core::error::BallistaError;
+    use ballista_core::execution_plans::{
+        BloomFilter, RuntimeFilterExec, ShuffleWriterExec,
+    };
+    use ballista_core::serde::scheduler::{
+        ColumnBounds, PartitionId, PartitionLocation, PartitionStats,
+    };
+    use datafusion::arrow::array::{ArrayRef, Int64Array};
+    use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+    use datafusion::common::ScalarValue;
+    use datafusion::datasource::physical_plan::CsvExec;
+    use datafusion::physical_plan::displayable;
+
+    use super::{resolve_runtime_filters, MapBloomFilters, RuntimeFilterStagePlanner};
+    use crate::planner::DefaultStagePlanner;
+    use crate::state::execution_graph::{ExecutionGraph, ExecutionStage};
+    use crate::test_utils::{datafusion_test_context, mock_executor};
+
+    fn build_side_location(
+        partition: usize,
+        num_rows: u64,
+        bounds: Option<(i64, i64)>,
+    ) -> PartitionLocation {
+        let (min, max) = match bounds {
+            Some((min, max)) => (Some(min), Some(max)),
+            None => (None, None),
+        };
+        PartitionLocation {
+            map_partition_id: 0,
+            partition_id: PartitionId::new("job", 1, partition),
+            executor_meta: mock_executor("executor".to_owned()),
+            partition_stats: PartitionStats::new(Some(num_rows), Some(1), Some(1))
+                .with_null_counts(vec![0, 0])
+                .with_column_bounds(vec![
+                    ColumnBounds {
+                        min: min.map(|min| ScalarValue::Int64(Some(min))),
+                        max: max.map(|max| ScalarValue::Int64(Some(max))),
+                    },
+                    ColumnBounds::default(),
+                ]),
+            path: String::new(),
+        }
+    }
+
+    fn bloom_filters(
+        map_partition_keys: Vec<(usize, Vec<i64>)>,
+    ) -> Result<HashMap<usize, MapBloomFilters>, BallistaError> {
+        let mut bloom_filters = MapBloomFilters::new();
+        for (map_partition, keys) in map_partition_keys {
+            let mut bloom_filter = BloomFilter::new(128);
+            bloom_filter.insert(&(Arc::new(Int64Array::from(keys)) as ArrayRef))?;
+            bloom_filters.insert(map_partition, vec![(0, Arc::new(bloom_filter))]);
+        }
+        Ok(HashMap::from([(1, bloom_filters)]))
+    }
+
+    #[tokio::test]
+    async fn join_probe_side_filtered_by_build_side() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let plan = ctx
+            .sql(
+                "select l_shipmode, o_orderpriority from lineitem join orders \
+                on l_orderkey = o_orderkey",
+            )
+            .await?
+            .create_physical_plan()
+            .await?;
+        let graph = ExecutionGraph::new_with_stage_planner(
+            "localhost:50050",
+            "job",
+            "",
+            "session",
+            plan,
+            0,
+            &RuntimeFilterStagePlanner::new(&DefaultStagePlanner),
+        )?;
+
+        // stage 1 scans lineitem, the build side, and stage 2 scans orders, the probe side
+        let Some(ExecutionStage::UnResolved(probe_stage)) = graph.stages().get(&2) else {
+            panic!("The probe side stage should wait for the build side stage");
+        };
+        assert_eq!(probe_stage.inputs.keys().collect::<Vec<_>>(), vec![&1]);
+        let mut runtime_filters = vec![];
+        probe_stage.plan.apply(&mut |plan| {
+            if let Some(filter) = plan.as_any().downcast_ref::<RuntimeFilterExec>() {
+                runtime_filters.push(filter.clone());
+            }
+            Ok(TreeNodeRecursion::Continue)
+        })?;
+        assert_eq!(runtime_filters.len(), 1);
+        let runtime_filter = &runtime_filters[0];
+        assert_eq!(runtime_filter.build_stage_id, 1);
+        assert_eq!(runtime_filter.build_column, 0);
+        assert_eq!(runtime_filter.probe_column.name(), "o_orderkey");
+        assert!(runtime_filter.input().as_any().is::<CsvExec>());
+
+        // the filter of the join key is between the bounds of all the build side outputs
+        let locations = HashMap::from([(
+            1,
+            HashMap::from([
+                (0, vec![build_side_location(0, 3, Some((4, 10)))]),
+                (1, vec![build_side_location(1, 2, Some((1, 7)))]),
+                (2, vec![build_side_location(2, 0, None)]),
+            ]),
+        )]);
+        let plan = resolve_runtime_filters(
+            probe_stage.plan.clone(),
+            &locations,
+            &HashMap::new(),
+            &HashSet::new(),
+        )?;
+        let display = displayable(plan.as_ref()).indent(false).to_string();
+        assert!(
+            display.contains("FilterExec: o_orderkey@0 >= 1 AND o_orderkey@0 <= 10"),
+            "{display}"
+        );
+        assert!(!display.contains("RuntimeFilterExec"), "{display}");
+
+        // the filter is removed when the bounds of an output are unknown
+        let locations = HashMap::from([(
+            1,
+            HashMap::from([
+                (0, vec![build_side_location(0, 3, Some((4, 10)))]),
+                (1, vec![build_side_location(1, 2, None)]),
+            ]),
+        )]);
+        let plan = resolve_runtime_filters(
+            probe_stage.plan.clone(),
+            &locations,
+            &HashMap::new(),
+            &HashSet::new(),
+        )?;
+        let display = displayable(plan.as_ref()).indent(false).to_string();
+        assert!(!display.contains("FilterExec"), "{display}");
+        assert!(!display.contains("RuntimeFilterExec"), "{display}");
+        Ok(())
+    }
+    #[tokio::test]
+    async fn join_probe_side_filtered_by_bloom_filter() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let plan = ctx
+            .sql(
+                "select l_shipmode, o_orderpriority from lineitem join orders \
+                on l_orderkey = o_orderkey",
+            )
+            .await?
+            .create_physical_plan()
+            .await?;
+        let graph = ExecutionGraph::new_with_stage_planner(
+            "localhost:50050",
+            "job",
+            "",
+            "session",
+            plan,
+            0,
+            &RuntimeFilterStagePlanner::new(&DefaultStagePlanner)
+                .with_bloom_filters(true),
+        )?;
+
+        // the build side stage collects the bloom filters of its join key
+        let Some(ExecutionStage::Resolved(build_stage)) = graph.stages().get(&1) else {
+            panic!("The build side stage should be resolved");
+        };
+        let shuffle_writer = build_stage
+            .plan
+            .as_any()
+            .downcast_ref::<ShuffleWriterExec>()
+            .unwrap();
+        assert_eq!(shuffle_writer.bloom_filter_columns(), &[0]);
+        let Some(ExecutionStage::UnResolved(probe_stage)) = graph.stages().get(&2) else {
+            panic!("The probe side stage should wait for the build side stage");
+        };
+
+        // the probe side is filtered by the union of the bloom filters of the map tasks
+        // of the build side
+        let mut second_map_location = build_side_location(1, 2, None);
+        second_map_location.map_partition_id = 1;
+        let locations = HashMap::from([(
+            1,
+            HashMap::from([
+                (0, vec![build_side_location(0, 3, None)]),
+                (
+                    1,
+                    vec![build_side_location(1, 1, None), second_map_location],
+                ),
+                (2, vec![build_side_location(2, 0, None)]),
+            ]),
+        )]);
+        let plan = resolve_runtime_filters(
+            probe_stage.plan.clone(),
+            &locations,
+            &bloom_filters(vec![(0, vec![4, 10]), (1, vec![1])])?,
+            &HashSet::new(),
+        )?;
+        let display = displayable(plan.as_ref()).indent(false).to_string();
+        assert!(
+            display.contains("BloomFilterExec: column=o_orderkey@0, bytes=128"),
+            "{display}"
+        );
+        assert!(!display.contains("FilterExec: o_orderkey"), "{display}");
+        assert!(!display.contains("RuntimeFilterExec"), "{display}");
+
+        // no bloom filter applies if the filter of a map task is unknown
+        let plan = resolve_runtime_filters(
+            probe_stage.plan.clone(),
+            &locations,
+            &bloom_filters(vec![(0, vec![4, 10])])?,
+            &HashSet::new(),
+        )?;
+        let display = displayable(plan.as_ref()).indent(false).to_string();
+        assert!(!display.contains("BloomFilterExec"), "{display}");
+
+        // both filters apply with the bounds known as well
+        let locations = HashMap::from([(
+            1,
+            HashMap::from([(0, vec![build_side_location(0, 3, Some((4, 10)))])]),
+        )]);
+        let plan = resolve_runtime_filters(
+            probe_stage.plan.clone(),
+            &locations,
+            &bloom_filters(vec![(0, vec![4, 10])])?,
+            &HashSet::new(),
+        )?;
+        let display = displayable(plan.as_ref()).indent(false).to_string();
+        assert!(display.contains("BloomFilterExec"), "{display}");
+        assert!(
+            display.contains("FilterExec: o_orderkey@0 >= 4 AND o_orderkey@0 <= 10"),
+            "{display}"
+        );
+        Ok(())
+    }
+}
@@ -20,11 +20,15 @@ use anyhow::{Context, Result};
 use arrow_flight::flight_service_server::FlightServiceServer;
 use futures::future::{self, Either, TryFutureExt};
 use hyper::{server::conn::AddrStream, service::make_service_fn, Server};
-use log::info;
+use log::{error, info};
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 use tonic::transport::server::Connected;
+use tonic_health::server::health_reporter;
+use tonic_health::ServingStatus;
 use tower::Service;
 
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
@@ -34,12 +38,13 @@ use ballista_core::serde::BallistaCodec;
 use ballista_core::utils::create_grpc_server;
 use ballista_core::BALLISTA_VERSION;
 
-use crate::api::{get_routes, EitherBody, Error};
+use crate::api::{get_http_routes, get_routes, get_startup_routes, EitherBody, Error};
 use crate::cluster::BallistaCluster;
-use crate::config::SchedulerConfig;
+use crate::config::{HttpServerConfig, SchedulerConfig};
 use crate::flight_sql::FlightSqlServiceImpl;
 use crate::metrics::default_metrics_collector;
 use crate::scheduler_server::externalscaler::external_scaler_server::ExternalScalerServer;
+use crate::scheduler_server::readiness::{ReadinessStage, SchedulerReadiness};
 use crate::scheduler_server::SchedulerServer;
 
 pub async fn start_server(
@@ -58,19 +63,70 @@ pub async fn start_server(
     );
 
     let metrics_collector = default_metrics_collector()?;
+    // the readiness endpoint is served on the gRPC port unless there is an HTTP server
+    let (readiness_address, readiness_tls) = match &config.http_server {
+        Some(http_config) => (http_config.bind_address, http_config.tls.clone()),
+        None => (addr, None),
+    };
+    let standby = config.standby;
 
     let mut scheduler_server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
         SchedulerServer::new(
             config.scheduler_name(),
             cluster,
-            BallistaCodec::default(),
+            BallistaCodec::default().with_plan_protection(config.plan_protection.clone()),
             config,
             metrics_collector,
         );
 
+    // the overall status of the gRPC health service follows the readiness of the scheduler
+    let (mut health_reporter, health_service) = health_reporter();
+    health_reporter
+        .set_service_status("", ServingStatus::NotServing)
+        .await;
+
+    // the readiness endpoint answers 503 while the scheduler initializes
+    let startup_server = StartupServer::start(
+        scheduler_server.readiness.clone(),
+        standby,
+        readiness_address,
+        readiness_tls,
+    )?;
     scheduler_server.init().await?;
+    startup_server.stop().await;
+
+    let server = Server::try_bind(&addr).context("Could not bind grpc server")?;
+    // the HTTP routes are served on the gRPC port unless a dedicated HTTP server is set
+    let dedicated_http_server = scheduler_server.state.config.http_server.is_some();
+    if let Some(http_config) = scheduler_server.state.config.http_server.clone() {
+        start_http_server(scheduler_server.clone(), http_config)?;
+    }
+    scheduler_server
+        .readiness
+        .advance(ReadinessStage::GrpcBound);
+    health_reporter
+        .set_service_status("", ServingStatus::Serving)
+        .await;
+
+    // on Ctrl-C, the scheduler is shut down while the gRPC server keeps serving, e.g. the
+    // status of the jobs, but rejects new jobs and task status updates with UNAVAILABLE
+    // while the events already received are drained
+    let shutdown_server = scheduler_server.clone();
+    let shutdown_signal = async move {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for the shutdown signal: {e:?}");
+            return future::pending().await;
+        }
+        info!("Received shutdown signal");
+        health_reporter
+            .set_service_status("", ServingStatus::NotServing)
+            .await;
+        if let Err(e) = shutdown_server.shutdown().await {
+            error!("Failed to shut down the scheduler gracefully: {e:?}");
+        }
+    };
 
-    Server::bind(&addr)
+    server
         .serve(make_service_fn(move |request: &AddrStream| {
             let config = &scheduler_server.state.config;
             let scheduler_grpc_server =
@@ -86,7 +142,8 @@ pub async fn start_server(
 
             let tonic_builder = create_grpc_server()
                 .add_service(scheduler_grpc_server)
-                .add_service(keda_scaler);
+                .add_service(keda_scaler)
+                .add_service(health_service.clone());
 
             #[cfg(feature = "flight-sql")]
             let tonic_builder = tonic_builder.add_service(FlightServiceServer::new(
@@ -105,7 +162,10 @@ pub async fn start_server(
                     parts.extensions.insert(connect_info.clone());
                     let req = http::Request::from_parts(parts, body);
 
-                    if req.uri().path().starts_with("/api") {
+                    let path = req.uri().path();
+                    if !dedicated_http_server
+                        && (path.starts_with("/api") || path == "/ready")
+                    {
                         return Either::Left(
                             warp.call(req)
                                 .map_ok(|res| res.map(EitherBody::Left))
@@ -122,6 +182,99 @@ pub async fn start_server(
                 },
             ))
         }))
+        .with_graceful_shutdown(shutdown_signal)
         .await
         .context("Could not start grpc server")
 }
+
+/// Serve the REST API, the metrics and the readiness endpoint on the dedicated HTTP server
+fn start_http_server(
+    scheduler_server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode>,
+    config: HttpServerConfig,
+) -> Result<()> {
+    let routes = get_http_routes(scheduler_server, config.basic_auth);
+    let server = warp::serve(routes);
+    match config.tls {
+        Some((cert_path, key_path)) => {
+            let cert = std::fs::read(&cert_path)
+                .with_context(|| format!("Could not read certificate {cert_path}"))?;
+            let key = std::fs::read(&key_path)
+                .with_context(|| format!("Could not read private key {key_path}"))?;
+            // the certificate and key are checked when binding, so that the scheduler
+            // fails to start rather than serving without its HTTP server
+            let (bind_address, server) = server
+                .tls()
+                .cert(cert)
+                .key(key)
+                .try_bind_with_graceful_shutdown(
+                    config.bind_address,
+                    future::pending::<()>(),
+                )
+                .context("Could not bind https server")?;
+            info!("Scheduler HTTPS server listening on {}", bind_address);
+            tokio::spawn(server);
+        }
+        None => {
+            let (bind_address, server) = server
+                .try_bind_ephemeral(config.bind_address)
+                .context("Could not bind http server")?;
+            info!("Scheduler HTTP server listening on {}", bind_address);
+            tokio::spawn(server);
+        }
+    }
+    Ok(())
+}
+
+/// The server of the readiness endpoint alone while the scheduler initializes, before the
+/// servers of the scheduler bind the same address
+struct StartupServer {
+    stop: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+impl StartupServer {
+    /// Serve the readiness endpoint on `address`, over TLS with the PEM encoded
+    /// certificate and private key at the paths of `tls` if set
+    fn start(
+        readiness: Arc<SchedulerReadiness>,
+        standby: bool,
+        address: SocketAddr,
+        tls: Option<(String, String)>,
+    ) -> Result<Self> {
+        let (stop, stopped) = oneshot::channel::<()>();
+        let stopped = async {
+            stopped.await.ok();
+        };
+        let server = warp::serve(get_startup_routes(readiness, standby));
+        let handle = match tls {
+            Some((cert_path, key_path)) => {
+                let cert = std::fs::read(&cert_path)
+                    .with_context(|| format!("Could not read certificate {cert_path}"))?;
+                let key = std::fs::read(&key_path)
+                    .with_context(|| format!("Could not read private key {key_path}"))?;
+                let (_, server) = server
+                    .tls()
+                    .cert(cert)
+                    .key(key)
+                    .try_bind_with_graceful_shutdown(address, stopped)
+                    .context("Could not bind readiness server")?;
+                tokio::spawn(server)
+            }
+            None => {
+                let (_, server) = server
+                    .try_bind_with_graceful_shutdown(address, stopped)
+                    .context("Could not bind readiness server")?;
+                tokio::spawn(server)
+            }
+        };
+        Ok(Self { stop, handle })
+    }
+
+    /// Stop serving once the pending requests are answered, releasing the address
+    async fn stop(self) {
+        let _ = self.stop.send(());
+        if let Err(e) = self.handle.await {
+            error!("Readiness server of the scheduler startup failed: {e:?}");
+        }
+    }
+}
@@ -0,0 +1,390 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::sync::Arc;
+
+use ballista_core::admin_statement::{AdminStatement, AdminStatementNode};
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::job_status::Status;
+use datafusion::common::{DFSchema, ScalarValue};
+use datafusion::logical_expr::{
+    lit, EmptyRelation, Extension, LogicalPlan, SetVariable, Statement, Values,
+};
+use datafusion::prelude::SessionContext;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+use log::info;
+
+use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::SchedulerServer;
+
+/// The plan standing for an administrative statement until the job returning its result
+/// is created, see [SchedulerServer::execute_admin_statement]
+pub(crate) fn admin_statement_plan(statement: AdminStatement) -> Result<LogicalPlan> {
+    let node = Arc::new(AdminStatementNode::try_new(statement)?);
+    Ok(LogicalPlan::Extension(Extension { node }))
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T, U> {
+    /// Execute the administrative statement which the plan stands for, if any, right
+    /// before the job returning its result is created, so that the statement is neither
+    /// executed when a query is only prepared nor when the submission is a retry
+    pub(crate) async fn execute_admin_statement(
+        &self,
+        plan: LogicalPlan,
+    ) -> Result<LogicalPlan> {
+        if let LogicalPlan::Extension(Extension { node }) = &plan {
+            if let Some(node) = node.as_any().downcast_ref::<AdminStatementNode>() {
+                return self.plan_admin_statement(node.statement()).await;
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Execute an administrative statement and return a plan producing its result,
+    /// which is then run as a normal job so that the result reaches the client the
+    /// same way as any query result
+    pub(crate) async fn plan_admin_statement(
+        &self,
+        statement: &AdminStatement,
+    ) -> Result<LogicalPlan> {
+        let rows = match statement {
+            AdminStatement::ShowJobs => self.show_jobs().await?,
+            AdminStatement::ShowExecutors => self.show_executors().await?,
+            AdminStatement::KillJob(job_id) => self.kill_job(job_id).await?,
+        };
+
+        let schema = Arc::new(DFSchema::try_from(statement.schema().as_ref().clone())?);
+        if rows.is_empty() {
+            return Ok(LogicalPlan::EmptyRelation(EmptyRelation {
+                produce_one_row: false,
+                schema,
+            }));
+        }
+        let values = rows
+            .into_iter()
+            .map(|row| row.into_iter().map(lit).collect())
+            .collect();
+        Ok(LogicalPlan::Values(Values { schema, values }))
+    }
+
+    /// Plan a SQL query of a session. The `SET` statements change the settings of the
+    /// session, which are saved for its next jobs, and are planned as an empty result.
+    pub(crate) async fn plan_sql(
+        &self,
+        session_id: &str,
+        session_ctx: &SessionContext,
+        sql: &str,
+    ) -> Result<LogicalPlan> {
+        let plan = session_ctx.state().create_logical_plan(sql).await?;
+        if let LogicalPlan::Statement(Statement::SetVariable(SetVariable {
+            variable,
+            value,
+            schema,
+        })) = &plan
+        {
+            info!("Setting {variable}={value} for session {session_id}");
+            self.state
+                .session_manager
+                .set_session_setting(session_id, session_ctx, variable, value)
+                .await?;
+            return Ok(LogicalPlan::EmptyRelation(EmptyRelation {
+                produce_one_row: false,
+                schema: schema.clone(),
+            }));
+        }
+        Ok(session_ctx
+            .execute_logical_plan(plan)
+            .await?
+            .into_optimized_plan()?)
+    }
+
+    async fn show_jobs(&self) -> Result<Vec<Vec<ScalarValue>>> {
+        let mut jobs = self.state.task_manager.get_jobs().await?;
+        jobs.sort_by_key(|job| job.start_time);
+
+        Ok(jobs
+            .into_iter()
+            .map(|job| {
+                let (status, error) = match job.status.status {
+                    Some(Status::Queued(_)) => ("Queued", None),
+                    Some(Status::Planning(_)) => ("Planning", None),
+                    Some(Status::Running(_)) => ("Running", None),
+                    Some(Status::Failed(failed)) => ("Failed", Some(failed.error)),
+                    Some(Status::Successful(_)) => ("Successful", None),
+                    None => ("Unknown", None),
+                };
+                vec![
+                    ScalarValue::from(job.job_id),
+                    ScalarValue::from(job.job_name),
+                    ScalarValue::from(status),
+                    ScalarValue::Utf8(error),
+                    ScalarValue::from(job.num_stages as u32),
+                    ScalarValue::from(job.completed_stages as u32),
+                    ScalarValue::TimestampMillisecond(Some(job.start_time as i64), None),
+                    ScalarValue::TimestampMillisecond(
+                        (job.end_time > 0).then_some(job.end_time as i64),
+                        None,
+                    ),
+                ]
+            })
+            .collect())
+    }
+
+    async fn show_executors(&self) -> Result<Vec<Vec<ScalarValue>>> {
+        let mut executors = self.state.executor_manager.get_executor_state().await?;
+        executors.sort_by(|(a, _), (b, _)| a.id.cmp(&b.id));
+
+        Ok(executors
+            .into_iter()
+            .map(|(metadata, last_seen)| {
+                vec![
+                    ScalarValue::from(metadata.id),
+                    ScalarValue::from(metadata.host),
+                    ScalarValue::from(metadata.port as u32),
+                    ScalarValue::from(metadata.grpc_port as u32),
+                    ScalarValue::from(metadata.specification.task_slots),
+                    ScalarValue::TimestampMillisecond(
+                        Some(last_seen.as_millis() as i64),
+                        None,
+                    ),
+                ]
+            })
+            .collect())
+    }
+
+    /// Cancel a job, reporting whether it was cancelled, i.e. whether it had not finished
+    async fn kill_job(&self, job_id: &str) -> Result<Vec<Vec<ScalarValue>>> {
+        let status = self
+            .state
+            .task_manager
+            .get_job_status(job_id)
+            .await?
+            .ok_or_else(|| BallistaError::General(format!("Job {job_id} not found")))?;
+
+        let cancelled = !matches!(
+            status.status,
+            Some(Status::Successful(_)) | Some(Status::Failed(_))
+        );
+        if cancelled {
+            info!("Received KILL JOB statement for job {}", job_id);
+            self.query_stage_event_loop
+                .get_sender()?
+                .post_event(QueryStageSchedulerEvent::JobCancel(job_id.to_string()))
+                .await?;
+        } else {
+            info!(
+                "Received KILL JOB statement for job {}, which already finished",
+                job_id
+            );
+        }
+
+        Ok(vec![vec![
+            ScalarValue::from(job_id),
+            ScalarValue::Boolean(Some(cancelled)),
+        ]])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use ballista_core::admin_statement::AdminStatement;
+    use ballista_core::config::BallistaConfig;
+    use ballista_core::error::Result;
+    use ballista_core::serde::protobuf::job_status::Status;
+    use ballista_core::serde::BallistaCodec;
+    use datafusion::arrow::array::{Array, BooleanArray, StringArray};
+    use datafusion::logical_expr::LogicalPlan;
+    use datafusion::physical_plan::collect;
+    use datafusion::prelude::SessionContext;
+    use datafusion_proto::physical_plan::AsExecutionPlan;
+    use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+
+    use crate::config::SchedulerConfig;
+    use crate::metrics::default_metrics_collector;
+    use crate::scheduler_server::admin_statement::admin_statement_plan;
+    use crate::scheduler_server::SchedulerServer;
+    use crate::test_utils::test_cluster_context;
+
+    async fn test_scheduler() -> Result<SchedulerServer<LogicalPlanNode, PhysicalPlanNode>>
+    {
+        let cluster = test_cluster_context();
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster,
+                BallistaCodec::default(),
+                Arc::new(SchedulerConfig::default()),
+                default_metrics_collector()?,
+            );
+        scheduler.init().await?;
+        Ok(scheduler)
+    }
+
+    async fn submit_job(
+        scheduler: &SchedulerServer<LogicalPlanNode, PhysicalPlanNode>,
+        ctx: &SessionContext,
+        job_id: &str,
+    ) -> Result<()> {
+        let plan = ctx.sql("SELECT 1").await?.into_optimized_plan()?;
+        scheduler
+            .submit_job(job_id, "test job", Arc::new(ctx.clone()), &plan)
+            .await?;
+        // jobs are planned asynchronously
+        while !scheduler
+            .state
+            .task_manager
+            .get_jobs()
+            .await?
+            .iter()
+            .any(|job| job.job_id == job_id)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_show_jobs() -> Result<()> {
+        let scheduler = test_scheduler().await?;
+        let ctx = SessionContext::new();
+
+        let plan = scheduler
+            .plan_admin_statement(&AdminStatement::ShowJobs)
+            .await?;
+        assert!(matches!(plan, LogicalPlan::EmptyRelation(_)));
+
+        submit_job(&scheduler, &ctx, "job-1").await?;
+
+        let plan = scheduler
+            .plan_admin_statement(&AdminStatement::ShowJobs)
+            .await?;
+        assert!(matches!(plan, LogicalPlan::Values(_)));
+
+        // the result must survive the trip to the executors
+        let physical_plan = ctx.state().create_physical_plan(&plan).await?;
+        let codec = BallistaCodec::<LogicalPlanNode, PhysicalPlanNode>::default();
+        let proto = PhysicalPlanNode::try_from_physical_plan(
+            physical_plan,
+            codec.physical_extension_codec(),
+        )?;
+        let physical_plan = proto.try_into_physical_plan(
+            &ctx,
+            ctx.runtime_env().as_ref(),
+            codec.physical_extension_codec(),
+        )?;
+
+        let batches = collect(physical_plan, ctx.task_ctx()).await?;
+        assert_eq!(batches.len(), 1);
+        let job_ids = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(job_ids.len(), 1);
+        assert_eq!(job_ids.value(0), "job-1");
+        assert_eq!(
+            batches[0].schema(),
+            AdminStatement::ShowJobs.schema(),
+            "result schema should match the statement schema"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_kill_job() -> Result<()> {
+        let scheduler = test_scheduler().await?;
+        let ctx = SessionContext::new();
+
+        let result = scheduler
+            .plan_admin_statement(&AdminStatement::KillJob("missing".to_string()))
+            .await;
+        assert!(result.is_err(), "unknown jobs cannot be killed");
+
+        submit_job(&scheduler, &ctx, "job-1").await?;
+
+        // the statement is only executed along with the job returning its result
+        let plan = admin_statement_plan(AdminStatement::KillJob("job-1".to_string()))?;
+        assert!(matches!(plan, LogicalPlan::Extension(_)));
+        let kill_job = || async {
+            let plan = scheduler.execute_admin_statement(plan.clone()).await?;
+            let physical_plan = ctx.state().create_physical_plan(&plan).await?;
+            let batches = collect(physical_plan, ctx.task_ctx()).await?;
+            let cancelled = batches[0]
+                .column(1)
+                .as_any()
+                .downcast_ref::<BooleanArray>()
+                .unwrap()
+                .value(0);
+            Result::Ok(cancelled)
+        };
+        assert!(kill_job().await?);
+
+        // the job is cancelled asynchronously, a finished job is not cancelled again
+        while !matches!(
+            scheduler
+                .state
+                .task_manager
+                .get_job_status("job-1")
+                .await?
+                .and_then(|status| status.status),
+            Some(Status::Failed(_))
+        ) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!kill_job().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_statement() -> Result<()> {
+        let scheduler = test_scheduler().await?;
+        let session_manager = &scheduler.state.session_manager;
+        let ctx = session_manager
+            .create_session(&BallistaConfig::new()?)
+            .await?;
+        let session_id = ctx.session_id();
+
+        for sql in [
+            "SET ballista.shuffle.partitions = 32",
+            "SET datafusion.execution.batch_size = 1024",
+        ] {
+            let ctx = session_manager.get_session(&session_id).await?;
+            let plan = scheduler.plan_sql(&session_id, &ctx, sql).await?;
+            assert!(matches!(plan, LogicalPlan::EmptyRelation(_)));
+        }
+
+        // the settings apply to the next jobs of the session
+        let ctx = session_manager.get_session(&session_id).await?;
+        let config = ctx.state().config().clone();
+        assert_eq!(32, config.target_partitions());
+        assert_eq!(1024, config.batch_size());
+
+        for sql in [
+            "SET ballista.shuffle.partitions = 'many'",
+            "SET ballista.no_such_option = 1",
+            "SET datafusion.no_such_option = 1",
+        ] {
+            let result = scheduler.plan_sql(&session_id, &ctx, sql).await;
+            assert!(result.is_err(), "{sql} should be rejected");
+        }
+        Ok(())
+    }
+}
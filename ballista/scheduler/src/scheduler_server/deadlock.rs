@@ -0,0 +1,283 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Detection of the jobs deadlocked waiting for task slots.
+//!
+//! The tasks of pipelined stages start before the stages they read complete, and hold
+//! their task slots while waiting for the outputs of these stages. When such waiting tasks
+//! hold all the task slots while tasks of their inputs still need slots, e.g. retried after
+//! an executor was lost, no task can make progress. Once this lasted for
+//! `slot_deadlock_timeout_seconds`, the waiting stages of the youngest deadlocked job are
+//! rewound, releasing their slots, and start again once their inputs progress.
+//!
+//! Only the jobs of this scheduler are seen, so a deadlock is only detected when their
+//! running tasks hold all the task slots of the cluster: the slots held by the jobs of
+//! other schedulers are released as these jobs progress. A task completing between two
+//! checks also shows that the jobs progress.
+//!
+//! The deadlocks are only resolved with the push-based task scheduling policy, as the
+//! executors polling for tasks can not cancel the tasks of the rewound stages. The jobs
+//! with pipelined stages are failed by the other schedulers.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use ballista_core::error::Result;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+use log::{error, warn};
+
+use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::SchedulerServer;
+use crate::state::task_manager::JobSlotUsage;
+
+/// Return the job whose waiting stages to rewind if the active jobs are deadlocked: no task
+/// slot is available, the running tasks of the jobs hold all the `total_slots` of the
+/// cluster and all wait for their inputs, and tasks wait for slots. The youngest job
+/// holding waiting tasks is chosen, so that the jobs which waited the longest keep their
+/// progress.
+fn detect_slot_deadlock(
+    total_slots: u32,
+    available_slots: u32,
+    jobs: &[JobSlotUsage],
+) -> Option<&JobSlotUsage> {
+    if available_slots > 0 {
+        return None;
+    }
+    let running_tasks: usize = jobs.iter().map(|job| job.running_tasks).sum();
+    if running_tasks < total_slots as usize {
+        return None;
+    }
+    let waiting_tasks: usize = jobs.iter().map(|job| job.waiting_tasks).sum();
+    let available_tasks: usize = jobs.iter().map(|job| job.available_tasks).sum();
+    if waiting_tasks == 0 || waiting_tasks < running_tasks || available_tasks == 0 {
+        return None;
+    }
+    jobs.iter()
+        .filter(|job| job.waiting_tasks > 0)
+        .max_by_key(|job| job.start_time)
+}
+
+/// Tracks how long the active jobs are deadlocked across the periodic checks
+#[derive(Default)]
+struct SlotDeadlockTracker {
+    /// When the deadlock was first seen, if the jobs are deadlocked
+    deadlocked_since: Option<Instant>,
+    /// The completed tasks of each job at the previous check
+    completed_tasks: HashMap<String, usize>,
+}
+
+impl SlotDeadlockTracker {
+    /// Return the job whose waiting stages to rewind if, at `now`, the active jobs have
+    /// been deadlocked for `timeout` without any task completing
+    fn check<'a>(
+        &mut self,
+        total_slots: u32,
+        available_slots: u32,
+        jobs: &'a [JobSlotUsage],
+        timeout: Duration,
+        now: Instant,
+    ) -> Option<&'a JobSlotUsage> {
+        let completed = jobs
+            .iter()
+            .map(|job| (job.job_id.clone(), job.completed_tasks))
+            .collect::<HashMap<_, _>>();
+        let progressed = completed != self.completed_tasks;
+        self.completed_tasks = completed;
+        let victim = detect_slot_deadlock(total_slots, available_slots, jobs);
+        let Some(victim) = victim.filter(|_| !progressed) else {
+            self.deadlocked_since = None;
+            return None;
+        };
+        let deadlocked_since = *self.deadlocked_since.get_or_insert(now);
+        if now.duration_since(deadlocked_since) < timeout {
+            return None;
+        }
+        // the deadlock is resolved by rewinding the victim
+        self.deadlocked_since = None;
+        Some(victim)
+    }
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T, U> {
+    /// Check periodically whether the active jobs are deadlocked waiting for task slots, and
+    /// rewind the waiting stages of a deadlocked job once the deadlock lasted for
+    /// `slot_deadlock_timeout_seconds`
+    pub(crate) fn resolve_slot_deadlocks(&self) -> Result<()> {
+        let state = self.state.clone();
+        let query_stage_scheduler = self.query_stage_scheduler.clone();
+        let event_sender = self.query_stage_event_loop.get_sender()?;
+        let timeout = Duration::from_secs(self.config.slot_deadlock_timeout_seconds);
+        let interval = timeout / 3;
+        tokio::task::spawn(async move {
+            let mut tracker = SlotDeadlockTracker::default();
+            loop {
+                tokio::time::sleep(interval).await;
+                let total_slots = state.executor_manager.total_task_slots().await;
+                let available_slots = state.executor_manager.available_task_slots().await;
+                let jobs = state.task_manager.job_slot_usage().await;
+                let Some(victim) = tracker.check(
+                    total_slots,
+                    available_slots,
+                    &jobs,
+                    timeout,
+                    Instant::now(),
+                ) else {
+                    continue;
+                };
+
+                warn!(
+                    "All the task slots are held by tasks waiting for their inputs for {:?}, \
+                     rewinding the waiting stages of job {}",
+                    timeout, victim.job_id
+                );
+                query_stage_scheduler
+                    .metrics_collector()
+                    .record_slot_deadlock();
+                let running_tasks = match state
+                    .task_manager
+                    .rewind_waiting_stages(&victim.job_id)
+                    .await
+                {
+                    Ok(running_tasks) => running_tasks,
+                    Err(e) => {
+                        error!(
+                            "Failed to rewind the waiting stages of job {}: {e:?}",
+                            victim.job_id
+                        );
+                        continue;
+                    }
+                };
+                if !running_tasks.is_empty() {
+                    if let Err(e) = event_sender
+                        .post_event(QueryStageSchedulerEvent::CancelTasks(running_tasks))
+                        .await
+                    {
+                        error!("Fail to send cancel tasks event due to {e:?}");
+                    }
+                }
+                if let Err(e) = event_sender
+                    .post_event(QueryStageSchedulerEvent::ReviveOffers)
+                    .await
+                {
+                    error!("Fail to send revive offers event due to {e:?}");
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn job(
+        job_id: &str,
+        start_time: u64,
+        running_tasks: usize,
+        waiting_tasks: usize,
+        available_tasks: usize,
+    ) -> JobSlotUsage {
+        JobSlotUsage {
+            job_id: job_id.to_owned(),
+            start_time,
+            running_tasks,
+            waiting_tasks,
+            available_tasks,
+            completed_tasks: 0,
+        }
+    }
+
+    #[test]
+    fn test_detect_slot_deadlock() {
+        let jobs = vec![job("job1", 1, 2, 2, 1), job("job2", 2, 2, 2, 0)];
+        // the youngest job holding waiting tasks is rewound
+        assert_eq!(
+            detect_slot_deadlock(4, 0, &jobs).map(|job| job.job_id.as_str()),
+            Some("job2")
+        );
+        // free task slots let the waiting inputs run
+        assert!(detect_slot_deadlock(4, 1, &jobs).is_none());
+        // the jobs of other schedulers hold task slots, released as they progress
+        assert!(detect_slot_deadlock(6, 0, &jobs).is_none());
+
+        // a task not waiting for its inputs releases its slot when it finishes
+        let jobs = vec![job("job1", 1, 3, 2, 1), job("job2", 2, 2, 2, 0)];
+        assert!(detect_slot_deadlock(5, 0, &jobs).is_none());
+
+        // no task needs a slot
+        let jobs = vec![job("job1", 1, 2, 2, 0)];
+        assert!(detect_slot_deadlock(2, 0, &jobs).is_none());
+    }
+
+    #[test]
+    fn test_slot_deadlock_tracker() {
+        let timeout = Duration::from_secs(60);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let victim = |victim: Option<&JobSlotUsage>| victim.map(|job| job.job_id.clone());
+        let mut tracker = SlotDeadlockTracker::default();
+        let jobs = vec![job("job1", 1, 2, 2, 1), job("job2", 2, 2, 2, 0)];
+
+        // the tasks completed before the first check count as progress
+        assert_eq!(victim(tracker.check(4, 0, &jobs, timeout, at(0))), None);
+        assert_eq!(victim(tracker.check(4, 0, &jobs, timeout, at(20))), None);
+        assert_eq!(victim(tracker.check(4, 0, &jobs, timeout, at(60))), None);
+        assert_eq!(
+            victim(tracker.check(4, 0, &jobs, timeout, at(80))),
+            Some("job2".to_owned())
+        );
+        // the deadlock is timed again once the victim was rewound
+        assert_eq!(victim(tracker.check(4, 0, &jobs, timeout, at(100))), None);
+        assert_eq!(victim(tracker.check(4, 0, &jobs, timeout, at(140))), None);
+
+        // a task completing shows that the jobs progress
+        let mut progressed = jobs.clone();
+        progressed[0].completed_tasks = 1;
+        assert_eq!(
+            victim(tracker.check(4, 0, &progressed, timeout, at(160))),
+            None
+        );
+        assert_eq!(
+            victim(tracker.check(4, 0, &progressed, timeout, at(200))),
+            None
+        );
+        assert_eq!(
+            victim(tracker.check(4, 0, &progressed, timeout, at(260))),
+            Some("job2".to_owned())
+        );
+
+        // a free task slot ends the deadlock
+        assert_eq!(
+            victim(tracker.check(4, 0, &progressed, timeout, at(280))),
+            None
+        );
+        assert_eq!(
+            victim(tracker.check(4, 1, &progressed, timeout, at(300))),
+            None
+        );
+        assert_eq!(
+            victim(tracker.check(4, 0, &progressed, timeout, at(340))),
+            None
+        );
+        assert_eq!(
+            victim(tracker.check(4, 0, &progressed, timeout, at(400))),
+            Some("job2".to_owned())
+        );
+    }
+}
@@ -15,12 +15,14 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 
 use datafusion::logical_expr::LogicalPlan;
 
 use crate::state::execution_graph::RunningTaskInfo;
-use ballista_core::serde::protobuf::TaskStatus;
+use crate::state::stage_alerts::StageAlert;
+use ballista_core::serde::protobuf::{FailedJobTask, TaskStatus};
 use datafusion::prelude::SessionContext;
 use std::sync::Arc;
 
@@ -54,6 +56,8 @@ pub enum QueryStageSchedulerEvent {
     JobRunningFailed {
         job_id: String,
         fail_message: String,
+        failed_task: Option<FailedJobTask>,
+        error_chain: Vec<String>,
         queued_at: u64,
         failed_at: u64,
     },
@@ -63,7 +67,12 @@ pub enum QueryStageSchedulerEvent {
     TaskUpdating(String, Vec<TaskStatus>),
     ReviveOffers,
     ExecutorLost(String, Option<String>),
+    /// Shuffle outputs of an executor are missing, given as the missing map partitions of
+    /// every stage of every job
+    ShuffleOutputsLost(String, HashMap<String, HashMap<usize, HashSet<usize>>>),
     CancelTasks(Vec<RunningTaskInfo>),
+    /// A stage exceeded the threshold of a stage alert rule
+    StageAlert(StageAlert),
 }
 
 impl Debug for QueryStageSchedulerEvent {
@@ -103,6 +112,7 @@ impl Debug for QueryStageSchedulerEvent {
                 fail_message,
                 queued_at,
                 failed_at,
+                ..
             } => {
                 write!(
                     f,
@@ -130,9 +140,18 @@ impl Debug for QueryStageSchedulerEvent {
                     "ExecutorLost : executor_id={executor_id}, reason:[{reason:?}]."
                 )
             }
+            QueryStageSchedulerEvent::ShuffleOutputsLost(executor_id, missing) => {
+                write!(
+                    f,
+                    "ShuffleOutputsLost : executor_id={executor_id}, missing={missing:?}."
+                )
+            }
             QueryStageSchedulerEvent::CancelTasks(status) => {
                 write!(f, "CancelTasks : status:[{status:?}].")
             }
+            QueryStageSchedulerEvent::StageAlert(alert) => {
+                write!(f, "StageAlert : {alert}.")
+            }
         }
     }
 }
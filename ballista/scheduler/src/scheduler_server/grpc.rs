@@ -15,27 +15,40 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use ballista_core::admin_statement::AdminStatement;
 use ballista_core::config::{BallistaConfig, BALLISTA_JOB_NAME};
+use ballista_core::error::BallistaError;
 use ballista_core::serde::protobuf::execute_query_params::{OptionalSessionId, Query};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 
+use ballista_core::protocol::negotiate_protocol_version;
 use ballista_core::serde::protobuf::executor_registration::OptionalHost;
 use ballista_core::serde::protobuf::scheduler_grpc_server::SchedulerGrpc;
 use ballista_core::serde::protobuf::{
-    execute_query_failure_result, execute_query_result, AvailableTaskSlots,
-    CancelJobParams, CancelJobResult, CleanJobDataParams, CleanJobDataResult,
-    CreateSessionParams, CreateSessionResult, ExecuteQueryFailureResult,
-    ExecuteQueryParams, ExecuteQueryResult, ExecuteQuerySuccessResult, ExecutorHeartbeat,
-    ExecutorStoppedParams, ExecutorStoppedResult, GetFileMetadataParams,
-    GetFileMetadataResult, GetJobStatusParams, GetJobStatusResult, HeartBeatParams,
-    HeartBeatResult, PollWorkParams, PollWorkResult, RegisterExecutorParams,
-    RegisterExecutorResult, RemoveSessionParams, RemoveSessionResult,
+    execute_query_failure_result, execute_query_result, job_status, AcquireJobsParams,
+    AcquireJobsResult, AvailableTaskSlots, CancelJobParams, CancelJobResult,
+    CleanJobDataParams, CleanJobDataResult, CreateSessionParams, CreateSessionResult,
+    CreateTemporaryTable, ExecuteQueryFailureResult, ExecuteQueryParams,
+    ExecuteQueryResult, ExecuteQuerySuccessResult, ExecutionGraphFormat,
+    ExecutorHeartbeat, ExecutorStoppedParams, ExecutorStoppedResult,
+    ExportExecutionGraphParams, ExportExecutionGraphResult, GetFileMetadataParams,
+    GetFileMetadataResult, GetJobPlanParams, GetJobPlanResult,
+    GetJobSchedulingTraceParams, GetJobStatusParams, GetJobStatusResult,
+    GetShuffleLocationsParams, GetShuffleLocationsResult, GetTaskLogsParams,
+    GetTaskLogsResult, HandOffJobsParams, HandOffJobsResult, HandshakeParams,
+    HandshakeResult, HeartBeatParams, HeartBeatResult, JobStatus, PollWorkParams,
+    PollWorkResult, PromoteSchedulerParams, PromoteSchedulerResult,
+    RegisterExecutorParams, RegisterExecutorResult, RemoveSessionParams,
+    RemoveSessionResult, ReportShuffleInventoryParams, ReportShuffleInventoryResult,
+    ResizeExecutorTaskSlotsParams, ResizeExecutorTaskSlotsResult, TaskStatus,
     UpdateSessionParams, UpdateSessionResult, UpdateTaskStatusParams,
-    UpdateTaskStatusResult,
+    UpdateTaskStatusResult, WatchJobStatusParams,
 };
 use ballista_core::serde::scheduler::ExecutorMetadata;
+use ballista_core::BALLISTA_VERSION;
 
+use datafusion::arrow::datatypes::Schema;
 use datafusion::datasource::file_format::parquet::ParquetFormat;
 use datafusion::datasource::file_format::FileFormat;
 use datafusion_proto::logical_plan::AsLogicalPlan;
@@ -43,18 +56,30 @@ use datafusion_proto::physical_plan::AsExecutionPlan;
 use futures::TryStreamExt;
 use log::{debug, error, info, trace, warn};
 use object_store::{local::LocalFileSystem, path::Path, ObjectStore};
+use prost::encoding::decode_varint;
 
 use std::ops::Deref;
 use std::sync::Arc;
 
-use crate::cluster::{bind_task_bias, bind_task_round_robin};
+use crate::cluster::{
+    bind_task_bias, bind_task_round_robin, bind_task_weighted, CapableExecutors,
+};
 use crate::config::TaskDistributionPolicy;
+use crate::scheduler_server::admin_statement::admin_statement_plan;
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::job_handoff::scheduler_client;
 use datafusion::prelude::SessionContext;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tonic::{Request, Response, Status};
 
-use crate::scheduler_server::SchedulerServer;
+use crate::scheduler_server::job_watch::{watch_job, JobWatchStream};
+use crate::scheduler_server::trace_watch::{
+    watch_scheduling_trace, SchedulingTraceStream,
+};
+use crate::scheduler_server::{timestamp_millis, SchedulerServer};
+use crate::state::execution_graph_dot::ExecutionGraphDot;
+use crate::state::execution_graph_json::ExecutionGraphDescription;
+use crate::state::session_manager::{with_collect_stage, with_query_settings};
 
 #[tonic::async_trait]
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
@@ -70,19 +95,26 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 "Bad request because poll work is not supported for push-based task scheduling",
             ));
         }
+        self.check_not_shutting_down()?;
         let remote_addr = request.remote_addr();
         if let PollWorkParams {
             metadata: Some(metadata),
             num_free_slots,
             task_status,
+            running_jobs,
         } = request.into_inner()
         {
             trace!("Received poll_work request for {:?}", metadata);
             let executor_id = metadata.id.clone();
+            let protocol_version = check_protocol_version(
+                &format!("Executor {executor_id}"),
+                metadata.protocol_version,
+                metadata.min_protocol_version,
+            )?;
 
             // It's not necessary.
             // It's only for the scheduler to have a picture of the whole executor cluster.
-            {
+            let capable_executors = {
                 let metadata = ExecutorMetadata {
                     id: metadata.id,
                     host: metadata
@@ -94,7 +126,21 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     port: metadata.port as u16,
                     grpc_port: metadata.grpc_port as u16,
                     specification: metadata.specification.unwrap().into(),
+                    zone: metadata.zone,
+                    rack: metadata.rack,
+                    instance_type: metadata.instance_type,
+                    capabilities: metadata
+                        .capabilities
+                        .map(Into::into)
+                        .unwrap_or_default(),
+                    protocol_version,
                 };
+                if let Err(e) = metadata.check_compatibility() {
+                    let msg = format!("Executor {executor_id} rejected: {e}");
+                    error!("{}", msg);
+                    return Err(Status::failed_precondition(msg));
+                }
+                let capable_executors = CapableExecutors::new([metadata.clone()]);
                 if let Err(e) = self
                     .state
                     .executor_manager
@@ -103,7 +149,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 {
                     warn!("Could not save executor metadata: {:?}", e);
                 }
-            }
+                capable_executors
+            };
 
             self.update_task_status(&executor_id, task_status)
                 .await
@@ -116,25 +163,66 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     Status::internal(msg)
                 })?;
 
+            let active_jobs = self
+                .state
+                .executor_manager
+                .jobs_for_executor(
+                    &executor_id,
+                    &running_jobs,
+                    self.state.task_manager.get_running_job_cache(),
+                )
+                .await;
             let mut available_slots = [AvailableTaskSlots {
                 executor_id,
                 slots: num_free_slots,
+                excess_slots: 0,
             }];
-            let available_slots = available_slots.iter_mut().collect();
-            let active_jobs = self.state.task_manager.get_running_job_cache();
-            let schedulable_tasks = match self.state.config.task_distribution {
-                TaskDistributionPolicy::Bias => {
-                    bind_task_bias(available_slots, active_jobs, |_| false).await
-                }
-                TaskDistributionPolicy::RoundRobin => {
-                    bind_task_round_robin(available_slots, active_jobs, |_| false).await
-                }
-                TaskDistributionPolicy::ConsistentHash{..} => {
-                    return Err(Status::unimplemented(
-                        "ConsistentHash TaskDistribution is not feasible for pull-based task scheduling"))
-                }
-            };
+            // The tasks of every job are bound with its own task distribution policy
+            let mut schedulable_tasks = vec![];
+            for (distribution, active_jobs) in self
+                .state
+                .executor_manager
+                .jobs_by_task_distribution(active_jobs)
+            {
+                let available_slots = available_slots.iter_mut().collect();
+                schedulable_tasks.extend(match distribution {
+                    TaskDistributionPolicy::Bias => {
+                        bind_task_bias(
+                            available_slots,
+                            &capable_executors,
+                            active_jobs,
+                            |_| false,
+                        )
+                        .await
+                    }
+                    TaskDistributionPolicy::RoundRobin => {
+                        bind_task_round_robin(
+                            available_slots,
+                            &capable_executors,
+                            active_jobs,
+                            |_| false,
+                        )
+                        .await
+                    }
+                    TaskDistributionPolicy::Weighted => {
+                        bind_task_weighted(
+                            available_slots,
+                            &capable_executors,
+                            active_jobs,
+                        )
+                        .await
+                    }
+                    TaskDistributionPolicy::ConsistentHash{..} => {
+                        return Err(Status::unimplemented(
+                            "ConsistentHash TaskDistribution is not feasible for pull-based task scheduling"))
+                    }
+                });
+            }
 
+            self.state
+                .task_manager
+                .scheduling_traces()
+                .record_bound_tasks(&schedulable_tasks);
             let mut tasks = vec![];
             for (_, task) in schedulable_tasks {
                 match self.state.task_manager.prepare_task_definition(task) {
@@ -161,6 +249,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         } = request.into_inner()
         {
             info!("Received register executor request for {:?}", metadata);
+            let protocol_version = check_protocol_version(
+                &format!("Executor {}", metadata.id),
+                metadata.protocol_version,
+                metadata.min_protocol_version,
+            )?;
             let metadata = ExecutorMetadata {
                 id: metadata.id,
                 host: metadata
@@ -172,6 +265,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 port: metadata.port as u16,
                 grpc_port: metadata.grpc_port as u16,
                 specification: metadata.specification.unwrap().into(),
+                zone: metadata.zone,
+                rack: metadata.rack,
+                instance_type: metadata.instance_type,
+                capabilities: metadata.capabilities.map(Into::into).unwrap_or_default(),
+                protocol_version,
             };
 
             self.do_register_executor(metadata).await.map_err(|e| {
@@ -197,18 +295,42 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
             metrics,
             status,
             metadata,
+            task_status,
+            running_jobs,
         } = request.into_inner();
         debug!("Received heart beat request for {:?}", executor_id);
 
         // If not registered, do registration first before saving heart beat
-        if let Err(e) = self
+        let registered = self
             .state
             .executor_manager
             .get_executor_metadata(&executor_id)
-            .await
+            .await;
+        if registered.is_ok()
+            && self.state.executor_manager.is_dead_executor(&executor_id)
         {
+            // The task slots of a dead executor have been released, so it needs to
+            // register again before it can be offered any tasks
+            info!(
+                "Received heart beat from dead executor {}, asking it to register again",
+                executor_id
+            );
+            // the executor does not send the status of its tasks again once it registers
+            self.update_heartbeat_task_status(&executor_id, task_status)
+                .await?;
+            return Ok(Response::new(HeartBeatResult {
+                reregister: true,
+                available_task_slots: 0,
+            }));
+        }
+        if let Err(e) = registered {
             warn!("Fail to get executor metadata: {}", e);
             if let Some(metadata) = metadata {
+                let protocol_version = check_protocol_version(
+                    &format!("Executor {}", metadata.id),
+                    metadata.protocol_version,
+                    metadata.min_protocol_version,
+                )?;
                 let metadata = ExecutorMetadata {
                     id: metadata.id,
                     host: metadata
@@ -220,6 +342,14 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     port: metadata.port as u16,
                     grpc_port: metadata.grpc_port as u16,
                     specification: metadata.specification.unwrap().into(),
+                    zone: metadata.zone,
+                    rack: metadata.rack,
+                    instance_type: metadata.instance_type,
+                    capabilities: metadata
+                        .capabilities
+                        .map(Into::into)
+                        .unwrap_or_default(),
+                    protocol_version,
                 };
 
                 self.do_register_executor(metadata).await.map_err(|e| {
@@ -235,13 +365,14 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         }
 
         let executor_heartbeat = ExecutorHeartbeat {
-            executor_id,
+            executor_id: executor_id.clone(),
             timestamp: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .expect("Time went backwards")
                 .as_secs(),
             metrics,
             status,
+            running_jobs,
         };
 
         self.state
@@ -253,7 +384,21 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 error!("{}", msg);
                 Status::internal(msg)
             })?;
-        Ok(Response::new(HeartBeatResult { reregister: false }))
+
+        self.update_heartbeat_task_status(&executor_id, task_status)
+            .await?;
+
+        // the heartbeats do not read the task slots of all the executors from the store
+        let available_task_slots = self
+            .state
+            .executor_manager
+            .get_cached_available_task_slots(&executor_id)
+            .await
+            .unwrap_or_default();
+        Ok(Response::new(HeartBeatResult {
+            reregister: false,
+            available_task_slots,
+        }))
     }
 
     async fn update_task_status(
@@ -269,6 +414,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
             "Received task status update request for executor {:?}",
             executor_id
         );
+        self.check_not_shutting_down()?;
 
         self.update_task_status(&executor_id, task_status)
             .await
@@ -396,6 +542,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         request: Request<RemoveSessionParams>,
     ) -> Result<Response<RemoveSessionResult>, Status> {
         let session_params = request.into_inner();
+        self.state
+            .remove_temporary_tables(&session_params.session_id);
         self.state
             .session_manager
             .remove_session(&session_params.session_id)
@@ -414,13 +562,21 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<ExecuteQueryParams>,
     ) -> Result<Response<ExecuteQueryResult>, Status> {
+        self.check_not_shutting_down()?;
         let query_params = request.into_inner();
         if let ExecuteQueryParams {
             query: Some(query),
             optional_session_id,
             settings,
+            temporary_table,
+            idempotency_key,
+            protocol_version,
+            min_protocol_version,
+            collect_stage,
         } = query_params
         {
+            check_protocol_version("Client", protocol_version, min_protocol_version)?;
+
             let mut query_settings = HashMap::new();
             for kv_pair in settings {
                 query_settings.insert(kv_pair.key, kv_pair.value);
@@ -465,14 +621,31 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 }
             };
 
+            let session_ctx = with_query_settings(session_ctx, &query_settings);
+            let session_ctx = with_collect_stage(session_ctx, collect_stage);
+
             let plan = match query {
                 Query::LogicalPlan(message) => {
-                    match T::try_decode(message.as_slice()).and_then(|m| {
-                        m.try_into_logical_plan(
-                            session_ctx.deref(),
-                            self.state.codec.logical_extension_codec(),
-                        )
-                    }) {
+                    // checked before decoding, which recurses as deep as the plan
+                    let max_depth = self.config.max_job_plan_depth as usize;
+                    let too_deep =
+                        max_depth > 0 && exceeds_message_depth(&message, max_depth);
+                    let plan = if too_deep {
+                        Err(format!(
+                            "the plan is nested deeper than the maximum of {max_depth} \
+                            messages"
+                        ))
+                    } else {
+                        T::try_decode(message.as_slice())
+                            .and_then(|m| {
+                                m.try_into_logical_plan(
+                                    session_ctx.deref(),
+                                    self.state.codec.logical_extension_codec(),
+                                )
+                            })
+                            .map_err(|e| e.to_string())
+                    };
+                    match plan {
                         Ok(plan) => plan,
                         Err(e) => {
                             let msg =
@@ -489,11 +662,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     }
                 }
                 Query::Sql(sql) => {
-                    match session_ctx
-                        .sql(&sql)
-                        .await
-                        .and_then(|df| df.into_optimized_plan())
-                    {
+                    let plan = match AdminStatement::parse(&sql) {
+                        Some(statement) => admin_statement_plan(statement),
+                        None => self.plan_sql(&session_id, &session_ctx, &sql).await,
+                    };
+                    match plan {
                         Ok(plan) => plan,
                         Err(e) => {
                             let msg = format!("Error parsing SQL: {e}");
@@ -512,12 +685,95 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
 
             debug!("Received plan for execution: {:?}", plan);
 
+            // the schema of the output is returned before the job runs
+            let schema: Schema = plan.schema().as_ref().into();
+            let schema =
+                datafusion_proto::protobuf::Schema::try_from(&schema).map_err(|e| {
+                    let msg = format!("Failed to serialize the schema of the query: {e}");
+                    error!("{}", msg);
+                    Status::internal(msg)
+                })?;
+
             let job_id = self.state.task_manager.generate_job_id();
             let job_name = query_settings
                 .get(BALLISTA_JOB_NAME)
                 .cloned()
                 .unwrap_or_else(|| "None".to_string());
 
+            // a retried submission returns the job of the first submission of the session
+            let idempotency_key_ttl = self.config.job_idempotency_key_ttl_seconds;
+            let idempotency_key = (!idempotency_key.is_empty()
+                && idempotency_key_ttl > 0)
+                .then(|| format!("{session_id}/{idempotency_key}"));
+            if let Some(key) = &idempotency_key {
+                let submitted_job_id = self
+                    .state
+                    .task_manager
+                    .get_idempotency_key(key)
+                    .await
+                    .map_err(|e| {
+                        let msg = format!("Failed to get idempotency key {key}: {e}");
+                        error!("{}", msg);
+                        Status::internal(msg)
+                    })?;
+                if let Some(job_id) = submitted_job_id {
+                    info!(
+                        "Job {} was already submitted with idempotency key {}",
+                        job_id, key
+                    );
+                    return Ok(self.submitted_job_result(job_id, session_id, schema));
+                }
+            }
+
+            let plan = match self.execute_admin_statement(plan).await {
+                Ok(plan) => plan,
+                Err(e) => {
+                    let msg = format!("Error executing statement: {e}");
+                    error!("{}", msg);
+                    return Ok(Response::new(ExecuteQueryResult {
+                        result: Some(execute_query_result::Result::Failure(
+                            ExecuteQueryFailureResult {
+                                failure: Some(execute_query_failure_result::Failure::PlanParsingFailure(msg)),
+                            },
+                        )),
+                    }));
+                }
+            };
+
+            // the table is added before the job is submitted, so that the output of the
+            // job is kept once it finishes, and only registered if the job succeeds
+            if let Some(CreateTemporaryTable { name, or_replace }) = temporary_table {
+                info!("Job {} creates temporary table {}", job_id, name);
+                if let Err(e) = self.state.add_temporary_table(
+                    &session_id,
+                    &session_ctx,
+                    &name,
+                    or_replace,
+                    &job_id,
+                    Arc::new(plan.schema().as_ref().into()),
+                ) {
+                    let msg = format!("Failed to create temporary table {name}: {e}");
+                    error!("{}", msg);
+                    return Ok(Response::new(ExecuteQueryResult {
+                        result: Some(execute_query_result::Result::Failure(
+                            ExecuteQueryFailureResult {
+                                failure: Some(execute_query_failure_result::Failure::PlanParsingFailure(msg)),
+                            },
+                        )),
+                    }));
+                }
+            }
+
+            // the job is queued behind the jobs already waiting to start
+            let job_queue_stats = self.query_stage_scheduler.job_queue_stats();
+            let estimated_start_delay_ms = job_queue_stats
+                .estimated_start_delay(
+                    job_queue_stats.queue_depth(),
+                    self.query_stage_scheduler.available_planning_slots(),
+                    timestamp_millis(),
+                )
+                .unwrap_or_default();
+
             self.submit_job(&job_id, &job_name, session_ctx, &plan)
                 .await
                 .map_err(|e| {
@@ -528,9 +784,53 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     Status::internal(msg)
                 })?;
 
+            // the key is only registered once the job is submitted, so that the retries
+            // of a failed submission are submitted again
+            if let Some(key) = &idempotency_key {
+                match self
+                    .state
+                    .task_manager
+                    .register_idempotency_key(
+                        key,
+                        &job_id,
+                        Duration::from_secs(idempotency_key_ttl),
+                    )
+                    .await
+                {
+                    Ok(Some(submitted_job_id)) => {
+                        // a concurrent submission with the same key was submitted first
+                        info!(
+                            "Job {} was already submitted with idempotency key {}, \
+                            cancelling job {}",
+                            submitted_job_id, key, job_id
+                        );
+                        self.query_stage_event_loop
+                            .get_sender()
+                            .map_err(|e| Status::internal(e.to_string()))?
+                            .post_event(QueryStageSchedulerEvent::JobCancel(job_id))
+                            .await
+                            .map_err(|e| Status::internal(e.to_string()))?;
+                        return Ok(self.submitted_job_result(
+                            submitted_job_id,
+                            session_id,
+                            schema,
+                        ));
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        warn!("Failed to register idempotency key {key}: {e}");
+                    }
+                }
+            }
+
             Ok(Response::new(ExecuteQueryResult {
                 result: Some(execute_query_result::Result::Success(
-                    ExecuteQuerySuccessResult { job_id, session_id },
+                    ExecuteQuerySuccessResult {
+                        job_id,
+                        session_id,
+                        schema: Some(schema),
+                        estimated_start_delay_ms,
+                    },
                 )),
             }))
         } else {
@@ -545,7 +845,57 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         let job_id = request.into_inner().job_id;
         trace!("Received get_job_status request for job {}", job_id);
         match self.state.task_manager.get_job_status(&job_id).await {
-            Ok(status) => Ok(Response::new(GetJobStatusResult { status })),
+            Ok(mut status) => {
+                if let Some(JobStatus {
+                    status: Some(job_status::Status::Queued(queued)),
+                    ..
+                }) = status.as_mut()
+                {
+                    queued.estimated_start_delay_ms = self
+                        .query_stage_scheduler
+                        .job_queue_stats()
+                        .estimated_job_start_delay(
+                            &job_id,
+                            self.query_stage_scheduler.available_planning_slots(),
+                            timestamp_millis(),
+                        )
+                        .unwrap_or_default();
+                }
+                Ok(Response::new(GetJobStatusResult { status }))
+            }
+            Err(e) => {
+                let msg = format!("Error getting status for job {job_id}: {e:?}");
+                error!("{}", msg);
+                Err(Status::internal(msg))
+            }
+        }
+    }
+
+    type WatchJobStatusStream = JobWatchStream;
+
+    async fn watch_job_status(
+        &self,
+        request: Request<WatchJobStatusParams>,
+    ) -> Result<Response<Self::WatchJobStatusStream>, Status> {
+        let job_id = request.into_inner().job_id;
+        trace!("Received watch_job_status request for job {}", job_id);
+        let task_manager = &self.state.task_manager;
+
+        // subscribe before reading the progress of the job so that no update is missed
+        let job_updates = task_manager.job_updates().await.map_err(|e| {
+            let msg = format!("Error watching updates of job {job_id}: {e:?}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+        match task_manager.get_job_progress(&job_id).await {
+            Ok(Some((status, successful_stages))) => Ok(Response::new(watch_job(
+                task_manager.clone(),
+                job_updates,
+                job_id,
+                status,
+                successful_stages,
+            ))),
+            Ok(None) => Err(Status::not_found(format!("Job {job_id} not found"))),
             Err(e) => {
                 let msg = format!("Error getting status for job {job_id}: {e:?}");
                 error!("{}", msg);
@@ -554,6 +904,60 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         }
     }
 
+    type GetJobSchedulingTraceStream = SchedulingTraceStream;
+
+    async fn get_job_scheduling_trace(
+        &self,
+        request: Request<GetJobSchedulingTraceParams>,
+    ) -> Result<Response<Self::GetJobSchedulingTraceStream>, Status> {
+        let GetJobSchedulingTraceParams { job_id, follow } = request.into_inner();
+        trace!(
+            "Received get_job_scheduling_trace request for job {}",
+            job_id
+        );
+        let task_manager = &self.state.task_manager;
+
+        if task_manager
+            .scheduling_traces()
+            .events_from(&job_id, u64::MAX)
+            .is_none()
+        {
+            match task_manager.get_job_status(&job_id).await {
+                // the traces are only kept in memory by the scheduler curating the job
+                Ok(Some(JobStatus {
+                    status: Some(job_status::Status::Running(running)),
+                    ..
+                })) if running.scheduler != self.scheduler_name => {
+                    let mut client = scheduler_client(&running.scheduler)
+                        .await
+                        .map_err(|e| Status::unavailable(e.to_string()))?;
+                    let events = client
+                        .get_job_scheduling_trace(GetJobSchedulingTraceParams {
+                            job_id,
+                            follow,
+                        })
+                        .await?
+                        .into_inner();
+                    return Ok(Response::new(Box::pin(events)));
+                }
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    return Err(Status::not_found(format!("Job {job_id} not found")))
+                }
+                Err(e) => {
+                    let msg = format!("Error getting status for job {job_id}: {e:?}");
+                    error!("{}", msg);
+                    return Err(Status::internal(msg));
+                }
+            }
+        }
+        Ok(Response::new(watch_scheduling_trace(
+            task_manager.clone(),
+            job_id,
+            follow,
+        )))
+    }
+
     async fn executor_stopped(
         &self,
         request: Request<ExecutorStoppedParams>,
@@ -561,6 +965,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         let ExecutorStoppedParams {
             executor_id,
             reason,
+            preempted,
         } = request.into_inner();
         info!(
             "Received executor stopped request from Executor {} with reason '{}'",
@@ -574,6 +979,34 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
             Status::internal(msg)
         })?;
 
+        if preempted {
+            // the shuffle outputs of the executor are lost once its instance is
+            // terminated, so they are replicated to other executors while it drains its
+            // running tasks, and the outputs failing to replicate are recomputed
+            let state = self.state.clone();
+            let event_sender = event_sender.clone();
+            let executor_id = executor_id.clone();
+            tokio::spawn(async move {
+                let missing = state.replicate_shuffle_outputs(&executor_id).await;
+                if missing.is_empty() {
+                    return;
+                }
+                warn!(
+                    "Recomputing the outputs of executor {} failing to replicate: {:?}",
+                    executor_id, missing
+                );
+                if let Err(e) = event_sender
+                    .post_event(QueryStageSchedulerEvent::ShuffleOutputsLost(
+                        executor_id,
+                        missing,
+                    ))
+                    .await
+                {
+                    error!("Post to query stage event loop error due to {e:?}");
+                }
+            });
+        }
+
         Self::remove_executor(
             executor_manager,
             event_sender,
@@ -632,121 +1065,1016 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
             })?;
         Ok(Response::new(CleanJobDataResult {}))
     }
-}
 
-#[cfg(all(test, feature = "sled"))]
-mod test {
-    use std::sync::Arc;
-    use std::time::Duration;
+    async fn export_execution_graph(
+        &self,
+        request: Request<ExportExecutionGraphParams>,
+    ) -> Result<Response<ExportExecutionGraphResult>, Status> {
+        let ExportExecutionGraphParams { job_id, format } = request.into_inner();
+        debug!("Received export execution graph request for job {}", job_id);
 
-    use datafusion_proto::protobuf::LogicalPlanNode;
-    use datafusion_proto::protobuf::PhysicalPlanNode;
-    use tonic::Request;
+        let graph = self
+            .state
+            .task_manager
+            .get_job_execution_graph(&job_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting execution graph for job {job_id}: {e}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
 
-    use crate::config::SchedulerConfig;
-    use crate::metrics::default_metrics_collector;
-    use ballista_core::error::BallistaError;
-    use ballista_core::serde::protobuf::{
-        executor_registration::OptionalHost, executor_status, ExecutorRegistration,
-        ExecutorStatus, ExecutorStoppedParams, HeartBeatParams, PollWorkParams,
-        RegisterExecutorParams,
-    };
-    use ballista_core::serde::scheduler::ExecutorSpecification;
-    use ballista_core::serde::BallistaCodec;
+        let content = match ExecutionGraphFormat::try_from(format) {
+            Ok(ExecutionGraphFormat::Dot) => ExecutionGraphDot::generate(&graph)
+                .map_err(|e| {
+                    let msg = format!("Error generating DOT for job {job_id}: {e:?}");
+                    error!("{}", msg);
+                    Status::internal(msg)
+                })?,
+            Ok(ExecutionGraphFormat::Json) => {
+                serde_json::to_string(&ExecutionGraphDescription::from(graph.as_ref()))
+                    .map_err(|e| {
+                        let msg = format!("Error generating JSON for job {job_id}: {e}");
+                        error!("{}", msg);
+                        Status::internal(msg)
+                    })?
+            }
+            Err(_) => {
+                return Err(Status::invalid_argument(format!(
+                    "Unknown execution graph format {format}"
+                )))
+            }
+        };
+        Ok(Response::new(ExportExecutionGraphResult { content }))
+    }
 
-    use crate::state::SchedulerState;
-    use crate::test_utils::await_condition;
-    use crate::test_utils::test_cluster_context;
+    async fn get_task_logs(
+        &self,
+        request: Request<GetTaskLogsParams>,
+    ) -> Result<Response<GetTaskLogsResult>, Status> {
+        let GetTaskLogsParams {
+            job_id,
+            stage_id,
+            task_id,
+        } = request.into_inner();
+        debug!(
+            "Received get task logs request for task {} of stage {} of job {}",
+            task_id, stage_id, job_id
+        );
 
-    use super::{SchedulerGrpc, SchedulerServer};
+        let result = self
+            .state
+            .get_task_logs(&job_id, stage_id as usize, task_id as usize)
+            .await
+            .unwrap_or_default();
+        Ok(Response::new(result))
+    }
 
-    #[tokio::test]
-    async fn test_poll_work() -> Result<(), BallistaError> {
-        let cluster = test_cluster_context();
+    async fn get_shuffle_locations(
+        &self,
+        request: Request<GetShuffleLocationsParams>,
+    ) -> Result<Response<GetShuffleLocationsResult>, Status> {
+        let GetShuffleLocationsParams {
+            job_id,
+            stage_id,
+            partition_id,
+        } = request.into_inner();
+        trace!(
+            "Received get shuffle locations request for partition {} of stage {} of job {}",
+            partition_id, stage_id, job_id
+        );
 
-        let config = SchedulerConfig::default();
-        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
-            SchedulerServer::new(
-                "localhost:50050".to_owned(),
-                cluster.clone(),
-                BallistaCodec::default(),
-                Arc::new(config),
-                default_metrics_collector().unwrap(),
-            );
-        scheduler.init().await?;
-        let exec_meta = ExecutorRegistration {
-            id: "abc".to_owned(),
-            optional_host: Some(OptionalHost::Host("http://localhost:8080".to_owned())),
-            port: 0,
-            grpc_port: 0,
-            specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
-        };
-        let request: Request<PollWorkParams> = Request::new(PollWorkParams {
-            metadata: Some(exec_meta.clone()),
-            num_free_slots: 0,
-            task_status: vec![],
-        });
-        let response = scheduler
-            .poll_work(request)
+        let (locations, complete) = self
+            .state
+            .task_manager
+            .get_shuffle_locations(&job_id, stage_id as usize, partition_id as usize)
             .await
-            .expect("Received error response")
-            .into_inner();
-        // no response task since we told the scheduler we didn't want to accept one
-        assert!(response.tasks.is_empty());
-        let state: SchedulerState<LogicalPlanNode, PhysicalPlanNode> =
-            SchedulerState::new_with_default_scheduler_name(
-                cluster.clone(),
-                BallistaCodec::default(),
-            );
-        state.init().await?;
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "No shuffle output of stage {stage_id} of active job {job_id}"
+                ))
+            })?;
+        let partition_location = locations
+            .into_iter()
+            .map(|location| location.try_into())
+            .collect::<Result<Vec<_>, BallistaError>>()
+            .map_err(|e| {
+                Status::internal(format!("Failed to encode shuffle locations: {e:?}"))
+            })?;
+        Ok(Response::new(GetShuffleLocationsResult {
+            partition_location,
+            complete,
+        }))
+    }
 
-        // executor should be registered
-        let stored_executor = state
+    async fn resize_executor_task_slots(
+        &self,
+        request: Request<ResizeExecutorTaskSlotsParams>,
+    ) -> Result<Response<ResizeExecutorTaskSlotsResult>, Status> {
+        let ResizeExecutorTaskSlotsParams {
+            executor_id,
+            task_slots,
+        } = request.into_inner();
+        // the executors polling for work report their own free slots
+        if !self.state.config.is_push_staged_scheduling() {
+            return Err(Status::failed_precondition(
+                "The task slots of executors can only be resized with push-staged scheduling",
+            ));
+        }
+        if task_slots == 0 {
+            return Err(Status::invalid_argument(
+                "The task slots of an executor must be at least one",
+            ));
+        }
+        if self
+            .state
             .executor_manager
-            .get_executor_metadata("abc")
-            .await
-            .expect("getting executor");
-
-        assert_eq!(stored_executor.grpc_port, 0);
-        assert_eq!(stored_executor.port, 0);
-        assert_eq!(stored_executor.specification.task_slots, 2);
-        assert_eq!(stored_executor.host, "http://localhost:8080".to_owned());
-
-        let request: Request<PollWorkParams> = Request::new(PollWorkParams {
-            metadata: Some(exec_meta.clone()),
-            num_free_slots: 1,
-            task_status: vec![],
-        });
-        let response = scheduler
-            .poll_work(request)
+            .get_executor_metadata(&executor_id)
             .await
-            .expect("Received error response")
-            .into_inner();
-
-        // still no response task since there are no tasks in the scheduler
-        assert!(response.tasks.is_empty());
-        let state: SchedulerState<LogicalPlanNode, PhysicalPlanNode> =
-            SchedulerState::new_with_default_scheduler_name(
-                cluster.clone(),
-                BallistaCodec::default(),
-            );
-        state.init().await?;
+            .is_err()
+        {
+            return Err(Status::not_found(format!(
+                "Executor {executor_id} not registered"
+            )));
+        }
 
-        // executor should be registered
-        let stored_executor = state
+        let previous_task_slots = self
+            .state
             .executor_manager
-            .get_executor_metadata("abc")
+            .resize_executor_task_slots(&executor_id, task_slots)
             .await
-            .expect("getting executor");
+            .map_err(|e| {
+                let msg =
+                    format!("Failed to resize task slots of executor {executor_id}: {e}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+        info!(
+            "Resized task slots of executor {} from {} to {}",
+            executor_id, previous_task_slots, task_slots
+        );
 
-        assert_eq!(stored_executor.grpc_port, 0);
-        assert_eq!(stored_executor.port, 0);
-        assert_eq!(stored_executor.specification.task_slots, 2);
+        if task_slots > previous_task_slots {
+            self.revive_offers().await.map_err(|e| {
+                let msg = format!("Failed to send revive offers event: {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+        }
+
+        Ok(Response::new(ResizeExecutorTaskSlotsResult {
+            previous_task_slots,
+        }))
+    }
+
+    async fn hand_off_jobs(
+        &self,
+        request: Request<HandOffJobsParams>,
+    ) -> Result<Response<HandOffJobsResult>, Status> {
+        let HandOffJobsParams {
+            job_ids,
+            scheduler_id,
+        } = request.into_inner();
+        if scheduler_id.is_empty() {
+            return Err(Status::invalid_argument(
+                "The scheduler to hand off the jobs to must be given",
+            ));
+        }
+        info!("Received request to hand off jobs to scheduler {scheduler_id}");
+
+        let job_ids = self
+            .hand_off_jobs(job_ids, &scheduler_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Failed to hand off jobs to {scheduler_id}: {e}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+        Ok(Response::new(HandOffJobsResult { job_ids }))
+    }
+
+    async fn acquire_jobs(
+        &self,
+        request: Request<AcquireJobsParams>,
+    ) -> Result<Response<AcquireJobsResult>, Status> {
+        let AcquireJobsParams { job_ids } = request.into_inner();
+        self.check_not_shutting_down()?;
+        if self.is_standby() {
+            return Err(Status::unavailable(format!(
+                "Scheduler {} is a standby",
+                self.scheduler_name
+            )));
+        }
+
+        let mut acquired = vec![];
+        for job_id in job_ids {
+            match self.state.task_manager.acquire_job(&job_id).await {
+                Ok(true) => acquired.push(job_id),
+                Ok(false) => warn!("Job {job_id} could not be acquired"),
+                Err(e) => error!("Failed to acquire job {job_id}: {e:?}"),
+            }
+        }
+        if !acquired.is_empty() {
+            self.revive_offers().await.map_err(|e| {
+                let msg = format!("Failed to send revive offers event: {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+        }
+
+        Ok(Response::new(AcquireJobsResult { job_ids: acquired }))
+    }
+
+    async fn promote_scheduler(
+        &self,
+        request: Request<PromoteSchedulerParams>,
+    ) -> Result<Response<PromoteSchedulerResult>, Status> {
+        let PromoteSchedulerParams { failed_schedulers } = request.into_inner();
+        if !self.is_standby() {
+            return Err(Status::failed_precondition(format!(
+                "Scheduler {} is not a standby",
+                self.scheduler_name
+            )));
+        }
+        info!("Received request to promote, failed schedulers {failed_schedulers:?}");
+
+        let job_ids = self.promote(failed_schedulers).await.map_err(|e| {
+            let msg = format!("Failed to promote scheduler {}: {e}", self.scheduler_name);
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+        Ok(Response::new(PromoteSchedulerResult { job_ids }))
+    }
+
+    async fn report_shuffle_inventory(
+        &self,
+        request: Request<ReportShuffleInventoryParams>,
+    ) -> Result<Response<ReportShuffleInventoryResult>, Status> {
+        let ReportShuffleInventoryParams {
+            executor_id,
+            jobs,
+            listed_at,
+        } = request.into_inner();
+        debug!(
+            "Received shuffle inventory of {} jobs from executor {}",
+            jobs.len(),
+            executor_id
+        );
+
+        let mut inventory = HashMap::new();
+        let mut stale_job_ids = vec![];
+        for job in jobs {
+            let status = self
+                .state
+                .task_manager
+                .get_job_status(&job.job_id)
+                .await
+                .map_err(|e| {
+                    let msg =
+                        format!("Error getting status of job {}: {e:?}", job.job_id);
+                    error!("{}", msg);
+                    Status::internal(msg)
+                })?;
+            if status.is_none() {
+                stale_job_ids.push(job.job_id);
+                continue;
+            }
+            let stages: HashMap<usize, HashSet<usize>> = job
+                .stages
+                .into_iter()
+                .map(|stage| {
+                    (
+                        stage.stage_id as usize,
+                        stage
+                            .map_partitions
+                            .into_iter()
+                            .map(|partition| partition as usize)
+                            .collect(),
+                    )
+                })
+                .collect();
+            inventory.insert(job.job_id, stages);
+        }
+
+        let missing = self
+            .state
+            .task_manager
+            .missing_shuffle_outputs(&executor_id, &inventory, listed_at as u128)
+            .await;
+        let recomputed_job_ids: Vec<String> = missing.keys().cloned().collect();
+        if !missing.is_empty() {
+            warn!(
+                "Shuffle outputs of executor {} missing: {:?}",
+                executor_id, missing
+            );
+            self.query_stage_event_loop
+                .get_sender()
+                .map_err(|e| {
+                    let msg = format!("Get query stage event loop error due to {e:?}");
+                    error!("{}", msg);
+                    Status::internal(msg)
+                })?
+                .post_event(QueryStageSchedulerEvent::ShuffleOutputsLost(
+                    executor_id,
+                    missing,
+                ))
+                .await
+                .map_err(|e| {
+                    let msg =
+                        format!("Post to query stage event loop error due to {e:?}");
+                    error!("{}", msg);
+                    Status::internal(msg)
+                })?;
+        }
+
+        Ok(Response::new(ReportShuffleInventoryResult {
+            recomputed_job_ids,
+            stale_job_ids,
+        }))
+    }
+
+    async fn handshake(
+        &self,
+        request: Request<HandshakeParams>,
+    ) -> Result<Response<HandshakeResult>, Status> {
+        let HandshakeParams {
+            protocol_version,
+            min_protocol_version,
+            ballista_version,
+        } = request.into_inner();
+        let protocol_version = check_protocol_version(
+            &format!("Peer of Ballista {ballista_version}"),
+            protocol_version,
+            min_protocol_version,
+        )?;
+        Ok(Response::new(HandshakeResult {
+            protocol_version,
+            ballista_version: BALLISTA_VERSION.to_owned(),
+        }))
+    }
+
+    async fn get_job_plan(
+        &self,
+        request: Request<GetJobPlanParams>,
+    ) -> Result<Response<GetJobPlanResult>, Status> {
+        let GetJobPlanParams { job_id, session_id } = request.into_inner();
+        debug!("Received get job plan request for job {}", job_id);
+        // the plans of the jobs of other sessions are reported as not found
+        let plan = self
+            .state
+            .task_manager
+            .get_job_plan(&job_id, &session_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Error getting the plan of job {job_id}: {e}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
+        Ok(Response::new(plan))
+    }
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T, U> {
+    /// Reject the requests with UNAVAILABLE once the scheduler started shutting down,
+    /// from when the jobs it releases may be taken over by other schedulers, so that they
+    /// are retried with another scheduler
+    fn check_not_shutting_down(&self) -> Result<(), Status> {
+        if self.is_shutting_down() {
+            return Err(Status::unavailable(format!(
+                "Scheduler {} is shutting down",
+                self.scheduler_name
+            )));
+        }
+        Ok(())
+    }
+
+    /// The result of a submission returning the job already submitted with its
+    /// idempotency key
+    fn submitted_job_result(
+        &self,
+        job_id: String,
+        session_id: String,
+        schema: datafusion_proto::protobuf::Schema,
+    ) -> Response<ExecuteQueryResult> {
+        let estimated_start_delay_ms = self
+            .query_stage_scheduler
+            .job_queue_stats()
+            .estimated_job_start_delay(
+                &job_id,
+                self.query_stage_scheduler.available_planning_slots(),
+                timestamp_millis(),
+            )
+            .unwrap_or_default();
+        Response::new(ExecuteQueryResult {
+            result: Some(execute_query_result::Result::Success(
+                ExecuteQuerySuccessResult {
+                    job_id,
+                    session_id,
+                    schema: Some(schema),
+                    estimated_start_delay_ms,
+                },
+            )),
+        })
+    }
+
+    /// Update the status of the tasks sent along with a heartbeat to save a request
+    async fn update_heartbeat_task_status(
+        &self,
+        executor_id: &str,
+        task_status: Vec<TaskStatus>,
+    ) -> Result<(), Status> {
+        if task_status.is_empty() {
+            return Ok(());
+        }
+        self.check_not_shutting_down()?;
+        self.update_task_status(executor_id, task_status)
+            .await
+            .map_err(|e| {
+                let msg = format!(
+                    "Fail to update tasks status from executor {:?} due to {:?}",
+                    executor_id, e
+                );
+                error!("{}", msg);
+                Status::internal(msg)
+            })
+    }
+}
+
+/// Check that a client or an executor speaks a version of the protocol of the scheduler,
+/// see [ballista_core::protocol]. Returns the negotiated version.
+fn check_protocol_version(
+    peer: &str,
+    version: u32,
+    min_version: u32,
+) -> Result<u32, Status> {
+    negotiate_protocol_version(peer, version, min_version).map_err(|e| {
+        warn!("{e}");
+        Status::failed_precondition(e.to_string())
+    })
+}
+
+/// Whether an encoded protobuf message nests messages deeper than `max_depth`, found
+/// without recursion. The length delimited fields which parse as messages are taken for
+/// nested messages, so that strings may count as messages too.
+fn exceeds_message_depth(message: &[u8], max_depth: usize) -> bool {
+    let mut messages = vec![(message, 1)];
+    while let Some((message, depth)) = messages.pop() {
+        if depth > max_depth {
+            return true;
+        }
+        if let Some(fields) = length_delimited_fields(message) {
+            messages.extend(fields.into_iter().map(|field| (field, depth + 1)));
+        }
+    }
+    false
+}
+
+/// The length delimited fields of an encoded protobuf message, None if it does not parse
+/// as a message
+fn length_delimited_fields(mut message: &[u8]) -> Option<Vec<&[u8]>> {
+    let mut fields = vec![];
+    while !message.is_empty() {
+        let key = decode_varint(&mut message).ok()?;
+        if key >> 3 == 0 {
+            return None;
+        }
+        match key & 0x7 {
+            0 => {
+                decode_varint(&mut message).ok()?;
+            }
+            1 => message = message.get(8..)?,
+            2 => {
+                let len = usize::try_from(decode_varint(&mut message).ok()?).ok()?;
+                fields.push(message.get(..len)?);
+                message = &message[len..];
+            }
+            5 => message = message.get(4..)?,
+            _ => return None,
+        }
+    }
+    Some(fields)
+}
+
+#[cfg(all(test, feature = "sled"))]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use datafusion::arrow::datatypes::{DataType, Schema};
+    use datafusion::prelude::SessionContext;
+    use datafusion_proto::logical_plan::{AsLogicalPlan, DefaultLogicalExtensionCodec};
+    use datafusion_proto::protobuf::LogicalPlanNode;
+    use datafusion_proto::protobuf::PhysicalPlanNode;
+    use tonic::Request;
+
+    use crate::config::SchedulerConfig;
+    use crate::metrics::default_metrics_collector;
+    use ballista_core::config::BallistaConfig;
+    use ballista_core::error::BallistaError;
+    use ballista_core::protocol::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+    use ballista_core::serde::protobuf::execute_query_params::{
+        OptionalSessionId, Query,
+    };
+    use ballista_core::serde::protobuf::{
+        execute_query_result, executor_registration::OptionalHost, executor_status,
+        ExecuteQueryParams, ExecutorRegistration, ExecutorStatus, ExecutorStoppedParams,
+        GetJobPlanParams, HandshakeParams, HeartBeatParams, PollWorkParams,
+        RegisterExecutorParams,
+    };
+    use ballista_core::serde::scheduler::{ExecutorCapabilities, ExecutorSpecification};
+    use ballista_core::serde::BallistaCodec;
+    use ballista_core::BALLISTA_VERSION;
+
+    use crate::state::SchedulerState;
+    use crate::test_utils::await_condition;
+    use crate::test_utils::test_cluster_context;
+
+    use super::{SchedulerGrpc, SchedulerServer};
+
+    #[tokio::test]
+    async fn test_poll_work() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default();
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+        let exec_meta = ExecutorRegistration {
+            id: "abc".to_owned(),
+            optional_host: Some(OptionalHost::Host("http://localhost:8080".to_owned())),
+            port: 0,
+            grpc_port: 0,
+            specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            zone: String::new(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: None,
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+        };
+        let request: Request<PollWorkParams> = Request::new(PollWorkParams {
+            metadata: Some(exec_meta.clone()),
+            num_free_slots: 0,
+            task_status: vec![],
+            running_jobs: vec![],
+        });
+        let response = scheduler
+            .poll_work(request)
+            .await
+            .expect("Received error response")
+            .into_inner();
+        // no response task since we told the scheduler we didn't want to accept one
+        assert!(response.tasks.is_empty());
+        let state: SchedulerState<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerState::new_with_default_scheduler_name(
+                cluster.clone(),
+                BallistaCodec::default(),
+            );
+        state.init().await?;
+
+        // executor should be registered
+        let stored_executor = state
+            .executor_manager
+            .get_executor_metadata("abc")
+            .await
+            .expect("getting executor");
+
+        assert_eq!(stored_executor.grpc_port, 0);
+        assert_eq!(stored_executor.port, 0);
+        assert_eq!(stored_executor.specification.task_slots, 2);
+        assert_eq!(stored_executor.host, "http://localhost:8080".to_owned());
+
+        let request: Request<PollWorkParams> = Request::new(PollWorkParams {
+            metadata: Some(exec_meta.clone()),
+            num_free_slots: 1,
+            task_status: vec![],
+            running_jobs: vec![],
+        });
+        let response = scheduler
+            .poll_work(request)
+            .await
+            .expect("Received error response")
+            .into_inner();
+
+        // still no response task since there are no tasks in the scheduler
+        assert!(response.tasks.is_empty());
+        let state: SchedulerState<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerState::new_with_default_scheduler_name(
+                cluster.clone(),
+                BallistaCodec::default(),
+            );
+        state.init().await?;
+
+        // executor should be registered
+        let stored_executor = state
+            .executor_manager
+            .get_executor_metadata("abc")
+            .await
+            .expect("getting executor");
+
+        assert_eq!(stored_executor.grpc_port, 0);
+        assert_eq!(stored_executor.port, 0);
+        assert_eq!(stored_executor.specification.task_slots, 2);
         assert_eq!(stored_executor.host, "http://localhost:8080".to_owned());
 
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_execute_query_schema() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default();
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let request = Request::new(ExecuteQueryParams {
+            query: Some(Query::Sql("SELECT 1 AS a, 'x' AS b".to_owned())),
+            settings: vec![],
+            temporary_table: None,
+            optional_session_id: None,
+            idempotency_key: String::new(),
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            collect_stage: false,
+        });
+        let response = scheduler
+            .execute_query(request)
+            .await
+            .expect("Received error response")
+            .into_inner();
+        let Some(execute_query_result::Result::Success(result)) = response.result else {
+            panic!("Expected a successful result, got {response:?}");
+        };
+
+        // the schema of the output is known before the job runs
+        let schema: Schema = result.schema.as_ref().unwrap().try_into()?;
+        assert_eq!(
+            vec!["a", "b"],
+            schema
+                .fields()
+                .iter()
+                .map(|field| field.name().as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(&DataType::Int64, schema.field(0).data_type());
+        assert_eq!(&DataType::Utf8, schema.field(1).data_type());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_query_max_plan_depth() -> Result<(), BallistaError> {
+        let ctx = SessionContext::new();
+        let plan = ctx.sql("SELECT 1 AS a").await?.into_optimized_plan()?;
+        let mut message = vec![];
+        LogicalPlanNode::try_from_logical_plan(&plan, &DefaultLogicalExtensionCodec {})?
+            .try_encode(&mut message)?;
+
+        for (max_plan_depth, accepted) in [(0, true), (100, true), (2, false)] {
+            let cluster = test_cluster_context();
+            let config =
+                SchedulerConfig::default().with_max_job_plan_depth(max_plan_depth);
+            let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+                SchedulerServer::new(
+                    "localhost:50050".to_owned(),
+                    cluster,
+                    BallistaCodec::default(),
+                    Arc::new(config),
+                    default_metrics_collector().unwrap(),
+                );
+            scheduler.init().await?;
+
+            let request = Request::new(ExecuteQueryParams {
+                query: Some(Query::LogicalPlan(message.clone())),
+                settings: vec![],
+                temporary_table: None,
+                optional_session_id: None,
+                idempotency_key: String::new(),
+                protocol_version: PROTOCOL_VERSION,
+                min_protocol_version: MIN_PROTOCOL_VERSION,
+                collect_stage: false,
+            });
+            let response = scheduler.execute_query(request).await?.into_inner();
+            match response.result {
+                Some(execute_query_result::Result::Success(_)) if accepted => {}
+                Some(execute_query_result::Result::Failure(failure)) if !accepted => {
+                    let failure = format!("{failure:?}");
+                    assert!(failure.contains("nested deeper"), "{failure}");
+                }
+                other => panic!(
+                    "Expected the plan to be accepted: {accepted} but found {other:?}"
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_job_plan() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default();
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let request = Request::new(ExecuteQueryParams {
+            query: Some(Query::Sql("SELECT 1 AS a".to_owned())),
+            settings: vec![],
+            temporary_table: None,
+            optional_session_id: None,
+            idempotency_key: String::new(),
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            collect_stage: false,
+        });
+        let response = scheduler.execute_query(request).await?.into_inner();
+        let Some(execute_query_result::Result::Success(result)) = response.result else {
+            panic!("Expected a successful result, got {response:?}");
+        };
+
+        // jobs are planned asynchronously
+        let job_id = result.job_id;
+        let session_id = result.session_id;
+        let task_manager = scheduler.state.task_manager.clone();
+        let planned = await_condition(Duration::from_millis(10), 100, || {
+            let task_manager = task_manager.clone();
+            let job_id = job_id.clone();
+            async move {
+                Ok(task_manager
+                    .get_job_execution_graph(&job_id)
+                    .await?
+                    .is_some())
+            }
+        })
+        .await?;
+        assert!(planned, "Job not planned after 1s");
+
+        let plan = scheduler
+            .get_job_plan(Request::new(GetJobPlanParams {
+                job_id: job_id.clone(),
+                session_id: session_id.clone(),
+            }))
+            .await?
+            .into_inner();
+        assert!(
+            plan.logical_plan.contains("Projection"),
+            "{}",
+            plan.logical_plan
+        );
+        assert!(!plan.encoded_logical_plan.is_empty());
+        assert_eq!(1, plan.stages.len());
+        assert!(plan.stages[0].plan.contains("ShuffleWriterExec"));
+        assert!(!plan.stages[0].encoded_plan.is_empty());
+
+        // the logical plan is stored once per job, not in the snapshots of the graph
+        let stored = cluster.job_state().get_job_plan(&job_id).await?.unwrap();
+        assert_eq!(plan.logical_plan, stored.logical_plan);
+
+        let missing = scheduler
+            .get_job_plan(Request::new(GetJobPlanParams {
+                job_id: "missing".to_owned(),
+                session_id: session_id.clone(),
+            }))
+            .await;
+        assert_eq!(tonic::Code::NotFound, missing.unwrap_err().code());
+        // the plan is not returned to other sessions
+        let other_session = scheduler
+            .get_job_plan(Request::new(GetJobPlanParams {
+                job_id,
+                session_id: "other-session".to_owned(),
+            }))
+            .await;
+        assert_eq!(tonic::Code::NotFound, other_session.unwrap_err().code());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_query_idempotency_key() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default();
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let mut session_ids = vec![];
+        for _ in 0..2 {
+            let session = scheduler
+                .state
+                .session_manager
+                .create_session(&BallistaConfig::builder().build()?)
+                .await?;
+            session_ids.push(session.session_id());
+        }
+        let submit = |session_id: &str, idempotency_key: &str| {
+            let request = Request::new(ExecuteQueryParams {
+                query: Some(Query::Sql("SELECT 1".to_owned())),
+                settings: vec![],
+                temporary_table: None,
+                optional_session_id: Some(OptionalSessionId::SessionId(
+                    session_id.to_owned(),
+                )),
+                idempotency_key: idempotency_key.to_owned(),
+                protocol_version: PROTOCOL_VERSION,
+                min_protocol_version: MIN_PROTOCOL_VERSION,
+                collect_stage: false,
+            });
+            let scheduler = &scheduler;
+            async move {
+                let response = scheduler
+                    .execute_query(request)
+                    .await
+                    .expect("Received error response")
+                    .into_inner();
+                match response.result {
+                    Some(execute_query_result::Result::Success(result)) => result.job_id,
+                    _ => panic!("Expected a successful result, got {response:?}"),
+                }
+            }
+        };
+
+        // a retried submission returns the job of the first submission
+        let session_id = &session_ids[0];
+        let job_id = submit(session_id, "key-1").await;
+        assert_eq!(job_id, submit(session_id, "key-1").await);
+        assert_ne!(job_id, submit(session_id, "key-2").await);
+        // the keys of other sessions are distinct
+        assert_ne!(job_id, submit(&session_ids[1], "key-1").await);
+        // submissions without key are never deduplicated
+        assert_ne!(submit(session_id, "").await, submit(session_id, "").await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handshake() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default();
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let result = scheduler
+            .handshake(Request::new(HandshakeParams {
+                protocol_version: PROTOCOL_VERSION + 1,
+                min_protocol_version: MIN_PROTOCOL_VERSION,
+                ballista_version: "future".to_owned(),
+            }))
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert_eq!(PROTOCOL_VERSION, result.protocol_version);
+        assert_eq!(BALLISTA_VERSION, result.ballista_version);
+
+        // a client no longer speaking the protocol of the scheduler is rejected
+        let status = scheduler
+            .execute_query(Request::new(ExecuteQueryParams {
+                query: Some(Query::Sql("SELECT 1".to_owned())),
+                settings: vec![],
+                temporary_table: None,
+                optional_session_id: None,
+                idempotency_key: String::new(),
+                protocol_version: PROTOCOL_VERSION + 2,
+                min_protocol_version: PROTOCOL_VERSION + 1,
+                collect_stage: false,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(tonic::Code::FailedPrecondition, status.code());
+        assert!(status.message().contains("must be upgraded"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_register_executor_capabilities() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default();
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let registration = |id: &str, capabilities: ExecutorCapabilities, version| {
+            Request::new(RegisterExecutorParams {
+                metadata: Some(ExecutorRegistration {
+                    id: id.to_owned(),
+                    optional_host: Some(OptionalHost::Host(
+                        "http://localhost:8080".to_owned(),
+                    )),
+                    port: 0,
+                    grpc_port: 0,
+                    specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+                    zone: String::new(),
+                    rack: String::new(),
+                    instance_type: String::new(),
+                    capabilities: Some(capabilities.into()),
+                    protocol_version: version,
+                    min_protocol_version: MIN_PROTOCOL_VERSION,
+                }),
+            })
+        };
+
+        let capabilities = ExecutorCapabilities::local(1024);
+        let response = scheduler
+            .register_executor(registration(
+                "compatible",
+                capabilities.clone(),
+                PROTOCOL_VERSION,
+            ))
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert!(response.success);
+        let stored_executor = scheduler
+            .state
+            .executor_manager
+            .get_executor_metadata("compatible")
+            .await?;
+        assert_eq!(capabilities, stored_executor.capabilities);
+        assert_eq!(PROTOCOL_VERSION, stored_executor.protocol_version);
+
+        // an executor of another major version of DataFusion cannot run the plans
+        let incompatible = ExecutorCapabilities {
+            datafusion_version: "1.0.0".to_owned(),
+            ..capabilities
+        };
+        assert!(scheduler
+            .register_executor(registration(
+                "incompatible",
+                incompatible.clone(),
+                PROTOCOL_VERSION,
+            ))
+            .await
+            .is_err());
+        assert!(scheduler
+            .state
+            .executor_manager
+            .get_executor_metadata("incompatible")
+            .await
+            .is_err());
+
+        // the capabilities of the executors predating the negotiation are not checked
+        let response = scheduler
+            .register_executor(registration("legacy", incompatible, 0))
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert!(response.success);
+        let stored_executor = scheduler
+            .state
+            .executor_manager
+            .get_executor_metadata("legacy")
+            .await?;
+        assert_eq!(1, stored_executor.protocol_version);
+        assert!(!stored_executor.advertises_capabilities());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_stop_executor() -> Result<(), BallistaError> {
         let cluster = test_cluster_context();
@@ -768,11 +2096,18 @@ mod test {
             port: 0,
             grpc_port: 0,
             specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            zone: String::new(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: None,
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
         };
 
         let request: Request<RegisterExecutorParams> =
             Request::new(RegisterExecutorParams {
                 metadata: Some(exec_meta.clone()),
+                task_status: vec![],
             });
         let response = scheduler
             .register_executor(request)
@@ -800,6 +2135,7 @@ mod test {
             Request::new(ExecutorStoppedParams {
                 executor_id: "abc".to_owned(),
                 reason: "test_stop".to_owned(),
+                preempted: false,
             });
 
         let _response = scheduler
@@ -853,6 +2189,12 @@ mod test {
             port: 0,
             grpc_port: 0,
             specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            zone: String::new(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: None,
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
         };
 
         let request: Request<HeartBeatParams> = Request::new(HeartBeatParams {
@@ -862,11 +2204,16 @@ mod test {
                 status: Some(executor_status::Status::Active("".to_string())),
             }),
             metadata: Some(exec_meta.clone()),
+            task_status: vec![],
+            running_jobs: vec![],
         });
-        scheduler
+        let response = scheduler
             .heart_beat_from_executor(request)
             .await
-            .expect("Received error response");
+            .expect("Received error response")
+            .into_inner();
+        // the available task slots of the executor are sent back
+        assert_eq!(2, response.available_task_slots);
 
         let state = scheduler.state.clone();
         // executor should be registered
@@ -884,6 +2231,90 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_reregister_dead_executor() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let config = SchedulerConfig::default();
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster,
+                BallistaCodec::default(),
+                Arc::new(config),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let exec_meta = ExecutorRegistration {
+            id: "abc".to_owned(),
+            optional_host: Some(OptionalHost::Host("http://localhost:8080".to_owned())),
+            port: 0,
+            grpc_port: 0,
+            specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            zone: String::new(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: None,
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+        };
+        let heartbeat = || {
+            Request::new(HeartBeatParams {
+                executor_id: exec_meta.id.clone(),
+                metrics: vec![],
+                status: Some(ExecutorStatus {
+                    status: Some(executor_status::Status::Active("".to_string())),
+                }),
+                metadata: Some(exec_meta.clone()),
+                task_status: vec![],
+                running_jobs: vec![],
+            })
+        };
+
+        let response = scheduler
+            .heart_beat_from_executor(heartbeat())
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert!(!response.reregister);
+
+        // the executor is declared dead, e.g. because its heartbeats timed out
+        let state = scheduler.state.clone();
+        state
+            .executor_manager
+            .remove_executor("abc", Some("test".to_string()))
+            .await?;
+
+        let response = scheduler
+            .heart_beat_from_executor(heartbeat())
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert!(response.reregister);
+        assert!(state.executor_manager.is_dead_executor("abc"));
+
+        let response = scheduler
+            .register_executor(Request::new(RegisterExecutorParams {
+                metadata: Some(exec_meta.clone()),
+                task_status: vec![],
+            }))
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert!(response.success);
+
+        let response = scheduler
+            .heart_beat_from_executor(heartbeat())
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert!(!response.reregister);
+        assert!(!state.executor_manager.is_dead_executor("abc"));
+
+        Ok(())
+    }
+
     #[tokio::test]
     #[ignore]
     async fn test_expired_executor() -> Result<(), BallistaError> {
@@ -906,11 +2337,18 @@ mod test {
             port: 0,
             grpc_port: 0,
             specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            zone: String::new(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: None,
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
         };
 
         let request: Request<RegisterExecutorParams> =
             Request::new(RegisterExecutorParams {
                 metadata: Some(exec_meta.clone()),
+                task_status: vec![],
             });
         let response = scheduler
             .register_executor(request)
@@ -942,6 +2380,8 @@ mod test {
                 status: Some(executor_status::Status::Active("".to_string())),
             }),
             metadata: Some(exec_meta.clone()),
+            task_status: vec![],
+            running_jobs: vec![],
         });
 
         let _response = scheduler
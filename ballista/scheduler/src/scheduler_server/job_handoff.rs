@@ -0,0 +1,386 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::serde::protobuf::{
+    AcquireJobsParams, TaskStatus, UpdateTaskStatusParams,
+};
+use ballista_core::utils::create_grpc_client_connection;
+use dashmap::DashMap;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+use log::{error, info, warn};
+
+use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::SchedulerServer;
+
+/// Max time to wait for the pending events of the jobs handed off to be processed before
+/// releasing the jobs
+const HANDOFF_FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Status updates of the tasks of a job, keyed by the executor running the tasks
+type TaskStatusUpdates = Vec<(String, Vec<TaskStatus>)>;
+
+enum JobHandoff {
+    /// The job is being handed off, the status of its tasks is held until the other
+    /// scheduler acquires the job or the job is restored on this scheduler
+    Pending(TaskStatusUpdates),
+    /// The job is owned by the scheduler with the given endpoint, to which the status of its
+    /// tasks is forwarded, e.g. from the executors polling this scheduler for work
+    Completed(String),
+}
+
+/// The jobs handed off by this scheduler to other schedulers
+#[derive(Default)]
+pub(crate) struct JobHandoffs {
+    jobs: DashMap<String, JobHandoff>,
+}
+
+impl JobHandoffs {
+    fn start(&self, job_id: &str) {
+        self.jobs
+            .insert(job_id.to_owned(), JobHandoff::Pending(vec![]));
+    }
+
+    /// Record the new scheduler of a job, returns the updates held while the job was handed
+    /// off
+    fn complete(&self, job_id: &str, scheduler_id: &str) -> TaskStatusUpdates {
+        match self.jobs.insert(
+            job_id.to_owned(),
+            JobHandoff::Completed(scheduler_id.to_owned()),
+        ) {
+            Some(JobHandoff::Pending(updates)) => updates,
+            _ => vec![],
+        }
+    }
+
+    /// Forget the handoff of a job which stays with this scheduler, returns the updates held
+    /// while the job was handed off
+    fn cancel(&self, job_id: &str) -> TaskStatusUpdates {
+        match self.jobs.remove(job_id) {
+            Some((_, JobHandoff::Pending(updates))) => updates,
+            _ => vec![],
+        }
+    }
+
+    /// Split the status updates of tasks between the jobs of this scheduler, which are
+    /// returned, and the jobs handed off. The updates of the jobs being handed off are held
+    /// and those of the jobs handed off are returned by scheduler.
+    fn intercept(
+        &self,
+        executor_id: &str,
+        tasks_status: Vec<TaskStatus>,
+    ) -> (Vec<TaskStatus>, HashMap<String, Vec<TaskStatus>>) {
+        if self.jobs.is_empty() {
+            return (tasks_status, HashMap::new());
+        }
+        let mut local = vec![];
+        let mut forwarded: HashMap<String, Vec<TaskStatus>> = HashMap::new();
+        for status in tasks_status {
+            match self.jobs.get_mut(&status.job_id).as_deref_mut() {
+                Some(JobHandoff::Pending(updates)) => match updates.last_mut() {
+                    Some((executor, held)) if executor == executor_id => {
+                        held.push(status)
+                    }
+                    _ => updates.push((executor_id.to_owned(), vec![status])),
+                },
+                Some(JobHandoff::Completed(scheduler_id)) => forwarded
+                    .entry(scheduler_id.clone())
+                    .or_default()
+                    .push(status),
+                None => local.push(status),
+            }
+        }
+        (local, forwarded)
+    }
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T, U> {
+    /// Transfer the ownership of active jobs to the scheduler with the endpoint `target`,
+    /// e.g. to drain this scheduler, all the active jobs when `job_ids` is empty. The jobs
+    /// are released to the other scheduler, which alone may acquire them, see
+    /// [crate::cluster::JobState::release_job], and the executors are told to send the
+    /// status of their tasks to it once the global state shows it acquired them. The jobs
+    /// the other scheduler fails to acquire stay with this scheduler. Returns the jobs
+    /// handed off.
+    pub(crate) async fn hand_off_jobs(
+        &self,
+        job_ids: Vec<String>,
+        target: &str,
+    ) -> Result<Vec<String>> {
+        if target == self.scheduler_name {
+            return Err(BallistaError::General(format!(
+                "Scheduler {target} cannot hand off jobs to itself"
+            )));
+        }
+        let job_ids: Vec<String> = if job_ids.is_empty() {
+            self.state
+                .task_manager
+                .get_running_job_cache()
+                .keys()
+                .cloned()
+                .collect()
+        } else {
+            job_ids
+        };
+
+        // hold the status updates of the jobs from now on, then let the event loop process
+        // the updates received so far before releasing the jobs
+        for job_id in &job_ids {
+            self.job_handoffs.start(job_id);
+        }
+        if !self.flush_events(HANDOFF_FLUSH_TIMEOUT).await {
+            warn!(
+                "Scheduler events still pending after {:?}, handing off jobs anyway",
+                HANDOFF_FLUSH_TIMEOUT
+            );
+        }
+
+        let mut released = HashMap::new();
+        for job_id in job_ids {
+            match self.state.task_manager.release_job(&job_id, target).await {
+                Ok(Some(job_info)) => {
+                    released.insert(job_id, job_info);
+                }
+                Ok(None) => {
+                    warn!("Job {job_id} is not active, not handed off");
+                    self.restore_task_status(&job_id).await?;
+                }
+                Err(e) => {
+                    error!("Failed to release job {job_id}: {e:?}");
+                    self.restore_task_status(&job_id).await?;
+                }
+            }
+        }
+        if released.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let claimed: HashSet<String> = match self
+            .acquire_jobs_on(target, released.keys().cloned().collect())
+            .await
+        {
+            Ok(acquired) => acquired.into_iter().collect(),
+            Err(e) => {
+                error!("Failed to hand off jobs to scheduler {target}: {e:?}");
+                HashSet::new()
+            }
+        };
+
+        let mut handed_off = vec![];
+        let mut restored = false;
+        for (job_id, (job_info, epoch)) in released {
+            // the jobs claimed by the other scheduler must be owned by it in the global
+            // state, the others are taken back unless it acquired them in the meantime
+            let acquired = claimed.contains(&job_id)
+                && self.is_acquired_by(&job_id, target, epoch).await;
+            let acquired = acquired
+                || match self
+                    .state
+                    .task_manager
+                    .restore_job(&job_id, job_info, target, epoch)
+                    .await
+                {
+                    Ok(true) => {
+                        warn!(
+                            "Job {job_id} not acquired by scheduler {target}, restored"
+                        );
+                        restored = true;
+                        false
+                    }
+                    Ok(false) => true,
+                    Err(e) => {
+                        error!("Failed to restore job {job_id}: {e:?}");
+                        false
+                    }
+                };
+            if acquired {
+                info!("Handed off job {job_id} to scheduler {target}");
+                self.metrics_collector().record_handed_off(&job_id);
+                let updates = self.job_handoffs.complete(&job_id, target);
+                for (executor_id, tasks_status) in updates {
+                    if let Err(e) = self
+                        .forward_task_status(target, &executor_id, tasks_status)
+                        .await
+                    {
+                        error!("Failed to forward task status of job {job_id}: {e:?}");
+                    }
+                }
+                if self.state.config.is_push_staged_scheduling() {
+                    self.state
+                        .executor_manager
+                        .update_job_scheduler(&job_id, target)
+                        .await;
+                }
+                handed_off.push(job_id);
+            } else {
+                self.restore_task_status(&job_id).await?;
+            }
+        }
+        if restored {
+            self.revive_offers().await?;
+        }
+        Ok(handed_off)
+    }
+
+    async fn is_acquired_by(&self, job_id: &str, target: &str, epoch: u64) -> bool {
+        match self
+            .state
+            .task_manager
+            .is_acquired_by(job_id, target, epoch)
+            .await
+        {
+            Ok(acquired) => acquired,
+            Err(e) => {
+                error!("Failed to check the owner of job {job_id}: {e:?}");
+                false
+            }
+        }
+    }
+
+    /// Hold the status updates of the jobs being handed off, and forward those of the jobs
+    /// handed off to their new scheduler. Returns the updates of the jobs of this scheduler.
+    pub(crate) async fn intercept_task_status(
+        &self,
+        executor_id: &str,
+        tasks_status: Vec<TaskStatus>,
+    ) -> Vec<TaskStatus> {
+        let (local, forwarded) = self.job_handoffs.intercept(executor_id, tasks_status);
+        // the updates of the other jobs are processed even if some cannot be forwarded
+        for (scheduler_id, tasks_status) in forwarded {
+            if let Err(e) = self
+                .forward_task_status(&scheduler_id, executor_id, tasks_status)
+                .await
+            {
+                error!(
+                    "Failed to forward task status to scheduler {scheduler_id}: {e:?}"
+                );
+            }
+        }
+        local
+    }
+
+    /// Post the status updates held during the handoff of a job which stays with this
+    /// scheduler
+    async fn restore_task_status(&self, job_id: &str) -> Result<()> {
+        for (executor_id, tasks_status) in self.job_handoffs.cancel(job_id) {
+            self.query_stage_event_loop
+                .get_sender()?
+                .post_event(QueryStageSchedulerEvent::TaskUpdating(
+                    executor_id,
+                    tasks_status,
+                ))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn acquire_jobs_on(
+        &self,
+        scheduler_id: &str,
+        job_ids: Vec<String>,
+    ) -> Result<Vec<String>> {
+        let mut client = scheduler_client(scheduler_id).await?;
+        let result = client
+            .acquire_jobs(AcquireJobsParams { job_ids })
+            .await
+            .map_err(|e| BallistaError::GrpcActionError(format!("{e:?}")))?;
+        Ok(result.into_inner().job_ids)
+    }
+
+    async fn forward_task_status(
+        &self,
+        scheduler_id: &str,
+        executor_id: &str,
+        task_status: Vec<TaskStatus>,
+    ) -> Result<()> {
+        let mut client = scheduler_client(scheduler_id).await?;
+        client
+            .update_task_status(UpdateTaskStatusParams {
+                executor_id: executor_id.to_owned(),
+                task_status,
+            })
+            .await
+            .map_err(|e| BallistaError::GrpcActionError(format!("{e:?}")))?;
+        Ok(())
+    }
+}
+
+pub(super) async fn scheduler_client(
+    scheduler_id: &str,
+) -> Result<SchedulerGrpcClient<tonic::transport::Channel>> {
+    let connection = create_grpc_client_connection(format!("http://{scheduler_id}"))
+        .await
+        .map_err(|e| {
+            BallistaError::GrpcConnectionError(format!(
+                "Error connecting to scheduler {scheduler_id}: {e:?}"
+            ))
+        })?;
+    Ok(SchedulerGrpcClient::new(connection))
+}
+
+#[cfg(test)]
+mod test {
+    use ballista_core::serde::protobuf::TaskStatus;
+
+    use super::JobHandoffs;
+
+    fn task_status(job_id: &str, task_id: u32) -> TaskStatus {
+        TaskStatus {
+            task_id,
+            job_id: job_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_intercept_task_status() {
+        let handoffs = JobHandoffs::default();
+        handoffs.start("pending");
+        handoffs.start("completed");
+        handoffs.complete("completed", "localhost:50051");
+
+        let (local, forwarded) = handoffs.intercept(
+            "executor",
+            vec![
+                task_status("local", 0),
+                task_status("pending", 1),
+                task_status("completed", 2),
+            ],
+        );
+        assert_eq!(local, vec![task_status("local", 0)]);
+        assert_eq!(forwarded.len(), 1);
+        assert_eq!(
+            forwarded.get("localhost:50051"),
+            Some(&vec![task_status("completed", 2)])
+        );
+
+        let held = handoffs.cancel("pending");
+        assert_eq!(
+            held,
+            vec![("executor".to_owned(), vec![task_status("pending", 1)])]
+        );
+        let (local, forwarded) =
+            handoffs.intercept("executor", vec![task_status("pending", 3)]);
+        assert_eq!(local, vec![task_status("pending", 3)]);
+        assert!(forwarded.is_empty());
+    }
+}
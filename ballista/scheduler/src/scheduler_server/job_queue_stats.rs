@@ -0,0 +1,176 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::VecDeque;
+
+use parking_lot::Mutex;
+
+/// Maximum number of recent job starts from which the throughput is computed
+const MAX_RECENT_STARTS: usize = 100;
+
+/// Maximum age of the job starts from which the throughput is computed
+const RECENT_STARTS_WINDOW_MS: u64 = 10 * 60 * 1000;
+
+#[derive(Default)]
+struct JobQueueStatsInner {
+    /// Jobs waiting to start planning, in queueing order
+    queued_jobs: VecDeque<String>,
+    /// Times at which the recent jobs started planning, oldest first
+    recent_starts: VecDeque<u64>,
+}
+
+/// Depth of the queue of jobs waiting for the planning pool and recent rate at which jobs
+/// leave it, from which the delay before a queued job starts is estimated
+#[derive(Default)]
+pub(crate) struct JobQueueStats {
+    inner: Mutex<JobQueueStatsInner>,
+}
+
+impl JobQueueStats {
+    pub(crate) fn job_queued(&self, job_id: &str) {
+        self.inner.lock().queued_jobs.push_back(job_id.to_owned());
+    }
+
+    /// Record that a queued job started planning at `now`
+    pub(crate) fn job_started(&self, job_id: &str, now: u64) {
+        let mut inner = self.inner.lock();
+        inner.queued_jobs.retain(|queued| queued != job_id);
+        inner.recent_starts.push_back(now);
+        if inner.recent_starts.len() > MAX_RECENT_STARTS {
+            inner.recent_starts.pop_front();
+        }
+    }
+
+    /// Remove a job which leaves the queue without starting, e.g. cancelled
+    pub(crate) fn job_removed(&self, job_id: &str) {
+        self.inner
+            .lock()
+            .queued_jobs
+            .retain(|queued| queued != job_id);
+    }
+
+    /// Number of jobs waiting to start planning
+    pub(crate) fn queue_depth(&self) -> usize {
+        self.inner.lock().queued_jobs.len()
+    }
+
+    /// Estimate the delay in milliseconds before a job starts planning, given the number
+    /// of jobs queued before it and the number of free planning slots. `None` if the job
+    /// waits for a slot and fewer than two jobs started recently.
+    pub(crate) fn estimated_start_delay(
+        &self,
+        jobs_ahead: usize,
+        free_slots: usize,
+        now: u64,
+    ) -> Option<u64> {
+        if jobs_ahead < free_slots {
+            return Some(0);
+        }
+        let mut inner = self.inner.lock();
+        while matches!(
+            inner.recent_starts.front(),
+            Some(start) if now.saturating_sub(*start) > RECENT_STARTS_WINDOW_MS
+        ) {
+            inner.recent_starts.pop_front();
+        }
+        if inner.recent_starts.len() < 2 {
+            return None;
+        }
+        let intervals = inner.recent_starts.len() as u64 - 1;
+        let oldest_start = *inner.recent_starts.front()?;
+        let latest_start = *inner.recent_starts.back()?;
+        let mean_interval = latest_start.saturating_sub(oldest_start) / intervals;
+        // the first jobs ahead take the free slots, the others and this job wait for a
+        // start each
+        let starts_ahead = (jobs_ahead - free_slots + 1) as u64;
+        Some(starts_ahead * mean_interval)
+    }
+
+    /// Estimate the delay in milliseconds before a queued job starts planning, given the
+    /// number of free planning slots. `None` if the job is not queued or the rate of the
+    /// starts is unknown.
+    pub(crate) fn estimated_job_start_delay(
+        &self,
+        job_id: &str,
+        free_slots: usize,
+        now: u64,
+    ) -> Option<u64> {
+        let jobs_ahead = self
+            .inner
+            .lock()
+            .queued_jobs
+            .iter()
+            .position(|queued| queued == job_id)?;
+        self.estimated_start_delay(jobs_ahead, free_slots, now)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_estimated_start_delay() {
+        let stats = JobQueueStats::default();
+        stats.job_queued("job1");
+        stats.job_queued("job2");
+        stats.job_queued("job3");
+        assert_eq!(3, stats.queue_depth());
+        assert_eq!(None, stats.estimated_start_delay(0, 0, 1_000));
+
+        stats.job_started("job1", 1_000);
+        assert_eq!(None, stats.estimated_start_delay(0, 0, 1_000));
+        stats.job_started("job2", 3_000);
+        assert_eq!(1, stats.queue_depth());
+        // the jobs start every 2 seconds
+        assert_eq!(
+            Some(2_000),
+            stats.estimated_job_start_delay("job3", 0, 5_000)
+        );
+        assert_eq!(Some(4_000), stats.estimated_start_delay(1, 0, 5_000));
+        assert_eq!(Some(4_000), stats.estimated_start_delay(2, 1, 5_000));
+        assert_eq!(None, stats.estimated_job_start_delay("job1", 0, 5_000));
+
+        stats.job_removed("job3");
+        assert_eq!(0, stats.queue_depth());
+
+        // the starts older than the window are forgotten
+        assert_eq!(
+            None,
+            stats.estimated_start_delay(0, 0, 3_001 + RECENT_STARTS_WINDOW_MS)
+        );
+    }
+
+    #[test]
+    fn test_idle_start_delay() {
+        let stats = JobQueueStats::default();
+        stats.job_started("job1", 1_000);
+        stats.job_started("job2", 2_000);
+
+        // an idle scheduler starts the jobs right away, however long ago the last one
+        // started
+        assert_eq!(Some(0), stats.estimated_start_delay(0, 2, 60_000));
+        assert_eq!(Some(0), stats.estimated_start_delay(1, 2, 60_000));
+        assert_eq!(Some(1_000), stats.estimated_start_delay(2, 2, 60_000));
+
+        // even if no job started recently
+        let stats = JobQueueStats::default();
+        assert_eq!(Some(0), stats.estimated_start_delay(0, 1, 60_000));
+        stats.job_queued("job1");
+        assert_eq!(Some(0), stats.estimated_job_start_delay("job1", 1, 60_000));
+    }
+}
@@ -0,0 +1,149 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::{HashSet, VecDeque};
+use std::pin::Pin;
+use std::time::Duration;
+
+use ballista_core::serde::protobuf::{
+    job_status, watch_job_status_result, JobStatus, StageCompleted, WatchJobStatusResult,
+};
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+use futures::{stream, Stream, StreamExt};
+use tonic::Status;
+
+use crate::state::task_manager::TaskManager;
+
+/// Interval after which the progress of a watched job is read again without update
+/// notification, in case a notification was missed
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(crate) type JobWatchStream =
+    Pin<Box<dyn Stream<Item = Result<WatchJobStatusResult, Status>> + Send>>;
+
+/// Watch the progress of a job, given its current status and successful stages. The
+/// stream starts with the current status and ends after the job completes.
+pub(crate) fn watch_job<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
+    task_manager: TaskManager<T, U>,
+    job_updates: Pin<Box<dyn Stream<Item = String> + Send>>,
+    job_id: String,
+    status: JobStatus,
+    successful_stages: Vec<usize>,
+) -> JobWatchStream {
+    let mut watch = JobWatch {
+        task_manager,
+        job_updates: Some(job_updates),
+        job_id,
+        status: None,
+        successful_stages: HashSet::new(),
+        pending: VecDeque::new(),
+        done: false,
+    };
+    watch.update(status, successful_stages);
+
+    Box::pin(stream::unfold(watch, |mut watch| async move {
+        loop {
+            if let Some(result) = watch.pending.pop_front() {
+                return Some((Ok(result), watch));
+            }
+            if watch.done {
+                return None;
+            }
+
+            watch.wait_for_update().await;
+            match watch.task_manager.get_job_progress(&watch.job_id).await {
+                Ok(Some((status, successful_stages))) => {
+                    watch.update(status, successful_stages)
+                }
+                Ok(None) => {
+                    watch.done = true;
+                    let msg = format!("Job {} not found", watch.job_id);
+                    return Some((Err(Status::not_found(msg)), watch));
+                }
+                Err(e) => {
+                    watch.done = true;
+                    let msg =
+                        format!("Error getting status for job {}: {e:?}", watch.job_id);
+                    return Some((Err(Status::internal(msg)), watch));
+                }
+            }
+        }
+    }))
+}
+
+struct JobWatch<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> {
+    task_manager: TaskManager<T, U>,
+    /// `None` once the stream of job updates ended
+    job_updates: Option<Pin<Box<dyn Stream<Item = String> + Send>>>,
+    job_id: String,
+    /// Last status sent
+    status: Option<JobStatus>,
+    /// Stages whose completion was sent
+    successful_stages: HashSet<usize>,
+    /// Results to send
+    pending: VecDeque<WatchJobStatusResult>,
+    done: bool,
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> JobWatch<T, U> {
+    /// Wait until the job is updated or the refresh interval elapsed
+    async fn wait_for_update(&mut self) {
+        let Some(job_updates) = self.job_updates.as_mut() else {
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+            return;
+        };
+        let job_id = &self.job_id;
+        let update = tokio::time::timeout(REFRESH_INTERVAL, async {
+            while let Some(updated_job_id) = job_updates.next().await {
+                if &updated_job_id == job_id {
+                    return true;
+                }
+            }
+            false
+        });
+        if let Ok(false) = update.await {
+            self.job_updates = None;
+        }
+    }
+
+    /// Queue the stages completed since the last update, then the status if it changed
+    fn update(&mut self, status: JobStatus, successful_stages: Vec<usize>) {
+        for stage_id in successful_stages {
+            if self.successful_stages.insert(stage_id) {
+                self.pending.push_back(WatchJobStatusResult {
+                    event: Some(watch_job_status_result::Event::StageCompleted(
+                        StageCompleted {
+                            stage_id: stage_id as u32,
+                        },
+                    )),
+                });
+            }
+        }
+
+        if self.status.as_ref() != Some(&status) {
+            self.done = matches!(
+                status.status,
+                Some(job_status::Status::Successful(_) | job_status::Status::Failed(_))
+            );
+            self.status = Some(status.clone());
+            self.pending.push_back(WatchJobStatusResult {
+                event: Some(watch_job_status_result::Event::Status(status)),
+            });
+        }
+    }
+}
@@ -0,0 +1,324 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Export of the lineage of jobs as [OpenLineage](https://openlineage.io) run events, so
+//! that the jobs show up in data lineage catalogs such as Marquez. A START event is posted
+//! when a job is submitted, and a COMPLETE, FAIL or ABORT event when it ends, with the
+//! datasets read and written by the job, derived from its logical plan.
+//!
+//! The files read or written are named after their location, e.g. namespace `s3://bucket`
+//! and name `path/to/table`, following the naming conventions of OpenLineage. The other
+//! tables are named after their table reference, in the namespace of the jobs.
+
+use std::collections::BTreeSet;
+
+use dashmap::DashMap;
+use datafusion::arrow::temporal_conversions::timestamp_ms_to_datetime;
+use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
+use datafusion::datasource::listing::{ListingTable, ListingTableUrl};
+use datafusion::datasource::source_as_provider;
+use datafusion::logical_expr::LogicalPlan;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Request};
+use log::{debug, warn};
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+const PRODUCER: &str = "https://github.com/apache/arrow-ballista";
+const SCHEMA_URL: &str =
+    "https://openlineage.io/spec/2-0-2/OpenLineage.json#/definitions/RunEvent";
+
+/// A dataset read or written by a job
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub(crate) struct Dataset {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl Dataset {
+    fn from_location(url: &ListingTableUrl) -> Self {
+        if url.scheme() == "file" {
+            Self {
+                namespace: "file".to_owned(),
+                name: format!("/{}", url.prefix()),
+            }
+        } else {
+            Self {
+                namespace: url.object_store().as_str().trim_end_matches('/').to_owned(),
+                name: url.prefix().to_string(),
+            }
+        }
+    }
+
+    fn from_table(namespace: &str, table: impl ToString) -> Self {
+        Self {
+            namespace: namespace.to_owned(),
+            name: table.to_string(),
+        }
+    }
+}
+
+/// The datasets read and written by a logical plan, the tables which are not files being
+/// named in `namespace`
+pub(crate) fn plan_datasets(
+    plan: &LogicalPlan,
+    namespace: &str,
+) -> (Vec<Dataset>, Vec<Dataset>) {
+    let mut inputs = BTreeSet::new();
+    let mut outputs = BTreeSet::new();
+    let _ = plan.apply(&mut |plan| {
+        match plan {
+            LogicalPlan::TableScan(scan) => {
+                let provider = source_as_provider(&scan.source).ok();
+                let table = provider.as_ref().and_then(|provider| {
+                    provider.as_any().downcast_ref::<ListingTable>()
+                });
+                match table {
+                    Some(table) => inputs
+                        .extend(table.table_paths().iter().map(Dataset::from_location)),
+                    None => {
+                        inputs.insert(Dataset::from_table(namespace, &scan.table_name));
+                    }
+                }
+            }
+            LogicalPlan::Dml(dml) => {
+                outputs.insert(Dataset::from_table(namespace, &dml.table_name));
+            }
+            LogicalPlan::Copy(copy) => {
+                let dataset = match ListingTableUrl::parse(&copy.output_url) {
+                    Ok(url) => Dataset::from_location(&url),
+                    Err(_) => Dataset::from_table("file", &copy.output_url),
+                };
+                outputs.insert(dataset);
+            }
+            _ => {}
+        }
+        Ok(TreeNodeRecursion::Continue)
+    });
+    (inputs.into_iter().collect(), outputs.into_iter().collect())
+}
+
+/// The state of a run ending a job
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RunEnd {
+    Complete,
+    Fail,
+    Abort,
+}
+
+impl RunEnd {
+    fn event_type(&self) -> &'static str {
+        match self {
+            RunEnd::Complete => "COMPLETE",
+            RunEnd::Fail => "FAIL",
+            RunEnd::Abort => "ABORT",
+        }
+    }
+}
+
+/// The lineage of a queued or running job
+struct JobRun {
+    run_id: Uuid,
+    job_name: String,
+    inputs: Vec<Dataset>,
+    outputs: Vec<Dataset>,
+}
+
+/// Posts the OpenLineage run events of the jobs to an HTTP endpoint, e.g. the
+/// `/api/v1/lineage` endpoint of Marquez. The events are posted in order by a background
+/// task, the failures to post them being logged.
+pub(crate) struct LineageEmitter {
+    url: String,
+    namespace: String,
+    runs: DashMap<String, JobRun>,
+    sender: mpsc::UnboundedSender<Value>,
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<Value>>>,
+}
+
+impl LineageEmitter {
+    pub(crate) fn new(url: String, namespace: String) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            url,
+            namespace,
+            runs: DashMap::new(),
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+        }
+    }
+
+    /// Start posting the events, must be called within a tokio runtime
+    pub(crate) fn start(&self) {
+        let Some(mut receiver) = self.receiver.lock().take() else {
+            return;
+        };
+        let url = self.url.clone();
+        tokio::spawn(async move {
+            let client = Client::new();
+            while let Some(event) = receiver.recv().await {
+                post_event(&client, &url, event).await;
+            }
+        });
+    }
+
+    /// Record the datasets of a queued job, posted with its events
+    pub(crate) fn job_queued(&self, job_id: &str, job_name: &str, plan: &LogicalPlan) {
+        let (inputs, outputs) = plan_datasets(plan, &self.namespace);
+        let job_name = if job_name.is_empty() {
+            job_id
+        } else {
+            job_name
+        };
+        self.runs.insert(
+            job_id.to_owned(),
+            JobRun {
+                run_id: Uuid::new_v4(),
+                job_name: job_name.to_owned(),
+                inputs,
+                outputs,
+            },
+        );
+    }
+
+    /// Post the START event of a submitted job
+    pub(crate) fn job_started(&self, job_id: &str, started_at: u64) {
+        if let Some(run) = self.runs.get(job_id) {
+            self.send(self.run_event("START", &run, started_at));
+        }
+    }
+
+    /// Post the event ending the run of a job, and forget its lineage
+    pub(crate) fn job_ended(&self, job_id: &str, end: RunEnd, ended_at: u64) {
+        if let Some((_, run)) = self.runs.remove(job_id) {
+            self.send(self.run_event(end.event_type(), &run, ended_at));
+        }
+    }
+
+    fn run_event(&self, event_type: &str, run: &JobRun, at: u64) -> Value {
+        let event_time = timestamp_ms_to_datetime(at as i64)
+            .map(|time| time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+            .unwrap_or_default();
+        json!({
+            "eventType": event_type,
+            "eventTime": event_time,
+            "run": { "runId": run.run_id.to_string() },
+            "job": { "namespace": self.namespace, "name": run.job_name },
+            "inputs": run.inputs,
+            "outputs": run.outputs,
+            "producer": PRODUCER,
+            "schemaURL": SCHEMA_URL,
+        })
+    }
+
+    fn send(&self, event: Value) {
+        if self.sender.send(event).is_err() {
+            warn!("Failed to queue lineage event, the lineage emitter stopped");
+        }
+    }
+}
+
+async fn post_event(client: &Client<HttpConnector>, url: &str, event: Value) {
+    debug!("Posting lineage event {event} to {url}");
+    let request = Request::post(url)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(event.to_string()));
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("Invalid lineage endpoint {url}: {e}");
+            return;
+        }
+    };
+    match client.request(request).await {
+        Ok(response) if !response.status().is_success() => {
+            warn!(
+                "Lineage endpoint {url} rejected event with status {}",
+                response.status()
+            );
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Failed to post lineage event to {url}: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::datasource::file_format::csv::CsvFormat;
+    use datafusion::datasource::listing::{
+        ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl,
+    };
+    use datafusion::datasource::{provider_as_source, MemTable};
+    use datafusion::error::Result;
+    use datafusion::logical_expr::LogicalPlanBuilder;
+
+    use super::{plan_datasets, Dataset, LineageEmitter, RunEnd};
+
+    fn dataset(namespace: &str, name: &str) -> Dataset {
+        Dataset {
+            namespace: namespace.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_plan_datasets() -> Result<()> {
+        let schema =
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let config =
+            ListingTableConfig::new(ListingTableUrl::parse("s3://bucket/data/orders/")?)
+                .with_listing_options(ListingOptions::new(Arc::new(CsvFormat::default())))
+                .with_schema(schema.clone());
+        let orders = provider_as_source(Arc::new(ListingTable::try_new(config)?));
+        let customers = provider_as_source(Arc::new(MemTable::try_new(
+            schema.clone(),
+            vec![vec![]],
+        )?));
+
+        let plan = LogicalPlanBuilder::scan("orders", orders, None)?
+            .union(LogicalPlanBuilder::scan("customers", customers, None)?.build()?)?
+            .build()?;
+        let plan =
+            LogicalPlanBuilder::insert_into(plan, "ids", &schema, false)?.build()?;
+
+        let (inputs, outputs) = plan_datasets(&plan, "ballista");
+        assert_eq!(
+            inputs,
+            vec![
+                dataset("ballista", "customers"),
+                dataset("s3://bucket", "data/orders"),
+            ]
+        );
+        assert_eq!(outputs, vec![dataset("ballista", "ids")]);
+
+        let emitter =
+            LineageEmitter::new("http://localhost".to_owned(), "ballista".to_owned());
+        emitter.job_queued("job", "", &plan);
+        let event = emitter.run_event("START", &emitter.runs.get("job").unwrap(), 0);
+        assert_eq!(event["eventTime"], "1970-01-01T00:00:00.000Z");
+        assert_eq!(event["job"]["name"], "job");
+        assert_eq!(event["inputs"][1]["namespace"], "s3://bucket");
+        emitter.job_ended("job", RunEnd::Complete, 0);
+        assert!(emitter.runs.is_empty());
+        Ok(())
+    }
+}
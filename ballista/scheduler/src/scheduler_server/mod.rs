@@ -15,10 +15,12 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use ballista_core::error::Result;
+use ballista_core::error::{BallistaError, Result};
 use ballista_core::event_loop::{EventLoop, EventSender};
 use ballista_core::serde::protobuf::TaskStatus;
 use ballista_core::serde::BallistaCodec;
@@ -29,14 +31,18 @@ use datafusion::prelude::{SessionConfig, SessionContext};
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 
-use crate::cluster::BallistaCluster;
+use crate::cluster::{BallistaCluster, ExecutorExpirationStream};
 use crate::config::SchedulerConfig;
 use crate::metrics::SchedulerMetricsCollector;
 use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
-use log::{error, warn};
+use futures::StreamExt;
+use log::{error, info, warn};
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::job_handoff::JobHandoffs;
 use crate::scheduler_server::query_stage_scheduler::QueryStageScheduler;
+use crate::scheduler_server::readiness::{ReadinessStage, SchedulerReadiness};
+use crate::scheduler_server::standby::StandbyState;
 
 use crate::state::executor_manager::ExecutorManager;
 
@@ -49,10 +55,20 @@ pub mod externalscaler {
     include!(concat!(env!("OUT_DIR"), "/externalscaler.rs"));
 }
 
+pub(crate) mod admin_statement;
+mod deadlock;
 pub mod event;
 mod external_scaler;
 mod grpc;
+mod job_handoff;
+mod job_queue_stats;
+mod job_watch;
+mod lineage;
+mod planning_pool;
 pub(crate) mod query_stage_scheduler;
+pub mod readiness;
+mod standby;
+mod trace_watch;
 
 pub(crate) type SessionBuilder = fn(SessionConfig) -> SessionState;
 
@@ -64,6 +80,15 @@ pub struct SchedulerServer<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     pub(crate) query_stage_event_loop: EventLoop<QueryStageSchedulerEvent>,
     query_stage_scheduler: Arc<QueryStageScheduler<T, U>>,
     config: Arc<SchedulerConfig>,
+    /// Initialization stage of the scheduler, see [`SchedulerReadiness`]
+    pub readiness: Arc<SchedulerReadiness>,
+    /// Set once the scheduler started shutting down, new jobs are rejected from then on
+    shutting_down: Arc<AtomicBool>,
+    /// The jobs handed off to other schedulers, see [`SchedulerServer::hand_off_jobs`]
+    job_handoffs: Arc<JobHandoffs>,
+    /// The running jobs tailed while the scheduler is a standby, see
+    /// [`SchedulerServer::promote`]
+    standby: Arc<StandbyState>,
 }
 
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T, U> {
@@ -89,7 +114,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             "query_stage".to_owned(),
             config.event_loop_buffer_size as usize,
             query_stage_scheduler.clone(),
-        );
+        )
+        .with_concurrency(config.event_loop_concurrency as usize);
+        let standby = Arc::new(StandbyState::new(config.standby));
 
         Self {
             scheduler_name,
@@ -98,10 +125,13 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             query_stage_event_loop,
             query_stage_scheduler,
             config,
+            readiness: Arc::new(SchedulerReadiness::new()),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            job_handoffs: Arc::new(JobHandoffs::default()),
+            standby,
         }
     }
 
-    #[allow(dead_code)]
     pub fn new_with_task_launcher(
         scheduler_name: String,
         cluster: BallistaCluster,
@@ -126,7 +156,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             "query_stage".to_owned(),
             config.event_loop_buffer_size as usize,
             query_stage_scheduler.clone(),
-        );
+        )
+        .with_concurrency(config.event_loop_concurrency as usize);
+        let standby = Arc::new(StandbyState::new(config.standby));
 
         Self {
             scheduler_name,
@@ -135,17 +167,142 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             query_stage_event_loop,
             query_stage_scheduler,
             config,
+            readiness: Arc::new(SchedulerReadiness::new()),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            job_handoffs: Arc::new(JobHandoffs::default()),
+            standby,
         }
     }
 
     pub async fn init(&mut self) -> Result<()> {
         self.state.init().await?;
+        self.readiness
+            .advance(ReadinessStage::StateBackendConnected);
         self.query_stage_event_loop.start()?;
-        self.expire_dead_executors()?;
+        self.readiness.advance(ReadinessStage::EventLoopRunning);
+        if self.is_standby() {
+            self.start_standby().await?;
+        } else {
+            self.start_maintenance().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Start renewing the lease of the scheduler, expiring the dead executors, renewing
+    /// the slot reservations, resolving the deadlocks of the jobs waiting for task slots
+    /// and checking the duration of the running stages, which a standby scheduler leaves
+    /// to the active schedulers until it is promoted
+    async fn start_maintenance(&self) -> Result<()> {
+        if self.state.config.scheduler_lease_timeout_seconds > 0 {
+            // the lease is held before the scheduler owns any job
+            self.state.task_manager.renew_scheduler_lease().await?;
+            self.maintain_scheduler_lease();
+        }
+        match self.state.executor_manager.executor_expirations().await? {
+            Some(expirations) => self.remove_expired_executors(expirations)?,
+            None => self.expire_dead_executors()?,
+        }
+        if self.state.config.slot_reservation_timeout_seconds > 0 {
+            self.maintain_slot_reservations()?;
+        }
+        // the jobs with pipelined stages, which may deadlock, are only run with the
+        // push-based scheduling policy
+        if self.state.config.slot_deadlock_timeout_seconds > 0
+            && self.state.config.is_push_staged_scheduling()
+        {
+            self.resolve_slot_deadlocks()?;
+        }
+        if let Some(interval) = self.state.task_manager.stage_duration_check_interval() {
+            self.check_stage_durations(interval)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate the `stage_duration_ms` alert rules against the running stages every
+    /// `interval`, which exceed them even if no task status of the stages arrives
+    fn check_stage_durations(&self, interval: Duration) -> Result<()> {
+        let state = self.state.clone();
+        let event_sender = self.query_stage_event_loop.get_sender()?;
+        tokio::task::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                for alert in state.task_manager.evaluate_stage_durations().await {
+                    if let Err(e) = event_sender
+                        .post_event(QueryStageSchedulerEvent::StageAlert(alert))
+                        .await
+                    {
+                        error!("Fail to send stage alert event due to {e:?}");
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Shut the scheduler down gracefully: stop accepting new jobs and task status
+    /// updates, plan the queued jobs and process the pending events of the event loop for
+    /// up to `shutdown_timeout_seconds`, then persist the state held in memory and
+    /// release the ownership of the active jobs. The jobs still waiting to be planned
+    /// after the timeout are not persisted.
+    pub async fn shutdown(&self) -> Result<()> {
+        if self.shutting_down.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        info!("Scheduler {} is shutting down", self.scheduler_name);
+        self.readiness.advance(ReadinessStage::ShuttingDown);
+
+        let timeout = Duration::from_secs(self.config.shutdown_timeout_seconds);
+        if !self.flush_events(timeout).await {
+            warn!(
+                "Scheduler events still pending after {:?}, shutting down anyway",
+                timeout
+            );
+        }
+        self.query_stage_event_loop.stop();
 
+        let released = self.state.task_manager.release_active_jobs().await?;
+        self.state.executor_manager.shutdown().await?;
+        info!(
+            "Scheduler {} shut down, released {} active jobs",
+            self.scheduler_name, released
+        );
         Ok(())
     }
 
+    /// Wait until the jobs queued so far are planned and the events posted so far,
+    /// including the events of the planned jobs, are processed. Returns false if some
+    /// are still pending after `timeout`.
+    pub(crate) async fn flush_events(&self, timeout: Duration) -> bool {
+        let flushed = async {
+            loop {
+                let spawned = self.query_stage_scheduler.spawned_planning_jobs();
+                // the planned jobs post their events before they are counted as planned
+                self.query_stage_scheduler.wait_planning_idle().await;
+                if !self.query_stage_event_loop.flush(timeout).await {
+                    return false;
+                }
+                // the events processed meanwhile may have queued other jobs to plan
+                if self.query_stage_scheduler.spawned_planning_jobs() == spawned {
+                    return true;
+                }
+            }
+        };
+        tokio::time::timeout(timeout, flushed)
+            .await
+            .unwrap_or(false)
+    }
+
+    /// Whether the scheduler started shutting down
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+
+    /// Whether the scheduler is a standby which was not promoted yet
+    pub fn is_standby(&self) -> bool {
+        self.standby.is_standby()
+    }
+
     pub fn pending_job_number(&self) -> usize {
         self.state.task_manager.pending_job_number()
     }
@@ -165,6 +322,18 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
         ctx: Arc<SessionContext>,
         plan: &LogicalPlan,
     ) -> Result<()> {
+        if self.is_shutting_down() {
+            return Err(BallistaError::General(format!(
+                "Scheduler {} is shutting down, job {job_id} rejected",
+                self.scheduler_name
+            )));
+        }
+        if self.is_standby() {
+            return Err(BallistaError::General(format!(
+                "Scheduler {} is a standby, job {job_id} rejected",
+                self.scheduler_name
+            )));
+        }
         self.query_stage_event_loop
             .get_sender()?
             .post_event(QueryStageSchedulerEvent::JobQueued {
@@ -194,13 +363,26 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             warn!("{}", error_msg);
             return Ok(());
         }
-        self.query_stage_event_loop
-            .get_sender()?
-            .post_event(QueryStageSchedulerEvent::TaskUpdating(
-                executor_id.to_owned(),
-                tasks_status,
-            ))
-            .await
+        let tasks_status = self.intercept_task_status(executor_id, tasks_status).await;
+        // the status of the tasks of every job is updated by a separate event, so that the
+        // events of the jobs can be processed concurrently
+        let mut job_tasks_status: HashMap<String, Vec<TaskStatus>> = HashMap::new();
+        for task_status in tasks_status {
+            job_tasks_status
+                .entry(task_status.job_id.clone())
+                .or_default()
+                .push(task_status);
+        }
+        let event_sender = self.query_stage_event_loop.get_sender()?;
+        for tasks_status in job_tasks_status.into_values() {
+            event_sender
+                .post_event(QueryStageSchedulerEvent::TaskUpdating(
+                    executor_id.to_owned(),
+                    tasks_status,
+                ))
+                .await?;
+        }
+        Ok(())
     }
 
     pub(crate) async fn revive_offers(&self) -> Result<()> {
@@ -271,6 +453,97 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
         Ok(())
     }
 
+    /// Periodically renew the lease of this scheduler
+    fn maintain_scheduler_lease(&self) {
+        let state = self.state.clone();
+        // Renew well before the lease expires
+        let interval =
+            Duration::from_secs(state.config.scheduler_lease_timeout_seconds) / 3;
+        tokio::task::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                if let Err(e) = state.task_manager.renew_scheduler_lease().await {
+                    error!("Failed to renew scheduler lease: {e:?}");
+                }
+            }
+        });
+    }
+
+    /// Periodically renew the slots held by this scheduler and reclaim the slots held by
+    /// schedulers which stopped renewing theirs
+    fn maintain_slot_reservations(&self) -> Result<()> {
+        let state = self.state.clone();
+        let query_stage_scheduler = self.query_stage_scheduler.clone();
+        let event_sender = self.query_stage_event_loop.get_sender()?;
+        // Renew well before the reservation expires
+        let interval =
+            Duration::from_secs(state.config.slot_reservation_timeout_seconds) / 3;
+        tokio::task::spawn(async move {
+            loop {
+                if let Err(e) = state.executor_manager.renew_slot_reservation().await {
+                    error!("Failed to renew slot reservation: {e:?}");
+                }
+                match state
+                    .executor_manager
+                    .reclaim_expired_slot_reservations()
+                    .await
+                {
+                    Ok(reclaimed) if reclaimed > 0 => {
+                        query_stage_scheduler
+                            .metrics_collector()
+                            .record_reclaimed_slots(reclaimed as u64);
+                        if state.config.is_push_staged_scheduling() {
+                            if let Err(e) = event_sender
+                                .post_event(QueryStageSchedulerEvent::ReviveOffers)
+                                .await
+                            {
+                                error!("Fail to send revive offers event due to {e:?}");
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        error!("Failed to reclaim expired slot reservations: {e:?}");
+                    }
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+        Ok(())
+    }
+
+    /// Remove executors as soon as the cluster state reports that their liveness lease expired
+    fn remove_expired_executors(
+        &self,
+        mut expirations: ExecutorExpirationStream,
+    ) -> Result<()> {
+        let state = self.state.clone();
+        let event_sender = self.query_stage_event_loop.get_sender()?;
+        tokio::task::spawn(async move {
+            while let Some(executor_id) = expirations.next().await {
+                let stop_reason =
+                    format!("Executor {executor_id} liveness lease expired");
+
+                warn!("{stop_reason}");
+
+                Self::remove_executor(
+                    state.executor_manager.clone(),
+                    event_sender.clone(),
+                    &executor_id,
+                    Some(stop_reason.clone()),
+                    0,
+                );
+
+                // The executor may still be running if only its heartbeats were lost
+                state
+                    .executor_manager
+                    .stop_executor(&executor_id, stop_reason)
+                    .await;
+            }
+        });
+        Ok(())
+    }
+
     pub(crate) fn remove_executor(
         executor_manager: ExecutorManager,
         event_sender: EventSender<QueryStageSchedulerEvent>,
@@ -301,6 +574,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
     }
 
     async fn do_register_executor(&self, metadata: ExecutorMetadata) -> Result<()> {
+        // executors which cannot run the plans of this scheduler would fail their tasks
+        metadata.check_compatibility().map_err(|e| {
+            BallistaError::General(format!("Executor {} rejected: {e}", metadata.id))
+        })?;
+
         let executor_data = ExecutorData {
             executor_id: metadata.id.clone(),
             total_task_slots: metadata.specification.task_slots,
@@ -340,6 +618,7 @@ pub fn timestamp_millis() -> u64 {
 #[cfg(all(test, feature = "sled"))]
 mod test {
     use std::sync::Arc;
+    use std::time::Duration;
 
     use datafusion::arrow::datatypes::{DataType, Field, Schema};
     use datafusion::logical_expr::{col, sum, LogicalPlan};
@@ -347,18 +626,24 @@ mod test {
     use datafusion::test_util::scan_empty;
     use datafusion_proto::protobuf::LogicalPlanNode;
     use datafusion_proto::protobuf::PhysicalPlanNode;
+    use futures::StreamExt;
+    use tonic::{Code, Request};
 
     use ballista_core::config::{
         BallistaConfig, TaskSchedulingPolicy, BALLISTA_DEFAULT_SHUFFLE_PARTITIONS,
+        BALLISTA_STAGE_PIPELINED,
     };
     use ballista_core::error::Result;
 
-    use crate::config::SchedulerConfig;
+    use crate::config::{JobAdmissionPolicy, SchedulerConfig};
 
+    use ballista_core::protocol::PROTOCOL_VERSION;
+    use ballista_core::serde::protobuf::scheduler_grpc_server::SchedulerGrpc;
     use ballista_core::serde::protobuf::{
-        failed_task, job_status, task_status, ExecutionError, FailedTask, JobStatus,
-        MultiTaskDefinition, ShuffleWritePartition, SuccessfulJob, SuccessfulTask,
-        TaskId, TaskStatus,
+        failed_task, job_status, task_status, watch_job_status_result, ExecutionError,
+        FailedTask, JobStatus, MultiTaskDefinition, ShuffleWritePartition,
+        StageCompleted, SuccessfulJob, SuccessfulTask, TaskId, TaskStatus,
+        UpdateTaskStatusParams, WatchJobStatusParams,
     };
     use ballista_core::serde::scheduler::{
         ExecutorData, ExecutorMetadata, ExecutorSpecification,
@@ -434,6 +719,9 @@ mod test {
                         num_batches: 1,
                         num_rows: 1,
                         num_bytes: 1,
+                        null_counts: vec![],
+                        column_bounds: vec![],
+                        bloom_filters: vec![],
                     })
                 }
 
@@ -517,6 +805,101 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_push_scheduling_concurrent_jobs() -> Result<()> {
+        let plan = test_plan();
+
+        let metrics_collector = Arc::new(TestMetricsCollector::default());
+
+        // the requests to revive the offers which follow every status update run
+        // alongside the events of the jobs
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged)
+                .with_event_loop_concurrency(4),
+            metrics_collector.clone(),
+            4,
+            1,
+            None,
+        )
+        .await?;
+
+        test.submit("job1", "", &plan).await?;
+        let status = test.run("job2", "", &plan).await.expect("running plan");
+        assert!(
+            matches!(status.status, Some(job_status::Status::Successful(_))),
+            "Expected success status but found {status:?}"
+        );
+        let status = test.await_completion_timeout("job1", 10_000).await?;
+        assert!(
+            matches!(status.status, Some(job_status::Status::Successful(_))),
+            "Expected success status but found {status:?}"
+        );
+
+        for job_id in ["job1", "job2"] {
+            assert_submitted_event(job_id, &metrics_collector);
+            assert_completed_event(job_id, &metrics_collector);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch_job_status() -> Result<()> {
+        let plan = test_plan();
+
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged),
+            Arc::new(TestMetricsCollector::default()),
+            4,
+            1,
+            None,
+        )
+        .await?;
+
+        let status = test.run("job", "", &plan).await?;
+        assert!(matches!(
+            status.status,
+            Some(job_status::Status::Successful(_))
+        ));
+
+        // the stream of a completed job sends its completed stages then its final status
+        let events: Vec<_> = test
+            .scheduler()
+            .watch_job_status(Request::new(WatchJobStatusParams {
+                job_id: "job".to_owned(),
+            }))
+            .await
+            .expect("watching job")
+            .into_inner()
+            .map(|result| result.expect("job status stream error").event)
+            .collect()
+            .await;
+        assert_eq!(
+            events,
+            vec![
+                Some(watch_job_status_result::Event::StageCompleted(
+                    StageCompleted { stage_id: 1 }
+                )),
+                Some(watch_job_status_result::Event::StageCompleted(
+                    StageCompleted { stage_id: 2 }
+                )),
+                Some(watch_job_status_result::Event::Status(status)),
+            ]
+        );
+
+        let result = test
+            .scheduler()
+            .watch_job_status(Request::new(WatchJobStatusParams {
+                job_id: "unknown".to_owned(),
+            }))
+            .await;
+        assert_eq!(Code::NotFound, result.err().unwrap().code());
+
+        Ok(())
+    }
+
     // Simulate a task failure and ensure the job status is updated correctly
     #[tokio::test]
     async fn test_job_failure() -> Result<()> {
@@ -637,6 +1020,216 @@ mod test {
         Ok(())
     }
 
+    // A job submitted while the cluster has no task slots should be failed instead of
+    // staying queued when the admission policy rejects such jobs.
+    #[tokio::test]
+    async fn test_job_admission_rejected() -> Result<()> {
+        let plan = test_plan();
+        let metrics_collector = Arc::new(TestMetricsCollector::default());
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged)
+                .with_job_admission_policy(JobAdmissionPolicy::Reject),
+            metrics_collector.clone(),
+            0,
+            0,
+            None,
+        )
+        .await?;
+
+        let status = test.run("job", "", &plan).await?;
+
+        assert!(
+            matches!(
+                status,
+                JobStatus {
+                    status: Some(job_status::Status::Failed(_)),
+                    ..
+                }
+            ),
+            "{}",
+            "Expected job status to be failed but it was {status:?}"
+        );
+
+        assert_no_submitted_event("job", &metrics_collector);
+        assert_failed_event("job", &metrics_collector);
+
+        Ok(())
+    }
+
+    // A scheduler shutting down should save its active jobs and reject new jobs.
+    #[tokio::test]
+    async fn test_shutdown() -> Result<()> {
+        let plan = test_plan();
+        let task_slots = 4;
+
+        let scheduler = test_scheduler(TaskSchedulingPolicy::PullStaged).await?;
+        let config = test_session(task_slots);
+        let ctx = scheduler
+            .state
+            .session_manager
+            .create_session(&config)
+            .await?;
+
+        scheduler
+            .state
+            .task_manager
+            .queue_job("job", "", timestamp_millis())?;
+        scheduler
+            .state
+            .submit_job("job", "", ctx.clone(), &plan, 0)
+            .await?;
+
+        scheduler.shutdown().await?;
+        assert!(scheduler.is_shutting_down());
+        // shutting down again is a no-op
+        scheduler.shutdown().await?;
+
+        let status = scheduler.state.task_manager.get_job_status("job").await?;
+        assert!(
+            matches!(
+                status,
+                Some(JobStatus {
+                    status: Some(job_status::Status::Running(_)),
+                    ..
+                })
+            ),
+            "Expected job status to be running but it was {status:?}"
+        );
+
+        assert!(scheduler.submit_job("job2", "", ctx, &plan).await.is_err());
+        // the task status updates are rejected, as the job may be taken over
+        let request = Request::new(UpdateTaskStatusParams {
+            executor_id: "executor-1".to_owned(),
+            task_status: vec![],
+        });
+        let status = SchedulerGrpc::update_task_status(&scheduler, request)
+            .await
+            .unwrap_err();
+        assert_eq!(Code::Unavailable, status.code());
+
+        Ok(())
+    }
+
+    // Flushing the events should wait for the queued jobs to be planned and for the
+    // events of the planned jobs to be processed.
+    #[tokio::test]
+    async fn test_flush_events() -> Result<()> {
+        let plan = test_plan();
+        let metrics_collector = Arc::new(TestMetricsCollector::default());
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                test_cluster_context(),
+                BallistaCodec::default(),
+                Arc::new(
+                    SchedulerConfig::default()
+                        .with_scheduler_policy(TaskSchedulingPolicy::PullStaged)
+                        .with_job_planning_concurrency(1),
+                ),
+                metrics_collector.clone(),
+            );
+        scheduler.init().await?;
+        let ctx = scheduler
+            .state
+            .session_manager
+            .create_session(&test_session(4))
+            .await?;
+
+        // the second job waits for the first one to be planned
+        for job_id in ["job1", "job2"] {
+            scheduler.submit_job(job_id, "", ctx.clone(), &plan).await?;
+        }
+        assert!(scheduler.flush_events(Duration::from_secs(5)).await);
+
+        for job_id in ["job1", "job2"] {
+            let status = scheduler.state.task_manager.get_job_status(job_id).await?;
+            assert!(
+                matches!(
+                    status,
+                    Some(JobStatus {
+                        status: Some(job_status::Status::Running(_)),
+                        ..
+                    })
+                ),
+                "Expected job status to be running but it was {status:?}"
+            );
+            assert_submitted_event(job_id, &metrics_collector);
+        }
+
+        Ok(())
+    }
+
+    // Jobs with pipelined stages should be failed at submission with the pull-based
+    // scheduling policy, whose executors can not cancel the tasks of the stages rewound
+    // to resolve a deadlock.
+    #[tokio::test]
+    async fn test_pipelined_stages_rejected() -> Result<()> {
+        let plan = test_plan();
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_STAGE_PIPELINED, "true")
+            .build()?;
+        for (policy, rejected) in [
+            (TaskSchedulingPolicy::PullStaged, true),
+            (TaskSchedulingPolicy::PushStaged, false),
+        ] {
+            let scheduler = test_scheduler(policy).await?;
+            let ctx = scheduler
+                .state
+                .session_manager
+                .create_session(&config)
+                .await?;
+            scheduler
+                .state
+                .task_manager
+                .queue_job("job", "", timestamp_millis())?;
+            let result = scheduler.state.submit_job("job", "", ctx, &plan, 0).await;
+            assert_eq!(rejected, result.is_err(), "{policy:?}: {result:?}");
+        }
+
+        Ok(())
+    }
+
+    // Jobs larger than the limits of the scheduler should be failed at submission.
+    #[tokio::test]
+    async fn test_job_size_limits() -> Result<()> {
+        let plan = test_plan();
+        let configs = [
+            SchedulerConfig::default().with_max_job_plan_nodes(1),
+            SchedulerConfig::default().with_max_job_stages(1),
+            SchedulerConfig::default().with_max_job_tasks(1),
+        ];
+        for config in configs {
+            let metrics_collector = Arc::new(TestMetricsCollector::default());
+            let mut test = SchedulerTest::new(
+                config.with_scheduler_policy(TaskSchedulingPolicy::PushStaged),
+                metrics_collector.clone(),
+                4,
+                1,
+                None,
+            )
+            .await?;
+
+            let status = test.run("job", "", &plan).await?;
+
+            assert!(
+                matches!(
+                    status,
+                    JobStatus {
+                        status: Some(job_status::Status::Failed(_)),
+                        ..
+                    }
+                ),
+                "{}",
+                "Expected job status to be failed but it was {status:?}"
+            );
+            assert_no_submitted_event("job", &metrics_collector);
+            assert_failed_event("job", &metrics_collector);
+        }
+
+        Ok(())
+    }
+
     async fn test_scheduler(
         scheduling_policy: TaskSchedulingPolicy,
     ) -> Result<SchedulerServer<LogicalPlanNode, PhysicalPlanNode>> {
@@ -667,6 +1260,11 @@ mod test {
                     port: 8080,
                     grpc_port: 9090,
                     specification: ExecutorSpecification { task_slots },
+                    zone: String::new(),
+                    rack: String::new(),
+                    instance_type: String::new(),
+                    capabilities: Default::default(),
+                    protocol_version: PROTOCOL_VERSION,
                 },
                 ExecutorData {
                     executor_id: "executor-1".to_owned(),
@@ -683,6 +1281,11 @@ mod test {
                     specification: ExecutorSpecification {
                         task_slots: num_partitions as u32 - task_slots,
                     },
+                    zone: String::new(),
+                    rack: String::new(),
+                    instance_type: String::new(),
+                    capabilities: Default::default(),
+                    protocol_version: PROTOCOL_VERSION,
                 },
                 ExecutorData {
                     executor_id: "executor-2".to_owned(),
@@ -0,0 +1,248 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::future::Future;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use log::error;
+use tokio::runtime::Runtime;
+use tokio::sync::{Notify, Semaphore};
+
+/// Dedicated thread pool on which the execution graphs of submitted jobs are planned, so
+/// that planning big plans does not hold up the scheduler event loop or other jobs.
+///
+/// At most `concurrency` jobs are planned at once, the others wait for a permit.
+pub(crate) struct PlanningPool {
+    /// The runtime of the pool, or the error which prevented building it, which fails
+    /// the jobs submitted to the pool rather than the scheduler. Taken on drop.
+    runtime: Option<io::Result<Runtime>>,
+    permits: Arc<Semaphore>,
+    jobs: Arc<SpawnedJobs>,
+}
+
+/// The jobs spawned on a pool
+#[derive(Default)]
+struct SpawnedJobs {
+    /// The number of jobs spawned since the pool was created
+    total: AtomicUsize,
+    /// The number of jobs spawned which are not planned yet
+    unplanned: AtomicUsize,
+    /// Notified when the last unplanned job is planned
+    idle: Notify,
+}
+
+/// Counts a job spawned on the pool as unplanned until it is planned, or dropped with
+/// the pool
+struct UnplannedGuard(Arc<SpawnedJobs>);
+
+impl UnplannedGuard {
+    fn new(jobs: Arc<SpawnedJobs>) -> Self {
+        jobs.total.fetch_add(1, Ordering::SeqCst);
+        jobs.unplanned.fetch_add(1, Ordering::SeqCst);
+        Self(jobs)
+    }
+}
+
+impl Drop for UnplannedGuard {
+    fn drop(&mut self) {
+        if self.0.unplanned.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl PlanningPool {
+    pub(crate) fn new(concurrency: usize) -> Self {
+        let concurrency = concurrency.max(1);
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("job-planner")
+            .worker_threads(concurrency)
+            .build();
+        if let Err(e) = &runtime {
+            error!("Failed to start the job planning threads: {e}");
+        }
+
+        Self {
+            runtime: Some(runtime),
+            permits: Arc::new(Semaphore::new(concurrency)),
+            jobs: Arc::new(SpawnedJobs::default()),
+        }
+    }
+
+    /// Number of jobs which may start planning right away
+    pub(crate) fn available_slots(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    /// Number of jobs spawned since the pool was created
+    pub(crate) fn spawned_jobs(&self) -> usize {
+        self.jobs.total.load(Ordering::SeqCst)
+    }
+
+    /// Wait until no job is left to plan, whether planning or waiting for a permit
+    pub(crate) async fn wait_idle(&self) {
+        loop {
+            // created before checking the count so that the notification is not missed
+            let idle = self.jobs.idle.notified();
+            if self.jobs.unplanned.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    /// Run the future created by `plan` on the planning pool once a permit is available.
+    /// `on_start` is called when the permit is acquired, right before planning begins.
+    /// Returns an error if the threads of the pool could not be started.
+    pub(crate) fn spawn<S, F, Fut>(&self, on_start: S, plan: F) -> Result<()>
+    where
+        S: FnOnce() + Send + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        match &self.runtime {
+            Some(Ok(runtime)) => {
+                let permits = self.permits.clone();
+                let unplanned = UnplannedGuard::new(self.jobs.clone());
+                runtime.spawn(async move {
+                    let _unplanned = unplanned;
+                    // The semaphore is never closed
+                    let _permit = permits.acquire_owned().await;
+                    on_start();
+                    plan().await;
+                });
+                Ok(())
+            }
+            Some(Err(e)) => Err(BallistaError::General(format!(
+                "The job planning threads could not be started: {e}"
+            ))),
+            None => Err(BallistaError::General(
+                "The job planning pool is shut down".to_owned(),
+            )),
+        }
+    }
+}
+
+impl Drop for PlanningPool {
+    fn drop(&mut self) {
+        // The pool may be dropped from within an async context, where blocking on the
+        // shutdown of the runtime is not allowed
+        if let Some(Ok(runtime)) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PlanningPool;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::{mpsc, Semaphore};
+
+    #[tokio::test]
+    async fn test_planning_concurrency() {
+        let pool = PlanningPool::new(2);
+        // the jobs are planned until the test lets them finish
+        let finish = Arc::new(Semaphore::new(0));
+        let running = Arc::new(AtomicUsize::new(0));
+        let (started_tx, mut started_rx) = mpsc::unbounded_channel();
+        let (done_tx, mut done_rx) = mpsc::unbounded_channel();
+
+        for _ in 0..4 {
+            let finish = finish.clone();
+            let running = running.clone();
+            let started_tx = started_tx.clone();
+            let done_tx = done_tx.clone();
+            pool.spawn(
+                || {},
+                move || async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    started_tx.send(now).unwrap();
+                    finish.acquire().await.unwrap().forget();
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    done_tx.send(()).unwrap();
+                },
+            )
+            .unwrap();
+        }
+
+        // two jobs are planned at once, the others wait for a permit
+        for _ in 0..2 {
+            started_rx.recv().await.unwrap();
+        }
+        assert_eq!(running.load(Ordering::SeqCst), 2);
+        assert!(started_rx.try_recv().is_err());
+
+        // every job finishing lets a waiting job start
+        for _ in 0..2 {
+            finish.add_permits(1);
+            done_rx.recv().await.unwrap();
+            assert!(started_rx.recv().await.unwrap() <= 2);
+        }
+        assert!(started_rx.try_recv().is_err());
+
+        finish.add_permits(2);
+        for _ in 0..2 {
+            done_rx.recv().await.unwrap();
+        }
+        assert_eq!(running.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_wait_idle() {
+        let pool = PlanningPool::new(1);
+        pool.wait_idle().await;
+        assert_eq!(pool.spawned_jobs(), 0);
+
+        let finish = Arc::new(Semaphore::new(0));
+        let planned = Arc::new(AtomicUsize::new(0));
+        for _ in 0..2 {
+            let finish = finish.clone();
+            let planned = planned.clone();
+            pool.spawn(
+                || {},
+                move || async move {
+                    finish.acquire().await.unwrap().forget();
+                    planned.fetch_add(1, Ordering::SeqCst);
+                },
+            )
+            .unwrap();
+        }
+
+        // the job waiting for a permit is waited for too
+        let wait_idle = pool.wait_idle();
+        tokio::pin!(wait_idle);
+        finish.add_permits(1);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), &mut wait_idle)
+                .await
+                .is_err()
+        );
+        finish.add_permits(1);
+        tokio::time::timeout(Duration::from_secs(5), wait_idle)
+            .await
+            .unwrap();
+        assert_eq!(planned.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.spawned_jobs(), 2);
+    }
+}
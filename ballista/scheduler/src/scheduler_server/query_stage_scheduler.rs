@@ -33,9 +33,16 @@ use tokio::sync::mpsc;
 use tokio::time::Instant;
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::job_queue_stats::JobQueueStats;
+use crate::scheduler_server::lineage::{LineageEmitter, RunEnd};
+use crate::scheduler_server::planning_pool::PlanningPool;
 
 use crate::state::SchedulerState;
 
+/// The key of the requests to revive the offers, which are processed one at a time,
+/// concurrently with the events of the jobs
+const REVIVE_OFFERS_KEY: &str = "revive_offers";
+
 pub(crate) struct QueryStageScheduler<
     T: 'static + AsLogicalPlan,
     U: 'static + AsExecutionPlan,
@@ -43,6 +50,9 @@ pub(crate) struct QueryStageScheduler<
     state: Arc<SchedulerState<T, U>>,
     metrics_collector: Arc<dyn SchedulerMetricsCollector>,
     config: Arc<SchedulerConfig>,
+    planning_pool: PlanningPool,
+    job_queue_stats: Arc<JobQueueStats>,
+    lineage: Option<LineageEmitter>,
 }
 
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> QueryStageScheduler<T, U> {
@@ -51,16 +61,44 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> QueryStageSchedul
         metrics_collector: Arc<dyn SchedulerMetricsCollector>,
         config: Arc<SchedulerConfig>,
     ) -> Self {
+        let planning_pool = PlanningPool::new(config.job_planning_concurrency as usize);
+        let lineage = config
+            .openlineage_url
+            .clone()
+            .map(|url| LineageEmitter::new(url, config.openlineage_namespace.clone()));
+
         Self {
             state,
             metrics_collector,
             config,
+            planning_pool,
+            job_queue_stats: Arc::new(JobQueueStats::default()),
+            lineage,
         }
     }
 
     pub(crate) fn metrics_collector(&self) -> &dyn SchedulerMetricsCollector {
         self.metrics_collector.as_ref()
     }
+
+    pub(crate) fn job_queue_stats(&self) -> &JobQueueStats {
+        self.job_queue_stats.as_ref()
+    }
+
+    /// Number of jobs which may start planning right away
+    pub(crate) fn available_planning_slots(&self) -> usize {
+        self.planning_pool.available_slots()
+    }
+
+    /// Number of jobs handed to the planning pool since the scheduler started
+    pub(crate) fn spawned_planning_jobs(&self) -> usize {
+        self.planning_pool.spawned_jobs()
+    }
+
+    /// Wait until no job is left to plan
+    pub(crate) async fn wait_planning_idle(&self) {
+        self.planning_pool.wait_idle().await
+    }
 }
 
 #[async_trait]
@@ -69,6 +107,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
 {
     fn on_start(&self) {
         info!("Starting QueryStageScheduler");
+        if let Some(lineage) = &self.lineage {
+            lineage.start();
+        }
     }
 
     fn on_stop(&self) {
@@ -79,7 +120,6 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         &self,
         event: QueryStageSchedulerEvent,
         tx_event: &mpsc::Sender<QueryStageSchedulerEvent>,
-        _rx_event: &mpsc::Receiver<QueryStageSchedulerEvent>,
     ) -> Result<()> {
         let mut time_recorder = None;
         if self.config.scheduler_event_expected_processing_duration > 0 {
@@ -104,9 +144,32 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     error!("Fail to queue job {} due to {:?}", job_id, e);
                     return Ok(());
                 }
+                self.metrics_collector.record_queued(&job_id, &job_name);
+                if let Some(lineage) = &self.lineage {
+                    lineage.job_queued(&job_id, &job_name, &plan);
+                }
+                self.job_queue_stats.job_queued(&job_id);
+
+                let task_manager = self.state.task_manager.clone();
+                let job_queue_stats = self.job_queue_stats.clone();
+                let planning_job_id = job_id.clone();
+                let on_start = move || {
+                    let planning_started_at = timestamp_millis();
+                    job_queue_stats.job_started(&planning_job_id, planning_started_at);
+                    if let Err(e) =
+                        task_manager.start_planning(&planning_job_id, planning_started_at)
+                    {
+                        warn!(
+                            "Fail to mark job {} as planning due to {:?}",
+                            planning_job_id, e
+                        );
+                    }
+                };
 
                 let state = self.state.clone();
-                tokio::spawn(async move {
+                let planning_sender = event_sender.clone();
+                let planned_job_id = job_id.clone();
+                let planning = self.planning_pool.spawn(on_start, move || async move {
                     let event = if let Err(e) = state
                         .submit_job(&job_id, &job_name, session_ctx, &plan, queued_at)
                         .await
@@ -126,10 +189,23 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                             submitted_at: timestamp_millis(),
                         }
                     };
-                    if let Err(e) = event_sender.post_event(event).await {
+                    if let Err(e) = planning_sender.post_event(event).await {
                         error!("Fail to send event due to {}", e);
                     }
                 });
+                if let Err(e) = planning {
+                    let fail_message =
+                        format!("Error planning job {planned_job_id}: {e:?}");
+                    error!("{}", &fail_message);
+                    event_sender
+                        .post_event(QueryStageSchedulerEvent::JobPlanningFailed {
+                            job_id: planned_job_id,
+                            fail_message,
+                            queued_at,
+                            failed_at: timestamp_millis(),
+                        })
+                        .await?;
+                }
             }
             QueryStageSchedulerEvent::JobSubmitted {
                 job_id,
@@ -138,6 +214,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             } => {
                 self.metrics_collector
                     .record_submitted(&job_id, queued_at, submitted_at);
+                if let Some(lineage) = &self.lineage {
+                    lineage.job_started(&job_id, submitted_at);
+                }
 
                 info!("Job {} submitted", job_id);
 
@@ -155,6 +234,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             } => {
                 self.metrics_collector
                     .record_failed(&job_id, queued_at, failed_at);
+                if let Some(lineage) = &self.lineage {
+                    lineage.job_ended(&job_id, RunEnd::Fail, failed_at);
+                }
 
                 error!("Job {} failed: {}", job_id, fail_message);
                 if let Err(e) = self
@@ -176,6 +258,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             } => {
                 self.metrics_collector
                     .record_completed(&job_id, queued_at, completed_at);
+                if let Some(lineage) = &self.lineage {
+                    lineage.job_ended(&job_id, RunEnd::Complete, completed_at);
+                }
 
                 info!("Job {} success", job_id);
                 if let Err(e) = self.state.task_manager.succeed_job(&job_id).await {
@@ -189,17 +274,22 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             QueryStageSchedulerEvent::JobRunningFailed {
                 job_id,
                 fail_message,
+                failed_task,
+                error_chain,
                 queued_at,
                 failed_at,
             } => {
                 self.metrics_collector
                     .record_failed(&job_id, queued_at, failed_at);
+                if let Some(lineage) = &self.lineage {
+                    lineage.job_ended(&job_id, RunEnd::Fail, failed_at);
+                }
 
                 error!("Job {} running failed", job_id);
                 match self
                     .state
                     .task_manager
-                    .abort_job(&job_id, fail_message)
+                    .abort_job(&job_id, fail_message, failed_task, error_chain)
                     .await
                 {
                     Ok((running_tasks, _pending_tasks)) => {
@@ -231,6 +321,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             }
             QueryStageSchedulerEvent::JobCancel(job_id) => {
                 self.metrics_collector.record_cancelled(&job_id);
+                if let Some(lineage) = &self.lineage {
+                    lineage.job_ended(&job_id, RunEnd::Abort, timestamp_millis());
+                }
+                self.job_queue_stats.job_removed(&job_id);
 
                 info!("Job {} Cancelled", job_id);
                 match self.state.task_manager.cancel_job(&job_id).await {
@@ -257,12 +351,6 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 );
 
                 let num_status = tasks_status.len();
-                if self.state.config.is_push_staged_scheduling() {
-                    self.state
-                        .executor_manager
-                        .unbind_tasks(vec![(executor_id.clone(), num_status as u32)])
-                        .await?;
-                }
                 match self
                     .state
                     .update_task_statuses(&executor_id, tasks_status)
@@ -313,6 +401,35 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     }
                 }
             }
+            QueryStageSchedulerEvent::ShuffleOutputsLost(executor_id, missing) => {
+                match self
+                    .state
+                    .task_manager
+                    .reset_shuffle_outputs(&executor_id, &missing)
+                    .await
+                {
+                    Ok(tasks) => {
+                        if !tasks.is_empty() {
+                            if let Err(e) = self
+                                .state
+                                .executor_manager
+                                .cancel_running_tasks(tasks)
+                                .await
+                            {
+                                warn!("Fail to cancel running tasks due to {:?}", e);
+                            }
+                        }
+                        event_sender
+                            .post_event(QueryStageSchedulerEvent::ReviveOffers)
+                            .await?;
+                    }
+                    Err(e) => {
+                        error!(
+                            "TaskManager error to recompute shuffle outputs of Executor {executor_id}: {e}"
+                        );
+                    }
+                }
+            }
             QueryStageSchedulerEvent::CancelTasks(tasks) => {
                 if let Err(e) = self
                     .state
@@ -326,6 +443,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             QueryStageSchedulerEvent::JobDataClean(job_id) => {
                 self.state.executor_manager.clean_up_job_data(job_id);
             }
+            QueryStageSchedulerEvent::StageAlert(alert) => {
+                warn!("Stage alert: {alert}");
+                self.metrics_collector
+                    .record_stage_alert(alert.rule.metric.name());
+            }
         }
         if let Some((start, ec)) = time_recorder {
             let duration = start.elapsed();
@@ -345,6 +467,50 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     fn on_error(&self, error: BallistaError) {
         error!("Error received by QueryStageScheduler: {:?}", error);
     }
+
+    /// The events of a job are keyed by its ID, the events of the whole cluster share the
+    /// empty key, except the requests to revive the offers which have a key of their own,
+    /// so that the status updates of a job in push-based scheduling, each followed by
+    /// such a request, do not wait for the events of every other job
+    fn event_key(&self, event: &QueryStageSchedulerEvent) -> String {
+        match event {
+            QueryStageSchedulerEvent::JobQueued { job_id, .. }
+            | QueryStageSchedulerEvent::JobSubmitted { job_id, .. }
+            | QueryStageSchedulerEvent::JobPlanningFailed { job_id, .. }
+            | QueryStageSchedulerEvent::JobFinished { job_id, .. }
+            | QueryStageSchedulerEvent::JobRunningFailed { job_id, .. }
+            | QueryStageSchedulerEvent::JobUpdated(job_id)
+            | QueryStageSchedulerEvent::JobCancel(job_id)
+            | QueryStageSchedulerEvent::JobDataClean(job_id) => job_id.clone(),
+            QueryStageSchedulerEvent::StageAlert(alert) => alert.job_id.clone(),
+            // the status of the tasks of a single job, as split by the scheduler server
+            QueryStageSchedulerEvent::TaskUpdating(_, tasks_status) => {
+                match tasks_status.split_first() {
+                    Some((first, rest))
+                        if rest.iter().all(|status| status.job_id == first.job_id) =>
+                    {
+                        first.job_id.clone()
+                    }
+                    _ => String::new(),
+                }
+            }
+            QueryStageSchedulerEvent::ReviveOffers => REVIVE_OFFERS_KEY.to_owned(),
+            QueryStageSchedulerEvent::ExecutorLost(..)
+            | QueryStageSchedulerEvent::ShuffleOutputsLost(..)
+            | QueryStageSchedulerEvent::CancelTasks(_) => String::new(),
+        }
+    }
+
+    /// A request to revive the offers waiting to be processed binds the tasks of the
+    /// requests received meanwhile
+    fn is_coalesced(&self, event: &QueryStageSchedulerEvent) -> bool {
+        matches!(event, QueryStageSchedulerEvent::ReviveOffers)
+    }
+
+    fn on_processed(&self, event_key: &str, latency: Duration) {
+        self.metrics_collector
+            .record_event_latency(event_key, latency);
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,99 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use log::info;
+use parking_lot::Mutex;
+
+/// Initialization stages of a scheduler, in the order they are reached, followed by its
+/// shutdown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+pub enum ReadinessStage {
+    Starting,
+    StateBackendConnected,
+    EventLoopRunning,
+    GrpcBound,
+    ShuttingDown,
+}
+
+/// Readiness of a scheduler to serve requests, which is reached once its state backend
+/// is connected, its event loop is running and its gRPC server is bound, and left once it
+/// starts shutting down
+#[derive(Debug)]
+pub struct SchedulerReadiness {
+    stage: Mutex<ReadinessStage>,
+}
+
+impl SchedulerReadiness {
+    pub fn new() -> Self {
+        Self {
+            stage: Mutex::new(ReadinessStage::Starting),
+        }
+    }
+
+    /// Current initialization stage
+    pub fn stage(&self) -> ReadinessStage {
+        *self.stage.lock()
+    }
+
+    /// Whether the scheduler is fully initialized and not shutting down
+    pub fn is_ready(&self) -> bool {
+        self.stage() == ReadinessStage::GrpcBound
+    }
+
+    /// Move to a later initialization stage, stages are never left for earlier ones
+    pub fn advance(&self, stage: ReadinessStage) {
+        let mut current = self.stage.lock();
+        if stage > *current {
+            info!(
+                "Scheduler readiness moved from {:?} to {:?}",
+                *current, stage
+            );
+            *current = stage;
+        }
+    }
+}
+
+impl Default for SchedulerReadiness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_readiness_stages() {
+        let readiness = SchedulerReadiness::new();
+        assert_eq!(ReadinessStage::Starting, readiness.stage());
+        assert!(!readiness.is_ready());
+
+        readiness.advance(ReadinessStage::EventLoopRunning);
+        readiness.advance(ReadinessStage::StateBackendConnected);
+        assert_eq!(ReadinessStage::EventLoopRunning, readiness.stage());
+        assert!(!readiness.is_ready());
+
+        readiness.advance(ReadinessStage::GrpcBound);
+        assert!(readiness.is_ready());
+
+        readiness.advance(ReadinessStage::ShuttingDown);
+        readiness.advance(ReadinessStage::GrpcBound);
+        assert_eq!(ReadinessStage::ShuttingDown, readiness.stage());
+        assert!(!readiness.is_ready());
+    }
+}
@@ -0,0 +1,367 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! A standby scheduler is a hot spare of the active schedulers: it rejects jobs and binds
+//! no tasks, but keeps the state of the cluster warm so that, once promoted, it takes
+//! over the jobs of a failed scheduler within seconds instead of scanning all the state.
+//!
+//! The executor heartbeats are tailed by the executor manager as on any scheduler, and
+//! the owners of the running jobs are tailed from the [JobStateEvent]s. The execution
+//! graphs of the running jobs are kept decoded in memory and decoded again in the
+//! background whenever they are saved, so the promotion only reads the task status
+//! updates saved since.
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::job_status::Status;
+use ballista_core::serde::protobuf::JobStatus;
+use dashmap::DashMap;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+use futures::StreamExt;
+use log::{error, info, warn};
+use parking_lot::Mutex;
+
+use crate::cluster::JobStateEvent;
+use crate::scheduler_server::SchedulerServer;
+
+/// Max number of jobs taken over at once when a standby scheduler is promoted
+const TAKE_OVER_CONCURRENCY: usize = 16;
+
+/// Interval at which the execution graphs saved since they were last decoded are decoded
+/// again
+const GRAPH_WARMING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Whether a scheduler is a standby, and the running jobs of the cluster it tails
+pub(crate) struct StandbyState {
+    /// Cleared once the scheduler is promoted
+    standby: AtomicBool,
+    /// The scheduler owning each running job, empty if the job was released
+    running_jobs: DashMap<String, String>,
+    /// The jobs whose execution graph was saved since it was last decoded
+    stale_graphs: Mutex<HashSet<String>>,
+}
+
+impl StandbyState {
+    pub(crate) fn new(standby: bool) -> Self {
+        Self {
+            standby: AtomicBool::new(standby),
+            running_jobs: DashMap::new(),
+            stale_graphs: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub(crate) fn is_standby(&self) -> bool {
+        self.standby.load(Ordering::SeqCst)
+    }
+
+    /// Leave the standby mode, returns whether the scheduler was a standby
+    fn promote(&self) -> bool {
+        self.standby.swap(false, Ordering::SeqCst)
+    }
+
+    /// Record the owner of a running job, and forget the jobs which are no longer
+    /// running. The status is saved along with the execution graph, which becomes stale.
+    fn track(&self, job_id: &str, status: &JobStatus) {
+        self.stale_graphs.lock().insert(job_id.to_owned());
+        match &status.status {
+            Some(Status::Running(running)) => {
+                self.running_jobs
+                    .insert(job_id.to_owned(), running.scheduler.clone());
+            }
+            _ => {
+                self.running_jobs.remove(job_id);
+            }
+        }
+    }
+
+    fn set_owner(&self, job_id: &str, owner: &str) {
+        if let Some(mut current) = self.running_jobs.get_mut(job_id) {
+            *current = owner.to_owned();
+        }
+    }
+
+    fn take_stale_graphs(&self) -> Vec<String> {
+        self.stale_graphs.lock().drain().collect()
+    }
+
+    /// The running jobs released by their scheduler or owned by one of the
+    /// `failed_schedulers`, with their owner
+    fn jobs_to_take_over(&self, failed_schedulers: &[String]) -> Vec<(String, String)> {
+        self.running_jobs
+            .iter()
+            .filter(|job| {
+                job.value().is_empty() || failed_schedulers.contains(job.value())
+            })
+            .map(|job| (job.key().clone(), job.value().clone()))
+            .collect()
+    }
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T, U> {
+    /// Load the owners of the running jobs, then keep them up to date from the job state
+    /// events until the scheduler is promoted
+    pub(crate) async fn start_standby(&self) -> Result<()> {
+        // subscribe before loading the jobs so that no update is missed in between
+        let mut events = self.state.task_manager.job_state_events().await?;
+        for job_id in self.state.task_manager.get_job_ids().await? {
+            if let Some(status) = self.state.task_manager.get_job_status(&job_id).await? {
+                self.standby.track(&job_id, &status);
+            }
+        }
+        info!(
+            "Scheduler {} is a standby, tailing {} running jobs",
+            self.scheduler_name,
+            self.standby.running_jobs.len()
+        );
+
+        let standby = self.standby.clone();
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            while standby.is_standby() {
+                for job_id in standby.take_stale_graphs() {
+                    if !standby.is_standby() {
+                        break;
+                    }
+                    if let Err(e) = state.task_manager.warm_execution_graph(&job_id).await
+                    {
+                        warn!(
+                            "Failed to decode the execution graph of job {job_id}: {e:?}"
+                        );
+                    }
+                }
+                tokio::time::sleep(GRAPH_WARMING_INTERVAL).await;
+            }
+        });
+
+        let standby = self.standby.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                if !standby.is_standby() {
+                    break;
+                }
+                match event {
+                    JobStateEvent::JobUpdated { job_id, status } => {
+                        standby.track(&job_id, &status)
+                    }
+                    JobStateEvent::JobAcquired { job_id, owner } => {
+                        standby.set_owner(&job_id, &owner)
+                    }
+                    JobStateEvent::JobReleased { job_id } => {
+                        standby.set_owner(&job_id, "")
+                    }
+                    _ => {}
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Turn this standby scheduler into an active one: take over the running jobs of the
+    /// `failed_schedulers` along with the jobs released by their scheduler, see
+    /// [crate::cluster::JobState::try_take_over_job], start expiring the dead executors and
+    /// schedule the tasks of the jobs taken over. Returns the jobs taken over.
+    pub(crate) async fn promote(
+        &self,
+        failed_schedulers: Vec<String>,
+    ) -> Result<Vec<String>> {
+        if !self.standby.promote() {
+            return Err(BallistaError::General(format!(
+                "Scheduler {} is not a standby",
+                self.scheduler_name
+            )));
+        }
+        info!(
+            "Promoting scheduler {}, taking over the jobs of schedulers {:?}",
+            self.scheduler_name, failed_schedulers
+        );
+        self.start_maintenance().await?;
+
+        let jobs = self.standby.jobs_to_take_over(&failed_schedulers);
+        self.standby.running_jobs.clear();
+        self.standby.take_stale_graphs();
+        let task_manager = &self.state.task_manager;
+        let taken_over: Vec<String> = futures::stream::iter(jobs)
+            .map(|(job_id, owner)| async move {
+                let result = if owner.is_empty() {
+                    task_manager.acquire_job(&job_id).await
+                } else {
+                    task_manager.take_over_job(&job_id, &owner).await
+                };
+                match result {
+                    Ok(true) => Some(job_id),
+                    Ok(false) => {
+                        warn!("Job {job_id} could not be taken over, it moved on");
+                        None
+                    }
+                    Err(e) => {
+                        error!("Failed to take over job {job_id}: {e:?}");
+                        None
+                    }
+                }
+            })
+            .buffer_unordered(TAKE_OVER_CONCURRENCY)
+            .filter_map(futures::future::ready)
+            .collect()
+            .await;
+        // the graphs of the jobs of the other schedulers are no longer kept up to date
+        task_manager.forget_warm_execution_graphs();
+        info!(
+            "Scheduler {} promoted, took over {} jobs",
+            self.scheduler_name,
+            taken_over.len()
+        );
+
+        if !taken_over.is_empty() {
+            self.revive_offers().await?;
+        }
+        Ok(taken_over)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cluster::kv::KeyValueState;
+    use crate::cluster::storage::sled::SledClient;
+    use crate::cluster::{BallistaCluster, JobState};
+    use crate::config::SchedulerConfig;
+    use crate::test_utils::{test_aggregation_plan_with_job_id, TestMetricsCollector};
+    use ballista_core::serde::protobuf::{RunningJob, SuccessfulJob};
+    use ballista_core::serde::BallistaCodec;
+    use ballista_core::utils::default_session_builder;
+    use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+    use std::sync::Arc;
+
+    fn running(scheduler: &str) -> JobStatus {
+        JobStatus {
+            status: Some(Status::Running(RunningJob {
+                scheduler: scheduler.to_owned(),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_jobs_to_take_over() {
+        let standby = StandbyState::new(true);
+        standby.track("job1", &running("scheduler1:50050"));
+        standby.track("job2", &running("scheduler2:50050"));
+        standby.track("job3", &running("scheduler1:50050"));
+        standby.track("job4", &running("scheduler1:50050"));
+        standby.set_owner("job4", "");
+        standby.track(
+            "job3",
+            &JobStatus {
+                status: Some(Status::Successful(SuccessfulJob::default())),
+                ..Default::default()
+            },
+        );
+
+        let mut jobs = standby.jobs_to_take_over(&["scheduler1:50050".to_owned()]);
+        jobs.sort();
+        assert_eq!(
+            jobs,
+            vec![
+                ("job1".to_owned(), "scheduler1:50050".to_owned()),
+                ("job4".to_owned(), String::new()),
+            ]
+        );
+
+        assert!(standby.promote());
+        assert!(!standby.is_standby());
+        assert!(!standby.promote());
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_promote_over_kv_state() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let kv_state = |name: &str| {
+            Arc::new(
+                KeyValueState::<_, LogicalPlanNode, PhysicalPlanNode>::new(
+                    name,
+                    store.clone(),
+                    BallistaCodec::default(),
+                    default_session_builder,
+                )
+                .with_scheduler_lease(Duration::from_secs(60)),
+            )
+        };
+
+        // a job of a scheduler which still renews its lease and a job of a failed one
+        let alive = kv_state("scheduler-1:50050");
+        alive.renew_scheduler_lease().await?;
+        let failed = kv_state("scheduler-2:50050");
+        let mut graphs = vec![];
+        for (state, scheduler, job_id) in [
+            (&alive, "scheduler-1:50050", "job-1"),
+            (&failed, "scheduler-2:50050", "job-2"),
+        ] {
+            let mut graph = test_aggregation_plan_with_job_id(4, job_id).await;
+            graph.set_scheduler(scheduler);
+            state.accept_job(job_id, "", 0)?;
+            state.submit_job(job_id.to_owned(), &graph).await?;
+            graphs.push(graph);
+        }
+
+        let standby_state = kv_state("scheduler-3:50050");
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "scheduler-3:50050".to_owned(),
+                BallistaCluster::new(standby_state.clone(), standby_state.clone()),
+                BallistaCodec::default(),
+                Arc::new(SchedulerConfig::default().with_standby(true)),
+                Arc::new(TestMetricsCollector::default()),
+            );
+        scheduler.init().await?;
+
+        // only the job of the scheduler whose lease expired is taken over
+        let taken_over = scheduler
+            .promote(vec![
+                "scheduler-1:50050".to_owned(),
+                "scheduler-2:50050".to_owned(),
+            ])
+            .await?;
+        assert_eq!(taken_over, vec!["job-2".to_owned()]);
+        assert!(scheduler
+            .state
+            .task_manager
+            .get_active_execution_graph("job-2")
+            .is_some());
+        let status = standby_state.get_job_status("job-2").await?;
+        let Some(JobStatus {
+            status: Some(Status::Running(running)),
+            ..
+        }) = status
+        else {
+            panic!("job-2 is not running: {status:?}");
+        };
+        assert_eq!(running.scheduler, "scheduler-3:50050");
+        assert_eq!(running.epoch, 2);
+
+        // the failed scheduler can no longer save the job taken over
+        assert!(failed.save_job("job-2", &graphs[1]).await.is_err());
+        assert!(alive.save_job("job-1", &graphs[0]).await.is_ok());
+
+        Ok(())
+    }
+}
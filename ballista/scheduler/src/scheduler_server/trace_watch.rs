@@ -0,0 +1,184 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::time::Duration;
+
+use ballista_core::serde::protobuf::{job_status, SchedulingTraceEvent};
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+use futures::{stream, Stream};
+use tonic::Status;
+
+use crate::state::task_manager::TaskManager;
+
+/// Interval at which the trace of a followed job is read again for new events
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+pub(crate) type SchedulingTraceStream =
+    Pin<Box<dyn Stream<Item = Result<SchedulingTraceEvent, Status>> + Send>>;
+
+/// Stream the scheduling trace of a job. Without `follow`, the stream ends after the events
+/// traced so far, otherwise it also streams the events traced until the job completes.
+pub(crate) fn watch_scheduling_trace<
+    T: 'static + AsLogicalPlan,
+    U: 'static + AsExecutionPlan,
+>(
+    task_manager: TaskManager<T, U>,
+    job_id: String,
+    follow: bool,
+) -> SchedulingTraceStream {
+    let watch = TraceWatch {
+        task_manager,
+        job_id,
+        follow,
+        next_seq: 0,
+        pending: VecDeque::new(),
+        done: false,
+    };
+
+    Box::pin(stream::unfold(watch, |mut watch| async move {
+        loop {
+            if let Some(event) = watch.pending.pop_front() {
+                return Some((Ok(event), watch));
+            }
+            if watch.done {
+                return None;
+            }
+
+            if watch.read_events() {
+                continue;
+            }
+            if !watch.follow {
+                watch.done = true;
+                continue;
+            }
+            match watch.task_manager.get_job_status(&watch.job_id).await {
+                Ok(Some(status))
+                    if !matches!(
+                        status.status,
+                        Some(
+                            job_status::Status::Successful(_)
+                                | job_status::Status::Failed(_)
+                        )
+                    ) =>
+                {
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                Ok(_) => {
+                    // the job completed or is gone, send the events traced in the meantime
+                    watch.done = true;
+                    watch.read_events();
+                }
+                Err(e) => {
+                    watch.done = true;
+                    let msg =
+                        format!("Error getting status for job {}: {e:?}", watch.job_id);
+                    return Some((Err(Status::internal(msg)), watch));
+                }
+            }
+        }
+    }))
+}
+
+struct TraceWatch<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> {
+    task_manager: TaskManager<T, U>,
+    job_id: String,
+    follow: bool,
+    /// Sequence number of the next event to send
+    next_seq: u64,
+    /// Events to send
+    pending: VecDeque<SchedulingTraceEvent>,
+    done: bool,
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TraceWatch<T, U> {
+    /// Queue the events traced since the last read, returns whether there were any
+    fn read_events(&mut self) -> bool {
+        let events = self
+            .task_manager
+            .scheduling_traces()
+            .events_from(&self.job_id, self.next_seq)
+            .unwrap_or_default();
+        let Some(last) = events.last() else {
+            return false;
+        };
+        self.next_seq = last.seq + 1;
+        self.pending.extend(events);
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use ballista_core::error::Result;
+    use ballista_core::serde::BallistaCodec;
+    use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+    use futures::{StreamExt, TryStreamExt};
+
+    use super::{watch_scheduling_trace, SchedulingTraceStream};
+    use crate::state::task_manager::TaskManager;
+    use crate::test_utils::{test_aggregation_plan_with_job_id, test_cluster_context};
+
+    async fn next_seq(events: &mut SchedulingTraceStream) -> Option<u64> {
+        tokio::time::timeout(Duration::from_secs(5), events.next())
+            .await
+            .expect("the stream follows the trace")
+            .map(|event| event.unwrap().seq)
+    }
+
+    #[tokio::test]
+    async fn test_watch_scheduling_trace() -> Result<()> {
+        let cluster = test_cluster_context();
+        let task_manager: TaskManager<LogicalPlanNode, PhysicalPlanNode> =
+            TaskManager::new(
+                cluster.job_state(),
+                BallistaCodec::default(),
+                "localhost:50050".to_owned(),
+            );
+        let mut graph = test_aggregation_plan_with_job_id(4, "job").await;
+        graph.revive();
+        cluster.job_state().save_job("job", &graph).await?;
+        let traces = task_manager.scheduling_traces().clone();
+        traces.record_launch_failure("job", "executor-1", vec![0], "unreachable");
+
+        // without follow, the stream ends after the events traced so far
+        let events: Vec<_> =
+            watch_scheduling_trace(task_manager.clone(), "job".to_owned(), false)
+                .try_collect()
+                .await
+                .unwrap();
+        assert_eq!(events.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![0]);
+
+        let mut events = watch_scheduling_trace(task_manager, "job".to_owned(), true);
+        assert_eq!(Some(0), next_seq(&mut events).await);
+        traces.record_launch_failure("job", "executor-2", vec![1], "unreachable");
+        assert_eq!(Some(1), next_seq(&mut events).await);
+
+        // the events traced until the job completes are sent before the stream ends
+        traces.record_launch_failure("job", "executor-2", vec![2], "unreachable");
+        graph.fail_job("failed".to_owned(), None, vec![]);
+        cluster.job_state().save_job("job", &graph).await?;
+        assert_eq!(Some(2), next_seq(&mut events).await);
+        assert_eq!(None, next_seq(&mut events).await);
+
+        Ok(())
+    }
+}
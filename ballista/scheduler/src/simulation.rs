@@ -0,0 +1,777 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Replay of a recorded workload against a scheduler with virtual executors, to compare
+//! scheduling policies and cluster sizes for capacity planning.
+//!
+//! The jobs of the workload are submitted at their recorded times and planned into their
+//! recorded stage DAGs, and the virtual executors complete every task after the recorded
+//! duration of its stage instead of running it. The workload is replayed `speedup` times
+//! faster than recorded and the measured times are scaled back, so the time spent in the
+//! scheduler itself is scaled up as well and the speedup should keep it small compared to
+//! the task durations.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use ballista_core::config::{BallistaConfig, TaskSchedulingPolicy};
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::protocol::PROTOCOL_VERSION;
+use ballista_core::serde::protobuf::{
+    task_status, MultiTaskDefinition, ShuffleWritePartition, SuccessfulTask, TaskId,
+    TaskStatus,
+};
+use ballista_core::serde::scheduler::{
+    ExecutorData, ExecutorMetadata, ExecutorSpecification,
+};
+use ballista_core::serde::BallistaCodec;
+use ballista_core::utils::default_session_builder;
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::common::{DFSchema, DFSchemaRef};
+use datafusion::error::DataFusionError;
+use datafusion::execution::context::{QueryPlanner, SessionConfig, SessionState};
+use datafusion::logical_expr::{
+    Expr, Extension, LogicalPlan, UserDefinedLogicalNodeCore,
+};
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_plan::empty::EmptyExec;
+use datafusion::physical_plan::repartition::RepartitionExec;
+use datafusion::physical_plan::union::UnionExec;
+use datafusion::physical_plan::{ExecutionPlan, Partitioning};
+use datafusion::physical_planner::{DefaultPhysicalPlanner, PhysicalPlanner};
+use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+use log::error;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Notify;
+
+use crate::cluster::BallistaCluster;
+use crate::config::SchedulerConfig;
+use crate::metrics::SchedulerMetricsCollector;
+use crate::scheduler_server::{timestamp_millis, SchedulerServer};
+use crate::state::executor_manager::ExecutorManager;
+use crate::state::task_manager::TaskLauncher;
+
+const SIMULATION_SCHEDULER_NAME: &str = "localhost:50050";
+
+/// A job of a recorded workload, replayed with the stage DAG it was recorded with
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RecordedJob {
+    pub name: String,
+    /// Submission time in milliseconds since the start of the workload
+    pub submitted_at: u64,
+    /// Stages of the job, each read by at most one other stage. The only stage read by no
+    /// other stage computes the output of the job.
+    pub stages: Vec<RecordedStage>,
+}
+
+/// A stage of a recorded job
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RecordedStage {
+    pub stage_id: usize,
+    /// Number of tasks of the stage, at least one per input stage
+    pub partitions: usize,
+    /// Stages whose outputs the stage reads, none for a stage scanning job inputs
+    #[serde(default)]
+    pub inputs: Vec<usize>,
+    /// Duration in milliseconds of every task of the stage
+    pub task_duration: u64,
+}
+
+impl RecordedJob {
+    /// The physical plan of the job, which the scheduler splits into the recorded stages,
+    /// and the task durations of the stages in the order the scheduler numbers them
+    fn plan(&self) -> datafusion::error::Result<(Arc<dyn ExecutionPlan>, Vec<u64>)> {
+        let invalid = |message: String| {
+            DataFusionError::Plan(format!(
+                "Invalid stages of job {}: {message}",
+                self.name
+            ))
+        };
+        let stages: HashMap<usize, &RecordedStage> = self
+            .stages
+            .iter()
+            .map(|stage| (stage.stage_id, stage))
+            .collect();
+        if stages.len() != self.stages.len() {
+            return Err(invalid("duplicate stage ids".to_owned()));
+        }
+        let mut read_stages = HashSet::new();
+        for stage in &self.stages {
+            if stage.partitions < stage.inputs.len().max(1) {
+                return Err(invalid(format!(
+                    "stage {} has fewer partitions than input stages",
+                    stage.stage_id
+                )));
+            }
+            for input in &stage.inputs {
+                if !stages.contains_key(input) || !read_stages.insert(*input) {
+                    return Err(invalid(format!(
+                        "stage {input} is missing or read by several stages"
+                    )));
+                }
+            }
+        }
+        let mut final_stages = self
+            .stages
+            .iter()
+            .filter(|stage| !read_stages.contains(&stage.stage_id));
+        let (Some(final_stage), None) = (final_stages.next(), final_stages.next()) else {
+            return Err(invalid("there is not a single final stage".to_owned()));
+        };
+
+        let mut task_durations = vec![];
+        let plan = plan_recorded_stage(final_stage, &stages, &mut task_durations)?;
+        task_durations.push(final_stage.task_duration);
+        // stages reading each other in a cycle are not reached from the final stage
+        if task_durations.len() != stages.len() {
+            return Err(invalid("some stages read each other in a cycle".to_owned()));
+        }
+        Ok((plan, task_durations))
+    }
+}
+
+fn recorded_job_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![Field::new("id", DataType::UInt64, false)]))
+}
+
+/// The plan of a recorded stage, whose input stages are hash partitioned into a share of
+/// its tasks each. The union of the inputs has no known partitioning, so that the stage
+/// reading it repartitions it in a stage of its own. The task durations of the input
+/// stages are pushed in the order the scheduler numbers them, i.e. after their inputs.
+fn plan_recorded_stage(
+    stage: &RecordedStage,
+    stages: &HashMap<usize, &RecordedStage>,
+    task_durations: &mut Vec<u64>,
+) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+    if stage.inputs.is_empty() {
+        return Ok(Arc::new(
+            EmptyExec::new(recorded_job_schema()).with_partitions(stage.partitions),
+        ));
+    }
+    let num_inputs = stage.inputs.len();
+    let mut inputs: Vec<Arc<dyn ExecutionPlan>> = vec![];
+    for (i, input_id) in stage.inputs.iter().enumerate() {
+        let input = stages[input_id];
+        let input_plan = plan_recorded_stage(input, stages, task_durations)?;
+        task_durations.push(input.task_duration);
+        let partitions = stage.partitions / num_inputs
+            + usize::from(i < stage.partitions % num_inputs);
+        inputs.push(Arc::new(RepartitionExec::try_new(
+            input_plan,
+            Partitioning::Hash(vec![Arc::new(Column::new("id", 0))], partitions),
+        )?));
+    }
+    Ok(Arc::new(UnionExec::new(inputs)))
+}
+
+/// Logical plan node of a recorded job, planned by the [SimulationQueryPlanner]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RecordedJobNode {
+    job: RecordedJob,
+    schema: DFSchemaRef,
+}
+
+impl RecordedJobNode {
+    fn try_new(job: RecordedJob) -> datafusion::error::Result<Self> {
+        let schema =
+            Arc::new(DFSchema::try_from(recorded_job_schema().as_ref().clone())?);
+        Ok(Self { job, schema })
+    }
+}
+
+impl UserDefinedLogicalNodeCore for RecordedJobNode {
+    fn name(&self) -> &str {
+        "RecordedJob"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RecordedJob: {}", self.job.name)
+    }
+
+    fn from_template(&self, _exprs: &[Expr], _inputs: &[LogicalPlan]) -> Self {
+        self.clone()
+    }
+}
+
+/// Query planner of the simulated sessions, planning the recorded jobs as is rather than
+/// optimizing them
+struct SimulationQueryPlanner;
+
+#[async_trait]
+impl QueryPlanner for SimulationQueryPlanner {
+    async fn create_physical_plan(
+        &self,
+        logical_plan: &LogicalPlan,
+        session_state: &SessionState,
+    ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+        if let LogicalPlan::Extension(Extension { node }) = logical_plan {
+            if let Some(node) = node.as_any().downcast_ref::<RecordedJobNode>() {
+                return node.job.plan().map(|(plan, _)| plan);
+            }
+        }
+        DefaultPhysicalPlanner::default()
+            .create_physical_plan(logical_plan, session_state)
+            .await
+    }
+}
+
+fn simulation_session_builder(config: SessionConfig) -> SessionState {
+    default_session_builder(config).with_query_planner(Arc::new(SimulationQueryPlanner))
+}
+
+/// Jobs recorded from a cluster, to be replayed by [simulate]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Workload {
+    pub jobs: Vec<RecordedJob>,
+}
+
+impl Workload {
+    /// Load a workload serialized as JSON
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| {
+            BallistaError::General(format!("Invalid simulation workload: {e}"))
+        })
+    }
+}
+
+/// Cluster and scheduling policies against which a workload is replayed
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    /// Number of virtual executors
+    pub num_executors: usize,
+    /// Number of task slots of each virtual executor
+    pub task_slots_per_executor: usize,
+    /// Configuration of the scheduler, whose tasks are always pushed to the executors
+    pub scheduler_config: SchedulerConfig,
+    /// How many times faster than recorded the workload is replayed
+    pub speedup: u32,
+    /// Max real time of the replay, after which the simulation fails with the jobs left
+    /// running, e.g. when a scheduling bug stalls them
+    pub timeout: Duration,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            num_executors: 4,
+            task_slots_per_executor: 8,
+            scheduler_config: SchedulerConfig::default(),
+            speedup: 100,
+            timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Measurements of a replayed workload, in recorded time
+#[derive(Debug, Clone)]
+pub struct SimulationResult {
+    /// Time from the start of the workload until its last job completed
+    pub makespan: Duration,
+    /// Time from the submission of every job until its completion, in submission order
+    pub job_latencies: Vec<(String, Duration)>,
+    /// Number of tasks run on the executors
+    pub num_tasks: usize,
+    /// Fraction of the time of the task slots of the cluster spent running tasks
+    pub slot_utilization: f64,
+}
+
+impl SimulationResult {
+    pub fn mean_job_latency(&self) -> Duration {
+        if self.job_latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        self.job_latencies
+            .iter()
+            .map(|(_, latency)| *latency)
+            .sum::<Duration>()
+            / self.job_latencies.len() as u32
+    }
+
+    pub fn max_job_latency(&self) -> Duration {
+        self.job_latencies
+            .iter()
+            .map(|(_, latency)| *latency)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for SimulationResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} jobs and {} tasks in {:?}: job latency mean {:?} max {:?}, slot utilization {:.1}%",
+            self.job_latencies.len(),
+            self.num_tasks,
+            self.makespan,
+            self.mean_job_latency(),
+            self.max_job_latency(),
+            self.slot_utilization * 100.0
+        )
+    }
+}
+
+/// Replay a workload against a scheduler with the configured virtual executors and policies
+pub async fn simulate(
+    workload: &Workload,
+    config: SimulationConfig,
+) -> Result<SimulationResult> {
+    let mut jobs = workload.jobs.clone();
+    jobs.sort_by_key(|job| job.submitted_at);
+    // the stages of all the jobs are checked before replaying any of them
+    let task_durations = jobs
+        .iter()
+        .map(|job| job.plan().map(|(_, task_durations)| task_durations))
+        .collect::<datafusion::error::Result<Vec<_>>>()?;
+    let num_jobs = jobs.len();
+
+    let speedup = config.speedup.max(1);
+    let scale = |recorded_ms: u64| Duration::from_millis(recorded_ms) / speedup;
+
+    let (status_sender, mut status_receiver) = unbounded_channel();
+    let launcher = Arc::new(SimulatedTaskLauncher {
+        sender: status_sender,
+        jobs: Mutex::new(HashMap::new()),
+        speedup,
+        launched_tasks: AtomicUsize::new(0),
+        busy_time: AtomicU64::new(0),
+    });
+    let metrics = Arc::new(SimulationMetricsCollector::default());
+
+    let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+        SchedulerServer::new_with_task_launcher(
+            SIMULATION_SCHEDULER_NAME.to_owned(),
+            BallistaCluster::new_memory(
+                SIMULATION_SCHEDULER_NAME,
+                simulation_session_builder,
+            ),
+            BallistaCodec::default(),
+            Arc::new(
+                config
+                    .scheduler_config
+                    .clone()
+                    .with_scheduler_policy(TaskSchedulingPolicy::PushStaged),
+            ),
+            metrics.clone(),
+            launcher.clone(),
+        );
+    scheduler.init().await?;
+
+    for i in 0..config.num_executors {
+        let executor_id = format!("virtual-executor-{i}");
+        let metadata = ExecutorMetadata {
+            id: executor_id.clone(),
+            host: String::default(),
+            port: 0,
+            grpc_port: 0,
+            specification: ExecutorSpecification {
+                task_slots: config.task_slots_per_executor as u32,
+            },
+            zone: String::new(),
+            rack: String::new(),
+            instance_type: String::new(),
+            capabilities: Default::default(),
+            protocol_version: PROTOCOL_VERSION,
+        };
+        let executor_data = ExecutorData {
+            executor_id,
+            total_task_slots: config.task_slots_per_executor as u32,
+            available_task_slots: config.task_slots_per_executor as u32,
+        };
+        scheduler
+            .state
+            .executor_manager
+            .register_executor(metadata, executor_data)
+            .await?;
+    }
+
+    let scheduler_clone = scheduler.clone();
+    let status_updates = tokio::spawn(async move {
+        while let Some((executor_id, status)) = status_receiver.recv().await {
+            if let Err(e) = scheduler_clone
+                .update_task_status(&executor_id, status)
+                .await
+            {
+                error!("Failed to update task status: {e}");
+            }
+        }
+    });
+
+    let start = Instant::now();
+    let replay = async {
+        let mut submissions = vec![];
+        for (job, task_durations) in jobs.into_iter().zip(task_durations) {
+            tokio::time::sleep_until((start + scale(job.submitted_at)).into()).await;
+
+            let session_ctx = scheduler
+                .state
+                .session_manager
+                .create_session(&BallistaConfig::new()?)
+                .await?;
+            let plan = LogicalPlan::Extension(Extension {
+                node: Arc::new(RecordedJobNode::try_new(job.clone())?),
+            });
+
+            let job_id = scheduler.state.task_manager.generate_job_id();
+            launcher.jobs.lock().insert(job_id.clone(), task_durations);
+            submissions.push((job_id.clone(), job.name.clone(), Instant::now()));
+            scheduler
+                .submit_job(&job_id, &job.name, session_ctx, &plan)
+                .await?;
+        }
+
+        loop {
+            // created before checking the count so that no notification is missed
+            let notified = metrics.notify.notified();
+            if metrics.completed.lock().len() >= submissions.len() {
+                break;
+            }
+            notified.await;
+        }
+        Ok::<_, BallistaError>(submissions)
+    };
+    let replayed = tokio::time::timeout_at((start + config.timeout).into(), replay).await;
+    status_updates.abort();
+    let submissions = replayed.map_err(|_| {
+        BallistaError::General(format!(
+            "The simulation did not complete within {:?}, {} of the {num_jobs} jobs \
+            completed",
+            config.timeout,
+            metrics.completed.lock().len()
+        ))
+    })??;
+
+    let failed = metrics.failed_jobs.load(Ordering::SeqCst);
+    if failed > 0 {
+        return Err(BallistaError::General(format!(
+            "{failed} of the simulated jobs failed"
+        )));
+    }
+
+    let completed = metrics.completed.lock();
+    let unscale = |real: Duration| real * speedup;
+    let job_latencies = submissions
+        .into_iter()
+        .map(|(job_id, name, submitted)| {
+            let latency = completed
+                .get(&job_id)
+                .map(|completed| completed.saturating_duration_since(submitted))
+                .unwrap_or_default();
+            (name, unscale(latency))
+        })
+        .collect();
+    let makespan = unscale(
+        completed
+            .values()
+            .max()
+            .map(|last| last.saturating_duration_since(start))
+            .unwrap_or_default(),
+    );
+    let total_slots = (config.num_executors * config.task_slots_per_executor) as f64;
+    let slot_utilization = if makespan.is_zero() || total_slots == 0.0 {
+        0.0
+    } else {
+        launcher.busy_time.load(Ordering::SeqCst) as f64
+            / (makespan.as_millis() as f64 * total_slots)
+    };
+
+    Ok(SimulationResult {
+        makespan,
+        job_latencies,
+        num_tasks: launcher.launched_tasks.load(Ordering::SeqCst),
+        slot_utilization,
+    })
+}
+
+/// Launcher for virtual executors, which complete every task after its recorded duration
+struct SimulatedTaskLauncher {
+    sender: UnboundedSender<(String, Vec<TaskStatus>)>,
+    /// The task durations of the stages of the recorded jobs by job ID, in stage ID order
+    jobs: Mutex<HashMap<String, Vec<u64>>>,
+    speedup: u32,
+    launched_tasks: AtomicUsize,
+    /// Recorded milliseconds spent running tasks
+    busy_time: AtomicU64,
+}
+
+#[async_trait]
+impl TaskLauncher for SimulatedTaskLauncher {
+    async fn launch_tasks(
+        &self,
+        executor: &ExecutorMetadata,
+        tasks: Vec<MultiTaskDefinition>,
+        _executor_manager: &ExecutorManager,
+    ) -> Result<()> {
+        for task in tasks {
+            let duration = self
+                .jobs
+                .lock()
+                .get(&task.job_id)
+                .and_then(|task_durations| {
+                    task_durations
+                        .get((task.stage_id as usize).saturating_sub(1))
+                        .cloned()
+                })
+                .unwrap_or(0);
+            for TaskId {
+                task_id,
+                partition_id,
+                ..
+            } in task.task_ids
+            {
+                self.launched_tasks.fetch_add(1, Ordering::SeqCst);
+                self.busy_time.fetch_add(duration, Ordering::SeqCst);
+
+                let sender = self.sender.clone();
+                let executor_id = executor.id.clone();
+                let job_id = task.job_id.clone();
+                let stage_id = task.stage_id;
+                let stage_attempt_num = task.stage_attempt_num;
+                let real_duration = Duration::from_millis(duration) / self.speedup;
+                tokio::spawn(async move {
+                    let launch_time = timestamp_millis();
+                    tokio::time::sleep(real_duration).await;
+                    let status = TaskStatus {
+                        task_id,
+                        job_id,
+                        stage_id,
+                        stage_attempt_num,
+                        partition_id,
+                        launch_time,
+                        start_exec_time: launch_time,
+                        end_exec_time: timestamp_millis(),
+                        metrics: vec![],
+                        status: Some(task_status::Status::Successful(SuccessfulTask {
+                            executor_id: executor_id.clone(),
+                            partitions: vec![ShuffleWritePartition {
+                                partition_id: 0,
+                                path: String::default(),
+                                num_batches: 1,
+                                num_rows: 1,
+                                num_bytes: 1,
+                                null_counts: vec![],
+                                column_bounds: vec![],
+                                bloom_filters: vec![],
+                            }],
+                        })),
+                    };
+                    if let Err(e) = sender.send((executor_id, vec![status])) {
+                        error!("Error sending simulated task status: {e:?}");
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Metrics collector recording when the jobs complete
+#[derive(Default)]
+struct SimulationMetricsCollector {
+    completed: Mutex<HashMap<String, Instant>>,
+    failed_jobs: AtomicUsize,
+    notify: Notify,
+}
+
+impl SimulationMetricsCollector {
+    fn job_finished(&self, job_id: &str) {
+        self.completed
+            .lock()
+            .insert(job_id.to_owned(), Instant::now());
+        self.notify.notify_waiters();
+    }
+}
+
+impl SchedulerMetricsCollector for SimulationMetricsCollector {
+    fn record_submitted(&self, _job_id: &str, _queued_at: u64, _submitted_at: u64) {}
+
+    fn record_completed(&self, job_id: &str, _queued_at: u64, _completed_at: u64) {
+        self.job_finished(job_id);
+    }
+
+    fn record_failed(&self, job_id: &str, _queued_at: u64, _failed_at: u64) {
+        self.failed_jobs.fetch_add(1, Ordering::SeqCst);
+        self.job_finished(job_id);
+    }
+
+    fn record_cancelled(&self, job_id: &str) {
+        self.failed_jobs.fetch_add(1, Ordering::SeqCst);
+        self.job_finished(job_id);
+    }
+
+    fn set_pending_tasks_queue_size(&self, _value: u64) {}
+
+    fn record_state_operation(
+        &self,
+        _keyspace: &str,
+        _operation: &str,
+        _latency: Duration,
+        _failed: bool,
+    ) {
+    }
+
+    fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::TaskDistributionPolicy;
+
+    #[tokio::test]
+    async fn test_simulate_workload() -> Result<()> {
+        let workload = Workload::from_json(
+            r#"{"jobs": [
+                {"name": "etl", "submitted_at": 0, "stages": [
+                    {"stage_id": 1, "partitions": 4, "task_duration": 1000},
+                    {"stage_id": 2, "partitions": 2, "task_duration": 200},
+                    {"stage_id": 3, "partitions": 2, "inputs": [1, 2],
+                     "task_duration": 500}
+                ]},
+                {"name": "report", "submitted_at": 200, "stages": [
+                    {"stage_id": 1, "partitions": 2, "task_duration": 300},
+                    {"stage_id": 2, "partitions": 2, "inputs": [1],
+                     "task_duration": 300}
+                ]}
+            ]}"#,
+        )?;
+
+        for task_distribution in [
+            TaskDistributionPolicy::Bias,
+            TaskDistributionPolicy::RoundRobin,
+            TaskDistributionPolicy::Weighted,
+        ] {
+            let result = simulate(
+                &workload,
+                SimulationConfig {
+                    num_executors: 2,
+                    task_slots_per_executor: 2,
+                    scheduler_config: SchedulerConfig::default()
+                        .with_task_distribution(task_distribution),
+                    speedup: 100,
+                    timeout: Duration::from_secs(60),
+                },
+            )
+            .await?;
+            assert_eq!(2, result.job_latencies.len());
+            assert_eq!("etl", result.job_latencies[0].0);
+            // every stage runs after the stages it reads
+            assert!(result.job_latencies[0].1 >= Duration::from_millis(1500));
+            assert!(result.job_latencies[1].1 >= Duration::from_millis(600));
+            assert!(result.makespan >= Duration::from_millis(1500));
+            // the tasks of all the recorded stages run once
+            assert_eq!(12, result.num_tasks);
+            assert!(result.slot_utilization > 0.0);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_simulation_timeout() -> Result<()> {
+        let workload = Workload::from_json(
+            r#"{"jobs": [
+                {"name": "hour", "submitted_at": 0, "stages": [
+                    {"stage_id": 1, "partitions": 1, "task_duration": 3600000}
+                ]}
+            ]}"#,
+        )?;
+        let result = simulate(
+            &workload,
+            SimulationConfig {
+                speedup: 1,
+                timeout: Duration::from_millis(200),
+                ..Default::default()
+            },
+        )
+        .await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_recorded_stages() -> Result<()> {
+        let stage = |stage_id, partitions, inputs: Vec<usize>| RecordedStage {
+            stage_id,
+            partitions,
+            inputs,
+            task_duration: stage_id as u64 * 100,
+        };
+        let job = |stages| RecordedJob {
+            name: "job".to_owned(),
+            submitted_at: 0,
+            stages,
+        };
+
+        // the stages are numbered by the scheduler after the stages they read
+        let (_, task_durations) = job(vec![
+            stage(1, 2, vec![3, 2]),
+            stage(2, 4, vec![]),
+            stage(3, 2, vec![4]),
+            stage(4, 8, vec![]),
+        ])
+        .plan()?;
+        assert_eq!(vec![400, 300, 200, 100], task_durations);
+
+        // a stage read by two stages
+        assert!(job(vec![
+            stage(1, 2, vec![]),
+            stage(2, 2, vec![1]),
+            stage(3, 2, vec![1, 2]),
+        ])
+        .plan()
+        .is_err());
+        // a missing input stage
+        assert!(job(vec![stage(1, 2, vec![2])]).plan().is_err());
+        // stages reading each other
+        assert!(job(vec![
+            stage(1, 2, vec![]),
+            stage(2, 2, vec![3]),
+            stage(3, 2, vec![2]),
+        ])
+        .plan()
+        .is_err());
+        // more input stages than tasks
+        assert!(job(vec![
+            stage(1, 2, vec![]),
+            stage(2, 2, vec![]),
+            stage(3, 1, vec![1, 2]),
+        ])
+        .plan()
+        .is_err());
+        Ok(())
+    }
+}
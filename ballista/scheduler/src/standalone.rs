@@ -32,30 +32,37 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpListener;
 
+/// Start an in-process scheduler listening on a port assigned by the OS, so that several
+/// standalone clusters can run on the same machine. Returns the address it listens on.
 pub async fn new_standalone_scheduler() -> Result<SocketAddr> {
     let metrics_collector = default_metrics_collector()?;
 
+    // Let the OS assign a random, free port
+    let listener = TcpListener::bind("localhost:0").await?;
+    let addr = listener.local_addr()?;
+    let config = SchedulerConfig::default()
+        .with_hostname("localhost")
+        .with_port(addr.port());
+    let scheduler_name = config.scheduler_name();
+
     let cluster = BallistaCluster::new_kv(
         SledClient::try_new_temporary()?,
-        "localhost:50050",
+        scheduler_name.clone(),
         default_session_builder,
         BallistaCodec::default(),
     );
 
     let mut scheduler_server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
         SchedulerServer::new(
-            "localhost:50050".to_owned(),
+            scheduler_name,
             cluster,
             BallistaCodec::default(),
-            Arc::new(SchedulerConfig::default()),
+            Arc::new(config),
             metrics_collector,
         );
 
     scheduler_server.init().await?;
     let server = SchedulerGrpcServer::new(scheduler_server.clone());
-    // Let the OS assign a random, free port
-    let listener = TcpListener::bind("localhost:0").await?;
-    let addr = listener.local_addr()?;
     info!(
         "Ballista v{} Rust Scheduler listening on {:?}",
         BALLISTA_VERSION, addr
@@ -22,22 +22,38 @@ use std::iter::FromIterator;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_plan::aggregates::{AggregateExec, AggregateMode};
+use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
 use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::filter::FilterExec;
+use datafusion::physical_plan::limit::{GlobalLimitExec, LocalLimitExec};
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::sorts::sort::SortExec;
+use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
 use datafusion::physical_plan::{accept, ExecutionPlan, ExecutionPlanVisitor};
 use datafusion::prelude::SessionContext;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use log::{error, info, warn};
 
 use ballista_core::error::{BallistaError, Result};
-use ballista_core::execution_plans::{ShuffleWriterExec, UnresolvedShuffleExec};
+use ballista_core::execution_plans::{
+    RuntimeFilterExec, ShuffleWriterExec, UnresolvedShuffleExec,
+};
 use ballista_core::serde::protobuf::failed_task::FailedReason;
 use ballista_core::serde::protobuf::job_status::Status;
 use ballista_core::serde::protobuf::{
-    self, execution_graph_stage::StageType, FailedTask, JobStatus, ResultLost,
-    RunningJob, SuccessfulJob, TaskStatus,
+    self, execution_graph_stage::StageType, FailedTask, JobStatus, KeyValuePair,
+    ResultLost, RunningJob, SuccessfulJob, TaskStatus,
+};
+use ballista_core::serde::protobuf::{
+    job_status, FailedJob, FailedJobTask, ShuffleWritePartition,
+};
+use ballista_core::serde::protobuf::{task_status, RunningTask, SuccessfulTask};
+use ballista_core::serde::scheduler::from_proto::{
+    bloom_filters_from_proto, column_bounds_from_proto,
 };
-use ballista_core::serde::protobuf::{job_status, FailedJob, ShuffleWritePartition};
-use ballista_core::serde::protobuf::{task_status, RunningTask};
 use ballista_core::serde::scheduler::{
     ExecutorMetadata, PartitionId, PartitionLocation, PartitionStats,
 };
@@ -45,13 +61,16 @@ use ballista_core::serde::BallistaCodec;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 
 use crate::display::print_stage_metrics;
-use crate::planner::DistributedPlanner;
+use crate::planner::{
+    deduplicate_stages, find_unresolved_shuffles, DefaultStagePlanner, StagePlanner,
+};
+use crate::runtime_filter::MapBloomFilters;
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::scheduler_server::timestamp_millis;
 use crate::state::execution_graph::execution_stage::RunningStage;
 pub(crate) use crate::state::execution_graph::execution_stage::{
-    ExecutionStage, FailedStage, ResolvedStage, StageOutput, SuccessfulStage, TaskInfo,
-    UnresolvedStage,
+    ExecutionStage, ExecutorTaskLimit, FailedStage, ResolvedStage, StageOutput,
+    SuccessfulStage, TaskInfo, UnresolvedStage,
 };
 use crate::state::task_manager::UpdatedStages;
 
@@ -129,6 +148,75 @@ pub struct ExecutionGraph {
     /// Failed stage attempts, record the failed stage attempts to limit the retry times.
     /// Map from Stage ID -> Set<Stage_ATTPMPT_NUM>
     failed_stage_attempts: HashMap<usize, HashSet<usize>>,
+    /// Max number of running tasks of the stages
+    max_running_stage_tasks: StageTaskCaps,
+    /// Max number of running tasks of every stage on the same executor, `None` means no limit
+    max_stage_tasks_per_executor: Option<usize>,
+    /// Whether the stages start before their input stages complete, once all the input
+    /// tasks are scheduled and some of them finished
+    pipelined_stages: bool,
+    /// Limits of the rows and bytes output by the final stage
+    result_limits: ResultLimits,
+    /// Final single partition stage of the job run by the client, encoded with the
+    /// physical codec of the scheduler, empty if the scheduler runs all the stages
+    collect_plan: Vec<u8>,
+    /// DataFusion options of the job session which differ from the defaults, kept with
+    /// the graph for the tasks of a job taken over by another scheduler
+    session_props: Vec<KeyValuePair>,
+}
+
+/// Max number of running tasks of the stages of a job, e.g. for stages scanning rate
+/// limited sources: a default for every stage, overridden for some stages
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StageTaskCaps {
+    /// Max number of running tasks of every stage, `None` means no limit
+    pub default: Option<usize>,
+    /// Max number of running tasks of some stages by stage id, overriding the default,
+    /// 0 means no limit
+    pub by_stage: HashMap<usize, usize>,
+}
+
+impl StageTaskCaps {
+    /// Max number of running tasks of the given stage, `None` means no limit
+    pub fn of(&self, stage_id: usize) -> Option<usize> {
+        match self.by_stage.get(&stage_id) {
+            Some(0) => None,
+            Some(max_tasks) => Some(*max_tasks),
+            None => self.default,
+        }
+    }
+}
+
+/// Limits of the results of a job, protecting the clients from collecting more rows or bytes
+/// than they can hold
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResultLimits {
+    /// Max number of rows output by the final stage, `None` means no limit
+    pub max_rows: Option<u64>,
+    /// Max number of bytes output by the final stage, `None` means no limit
+    pub max_bytes: Option<u64>,
+    /// Whether the output exceeding the limits is truncated rather than failing the job
+    pub truncate: bool,
+}
+
+impl ResultLimits {
+    /// Whether the rows or the bytes of the results are limited
+    fn is_limited(&self) -> bool {
+        self.max_rows.is_some() || self.max_bytes.is_some()
+    }
+
+    /// Describe the limit exceeded by the given number of rows and bytes, if any
+    fn exceeded(&self, num_rows: u64, num_bytes: u64) -> Option<String> {
+        match (self.max_rows, self.max_bytes) {
+            (Some(max_rows), _) if num_rows > max_rows => Some(format!(
+                "the results have more than {max_rows} rows (ballista.results.max_rows)"
+            )),
+            (_, Some(max_bytes)) if num_bytes > max_bytes => Some(format!(
+                "the results have more than {max_bytes} bytes (ballista.results.max_bytes)"
+            )),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -149,11 +237,37 @@ impl ExecutionGraph {
         plan: Arc<dyn ExecutionPlan>,
         queued_at: u64,
     ) -> Result<Self> {
-        let mut planner = DistributedPlanner::new();
+        Self::new_with_stage_planner(
+            scheduler_id,
+            job_id,
+            job_name,
+            session_id,
+            plan,
+            queued_at,
+            &DefaultStagePlanner,
+        )
+    }
 
+    /// Create the execution graph of a job whose plan is split into stages by the given
+    /// [StagePlanner]
+    pub fn new_with_stage_planner(
+        scheduler_id: &str,
+        job_id: &str,
+        job_name: &str,
+        session_id: &str,
+        plan: Arc<dyn ExecutionPlan>,
+        queued_at: u64,
+        stage_planner: &dyn StagePlanner,
+    ) -> Result<Self> {
         let output_partitions = plan.properties().output_partitioning().partition_count();
 
-        let shuffle_stages = planner.plan_query_stages(job_id, plan)?;
+        let shuffle_stages = stage_planner.plan_query_stages(job_id, plan)?;
+        if shuffle_stages.is_empty() {
+            return Err(BallistaError::Internal(format!(
+                "The stage planner {stage_planner:?} planned no stages for job {job_id}"
+            )));
+        }
+        let shuffle_stages = deduplicate_stages(job_id, shuffle_stages)?;
 
         let builder = ExecutionStageBuilder::new();
         let stages = builder.build(shuffle_stages)?;
@@ -172,6 +286,7 @@ impl ExecutionGraph {
                     queued_at,
                     started_at,
                     scheduler: scheduler_id.to_string(),
+                    epoch: 0,
                 })),
             },
             queued_at,
@@ -182,6 +297,12 @@ impl ExecutionGraph {
             output_locations: vec![],
             task_id_gen: 0,
             failed_stage_attempts: HashMap::new(),
+            max_running_stage_tasks: StageTaskCaps::default(),
+            max_stage_tasks_per_executor: None,
+            pipelined_stages: false,
+            result_limits: ResultLimits::default(),
+            collect_plan: vec![],
+            session_props: vec![],
         })
     }
 
@@ -201,6 +322,16 @@ impl ExecutionGraph {
         &self.status
     }
 
+    /// Transfer the ownership of the running job to another scheduler, which receives the
+    /// status of its tasks from now on, and increment the ownership epoch of the job
+    pub fn set_scheduler(&mut self, scheduler_id: &str) {
+        self.scheduler_id = Some(scheduler_id.to_string());
+        if let Some(Status::Running(running)) = self.status.status.as_mut() {
+            running.scheduler = scheduler_id.to_string();
+            running.epoch += 1;
+        }
+    }
+
     pub fn start_time(&self) -> u64 {
         self.start_time
     }
@@ -209,16 +340,164 @@ impl ExecutionGraph {
         self.end_time
     }
 
+    /// Number of partitions of the output of the job
+    pub fn output_partitions(&self) -> usize {
+        self.output_partitions
+    }
+
+    /// Max number of running tasks of the stages
+    pub fn max_running_stage_tasks(&self) -> StageTaskCaps {
+        self.max_running_stage_tasks.clone()
+    }
+
+    /// Limit the number of running tasks of every stage, e.g. for stages scanning rate
+    /// limited sources, unless the stage has a limit of its own. Only the tasks scheduled
+    /// afterwards are limited.
+    pub fn set_max_running_stage_tasks(&mut self, max_tasks: Option<usize>) {
+        self.max_running_stage_tasks.default = max_tasks;
+    }
+
+    /// Limit the number of running tasks of the given stages by stage id, overriding
+    /// the limit of every stage, 0 meaning no limit for the stage
+    pub fn set_max_running_tasks_by_stage(&mut self, max_tasks: HashMap<usize, usize>) {
+        self.max_running_stage_tasks.by_stage = max_tasks;
+    }
+
+    /// Max number of running tasks of every stage on the same executor, `None` means no limit
+    pub fn max_stage_tasks_per_executor(&self) -> Option<usize> {
+        self.max_stage_tasks_per_executor
+    }
+
+    /// Limit the number of tasks of every stage running or holding their outputs on the
+    /// same executor, so that the failure of an executor only loses a bounded part of the
+    /// work of a stage. Only the tasks scheduled afterwards are limited.
+    pub fn set_max_stage_tasks_per_executor(&mut self, max_tasks: Option<usize>) {
+        self.max_stage_tasks_per_executor = max_tasks;
+    }
+
+    /// Whether the stages start before their input stages complete
+    pub fn pipelined_stages(&self) -> bool {
+        self.pipelined_stages
+    }
+
+    /// Start the stages once all the tasks of their input stages are scheduled and some of
+    /// them finished, rather than once the input stages complete. The tasks of these stages
+    /// poll the scheduler for the shuffle partitions written after they started.
+    pub fn set_pipelined_stages(&mut self, pipelined: bool) {
+        self.pipelined_stages = pipelined;
+    }
+
+    pub fn result_limits(&self) -> ResultLimits {
+        self.result_limits
+    }
+
+    /// Limit the rows and bytes output by the final stage. The job fails as soon as its
+    /// output exceeds the limits, or its output partitions are truncated when it succeeds.
+    pub fn set_result_limits(&mut self, result_limits: ResultLimits) {
+        self.result_limits = result_limits;
+    }
+
+    /// Final single partition stage of the job run by the client, empty if the scheduler
+    /// runs all the stages
+    pub fn collect_plan(&self) -> &[u8] {
+        &self.collect_plan
+    }
+
+    /// Keep the encoded plan of the stage detached by [Self::detach_collect_stage], which
+    /// is passed to the client along with the output locations of the job
+    pub fn set_collect_plan(&mut self, collect_plan: Vec<u8>) {
+        self.collect_plan = collect_plan;
+    }
+
+    /// DataFusion options of the job session sent along with every task
+    pub fn session_props(&self) -> &[KeyValuePair] {
+        &self.session_props
+    }
+
+    pub fn set_session_props(&mut self, session_props: Vec<KeyValuePair>) {
+        self.session_props = session_props;
+    }
+
+    /// Detach the final stage of the job for the client to run it, if this stage reads
+    /// the output of a single stage into a single partition, e.g. a final sort, limit or
+    /// merge. The stage it reads becomes the final stage, and the detached plan is
+    /// returned with the [UnresolvedShuffleExec] reading the output partitions of the
+    /// job. The final stage is kept when the results of the job are limited, as the
+    /// limits apply to its output, and when it does more than reading its input, e.g.
+    /// writes or commits files, which must happen on the cluster.
+    pub fn detach_collect_stage(&mut self) -> Option<Arc<dyn ExecutionPlan>> {
+        if self.result_limits != ResultLimits::default() {
+            return None;
+        }
+        let (final_stage_id, input_stage_id, plan) =
+            self.stages.values().find_map(|stage| match stage {
+                ExecutionStage::UnResolved(stage)
+                    if stage.output_links.is_empty() && stage.inputs.len() == 1 =>
+                {
+                    let input_stage_id = *stage.inputs.keys().next()?;
+                    // The final stage is a ShuffleWriterExec writing out its input
+                    let plan = stage.plan.children().first().cloned()?;
+                    Some((stage.stage_id, input_stage_id, plan))
+                }
+                _ => None,
+            })?;
+        if plan.properties().output_partitioning().partition_count() != 1
+            || !is_read_only_plan(&plan)
+        {
+            return None;
+        }
+        let output_partitions = match find_unresolved_shuffles(&plan).ok()?.as_slice() {
+            [shuffle] if shuffle.stage_id == input_stage_id => {
+                shuffle.output_partition_count
+            }
+            _ => return None,
+        };
+        let output_links = match self.stages.get_mut(&input_stage_id) {
+            Some(ExecutionStage::UnResolved(stage)) => &mut stage.output_links,
+            Some(ExecutionStage::Resolved(stage)) => &mut stage.output_links,
+            _ => return None,
+        };
+        if output_links.as_slice() != [final_stage_id] {
+            return None;
+        }
+        output_links.clear();
+        self.stages.remove(&final_stage_id);
+        self.output_partitions = output_partitions;
+        info!(
+            "Detached the final stage {} of job {}, run by the client on the {} output \
+             partitions of stage {}",
+            final_stage_id, self.job_id, output_partitions, input_stage_id
+        );
+        Some(plan)
+    }
+
     pub fn stage_count(&self) -> usize {
         self.stages.len()
     }
 
+    /// Get the number of tasks of all the stages
+    pub fn task_count(&self) -> usize {
+        self.stages.values().map(|stage| stage.task_count()).sum()
+    }
+
     pub fn next_task_id(&mut self) -> usize {
         let new_tid = self.task_id_gen;
         self.task_id_gen += 1;
         new_tid
     }
 
+    /// The id of the next task launched
+    pub(crate) fn task_id_gen(&self) -> usize {
+        self.task_id_gen
+    }
+
+    /// Make sure that the next tasks launched get ids of at least `task_id_gen`, e.g.
+    /// once the task status updates saved since a snapshot of the graph are replayed on
+    /// it
+    pub(crate) fn advance_task_id_gen(&mut self, task_id_gen: usize) {
+        self.task_id_gen = self.task_id_gen.max(task_id_gen);
+    }
+
     pub(crate) fn stages(&self) -> &HashMap<usize, ExecutionStage> {
         &self.stages
     }
@@ -299,6 +578,7 @@ impl ExecutionGraph {
         let mut resolved_stages = HashSet::new();
         let mut successful_stages = HashSet::new();
         let mut failed_stages = HashMap::new();
+        let mut failed_stage_tasks = HashMap::new();
         let mut rollback_running_stages = HashMap::new();
         let mut resubmit_successful_stages: HashMap<usize, HashSet<usize>> =
             HashMap::new();
@@ -308,6 +588,7 @@ impl ExecutionGraph {
             if let Some(stage) = self.stages.get_mut(&stage_id) {
                 if let ExecutionStage::Running(running_stage) = stage {
                     let mut locations = vec![];
+                    let mut bloom_filters = MapBloomFilters::new();
                     for task_status in stage_task_statuses.into_iter() {
                         let task_stage_attempt_num =
                             task_status.stage_attempt_num as usize;
@@ -326,6 +607,14 @@ impl ExecutionGraph {
                             partition_id
                         );
                         let operator_metrics = task_status.metrics.clone();
+                        let task_id = task_status.task_id;
+                        let failed_job_task = || FailedJobTask {
+                            stage_id: stage_id as u32,
+                            stage_attempt_num: task_stage_attempt_num as u32,
+                            partition_id: partition_id as u32,
+                            task_id,
+                            executor_id: executor.id.clone(),
+                        };
 
                         if !running_stage
                             .update_task_info(partition_id, task_status.clone())
@@ -395,10 +684,18 @@ impl ExecutionGraph {
                                             failed_task.error
                                         );
                                         error!("{}", error_msg);
+                                        failed_stage_tasks.insert(
+                                            stage_id,
+                                            (failed_job_task(), failed_task.error),
+                                        );
                                         failed_stages.insert(stage_id, error_msg);
                                     }
                                 }
                                 Some(FailedReason::ExecutionError(_)) => {
+                                    failed_stage_tasks.insert(
+                                        stage_id,
+                                        (failed_job_task(), failed_task.error.clone()),
+                                    );
                                     failed_stages.insert(stage_id, failed_task.error);
                                 }
                                 Some(_) => {
@@ -417,6 +714,10 @@ impl ExecutionGraph {
                                                 partition_id, stage_id, max_task_failures, failed_task.error
                                             );
                                             error!("{}", error_msg);
+                                            failed_stage_tasks.insert(
+                                                stage_id,
+                                                (failed_job_task(), failed_task.error),
+                                            );
                                             failed_stages.insert(stage_id, error_msg);
                                         }
                                     } else if failed_task.retryable {
@@ -429,6 +730,10 @@ impl ExecutionGraph {
                                     let error_msg = format!(
                                         "Task {partition_id} in Stage {stage_id} failed with unknown failure reasons, fail the stage");
                                     error!("{}", error_msg);
+                                    failed_stage_tasks.insert(
+                                        stage_id,
+                                        (failed_job_task(), failed_task.error),
+                                    );
                                     failed_stages.insert(stage_id, error_msg);
                                 }
                             }
@@ -440,6 +745,18 @@ impl ExecutionGraph {
                             running_stage
                                 .update_task_metrics(partition_id, operator_metrics)?;
 
+                            // the bloom filters of all the outputs of the map task are
+                            // sent along with its first output
+                            if let Some(shuffle) = successful_task
+                                .partitions
+                                .iter()
+                                .find(|shuffle| !shuffle.bloom_filters.is_empty())
+                            {
+                                bloom_filters.insert(
+                                    partition_id,
+                                    bloom_filters_from_proto(&shuffle.bloom_filters),
+                                );
+                            }
                             locations.append(&mut partition_to_location(
                                 &job_id,
                                 partition_id,
@@ -478,6 +795,7 @@ impl ExecutionGraph {
                                 stage_id,
                                 is_final_successful,
                                 locations,
+                                &bloom_filters,
                                 output_links,
                             )?
                             .into_iter(),
@@ -495,6 +813,14 @@ impl ExecutionGraph {
                             task_stage_attempt_num,
                             partition_id
                         );
+                        let task_id = task_status.task_id;
+                        let failed_job_task = || FailedJobTask {
+                            stage_id: stage_id as u32,
+                            stage_attempt_num: task_stage_attempt_num as u32,
+                            partition_id: partition_id as u32,
+                            task_id,
+                            executor_id: executor.id.clone(),
+                        };
                         let mut should_ignore = true;
                         // handle delayed failed tasks if the stage's next attempt is still in UnResolved status.
                         if let Some(task_status::Status::Failed(failed_task)) =
@@ -507,6 +833,13 @@ impl ExecutionGraph {
                                 match failed_reason {
                                     Some(FailedReason::ExecutionError(_)) => {
                                         should_ignore = false;
+                                        failed_stage_tasks.insert(
+                                            stage_id,
+                                            (
+                                                failed_job_task(),
+                                                failed_task.error.clone(),
+                                            ),
+                                        );
                                         failed_stages.insert(stage_id, failed_task.error);
                                     }
                                     Some(FailedReason::FetchPartitionError(
@@ -646,6 +979,7 @@ impl ExecutionGraph {
             resolved_stages,
             successful_stages,
             failed_stages,
+            failed_stage_tasks,
             rollback_running_stages,
             resubmit_successful_stages: resubmit_successful_stages
                 .keys()
@@ -662,6 +996,8 @@ impl ExecutionGraph {
         let job_id = self.job_id().to_owned();
         let mut has_resolved = false;
         let mut job_err_msg = "".to_owned();
+        let mut failed_task = None;
+        let mut error_chain = vec![];
 
         for stage_id in updated_stages.resolved_stages {
             self.resolve_stage(stage_id)?;
@@ -676,6 +1012,18 @@ impl ExecutionGraph {
         for (stage_id, err_msg) in &updated_stages.failed_stages {
             job_err_msg =
                 format!("Job failed due to stage {stage_id} failed: {err_msg}\n");
+
+            error_chain = vec![format!("Job failed due to stage {stage_id} failed")];
+            error_chain.push(err_msg.clone());
+            failed_task = None;
+            if let Some((task, task_err_msg)) =
+                updated_stages.failed_stage_tasks.get(stage_id)
+            {
+                if task_err_msg != err_msg {
+                    error_chain.push(task_err_msg.clone());
+                }
+                failed_task = Some(task.clone());
+            }
         }
 
         let mut events = vec![];
@@ -698,17 +1046,46 @@ impl ExecutionGraph {
             }
         }
 
+        let result_limit_error = if updated_stages.failed_stages.is_empty() {
+            self.result_limit_error()
+        } else {
+            None
+        };
+
         if !updated_stages.failed_stages.is_empty() {
             info!("Job {} is failed", job_id);
-            self.fail_job(job_err_msg.clone());
+            let error_chain = truncate_error_chain(error_chain);
+            self.fail_job(
+                job_err_msg.clone(),
+                failed_task.clone(),
+                error_chain.clone(),
+            );
+            events.push(QueryStageSchedulerEvent::JobRunningFailed {
+                job_id,
+                fail_message: job_err_msg,
+                failed_task,
+                error_chain,
+                queued_at: self.queued_at,
+                failed_at: timestamp_millis(),
+            });
+        } else if let Some(error) = result_limit_error {
+            info!("Job {} is failed, {}", job_id, error);
+            let job_err_msg = format!("Job failed due to its results: {error}");
+            let error_chain = vec![job_err_msg.clone()];
+            self.fail_job(job_err_msg.clone(), None, error_chain.clone());
             events.push(QueryStageSchedulerEvent::JobRunningFailed {
                 job_id,
                 fail_message: job_err_msg,
+                failed_task: None,
+                error_chain,
                 queued_at: self.queued_at,
                 failed_at: timestamp_millis(),
             });
-        } else if self.is_successful() {
-            // If this ExecutionGraph is successful, finish it
+        } else if self.is_successful()
+            && !matches!(self.status.status, Some(Status::Failed(_)))
+        {
+            // If this ExecutionGraph is successful, finish it, unless it already failed
+            // e.g. due to its result limits
             info!("Job {} is success, finalizing output partitions", job_id);
             self.succeed_job()?;
             events.push(QueryStageSchedulerEvent::JobFinished {
@@ -728,9 +1105,28 @@ impl ExecutionGraph {
         stage_id: usize,
         is_completed: bool,
         locations: Vec<PartitionLocation>,
+        bloom_filters: &MapBloomFilters,
         output_links: Vec<usize>,
     ) -> Result<Vec<usize>> {
         let mut resolved_stages = vec![];
+        // the running stages whose tasks are all scheduled and some finished, whose outputs
+        // can be consumed by pipelined stages
+        let started_stages: HashSet<usize> = if self.pipelined_stages {
+            self.stages
+                .values()
+                .filter_map(|stage| match stage {
+                    ExecutionStage::Running(running)
+                        if running.available_tasks() == 0
+                            && running.successful_tasks() > 0 =>
+                    {
+                        Some(running.stage_id)
+                    }
+                    _ => None,
+                })
+                .collect()
+        } else {
+            HashSet::new()
+        };
         let job_id = &self.job_id;
         if output_links.is_empty() {
             // If `output_links` is empty, then this is a final stage
@@ -742,8 +1138,11 @@ impl ExecutionGraph {
                     if let ExecutionStage::UnResolved(linked_unresolved_stage) =
                         linked_stage
                     {
-                        linked_unresolved_stage
-                            .add_input_partitions(stage_id, locations.clone())?;
+                        linked_unresolved_stage.add_input_partitions(
+                            stage_id,
+                            locations.clone(),
+                            bloom_filters,
+                        )?;
 
                         // If all tasks for this stage are complete, mark the input complete in the parent stage
                         if is_completed {
@@ -751,9 +1150,31 @@ impl ExecutionGraph {
                         }
 
                         // If all input partitions are ready, we can resolve any UnresolvedShuffleExec in the parent stage plan
-                        if linked_unresolved_stage.resolvable() {
+                        if linked_unresolved_stage.resolvable()
+                            || (self.pipelined_stages
+                                && linked_unresolved_stage.pipelinable(&started_stages))
+                        {
                             resolved_stages.push(linked_unresolved_stage.stage_id);
                         }
+                    } else if let (
+                        true,
+                        ExecutionStage::Resolved(ResolvedStage { inputs, .. })
+                        | ExecutionStage::Running(RunningStage { inputs, .. }),
+                    ) = (self.pipelined_stages, linked_stage)
+                    {
+                        // A pipelined stage started before this stage completed, its tasks
+                        // get the new locations from its inputs
+                        let stage_inputs = inputs.get_mut(&stage_id).ok_or_else(|| {
+                            BallistaError::Internal(format!(
+                                "Error updating job {job_id}: {stage_id} is not an input of stage {link}"
+                            ))
+                        })?;
+                        for location in locations.iter() {
+                            stage_inputs.add_partition(location.clone());
+                        }
+                        if is_completed {
+                            stage_inputs.complete = true;
+                        }
                     } else {
                         return Err(BallistaError::Internal(format!(
                             "Error updating job {job_id}: The stage {link} as the output link of stage {stage_id}  should be unresolved"
@@ -769,6 +1190,35 @@ impl ExecutionGraph {
         Ok(resolved_stages)
     }
 
+    /// Get the locations known so far of a partition of the shuffle written by a stage, as
+    /// published to the stages reading it, and whether the writing stage completed. `None`
+    /// if no stage reads the shuffle.
+    pub fn shuffle_locations(
+        &self,
+        stage_id: usize,
+        partition_id: usize,
+    ) -> Option<(Vec<PartitionLocation>, bool)> {
+        self.stages.values().find_map(|stage| {
+            let inputs = match stage {
+                ExecutionStage::UnResolved(stage) => &stage.inputs,
+                ExecutionStage::Resolved(stage) => &stage.inputs,
+                ExecutionStage::Running(stage) => &stage.inputs,
+                ExecutionStage::Successful(stage) => &stage.inputs,
+                ExecutionStage::Failed(_) => return None,
+            };
+            inputs.get(&stage_id).map(|output| {
+                (
+                    output
+                        .partition_locations
+                        .get(&partition_id)
+                        .cloned()
+                        .unwrap_or_default(),
+                    output.is_complete(),
+                )
+            })
+        })
+    }
+
     /// Return all the currently running stage ids
     pub fn running_stages(&self) -> Vec<usize> {
         self.stages
@@ -809,6 +1259,66 @@ impl ExecutionGraph {
             .collect::<Vec<RunningTaskInfo>>()
     }
 
+    /// Number of running tasks of the stages started before all their inputs completed,
+    /// which hold their task slots while waiting for the outputs of their inputs
+    pub fn waiting_tasks(&self) -> usize {
+        self.stages
+            .values()
+            .map(|stage| match stage {
+                ExecutionStage::Running(stage) if stage.waits_for_inputs() => {
+                    stage.running_tasks().len()
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Number of successful tasks of the running and successful stages, which changes as
+    /// the tasks of the job complete
+    pub fn completed_tasks(&self) -> usize {
+        self.stages
+            .values()
+            .map(|stage| match stage {
+                ExecutionStage::Running(stage) => stage.successful_tasks(),
+                ExecutionStage::Successful(stage) => stage.task_infos.len(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Move the running stages started before all their inputs completed back to
+    /// unresolved, releasing the task slots their tasks hold, e.g. to resolve a deadlock
+    /// of the jobs waiting for task slots. The stages start again once their inputs
+    /// progress. Returns the running tasks to cancel.
+    pub fn rewind_waiting_stages(&mut self) -> Result<Vec<RunningTaskInfo>> {
+        let waiting_stages: Vec<usize> = self
+            .stages
+            .iter()
+            .filter_map(|(stage_id, stage)| match stage {
+                ExecutionStage::Running(stage) if stage.waits_for_inputs() => {
+                    Some(*stage_id)
+                }
+                _ => None,
+            })
+            .collect();
+        let mut running_tasks = vec![];
+        for stage_id in waiting_stages {
+            // the stage may have been rewound along with a waiting stage it reads
+            if let Some(ExecutionStage::Running(_)) = self.stages.get(&stage_id) {
+                running_tasks
+                    .extend(self.rollback_running_stage(stage_id, HashSet::new())?);
+            }
+        }
+        if !running_tasks.is_empty() {
+            info!(
+                "Rewound the waiting stages of job {}, {} running tasks to cancel",
+                self.job_id,
+                running_tasks.len()
+            );
+        }
+        Ok(running_tasks)
+    }
+
     /// Total number of tasks in this plan that are ready for scheduling
     pub fn available_tasks(&self) -> usize {
         self.stages
@@ -846,10 +1356,18 @@ impl ExecutionGraph {
 
         let job_id = self.job_id.clone();
         let session_id = self.session_id.clone();
+        let max_running_tasks = self.max_running_stage_tasks.clone();
+        let max_tasks_per_executor = self.max_stage_tasks_per_executor;
+        let is_schedulable = |stage: &RunningStage| {
+            stage.schedulable_tasks(max_running_tasks.of(stage.stage_id)) > 0
+                && stage
+                    .executor_task_limit(max_tasks_per_executor)
+                    .allows(executor_id)
+        };
 
         let find_candidate = self.stages.iter().any(|(_stage_id, stage)| {
             if let ExecutionStage::Running(stage) = stage {
-                stage.available_tasks() > 0
+                is_schedulable(stage)
             } else {
                 false
             }
@@ -862,7 +1380,7 @@ impl ExecutionGraph {
 
         let mut next_task = self.stages.iter_mut().find(|(_stage_id, stage)| {
             if let ExecutionStage::Running(stage) = stage {
-                stage.available_tasks() > 0
+                is_schedulable(stage)
             } else {
                 false
             }
@@ -912,6 +1430,8 @@ impl ExecutionGraph {
                     task_attempt,
                     data_cache: false,
                     plan: stage.plan.clone(),
+                    binding: TaskBinding::Bias,
+                    queue_wait_ms: stage.queue_wait_ms(partition_id),
                 })
             } else {
                 Err(BallistaError::General(format!("Stage {stage_id} is not a running stage")))
@@ -962,11 +1482,12 @@ impl ExecutionGraph {
     }
 
     fn get_running_stage_id(&mut self, black_list: &[usize]) -> Option<usize> {
+        let max_running_tasks = &self.max_running_stage_tasks;
         let mut running_stage_id = self.stages.iter().find_map(|(stage_id, stage)| {
             if black_list.contains(stage_id) {
                 None
             } else if let ExecutionStage::Running(stage) = stage {
-                if stage.available_tasks() > 0 {
+                if stage.schedulable_tasks(max_running_tasks.of(*stage_id)) > 0 {
                     Some(*stage_id)
                 } else {
                     None
@@ -997,6 +1518,267 @@ impl ExecutionGraph {
         self.output_locations.clone()
     }
 
+    /// The shuffle outputs of the tasks run by an executor, which other stages are yet to
+    /// read, missing from the shuffle files held by the executor, given as the map
+    /// partitions held for every stage. Only the outputs of the tasks which finished before
+    /// the files were listed, at `listed_at` in milliseconds, are checked.
+    ///
+    /// Returns the missing map partitions of every stage
+    pub fn missing_shuffle_outputs(
+        &self,
+        executor_id: &str,
+        held: &HashMap<usize, HashSet<usize>>,
+        listed_at: u128,
+    ) -> HashMap<usize, HashSet<usize>> {
+        let finished_before_listing = |stage_id: usize, map_partition_id: usize| {
+            let task_info = match self.stages.get(&stage_id) {
+                Some(ExecutionStage::Successful(stage)) => {
+                    stage.task_infos.get(map_partition_id)
+                }
+                Some(ExecutionStage::Running(stage)) => stage
+                    .task_infos
+                    .get(map_partition_id)
+                    .and_then(Option::as_ref),
+                _ => None,
+            };
+            task_info
+                .map(|info| info.end_exec_time < listed_at)
+                .unwrap_or(false)
+        };
+
+        let mut missing: HashMap<usize, HashSet<usize>> = HashMap::new();
+        for stage in self.stages.values() {
+            let inputs = match stage {
+                ExecutionStage::UnResolved(stage) => &stage.inputs,
+                ExecutionStage::Resolved(stage) => &stage.inputs,
+                ExecutionStage::Running(stage) => &stage.inputs,
+                _ => continue,
+            };
+            for (input_stage_id, output) in inputs {
+                for loc in output.partition_locations.values().flatten() {
+                    if loc.executor_meta.id == executor_id
+                        && !held
+                            .get(input_stage_id)
+                            .map(|partitions| partitions.contains(&loc.map_partition_id))
+                            .unwrap_or(false)
+                        && finished_before_listing(*input_stage_id, loc.map_partition_id)
+                    {
+                        missing
+                            .entry(*input_stage_id)
+                            .or_default()
+                            .insert(loc.map_partition_id);
+                    }
+                }
+            }
+        }
+        missing
+    }
+
+    /// Reset the tasks run by an executor whose shuffle outputs are missing, given as the
+    /// missing map partitions of every stage, see [Self::missing_shuffle_outputs]. The
+    /// stages reading these outputs roll back until the reset tasks are run again, while
+    /// the other outputs of the executor are kept.
+    ///
+    /// Returns the reset stage ids and running tasks should be killed
+    pub fn reset_missing_shuffle_outputs(
+        &mut self,
+        executor_id: &str,
+        missing: &HashMap<usize, HashSet<usize>>,
+    ) -> Result<(HashSet<usize>, Vec<RunningTaskInfo>)> {
+        let job_id = self.job_id.clone();
+        let mut rollback_resolved_stages = HashSet::new();
+        let mut rollback_running_stages = HashSet::new();
+        for (stage_id, stage) in self.stages.iter_mut() {
+            let stage_inputs = match stage {
+                ExecutionStage::UnResolved(stage) => &mut stage.inputs,
+                ExecutionStage::Resolved(stage) => &mut stage.inputs,
+                ExecutionStage::Running(stage) => &mut stage.inputs,
+                _ => continue,
+            };
+            let mut rollback_stage = false;
+            for (input_stage_id, stage_output) in stage_inputs.iter_mut() {
+                let Some(map_partitions) = missing.get(input_stage_id) else {
+                    continue;
+                };
+                let mut match_found = false;
+                for locs in stage_output.partition_locations.values_mut() {
+                    let before_len = locs.len();
+                    locs.retain(|loc| {
+                        loc.executor_meta.id != executor_id
+                            || !map_partitions.contains(&loc.map_partition_id)
+                    });
+                    match_found |= locs.len() < before_len;
+                }
+                if match_found {
+                    stage_output.complete = false;
+                    rollback_stage = true;
+                }
+            }
+            if rollback_stage {
+                match stage {
+                    ExecutionStage::Resolved(_) => {
+                        rollback_resolved_stages.insert(*stage_id);
+                    }
+                    ExecutionStage::Running(_) => {
+                        rollback_running_stages.insert(*stage_id);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut reset_running_stages = HashSet::new();
+        let mut resubmit_successful_stages = HashSet::new();
+        for (stage_id, map_partitions) in missing {
+            let reset = match self.stages.get_mut(stage_id) {
+                Some(ExecutionStage::Running(stage)) => {
+                    let reset = stage.reset_map_partitions(executor_id, map_partitions);
+                    if reset > 0 {
+                        reset_running_stages.insert(*stage_id);
+                    }
+                    reset
+                }
+                Some(ExecutionStage::Successful(stage)) => {
+                    let reset = stage.reset_map_partitions(executor_id, map_partitions);
+                    if reset > 0 {
+                        resubmit_successful_stages.insert(*stage_id);
+                    }
+                    reset
+                }
+                _ => 0,
+            };
+            if reset > 0 {
+                warn!(
+                    "Reset {} tasks for job/stage {}/{} missing outputs on Executor {}",
+                    reset, job_id, stage_id, executor_id
+                );
+            }
+        }
+
+        for stage_id in rollback_resolved_stages.iter() {
+            self.rollback_resolved_stage(*stage_id)?;
+        }
+
+        let mut all_running_tasks = vec![];
+        for stage_id in rollback_running_stages.iter() {
+            let tasks = self.rollback_running_stage(
+                *stage_id,
+                HashSet::from([executor_id.to_owned()]),
+            )?;
+            all_running_tasks.extend(tasks);
+        }
+
+        for stage_id in resubmit_successful_stages.iter() {
+            self.rerun_successful_stage(*stage_id);
+        }
+
+        let mut reset_stage = HashSet::new();
+        reset_stage.extend(reset_running_stages);
+        reset_stage.extend(rollback_resolved_stages);
+        reset_stage.extend(rollback_running_stages);
+        reset_stage.extend(resubmit_successful_stages);
+        Ok((reset_stage, all_running_tasks))
+    }
+
+    /// The executor which ran the latest attempt of a task of a stage, if it is recorded
+    pub(crate) fn task_executor(
+        &self,
+        stage_id: usize,
+        task_id: usize,
+    ) -> Option<String> {
+        let task_infos: Vec<&TaskInfo> = match self.stages.get(&stage_id)? {
+            ExecutionStage::Running(stage) => stage.task_infos.iter().flatten().collect(),
+            ExecutionStage::Successful(stage) => stage.task_infos.iter().collect(),
+            ExecutionStage::Failed(stage) => stage.task_infos.iter().flatten().collect(),
+            _ => return None,
+        };
+        task_infos
+            .into_iter()
+            .filter(|task_info| task_info.task_id == task_id)
+            .find_map(|task_info| match &task_info.task_status {
+                task_status::Status::Running(RunningTask { executor_id })
+                | task_status::Status::Successful(SuccessfulTask {
+                    executor_id, ..
+                }) => Some(executor_id.clone()),
+                _ => None,
+            })
+    }
+
+    /// The shuffle outputs held by an executor which other stages are yet to read
+    pub fn shuffle_outputs_on(&self, executor_id: &str) -> Vec<PartitionLocation> {
+        let mut outputs = HashMap::new();
+        for stage in self.stages.values() {
+            let inputs = match stage {
+                ExecutionStage::UnResolved(stage) => &stage.inputs,
+                ExecutionStage::Resolved(stage) => &stage.inputs,
+                ExecutionStage::Running(stage) => &stage.inputs,
+                _ => continue,
+            };
+            for output in inputs.values() {
+                for loc in output.partition_locations.values().flatten() {
+                    if loc.executor_meta.id == executor_id {
+                        outputs
+                            .entry(loc.path.clone())
+                            .or_insert_with(|| loc.clone());
+                    }
+                }
+            }
+        }
+        outputs.into_values().collect()
+    }
+
+    /// Replace the shuffle outputs held by an executor with their replicas on other
+    /// executors, given by the paths of the outputs on the executor. The resolved and
+    /// running stages reading them are resolved again, so that their next tasks read the
+    /// replicas.
+    ///
+    /// Returns the number of replaced outputs
+    pub fn replace_shuffle_outputs(
+        &mut self,
+        executor_id: &str,
+        replicas: &HashMap<String, PartitionLocation>,
+    ) -> Result<usize> {
+        let mut replaced = 0;
+        let mut resolve_again = vec![];
+        for (stage_id, stage) in self.stages.iter_mut() {
+            let stage_inputs = match stage {
+                ExecutionStage::UnResolved(stage) => &mut stage.inputs,
+                ExecutionStage::Resolved(stage) => &mut stage.inputs,
+                ExecutionStage::Running(stage) => &mut stage.inputs,
+                _ => continue,
+            };
+            let mut stage_replaced = 0;
+            for output in stage_inputs.values_mut() {
+                for loc in output.partition_locations.values_mut().flatten() {
+                    if loc.executor_meta.id != executor_id {
+                        continue;
+                    }
+                    if let Some(replica) = replicas.get(&loc.path) {
+                        *loc = replica.clone();
+                        stage_replaced += 1;
+                    }
+                }
+            }
+            if stage_replaced > 0 && !matches!(stage, ExecutionStage::UnResolved(_)) {
+                resolve_again.push(*stage_id);
+            }
+            replaced += stage_replaced;
+        }
+
+        for stage_id in resolve_again {
+            match self.stages.get_mut(&stage_id) {
+                Some(ExecutionStage::Resolved(stage)) => {
+                    *stage = stage.to_unresolved()?.to_resolved(&self.job_id)?;
+                }
+                Some(ExecutionStage::Running(stage)) => {
+                    stage.resolve_inputs(&self.job_id)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(replaced)
+    }
+
     /// Reset running and successful stages on a given executor
     /// This will first check the unresolved/resolved/running stages and reset the running tasks and successful tasks.
     /// Then it will check the successful stage and whether there are running parent stages need to read shuffle from it.
@@ -1151,10 +1933,12 @@ impl ExecutionGraph {
     /// Convert unresolved stage to be resolved
     pub fn resolve_stage(&mut self, stage_id: usize) -> Result<bool> {
         if let Some(ExecutionStage::UnResolved(stage)) = self.stages.remove(&stage_id) {
-            self.stages
-                .insert(stage_id, ExecutionStage::Resolved(stage.to_resolved()?));
-            Ok(true)
-        } else {
+            self.stages.insert(
+                stage_id,
+                ExecutionStage::Resolved(stage.to_resolved(&self.job_id)?),
+            );
+            Ok(true)
+        } else {
             warn!(
                 "Fail to find a unresolved stage {}/{} to resolve",
                 self.job_id(),
@@ -1205,7 +1989,7 @@ impl ExecutionGraph {
         failure_reasons: HashSet<String>,
     ) -> Result<Vec<RunningTaskInfo>> {
         if let Some(ExecutionStage::Running(stage)) = self.stages.remove(&stage_id) {
-            let running_tasks = stage
+            let mut running_tasks: Vec<RunningTaskInfo> = stage
                 .running_tasks()
                 .into_iter()
                 .map(
@@ -1222,6 +2006,19 @@ impl ExecutionGraph {
                 stage_id,
                 ExecutionStage::UnResolved(stage.to_unresolved(failure_reasons)?),
             );
+            if self.pipelined_stages {
+                // the pipelined stages reading the outputs of this stage restart as well
+                for link in stage.output_links {
+                    match self.stages.get(&link) {
+                        Some(ExecutionStage::Running(_)) => running_tasks
+                            .extend(self.rollback_running_stage(link, HashSet::new())?),
+                        Some(ExecutionStage::Resolved(_)) => {
+                            self.rollback_resolved_stage(link)?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
             Ok(running_tasks)
         } else {
             warn!(
@@ -1265,8 +2062,111 @@ impl ExecutionGraph {
         }
     }
 
-    /// fail job with error message
-    pub fn fail_job(&mut self, error: String) {
+    /// Cancel the running attempt of a task, e.g. when it is wedged on a bad executor. The
+    /// task is scheduled again in a new attempt, without counting as a task failure.
+    /// Returns the cancelled attempt to cancel on its executor, or `None` if the task is not
+    /// running.
+    pub fn cancel_task_attempt(
+        &mut self,
+        stage_id: usize,
+        partition_id: usize,
+    ) -> Option<RunningTaskInfo> {
+        let Some(ExecutionStage::Running(stage)) = self.stages.get_mut(&stage_id) else {
+            warn!(
+                "Fail to find a running stage {}/{} to cancel a task of",
+                self.job_id, stage_id
+            );
+            return None;
+        };
+        let (task_id, executor_id) = stage.cancel_task(partition_id)?;
+        info!(
+            "Cancelled task attempt TID {} {}/{}/{} on executor {}",
+            task_id, self.job_id, stage_id, partition_id, executor_id
+        );
+        Some(RunningTaskInfo {
+            task_id,
+            job_id: self.job_id.clone(),
+            stage_id,
+            partition_id,
+            executor_id,
+        })
+    }
+
+    /// Cancel the running attempt of a stage, which is retried from scratch in a new
+    /// attempt, discarding the outputs of the cancelled attempt. Returns the running tasks of
+    /// the cancelled attempt to cancel on the executors, or `None` if the stage is not
+    /// running.
+    pub fn cancel_stage_attempt(
+        &mut self,
+        stage_id: usize,
+    ) -> Result<Option<Vec<RunningTaskInfo>>> {
+        let stage = match self.stages.remove(&stage_id) {
+            Some(ExecutionStage::Running(stage)) => stage,
+            other => {
+                if let Some(other) = other {
+                    self.stages.insert(stage_id, other);
+                }
+                warn!(
+                    "Fail to find a running stage {}/{} to cancel",
+                    self.job_id, stage_id
+                );
+                return Ok(None);
+            }
+        };
+        let mut running_tasks: Vec<RunningTaskInfo> = stage
+            .running_tasks()
+            .into_iter()
+            .map(
+                |(task_id, stage_id, partition_id, executor_id)| RunningTaskInfo {
+                    task_id,
+                    job_id: self.job_id.clone(),
+                    stage_id,
+                    partition_id,
+                    executor_id,
+                },
+            )
+            .collect();
+        self.stages
+            .insert(stage_id, ExecutionStage::Resolved(stage.to_resolved()));
+
+        if stage.output_links.is_empty() {
+            self.output_locations.clear();
+        }
+        for link in stage.output_links {
+            // the pipelined stages reading the outputs of this stage restart as well
+            match self.stages.get(&link) {
+                Some(ExecutionStage::Running(_)) => running_tasks
+                    .extend(self.rollback_running_stage(link, HashSet::new())?),
+                Some(ExecutionStage::Resolved(_)) => {
+                    self.rollback_resolved_stage(link)?;
+                }
+                _ => {}
+            }
+            // the outputs published by the cancelled attempt are written again
+            if let Some(ExecutionStage::UnResolved(linked)) = self.stages.get_mut(&link) {
+                linked.inputs.insert(stage_id, StageOutput::new());
+            }
+        }
+        info!(
+            "Cancelled stage attempt {}/{}.{}, {} running tasks to cancel",
+            self.job_id,
+            stage_id,
+            stage.stage_attempt_num,
+            running_tasks.len()
+        );
+        self.revive();
+        Ok(Some(running_tasks))
+    }
+
+    /// fail job with error message, the task whose failure failed the job, if any, and the
+    /// error messages from the job failure down to the task error
+    pub fn fail_job(
+        &mut self,
+        error: String,
+        failed_task: Option<FailedJobTask>,
+        error_chain: Vec<String>,
+    ) {
+        self.end_time = timestamp_millis();
         self.status = JobStatus {
             job_id: self.job_id.clone(),
             job_name: self.job_name.clone(),
@@ -1275,6 +2175,8 @@ impl ExecutionGraph {
                 queued_at: self.queued_at,
                 started_at: self.start_time,
                 ended_at: self.end_time,
+                failed_task,
+                error_chain,
             })),
         };
     }
@@ -1288,31 +2190,143 @@ impl ExecutionGraph {
             )));
         }
 
-        let partition_location = self
-            .output_locations()
+        let output_ordering = self.output_ordering();
+        let (output_locations, truncated) =
+            self.truncate_output_locations(!output_ordering.is_empty());
+        if truncated {
+            warn!(
+                "Job {} exceeded its result limits {:?}, truncating its output to {} partitions",
+                self.job_id,
+                self.result_limits,
+                output_locations.len()
+            );
+        }
+        let partition_location = output_locations
             .into_iter()
             .map(|l| l.try_into())
             .collect::<Result<Vec<_>>>()?;
 
+        self.end_time = timestamp_millis();
         self.status = JobStatus {
             job_id: self.job_id.clone(),
             job_name: self.job_name.clone(),
             status: Some(job_status::Status::Successful(SuccessfulJob {
+                output_stats: Some(job_output_stats(&partition_location)),
                 partition_location,
 
                 queued_at: self.queued_at,
                 started_at: self.start_time,
                 ended_at: self.end_time,
+                output_ordering,
+                truncated,
+                collect_plan: self.collect_plan.clone(),
             })),
         };
-        self.end_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
 
         Ok(())
     }
 
+    /// Describe the result limit exceeded by the output of the final stage so far, if the
+    /// job fails rather than truncating its output. The output of unknown size fails the
+    /// limited jobs, as it may exceed the limits.
+    fn result_limit_error(&self) -> Option<String> {
+        if !self.result_limits.is_limited()
+            || self.result_limits.truncate
+            || matches!(self.status.status, Some(Status::Failed(_)))
+        {
+            return None;
+        }
+        match output_totals(&self.output_locations) {
+            Some((num_rows, num_bytes)) => {
+                self.result_limits.exceeded(num_rows, num_bytes)
+            }
+            None => Some(
+                "the results have output partitions of unknown size, which may exceed \
+                the result limits"
+                    .to_owned(),
+            ),
+        }
+    }
+
+    /// The output locations of the job, and whether they were truncated to the first
+    /// partitions holding the rows and bytes allowed by the result limits. The sorted
+    /// outputs are kept whole since the client needs all of them to merge the first rows,
+    /// as are the outputs of unknown size, the client truncating them to the limits.
+    fn truncate_output_locations(&self, sorted: bool) -> (Vec<PartitionLocation>, bool) {
+        if !self.result_limits.is_limited() || !self.result_limits.truncate {
+            return (self.output_locations(), false);
+        }
+        let Some((num_rows, num_bytes)) = output_totals(&self.output_locations) else {
+            return (self.output_locations(), true);
+        };
+        if self.result_limits.exceeded(num_rows, num_bytes).is_none() {
+            return (self.output_locations(), false);
+        }
+        if sorted {
+            return (self.output_locations(), true);
+        }
+
+        let mut locations = self.output_locations();
+        locations.sort_by_key(|location| {
+            (
+                location.partition_id.partition_id,
+                location.map_partition_id,
+            )
+        });
+        let (mut num_rows, mut num_bytes) = (0, 0);
+        let kept = locations
+            .into_iter()
+            .take_while(|location| {
+                let below_limits = self
+                    .result_limits
+                    .max_rows
+                    .map_or(true, |max_rows| num_rows < max_rows)
+                    && self
+                        .result_limits
+                        .max_bytes
+                        .map_or(true, |max_bytes| num_bytes < max_bytes);
+                num_rows += location.partition_stats.num_rows().unwrap_or(0);
+                num_bytes += location.partition_stats.num_bytes().unwrap_or(0);
+                below_limits
+            })
+            .collect();
+        (kept, true)
+    }
+
+    /// Sort order of the output partitions of the final stage, so that clients can merge
+    /// them. Empty if the output is not sorted or is sorted by expressions other than columns.
+    fn output_ordering(&self) -> Vec<protobuf::OutputSortColumn> {
+        let final_plan = self.stages.values().find_map(|stage| match stage {
+            ExecutionStage::Successful(stage) if stage.output_links.is_empty() => {
+                Some(stage.plan.clone())
+            }
+            _ => None,
+        });
+        // The final stage is a ShuffleWriterExec writing out each partition of its input
+        let ordering = final_plan
+            .and_then(|plan| plan.children().first().cloned())
+            .and_then(|input| input.output_ordering().map(|ordering| ordering.to_vec()));
+
+        ordering
+            .and_then(|ordering| {
+                ordering
+                    .iter()
+                    .map(|sort_expr| {
+                        sort_expr
+                            .expr
+                            .as_any()
+                            .downcast_ref::<Column>()
+                            .map(|column| protobuf::OutputSortColumn {
+                                column_index: column.index() as u32,
+                                descending: sort_expr.options.descending,
+                                nulls_first: sort_expr.options.nulls_first,
+                            })
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+            .unwrap_or_default()
+    }
+
     /// Clear the stage failure count for this stage if the stage is finally success
     fn clear_stage_failure(&mut self, stage_id: usize) {
         self.failed_stage_attempts.remove(&stage_id);
@@ -1396,6 +2410,28 @@ impl ExecutionGraph {
             output_locations,
             task_id_gen: proto.task_id_gen as usize,
             failed_stage_attempts,
+            max_running_stage_tasks: StageTaskCaps {
+                default: (proto.max_running_stage_tasks > 0)
+                    .then_some(proto.max_running_stage_tasks as usize),
+                by_stage: proto
+                    .max_running_tasks_by_stage
+                    .into_iter()
+                    .map(|(stage_id, max_tasks)| (stage_id as usize, max_tasks as usize))
+                    .collect(),
+            },
+            max_stage_tasks_per_executor: (proto.max_stage_tasks_per_executor > 0)
+                .then_some(proto.max_stage_tasks_per_executor as usize),
+            pipelined_stages: proto.pipelined_stages,
+            result_limits: proto
+                .result_limits
+                .map(|limits| ResultLimits {
+                    max_rows: (limits.max_rows > 0).then_some(limits.max_rows),
+                    max_bytes: (limits.max_bytes > 0).then_some(limits.max_bytes),
+                    truncate: limits.truncate,
+                })
+                .unwrap_or_default(),
+            collect_plan: proto.collect_plan,
+            session_props: proto.session_props,
         })
     }
 
@@ -1464,6 +2500,22 @@ impl ExecutionGraph {
             session_id: graph.session_id,
             status: Some(graph.status),
             queued_at: graph.queued_at,
+            max_running_stage_tasks: graph.max_running_stage_tasks.default.unwrap_or(0)
+                as u32,
+            max_running_tasks_by_stage: graph
+                .max_running_stage_tasks
+                .by_stage
+                .into_iter()
+                .map(|(stage_id, max_tasks)| (stage_id as u32, max_tasks as u32))
+                .collect(),
+            max_stage_tasks_per_executor: graph.max_stage_tasks_per_executor.unwrap_or(0)
+                as u32,
+            pipelined_stages: graph.pipelined_stages,
+            result_limits: Some(protobuf::ResultLimits {
+                max_rows: graph.result_limits.max_rows.unwrap_or(0),
+                max_bytes: graph.result_limits.max_bytes.unwrap_or(0),
+                truncate: graph.result_limits.truncate,
+            }),
             start_time: graph.start_time,
             end_time: graph.end_time,
             stages,
@@ -1472,6 +2524,8 @@ impl ExecutionGraph {
             scheduler_id: graph.scheduler_id.unwrap_or_default(),
             task_id_gen: graph.task_id_gen as u32,
             failed_attempts,
+            collect_plan: graph.collect_plan,
+            session_props: graph.session_props,
         })
     }
 }
@@ -1584,27 +2638,36 @@ impl ExecutionPlanVisitor for ExecutionStageBuilder {
         } else if let Some(unresolved_shuffle) =
             plan.as_any().downcast_ref::<UnresolvedShuffleExec>()
         {
-            if let Some(output_links) =
-                self.output_links.get_mut(&unresolved_shuffle.stage_id)
-            {
-                if !output_links.contains(&self.current_stage_id) {
-                    output_links.push(self.current_stage_id);
-                }
-            } else {
-                self.output_links
-                    .insert(unresolved_shuffle.stage_id, vec![self.current_stage_id]);
+            self.add_input_stage(unresolved_shuffle.stage_id);
+        } else if let Some(runtime_filter) =
+            plan.as_any().downcast_ref::<RuntimeFilterExec>()
+        {
+            // the stage waits for the stage computing the bounds of its filter
+            self.add_input_stage(runtime_filter.build_stage_id);
+        }
+        Ok(true)
+    }
+}
+
+impl ExecutionStageBuilder {
+    fn add_input_stage(&mut self, input_stage_id: usize) {
+        if let Some(output_links) = self.output_links.get_mut(&input_stage_id) {
+            if !output_links.contains(&self.current_stage_id) {
+                output_links.push(self.current_stage_id);
             }
+        } else {
+            self.output_links
+                .insert(input_stage_id, vec![self.current_stage_id]);
+        }
 
-            if let Some(deps) = self.stage_dependencies.get_mut(&self.current_stage_id) {
-                if !deps.contains(&unresolved_shuffle.stage_id) {
-                    deps.push(unresolved_shuffle.stage_id);
-                }
-            } else {
-                self.stage_dependencies
-                    .insert(self.current_stage_id, vec![unresolved_shuffle.stage_id]);
+        if let Some(deps) = self.stage_dependencies.get_mut(&self.current_stage_id) {
+            if !deps.contains(&input_stage_id) {
+                deps.push(input_stage_id);
             }
+        } else {
+            self.stage_dependencies
+                .insert(self.current_stage_id, vec![input_stage_id]);
         }
-        Ok(true)
     }
 }
 
@@ -1619,6 +2682,44 @@ pub struct TaskDescription {
     pub task_attempt: usize,
     pub data_cache: bool,
     pub plan: Arc<dyn ExecutionPlan>,
+    /// Why the task was bound to its executor
+    pub binding: TaskBinding,
+    /// Time in milliseconds the task waited for a task slot since its stage started running
+    pub queue_wait_ms: u64,
+}
+
+/// Why a task was bound to an executor, reported in the scheduling trace of its job
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskBinding {
+    /// Final stage task bound to the zone of the client of the job
+    ResultZone,
+    /// Task bound to one of the preferred hosts of its partition
+    PlacementHint,
+    /// Task bound next to the executors holding most of its input
+    InputLocality,
+    /// Task bound by the bias task distribution policy, filling the executors one by one
+    Bias,
+    /// Task bound by the round-robin task distribution policy
+    RoundRobin,
+    /// Task bound by the weighted task distribution policy, in proportion to the
+    /// available slots of the executors
+    Weighted,
+    /// Task bound by the consistent hashing of the files it scans
+    ConsistentHash,
+}
+
+impl TaskBinding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskBinding::ResultZone => "result_zone",
+            TaskBinding::PlacementHint => "placement_hint",
+            TaskBinding::InputLocality => "input_locality",
+            TaskBinding::Bias => "bias",
+            TaskBinding::RoundRobin => "round_robin",
+            TaskBinding::Weighted => "weighted",
+            TaskBinding::ConsistentHash => "consistent_hash",
+        }
+    }
 }
 
 impl Debug for TaskDescription {
@@ -1641,6 +2742,21 @@ impl Debug for TaskDescription {
 }
 
 impl TaskDescription {
+    /// The token identifying the launch of this task attempt, the same for the launches
+    /// retried by this scheduler or by the scheduler taking over the job after a failover,
+    /// for the executor to run the task attempt only once
+    pub fn launch_token(&self) -> String {
+        format!(
+            "{}/{}.{}/{}.{}/{}",
+            self.partition.job_id,
+            self.partition.stage_id,
+            self.stage_attempt_num,
+            self.partition.partition_id,
+            self.task_attempt,
+            self.task_id
+        )
+    }
+
     pub fn get_output_partition_number(&self) -> usize {
         let shuffle_writer = self
             .plan
@@ -1663,40 +2779,140 @@ fn partition_to_location(
 ) -> Vec<PartitionLocation> {
     shuffles
         .into_iter()
-        .map(|shuffle| PartitionLocation {
-            map_partition_id,
-            partition_id: PartitionId {
-                job_id: job_id.to_owned(),
-                stage_id,
-                partition_id: shuffle.partition_id as usize,
-            },
-            executor_meta: executor.clone(),
-            partition_stats: PartitionStats::new(
+        .map(|shuffle| {
+            let mut partition_stats = PartitionStats::new(
                 Some(shuffle.num_rows),
                 Some(shuffle.num_batches),
                 Some(shuffle.num_bytes),
-            ),
-            path: shuffle.path,
+            );
+            // the null counts are unknown if reported by an older executor
+            if !shuffle.null_counts.is_empty() {
+                partition_stats = partition_stats.with_null_counts(shuffle.null_counts);
+            }
+            // the bounds are only collected for the runtime filters of joins
+            if !shuffle.column_bounds.is_empty() {
+                partition_stats = partition_stats
+                    .with_column_bounds(column_bounds_from_proto(&shuffle.column_bounds));
+            }
+            PartitionLocation {
+                map_partition_id,
+                partition_id: PartitionId {
+                    job_id: job_id.to_owned(),
+                    stage_id,
+                    partition_id: shuffle.partition_id as usize,
+                },
+                executor_meta: executor.clone(),
+                partition_stats,
+                path: shuffle.path,
+            }
+        })
+        .collect()
+}
+
+/// Whether a final stage only merges, sorts, limits, filters, projects or finally
+/// aggregates the output partitions it reads, so that it can run in the client process
+fn is_read_only_plan(plan: &Arc<dyn ExecutionPlan>) -> bool {
+    let any = plan.as_any();
+    let read_only = any.is::<UnresolvedShuffleExec>()
+        || any.is::<SortPreservingMergeExec>()
+        || any.is::<CoalescePartitionsExec>()
+        || any.is::<CoalesceBatchesExec>()
+        || any.is::<GlobalLimitExec>()
+        || any.is::<LocalLimitExec>()
+        || any.is::<SortExec>()
+        || any.is::<ProjectionExec>()
+        || any.is::<FilterExec>()
+        || any
+            .downcast_ref::<AggregateExec>()
+            .is_some_and(|aggregate| {
+                matches!(
+                    aggregate.mode(),
+                    AggregateMode::Final | AggregateMode::FinalPartitioned
+                )
+            });
+    read_only && plan.children().iter().all(is_read_only_plan)
+}
+
+/// Total rows and bytes of the output partitions of a job, `None` if the statistics of
+/// any partition are unknown
+fn output_totals(locations: &[PartitionLocation]) -> Option<(u64, u64)> {
+    locations
+        .iter()
+        .try_fold((0, 0), |(num_rows, num_bytes), location| {
+            Some((
+                num_rows + location.partition_stats.num_rows()?,
+                num_bytes + location.partition_stats.num_bytes()?,
+            ))
+        })
+}
+
+/// Sum up the statistics of the output partitions of a job. Unknown statistics are skipped.
+fn job_output_stats(
+    partition_location: &[protobuf::PartitionLocation],
+) -> protobuf::JobOutputStats {
+    let total = |stat: fn(&protobuf::PartitionStats) -> i64| {
+        partition_location
+            .iter()
+            .filter_map(|location| location.partition_stats.as_ref())
+            .map(|stats| stat(stats).max(0) as u64)
+            .sum()
+    };
+    protobuf::JobOutputStats {
+        num_rows: total(|stats| stats.num_rows),
+        num_batches: total(|stats| stats.num_batches),
+        num_bytes: total(|stats| stats.num_bytes),
+    }
+}
+
+/// Maximum number of messages kept in the error chain of a failed job
+const MAX_ERROR_CHAIN_LEN: usize = 8;
+/// Maximum number of characters kept of each message in the error chain of a failed job
+const MAX_ERROR_MESSAGE_LEN: usize = 1024;
+
+/// Truncate the error chain of a failed job so that its status stays small
+fn truncate_error_chain(error_chain: Vec<String>) -> Vec<String> {
+    error_chain
+        .into_iter()
+        .take(MAX_ERROR_CHAIN_LEN)
+        .map(|message| {
+            if message.chars().count() > MAX_ERROR_MESSAGE_LEN {
+                let mut truncated: String =
+                    message.chars().take(MAX_ERROR_MESSAGE_LEN).collect();
+                truncated.push_str("...");
+                truncated
+            } else {
+                message
+            }
         })
         .collect()
 }
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
 
+    use crate::planner::{find_unresolved_shuffles, DefaultStagePlanner};
+    use crate::runtime_filter::RuntimeFilterStagePlanner;
     use crate::scheduler_server::event::QueryStageSchedulerEvent;
     use ballista_core::error::Result;
+    use ballista_core::execution_plans::BloomFilter;
     use ballista_core::serde::protobuf::{
         self, failed_task, job_status, ExecutionError, FailedTask, FetchPartitionError,
         IoError, JobStatus, TaskKilled,
     };
-
-    use crate::state::execution_graph::ExecutionGraph;
+    use ballista_core::serde::scheduler::to_proto::bloom_filters_to_proto;
+    use ballista_core::serde::scheduler::PartitionStats;
+    use ballista_core::serde::BallistaCodec;
+    use datafusion::arrow::array::{ArrayRef, Int64Array};
+    use datafusion::physical_plan::displayable;
+    use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+
+    use crate::state::execution_graph::{ExecutionGraph, ExecutionStage, ResultLimits};
     use crate::test_utils::{
-        mock_completed_task, mock_executor, mock_failed_task, test_aggregation_plan,
-        test_coalesce_plan, test_join_plan, test_two_aggregations_plan,
-        test_union_all_plan, test_union_plan,
+        datafusion_test_context, mock_completed_task, mock_executor, mock_failed_task,
+        test_aggregation_plan, test_coalesce_plan, test_join_plan,
+        test_two_aggregations_plan, test_union_all_plan, test_union_plan,
     };
 
     #[tokio::test]
@@ -1771,6 +2987,16 @@ mod test {
 
         assert_eq!(outputs.len(), agg_graph.output_partitions);
 
+        // Every mocked output partition contains a single row
+        match &status.status {
+            Some(job_status::Status::Successful(successful)) => {
+                let output_stats = successful.output_stats.as_ref().unwrap();
+                assert_eq!(output_stats.num_rows, outputs.len() as u64);
+                assert_eq!(output_stats.num_bytes, outputs.len() as u64);
+            }
+            other => panic!("Expected success status but found {other:?}"),
+        }
+
         for location in outputs {
             assert_eq!(location.executor_meta.host, "localhost2".to_owned());
         }
@@ -1778,6 +3004,125 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_finalize_output_ordering() -> Result<()> {
+        let mut agg_graph = test_aggregation_plan(4).await;
+        drain_tasks(&mut agg_graph)?;
+
+        match agg_graph.status().status {
+            Some(job_status::Status::Successful(successful)) => {
+                assert!(successful.output_ordering.is_empty());
+            }
+            other => panic!("Expected success status but found {other:?}"),
+        }
+
+        // The join plan is ordered by `id DESC NULLS LAST`
+        let mut join_graph = test_join_plan(4).await;
+        drain_tasks(&mut join_graph)?;
+
+        match join_graph.status().status {
+            Some(job_status::Status::Successful(successful)) => {
+                assert_eq!(
+                    successful.output_ordering,
+                    vec![protobuf::OutputSortColumn {
+                        column_index: 0,
+                        descending: true,
+                        nulls_first: false,
+                    }]
+                );
+            }
+            other => panic!("Expected success status but found {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_result_limits() -> Result<()> {
+        // Every mocked output partition contains a single row of a single byte
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.set_result_limits(ResultLimits {
+            max_rows: Some(2),
+            max_bytes: None,
+            truncate: false,
+        });
+        drain_tasks(&mut agg_graph)?;
+        match agg_graph.status().status.as_ref() {
+            Some(job_status::Status::Failed(failed)) => {
+                assert!(failed.error.contains("ballista.results.max_rows"));
+            }
+            other => panic!("Expected failed status but found {other:?}"),
+        }
+
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.set_result_limits(ResultLimits {
+            max_rows: Some(2),
+            max_bytes: None,
+            truncate: true,
+        });
+        drain_tasks(&mut agg_graph)?;
+        match agg_graph.status().status.as_ref() {
+            Some(job_status::Status::Successful(successful)) => {
+                assert!(successful.truncated);
+                assert_eq!(successful.partition_location.len(), 2);
+                assert_eq!(successful.output_stats.as_ref().unwrap().num_rows, 2);
+            }
+            other => panic!("Expected success status but found {other:?}"),
+        }
+
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.set_result_limits(ResultLimits {
+            max_rows: None,
+            max_bytes: Some(4),
+            truncate: false,
+        });
+        drain_tasks(&mut agg_graph)?;
+        match agg_graph.status().status.as_ref() {
+            Some(job_status::Status::Successful(successful)) => {
+                assert!(!successful.truncated);
+                assert_eq!(successful.partition_location.len(), 4);
+            }
+            other => panic!("Expected success status but found {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_result_limits_unknown_output_size() -> Result<()> {
+        for truncate in [false, true] {
+            let mut agg_graph = test_aggregation_plan(4).await;
+            agg_graph.set_result_limits(ResultLimits {
+                max_rows: Some(10),
+                max_bytes: None,
+                truncate,
+            });
+            let executor = mock_executor("executor-id1".to_string());
+            while let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+                // the statistics of the outputs decoded from a graph stored without them
+                for location in agg_graph.output_locations.iter_mut() {
+                    location.partition_stats = PartitionStats::default();
+                }
+                let task_status = mock_completed_task(task, &executor.id);
+                agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+            }
+
+            match agg_graph.status().status.as_ref() {
+                Some(job_status::Status::Failed(failed)) if !truncate => {
+                    assert!(failed.error.contains("unknown size"), "{}", failed.error);
+                }
+                Some(job_status::Status::Successful(successful)) if truncate => {
+                    // the client truncates the output to the limits
+                    assert!(successful.truncated);
+                    assert_eq!(successful.partition_location.len(), 4);
+                }
+                other => panic!("Expected truncate: {truncate} but found {other:?}"),
+            }
+        }
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_reset_completed_stage_executor_lost() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
@@ -1873,6 +3218,133 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_reset_missing_shuffle_outputs() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let mut join_graph = test_join_plan(4).await;
+        join_graph.revive();
+
+        // Complete the two leaf stages and the 4 tasks of the join stage on executor 1
+        for _ in 0..2 {
+            let task = join_graph.pop_next_task(&executor1.id)?.expect("leaf task");
+            let task_status = mock_completed_task(task, &executor1.id);
+            join_graph.update_task_status(&executor1, vec![task_status], 1, 1)?;
+        }
+        join_graph.revive();
+        assert_eq!(join_graph.available_tasks(), 4);
+        let mut join_stage_id = 0;
+        for _ in 0..4 {
+            let task = join_graph.pop_next_task(&executor1.id)?.expect("join task");
+            join_stage_id = task.partition.stage_id;
+            let task_status = mock_completed_task(task, &executor1.id);
+            join_graph.update_task_status(&executor1, vec![task_status], 1, 1)?;
+        }
+        assert_eq!(join_graph.available_tasks(), 0);
+
+        // Executor 1 holds the shuffle files of all the join tasks but the first one
+        let held = HashMap::from([(join_stage_id, HashSet::from([1, 2, 3]))]);
+        let missing = join_graph.missing_shuffle_outputs(&executor1.id, &held, u128::MAX);
+        assert_eq!(
+            missing,
+            HashMap::from([(join_stage_id, HashSet::from([0]))])
+        );
+
+        let reset = join_graph.reset_missing_shuffle_outputs(&executor1.id, &missing)?;
+
+        // The join stage runs its first task again and the final stage rolls back, while
+        // the leaf stages and the other join tasks on executor 1 are kept
+        assert_eq!(reset.0, HashSet::from([join_stage_id, join_stage_id + 1]));
+        assert_eq!(join_graph.available_tasks(), 1);
+        let task = join_graph
+            .pop_next_task(&executor1.id)?
+            .expect("reset task");
+        assert_eq!(task.partition.stage_id, join_stage_id);
+        assert_eq!(task.partition.partition_id, 0);
+        let task_status = mock_completed_task(task, &executor1.id);
+        join_graph.update_task_status(&executor1, vec![task_status], 1, 1)?;
+
+        drain_tasks(&mut join_graph)?;
+        assert!(join_graph.is_successful(), "Failed to complete join plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replace_shuffle_outputs() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut join_graph = test_join_plan(4).await;
+        join_graph.revive();
+
+        // Complete the two leaf stages on executor 1, resolving the join stage
+        for _ in 0..2 {
+            let task = join_graph.pop_next_task(&executor1.id)?.expect("leaf task");
+            let task_status = mock_completed_task(task, &executor1.id);
+            join_graph.update_task_status(&executor1, vec![task_status], 1, 1)?;
+        }
+        let outputs = join_graph.shuffle_outputs_on(&executor1.id);
+        assert!(!outputs.is_empty());
+
+        // Replicate the outputs of executor 1 to executor 2
+        let replicas: HashMap<_, _> = outputs
+            .iter()
+            .map(|output| {
+                let mut replica = output.clone();
+                replica.executor_meta = executor2.clone();
+                replica.path = format!("/replica{}", output.path);
+                (output.path.clone(), replica)
+            })
+            .collect();
+        let replaced = join_graph.replace_shuffle_outputs(&executor1.id, &replicas)?;
+        assert_eq!(replaced, outputs.len());
+        assert!(join_graph.shuffle_outputs_on(&executor1.id).is_empty());
+        assert_eq!(
+            join_graph.shuffle_outputs_on(&executor2.id).len(),
+            outputs.len()
+        );
+
+        // Losing executor 1 no longer rolls back the join stage
+        let reset = join_graph.reset_stages_on_lost_executor(&executor1.id)?;
+        assert!(reset.0.is_empty());
+        join_graph.revive();
+        assert_eq!(join_graph.available_tasks(), 4);
+
+        drain_tasks(&mut join_graph)?;
+        assert!(join_graph.is_successful(), "Failed to complete join plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_task_executor() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut join_graph = test_join_plan(4).await;
+        join_graph.revive();
+
+        let task1 = join_graph.pop_next_task(&executor1.id)?.expect("leaf task");
+        let task2 = join_graph.pop_next_task(&executor2.id)?.expect("leaf task");
+        let (stage1, task_id1) = (task1.partition.stage_id, task1.task_id);
+        let (stage2, task_id2) = (task2.partition.stage_id, task2.task_id);
+        assert_eq!(
+            join_graph.task_executor(stage1, task_id1),
+            Some(executor1.id.clone())
+        );
+
+        // the executor of a successful task is still known
+        let task_status = mock_completed_task(task2, &executor2.id);
+        join_graph.update_task_status(&executor2, vec![task_status], 1, 1)?;
+        assert_eq!(
+            join_graph.task_executor(stage2, task_id2),
+            Some(executor2.id.clone())
+        );
+
+        assert_eq!(join_graph.task_executor(stage1, task_id2), None);
+        assert_eq!(join_graph.task_executor(stage1, 100), None);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_task_update_after_reset_stage() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
@@ -1933,6 +3405,321 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_max_running_stage_tasks() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.set_max_running_stage_tasks(Some(2));
+        agg_graph.revive();
+
+        // Complete the first stage
+        if let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+            let task_status = mock_completed_task(task, &executor.id);
+            agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+
+        // Only 2 of the 4 tasks of the second stage run at once
+        let first_task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        agg_graph.pop_next_task(&executor.id)?.unwrap();
+        assert!(agg_graph.pop_next_task(&executor.id)?.is_none());
+        assert_eq!(agg_graph.available_tasks(), 2);
+
+        // A finished task makes room for the next one
+        let task_status = mock_completed_task(first_task, &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        assert!(agg_graph.pop_next_task(&executor.id)?.is_some());
+        assert!(agg_graph.pop_next_task(&executor.id)?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_running_tasks_by_stage() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.set_max_running_stage_tasks(Some(1));
+        agg_graph.set_max_running_tasks_by_stage(HashMap::from([(2, 3)]));
+        agg_graph.revive();
+
+        // Complete the first stage
+        if let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+            let task_status = mock_completed_task(task, &executor.id);
+            agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+
+        // The second stage runs 3 tasks at once rather than the default 1
+        for _ in 0..3 {
+            assert!(agg_graph.pop_next_task(&executor.id)?.is_some());
+        }
+        assert!(agg_graph.pop_next_task(&executor.id)?.is_none());
+        assert_eq!(agg_graph.available_tasks(), 1);
+
+        // The limits of the stages are stored with the graph
+        let codec: BallistaCodec<LogicalPlanNode, PhysicalPlanNode> =
+            BallistaCodec::default();
+        let proto = ExecutionGraph::encode_execution_graph(agg_graph, &codec)?;
+        assert_eq!(proto.max_running_stage_tasks, 1);
+        assert_eq!(proto.max_running_tasks_by_stage, HashMap::from([(2, 3)]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_stage_tasks_per_executor() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.set_max_stage_tasks_per_executor(Some(1));
+        agg_graph.revive();
+
+        // Complete the first stage
+        if let Some(task) = agg_graph.pop_next_task(&executor1.id)? {
+            let task_status = mock_completed_task(task, &executor1.id);
+            agg_graph.update_task_status(&executor1, vec![task_status], 1, 1)?;
+        }
+
+        // Only 1 task of the second stage runs on every executor
+        let first_task = agg_graph.pop_next_task(&executor1.id)?.unwrap();
+        assert!(agg_graph.pop_next_task(&executor1.id)?.is_none());
+        assert!(agg_graph.pop_next_task(&executor2.id)?.is_some());
+        assert!(agg_graph.pop_next_task(&executor2.id)?.is_none());
+        assert_eq!(agg_graph.available_tasks(), 2);
+
+        // A finished task still counts, as its executor holds its outputs
+        let task_status = mock_completed_task(first_task, &executor1.id);
+        agg_graph.update_task_status(&executor1, vec![task_status], 1, 1)?;
+        assert!(agg_graph.pop_next_task(&executor1.id)?.is_none());
+        let executor3 = mock_executor("executor-id3".to_string());
+        assert!(agg_graph.pop_next_task(&executor3.id)?.is_some());
+        assert!(agg_graph.pop_next_task(&executor3.id)?.is_none());
+        assert_eq!(agg_graph.available_tasks(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_stages() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_two_aggregations_plan(4).await;
+        agg_graph.set_pipelined_stages(true);
+        agg_graph.revive();
+
+        // Complete the first stage
+        if let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+            let task_status = mock_completed_task(task, &executor.id);
+            agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+        agg_graph.revive();
+
+        // Schedule all the tasks of the second stage and complete one of them
+        let mut tasks = vec![];
+        while let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+            tasks.push(task);
+        }
+        assert_eq!(tasks.len(), 4);
+        let stage_id = tasks[0].partition.stage_id;
+        let task_status = mock_completed_task(tasks.remove(0), &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+
+        // The last stage is resolved before the second stage completes
+        assert!(matches!(
+            agg_graph.stages().get(&3),
+            Some(ExecutionStage::Resolved(_))
+        ));
+        let (locations, complete) = agg_graph.shuffle_locations(stage_id, 0).unwrap();
+        assert_eq!(locations.len(), 1);
+        assert!(!complete);
+
+        // The outputs of the remaining tasks are published to the last stage
+        for task in tasks {
+            let task_status = mock_completed_task(task, &executor.id);
+            agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+        let (locations, complete) = agg_graph.shuffle_locations(stage_id, 0).unwrap();
+        assert_eq!(locations.len(), 4);
+        assert!(complete);
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rewind_waiting_stages() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_two_aggregations_plan(4).await;
+        agg_graph.set_pipelined_stages(true);
+        agg_graph.revive();
+
+        // Complete the first stage
+        if let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+            let task_status = mock_completed_task(task, &executor.id);
+            agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+        agg_graph.revive();
+
+        // Start the last stage once a task of the second stage completed
+        let mut tasks = vec![];
+        while let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+            tasks.push(task);
+        }
+        let task_status = mock_completed_task(tasks.remove(0), &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        agg_graph.revive();
+        let waiting_task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        assert_eq!(waiting_task.partition.stage_id, 3);
+        assert_eq!(agg_graph.waiting_tasks(), 1);
+
+        // The last stage releases its task slots until its inputs progress
+        let cancelled = agg_graph.rewind_waiting_stages()?;
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].stage_id, 3);
+        assert_eq!(agg_graph.waiting_tasks(), 0);
+        assert!(matches!(
+            agg_graph.stages().get(&3),
+            Some(ExecutionStage::UnResolved(_))
+        ));
+
+        for task in tasks {
+            let task_status = mock_completed_task(task, &executor.id);
+            agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detach_collect_stage() -> Result<()> {
+        let mut coalesce_graph = test_coalesce_plan(4).await;
+        assert_eq!(coalesce_graph.stage_count(), 2);
+        assert_eq!(coalesce_graph.output_partitions(), 1);
+
+        let collect_plan = coalesce_graph.detach_collect_stage().unwrap();
+        assert_eq!(
+            collect_plan
+                .properties()
+                .output_partitioning()
+                .partition_count(),
+            1
+        );
+        let shuffles = find_unresolved_shuffles(&collect_plan)?;
+        assert_eq!(shuffles.len(), 1);
+        // The stage read by the detached stage is the final stage
+        assert_eq!(coalesce_graph.stage_count(), 1);
+        assert_eq!(
+            coalesce_graph.output_partitions(),
+            shuffles[0].output_partition_count
+        );
+        coalesce_graph.set_collect_plan(vec![1, 2, 3]);
+
+        drain_tasks(&mut coalesce_graph)?;
+        coalesce_graph.succeed_job()?;
+        match &coalesce_graph.status().status {
+            Some(job_status::Status::Successful(successful)) => {
+                assert_eq!(successful.collect_plan, vec![1, 2, 3]);
+            }
+            other => panic!("Expected a successful job but found {other:?}"),
+        }
+
+        // The final stage of an aggregation has as many partitions as the stage it reads
+        let mut agg_graph = test_aggregation_plan(4).await;
+        assert!(agg_graph.detach_collect_stage().is_none());
+        assert_eq!(agg_graph.stage_count(), 2);
+
+        // The result limits apply to the output of the final stage
+        let mut coalesce_graph = test_coalesce_plan(4).await;
+        coalesce_graph.set_result_limits(ResultLimits {
+            max_rows: Some(10),
+            ..Default::default()
+        });
+        assert!(coalesce_graph.detach_collect_stage().is_none());
+
+        // The commit of a distributed write runs on the cluster
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+        let plan = session_state
+            .create_logical_plan(
+                "COPY (select l_returnflag, l_quantity from lineitem) \
+                TO '/tmp/ballista/output/' STORED AS PARQUET",
+            )
+            .await?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+        let mut copy_graph =
+            ExecutionGraph::new("localhost:50050", "job", "", "session", plan, 0)?;
+        assert_eq!(copy_graph.stage_count(), 2);
+        assert!(copy_graph.detach_collect_stage().is_none());
+        assert_eq!(copy_graph.stage_count(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bloom_filters_kept_in_memory() -> Result<()> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let plan = ctx
+            .sql(
+                "select l_shipmode, o_orderpriority from lineitem join orders \
+                on l_orderkey = o_orderkey",
+            )
+            .await?
+            .create_physical_plan()
+            .await?;
+        let mut graph = ExecutionGraph::new_with_stage_planner(
+            "localhost:50050",
+            "job",
+            "",
+            "session",
+            plan,
+            0,
+            &RuntimeFilterStagePlanner::new(&DefaultStagePlanner)
+                .with_bloom_filters(true),
+        )?;
+        graph.revive();
+
+        // stage 1 scans lineitem, the build side read by the join stage 3, and stage 2
+        // scans orders, the probe side filtered by stage 1
+        let executor = mock_executor("executor-id1".to_string());
+        let mut bloom_filter = BloomFilter::new(128);
+        bloom_filter.insert(&(Arc::new(Int64Array::from(vec![1, 2])) as ArrayRef))?;
+        let mut tasks = vec![];
+        while let Some(task) = graph.pop_next_task(&executor.id)? {
+            tasks.push(task);
+        }
+        assert!(tasks.iter().all(|task| task.partition.stage_id == 1));
+        for task in tasks {
+            let mut task_status = mock_completed_task(task, &executor.id);
+            if let Some(protobuf::task_status::Status::Successful(successful)) =
+                task_status.status.as_mut()
+            {
+                successful.partitions[0].bloom_filters =
+                    bloom_filters_to_proto([(0, &bloom_filter)]);
+            }
+            graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+
+        // the bloom filters are applied to the probe side stage, then dropped with the
+        // filters which only the join stage would have kept
+        let Some(ExecutionStage::Resolved(probe_stage)) = graph.stages().get(&2) else {
+            panic!("The probe side stage should be resolved");
+        };
+        let display = displayable(probe_stage.plan.as_ref())
+            .indent(false)
+            .to_string();
+        assert!(display.contains("BloomFilterExec"), "{display}");
+        assert!(probe_stage.inputs[&1].bloom_filters.is_empty());
+        let Some(ExecutionStage::UnResolved(join_stage)) = graph.stages().get(&3) else {
+            panic!("The join stage should wait for the probe side stage");
+        };
+        assert!(!join_stage.inputs[&1].partition_locations.is_empty());
+        assert!(join_stage.inputs[&1].bloom_filters.is_empty());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_do_not_retry_killed_task() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
@@ -2063,6 +3850,20 @@ mod test {
         assert!(failure_reason.contains("IOError"));
         assert!(!agg_graph.is_successful());
 
+        match &agg_graph.status.status {
+            Some(job_status::Status::Failed(failed)) => {
+                let failed_task = failed.failed_task.as_ref().unwrap();
+                assert_eq!(failed_task.stage_id, 2);
+                assert_eq!(failed_task.partition_id, 1);
+                assert_eq!(failed_task.executor_id, executor2.id);
+                assert_eq!(failed.error_chain.len(), 3);
+                assert_eq!(failed.error_chain[0], "Job failed due to stage 2 failed");
+                assert_eq!(failed.error_chain[2], "IOError");
+                assert!(failed.ended_at >= failed.started_at);
+            }
+            other => panic!("Expected failed status but found {other:?}"),
+        }
+
         Ok(())
     }
 
@@ -2832,6 +4633,89 @@ mod test {
     //     todo!()
     // }
 
+    #[tokio::test]
+    async fn test_cancel_task_attempt() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.revive();
+
+        let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        let stage_id = task.partition.stage_id;
+        let partition_id = task.partition.partition_id;
+        let available_tasks = agg_graph.available_tasks();
+
+        let cancelled = agg_graph
+            .cancel_task_attempt(stage_id, partition_id)
+            .unwrap();
+        assert_eq!(cancelled.task_id, task.task_id);
+        assert_eq!(cancelled.executor_id, executor.id);
+        assert_eq!(agg_graph.available_tasks(), available_tasks + 1);
+        // the task is not running anymore
+        assert!(agg_graph
+            .cancel_task_attempt(stage_id, partition_id)
+            .is_none());
+
+        // the late failure of the cancelled attempt does not fail the stage
+        let task_status = mock_failed_task(
+            task,
+            FailedTask {
+                error: "Task cancelled".to_string(),
+                retryable: false,
+                count_to_failures: false,
+                failed_reason: Some(failed_task::FailedReason::ExecutionError(
+                    ExecutionError {},
+                )),
+            },
+        );
+        let events = agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+        assert!(events.is_empty());
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stage_attempt() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.revive();
+
+        // complete the first stage and start the second one
+        let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        let task_status = mock_completed_task(task, &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+        let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        let stage_id = task.partition.stage_id;
+        let task_status = mock_completed_task(task, &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+        let running_task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        assert_eq!(agg_graph.available_tasks(), 2);
+
+        let cancelled = agg_graph.cancel_stage_attempt(stage_id)?.unwrap();
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0].task_id, running_task.task_id);
+        // the stage is retried from scratch in a new attempt
+        assert_eq!(agg_graph.available_tasks(), 4);
+        match agg_graph.stages().get(&stage_id) {
+            Some(ExecutionStage::Running(stage)) => {
+                assert_eq!(stage.stage_attempt_num, 1)
+            }
+            other => panic!("Expected a running stage but found {other:?}"),
+        }
+        assert!(agg_graph.cancel_stage_attempt(100)?.is_none());
+
+        // the late status of the cancelled attempt is ignored
+        let task_status = mock_completed_task(running_task, &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+        assert_eq!(agg_graph.available_tasks(), 4);
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+        assert_eq!(agg_graph.output_locations().len(), 4);
+        Ok(())
+    }
+
     fn drain_tasks(graph: &mut ExecutionGraph) -> Result<()> {
         let executor = mock_executor("executor-id1".to_string());
         while let Some(task) = graph.pop_next_task(&executor.id)? {
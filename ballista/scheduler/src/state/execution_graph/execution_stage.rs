@@ -45,6 +45,8 @@ use ballista_core::serde::BallistaCodec;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 
 use crate::display::DisplayableBallistaExecutionPlan;
+use crate::runtime_filter::{is_filtered_by, MapBloomFilters};
+use crate::scheduler_server::timestamp_millis;
 
 /// A stage in the ExecutionGraph,
 /// represents a set of tasks (one per each `partition`) which can be executed concurrently.
@@ -88,6 +90,17 @@ impl ExecutionStage {
         }
     }
 
+    /// Get the query plan for this query stage, shared with the stage
+    pub(crate) fn shared_plan(&self) -> Arc<dyn ExecutionPlan> {
+        match self {
+            ExecutionStage::UnResolved(stage) => stage.plan.clone(),
+            ExecutionStage::Resolved(stage) => stage.plan.clone(),
+            ExecutionStage::Running(stage) => stage.plan.clone(),
+            ExecutionStage::Successful(stage) => stage.plan.clone(),
+            ExecutionStage::Failed(stage) => stage.plan.clone(),
+        }
+    }
+
     /// Get the query plan for this query stage
     pub(crate) fn plan(&self) -> &dyn ExecutionPlan {
         match self {
@@ -98,6 +111,11 @@ impl ExecutionStage {
             ExecutionStage::Failed(stage) => stage.plan.as_ref(),
         }
     }
+
+    /// Get the number of tasks of this stage, one per partition of its input
+    pub(crate) fn task_count(&self) -> usize {
+        get_stage_partitions(self.plan())
+    }
 }
 
 /// For a stage whose input stages are not all completed, we say it's a unresolved stage
@@ -170,6 +188,50 @@ pub(crate) struct RunningStage {
     pub(crate) task_failure_numbers: Vec<usize>,
     /// Combined metrics of the already finished tasks in the stage, If it is None, no task is finished yet.
     pub(crate) stage_metrics: Option<Vec<MetricsSet>>,
+    /// IDs of the task attempts cancelled to be scheduled again, whose late statuses are ignored
+    pub(crate) cancelled_tasks: HashSet<usize>,
+    /// Time in milliseconds since the epoch at which the stage attempt started running
+    pub(crate) running_since: u64,
+    /// Time in milliseconds since the epoch at which each task was last ready to be
+    /// scheduled, when the stage attempt started running or when the task was reset.
+    /// The index of the Vec is the task's partition id.
+    pub(crate) task_ready_times: Vec<u64>,
+}
+
+/// The number of tasks of a running stage on every executor, running or holding their
+/// outputs, to bind at most a max number of tasks of the stage to the same executor,
+/// along with the executors unable to run the tasks of the stage
+#[derive(Debug, Default)]
+pub(crate) struct ExecutorTaskLimit {
+    max_tasks: Option<usize>,
+    executor_tasks: HashMap<String, usize>,
+    excluded: HashSet<String>,
+}
+
+impl ExecutorTaskLimit {
+    /// Exclude the given executors, to which no task of the stage is bound
+    pub(crate) fn excluding(mut self, executors: HashSet<String>) -> Self {
+        self.excluded.extend(executors);
+        self
+    }
+
+    /// Whether another task of the stage can be bound to the executor
+    pub(crate) fn allows(&self, executor_id: &str) -> bool {
+        !self.excluded.contains(executor_id)
+            && self.max_tasks.map_or(true, |max_tasks| {
+                self.executor_tasks.get(executor_id).copied().unwrap_or(0) < max_tasks
+            })
+    }
+
+    /// Record a task of the stage bound to the executor
+    pub(crate) fn bind(&mut self, executor_id: &str) {
+        if self.max_tasks.is_some() {
+            *self
+                .executor_tasks
+                .entry(executor_id.to_owned())
+                .or_default() += 1;
+        }
+    }
 }
 
 /// If a stage finishes successfully, its task statuses and metrics will be finalized
@@ -223,19 +285,19 @@ pub(crate) struct FailedStage {
 #[derive(Clone)]
 pub(crate) struct TaskInfo {
     /// Task ID
-    pub(super) task_id: usize,
+    pub(crate) task_id: usize,
     /// Task scheduled time
-    pub(super) scheduled_time: u128,
+    pub(crate) scheduled_time: u128,
     /// Task launch time
-    pub(super) launch_time: u128,
+    pub(crate) launch_time: u128,
     /// Start execution time
-    pub(super) start_exec_time: u128,
+    pub(crate) start_exec_time: u128,
     /// Finish execution time
-    pub(super) end_exec_time: u128,
+    pub(crate) end_exec_time: u128,
     /// Task finish time
-    pub(super) finish_time: u128,
+    pub(crate) finish_time: u128,
     /// Task Status
-    pub(super) task_status: task_status::Status,
+    pub(crate) task_status: task_status::Status,
 }
 
 impl UnresolvedStage {
@@ -278,16 +340,26 @@ impl UnresolvedStage {
         }
     }
 
-    /// Add input partitions published from an input stage.
+    /// Add input partitions published from an input stage, along with the bloom filters
+    /// of the map tasks publishing them, only kept if the stage has a runtime filter with
+    /// the outputs of the input stage.
     pub(super) fn add_input_partitions(
         &mut self,
         stage_id: usize,
         locations: Vec<PartitionLocation>,
+        bloom_filters: &MapBloomFilters,
     ) -> Result<()> {
         if let Some(stage_inputs) = self.inputs.get_mut(&stage_id) {
             for partition in locations {
                 stage_inputs.add_partition(partition);
             }
+            if !bloom_filters.is_empty() && is_filtered_by(&self.plan, stage_id) {
+                stage_inputs.bloom_filters.extend(
+                    bloom_filters.iter().map(|(map_partition, filters)| {
+                        (*map_partition, filters.clone())
+                    }),
+                );
+            }
         } else {
             return Err(BallistaError::Internal(format!("Error adding input partitions to stage {}, {} is not a valid child stage ID", self.stage_id, stage_id)));
         }
@@ -317,6 +389,9 @@ impl UnresolvedStage {
 
                     locs.retain(|loc| loc.executor_meta.id != executor_id);
                 });
+            stage_output
+                .bloom_filters
+                .retain(|map_partition, _| !bad_map_partitions.contains(map_partition));
             stage_output.complete = false;
             Ok(bad_map_partitions)
         } else {
@@ -337,16 +412,46 @@ impl UnresolvedStage {
         self.inputs.iter().all(|(_, input)| input.is_complete())
     }
 
-    /// Change to the resolved state
-    pub(super) fn to_resolved(&self) -> Result<ResolvedStage> {
+    /// Returns true if every input is complete or is one of the `started_inputs`, whose
+    /// outputs can be consumed while they are written
+    pub(super) fn pipelinable(&self, started_inputs: &HashSet<usize>) -> bool {
+        self.inputs.iter().all(|(stage_id, input)| {
+            input.is_complete() || started_inputs.contains(stage_id)
+        })
+    }
+
+    /// Change to the resolved state. The inputs which are not complete yet, if the stage
+    /// is pipelined, are read by polling the scheduler for the locations of job `job_id`.
+    /// The bloom filters of the inputs are dropped once applied by the runtime filters.
+    pub(super) fn to_resolved(&self, job_id: &str) -> Result<ResolvedStage> {
         let input_locations = self
             .inputs
             .iter()
             .map(|(stage, input)| (*stage, input.partition_locations.clone()))
             .collect();
+        let incomplete_inputs = self
+            .inputs
+            .iter()
+            .filter(|(_, input)| !input.is_complete())
+            .map(|(stage, _)| *stage)
+            .collect();
         let plan = crate::planner::remove_unresolved_shuffles(
             self.plan.clone(),
             &input_locations,
+            job_id,
+            &incomplete_inputs,
+        )?;
+        let bloom_filters = self
+            .inputs
+            .iter()
+            .filter(|(_, input)| !input.bloom_filters.is_empty())
+            .map(|(stage, input)| (*stage, input.bloom_filters.clone()))
+            .collect();
+        let plan = crate::runtime_filter::resolve_runtime_filters(
+            plan,
+            &input_locations,
+            &bloom_filters,
+            &incomplete_inputs,
         )?;
 
         // Optimize join order and statistics based on new resolved statistics
@@ -357,12 +462,16 @@ impl UnresolvedStage {
         let plan =
             optimize_aggregate.optimize(plan, SessionConfig::default().options())?;
 
+        let mut inputs = self.inputs.clone();
+        for input in inputs.values_mut() {
+            input.bloom_filters.clear();
+        }
         Ok(ResolvedStage::new(
             self.stage_id,
             self.stage_attempt_num,
             plan,
             self.output_links.clone(),
-            self.inputs.clone(),
+            inputs,
             self.last_attempt_failure_reasons.clone(),
         ))
     }
@@ -441,7 +550,7 @@ impl ResolvedStage {
         inputs: HashMap<usize, StageOutput>,
         last_attempt_failure_reasons: HashSet<String>,
     ) -> Self {
-        let partitions = get_stage_partitions(plan.clone());
+        let partitions = get_stage_partitions(plan.as_ref());
 
         Self {
             stage_id,
@@ -545,6 +654,12 @@ impl Debug for ResolvedStage {
 }
 
 impl RunningStage {
+    /// Time in milliseconds the task of the given partition bound now waited for a task
+    /// slot since it was ready to be scheduled
+    pub(crate) fn queue_wait_ms(&self, partition_id: usize) -> u64 {
+        timestamp_millis().saturating_sub(self.task_ready_times[partition_id])
+    }
+
     pub(super) fn new(
         stage_id: usize,
         stage_attempt_num: usize,
@@ -553,6 +668,7 @@ impl RunningStage {
         output_links: Vec<usize>,
         inputs: HashMap<usize, StageOutput>,
     ) -> Self {
+        let now = timestamp_millis();
         Self {
             stage_id,
             stage_attempt_num,
@@ -563,6 +679,9 @@ impl RunningStage {
             task_infos: vec![None; partitions],
             task_failure_numbers: vec![0; partitions],
             stage_metrics: None,
+            cancelled_tasks: HashSet::new(),
+            running_since: now,
+            task_ready_times: vec![now; partitions],
         }
     }
 
@@ -609,6 +728,22 @@ impl RunningStage {
         }
     }
 
+    /// Resolve the plan again with the current input locations, e.g. after the shuffle
+    /// partitions read by the stage were replicated to other executors, so that the tasks
+    /// launched from now on read the replicas
+    pub(super) fn resolve_inputs(&mut self, job_id: &str) -> Result<()> {
+        let unresolved = UnresolvedStage::new_with_inputs(
+            self.stage_id,
+            self.stage_attempt_num,
+            crate::planner::rollback_resolved_shuffles(self.plan.clone())?,
+            self.output_links.clone(),
+            self.inputs.clone(),
+            HashSet::new(),
+        );
+        self.plan = unresolved.to_resolved(job_id)?.plan;
+        Ok(())
+    }
+
     /// Change to the resolved state and bump the stage attempt number
     pub(super) fn to_resolved(&self) -> ResolvedStage {
         ResolvedStage::new(
@@ -673,6 +808,11 @@ impl RunningStage {
         self.task_infos.iter().filter(|s| s.is_some()).count()
     }
 
+    /// Whether the stage started before all its inputs completed, as a pipelined stage
+    pub(super) fn waits_for_inputs(&self) -> bool {
+        self.inputs.values().any(|input| !input.is_complete())
+    }
+
     /// Returns a vector of currently running tasks in this stage
     pub(super) fn running_tasks(&self) -> Vec<(usize, usize, usize, String)> {
         self.task_infos
@@ -695,6 +835,58 @@ impl RunningStage {
         self.task_infos.iter().filter(|s| s.is_none()).count()
     }
 
+    /// Returns the number of available tasks which can be scheduled now without this stage
+    /// running more than `max_running_tasks` tasks at once.
+    pub(crate) fn schedulable_tasks(&self, max_running_tasks: Option<usize>) -> usize {
+        let available_tasks = self.available_tasks();
+        match max_running_tasks {
+            Some(max_running_tasks) => {
+                let running_tasks = self
+                    .task_infos
+                    .iter()
+                    .filter(|info| {
+                        matches!(
+                            info,
+                            Some(TaskInfo {
+                                task_status: task_status::Status::Running(_),
+                                ..
+                            })
+                        )
+                    })
+                    .count();
+                available_tasks.min(max_running_tasks.saturating_sub(running_tasks))
+            }
+            None => available_tasks,
+        }
+    }
+
+    /// The limit of the tasks of this stage on the same executor, given the tasks of the
+    /// stage running on every executor and the successful tasks whose outputs it holds,
+    /// unlimited if `max_tasks_per_executor` is `None`
+    pub(crate) fn executor_task_limit(
+        &self,
+        max_tasks_per_executor: Option<usize>,
+    ) -> ExecutorTaskLimit {
+        let mut executor_tasks = HashMap::new();
+        if max_tasks_per_executor.is_some() {
+            for task_info in self.task_infos.iter().flatten() {
+                let executor_id = match &task_info.task_status {
+                    task_status::Status::Running(running) => &running.executor_id,
+                    task_status::Status::Successful(successful) => {
+                        &successful.executor_id
+                    }
+                    _ => continue,
+                };
+                *executor_tasks.entry(executor_id.clone()).or_default() += 1;
+            }
+        }
+        ExecutorTaskLimit {
+            max_tasks: max_tasks_per_executor,
+            executor_tasks,
+            excluded: HashSet::new(),
+        }
+    }
+
     /// Update the TaskInfo for task partition
     pub(super) fn update_task_info(
         &mut self,
@@ -702,14 +894,22 @@ impl RunningStage {
         status: TaskStatus,
     ) -> bool {
         debug!("Updating TaskInfo for partition {}", partition_id);
-        let task_info = self.task_infos[partition_id].as_ref().unwrap();
-        let task_id = task_info.task_id;
+        // the task info is missing when the status is replayed on a persisted graph which
+        // was saved before the task was launched
+        let (task_id, scheduled_time) = match self.task_infos[partition_id].as_ref() {
+            Some(task_info) => (task_info.task_id, task_info.scheduled_time),
+            None => (status.task_id as usize, status.launch_time as u128),
+        };
+        if self.cancelled_tasks.contains(&(status.task_id as usize)) {
+            warn!("Ignore TaskStatus update with TID {} because the task attempt was cancelled for partition {}",
+                status.task_id, partition_id);
+            return false;
+        }
         if (status.task_id as usize) < task_id {
             warn!("Ignore TaskStatus update with TID {} because there is more recent task attempt with TID {} running for partition {}",
                 status.task_id, task_id, partition_id);
             return false;
         }
-        let scheduled_time = task_info.scheduled_time;
         let task_status = status.status.unwrap();
         let updated_task_info = TaskInfo {
             task_id,
@@ -740,12 +940,14 @@ impl RunningStage {
     pub(super) fn update_task_metrics(
         &mut self,
         partition: usize,
-        metrics: Vec<OperatorMetricsSet>,
+        mut metrics: Vec<OperatorMetricsSet>,
     ) -> Result<()> {
         // For some cases, task metrics not set, especially for testings.
         if metrics.is_empty() {
             return Ok(());
         }
+        // the sets of older executors, without operator IDs, are already ordered
+        metrics.sort_by_key(|ms| ms.operator_id);
 
         let new_metrics_set = if let Some(combined_metrics) = &mut self.stage_metrics {
             if metrics.len() != combined_metrics.len() {
@@ -755,12 +957,14 @@ impl RunningStage {
             let metrics_values_array = metrics
                 .into_iter()
                 .map(|ms| {
+                    // the metrics which can't be interpreted, e.g. of a newer version,
+                    // are skipped
                     ms.metrics
                         .into_iter()
-                        .map(|m| m.try_into())
-                        .collect::<Result<Vec<_>>>()
+                        .filter_map(|m| m.try_into().ok())
+                        .collect::<Vec<MetricValue>>()
                 })
-                .collect::<Result<Vec<_>>>()?;
+                .collect::<Vec<_>>();
 
             combined_metrics
                 .iter_mut()
@@ -801,19 +1005,68 @@ impl RunningStage {
     /// re-scheduled.
     pub fn reset_task_info(&mut self, partition_id: usize) {
         self.task_infos[partition_id] = None;
+        self.task_ready_times[partition_id] = timestamp_millis();
+    }
+
+    /// Cancel the running task attempt of the given task partition, so that the task is
+    /// scheduled again. Returns the task ID and executor ID of the cancelled attempt, or
+    /// `None` if the task is not running.
+    pub(super) fn cancel_task(&mut self, partition_id: usize) -> Option<(usize, String)> {
+        match self.task_infos.get(partition_id) {
+            Some(Some(TaskInfo {
+                task_id,
+                task_status: task_status::Status::Running(RunningTask { executor_id }),
+                ..
+            })) => {
+                let cancelled = (*task_id, executor_id.clone());
+                self.cancelled_tasks.insert(*task_id);
+                self.reset_task_info(partition_id);
+                Some(cancelled)
+            }
+            _ => None,
+        }
     }
 
     /// Reset the running and completed tasks on a given executor
     /// Returns the number of running tasks that were reset
     pub fn reset_tasks(&mut self, executor: &str) -> usize {
+        self.reset_tasks_of(executor, |_| true)
+    }
+
+    /// Reset the running and completed tasks of the given map partitions on a given
+    /// executor, e.g. when their shuffle outputs went missing.
+    /// Returns the number of tasks that were reset
+    pub fn reset_map_partitions(
+        &mut self,
+        executor: &str,
+        map_partitions: &HashSet<usize>,
+    ) -> usize {
+        self.reset_tasks_of(executor, |partition| map_partitions.contains(&partition))
+    }
+
+    fn reset_tasks_of(
+        &mut self,
+        executor: &str,
+        reset_partition: impl Fn(usize) -> bool,
+    ) -> usize {
         let mut reset = 0;
-        for task in self.task_infos.iter_mut() {
+        let now = timestamp_millis();
+        for (partition, (task, ready_time)) in self
+            .task_infos
+            .iter_mut()
+            .zip(self.task_ready_times.iter_mut())
+            .enumerate()
+        {
+            if !reset_partition(partition) {
+                continue;
+            }
             match task {
                 Some(TaskInfo {
                     task_status: task_status::Status::Running(RunningTask { executor_id }),
                     ..
                 }) if *executor == *executor_id => {
                     *task = None;
+                    *ready_time = now;
                     reset += 1;
                 }
                 Some(TaskInfo {
@@ -825,6 +1078,7 @@ impl RunningStage {
                     ..
                 }) if *executor == *executor_id => {
                     *task = None;
+                    *ready_time = now;
                     reset += 1;
                 }
                 _ => {}
@@ -899,6 +1153,7 @@ impl SuccessfulStage {
         } else {
             Some(self.stage_metrics.clone())
         };
+        let now = timestamp_millis();
         RunningStage {
             stage_id: self.stage_id,
             stage_attempt_num: self.stage_attempt_num + 1,
@@ -910,15 +1165,45 @@ impl SuccessfulStage {
             // It is Ok to forget the previous task failure attempts
             task_failure_numbers: vec![0; self.partitions],
             stage_metrics,
+            cancelled_tasks: HashSet::new(),
+            running_since: now,
+            task_ready_times: vec![now; self.partitions],
         }
     }
 
     /// Reset the successful tasks on a given executor
     /// Returns the number of running tasks that were reset
     pub fn reset_tasks(&mut self, executor: &str) -> usize {
-        let mut reset = 0;
         let failure_reason = format!("Task failure due to Executor {executor} lost");
-        for task in self.task_infos.iter_mut() {
+        self.reset_tasks_of(executor, &failure_reason, |_| true)
+    }
+
+    /// Reset the successful tasks of the given map partitions on a given executor, whose
+    /// shuffle outputs went missing.
+    /// Returns the number of tasks that were reset
+    pub fn reset_map_partitions(
+        &mut self,
+        executor: &str,
+        map_partitions: &HashSet<usize>,
+    ) -> usize {
+        let failure_reason =
+            format!("Task failure due to shuffle output missing on Executor {executor}");
+        self.reset_tasks_of(executor, &failure_reason, |partition| {
+            map_partitions.contains(&partition)
+        })
+    }
+
+    fn reset_tasks_of(
+        &mut self,
+        executor: &str,
+        failure_reason: &str,
+        reset_partition: impl Fn(usize) -> bool,
+    ) -> usize {
+        let mut reset = 0;
+        for (partition, task) in self.task_infos.iter_mut().enumerate() {
+            if !reset_partition(partition) {
+                continue;
+            }
             match task {
                 TaskInfo {
                     task_id,
@@ -937,7 +1222,7 @@ impl SuccessfulStage {
                         end_exec_time: 0,
                         finish_time: 0,
                         task_status: task_status::Status::Failed(FailedTask {
-                            error: failure_reason.clone(),
+                            error: failure_reason.to_owned(),
                             retryable: true,
                             count_to_failures: false,
                             failed_reason: Some(FailedReason::ResultLost(ResultLost {})),
@@ -1172,7 +1457,7 @@ impl Debug for FailedStage {
 /// Get the total number of partitions for a stage with plan.
 /// Only for [`ShuffleWriterExec`], the input partition count and the output partition count
 /// will be different. Here, we should use the input partition count.
-fn get_stage_partitions(plan: Arc<dyn ExecutionPlan>) -> usize {
+fn get_stage_partitions(plan: &dyn ExecutionPlan) -> usize {
     plan.as_any()
         .downcast_ref::<ShuffleWriterExec>()
         .map(|shuffle_writer| shuffle_writer.input_partition_count())
@@ -1189,6 +1474,10 @@ pub(crate) struct StageOutput {
     pub partition_locations: HashMap<usize, Vec<PartitionLocation>>,
     /// Flag indicating whether all tasks are complete
     pub complete: bool,
+    /// Bloom filters of the outputs of the map tasks, for the runtime filter of the stage
+    /// reading them. Only kept in memory until the stage is resolved, they are neither
+    /// encoded with the graph nor part of the partition locations.
+    pub bloom_filters: MapBloomFilters,
 }
 
 impl StageOutput {
@@ -1196,6 +1485,7 @@ impl StageOutput {
         Self {
             partition_locations: HashMap::new(),
             complete: false,
+            bloom_filters: HashMap::new(),
         }
     }
 
@@ -1245,6 +1535,7 @@ fn decode_inputs(
             StageOutput {
                 partition_locations: outputs,
                 complete: input.complete,
+                bloom_filters: HashMap::new(),
             },
         );
     }
@@ -1321,3 +1612,65 @@ fn encode_taskinfo(task_info: TaskInfo, partition_id: usize) -> protobuf::TaskIn
         status: Some(task_info_status),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use ballista_core::serde::protobuf::{
+        operator_metric, NamedCount, OperatorMetric, OperatorMetricsSet,
+    };
+    use datafusion::arrow::datatypes::Schema;
+    use datafusion::physical_plan::empty::EmptyExec;
+
+    use super::RunningStage;
+
+    fn operator_metrics(operator_id: u32, name: &str, value: u64) -> OperatorMetricsSet {
+        OperatorMetricsSet {
+            metrics: vec![
+                OperatorMetric {
+                    metric: Some(operator_metric::Metric::Count(NamedCount {
+                        name: name.to_owned(),
+                        value,
+                    })),
+                    ..Default::default()
+                },
+                // a metric of a newer version which can't be interpreted
+                OperatorMetric::default(),
+            ],
+            operator_id,
+            version: 1,
+        }
+    }
+
+    #[test]
+    fn test_update_task_metrics_by_operator_id() {
+        let plan = Arc::new(EmptyExec::new(Arc::new(Schema::empty())));
+        let mut stage = RunningStage::new(1, 0, plan, 2, vec![], HashMap::new());
+        stage
+            .update_task_metrics(
+                0,
+                vec![operator_metrics(1, "b", 1), operator_metrics(0, "a", 2)],
+            )
+            .unwrap();
+        stage
+            .update_task_metrics(
+                1,
+                vec![operator_metrics(0, "a", 3), operator_metrics(1, "b", 4)],
+            )
+            .unwrap();
+
+        // the metrics of every operator are combined whatever the order of the sets
+        let stage_metrics = stage.stage_metrics.as_ref().unwrap();
+        assert_eq!(
+            stage_metrics[0].sum_by_name("a").map(|v| v.as_usize()),
+            Some(5)
+        );
+        assert_eq!(
+            stage_metrics[1].sum_by_name("b").map(|v| v.as_usize()),
+            Some(5)
+        );
+        assert_eq!(stage_metrics[1].iter().count(), 1);
+    }
+}
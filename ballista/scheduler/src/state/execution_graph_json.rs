@@ -0,0 +1,277 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Utilities for producing structured descriptions of execution graphs, to be exported as JSON
+
+use crate::state::execution_graph::{ExecutionGraph, ExecutionStage, TaskInfo};
+use ballista_core::serde::protobuf::{job_status, task_status};
+use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::ExecutionPlan;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Description of an execution graph: its stages, the dependencies between them, the states
+/// of their tasks and their metrics
+#[derive(Debug, Serialize)]
+pub struct ExecutionGraphDescription {
+    pub job_id: String,
+    pub job_name: String,
+    pub session_id: String,
+    pub status: String,
+    pub start_time: u64,
+    pub end_time: u64,
+    /// Stages sorted by stage ID
+    pub stages: Vec<StageDescription>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StageDescription {
+    pub stage_id: usize,
+    pub stage_attempt_num: usize,
+    pub status: String,
+    /// Number of partitions, unknown until the stage is resolved
+    pub partitions: Option<usize>,
+    /// Stages whose outputs are the inputs of this stage
+    pub input_stages: Vec<usize>,
+    /// Stages taking the outputs of this stage as inputs, empty for the final stage
+    pub output_links: Vec<usize>,
+    pub plan: String,
+    /// One task per partition, for stages which are running or finished
+    pub tasks: Vec<TaskStateDescription>,
+    /// Combined metrics of the finished tasks, for each operator with metrics
+    pub metrics: Vec<Vec<MetricDescription>>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskStateDescription {
+    pub partition_id: usize,
+    pub task_id: Option<usize>,
+    pub status: String,
+    pub executor_id: Option<String>,
+    pub launch_time: Option<u64>,
+    pub start_exec_time: Option<u64>,
+    pub end_exec_time: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricDescription {
+    pub name: String,
+    pub value: usize,
+    pub labels: HashMap<String, String>,
+}
+
+impl From<&ExecutionGraph> for ExecutionGraphDescription {
+    fn from(graph: &ExecutionGraph) -> Self {
+        let status = match &graph.status().status {
+            Some(job_status::Status::Queued(_)) => "Queued",
+            Some(job_status::Status::Planning(_)) => "Planning",
+            Some(job_status::Status::Running(_)) => "Running",
+            Some(job_status::Status::Failed(_)) => "Failed",
+            Some(job_status::Status::Successful(_)) => "Successful",
+            None => "Unknown",
+        };
+        let mut stages: Vec<StageDescription> =
+            graph.stages().values().map(describe_stage).collect();
+        stages.sort_by_key(|stage| stage.stage_id);
+
+        Self {
+            job_id: graph.job_id().to_string(),
+            job_name: graph.job_name().to_string(),
+            session_id: graph.session_id().to_string(),
+            status: status.to_string(),
+            start_time: graph.start_time(),
+            end_time: graph.end_time(),
+            stages,
+        }
+    }
+}
+
+fn describe_stage(stage: &ExecutionStage) -> StageDescription {
+    let mut description = StageDescription {
+        stage_id: 0,
+        stage_attempt_num: 0,
+        status: stage.variant_name().to_string(),
+        partitions: None,
+        input_stages: vec![],
+        output_links: vec![],
+        plan: describe_plan(stage.plan()),
+        tasks: vec![],
+        metrics: vec![],
+        error_message: None,
+    };
+    match stage {
+        ExecutionStage::UnResolved(stage) => {
+            description.stage_id = stage.stage_id;
+            description.stage_attempt_num = stage.stage_attempt_num;
+            description.input_stages = stage.inputs.keys().cloned().collect();
+            description.output_links = stage.output_links.clone();
+        }
+        ExecutionStage::Resolved(stage) => {
+            description.stage_id = stage.stage_id;
+            description.stage_attempt_num = stage.stage_attempt_num;
+            description.partitions = Some(stage.partitions);
+            description.input_stages = stage.inputs.keys().cloned().collect();
+            description.output_links = stage.output_links.clone();
+        }
+        ExecutionStage::Running(stage) => {
+            description.stage_id = stage.stage_id;
+            description.stage_attempt_num = stage.stage_attempt_num;
+            description.partitions = Some(stage.partitions);
+            description.input_stages = stage.inputs.keys().cloned().collect();
+            description.output_links = stage.output_links.clone();
+            description.tasks =
+                describe_tasks(stage.task_infos.iter().map(Option::as_ref));
+            description.metrics = describe_metrics(stage.stage_metrics.as_deref());
+        }
+        ExecutionStage::Successful(stage) => {
+            description.stage_id = stage.stage_id;
+            description.stage_attempt_num = stage.stage_attempt_num;
+            description.partitions = Some(stage.partitions);
+            description.input_stages = stage.inputs.keys().cloned().collect();
+            description.output_links = stage.output_links.clone();
+            description.tasks = describe_tasks(stage.task_infos.iter().map(Some));
+            description.metrics = describe_metrics(Some(&stage.stage_metrics));
+        }
+        ExecutionStage::Failed(stage) => {
+            description.stage_id = stage.stage_id;
+            description.stage_attempt_num = stage.stage_attempt_num;
+            description.partitions = Some(stage.partitions);
+            description.output_links = stage.output_links.clone();
+            description.tasks =
+                describe_tasks(stage.task_infos.iter().map(Option::as_ref));
+            description.metrics = describe_metrics(stage.stage_metrics.as_deref());
+            description.error_message = Some(stage.error_message.clone());
+        }
+    }
+    description.input_stages.sort();
+    description
+}
+
+fn describe_plan(plan: &dyn ExecutionPlan) -> String {
+    DisplayableExecutionPlan::new(plan)
+        .indent(false)
+        .to_string()
+}
+
+fn describe_tasks<'a>(
+    task_infos: impl Iterator<Item = Option<&'a TaskInfo>>,
+) -> Vec<TaskStateDescription> {
+    task_infos
+        .enumerate()
+        .map(|(partition_id, task_info)| match task_info {
+            Some(task_info) => {
+                let (status, executor_id) = match &task_info.task_status {
+                    task_status::Status::Running(running) => {
+                        ("Running", Some(running.executor_id.clone()))
+                    }
+                    task_status::Status::Successful(successful) => {
+                        ("Successful", Some(successful.executor_id.clone()))
+                    }
+                    task_status::Status::Failed(_) => ("Failed", None),
+                };
+                // Times are zero until they are known
+                let time = |millis: u128| (millis > 0).then_some(millis as u64);
+                TaskStateDescription {
+                    partition_id,
+                    task_id: Some(task_info.task_id),
+                    status: status.to_string(),
+                    executor_id,
+                    launch_time: time(task_info.launch_time),
+                    start_exec_time: time(task_info.start_exec_time),
+                    end_exec_time: time(task_info.end_exec_time),
+                }
+            }
+            None => TaskStateDescription {
+                partition_id,
+                task_id: None,
+                status: "Pending".to_string(),
+                executor_id: None,
+                launch_time: None,
+                start_exec_time: None,
+                end_exec_time: None,
+            },
+        })
+        .collect()
+}
+
+fn describe_metrics(stage_metrics: Option<&[MetricsSet]>) -> Vec<Vec<MetricDescription>> {
+    stage_metrics
+        .unwrap_or_default()
+        .iter()
+        .map(|metrics_set| {
+            metrics_set
+                .iter()
+                .map(|metric| MetricDescription {
+                    name: metric.value().name().to_string(),
+                    value: metric.value().as_usize(),
+                    labels: metric
+                        .labels()
+                        .iter()
+                        .map(|label| {
+                            (label.name().to_string(), label.value().to_string())
+                        })
+                        .collect(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{mock_completed_task, mock_executor, test_aggregation_plan};
+    use ballista_core::error::Result;
+
+    #[tokio::test]
+    async fn test_describe_execution_graph() -> Result<()> {
+        let mut graph = test_aggregation_plan(4).await;
+        let executor = mock_executor("executor-1".to_string());
+        if let Some(task) = graph.pop_next_task(&executor.id)? {
+            let task_status = mock_completed_task(task, &executor.id);
+            graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+
+        graph.revive();
+
+        let description = ExecutionGraphDescription::from(&graph);
+        assert_eq!(description.status, "Running");
+        assert_eq!(description.stages.len(), 2);
+
+        let first = &description.stages[0];
+        assert_eq!(first.stage_id, 1);
+        assert_eq!(first.status, "Successful");
+        assert_eq!(first.output_links, vec![2]);
+        assert_eq!(first.tasks.len(), 1);
+        assert_eq!(first.tasks[0].status, "Successful");
+        assert_eq!(first.tasks[0].executor_id.as_deref(), Some("executor-1"));
+
+        let second = &description.stages[1];
+        assert_eq!(second.status, "Running");
+        assert_eq!(second.partitions, Some(4));
+        assert_eq!(second.input_stages, vec![1]);
+        assert!(second.output_links.is_empty());
+        assert_eq!(second.tasks.len(), 4);
+        assert!(second.tasks.iter().all(|task| task.status == "Pending"));
+
+        let json = serde_json::to_value(&description).unwrap();
+        assert_eq!(json["stages"][0]["tasks"][0]["status"], "Successful");
+        Ok(())
+    }
+}
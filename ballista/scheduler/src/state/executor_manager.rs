@@ -21,15 +21,18 @@ use ballista_core::error::BallistaError;
 use ballista_core::error::Result;
 use ballista_core::serde::protobuf;
 
-use crate::cluster::{BoundTask, ClusterState, ExecutorSlot};
-use crate::config::SchedulerConfig;
+use crate::cluster::{
+    BoundTask, ClusterState, ExecutorExpirationStream, ExecutorJobLimit, ExecutorSlot,
+};
+use crate::config::{SchedulerConfig, TaskDistributionPolicy};
 
 use crate::state::execution_graph::RunningTaskInfo;
 use crate::state::task_manager::JobInfoCache;
 use ballista_core::serde::protobuf::executor_grpc_client::ExecutorGrpcClient;
 use ballista_core::serde::protobuf::{
-    executor_status, CancelTasksParams, ExecutorHeartbeat, MultiTaskDefinition,
-    RemoveJobDataParams, StopExecutorParams,
+    executor_metric, executor_status, CancelTasksParams, DiskIoMetrics,
+    ExecutorHeartbeat, GetTaskLogsParams, GetTaskLogsResult, MultiTaskDefinition,
+    RemoveJobDataParams, StopExecutorParams, UpdateJobSchedulerParams,
 };
 use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
 use ballista_core::utils::{create_grpc_client_connection, get_time_before};
@@ -66,6 +69,27 @@ impl ExecutorManager {
         Ok(())
     }
 
+    /// Extend the expiry of the slots held by this scheduler
+    pub async fn renew_slot_reservation(&self) -> Result<()> {
+        self.cluster_state.renew_slot_reservation().await
+    }
+
+    /// Persist the cluster state buffered in memory before the scheduler exits
+    pub async fn shutdown(&self) -> Result<()> {
+        self.cluster_state.shutdown().await
+    }
+
+    /// Return the slots of schedulers whose reservation expired to the available task slots
+    pub async fn reclaim_expired_slot_reservations(&self) -> Result<u32> {
+        self.cluster_state.reclaim_expired_slot_reservations().await
+    }
+
+    /// Return a stream of executors whose liveness lease expired, if the cluster state
+    /// supports leases
+    pub async fn executor_expirations(&self) -> Result<Option<ExecutorExpirationStream>> {
+        self.cluster_state.executor_expirations().await
+    }
+
     /// Bind the ready to running tasks from [`active_jobs`] with available executors.
     ///
     /// If `executors` is provided, only bind slots from the specified executor IDs
@@ -77,17 +101,240 @@ impl ExecutorManager {
             warn!("There's no active jobs for binding tasks");
             return Ok(vec![]);
         }
-        let alive_executors = self.get_alive_executors();
+        let alive_executors: HashSet<String> = self
+            .select_executors(self.get_alive_executors())
+            .await
+            .into_iter()
+            .filter(|executor_id| !self.is_memory_saturated(executor_id))
+            .collect();
         if alive_executors.is_empty() {
             warn!("There's no alive executors for binding tasks");
             return Ok(vec![]);
         }
+        if self.config.max_jobs_per_executor > 0 {
+            let (executor_jobs, jobs) = self.executor_running_jobs(&active_jobs).await;
+            let job_limit = ExecutorJobLimit::new(
+                self.config.max_jobs_per_executor as usize,
+                executor_jobs,
+                jobs,
+            );
+            return self
+                .cluster_state
+                .bind_schedulable_tasks(
+                    self.config.task_distribution,
+                    active_jobs,
+                    Some(alive_executors),
+                    self.config.task_locality_label.as_deref(),
+                    Some(job_limit),
+                )
+                .await;
+        }
+        let mut bound_tasks = vec![];
+        for (distribution, jobs) in self.jobs_by_task_distribution(active_jobs) {
+            bound_tasks.extend(
+                self.cluster_state
+                    .bind_schedulable_tasks(
+                        distribution,
+                        jobs,
+                        Some(alive_executors.clone()),
+                        self.config.task_locality_label.as_deref(),
+                        None,
+                    )
+                    .await?,
+            );
+        }
+        Ok(bound_tasks)
+    }
+
+    /// The task distribution policy of a job, the one requested by its session or else the
+    /// one of the scheduler
+    fn task_distribution(&self, job: &JobInfoCache) -> TaskDistributionPolicy {
+        job.task_distribution
+            .unwrap_or(self.config.task_distribution)
+    }
+
+    /// Group the active jobs by task distribution policy, the jobs distributed with the
+    /// policy of the scheduler being bound first
+    pub(crate) fn jobs_by_task_distribution(
+        &self,
+        active_jobs: Arc<HashMap<String, JobInfoCache>>,
+    ) -> Vec<(TaskDistributionPolicy, Arc<HashMap<String, JobInfoCache>>)> {
+        if active_jobs
+            .values()
+            .all(|job| job.task_distribution.is_none())
+        {
+            return vec![(self.config.task_distribution, active_jobs)];
+        }
+        let mut groups = vec![(self.config.task_distribution, HashMap::new())];
+        for (job_id, job) in active_jobs.iter() {
+            let distribution = self.task_distribution(job);
+            match groups
+                .iter_mut()
+                .find(|(policy, _)| *policy == distribution)
+            {
+                Some((_, jobs)) => {
+                    jobs.insert(job_id.clone(), job.clone());
+                }
+                None => groups
+                    .push((distribution, HashMap::from([(job_id.clone(), job.clone())]))),
+            }
+        }
+        groups
+            .into_iter()
+            .filter(|(_, jobs)| !jobs.is_empty())
+            .map(|(distribution, jobs)| (distribution, Arc::new(jobs)))
+            .collect()
+    }
+
+    /// The active jobs whose tasks may be bound to an executor polling for work: all of
+    /// them, or, with `max_jobs_per_executor`, the jobs the executor runs and the jobs
+    /// running for the longest up to that number of jobs. The `running_jobs` reported by
+    /// the executor include the jobs of the other schedulers.
+    pub(crate) async fn jobs_for_executor(
+        &self,
+        executor_id: &str,
+        running_jobs: &[String],
+        active_jobs: Arc<HashMap<String, JobInfoCache>>,
+    ) -> Arc<HashMap<String, JobInfoCache>> {
+        let max_jobs = self.config.max_jobs_per_executor as usize;
+        if max_jobs == 0 {
+            return active_jobs;
+        }
+        let (executor_jobs, jobs) = self.executor_running_jobs(&active_jobs).await;
+        let mut allowed = executor_jobs.get(executor_id).cloned().unwrap_or_default();
+        allowed.extend(running_jobs.iter().cloned());
+        for job_id in jobs {
+            if allowed.len() >= max_jobs {
+                break;
+            }
+            allowed.insert(job_id);
+        }
+        Arc::new(
+            active_jobs
+                .iter()
+                .filter(|(job_id, _)| allowed.contains(*job_id))
+                .map(|(job_id, job_info)| (job_id.clone(), job_info.clone()))
+                .collect(),
+        )
+    }
+
+    /// The IDs of the jobs running tasks on every executor, those of this scheduler and
+    /// those the executors report for any scheduler, and the IDs of the active jobs from
+    /// the job running for the longest
+    async fn executor_running_jobs(
+        &self,
+        active_jobs: &HashMap<String, JobInfoCache>,
+    ) -> (HashMap<String, HashSet<String>>, Vec<String>) {
+        let mut executor_jobs: HashMap<String, HashSet<String>> = HashMap::new();
+        for (executor_id, heartbeat) in self.cluster_state.executor_heartbeats() {
+            if !heartbeat.running_jobs.is_empty() {
+                executor_jobs
+                    .entry(executor_id)
+                    .or_default()
+                    .extend(heartbeat.running_jobs);
+            }
+        }
+        let mut jobs = Vec::with_capacity(active_jobs.len());
+        for (job_id, job_info) in active_jobs.iter() {
+            let graph = job_info.execution_graph.read().await;
+            for task in graph.running_tasks() {
+                executor_jobs
+                    .entry(task.executor_id)
+                    .or_default()
+                    .insert(job_id.clone());
+            }
+            jobs.push((graph.start_time(), job_id.clone()));
+        }
+        jobs.sort_unstable();
+        (
+            executor_jobs,
+            jobs.into_iter().map(|(_, job_id)| job_id).collect(),
+        )
+    }
+
+    /// Select the executors which have the topology labels required by the scheduler config
+    async fn select_executors(&self, executors: HashSet<String>) -> HashSet<String> {
+        if self.config.executor_labels.is_empty() {
+            return executors;
+        }
+        let mut selected = HashSet::new();
+        for executor_id in executors {
+            match self.get_executor_metadata(&executor_id).await {
+                Ok(metadata) => {
+                    let matches =
+                        self.config.executor_labels.iter().all(|(name, value)| {
+                            metadata.topology_label(name) == Some(value.as_str())
+                        });
+                    if matches {
+                        selected.insert(executor_id);
+                    }
+                }
+                Err(e) => {
+                    warn!("Could not get metadata of executor {executor_id}: {e:?}");
+                }
+            }
+        }
+        selected
+    }
+
+    /// Get the total task slots of the alive executors which can be bound tasks
+    pub async fn total_task_slots(&self) -> u32 {
+        let mut task_slots = 0;
+        for executor_id in self.select_executors(self.get_alive_executors()).await {
+            match self.get_executor_metadata(&executor_id).await {
+                Ok(metadata) => task_slots += metadata.specification.task_slots,
+                Err(e) => {
+                    warn!("Could not get metadata of executor {executor_id}: {e:?}");
+                }
+            }
+        }
+        task_slots
+    }
+
+    /// Get the available task slots of the alive executors which can be bound tasks
+    pub async fn available_task_slots(&self) -> u32 {
+        let mut task_slots = 0;
+        for executor_id in self.select_executors(self.get_alive_executors()).await {
+            match self.get_available_task_slots(&executor_id).await {
+                Ok(slots) => task_slots += slots.unwrap_or_default(),
+                Err(e) => {
+                    warn!("Could not get task slots of executor {executor_id}: {e:?}");
+                }
+            }
+        }
+        task_slots
+    }
+
+    /// Get the number of available task slots of an executor, None if it has no task slots
+    /// registered
+    pub async fn get_available_task_slots(
+        &self,
+        executor_id: &str,
+    ) -> Result<Option<u32>> {
         self.cluster_state
-            .bind_schedulable_tasks(
-                self.config.task_distribution,
-                active_jobs,
-                Some(alive_executors),
-            )
+            .get_available_task_slots(executor_id)
+            .await
+    }
+
+    /// Get the number of available task slots of an executor as known in the memory of
+    /// the scheduler, None if they are not known without reading the cluster state store
+    pub async fn get_cached_available_task_slots(
+        &self,
+        executor_id: &str,
+    ) -> Option<u32> {
+        self.cluster_state
+            .get_cached_available_task_slots(executor_id)
+            .await
+    }
+
+    /// Change the task slots of a registered executor, returning its previous task slots
+    pub async fn resize_executor_task_slots(
+        &self,
+        executor_id: &str,
+        task_slots: u32,
+    ) -> Result<u32> {
+        self.cluster_state
+            .resize_executor_task_slots(executor_id, task_slots)
             .await
     }
 
@@ -191,6 +438,86 @@ impl ExecutorManager {
         }
     }
 
+    /// Send rpc to the alive Executors to send the status of the tasks of a job to another
+    /// scheduler, once the job was handed off to it
+    pub(crate) async fn update_job_scheduler(&self, job_id: &str, scheduler_id: &str) {
+        let requests =
+            self.get_alive_executors()
+                .into_iter()
+                .map(|executor| async move {
+                    let mut client = match self.get_client(&executor).await {
+                        Ok(client) => client,
+                        Err(e) => {
+                            warn!(
+                                "Failed to get client for Executor {}: {:?}",
+                                executor, e
+                            );
+                            return;
+                        }
+                    };
+                    if let Err(e) = client
+                        .update_job_scheduler(UpdateJobSchedulerParams {
+                            job_id: job_id.to_owned(),
+                            scheduler_id: scheduler_id.to_owned(),
+                        })
+                        .await
+                    {
+                        warn!(
+                        "Failed to call update_job_scheduler on Executor {} due to {:?}",
+                        executor, e
+                    )
+                    }
+                });
+        futures::future::join_all(requests).await;
+    }
+
+    /// Send rpc to the Executor which ran a task, or to all the alive Executors if it is
+    /// not known, to get the log lines of the task. Returns the result of the first
+    /// Executor which kept log lines of the task, if any.
+    pub(crate) async fn get_task_logs(
+        &self,
+        executor_id: Option<String>,
+        job_id: &str,
+        stage_id: usize,
+        task_id: usize,
+    ) -> Option<GetTaskLogsResult> {
+        let executors = match executor_id {
+            Some(executor_id) => vec![executor_id],
+            None => self.get_alive_executors().into_iter().collect(),
+        };
+        let requests = executors.into_iter().map(|executor| async move {
+            let mut client = match self.get_client(&executor).await {
+                Ok(client) => client,
+                Err(e) => {
+                    warn!("Failed to get client for Executor {}: {:?}", executor, e);
+                    return None;
+                }
+            };
+            match client
+                .get_task_logs(GetTaskLogsParams {
+                    job_id: job_id.to_owned(),
+                    stage_id: stage_id as u32,
+                    task_id: task_id as u32,
+                })
+                .await
+            {
+                Ok(response) => Some(response.into_inner()).filter(|result| result.found),
+                Err(e) => {
+                    warn!(
+                        "Failed to call get_task_logs on Executor {} due to {:?}",
+                        executor, e
+                    );
+                    None
+                }
+            }
+        });
+        futures::future::join_all(requests)
+            .await
+            .into_iter()
+            .flatten()
+            .next()
+    }
+
     /// Get a list of all executors along with the timestamp of their last recorded heartbeat
     pub async fn get_executor_state(&self) -> Result<Vec<(ExecutorMetadata, Duration)>> {
         let heartbeat_timestamps: Vec<(String, u64)> = self
@@ -313,6 +640,26 @@ impl ExecutorManager {
         Ok(())
     }
 
+    /// Copy shuffle partitions held by other executors to an executor. Returns the paths
+    /// of the copies on the executor, in the order of the partitions.
+    pub(crate) async fn replicate_shuffle_data(
+        &self,
+        executor_id: &str,
+        partitions: Vec<protobuf::PartitionLocation>,
+    ) -> Result<Vec<String>> {
+        let mut client = self.get_client(executor_id).await?;
+        let result = client
+            .replicate_shuffle_data(protobuf::ReplicateShuffleDataParams { partitions })
+            .await
+            .map_err(|e| {
+                BallistaError::Internal(format!(
+                    "Failed to replicate shuffle data to executor {}: {:?}",
+                    executor_id, e
+                ))
+            })?;
+        Ok(result.into_inner().paths)
+    }
+
     pub(crate) async fn save_executor_heartbeat(
         &self,
         heartbeat: ExecutorHeartbeat,
@@ -337,6 +684,43 @@ impl ExecutorManager {
             })
     }
 
+    /// Whether the cgroup of the executor used more memory than the percentage of its limit
+    /// allowed by the scheduler config, according to the last heartbeat of the executor
+    pub(crate) fn is_memory_saturated(&self, executor_id: &str) -> bool {
+        let max_percent = self.config.executor_max_memory_percent as u64;
+        if max_percent == 0 {
+            return false;
+        }
+        let Some(heartbeat) = self.cluster_state.get_executor_heartbeat(executor_id)
+        else {
+            return false;
+        };
+        heartbeat.metrics.iter().any(|metric| match &metric.metric {
+            Some(executor_metric::Metric::Cgroup(cgroup)) if cgroup.memory_max > 0 => {
+                cgroup.memory_current.saturating_mul(100)
+                    > cgroup.memory_max.saturating_mul(max_percent)
+            }
+            _ => false,
+        })
+    }
+
+    /// The metrics of the disk IO pools of the executor, according to its last heartbeat
+    pub(crate) fn disk_io_metrics(&self, executor_id: &str) -> Vec<DiskIoMetrics> {
+        self.cluster_state
+            .get_executor_heartbeat(executor_id)
+            .map(|heartbeat| {
+                heartbeat
+                    .metrics
+                    .into_iter()
+                    .filter_map(|metric| match metric.metric {
+                        Some(executor_metric::Metric::DiskIo(disk_io)) => Some(disk_io),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     /// Retrieve the set of all executor IDs where the executor has been observed in the last
     /// `last_seen_ts_threshold` seconds.
     pub(crate) fn get_alive_executors(&self) -> HashSet<String> {
@@ -433,3 +817,312 @@ impl ExecutorManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+
+    use ballista_core::config::{BallistaConfig, BALLISTA_TASK_DISTRIBUTION};
+    use ballista_core::error::Result;
+    use ballista_core::serde::protobuf::{
+        executor_metric, CgroupMetrics, DiskIoMetrics, ExecutorMetric,
+    };
+    use ballista_core::serde::scheduler::ExecutorData;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::empty::EmptyExec;
+    use datafusion::prelude::SessionConfig;
+
+    use crate::config::{SchedulerConfig, TaskDistributionPolicy};
+    use crate::state::execution_graph::ExecutionGraph;
+    use crate::state::executor_manager::ExecutorManager;
+    use crate::state::task_manager::JobInfoCache;
+    use crate::test_utils::{mock_executor, test_cluster_context};
+
+    #[tokio::test]
+    async fn test_max_jobs_per_executor() -> Result<()> {
+        let cluster = test_cluster_context();
+        for executor_id in ["executor_1", "executor_2"] {
+            cluster
+                .cluster_state()
+                .register_executor(
+                    mock_executor(executor_id.to_string()),
+                    ExecutorData {
+                        executor_id: executor_id.to_string(),
+                        total_task_slots: 8,
+                        available_task_slots: 8,
+                    },
+                )
+                .await?;
+        }
+        let config = SchedulerConfig::default().with_max_jobs_per_executor(1);
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), Arc::new(config));
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let plan = Arc::new(EmptyExec::new(schema).with_partitions(3));
+        let mut active_jobs = HashMap::new();
+        for job_id in ["job_a", "job_b", "job_c"] {
+            let mut graph = ExecutionGraph::new(
+                "localhost:50050",
+                job_id,
+                "",
+                "session",
+                plan.clone(),
+                0,
+            )?;
+            graph.revive();
+            active_jobs.insert(
+                job_id.to_string(),
+                JobInfoCache::new(graph, &SessionConfig::new()),
+            );
+        }
+        let active_jobs = Arc::new(active_jobs);
+
+        // every executor runs the tasks of a single job, the third job waits
+        let bound_tasks = executor_manager
+            .bind_schedulable_tasks(active_jobs.clone())
+            .await?;
+        let mut executor_jobs: HashMap<String, HashSet<String>> = HashMap::new();
+        for (executor_id, task) in bound_tasks.iter() {
+            executor_jobs
+                .entry(executor_id.clone())
+                .or_default()
+                .insert(task.partition.job_id.clone());
+        }
+        assert_eq!(6, bound_tasks.len());
+        assert_eq!(2, executor_jobs.len());
+        assert!(executor_jobs.values().all(|jobs| jobs.len() == 1));
+
+        // an executor polling for work is offered the job it runs
+        let executor_1_jobs = executor_manager
+            .jobs_for_executor("executor_1", &[], active_jobs.clone())
+            .await;
+        assert_eq!(
+            executor_1_jobs.keys().collect::<HashSet<_>>(),
+            executor_jobs["executor_1"].iter().collect::<HashSet<_>>()
+        );
+
+        // the third job is not bound to an executor running the job of another scheduler
+        cluster
+            .cluster_state()
+            .register_executor(
+                mock_executor("executor_3".to_string()),
+                ExecutorData {
+                    executor_id: "executor_3".to_string(),
+                    total_task_slots: 8,
+                    available_task_slots: 8,
+                },
+            )
+            .await?;
+        let mut heartbeat = cluster
+            .cluster_state()
+            .get_executor_heartbeat("executor_3")
+            .unwrap();
+        heartbeat.running_jobs = vec!["job_other".to_string()];
+        cluster
+            .cluster_state()
+            .save_executor_heartbeat(heartbeat)
+            .await?;
+        let bound_tasks = executor_manager
+            .bind_schedulable_tasks(active_jobs.clone())
+            .await?;
+        assert!(bound_tasks.is_empty());
+        let executor_3_jobs = executor_manager
+            .jobs_for_executor("executor_3", &["job_other".to_string()], active_jobs)
+            .await;
+        assert!(executor_3_jobs.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_task_distribution() -> Result<()> {
+        let cluster = test_cluster_context();
+        for executor_id in ["executor_1", "executor_2"] {
+            cluster
+                .cluster_state()
+                .register_executor(
+                    mock_executor(executor_id.to_string()),
+                    ExecutorData {
+                        executor_id: executor_id.to_string(),
+                        total_task_slots: 4,
+                        available_task_slots: 4,
+                    },
+                )
+                .await?;
+        }
+        let config = SchedulerConfig::default()
+            .with_task_distribution(TaskDistributionPolicy::Bias);
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), Arc::new(config));
+
+        let round_robin_config = BallistaConfig::builder()
+            .set(BALLISTA_TASK_DISTRIBUTION, "round-robin")
+            .build()?;
+        let round_robin_session =
+            SessionConfig::new().with_extension(Arc::new(round_robin_config));
+        assert_eq!(
+            Some(TaskDistributionPolicy::RoundRobin),
+            TaskDistributionPolicy::from_session_config(&round_robin_session).unwrap()
+        );
+        let consistent_hash_config = BallistaConfig::builder()
+            .set(BALLISTA_TASK_DISTRIBUTION, "consistent-hash")
+            .build()?;
+        assert!(TaskDistributionPolicy::from_session_config(
+            &SessionConfig::new().with_extension(Arc::new(consistent_hash_config))
+        )
+        .is_err());
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let plan = Arc::new(EmptyExec::new(schema).with_partitions(2));
+        let mut active_jobs = HashMap::new();
+        for job_id in ["job_bias", "job_round_robin"] {
+            let mut graph = ExecutionGraph::new(
+                "localhost:50050",
+                job_id,
+                "",
+                "session",
+                plan.clone(),
+                0,
+            )?;
+            graph.revive();
+            let session_config = if job_id == "job_round_robin" {
+                round_robin_session.clone()
+            } else {
+                SessionConfig::new()
+            };
+            active_jobs.insert(
+                job_id.to_string(),
+                JobInfoCache::new(graph, &session_config),
+            );
+        }
+
+        // the tasks of the job with the policy of the scheduler fill an executor while the
+        // tasks of the job overriding it are spread over the executors
+        let bound_tasks = executor_manager
+            .bind_schedulable_tasks(Arc::new(active_jobs))
+            .await?;
+        let mut job_executors: HashMap<String, HashSet<String>> = HashMap::new();
+        for (executor_id, task) in bound_tasks.iter() {
+            job_executors
+                .entry(task.partition.job_id.clone())
+                .or_default()
+                .insert(executor_id.clone());
+        }
+        assert_eq!(4, bound_tasks.len());
+        assert_eq!(1, job_executors["job_bias"].len());
+        assert_eq!(2, job_executors["job_round_robin"].len());
+
+        Ok(())
+    }
+    #[tokio::test]
+    async fn test_executor_max_memory_percent() -> Result<()> {
+        let cluster = test_cluster_context();
+        for executor_id in ["executor_1", "executor_2"] {
+            cluster
+                .cluster_state()
+                .register_executor(
+                    mock_executor(executor_id.to_string()),
+                    ExecutorData {
+                        executor_id: executor_id.to_string(),
+                        total_task_slots: 4,
+                        available_task_slots: 4,
+                    },
+                )
+                .await?;
+        }
+        // the cgroup of the first executor uses 90% of its memory limit
+        let mut heartbeat = cluster
+            .cluster_state()
+            .get_executor_heartbeat("executor_1")
+            .unwrap();
+        heartbeat.metrics.push(ExecutorMetric {
+            metric: Some(executor_metric::Metric::Cgroup(CgroupMetrics {
+                memory_current: 900,
+                memory_max: 1000,
+                ..Default::default()
+            })),
+        });
+        cluster
+            .cluster_state()
+            .save_executor_heartbeat(heartbeat)
+            .await?;
+        let config = SchedulerConfig::default().with_executor_max_memory_percent(80);
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), Arc::new(config));
+        assert!(executor_manager.is_memory_saturated("executor_1"));
+        assert!(!executor_manager.is_memory_saturated("executor_2"));
+
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let plan = Arc::new(EmptyExec::new(schema).with_partitions(4));
+        let mut graph =
+            ExecutionGraph::new("localhost:50050", "job", "", "session", plan, 0)?;
+        graph.revive();
+        let mut active_jobs = HashMap::new();
+        active_jobs.insert(
+            "job".to_string(),
+            JobInfoCache::new(graph, &SessionConfig::new()),
+        );
+
+        // the tasks are only bound to the executor with memory to spare
+        let bound_tasks = executor_manager
+            .bind_schedulable_tasks(Arc::new(active_jobs))
+            .await?;
+        assert_eq!(4, bound_tasks.len());
+        assert!(bound_tasks
+            .iter()
+            .all(|(executor_id, _)| executor_id == "executor_2"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_disk_io_metrics() -> Result<()> {
+        let cluster = test_cluster_context();
+        cluster
+            .cluster_state()
+            .register_executor(
+                mock_executor("executor_1".to_string()),
+                ExecutorData {
+                    executor_id: "executor_1".to_string(),
+                    total_task_slots: 4,
+                    available_task_slots: 4,
+                },
+            )
+            .await?;
+        let executor_manager = ExecutorManager::new(
+            cluster.cluster_state(),
+            Arc::new(SchedulerConfig::default()),
+        );
+        assert!(executor_manager.disk_io_metrics("executor_1").is_empty());
+
+        let mut heartbeat = cluster
+            .cluster_state()
+            .get_executor_heartbeat("executor_1")
+            .unwrap();
+        let write = DiskIoMetrics {
+            pool: "write".to_string(),
+            max_concurrency: 8,
+            running: 2,
+            queued: 1,
+            completed: 10,
+            wait_time_nanos: 1_000,
+        };
+        heartbeat.metrics.push(ExecutorMetric {
+            metric: Some(executor_metric::Metric::Cgroup(CgroupMetrics::default())),
+        });
+        heartbeat.metrics.push(ExecutorMetric {
+            metric: Some(executor_metric::Metric::DiskIo(write.clone())),
+        });
+        cluster
+            .cluster_state()
+            .save_executor_heartbeat(heartbeat)
+            .await?;
+
+        assert_eq!(vec![write], executor_manager.disk_io_metrics("executor_1"));
+        assert!(executor_manager.disk_io_metrics("executor_2").is_empty());
+
+        Ok(())
+    }
+}
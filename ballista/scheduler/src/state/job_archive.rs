@@ -0,0 +1,144 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Archive of the finished jobs in an object store. The execution graph of a finished job,
+//! holding its final status, the metrics of its stages and its plans, is archived before
+//! its state is cleaned up from the backend, so that the REST API still serves the job
+//! afterwards.
+
+use std::fmt;
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::object_store_registry::BallistaObjectStoreRegistry;
+use ballista_core::plan_protection::PlanProtection;
+use ballista_core::serde::protobuf;
+use datafusion::datasource::object_store::ObjectStoreRegistry;
+use datafusion::error::DataFusionError;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use prost::Message;
+use url::Url;
+
+use crate::state::decode_protobuf;
+
+/// Archive of the execution graphs of the finished jobs, one object per job under a prefix
+/// of an object store
+pub struct JobArchive {
+    store: Arc<dyn ObjectStore>,
+    prefix: Path,
+}
+
+impl fmt::Debug for JobArchive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobArchive")
+            .field("store", &self.store.to_string())
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+impl JobArchive {
+    /// Archive to the prefix at `url` of an object store, e.g. `s3://bucket/ballista/jobs`
+    pub fn try_new(url: &str) -> Result<Self> {
+        let parsed = Url::parse(url).map_err(|e| {
+            BallistaError::General(format!("Invalid job archive URL {url}: {e}"))
+        })?;
+        let store = BallistaObjectStoreRegistry::new().get_store(&parsed)?;
+        let prefix = Path::from_url_path(parsed.path()).map_err(|e| {
+            BallistaError::General(format!("Invalid job archive URL {url}: {e}"))
+        })?;
+        Ok(Self::new(store, prefix))
+    }
+
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: Path) -> Self {
+        Self { store, prefix }
+    }
+
+    fn path(&self, job_id: &str) -> Path {
+        self.prefix.child(format!("{job_id}.pb"))
+    }
+
+    /// Archive the encoded execution graph of a job, protected like the graphs stored in
+    /// the backend
+    pub(crate) async fn put(
+        &self,
+        graph: &protobuf::ExecutionGraph,
+        plan_protection: &PlanProtection,
+    ) -> Result<()> {
+        let data = plan_protection.protect(graph.encode_to_vec())?;
+        self.store
+            .put(&self.path(&graph.job_id), data.into())
+            .await
+            .map_err(DataFusionError::from)?;
+        Ok(())
+    }
+
+    /// The encoded execution graph of an archived job, `None` if the job is not archived
+    pub(crate) async fn get(
+        &self,
+        job_id: &str,
+        plan_protection: &PlanProtection,
+    ) -> Result<Option<protobuf::ExecutionGraph>> {
+        let data = match self.store.get(&self.path(job_id)).await {
+            Ok(result) => result.bytes().await.map_err(DataFusionError::from)?,
+            Err(object_store::Error::NotFound { .. }) => return Ok(None),
+            Err(e) => return Err(DataFusionError::from(e).into()),
+        };
+        let encoded = plan_protection.unprotect(data.to_vec())?;
+        decode_protobuf(&encoded).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ballista_core::plan_protection::PlanProtection;
+    use ballista_core::serde::protobuf;
+    use object_store::memory::InMemory;
+    use object_store::path::Path;
+
+    use super::JobArchive;
+
+    #[tokio::test]
+    async fn test_job_archive() {
+        let archive = JobArchive::new(Arc::new(InMemory::new()), Path::from("jobs"));
+        let protection = PlanProtection::new(Some(&"01".repeat(32)), None).unwrap();
+        assert!(archive.get("job", &protection).await.unwrap().is_none());
+
+        let graph = protobuf::ExecutionGraph {
+            job_id: "job".to_owned(),
+            session_id: "session".to_owned(),
+            ..Default::default()
+        };
+        archive.put(&graph, &protection).await.unwrap();
+        assert_eq!(archive.get("job", &protection).await.unwrap(), Some(graph));
+        assert!(archive.get("other", &protection).await.unwrap().is_none());
+
+        // the archived graphs are protected like the graphs of the backend
+        let other_protection = PlanProtection::new(Some(&"02".repeat(32)), None).unwrap();
+        assert!(archive.get("job", &other_protection).await.is_err());
+    }
+
+    #[test]
+    fn test_job_archive_url() {
+        let archive = JobArchive::try_new("file:///tmp/ballista/jobs").unwrap();
+        assert_eq!(archive.path("job").as_ref(), "tmp/ballista/jobs/job.pb");
+        assert!(JobArchive::try_new("unknown://bucket/jobs").is_err());
+    }
+}
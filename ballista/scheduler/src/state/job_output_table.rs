@@ -0,0 +1,156 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+use std::any::Any;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ballista_core::execution_plans::ShuffleReaderExec;
+use ballista_core::serde::protobuf::{job_status, PartitionLocation};
+use ballista_core::serde::scheduler::PartitionLocation as ScheduledPartitionLocation;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::common::{exec_err, plan_err, DataFusionError};
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::error::Result;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::Expr;
+use datafusion::physical_expr::expressions::Column;
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion::physical_plan::empty::EmptyExec;
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::ExecutionPlan;
+use datafusion_proto::logical_plan::AsLogicalPlan;
+use datafusion_proto::physical_plan::AsExecutionPlan;
+
+use crate::state::task_manager::TaskManager;
+
+/// Table reading the output of a job from the executors, backing the temporary tables
+/// of a session. The output is located when the table is scanned, so the table can be
+/// registered as soon as the job is submitted.
+pub struct JobOutputTable<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> {
+    job_id: String,
+    schema: SchemaRef,
+    task_manager: TaskManager<T, U>,
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> JobOutputTable<T, U> {
+    pub fn new(
+        job_id: String,
+        schema: SchemaRef,
+        task_manager: TaskManager<T, U>,
+    ) -> Self {
+        Self {
+            job_id,
+            schema,
+            task_manager,
+        }
+    }
+
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    async fn output_locations(&self) -> Result<Vec<PartitionLocation>> {
+        let status = self
+            .task_manager
+            .get_job_status(&self.job_id)
+            .await
+            .map_err(|e| DataFusionError::External(Box::new(e)))?
+            .and_then(|status| status.status);
+        match status {
+            Some(job_status::Status::Successful(job)) => Ok(job.partition_location),
+            Some(job_status::Status::Failed(job)) => exec_err!(
+                "Job {} creating the table failed: {}",
+                self.job_id,
+                job.error
+            ),
+            Some(_) => plan_err!(
+                "Job {} creating the table has not finished yet",
+                self.job_id
+            ),
+            None => plan_err!("Job {} creating the table not found", self.job_id),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TableProvider
+    for JobOutputTable<T, U>
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Temporary
+    }
+
+    async fn scan(
+        &self,
+        _state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        _filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let locations = self.output_locations().await?;
+
+        let mut partitions: Vec<Vec<ScheduledPartitionLocation>> = vec![];
+        let mut stage_id = 0;
+        for location in locations {
+            let location: ScheduledPartitionLocation = location
+                .try_into()
+                .map_err(|e| DataFusionError::External(Box::new(e)))?;
+            let partition_id = location.partition_id.partition_id;
+            stage_id = location.partition_id.stage_id;
+            if partitions.len() <= partition_id {
+                partitions.resize(partition_id + 1, vec![]);
+            }
+            partitions[partition_id].push(location);
+        }
+
+        let input: Arc<dyn ExecutionPlan> = if partitions.is_empty() {
+            Arc::new(EmptyExec::new(self.schema.clone()))
+        } else {
+            Arc::new(ShuffleReaderExec::try_new(
+                stage_id,
+                partitions,
+                self.schema.clone(),
+            )?)
+        };
+
+        match projection {
+            Some(projection) => {
+                let exprs = projection
+                    .iter()
+                    .map(|i| {
+                        let name = self.schema.field(*i).name().clone();
+                        let expr: Arc<dyn PhysicalExpr> =
+                            Arc::new(Column::new(&name, *i));
+                        (expr, name)
+                    })
+                    .collect();
+                Ok(Arc::new(ProjectionExec::try_new(exprs, input)?))
+            }
+            None => Ok(input),
+        }
+    }
+}
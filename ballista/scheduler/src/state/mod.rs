@@ -17,28 +17,37 @@
 
 use datafusion::common::tree_node::{TreeNode, TreeNodeRecursion};
 use datafusion::datasource::listing::{ListingTable, ListingTableUrl};
+use datafusion::datasource::physical_plan::{AvroExec, CsvExec, NdJsonExec, ParquetExec};
 use datafusion::datasource::source_as_provider;
 use datafusion::error::DataFusionError;
 use std::any::type_name;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Instant;
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 
 use crate::state::executor_manager::ExecutorManager;
+use crate::state::job_archive::JobArchive;
+use crate::state::job_output_table::JobOutputTable;
 use crate::state::session_manager::SessionManager;
 use crate::state::task_manager::{TaskLauncher, TaskManager};
 
 use crate::cluster::{BallistaCluster, BoundTask, ExecutorSlot};
-use crate::config::SchedulerConfig;
+use crate::config::{JobAdmissionPolicy, SchedulerConfig};
+use crate::planner::balance_scan_file_groups;
+use crate::row_group_pruning::prune_row_groups;
 use crate::state::execution_graph::TaskDescription;
+use ballista_core::config::{BallistaConfig, BALLISTA_STAGE_PIPELINED};
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::event_loop::EventSender;
-use ballista_core::serde::protobuf::TaskStatus;
+use ballista_core::serde::protobuf::{self, TaskStatus};
+use ballista_core::serde::scheduler::PartitionLocation;
 use ballista_core::serde::BallistaCodec;
+use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::logical_expr::LogicalPlan;
 use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::ExecutionPlan;
 use datafusion::prelude::SessionContext;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
@@ -47,10 +56,89 @@ use prost::Message;
 
 pub mod execution_graph;
 pub mod execution_graph_dot;
+pub mod execution_graph_json;
 pub mod executor_manager;
+pub mod job_archive;
+pub mod job_output_table;
+pub mod scheduling_trace;
 pub mod session_manager;
+pub mod stage_alerts;
 pub mod task_manager;
 
+/// Estimate the peak number of tasks of a job running at once, as the largest number of
+/// partitions of an operator of its physical plan, since every stage runs one task per
+/// partition
+pub(crate) fn estimate_peak_parallelism(plan: &dyn ExecutionPlan) -> usize {
+    plan.children()
+        .iter()
+        .map(|child| estimate_peak_parallelism(child.as_ref()))
+        .fold(
+            plan.properties().output_partitioning().partition_count(),
+            usize::max,
+        )
+}
+
+/// Create the physical plan of a job as the scheduler runs it from its logical `plan`
+/// optimized by the session, with the row groups of the Parquet scans pruned and the
+/// scanned files balanced between the tasks as enabled by the Ballista settings of the
+/// session
+pub async fn create_job_physical_plan(
+    session_ctx: &SessionContext,
+    plan: &LogicalPlan,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    // the logical plan is already optimized by the session, it is not optimized again
+    let mut plan = session_ctx
+        .state()
+        .with_analyzer_rules(vec![])
+        .with_optimizer_rules(vec![])
+        .create_physical_plan(plan)
+        .await?;
+    let ballista_config = session_ctx
+        .state()
+        .config()
+        .get_extension::<BallistaConfig>();
+    if ballista_config
+        .as_ref()
+        .map(|config| config.parquet_prune_row_groups())
+        .unwrap_or(false)
+    {
+        plan = prune_row_groups(plan, &session_ctx.runtime_env()).await?;
+    }
+    if let Some(target_bytes) = ballista_config
+        .as_ref()
+        .and_then(|config| config.scan_target_bytes_per_task())
+    {
+        plan = balance_scan_file_groups(plan, target_bytes)?;
+    }
+    Ok(plan)
+}
+
+/// The schemes of the URLs of the object stores read by the file scans of the plan
+pub(crate) fn required_object_store_schemes(
+    plan: &dyn ExecutionPlan,
+) -> BTreeSet<String> {
+    let any = plan.as_any();
+    let base_config = if let Some(exec) = any.downcast_ref::<ParquetExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = any.downcast_ref::<CsvExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = any.downcast_ref::<NdJsonExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = any.downcast_ref::<AvroExec>() {
+        Some(exec.base_config())
+    } else {
+        None
+    };
+    let mut schemes: BTreeSet<String> = base_config
+        .map(|config| config.object_store_url.as_ref().scheme().to_owned())
+        .into_iter()
+        .collect();
+    for child in plan.children() {
+        schemes.extend(required_object_store_schemes(child.as_ref()));
+    }
+    schemes
+}
+
 pub fn decode_protobuf<T: Message + Default>(bytes: &[u8]) -> Result<T> {
     T::decode(bytes).map_err(|e| {
         BallistaError::Internal(format!(
@@ -85,6 +173,19 @@ pub fn encode_protobuf<T: Message + Default>(msg: &T) -> Result<Vec<u8>> {
     Ok(value)
 }
 
+/// The archive of the finished jobs configured for the scheduler, if any. The jobs are not
+/// archived when the archive cannot be opened.
+fn job_archive(config: &SchedulerConfig) -> Option<Arc<JobArchive>> {
+    let url = config.job_archive_url.as_deref()?;
+    match JobArchive::try_new(url) {
+        Ok(archive) => Some(Arc::new(archive)),
+        Err(e) => {
+            error!("The finished jobs are not archived to {url}: {e}");
+            None
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct SchedulerState<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> {
     pub executor_manager: ExecutorManager,
@@ -110,7 +211,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
                 cluster.job_state(),
                 codec.clone(),
                 scheduler_name,
-            ),
+            )
+            .with_max_job_size(
+                config.max_job_stages as usize,
+                config.max_job_tasks as usize,
+            )
+            .with_job_cache_shards(config.job_cache_shards as usize)
+            .with_stage_alert_rules(config.stage_alert_rules.clone())
+            .with_stage_planner(config.stage_planner.clone())
+            .with_job_archive(job_archive(&config)),
             session_manager: SessionManager::new(cluster.job_state()),
             codec,
             config,
@@ -144,7 +253,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
                 codec.clone(),
                 scheduler_name,
                 dispatcher,
-            ),
+            )
+            .with_max_job_size(
+                config.max_job_stages as usize,
+                config.max_job_tasks as usize,
+            )
+            .with_job_cache_shards(config.job_cache_shards as usize)
+            .with_stage_alert_rules(config.stage_alert_rules.clone())
+            .with_stage_planner(config.stage_planner.clone())
+            .with_job_archive(job_archive(&config)),
             session_manager: SessionManager::new(cluster.job_state()),
             codec,
             config,
@@ -238,6 +355,145 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
         }
     }
 
+    /// Get the log lines of a task from the executor which ran it. All the executors are
+    /// asked if the task is not recorded in the execution graph of its job, e.g. for an
+    /// earlier attempt of the task.
+    pub(crate) async fn get_task_logs(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        task_id: usize,
+    ) -> Option<protobuf::GetTaskLogsResult> {
+        let executor_id = self
+            .task_manager
+            .get_task_executor(job_id, stage_id, task_id)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to get the executor of task {task_id} of job {job_id}: {e:?}"
+                );
+                None
+            });
+        self.executor_manager
+            .get_task_logs(executor_id, job_id, stage_id, task_id)
+            .await
+    }
+
+    /// Replicate the shuffle outputs held by an executor about to be terminated, e.g. on
+    /// a preemption notice, to the other alive executors, so that the stages reading them
+    /// read the replicas once the executor is gone. The outputs of every job are spread
+    /// over the other executors.
+    ///
+    /// Returns the map partitions of every stage of every job whose outputs could not be
+    /// replicated, to recompute them
+    pub(crate) async fn replicate_shuffle_outputs(
+        &self,
+        executor_id: &str,
+    ) -> HashMap<String, HashMap<usize, HashSet<usize>>> {
+        let mut targets: Vec<String> = self
+            .executor_manager
+            .get_alive_executors()
+            .into_iter()
+            .filter(|target| target != executor_id)
+            .collect();
+        targets.sort();
+
+        let mut not_replicated: HashMap<String, HashMap<usize, HashSet<usize>>> =
+            HashMap::new();
+        for (job_id, locations) in self.task_manager.shuffle_outputs_on(executor_id).await
+        {
+            let mut failed = vec![];
+            if targets.is_empty() {
+                failed = locations;
+            } else {
+                let mut assigned = vec![vec![]; targets.len()];
+                for (i, location) in locations.into_iter().enumerate() {
+                    assigned[i % targets.len()].push(location);
+                }
+                let replications = targets
+                    .iter()
+                    .zip(assigned)
+                    .filter(|(_, locations)| !locations.is_empty())
+                    .map(|(target, locations)| {
+                        let job_id = &job_id;
+                        async move {
+                            let result = self
+                                .replicate_shuffle_outputs_to(
+                                    job_id,
+                                    executor_id,
+                                    target,
+                                    &locations,
+                                )
+                                .await;
+                            (target, locations, result)
+                        }
+                    });
+                for (target, locations, result) in
+                    futures::future::join_all(replications).await
+                {
+                    match result {
+                        Ok(replaced) => info!(
+                            "Replicated {} shuffle outputs of job {} to executor {}",
+                            replaced, job_id, target
+                        ),
+                        Err(e) => {
+                            warn!(
+                                "Fail to replicate shuffle outputs of job {} to {}: {}",
+                                job_id, target, e
+                            );
+                            failed.extend(locations);
+                        }
+                    }
+                }
+            }
+            for location in failed {
+                not_replicated
+                    .entry(job_id.clone())
+                    .or_default()
+                    .entry(location.partition_id.stage_id)
+                    .or_default()
+                    .insert(location.map_partition_id);
+            }
+        }
+        not_replicated
+    }
+
+    /// Replicate shuffle outputs held by an executor to another executor, then replace
+    /// them with the replicas in the graph of the job
+    async fn replicate_shuffle_outputs_to(
+        &self,
+        job_id: &str,
+        executor_id: &str,
+        target: &str,
+        locations: &[PartitionLocation],
+    ) -> Result<usize> {
+        let target_metadata = self.executor_manager.get_executor_metadata(target).await?;
+        let partitions = locations
+            .iter()
+            .cloned()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<protobuf::PartitionLocation>>>()?;
+        let paths = self
+            .executor_manager
+            .replicate_shuffle_data(target, partitions)
+            .await?;
+        let replicas = locations
+            .iter()
+            .zip(paths)
+            .map(|(location, path)| {
+                let replica = PartitionLocation {
+                    executor_meta: target_metadata.clone(),
+                    path,
+                    ..location.clone()
+                };
+                (location.path.clone(), replica)
+            })
+            .collect();
+        self.task_manager
+            .replace_shuffle_outputs(job_id, executor_id, &replicas)
+            .await
+    }
+
     /// Given a vector of bound tasks,
     /// 1. Firstly reorganize according to: executor -> job stage -> tasks;
     /// 2. Then launch the task set vector to each executor one by one.
@@ -253,6 +509,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             String,
             HashMap<(String, usize), Vec<TaskDescription>>,
         > = HashMap::new();
+        self.task_manager
+            .scheduling_traces()
+            .record_bound_tasks(&bound_tasks);
         for (executor_id, task) in bound_tasks.into_iter() {
             let stage_key = (task.partition.job_id.clone(), task.partition.stage_id);
             if let Some(tasks) = executor_stage_assignments.get_mut(&executor_id) {
@@ -276,6 +535,14 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             let tasks: Vec<Vec<TaskDescription>> = tasks.into_values().collect();
             // Total number of tasks to be launched for one executor
             let n_tasks: usize = tasks.iter().map(|stage_tasks| stage_tasks.len()).sum();
+            // IDs of the tasks per job, traced if the tasks fail to be launched
+            let mut job_task_ids: HashMap<String, Vec<u32>> = HashMap::new();
+            for task in tasks.iter().flatten() {
+                job_task_ids
+                    .entry(task.partition.job_id.clone())
+                    .or_default()
+                    .push(task.task_id as u32);
+            }
 
             let state = self.clone();
             let join_handle = tokio::spawn(async move {
@@ -292,6 +559,17 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
                         {
                             let err_msg = format!("Failed to launch new task: {e}");
                             error!("{}", err_msg.clone());
+                            for (job_id, task_ids) in job_task_ids {
+                                state
+                                    .task_manager
+                                    .scheduling_traces()
+                                    .record_launch_failure(
+                                        &job_id,
+                                        &executor_id,
+                                        task_ids,
+                                        &err_msg,
+                                    );
+                            }
 
                             // It's OK to remove executor aggressively,
                             // since if the executor is in healthy state, it will be registered again.
@@ -304,6 +582,17 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
                     }
                     Err(e) => {
                         error!("Failed to launch new task, could not get executor metadata: {}", e);
+                        for (job_id, task_ids) in job_task_ids {
+                            state
+                                .task_manager
+                                .scheduling_traces()
+                                .record_launch_failure(
+                                    &job_id,
+                                    &executor_id,
+                                    task_ids,
+                                    &e.to_string(),
+                                );
+                        }
                         false
                     }
                 };
@@ -331,19 +620,42 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             .collect::<Vec<ExecutorSlot>>())
     }
 
+    /// Update the statuses of tasks reported by an executor. With push-based scheduling, the
+    /// task slots of the tasks are returned to the executor, in the same transaction as the
+    /// task statuses when the cluster storage supports it, so that they cannot diverge.
     pub(crate) async fn update_task_statuses(
         &self,
         executor_id: &str,
         tasks_status: Vec<TaskStatus>,
     ) -> Result<Vec<QueryStageSchedulerEvent>> {
-        let executor = self
+        let release_slots = self.config.is_push_staged_scheduling();
+        let num_slots = tasks_status.len() as u32;
+        let mut released_slots = 0;
+
+        let result = match self
             .executor_manager
             .get_executor_metadata(executor_id)
-            .await?;
-
-        self.task_manager
-            .update_task_statuses(&executor, tasks_status)
             .await
+        {
+            Ok(executor) => {
+                self.task_manager
+                    .update_task_statuses(
+                        &executor,
+                        tasks_status,
+                        release_slots.then_some(&mut released_slots),
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        // the slots of the tasks whose status was not saved along with their release
+        if release_slots && released_slots < num_slots {
+            self.executor_manager
+                .unbind_tasks(vec![(executor_id.to_owned(), num_slots - released_slots)])
+                .await?;
+        }
+        result
     }
 
     pub(crate) async fn submit_job(
@@ -356,14 +668,29 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
     ) -> Result<()> {
         let start = Instant::now();
 
-        if log::max_level() >= log::Level::Debug {
-            // optimizing the plan here is redundant because the physical planner will do this again
-            // but it is helpful to see what the optimized plan will be
-            let optimized_plan = session_ctx.state().optimize(plan)?;
-            debug!("Optimized plan: {}", optimized_plan.display_indent());
+        // the tasks of the pipelined stages rewound to resolve a deadlock are cancelled,
+        // which the executors polling for tasks do not support
+        let pipelined = session_ctx
+            .state()
+            .config()
+            .get_extension::<BallistaConfig>()
+            .map(|config| config.stage_pipelined())
+            .unwrap_or(false);
+        if pipelined && !self.config.is_push_staged_scheduling() {
+            return Err(BallistaError::General(format!(
+                "Job {job_id} sets {BALLISTA_STAGE_PIPELINED}, which is only supported \
+                with the push-based task scheduling policy"
+            )));
         }
 
+        // the physical plan is created from the optimized plan, which is kept with the
+        // job, see `GetJobPlan`
+        let optimized_plan = session_ctx.state().optimize(plan)?;
+        debug!("Optimized plan: {}", optimized_plan.display_indent());
+
+        let mut plan_nodes = 0u64;
         plan.apply(&mut |plan| {
+            plan_nodes += 1;
             if let LogicalPlan::TableScan(scan) = plan {
                 let provider = source_as_provider(&scan.source)?;
                 if let Some(table) = provider.as_any().downcast_ref::<ListingTable>() {
@@ -402,14 +729,35 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             Ok(TreeNodeRecursion::Continue)
         })?;
 
-        let plan = session_ctx.state().create_physical_plan(plan).await?;
+        let max_plan_nodes = self.config.max_job_plan_nodes;
+        if max_plan_nodes > 0 && plan_nodes > max_plan_nodes {
+            return Err(BallistaError::General(format!(
+                "Job {job_id} has {plan_nodes} plan nodes, more than the maximum of \
+                {max_plan_nodes} plan nodes per job"
+            )));
+        }
+
+        let plan = create_job_physical_plan(&session_ctx, &optimized_plan).await?;
         debug!(
             "Physical plan: {}",
             DisplayableExecutionPlan::new(plan.as_ref()).indent(false)
         );
 
+        self.admit_job(job_id, plan.as_ref()).await?;
+        self.check_executor_capabilities(job_id, plan.as_ref())
+            .await?;
+
         self.task_manager
-            .submit_job(job_id, job_name, &session_ctx.session_id(), plan, queued_at)
+            .submit_job(
+                job_id,
+                job_name,
+                &session_ctx.session_id(),
+                session_ctx.state().config(),
+                &optimized_plan,
+                plan,
+                queued_at,
+                self.session_manager.is_temporary_table_job(job_id),
+            )
             .await?;
 
         let elapsed = start.elapsed();
@@ -419,8 +767,116 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
         Ok(())
     }
 
+    /// Check the estimated peak task parallelism of the job against the task slots of the
+    /// cluster according to the job admission policy of the scheduler
+    async fn admit_job(&self, job_id: &str, plan: &dyn ExecutionPlan) -> Result<()> {
+        if self.config.job_admission_policy == JobAdmissionPolicy::Accept {
+            return Ok(());
+        }
+
+        let parallelism = estimate_peak_parallelism(plan);
+        let task_slots = self.executor_manager.total_task_slots().await as usize;
+        if task_slots == 0 {
+            let message = format!(
+                "Job {job_id} needs up to {parallelism} task slots but there are no \
+                alive executors with task slots to run it"
+            );
+            if self.config.job_admission_policy == JobAdmissionPolicy::Reject {
+                return Err(BallistaError::General(message));
+            }
+            warn!("{message}, it will stay queued until executors register");
+        } else if parallelism > task_slots {
+            warn!(
+                "Job {job_id} needs up to {parallelism} task slots but the cluster has \
+                {task_slots}, its tasks will wait for free task slots"
+            );
+        }
+        Ok(())
+    }
+
+    /// Check that the alive executors can access the object stores the job reads from, so
+    /// that the job fails when it is submitted rather than when its scan tasks run. Executors
+    /// which do not advertise their object stores are assumed to access all of them.
+    async fn check_executor_capabilities(
+        &self,
+        job_id: &str,
+        plan: &dyn ExecutionPlan,
+    ) -> Result<()> {
+        let schemes = required_object_store_schemes(plan);
+        if schemes.is_empty() {
+            return Ok(());
+        }
+        let mut executors = vec![];
+        for executor_id in self.executor_manager.get_alive_executors() {
+            if let Ok(metadata) = self
+                .executor_manager
+                .get_executor_metadata(&executor_id)
+                .await
+            {
+                executors.push(metadata);
+            }
+        }
+        if executors.is_empty() {
+            return Ok(());
+        }
+        for scheme in schemes {
+            if !executors
+                .iter()
+                .any(|metadata| metadata.supports_object_store(&scheme))
+            {
+                return Err(BallistaError::General(format!(
+                    "Job {job_id} reads from {scheme} object stores which none of the \
+                    alive executors can access"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Add a temporary table of the session, backed by the output of the job and
+    /// registered once the job succeeds
+    pub(crate) fn add_temporary_table(
+        &self,
+        session_id: &str,
+        session_ctx: &SessionContext,
+        name: &str,
+        or_replace: bool,
+        job_id: &str,
+        schema: SchemaRef,
+    ) -> Result<()> {
+        let table = Arc::new(JobOutputTable::new(
+            job_id.to_string(),
+            schema,
+            self.task_manager.clone(),
+        ));
+        self.session_manager.add_temporary_table(
+            session_id,
+            session_ctx,
+            name,
+            or_replace,
+            job_id,
+            table,
+        )
+    }
+
+    /// Drop the temporary tables of the session and clean up the jobs which backed them
+    pub(crate) fn remove_temporary_tables(&self, session_id: &str) {
+        for job_id in self.session_manager.remove_temporary_tables(session_id) {
+            self.clean_up_successful_job(job_id);
+        }
+    }
+
     /// Spawn a delayed future to clean up job data on both Scheduler and Executors
     pub(crate) fn clean_up_successful_job(&self, job_id: String) {
+        if let Some(replaced_job_id) =
+            self.session_manager.register_temporary_table(&job_id)
+        {
+            self.clean_up_successful_job(replaced_job_id);
+        }
+        // the output of jobs backing temporary tables is kept as long as the tables
+        if self.session_manager.is_temporary_table_job(&job_id) {
+            return;
+        }
         self.executor_manager.clean_up_job_data_delayed(
             job_id.clone(),
             self.config.finished_job_data_clean_up_interval_seconds,
@@ -433,6 +889,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
 
     /// Spawn a delayed future to clean up job data on both Scheduler and Executors
     pub(crate) fn clean_up_failed_job(&self, job_id: String) {
+        self.session_manager.abort_temporary_table(&job_id);
         self.executor_manager.clean_up_job_data(job_id.clone());
         self.task_manager.clean_up_job_delayed(
             job_id,
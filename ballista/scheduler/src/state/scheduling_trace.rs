@@ -0,0 +1,196 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Trace of the scheduling decisions of the jobs curated by the scheduler: which executor
+//! each task was bound to, why, and how long it waited for a task slot. The traces are
+//! kept in memory, for a bounded number of jobs and of events per job, so they are lost
+//! when the scheduler restarts or hands a job off to another scheduler.
+
+use std::collections::{HashMap, VecDeque};
+
+use ballista_core::serde::protobuf::{
+    scheduling_trace_event, SchedulingTraceEvent, TaskBound, TaskLaunchFailed,
+};
+use parking_lot::Mutex;
+
+use crate::cluster::BoundTask;
+use crate::scheduler_server::timestamp_millis;
+
+/// Max number of events kept in the trace of a job, the oldest events being dropped
+const MAX_EVENTS_PER_JOB: usize = 10_000;
+
+/// Max number of jobs whose trace is kept, the traces of the oldest jobs being dropped
+const MAX_TRACED_JOBS: usize = 1_000;
+
+#[derive(Default)]
+pub struct SchedulingTraces {
+    inner: Mutex<Traces>,
+}
+
+#[derive(Default)]
+struct Traces {
+    jobs: HashMap<String, JobTrace>,
+    /// IDs of the traced jobs, from the oldest
+    order: VecDeque<String>,
+}
+
+#[derive(Default)]
+struct JobTrace {
+    events: VecDeque<SchedulingTraceEvent>,
+    next_seq: u64,
+}
+
+impl SchedulingTraces {
+    /// Record the binding of tasks to the task slots of executors
+    pub(crate) fn record_bound_tasks(&self, tasks: &[BoundTask]) {
+        if tasks.is_empty() {
+            return;
+        }
+        let timestamp = timestamp_millis();
+        let mut traces = self.inner.lock();
+        for (executor_id, task) in tasks {
+            traces.record(
+                &task.partition.job_id,
+                timestamp,
+                scheduling_trace_event::Event::TaskBound(TaskBound {
+                    task_id: task.task_id as u32,
+                    stage_id: task.partition.stage_id as u32,
+                    stage_attempt_num: task.stage_attempt_num as u32,
+                    partition_id: task.partition.partition_id as u32,
+                    task_attempt: task.task_attempt as u32,
+                    executor_id: executor_id.clone(),
+                    reason: task.binding.as_str().to_owned(),
+                    queue_wait_ms: task.queue_wait_ms,
+                }),
+            );
+        }
+    }
+
+    /// Record the failure to launch tasks of a job bound to an executor
+    pub(crate) fn record_launch_failure(
+        &self,
+        job_id: &str,
+        executor_id: &str,
+        task_ids: Vec<u32>,
+        error: &str,
+    ) {
+        self.inner.lock().record(
+            job_id,
+            timestamp_millis(),
+            scheduling_trace_event::Event::TaskLaunchFailed(TaskLaunchFailed {
+                executor_id: executor_id.to_owned(),
+                task_ids,
+                error: error.to_owned(),
+            }),
+        );
+    }
+
+    /// The events of the trace of a job from the sequence number `from`, `None` if the job
+    /// has no trace
+    pub(crate) fn events_from(
+        &self,
+        job_id: &str,
+        from: u64,
+    ) -> Option<Vec<SchedulingTraceEvent>> {
+        let traces = self.inner.lock();
+        let trace = traces.jobs.get(job_id)?;
+        Some(
+            trace
+                .events
+                .iter()
+                .filter(|event| event.seq >= from)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    pub(crate) fn remove_job(&self, job_id: &str) {
+        let mut traces = self.inner.lock();
+        if traces.jobs.remove(job_id).is_some() {
+            traces.order.retain(|traced| traced != job_id);
+        }
+    }
+}
+
+impl Traces {
+    fn record(
+        &mut self,
+        job_id: &str,
+        timestamp: u64,
+        event: scheduling_trace_event::Event,
+    ) {
+        if !self.jobs.contains_key(job_id) {
+            while self.order.len() >= MAX_TRACED_JOBS {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.jobs.remove(&oldest);
+                }
+            }
+            self.order.push_back(job_id.to_owned());
+        }
+        let trace = self.jobs.entry(job_id.to_owned()).or_default();
+        if trace.events.len() >= MAX_EVENTS_PER_JOB {
+            trace.events.pop_front();
+        }
+        trace.events.push_back(SchedulingTraceEvent {
+            seq: trace.next_seq,
+            timestamp,
+            event: Some(event),
+        });
+        trace.next_seq += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ballista_core::serde::protobuf::scheduling_trace_event::Event;
+
+    use super::{SchedulingTraces, MAX_EVENTS_PER_JOB};
+
+    #[test]
+    fn test_scheduling_traces() {
+        let traces = SchedulingTraces::default();
+        assert!(traces.events_from("job", 0).is_none());
+
+        traces.record_launch_failure("job", "executor-1", vec![0, 1], "unreachable");
+        traces.record_launch_failure("other", "executor-2", vec![0], "unreachable");
+        traces.record_launch_failure("job", "executor-2", vec![2], "unreachable");
+
+        let events = traces.events_from("job", 0).unwrap();
+        assert_eq!(events.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![0, 1]);
+        match &events[1].event {
+            Some(Event::TaskLaunchFailed(failed)) => {
+                assert_eq!(failed.executor_id, "executor-2");
+                assert_eq!(failed.task_ids, vec![2]);
+            }
+            other => panic!("Unexpected event {other:?}"),
+        }
+        assert_eq!(traces.events_from("job", 1).unwrap().len(), 1);
+        assert!(traces.events_from("job", 2).unwrap().is_empty());
+
+        // the oldest events are dropped
+        for _ in 0..MAX_EVENTS_PER_JOB {
+            traces.record_launch_failure("job", "executor-1", vec![3], "unreachable");
+        }
+        let events = traces.events_from("job", 0).unwrap();
+        assert_eq!(events.len(), MAX_EVENTS_PER_JOB);
+        assert_eq!(events[0].seq, 2);
+
+        traces.remove_job("job");
+        assert!(traces.events_from("job", 0).is_none());
+        assert_eq!(traces.events_from("other", 0).unwrap().len(), 1);
+    }
+}
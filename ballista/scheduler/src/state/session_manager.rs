@@ -16,21 +16,121 @@
 // under the License.
 
 use crate::scheduler_server::SessionBuilder;
-use ballista_core::config::BallistaConfig;
-use ballista_core::error::Result;
+use ballista_core::config::{
+    BallistaConfig, BALLISTA_CLIENT_COLLECT_STAGE, DATAFUSION_CONFIG_PREFIX,
+};
+use ballista_core::error::{BallistaError, Result};
 use datafusion::prelude::{SessionConfig, SessionContext};
 
 use crate::cluster::JobState;
+use dashmap::DashMap;
+use datafusion::datasource::TableProvider;
+use log::warn;
+use std::collections::HashMap;
 use std::sync::Arc;
 
+/// A temporary table of a session, holding the output of the job which created it
+#[derive(Clone)]
+struct TemporaryTable {
+    job_id: String,
+    table: Arc<dyn TableProvider>,
+}
+
+/// A temporary table whose job is still running, registered once the job succeeds
+#[derive(Clone)]
+struct PendingTemporaryTable {
+    session_id: String,
+    name: String,
+    table: Arc<dyn TableProvider>,
+}
+
 #[derive(Clone)]
 pub struct SessionManager {
     state: Arc<dyn JobState>,
+    /// Temporary tables of each session by name, kept by the scheduler since sessions
+    /// may be rebuilt from their settings
+    temporary_tables: Arc<DashMap<String, HashMap<String, TemporaryTable>>>,
+    /// Temporary tables by the job creating them, until the job ends
+    pending_temporary_tables: Arc<DashMap<String, PendingTemporaryTable>>,
 }
 
 impl SessionManager {
     pub fn new(state: Arc<dyn JobState>) -> Self {
-        Self { state }
+        Self {
+            state,
+            temporary_tables: Arc::new(DashMap::new()),
+            pending_temporary_tables: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Add a temporary table of the session, backed by the output of the given job.
+    /// The table is only registered in the session once the job succeeds.
+    pub fn add_temporary_table(
+        &self,
+        session_id: &str,
+        session_ctx: &SessionContext,
+        name: &str,
+        or_replace: bool,
+        job_id: &str,
+        table: Arc<dyn TableProvider>,
+    ) -> Result<()> {
+        if !or_replace && session_ctx.table_exist(name)? {
+            return Err(BallistaError::General(format!(
+                "Table {name} already exists"
+            )));
+        }
+        self.pending_temporary_tables.insert(
+            job_id.to_string(),
+            PendingTemporaryTable {
+                session_id: session_id.to_string(),
+                name: name.to_string(),
+                table,
+            },
+        );
+        Ok(())
+    }
+
+    /// Register the temporary table created by the job once it succeeds, if any.
+    /// Returns the job backing the table it replaces, if any.
+    pub fn register_temporary_table(&self, job_id: &str) -> Option<String> {
+        let (_, pending) = self.pending_temporary_tables.remove(job_id)?;
+        let replaced = self
+            .temporary_tables
+            .entry(pending.session_id)
+            .or_default()
+            .insert(
+                pending.name,
+                TemporaryTable {
+                    job_id: job_id.to_string(),
+                    table: pending.table,
+                },
+            );
+        replaced.map(|table| table.job_id)
+    }
+
+    /// Drop the temporary table created by the job if it failed or was cancelled
+    pub fn abort_temporary_table(&self, job_id: &str) {
+        self.pending_temporary_tables.remove(job_id);
+    }
+
+    /// Whether the output of the job backs a temporary table, in which case it must be
+    /// kept until the session is removed
+    pub fn is_temporary_table_job(&self, job_id: &str) -> bool {
+        self.pending_temporary_tables.contains_key(job_id)
+            || self
+                .temporary_tables
+                .iter()
+                .any(|tables| tables.values().any(|table| table.job_id == job_id))
+    }
+
+    /// Drop the temporary tables of the session, returning the jobs which backed them
+    pub fn remove_temporary_tables(&self, session_id: &str) -> Vec<String> {
+        self.pending_temporary_tables
+            .retain(|_, pending| pending.session_id != session_id);
+        self.temporary_tables
+            .remove(session_id)
+            .map(|(_, tables)| tables.into_values().map(|table| table.job_id).collect())
+            .unwrap_or_default()
     }
 
     pub async fn remove_session(
@@ -48,6 +148,34 @@ impl SessionManager {
         self.state.update_session(session_id, config).await
     }
 
+    /// Change a setting of the session, e.g. with a SQL `SET` statement, on top of the
+    /// settings of `session_ctx`. The updated settings are saved in the cluster state.
+    pub async fn set_session_setting(
+        &self,
+        session_id: &str,
+        session_ctx: &SessionContext,
+        name: &str,
+        value: &str,
+    ) -> Result<Arc<SessionContext>> {
+        if !name.starts_with(DATAFUSION_CONFIG_PREFIX)
+            && !BallistaConfig::valid_entries().contains_key(name)
+        {
+            return Err(BallistaError::General(format!(
+                "Unknown setting {name}, only the Ballista settings and the DataFusion \
+                settings prefixed with {DATAFUSION_CONFIG_PREFIX} can be set"
+            )));
+        }
+        let mut settings = session_ctx
+            .state()
+            .config()
+            .get_extension::<BallistaConfig>()
+            .map(|config| config.settings().clone())
+            .unwrap_or_default();
+        settings.insert(name.to_owned(), value.to_owned());
+        let config = BallistaConfig::with_settings(settings)?;
+        self.update_session(session_id, &config).await
+    }
+
     pub async fn create_session(
         &self,
         config: &BallistaConfig,
@@ -56,7 +184,13 @@ impl SessionManager {
     }
 
     pub async fn get_session(&self, session_id: &str) -> Result<Arc<SessionContext>> {
-        self.state.get_session(session_id).await
+        let session_ctx = self.state.get_session(session_id).await?;
+        if let Some(tables) = self.temporary_tables.get(session_id) {
+            for (name, table) in tables.iter() {
+                session_ctx.register_table(name.as_str(), table.table.clone())?;
+            }
+        }
+        Ok(session_ctx)
     }
 }
 
@@ -65,9 +199,7 @@ pub fn create_datafusion_context(
     ballista_config: &BallistaConfig,
     session_builder: SessionBuilder,
 ) -> Arc<SessionContext> {
-    let config =
-        SessionConfig::from_string_hash_map(ballista_config.settings().clone()).unwrap();
-    let config = config
+    let config = SessionConfig::new()
         .with_target_partitions(ballista_config.default_shuffle_partitions())
         .with_batch_size(ballista_config.default_batch_size())
         .with_repartition_joins(ballista_config.repartition_joins())
@@ -79,7 +211,68 @@ pub fn create_datafusion_context(
             "datafusion.optimizer.hash_join_single_partition_threshold",
             ballista_config.hash_join_single_partition_threshold(),
         )
-        .set_bool("datafusion.optimizer.enable_round_robin_repartition", false);
+        .set_bool("datafusion.optimizer.enable_round_robin_repartition", false)
+        // the Ballista settings of the session are kept for the jobs of the session
+        .with_extension(Arc::new(ballista_config.clone()));
+    // explicit DataFusion settings take precedence over the ones derived from Ballista settings
+    let config = ballista_config.apply_datafusion_settings(config);
     let session_state = session_builder(config);
     Arc::new(SessionContext::new_with_state(session_state))
 }
+
+/// Create the context of a job of the session, whose DataFusion options are overridden by
+/// the DataFusion settings sent along with the query, e.g. the options of the client session.
+/// The session itself is left unchanged.
+pub fn with_query_settings(
+    session_ctx: Arc<SessionContext>,
+    settings: &HashMap<String, String>,
+) -> Arc<SessionContext> {
+    let mut settings = settings
+        .iter()
+        .filter(|(name, _)| name.starts_with(DATAFUSION_CONFIG_PREFIX))
+        .peekable();
+    if settings.peek().is_none() {
+        return session_ctx;
+    }
+
+    let mut state = session_ctx.state();
+    for (name, value) in settings {
+        if let Err(e) = state.config_mut().options_mut().set(name, value) {
+            warn!("Ignoring setting {name}={value} of the query: {e}");
+        }
+    }
+    Arc::new(SessionContext::new_with_state(state))
+}
+
+/// Create the context of a job whose final stage is detached for the client to run only
+/// if the client submitting the job said it runs it, whatever the setting of the session,
+/// which may be shared with clients that do not, e.g. Flight SQL clients.
+pub fn with_collect_stage(
+    session_ctx: Arc<SessionContext>,
+    collect_stage: bool,
+) -> Arc<SessionContext> {
+    let state = session_ctx.state();
+    let Some(config) = state.config().get_extension::<BallistaConfig>() else {
+        return session_ctx;
+    };
+    if config.client_collect_stage() == collect_stage {
+        return session_ctx;
+    }
+
+    let mut settings = config.settings().clone();
+    settings.insert(
+        BALLISTA_CLIENT_COLLECT_STAGE.to_owned(),
+        collect_stage.to_string(),
+    );
+    let config = match BallistaConfig::with_settings(settings) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Ignoring {BALLISTA_CLIENT_COLLECT_STAGE}={collect_stage}: {e}");
+            return session_ctx;
+        }
+    };
+    let mut state = state;
+    let session_config = std::mem::take(state.config_mut());
+    *state.config_mut() = session_config.with_extension(Arc::new(config));
+    Arc::new(SessionContext::new_with_state(state))
+}
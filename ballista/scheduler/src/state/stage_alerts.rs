@@ -0,0 +1,443 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Threshold rules on the metrics of the stages, e.g. `spilled_bytes>1073741824`,
+//! `task_retries>3` or `stage_duration_ms>600000`, evaluated as the task statuses of the
+//! stages arrive. The duration of the running stages is also evaluated periodically, so
+//! that a stage whose tasks hang raises its alert. A rule raises one alert per stage
+//! attempt the first time it is exceeded.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+use ballista_core::serde::protobuf::{task_status, TaskStatus};
+use datafusion::physical_plan::metrics::MetricsSet;
+use parking_lot::Mutex;
+
+use crate::scheduler_server::timestamp_millis;
+use crate::state::execution_graph::{ExecutionGraph, ExecutionStage, TaskInfo};
+
+/// The metric of a stage checked by an alert rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StageAlertMetric {
+    /// Number of retryable task failures of the stage attempt
+    TaskRetries,
+    /// Time in milliseconds since the first task of the stage attempt was launched, or
+    /// scheduled if no status of the task arrived yet, until its last task finished if it
+    /// is not running anymore
+    StageDurationMs,
+    /// Sum of an operator metric over the finished tasks of the stage attempt, as named by
+    /// DataFusion, e.g. `spilled_bytes`, `spill_count` or `output_rows`
+    Operator(String),
+}
+
+impl StageAlertMetric {
+    pub fn name(&self) -> &str {
+        match self {
+            StageAlertMetric::TaskRetries => "task_retries",
+            StageAlertMetric::StageDurationMs => "stage_duration_ms",
+            StageAlertMetric::Operator(name) => name,
+        }
+    }
+}
+
+/// A rule raising an alert when a metric of a stage exceeds a threshold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageAlertRule {
+    pub metric: StageAlertMetric,
+    pub threshold: u64,
+}
+
+impl FromStr for StageAlertRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (metric, threshold) = s.split_once('>').ok_or_else(|| {
+            format!("Invalid stage alert rule {s}, expected metric>threshold")
+        })?;
+        let metric = match metric.trim() {
+            "" => return Err(format!("Missing metric in stage alert rule {s}")),
+            "task_retries" => StageAlertMetric::TaskRetries,
+            "stage_duration_ms" => StageAlertMetric::StageDurationMs,
+            name => StageAlertMetric::Operator(name.to_owned()),
+        };
+        let threshold = threshold
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid threshold in stage alert rule {s}: {e}"))?;
+        Ok(Self { metric, threshold })
+    }
+}
+
+impl Display for StageAlertRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}>{}", self.metric.name(), self.threshold)
+    }
+}
+
+/// Parse a comma separated list of stage alert rules
+pub fn parse_stage_alert_rules(rules: &str) -> Result<Vec<StageAlertRule>, String> {
+    rules
+        .split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(StageAlertRule::from_str)
+        .collect()
+}
+
+/// An alert raised by a stage exceeding the threshold of a rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageAlert {
+    pub job_id: String,
+    pub stage_id: usize,
+    pub stage_attempt_num: usize,
+    pub rule: StageAlertRule,
+    pub value: u64,
+}
+
+impl Display for StageAlert {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "stage {}/{} attempt {} exceeded {} with {}",
+            self.job_id, self.stage_id, self.stage_attempt_num, self.rule, self.value
+        )
+    }
+}
+
+/// (job ID, stage ID, stage attempt number)
+type StageAttempt = (String, usize, usize);
+
+/// Min interval between two periodic evaluations of the duration of the running stages
+const MIN_DURATION_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Evaluates the stage alert rules against the stages of the jobs curated by a scheduler
+#[derive(Default)]
+pub(crate) struct StageAlertEvaluator {
+    rules: Vec<StageAlertRule>,
+    /// Number of retryable task failures of the stage attempts
+    task_retries: Mutex<HashMap<StageAttempt, u64>>,
+    /// The rules already raised by the stage attempts, by index
+    raised: Mutex<HashSet<(StageAttempt, usize)>>,
+}
+
+impl StageAlertEvaluator {
+    pub(crate) fn new(rules: Vec<StageAlertRule>) -> Self {
+        Self {
+            rules,
+            ..Default::default()
+        }
+    }
+
+    /// Evaluate the rules against the stages updated by `statuses`, once they are applied
+    /// to `graph`, and return the alerts raised
+    pub(crate) fn evaluate(
+        &self,
+        graph: &ExecutionGraph,
+        statuses: &[TaskStatus],
+    ) -> Vec<StageAlert> {
+        if self.rules.is_empty() {
+            return vec![];
+        }
+        let job_id = graph.job_id();
+        let mut updated_stages = HashSet::new();
+        {
+            let mut task_retries = self.task_retries.lock();
+            for status in statuses {
+                let attempt = (
+                    job_id.to_owned(),
+                    status.stage_id as usize,
+                    status.stage_attempt_num as usize,
+                );
+                if let Some(task_status::Status::Failed(failed)) = &status.status {
+                    if failed.retryable {
+                        *task_retries.entry(attempt.clone()).or_default() += 1;
+                    }
+                }
+                updated_stages.insert(attempt);
+            }
+        }
+
+        let mut alerts = vec![];
+        for attempt in updated_stages {
+            let (_, stage_id, stage_attempt_num) = &attempt;
+            let Some(stage) = graph.stages().get(stage_id) else {
+                continue;
+            };
+            if stage_attempt_num_of(stage) != Some(*stage_attempt_num) {
+                continue;
+            }
+            for (index, rule) in self.rules.iter().enumerate() {
+                let value = match &rule.metric {
+                    StageAlertMetric::TaskRetries => self
+                        .task_retries
+                        .lock()
+                        .get(&attempt)
+                        .copied()
+                        .unwrap_or_default(),
+                    StageAlertMetric::StageDurationMs => stage_duration_ms(stage),
+                    StageAlertMetric::Operator(name) => operator_metric(stage, name),
+                };
+                alerts.extend(self.raise(&attempt, index, value));
+            }
+        }
+        alerts
+    }
+
+    /// The interval of the periodic evaluation of the duration of the running stages, a
+    /// tenth of the lowest `stage_duration_ms` threshold, or `None` without such rules
+    pub(crate) fn duration_check_interval(&self) -> Option<Duration> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.metric == StageAlertMetric::StageDurationMs)
+            .map(|rule| Duration::from_millis(rule.threshold / 10))
+            .min()
+            .map(|interval| interval.max(MIN_DURATION_CHECK_INTERVAL))
+    }
+
+    /// Evaluate the `stage_duration_ms` rules against the running stages of `graph`,
+    /// which exceed them while their tasks hang without any task status arriving, and
+    /// return the alerts raised
+    pub(crate) fn evaluate_running_stages(
+        &self,
+        graph: &ExecutionGraph,
+    ) -> Vec<StageAlert> {
+        let mut alerts = vec![];
+        for (stage_id, stage) in graph.stages() {
+            let ExecutionStage::Running(running) = stage else {
+                continue;
+            };
+            let attempt = (
+                graph.job_id().to_owned(),
+                *stage_id,
+                running.stage_attempt_num,
+            );
+            for (index, rule) in self.rules.iter().enumerate() {
+                if rule.metric == StageAlertMetric::StageDurationMs {
+                    alerts.extend(self.raise(&attempt, index, stage_duration_ms(stage)));
+                }
+            }
+        }
+        alerts
+    }
+
+    /// The alert of the rule at `index` if `value` exceeds its threshold, unless the
+    /// stage attempt already raised it
+    fn raise(
+        &self,
+        attempt: &StageAttempt,
+        index: usize,
+        value: u64,
+    ) -> Option<StageAlert> {
+        let rule = &self.rules[index];
+        if value <= rule.threshold {
+            return None;
+        }
+        if !self.raised.lock().insert((attempt.clone(), index)) {
+            return None;
+        }
+        let (job_id, stage_id, stage_attempt_num) = attempt;
+        Some(StageAlert {
+            job_id: job_id.clone(),
+            stage_id: *stage_id,
+            stage_attempt_num: *stage_attempt_num,
+            rule: rule.clone(),
+            value,
+        })
+    }
+
+    /// Forget the state of the stages of a job which is not curated anymore
+    pub(crate) fn remove_job(&self, job_id: &str) {
+        self.task_retries
+            .lock()
+            .retain(|(stage_job_id, _, _), _| stage_job_id != job_id);
+        self.raised
+            .lock()
+            .retain(|((stage_job_id, _, _), _)| stage_job_id != job_id);
+    }
+}
+
+fn stage_attempt_num_of(stage: &ExecutionStage) -> Option<usize> {
+    match stage {
+        ExecutionStage::Running(stage) => Some(stage.stage_attempt_num),
+        ExecutionStage::Successful(stage) => Some(stage.stage_attempt_num),
+        ExecutionStage::Failed(stage) => Some(stage.stage_attempt_num),
+        ExecutionStage::UnResolved(_) | ExecutionStage::Resolved(_) => None,
+    }
+}
+
+fn stage_duration_ms(stage: &ExecutionStage) -> u64 {
+    let (task_infos, running): (Vec<&TaskInfo>, bool) = match stage {
+        ExecutionStage::Running(stage) => {
+            (stage.task_infos.iter().flatten().collect(), true)
+        }
+        ExecutionStage::Successful(stage) => (stage.task_infos.iter().collect(), false),
+        ExecutionStage::Failed(stage) => {
+            (stage.task_infos.iter().flatten().collect(), false)
+        }
+        ExecutionStage::UnResolved(_) | ExecutionStage::Resolved(_) => return 0,
+    };
+    // the launch time of a task is only known once its status arrives
+    let Some(launched_at) = task_infos
+        .iter()
+        .map(|info| match info.launch_time {
+            0 => info.scheduled_time,
+            launch_time => launch_time,
+        })
+        .filter(|launch_time| *launch_time > 0)
+        .min()
+    else {
+        return 0;
+    };
+    let ended_at = if running {
+        timestamp_millis() as u128
+    } else {
+        task_infos
+            .iter()
+            .map(|info| info.end_exec_time)
+            .max()
+            .unwrap_or_default()
+    };
+    ended_at.saturating_sub(launched_at) as u64
+}
+
+fn operator_metric(stage: &ExecutionStage, name: &str) -> u64 {
+    let stage_metrics: &[MetricsSet] = match stage {
+        ExecutionStage::Running(stage) => {
+            stage.stage_metrics.as_deref().unwrap_or_default()
+        }
+        ExecutionStage::Successful(stage) => &stage.stage_metrics,
+        ExecutionStage::Failed(stage) => {
+            stage.stage_metrics.as_deref().unwrap_or_default()
+        }
+        ExecutionStage::UnResolved(_) | ExecutionStage::Resolved(_) => &[],
+    };
+    stage_metrics
+        .iter()
+        .filter_map(|metrics| metrics.sum_by_name(name))
+        .map(|value| value.as_usize() as u64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ballista_core::error::Result;
+    use ballista_core::serde::protobuf::{failed_task, ExecutionError, FailedTask};
+
+    use super::{
+        parse_stage_alert_rules, StageAlertEvaluator, StageAlertMetric, StageAlertRule,
+    };
+    use crate::test_utils::{mock_executor, mock_failed_task, test_aggregation_plan};
+
+    #[test]
+    fn test_parse_stage_alert_rules() {
+        let rules = parse_stage_alert_rules(
+            "spilled_bytes>1073741824, task_retries > 3,stage_duration_ms>600000,",
+        )
+        .unwrap();
+        assert_eq!(
+            rules,
+            vec![
+                StageAlertRule {
+                    metric: StageAlertMetric::Operator("spilled_bytes".to_owned()),
+                    threshold: 1073741824,
+                },
+                StageAlertRule {
+                    metric: StageAlertMetric::TaskRetries,
+                    threshold: 3,
+                },
+                StageAlertRule {
+                    metric: StageAlertMetric::StageDurationMs,
+                    threshold: 600000,
+                },
+            ]
+        );
+        assert_eq!(rules[1].to_string(), "task_retries>3");
+        assert!(parse_stage_alert_rules("").unwrap().is_empty());
+        assert!(parse_stage_alert_rules("spilled_bytes").is_err());
+        assert!(parse_stage_alert_rules(">3").is_err());
+        assert!(parse_stage_alert_rules("task_retries>many").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_task_retries_alert() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut graph = test_aggregation_plan(4).await;
+        graph.revive();
+        let evaluator =
+            StageAlertEvaluator::new(parse_stage_alert_rules("task_retries>1").unwrap());
+
+        let mut alerts = vec![];
+        for _ in 0..3 {
+            let task = graph.pop_next_task(&executor.id)?.unwrap();
+            let status = mock_failed_task(
+                task,
+                FailedTask {
+                    error: "Error".to_string(),
+                    retryable: true,
+                    count_to_failures: true,
+                    failed_reason: Some(failed_task::FailedReason::ExecutionError(
+                        ExecutionError {},
+                    )),
+                },
+            );
+            graph.update_task_status(&executor, vec![status.clone()], 4, 4)?;
+            alerts.push(evaluator.evaluate(&graph, &[status]));
+        }
+
+        // the rule is raised once, by the second retry
+        assert!(alerts[0].is_empty());
+        assert_eq!(alerts[1].len(), 1);
+        assert_eq!(alerts[1][0].value, 2);
+        assert!(alerts[2].is_empty());
+
+        evaluator.remove_job(graph.job_id());
+        assert!(evaluator.raised.lock().is_empty());
+        assert!(evaluator.task_retries.lock().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_running_stage_duration_alert() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut graph = test_aggregation_plan(4).await;
+        graph.revive();
+        let evaluator = StageAlertEvaluator::new(
+            parse_stage_alert_rules("task_retries>1,stage_duration_ms>50").unwrap(),
+        );
+        assert_eq!(
+            evaluator.duration_check_interval(),
+            Some(Duration::from_secs(1))
+        );
+
+        // the task hangs, no task status arrives
+        let task = graph.pop_next_task(&executor.id)?.unwrap();
+        assert!(evaluator.evaluate_running_stages(&graph).is_empty());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let alerts = evaluator.evaluate_running_stages(&graph);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].stage_id, task.partition.stage_id);
+        assert_eq!(alerts[0].rule.metric, StageAlertMetric::StageDurationMs);
+        // the alert is raised once per stage attempt
+        assert!(evaluator.evaluate_running_stages(&graph).is_empty());
+        Ok(())
+    }
+}
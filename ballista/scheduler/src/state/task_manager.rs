@@ -17,37 +17,51 @@
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 
+use crate::config::TaskDistributionPolicy;
+use crate::planner::{DefaultStagePlanner, StagePlanner};
+use crate::runtime_filter::RuntimeFilterStagePlanner;
 use crate::state::execution_graph::{
-    ExecutionGraph, ExecutionStage, RunningTaskInfo, TaskDescription,
+    ExecutionGraph, ExecutionStage, ResultLimits, RunningTaskInfo, TaskDescription,
 };
 use crate::state::executor_manager::ExecutorManager;
+use crate::state::job_archive::JobArchive;
+use crate::state::scheduling_trace::SchedulingTraces;
+use crate::state::stage_alerts::{StageAlert, StageAlertEvaluator, StageAlertRule};
 
 use ballista_core::error::BallistaError;
 use ballista_core::error::Result;
 
-use crate::cluster::JobState;
+use crate::cluster::event::ClusterEventSender;
+use crate::cluster::{JobState, JobStateEvent, JobStateEventStream};
 use ballista_core::serde::protobuf::{
-    job_status, JobStatus, KeyValuePair, MultiTaskDefinition, TaskDefinition, TaskId,
-    TaskStatus,
+    job_status, FailedJobTask, GetJobPlanResult, JobLogicalPlan, JobStatus, KeyValuePair,
+    MultiTaskDefinition, StagePlan, TaskDefinition, TaskId, TaskStatus,
 };
-use ballista_core::serde::scheduler::ExecutorMetadata;
+use ballista_core::serde::scheduler::{ExecutorMetadata, PartitionLocation};
 use ballista_core::serde::BallistaCodec;
+use ballista_core::utils::session_config_props;
 use dashmap::DashMap;
+use futures::stream::{self, Stream, StreamExt};
 
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::physical_plan::display::DisplayableExecutionPlan;
 use datafusion::physical_plan::ExecutionPlan;
+use datafusion::prelude::{SessionConfig, SessionContext};
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 use log::{debug, error, info, warn};
+use parking_lot::Mutex;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use std::collections::{HashMap, HashSet};
 use std::ops::Deref;
+use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
-use ballista_core::config::BALLISTA_DATA_CACHE_ENABLED;
+use ballista_core::config::{BallistaConfig, BALLISTA_DATA_CACHE_ENABLED};
 use tracing::trace;
 
 type ActiveJobCache = Arc<DashMap<String, JobInfoCache>>;
@@ -118,6 +132,19 @@ pub struct TaskManager<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     // Cache for active jobs curated by this scheduler
     active_job_cache: ActiveJobCache,
     launcher: Arc<dyn TaskLauncher>,
+    // Sender of the IDs of the jobs whose status or stages were updated by this scheduler
+    job_updates: Arc<ClusterEventSender<String>>,
+    // Maximum number of stages and tasks of submitted jobs, zero means no limit
+    max_job_stages: usize,
+    max_job_tasks: usize,
+    // Threshold rules on the metrics of the stages of the jobs curated by this scheduler
+    stage_alerts: Arc<StageAlertEvaluator>,
+    // Traces of the scheduling decisions of the jobs curated by this scheduler
+    scheduling_traces: Arc<SchedulingTraces>,
+    // Splits the physical plans of the submitted jobs into query stages
+    stage_planner: Arc<dyn StagePlanner>,
+    // Archive of the finished jobs whose state is cleaned up from the backend
+    job_archive: Option<Arc<JobArchive>>,
 }
 
 #[derive(Clone)]
@@ -126,17 +153,36 @@ pub struct JobInfoCache {
     pub execution_graph: Arc<RwLock<ExecutionGraph>>,
     // Cache for job status
     pub status: Option<job_status::Status>,
-    // Cache for encoded execution stage plan to avoid duplicated encoding for multiple tasks
-    encoded_stage_plans: HashMap<usize, Vec<u8>>,
+    // Cache for encoded execution stage plan to avoid duplicated encoding for multiple tasks,
+    // behind a lock of the job so that it is updated without locking the shard of the job
+    encoded_stage_plans: Arc<Mutex<HashMap<usize, Vec<u8>>>>,
+    // DataFusion options of the job session kept with its graph, sent with every task
+    session_props: Vec<KeyValuePair>,
+    // Zone of the client of the job session, the final stage tasks are bound to executors of this zone
+    pub result_zone: Option<String>,
+    // Task distribution policy requested by the job session, overriding the one of the scheduler
+    pub task_distribution: Option<TaskDistributionPolicy>,
 }
 
 impl JobInfoCache {
-    pub fn new(graph: ExecutionGraph) -> Self {
+    pub fn new(graph: ExecutionGraph, session_config: &SessionConfig) -> Self {
         let status = graph.status().status.clone();
+        let session_props = graph.session_props().to_vec();
         Self {
             execution_graph: Arc::new(RwLock::new(graph)),
             status,
-            encoded_stage_plans: HashMap::new(),
+            encoded_stage_plans: Arc::new(Mutex::new(HashMap::new())),
+            session_props,
+            result_zone: session_config
+                .get_extension::<BallistaConfig>()
+                .and_then(|config| config.client_zone()),
+            task_distribution: TaskDistributionPolicy::from_session_config(
+                session_config,
+            )
+            .unwrap_or_else(|e| {
+                warn!("Ignoring the task distribution of the job session: {e}");
+                None
+            }),
         }
     }
 }
@@ -146,10 +192,27 @@ pub struct UpdatedStages {
     pub resolved_stages: HashSet<usize>,
     pub successful_stages: HashSet<usize>,
     pub failed_stages: HashMap<usize, String>,
+    /// Map from the ID of a failed stage -> (the task which failed the stage, the task error)
+    pub failed_stage_tasks: HashMap<usize, (FailedJobTask, String)>,
     pub rollback_running_stages: HashMap<usize, HashSet<String>>,
     pub resubmit_successful_stages: HashSet<usize>,
 }
 
+/// The task slots used by an active job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct JobSlotUsage {
+    pub job_id: String,
+    pub start_time: u64,
+    pub running_tasks: usize,
+    /// The running tasks waiting for the outputs of their inputs, see
+    /// [ExecutionGraph::waiting_tasks]
+    pub waiting_tasks: usize,
+    /// The tasks ready to be bound to task slots
+    pub available_tasks: usize,
+    /// The successful tasks, see [ExecutionGraph::completed_tasks]
+    pub completed_tasks: usize,
+}
+
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U> {
     pub fn new(
         state: Arc<dyn JobState>,
@@ -162,6 +225,13 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             scheduler_id: scheduler_id.clone(),
             active_job_cache: Arc::new(DashMap::new()),
             launcher: Arc::new(DefaultTaskLauncher::new(scheduler_id)),
+            job_updates: Arc::new(ClusterEventSender::default()),
+            max_job_stages: 0,
+            max_job_tasks: 0,
+            stage_alerts: Arc::new(StageAlertEvaluator::default()),
+            scheduling_traces: Arc::new(SchedulingTraces::default()),
+            stage_planner: Arc::new(DefaultStagePlanner),
+            job_archive: None,
         }
     }
 
@@ -178,7 +248,85 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             scheduler_id,
             active_job_cache: Arc::new(DashMap::new()),
             launcher,
+            job_updates: Arc::new(ClusterEventSender::default()),
+            max_job_stages: 0,
+            max_job_tasks: 0,
+            stage_alerts: Arc::new(StageAlertEvaluator::default()),
+            scheduling_traces: Arc::new(SchedulingTraces::default()),
+            stage_planner: Arc::new(DefaultStagePlanner),
+            job_archive: None,
+        }
+    }
+
+    /// Limit the number of stages and the total number of tasks of the submitted jobs, zero
+    /// means no limit. Larger jobs fail at submission.
+    pub fn with_max_job_size(mut self, max_stages: usize, max_tasks: usize) -> Self {
+        self.max_job_stages = max_stages;
+        self.max_job_tasks = max_tasks;
+        self
+    }
+
+    /// Shard the active jobs by job ID over `shards` locks, rounded up to a power of two, or
+    /// over the default number of shards, four times the number of cores, if zero. It must
+    /// be set before any job is active.
+    pub fn with_job_cache_shards(mut self, shards: usize) -> Self {
+        if shards > 0 {
+            self.active_job_cache = Arc::new(DashMap::with_shard_amount(
+                shards.next_power_of_two().max(2),
+            ));
         }
+        self
+    }
+
+    /// Raise a `StageAlert` event when a stage exceeds the threshold of one of `rules`
+    pub fn with_stage_alert_rules(mut self, rules: Vec<StageAlertRule>) -> Self {
+        self.stage_alerts = Arc::new(StageAlertEvaluator::new(rules));
+        self
+    }
+
+    /// Split the physical plans of the submitted jobs into query stages with `stage_planner`
+    pub fn with_stage_planner(mut self, stage_planner: Arc<dyn StagePlanner>) -> Self {
+        self.stage_planner = stage_planner;
+        self
+    }
+
+    /// Archive the finished jobs to `job_archive` before their state is cleaned up from the
+    /// backend, and serve the archived jobs no longer in the backend
+    pub fn with_job_archive(mut self, job_archive: Option<Arc<JobArchive>>) -> Self {
+        self.job_archive = job_archive;
+        self
+    }
+
+    /// The traces of the scheduling decisions of the jobs curated by this scheduler
+    pub(crate) fn scheduling_traces(&self) -> &Arc<SchedulingTraces> {
+        &self.scheduling_traces
+    }
+
+    /// Get a stream of the IDs of the jobs whose status changed or whose tasks were updated,
+    /// by this scheduler or by the schedulers sharing its cluster state
+    pub(crate) async fn job_updates(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = String> + Send>>> {
+        let cluster_updates =
+            self.state
+                .job_state_events()
+                .await?
+                .filter_map(|event| async move {
+                    match event {
+                        JobStateEvent::JobUpdated { job_id, .. }
+                        | JobStateEvent::JobAcquired { job_id, .. }
+                        | JobStateEvent::JobReleased { job_id } => Some(job_id),
+                        _ => None,
+                    }
+                });
+        Ok(Box::pin(stream::select(
+            self.job_updates.subscribe(),
+            cluster_updates,
+        )))
+    }
+
+    fn notify_job_updated(&self, job_id: &str) {
+        self.job_updates.send(&job_id.to_string());
     }
 
     /// Enqueue a job for scheduling
@@ -186,6 +334,13 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         self.state.accept_job(job_id, job_name, queued_at)
     }
 
+    /// Mark a queued job as being planned
+    pub fn start_planning(&self, job_id: &str, started_at: u64) -> Result<()> {
+        self.state.start_planning(job_id, started_at)?;
+        self.notify_job_updated(job_id);
+        Ok(())
+    }
+
     /// Get the number of queued jobs. If it's big, then it means the scheduler is too busy.
     /// In normal case, it's better to be 0.
     pub fn pending_job_number(&self) -> usize {
@@ -199,34 +354,184 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
 
     /// Generate an ExecutionGraph for the job and save it to the persistent state.
     /// By default, this job will be curated by the scheduler which receives it.
-    /// Then we will also save it to the active execution graph
+    /// Then we will also save it to the active execution graph. Unless the output of the
+    /// job is kept on the executors for a temporary table, its final stage may be
+    /// detached for the client to run it, see [ExecutionGraph::detach_collect_stage].
+    #[allow(clippy::too_many_arguments)]
     pub async fn submit_job(
         &self,
         job_id: &str,
         job_name: &str,
         session_id: &str,
+        session_config: &SessionConfig,
+        logical_plan: &LogicalPlan,
         plan: Arc<dyn ExecutionPlan>,
         queued_at: u64,
+        output_kept: bool,
     ) -> Result<()> {
-        let mut graph = ExecutionGraph::new(
+        TaskDistributionPolicy::from_session_config(session_config)
+            .map_err(BallistaError::General)?;
+        let runtime_filter_planner = RuntimeFilterStagePlanner::for_session(
+            self.stage_planner.as_ref(),
+            session_config,
+        );
+        let stage_planner: &dyn StagePlanner = match &runtime_filter_planner {
+            Some(runtime_filter_planner) => runtime_filter_planner,
+            None => self.stage_planner.as_ref(),
+        };
+        let mut graph = ExecutionGraph::new_with_stage_planner(
             &self.scheduler_id,
             job_id,
             job_name,
             session_id,
             plan,
             queued_at,
+            stage_planner,
         )?;
+        self.check_job_size(&graph)?;
+        graph.set_max_running_stage_tasks(
+            session_config
+                .get_extension::<BallistaConfig>()
+                .and_then(|config| config.job_max_concurrent_stage_tasks()),
+        );
+        if let Some(config) = session_config.get_extension::<BallistaConfig>() {
+            graph.set_max_running_tasks_by_stage(
+                config.job_max_concurrent_tasks_by_stage(),
+            );
+        }
+        graph.set_max_stage_tasks_per_executor(
+            session_config
+                .get_extension::<BallistaConfig>()
+                .and_then(|config| config.stage_max_tasks_per_executor()),
+        );
+        graph.set_pipelined_stages(
+            session_config
+                .get_extension::<BallistaConfig>()
+                .map(|config| config.stage_pipelined())
+                .unwrap_or(false),
+        );
+        if let Some(config) = session_config.get_extension::<BallistaConfig>() {
+            graph.set_result_limits(ResultLimits {
+                max_rows: config.results_max_rows().map(|max_rows| max_rows as u64),
+                max_bytes: config.results_max_bytes().map(|max_bytes| max_bytes as u64),
+                truncate: config.results_truncate(),
+            });
+        }
+        // only the clients which said they run the final stage get it detached, see
+        // `with_collect_stage`. They fetch the partitions it reads from the executors,
+        // never through the scheduler.
+        let collect_stage = session_config
+            .get_extension::<BallistaConfig>()
+            .map(|config| {
+                config.client_collect_stage() && !config.client_fetch_via_scheduler()
+            })
+            .unwrap_or(false);
+        if collect_stage && !output_kept {
+            if let Some(collect_plan) = graph.detach_collect_stage() {
+                let mut buf = vec![];
+                U::try_from_physical_plan(
+                    collect_plan,
+                    self.codec.physical_extension_codec(),
+                )?
+                .try_encode(&mut buf)?;
+                graph.set_collect_plan(buf);
+            }
+        }
+        graph.set_session_props(session_config_props(session_config));
         info!("Submitting execution graph: {:?}", graph);
 
+        let job_plan = JobLogicalPlan {
+            logical_plan: logical_plan.display_indent().to_string(),
+            encoded_logical_plan: self.encode_logical_plan(job_id, logical_plan),
+        };
+        self.state.save_job_plan(job_id, &job_plan).await?;
         self.state.submit_job(job_id.to_string(), &graph).await?;
 
         graph.revive();
         self.active_job_cache
-            .insert(job_id.to_owned(), JobInfoCache::new(graph));
+            .insert(job_id.to_owned(), JobInfoCache::new(graph, session_config));
+        self.notify_job_updated(job_id);
 
         Ok(())
     }
 
+    /// Encode the logical plan of a job, which is kept with the job only if it can be
+    /// encoded, e.g. unless it has extension nodes unknown to the codec
+    fn encode_logical_plan(&self, job_id: &str, plan: &LogicalPlan) -> Vec<u8> {
+        let mut buf = vec![];
+        let encoded =
+            T::try_from_logical_plan(plan, self.codec.logical_extension_codec())
+                .and_then(|proto| proto.try_encode(&mut buf));
+        if let Err(e) = encoded {
+            debug!("Not keeping the logical plan of job {job_id}: {e}");
+            buf.clear();
+        }
+        buf
+    }
+
+    /// Get the optimized logical plan of a job and the physical plans of its stages, as
+    /// displayed by DataFusion and encoded, `None` unless the job was submitted by the
+    /// session `session_id`
+    pub(crate) async fn get_job_plan(
+        &self,
+        job_id: &str,
+        session_id: &str,
+    ) -> Result<Option<GetJobPlanResult>> {
+        let Some(graph) = self.get_job_execution_graph(job_id).await? else {
+            return Ok(None);
+        };
+        if graph.session_id() != session_id {
+            return Ok(None);
+        }
+        let logical_plan = self.state.get_job_plan(job_id).await?.unwrap_or_default();
+        let mut stages = graph
+            .stages()
+            .iter()
+            .map(|(stage_id, stage)| {
+                let mut encoded_plan = vec![];
+                U::try_from_physical_plan(
+                    stage.shared_plan(),
+                    self.codec.physical_extension_codec(),
+                )?
+                .try_encode(&mut encoded_plan)?;
+                Ok(StagePlan {
+                    stage_id: *stage_id as u32,
+                    plan: DisplayableExecutionPlan::new(stage.plan())
+                        .indent(false)
+                        .to_string(),
+                    encoded_plan,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        stages.sort_by_key(|stage| stage.stage_id);
+        Ok(Some(GetJobPlanResult {
+            logical_plan: logical_plan.logical_plan,
+            encoded_logical_plan: logical_plan.encoded_logical_plan,
+            stages,
+        }))
+    }
+
+    /// Check the number of stages and tasks of a job against the limits of the scheduler
+    fn check_job_size(&self, graph: &ExecutionGraph) -> Result<()> {
+        let stages = graph.stage_count();
+        if self.max_job_stages > 0 && stages > self.max_job_stages {
+            return Err(BallistaError::General(format!(
+                "Job {} has {stages} stages, more than the maximum of {} stages per job",
+                graph.job_id(),
+                self.max_job_stages
+            )));
+        }
+        let tasks = graph.task_count();
+        if self.max_job_tasks > 0 && tasks > self.max_job_tasks {
+            return Err(BallistaError::General(format!(
+                "Job {} has {tasks} tasks, more than the maximum of {} tasks per job",
+                graph.job_id(),
+                self.max_job_tasks
+            )));
+        }
+        Ok(())
+    }
+
     pub fn get_running_job_cache(&self) -> Arc<HashMap<String, JobInfoCache>> {
         let ret = self
             .active_job_cache
@@ -263,6 +568,16 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         Ok(jobs)
     }
 
+    /// Get the IDs of the jobs in the state, active on this scheduler or not
+    pub(crate) async fn get_job_ids(&self) -> Result<HashSet<String>> {
+        self.state.get_jobs().await
+    }
+
+    /// Return the stream of [JobStateEvent]s of the jobs of all the schedulers
+    pub(crate) async fn job_state_events(&self) -> Result<JobStateEventStream> {
+        self.state.job_state_events().await
+    }
+
     /// Get the status of of a job. First look in the active cache.
     /// If no one found, then in the Active/Completed jobs, and then in Failed jobs
     pub async fn get_job_status(&self, job_id: &str) -> Result<Option<JobStatus>> {
@@ -275,6 +590,58 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         }
     }
 
+    /// Get the job submitted with a client supplied idempotency key, if any
+    pub(crate) async fn get_idempotency_key(&self, key: &str) -> Result<Option<String>> {
+        self.state.get_idempotency_key(key).await
+    }
+
+    /// Register the job submitted with a client supplied idempotency key, returning the job
+    /// previously submitted with the same key if any
+    pub(crate) async fn register_idempotency_key(
+        &self,
+        key: &str,
+        job_id: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>> {
+        self.state.register_idempotency_key(key, job_id, ttl).await
+    }
+
+    /// Get the status of a job along with the IDs of its successful stages, in order. The
+    /// stages of jobs which were not planned are empty.
+    pub(crate) async fn get_job_progress(
+        &self,
+        job_id: &str,
+    ) -> Result<Option<(JobStatus, Vec<usize>)>> {
+        if let Some(graph) = self.get_active_execution_graph(job_id) {
+            let graph = graph.read().await;
+            return Ok(Some((graph.status().clone(), successful_stages(&graph))));
+        }
+        let Some(status) = self.state.get_job_status(job_id).await? else {
+            return Ok(None);
+        };
+        let stages = self
+            .state
+            .get_execution_graph(job_id)
+            .await?
+            .map(|graph| successful_stages(&graph))
+            .unwrap_or_default();
+        Ok(Some((status, stages)))
+    }
+
+    /// Get the locations of the shuffle partition `partition_id` of stage `stage_id` of an
+    /// active job known so far, and whether the stage completed. `None` if the job is not
+    /// active or the stage is not an input of any of its stages.
+    pub(crate) async fn get_shuffle_locations(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        partition_id: usize,
+    ) -> Option<(Vec<PartitionLocation>, bool)> {
+        let graph = self.get_active_execution_graph(job_id)?;
+        let graph = graph.read().await;
+        graph.shuffle_locations(stage_id, partition_id)
+    }
+
     /// Get the execution graph of of a job. First look in the active cache.
     /// If no one found, then in the Active/Completed jobs.
     pub(crate) async fn get_job_execution_graph(
@@ -285,20 +652,67 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             let guard = cached.read().await;
 
             Ok(Some(Arc::new(guard.deref().clone())))
+        } else if let Some(graph) = self.state.get_execution_graph(job_id).await? {
+            Ok(Some(Arc::new(graph)))
         } else {
-            let graph = self.state.get_execution_graph(job_id).await?;
+            Ok(self
+                .get_archived_execution_graph(job_id)
+                .await?
+                .map(Arc::new))
+        }
+    }
 
-            Ok(graph.map(Arc::new))
+    /// The executor which ran the latest attempt of a task of a job, if it is recorded in
+    /// the execution graph of the job
+    pub(crate) async fn get_task_executor(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        task_id: usize,
+    ) -> Result<Option<String>> {
+        if let Some(graph) = self.get_active_execution_graph(job_id) {
+            return Ok(graph.read().await.task_executor(stage_id, task_id));
         }
+        Ok(self
+            .get_job_execution_graph(job_id)
+            .await?
+            .and_then(|graph| graph.task_executor(stage_id, task_id)))
     }
 
-    /// Update given task statuses in the respective job and return a tuple containing:
-    /// 1. A list of QueryStageSchedulerEvent to publish.
-    /// 2. A list of reservations that can now be offered.
+    /// Get the execution graph of a job archived once its state was cleaned up. The plans
+    /// are decoded in the session of the job if it still exists, in a default session
+    /// otherwise.
+    async fn get_archived_execution_graph(
+        &self,
+        job_id: &str,
+    ) -> Result<Option<ExecutionGraph>> {
+        let Some(archive) = &self.job_archive else {
+            return Ok(None);
+        };
+        let Some(proto) = archive.get(job_id, self.codec.plan_protection()).await? else {
+            return Ok(None);
+        };
+        let session = match self.state.get_session(&proto.session_id).await {
+            Ok(session) => session,
+            Err(_) => Arc::new(SessionContext::new()),
+        };
+        let graph =
+            ExecutionGraph::decode_execution_graph(proto, &self.codec, session.as_ref())
+                .await?;
+        Ok(Some(graph))
+    }
+
+    /// Update given task statuses in the respective job and return a list of
+    /// QueryStageSchedulerEvent to publish.
+    ///
+    /// If `released_slots` is provided, the task slots of the tasks are returned to the
+    /// executor in the same transaction as their statuses when the job state supports it, and
+    /// counted in `released_slots`. The caller releases the other slots.
     pub(crate) async fn update_task_statuses(
         &self,
         executor: &ExecutorMetadata,
         task_status: Vec<TaskStatus>,
+        mut released_slots: Option<&mut u32>,
     ) -> Result<Vec<QueryStageSchedulerEvent>> {
         let mut job_updates: HashMap<String, Vec<TaskStatus>> = HashMap::new();
         for status in task_status {
@@ -318,18 +732,39 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                 self.get_active_execution_graph(&job_id)
             {
                 let mut graph = cached.write().await;
-                graph.update_task_status(
+                let job_events = graph.update_task_status(
                     executor,
-                    statuses,
+                    statuses.clone(),
                     TASK_MAX_FAILURES,
                     STAGE_MAX_FAILURES,
-                )?
+                )?;
+                let alerts = self.stage_alerts.evaluate(&graph, &statuses);
+                let job_events = job_events
+                    .into_iter()
+                    .chain(alerts.into_iter().map(QueryStageSchedulerEvent::StageAlert))
+                    .collect();
+                // the graph is still updated in memory if the updates cannot be saved
+                if let Err(e) = self
+                    .save_task_statuses(
+                        &job_id,
+                        &graph,
+                        executor,
+                        &statuses,
+                        released_slots.as_deref_mut(),
+                    )
+                    .await
+                {
+                    warn!("Failed to save task status updates of job {job_id}: {e}");
+                }
+                job_events
             } else {
                 // TODO Deal with curator changed case
                 error!("Fail to find job {} in the active cache and it may not be curated by this scheduler", job_id);
                 vec![]
             };
 
+            self.notify_job_updated(&job_id);
+
             for event in job_events {
                 events.push(event);
             }
@@ -338,6 +773,35 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         Ok(events)
     }
 
+    /// Save task status updates, along with the release of the task slots of the tasks if
+    /// `released_slots` is provided and the job state supports it, in which case the released
+    /// slots are counted in `released_slots`
+    async fn save_task_statuses(
+        &self,
+        job_id: &str,
+        graph: &ExecutionGraph,
+        executor: &ExecutorMetadata,
+        statuses: &[TaskStatus],
+        released_slots: Option<&mut u32>,
+    ) -> Result<()> {
+        if let Some(released_slots) = released_slots {
+            let num_slots = statuses.len() as u32;
+            if self
+                .state
+                .save_task_statuses_and_release_slots(
+                    job_id, graph, executor, statuses, num_slots,
+                )
+                .await?
+            {
+                *released_slots += num_slots;
+                return Ok(());
+            }
+        }
+        self.state
+            .save_task_statuses(job_id, graph, executor, statuses)
+            .await
+    }
+
     /// Mark a job to success. This will create a key under the CompletedJobs keyspace
     /// and remove the job from ActiveJobs
     pub(crate) async fn succeed_job(&self, job_id: &str) -> Result<()> {
@@ -347,6 +811,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             let graph = graph.read().await.clone();
             if graph.is_successful() {
                 self.state.save_job(job_id, &graph).await?;
+                self.notify_job_updated(job_id);
             } else {
                 error!("Job {} has not finished and cannot be completed", job_id);
                 return Ok(());
@@ -363,7 +828,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         &self,
         job_id: &str,
     ) -> Result<(Vec<RunningTaskInfo>, usize)> {
-        self.abort_job(job_id, "Cancelled".to_owned()).await
+        self.abort_job(job_id, "Cancelled".to_owned(), None, vec![])
+            .await
     }
 
     /// Abort the job and return a Vec of running tasks need to cancel
@@ -371,6 +837,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         &self,
         job_id: &str,
         failure_reason: String,
+        failed_task: Option<FailedJobTask>,
+        error_chain: Vec<String>,
     ) -> Result<(Vec<RunningTaskInfo>, usize)> {
         let (tasks_to_cancel, pending_tasks) = if let Some(graph) =
             self.remove_active_execution_graph(job_id)
@@ -386,9 +854,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                 job_id
             );
 
-            guard.fail_job(failure_reason);
+            guard.fail_job(failure_reason, failed_task, error_chain);
 
             self.state.save_job(job_id, &guard).await?;
+            self.notify_job_updated(job_id);
 
             (running_tasks, pending_tasks)
         } else {
@@ -409,7 +878,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
     ) -> Result<()> {
         self.state
             .fail_unscheduled_job(job_id, failure_reason)
-            .await
+            .await?;
+        self.notify_job_updated(job_id);
+        Ok(())
     }
 
     pub async fn update_job(&self, job_id: &str) -> Result<usize> {
@@ -424,6 +895,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             println!("Saving job with status {:?}", graph.status());
 
             self.state.save_job(job_id, &graph).await?;
+            self.notify_job_updated(job_id);
 
             let new_tasks = graph.available_tasks() - curr_available_tasks;
 
@@ -435,27 +907,366 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         }
     }
 
+    /// Save the execution graphs of the active jobs and release their ownership, so that
+    /// they may be acquired by another scheduler once this one exits. Returns the number
+    /// of released jobs.
+    pub(crate) async fn release_active_jobs(&self) -> Result<usize> {
+        let mut released = 0;
+        for (job_id, graph) in self.active_execution_graphs() {
+            let graph = graph.read().await;
+            if let Err(e) = self.state.release_job(&job_id, &graph, None).await {
+                error!("Failed to release job {job_id}: {e:?}");
+                continue;
+            }
+            released += 1;
+        }
+        Ok(released)
+    }
+
+    /// Release the ownership of an active job to hand it off to the scheduler `target`.
+    /// The job is no longer active on this scheduler, and its cached state is returned
+    /// with its ownership epoch, to check whether `target` acquired it, or take it back
+    /// with `restore_job` if not.
+    pub(crate) async fn release_job(
+        &self,
+        job_id: &str,
+        target: &str,
+    ) -> Result<Option<(JobInfoCache, u64)>> {
+        let Some((_, job_info)) = self.active_job_cache.remove(job_id) else {
+            return Ok(None);
+        };
+        self.stage_alerts.remove_job(job_id);
+        let released = {
+            let graph = job_info.execution_graph.read().await;
+            let epoch = match &graph.status().status {
+                Some(job_status::Status::Running(running)) => running.epoch,
+                _ => 0,
+            };
+            self.state
+                .release_job(job_id, &graph, Some(target))
+                .await
+                .map(|_| epoch)
+        };
+        match released {
+            Ok(epoch) => Ok(Some((job_info, epoch))),
+            Err(e) => {
+                self.active_job_cache.insert(job_id.to_owned(), job_info);
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether the scheduler `target` acquired a job released to it with `release_job` at
+    /// the ownership `epoch`, according to the global state rather than to `target`
+    pub(crate) async fn is_acquired_by(
+        &self,
+        job_id: &str,
+        target: &str,
+        epoch: u64,
+    ) -> Result<bool> {
+        Ok(matches!(
+            self.state.get_job_status(job_id).await?,
+            Some(JobStatus {
+                status: Some(job_status::Status::Running(running)),
+                ..
+            }) if running.scheduler == target && running.epoch > epoch
+        ))
+    }
+
+    /// Take back the ownership of a job released to `target` with `release_job` at the
+    /// ownership `epoch`, unless `target` acquired it since, see [JobState::reclaim_job].
+    /// Returns whether the job is active on this scheduler again.
+    pub(crate) async fn restore_job(
+        &self,
+        job_id: &str,
+        job_info: JobInfoCache,
+        target: &str,
+        epoch: u64,
+    ) -> Result<bool> {
+        let reclaimed = {
+            let mut graph = job_info.execution_graph.write().await;
+            graph.set_scheduler(&self.scheduler_id);
+            self.state
+                .reclaim_job(job_id, &graph, target, epoch)
+                .await?
+        };
+        if reclaimed {
+            self.active_job_cache.insert(job_id.to_owned(), job_info);
+        }
+        Ok(reclaimed)
+    }
+
+    /// Acquire the ownership of a running job released by another scheduler, see
+    /// [JobState::try_acquire_job]. Returns whether the job is now active on this scheduler.
+    pub(crate) async fn acquire_job(&self, job_id: &str) -> Result<bool> {
+        if self.active_job_cache.contains_key(job_id) {
+            return Ok(true);
+        }
+        let Some(graph) = self.state.try_acquire_job(job_id).await? else {
+            return Ok(false);
+        };
+        info!("Acquired job {job_id} released by another scheduler");
+        self.activate_acquired_job(job_id, graph).await?;
+        Ok(true)
+    }
+
+    /// Renew the lease of this scheduler, see [JobState::renew_scheduler_lease]
+    pub(crate) async fn renew_scheduler_lease(&self) -> Result<()> {
+        self.state.renew_scheduler_lease().await
+    }
+
+    /// Keep the execution graph of a running job of another scheduler warm, see
+    /// [JobState::warm_execution_graph]
+    pub(crate) async fn warm_execution_graph(&self, job_id: &str) -> Result<()> {
+        self.state.warm_execution_graph(job_id).await
+    }
+
+    pub(crate) fn forget_warm_execution_graphs(&self) {
+        self.state.forget_warm_execution_graphs()
+    }
+
+    /// Take over the ownership of a running job of a failed scheduler, see
+    /// [JobState::try_take_over_job]. Returns whether the job is now active on this
+    /// scheduler.
+    pub(crate) async fn take_over_job(
+        &self,
+        job_id: &str,
+        failed_scheduler: &str,
+    ) -> Result<bool> {
+        if self.active_job_cache.contains_key(job_id) {
+            return Ok(true);
+        }
+        let Some(graph) = self
+            .state
+            .try_take_over_job(job_id, failed_scheduler)
+            .await?
+        else {
+            return Ok(false);
+        };
+        info!("Took over job {job_id} of scheduler {failed_scheduler}");
+        self.activate_acquired_job(job_id, graph).await?;
+        Ok(true)
+    }
+
+    async fn activate_acquired_job(
+        &self,
+        job_id: &str,
+        mut graph: ExecutionGraph,
+    ) -> Result<()> {
+        let session = self.state.get_session(graph.session_id()).await?;
+        graph.revive();
+        self.active_job_cache.insert(
+            job_id.to_owned(),
+            JobInfoCache::new(graph, session.state().config()),
+        );
+        self.notify_job_updated(job_id);
+        Ok(())
+    }
+
     /// return a Vec of running tasks need to cancel
     pub async fn executor_lost(&self, executor_id: &str) -> Result<Vec<RunningTaskInfo>> {
         // Collect all the running task need to cancel when there are running stages rolled back.
         let mut running_tasks_to_cancel: Vec<RunningTaskInfo> = vec![];
         // Collect graphs we update so we can update them in storage
         let updated_graphs: DashMap<String, ExecutionGraph> = DashMap::new();
-        {
-            for pairs in self.active_job_cache.iter() {
-                let (job_id, job_info) = pairs.pair();
-                let mut graph = job_info.execution_graph.write().await;
-                let reset = graph.reset_stages_on_lost_executor(executor_id)?;
-                if !reset.0.is_empty() {
-                    updated_graphs.insert(job_id.to_owned(), graph.clone());
-                    running_tasks_to_cancel.extend(reset.1);
-                }
+        // the shards of the active jobs are not locked while waiting for their graphs
+        for (job_id, graph) in self.active_execution_graphs() {
+            let mut graph = graph.write().await;
+            let reset = graph.reset_stages_on_lost_executor(executor_id)?;
+            if !reset.0.is_empty() {
+                updated_graphs.insert(job_id, graph.clone());
+                running_tasks_to_cancel.extend(reset.1);
+            }
+        }
+
+        Ok(running_tasks_to_cancel)
+    }
+
+    /// Check the shuffle files held by an executor, given as the map partitions held for
+    /// every stage of every job, against the active jobs. Returns the missing map
+    /// partitions of every stage of the jobs missing shuffle outputs of the executor, see
+    /// [ExecutionGraph::missing_shuffle_outputs].
+    pub(crate) async fn missing_shuffle_outputs(
+        &self,
+        executor_id: &str,
+        inventory: &HashMap<String, HashMap<usize, HashSet<usize>>>,
+        listed_at: u128,
+    ) -> HashMap<String, HashMap<usize, HashSet<usize>>> {
+        let no_files = HashMap::new();
+        let mut missing = HashMap::new();
+        for (job_id, graph) in self.active_execution_graphs() {
+            let held = inventory.get(&job_id).unwrap_or(&no_files);
+            let missing_outputs =
+                graph
+                    .read()
+                    .await
+                    .missing_shuffle_outputs(executor_id, held, listed_at);
+            if !missing_outputs.is_empty() {
+                missing.insert(job_id, missing_outputs);
             }
         }
+        missing
+    }
 
+    /// Recompute the missing shuffle outputs of an executor, given as the missing map
+    /// partitions of every stage of every job, e.g. after files of the executor went
+    /// missing. Returns the running tasks to cancel.
+    pub(crate) async fn reset_shuffle_outputs(
+        &self,
+        executor_id: &str,
+        missing: &HashMap<String, HashMap<usize, HashSet<usize>>>,
+    ) -> Result<Vec<RunningTaskInfo>> {
+        let mut running_tasks_to_cancel = vec![];
+        for (job_id, missing_outputs) in missing {
+            let Some(graph) = self.get_active_execution_graph(job_id) else {
+                continue;
+            };
+            let mut graph = graph.write().await;
+            let (reset_stages, running_tasks) =
+                graph.reset_missing_shuffle_outputs(executor_id, missing_outputs)?;
+            if !reset_stages.is_empty() {
+                warn!(
+                    "Recomputing stages {:?} of job {} for lost shuffle outputs of executor {}",
+                    reset_stages, job_id, executor_id
+                );
+                self.state.save_job(job_id, &graph).await?;
+                self.notify_job_updated(job_id);
+            }
+            running_tasks_to_cancel.extend(running_tasks);
+        }
         Ok(running_tasks_to_cancel)
     }
 
+    /// The shuffle outputs held by an executor which other stages of the active jobs are
+    /// yet to read, by job
+    pub(crate) async fn shuffle_outputs_on(
+        &self,
+        executor_id: &str,
+    ) -> HashMap<String, Vec<PartitionLocation>> {
+        let mut outputs = HashMap::new();
+        for (job_id, graph) in self.active_execution_graphs() {
+            let locations = graph.read().await.shuffle_outputs_on(executor_id);
+            if !locations.is_empty() {
+                outputs.insert(job_id, locations);
+            }
+        }
+        outputs
+    }
+
+    /// Replace the shuffle outputs held by an executor for a job with their replicas on
+    /// other executors, given by the paths of the outputs on the executor, see
+    /// [ExecutionGraph::replace_shuffle_outputs]. Returns the number of replaced outputs.
+    pub(crate) async fn replace_shuffle_outputs(
+        &self,
+        job_id: &str,
+        executor_id: &str,
+        replicas: &HashMap<String, PartitionLocation>,
+    ) -> Result<usize> {
+        let Some(graph) = self.get_active_execution_graph(job_id) else {
+            return Ok(0);
+        };
+        let mut graph = graph.write().await;
+        let replaced = graph.replace_shuffle_outputs(executor_id, replicas)?;
+        if replaced > 0 {
+            self.state.save_job(job_id, &graph).await?;
+        }
+        Ok(replaced)
+    }
+
+    /// Cancel the running attempt of a task of an active job, which is scheduled again.
+    /// Returns the cancelled attempt to cancel on its executor, or `None` if the task is not
+    /// running.
+    pub(crate) async fn cancel_task_attempt(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        partition_id: usize,
+    ) -> Result<Option<RunningTaskInfo>> {
+        let Some(graph) = self.get_active_execution_graph(job_id) else {
+            return Ok(None);
+        };
+        let mut graph = graph.write().await;
+        let cancelled = graph.cancel_task_attempt(stage_id, partition_id);
+        if cancelled.is_some() {
+            self.state.save_job(job_id, &graph).await?;
+            self.notify_job_updated(job_id);
+        }
+        Ok(cancelled)
+    }
+
+    /// Cancel the running attempt of a stage of an active job, which is retried in a new
+    /// attempt. Returns the running tasks to cancel on the executors, or `None` if the stage
+    /// is not running.
+    pub(crate) async fn cancel_stage_attempt(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+    ) -> Result<Option<Vec<RunningTaskInfo>>> {
+        let Some(graph) = self.get_active_execution_graph(job_id) else {
+            return Ok(None);
+        };
+        let mut graph = graph.write().await;
+        let running_tasks = graph.cancel_stage_attempt(stage_id)?;
+        if running_tasks.is_some() {
+            self.state.save_job(job_id, &graph).await?;
+            self.notify_job_updated(job_id);
+        }
+        Ok(running_tasks)
+    }
+
+    /// The interval of the periodic evaluation of the duration of the running stages,
+    /// `None` without `stage_duration_ms` alert rules
+    pub(crate) fn stage_duration_check_interval(&self) -> Option<Duration> {
+        self.stage_alerts.duration_check_interval()
+    }
+
+    /// Evaluate the `stage_duration_ms` alert rules against the running stages of the
+    /// active jobs, and return the alerts raised
+    pub(crate) async fn evaluate_stage_durations(&self) -> Vec<StageAlert> {
+        let mut alerts = vec![];
+        for (_, graph) in self.active_execution_graphs() {
+            let graph = graph.read().await;
+            alerts.extend(self.stage_alerts.evaluate_running_stages(&graph));
+        }
+        alerts
+    }
+
+    /// The usage of task slots by the active jobs, to detect the jobs deadlocked waiting
+    /// for task slots
+    pub(crate) async fn job_slot_usage(&self) -> Vec<JobSlotUsage> {
+        let mut usage = vec![];
+        for (job_id, graph) in self.active_execution_graphs() {
+            let graph = graph.read().await;
+            usage.push(JobSlotUsage {
+                job_id,
+                start_time: graph.start_time(),
+                running_tasks: graph.running_tasks().len(),
+                waiting_tasks: graph.waiting_tasks(),
+                available_tasks: graph.available_tasks(),
+                completed_tasks: graph.completed_tasks(),
+            });
+        }
+        usage
+    }
+
+    /// Rewind the stages of a job whose tasks wait for the outputs of their inputs, see
+    /// [ExecutionGraph::rewind_waiting_stages]. Returns the running tasks to cancel.
+    pub(crate) async fn rewind_waiting_stages(
+        &self,
+        job_id: &str,
+    ) -> Result<Vec<RunningTaskInfo>> {
+        let Some(graph) = self.get_active_execution_graph(job_id) else {
+            return Ok(vec![]);
+        };
+        let mut graph = graph.write().await;
+        let running_tasks = graph.rewind_waiting_stages()?;
+        if !running_tasks.is_empty() {
+            self.state.save_job(job_id, &graph).await?;
+            self.notify_job_updated(job_id);
+        }
+        Ok(running_tasks)
+    }
+
     /// Retrieve the number of available tasks for the given job. The value returned
     /// is strictly a point-in-time snapshot
     pub async fn get_available_task_count(&self, job_id: &str) -> Result<usize> {
@@ -477,26 +1288,12 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
 
         let job_id = task.partition.job_id.clone();
         let stage_id = task.partition.stage_id;
+        let launch_token = task.launch_token();
 
-        if let Some(mut job_info) = self.active_job_cache.get_mut(&job_id) {
-            let plan = if let Some(plan) = job_info.encoded_stage_plans.get(&stage_id) {
-                plan.clone()
-            } else {
-                let mut plan_buf: Vec<u8> = vec![];
-                let plan_proto = U::try_from_physical_plan(
-                    task.plan,
-                    self.codec.physical_extension_codec(),
-                )?;
-                plan_proto.try_encode(&mut plan_buf)?;
-
-                job_info
-                    .encoded_stage_plans
-                    .insert(stage_id, plan_buf.clone());
-
-                plan_buf
-            };
+        if let Some(job_info) = self.get_job_info(&job_id) {
+            let plan = self.encoded_stage_plan(&job_info, stage_id, task.plan)?;
 
-            let mut props = vec![];
+            let mut props = job_info.session_props.clone();
             if task.data_cache {
                 props.push(KeyValuePair {
                     key: BALLISTA_DATA_CACHE_ENABLED.to_string(),
@@ -518,6 +1315,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                     .unwrap()
                     .as_millis() as u64,
                 props,
+                launch_token,
             };
             Ok(task_definition)
         } else {
@@ -572,24 +1370,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                 trace!("With task details {:?}", tasks);
             }
 
-            if let Some(mut job_info) = self.active_job_cache.get_mut(&job_id) {
-                let plan = if let Some(plan) = job_info.encoded_stage_plans.get(&stage_id)
-                {
-                    plan.clone()
-                } else {
-                    let mut plan_buf: Vec<u8> = vec![];
-                    let plan_proto = U::try_from_physical_plan(
-                        task.plan.clone(),
-                        self.codec.physical_extension_codec(),
-                    )?;
-                    plan_proto.try_encode(&mut plan_buf)?;
-
-                    job_info
-                        .encoded_stage_plans
-                        .insert(stage_id, plan_buf.clone());
-
-                    plan_buf
-                };
+            if let Some(job_info) = self.get_job_info(&job_id) {
+                let plan =
+                    self.encoded_stage_plan(&job_info, stage_id, task.plan.clone())?;
 
                 let launch_time = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
@@ -601,12 +1384,18 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
 
                 let mut multi_tasks = vec![];
                 if !tasks_with_data_cache.is_empty() {
+                    let mut props = job_info.session_props.clone();
+                    props.push(KeyValuePair {
+                        key: BALLISTA_DATA_CACHE_ENABLED.to_string(),
+                        value: "true".to_string(),
+                    });
                     let task_ids = tasks_with_data_cache
                         .into_iter()
                         .map(|task| TaskId {
                             task_id: task.task_id as u32,
                             task_attempt_num: task.task_attempt as u32,
                             partition_id: task.partition.partition_id as u32,
+                            launch_token: task.launch_token(),
                         })
                         .collect();
                     multi_tasks.push(MultiTaskDefinition {
@@ -617,10 +1406,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                         plan: plan.clone(),
                         session_id: session_id.clone(),
                         launch_time,
-                        props: vec![KeyValuePair {
-                            key: BALLISTA_DATA_CACHE_ENABLED.to_string(),
-                            value: "true".to_string(),
-                        }],
+                        props,
                     });
                 }
                 if !tasks_without_data_cache.is_empty() {
@@ -630,6 +1416,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                             task_id: task.task_id as u32,
                             task_attempt_num: task.task_attempt as u32,
                             partition_id: task.partition.partition_id as u32,
+                            launch_token: task.launch_token(),
                         })
                         .collect();
                     multi_tasks.push(MultiTaskDefinition {
@@ -640,7 +1427,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                         plan,
                         session_id,
                         launch_time,
-                        props: vec![],
+                        props: job_info.session_props.clone(),
                     });
                 }
 
@@ -655,6 +1442,45 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         }
     }
 
+    /// The cached state of an active job, cloned so that the shard of the job is not
+    /// locked while it is used
+    fn get_job_info(&self, job_id: &str) -> Option<JobInfoCache> {
+        self.active_job_cache
+            .get(job_id)
+            .map(|job_info| job_info.value().clone())
+    }
+
+    /// The encoded plan of a stage of a job, encoded once for all the tasks of the stage
+    fn encoded_stage_plan(
+        &self,
+        job_info: &JobInfoCache,
+        stage_id: usize,
+        plan: Arc<dyn ExecutionPlan>,
+    ) -> Result<Vec<u8>> {
+        if let Some(plan) = job_info.encoded_stage_plans.lock().get(&stage_id) {
+            return Ok(plan.clone());
+        }
+        let mut plan_buf: Vec<u8> = vec![];
+        let plan_proto =
+            U::try_from_physical_plan(plan, self.codec.physical_extension_codec())?;
+        plan_proto.try_encode(&mut plan_buf)?;
+        let plan_buf = self.codec.plan_protection().protect(plan_buf)?;
+        job_info
+            .encoded_stage_plans
+            .lock()
+            .insert(stage_id, plan_buf.clone());
+        Ok(plan_buf)
+    }
+
+    /// The execution graphs of the active jobs, collected first so that the shards of the
+    /// jobs are not locked while waiting for the locks of the graphs
+    fn active_execution_graphs(&self) -> Vec<(String, Arc<RwLock<ExecutionGraph>>)> {
+        self.active_job_cache
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.execution_graph.clone()))
+            .collect()
+    }
+
     /// Get the `ExecutionGraph` for the given job ID from cache
     pub(crate) fn get_active_execution_graph(
         &self,
@@ -671,6 +1497,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         &self,
         job_id: &str,
     ) -> Option<Arc<RwLock<ExecutionGraph>>> {
+        self.stage_alerts.remove_job(job_id);
         self.active_job_cache
             .remove(job_id)
             .map(|value| value.1.execution_graph)
@@ -692,10 +1519,29 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             info!("The interval is 0 and the clean up for the failed job state {} will not triggered", job_id);
             return;
         }
+        let expires = self.state.expires_finished_jobs();
+        if expires {
+            debug!("The state of job {job_id} is deleted once its retention elapsed");
+        }
 
         let state = self.state.clone();
+        let scheduling_traces = self.scheduling_traces.clone();
+        let job_archive = self.job_archive.clone();
+        let codec = self.codec.clone();
         tokio::spawn(async move {
+            // archived once finished rather than when cleaned up, since the backends
+            // expiring the finished jobs drop their state on their own
+            if let Some(job_archive) = job_archive {
+                if let Err(err) = archive_job(&state, &job_archive, &codec, &job_id).await
+                {
+                    error!("Failed to archive job {job_id}: {err:?}");
+                }
+            }
             tokio::time::sleep(Duration::from_secs(clean_up_interval)).await;
+            scheduling_traces.remove_job(&job_id);
+            if expires {
+                return;
+            }
             if let Err(err) = state.remove_job(&job_id).await {
                 error!("Failed to delete job {job_id}: {err:?}");
             }
@@ -703,6 +1549,34 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
     }
 }
 
+/// Archive the execution graph of a finished job, as stored in the backend
+async fn archive_job<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
+    state: &Arc<dyn JobState>,
+    job_archive: &JobArchive,
+    codec: &BallistaCodec<T, U>,
+    job_id: &str,
+) -> Result<()> {
+    let Some(graph) = state.get_execution_graph(job_id).await? else {
+        debug!("Job {job_id} has no execution graph to archive");
+        return Ok(());
+    };
+    let proto = ExecutionGraph::encode_execution_graph(graph, codec)?;
+    job_archive.put(&proto, codec.plan_protection()).await?;
+    debug!("Archived job {job_id}");
+    Ok(())
+}
+
+fn successful_stages(graph: &ExecutionGraph) -> Vec<usize> {
+    let mut stages: Vec<usize> = graph
+        .stages()
+        .iter()
+        .filter(|(_, stage)| matches!(stage, ExecutionStage::Successful(_)))
+        .map(|(stage_id, _)| *stage_id)
+        .collect();
+    stages.sort_unstable();
+    stages
+}
+
 pub struct JobOverview {
     pub job_id: String,
     pub job_name: String,
@@ -733,3 +1607,87 @@ impl From<&ExecutionGraph> for JobOverview {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use ballista_core::error::Result;
+    use ballista_core::serde::BallistaCodec;
+    use datafusion::prelude::SessionConfig;
+    use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+
+    use crate::state::task_manager::{JobInfoCache, TaskManager};
+    use crate::test_utils::{test_aggregation_plan_with_job_id, test_cluster_context};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_task_assignment_stress() -> Result<()> {
+        let cluster = test_cluster_context();
+        let task_manager: Arc<TaskManager<LogicalPlanNode, PhysicalPlanNode>> = Arc::new(
+            TaskManager::new(
+                cluster.job_state(),
+                BallistaCodec::default(),
+                "localhost:50050".to_owned(),
+            )
+            .with_job_cache_shards(16),
+        );
+        let num_jobs = 64;
+        let mut available_tasks = 0;
+        for job in 0..num_jobs {
+            let job_id = format!("job-{job}");
+            let mut graph = test_aggregation_plan_with_job_id(4, &job_id).await;
+            graph.revive();
+            if job > 0 {
+                available_tasks += graph.available_tasks();
+            }
+            task_manager
+                .active_job_cache
+                .insert(job_id, JobInfoCache::new(graph, &SessionConfig::new()));
+        }
+
+        // a huge job holds the lock of its graph, e.g. while updating its task status, and
+        // a lost executor waits for it
+        let busy_graph = task_manager.get_active_execution_graph("job-0").unwrap();
+        let busy_guard = busy_graph.write().await;
+        let executor_lost = {
+            let task_manager = task_manager.clone();
+            tokio::spawn(async move { task_manager.executor_lost("executor-1").await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // the tasks of the other jobs are still assigned concurrently
+        let assigners: Vec<_> = (0..8)
+            .map(|assigner| {
+                let task_manager = task_manager.clone();
+                tokio::spawn(async move {
+                    let mut assigned = 0;
+                    for job in (1..num_jobs).filter(|job| job % 8 == assigner) {
+                        let job_id = format!("job-{job}");
+                        let graph = task_manager.get_active_execution_graph(&job_id);
+                        while let Some(task) =
+                            graph.as_ref().unwrap().write().await.pop_next_task("e")?
+                        {
+                            task_manager.prepare_task_definition(task)?;
+                            assigned += 1;
+                        }
+                    }
+                    Ok::<usize, ballista_core::error::BallistaError>(assigned)
+                })
+            })
+            .collect();
+        let mut assigned = 0;
+        for assigner in assigners {
+            assigned += tokio::time::timeout(Duration::from_secs(10), assigner)
+                .await
+                .expect("the assignment is not blocked by the busy job")
+                .unwrap()?;
+        }
+        assert!(available_tasks > 0);
+        assert_eq!(available_tasks, assigned);
+
+        drop(busy_guard);
+        executor_lost.await.unwrap()?;
+        Ok(())
+    }
+}
@@ -32,10 +32,12 @@ use crate::state::executor_manager::ExecutorManager;
 use crate::state::task_manager::TaskLauncher;
 
 use ballista_core::config::{BallistaConfig, BALLISTA_DEFAULT_SHUFFLE_PARTITIONS};
+use ballista_core::protocol::PROTOCOL_VERSION;
 use ballista_core::serde::protobuf::job_status::Status;
+use ballista_core::serde::protobuf::scheduler_grpc_server::SchedulerGrpc;
 use ballista_core::serde::protobuf::{
-    task_status, FailedTask, JobStatus, MultiTaskDefinition, ShuffleWritePartition,
-    SuccessfulTask, TaskId, TaskStatus,
+    task_status, watch_job_status_result, FailedTask, JobStatus, MultiTaskDefinition,
+    ShuffleWritePartition, SuccessfulTask, TaskId, TaskStatus, WatchJobStatusParams,
 };
 use ballista_core::serde::scheduler::{
     ExecutorData, ExecutorMetadata, ExecutorSpecification,
@@ -58,8 +60,10 @@ use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::state::execution_graph::{ExecutionGraph, TaskDescription};
 use ballista_core::utils::default_session_builder;
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+use futures::StreamExt;
 use parking_lot::Mutex;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tonic::{Code, Request};
 
 pub const TPCH_TABLES: &[&str] = &[
     "part", "supplier", "partsupp", "customer", "orders", "lineitem", "nation", "region",
@@ -277,6 +281,9 @@ pub fn default_task_runner() -> impl TaskRunner {
                 num_batches: 1,
                 num_rows: 1,
                 num_bytes: 1,
+                null_counts: vec![],
+                column_bounds: vec![],
+                bloom_filters: vec![],
             })
             .collect();
 
@@ -439,6 +446,11 @@ impl SchedulerTest {
                 specification: ExecutorSpecification {
                     task_slots: task_slots as u32,
                 },
+                zone: String::new(),
+                rack: String::new(),
+                instance_type: String::new(),
+                capabilities: Default::default(),
+                protocol_version: PROTOCOL_VERSION,
             };
 
             let executor_data = ExecutorData {
@@ -540,67 +552,64 @@ impl SchedulerTest {
         job_id: &str,
         timeout_ms: u64,
     ) -> Result<JobStatus> {
-        let mut time = 0;
-        let final_status: Result<JobStatus> = loop {
-            let status = self
+        match tokio::time::timeout(
+            Duration::from_millis(timeout_ms),
+            self.await_completion(job_id),
+        )
+        .await
+        {
+            Ok(status) => status,
+            Err(_) => self
                 .scheduler
                 .state
                 .task_manager
                 .get_job_status(job_id)
-                .await?;
-
-            if let Some(JobStatus {
-                status: Some(inner),
-                ..
-            }) = status.as_ref()
-            {
-                match inner {
-                    Status::Failed(_) | Status::Successful(_) => {
-                        break Ok(status.unwrap())
-                    }
-                    _ => {
-                        if time >= timeout_ms {
-                            break Ok(status.unwrap());
-                        } else {
-                            continue;
-                        }
-                    }
-                }
-            }
-
-            tokio::time::sleep(Duration::from_millis(100)).await;
-            time += 100;
-        };
-
-        final_status
+                .await?
+                .ok_or_else(|| {
+                    BallistaError::Internal(format!("Job {job_id} not found"))
+                }),
+        }
     }
 
+    /// Wait for the job to complete, following the job status stream of the scheduler
     pub async fn await_completion(&self, job_id: &str) -> Result<JobStatus> {
-        let final_status: Result<JobStatus> = loop {
-            let status = self
-                .scheduler
-                .state
-                .task_manager
-                .get_job_status(job_id)
-                .await?;
-
-            if let Some(JobStatus {
-                status: Some(inner),
-                ..
-            }) = status.as_ref()
-            {
-                match inner {
-                    Status::Failed(_) | Status::Successful(_) => {
-                        break Ok(status.unwrap())
-                    }
-                    _ => continue,
+        let mut updates = loop {
+            let request = Request::new(WatchJobStatusParams {
+                job_id: job_id.to_owned(),
+            });
+            match self.scheduler.watch_job_status(request).await {
+                Ok(response) => break response.into_inner(),
+                // the job is not queued yet
+                Err(status) if status.code() == Code::NotFound => {
+                    tokio::time::sleep(Duration::from_millis(100)).await
+                }
+                Err(status) => {
+                    return Err(BallistaError::General(format!(
+                        "Failed to watch job {job_id}: {status}"
+                    )))
                 }
             }
-
-            tokio::time::sleep(Duration::from_millis(100)).await
         };
 
-        final_status
+        let mut final_status = None;
+        while let Some(result) = updates.next().await {
+            let result = result.map_err(|status| {
+                BallistaError::General(format!("Failed to watch job {job_id}: {status}"))
+            })?;
+            if let Some(watch_job_status_result::Event::Status(status)) = result.event {
+                final_status = Some(status);
+            }
+        }
+
+        match final_status {
+            Some(JobStatus {
+                status: Some(Status::Failed(_) | Status::Successful(_)),
+                ..
+            }) => Ok(final_status.unwrap()),
+            _ => Err(BallistaError::Internal(format!(
+                "Job {job_id} status stream ended before the job completed"
+            ))),
+        }
     }
 
     pub async fn run(
@@ -632,31 +641,11 @@ impl SchedulerTest {
             }
         });
 
-        let final_status: Result<JobStatus> = loop {
-            let status = self
-                .scheduler
-                .state
-                .task_manager
-                .get_job_status(job_id)
-                .await?;
-
-            if let Some(JobStatus {
-                status: Some(inner),
-                ..
-            }) = status.as_ref()
-            {
-                match inner {
-                    Status::Failed(_) | Status::Successful(_) => {
-                        break Ok(status.unwrap())
-                    }
-                    _ => continue,
-                }
-            }
-
-            tokio::time::sleep(Duration::from_millis(100)).await
-        };
+        self.await_completion(job_id).await
+    }
 
-        final_status
+    pub fn scheduler(&self) -> &SchedulerServer<LogicalPlanNode, PhysicalPlanNode> {
+        &self.scheduler
     }
 }
 
@@ -732,6 +721,15 @@ impl SchedulerMetricsCollector for TestMetricsCollector {
 
     fn set_pending_tasks_queue_size(&self, _value: u64) {}
 
+    fn record_state_operation(
+        &self,
+        _keyspace: &str,
+        _operation: &str,
+        _latency: Duration,
+        _failed: bool,
+    ) {
+    }
+
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
         Ok(None)
     }
@@ -1007,6 +1005,11 @@ pub fn mock_executor(executor_id: String) -> ExecutorMetadata {
         port: 8080,
         grpc_port: 9090,
         specification: ExecutorSpecification { task_slots: 1 },
+        zone: String::new(),
+        rack: String::new(),
+        instance_type: String::new(),
+        capabilities: Default::default(),
+        protocol_version: PROTOCOL_VERSION,
     }
 }
 
@@ -1027,6 +1030,9 @@ pub fn mock_completed_task(task: TaskDescription, executor_id: &str) -> TaskStat
             num_batches: 1,
             num_rows: 1,
             num_bytes: 1,
+            null_counts: vec![],
+            column_bounds: vec![],
+            bloom_filters: vec![],
         })
     }
 
@@ -1065,6 +1071,9 @@ pub fn mock_failed_task(task: TaskDescription, failed_task: FailedTask) -> TaskS
             num_batches: 1,
             num_rows: 1,
             num_bytes: 1,
+            null_counts: vec![],
+            column_bounds: vec![],
+            bloom_filters: vec![],
         })
     }
 
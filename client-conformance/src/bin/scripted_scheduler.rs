@@ -0,0 +1,51 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Scheduler answering the client API with scripted responses, for the conformance tests
+//! of the client implementations in other languages
+
+use std::sync::Arc;
+
+use ballista_client_conformance::ScriptedScheduler;
+use clap::Parser;
+use tokio::net::TcpListener;
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about)]
+struct Args {
+    /// Address to bind the scheduler to
+    #[clap(long, default_value = "0.0.0.0")]
+    bind_host: String,
+    /// Port of the scheduler and of its Flight service
+    #[clap(long, default_value_t = 50050)]
+    port: u16,
+    /// Host advertised in the locations of the output partitions of the jobs
+    #[clap(long, default_value = "localhost")]
+    external_host: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let args = Args::parse();
+    let listener = TcpListener::bind((args.bind_host.as_str(), args.port)).await?;
+    let port = listener.local_addr()?.port();
+    Arc::new(ScriptedScheduler::new(args.external_host, port))
+        .serve(listener)
+        .await?;
+    Ok(())
+}
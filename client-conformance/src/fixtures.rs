@@ -0,0 +1,338 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Golden messages of the client API. Each fixture is committed in the `fixtures` directory
+//! twice: encoded in the protobuf binary format (`.binpb`) and in the protobuf text format
+//! (`.txtpb`). A client implementation conforms when it decodes every binary fixture to the
+//! message of its text fixture, and when the messages it encodes decode to the same
+//! messages. The binary encoding of a message is not canonical, so that the bytes encoded by
+//! other implementations are not expected to match the fixtures.
+
+use std::path::PathBuf;
+
+use ballista_core::serde::protobuf::{
+    action, execute_query_failure_result, execute_query_params, execute_query_result,
+    job_status, Action, CancelJobParams, CancelJobResult, CleanJobDataParams,
+    CreateSessionParams, CreateSessionResult, ExecuteQueryFailureResult,
+    ExecuteQueryParams, ExecuteQueryResult, ExecuteQuerySuccessResult, ExecutorMetadata,
+    FailedJob, FetchPartition, GetJobStatusParams, GetJobStatusResult, HandshakeParams,
+    HandshakeResult, JobOutputStats, JobStatus, KeyValuePair, PartitionId,
+    PartitionLocation, PartitionStats, QueuedJob, RunningJob, SuccessfulJob,
+};
+use prost::{DecodeError, Message};
+
+/// ID of the job of the fixtures
+pub const JOB_ID: &str = "conformance-job";
+
+/// Name of the job of the fixtures
+pub const JOB_NAME: &str = "conformance";
+
+/// ID of the session of the fixtures
+pub const SESSION_ID: &str = "conformance-session";
+
+/// ID of the executor holding the output partitions of the job
+pub const EXECUTOR_ID: &str = "conformance-executor";
+
+/// Version of Ballista advertised by the handshakes of the fixtures
+pub const BALLISTA_VERSION: &str = "0.12.0";
+
+/// Times of the transitions of the job of the fixtures, in milliseconds since the epoch
+pub const QUEUED_AT: u64 = 1_700_000_000_000;
+pub const STARTED_AT: u64 = 1_700_000_001_000;
+pub const ENDED_AT: u64 = 1_700_000_002_000;
+
+/// Number of rows of each output partition of the successful jobs
+pub const PARTITION_ROWS: [i64; 2] = [3, 2];
+
+/// Number of bytes reported for each output partition of the successful jobs
+pub const PARTITION_BYTES: i64 = 1024;
+
+type Matcher = Box<dyn Fn(&[u8]) -> Result<bool, DecodeError> + Send + Sync>;
+
+/// A golden message of the client API
+pub struct Fixture {
+    /// Name of the fixture, the stem of its files in the fixtures directory
+    pub name: &'static str,
+    /// Fully qualified name of the protobuf message
+    pub message_type: &'static str,
+    encoded: Vec<u8>,
+    matcher: Matcher,
+}
+
+impl Fixture {
+    fn new<M>(name: &'static str, message_type: &'static str, message: M) -> Self
+    where
+        M: Message + Default + PartialEq + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            message_type,
+            encoded: message.encode_to_vec(),
+            matcher: Box::new(move |encoded| Ok(M::decode(encoded)? == message)),
+        }
+    }
+
+    /// The message of the fixture, encoded by this build
+    pub fn encoded(&self) -> &[u8] {
+        &self.encoded
+    }
+
+    /// Whether an encoded message decodes to the message of the fixture
+    pub fn matches(&self, encoded: &[u8]) -> Result<bool, DecodeError> {
+        (self.matcher)(encoded)
+    }
+
+    /// Path of the binary encoding of the fixture
+    pub fn binary_path(&self) -> PathBuf {
+        fixtures_dir().join(format!("{}.binpb", self.name))
+    }
+
+    /// Path of the text encoding of the fixture
+    pub fn text_path(&self) -> PathBuf {
+        fixtures_dir().join(format!("{}.txtpb", self.name))
+    }
+}
+
+/// Directory of the committed fixtures
+pub fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+}
+
+/// The output of a successful job, whose partitions are served by the Flight service at
+/// `host` and `port`
+pub fn successful_job(job_id: &str, host: &str, port: u16) -> SuccessfulJob {
+    let partition_location = PARTITION_ROWS
+        .iter()
+        .enumerate()
+        .map(|(partition, &num_rows)| PartitionLocation {
+            map_partition_id: partition as u32,
+            partition_id: Some(PartitionId {
+                job_id: job_id.to_owned(),
+                stage_id: 1,
+                partition_id: partition as u32,
+            }),
+            executor_meta: Some(ExecutorMetadata {
+                id: EXECUTOR_ID.to_owned(),
+                host: host.to_owned(),
+                port: port as u32,
+                ..Default::default()
+            }),
+            partition_stats: Some(PartitionStats {
+                num_rows,
+                num_batches: 1,
+                num_bytes: PARTITION_BYTES,
+                column_stats: vec![],
+            }),
+            path: partition_path(job_id, partition),
+        })
+        .collect();
+    let num_rows: i64 = PARTITION_ROWS.iter().sum();
+    SuccessfulJob {
+        partition_location,
+        queued_at: QUEUED_AT,
+        started_at: STARTED_AT,
+        ended_at: ENDED_AT,
+        output_stats: Some(JobOutputStats {
+            num_rows: num_rows as u64,
+            num_batches: PARTITION_ROWS.len() as u64,
+            num_bytes: (PARTITION_BYTES as u64) * PARTITION_ROWS.len() as u64,
+        }),
+        ..Default::default()
+    }
+}
+
+/// Path of an output partition of a successful job, the opaque path sent back to the Flight
+/// service to fetch the partition
+pub fn partition_path(job_id: &str, partition: usize) -> String {
+    format!("/conformance/{job_id}/{partition}")
+}
+
+fn job_status(status: job_status::Status) -> GetJobStatusResult {
+    GetJobStatusResult {
+        status: Some(JobStatus {
+            job_id: JOB_ID.to_owned(),
+            job_name: JOB_NAME.to_owned(),
+            status: Some(status),
+        }),
+    }
+}
+
+/// The golden messages of the client API
+pub fn fixtures() -> Vec<Fixture> {
+    let job_name = || KeyValuePair {
+        key: "ballista.job.name".to_owned(),
+        value: JOB_NAME.to_owned(),
+    };
+    vec![
+        Fixture::new(
+            "handshake_params",
+            "ballista.protobuf.HandshakeParams",
+            HandshakeParams {
+                protocol_version: 2,
+                min_protocol_version: 1,
+                ballista_version: BALLISTA_VERSION.to_owned(),
+            },
+        ),
+        Fixture::new(
+            "handshake_result",
+            "ballista.protobuf.HandshakeResult",
+            HandshakeResult {
+                protocol_version: 2,
+                ballista_version: BALLISTA_VERSION.to_owned(),
+            },
+        ),
+        Fixture::new(
+            "create_session_params",
+            "ballista.protobuf.CreateSessionParams",
+            CreateSessionParams {
+                settings: vec![job_name()],
+            },
+        ),
+        Fixture::new(
+            "create_session_result",
+            "ballista.protobuf.CreateSessionResult",
+            CreateSessionResult {
+                session_id: SESSION_ID.to_owned(),
+            },
+        ),
+        Fixture::new(
+            "execute_query_params",
+            "ballista.protobuf.ExecuteQueryParams",
+            ExecuteQueryParams {
+                query: Some(execute_query_params::Query::Sql(
+                    "SELECT 'conformance-success'".to_owned(),
+                )),
+                optional_session_id: Some(
+                    execute_query_params::OptionalSessionId::SessionId(
+                        SESSION_ID.to_owned(),
+                    ),
+                ),
+                settings: vec![job_name()],
+                protocol_version: 2,
+                min_protocol_version: 1,
+                ..Default::default()
+            },
+        ),
+        Fixture::new(
+            "execute_query_result_success",
+            "ballista.protobuf.ExecuteQueryResult",
+            ExecuteQueryResult {
+                result: Some(execute_query_result::Result::Success(
+                    ExecuteQuerySuccessResult {
+                        job_id: JOB_ID.to_owned(),
+                        session_id: SESSION_ID.to_owned(),
+                        ..Default::default()
+                    },
+                )),
+            },
+        ),
+        Fixture::new(
+            "execute_query_result_failure",
+            "ballista.protobuf.ExecuteQueryResult",
+            ExecuteQueryResult {
+                result: Some(execute_query_result::Result::Failure(
+                    ExecuteQueryFailureResult {
+                        failure: Some(
+                            execute_query_failure_result::Failure::SessionNotFound(
+                                "Session conformance-unknown not found".to_owned(),
+                            ),
+                        ),
+                    },
+                )),
+            },
+        ),
+        Fixture::new(
+            "get_job_status_params",
+            "ballista.protobuf.GetJobStatusParams",
+            GetJobStatusParams {
+                job_id: JOB_ID.to_owned(),
+            },
+        ),
+        Fixture::new(
+            "get_job_status_result_queued",
+            "ballista.protobuf.GetJobStatusResult",
+            job_status(job_status::Status::Queued(QueuedJob {
+                queued_at: QUEUED_AT,
+                estimated_start_delay_ms: 500,
+            })),
+        ),
+        Fixture::new(
+            "get_job_status_result_running",
+            "ballista.protobuf.GetJobStatusResult",
+            job_status(job_status::Status::Running(RunningJob {
+                queued_at: QUEUED_AT,
+                started_at: STARTED_AT,
+                scheduler: "localhost:50050".to_owned(),
+            })),
+        ),
+        Fixture::new(
+            "get_job_status_result_failed",
+            "ballista.protobuf.GetJobStatusResult",
+            job_status(job_status::Status::Failed(FailedJob {
+                error: "Scripted failure".to_owned(),
+                queued_at: QUEUED_AT,
+                started_at: STARTED_AT,
+                ended_at: ENDED_AT,
+                error_chain: vec!["Scripted failure".to_owned()],
+                ..Default::default()
+            })),
+        ),
+        Fixture::new(
+            "get_job_status_result_successful",
+            "ballista.protobuf.GetJobStatusResult",
+            job_status(job_status::Status::Successful(successful_job(
+                JOB_ID,
+                "localhost",
+                50050,
+            ))),
+        ),
+        Fixture::new(
+            "fetch_partition_action",
+            "ballista.protobuf.Action",
+            Action {
+                action_type: Some(action::ActionType::FetchPartition(FetchPartition {
+                    job_id: JOB_ID.to_owned(),
+                    stage_id: 1,
+                    partition_id: 0,
+                    path: partition_path(JOB_ID, 0),
+                    host: "localhost".to_owned(),
+                    port: 50050,
+                })),
+                settings: vec![],
+            },
+        ),
+        Fixture::new(
+            "cancel_job_params",
+            "ballista.protobuf.CancelJobParams",
+            CancelJobParams {
+                job_id: JOB_ID.to_owned(),
+            },
+        ),
+        Fixture::new(
+            "cancel_job_result",
+            "ballista.protobuf.CancelJobResult",
+            CancelJobResult { cancelled: true },
+        ),
+        Fixture::new(
+            "clean_job_data_params",
+            "ballista.protobuf.CleanJobDataParams",
+            CleanJobDataParams {
+                job_id: JOB_ID.to_owned(),
+            },
+        ),
+    ]
+}
@@ -0,0 +1,651 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! A scheduler answering the client API with scripted responses, which also serves the
+//! output partitions of its jobs with the Flight service of an executor, on the same port.
+//! Client implementations run their conformance tests against it without a cluster.
+//!
+//! The scenario of a job is chosen by its SQL query, see [Scenario], and the job advances
+//! by one status at every request of its status: queued, running and then its final
+//! status. The other queries are rejected with a SQL parsing failure and the logical plans
+//! with a plan parsing failure. The RPCs of the executors and the optional RPCs of the
+//! clients, e.g. `WatchJobStatus`, are not implemented.
+
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action as FlightAction, ActionType, Criteria, Empty, FlightData, FlightDescriptor,
+    FlightInfo, HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaResult,
+    Ticket,
+};
+use ballista_core::protocol::negotiate_protocol_version;
+use ballista_core::serde::decode_protobuf;
+use ballista_core::serde::protobuf::scheduler_grpc_server::{
+    SchedulerGrpc, SchedulerGrpcServer,
+};
+use ballista_core::serde::protobuf::{
+    execute_query_failure_result, execute_query_params, execute_query_result, job_status,
+    AcquireJobsParams, AcquireJobsResult, CancelJobParams, CancelJobResult,
+    CleanJobDataParams, CleanJobDataResult, CreateSessionParams, CreateSessionResult,
+    ExecuteQueryFailureResult, ExecuteQueryParams, ExecuteQueryResult,
+    ExecuteQuerySuccessResult, ExecutorStoppedParams, ExecutorStoppedResult,
+    ExportExecutionGraphParams, ExportExecutionGraphResult, FailedJob,
+    GetFileMetadataParams, GetFileMetadataResult, GetJobPlanParams, GetJobPlanResult,
+    GetJobSchedulingTraceParams, GetJobStatusParams, GetJobStatusResult,
+    GetShuffleLocationsParams, GetShuffleLocationsResult, GetTaskLogsParams,
+    GetTaskLogsResult, HandOffJobsParams, HandOffJobsResult, HandshakeParams,
+    HandshakeResult, HeartBeatParams, HeartBeatResult, JobStatus, PollWorkParams,
+    PollWorkResult, PromoteSchedulerParams, PromoteSchedulerResult, QueuedJob,
+    RegisterExecutorParams, RegisterExecutorResult, RemoveSessionParams,
+    RemoveSessionResult, ReportShuffleInventoryParams, ReportShuffleInventoryResult,
+    ResizeExecutorTaskSlotsParams, ResizeExecutorTaskSlotsResult, RunningJob,
+    SchedulingTraceEvent, UpdateSessionParams, UpdateSessionResult,
+    UpdateTaskStatusParams, UpdateTaskStatusResult, WatchJobStatusParams,
+    WatchJobStatusResult,
+};
+use ballista_core::serde::scheduler::Action;
+use ballista_core::utils::create_grpc_server;
+use ballista_core::BALLISTA_VERSION;
+use datafusion::arrow::array::{Int64Array, StringArray};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use futures::{Stream, TryStreamExt};
+use log::{debug, info};
+use parking_lot::Mutex;
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::fixtures::{
+    partition_path, successful_job, ENDED_AT, JOB_ID, JOB_NAME, PARTITION_ROWS,
+    QUEUED_AT, SESSION_ID, STARTED_AT,
+};
+
+/// Query of the jobs which succeed, see [Scenario::Success]
+pub const SUCCESS_QUERY: &str = "SELECT 'conformance-success'";
+
+/// Query of the jobs which fail, see [Scenario::Failure]
+pub const FAILURE_QUERY: &str = "SELECT 'conformance-failure'";
+
+/// Query of the jobs which run until they are cancelled, see [Scenario::Cancel]
+pub const CANCEL_QUERY: &str = "SELECT 'conformance-cancel'";
+
+/// Error of the jobs of the failure scenario
+pub const SCRIPTED_FAILURE: &str = "Scripted failure";
+
+/// Error of the cancelled jobs, as reported by the scheduler
+pub const CANCELLED: &str = "Cancelled";
+
+/// Scenario of a job of the scripted scheduler
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scenario {
+    /// The job succeeds, its output partitions being served by the Flight service
+    Success,
+    /// The job fails with [SCRIPTED_FAILURE]
+    Failure,
+    /// The job runs until it is cancelled and then fails with [CANCELLED]
+    Cancel,
+}
+
+impl Scenario {
+    pub fn from_sql(sql: &str) -> Option<Self> {
+        match sql.trim() {
+            SUCCESS_QUERY => Some(Self::Success),
+            FAILURE_QUERY => Some(Self::Failure),
+            CANCEL_QUERY => Some(Self::Cancel),
+            _ => None,
+        }
+    }
+}
+
+/// Schema of the output of the successful jobs
+pub fn output_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, false),
+    ]))
+}
+
+/// Output partition `partition` of the successful jobs. The ids of the rows are numbered
+/// from 1 across the partitions and their names are `row-<id>`.
+pub fn output_partition(partition: usize) -> Option<RecordBatch> {
+    let num_rows = *PARTITION_ROWS.get(partition)?;
+    let first: i64 = PARTITION_ROWS[..partition].iter().sum::<i64>() + 1;
+    let ids: Vec<i64> = (first..first + num_rows).collect();
+    let names: Vec<String> = ids.iter().map(|id| format!("row-{id}")).collect();
+    let batch = RecordBatch::try_new(
+        output_schema(),
+        vec![
+            Arc::new(Int64Array::from(ids)),
+            Arc::new(StringArray::from(names)),
+        ],
+    )
+    .expect("valid output partition");
+    Some(batch)
+}
+
+struct ScriptedJob {
+    scenario: Scenario,
+    /// Number of requests of the status of the job
+    polls: usize,
+    cancelled: bool,
+}
+
+impl ScriptedJob {
+    /// Whether the final status of the job was reported
+    fn finished(&self) -> bool {
+        self.cancelled || (self.scenario != Scenario::Cancel && self.polls > 2)
+    }
+}
+
+pub struct ScriptedScheduler {
+    /// Host and port of the Flight service, advertised in the locations of the partitions
+    host: String,
+    port: u16,
+    sessions: Mutex<HashSet<String>>,
+    jobs: Mutex<HashMap<String, ScriptedJob>>,
+    next_id: AtomicUsize,
+}
+
+impl ScriptedScheduler {
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            sessions: Mutex::new(HashSet::from([SESSION_ID.to_owned()])),
+            jobs: Mutex::default(),
+            next_id: AtomicUsize::new(1),
+        }
+    }
+
+    /// Serve the client API and the Flight service on a listener until the server fails
+    pub async fn serve(
+        self: Arc<Self>,
+        listener: TcpListener,
+    ) -> Result<(), tonic::transport::Error> {
+        info!(
+            "Scripted scheduler listening on {:?}, advertising {}:{}",
+            listener.local_addr(),
+            self.host,
+            self.port
+        );
+        create_grpc_server()
+            .add_service(SchedulerGrpcServer::from_arc(self.clone()))
+            .add_service(FlightServiceServer::from_arc(self))
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+    }
+
+    fn next_id(&self) -> usize {
+        self.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    /// The statuses of a job, from the first reported to the current final status if any
+    fn script(&self, job_id: &str, job: &ScriptedJob) -> Vec<job_status::Status> {
+        let failed = |error: &str| {
+            job_status::Status::Failed(FailedJob {
+                error: error.to_owned(),
+                queued_at: QUEUED_AT,
+                started_at: STARTED_AT,
+                ended_at: ENDED_AT,
+                error_chain: vec![error.to_owned()],
+                ..Default::default()
+            })
+        };
+        let last = match (job.scenario, job.cancelled) {
+            (_, true) => Some(failed(CANCELLED)),
+            (Scenario::Success, false) => Some(job_status::Status::Successful(
+                successful_job(job_id, &self.host, self.port),
+            )),
+            (Scenario::Failure, false) => Some(failed(SCRIPTED_FAILURE)),
+            (Scenario::Cancel, false) => None,
+        };
+        vec![
+            job_status::Status::Queued(QueuedJob {
+                queued_at: QUEUED_AT,
+                estimated_start_delay_ms: 0,
+            }),
+            job_status::Status::Running(RunningJob {
+                queued_at: QUEUED_AT,
+                started_at: STARTED_AT,
+                scheduler: format!("{}:{}", self.host, self.port),
+            }),
+        ]
+        .into_iter()
+        .chain(last)
+        .collect()
+    }
+}
+
+fn execute_query_failure(
+    failure: execute_query_failure_result::Failure,
+) -> Response<ExecuteQueryResult> {
+    Response::new(ExecuteQueryResult {
+        result: Some(execute_query_result::Result::Failure(
+            ExecuteQueryFailureResult {
+                failure: Some(failure),
+            },
+        )),
+    })
+}
+
+fn negotiate(version: u32, min_version: u32) -> Result<u32, Status> {
+    negotiate_protocol_version("Client", version, min_version)
+        .map_err(|e| Status::failed_precondition(e.to_string()))
+}
+
+type BoxedStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + 'static>>;
+
+#[tonic::async_trait]
+impl SchedulerGrpc for ScriptedScheduler {
+    async fn handshake(
+        &self,
+        request: Request<HandshakeParams>,
+    ) -> Result<Response<HandshakeResult>, Status> {
+        let params = request.into_inner();
+        let protocol_version =
+            negotiate(params.protocol_version, params.min_protocol_version)?;
+        Ok(Response::new(HandshakeResult {
+            protocol_version,
+            ballista_version: BALLISTA_VERSION.to_owned(),
+        }))
+    }
+
+    async fn create_session(
+        &self,
+        _request: Request<CreateSessionParams>,
+    ) -> Result<Response<CreateSessionResult>, Status> {
+        let session_id = format!("{SESSION_ID}-{}", self.next_id());
+        self.sessions.lock().insert(session_id.clone());
+        Ok(Response::new(CreateSessionResult { session_id }))
+    }
+
+    async fn update_session(
+        &self,
+        request: Request<UpdateSessionParams>,
+    ) -> Result<Response<UpdateSessionResult>, Status> {
+        let session_id = request.into_inner().session_id;
+        let success = self.sessions.lock().contains(&session_id);
+        Ok(Response::new(UpdateSessionResult { success }))
+    }
+
+    async fn remove_session(
+        &self,
+        request: Request<RemoveSessionParams>,
+    ) -> Result<Response<RemoveSessionResult>, Status> {
+        let session_id = request.into_inner().session_id;
+        let success = self.sessions.lock().remove(&session_id);
+        Ok(Response::new(RemoveSessionResult { success }))
+    }
+
+    async fn execute_query(
+        &self,
+        request: Request<ExecuteQueryParams>,
+    ) -> Result<Response<ExecuteQueryResult>, Status> {
+        let params = request.into_inner();
+        negotiate(params.protocol_version, params.min_protocol_version)?;
+
+        let session_id = match params.optional_session_id {
+            Some(execute_query_params::OptionalSessionId::SessionId(session_id)) => {
+                if !self.sessions.lock().contains(&session_id) {
+                    return Ok(execute_query_failure(
+                        execute_query_failure_result::Failure::SessionNotFound(format!(
+                            "Session {session_id} not found"
+                        )),
+                    ));
+                }
+                session_id
+            }
+            None => {
+                let session_id = format!("{SESSION_ID}-{}", self.next_id());
+                self.sessions.lock().insert(session_id.clone());
+                session_id
+            }
+        };
+        let scenario = match params.query {
+            Some(execute_query_params::Query::Sql(sql)) => {
+                match Scenario::from_sql(&sql) {
+                    Some(scenario) => scenario,
+                    None => {
+                        return Ok(execute_query_failure(
+                            execute_query_failure_result::Failure::SqlParsingFailure(
+                                format!(
+                                    "The scripted scheduler has no scenario for {sql}"
+                                ),
+                            ),
+                        ))
+                    }
+                }
+            }
+            Some(execute_query_params::Query::LogicalPlan(_)) => {
+                return Ok(execute_query_failure(
+                    execute_query_failure_result::Failure::PlanParsingFailure(
+                        "The scripted scheduler only runs SQL queries".to_owned(),
+                    ),
+                ))
+            }
+            None => return Err(Status::invalid_argument("No query")),
+        };
+
+        let job_id = format!("{JOB_ID}-{}", self.next_id());
+        debug!("Job {job_id} of session {session_id} runs scenario {scenario:?}");
+        self.jobs.lock().insert(
+            job_id.clone(),
+            ScriptedJob {
+                scenario,
+                polls: 0,
+                cancelled: false,
+            },
+        );
+        let schema =
+            datafusion_proto::protobuf::Schema::try_from(output_schema().as_ref())
+                .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(ExecuteQueryResult {
+            result: Some(execute_query_result::Result::Success(
+                ExecuteQuerySuccessResult {
+                    job_id,
+                    session_id,
+                    schema: Some(schema),
+                    estimated_start_delay_ms: 0,
+                },
+            )),
+        }))
+    }
+
+    async fn get_job_status(
+        &self,
+        request: Request<GetJobStatusParams>,
+    ) -> Result<Response<GetJobStatusResult>, Status> {
+        let job_id = request.into_inner().job_id;
+        let mut jobs = self.jobs.lock();
+        let Some(job) = jobs.get_mut(&job_id) else {
+            return Ok(Response::new(GetJobStatusResult { status: None }));
+        };
+        let mut script = self.script(&job_id, job);
+        let step = job.polls.min(script.len() - 1);
+        job.polls = job.polls.saturating_add(1);
+        Ok(Response::new(GetJobStatusResult {
+            status: Some(JobStatus {
+                job_id,
+                job_name: JOB_NAME.to_owned(),
+                status: Some(script.swap_remove(step)),
+            }),
+        }))
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<CancelJobParams>,
+    ) -> Result<Response<CancelJobResult>, Status> {
+        let job_id = request.into_inner().job_id;
+        let mut jobs = self.jobs.lock();
+        let cancelled = match jobs.get_mut(&job_id) {
+            Some(job) if !job.finished() => {
+                job.cancelled = true;
+                // the next request of the status reports the cancellation
+                job.polls = usize::MAX;
+                true
+            }
+            _ => false,
+        };
+        Ok(Response::new(CancelJobResult { cancelled }))
+    }
+
+    async fn clean_job_data(
+        &self,
+        request: Request<CleanJobDataParams>,
+    ) -> Result<Response<CleanJobDataResult>, Status> {
+        let job_id = request.into_inner().job_id;
+        self.jobs.lock().remove(&job_id);
+        Ok(Response::new(CleanJobDataResult {}))
+    }
+
+    type WatchJobStatusStream = BoxedStream<WatchJobStatusResult>;
+
+    async fn watch_job_status(
+        &self,
+        _request: Request<WatchJobStatusParams>,
+    ) -> Result<Response<Self::WatchJobStatusStream>, Status> {
+        Err(Status::unimplemented("watch_job_status"))
+    }
+
+    async fn poll_work(
+        &self,
+        _request: Request<PollWorkParams>,
+    ) -> Result<Response<PollWorkResult>, Status> {
+        Err(Status::unimplemented("poll_work"))
+    }
+
+    async fn register_executor(
+        &self,
+        _request: Request<RegisterExecutorParams>,
+    ) -> Result<Response<RegisterExecutorResult>, Status> {
+        Err(Status::unimplemented("register_executor"))
+    }
+
+    async fn heart_beat_from_executor(
+        &self,
+        _request: Request<HeartBeatParams>,
+    ) -> Result<Response<HeartBeatResult>, Status> {
+        Err(Status::unimplemented("heart_beat_from_executor"))
+    }
+
+    async fn update_task_status(
+        &self,
+        _request: Request<UpdateTaskStatusParams>,
+    ) -> Result<Response<UpdateTaskStatusResult>, Status> {
+        Err(Status::unimplemented("update_task_status"))
+    }
+
+    async fn get_file_metadata(
+        &self,
+        _request: Request<GetFileMetadataParams>,
+    ) -> Result<Response<GetFileMetadataResult>, Status> {
+        Err(Status::unimplemented("get_file_metadata"))
+    }
+
+    async fn executor_stopped(
+        &self,
+        _request: Request<ExecutorStoppedParams>,
+    ) -> Result<Response<ExecutorStoppedResult>, Status> {
+        Err(Status::unimplemented("executor_stopped"))
+    }
+
+    async fn export_execution_graph(
+        &self,
+        _request: Request<ExportExecutionGraphParams>,
+    ) -> Result<Response<ExportExecutionGraphResult>, Status> {
+        Err(Status::unimplemented("export_execution_graph"))
+    }
+
+    async fn get_task_logs(
+        &self,
+        _request: Request<GetTaskLogsParams>,
+    ) -> Result<Response<GetTaskLogsResult>, Status> {
+        Err(Status::unimplemented("get_task_logs"))
+    }
+
+    async fn get_shuffle_locations(
+        &self,
+        _request: Request<GetShuffleLocationsParams>,
+    ) -> Result<Response<GetShuffleLocationsResult>, Status> {
+        Err(Status::unimplemented("get_shuffle_locations"))
+    }
+
+    async fn resize_executor_task_slots(
+        &self,
+        _request: Request<ResizeExecutorTaskSlotsParams>,
+    ) -> Result<Response<ResizeExecutorTaskSlotsResult>, Status> {
+        Err(Status::unimplemented("resize_executor_task_slots"))
+    }
+
+    async fn hand_off_jobs(
+        &self,
+        _request: Request<HandOffJobsParams>,
+    ) -> Result<Response<HandOffJobsResult>, Status> {
+        Err(Status::unimplemented("hand_off_jobs"))
+    }
+
+    async fn acquire_jobs(
+        &self,
+        _request: Request<AcquireJobsParams>,
+    ) -> Result<Response<AcquireJobsResult>, Status> {
+        Err(Status::unimplemented("acquire_jobs"))
+    }
+
+    async fn promote_scheduler(
+        &self,
+        _request: Request<PromoteSchedulerParams>,
+    ) -> Result<Response<PromoteSchedulerResult>, Status> {
+        Err(Status::unimplemented("promote_scheduler"))
+    }
+
+    async fn report_shuffle_inventory(
+        &self,
+        _request: Request<ReportShuffleInventoryParams>,
+    ) -> Result<Response<ReportShuffleInventoryResult>, Status> {
+        Err(Status::unimplemented("report_shuffle_inventory"))
+    }
+
+    async fn get_job_plan(
+        &self,
+        _request: Request<GetJobPlanParams>,
+    ) -> Result<Response<GetJobPlanResult>, Status> {
+        Err(Status::unimplemented("get_job_plan"))
+    }
+
+    type GetJobSchedulingTraceStream = BoxedStream<SchedulingTraceEvent>;
+
+    async fn get_job_scheduling_trace(
+        &self,
+        _request: Request<GetJobSchedulingTraceParams>,
+    ) -> Result<Response<Self::GetJobSchedulingTraceStream>, Status> {
+        Err(Status::unimplemented("get_job_scheduling_trace"))
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for ScriptedScheduler {
+    type DoActionStream = BoxedStream<arrow_flight::Result>;
+    type DoExchangeStream = BoxedStream<FlightData>;
+    type DoGetStream = BoxedStream<FlightData>;
+    type DoPutStream = BoxedStream<PutResult>;
+    type HandshakeStream = BoxedStream<HandshakeResponse>;
+    type ListActionsStream = BoxedStream<ActionType>;
+    type ListFlightsStream = BoxedStream<FlightInfo>;
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let action = decode_protobuf(&request.into_inner().ticket)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let Action::FetchPartition {
+            job_id,
+            partition_id,
+            path,
+            ..
+        } = action
+        else {
+            return Err(Status::unimplemented("Only FetchPartition is scripted"));
+        };
+        let known = self.jobs.lock().get(&job_id).is_some_and(|job| {
+            job.scenario == Scenario::Success && !job.cancelled && job.finished()
+        });
+        let batch = output_partition(partition_id)
+            .filter(|_| known && path == partition_path(&job_id, partition_id))
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "No output partition {partition_id} at {path} for job {job_id}"
+                ))
+            })?;
+
+        let stream = FlightDataEncoderBuilder::new()
+            .with_schema(output_schema())
+            .build(futures::stream::iter(vec![Ok(batch)]))
+            .map_err(|e| Status::from_error(Box::new(e)));
+        Ok(Response::new(Box::pin(stream) as Self::DoGetStream))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented("get_schema"))
+    }
+
+    async fn get_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        Err(Status::unimplemented("get_flight_info"))
+    }
+
+    async fn poll_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("poll_flight_info"))
+    }
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("handshake"))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("list_flights"))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("do_put"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<FlightAction>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("do_action"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented("list_actions"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange"))
+    }
+}
@@ -0,0 +1,111 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Golden fixtures of the client API. Run with `BALLISTA_UPDATE_FIXTURES=1` to write the
+//! binary and text fixtures of the messages of this build, after a change of the client
+//! API.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use ballista_client_conformance::fixtures;
+use ballista_client_conformance::fixtures::fixtures_dir;
+use prost::Message;
+use prost_reflect::text_format::FormatOptions;
+use prost_reflect::{DescriptorPool, DynamicMessage};
+
+const UPDATE_FIXTURES: &str = "BALLISTA_UPDATE_FIXTURES";
+
+/// The descriptors of the messages of the client API, compiled from the proto files of
+/// this build
+fn descriptor_pool() -> DescriptorPool {
+    let proto_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../ballista/core/proto");
+    let file_descriptors = protox::compile(["ballista.proto"], [proto_dir]).unwrap();
+    DescriptorPool::from_file_descriptor_set(file_descriptors).unwrap()
+}
+
+#[test]
+fn test_golden_fixtures() {
+    let update = std::env::var_os(UPDATE_FIXTURES).is_some();
+    let descriptor_pool = descriptor_pool();
+    for fixture in fixtures() {
+        let descriptor = descriptor_pool
+            .get_message_by_name(fixture.message_type)
+            .unwrap_or_else(|| panic!("Unknown message type {}", fixture.message_type));
+        let path = fixture.binary_path();
+        let text_path = fixture.text_path();
+        if update {
+            std::fs::write(&path, fixture.encoded()).unwrap();
+            let message =
+                DynamicMessage::decode(descriptor.clone(), fixture.encoded()).unwrap();
+            let text = format!(
+                "# proto-file: ballista/core/proto/ballista.proto\n\
+                # proto-message: {}\n\n{}\n",
+                fixture.message_type,
+                message.to_text_format_with_options(&FormatOptions::new().pretty(true))
+            );
+            std::fs::write(&text_path, text).unwrap();
+        }
+        let encoded = std::fs::read(&path)
+            .unwrap_or_else(|e| panic!("Cannot read fixture {}: {e}", path.display()));
+        assert!(
+            fixture.matches(&encoded).unwrap(),
+            "Fixture {} does not decode to its golden message, run with \
+            {UPDATE_FIXTURES}=1 if the change of the client API is intended",
+            fixture.name
+        );
+
+        let text = std::fs::read_to_string(&text_path).unwrap_or_else(|e| {
+            panic!("Cannot read fixture {}: {e}", text_path.display())
+        });
+        let header = format!("# proto-message: {}", fixture.message_type);
+        assert!(
+            text.lines().any(|line| line == header),
+            "Fixture {} does not declare its message type",
+            text_path.display()
+        );
+        // the text fixtures are the reference of the other languages, they must not drift
+        // from the golden messages
+        let message = DynamicMessage::parse_text_format(descriptor, &text)
+            .unwrap_or_else(|e| {
+                panic!("Cannot parse fixture {}: {e}", text_path.display())
+            });
+        assert!(
+            fixture.matches(&message.encode_to_vec()).unwrap(),
+            "Fixture {} does not parse to its golden message, run with \
+            {UPDATE_FIXTURES}=1 if the change of the client API is intended",
+            text_path.display()
+        );
+    }
+}
+
+#[test]
+fn test_no_stale_fixtures() {
+    let expected: HashSet<String> = fixtures()
+        .iter()
+        .flat_map(|fixture| {
+            [
+                format!("{}.binpb", fixture.name),
+                format!("{}.txtpb", fixture.name),
+            ]
+        })
+        .collect();
+    for entry in std::fs::read_dir(fixtures_dir()).unwrap() {
+        let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+        assert!(expected.contains(&name), "Stale fixture {name}");
+    }
+}
@@ -0,0 +1,243 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! The Rust client against the scripted scheduler, the reference run of the scenarios that
+//! the client implementations in other languages replay
+
+use std::sync::Arc;
+
+use ballista_client_conformance::fixtures::SESSION_ID;
+use ballista_client_conformance::scheduler::{
+    output_partition, output_schema, CANCELLED, CANCEL_QUERY, FAILURE_QUERY,
+    SCRIPTED_FAILURE, SUCCESS_QUERY,
+};
+use ballista_client_conformance::ScriptedScheduler;
+use ballista_core::client::BallistaClient;
+use ballista_core::protocol::{handshake, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::serde::protobuf::{
+    execute_query_failure_result, execute_query_params, execute_query_result, job_status,
+    CancelJobParams, ExecuteQueryParams, ExecuteQuerySuccessResult, GetJobStatusParams,
+};
+use ballista_core::serde::scheduler::PartitionId;
+use ballista_core::utils::create_grpc_client_connection;
+use datafusion::arrow::datatypes::Schema;
+use datafusion::physical_plan::common;
+use tokio::net::TcpListener;
+use tonic::transport::Channel;
+
+async fn start() -> SchedulerGrpcClient<Channel> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(Arc::new(ScriptedScheduler::new("127.0.0.1", port)).serve(listener));
+    let channel = create_grpc_client_connection(format!("http://127.0.0.1:{port}"))
+        .await
+        .unwrap();
+    SchedulerGrpcClient::new(channel)
+}
+
+fn query(sql: &str) -> ExecuteQueryParams {
+    ExecuteQueryParams {
+        query: Some(execute_query_params::Query::Sql(sql.to_owned())),
+        optional_session_id: Some(execute_query_params::OptionalSessionId::SessionId(
+            SESSION_ID.to_owned(),
+        )),
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: MIN_PROTOCOL_VERSION,
+        ..Default::default()
+    }
+}
+
+async fn submit(
+    scheduler: &mut SchedulerGrpcClient<Channel>,
+    sql: &str,
+) -> ExecuteQuerySuccessResult {
+    let result = scheduler
+        .execute_query(query(sql))
+        .await
+        .unwrap()
+        .into_inner();
+    match result.result {
+        Some(execute_query_result::Result::Success(success)) => success,
+        other => panic!("Unexpected result {other:?}"),
+    }
+}
+
+async fn status(
+    scheduler: &mut SchedulerGrpcClient<Channel>,
+    job_id: &str,
+) -> job_status::Status {
+    scheduler
+        .get_job_status(GetJobStatusParams {
+            job_id: job_id.to_owned(),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .status
+        .and_then(|status| status.status)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_successful_job() {
+    let mut scheduler = start().await;
+    assert_eq!(handshake(&mut scheduler).await.unwrap(), PROTOCOL_VERSION);
+
+    let submitted = submit(&mut scheduler, SUCCESS_QUERY).await;
+    assert_eq!(submitted.session_id, SESSION_ID);
+    let schema: Schema = submitted.schema.as_ref().unwrap().try_into().unwrap();
+    assert_eq!(&schema, output_schema().as_ref());
+
+    let job_id = submitted.job_id;
+    assert!(matches!(
+        status(&mut scheduler, &job_id).await,
+        job_status::Status::Queued(_)
+    ));
+    assert!(matches!(
+        status(&mut scheduler, &job_id).await,
+        job_status::Status::Running(_)
+    ));
+    let job_status::Status::Successful(successful) =
+        status(&mut scheduler, &job_id).await
+    else {
+        panic!("Job {job_id} did not succeed");
+    };
+    // the final status is reported again
+    assert!(matches!(
+        status(&mut scheduler, &job_id).await,
+        job_status::Status::Successful(_)
+    ));
+
+    assert_eq!(successful.partition_location.len(), 2);
+    for location in successful.partition_location {
+        let partition_id = location.partition_id.unwrap();
+        let executor = location.executor_meta.unwrap();
+        let mut client = BallistaClient::try_new(&executor.host, executor.port as u16)
+            .await
+            .unwrap();
+        let stream = client
+            .fetch_partition(
+                &executor.id,
+                &PartitionId {
+                    job_id: partition_id.job_id,
+                    stage_id: partition_id.stage_id as usize,
+                    partition_id: partition_id.partition_id as usize,
+                },
+                &location.path,
+                &executor.host,
+                executor.port as u16,
+            )
+            .await
+            .unwrap();
+        let batches = common::collect(stream).await.unwrap();
+        let expected = output_partition(partition_id.partition_id as usize).unwrap();
+        assert_eq!(batches, vec![expected]);
+        assert_eq!(
+            location.partition_stats.unwrap().num_rows as usize,
+            batches[0].num_rows()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_failed_job() {
+    let mut scheduler = start().await;
+    let job_id = submit(&mut scheduler, FAILURE_QUERY).await.job_id;
+    status(&mut scheduler, &job_id).await;
+    status(&mut scheduler, &job_id).await;
+    match status(&mut scheduler, &job_id).await {
+        job_status::Status::Failed(failed) => assert_eq!(failed.error, SCRIPTED_FAILURE),
+        other => panic!("Unexpected status {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_cancelled_job() {
+    let mut scheduler = start().await;
+    let job_id = submit(&mut scheduler, CANCEL_QUERY).await.job_id;
+    for _ in 0..5 {
+        status(&mut scheduler, &job_id).await;
+    }
+    assert!(matches!(
+        status(&mut scheduler, &job_id).await,
+        job_status::Status::Running(_)
+    ));
+
+    let cancel = || CancelJobParams {
+        job_id: job_id.clone(),
+    };
+    assert!(
+        scheduler
+            .cancel_job(cancel())
+            .await
+            .unwrap()
+            .into_inner()
+            .cancelled
+    );
+    match status(&mut scheduler, &job_id).await {
+        job_status::Status::Failed(failed) => assert_eq!(failed.error, CANCELLED),
+        other => panic!("Unexpected status {other:?}"),
+    }
+    // a finished job is not cancelled again
+    assert!(
+        !scheduler
+            .cancel_job(cancel())
+            .await
+            .unwrap()
+            .into_inner()
+            .cancelled
+    );
+}
+
+#[tokio::test]
+async fn test_rejected_queries() {
+    let mut scheduler = start().await;
+
+    let result = scheduler
+        .execute_query(query("SELECT 1"))
+        .await
+        .unwrap()
+        .into_inner();
+    let Some(execute_query_result::Result::Failure(failure)) = result.result else {
+        panic!("Unknown query accepted");
+    };
+    assert!(matches!(
+        failure.failure,
+        Some(execute_query_failure_result::Failure::SqlParsingFailure(_))
+    ));
+
+    let mut params = query(SUCCESS_QUERY);
+    params.optional_session_id = Some(
+        execute_query_params::OptionalSessionId::SessionId("unknown".to_owned()),
+    );
+    let result = scheduler.execute_query(params).await.unwrap().into_inner();
+    let Some(execute_query_result::Result::Failure(failure)) = result.result else {
+        panic!("Query of an unknown session accepted");
+    };
+    assert!(matches!(
+        failure.failure,
+        Some(execute_query_failure_result::Failure::SessionNotFound(_))
+    ));
+
+    // the clients speaking no common version of the protocol are rejected
+    let mut params = query(SUCCESS_QUERY);
+    params.protocol_version = PROTOCOL_VERSION + 2;
+    params.min_protocol_version = PROTOCOL_VERSION + 1;
+    let status = scheduler.execute_query(params).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::FailedPrecondition);
+}
@@ -0,0 +1,519 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Multi-node Ballista clusters running the scheduler and executor binaries, either as
+//! subprocesses or in docker containers
+
+use std::fs::{self, File};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use ballista::prelude::{BallistaConfig, BallistaContext};
+use ballista_core::error::{BallistaError, Result};
+use hyper::Client;
+use log::{info, warn};
+use serde_json::Value;
+use tempfile::TempDir;
+
+/// Maximum time to wait for a node to listen on its ports
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximum time to wait for a stage of a job to run
+const RUNNING_STAGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Counter making the names of the containers of the clusters unique within the process
+static NODE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// How the nodes of a cluster are launched
+#[derive(Clone, Debug)]
+pub enum Launcher {
+    /// Subprocesses of the `ballista-scheduler` and `ballista-executor` binaries of a directory
+    Process { bin_dir: PathBuf },
+    /// Docker containers of the scheduler and executor images, on the host network
+    Docker {
+        scheduler_image: String,
+        executor_image: String,
+    },
+}
+
+impl Launcher {
+    /// The launcher configured by the environment:
+    /// - `BALLISTA_INTEGRATION_LAUNCHER`, `process` (the default) or `docker`
+    /// - `BALLISTA_INTEGRATION_BIN_DIR`, the directory of the binaries, `target/debug` of the
+    ///   workspace by default
+    /// - `BALLISTA_INTEGRATION_SCHEDULER_IMAGE` and `BALLISTA_INTEGRATION_EXECUTOR_IMAGE`,
+    ///   the docker images, `ballista-scheduler` and `ballista-executor` by default
+    pub fn from_env() -> Result<Self> {
+        let launcher = std::env::var("BALLISTA_INTEGRATION_LAUNCHER")
+            .unwrap_or_else(|_| "process".to_owned());
+        match launcher.as_str() {
+            "process" => {
+                let bin_dir = std::env::var("BALLISTA_INTEGRATION_BIN_DIR")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|_| {
+                        Path::new(env!("CARGO_MANIFEST_DIR")).join("../target/debug")
+                    });
+                Ok(Self::Process { bin_dir })
+            }
+            "docker" => Ok(Self::Docker {
+                scheduler_image: std::env::var("BALLISTA_INTEGRATION_SCHEDULER_IMAGE")
+                    .unwrap_or_else(|_| "ballista-scheduler".to_owned()),
+                executor_image: std::env::var("BALLISTA_INTEGRATION_EXECUTOR_IMAGE")
+                    .unwrap_or_else(|_| "ballista-executor".to_owned()),
+            }),
+            other => Err(BallistaError::General(format!(
+                "Unknown launcher {other}, expected process or docker"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Role {
+    Scheduler,
+    Executor,
+}
+
+impl Role {
+    fn name(&self) -> &'static str {
+        match self {
+            Role::Scheduler => "scheduler",
+            Role::Executor => "executor",
+        }
+    }
+}
+
+/// A running scheduler or executor
+enum Running {
+    Process(Child),
+    Container(String),
+}
+
+/// A scheduler or executor of a cluster, which can be killed and restarted with the same
+/// arguments, ports and directories
+pub struct Node {
+    role: Role,
+    /// Name of the node, which prefixes its log files
+    name: String,
+    args: Vec<String>,
+    /// The ports the node listens on once started
+    ports: Vec<u16>,
+    running: Option<Running>,
+}
+
+impl Node {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Kill the node abruptly, as a crash would
+    pub fn kill(&mut self) -> Result<()> {
+        match self.running.take() {
+            Some(Running::Process(mut child)) => {
+                info!("Killing {}", self.name);
+                child.kill()?;
+                child.wait()?;
+            }
+            Some(Running::Container(container)) => {
+                info!("Killing container {container} of {}", self.name);
+                run_docker(&["kill", &container])?;
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// Builder of a [TestCluster]
+#[derive(Clone, Debug)]
+pub struct ClusterBuilder {
+    launcher: Launcher,
+    num_executors: usize,
+    concurrent_tasks: usize,
+    cluster_backend: String,
+    scheduler_args: Vec<String>,
+    executor_args: Vec<String>,
+}
+
+impl ClusterBuilder {
+    /// A cluster of two executors with four task slots each, whose scheduler uses the
+    /// cluster storage backend of `BALLISTA_INTEGRATION_CLUSTER_BACKEND`, `sled` by default
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            launcher: Launcher::from_env()?,
+            num_executors: 2,
+            concurrent_tasks: 4,
+            cluster_backend: std::env::var("BALLISTA_INTEGRATION_CLUSTER_BACKEND")
+                .unwrap_or_else(|_| "sled".to_owned()),
+            scheduler_args: vec![],
+            executor_args: vec![],
+        })
+    }
+
+    pub fn launcher(mut self, launcher: Launcher) -> Self {
+        self.launcher = launcher;
+        self
+    }
+
+    pub fn executors(mut self, num_executors: usize) -> Self {
+        self.num_executors = num_executors;
+        self
+    }
+
+    pub fn concurrent_tasks(mut self, concurrent_tasks: usize) -> Self {
+        self.concurrent_tasks = concurrent_tasks;
+        self
+    }
+
+    /// The cluster storage backend of the scheduler, e.g. `sled`, `memory` or `etcd`, whose
+    /// other settings such as `--etcd-urls` are passed with [Self::scheduler_arg]
+    pub fn cluster_backend(mut self, cluster_backend: impl Into<String>) -> Self {
+        self.cluster_backend = cluster_backend.into();
+        self
+    }
+
+    /// Pass an additional command line argument to the scheduler
+    pub fn scheduler_arg(mut self, arg: impl Into<String>) -> Self {
+        self.scheduler_args.push(arg.into());
+        self
+    }
+
+    /// Pass an additional command line argument to the executors
+    pub fn executor_arg(mut self, arg: impl Into<String>) -> Self {
+        self.executor_args.push(arg.into());
+        self
+    }
+
+    /// Start the scheduler then the executors, once the scheduler listens on its port
+    pub async fn start(self) -> Result<TestCluster> {
+        let dir = TempDir::new()?;
+        let scheduler_port = free_port()?;
+        let mut scheduler_args = vec![
+            "--bind-host".to_owned(),
+            "127.0.0.1".to_owned(),
+            "--external-host".to_owned(),
+            "localhost".to_owned(),
+            "--bind-port".to_owned(),
+            scheduler_port.to_string(),
+            "--cluster-backend".to_owned(),
+            self.cluster_backend.clone(),
+            "--sled-dir".to_owned(),
+            dir.path().join("sled").to_string_lossy().into_owned(),
+            // detect the killed executors quickly
+            "--executor-timeout-seconds".to_owned(),
+            "10".to_owned(),
+            "--expire-dead-executor-interval-seconds".to_owned(),
+            "1".to_owned(),
+        ];
+        scheduler_args.extend(self.scheduler_args.iter().cloned());
+
+        let mut cluster = TestCluster {
+            scheduler: Node {
+                role: Role::Scheduler,
+                name: "scheduler".to_owned(),
+                args: scheduler_args,
+                ports: vec![scheduler_port],
+                running: None,
+            },
+            executors: vec![],
+            scheduler_port,
+            builder: self,
+            dir,
+        };
+        cluster.restart_scheduler().await?;
+        for _ in 0..cluster.builder.num_executors {
+            cluster.add_executor().await?;
+        }
+        Ok(cluster)
+    }
+}
+
+/// A Ballista cluster of a scheduler and executors, whose nodes are killed once dropped
+pub struct TestCluster {
+    builder: ClusterBuilder,
+    /// Holds the work dirs, sled dir and logs of the nodes
+    dir: TempDir,
+    scheduler_port: u16,
+    scheduler: Node,
+    executors: Vec<Node>,
+}
+
+impl TestCluster {
+    pub fn scheduler_port(&self) -> u16 {
+        self.scheduler_port
+    }
+
+    /// The directory of the log files of the nodes
+    pub fn log_dir(&self) -> PathBuf {
+        self.dir.path().join("logs")
+    }
+
+    pub fn scheduler(&self) -> &Node {
+        &self.scheduler
+    }
+
+    pub fn executors(&self) -> &[Node] {
+        &self.executors
+    }
+
+    /// Connect a client to the scheduler
+    pub async fn context(&self, config: &BallistaConfig) -> Result<BallistaContext> {
+        BallistaContext::remote("localhost", self.scheduler_port, config).await
+    }
+
+    /// Wait until a stage of a running job of the scheduler is running, returns the id of
+    /// the job. Nodes killed once it returns are killed while the job runs, rather than
+    /// before it starts or after it completes.
+    pub async fn wait_for_running_stage(&self) -> Result<String> {
+        let start = Instant::now();
+        while start.elapsed() < RUNNING_STAGE_TIMEOUT {
+            let jobs = self.get_json("/api/jobs").await?;
+            let running_jobs = jobs
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|job| job["job_status"] == "Running")
+                .filter_map(|job| job["job_id"].as_str());
+            for job_id in running_jobs {
+                let stages = self.get_json(&format!("/api/job/{job_id}/stages")).await?;
+                let has_running_stage = stages["stages"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .any(|stage| stage["stage_status"] == "Running");
+                if has_running_stage {
+                    return Ok(job_id.to_owned());
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        Err(BallistaError::General(format!(
+            "No stage of a job ran within {RUNNING_STAGE_TIMEOUT:?}"
+        )))
+    }
+
+    /// Get a JSON response of the REST API of the scheduler
+    async fn get_json(&self, path: &str) -> Result<Value> {
+        let uri = format!("http://127.0.0.1:{}{path}", self.scheduler_port)
+            .parse()
+            .map_err(|e| BallistaError::General(format!("Invalid uri {path}: {e}")))?;
+        let response = Client::new().get(uri).await.map_err(|e| {
+            BallistaError::General(format!(
+                "Failed to get {path} from the scheduler: {e}"
+            ))
+        })?;
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|e| BallistaError::General(format!("Failed to read {path}: {e}")))?;
+        serde_json::from_slice(&body).map_err(|e| {
+            BallistaError::General(format!("Invalid response to {path}: {e}"))
+        })
+    }
+
+    pub fn kill_scheduler(&mut self) -> Result<()> {
+        self.scheduler.kill()
+    }
+
+    /// Start the scheduler again, with the same cluster storage
+    pub async fn restart_scheduler(&mut self) -> Result<()> {
+        self.start_node(None).await
+    }
+
+    pub fn kill_executor(&mut self, index: usize) -> Result<()> {
+        self.executor_mut(index)?.kill()
+    }
+
+    /// Start an executor again, with the same ports and work dir
+    pub async fn restart_executor(&mut self, index: usize) -> Result<()> {
+        self.executor_mut(index)?;
+        self.start_node(Some(index)).await
+    }
+
+    /// Start a new executor, returns its index
+    pub async fn add_executor(&mut self) -> Result<usize> {
+        let index = self.executors.len();
+        let work_dir = self.dir.path().join(format!("executor-{index}"));
+        fs::create_dir_all(&work_dir)?;
+        let (port, grpc_port) = (free_port()?, free_port()?);
+        let mut args = vec![
+            "--scheduler-host".to_owned(),
+            "localhost".to_owned(),
+            "--scheduler-port".to_owned(),
+            self.scheduler_port.to_string(),
+            "--bind-host".to_owned(),
+            "127.0.0.1".to_owned(),
+            "--external-host".to_owned(),
+            "localhost".to_owned(),
+            "--bind-port".to_owned(),
+            port.to_string(),
+            "--bind-grpc-port".to_owned(),
+            grpc_port.to_string(),
+            "--work-dir".to_owned(),
+            work_dir.to_string_lossy().into_owned(),
+            "--concurrent-tasks".to_owned(),
+            self.builder.concurrent_tasks.to_string(),
+        ];
+        args.extend(self.builder.executor_args.iter().cloned());
+        self.executors.push(Node {
+            role: Role::Executor,
+            name: format!("executor-{index}"),
+            args,
+            ports: vec![port, grpc_port],
+            running: None,
+        });
+        self.start_node(Some(index)).await?;
+        Ok(index)
+    }
+
+    fn executor_mut(&mut self, index: usize) -> Result<&mut Node> {
+        let num_executors = self.executors.len();
+        self.executors.get_mut(index).ok_or_else(|| {
+            BallistaError::General(format!(
+                "No executor {index}, the cluster has {num_executors} executors"
+            ))
+        })
+    }
+
+    /// Start the scheduler, or the executor of the given index, unless it is running, and
+    /// wait until it listens on its ports
+    async fn start_node(&mut self, executor: Option<usize>) -> Result<()> {
+        let log_dir = self.log_dir();
+        fs::create_dir_all(&log_dir)?;
+        let node = match executor {
+            Some(index) => &mut self.executors[index],
+            None => &mut self.scheduler,
+        };
+        if node.is_running() {
+            return Ok(());
+        }
+
+        let running = match &self.builder.launcher {
+            Launcher::Process { bin_dir } => {
+                let binary = bin_dir.join(format!("ballista-{}", node.role.name()));
+                let log = File::options()
+                    .create(true)
+                    .append(true)
+                    .open(log_dir.join(format!("{}.log", node.name)))?;
+                info!("Starting {} with {:?} {:?}", node.name, binary, node.args);
+                let child = Command::new(&binary)
+                    .args(&node.args)
+                    .env("RUST_LOG", "info")
+                    .stdout(Stdio::from(log.try_clone()?))
+                    .stderr(Stdio::from(log))
+                    .spawn()
+                    .map_err(|e| {
+                        BallistaError::General(format!(
+                            "Failed to start {binary:?}, build it with cargo build first: {e}"
+                        ))
+                    })?;
+                Running::Process(child)
+            }
+            Launcher::Docker {
+                scheduler_image,
+                executor_image,
+            } => {
+                let image = match node.role {
+                    Role::Scheduler => scheduler_image,
+                    Role::Executor => executor_image,
+                };
+                let container = format!(
+                    "ballista-integration-{}-{}-{}",
+                    std::process::id(),
+                    node.name,
+                    NODE_COUNTER.fetch_add(1, Ordering::Relaxed)
+                );
+                let dir = self.dir.path().to_string_lossy().into_owned();
+                let mut args = vec![
+                    "run",
+                    "--detach",
+                    "--rm",
+                    "--network",
+                    "host",
+                    "--name",
+                    &container,
+                    "--volume",
+                ];
+                let volume = format!("{dir}:{dir}");
+                args.push(&volume);
+                let data_volume = crate::tpch::data_dir()
+                    .map(|data| format!("{0}:{0}", data.to_string_lossy()));
+                if let Some(data_volume) = &data_volume {
+                    args.extend(["--volume", data_volume.as_str()]);
+                }
+                args.push(image);
+                args.extend(node.args.iter().map(String::as_str));
+                info!("Starting {} in container {container}", node.name);
+                run_docker(&args)?;
+                Running::Container(container)
+            }
+        };
+        node.running = Some(running);
+        let ports = node.ports.clone();
+        let name = node.name.clone();
+        for port in ports {
+            wait_for_port(&name, port).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TestCluster {
+    fn drop(&mut self) {
+        for node in self.executors.iter_mut().chain([&mut self.scheduler]) {
+            if let Err(e) = node.kill() {
+                warn!("Failed to kill {}: {e}", node.name);
+            }
+        }
+    }
+}
+
+/// A free local port, which may be taken again by the time it is listened on
+fn free_port() -> Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+async fn wait_for_port(name: &str, port: u16) -> Result<()> {
+    let start = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_err() {
+        if start.elapsed() > STARTUP_TIMEOUT {
+            return Err(BallistaError::General(format!(
+                "{name} does not listen on port {port} after {STARTUP_TIMEOUT:?}"
+            )));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
+
+fn run_docker(args: &[&str]) -> Result<()> {
+    let output = Command::new("docker").args(args).output()?;
+    if !output.status.success() {
+        return Err(BallistaError::General(format!(
+            "docker {} failed: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,26 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! End-to-end tests of Ballista clusters made of the real scheduler and executor binaries,
+//! launched as subprocesses or docker containers. The clusters run TPC-H queries while their
+//! nodes are killed and restarted, to assert that the jobs recover. Downstream projects can
+//! use the harness to validate their own cluster storage backends, see the README.
+
+pub mod cluster;
+pub mod tpch;
+
+pub use cluster::{ClusterBuilder, Launcher, Node, TestCluster};
@@ -0,0 +1,97 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! TPC-H tables and queries, whose results on a cluster are compared to the results of
+//! DataFusion on a single node
+
+use std::path::PathBuf;
+
+use ballista::prelude::BallistaContext;
+use ballista_core::error::{BallistaError, Result};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::arrow::util::pretty::pretty_format_batches;
+use datafusion::prelude::{ParquetReadOptions, SessionContext};
+
+pub const TABLES: &[&str] = &[
+    "part", "supplier", "partsupp", "customer", "orders", "lineitem", "nation", "region",
+];
+
+/// The directory of the TPC-H tables set with `TPCH_DATA`, one directory of Parquet files
+/// per table as converted by `tpch convert` of the benchmarks
+pub fn data_dir() -> Option<PathBuf> {
+    std::env::var_os("TPCH_DATA").map(PathBuf::from)
+}
+
+/// The SQL of a TPC-H query of the benchmarks
+pub fn query(query: usize) -> Result<String> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join(format!("../benchmarks/queries/q{query}.sql"));
+    Ok(std::fs::read_to_string(path)?)
+}
+
+/// Register the TPC-H tables in a Ballista context
+pub async fn register_tables(ctx: &BallistaContext) -> Result<()> {
+    let dir = required_data_dir()?;
+    for table in TABLES {
+        let path = dir.join(table);
+        ctx.register_parquet(
+            table,
+            &path.to_string_lossy(),
+            ParquetReadOptions::default(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Run a query with DataFusion on a single node, returns its sorted result lines
+pub async fn expected_result(sql: &str) -> Result<Vec<String>> {
+    let dir = required_data_dir()?;
+    let ctx = SessionContext::new();
+    for table in TABLES {
+        let path = dir.join(table);
+        ctx.register_parquet(
+            table,
+            &path.to_string_lossy(),
+            ParquetReadOptions::default(),
+        )
+        .await?;
+    }
+    let batches = ctx.sql(sql).await?.collect().await?;
+    sorted_lines(&batches)
+}
+
+/// The lines of the formatted rows of a result, sorted so that results can be compared
+/// regardless of the order of their rows
+pub fn sorted_lines(batches: &[RecordBatch]) -> Result<Vec<String>> {
+    let formatted = pretty_format_batches(batches)?.to_string();
+    let mut lines: Vec<String> = formatted
+        .lines()
+        .filter(|line| line.starts_with('|'))
+        .map(str::to_owned)
+        .collect();
+    lines.sort_unstable();
+    Ok(lines)
+}
+
+fn required_data_dir() -> Result<PathBuf> {
+    data_dir().ok_or_else(|| {
+        BallistaError::General(
+            "Set TPCH_DATA to the directory of the TPC-H tables in Parquet".to_owned(),
+        )
+    })
+}
@@ -0,0 +1,143 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Recovery of the jobs of a cluster whose nodes are killed. The tests need the scheduler
+//! and executor binaries, or images, and the TPC-H data, see the README, and are run with
+//! `cargo test -p ballista-integration-tests -- --ignored`.
+
+use std::time::Duration;
+
+use ballista::prelude::{BallistaConfig, BallistaContext};
+use ballista_core::error::Result;
+use ballista_integration_tests::{tpch, ClusterBuilder, TestCluster};
+
+/// Maximum time for a query to complete, including the recovery of killed nodes
+const QUERY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// TPC-H queries of a single statement whose results do not depend on the order of
+/// floating point additions
+const QUERIES: &[usize] = &[1, 3, 4, 5, 6, 10, 12, 13, 14, 19];
+
+async fn connect(cluster: &TestCluster) -> Result<BallistaContext> {
+    let config = BallistaConfig::builder()
+        .set("ballista.shuffle.partitions", "4")
+        .build()?;
+    let ctx = cluster.context(&config).await?;
+    tpch::register_tables(&ctx).await?;
+    Ok(ctx)
+}
+
+async fn run_query(ctx: &BallistaContext, query: usize) -> Result<Vec<String>> {
+    let sql = tpch::query(query)?;
+    let batches = tokio::time::timeout(QUERY_TIMEOUT, async {
+        ctx.sql(&sql).await?.collect().await
+    })
+    .await
+    .unwrap_or_else(|_| panic!("Query {query} did not complete in {QUERY_TIMEOUT:?}"))?;
+    tpch::sorted_lines(&batches)
+}
+
+async fn assert_query(ctx: &BallistaContext, query: usize) -> Result<()> {
+    let expected = tpch::expected_result(&tpch::query(query)?).await?;
+    assert_eq!(run_query(ctx, query).await?, expected, "Query {query}");
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs the ballista binaries and TPCH_DATA"]
+async fn tpch_queries() -> Result<()> {
+    let cluster = ClusterBuilder::from_env()?.start().await?;
+    let ctx = connect(&cluster).await?;
+    for query in QUERIES {
+        assert_query(&ctx, *query).await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs the ballista binaries and TPCH_DATA"]
+async fn executor_killed_mid_query() -> Result<()> {
+    let mut cluster = ClusterBuilder::from_env()?.executors(3).start().await?;
+    let ctx = connect(&cluster).await?;
+    let expected = tpch::expected_result(&tpch::query(5)?).await?;
+
+    let query = tokio::spawn(async move { run_query(&ctx, 5).await });
+    cluster.wait_for_running_stage().await?;
+    cluster.kill_executor(0)?;
+
+    assert_eq!(query.await.expect("query task panicked")?, expected);
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs the ballista binaries and TPCH_DATA"]
+async fn executor_restarted_mid_query() -> Result<()> {
+    let mut cluster = ClusterBuilder::from_env()?.start().await?;
+    let ctx = connect(&cluster).await?;
+    let expected = tpch::expected_result(&tpch::query(3)?).await?;
+
+    let query = tokio::spawn(async move { run_query(&ctx, 3).await });
+    cluster.wait_for_running_stage().await?;
+    cluster.kill_executor(1)?;
+    cluster.restart_executor(1).await?;
+
+    assert_eq!(query.await.expect("query task panicked")?, expected);
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs the ballista binaries and TPCH_DATA"]
+async fn scheduler_restarted() -> Result<()> {
+    let mut cluster = ClusterBuilder::from_env()?.start().await?;
+    let ctx = connect(&cluster).await?;
+    assert_query(&ctx, 6).await?;
+
+    cluster.kill_scheduler()?;
+    cluster.restart_scheduler().await?;
+
+    // the executors register again with the restarted scheduler, which reloads its state
+    // from the cluster storage
+    let ctx = connect(&cluster).await?;
+    assert_query(&ctx, 6).await?;
+    assert_query(&ctx, 12).await?;
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs the ballista binaries and TPCH_DATA"]
+async fn scheduler_restarted_mid_query() -> Result<()> {
+    let mut cluster = ClusterBuilder::from_env()?.start().await?;
+    let ctx = connect(&cluster).await?;
+    let expected = tpch::expected_result(&tpch::query(5)?).await?;
+
+    let query = tokio::spawn(async move { run_query(&ctx, 5).await });
+    cluster.wait_for_running_stage().await?;
+    cluster.kill_scheduler()?;
+    cluster.restart_scheduler().await?;
+
+    // the client fails the query once it can not poll its status, but never returns a
+    // partial result
+    if let Ok(result) = query.await.expect("query task panicked") {
+        assert_eq!(result, expected);
+    }
+    // the tasks the executors were running for the killed scheduler do not prevent the
+    // restarted one from running the query again
+    let ctx = connect(&cluster).await?;
+    assert_query(&ctx, 5).await?;
+    assert_query(&ctx, 12).await?;
+    Ok(())
+}
@@ -75,3 +75,14 @@ def test_execute_plan():
     batches = df.collect()
     assert len(batches) == 1
     assert len(batches[0]) == 1
+
+def test_register_udf():
+    import pyarrow as pa
+    import pyarrow.compute as pc
+    ctx = SessionContext("localhost", 50050)
+    ctx.register_udf("add_one", lambda x: pc.add(x, 1), [pa.int64()], pa.int64())
+    ctx.register_csv("test", "testdata/test.csv", has_header=True)
+    df = ctx.sql("SELECT add_one(a) AS a FROM test")
+    batches = df.collect()
+    assert len(batches) == 1
+    assert batches[0].column(0).to_pylist() == [2]
@@ -21,7 +21,8 @@ use pyo3::prelude::*;
 use std::path::PathBuf;
 
 use ballista::prelude::*;
-use datafusion::arrow::datatypes::Schema;
+use ballista_core::python_udf::PythonUdf;
+use datafusion::arrow::datatypes::{DataType, Schema};
 use datafusion::arrow::pyarrow::PyArrowType;
 use datafusion::prelude::*;
 use datafusion_python::catalog::PyTable;
@@ -326,6 +327,31 @@ impl PySessionContext {
         Ok(())
     }
 
+    /// Register a Python function, taking one pyarrow array per argument and returning a
+    /// pyarrow array, as a scalar UDF. The function is pickled with cloudpickle and run by
+    /// the Python workers of the executors.
+    pub fn register_udf(
+        &mut self,
+        name: &str,
+        func: PyObject,
+        input_types: Vec<PyArrowType<DataType>>,
+        return_type: PyArrowType<DataType>,
+        py: Python,
+    ) -> PyResult<()> {
+        let pickled_function: Vec<u8> = py
+            .import("cloudpickle")?
+            .call_method1("dumps", (func,))?
+            .extract()?;
+        let udf = PythonUdf::new(
+            name,
+            pickled_function,
+            input_types.into_iter().map(|t| t.0).collect(),
+            return_type.0,
+        );
+        self.ctx.context().register_udf(udf.into_udf());
+        Ok(())
+    }
+
     pub fn execute_logical_plan(
         &mut self,
         logical_plan: PyLogicalPlan,